@@ -0,0 +1,330 @@
+use crate::host_manager;
+use crate::serverless_function::print_dependency_report;
+use crate::utils::request_error_class;
+use reqwest::blocking::{multipart, Client, Response};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde_json::{json, Value};
+use shared_utils::error::{Classify, ErrorClass};
+use std::path::Path;
+use std::time::Duration;
+use std::{env, fs, io};
+use thiserror::Error;
+
+/// Env variable holding the admin token when `--token` isn't given
+const ADMIN_TOKEN_ENV: &str = "INVOK_ADMIN_TOKEN";
+
+/// Backups and restores move every stored archive, so they get a generous timeout
+const ADMIN_TIMEOUT_SECS: u64 = 1800;
+
+/// Admin command errors
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("No admin token: pass --token or set {0}")]
+    MissingToken(&'static str),
+
+    #[error("API error: Status code {0}. {1}")]
+    Api(reqwest::StatusCode, String),
+}
+
+impl Classify for AdminError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            AdminError::Network(e) => request_error_class(e),
+            AdminError::MissingToken(_) => ErrorClass::User,
+            AdminError::Api(status, _) => ErrorClass::from_status(status.as_u16()),
+            AdminError::Io(_) | AdminError::Json(_) => ErrorClass::System,
+        }
+    }
+}
+
+/// Downloads a backup of the whole control plane.
+///
+/// # Arguments
+///
+/// * `output` - Path the archive is written to
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn backup(output: &str, token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    println!("📦 Taking backup...");
+    let response = check(client.get(host_manager::admin_backup_url()).send()?)?;
+
+    let archive = response.bytes()?;
+    fs::write(output, &archive)?;
+    println!("✅ Backup written to {} ({} bytes)", output, archive.len());
+
+    Ok(())
+}
+
+/// Replaces the control plane state with a backup from `invok admin backup`.
+///
+/// # Arguments
+///
+/// * `path` - Path of the backup archive
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn restore(path: &str, token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    let archive = fs::read(path)?;
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "backup.tar.gz".to_string());
+    let form = multipart::Form::new().part(
+        "archive",
+        multipart::Part::bytes(archive)
+            .file_name(file_name)
+            .mime_str("application/gzip")?,
+    );
+
+    println!("🔁 Restoring {}...", path);
+    let response = check(
+        client
+            .post(host_manager::admin_restore_url())
+            .multipart(form)
+            .send()?,
+    )?;
+
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    println!("✅ Restore complete");
+    println!("👤 Users: {}", report["users"]);
+    println!("📝 Functions: {}", report["functions"]);
+    println!("🗂️  Versions: {}", report["versions"]);
+    println!(
+        "🐳 Pools adopted: {} (skipped, no running containers: {})",
+        report["pools_adopted"], report["pools_skipped"]
+    );
+
+    Ok(())
+}
+
+/// Flags an incident on the public status page.
+///
+/// # Arguments
+///
+/// * `title` - Short description of the incident
+/// * `message` - What is affected and what is being done about it
+/// * `major` - Whether most invocations or deploys fail
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn open_incident(
+    title: &str,
+    message: Option<&str>,
+    major: bool,
+    token: Option<&str>,
+) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    let response = check(
+        client
+            .post(host_manager::admin_incidents_url())
+            .json(&json!({
+                "title": title,
+                "message": message.unwrap_or_default(),
+                "severity": if major { "major" } else { "minor" },
+            }))
+            .send()?,
+    )?;
+
+    let incident: Value = serde_json::from_str(&response.text()?)?;
+    let id = incident["id"].as_str().unwrap_or_default();
+    println!("🚨 Incident {} opened", id);
+    println!("Resolve it with `invok admin incident resolve {}`", id);
+
+    Ok(())
+}
+
+/// Takes a resolved incident off the public status page.
+///
+/// # Arguments
+///
+/// * `id` - The incident to resolve
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn resolve_incident(id: &str, token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    check(client.delete(host_manager::admin_incident_url(id)).send()?)?;
+    println!("✅ Incident {} resolved", id);
+
+    Ok(())
+}
+
+/// Lists the scaling freeze windows.
+///
+/// # Arguments
+///
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn list_freezes(token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    let response = check(client.get(host_manager::admin_freezes_url()).send()?)?;
+    let windows: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if windows.is_empty() {
+        println!("No freeze windows declared");
+        return Ok(());
+    }
+    for window in windows {
+        let namespace = window["namespace"].as_str().unwrap_or("every namespace");
+        println!(
+            "❄️  {}  {} → {}  {}",
+            window["id"].as_str().unwrap_or_default(),
+            window["starts_at"].as_str().unwrap_or_default(),
+            window["ends_at"].as_str().unwrap_or_default(),
+            namespace
+        );
+        if let Some(reason) = window["reason"].as_str().filter(|r| !r.is_empty()) {
+            println!("    {}", reason);
+        }
+    }
+
+    Ok(())
+}
+
+/// Declares a window during which idle containers are kept instead of scaled down.
+///
+/// # Arguments
+///
+/// * `starts_at` - RFC 3339 time the window opens
+/// * `ends_at` - RFC 3339 time the window closes
+/// * `namespace` - The namespace the window applies to; every namespace when `None`
+/// * `reason` - Why scale-downs are suspended
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn add_freeze(
+    starts_at: &str,
+    ends_at: &str,
+    namespace: Option<&str>,
+    reason: Option<&str>,
+    token: Option<&str>,
+) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    let response = check(
+        client
+            .post(host_manager::admin_freezes_url())
+            .json(&json!({
+                "namespace": namespace,
+                "reason": reason.unwrap_or_default(),
+                "starts_at": starts_at,
+                "ends_at": ends_at,
+            }))
+            .send()?,
+    )?;
+
+    let window: Value = serde_json::from_str(&response.text()?)?;
+    let id = window["id"].as_str().unwrap_or_default();
+    println!(
+        "❄️  Scale-downs frozen from {} to {}",
+        window["starts_at"].as_str().unwrap_or_default(),
+        window["ends_at"].as_str().unwrap_or_default()
+    );
+    println!("Lift it early with `invok admin freeze lift {}`", id);
+
+    Ok(())
+}
+
+/// Lifts a scaling freeze window before it ends.
+///
+/// # Arguments
+///
+/// * `id` - The window to lift
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn lift_freeze(id: &str, token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    check(client.delete(host_manager::admin_freeze_url(id)).send()?)?;
+    println!("✅ Freeze window {} lifted", id);
+
+    Ok(())
+}
+
+/// Lists the functions of every namespace using deprecated runtimes or vulnerable
+/// dependency versions.
+///
+/// # Arguments
+///
+/// * `token` - Admin token; falls back to `INVOK_ADMIN_TOKEN`
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn dependency_report(token: Option<&str>) -> Result<(), AdminError> {
+    let client = admin_client(token)?;
+
+    let response = check(
+        client
+            .get(host_manager::admin_dependency_report_url())
+            .send()?,
+    )?;
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    print_dependency_report(&report, true);
+
+    Ok(())
+}
+
+/// Client sending the admin token on every request
+fn admin_client(token: Option<&str>) -> Result<Client, AdminError> {
+    let token = match token {
+        Some(token) => token.to_string(),
+        None => env::var(ADMIN_TOKEN_ENV).map_err(|_| AdminError::MissingToken(ADMIN_TOKEN_ENV))?,
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| AdminError::MissingToken(ADMIN_TOKEN_ENV))?,
+    );
+
+    Ok(Client::builder()
+        .timeout(Duration::from_secs(ADMIN_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?)
+}
+
+/// Turn non-success responses into errors carrying the server's message
+fn check(response: Response) -> Result<Response, AdminError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error_text = response
+        .text()
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(AdminError::Api(status, error_text))
+}
@@ -1,14 +1,28 @@
 use crate::host_manager;
+use crate::utils::request_error_class;
+use invok_client::{AuthResponse, ClientError, InvokClient, Session, TotpEnrollment};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use shared_utils::error::{Classify, ErrorClass};
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
 use std::path::Path;
+use std::sync::OnceLock;
 use thiserror::Error;
 
 // File to store auth token
 const AUTH_FILE: &str = ".serverless-cli-auth";
 
+// Profile chosen with `--profile`; its session is stored apart from the default one
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+// Env variables GitHub Actions sets in jobs with `id-token: write` permission
+const ACTIONS_ID_TOKEN_REQUEST_URL: &str = "ACTIONS_ID_TOKEN_REQUEST_URL";
+const ACTIONS_ID_TOKEN_REQUEST_TOKEN: &str = "ACTIONS_ID_TOKEN_REQUEST_TOKEN";
+
+// Audience requested for the OIDC token; must match the server's `oidc_audience`
+const OIDC_AUDIENCE: &str = "invok";
+
 /// Authentication errors
 #[derive(Debug, Error)]
 pub enum AuthError {
@@ -25,25 +39,29 @@ pub enum AuthError {
     Authentication(String),
 }
 
-/// User credentials for login/registration
-#[derive(Serialize)]
-pub struct Credentials {
-    pub email: String,
-    pub password: String,
-}
-
-/// Auth token response from the server
-#[derive(Deserialize)]
-pub struct AuthResponse {
-    pub token: String,
-    pub user: UserResponse,
+impl Classify for AuthError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            AuthError::Network(e) => request_error_class(e),
+            AuthError::Authentication(_) => ErrorClass::User,
+            AuthError::Io(_) | AuthError::Json(_) => ErrorClass::System,
+        }
+    }
 }
 
-/// User information response
-#[derive(Deserialize)]
-pub struct UserResponse {
-    pub uuid: String,
-    pub email: String,
+impl From<ClientError> for AuthError {
+    fn from(error: ClientError) -> Self {
+        match error {
+            ClientError::Network(e) => AuthError::Network(e),
+            ClientError::Io(e) => AuthError::Io(e),
+            ClientError::Json(e) => AuthError::Json(e),
+            // The server's answer says what went wrong
+            ClientError::Api { message, .. } | ClientError::TotpRequired(message) => {
+                AuthError::Authentication(message)
+            }
+            error => AuthError::Authentication(error.to_string()),
+        }
+    }
 }
 
 /// Authentication session stored locally
@@ -54,6 +72,16 @@ pub struct AuthSession {
     pub email: String,
 }
 
+impl AuthSession {
+    /// A client making calls on behalf of this session
+    pub fn client(&self) -> InvokClient {
+        host_manager::client().with_session(Session {
+            token: self.token.clone(),
+            namespace: self.user_uuid.clone(),
+        })
+    }
+}
+
 /// Registers a new user
 ///
 /// # Arguments
@@ -65,74 +93,153 @@ pub struct AuthSession {
 ///
 /// An AuthSession on success or AuthError on failure
 pub fn register(email: &str, password: &str) -> Result<AuthSession, AuthError> {
-    let client = Client::new();
-    let credentials = Credentials {
-        email: email.to_string(),
-        password: password.to_string(),
-    };
+    let auth_response = host_manager::client().register(email, password)?;
+    store_session(auth_response)
+}
 
-    let response = client
-        .post(host_manager::auth_register_url())
-        .json(&credentials)
-        .send()?;
+/// Login a user
+///
+/// Accounts with two-factor authentication also need `totp_code`, a code from the
+/// authenticator app or a recovery code; it's prompted for when the server asks for one
+/// and none was given.
+///
+/// # Arguments
+///
+/// * `email` - Email address of the user
+/// * `password` - Password of the user
+/// * `totp_code` - Second factor, if already known
+///
+/// # Returns
+///
+/// An AuthSession on success or AuthError on failure
+pub fn login(
+    email: &str,
+    password: &str,
+    totp_code: Option<&str>,
+) -> Result<AuthSession, AuthError> {
+    let client = host_manager::client();
+    let auth_response = match client.login(email, password, totp_code) {
+        Err(ClientError::TotpRequired(_)) if totp_code.is_none() => {
+            let code = prompt_totp_code()?;
+            client.login(email, password, Some(code.as_str()))?
+        }
+        result => result?,
+    };
+    store_session(auth_response)
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text()?;
-        return Err(AuthError::Authentication(error_text));
+/// Ask for the second factor on the terminal
+fn prompt_totp_code() -> Result<String, AuthError> {
+    if !io::stdin().is_terminal() {
+        return Err(AuthError::Authentication(
+            "Two-factor code required; pass it with --code".to_string(),
+        ));
     }
+    print!("Two-factor code (or recovery code): ");
+    io::stdout().flush()?;
+    let mut code = String::new();
+    io::stdin().lock().read_line(&mut code)?;
+    Ok(code.trim().to_string())
+}
 
-    let auth_response: AuthResponse = response.json()?;
+/// Starts enrolling the logged in user in two-factor authentication
+///
+/// # Returns
+///
+/// The secret to add to an authenticator app on success or AuthError on failure
+pub fn enroll_totp() -> Result<TotpEnrollment, AuthError> {
+    Ok(load_session()?.client().enroll_totp()?)
+}
 
-    // Save the session locally
-    let session = AuthSession {
-        token: auth_response.token,
-        user_uuid: auth_response.user.uuid,
-        email: auth_response.user.email,
+/// Confirms a pending enrollment, requiring a second factor at every login from then on
+///
+/// # Arguments
+///
+/// * `code` - A code from the authenticator app; prompted for when omitted
+///
+/// # Returns
+///
+/// The recovery codes, only ever shown now, on success or AuthError on failure
+pub fn enable_totp(code: Option<&str>) -> Result<Vec<String>, AuthError> {
+    let client = load_session()?.client();
+    let code = match code {
+        Some(code) => code.to_string(),
+        None => prompt_totp_code()?,
     };
+    Ok(client.enable_totp(&code)?)
+}
 
-    save_session(&session)?;
+/// Stops requiring a second factor at login
+///
+/// # Arguments
+///
+/// * `code` - A code from the authenticator app or a recovery code; prompted for when
+///   omitted
+pub fn disable_totp(code: Option<&str>) -> Result<(), AuthError> {
+    let client = load_session()?.client();
+    let code = match code {
+        Some(code) => code.to_string(),
+        None => prompt_totp_code()?,
+    };
+    Ok(client.disable_totp(&code)?)
+}
 
-    Ok(session)
+/// OIDC token issued by GitHub Actions
+#[derive(Deserialize)]
+struct ActionsIdToken {
+    value: String,
 }
 
-/// Login a user
+/// Login from a GitHub Actions workflow, without stored secrets
+///
+/// Requests an OIDC token for the running workflow and exchanges it for short-lived
+/// credentials that can only deploy into `namespace`, which must trust the repository.
 ///
 /// # Arguments
 ///
-/// * `email` - Email address of the user
-/// * `password` - Password of the user
+/// * `namespace` - UUID of the namespace to deploy into
 ///
 /// # Returns
 ///
 /// An AuthSession on success or AuthError on failure
-pub fn login(email: &str, password: &str) -> Result<AuthSession, AuthError> {
-    let client = Client::new();
-    let credentials = Credentials {
-        email: email.to_string(),
-        password: password.to_string(),
+pub fn login_with_github_oidc(namespace: &str) -> Result<AuthSession, AuthError> {
+    let missing = |name: &str| {
+        AuthError::Authentication(format!(
+            "{} is not set. Run inside GitHub Actions with the `id-token: write` permission.",
+            name
+        ))
     };
+    let request_url = std::env::var(ACTIONS_ID_TOKEN_REQUEST_URL)
+        .map_err(|_| missing(ACTIONS_ID_TOKEN_REQUEST_URL))?;
+    let request_token = std::env::var(ACTIONS_ID_TOKEN_REQUEST_TOKEN)
+        .map_err(|_| missing(ACTIONS_ID_TOKEN_REQUEST_TOKEN))?;
 
+    let client = Client::new();
     let response = client
-        .post(host_manager::auth_login_url())
-        .json(&credentials)
+        .get(format!("{}&audience={}", request_url, OIDC_AUDIENCE))
+        .bearer_auth(request_token)
         .send()?;
-
     if !response.status().is_success() {
         let error_text = response.text()?;
-        return Err(AuthError::Authentication(error_text));
+        return Err(AuthError::Authentication(format!(
+            "Failed to get an OIDC token from GitHub: {}",
+            error_text
+        )));
     }
+    let id_token: ActionsIdToken = response.json()?;
 
-    let auth_response: AuthResponse = response.json()?;
+    let auth_response = host_manager::client().exchange_oidc_token(&id_token.value, namespace)?;
+    store_session(auth_response)
+}
 
-    // Save the session locally
+/// Save the session of a successful login locally
+fn store_session(auth_response: AuthResponse) -> Result<AuthSession, AuthError> {
     let session = AuthSession {
         token: auth_response.token,
         user_uuid: auth_response.user.uuid,
         email: auth_response.user.email,
     };
-
     save_session(&session)?;
-
     Ok(session)
 }
 
@@ -157,6 +264,51 @@ pub fn load_session() -> Result<AuthSession, AuthError> {
         ));
     }
 
+    read_session(&auth_file_path)
+}
+
+/// Load the session of a profile, whichever profile the command runs with
+///
+/// # Arguments
+///
+/// * `profile` - The profile logged in to with `invok login --profile <profile>`
+pub fn load_profile_session(profile: &str) -> Result<AuthSession, AuthError> {
+    validate_profile(profile)?;
+    let auth_file_path = auth_file_path(Some(profile));
+
+    if !auth_file_path.exists() {
+        return Err(AuthError::Authentication(format!(
+            "Not logged in to profile '{}'. Please run 'invok login --profile {}' first.",
+            profile, profile
+        )));
+    }
+
+    read_session(&auth_file_path)
+}
+
+/// Makes every command run with the session of a profile, e.g. one per environment
+pub fn use_profile(profile: &str) -> Result<(), AuthError> {
+    validate_profile(profile)?;
+    let _ = PROFILE.set(profile.to_string());
+    Ok(())
+}
+
+/// Profiles end up in file names
+fn validate_profile(profile: &str) -> Result<(), AuthError> {
+    let valid = !profile.is_empty()
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AuthError::Authentication(format!(
+            "Invalid profile '{}': use letters, digits, '-' and '_'",
+            profile
+        )));
+    }
+    Ok(())
+}
+
+fn read_session(auth_file_path: &Path) -> Result<AuthSession, AuthError> {
     let mut file = File::open(auth_file_path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
@@ -166,16 +318,26 @@ pub fn load_session() -> Result<AuthSession, AuthError> {
     Ok(session)
 }
 
-/// Get the path to the auth file
+/// Get the path to the auth file of the profile the command runs with
 fn get_auth_file_path() -> std::path::PathBuf {
+    auth_file_path(PROFILE.get().map(String::as_str))
+}
+
+/// Get the path to the auth file of a profile, or the default one
+fn auth_file_path(profile: Option<&str>) -> std::path::PathBuf {
+    let file_name = match profile {
+        Some(profile) => format!("{}.{}", AUTH_FILE, profile),
+        None => AUTH_FILE.to_string(),
+    };
+
     // Check if we're running in Docker environment
     if std::env::var("ENV").unwrap_or_default() == "DOCKER" {
-        return Path::new(".").join(AUTH_FILE);
+        return Path::new(".").join(file_name);
     }
 
     // For native execution, use home directory
     let home_dir = dirs::home_dir().unwrap_or_else(|| Path::new(".").to_path_buf());
-    home_dir.join(AUTH_FILE)
+    home_dir.join(file_name)
 }
 
 /// Logout (remove saved session)
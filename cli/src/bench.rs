@@ -0,0 +1,228 @@
+use crate::auth::load_session;
+use crate::host_manager;
+use crate::serverless_function::FunctionError;
+use reqwest::blocking::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde_json::{json, Value};
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+/// How long a single benchmark request may take before it counts as failed
+const BENCH_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Requests the controller accepts in one report
+const MAX_BENCH_REQUESTS: u64 = 200_000;
+
+/// Sends requests to a function at a fixed rate, then prints the controller's report
+/// lining the latencies up with what the autoscaler did meanwhile.
+///
+/// Requests are sent on schedule whether or not earlier ones were answered, the way
+/// independent users would, so slow responses show up as latency rather than as a
+/// lower request rate.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to load
+/// * `rps` - Requests sent per second
+/// * `duration` - How long to send requests for
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn bench(name: &str, rps: u32, duration: Duration) -> Result<(), FunctionError> {
+    let total = u64::from(rps) * duration.as_secs();
+    if total == 0 {
+        return Err(FunctionError::CompressionError(
+            "Nothing to send: the rate and duration must be positive".to_string(),
+        ));
+    }
+    if total > MAX_BENCH_REQUESTS {
+        return Err(FunctionError::CompressionError(format!(
+            "{} requests is too many for one run (at most {}); lower --rps or --duration",
+            total, MAX_BENCH_REQUESTS
+        )));
+    }
+
+    let session = load_session()?;
+    let url = session.client().function_url(name)?;
+
+    println!(
+        "🚀 Sending {} requests/s to {} for {}s...",
+        rps,
+        name,
+        duration.as_secs()
+    );
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| FunctionError::IoError(io::Error::other(e)))?;
+    let (started_at_ms, samples) = rt.block_on(send_requests(&url, rps, total))?;
+    drop(rt);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+    let client = Client::builder()
+        .timeout(Duration::from_secs(BENCH_REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .post(host_manager::function_bench_url(name))
+        .json(&json!({ "started_at_ms": started_at_ms, "samples": samples }))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    print_report(&report);
+    Ok(())
+}
+
+/// Send `total` GET requests at `rps`, returning when the first one was sent (unix
+/// milliseconds) and the `[sent_at_ms, latency_ms, status]` of each
+async fn send_requests(
+    url: &str,
+    rps: u32,
+    total: u64,
+) -> Result<(u64, Vec<(u64, u64, u16)>), FunctionError> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(BENCH_REQUEST_TIMEOUT_SECS))
+        .build()?;
+
+    let started_at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let started = Instant::now();
+    let mut ticker = tokio::time::interval(Duration::from_secs(1) / rps);
+    // Catch up after a stall rather than lowering the rate
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let mut requests = JoinSet::new();
+    for _ in 0..total {
+        ticker.tick().await;
+        let client = client.clone();
+        let url = url.to_string();
+        requests.spawn(async move {
+            let sent_at = started.elapsed();
+            let status = match client.get(&url).send().await {
+                // Read the whole body, so the latency covers the full response
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    match response.bytes().await {
+                        Ok(_) => status,
+                        Err(_) => 0,
+                    }
+                }
+                Err(_) => 0,
+            };
+            let latency = started.elapsed() - sent_at;
+            (
+                sent_at.as_millis() as u64,
+                latency.as_millis() as u64,
+                status,
+            )
+        });
+    }
+
+    println!("⏳ All requests sent, waiting for the last responses...");
+    let mut samples = Vec::with_capacity(total as usize);
+    while let Some(sample) = requests.join_next().await {
+        if let Ok(sample) = sample {
+            samples.push(sample);
+        }
+    }
+    samples.sort_unstable();
+    Ok((started_at_ms, samples))
+}
+
+/// Print the controller's report of a run
+fn print_report(report: &Value) {
+    let latency = &report["latency"];
+    println!();
+    println!(
+        "Requests:  {} in {:.1}s, {} failed",
+        report["requests"],
+        report["duration_ms"].as_f64().unwrap_or_default() / 1000.0,
+        report["errors"],
+    );
+    println!(
+        "Latency:   p50 {}ms, p95 {}ms, p99 {}ms, max {}ms",
+        latency["p50"], latency["p95"], latency["p99"], latency["max"],
+    );
+
+    let windows = report["windows"].as_array().cloned().unwrap_or_default();
+    if !windows.is_empty() {
+        println!();
+        println!(
+            "{:>6}  {:>8}  {:>6}  {:>8}  {:>8}  {:>10}",
+            "SECOND", "REQUESTS", "ERRORS", "P50", "P95", "CONTAINERS"
+        );
+        for window in windows {
+            println!(
+                "{:>6}  {:>8}  {:>6}  {:>6}ms  {:>6}ms  {:>10}",
+                window["second"].as_u64().unwrap_or_default(),
+                window["requests"].as_u64().unwrap_or_default(),
+                window["errors"].as_u64().unwrap_or_default(),
+                window["p50_ms"].as_u64().unwrap_or_default(),
+                window["p95_ms"].as_u64().unwrap_or_default(),
+                window["containers"]
+                    .as_u64()
+                    .map_or("?".to_string(), |containers| containers.to_string()),
+            );
+        }
+    }
+
+    let impacts = report["impacts"].as_array().cloned().unwrap_or_default();
+    println!();
+    if impacts.is_empty() {
+        println!("Scaling:   the autoscaler did nothing during the run");
+    } else {
+        println!("Scaling events:");
+        for impact in impacts {
+            let p95 = |field: &str| {
+                impact[field]
+                    .as_u64()
+                    .map_or("-".to_string(), |p95| format!("{}ms", p95))
+            };
+            let detail = impact["detail"]
+                .as_str()
+                .map(|detail| format!(" ({})", detail))
+                .unwrap_or_default();
+            println!(
+                "  {:>+8.1}s  {} -> {} containers{}, p95 {} before / {} after",
+                impact["offset_ms"].as_f64().unwrap_or_default() / 1000.0,
+                impact["action"].as_str().unwrap_or("unknown"),
+                impact["containers"],
+                detail,
+                p95("p95_before_ms"),
+                p95("p95_after_ms"),
+            );
+        }
+    }
+
+    if let Some(peak) = report["peak_containers"].as_u64() {
+        match report["settled_after_secs"].as_u64() {
+            Some(settled) => println!(
+                "Pool peaked at {} containers and stopped growing after {}s",
+                peak, settled
+            ),
+            None => println!("Pool stayed at up to {} containers", peak),
+        }
+    }
+}
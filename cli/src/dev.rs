@@ -0,0 +1,295 @@
+use crate::auth::load_session;
+use crate::host_manager;
+use crate::serverless_function::{
+    archive_excludes, archive_function, load_function_config, FunctionError,
+};
+use crate::utils::FuncConfig;
+use futures_util::StreamExt;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, Response, StatusCode};
+use serde::Deserialize;
+use shared_utils::tar_files;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// How often the function's directory is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait before following the dev container's output again
+const LOGS_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Starting a dev container may pull its toolchain image first
+const START_TIMEOUT_SECS: u64 = 300;
+const SYNC_TIMEOUT_SECS: u64 = 120;
+
+/// A dev container, as the controller reports it
+#[derive(Debug, Deserialize)]
+struct DevInstance {
+    container: String,
+    /// Path the dev container is invoked on
+    path: String,
+    /// Seconds until the container is removed
+    expires_in: u64,
+}
+
+/// Size and modification time of each file of a function, by path relative to its
+/// directory
+type Snapshot = HashMap<String, (u64, SystemTime)>;
+
+/// Develops a function in a dev container on the platform: the function's directory is
+/// uploaded, then every change to it is synced and the function rebuilt and restarted,
+/// while the container's output is streamed here. The container is removed on Ctrl+C.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function, also its directory
+/// * `remote` - Run the function on the platform; the only mode there is
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn dev(name: &str, remote: bool) -> Result<(), FunctionError> {
+    if !remote {
+        return Err(FunctionError::CompressionError(format!(
+            "Only remote dev mode is available; run `invok dev {} --remote`",
+            name
+        )));
+    }
+    let config = load_function_config(name)?;
+
+    let session = load_session()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+    let client = Client::builder().default_headers(headers).build()?;
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| FunctionError::IoError(io::Error::other(e)))?;
+    let result = rt.block_on(run(&client, name, &config));
+    // Don't wait for the output stream, which only ends with the container
+    rt.shutdown_background();
+    result
+}
+
+async fn run(client: &Client, name: &str, config: &FuncConfig) -> Result<(), FunctionError> {
+    let dir = Path::new(name);
+    let excludes = archive_excludes(&config.runtime);
+    let mut snapshot = scan(dir, &excludes)?;
+    start(client, name, config).await?;
+    tokio::spawn(follow_output(client.clone(), name.to_string()));
+    println!(
+        "👀 Watching '{}' for changes (Press Ctrl+C to stop)\n",
+        name
+    );
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = ticker.tick() => {}
+        }
+
+        let current = scan(dir, &excludes)?;
+        let (changed, deleted) = diff(&snapshot, &current);
+        if changed.is_empty() && deleted.is_empty() {
+            continue;
+        }
+        match sync(client, name, &changed, &deleted).await {
+            Ok(true) => {
+                println!(
+                    "🔄 Synced {} changed and {} deleted files, restarting",
+                    changed.len(),
+                    deleted.len()
+                );
+                snapshot = current;
+            }
+            Ok(false) => {
+                println!("⏳ The dev container expired, starting a new one");
+                start(client, name, config).await?;
+                snapshot = current;
+            }
+            // Left out of the snapshot, so the next check tries again
+            Err(e) => eprintln!("❌ Error syncing changes: {}", e),
+        }
+    }
+
+    println!("\n🧹 Removing the dev container...");
+    stop(client, name).await
+}
+
+/// Upload the function's directory, and its shared packages, to a new dev container
+async fn start(client: &Client, name: &str, config: &FuncConfig) -> Result<(), FunctionError> {
+    let bundle = archive_function(name, config)?;
+    let form = Form::new().part(
+        "file",
+        Part::bytes(bundle)
+            .file_name(format!("{name}.zip"))
+            .mime_str("application/zip")?,
+    );
+
+    println!("🚀 Starting a dev container for '{}'...", name);
+    let response = client
+        .put(host_manager::function_dev_url(name))
+        .timeout(Duration::from_secs(START_TIMEOUT_SECS))
+        .multipart(form)
+        .send()
+        .await?;
+    let instance: DevInstance = check_status(response).await?.json().await?;
+
+    println!(
+        "✅ Dev container {} is building the function",
+        instance.container
+    );
+    println!("🌐 Dev URL: {}{}", host_manager::base_url(), instance.path);
+    println!(
+        "⏳ The container is replaced with a new one after {} minutes",
+        instance.expires_in / 60
+    );
+    Ok(())
+}
+
+/// Send changes to the dev container; `false` if the function has none anymore
+async fn sync(
+    client: &Client,
+    name: &str,
+    changed: &[String],
+    deleted: &[String],
+) -> Result<bool, FunctionError> {
+    let files = tar_files(Path::new(name), changed)?;
+    let form = Form::new()
+        .text("deleted", serde_json::to_string(deleted)?)
+        .part(
+            "files",
+            Part::bytes(files)
+                .file_name("files.tar")
+                .mime_str("application/x-tar")?,
+        );
+
+    let response = client
+        .post(host_manager::function_dev_sync_url(name))
+        .timeout(Duration::from_secs(SYNC_TIMEOUT_SECS))
+        .multipart(form)
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    check_status(response).await?;
+    Ok(true)
+}
+
+async fn stop(client: &Client, name: &str) -> Result<(), FunctionError> {
+    let response = client
+        .delete(host_manager::function_dev_url(name))
+        .timeout(Duration::from_secs(SYNC_TIMEOUT_SECS))
+        .send()
+        .await?;
+    // Already gone if it expired meanwhile
+    if response.status() != StatusCode::NOT_FOUND {
+        check_status(response).await?;
+    }
+    println!("✅ Dev container removed");
+    Ok(())
+}
+
+/// Print the dev container's output, following it again whenever the container is
+/// replaced
+async fn follow_output(client: Client, name: String) {
+    loop {
+        if let Ok(response) = client
+            .get(host_manager::function_dev_logs_url(&name))
+            .send()
+            .await
+        {
+            if response.status().is_success() {
+                let mut stream = response.bytes_stream();
+                let mut buffer = String::new();
+                while let Some(Ok(chunk)) = stream.next().await {
+                    buffer.push_str(&String::from_utf8_lossy(&chunk));
+                    while let Some(end) = buffer.find('\n') {
+                        let line: String = buffer.drain(..=end).collect();
+                        if let Some(data) = line.trim_end().strip_prefix("data:") {
+                            println!("{}", data.strip_prefix(' ').unwrap_or(data));
+                        }
+                    }
+                    let _ = io::stdout().flush();
+                }
+            }
+        }
+        tokio::time::sleep(LOGS_RETRY_DELAY).await;
+    }
+}
+
+/// Size and modification time of every file the function's archive would include
+fn scan(dir: &Path, excludes: &[&str]) -> io::Result<Snapshot> {
+    let mut snapshot = Snapshot::new();
+    scan_into(dir, dir, excludes, &mut snapshot)?;
+    Ok(snapshot)
+}
+
+fn scan_into(
+    dir: &Path,
+    base_dir: &Path,
+    excludes: &[&str],
+    snapshot: &mut Snapshot,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if excludes.contains(&entry.file_name().to_string_lossy().as_ref()) {
+            continue;
+        }
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            scan_into(&path, base_dir, excludes, snapshot)?;
+        } else if let Ok(relative) = path.strip_prefix(base_dir) {
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            snapshot.insert(relative, (metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(())
+}
+
+/// Files added or changed, and files removed, between two snapshots
+fn diff(before: &Snapshot, after: &Snapshot) -> (Vec<String>, Vec<String>) {
+    let mut changed: Vec<String> = after
+        .iter()
+        .filter(|(path, state)| before.get(*path) != Some(*state))
+        .map(|(path, _)| path.clone())
+        .collect();
+    let mut deleted: Vec<String> = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned()
+        .collect();
+    changed.sort();
+    deleted.sort();
+    (changed, deleted)
+}
+
+async fn check_status(response: Response) -> Result<Response, FunctionError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(FunctionError::Api(status, error_text))
+}
@@ -0,0 +1,192 @@
+use crate::auth::load_session;
+use crate::host_manager;
+use crate::serverless_function::FunctionError;
+use crossterm::terminal;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+/// How often the terminal size is checked, to resize the remote one with it
+const RESIZE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Exit code reported when the session ended without the command's
+const UNKNOWN_EXIT_CODE: i32 = 255;
+
+/// Messages the controller sends about the session
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ExecEvent {
+    Started { container: String },
+    Exited { exit_code: Option<i64> },
+    Error { message: String },
+}
+
+/// Puts the terminal in raw mode, so keys reach the remote command as typed, until dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Runs a command in one of a function's containers, attached to this terminal.
+///
+/// The server must allow exec sessions, and records each one in the namespace's audit
+/// log.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function
+/// * `command` - The command and its arguments
+/// * `tty` - Whether to give the command a terminal; ignored when stdin or stdout isn't one
+///
+/// # Returns
+///
+/// The command's exit code, or an error if the session couldn't run
+pub fn exec(name: &str, command: Vec<String>, tty: bool) -> Result<i32, FunctionError> {
+    let session = load_session()?;
+    let mut request = host_manager::function_exec_url(name)
+        .into_client_request()
+        .map_err(|e| FunctionError::CompressionError(format!("Invalid exec URL: {}", e)))?;
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+    let tty = tty && io::stdin().is_terminal() && io::stdout().is_terminal();
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| FunctionError::IoError(io::Error::other(e)))?;
+    let result = rt.block_on(run_session(name, request, command, tty));
+    // Reading stdin blocks a thread that only returns on the next key press
+    rt.shutdown_background();
+    result
+}
+
+async fn run_session(
+    name: &str,
+    request: tokio_tungstenite::tungstenite::handshake::client::Request,
+    command: Vec<String>,
+    tty: bool,
+) -> Result<i32, FunctionError> {
+    let (socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .map_err(|e| connect_error(name, e))?;
+    let (mut sender, mut receiver) = socket.split();
+
+    let mut size = terminal::size().unwrap_or((80, 24));
+    let start = json!({
+        "command": command,
+        "tty": tty,
+        "width": size.0,
+        "height": size.1,
+    });
+    sender
+        .send(Message::Text(start.to_string()))
+        .await
+        .map_err(session_error)?;
+
+    let mut raw_mode = None;
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    let mut stdin_open = true;
+    let mut buffer = [0u8; 4096];
+    let mut resize_check = tokio::time::interval(RESIZE_CHECK_INTERVAL);
+    let mut exit_code = None;
+    let mut error = None;
+
+    loop {
+        tokio::select! {
+            read = stdin.read(&mut buffer), if stdin_open => {
+                let message = match read {
+                    Ok(0) | Err(_) => {
+                        stdin_open = false;
+                        Message::Text(json!({ "type": "eof" }).to_string())
+                    }
+                    Ok(n) => Message::Binary(buffer[..n].to_vec()),
+                };
+                sender.send(message).await.map_err(session_error)?;
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Binary(data))) => {
+                    stdout.write_all(&data).await?;
+                    stdout.flush().await?;
+                }
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ExecEvent::Started { container }) => {
+                        eprintln!("🔧 Connected to container {} of {}", container, name);
+                        if tty {
+                            raw_mode = Some(RawMode::enable()?);
+                        }
+                    }
+                    Ok(ExecEvent::Exited { exit_code: code }) => exit_code = code,
+                    Ok(ExecEvent::Error { message }) => error = Some(message),
+                    Err(_) => {}
+                },
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(session_error(e)),
+            },
+            _ = resize_check.tick(), if tty => {
+                if let Ok(current) = terminal::size() {
+                    if current != size {
+                        size = current;
+                        let resize = json!({ "type": "resize", "width": size.0, "height": size.1 });
+                        sender
+                            .send(Message::Text(resize.to_string()))
+                            .await
+                            .map_err(session_error)?;
+                    }
+                }
+            }
+        }
+    }
+    drop(raw_mode);
+
+    match (exit_code, error) {
+        (Some(code), _) => Ok(i32::try_from(code).unwrap_or(UNKNOWN_EXIT_CODE)),
+        (None, Some(message)) => Err(FunctionError::CompressionError(message)),
+        (None, None) => Ok(UNKNOWN_EXIT_CODE),
+    }
+}
+
+fn connect_error(name: &str, error: WsError) -> FunctionError {
+    match error {
+        WsError::Http(response) if response.status() == StatusCode::NOT_FOUND => {
+            FunctionError::FunctionNotFound(name.to_string())
+        }
+        WsError::Http(response) => {
+            let body = response
+                .body()
+                .as_deref()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            FunctionError::CompressionError(format!(
+                "API error: Status code {}. {}",
+                response.status(),
+                body
+            ))
+        }
+        e => FunctionError::CompressionError(format!("Failed to connect: {}", e)),
+    }
+}
+
+fn session_error(error: WsError) -> FunctionError {
+    FunctionError::CompressionError(format!("Exec session failed: {}", error))
+}
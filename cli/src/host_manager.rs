@@ -15,23 +15,207 @@ pub fn base_url() -> &'static str {
     HOST_BASE
 }
 
-/// Generates the URL for the login endpoint
-pub fn auth_login_url() -> String {
-    format!("{}/auth/login", HOST_BASE)
+/// Returns a client of the API server, without a session
+pub fn client() -> invok_client::InvokClient {
+    invok_client::InvokClient::new(HOST_BASE)
 }
-/// Generates the URL for the register endpoint
-pub fn auth_register_url() -> String {
-    format!("{}/auth/register", HOST_BASE)
+
+/// Generates the URL for the OIDC trusts endpoint
+pub fn oidc_trusts_url() -> String {
+    format!("{}/invok/oidc/trusts", HOST_BASE)
+}
+/// Generates the URL for a single OIDC trust
+pub fn oidc_trust_url(id: i32) -> String {
+    format!("{}/invok/oidc/trusts/{}", HOST_BASE, id)
+}
+/// Generates the URL for the build args endpoint
+pub fn build_args_url() -> String {
+    format!("{}/invok/buildargs", HOST_BASE)
+}
+/// Generates the URL for a single build arg
+pub fn build_arg_url(name: &str) -> String {
+    format!("{}/invok/buildargs/{}", HOST_BASE, name)
+}
+/// Generates the URL for the gateway credentials endpoint
+pub fn credentials_url() -> String {
+    format!("{}/invok/credentials", HOST_BASE)
+}
+/// Generates the URL for a single gateway credential
+pub fn credential_url(name: &str) -> String {
+    format!("{}/invok/credentials/{}", HOST_BASE, name)
+}
+/// Generates the URL for the feature flags endpoint
+pub fn flags_url() -> String {
+    format!("{}/invok/flags", HOST_BASE)
+}
+/// Generates the URL for a single feature flag
+pub fn flag_url(key: &str) -> String {
+    format!("{}/invok/flags/{}", HOST_BASE, key)
+}
+/// Generates the URL for the function previews endpoint
+pub fn function_previews_url() -> String {
+    format!("{}/invok/previews", HOST_BASE)
+}
+/// Generates the URL for a single function preview
+pub fn function_preview_url(function_name: &str, branch: &str) -> String {
+    format!(
+        "{}/invok/previews/{}/{}",
+        HOST_BASE,
+        function_name,
+        urlencoding::encode(branch)
+    )
+}
+/// Generates the URL for diffing a deploy against the deployed function
+pub fn function_diff_url(function_name: &str) -> String {
+    format!("{}/invok/diff/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for promoting a function from another namespace
+pub fn function_promote_url(function_name: &str) -> String {
+    format!("{}/invok/promote/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for listing the namespace's deploy locks
+pub fn deploy_locks_url() -> String {
+    format!("{}/invok/locks", HOST_BASE)
+}
+/// Generates the URL for locking deploys of a function, or of the namespace without one
+pub fn deploy_lock_url(function_name: Option<&str>) -> String {
+    match function_name {
+        Some(name) => format!("{}/invok/lock/{}", HOST_BASE, name),
+        None => format!("{}/invok/lock", HOST_BASE),
+    }
+}
+/// Generates the URL for the namespace's deploy approvers
+pub fn deploy_approvers_url() -> String {
+    format!("{}/invok/approvers", HOST_BASE)
+}
+/// Generates the URL for listing deploys waiting for approval
+pub fn approvals_url() -> String {
+    format!("{}/invok/approvals", HOST_BASE)
+}
+/// Generates the URL for approving or rejecting a pending deploy
+pub fn approval_decision_url(id: i32, decision: &str) -> String {
+    format!("{}/invok/approvals/{}/{}", HOST_BASE, id, decision)
+}
+/// Generates the URL for the function delete endpoint
+pub fn function_delete_url(function_name: &str) -> String {
+    format!("{}/invok/delete/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the function trash endpoint
+pub fn function_trash_url() -> String {
+    format!("{}/invok/trash", HOST_BASE)
+}
+/// Generates the URL for the storage usage endpoint
+pub fn storage_url() -> String {
+    format!("{}/invok/storage", HOST_BASE)
+}
+/// Generates the URL for the namespace's dependency report
+pub fn dependency_report_url() -> String {
+    format!("{}/invok/report/dependencies", HOST_BASE)
+}
+/// Generates the URL for the function restore endpoint
+pub fn function_restore_url(function_name: &str) -> String {
+    format!("{}/invok/restore/{}", HOST_BASE, function_name)
 }
-/// Generates the URL for the function upload endpoint
-pub fn function_upload_url() -> String {
-    format!("{}/invok/deploy", HOST_BASE)
+/// Generates the URL for the namespace defaults endpoint
+pub fn namespace_defaults_url() -> String {
+    format!("{}/invok/defaults", HOST_BASE)
+}
+/// Generates the URL for the namespace notifications endpoint
+pub fn notifications_url() -> String {
+    format!("{}/invok/notifications", HOST_BASE)
+}
+/// Generates the URL for the egress allowlist endpoint of a function
+pub fn function_egress_url(function_name: &str) -> String {
+    format!("{}/invok/egress/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the namespace export endpoint
+pub fn namespace_export_url() -> String {
+    format!("{}/invok/export", HOST_BASE)
+}
+/// Generates the URL for the namespace import endpoint
+pub fn namespace_import_url() -> String {
+    format!("{}/invok/import", HOST_BASE)
 }
 /// Generates the URL for the function list endpoint
 pub fn function_list_url() -> String {
     format!("{}/invok/list", HOST_BASE)
 }
-/// Generates the URL for the function logs endpoint
-pub fn function_logs_url(namespace: &str, function_name: &str) -> String {
-    format!("{}/invok/logs/{}/{}", HOST_BASE, namespace, function_name)
+/// Generates the URL for the load test report endpoint of a function
+pub fn function_bench_url(function_name: &str) -> String {
+    format!("{}/invok/bench/{}", HOST_BASE, function_name)
+}
+/// Generates the websocket URL for running commands in a function's containers
+pub fn function_exec_url(function_name: &str) -> String {
+    format!(
+        "{}/invok/exec/{}",
+        HOST_BASE.replacen("http", "ws", 1),
+        function_name
+    )
+}
+/// Generates the URL for starting and stopping a function's dev container
+pub fn function_dev_url(function_name: &str) -> String {
+    format!("{}/invok/dev/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for syncing changes into a function's dev container
+pub fn function_dev_sync_url(function_name: &str) -> String {
+    format!("{}/invok/dev/{}/sync", HOST_BASE, function_name)
+}
+/// Generates the URL for the output of a function's dev container
+pub fn function_dev_logs_url(function_name: &str) -> String {
+    format!("{}/invok/dev/{}/logs", HOST_BASE, function_name)
+}
+/// Generates the URL for the function boot logs endpoint
+pub fn function_boot_logs_url(function_name: &str) -> String {
+    format!("{}/invok/bootlogs/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the function resource recommendations endpoint
+pub fn function_recommendations_url(namespace: &str, function_name: &str) -> String {
+    format!(
+        "{}/invok/recommendations/{}/{}",
+        HOST_BASE, namespace, function_name
+    )
+}
+/// Generates the URL for the recorded invocations endpoint of a function
+pub fn function_invocations_url(function_name: &str) -> String {
+    format!("{}/invok/invocations/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the replay endpoint of a recorded invocation
+pub fn invocation_replay_url(invocation_id: &str) -> String {
+    format!("{}/invok/replay/{}", HOST_BASE, invocation_id)
+}
+/// Generates the URL for the response cache purge endpoint of a function
+pub fn function_purge_url(function_name: &str) -> String {
+    format!("{}/invok/purge/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the routing rules endpoint of a function
+pub fn function_routing_url(function_name: &str) -> String {
+    format!("{}/invok/routing/{}", HOST_BASE, function_name)
+}
+/// Generates the URL for the admin backup endpoint
+pub fn admin_backup_url() -> String {
+    format!("{}/admin/backup", HOST_BASE)
+}
+/// Generates the URL for the admin restore endpoint
+pub fn admin_restore_url() -> String {
+    format!("{}/admin/restore", HOST_BASE)
+}
+/// Generates the URL for opening status page incidents
+pub fn admin_incidents_url() -> String {
+    format!("{}/admin/incidents", HOST_BASE)
+}
+/// Generates the URL for resolving a status page incident
+pub fn admin_incident_url(id: &str) -> String {
+    format!("{}/admin/incidents/{}", HOST_BASE, id)
+}
+/// Generates the URL for listing and declaring scaling freeze windows
+pub fn admin_freezes_url() -> String {
+    format!("{}/admin/freezes", HOST_BASE)
+}
+/// Generates the URL for the platform-wide dependency report
+pub fn admin_dependency_report_url() -> String {
+    format!("{}/admin/report/dependencies", HOST_BASE)
+}
+/// Generates the URL for lifting a scaling freeze window
+pub fn admin_freeze_url(id: &str) -> String {
+    format!("{}/admin/freezes/{}", HOST_BASE, id)
 }
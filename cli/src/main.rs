@@ -1,22 +1,56 @@
+mod admin;
 mod auth;
+mod bench;
+mod dev;
+mod exec;
 mod host_manager;
 mod serverless_function;
+mod template_registry;
 mod utils;
 
-use crate::auth::{login, logout, register};
+use crate::admin::{
+    add_freeze, backup, dependency_report, lift_freeze, list_freezes, open_incident,
+    resolve_incident, restore,
+};
+use crate::auth::{
+    disable_totp, enable_totp, enroll_totp, login, login_with_github_oidc, logout, register,
+    use_profile,
+};
+use crate::bench::bench;
+use crate::dev::dev;
+use crate::exec::exec;
 use crate::serverless_function::{
-    create_new_project, deploy_function, list_functions, stream_logs,
+    add_oidc_trust, boot_logs, create_new_project, decide_deploy, delete_flag, delete_function,
+    delete_preview, deploy_approvers, deploy_function, deploy_image, dry_run_deploy,
+    egress_allowlist, export_namespace, function_status, import_namespace, list_approvals,
+    list_build_args, list_credentials, list_deploy_locks, list_flags, list_functions,
+    list_invocations, list_oidc_trusts, list_previews, list_trash, lock_deploys,
+    namespace_defaults, notifications, promote_function, purge_function, remove_oidc_trust,
+    replay_invocation, restore_function, routing_rules, set_build_arg, set_credential, set_flag,
+    show_dependency_report, show_storage, stream_logs, unlock_deploys, unset_build_arg,
+    unset_credential,
 };
-use clap::{Arg, Command};
+use crate::template_registry::{create_from_template, list_templates};
+use clap::{Arg, ArgAction, Command};
+use shared_utils::error::{Classify, ErrorClass};
 use std::process;
+use std::time::Duration;
 
 fn main() {
     let matches = Command::new("CLI")
         .version("0.0.2")
         .author("Akinlua Bolamigbe <bolamigbeakinlua@gmail.com>")
         .about("Serverless Function Platform CLI - Create and deploy functions to the cloud")
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("PROFILE")
+                .global(true)
+                .help("Use the session of a profile, e.g. one per environment"),
+        )
         .subcommand(
             Command::new("create")
+                .visible_alias("new")
                 .about("Creates a new function")
                 .args([
                     Arg::new("name")
@@ -31,22 +65,213 @@ fn main() {
                         .long("runtime")
                         .value_name("RUNTIME")
                         .required(false)
-                        .help("The runtime for the function (supported: go, nodejs)"),
+                        .help("The runtime for the function (supported: go, nodejs, rust)"),
+                    Arg::new("template")
+                        .short('t')
+                        .long("template")
+                        .value_name("TEMPLATE")
+                        .help("Start from a template: a registry name, git URL or local directory"),
+                    Arg::new("var")
+                        .long("var")
+                        .value_name("KEY=VALUE")
+                        .action(ArgAction::Append)
+                        .requires("template")
+                        .help("Set a template variable"),
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .action(ArgAction::SetTrue)
+                        .requires("template")
+                        .help("Fetch the template again instead of using the cached copy"),
                 ]),
         )
+        .subcommand(
+            Command::new("templates").about("Lists the templates of the template registry"),
+        )
         .subcommand(
             Command::new("deploy")
                 .about("Deploys an existing function")
-                .arg(
+                .args([
                     Arg::new("name")
                         .short('n')
                         .long("name")
                         .value_name("NAME")
                         .required(true)
                         .help("The name of the function to deploy"),
+                    Arg::new("preview")
+                        .long("preview")
+                        .value_name("BRANCH")
+                        .help("Deploy a temporary preview instance for a branch instead"),
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Deploy even though the function's SLO freezes deploys"),
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["preview", "force"])
+                        .help("Show what the deploy would change, without deploying"),
+                    Arg::new("image")
+                        .long("image")
+                        .value_name("IMAGE")
+                        .conflicts_with("dry-run")
+                        .help("Deploy an image your CI built, e.g. registry.example.com/app:1.4, instead of building the sources"),
+                ]),
+        )
+        .subcommand(
+            Command::new("promote")
+                .about("Promotes the deployed version of a function from one profile's namespace to another's, without rebuilding it")
+                .args([
+                    Arg::new("name")
+                        .value_name("FUNCTION")
+                        .required(true)
+                        .help("The name of the function to promote"),
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("PROFILE")
+                        .required(true)
+                        .help("The profile to promote from, e.g. staging"),
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("PROFILE")
+                        .required(true)
+                        .help("The profile to promote to, e.g. prod"),
+                    Arg::new("env")
+                        .long("env")
+                        .value_name("KEY")
+                        .action(ArgAction::Append)
+                        .help("An environment variable of the source function to carry over (repeatable)"),
+                    Arg::new("version")
+                        .long("version")
+                        .value_name("VERSION")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Fail unless this is the version deployed in the source namespace"),
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Promote even though the target function's SLO freezes deploys"),
+                ]),
+        )
+        .subcommand(
+            Command::new("lock")
+                .about("Lock deploys of a function, or of every function of the namespace, until unlocked")
+                .args([
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .help("The function to lock; the whole namespace without one"),
+                    Arg::new("reason")
+                        .long("reason")
+                        .value_name("TEXT")
+                        .help("Why, shown to whoever tries to deploy"),
+                ]),
+        )
+        .subcommand(
+            Command::new("unlock")
+                .about("Lift the deploy lock of a function, or the namespace's own lock")
+                .arg(
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .help("The function to unlock; the namespace without one"),
+                ),
+        )
+        .subcommand(Command::new("locks").about("Lists the deploy locks of your namespace"))
+        .subcommand(
+            Command::new("approvers")
+                .about("Show or set the accounts whose approval your deploys wait for")
+                .args([
+                    Arg::new("set")
+                        .long("set")
+                        .value_name("EMAIL")
+                        .action(ArgAction::Append)
+                        .help("Replace the approvers; repeat for several accounts, other than your own"),
+                    Arg::new("clear")
+                        .long("clear")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("set")
+                        .help("Deploy without approval again"),
+                ]),
+        )
+        .subcommand(
+            Command::new("approvals")
+                .about("Lists the deploys waiting for approval, yours and those you may approve"),
+        )
+        .subcommand(
+            Command::new("approve")
+                .about("Approve a deploy waiting in a namespace you approve for, which deploys it")
+                .arg(
+                    Arg::new("id")
+                        .value_name("ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("The pending deploy, as listed by `invok approvals`"),
+                ),
+        )
+        .subcommand(
+            Command::new("reject")
+                .about("Reject a deploy waiting in a namespace you approve for")
+                .arg(
+                    Arg::new("id")
+                        .value_name("ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("The pending deploy, as listed by `invok approvals`"),
+                ),
+        )
+        .subcommand(
+            Command::new("preview")
+                .about("Manage temporary per-branch preview instances")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists preview instances"))
+                .subcommand(
+                    Command::new("delete")
+                        .about("Remove a preview instance before it expires")
+                        .args([
+                            Arg::new("name")
+                                .short('n')
+                                .long("name")
+                                .value_name("NAME")
+                                .required(true)
+                                .help("The function the preview belongs to"),
+                            Arg::new("branch")
+                                .value_name("BRANCH")
+                                .required(true)
+                                .help("The branch the preview was deployed from"),
+                        ]),
                 ),
         )
         .subcommand(Command::new("list").about("Lists all functions"))
+        .subcommand(
+            Command::new("delete")
+                .about("Delete a function (it can be restored from the trash)")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function to delete"),
+                ),
+        )
+        .subcommand(Command::new("trash").about("Lists deleted functions that can be restored"))
+        .subcommand(
+            Command::new("storage")
+                .about("Shows the storage your functions' recorded versions take"),
+        )
+        .subcommand(
+            Command::new("dependencies")
+                .about("Shows your functions using deprecated runtimes or vulnerable dependencies"),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore a deleted function from the trash")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function to restore"),
+                ),
+        )
         .subcommand(
             Command::new("logs")
                 .about("Stream logs from a function")
@@ -59,6 +284,313 @@ fn main() {
                         .help("The name of the function to get logs from"),
                 ),
         )
+        .subcommand(
+            Command::new("bootlogs")
+                .about("Show startup logs of a function that failed to start")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function to get boot logs for"),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .visible_alias("describe")
+                .about("Show the status of a function and its last crash")
+                .args([
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function to describe"),
+                    Arg::new("recommend")
+                        .long("recommend")
+                        .action(ArgAction::SetTrue)
+                        .help("Show resource usage and suggested memory/CPU limits"),
+                ]),
+        )
+        .subcommand(
+            Command::new("invocations")
+                .about("List the requests recorded for a function that records its invocations")
+                .arg(
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Send steady traffic to a function and see how the autoscaler reacts")
+                .args([
+                    Arg::new("name")
+                        .value_name("FUNCTION")
+                        .required(true)
+                        .help("The name of the function to load"),
+                    Arg::new("rps")
+                        .long("rps")
+                        .value_name("RPS")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u32).range(1..=1000))
+                        .help("Requests sent per second"),
+                    Arg::new("duration")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("How long to send requests for, in seconds"),
+                ]),
+        )
+        .subcommand(
+            Command::new("exec")
+                .about("Run a command in one of a function's containers, e.g. a shell")
+                .args([
+                    Arg::new("name")
+                        .value_name("FUNCTION")
+                        .required(true)
+                        .help("The name of the function"),
+                    Arg::new("no-tty")
+                        .short('T')
+                        .long("no-tty")
+                        .action(ArgAction::SetTrue)
+                        .help("Don't give the command a terminal, e.g. when piping to it"),
+                    Arg::new("command")
+                        .value_name("COMMAND")
+                        .num_args(1..)
+                        .last(true)
+                        .help("The command to run, after `--` (default: sh)"),
+                ]),
+        )
+        .subcommand(
+            Command::new("dev")
+                .about("Develop a function in a dev container, syncing local changes into it")
+                .args([
+                    Arg::new("name")
+                        .value_name("FUNCTION")
+                        .required(true)
+                        .help("The name of the function, also its directory"),
+                    Arg::new("remote")
+                        .long("remote")
+                        .action(ArgAction::SetTrue)
+                        .help("Run the function in a dev container on the platform"),
+                ]),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Send a recorded request to its function again")
+                .args([
+                    Arg::new("invocation-id")
+                        .value_name("INVOCATION_ID")
+                        .required(true)
+                        .help("The request id of the recorded invocation"),
+                    Arg::new("version")
+                        .long("version")
+                        .value_name("VERSION")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Replay on this version of the function instead of the current one"),
+                ]),
+        )
+        .subcommand(
+            Command::new("defaults")
+                .about("Show or set the defaults every function in your namespace inherits")
+                .arg(
+                    Arg::new("set")
+                        .long("set")
+                        .value_name("FILE")
+                        .help("Replace the defaults with the contents of a JSON file"),
+                ),
+        )
+        .subcommand(
+            Command::new("notifications")
+                .about("Show or set where deploys, crashes and failed scale-ups are reported")
+                .args([
+                    Arg::new("set")
+                        .long("set")
+                        .value_name("FILE")
+                        .conflicts_with("clear")
+                        .help("Replace the notification targets with the contents of a JSON file"),
+                    Arg::new("clear")
+                        .long("clear")
+                        .action(ArgAction::SetTrue)
+                        .help("Stop sending notifications"),
+                ]),
+        )
+        .subcommand(
+            Command::new("egress")
+                .about("Show or set the destinations a function may reach when egress control is on")
+                .args([
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function"),
+                    Arg::new("allow")
+                        .long("allow")
+                        .value_name("HOST[:PORT]")
+                        .action(ArgAction::Append)
+                        .help("Replace the allowlist; repeat for several hosts, `*.example.com` allows subdomains"),
+                    Arg::new("clear")
+                        .long("clear")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("allow")
+                        .help("Remove every destination, blocking all outbound traffic"),
+                ]),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Drop the responses cached for a function, here and at CDNs notified by webhook")
+                .arg(
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function"),
+                ),
+        )
+        .subcommand(
+            Command::new("routing")
+                .about("Show or set the rules sending matching requests to a given version of a function")
+                .args([
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The name of the function"),
+                    Arg::new("set")
+                        .long("set")
+                        .value_name("FILE")
+                        .help("Replace the rules with the contents of a JSON file"),
+                    Arg::new("clear")
+                        .long("clear")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("set")
+                        .help("Remove every rule, serving all requests from the current version"),
+                ]),
+        )
+        .subcommand(
+            Command::new("buildarg")
+                .about("Manage encrypted variables passed only to the build stage of your functions' images")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists the names of your build args"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a build arg, e.g. a private registry token")
+                        .args([
+                            Arg::new("name")
+                                .value_name("NAME")
+                                .required(true)
+                                .help("The name the Dockerfile declares with ARG, e.g. NPM_TOKEN"),
+                            Arg::new("value")
+                                .value_name("VALUE")
+                                .help("The value; read from stdin when omitted"),
+                        ]),
+                )
+                .subcommand(
+                    Command::new("unset").about("Remove a build arg").arg(
+                        Arg::new("name")
+                            .value_name("NAME")
+                            .required(true)
+                            .help("The build arg to remove"),
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("credential")
+                .about("Manage third-party credentials your functions send requests with through the gateway")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists your credentials and where they're sent"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a credential, e.g. an API key of a payment provider")
+                        .args([
+                            Arg::new("name")
+                                .value_name("NAME")
+                                .required(true)
+                                .help("The name functions use in /internal/fetch/NAME/..."),
+                            Arg::new("url")
+                                .long("url")
+                                .value_name("URL")
+                                .required(true)
+                                .help("Base URL requests are sent under, e.g. https://api.stripe.com/v1"),
+                            Arg::new("header")
+                                .long("header")
+                                .value_name("HEADER")
+                                .help("Header the value is sent in [default: Authorization]"),
+                            Arg::new("value")
+                                .value_name("VALUE")
+                                .help("The complete header value, e.g. 'Bearer sk_live_...'; read from stdin when omitted"),
+                        ]),
+                )
+                .subcommand(
+                    Command::new("unset").about("Remove a credential").arg(
+                        Arg::new("name")
+                            .value_name("NAME")
+                            .required(true)
+                            .help("The credential to remove"),
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("flags")
+                .about("Manage feature flags sent to your functions as x-invok-flag-<key> headers")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists your flags and their rollouts"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a flag, optionally for a share of requests only")
+                        .args([
+                            Arg::new("key")
+                                .value_name("KEY")
+                                .required(true)
+                                .help("The flag's key: lowercase letters, digits, '-' and '_'"),
+                            Arg::new("value")
+                                .value_name("VALUE")
+                                .required(true)
+                                .help("The value requests inside the rollout get"),
+                            Arg::new("rollout")
+                                .long("rollout")
+                                .value_name("PERCENT")
+                                .default_value("100")
+                                .value_parser(clap::value_parser!(u8).range(0..=100))
+                                .help("Percent of requests inside the rollout"),
+                            Arg::new("default")
+                                .long("default")
+                                .value_name("VALUE")
+                                .help("The value the other requests get; they get no header when omitted"),
+                        ]),
+                )
+                .subcommand(
+                    Command::new("delete").about("Remove a flag").arg(
+                        Arg::new("key")
+                            .value_name("KEY")
+                            .required(true)
+                            .help("The flag to remove"),
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Download every function in your namespace, with its versions, as a tarball")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .default_value("invok-export.tar.gz")
+                        .help("Where to write the archive"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Restore an archive from `invok export` into your namespace")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The archive to import"),
+                ),
+        )
         .subcommand(
             Command::new("login")
                 .about("Login to the serverless platform")
@@ -67,16 +599,77 @@ fn main() {
                         .short('e')
                         .long("email")
                         .value_name("EMAIL")
-                        .required(true)
+                        .required_unless_present("github-oidc")
                         .help("The email to login with"),
                     Arg::new("password")
                         .short('p')
                         .long("password")
                         .value_name("PASSWORD")
-                        .required(true)
+                        .required_unless_present("github-oidc")
                         .help("The password to login with"),
+                    Arg::new("code")
+                        .long("code")
+                        .value_name("CODE")
+                        .help("Two-factor code or recovery code; prompted for when needed"),
+                    Arg::new("github-oidc")
+                        .long("github-oidc")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["email", "password", "code"])
+                        .help("Login from a GitHub Actions workflow with its OIDC token (deploy only)"),
+                    Arg::new("namespace")
+                        .long("namespace")
+                        .value_name("UUID")
+                        .help("The namespace to deploy into with --github-oidc (defaults to $INVOK_NAMESPACE)"),
                 ]),
         )
+        .subcommand(
+            Command::new("oidc")
+                .about("Manage CI workflows trusted to deploy with an OIDC token")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("trust")
+                        .about("Manage trusted GitHub repositories")
+                        .subcommand_required(true)
+                        .subcommand(Command::new("list").about("Lists trusted workflows"))
+                        .subcommand(
+                            Command::new("add")
+                                .about("Trust a repository's workflows to deploy")
+                                .args([
+                                    Arg::new("repo")
+                                        .long("repo")
+                                        .value_name("OWNER/NAME")
+                                        .required(true)
+                                        .help("The repository; `*` in the name matches any characters"),
+                                    Arg::new("owner-id")
+                                        .long("owner-id")
+                                        .value_name("ID")
+                                        .help("Id of the repository owner; looked up on GitHub by default"),
+                                    Arg::new("repo-id")
+                                        .long("repo-id")
+                                        .value_name("ID")
+                                        .requires("owner-id")
+                                        .help("Id of the repository; looked up on GitHub by default"),
+                                    Arg::new("ref")
+                                        .long("ref")
+                                        .value_name("PATTERN")
+                                        .help("Only workflows running for this git ref, e.g. refs/heads/main"),
+                                    Arg::new("environment")
+                                        .long("environment")
+                                        .value_name("ENVIRONMENT")
+                                        .help("Only jobs running in this deployment environment"),
+                                ]),
+                        )
+                        .subcommand(
+                            Command::new("remove").about("Stop trusting a workflow").arg(
+                                Arg::new("id")
+                                    .value_name("ID")
+                                    .required(true)
+                                    .value_parser(clap::value_parser!(i32))
+                                    .help("The trust to remove, from 'invok oidc trust list'"),
+                            ),
+                        ),
+                ),
+        )
         .subcommand(
             Command::new("register").about("Register a new user").args([
                 Arg::new("email")
@@ -94,45 +687,366 @@ fn main() {
             ]),
         )
         .subcommand(Command::new("logout").about("Logout from the serverless platform"))
+        .subcommand(
+            Command::new("2fa")
+                .about("Manage two-factor authentication (TOTP) for your account")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("enroll")
+                        .about("Generate a secret to add to an authenticator app"),
+                )
+                .subcommand(
+                    Command::new("enable")
+                        .about("Confirm the app's code and require it at every login")
+                        .arg(
+                            Arg::new("code")
+                                .long("code")
+                                .value_name("CODE")
+                                .help("Code from the authenticator app; prompted for when omitted"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("disable")
+                        .about("Stop requiring a second factor at login")
+                        .arg(
+                            Arg::new("code")
+                                .long("code")
+                                .value_name("CODE")
+                                .help("Code from the authenticator app or a recovery code; prompted for when omitted"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("admin")
+                .about("Operator commands (need the server's admin token)")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("token")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .global(true)
+                        .help("Admin token (defaults to $INVOK_ADMIN_TOKEN)"),
+                )
+                .subcommand(
+                    Command::new("backup")
+                        .about("Download a backup of all users, functions and pool states")
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .value_name("FILE")
+                                .default_value("invok-backup.tar.gz")
+                                .help("Where to write the backup"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("Replace the server's state with a backup")
+                        .arg(
+                            Arg::new("file")
+                                .value_name("FILE")
+                                .required(true)
+                                .help("The backup to restore"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("incident")
+                        .about("Manage the incidents shown on the public status page")
+                        .subcommand_required(true)
+                        .subcommand(
+                            Command::new("open")
+                                .about("Flag an incident on the status page")
+                                .args([
+                                    Arg::new("title")
+                                        .value_name("TITLE")
+                                        .required(true)
+                                        .help("Short description of the incident"),
+                                    Arg::new("message")
+                                        .long("message")
+                                        .value_name("MESSAGE")
+                                        .help("What is affected and what is being done about it"),
+                                    Arg::new("major")
+                                        .long("major")
+                                        .action(ArgAction::SetTrue)
+                                        .help("Most invocations or deploys fail"),
+                                ]),
+                        )
+                        .subcommand(
+                            Command::new("resolve")
+                                .about("Take a resolved incident off the status page")
+                                .arg(
+                                    Arg::new("id")
+                                        .value_name("ID")
+                                        .required(true)
+                                        .help("The incident to resolve"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    Command::new("dependencies")
+                        .about("List functions using deprecated runtimes or vulnerable dependencies"),
+                )
+                .subcommand(
+                    Command::new("freeze")
+                        .about("Manage the windows during which idle containers aren't scaled down")
+                        .subcommand_required(true)
+                        .subcommand(Command::new("list").about("List the freeze windows"))
+                        .subcommand(
+                            Command::new("add")
+                                .about("Suspend scale-downs for a window, e.g. a sales event")
+                                .args([
+                                    Arg::new("from")
+                                        .long("from")
+                                        .value_name("TIME")
+                                        .required(true)
+                                        .help("RFC 3339 time the window opens"),
+                                    Arg::new("until")
+                                        .long("until")
+                                        .value_name("TIME")
+                                        .required(true)
+                                        .help("RFC 3339 time the window closes"),
+                                    Arg::new("namespace")
+                                        .long("namespace")
+                                        .value_name("NAMESPACE")
+                                        .help("Only freeze this namespace, not the whole platform"),
+                                    Arg::new("reason")
+                                        .long("reason")
+                                        .value_name("REASON")
+                                        .help("Why scale-downs are suspended"),
+                                ]),
+                        )
+                        .subcommand(
+                            Command::new("lift")
+                                .about("Lift a freeze window before it ends")
+                                .arg(
+                                    Arg::new("id")
+                                        .value_name("ID")
+                                        .required(true)
+                                        .help("The window to lift"),
+                                ),
+                        ),
+                ),
+        )
         .get_matches();
 
+    if let Some(profile) = matches.get_one::<String>("profile") {
+        if let Err(err) = use_profile(profile) {
+            eprintln!("❌ {}", err);
+            process::exit(err.class().exit_code());
+        }
+    }
+
     match matches.subcommand() {
         Some(("create", sub_matches)) => {
             if let Some(name) = sub_matches.get_one::<String>("name") {
                 if let Some(runtime) = sub_matches.get_one::<String>("runtime") {
-                    if let Err(err) = create_new_project(name, runtime) {
+                    let result = match sub_matches.get_one::<String>("template") {
+                        Some(template) => {
+                            let vars: Vec<String> = sub_matches
+                                .get_many::<String>("var")
+                                .map(|vars| vars.cloned().collect())
+                                .unwrap_or_default();
+                            create_from_template(
+                                name,
+                                template,
+                                runtime,
+                                &vars,
+                                sub_matches.get_flag("refresh"),
+                            )
+                        }
+                        None => create_new_project(name, runtime),
+                    };
+                    if let Err(err) = result {
                         eprintln!("Error creating function: {}", err);
-                        process::exit(1);
+                        process::exit(err.class().exit_code());
                     }
                 } else {
                     eprintln!("Runtime parameter is required");
-                    process::exit(1);
+                    process::exit(ErrorClass::User.exit_code());
                 }
             } else {
                 eprintln!("Name parameter is required");
-                process::exit(1);
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("templates", _)) => {
+            if let Err(err) = list_templates() {
+                eprintln!("❌ Error listing templates: {}", err);
+                process::exit(err.class().exit_code());
             }
         }
         Some(("deploy", sub_matches)) => {
             if let Some(name) = sub_matches.get_one::<String>("name") {
-                match deploy_function(name) {
-                    Ok(_) => {
-                        println!("🎉 Deployment completed successfully!");
+                let preview = sub_matches.get_one::<String>("preview");
+                let force = sub_matches.get_flag("force");
+                if sub_matches.get_flag("dry-run") {
+                    if let Err(err) = dry_run_deploy(name) {
+                        eprintln!("❌ Error comparing function: {}", err);
+                        process::exit(err.class().exit_code());
                     }
-                    Err(err) => {
-                        eprintln!("❌ Error deploying function: {}", err);
-                        process::exit(1);
+                } else if let Some(image) = sub_matches.get_one::<String>("image") {
+                    match deploy_image(name, image, preview.map(String::as_str), force) {
+                        Ok(_) => {
+                            println!("🎉 Deployment completed successfully!");
+                        }
+                        Err(err) => {
+                            eprintln!("❌ Error deploying image: {}", err);
+                            process::exit(err.class().exit_code());
+                        }
+                    }
+                } else {
+                    match deploy_function(name, preview.map(String::as_str), force) {
+                        Ok(_) => {
+                            println!("🎉 Deployment completed successfully!");
+                        }
+                        Err(err) => {
+                            eprintln!("❌ Error deploying function: {}", err);
+                            process::exit(err.class().exit_code());
+                        }
                     }
                 }
             } else {
                 eprintln!("Name parameter is required");
-                process::exit(1);
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("promote", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            let from = sub_matches
+                .get_one::<String>("from")
+                .expect("from is required");
+            let to = sub_matches.get_one::<String>("to").expect("to is required");
+            let env: Vec<String> = sub_matches
+                .get_many::<String>("env")
+                .map(|keys| keys.cloned().collect())
+                .unwrap_or_default();
+            let version = sub_matches.get_one::<i32>("version").copied();
+            if let Err(err) =
+                promote_function(name, from, to, &env, version, sub_matches.get_flag("force"))
+            {
+                eprintln!("❌ Error promoting function: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("lock", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("name");
+            let reason = sub_matches.get_one::<String>("reason");
+            if let Err(err) = lock_deploys(name.map(String::as_str), reason.map(String::as_str)) {
+                eprintln!("❌ Error locking deploys: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("unlock", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("name");
+            if let Err(err) = unlock_deploys(name.map(String::as_str)) {
+                eprintln!("❌ Error unlocking deploys: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("locks", _)) => {
+            if let Err(err) = list_deploy_locks() {
+                eprintln!("❌ Error listing deploy locks: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("approvers", sub_matches)) => {
+            let approvers = if sub_matches.get_flag("clear") {
+                Some(Vec::new())
+            } else {
+                sub_matches
+                    .get_many::<String>("set")
+                    .map(|emails| emails.cloned().collect())
+            };
+            if let Err(err) = deploy_approvers(approvers) {
+                eprintln!("❌ Error managing deploy approvers: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("approvals", _)) => {
+            if let Err(err) = list_approvals() {
+                eprintln!("❌ Error listing approvals: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("approve", sub_matches)) => {
+            let id = *sub_matches.get_one::<i32>("id").expect("id is required");
+            if let Err(err) = decide_deploy(id, true) {
+                eprintln!("❌ Error approving deploy: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("reject", sub_matches)) => {
+            let id = *sub_matches.get_one::<i32>("id").expect("id is required");
+            if let Err(err) = decide_deploy(id, false) {
+                eprintln!("❌ Error rejecting deploy: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("preview", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("list", _)) => list_previews(),
+                Some(("delete", delete_matches)) => {
+                    let name = delete_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    let branch = delete_matches
+                        .get_one::<String>("branch")
+                        .expect("branch is required");
+                    delete_preview(name, branch)
+                }
+                _ => unreachable!("preview requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing previews: {}", err);
+                process::exit(err.class().exit_code());
             }
         }
         Some(("list", _)) => {
             if let Err(err) = list_functions() {
                 eprintln!("Error getting function: {}", err);
-                process::exit(1);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("delete", sub_matches)) => {
+            if let Some(name) = sub_matches.get_one::<String>("name") {
+                if let Err(err) = delete_function(name) {
+                    eprintln!("❌ Error deleting function: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            } else {
+                eprintln!("Name parameter is required");
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("trash", _)) => {
+            if let Err(err) = list_trash() {
+                eprintln!("❌ Error listing trash: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("storage", _)) => {
+            if let Err(err) = show_storage() {
+                eprintln!("❌ Error showing storage: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("dependencies", _)) => {
+            if let Err(err) = show_dependency_report() {
+                eprintln!("❌ Error showing dependency report: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("restore", sub_matches)) => {
+            if let Some(name) = sub_matches.get_one::<String>("name") {
+                if let Err(err) = restore_function(name) {
+                    eprintln!("❌ Error restoring function: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            } else {
+                eprintln!("Name parameter is required");
+                process::exit(ErrorClass::User.exit_code());
             }
         }
         Some(("logs", sub_matches)) => {
@@ -143,12 +1057,187 @@ fn main() {
                     }
                     Err(err) => {
                         eprintln!("❌ Error streaming logs: {}", err);
-                        process::exit(1);
+                        process::exit(err.class().exit_code());
                     }
                 }
             } else {
                 eprintln!("Name parameter is required");
-                process::exit(1);
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("bootlogs", sub_matches)) => {
+            if let Some(name) = sub_matches.get_one::<String>("name") {
+                if let Err(err) = boot_logs(name) {
+                    eprintln!("❌ Error getting boot logs: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            } else {
+                eprintln!("Name parameter is required");
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("status", sub_matches)) => {
+            if let Some(name) = sub_matches.get_one::<String>("name") {
+                let recommend = sub_matches.get_flag("recommend");
+                if let Err(err) = function_status(name, recommend) {
+                    eprintln!("❌ Error getting function status: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            } else {
+                eprintln!("Name parameter is required");
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("invocations", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            if let Err(err) = list_invocations(name) {
+                eprintln!("❌ Error listing recorded invocations: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("bench", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            let rps = *sub_matches
+                .get_one::<u32>("rps")
+                .expect("rps has a default");
+            let duration = *sub_matches
+                .get_one::<u64>("duration")
+                .expect("duration has a default");
+            if let Err(err) = bench(name, rps, Duration::from_secs(duration)) {
+                eprintln!("❌ Error benchmarking function: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("exec", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            let command: Vec<String> = sub_matches
+                .get_many::<String>("command")
+                .map(|args| args.cloned().collect())
+                .unwrap_or_else(|| vec!["sh".to_string()]);
+            match exec(name, command, !sub_matches.get_flag("no-tty")) {
+                Ok(code) => process::exit(code),
+                Err(err) => {
+                    eprintln!("❌ Error running command: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            }
+        }
+        Some(("dev", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            if let Err(err) = dev(name, sub_matches.get_flag("remote")) {
+                eprintln!("❌ Error running dev mode: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("replay", sub_matches)) => {
+            let invocation_id = sub_matches
+                .get_one::<String>("invocation-id")
+                .expect("invocation id is required");
+            let version = sub_matches.get_one::<i32>("version").copied();
+            if let Err(err) = replay_invocation(invocation_id, version) {
+                eprintln!("❌ Error replaying invocation: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("defaults", sub_matches)) => {
+            let set_from = sub_matches.get_one::<String>("set");
+            if let Err(err) = namespace_defaults(set_from.map(String::as_str)) {
+                eprintln!("❌ Error managing namespace defaults: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("notifications", sub_matches)) => {
+            let set_from = sub_matches.get_one::<String>("set");
+            let clear = sub_matches.get_flag("clear");
+            if let Err(err) = notifications(set_from.map(String::as_str), clear) {
+                eprintln!("❌ Error managing notifications: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("egress", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            let allow = if sub_matches.get_flag("clear") {
+                Some(Vec::new())
+            } else {
+                sub_matches
+                    .get_many::<String>("allow")
+                    .map(|hosts| hosts.cloned().collect())
+            };
+            if let Err(err) = egress_allowlist(name, allow) {
+                eprintln!("❌ Error managing egress allowlist: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("purge", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            if let Err(err) = purge_function(name) {
+                eprintln!("❌ Error purging cached responses: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("routing", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("name")
+                .expect("name is required");
+            let set_from = sub_matches.get_one::<String>("set");
+            let clear = sub_matches.get_flag("clear");
+            if let Err(err) = routing_rules(name, set_from.map(String::as_str), clear) {
+                eprintln!("❌ Error managing routing rules: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("export", sub_matches)) => {
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("output has a default");
+            if let Err(err) = export_namespace(output) {
+                eprintln!("❌ Error exporting functions: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("import", sub_matches)) => {
+            if let Some(file) = sub_matches.get_one::<String>("file") {
+                if let Err(err) = import_namespace(file) {
+                    eprintln!("❌ Error importing functions: {}", err);
+                    process::exit(err.class().exit_code());
+                }
+            } else {
+                eprintln!("File parameter is required");
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("login", sub_matches)) if sub_matches.get_flag("github-oidc") => {
+            let Some(namespace) = sub_matches
+                .get_one::<String>("namespace")
+                .cloned()
+                .or_else(|| std::env::var("INVOK_NAMESPACE").ok())
+            else {
+                eprintln!("--namespace or INVOK_NAMESPACE is required with --github-oidc");
+                process::exit(ErrorClass::User.exit_code());
+            };
+            match login_with_github_oidc(&namespace) {
+                Ok(session) => {
+                    println!(
+                        "Logged in with GitHub OIDC as {} (User ID: {}); the token can only deploy",
+                        session.email, session.user_uuid
+                    );
+                }
+                Err(err) => {
+                    eprintln!("Login failed: {}", err);
+                    process::exit(err.class().exit_code());
+                }
             }
         }
         Some(("login", sub_matches)) => {
@@ -156,7 +1245,8 @@ fn main() {
                 sub_matches.get_one::<String>("email"),
                 sub_matches.get_one::<String>("password"),
             ) {
-                match login(email, password) {
+                let code = sub_matches.get_one::<String>("code").map(String::as_str);
+                match login(email, password, code) {
                     Ok(session) => {
                         println!(
                             "Logged in successfully as {} (User ID: {})",
@@ -165,12 +1255,12 @@ fn main() {
                     }
                     Err(err) => {
                         eprintln!("Login failed: {}", err);
-                        process::exit(1);
+                        process::exit(err.class().exit_code());
                     }
                 }
             } else {
                 eprintln!("Email and password are required");
-                process::exit(1);
+                process::exit(ErrorClass::User.exit_code());
             }
         }
         Some(("register", sub_matches)) => {
@@ -187,12 +1277,231 @@ fn main() {
                     }
                     Err(err) => {
                         eprintln!("Registration failed: {}", err);
-                        process::exit(1);
+                        process::exit(err.class().exit_code());
                     }
                 }
             } else {
                 eprintln!("Email and password are required");
-                process::exit(1);
+                process::exit(ErrorClass::User.exit_code());
+            }
+        }
+        Some(("2fa", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("enroll", _)) => enroll_totp().map(|enrollment| {
+                    println!("Add this secret to your authenticator app:");
+                    println!("  {}", enrollment.secret);
+                    println!("or scan a QR code of:");
+                    println!("  {}", enrollment.uri);
+                    println!("Then run 'invok 2fa enable' with the code it shows.");
+                }),
+                Some(("enable", enable_matches)) => {
+                    let code = enable_matches.get_one::<String>("code").map(String::as_str);
+                    enable_totp(code).map(|recovery_codes| {
+                        println!("🔒 Two-factor authentication enabled.");
+                        println!(
+                            "Recovery codes (each works once if you lose the app; store them safely, they won't be shown again):"
+                        );
+                        for code in recovery_codes {
+                            println!("  {}", code);
+                        }
+                    })
+                }
+                Some(("disable", disable_matches)) => {
+                    let code = disable_matches
+                        .get_one::<String>("code")
+                        .map(String::as_str);
+                    disable_totp(code).map(|_| println!("Two-factor authentication disabled."))
+                }
+                _ => unreachable!("2fa requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing two-factor authentication: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("buildarg", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("list", _)) => list_build_args(),
+                Some(("set", set_matches)) => {
+                    let name = set_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    set_build_arg(
+                        name,
+                        set_matches.get_one::<String>("value").map(String::as_str),
+                    )
+                }
+                Some(("unset", unset_matches)) => {
+                    let name = unset_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    unset_build_arg(name)
+                }
+                _ => unreachable!("buildarg requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing build args: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("credential", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("list", _)) => list_credentials(),
+                Some(("set", set_matches)) => {
+                    let name = set_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    let url = set_matches
+                        .get_one::<String>("url")
+                        .expect("url is required");
+                    set_credential(
+                        name,
+                        url,
+                        set_matches.get_one::<String>("header").map(String::as_str),
+                        set_matches.get_one::<String>("value").map(String::as_str),
+                    )
+                }
+                Some(("unset", unset_matches)) => {
+                    let name = unset_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    unset_credential(name)
+                }
+                _ => unreachable!("credential requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing credentials: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("flags", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("list", _)) => list_flags(),
+                Some(("set", set_matches)) => {
+                    let key = set_matches
+                        .get_one::<String>("key")
+                        .expect("key is required");
+                    let value = set_matches
+                        .get_one::<String>("value")
+                        .expect("value is required");
+                    set_flag(
+                        key,
+                        value,
+                        *set_matches.get_one::<u8>("rollout").unwrap_or(&100),
+                        set_matches.get_one::<String>("default").map(String::as_str),
+                    )
+                }
+                Some(("delete", delete_matches)) => {
+                    let key = delete_matches
+                        .get_one::<String>("key")
+                        .expect("key is required");
+                    delete_flag(key)
+                }
+                _ => unreachable!("flags requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing feature flags: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("oidc", sub_matches)) => {
+            let result = match sub_matches.subcommand() {
+                Some(("trust", trust_matches)) => match trust_matches.subcommand() {
+                    Some(("list", _)) => list_oidc_trusts(),
+                    Some(("add", add_matches)) => {
+                        let repository = add_matches
+                            .get_one::<String>("repo")
+                            .expect("repo is required");
+                        add_oidc_trust(
+                            repository,
+                            add_matches
+                                .get_one::<String>("owner-id")
+                                .map(String::as_str),
+                            add_matches.get_one::<String>("repo-id").map(String::as_str),
+                            add_matches.get_one::<String>("ref").map(String::as_str),
+                            add_matches
+                                .get_one::<String>("environment")
+                                .map(String::as_str),
+                        )
+                    }
+                    Some(("remove", remove_matches)) => {
+                        let id = remove_matches.get_one::<i32>("id").expect("id is required");
+                        remove_oidc_trust(*id)
+                    }
+                    _ => unreachable!("trust requires a subcommand"),
+                },
+                _ => unreachable!("oidc requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Error managing OIDC trusts: {}", err);
+                process::exit(err.class().exit_code());
+            }
+        }
+        Some(("admin", sub_matches)) => {
+            let token = sub_matches.get_one::<String>("token").map(String::as_str);
+            let result = match sub_matches.subcommand() {
+                Some(("backup", backup_matches)) => {
+                    let output = backup_matches
+                        .get_one::<String>("output")
+                        .expect("output has a default");
+                    backup(output, token)
+                }
+                Some(("restore", restore_matches)) => {
+                    let file = restore_matches
+                        .get_one::<String>("file")
+                        .expect("file is required");
+                    restore(file, token)
+                }
+                Some(("incident", incident_matches)) => match incident_matches.subcommand() {
+                    Some(("open", open_matches)) => {
+                        let title = open_matches
+                            .get_one::<String>("title")
+                            .expect("title is required");
+                        let message = open_matches.get_one::<String>("message");
+                        let major = open_matches.get_flag("major");
+                        open_incident(title, message.map(String::as_str), major, token)
+                    }
+                    Some(("resolve", resolve_matches)) => {
+                        let id = resolve_matches
+                            .get_one::<String>("id")
+                            .expect("id is required");
+                        resolve_incident(id, token)
+                    }
+                    _ => unreachable!("incident requires a subcommand"),
+                },
+                Some(("dependencies", _)) => dependency_report(token),
+                Some(("freeze", freeze_matches)) => match freeze_matches.subcommand() {
+                    Some(("list", _)) => list_freezes(token),
+                    Some(("add", add_matches)) => {
+                        let from = add_matches
+                            .get_one::<String>("from")
+                            .expect("from is required");
+                        let until = add_matches
+                            .get_one::<String>("until")
+                            .expect("until is required");
+                        let namespace = add_matches.get_one::<String>("namespace");
+                        let reason = add_matches.get_one::<String>("reason");
+                        add_freeze(
+                            from,
+                            until,
+                            namespace.map(String::as_str),
+                            reason.map(String::as_str),
+                            token,
+                        )
+                    }
+                    Some(("lift", lift_matches)) => {
+                        let id = lift_matches
+                            .get_one::<String>("id")
+                            .expect("id is required");
+                        lift_freeze(id, token)
+                    }
+                    _ => unreachable!("freeze requires a subcommand"),
+                },
+                _ => unreachable!("admin requires a subcommand"),
+            };
+            if let Err(err) = result {
+                eprintln!("❌ Admin command failed: {}", err);
+                process::exit(err.class().exit_code());
             }
         }
         Some(("logout", _)) => match logout() {
@@ -201,12 +1510,12 @@ fn main() {
             }
             Err(err) => {
                 eprintln!("Logout failed: {}", err);
-                process::exit(1);
+                process::exit(err.class().exit_code());
             }
         },
         _ => {
             eprintln!("Please use a valid subcommand. Run with --help for more information.");
-            process::exit(1);
+            process::exit(ErrorClass::User.exit_code());
         }
     }
 }
@@ -1,20 +1,25 @@
-use crate::auth::{load_session, AuthError};
+use crate::auth::{load_profile_session, load_session, AuthError};
 use crate::host_manager;
-use crate::utils::{create_fn_project_file, init_function_module, FuncConfig};
-use futures_util::stream::TryStreamExt;
-use reqwest::blocking::{multipart, Client};
+use crate::utils::{create_fn_project_file, init_function_module, request_error_class, FuncConfig};
+use invok_client::{ClientError, DeployOptions};
+use reqwest::blocking::{multipart, Client, Response};
 use reqwest::header::{self, HeaderMap, HeaderValue};
 use serde_json::Value;
-use shared_utils::{compress_dir_with_excludes, to_camel_case_handler};
+use shared_utils::error::{Classify, ErrorClass};
+use shared_utils::{
+    compress_function_with_shared, sha256_hex, to_camel_case_handler, zip_digests, zip_files,
+};
 use std::fs::File;
 use std::io::{self, Cursor, Read, Write};
-use std::path::Path;
+use std::path::{Component, Path};
 use std::time::Duration;
-use templates::{go_template, nodejs_template};
+use templates::{go_template, nodejs_template, rust_template};
 use thiserror::Error;
 
 // Constants
 const REQUEST_TIMEOUT_SECS: u64 = 120;
+/// Imports rebuild every function, so they get far longer than other requests
+const IMPORT_TIMEOUT_SECS: u64 = 1800;
 const CONFIG_FILE_PATH: &str = "config.json";
 
 /// Errors that can occur during serverless function operations
@@ -37,6 +42,43 @@ pub enum FunctionError {
 
     #[error("Authentication error: {0}")]
     AuthError(#[from] AuthError),
+
+    #[error("API error: Status code {0}. {1}")]
+    Api(reqwest::StatusCode, String),
+
+    #[error("Deploy error: {0}")]
+    DeployError(String),
+}
+
+impl Classify for FunctionError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            FunctionError::RequestError(e) => request_error_class(e),
+            FunctionError::FunctionNotFound(_)
+            | FunctionError::CompressionError(_)
+            | FunctionError::DeployError(_) => ErrorClass::User,
+            FunctionError::AuthError(e) => e.class(),
+            FunctionError::Api(status, _) => ErrorClass::from_status(status.as_u16()),
+            FunctionError::IoError(_) | FunctionError::JsonError(_) => ErrorClass::System,
+        }
+    }
+}
+
+impl From<ClientError> for FunctionError {
+    fn from(error: ClientError) -> Self {
+        match error {
+            ClientError::Network(e) => FunctionError::RequestError(e),
+            ClientError::Io(e) => FunctionError::IoError(e),
+            ClientError::Json(e) => FunctionError::JsonError(e),
+            ClientError::FunctionNotFound(name) => FunctionError::FunctionNotFound(name),
+            ClientError::Api { status, message } => FunctionError::Api(status, message),
+            ClientError::DeployFailed(message) => FunctionError::DeployError(message),
+            error @ ClientError::DeployUnfinished(_) => {
+                FunctionError::DeployError(error.to_string())
+            }
+            error => FunctionError::CompressionError(error.to_string()),
+        }
+    }
 }
 
 /// Creates a new serverless function project with the specified name and runtime.
@@ -50,17 +92,7 @@ pub enum FunctionError {
 ///
 /// A Result indicating success or containing an error
 pub fn create_new_project(name: &str, runtime: &str) -> Result<(), FunctionError> {
-    // Validate runtime
-    let normalized_runtime = match runtime.to_lowercase().as_str() {
-        "go" => "go",
-        "nodejs" | "node" | "typescript" | "ts" => "nodejs",
-        _ => {
-            return Err(FunctionError::CompressionError(format!(
-                "Unsupported runtime: '{}'. Supported runtimes: go, nodejs",
-                runtime
-            )))
-        }
-    };
+    let normalized_runtime = normalize_runtime(runtime)?;
 
     println!("Creating service... '{name}' [RUNTIME:'{normalized_runtime}']");
     // Create project file
@@ -86,6 +118,14 @@ pub fn create_new_project(name: &str, runtime: &str) -> Result<(), FunctionError
                     .as_bytes(),
             )?;
         }
+        "rust" => {
+            // Write template with replacements
+            file.write_all(
+                rust_template::ROUTE_TEMPLATE
+                    .replace("{{ROUTE}}", name)
+                    .as_bytes(),
+            )?;
+        }
         _ => {}
     }
 
@@ -96,6 +136,19 @@ pub fn create_new_project(name: &str, runtime: &str) -> Result<(), FunctionError
     Ok(())
 }
 
+/// Validates a runtime, resolving its aliases
+pub(crate) fn normalize_runtime(runtime: &str) -> Result<&'static str, FunctionError> {
+    match runtime.to_lowercase().as_str() {
+        "go" => Ok("go"),
+        "nodejs" | "node" | "typescript" | "ts" => Ok("nodejs"),
+        "rust" | "rs" => Ok("rust"),
+        _ => Err(FunctionError::CompressionError(format!(
+            "Unsupported runtime: '{}'. Supported runtimes: go, nodejs, rust",
+            runtime
+        ))),
+    }
+}
+
 /// List all functions
 pub fn list_functions() -> Result<(), FunctionError> {
     // Load authentication session
@@ -134,6 +187,9 @@ pub fn list_functions() -> Result<(), FunctionError> {
         println!("+--------------------------------------+----------------------+---------+");
 
         // Print each function as a table row
+        let hibernated = functions
+            .iter()
+            .any(|function| function["hibernated"].as_bool().unwrap_or(false));
         for function in functions {
             let uuid = function["uuid"].as_str().unwrap_or("N/A");
             let name = function["name"].as_str().unwrap_or("N/A");
@@ -145,6 +201,9 @@ pub fn list_functions() -> Result<(), FunctionError> {
 
         // Print table footer
         println!("+--------------------------------------+----------------------+---------+");
+        if hibernated {
+            println!("💤 This namespace is hibernated; the next request wakes it");
+        }
 
         Ok(())
     } else {
@@ -153,74 +212,165 @@ pub fn list_functions() -> Result<(), FunctionError> {
             .text()
             .unwrap_or_else(|_| "Unknown error".to_string());
 
-        Err(FunctionError::CompressionError(format!(
-            "API error: Status code {}. {}",
-            status, error_text
-        )))
+        Err(FunctionError::Api(status, error_text))
     }
 }
 
-/// Deploys an existing function to the serverless platform using authentication.
-///
-/// # Arguments
-///
-/// * `name` - The name of the function to deploy
-///
-/// # Returns
+/// Show the status of a deployed function, including why its last container crashed
 ///
-/// A Result indicating success or containing an error
-pub fn deploy_function(name: &str) -> Result<(), FunctionError> {
-    // Read configuration file
-    let mut config_file = File::open(format!("{name}/{CONFIG_FILE_PATH}"))?;
-    let mut contents = String::new();
-    config_file.read_to_string(&mut contents)?;
+/// With `recommend`, also prints sampled resource usage and suggested limits.
+pub fn function_status(name: &str, recommend: bool) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
 
-    let config: FuncConfig = serde_json::from_str(&contents)?;
+    let details = session
+        .client()
+        .with_timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .status(name)?;
 
-    // Validate function exists in config
-    if !config.function_name.contains(&name.to_string()) {
-        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    println!("Function:  {}", details.name);
+    println!("Runtime:   {}", details.runtime);
+    match &details.pool {
+        Some(pool) => println!(
+            "Instances: {} running ({} healthy, {} overloaded, {} idle)",
+            pool.get("total_containers").unwrap_or(&Value::from(0)),
+            pool.get("healthy_containers").unwrap_or(&Value::from(0)),
+            pool.get("overloaded_containers").unwrap_or(&Value::from(0)),
+            pool.get("idle_containers").unwrap_or(&Value::from(0)),
+        ),
+        None => match &details.hibernated_at {
+            Some(at) => println!("Instances: 0 running (hibernated since {at})"),
+            None => println!("Instances: 0 running (cold)"),
+        },
+    }
+    if let Some(port) = details.service_port {
+        println!(
+            "Port:      {} ({})",
+            port,
+            details.settings["service"].as_str().unwrap_or_default()
+        );
     }
 
-    let runtime = config.runtime;
-    println!("🚀 Deploying service... '{}'", name);
-
-    // Create ZIP archive with runtime-specific exclusions
-    let mut dest_zip = Cursor::new(Vec::new());
-    let exclude_files = match runtime.to_lowercase().as_str() {
-        "go" => vec!["go.mod", "go.sum", ".git", ".gitignore"],
-        "nodejs" | "node" | "typescript" | "ts" => {
-            vec!["node_modules", ".git", ".gitignore", "dist", "*.log"]
+    match &details.last_crash {
+        None => println!("Last crash: none recorded"),
+        Some(last_crash) => {
+            println!("Last crash: {}", last_crash.summary);
+            if let Some(logs) = last_crash.report["last_logs"].as_array() {
+                if !logs.is_empty() {
+                    println!("Last {} log lines before the crash:", logs.len());
+                    for line in logs {
+                        println!("  {}", line.as_str().unwrap_or_default());
+                    }
+                }
+            }
         }
-        _ => vec![],
-    };
+    }
+
+    if let Some(slo) = &details.slo {
+        println!(
+            "SLO:       {:.3}% good over {} days (objective {}%), {} of {} invocations bad",
+            slo.compliance, slo.window_days, slo.objective, slo.bad, slo.invocations,
+        );
+        println!(
+            "Budget:    {:.0}% left, burning {:.1}x over 1h and {:.1}x over 6h{}",
+            slo.budget_remaining.max(0.0) * 100.0,
+            slo.burn_rate_1h,
+            slo.burn_rate_6h,
+            if slo.deploys_frozen {
+                " (deploys frozen)"
+            } else {
+                ""
+            },
+        );
+    }
+
+    if recommend {
+        // Set up authorization headers
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", session.token))
+                .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+        );
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .default_headers(headers)
+            .build()?;
+        print_recommendations(&client, &session.user_uuid, name)?;
+    }
+
+    Ok(())
+}
+
+/// Print the resource usage of a function and the suggested memory/CPU limits
+fn print_recommendations(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+) -> Result<(), FunctionError> {
+    let response = client
+        .get(host_manager::function_recommendations_url(namespace, name))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
 
-    compress_dir_with_excludes(Path::new(name), &mut dest_zip, &exclude_files)
-        .map_err(|e| FunctionError::CompressionError(e.to_string()))?;
+    let details: Value = serde_json::from_str(&response.text()?)?;
+    let usage = &details["usage"];
+    let limits = &details["limits"];
 
-    // Reset the cursor to the beginning of the buffer
-    dest_zip.set_position(0);
+    println!();
+    println!(
+        "Limits:    {}MB memory, {} CPU",
+        limits["memory_mb"], limits["cpus"]
+    );
+    println!(
+        "Usage:     peak {:.0}MB / {:.2} CPU, average {:.0}MB / {:.2} CPU ({} samples)",
+        usage["peak_memory_mb"].as_f64().unwrap_or_default(),
+        usage["peak_cpu_cores"].as_f64().unwrap_or_default(),
+        usage["avg_memory_mb"].as_f64().unwrap_or_default(),
+        usage["avg_cpu_cores"].as_f64().unwrap_or_default(),
+        usage["samples"],
+    );
 
-    println!("📦 Zipped up the folder service... '{}'", name);
+    let recommendations = details["recommendations"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let samples = usage["samples"].as_u64().unwrap_or_default();
+    let min_samples = details["min_samples"].as_u64().unwrap_or_default();
 
-    deploy_with_auth(name, dest_zip)?;
+    if samples < min_samples {
+        println!(
+            "Recommendations: not enough data yet ({} of {} samples), invoke the function more",
+            samples, min_samples
+        );
+    } else if recommendations.is_empty() {
+        println!("Recommendations: current limits fit this function");
+    } else {
+        println!("Recommendations:");
+        for recommendation in recommendations {
+            println!(
+                "  - {}",
+                recommendation["message"].as_str().unwrap_or_default()
+            );
+        }
+    }
 
     Ok(())
 }
 
-/// Deploy a function using authentication
-fn deploy_with_auth(name: &str, dest_zip: Cursor<Vec<u8>>) -> Result<String, FunctionError> {
+/// Show the startup output of the last container that failed to become ready
+pub fn boot_logs(name: &str) -> Result<(), FunctionError> {
     // Load authentication session
     let session = load_session()?;
 
-    // Create multipart form
-    let form = multipart::Form::new().part(
-        "file",
-        multipart::Part::reader(dest_zip)
-            .file_name(format!("{name}.zip"))
-            .mime_str("application/zip")?,
-    );
-
     // Set up authorization headers
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -229,70 +379,172 @@ fn deploy_with_auth(name: &str, dest_zip: Cursor<Vec<u8>>) -> Result<String, Fun
             .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
     );
 
-    // Build client with timeout
     let client = Client::builder()
         .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
         .default_headers(headers)
         .build()?;
 
-    // Send request to API
     let response = client
-        .post(host_manager::function_upload_url())
-        .multipart(form)
+        .get(host_manager::function_boot_logs_url(name))
         .send()?;
 
-    // Check the response
-    if response.status().is_success() {
-        let response_text = response.text()?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        let message = response.text().unwrap_or_else(|_| "Not found".to_string());
+        println!("{}", message);
+        return Ok(());
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
 
-        // Generate function URL
-        let function_url = generate_function_url(name, &session.user_uuid);
+    let boot_log: Value = serde_json::from_str(&response.text()?)?;
 
-        // Print deployment success message with URL
-        println!("✅ Function deployed successfully!");
-        println!("📝 Function name: {}", name);
-        println!("🌐 Function URL: {}", function_url);
-        println!("🔗 You can invoke your function by making requests to the URL above");
+    println!(
+        "Container {} failed to start: {}",
+        boot_log["container_id"].as_str().unwrap_or("N/A"),
+        boot_log["reason"].as_str().unwrap_or("unknown")
+    );
+    match boot_log["lines"].as_array() {
+        Some(lines) if !lines.is_empty() => {
+            for line in lines {
+                println!("  {}", line.as_str().unwrap_or_default());
+            }
+        }
+        _ => println!("The container produced no output before failing."),
+    }
 
-        Ok(response_text)
-    } else {
-        let status = response.status();
+    Ok(())
+}
+
+/// List the invocations recorded for a function, newest first.
+///
+/// Only functions with `record_invocations` set in their `config.json` are recorded.
+pub fn list_invocations(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .get(host_manager::function_invocations_url(name))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    if !status.is_success() {
         let error_text = response
             .text()
             .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
 
-        Err(FunctionError::CompressionError(format!(
-            "API error: Status code {}. {}",
-            status, error_text
-        )))
+    let invocations: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if invocations.is_empty() {
+        println!(
+            "No invocations recorded for '{}'. Set \"record_invocations\": true in its config.json to record them.",
+            name
+        );
+        return Ok(());
     }
-}
 
-/// Generate the function URL for a deployed function
-fn generate_function_url(function_name: &str, user_uuid: &str) -> String {
-    format!(
-        "{}/invok/{}/{}",
-        host_manager::base_url(),
-        user_uuid,
-        function_name
-    )
+    println!(
+        "{:<38} {:<8} {:<8} {:<8} {:<10} RECORDED AT",
+        "ID", "METHOD", "STATUS", "VERSION", "BODY"
+    );
+    for invocation in invocations {
+        println!(
+            "{:<38} {:<8} {:<8} {:<8} {:<10} {}",
+            invocation["id"].as_str().unwrap_or("N/A"),
+            invocation["method"].as_str().unwrap_or("N/A"),
+            invocation["status"].as_u64().unwrap_or_default(),
+            invocation["version"]
+                .as_i64()
+                .map_or("N/A".to_string(), |version| version.to_string()),
+            format!("{}B", invocation["body_bytes"].as_u64().unwrap_or_default()),
+            invocation["recorded_at"].as_str().unwrap_or("N/A")
+        );
+    }
+
+    Ok(())
 }
 
-/// Stream logs from a deployed function
+/// Send a recorded invocation to its function again and print the response.
 ///
 /// # Arguments
 ///
-/// * `name` - The name of the function to stream logs from
+/// * `invocation_id` - The request id of the recorded invocation
+/// * `version` - Version of the function to replay on; the current one when `None`
 ///
 /// # Returns
 ///
 /// A Result indicating success or containing an error
-pub fn stream_logs(name: &str) -> Result<(), FunctionError> {
+pub fn replay_invocation(invocation_id: &str, version: Option<i32>) -> Result<(), FunctionError> {
     // Load authentication session
     let session = load_session()?;
 
-    // Build the logs URL
-    let logs_url = host_manager::function_logs_url(&session.user_uuid, name);
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let mut request = client.post(host_manager::invocation_replay_url(invocation_id));
+    if let Some(version) = version {
+        request = request.query(&[("version", version)]);
+    }
+    let response = request.send()?;
+
+    // Errors from the platform (unknown invocation, missing version) are returned before
+    // the function is reached, so they carry no replay header
+    let status = response.status();
+    if !response.headers().contains_key("x-invok-replay-of") {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("Replayed '{}': {}", invocation_id, status);
+    for name in ["x-invok-request-id", "x-invok-function", "x-invok-version"] {
+        if let Some(value) = response.headers().get(name).and_then(|v| v.to_str().ok()) {
+            println!("{}: {}", name, value);
+        }
+    }
+    println!();
+    println!("{}", response.text()?);
+
+    Ok(())
+}
+
+/// Delete a function. It stops serving right away and moves to the trash, where it can
+/// be restored with `invok restore` until the server's retention period ends.
+pub fn delete_function(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
 
     // Set up authorization headers
     let mut headers = HeaderMap::new();
@@ -302,81 +554,2053 @@ pub fn stream_logs(name: &str) -> Result<(), FunctionError> {
             .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
     );
 
-    // Use minimal single-threaded runtime for streaming
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_io()
-        .enable_time()
-        .build()
-        .map_err(|e| FunctionError::IoError(io::Error::new(io::ErrorKind::Other, e)))?;
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .delete(host_manager::function_delete_url(name))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let trashed: Value = serde_json::from_str(&response.text()?)?;
+    println!("🗑️  Function '{}' moved to the trash", name);
+    println!(
+        "It can be restored with `invok restore -n {}` until {}",
+        name,
+        trashed["purge_at"].as_str().unwrap_or("N/A")
+    );
 
-    rt.block_on(async { stream_logs_async(&logs_url, headers).await })
+    Ok(())
 }
 
-/// Async function to handle log streaming
-async fn stream_logs_async(url: &str, headers: HeaderMap) -> Result<(), FunctionError> {
-    // Build async client
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(300)) // 5 minute timeout for streaming
+/// List deleted functions that can still be restored
+pub fn list_trash() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
         .default_headers(headers)
-        .build()
-        .map_err(|e| FunctionError::RequestError(e))?;
+        .build()?;
 
-    println!("🔍 Connecting to function logs...");
+    let response = client.get(host_manager::function_trash_url()).send()?;
 
-    // Send request to logs endpoint
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| FunctionError::RequestError(e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
+    let functions: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if functions.is_empty() {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<8} {:<26} PURGED AT",
+        "NAME", "RUNTIME", "DELETED AT"
+    );
+    for function in functions {
+        println!(
+            "{:<20} {:<8} {:<26} {}",
+            function["name"].as_str().unwrap_or("N/A"),
+            function["runtime"].as_str().unwrap_or("N/A"),
+            function["deleted_at"].as_str().unwrap_or("N/A"),
+            function["purge_at"].as_str().unwrap_or("N/A")
+        );
+    }
+
+    Ok(())
+}
+
+/// Show the storage your functions' recorded versions take, and the retention limits
+/// they are pruned down to
+pub fn show_storage() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::storage_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
         let error_text = response
             .text()
-            .await
             .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
 
-        return Err(FunctionError::CompressionError(format!(
-            "Failed to connect to logs: Status code {}. {}",
-            status, error_text
-        )));
+    let usage: Value = serde_json::from_str(&response.text()?)?;
+    let functions = usage["functions"].as_array().cloned().unwrap_or_default();
+    if functions.is_empty() {
+        println!("No versions recorded yet.");
+    } else {
+        println!("{:<20} {:<10} {:<8} BYTES", "NAME", "VERSIONS", "LATEST");
+        for function in functions {
+            println!(
+                "{:<20} {:<10} {:<8} {}",
+                function["name"].as_str().unwrap_or("N/A"),
+                function["versions"].as_u64().unwrap_or(0),
+                function["latest_version"]
+                    .as_i64()
+                    .map_or("-".to_string(), |version| format!("v{version}")),
+                function["bytes"].as_u64().unwrap_or(0)
+            );
+        }
     }
 
-    println!("📡 Connected! Streaming logs... (Press Ctrl+C to stop)\n");
+    println!(
+        "\nTotal: {} versions, {} bytes",
+        usage["total_versions"].as_u64().unwrap_or(0),
+        usage["total_bytes"].as_u64().unwrap_or(0)
+    );
+    let keep = usage["keep_versions"]
+        .as_u64()
+        .map_or("unlimited".to_string(), |keep| keep.to_string());
+    let max_bytes = usage["max_bytes"]
+        .as_u64()
+        .map_or("unlimited".to_string(), |max| max.to_string());
+    println!(
+        "Retention: {} versions per function, {} bytes per namespace",
+        keep, max_bytes
+    );
 
-    // Stream the response
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk) = TryStreamExt::try_next(&mut stream)
-        .await
-        .map_err(|e| FunctionError::RequestError(e))?
-    {
-        let text = String::from_utf8_lossy(&chunk);
-
-        // Filter out empty lines and just print the log content
-        for line in text.lines() {
-            if !line.trim().is_empty() {
-                // Parse Server-Sent Events format if needed
-                if line.starts_with("data:") {
-                    let log_content = &line[5..]; // Remove "data:" prefix
-                    if !log_content.trim().is_empty() {
-                        println!("{}", log_content);
-                    }
-                } else if !line.starts_with(":")
-                    && !line.starts_with("event:")
-                    && !line.starts_with("id:")
-                {
-                    // Print non-SSE control lines directly
-                    println!("{}", line);
-                }
-            }
+    Ok(())
+}
+
+/// Show your functions using deprecated runtimes or vulnerable dependency versions
+pub fn show_dependency_report() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::dependency_report_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    print_dependency_report(&report, false);
+
+    Ok(())
+}
+
+/// Print a dependency report, with each function's namespace if `namespaces` is set
+pub fn print_dependency_report(report: &Value, namespaces: bool) {
+    let functions = report["functions"].as_array().cloned().unwrap_or_default();
+    let scanned = report["scanned"].as_u64().unwrap_or(0);
+    if functions.is_empty() {
+        println!("✅ None of {} scanned functions are flagged", scanned);
+    }
+    for function in &functions {
+        let name = function["name"].as_str().unwrap_or("N/A");
+        let runtime = function["runtime"].as_str().unwrap_or("N/A");
+        let version = function["runtime_version"].as_str().unwrap_or("");
+        match (namespaces, function["namespace"].as_str()) {
+            (true, Some(namespace)) => {
+                println!("⚠️  {} ({} {}) in {}", name, runtime, version, namespace)
+            }
+            _ => println!("⚠️  {} ({} {})", name, runtime, version),
+        }
+        if let Some(message) = function["deprecated_runtime"].as_str() {
+            println!("    deprecated runtime: {}", message);
+        }
+        for dependency in function["vulnerable"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+        {
+            println!(
+                "    {} {}: {} (fixed in {}){}",
+                dependency["package"].as_str().unwrap_or("N/A"),
+                dependency["version"].as_str().unwrap_or("N/A"),
+                dependency["advisory"].as_str().unwrap_or("N/A"),
+                dependency["fixed_in"].as_str().unwrap_or("N/A"),
+                dependency["severity"]
+                    .as_str()
+                    .map_or(String::new(), |severity| format!(" [{}]", severity))
+            );
+        }
+    }
+    if !functions.is_empty() {
+        println!(
+            "\n{} of {} scanned functions flagged",
+            functions.len(),
+            scanned
+        );
+    }
+    if let Some(loaded_at) = report["advisories_loaded_at"].as_str() {
+        println!("Advisories loaded at {}", loaded_at);
+    }
+}
+
+/// Bring a deleted function back from the trash
+pub fn restore_function(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .post(host_manager::function_restore_url(name))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(format!(
+            "'{}' is not in the trash",
+            name
+        )));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("♻️  Function '{}' restored", name);
+    Ok(())
+}
+
+/// List the preview instances in the namespace
+pub fn list_previews() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::function_previews_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let previews: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if previews.is_empty() {
+        println!("No previews found.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<20} {:<26} URL",
+        "FUNCTION", "BRANCH", "EXPIRES AT"
+    );
+    for preview in previews {
+        let name = preview["name"].as_str().unwrap_or("N/A");
+        println!(
+            "{:<20} {:<20} {:<26} {}",
+            preview["function"].as_str().unwrap_or("N/A"),
+            preview["branch"].as_str().unwrap_or("N/A"),
+            preview["expires_at"].as_str().unwrap_or("N/A"),
+            generate_function_url(name, &session.user_uuid)
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove a function's preview instance for a branch
+pub fn delete_preview(name: &str, branch: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .delete(host_manager::function_preview_url(name, branch))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(format!(
+            "no preview of '{}' for branch '{}'",
+            name, branch
+        )));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("🗑️  Preview of '{}' for branch '{}' removed", name, branch);
+    Ok(())
+}
+
+/// Drop the responses the platform cached for a function, and its preview and version
+/// instances; CDNs are told through the server's purge webhooks
+pub fn purge_function(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.post(host_manager::function_purge_url(name)).send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    println!("🧹 Cached responses of '{}' purged", name);
+    for path in report["paths"].as_array().cloned().unwrap_or_default() {
+        println!("   {}", path.as_str().unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// List the CI workflows trusted to deploy into the namespace with an OIDC token
+pub fn list_oidc_trusts() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::oidc_trusts_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let trusts: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if trusts.is_empty() {
+        println!("No trusted workflows. Add one with 'invok oidc trust add --repo OWNER/NAME'.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<6} {:<30} {:<24} {:<14}",
+        "ID", "REPOSITORY", "REF", "ENVIRONMENT"
+    );
+    let mut without_ids = false;
+    for trust in trusts {
+        // Trusts added before ids were stored accept no tokens
+        let marker = if trust["repository_owner_id"].is_null() {
+            without_ids = true;
+            " *"
+        } else {
+            ""
+        };
+        println!(
+            "{:<6} {:<30} {:<24} {:<14}",
+            trust["id"].as_i64().unwrap_or_default(),
+            format!(
+                "{}{}",
+                trust["repository"].as_str().unwrap_or("N/A"),
+                marker
+            ),
+            trust["ref_pattern"].as_str().unwrap_or("any"),
+            trust["environment"].as_str().unwrap_or("any")
+        );
+    }
+    if without_ids {
+        println!(
+            "\n* added before repository ids were checked; remove and add it again to use it."
+        );
+    }
+
+    Ok(())
+}
+
+/// GitHub's REST API, where the ids of repositories and their owners are looked up
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Looks up the ids GitHub tokens carry for a repository, since names can change hands.
+///
+/// `GITHUB_TOKEN` is sent when set, so private repositories resolve too.
+///
+/// # Returns
+///
+/// The owner's id, and the repository's id unless `repository` is a pattern.
+fn github_ids(repository: &str) -> Result<(String, Option<String>), FunctionError> {
+    let (owner, name) = repository.split_once('/').ok_or_else(|| {
+        FunctionError::DeployError(format!("repository '{repository}' must be OWNER/NAME"))
+    })?;
+    let url = if name.contains('*') {
+        format!("{GITHUB_API_URL}/users/{owner}")
+    } else {
+        format!("{GITHUB_API_URL}/repos/{owner}/{name}")
+    };
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()?;
+    let mut request = client
+        .get(&url)
+        .header(header::USER_AGENT, "invok-cli")
+        .header(header::ACCEPT, "application/vnd.github+json");
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(FunctionError::Api(
+            status,
+            format!(
+                "GitHub could not resolve '{repository}'; pass --owner-id and --repo-id, \
+                 or set GITHUB_TOKEN for a private repository"
+            ),
+        ));
+    }
+
+    let body: Value = serde_json::from_str(&response.text()?)?;
+    let id = |value: &Value| value.as_i64().map(|id| id.to_string());
+    if name.contains('*') {
+        let owner_id = id(&body["id"]).ok_or_else(|| {
+            FunctionError::DeployError(format!("GitHub returned no id for '{owner}'"))
+        })?;
+        Ok((owner_id, None))
+    } else {
+        match (id(&body["owner"]["id"]), id(&body["id"])) {
+            (Some(owner_id), Some(repository_id)) => Ok((owner_id, Some(repository_id))),
+            _ => Err(FunctionError::DeployError(format!(
+                "GitHub returned no ids for '{repository}'"
+            ))),
+        }
+    }
+}
+
+/// Trust a GitHub repository's workflows to deploy into the namespace with an OIDC token
+///
+/// The trust is pinned to the ids of the repository and its owner, which are looked up on
+/// GitHub unless given.
+///
+/// # Arguments
+///
+/// * `repository` - `owner/name` of the repository; `*` in the name matches any characters
+/// * `owner_id` - Id of the repository owner
+/// * `repository_id` - Id of the repository; not used with a pattern
+/// * `ref_pattern` - Git ref the workflow must run for, e.g. `refs/heads/main`
+/// * `environment` - Deployment environment the job must run in
+pub fn add_oidc_trust(
+    repository: &str,
+    owner_id: Option<&str>,
+    repository_id: Option<&str>,
+    ref_pattern: Option<&str>,
+    environment: Option<&str>,
+) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    let is_pattern = repository.contains('*');
+    let (owner_id, repository_id) = match (owner_id, repository_id) {
+        (Some(owner_id), Some(repository_id)) => {
+            (owner_id.to_string(), Some(repository_id.to_string()))
+        }
+        (Some(owner_id), None) if is_pattern => (owner_id.to_string(), None),
+        _ => github_ids(repository)?,
+    };
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .post(host_manager::oidc_trusts_url())
+        .json(&serde_json::json!({
+            "repository": repository,
+            "repository_owner_id": owner_id,
+            "repository_id": repository_id,
+            "ref_pattern": ref_pattern,
+            "environment": environment,
+        }))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let trust: Value = serde_json::from_str(&response.text()?)?;
+    println!(
+        "✅ Workflows of '{}' can now deploy (trust {}). Use 'invok login --github-oidc --namespace {}' in CI.",
+        repository,
+        trust["id"].as_i64().unwrap_or_default(),
+        session.user_uuid
+    );
+    Ok(())
+}
+
+/// Stop trusting a CI workflow
+pub fn remove_oidc_trust(id: i32) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.delete(host_manager::oidc_trust_url(id)).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("🗑️  Trust {} removed", id);
+    Ok(())
+}
+
+/// Lock deploys of a function, or of every function of the namespace, until unlocked
+///
+/// # Arguments
+///
+/// * `name` - The function to lock, or `None` for the whole namespace
+/// * `reason` - Why, shown to whoever tries to deploy
+pub fn lock_deploys(name: Option<&str>, reason: Option<&str>) -> Result<(), FunctionError> {
+    let client = authorized_client()?;
+    let response = client
+        .put(host_manager::deploy_lock_url(name))
+        .json(&serde_json::json!({ "reason": reason }))
+        .send()?;
+    check_response(response)?;
+
+    match name {
+        Some(name) => println!("🔒 Deploys of '{}' are locked", name),
+        None => println!("🔒 Deploys of every function of the namespace are locked"),
+    }
+    Ok(())
+}
+
+/// Lift the deploy lock of a function, or the namespace's own lock
+///
+/// # Arguments
+///
+/// * `name` - The function to unlock, or `None` for the namespace
+pub fn unlock_deploys(name: Option<&str>) -> Result<(), FunctionError> {
+    let client = authorized_client()?;
+    let response = client.delete(host_manager::deploy_lock_url(name)).send()?;
+    check_response(response)?;
+
+    match name {
+        Some(name) => println!("🔓 Deploys of '{}' are unlocked", name),
+        None => println!("🔓 The namespace lock is lifted; locks of single functions stay"),
+    }
+    Ok(())
+}
+
+/// List the deploy locks of the namespace
+pub fn list_deploy_locks() -> Result<(), FunctionError> {
+    let client = authorized_client()?;
+    let response = check_response(client.get(host_manager::deploy_locks_url()).send()?)?;
+
+    let locks: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if locks.is_empty() {
+        println!("No deploy locks. Add one with 'invok lock [NAME] --reason ...'.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:<28} {:<26} REASON",
+        "FUNCTION", "LOCKED BY", "SINCE"
+    );
+    for lock in locks {
+        println!(
+            "{:<24} {:<28} {:<26} {}",
+            lock["function"].as_str().unwrap_or("(namespace)"),
+            lock["locked_by"].as_str().unwrap_or("N/A"),
+            lock["created_at"].as_str().unwrap_or("N/A"),
+            lock["reason"].as_str().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+/// Show, or replace, the accounts that approve the namespace's deploys
+///
+/// # Arguments
+///
+/// * `approvers` - The approvers' emails to set; `None` only shows them, an empty list
+///   deploys without approval again
+pub fn deploy_approvers(approvers: Option<Vec<String>>) -> Result<(), FunctionError> {
+    let client = authorized_client()?;
+    let request = match &approvers {
+        Some(approvers) => client
+            .put(host_manager::deploy_approvers_url())
+            .json(&serde_json::json!({ "approvers": approvers })),
+        None => client.get(host_manager::deploy_approvers_url()),
+    };
+    let response = check_response(request.send()?)?;
+
+    let stored: Value = serde_json::from_str(&response.text()?)?;
+    let stored: Vec<&str> = stored["approvers"]
+        .as_array()
+        .map(|approvers| approvers.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    if stored.is_empty() {
+        println!("Deploys need no approval. Require it with 'invok approvers --set EMAIL,...'.");
+    } else {
+        println!("👥 Deploys wait for approval by one of:");
+        for approver in stored {
+            println!("   {}", approver);
+        }
+    }
+    Ok(())
+}
+
+/// List the deploys waiting for approval: the namespace's own, and those the user may
+/// approve in other namespaces
+pub fn list_approvals() -> Result<(), FunctionError> {
+    let client = authorized_client()?;
+    let response = check_response(client.get(host_manager::approvals_url()).send()?)?;
+
+    let approvals: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if approvals.is_empty() {
+        println!("No deploys are waiting for approval.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<6} {:<24} {:<38} {:<28} SINCE",
+        "ID", "FUNCTION", "NAMESPACE", "REQUESTED BY"
+    );
+    for approval in approvals {
+        println!(
+            "{:<6} {:<24} {:<38} {:<28} {}",
+            approval["id"].as_i64().unwrap_or_default(),
+            approval["function"].as_str().unwrap_or("N/A"),
+            approval["namespace"].as_str().unwrap_or("N/A"),
+            approval["requested_by"].as_str().unwrap_or("N/A"),
+            approval["created_at"].as_str().unwrap_or("N/A")
+        );
+    }
+    Ok(())
+}
+
+/// Approve a deploy waiting in another namespace, which deploys it, or reject it
+///
+/// # Arguments
+///
+/// * `id` - The pending deploy, as listed by `invok approvals`
+/// * `approve` - Approve the deploy; reject it otherwise
+pub fn decide_deploy(id: i32, approve: bool) -> Result<(), FunctionError> {
+    let decision = if approve { "approve" } else { "reject" };
+    if approve {
+        println!("🚀 Approving deploy {}, the function is built now...", id);
+    }
+    let client = authorized_client()?;
+    let response = client
+        .post(host_manager::approval_decision_url(id, decision))
+        .send()?;
+    let response = check_response(response)?;
+
+    if approve {
+        let message = response.text()?;
+        let name = message
+            .lines()
+            .find_map(|line| line.strip_prefix("Function: "))
+            .unwrap_or("N/A");
+        println!("✅ Deploy {} approved, '{}' is deployed", id, name);
+    } else {
+        println!("🚫 Deploy {} rejected", id);
+    }
+    Ok(())
+}
+
+/// List the names of the namespace's build args; their values are never shown
+pub fn list_build_args() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::build_args_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let build_args: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if build_args.is_empty() {
+        println!("No build args. Set one with 'invok buildarg set NAME'.");
+        return Ok(());
+    }
+
+    println!("{:<40} {:<30}", "NAME", "UPDATED");
+    for build_arg in build_args {
+        println!(
+            "{:<40} {:<30}",
+            build_arg["name"].as_str().unwrap_or("N/A"),
+            build_arg["updated_at"].as_str().unwrap_or("N/A")
+        );
+    }
+
+    Ok(())
+}
+
+/// Set a build arg of the namespace, passed to the build stage of its function images
+///
+/// # Arguments
+///
+/// * `name` - Name of the build arg, as the Dockerfile's `ARG` declares it
+/// * `value` - The value; read from stdin when `None`, keeping it out of the shell history
+pub fn set_build_arg(name: &str, value: Option<&str>) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    let value = match value {
+        Some(value) => value.to_string(),
+        None => {
+            let mut value = String::new();
+            io::stdin().read_to_string(&mut value)?;
+            value.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .put(host_manager::build_arg_url(name))
+        .json(&serde_json::json!({ "value": value }))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!(
+        "🔒 Build arg '{}' set; functions are built with it from their next deploy",
+        name
+    );
+    Ok(())
+}
+
+/// Remove a build arg of the namespace
+pub fn unset_build_arg(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.delete(host_manager::build_arg_url(name)).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("🗑️  Build arg '{}' removed", name);
+    Ok(())
+}
+
+/// List the namespace's gateway credentials; their values are never shown
+pub fn list_credentials() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::credentials_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let credentials: Vec<Value> = serde_json::from_str(&response.text()?)?;
+    if credentials.is_empty() {
+        println!("No credentials. Set one with 'invok credential set NAME --url URL'.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:<40} {:<20} {:<30}",
+        "NAME", "BASE URL", "HEADER", "UPDATED"
+    );
+    for credential in credentials {
+        println!(
+            "{:<24} {:<40} {:<20} {:<30}",
+            credential["name"].as_str().unwrap_or("N/A"),
+            credential["base_url"].as_str().unwrap_or("N/A"),
+            credential["header"].as_str().unwrap_or("N/A"),
+            credential["updated_at"].as_str().unwrap_or("N/A")
+        );
+    }
+
+    Ok(())
+}
+
+/// Set a credential of the namespace, which its functions send requests with through the
+/// gateway without ever seeing it
+///
+/// # Arguments
+///
+/// * `name` - Name functions refer to the credential by in gateway URLs
+/// * `base_url` - URL the gateway sends requests signed with the credential under
+/// * `header` - Header the value is sent in; `Authorization` when `None`
+/// * `value` - The complete header value; read from stdin when `None`, keeping it out of
+///   the shell history
+pub fn set_credential(
+    name: &str,
+    base_url: &str,
+    header: Option<&str>,
+    value: Option<&str>,
+) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    let value = match value {
+        Some(value) => value.to_string(),
+        None => {
+            let mut value = String::new();
+            io::stdin().read_to_string(&mut value)?;
+            value.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .put(host_manager::credential_url(name))
+        .json(&serde_json::json!({
+            "base_url": base_url,
+            "header": header,
+            "value": value,
+        }))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!(
+        "🔒 Credential '{}' set; functions reach {} through $INVOK_API_URL/internal/fetch/{}/",
+        name, base_url, name
+    );
+    Ok(())
+}
+
+/// Remove a gateway credential of the namespace
+pub fn unset_credential(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.delete(host_manager::credential_url(name)).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("🗑️  Credential '{}' removed", name);
+    Ok(())
+}
+
+/// List the namespace's feature flags and their rollouts
+pub fn list_flags() -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.get(host_manager::flags_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let flags: Value = serde_json::from_str(&response.text()?)?;
+    let flags = flags["flags"].as_object().cloned().unwrap_or_default();
+    if flags.is_empty() {
+        println!("No feature flags. Set one with 'invok flags set KEY VALUE'.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<30} {:<20} {:<10} {:<20}",
+        "KEY", "VALUE", "ROLLOUT", "DEFAULT"
+    );
+    for (key, flag) in flags {
+        println!(
+            "{:<30} {:<20} {:<10} {:<20}",
+            key,
+            flag["value"].as_str().unwrap_or("N/A"),
+            format!("{}%", flag["rollout"].as_u64().unwrap_or(100)),
+            flag["default"].as_str().unwrap_or("-")
+        );
+    }
+
+    Ok(())
+}
+
+/// Set a feature flag of the namespace, sent to its functions as the
+/// `x-invok-flag-<key>` header
+///
+/// # Arguments
+///
+/// * `key` - The flag's key
+/// * `value` - The value requests inside the rollout get
+/// * `rollout` - Percent of requests inside the rollout
+/// * `default` - The value the other requests get; they get no header when `None`
+pub fn set_flag(
+    key: &str,
+    value: &str,
+    rollout: u8,
+    default: Option<&str>,
+) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .put(host_manager::flag_url(key))
+        .json(&serde_json::json!({ "value": value, "rollout": rollout, "default": default }))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!(
+        "🚩 Flag '{}' set to '{}' for {}% of requests",
+        key, value, rollout
+    );
+    Ok(())
+}
+
+/// Remove a feature flag of the namespace
+pub fn delete_flag(key: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client.delete(host_manager::flag_url(key)).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    println!("🗑️  Flag '{}' removed", key);
+    Ok(())
+}
+
+/// Show the namespace defaults, or replace them with the contents of a JSON file.
+///
+/// Functions pick up changed defaults on their next deploy.
+pub fn namespace_defaults(set_from: Option<&str>) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let request = match set_from {
+        Some(path) => {
+            let defaults: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            client
+                .put(host_manager::namespace_defaults_url())
+                .json(&defaults)
+        }
+        None => client.get(host_manager::namespace_defaults_url()),
+    };
+    let response = request.send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let defaults: Value = serde_json::from_str(&response.text()?)?;
+    if set_from.is_some() {
+        println!("Namespace defaults updated; they apply to functions on their next deploy.");
+    }
+    println!("{}", serde_json::to_string_pretty(&defaults)?);
+
+    Ok(())
+}
+
+/// Show where deploys and scaling anomalies are reported, replace the targets with the
+/// contents of a JSON file, or clear them.
+pub fn notifications(set_from: Option<&str>, clear: bool) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let request = match set_from {
+        Some(path) => {
+            let notifications: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            client
+                .put(host_manager::notifications_url())
+                .json(&notifications)
+        }
+        None if clear => client
+            .put(host_manager::notifications_url())
+            .json(&serde_json::json!({ "targets": [] })),
+        None => client.get(host_manager::notifications_url()),
+    };
+    let response = request.send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let notifications: Value = serde_json::from_str(&response.text()?)?;
+    if clear {
+        println!("Notifications turned off.");
+        return Ok(());
+    }
+    if set_from.is_some() {
+        println!("Notification targets updated.");
+    }
+    println!("{}", serde_json::to_string_pretty(&notifications)?);
+
+    Ok(())
+}
+
+/// Show a function's egress allowlist, or replace it with `allow`.
+///
+/// The allowlist only matters when the server routes function traffic through egress
+/// proxies; an empty one blocks every outbound connection.
+pub fn egress_allowlist(
+    function_name: &str,
+    allow: Option<Vec<String>>,
+) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let url = host_manager::function_egress_url(function_name);
+    let updating = allow.is_some();
+    let request = match allow {
+        Some(allow) => client.put(url).json(&serde_json::json!({ "allow": allow })),
+        None => client.get(url),
+    };
+    let response = request.send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let allowlist: Value = serde_json::from_str(&response.text()?)?;
+    if updating {
+        println!("Egress allowlist of '{}' updated.", function_name);
+    }
+    let hosts = allowlist["allow"].as_array().cloned().unwrap_or_default();
+    if hosts.is_empty() {
+        println!("'{}' may not reach any outside destination.", function_name);
+    }
+    for host in hosts {
+        println!("{}", host.as_str().unwrap_or_default());
+    }
+
+    Ok(())
+}
+
+/// Show the rules routing a function's invocations to its versions, or replace them with
+/// the contents of a JSON file (`clear` removes them all).
+///
+/// Versions the new rules name are deployed before the update returns, so it can take a
+/// while.
+pub fn routing_rules(
+    function_name: &str,
+    set_from: Option<&str>,
+    clear: bool,
+) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let url = host_manager::function_routing_url(function_name);
+    let update: Option<Value> = match set_from {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None if clear => Some(serde_json::json!({ "rules": [] })),
+        None => None,
+    };
+    let updating = update.is_some();
+    let request = match update {
+        Some(rules) => client.put(url).json(&rules),
+        None => client.get(url),
+    };
+    let response = request.send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let rules: Value = serde_json::from_str(&response.text()?)?;
+    if updating {
+        println!("Routing rules of '{}' updated.", function_name);
+    }
+    if rules["rules"].as_array().is_none_or(Vec::is_empty) {
+        println!(
+            "Every invocation of '{}' is served by its current version.",
+            function_name
+        );
+    } else {
+        println!("{}", serde_json::to_string_pretty(&rules)?);
+    }
+
+    Ok(())
+}
+
+/// Downloads every function of the namespace, with its version history and the
+/// namespace defaults, into a tarball.
+///
+/// # Arguments
+///
+/// * `output` - Path the archive is written to
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn export_namespace(output: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    println!("📦 Exporting namespace {}...", session.user_uuid);
+    let response = client.get(host_manager::namespace_export_url()).send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let archive = response.bytes()?;
+    std::fs::write(output, &archive)?;
+    println!("✅ Exported to {} ({} bytes)", output, archive.len());
+    println!(
+        "🔁 Restore it on any invok installation with: invok import {}",
+        output
+    );
+
+    Ok(())
+}
+
+/// Restores an archive produced by `invok export` into the namespace of the current
+/// session, redeploying every function in it.
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive to import
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error. Functions that failed to import
+/// are reported as an error after the summary is printed.
+pub fn import_namespace(path: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    let archive = std::fs::read(path)?;
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "export.tar.gz".to_string());
+    let form = multipart::Form::new().part(
+        "archive",
+        multipart::Part::bytes(archive)
+            .file_name(file_name)
+            .mime_str("application/gzip")?,
+    );
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(IMPORT_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    println!(
+        "🚀 Importing {} into namespace {}...",
+        path, session.user_uuid
+    );
+    let response = client
+        .post(host_manager::namespace_import_url())
+        .multipart(form)
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let report: Value = serde_json::from_str(&response.text()?)?;
+    let list = |key: &str| report[key].as_array().cloned().unwrap_or_default();
+
+    for function in list("imported") {
+        println!(
+            "✅ {} ({} versions)",
+            function["name"].as_str().unwrap_or("N/A"),
+            function["versions"].as_u64().unwrap_or_default()
+        );
+    }
+    for function in list("skipped") {
+        println!(
+            "⏭️  {}: {}",
+            function["name"].as_str().unwrap_or("N/A"),
+            function["reason"].as_str().unwrap_or_default()
+        );
+    }
+    let failed = list("failed");
+    for function in &failed {
+        println!(
+            "❌ {}: {}",
+            function["name"].as_str().unwrap_or("N/A"),
+            function["reason"].as_str().unwrap_or_default()
+        );
+    }
+
+    if !failed.is_empty() {
+        return Err(FunctionError::CompressionError(format!(
+            "{} functions failed to import",
+            failed.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Deploys an existing function to the serverless platform using authentication.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to deploy
+/// * `preview` - Deploy a temporary preview instance for this branch instead
+/// * `force` - Deploy even when the function's SLO freezes deploys
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn deploy_function(
+    name: &str,
+    preview: Option<&str>,
+    force: bool,
+) -> Result<(), FunctionError> {
+    let config = load_function_config(name)?;
+    println!("🚀 Deploying service... '{}'", name);
+
+    // Create ZIP archive with runtime-specific exclusions
+    let archive = archive_function(name, &config)?;
+    if config.shared.is_empty() {
+        println!("📦 Zipped up the folder service... '{}'", name);
+    } else {
+        println!(
+            "📦 Zipped up the folder service... '{}' with {}",
+            name,
+            config.shared.join(", ")
+        );
+    }
+
+    deploy_with_auth(name, archive, preview, force)?;
+
+    Ok(())
+}
+
+/// Deploys an image built by the function's own CI, instead of building its sources.
+///
+/// The environment and settings come from the function's `config.json` when there is
+/// a directory `name` with one; the options building its sources are left out. Only
+/// that config, naming the image, is uploaded.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to deploy
+/// * `image` - The image to deploy, e.g. `registry.example.com/orders:1.4`
+/// * `preview` - Deploy a temporary preview instance for this branch instead
+/// * `force` - Deploy even when the function's SLO freezes deploys
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn deploy_image(
+    name: &str,
+    image: &str,
+    preview: Option<&str>,
+    force: bool,
+) -> Result<(), FunctionError> {
+    let path = format!("{name}/{CONFIG_FILE_PATH}");
+    let mut config = match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => serde_json::json!({}),
+        Err(e) => return Err(e.into()),
+    };
+    let Value::Object(fields) = &mut config else {
+        return Err(FunctionError::CompressionError(format!(
+            "{} is not a JSON object",
+            path
+        )));
+    };
+    // The image takes the place of the sources, so the options building them don't apply
+    for key in ["shared", "registries", "test"] {
+        fields.remove(key);
+    }
+    fields.insert("function_name".to_string(), Value::from(name));
+    fields.insert("runtime".to_string(), Value::from("image"));
+    fields.entry("env").or_insert_with(|| serde_json::json!({}));
+    // Registry credentials configured for the image are kept
+    let mut image_config = match fields.remove("image") {
+        Some(Value::Object(image_config)) => image_config,
+        _ => serde_json::Map::new(),
+    };
+    image_config.insert("reference".to_string(), Value::from(image));
+    fields.insert("image".to_string(), Value::Object(image_config));
+
+    let config = serde_json::to_vec_pretty(&config)?;
+    let archive = zip_files(&[(CONFIG_FILE_PATH, config.as_slice())])?;
+    println!("🚀 Deploying image '{}' as '{}'", image, name);
+
+    let message = deploy_with_auth(name, archive, preview, force)?;
+    for line in message.lines().filter(|line| line.starts_with("Image ")) {
+        println!("🐳 {}", line);
+    }
+    Ok(())
+}
+
+/// Show what deploying a function would change, without deploying it
+///
+/// Only the digests of the function's files and of its environment variables' values are
+/// uploaded, along with the rest of its config; the controller compares them with the
+/// deployed version.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to compare
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn dry_run_deploy(name: &str) -> Result<(), FunctionError> {
+    let config = load_function_config(name)?;
+    let archive = archive_function(name, &config)?;
+    let files = zip_digests(&archive)?;
+
+    // The values of the environment never leave the machine
+    let mut function_config: Value = serde_json::from_str(&std::fs::read_to_string(format!(
+        "{name}/{CONFIG_FILE_PATH}"
+    ))?)?;
+    if let Some(Value::Object(env)) = function_config.get_mut("env") {
+        for value in env.values_mut() {
+            let plain = match &*value {
+                Value::String(plain) => plain.clone(),
+                other => other.to_string(),
+            };
+            *value = Value::String(sha256_hex(plain.as_bytes()));
+        }
+    }
+
+    // Load authentication session
+    let session = load_session()?;
+
+    // Set up authorization headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    let response = client
+        .post(host_manager::function_diff_url(name))
+        .json(&serde_json::json!({
+            "files": files,
+            "config": function_config,
+        }))
+        .send()?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let diff: Value = serde_json::from_str(&response.text()?)?;
+    match diff["deployed_version"].as_i64() {
+        Some(version) => println!("🔍 Deploying '{}' would change v{}:", name, version),
+        None => println!("🔍 '{}' isn't deployed yet; deploying it would add:", name),
+    }
+
+    let mut changed = false;
+    for (title, changes) in [("Files", &diff["files"]), ("Env", &diff["env"])] {
+        let listed: Vec<(&str, &str)> = [("added", "+"), ("modified", "~"), ("removed", "-")]
+            .into_iter()
+            .flat_map(|(kind, sign)| {
+                changes[kind]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |item| Some((sign, item.as_str()?)))
+            })
+            .collect();
+        if listed.is_empty() {
+            continue;
+        }
+        changed = true;
+        println!("\n{}:", title);
+        for (sign, item) in listed {
+            println!("  {} {}", sign, item);
+        }
+    }
+    for (title, changes) in [
+        ("Config", &diff["config"]),
+        ("Resources", &diff["resources"]),
+    ] {
+        let changes = changes.as_array().cloned().unwrap_or_default();
+        if changes.is_empty() {
+            continue;
+        }
+        changed = true;
+        println!("\n{}:", title);
+        for change in changes {
+            println!(
+                "  ~ {}: {} → {}",
+                change["key"].as_str().unwrap_or_default(),
+                change["before"],
+                change["after"]
+            );
+        }
+    }
+
+    if !changed {
+        println!("✅ No changes");
+    }
+    Ok(())
+}
+
+/// Reads the `config.json` of a function's directory
+pub(crate) fn load_function_config(name: &str) -> Result<FuncConfig, FunctionError> {
+    let mut config_file = File::open(format!("{name}/{CONFIG_FILE_PATH}"))?;
+    let mut contents = String::new();
+    config_file.read_to_string(&mut contents)?;
+
+    let config: FuncConfig = serde_json::from_str(&contents)?;
+
+    // Validate function exists in config
+    if !config.function_name.contains(&name.to_string()) {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    Ok(config)
+}
+
+/// Zips up a function's directory, with the shared packages its config lists
+pub(crate) fn archive_function(name: &str, config: &FuncConfig) -> Result<Vec<u8>, FunctionError> {
+    let mut shared = Vec::new();
+    for package in &config.shared {
+        let dir = Path::new(package);
+        let sibling = dir.components().count() == 1
+            && matches!(dir.components().next(), Some(Component::Normal(_)));
+        if !sibling || package == name {
+            return Err(FunctionError::CompressionError(format!(
+                "Shared package '{}' must be a directory next to '{}'",
+                package, name
+            )));
+        }
+        if !dir.is_dir() {
+            return Err(FunctionError::CompressionError(format!(
+                "Shared package '{}' not found",
+                package
+            )));
+        }
+        shared.push((package.clone(), dir.to_path_buf()));
+    }
+
+    let mut dest_zip = Cursor::new(Vec::new());
+    compress_function_with_shared(
+        Path::new(name),
+        &shared,
+        &mut dest_zip,
+        &archive_excludes(&config.runtime),
+        &archive_excludes(&config.runtime),
+    )
+    .map_err(|e| FunctionError::CompressionError(e.to_string()))?;
+    Ok(dest_zip.into_inner())
+}
+
+/// Files left out of a function's archive: generated by the build, or not needed by it.
+/// Manifests and lockfiles are kept: the build resolves the versions they pin, and the
+/// platform checks those against its advisories.
+pub(crate) fn archive_excludes(runtime: &str) -> Vec<&'static str> {
+    match runtime.to_lowercase().as_str() {
+        "go" => vec![".git", ".gitignore"],
+        "nodejs" | "node" | "typescript" | "ts" => {
+            vec!["node_modules", ".git", ".gitignore", "dist", "*.log"]
+        }
+        "rust" | "rs" => vec!["target", ".git", ".gitignore"],
+        _ => vec![],
+    }
+}
+
+/// A client sending the session's token, for the calls that aren't on `InvokClient`
+fn authorized_client() -> Result<Client, FunctionError> {
+    let session = load_session()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", session.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+    Ok(Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?)
+}
+
+fn check_response(response: Response) -> Result<Response, FunctionError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let error_text = response
+        .text()
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(FunctionError::Api(status, error_text))
+}
+
+/// Deploy a function using authentication
+fn deploy_with_auth(
+    name: &str,
+    archive: Vec<u8>,
+    preview: Option<&str>,
+    force: bool,
+) -> Result<String, FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+    let client = session
+        .client()
+        .with_timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS));
+
+    let mut deployment = client.submit_deploy(
+        name,
+        archive,
+        &DeployOptions {
+            preview: preview.map(str::to_string),
+            force,
+        },
+    )?;
+
+    // Namespaces with approvers hold the deploy until one of them approves it
+    if let Some(approval) = deployment.pending_approval {
+        println!(
+            "⏳ Deploy of '{}' is waiting for approval (approval {})",
+            deployment.name, approval
+        );
+        println!(
+            "👥 An approver of the namespace can approve it with 'invok approve {}'",
+            approval
+        );
+        return Ok(deployment.message);
+    }
+
+    // The controller builds it in the background; poll until it's done
+    if let Some(id) = deployment.deployment {
+        println!("🔨 Building '{}' (deployment {})...", deployment.name, id);
+        println!("💡 Stopping here doesn't stop the deploy, it carries on on the controller");
+        let finished = client.wait_for_deployment(id)?;
+        deployment.message = finished.message.unwrap_or_default();
+    }
+
+    // Print deployment success message with URL
+    if preview.is_some() {
+        println!("✅ Preview deployed successfully!");
+    } else {
+        println!("✅ Function deployed successfully!");
+    }
+    println!("📝 Function name: {}", deployment.name);
+    println!(
+        "🌐 Function URL: {}",
+        client.function_url(&deployment.name)?
+    );
+    println!("🔗 You can invoke your function by making requests to the URL above");
+    if preview.is_some() {
+        println!(
+            "⏳ The preview expires automatically; remove it sooner with `invok preview delete`"
+        );
+    }
+
+    Ok(deployment.message)
+}
+
+/// Promote the deployed version of a function from one profile's namespace to
+/// another's, e.g. from staging to production, without building it again
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to promote
+/// * `from` - The profile of the namespace to promote from
+/// * `to` - The profile of the namespace to promote to
+/// * `env` - Environment variables of the source function to carry over
+/// * `version` - The version expected to be deployed in the source namespace
+/// * `force` - Promote even when the target function's SLO freezes deploys
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn promote_function(
+    name: &str,
+    from: &str,
+    to: &str,
+    env: &[String],
+    version: Option<i32>,
+    force: bool,
+) -> Result<(), FunctionError> {
+    let source = load_profile_session(from)?;
+    let target = load_profile_session(to)?;
+
+    // Set up authorization headers; the source namespace authorizes reading its function
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", target.token))
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+    headers.insert(
+        "X-Invok-Source-Token",
+        HeaderValue::from_str(&source.token)
+            .map_err(|_| FunctionError::CompressionError("Invalid token format".to_string()))?,
+    );
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .default_headers(headers)
+        .build()?;
+
+    println!("🚚 Promoting '{}' from {} to {}...", name, from, to);
+    let response = client
+        .post(host_manager::function_promote_url(name))
+        .json(&serde_json::json!({
+            "version": version,
+            "env": env,
+            "force": force,
+        }))
+        .send()?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(FunctionError::FunctionNotFound(name.to_string()));
+    }
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(FunctionError::Api(status, error_text));
+    }
+
+    let promotion: Value = serde_json::from_str(&response.text()?)?;
+    println!(
+        "✅ v{} of '{}' promoted as v{}",
+        promotion["promoted_from"]["version"], name, promotion["version"]
+    );
+    if !env.is_empty() {
+        println!("🔑 Carried over: {}", env.join(", "));
+    }
+    println!(
+        "🌐 Function URL: {}",
+        generate_function_url(name, &target.user_uuid)
+    );
+    Ok(())
+}
+
+/// Generate the function URL for a deployed function
+fn generate_function_url(function_name: &str, user_uuid: &str) -> String {
+    format!(
+        "{}/invok/{}/{}",
+        host_manager::base_url(),
+        user_uuid,
+        function_name
+    )
+}
+
+/// Stream logs from a deployed function
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to stream logs from
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn stream_logs(name: &str) -> Result<(), FunctionError> {
+    // Load authentication session
+    let session = load_session()?;
+
+    println!("🔍 Connecting to function logs...");
+    let logs = session.client().logs(name)?;
+    println!("📡 Connected! Streaming logs... (Press Ctrl+C to stop)\n");
+
+    for line in logs {
+        let line = line?;
+        if !line.trim().is_empty() {
+            println!("{}", line);
         }
 
         // Flush stdout to ensure real-time output
-        io::stdout()
-            .flush()
-            .map_err(|e| FunctionError::IoError(e))?;
+        io::stdout().flush()?;
     }
 
     println!("\n📴 Log stream ended");
@@ -0,0 +1,543 @@
+/*!
+Starter templates beyond the built-in ones, fetched from git.
+
+A registry is a JSON index of templates, each a directory of a git repository; templates
+can also be given as a git URL or a local directory. Repositories are cloned once into the
+local cache and reused after that.
+*/
+
+use crate::serverless_function::{normalize_runtime, FunctionError};
+use crate::utils::{create_fn_project_dir, function_file};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use shared_utils::to_camel_case_handler;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// The registry used unless `INVOK_TEMPLATE_REGISTRY` names another
+const DEFAULT_REGISTRY: &str =
+    "https://raw.githubusercontent.com/alob-mtc/invok/main/templates/registry.json";
+
+/// Environment variable holding the URL or path of the registry to use
+const REGISTRY_ENV: &str = "INVOK_TEMPLATE_REGISTRY";
+
+/// File describing a template, at its root; never copied into functions
+const MANIFEST_FILE: &str = "invok-template.json";
+
+const REGISTRY_TIMEOUT_SECS: u64 = 10;
+
+/// The index of a registry
+#[derive(Debug, Deserialize)]
+struct Registry {
+    templates: Vec<RegistryEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryEntry {
+    name: String,
+    #[serde(default)]
+    description: String,
+    runtime: Option<String>,
+    /// Git URL of the repository holding the template
+    repository: String,
+    /// Branch or tag to clone; the repository's default branch if unset
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+    /// Directory of the template in the repository; its root if unset
+    path: Option<String>,
+}
+
+/// What `invok-template.json` declares
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    runtime: Option<String>,
+    #[serde(default)]
+    variables: BTreeMap<String, Variable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Variable {
+    #[serde(default)]
+    description: String,
+    /// Value used when none is given; the variable is required without one
+    default: Option<String>,
+}
+
+/// Where a template's files are
+struct TemplateSource {
+    repository: String,
+    git_ref: Option<String>,
+    path: Option<String>,
+    runtime: Option<String>,
+}
+
+/// Creates a function from a template.
+///
+/// `{{NAME}}`, `{{ROUTE}}` and `{{HANDLER}}` in the template's files and file names are
+/// replaced as in the built-in templates, and so is every variable the template declares
+/// or `vars` sets.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function to create
+/// * `template` - A template of the registry, a git URL (with an optional `#ref`), or a
+///   local directory starting with `./`, `../` or `/`
+/// * `runtime` - The runtime to use if the template doesn't set one
+/// * `vars` - `KEY=VALUE` template variables
+/// * `refresh` - Fetch the template again instead of using the cached copy
+///
+/// # Returns
+///
+/// A Result indicating success or containing an error
+pub fn create_from_template(
+    name: &str,
+    template: &str,
+    runtime: &str,
+    vars: &[String],
+    refresh: bool,
+) -> Result<(), FunctionError> {
+    let mut values = parse_vars(vars)?;
+
+    let (template_dir, source_runtime) = if is_local_path(template) {
+        (PathBuf::from(template), None)
+    } else {
+        let source = resolve_source(template)?;
+        let checkout = fetch(&source, refresh)?;
+        let template_dir = match &source.path {
+            Some(path) => checkout.join(relative_path(path)?),
+            None => checkout,
+        };
+        (template_dir, source.runtime)
+    };
+    if !template_dir.is_dir() {
+        return Err(template_error(format!(
+            "Template directory '{}' doesn't exist",
+            template_dir.display()
+        )));
+    }
+
+    let manifest = read_manifest(&template_dir)?;
+    let runtime = normalize_runtime(
+        manifest
+            .runtime
+            .as_deref()
+            .or(source_runtime.as_deref())
+            .unwrap_or(runtime),
+    )?;
+    let handler_file = function_file(runtime);
+    if !template_dir.join(handler_file).is_file() {
+        return Err(template_error(format!(
+            "The template has no {} for the {} runtime",
+            handler_file, runtime
+        )));
+    }
+
+    let mut missing = Vec::new();
+    for (key, variable) in &manifest.variables {
+        if values.contains_key(key) {
+            continue;
+        }
+        match &variable.default {
+            Some(default) => {
+                values.insert(key.clone(), default.clone());
+            }
+            None if variable.description.is_empty() => missing.push(key.clone()),
+            None => missing.push(format!("{} ({})", key, variable.description)),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(template_error(format!(
+            "Set the template's variables with --var KEY=VALUE: {}",
+            missing.join(", ")
+        )));
+    }
+    values.insert("NAME".to_string(), name.to_string());
+    values.insert("ROUTE".to_string(), name.to_string());
+    values.insert("HANDLER".to_string(), to_camel_case_handler(name));
+
+    let env = read_template_env(&template_dir, &values)?;
+
+    println!("Creating service... '{name}' [RUNTIME:'{runtime}'] from template '{template}'");
+    let project_dir = create_fn_project_dir(name, runtime, env)?;
+    copy_template(&template_dir, &project_dir, &values, true)?;
+    println!("Function created");
+
+    Ok(())
+}
+
+/// Lists the templates of the registry
+pub fn list_templates() -> Result<(), FunctionError> {
+    let registry = load_registry()?;
+    if registry.templates.is_empty() {
+        println!("No templates found.");
+        return Ok(());
+    }
+
+    println!("+--------------------------+---------+------------------------------------------+");
+    println!("| Name                     | Runtime | Description                              |");
+    println!("+--------------------------+---------+------------------------------------------+");
+    for template in &registry.templates {
+        println!(
+            "| {:<24} | {:<7} | {:<40} |",
+            template.name,
+            template.runtime.as_deref().unwrap_or("-"),
+            template.description
+        );
+    }
+    println!("+--------------------------+---------+------------------------------------------+");
+
+    Ok(())
+}
+
+fn parse_vars(vars: &[String]) -> Result<BTreeMap<String, String>, FunctionError> {
+    vars.iter()
+        .map(|var| match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(template_error(format!(
+                "Invalid template variable '{}', expected KEY=VALUE",
+                var
+            ))),
+        })
+        .collect()
+}
+
+fn is_local_path(template: &str) -> bool {
+    template.starts_with("./") || template.starts_with("../") || template.starts_with('/')
+}
+
+fn is_git_url(template: &str) -> bool {
+    template.contains("://") || template.starts_with("git@") || template.ends_with(".git")
+}
+
+/// Finds where a template given by name or git URL lives
+fn resolve_source(template: &str) -> Result<TemplateSource, FunctionError> {
+    if is_git_url(template) {
+        let (repository, git_ref) = match template.rsplit_once('#') {
+            Some((repository, git_ref)) => (repository, Some(git_ref.to_string())),
+            None => (template, None),
+        };
+        return Ok(TemplateSource {
+            repository: repository.to_string(),
+            git_ref,
+            path: None,
+            runtime: None,
+        });
+    }
+
+    let registry = load_registry()?;
+    let entry = registry
+        .templates
+        .into_iter()
+        .find(|entry| entry.name == template)
+        .ok_or_else(|| {
+            template_error(format!(
+                "No template named '{}'; run `invok templates` to list them",
+                template
+            ))
+        })?;
+    Ok(TemplateSource {
+        repository: entry.repository,
+        git_ref: entry.git_ref,
+        path: entry.path,
+        runtime: entry.runtime,
+    })
+}
+
+/// Loads the registry's index, falling back to the cached copy when it can't be fetched
+fn load_registry() -> Result<Registry, FunctionError> {
+    let location = std::env::var(REGISTRY_ENV).unwrap_or_else(|_| DEFAULT_REGISTRY.to_string());
+    if !location.starts_with("http://") && !location.starts_with("https://") {
+        let contents = fs::read_to_string(&location)?;
+        return Ok(serde_json::from_str(&contents)?);
+    }
+
+    let cached = cache_dir().join(format!("registry-{}.json", cache_key(&location)));
+    match fetch_registry(&location) {
+        Ok(contents) => {
+            let registry = serde_json::from_str(&contents)?;
+            if let Some(parent) = cached.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&cached, contents)?;
+            Ok(registry)
+        }
+        Err(e) if cached.exists() => {
+            eprintln!(
+                "⚠️  Couldn't fetch the template registry ({}), using the cached copy",
+                e
+            );
+            Ok(serde_json::from_str(&fs::read_to_string(&cached)?)?)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn fetch_registry(url: &str) -> Result<String, FunctionError> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REGISTRY_TIMEOUT_SECS))
+        .build()?;
+    let response = client.get(url).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(template_error(format!(
+            "Registry error: Status code {}",
+            status
+        )));
+    }
+    Ok(response.text()?)
+}
+
+/// Clones a template's repository into the cache, unless it's there already
+fn fetch(source: &TemplateSource, refresh: bool) -> Result<PathBuf, FunctionError> {
+    let key = match &source.git_ref {
+        Some(git_ref) => cache_key(&format!("{}@{}", source.repository, git_ref)),
+        None => cache_key(&source.repository),
+    };
+    let checkout = cache_dir().join(key);
+    if checkout.is_dir() && !refresh {
+        return Ok(checkout);
+    }
+
+    println!("Fetching template from {}...", source.repository);
+    let staging = checkout.with_extension("tmp");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    if let Some(parent) = staging.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut clone = Command::new("git");
+    clone.args(["clone", "--quiet", "--depth", "1"]);
+    if let Some(git_ref) = &source.git_ref {
+        clone.args(["--branch", git_ref]);
+    }
+    let status = clone
+        .arg("--")
+        .arg(&source.repository)
+        .arg(&staging)
+        .status()
+        .map_err(|e| template_error(format!("Fetching templates requires git: {}", e)))?;
+    if !status.success() {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(template_error(format!(
+            "Failed to clone {}",
+            source.repository
+        )));
+    }
+
+    if checkout.exists() {
+        fs::remove_dir_all(&checkout)?;
+    }
+    fs::rename(&staging, &checkout)?;
+    Ok(checkout)
+}
+
+fn read_manifest(template_dir: &Path) -> Result<Manifest, FunctionError> {
+    let path = template_dir.join(MANIFEST_FILE);
+    if !path.is_file() {
+        return Ok(Manifest::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// The `env` of the template's `config.json`, if it has one
+fn read_template_env(
+    template_dir: &Path,
+    values: &BTreeMap<String, String>,
+) -> Result<Value, FunctionError> {
+    let path = template_dir.join("config.json");
+    if !path.is_file() {
+        return Ok(Value::Object(Map::new()));
+    }
+    let config: Value = serde_json::from_str(&substitute(&fs::read_to_string(path)?, values))?;
+    Ok(match config.get("env") {
+        Some(env @ Value::Object(_)) => env.clone(),
+        _ => Value::Object(Map::new()),
+    })
+}
+
+/// Copies a template's files, substituting its variables in text files and file names.
+/// Symlinks are skipped, so a template can't copy files from outside itself, and a file
+/// name a variable turns into a path is refused, so it can't write outside the function.
+fn copy_template(
+    from: &Path,
+    to: &Path,
+    values: &BTreeMap<String, String>,
+    root: bool,
+) -> Result<(), FunctionError> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let skipped = file_name == ".git"
+            || (root && (file_name == MANIFEST_FILE || file_name == "config.json"));
+        if skipped {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        let target = to.join(target_name(&file_name, values)?);
+        if file_type.is_dir() {
+            fs::create_dir_all(&target)?;
+            copy_template(&entry.path(), &target, values, false)?;
+        } else if file_type.is_file() {
+            let contents = fs::read(entry.path())?;
+            match String::from_utf8(contents) {
+                Ok(text) => fs::write(&target, substitute(&text, values))?,
+                Err(e) => fs::write(&target, e.into_bytes())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A file name with the template's variables substituted, refusing any that isn't a
+/// plain name, e.g. `{{NAME}}` set to `../../.bashrc`
+fn target_name(
+    file_name: &str,
+    values: &BTreeMap<String, String>,
+) -> Result<String, FunctionError> {
+    let name = substitute(file_name, values);
+    let mut components = Path::new(&name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(name),
+        _ => Err(template_error(format!(
+            "Invalid file name '{}' for template file '{}'",
+            name, file_name
+        ))),
+    }
+}
+
+fn substitute(text: &str, values: &BTreeMap<String, String>) -> String {
+    values.iter().fold(text.to_string(), |text, (key, value)| {
+        text.replace(&format!("{{{{{key}}}}}"), value)
+    })
+}
+
+/// A registry path, refusing any that leaves the repository
+fn relative_path(path: &str) -> Result<PathBuf, FunctionError> {
+    let path = Path::new(path);
+    if path.components().all(|c| matches!(c, Component::Normal(_))) {
+        Ok(path.to_path_buf())
+    } else {
+        Err(template_error(format!(
+            "Invalid template path '{}'",
+            path.display()
+        )))
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("invok")
+        .join("templates")
+}
+
+/// A directory name for a cached repository or registry
+fn cache_key(location: &str) -> String {
+    location
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn template_error(message: String) -> FunctionError {
+    FunctionError::CompressionError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_substitute() {
+        let values = values(&[("NAME", "hello"), ("GREETING", "Hi")]);
+
+        assert_eq!(
+            substitute("{{GREETING}} from {{NAME}}, {{NAME}}!", &values),
+            "Hi from hello, hello!"
+        );
+        assert_eq!(substitute("{{UNSET}} {NAME}", &values), "{{UNSET}} {NAME}");
+        assert_eq!(substitute("", &values), "");
+    }
+
+    #[test]
+    fn test_target_name() {
+        let name = |value: &str| target_name("{{NAME}}.go", &values(&[("NAME", value)]));
+
+        assert_eq!(name("hello").unwrap(), "hello.go");
+        assert!(name("../../.bashrc").is_err());
+        assert!(name("sub/dir").is_err());
+        assert!(name("sub\\dir").is_err());
+        assert!(name("/etc/passwd").is_err());
+        assert!(target_name("{{NAME}}", &values(&[("NAME", "..")])).is_err());
+        assert!(target_name("{{NAME}}", &values(&[("NAME", ".")])).is_err());
+        assert!(target_name("{{NAME}}", &values(&[("NAME", "")])).is_err());
+    }
+
+    #[test]
+    fn test_copy_template_refuses_traversal() {
+        let root = std::env::temp_dir().join(format!("invok-template-{}", std::process::id()));
+        let (from, to) = (root.join("template"), root.join("function"));
+        fs::create_dir_all(from.join("{{NAME}}")).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        fs::write(from.join("{{NAME}}").join("main.go"), "package {{NAME}}").unwrap();
+
+        let copied = copy_template(&from, &to, &values(&[("NAME", "hello")]), true);
+        let escaped = copy_template(&from, &to, &values(&[("NAME", "..")]), true);
+        let contents = fs::read_to_string(to.join("hello").join("main.go"));
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(copied.is_ok());
+        assert_eq!(contents.unwrap(), "package hello");
+        assert!(escaped.is_err());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("templates/go-http").unwrap(),
+            PathBuf::from("templates/go-http")
+        );
+        assert!(relative_path("../outside").is_err());
+        assert!(relative_path("templates/../../outside").is_err());
+        assert!(relative_path("/etc").is_err());
+        assert!(relative_path("./templates").is_err());
+    }
+
+    #[test]
+    fn test_resolve_source_git_url() {
+        let source = resolve_source("https://github.com/acme/templates.git#v2").unwrap();
+        assert_eq!(source.repository, "https://github.com/acme/templates.git");
+        assert_eq!(source.git_ref.as_deref(), Some("v2"));
+        assert!(source.path.is_none());
+
+        let source = resolve_source("git@github.com:acme/templates.git").unwrap();
+        assert_eq!(source.repository, "git@github.com:acme/templates.git");
+        assert!(source.git_ref.is_none());
+    }
+
+    #[test]
+    fn test_parse_vars() {
+        let vars = parse_vars(&["DB=postgres".to_string(), "URL=a=b".to_string()]).unwrap();
+        assert_eq!(vars["DB"], "postgres");
+        assert_eq!(vars["URL"], "a=b");
+
+        assert_eq!(parse_vars(&["EMPTY=".to_string()]).unwrap()["EMPTY"], "");
+        assert!(parse_vars(&["NOVALUE".to_string()]).is_err());
+        assert!(parse_vars(&["=value".to_string()]).is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use shared_utils::error::ErrorClass;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -16,9 +17,44 @@ pub struct FuncConfig {
     pub function_name: String,
     pub runtime: String,
     pub env: Value,
+    /// Directories next to the function's with code it shares with other functions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shared: Vec<String>,
+}
+
+/// Class of a failed request: worth retrying when the controller couldn't be reached in
+/// time or answered that it is unavailable
+pub fn request_error_class(e: &reqwest::Error) -> ErrorClass {
+    if e.is_timeout() || e.is_connect() {
+        return ErrorClass::Retryable;
+    }
+    e.status().map_or(ErrorClass::System, |status| {
+        ErrorClass::from_status(status.as_u16())
+    })
 }
 
 pub fn create_fn_project_file(name: &str, runtime: &str) -> io::Result<File> {
+    let path = create_fn_project_dir(name, runtime, Value::Object(Map::new()))?;
+
+    let routes_file_path = path.join(function_file(runtime));
+    let routes_file = File::create(&routes_file_path)?;
+
+    Ok(routes_file)
+}
+
+/// The file holding a function's handler, for its runtime
+pub fn function_file(runtime: &str) -> &'static str {
+    match runtime {
+        "go" => "function.go",
+        "nodejs" => "function.ts",
+        "rust" => "function.rs",
+        _ => "",
+    }
+}
+
+/// Registers a function in the project's config and creates its directory, with the
+/// function's own config holding `env`
+pub fn create_fn_project_dir(name: &str, runtime: &str, env: Value) -> io::Result<PathBuf> {
     create_global_config_file(name, runtime)?;
 
     let path = Path::new(name);
@@ -30,26 +66,17 @@ pub fn create_fn_project_file(name: &str, runtime: &str) -> io::Result<File> {
     }
 
     fs::create_dir(path)?;
-    create_fn_config(name, runtime)?;
-
-    let function_file = match runtime {
-        "go" => "function.go",
-        "nodejs" => "function.ts",
-        _ => "",
-    };
-
-    let routes_file_path = path.join(function_file);
-    let routes_file = File::create(&routes_file_path)?;
-
-    Ok(routes_file)
+    create_fn_config(name, runtime, env)?;
+    Ok(path.to_path_buf())
 }
 
-fn create_fn_config(name: &str, runtime: &str) -> io::Result<()> {
+fn create_fn_config(name: &str, runtime: &str, env: Value) -> io::Result<()> {
     let mut f = File::create(format!("{name}/config.json"))?;
     let config = FuncConfig {
         function_name: name.to_string(),
         runtime: runtime.to_string(),
-        env: Value::Object(Map::new()),
+        env,
+        shared: Vec::new(),
     };
     let serialized = serde_json::to_string(&config)?;
     f.write_all(serialized.as_bytes())
@@ -95,6 +122,17 @@ pub fn init_function_module(function_name: &str, runtime: &str) -> io::Result<()
             let mut ignore_file = File::create(format!("{}/.gitignore", function_name))?;
             ignore_file.write_all(templates::nodejs_template::GIT_IGNORE_TEMPLATE.as_bytes())
         }
+        "rust" => {
+            println!("Initializing Cargo.toml...");
+            let mut cargo_file = File::create(format!("{}/Cargo.toml", function_name))?;
+            cargo_file.write_all(
+                templates::rust_template::CARGO_TOML_TEMPLATE
+                    .replace("{{ROUTE}}", function_name)
+                    .as_bytes(),
+            )?;
+            let mut ignore_file = File::create(format!("{}/.gitignore", function_name))?;
+            ignore_file.write_all(templates::rust_template::GIT_IGNORE_TEMPLATE.as_bytes())
+        }
         _ => Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             format!("Unsupported runtime: {}", runtime),
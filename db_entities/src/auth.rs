@@ -11,12 +11,86 @@ pub struct Model {
     pub email: String,
     pub password: String,
     pub uuid: Uuid,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub defaults: Option<Json>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub recovery_codes: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub notifications: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub deploy_approvers: Option<Json>,
+    pub hibernated_at: Option<DateTimeWithTimeZone>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub feature_flags: Option<Json>,
+    pub totp_last_step: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_token::Entity")]
+    ApiToken,
+    #[sea_orm(has_many = "super::audit_log::Entity")]
+    AuditLog,
+    #[sea_orm(has_many = "super::build_arg::Entity")]
+    BuildArg,
+    #[sea_orm(has_many = "super::deploy_lock::Entity")]
+    DeployLock,
+    #[sea_orm(has_many = "super::deployment::Entity")]
+    Deployment,
+    #[sea_orm(has_many = "super::domain::Entity")]
+    Domain,
+    #[sea_orm(has_many = "super::egress_credential::Entity")]
+    EgressCredential,
     #[sea_orm(has_many = "super::function::Entity")]
     Function,
+    #[sea_orm(has_many = "super::oidc_trust::Entity")]
+    OidcTrust,
+    #[sea_orm(has_many = "super::pending_deploy::Entity")]
+    PendingDeploy,
+}
+
+impl Related<super::api_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiToken.def()
+    }
+}
+
+impl Related<super::audit_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditLog.def()
+    }
+}
+
+impl Related<super::build_arg::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BuildArg.def()
+    }
+}
+
+impl Related<super::deploy_lock::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeployLock.def()
+    }
+}
+
+impl Related<super::deployment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Deployment.def()
+    }
+}
+
+impl Related<super::domain::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Domain.def()
+    }
+}
+
+impl Related<super::egress_credential::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EgressCredential.def()
+    }
 }
 
 impl Related<super::function::Entity> for Entity {
@@ -25,4 +99,16 @@ impl Related<super::function::Entity> for Entity {
     }
 }
 
+impl Related<super::oidc_trust::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OidcTrust.def()
+    }
+}
+
+impl Related<super::pending_deploy::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PendingDeploy.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -11,6 +11,23 @@ pub struct Model {
     pub runtime: String,
     pub uuid: Uuid,
     pub auth_id: i32,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub settings: Option<Json>,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub readme: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub openapi: Option<String>,
+    pub preview_of: Option<String>,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub egress_allowlist: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub routing_rules: Option<Json>,
+    #[sea_orm(unique)]
+    pub service_port: Option<i32>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub dependencies: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -23,6 +40,16 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Auth,
+    #[sea_orm(has_many = "super::domain::Entity")]
+    Domain,
+    #[sea_orm(has_many = "super::event_source::Entity")]
+    EventSource,
+    #[sea_orm(has_many = "super::function_version::Entity")]
+    FunctionVersion,
+    #[sea_orm(has_many = "super::schedule::Entity")]
+    Schedule,
+    #[sea_orm(has_many = "super::usage::Entity")]
+    Usage,
 }
 
 impl Related<super::auth::Entity> for Entity {
@@ -31,4 +58,34 @@ impl Related<super::auth::Entity> for Entity {
     }
 }
 
+impl Related<super::domain::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Domain.def()
+    }
+}
+
+impl Related<super::event_source::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EventSource.def()
+    }
+}
+
+impl Related<super::function_version::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FunctionVersion.def()
+    }
+}
+
+impl Related<super::schedule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Schedule.def()
+    }
+}
+
+impl Related<super::usage::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Usage.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
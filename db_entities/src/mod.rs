@@ -2,5 +2,18 @@
 
 pub mod prelude;
 
+pub mod api_token;
+pub mod audit_log;
 pub mod auth;
+pub mod build_arg;
+pub mod deploy_lock;
+pub mod deployment;
+pub mod domain;
+pub mod egress_credential;
+pub mod event_source;
 pub mod function;
+pub mod function_version;
+pub mod oidc_trust;
+pub mod pending_deploy;
+pub mod schedule;
+pub mod usage;
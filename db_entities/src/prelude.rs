@@ -1,4 +1,17 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
+pub use super::api_token::Entity as ApiToken;
+pub use super::audit_log::Entity as AuditLog;
 pub use super::auth::Entity as Auth;
+pub use super::build_arg::Entity as BuildArg;
+pub use super::deploy_lock::Entity as DeployLock;
+pub use super::deployment::Entity as Deployment;
+pub use super::domain::Entity as Domain;
+pub use super::egress_credential::Entity as EgressCredential;
+pub use super::event_source::Entity as EventSource;
 pub use super::function::Entity as Function;
+pub use super::function_version::Entity as FunctionVersion;
+pub use super::oidc_trust::Entity as OidcTrust;
+pub use super::pending_deploy::Entity as PendingDeploy;
+pub use super::schedule::Entity as Schedule;
+pub use super::usage::Entity as Usage;
@@ -8,8 +8,67 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20250111_230947_create_auth_table::Migration),
             Box::new(m20250111_231042_create_function_table::Migration),
+            Box::new(m20250801_120000_add_function_settings::Migration),
+            Box::new(m20250815_120000_add_namespace_defaults::Migration),
+            Box::new(m20250901_120000_create_function_version_table::Migration),
+            Box::new(m20250910_120000_add_function_deleted_at::Migration),
+            Box::new(m20250915_120000_add_function_docs::Migration),
+            Box::new(m20250920_120000_add_function_preview::Migration),
+            Box::new(m20250925_120000_create_oidc_trust_table::Migration),
+            Box::new(m20251001_120000_create_build_arg_table::Migration),
+            Box::new(m20251010_120000_add_auth_totp::Migration),
+            Box::new(m20251015_120000_add_function_egress_allowlist::Migration),
+            Box::new(m20251016_120000_add_function_routing_rules::Migration),
+            Box::new(m20251017_120000_add_auth_notifications::Migration),
+            Box::new(m20251020_120000_create_schedule_table::Migration),
+            Box::new(m20251021_120000_create_event_source_table::Migration),
+            Box::new(m20251022_120000_create_api_token_table::Migration),
+            Box::new(m20251023_120000_create_domain_table::Migration),
+            Box::new(m20251024_120000_create_usage_table::Migration),
+            Box::new(m20251025_120000_create_audit_log_table::Migration),
+            Box::new(m20251026_120000_add_function_version_promoted_from::Migration),
+            Box::new(m20251027_120000_create_deploy_gate_tables::Migration),
+            Box::new(m20251028_120000_add_function_service_port::Migration),
+            Box::new(m20251029_120000_add_auth_hibernated_at::Migration),
+            Box::new(m20251030_120000_add_auth_feature_flags::Migration),
+            Box::new(m20251031_120000_create_egress_credential_table::Migration),
+            Box::new(m20251101_120000_add_function_dependencies::Migration),
+            Box::new(m20251102_120000_add_function_version_test_results::Migration),
+            Box::new(m20251103_120000_create_deployment_table::Migration),
+            Box::new(m20251104_120000_add_auth_totp_last_step::Migration),
+            Box::new(m20251105_120000_add_oidc_trust_repository_ids::Migration),
         ]
     }
 }
 mod m20250111_230947_create_auth_table;
 mod m20250111_231042_create_function_table;
+mod m20250801_120000_add_function_settings;
+mod m20250815_120000_add_namespace_defaults;
+mod m20250901_120000_create_function_version_table;
+mod m20250910_120000_add_function_deleted_at;
+mod m20250915_120000_add_function_docs;
+mod m20250920_120000_add_function_preview;
+mod m20250925_120000_create_oidc_trust_table;
+mod m20251001_120000_create_build_arg_table;
+mod m20251010_120000_add_auth_totp;
+mod m20251015_120000_add_function_egress_allowlist;
+mod m20251016_120000_add_function_routing_rules;
+mod m20251017_120000_add_auth_notifications;
+mod m20251020_120000_create_schedule_table;
+mod m20251021_120000_create_event_source_table;
+mod m20251022_120000_create_api_token_table;
+mod m20251023_120000_create_domain_table;
+mod m20251024_120000_create_usage_table;
+mod m20251025_120000_create_audit_log_table;
+mod m20251026_120000_add_function_version_promoted_from;
+mod m20251027_120000_create_deploy_gate_tables;
+mod m20251028_120000_add_function_service_port;
+mod m20251029_120000_add_auth_hibernated_at;
+mod m20251030_120000_add_auth_feature_flags;
+mod m20251031_120000_create_egress_credential_table;
+mod m20251101_120000_add_function_dependencies;
+mod m20251102_120000_add_function_version_test_results;
+mod m20251103_120000_create_deployment_table;
+mod m20251104_120000_add_auth_totp_last_step;
+mod m20251105_120000_add_oidc_trust_repository_ids;
+pub mod seed;
@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Per-function settings from the deploy config (routing policy, limits, ...)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .add_column_if_not_exists(json_binary_null(Function::Settings))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .drop_column(Function::Settings)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Settings,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Defaults the user's functions inherit at deploy time (memory, timeout, env, ...)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .add_column_if_not_exists(json_binary_null(Auth::Defaults))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .drop_column(Auth::Defaults)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Defaults,
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every deployed archive, so namespaces can be exported and restored elsewhere
+        manager
+            .create_table(
+                Table::create()
+                    .table(FunctionVersion::Table)
+                    .if_not_exists()
+                    .col(pk_auto(FunctionVersion::Id))
+                    .col(integer(FunctionVersion::FunctionId))
+                    .col(integer(FunctionVersion::Version))
+                    .col(blob(FunctionVersion::Archive))
+                    .col(json_binary_null(FunctionVersion::Settings))
+                    .col(
+                        timestamp_with_time_zone(FunctionVersion::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-function_version-function_id")
+                            .from(FunctionVersion::Table, FunctionVersion::FunctionId)
+                            .to(Function::Table, Function::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-function_version-function-version-unique")
+                    .table(FunctionVersion::Table)
+                    .col(FunctionVersion::FunctionId)
+                    .col(FunctionVersion::Version)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-function_version-function-version-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(FunctionVersion::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum FunctionVersion {
+    Table,
+    Id,
+    FunctionId,
+    Version,
+    Archive,
+    Settings,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Id,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Set when a function is moved to the trash; purged once the retention period ends
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .add_column_if_not_exists(timestamp_with_time_zone_null(Function::DeletedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .drop_column(Function::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    DeletedAt,
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // README and OpenAPI document shipped in the function bundle, if any
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .add_column_if_not_exists(text_null(Function::Readme))
+                    .add_column_if_not_exists(text_null(Function::Openapi))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .drop_column(Function::Readme)
+                    .drop_column(Function::Openapi)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Readme,
+    Openapi,
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Preview instances record the function they preview and when they are removed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .add_column_if_not_exists(string_null(Function::PreviewOf))
+                    .add_column_if_not_exists(timestamp_with_time_zone_null(Function::ExpiresAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Function::Table)
+                    .drop_column(Function::PreviewOf)
+                    .drop_column(Function::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    PreviewOf,
+    ExpiresAt,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // CI workflows allowed to exchange an OIDC token for deploy credentials
+        manager
+            .create_table(
+                Table::create()
+                    .table(OidcTrust::Table)
+                    .if_not_exists()
+                    .col(pk_auto(OidcTrust::Id))
+                    .col(integer(OidcTrust::AuthId))
+                    .col(string(OidcTrust::Repository))
+                    .col(string_null(OidcTrust::RefPattern))
+                    .col(string_null(OidcTrust::Environment))
+                    .col(
+                        timestamp_with_time_zone(OidcTrust::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oidc_trust-auth_id")
+                            .from(OidcTrust::Table, OidcTrust::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OidcTrust::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OidcTrust {
+    Table,
+    Id,
+    AuthId,
+    Repository,
+    RefPattern,
+    Environment,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
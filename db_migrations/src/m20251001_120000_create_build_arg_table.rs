@@ -0,0 +1,82 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Encrypted variables passed to the build stage of a namespace's function images
+        manager
+            .create_table(
+                Table::create()
+                    .table(BuildArg::Table)
+                    .if_not_exists()
+                    .col(pk_auto(BuildArg::Id))
+                    .col(integer(BuildArg::AuthId))
+                    .col(string(BuildArg::Name))
+                    .col(text(BuildArg::Value))
+                    .col(
+                        timestamp_with_time_zone(BuildArg::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(BuildArg::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-build_arg-auth_id")
+                            .from(BuildArg::Table, BuildArg::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-build_arg-auth-name-unique")
+                    .table(BuildArg::Table)
+                    .col(BuildArg::AuthId)
+                    .col(BuildArg::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-build_arg-auth-name-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(BuildArg::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BuildArg {
+    Table,
+    Id,
+    AuthId,
+    Name,
+    Value,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
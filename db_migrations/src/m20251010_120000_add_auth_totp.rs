@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // TOTP second factor: the secret (pending until a first code confirms it) and the
+        // hashes of the unused recovery codes
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .add_column_if_not_exists(string_null(Auth::TotpSecret))
+                    .add_column_if_not_exists(boolean(Auth::TotpEnabled).default(false))
+                    .add_column_if_not_exists(json_binary_null(Auth::RecoveryCodes))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .drop_column(Auth::TotpSecret)
+                    .drop_column(Auth::TotpEnabled)
+                    .drop_column(Auth::RecoveryCodes)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    TotpSecret,
+    TotpEnabled,
+    RecoveryCodes,
+}
@@ -0,0 +1,107 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cron schedules invoking a function with a fixed payload
+        manager
+            .create_table(
+                Table::create()
+                    .table(Schedule::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Schedule::Id))
+                    .col(integer(Schedule::FunctionId))
+                    .col(string(Schedule::Name))
+                    .col(string(Schedule::Cron))
+                    .col(string(Schedule::Timezone).default("UTC"))
+                    .col(json_binary_null(Schedule::Payload))
+                    .col(boolean(Schedule::Enabled).default(true))
+                    .col(timestamp_with_time_zone_null(Schedule::LastRunAt))
+                    .col(timestamp_with_time_zone_null(Schedule::NextRunAt))
+                    .col(
+                        timestamp_with_time_zone(Schedule::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(Schedule::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-schedule-function_id")
+                            .from(Schedule::Table, Schedule::FunctionId)
+                            .to(Function::Table, Function::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-schedule-function-name-unique")
+                    .table(Schedule::Table)
+                    .col(Schedule::FunctionId)
+                    .col(Schedule::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // The dispatcher looks up the schedules that are due
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-schedule-next_run_at")
+                    .table(Schedule::Table)
+                    .col(Schedule::NextRunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-schedule-next_run_at").to_owned())
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-schedule-function-name-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Schedule::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Schedule {
+    Table,
+    Id,
+    FunctionId,
+    Name,
+    Cron,
+    Timezone,
+    Payload,
+    Enabled,
+    LastRunAt,
+    NextRunAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Id,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Queues, streams and webhooks whose events invoke a function
+        manager
+            .create_table(
+                Table::create()
+                    .table(EventSource::Table)
+                    .if_not_exists()
+                    .col(pk_auto(EventSource::Id))
+                    .col(integer(EventSource::FunctionId))
+                    .col(string(EventSource::Name))
+                    .col(string(EventSource::Kind))
+                    .col(json_binary(EventSource::Config))
+                    .col(boolean(EventSource::Enabled).default(true))
+                    .col(
+                        timestamp_with_time_zone(EventSource::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(EventSource::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-event_source-function_id")
+                            .from(EventSource::Table, EventSource::FunctionId)
+                            .to(Function::Table, Function::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-event_source-function-name-unique")
+                    .table(EventSource::Table)
+                    .col(EventSource::FunctionId)
+                    .col(EventSource::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-event_source-function-name-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(EventSource::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EventSource {
+    Table,
+    Id,
+    FunctionId,
+    Name,
+    Kind,
+    Config,
+    Enabled,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Id,
+}
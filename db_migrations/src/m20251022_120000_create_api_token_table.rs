@@ -0,0 +1,87 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Long-lived, scoped credentials of a namespace; only a hash of the token is stored
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ApiToken::Id))
+                    .col(integer(ApiToken::AuthId))
+                    .col(string(ApiToken::Name))
+                    .col(string(ApiToken::Prefix))
+                    .col(string_uniq(ApiToken::TokenHash))
+                    .col(json_binary(ApiToken::Scopes))
+                    .col(timestamp_with_time_zone_null(ApiToken::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(ApiToken::LastUsedAt))
+                    .col(timestamp_with_time_zone_null(ApiToken::RevokedAt))
+                    .col(
+                        timestamp_with_time_zone(ApiToken::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api_token-auth_id")
+                            .from(ApiToken::Table, ApiToken::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-api_token-auth-name-unique")
+                    .table(ApiToken::Table)
+                    .col(ApiToken::AuthId)
+                    .col(ApiToken::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-api_token-auth-name-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ApiToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiToken {
+    Table,
+    Id,
+    AuthId,
+    Name,
+    Prefix,
+    TokenHash,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
@@ -0,0 +1,75 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Custom hostnames routed to a function once the namespace proved it owns them
+        manager
+            .create_table(
+                Table::create()
+                    .table(Domain::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Domain::Id))
+                    .col(integer(Domain::AuthId))
+                    .col(integer(Domain::FunctionId))
+                    .col(string_uniq(Domain::Hostname))
+                    .col(string(Domain::VerificationToken))
+                    .col(timestamp_with_time_zone_null(Domain::VerifiedAt))
+                    .col(
+                        timestamp_with_time_zone(Domain::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-domain-auth_id")
+                            .from(Domain::Table, Domain::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-domain-function_id")
+                            .from(Domain::Table, Domain::FunctionId)
+                            .to(Function::Table, Function::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Domain::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Domain {
+    Table,
+    Id,
+    AuthId,
+    FunctionId,
+    Hostname,
+    VerificationToken,
+    VerifiedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Id,
+}
@@ -0,0 +1,82 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Daily totals of a function's invocations and resources, for quotas and billing
+        manager
+            .create_table(
+                Table::create()
+                    .table(Usage::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Usage::Id))
+                    .col(integer(Usage::FunctionId))
+                    .col(date(Usage::Day))
+                    .col(big_integer(Usage::Invocations).default(0))
+                    .col(big_integer(Usage::Errors).default(0))
+                    .col(big_integer(Usage::DurationMs).default(0))
+                    .col(big_integer(Usage::CpuMs).default(0))
+                    .col(big_integer(Usage::MemoryMbSeconds).default(0))
+                    .col(big_integer(Usage::EgressBytes).default(0))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-usage-function_id")
+                            .from(Usage::Table, Usage::FunctionId)
+                            .to(Function::Table, Function::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-usage-function-day-unique")
+                    .table(Usage::Table)
+                    .col(Usage::FunctionId)
+                    .col(Usage::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-usage-function-day-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Usage::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Usage {
+    Table,
+    Id,
+    FunctionId,
+    Day,
+    Invocations,
+    Errors,
+    DurationMs,
+    CpuMs,
+    MemoryMbSeconds,
+    EgressBytes,
+}
+
+#[derive(DeriveIden)]
+enum Function {
+    Table,
+    Id,
+}
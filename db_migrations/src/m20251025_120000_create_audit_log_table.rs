@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Who did what to a namespace. Entries outlive deleted functions, so the target
+        // is recorded by name rather than by foreign key
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(pk_auto(AuditLog::Id))
+                    .col(integer(AuditLog::AuthId))
+                    .col(string(AuditLog::Actor))
+                    .col(string(AuditLog::Action))
+                    .col(string_null(AuditLog::Target))
+                    .col(json_binary_null(AuditLog::Details))
+                    .col(string_null(AuditLog::SourceIp))
+                    .col(
+                        timestamp_with_time_zone(AuditLog::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-audit_log-auth_id")
+                            .from(AuditLog::Table, AuditLog::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Entries are listed per namespace, newest first
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-audit_log-auth-created_at")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::AuthId)
+                    .col(AuditLog::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-audit_log-auth-created_at")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditLog {
+    Table,
+    Id,
+    AuthId,
+    Actor,
+    Action,
+    Target,
+    Details,
+    SourceIp,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
@@ -0,0 +1,146 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Accounts that approve the namespace's deploys; none means deploys need no approval
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .add_column_if_not_exists(json_binary_null(Auth::DeployApprovers))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Deploys refused until unlocked; without a function name the whole namespace is
+        // locked. Functions are named rather than referenced, so they can be locked before
+        // their first deploy
+        manager
+            .create_table(
+                Table::create()
+                    .table(DeployLock::Table)
+                    .if_not_exists()
+                    .col(pk_auto(DeployLock::Id))
+                    .col(integer(DeployLock::AuthId))
+                    .col(string_null(DeployLock::FunctionName))
+                    .col(string_null(DeployLock::Reason))
+                    .col(string(DeployLock::LockedBy))
+                    .col(
+                        timestamp_with_time_zone(DeployLock::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-deploy_lock-auth_id")
+                            .from(DeployLock::Table, DeployLock::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Deploys waiting for an approver, with the archive they deploy once approved
+        manager
+            .create_table(
+                Table::create()
+                    .table(PendingDeploy::Table)
+                    .if_not_exists()
+                    .col(pk_auto(PendingDeploy::Id))
+                    .col(integer(PendingDeploy::AuthId))
+                    .col(string(PendingDeploy::FunctionName))
+                    .col(blob(PendingDeploy::Archive))
+                    .col(boolean(PendingDeploy::Force).default(false))
+                    .col(string(PendingDeploy::RequestedBy))
+                    .col(string(PendingDeploy::Status))
+                    .col(string_null(PendingDeploy::DecidedBy))
+                    .col(timestamp_with_time_zone_null(PendingDeploy::DecidedAt))
+                    .col(
+                        timestamp_with_time_zone(PendingDeploy::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-pending_deploy-auth_id")
+                            .from(PendingDeploy::Table, PendingDeploy::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Approvers list the deploys still pending
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-pending_deploy-status")
+                    .table(PendingDeploy::Table)
+                    .col(PendingDeploy::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-pending_deploy-status").to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(PendingDeploy::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(DeployLock::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Auth::Table)
+                    .drop_column(Auth::DeployApprovers)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DeployLock {
+    Table,
+    Id,
+    AuthId,
+    FunctionName,
+    Reason,
+    LockedBy,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum PendingDeploy {
+    Table,
+    Id,
+    AuthId,
+    FunctionName,
+    Archive,
+    Force,
+    RequestedBy,
+    Status,
+    DecidedBy,
+    DecidedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+    DeployApprovers,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Encrypted third-party credentials the egress gateway signs functions' requests with
+        manager
+            .create_table(
+                Table::create()
+                    .table(EgressCredential::Table)
+                    .if_not_exists()
+                    .col(pk_auto(EgressCredential::Id))
+                    .col(integer(EgressCredential::AuthId))
+                    .col(string(EgressCredential::Name))
+                    .col(text(EgressCredential::BaseUrl))
+                    .col(string(EgressCredential::Header))
+                    .col(text(EgressCredential::Value))
+                    .col(
+                        timestamp_with_time_zone(EgressCredential::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(EgressCredential::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-egress_credential-auth_id")
+                            .from(EgressCredential::Table, EgressCredential::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-egress_credential-auth-name-unique")
+                    .table(EgressCredential::Table)
+                    .col(EgressCredential::AuthId)
+                    .col(EgressCredential::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-egress_credential-auth-name-unique")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(EgressCredential::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EgressCredential {
+    Table,
+    Id,
+    AuthId,
+    Name,
+    BaseUrl,
+    Header,
+    Value,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
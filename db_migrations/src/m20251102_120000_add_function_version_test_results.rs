@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Results of the tests run while building the version, if its config asked for them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FunctionVersion::Table)
+                    .add_column_if_not_exists(json_binary_null(FunctionVersion::TestResults))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FunctionVersion::Table)
+                    .drop_column(FunctionVersion::TestResults)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum FunctionVersion {
+    Table,
+    TestResults,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Deploys queued for the job runners, with the archive they deploy until they
+        // finish, so a deploy survives the controller that accepted it restarting
+        manager
+            .create_table(
+                Table::create()
+                    .table(Deployment::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Deployment::Id))
+                    .col(integer(Deployment::AuthId))
+                    .col(string(Deployment::FunctionName))
+                    .col(string_null(Deployment::PreviewOf))
+                    .col(blob(Deployment::Archive))
+                    .col(string(Deployment::Status))
+                    .col(text_null(Deployment::Message))
+                    .col(
+                        timestamp_with_time_zone(Deployment::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(Deployment::FinishedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-deployment-auth_id")
+                            .from(Deployment::Table, Deployment::AuthId)
+                            .to(Auth::Table, Auth::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Deployment::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Deployment {
+    Table,
+    Id,
+    AuthId,
+    FunctionName,
+    PreviewOf,
+    Archive,
+    Status,
+    Message,
+    CreatedAt,
+    FinishedAt,
+}
+
+#[derive(DeriveIden)]
+enum Auth {
+    Table,
+    Id,
+}
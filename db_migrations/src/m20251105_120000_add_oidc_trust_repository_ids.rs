@@ -0,0 +1,41 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Immutable ids of the trusted repository and its owner; names can be renamed
+        // and taken over, ids can't. Trusts stored before have neither and accept no
+        // tokens until they are added again.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OidcTrust::Table)
+                    .add_column_if_not_exists(string_null(OidcTrust::RepositoryOwnerId))
+                    .add_column_if_not_exists(string_null(OidcTrust::RepositoryId))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OidcTrust::Table)
+                    .drop_column(OidcTrust::RepositoryOwnerId)
+                    .drop_column(OidcTrust::RepositoryId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OidcTrust {
+    Table,
+    RepositoryOwnerId,
+    RepositoryId,
+}
@@ -1,6 +1,21 @@
 use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Database;
 
 #[async_std::main]
 async fn main() {
+    // `seed` fills a migrated database with the rows tests expect; everything else is
+    // handled by the migrator CLI
+    if std::env::args().nth(1).as_deref() == Some("seed") {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let db = Database::connect(url)
+            .await
+            .expect("Failed to connect to the database");
+        db_migrations::seed::seed(&db)
+            .await
+            .expect("Failed to seed the database");
+        println!("Seeded namespace {}", db_migrations::seed::SEED_NAMESPACE);
+        return;
+    }
+
     cli::run_cli(db_migrations::Migrator).await;
 }
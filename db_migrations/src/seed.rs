@@ -0,0 +1,134 @@
+//! Fixed rows for tests and local development.
+//!
+//! Seeds one namespace with one function and a row in each table hanging off them, so
+//! queries against the newer tables have something to find. Never run it against a
+//! production database: the seed account exists in every seeded installation.
+
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+/// Namespace of the seed account
+pub const SEED_NAMESPACE: &str = "00000000-0000-4000-8000-000000000001";
+
+/// Email of the seed account. Its password hash matches no password; tests act as the
+/// account with a JWT issued for [`SEED_NAMESPACE`]
+pub const SEED_EMAIL: &str = "seed@invok.test";
+
+/// Name of the seed function
+pub const SEED_FUNCTION: &str = "hello";
+
+/// Inserts the seed rows into a migrated database. Seeding twice fails on the unique
+/// email, leaving the first seed untouched.
+pub async fn seed<C: ConnectionTrait>(db: &C) -> Result<(), DbErr> {
+    let namespace = Expr::val(SEED_NAMESPACE).cast_as(Alias::new("uuid"));
+
+    let auth_id = insert_returning_id(
+        db,
+        Query::insert()
+            .into_table(Alias::new("auth"))
+            .columns(cols(["email", "password", "uuid", "totp_enabled"]))
+            .values_panic([
+                SEED_EMAIL.into(),
+                "!".into(),
+                namespace.clone(),
+                false.into(),
+            ])
+            .returning_col(Alias::new("id"))
+            .to_owned(),
+    )
+    .await?;
+
+    let function_id = insert_returning_id(
+        db,
+        Query::insert()
+            .into_table(Alias::new("function"))
+            .columns(cols(["name", "runtime", "uuid", "auth_id"]))
+            .values_panic([SEED_FUNCTION.into(), "go".into(), namespace, auth_id.into()])
+            .returning_col(Alias::new("id"))
+            .to_owned(),
+    )
+    .await?;
+
+    let rows = [
+        Query::insert()
+            .into_table(Alias::new("schedule"))
+            .columns(cols(["function_id", "name", "cron"]))
+            .values_panic([function_id.into(), "nightly".into(), "0 3 * * *".into()])
+            .to_owned(),
+        Query::insert()
+            .into_table(Alias::new("event_source"))
+            .columns(cols(["function_id", "name", "kind", "config"]))
+            .values_panic([
+                function_id.into(),
+                "orders".into(),
+                "redis_stream".into(),
+                Expr::val(r#"{"stream":"orders"}"#).cast_as(Alias::new("jsonb")),
+            ])
+            .to_owned(),
+        Query::insert()
+            .into_table(Alias::new("api_token"))
+            .columns(cols(["auth_id", "name", "prefix", "token_hash", "scopes"]))
+            .values_panic([
+                auth_id.into(),
+                "ci".into(),
+                "invok_seed".into(),
+                "seed-token-hash".into(),
+                Expr::val(r#"["deploy"]"#).cast_as(Alias::new("jsonb")),
+            ])
+            .to_owned(),
+        Query::insert()
+            .into_table(Alias::new("domain"))
+            .columns(cols([
+                "auth_id",
+                "function_id",
+                "hostname",
+                "verification_token",
+            ]))
+            .values_panic([
+                auth_id.into(),
+                function_id.into(),
+                "hello.invok.test".into(),
+                "seed-verification".into(),
+            ])
+            .to_owned(),
+        Query::insert()
+            .into_table(Alias::new("usage"))
+            .columns(cols(["function_id", "day", "invocations", "errors"]))
+            .values_panic([
+                function_id.into(),
+                Expr::current_date().into(),
+                42i64.into(),
+                1i64.into(),
+            ])
+            .to_owned(),
+        Query::insert()
+            .into_table(Alias::new("audit_log"))
+            .columns(cols(["auth_id", "actor", "action", "target"]))
+            .values_panic([
+                auth_id.into(),
+                SEED_EMAIL.into(),
+                "function.deploy".into(),
+                SEED_FUNCTION.into(),
+            ])
+            .to_owned(),
+    ];
+    for row in rows {
+        db.execute(db.get_database_backend().build(&row)).await?;
+    }
+    Ok(())
+}
+
+fn cols<const N: usize>(names: [&'static str; N]) -> [Alias; N] {
+    names.map(Alias::new)
+}
+
+async fn insert_returning_id<C: ConnectionTrait>(
+    db: &C,
+    insert: InsertStatement,
+) -> Result<i32, DbErr> {
+    let statement: Statement = db.get_database_backend().build(&insert);
+    db.query_one(statement)
+        .await?
+        .ok_or(DbErr::RecordNotInserted)?
+        .try_get("", "id")
+}
@@ -0,0 +1,462 @@
+use crate::error::{ClientError, ClientResult};
+use crate::logs::LogStream;
+use crate::types::{
+    AuthResponse, DeployOptions, Deployment, DeploymentState, DeploymentStatus, FunctionStatus,
+    Invocation, InvokeRequest, Session, TotpEnrollment,
+};
+use reqwest::blocking::{multipart, Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// How long calls may take unless set with [`InvokClient::with_timeout`]. Deploys build
+/// an image, so this is generous
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long to wait for a queued deploy to finish. Builds pull base images and install
+/// dependencies, so this is generous
+const DEPLOY_WAIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often a queued deploy is looked up while waiting for it
+const DEPLOY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a log stream stays open
+const LOG_STREAM_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Header identifying an invocation
+const REQUEST_ID_HEADER: &str = "x-invok-request-id";
+
+/// Error body of a failed login
+#[derive(Deserialize)]
+struct LoginError {
+    #[serde(default)]
+    totp_required: bool,
+}
+
+/// Recovery codes issued when two-factor authentication is enabled
+#[derive(Deserialize)]
+struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+/// Blocking client of one invok installation.
+///
+/// Calls managing functions need a [`Session`], from a login or stored by the caller,
+/// and act on the functions of its namespace.
+#[derive(Debug, Clone)]
+pub struct InvokClient {
+    base_url: String,
+    http: Client,
+    timeout: Duration,
+    session: Option<Session>,
+}
+
+impl InvokClient {
+    /// A client of the installation at `base_url`, e.g. `https://freeserverless.com`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: Client::new(),
+            timeout: DEFAULT_TIMEOUT,
+            session: None,
+        }
+    }
+
+    /// Make the calls of a session
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// How long calls other than log streams may take
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// URL a function of the session's namespace is invoked at
+    pub fn function_url(&self, function_name: &str) -> ClientResult<String> {
+        Ok(format!(
+            "{}/invok/{}/{}",
+            self.base_url,
+            self.namespace()?,
+            function_name
+        ))
+    }
+
+    /// Creates an account.
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - Email address of the account
+    /// * `password` - Password of the account
+    ///
+    /// # Returns
+    ///
+    /// The token issued for the new account
+    pub fn register(&self, email: &str, password: &str) -> ClientResult<AuthResponse> {
+        let response = self
+            .request(Method::POST, "/auth/register")
+            .json(&json!({ "email": email, "password": password }))
+            .send()?;
+        Ok(check(response)?.json()?)
+    }
+
+    /// Logs in to an account.
+    ///
+    /// Accounts with two-factor authentication also need `totp_code`, a code from the
+    /// authenticator app or a recovery code. Without one, or with a wrong one, the login
+    /// fails with [`ClientError::TotpRequired`].
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - Email address of the account
+    /// * `password` - Password of the account
+    /// * `totp_code` - Second factor, for accounts that need one
+    ///
+    /// # Returns
+    ///
+    /// The token issued for the account
+    pub fn login(
+        &self,
+        email: &str,
+        password: &str,
+        totp_code: Option<&str>,
+    ) -> ClientResult<AuthResponse> {
+        let mut body = json!({ "email": email, "password": password });
+        if let Some(code) = totp_code {
+            body["totp_code"] = json!(code);
+        }
+        let response = self
+            .request(Method::POST, "/auth/login")
+            .json(&body)
+            .send()?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text()?;
+            let totp_required = serde_json::from_str::<LoginError>(&message)
+                .map(|error| error.totp_required)
+                .unwrap_or(false);
+            return Err(if totp_required {
+                ClientError::TotpRequired(message)
+            } else {
+                ClientError::Api { status, message }
+            });
+        }
+        Ok(response.json()?)
+    }
+
+    /// Exchanges an OIDC token of a CI workflow for short-lived credentials that can only
+    /// deploy into `namespace`, which must trust the workflow's repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The OIDC token issued to the workflow
+    /// * `namespace` - UUID of the namespace to deploy into
+    pub fn exchange_oidc_token(&self, token: &str, namespace: &str) -> ClientResult<AuthResponse> {
+        let response = self
+            .request(Method::POST, "/auth/oidc/exchange")
+            .json(&json!({ "token": token, "namespace": namespace }))
+            .send()?;
+        Ok(check(response)?.json()?)
+    }
+
+    /// Starts enrolling the session's account in two-factor authentication
+    pub fn enroll_totp(&self) -> ClientResult<TotpEnrollment> {
+        let response = self.authorized(Method::POST, "/auth/totp/enroll")?.send()?;
+        Ok(check(response)?.json()?)
+    }
+
+    /// Confirms a pending enrollment with a code from the authenticator app, requiring a
+    /// second factor at every login from then on.
+    ///
+    /// # Returns
+    ///
+    /// The recovery codes, which are never shown again
+    pub fn enable_totp(&self, code: &str) -> ClientResult<Vec<String>> {
+        let response = self
+            .authorized(Method::POST, "/auth/totp/enable")?
+            .json(&json!({ "code": code }))
+            .send()?;
+        let recovery_codes: RecoveryCodes = check(response)?.json()?;
+        Ok(recovery_codes.recovery_codes)
+    }
+
+    /// Stops requiring a second factor at login, given a code from the authenticator app
+    /// or a recovery code
+    pub fn disable_totp(&self, code: &str) -> ClientResult<()> {
+        let response = self
+            .authorized(Method::POST, "/auth/totp/disable")?
+            .json(&json!({ "code": code }))
+            .send()?;
+        check(response)?;
+        Ok(())
+    }
+
+    /// Deploys a function, waiting for the controller to build and deploy it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function
+    /// * `archive` - The function's directory, zipped, with its `config.json`
+    /// * `options` - Whether to deploy a preview, or past an SLO deploy freeze
+    ///
+    /// # Returns
+    ///
+    /// The name the function is served under and the output of the deploy, or the
+    /// pending deploy when the namespace's deploys need approval.
+    /// [`ClientError::DeployFailed`] if the deploy failed.
+    pub fn deploy(
+        &self,
+        name: &str,
+        archive: Vec<u8>,
+        options: &DeployOptions,
+    ) -> ClientResult<Deployment> {
+        let mut deployment = self.submit_deploy(name, archive, options)?;
+        if let Some(id) = deployment.deployment {
+            let finished = self.wait_for_deployment(id)?;
+            deployment.message = finished.message.unwrap_or_default();
+        }
+        Ok(deployment)
+    }
+
+    /// Uploads a function to deploy, without waiting for the deploy. The controller
+    /// queues it, and it carries on if the controller restarts.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function
+    /// * `archive` - The function's directory, zipped, with its `config.json`
+    /// * `options` - Whether to deploy a preview, or past an SLO deploy freeze
+    ///
+    /// # Returns
+    ///
+    /// The name the function will be served under, and the queued deploy to follow with
+    /// [`InvokClient::wait_for_deployment`], or the pending deploy when the namespace's
+    /// deploys need approval
+    pub fn submit_deploy(
+        &self,
+        name: &str,
+        archive: Vec<u8>,
+        options: &DeployOptions,
+    ) -> ClientResult<Deployment> {
+        // The server needs the preview branch and the force flag before the archive
+        let mut form = multipart::Form::new();
+        if let Some(branch) = &options.preview {
+            form = form.text("preview", branch.clone());
+        }
+        if options.force {
+            form = form.text("force", "true");
+        }
+        let form = form.part(
+            "file",
+            multipart::Part::bytes(archive)
+                .file_name(format!("{name}.zip"))
+                .mime_str("application/zip")?,
+        );
+
+        let response = self
+            .authorized(Method::POST, "/invok/deploy")?
+            .multipart(form)
+            .send()?;
+        let message = check(response)?.text()?;
+        Ok(parse_deployment(name, message))
+    }
+
+    /// Looks up a deploy of the session's namespace queued by
+    /// [`InvokClient::submit_deploy`]
+    pub fn deployment(&self, id: i32) -> ClientResult<DeploymentStatus> {
+        let path = format!("/invok/deployments/{id}");
+        let response = self.authorized(Method::GET, &path)?.send()?;
+        Ok(check(response)?.json()?)
+    }
+
+    /// Waits for a queued deploy to finish, looking it up every couple of seconds.
+    ///
+    /// # Returns
+    ///
+    /// The deploy once it succeeded. [`ClientError::DeployFailed`] if it failed, and
+    /// [`ClientError::DeployUnfinished`] if it hadn't finished after half an hour.
+    pub fn wait_for_deployment(&self, id: i32) -> ClientResult<DeploymentStatus> {
+        let started = Instant::now();
+        loop {
+            let deployment = self.deployment(id)?;
+            match deployment.status {
+                DeploymentState::Succeeded => return Ok(deployment),
+                DeploymentState::Failed => {
+                    return Err(ClientError::DeployFailed(
+                        deployment.message.unwrap_or_default(),
+                    ))
+                }
+                DeploymentState::Queued | DeploymentState::Running => {}
+            }
+            if started.elapsed() >= DEPLOY_WAIT_TIMEOUT {
+                return Err(ClientError::DeployUnfinished(id));
+            }
+            std::thread::sleep(DEPLOY_POLL_INTERVAL);
+        }
+    }
+
+    /// Sends a request to a function of the session's namespace. Any answer of the
+    /// function, error statuses included, is returned as is.
+    pub fn invoke(&self, name: &str, request: InvokeRequest) -> ClientResult<Invocation> {
+        let mut builder = self
+            .http
+            .request(request.method, self.function_url(name)?)
+            .timeout(self.timeout)
+            .query(&request.query)
+            .body(request.body);
+        for (header, value) in &request.headers {
+            builder = builder.header(header, value);
+        }
+        let response = builder.send()?;
+
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect::<std::collections::HashMap<_, _>>();
+        Ok(Invocation {
+            status: response.status().as_u16(),
+            request_id: headers.get(REQUEST_ID_HEADER).cloned(),
+            headers,
+            body: response.bytes()?.to_vec(),
+        })
+    }
+
+    /// Streams the logs of a function of the session's namespace
+    pub fn logs(&self, name: &str) -> ClientResult<LogStream> {
+        let path = format!("/invok/logs/{}/{}", self.namespace()?, name);
+        let response = self
+            .authorized(Method::GET, &path)?
+            .timeout(LOG_STREAM_TIMEOUT)
+            .send()?;
+        Ok(LogStream::new(check(response)?))
+    }
+
+    /// Describes a function of the session's namespace: its settings, containers, SLO
+    /// and last crash
+    pub fn status(&self, name: &str) -> ClientResult<FunctionStatus> {
+        let path = format!("/invok/status/{}/{}", self.namespace()?, name);
+        let response = self.authorized(Method::GET, &path)?.send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(ClientError::FunctionNotFound(name.to_string()));
+        }
+        Ok(check(response)?.json()?)
+    }
+
+    fn namespace(&self) -> ClientResult<&str> {
+        self.session
+            .as_ref()
+            .map(|session| session.namespace.as_str())
+            .ok_or(ClientError::NotLoggedIn)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.base_url, path))
+            .timeout(self.timeout)
+    }
+
+    fn authorized(&self, method: Method, path: &str) -> ClientResult<RequestBuilder> {
+        let session = self.session.as_ref().ok_or(ClientError::NotLoggedIn)?;
+        Ok(self.request(method, path).bearer_auth(&session.token))
+    }
+}
+
+/// Turn error responses into [`ClientError::Api`]
+fn check(response: Response) -> ClientResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response
+        .text()
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(ClientError::Api { status, message })
+}
+
+/// Reads what the controller reported about a deploy
+fn parse_deployment(name: &str, message: String) -> Deployment {
+    let field = |prefix: &str| {
+        message
+            .lines()
+            .find_map(|line| line.strip_prefix(prefix))
+            .map(str::to_string)
+    };
+    // Previews are served under the instance name the server picked
+    let deployed_name = field("Function: ").unwrap_or_else(|| name.to_string());
+    let deployment = field("Deployment: ").and_then(|id| id.trim().parse().ok());
+    let pending_approval = field("Approval: ").and_then(|id| id.trim().parse().ok());
+    Deployment {
+        name: deployed_name,
+        message,
+        deployment,
+        pending_approval,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_url_needs_a_session() {
+        let client = InvokClient::new("https://invok.test/");
+        assert!(matches!(
+            client.function_url("hello"),
+            Err(ClientError::NotLoggedIn)
+        ));
+
+        let client = client.with_session(Session {
+            token: "token".to_string(),
+            namespace: "ns".to_string(),
+        });
+        assert_eq!(
+            client.function_url("hello").unwrap(),
+            "https://invok.test/invok/ns/hello"
+        );
+    }
+
+    #[test]
+    fn test_parse_deployment() {
+        let queued = parse_deployment(
+            "hello",
+            "Deploy of 'hello--feature' queued\nDeployment: 7\nFunction: hello--feature\nUser UUID: ns"
+                .to_string(),
+        );
+        assert_eq!(queued.name, "hello--feature");
+        assert_eq!(queued.deployment, Some(7));
+        assert_eq!(queued.pending_approval, None);
+
+        let pending = parse_deployment(
+            "hello",
+            "Deploy of 'hello' is waiting for approval\nApproval: 42\nFunction: hello\nUser UUID: ns"
+                .to_string(),
+        );
+        assert_eq!(pending.name, "hello");
+        assert_eq!(pending.deployment, None);
+        assert_eq!(pending.pending_approval, Some(42));
+    }
+
+    #[test]
+    fn test_deployment_status() {
+        let deployment: DeploymentStatus = serde_json::from_value(json!({
+            "id": 7,
+            "function": "hello",
+            "status": "running",
+            "message": null,
+            "created_at": "2025-11-03T12:00:00+00:00",
+            "finished_at": null,
+        }))
+        .unwrap();
+        assert_eq!(deployment.status, DeploymentState::Running);
+        assert!(!deployment.status.is_finished());
+        assert!(DeploymentState::Failed.is_finished());
+    }
+}
@@ -0,0 +1,41 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Errors of the calls to the invok API
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The call needs a session; see [`crate::InvokClient::with_session`]
+    #[error("Not logged in")]
+    NotLoggedIn,
+
+    /// The account has two-factor authentication and the login carried no code
+    #[error("Two-factor code required: {0}")]
+    TotpRequired(String),
+
+    #[error("Function not found: {0}")]
+    FunctionNotFound(String),
+
+    /// A queued deploy failed, with the reason the controller gave
+    #[error("Deploy failed: {0}")]
+    DeployFailed(String),
+
+    /// A queued deploy hadn't finished after waiting for it; it still runs on the
+    /// controller and can be looked up with [`crate::InvokClient::deployment`]
+    #[error("Deployment {0} hasn't finished yet")]
+    DeployUnfinished(i32),
+
+    /// Any other error response, with the body the controller sent
+    #[error("API error: Status code {status}. {message}")]
+    Api { status: StatusCode, message: String },
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
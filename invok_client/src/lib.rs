@@ -0,0 +1,46 @@
+//! Client for the invok management API.
+//!
+//! Wraps the controller's HTTP routes in typed, blocking calls, so tools that manage
+//! functions don't have to know the routes or the shape of their responses. The `invok`
+//! CLI goes through it too, which keeps the two in step with the handlers.
+//!
+//! | Call | Route |
+//! |------|-------|
+//! | [`InvokClient::register`] | `POST /auth/register` |
+//! | [`InvokClient::login`] | `POST /auth/login` |
+//! | [`InvokClient::exchange_oidc_token`] | `POST /auth/oidc/exchange` |
+//! | [`InvokClient::enroll_totp`], [`InvokClient::enable_totp`], [`InvokClient::disable_totp`] | `POST /auth/totp/:action` |
+//! | [`InvokClient::deploy`], [`InvokClient::submit_deploy`] | `POST /invok/deploy` |
+//! | [`InvokClient::deployment`], [`InvokClient::wait_for_deployment`] | `GET /invok/deployments/:id` |
+//! | [`InvokClient::invoke`] | `/invok/:namespace/:function_name` |
+//! | [`InvokClient::logs`] | `GET /invok/logs/:namespace/:function_name` |
+//! | [`InvokClient::status`] | `GET /invok/status/:namespace/:function_name` |
+//!
+//! ```no_run
+//! use invok_client::{DeployOptions, InvokClient, InvokeRequest};
+//!
+//! # fn main() -> Result<(), invok_client::ClientError> {
+//! let client = InvokClient::new("https://freeserverless.com");
+//! let session = client.login("me@example.com", "secret", None)?.session();
+//! let client = client.with_session(session);
+//!
+//! let archive = std::fs::read("hello.zip").expect("archive");
+//! let deployment = client.deploy("hello", archive, &DeployOptions::default())?;
+//! let invocation = client.invoke(&deployment.name, InvokeRequest::get().query("name", "Ada"))?;
+//! println!("{}", invocation.text());
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod logs;
+mod types;
+
+pub use client::InvokClient;
+pub use error::{ClientError, ClientResult};
+pub use logs::LogStream;
+pub use types::{
+    AuthResponse, CrashSummary, DeployOptions, Deployment, DeploymentState, DeploymentStatus,
+    FunctionStatus, Invocation, InvokeRequest, Session, SloStatus, TotpEnrollment, User,
+};
@@ -0,0 +1,82 @@
+use crate::error::ClientResult;
+use std::io::{BufRead, BufReader, Read};
+
+/// Log lines of a function as the controller streams them, until the stream ends or
+/// times out.
+///
+/// The controller sends logs as server-sent events; each item is the data of one event.
+pub struct LogStream {
+    lines: std::io::Lines<BufReader<Box<dyn Read + Send>>>,
+}
+
+impl LogStream {
+    pub(crate) fn new(body: impl Read + Send + 'static) -> Self {
+        let body: Box<dyn Read + Send> = Box::new(body);
+        Self {
+            lines: BufReader::new(body).lines(),
+        }
+    }
+}
+
+impl Iterator for LogStream {
+    type Item = ClientResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut data: Option<String> = None;
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e.into())),
+                // An event cut short by the end of the stream is still delivered
+                None => return data.map(Ok),
+            };
+
+            if line.is_empty() {
+                match data.take() {
+                    Some(data) => return Some(Ok(data)),
+                    None => continue,
+                }
+            }
+            // Comments (keep-alives), event names and ids carry no log
+            let Some(value) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match &mut data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => data = Some(value.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(body: &'static str) -> Vec<String> {
+        LogStream::new(body.as_bytes())
+            .collect::<ClientResult<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_yields_the_data_of_each_event() {
+        let body = ": connected\n\ndata: first line\n\nevent: log\ndata: second line\n\n";
+        assert_eq!(events(body), vec!["first line", "second line"]);
+    }
+
+    #[test]
+    fn test_joins_multi_line_data() {
+        let body = "data: panic: boom\ndata:   at main.go:12\n\n";
+        assert_eq!(events(body), vec!["panic: boom\n  at main.go:12"]);
+    }
+
+    #[test]
+    fn test_delivers_an_event_cut_short() {
+        assert_eq!(events("data: last words"), vec!["last words"]);
+    }
+}
@@ -0,0 +1,216 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Credentials of the calls made on behalf of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Bearer token issued at login
+    pub token: String,
+    /// UUID of the account, which is also the namespace of its functions
+    pub namespace: String,
+}
+
+/// Account a token was issued for
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub uuid: String,
+    pub email: String,
+}
+
+/// Answer to a registration, login or OIDC exchange
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthResponse {
+    pub token: String,
+    pub user: User,
+}
+
+impl AuthResponse {
+    /// The session to make further calls with
+    pub fn session(&self) -> Session {
+        Session {
+            token: self.token.clone(),
+            namespace: self.user.uuid.clone(),
+        }
+    }
+}
+
+/// A new two-factor secret to add to an authenticator app
+#[derive(Debug, Clone, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    /// `otpauth://` URI of the secret, for QR codes
+    pub uri: String,
+}
+
+/// How a function is deployed
+#[derive(Debug, Clone, Default)]
+pub struct DeployOptions {
+    /// Deploy a temporary preview instance for this branch instead
+    pub preview: Option<String>,
+    /// Deploy even when the function's SLO freezes deploys
+    pub force: bool,
+}
+
+/// A successful deploy, or one queued or held for approval
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    /// Name the function is served under; previews get an instance name of their own
+    pub name: String,
+    /// What the controller reported; the output of the deploy once it finished
+    pub message: String,
+    /// Id of the queued deploy, to look it up with [`crate::InvokClient::deployment`]
+    pub deployment: Option<i32>,
+    /// Id of the pending deploy when the namespace's deploys need approval; the function
+    /// isn't deployed until an approver approves it
+    pub pending_approval: Option<i32>,
+}
+
+/// Where a queued deploy is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    Queued,
+    /// Being built and deployed
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl DeploymentState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, DeploymentState::Succeeded | DeploymentState::Failed)
+    }
+}
+
+/// A queued deploy, as the controller reports it
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeploymentStatus {
+    pub id: i32,
+    /// Name the function is deployed under
+    pub function: String,
+    pub status: DeploymentState,
+    /// Output of the deploy once it succeeded, or why it failed
+    #[serde(default)]
+    pub message: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+    /// RFC 3339
+    #[serde(default)]
+    pub finished_at: Option<String>,
+}
+
+/// A request sent to a deployed function
+#[derive(Debug, Clone)]
+pub struct InvokeRequest {
+    pub(crate) method: Method,
+    pub(crate) query: Vec<(String, String)>,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl InvokeRequest {
+    pub fn new(method: Method) -> Self {
+        Self {
+            method,
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn get() -> Self {
+        Self::new(Method::GET)
+    }
+
+    pub fn post(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(Method::POST).body(body)
+    }
+
+    /// Add a query parameter
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add a header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// What a function answered
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub status: u16,
+    /// The `X-Invok-Request-Id` of the invocation, to find its logs or replay it
+    pub request_id: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Invocation {
+    /// The body as text, with invalid UTF-8 replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// The body parsed as JSON
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Compliance of a function with its SLO
+#[derive(Debug, Clone, Deserialize)]
+pub struct SloStatus {
+    /// Percent of invocations that must be good
+    pub objective: f64,
+    pub latency_ms: Option<u64>,
+    pub window_days: u32,
+    pub invocations: u64,
+    pub bad: u64,
+    /// Percent of good invocations
+    pub compliance: f64,
+    /// Share of the error budget left: 1 when untouched, 0 or less once spent
+    pub budget_remaining: f64,
+    pub burn_rate_1h: f64,
+    pub burn_rate_6h: f64,
+    pub budget_exhausted: bool,
+    pub deploys_frozen: bool,
+}
+
+/// The last crash of a function's containers
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrashSummary {
+    pub summary: String,
+    /// The full crash report, including the last log lines
+    pub report: serde_json::Value,
+}
+
+/// A deployed function and its containers
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionStatus {
+    pub name: String,
+    pub namespace: String,
+    pub runtime: String,
+    /// Settings of `config.json`, merged with the namespace defaults
+    pub settings: serde_json::Value,
+    /// Port published for a TCP or UDP service function
+    #[serde(default)]
+    pub service_port: Option<u16>,
+    /// RFC 3339 time the namespace was hibernated for lack of traffic; its next request
+    /// wakes it
+    #[serde(default)]
+    pub hibernated_at: Option<String>,
+    pub slo: Option<SloStatus>,
+    /// State of the function's container pool; `None` while it has no containers
+    pub pool: Option<HashMap<String, serde_json::Value>>,
+    pub last_crash: Option<CrashSummary>,
+}
@@ -0,0 +1,313 @@
+import createClient, { type Client } from "openapi-fetch";
+import { InvokError } from "./errors.js";
+import { readEvents } from "./logs.js";
+import type { components, paths } from "./schema.js";
+
+type Schemas = components["schemas"];
+
+export type AuthResponse = Schemas["AuthResponse"];
+export type User = Schemas["User"];
+export type TotpEnrollment = Schemas["TotpEnrollment"];
+export type FunctionSummary = Schemas["FunctionSummary"];
+export type FunctionStatus = Schemas["FunctionStatus"];
+export type TrashedFunction = Schemas["TrashedFunction"];
+export type PreviewInstance = Schemas["PreviewInstance"];
+export type DeploymentStatus = Schemas["Deployment"];
+
+/** How often a queued deploy is looked up while waiting for it, in milliseconds */
+const DEPLOY_POLL_INTERVAL_MS = 2000;
+
+export interface InvokClientOptions {
+  /** Address of the controller, e.g. `https://invok.example.com` */
+  baseUrl: string;
+  /** Token of a session started earlier; `login` and `register` set one */
+  token?: string;
+  /** `fetch` implementation, for runtimes or tests that need their own */
+  fetch?: typeof fetch;
+}
+
+export interface DeployOptions {
+  /** Deploy a temporary instance for this branch instead of the function itself */
+  preview?: string;
+  /** Deploy even though the function's SLO freezes deploys */
+  force?: boolean;
+}
+
+export interface Deployment {
+  /** Name the function is served under; the instance's name for previews */
+  name: string;
+  /** Build output of the controller */
+  message: string;
+  /** Id of the queued deploy, to look it up with `deployment` */
+  deployment?: number;
+  /**
+   * Id of the pending deploy when the namespace's deploys need approval; the function
+   * isn't deployed until an approver approves it
+   */
+  pendingApproval?: number;
+}
+
+/** Result shape shared by the typed calls of openapi-fetch */
+type Answer<T> = { data?: T; error?: unknown; response: Response };
+
+/**
+ * Client of the Invok management API.
+ *
+ * Typed calls for every route of `serverless_core/openapi.yaml` are on `api`; the
+ * methods cover the common ones, plus deploys and log streams, which don't fit a
+ * JSON request/response.
+ */
+export class InvokClient {
+  /** Typed client of every documented route, for calls the methods don't cover */
+  readonly api: Client<paths>;
+  private readonly baseUrl: string;
+  private readonly fetch: typeof fetch;
+  private token?: string;
+
+  constructor(options: InvokClientOptions) {
+    this.baseUrl = options.baseUrl.replace(/\/+$/, "");
+    this.fetch = options.fetch ?? globalThis.fetch.bind(globalThis);
+    this.token = options.token;
+    this.api = createClient<paths>({ baseUrl: this.baseUrl, fetch: this.fetch });
+    this.api.use({
+      onRequest: ({ request }) => {
+        if (this.token && !request.headers.has("Authorization")) {
+          request.headers.set("Authorization", `Bearer ${this.token}`);
+        }
+        return request;
+      },
+    });
+  }
+
+  /** Token of the current session, if any */
+  get sessionToken(): string | undefined {
+    return this.token;
+  }
+
+  /** Uses the token of a session started elsewhere, or drops the session */
+  setToken(token: string | undefined): void {
+    this.token = token;
+  }
+
+  /** Creates an account and starts a session with it */
+  async register(email: string, password: string): Promise<AuthResponse> {
+    const auth = await unwrap(this.api.POST("/auth/register", { body: { email, password } }));
+    this.token = auth.token;
+    return auth;
+  }
+
+  /**
+   * Starts a session. Accounts with two-factor authentication need `totpCode`; without
+   * it the error has `totpRequired` set.
+   */
+  async login(email: string, password: string, totpCode?: string): Promise<AuthResponse> {
+    const auth = await unwrap(
+      this.api.POST("/auth/login", { body: { email, password, totp_code: totpCode } }),
+    );
+    this.token = auth.token;
+    return auth;
+  }
+
+  /**
+   * Exchanges a CI workflow's OIDC token for a short-lived session that can only deploy
+   * into `namespace`
+   */
+  async exchangeOidcToken(token: string, namespace: string): Promise<AuthResponse> {
+    const auth = await unwrap(
+      this.api.POST("/auth/oidc/exchange", { body: { token, namespace } }),
+    );
+    this.token = auth.token;
+    return auth;
+  }
+
+  /** Starts enrolling in two-factor authentication */
+  async enrollTotp(): Promise<TotpEnrollment> {
+    return unwrap(this.api.POST("/auth/totp/enroll"));
+  }
+
+  /** Confirms the enrollment with a code from the app; returns the recovery codes */
+  async enableTotp(code: string): Promise<string[]> {
+    const enabled = await unwrap(this.api.POST("/auth/totp/enable", { body: { code } }));
+    return enabled.recovery_codes;
+  }
+
+  /** Turns two-factor authentication off, with a code from the app or a recovery code */
+  async disableTotp(code: string): Promise<void> {
+    await unwrap(this.api.POST("/auth/totp/disable", { body: { code } }));
+  }
+
+  /** Functions of the session's namespace */
+  async listFunctions(): Promise<FunctionSummary[]> {
+    return unwrap(this.api.GET("/invok/list"));
+  }
+
+  /** Settings, pool and health of a function */
+  async status(namespace: string, name: string): Promise<FunctionStatus> {
+    return unwrap(
+      this.api.GET("/invok/status/{namespace}/{function_name}", {
+        params: { path: { namespace, function_name: name } },
+      }),
+    );
+  }
+
+  /** Moves a function to the trash */
+  async deleteFunction(name: string): Promise<TrashedFunction> {
+    return unwrap(
+      this.api.DELETE("/invok/delete/{function_name}", {
+        params: { path: { function_name: name } },
+      }),
+    );
+  }
+
+  /** Deleted functions that can still be restored */
+  async listTrash(): Promise<TrashedFunction[]> {
+    return unwrap(this.api.GET("/invok/trash"));
+  }
+
+  /** Serves a function from the trash again */
+  async restoreFunction(name: string): Promise<{ name: string; runtime: string }> {
+    return unwrap(
+      this.api.POST("/invok/restore/{function_name}", {
+        params: { path: { function_name: name } },
+      }),
+    );
+  }
+
+  /** Preview instances of the namespace's functions */
+  async listPreviews(): Promise<PreviewInstance[]> {
+    return unwrap(this.api.GET("/invok/previews"));
+  }
+
+  /** Removes the preview instance of a function for a branch */
+  async deletePreview(name: string, branch: string): Promise<PreviewInstance> {
+    return unwrap(
+      this.api.DELETE("/invok/previews/{function_name}/{branch}", {
+        params: { path: { function_name: name, branch } },
+      }),
+    );
+  }
+
+  /**
+   * Builds and deploys a function from a ZIP archive of its source. Resolves once the
+   * controller has built the image, which can take minutes, or once the deploy is held
+   * for approval.
+   */
+  async deploy(name: string, archive: Blob, options: DeployOptions = {}): Promise<Deployment> {
+    const deployment = await this.submitDeploy(name, archive, options);
+    if (deployment.deployment !== undefined) {
+      const finished = await this.waitForDeployment(deployment.deployment);
+      deployment.message = finished.message ?? "";
+    }
+    return deployment;
+  }
+
+  /**
+   * Uploads a function to deploy without waiting for the build. The controller queues
+   * the deploy, which carries on if it restarts; follow it with `waitForDeployment`.
+   */
+  async submitDeploy(
+    name: string,
+    archive: Blob,
+    options: DeployOptions = {},
+  ): Promise<Deployment> {
+    // The server needs the preview branch and the force flag before the archive
+    const form = new FormData();
+    if (options.preview !== undefined) {
+      form.append("preview", options.preview);
+    }
+    if (options.force) {
+      form.append("force", "true");
+    }
+    form.append("file", new Blob([archive], { type: "application/zip" }), `${name}.zip`);
+
+    const response = await this.send("/invok/deploy", { method: "POST", body: form });
+    const message = await response.text();
+    const field = (prefix: string) =>
+      message
+        .split("\n")
+        .find((line) => line.startsWith(prefix))
+        ?.slice(prefix.length);
+    // Previews are served under the instance name the server picked
+    const deployed = field("Function: ");
+    const queued = field("Deployment: ");
+    const approval = field("Approval: ");
+    return {
+      name: deployed ?? name,
+      message,
+      deployment: queued === undefined ? undefined : Number(queued),
+      pendingApproval: approval === undefined ? undefined : Number(approval),
+    };
+  }
+
+  /** Where a queued deploy of the session's namespace is at */
+  async deployment(id: number): Promise<DeploymentStatus> {
+    return unwrap(this.api.GET("/invok/deployments/{id}", { params: { path: { id } } }));
+  }
+
+  /**
+   * Resolves once a queued deploy succeeded, looking it up every couple of seconds.
+   * Throws an `InvokError` if it failed.
+   */
+  async waitForDeployment(id: number): Promise<DeploymentStatus> {
+    for (;;) {
+      const deployment = await this.deployment(id);
+      if (deployment.status === "succeeded") {
+        return deployment;
+      }
+      if (deployment.status === "failed") {
+        throw new InvokError(500, `Failed to deploy function: ${deployment.message ?? ""}`);
+      }
+      await new Promise((resolve) => setTimeout(resolve, DEPLOY_POLL_INTERVAL_MS));
+    }
+  }
+
+  /**
+   * Streams the log lines of a function's containers until the controller ends the
+   * stream or `signal` aborts it
+   */
+  async *logs(namespace: string, name: string, signal?: AbortSignal): AsyncGenerator<string> {
+    const path = `/invok/logs/${encodeURIComponent(namespace)}/${encodeURIComponent(name)}`;
+    const response = await this.send(path, {
+      headers: { Accept: "text/event-stream" },
+      signal,
+    });
+    if (response.body) {
+      yield* readEvents(response.body);
+    }
+  }
+
+  /**
+   * Sends a request to a function. Any answer of the function, error statuses included,
+   * is returned as is.
+   */
+  async invoke(namespace: string, name: string, init: RequestInit = {}): Promise<Response> {
+    return this.fetch(this.functionUrl(namespace, name), init);
+  }
+
+  /** Address functions of a namespace are invoked at */
+  functionUrl(namespace: string, name: string): string {
+    return `${this.baseUrl}/invok/${encodeURIComponent(namespace)}/${encodeURIComponent(name)}`;
+  }
+
+  /** Sends a request with the session's token, failing on anything but success */
+  private async send(path: string, init: RequestInit): Promise<Response> {
+    if (!this.token) {
+      throw new InvokError(401, "Not logged in");
+    }
+    const headers = new Headers(init.headers);
+    headers.set("Authorization", `Bearer ${this.token}`);
+    const response = await this.fetch(`${this.baseUrl}${path}`, { ...init, headers });
+    if (!response.ok) {
+      throw InvokError.from(response.status, await response.text());
+    }
+    return response;
+  }
+}
+
+async function unwrap<T>(answer: Promise<Answer<T>>): Promise<T> {
+  const { data, error, response } = await answer;
+  if (!response.ok) {
+    throw InvokError.from(response.status, error);
+  }
+  return data as T;
+}
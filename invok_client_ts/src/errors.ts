@@ -0,0 +1,27 @@
+/** An answer of the controller other than success */
+export class InvokError extends Error {
+  /** HTTP status of the answer */
+  readonly status: number;
+  /** Set when a login needs a two-factor code; retry with `totpCode` */
+  readonly totpRequired: boolean;
+
+  constructor(status: number, message: string, totpRequired = false) {
+    super(message);
+    this.name = "InvokError";
+    this.status = status;
+    this.totpRequired = totpRequired;
+  }
+
+  /**
+   * Builds the error of a failed answer. Auth routes answer with `{ error }` objects,
+   * the others with plain text.
+   */
+  static from(status: number, body: unknown): InvokError {
+    if (typeof body === "object" && body !== null && "error" in body) {
+      const { error, totp_required } = body as { error: unknown; totp_required?: unknown };
+      return new InvokError(status, String(error), totp_required === true);
+    }
+    const message = typeof body === "string" && body.length > 0 ? body : `HTTP ${status}`;
+    return new InvokError(status, message);
+  }
+}
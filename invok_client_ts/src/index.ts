@@ -0,0 +1,17 @@
+export {
+  InvokClient,
+  type AuthResponse,
+  type Deployment,
+  type DeploymentStatus,
+  type DeployOptions,
+  type FunctionStatus,
+  type FunctionSummary,
+  type InvokClientOptions,
+  type PreviewInstance,
+  type TotpEnrollment,
+  type TrashedFunction,
+  type User,
+} from "./client.js";
+export { InvokError } from "./errors.js";
+export { readEvents } from "./logs.js";
+export type { components, paths } from "./schema.js";
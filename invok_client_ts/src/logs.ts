@@ -0,0 +1,50 @@
+/**
+ * Yields the data of each server-sent event of a log stream, until the stream ends or
+ * is aborted. Multi-line data is joined with newlines, like the Rust client does.
+ */
+export async function* readEvents(body: ReadableStream<Uint8Array>): AsyncGenerator<string> {
+  const reader = body.pipeThrough(new TextDecoderStream()).getReader();
+  let buffered = "";
+  let data: string | undefined;
+
+  try {
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) {
+        break;
+      }
+      buffered += value;
+
+      let newline: number;
+      while ((newline = buffered.indexOf("\n")) >= 0) {
+        const line = buffered.slice(0, newline).replace(/\r$/, "");
+        buffered = buffered.slice(newline + 1);
+
+        if (line === "") {
+          if (data !== undefined) {
+            yield data;
+            data = undefined;
+          }
+          continue;
+        }
+        // Comments (keep-alives), event names and ids carry no log
+        if (!line.startsWith("data:")) {
+          continue;
+        }
+        const field = line.slice("data:".length).replace(/^ /, "");
+        data = data === undefined ? field : `${data}\n${field}`;
+      }
+    }
+  } finally {
+    reader.releaseLock();
+  }
+
+  // An event cut short by the end of the stream is still delivered
+  if (buffered.startsWith("data:")) {
+    const field = buffered.slice("data:".length).replace(/^ /, "");
+    data = data === undefined ? field : `${data}\n${field}`;
+  }
+  if (data !== undefined) {
+    yield data;
+  }
+}
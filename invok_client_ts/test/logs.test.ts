@@ -0,0 +1,76 @@
+import assert from "node:assert/strict";
+import { test } from "node:test";
+import { readEvents } from "../src/logs.js";
+
+/** A response body delivering `chunks` one read at a time */
+function body(...chunks: (string | Uint8Array)[]): ReadableStream<Uint8Array> {
+  const encoder = new TextEncoder();
+  return new ReadableStream({
+    start(controller) {
+      for (const chunk of chunks) {
+        controller.enqueue(typeof chunk === "string" ? encoder.encode(chunk) : chunk);
+      }
+      controller.close();
+    },
+  });
+}
+
+async function events(stream: ReadableStream<Uint8Array>): Promise<string[]> {
+  const received: string[] = [];
+  for await (const data of readEvents(stream)) {
+    received.push(data);
+  }
+  return received;
+}
+
+test("yields the data of each event", async () => {
+  assert.deepEqual(await events(body("data: one\n\ndata: two\n\n")), ["one", "two"]);
+});
+
+test("joins multi-line data with newlines", async () => {
+  assert.deepEqual(await events(body("data: first\ndata: second\n\n")), ["first\nsecond"]);
+});
+
+test("reassembles events split across reads", async () => {
+  assert.deepEqual(await events(body("da", "ta: spl", "it\n", "\ndata: next\n\n")), [
+    "split",
+    "next",
+  ]);
+});
+
+test("decodes characters split across reads", async () => {
+  const encoded = new TextEncoder().encode("data: héllo ✓\n\n");
+  const cut = encoded.indexOf(0xc3) + 1;
+  assert.deepEqual(await events(body(encoded.slice(0, cut), encoded.slice(cut))), [
+    "héllo ✓",
+  ]);
+});
+
+test("accepts CRLF line endings", async () => {
+  assert.deepEqual(await events(body("data: one\r\ndata: two\r\n\r\n")), ["one\ntwo"]);
+});
+
+test("strips a single space after the colon only", async () => {
+  assert.deepEqual(await events(body("data:tight\n\ndata:  indented\n\n")), [
+    "tight",
+    " indented",
+  ]);
+});
+
+test("skips comments, event names and ids", async () => {
+  const stream = body(": keep-alive\n\nevent: log\nid: 7\ndata: line\nretry: 1000\n\n");
+  assert.deepEqual(await events(stream), ["line"]);
+});
+
+test("yields empty data lines", async () => {
+  assert.deepEqual(await events(body("data:\n\ndata: \ndata: x\n\n")), ["", "\nx"]);
+});
+
+test("delivers an event cut short by the end of the stream", async () => {
+  assert.deepEqual(await events(body("data: one\n\ndata: two\ndata: three")), [
+    "one",
+    "two\nthree",
+  ]);
+  assert.deepEqual(await events(body("data: pending\n")), ["pending"]);
+  assert.deepEqual(await events(body("")), []);
+});
@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory secrets mounted as files are read from
+const SECRETS_DIR: &str = "/run/secrets";
+
+/// Where images keep the bundle's data files, unless `INVOK_DATA_DIR` says otherwise
+const DATA_DIR: &str = "/invok/data";
+
+/// Namespace of the function
+pub fn namespace() -> Option<String> {
+    env("INVOK_NAMESPACE")
+}
+
+/// Name of the function
+pub fn function_name() -> Option<String> {
+    env("INVOK_FUNCTION")
+}
+
+/// Version of the function, counting its deploys
+pub fn function_version() -> Option<u32> {
+    env("INVOK_FUNCTION_VERSION")?.parse().ok()
+}
+
+/// Reads an environment variable of the function, e.g. one set in its `config.json`
+pub fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+/// Reads an environment variable of the function, or `default` when it's unset
+pub fn env_or(key: &str, default: &str) -> String {
+    env(key).unwrap_or_else(|| default.to_string())
+}
+
+/// Reads a secret: the environment variable `key`, or else the file `/run/secrets/<key>`
+/// where mounted secrets live. Surrounding whitespace is trimmed and empty secrets are
+/// treated as unset.
+pub fn secret(key: &str) -> Option<String> {
+    secret_in(key, Path::new(SECRETS_DIR))
+}
+
+/// Directory holding the files of the bundle's `data/` directory, read-only
+pub fn data_dir() -> PathBuf {
+    PathBuf::from(env_or("INVOK_DATA_DIR", DATA_DIR))
+}
+
+/// Path of a file of the bundle's `data/` directory, e.g. `data_file("models/en.bin")`.
+/// `None` for paths that would leave it.
+pub fn data_file(path: &str) -> Option<PathBuf> {
+    data_file_in(path, &data_dir())
+}
+
+fn data_file_in(path: &str, dir: &Path) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let inside = path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    inside.then(|| dir.join(path))
+}
+
+fn secret_in(key: &str, dir: &Path) -> Option<String> {
+    env(key)
+        .or_else(|| {
+            // Keys are plain names; don't let one wander out of the secrets directory
+            if key.is_empty() || key.contains(['/', '\\']) || key.starts_with('.') {
+                return None;
+            }
+            fs::read_to_string(dir.join(key)).ok()
+        })
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_falls_back_to_file() {
+        let dir = std::env::temp_dir().join(format!("invok-sdk-secrets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("INVOK_SDK_TEST_SECRET"), "s3cret\n").unwrap();
+
+        assert_eq!(
+            secret_in("INVOK_SDK_TEST_SECRET", &dir).as_deref(),
+            Some("s3cret")
+        );
+        assert_eq!(secret_in("INVOK_SDK_TEST_MISSING", &dir), None);
+        assert_eq!(secret_in("../INVOK_SDK_TEST_SECRET", &dir), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_stays_in_data_dir() {
+        let dir = Path::new("/invok/data");
+
+        assert_eq!(
+            data_file_in("models/en.bin", dir),
+            Some(PathBuf::from("/invok/data/models/en.bin"))
+        );
+        assert_eq!(data_file_in("../config.json", dir), None);
+        assert_eq!(data_file_in("/etc/passwd", dir), None);
+        assert_eq!(data_file_in("", dir), None);
+    }
+}
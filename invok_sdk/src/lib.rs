@@ -0,0 +1,44 @@
+//! Runtime SDK for Rust functions deployed on invok.
+//!
+//! A function is a single handler marked with [`handler`]; the macro generates the `main`
+//! serving it, so the function compiles to a thin binary that:
+//! - listens on `PORT` (8080 by default) and signals the controller once it accepts
+//!   connections,
+//! - logs each request, and whatever the handler logs through [`info!`] and friends, as JSON
+//!   lines tagged with the request's id,
+//! - runs the optional init and shutdown [`Hooks`] when the controller starts and stops
+//!   the container,
+//! - shuts down gracefully on `SIGTERM`.
+//!
+//! The `env` of the function's `config.json` reaches the handler as environment variables,
+//! read with [`env`], [`env_or`] and [`secret`]. The invocation context comes with every
+//! [`Request`] (its id and deadline) and from [`namespace`], [`function_name`] and
+//! [`function_version`]. Files of the bundle's `data/` directory are found with [`data_file`].
+//!
+//! ```ignore
+//! use invok::{Request, Response};
+//!
+//! #[invok::handler]
+//! async fn hello(request: Request) -> Response {
+//!     let name = request.query("name").unwrap_or("someone");
+//!     invok::info!("greeting {}", name);
+//!     Response::json(&serde_json::json!({ "message": format!("{name} says Hello") }))
+//! }
+//! ```
+
+mod env;
+mod lifecycle;
+mod log;
+mod request;
+mod response;
+mod server;
+
+pub use env::{
+    data_dir, data_file, env, env_or, function_name, function_version, namespace, secret,
+};
+pub use invok_sdk_macros::handler;
+pub use lifecycle::Hooks;
+pub use log::{log, Level};
+pub use request::Request;
+pub use response::{IntoResponse, Response};
+pub use server::{serve, serve_with_hooks};
@@ -0,0 +1,99 @@
+use crate::log::{write_line, Level};
+use crate::response::Response;
+use serde_json::Map;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Called by the controller once the container is ready, before it serves invocations
+pub(crate) const INIT_PATH: &str = "/__invok/init";
+
+/// Called by the controller before the container is removed
+pub(crate) const SHUTDOWN_PATH: &str = "/__invok/shutdown";
+
+type Hook = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// Code run when the controller starts and stops a container, set with
+/// `#[invok::handler(init = ..., shutdown = ...)]`.
+///
+/// Init runs before the container receives invocations, e.g. to open connections; an
+/// error fails the start and is reported in the function's boot logs. Shutdown runs
+/// before the container is removed, to release what init opened. Both succeed without
+/// doing anything when unset.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    init: Option<Hook>,
+    shutdown: Option<Hook>,
+}
+
+impl Hooks {
+    /// No hooks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `hook` when the container starts
+    pub fn on_init<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.init = Some(Arc::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Runs `hook` before the container is removed
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.shutdown = Some(Arc::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Answers the controller's call to a lifecycle path, or `None` for other paths
+    pub(crate) async fn handle(&self, path: &str) -> Option<Response> {
+        let (name, hook) = match path {
+            INIT_PATH => ("init", &self.init),
+            SHUTDOWN_PATH => ("shutdown", &self.shutdown),
+            _ => return None,
+        };
+        let result = match hook {
+            Some(hook) => hook().await,
+            None => Ok(()),
+        };
+        Some(match result {
+            Ok(()) => Response::new(200),
+            Err(e) => {
+                write_line(
+                    Level::Error,
+                    &format!("The {name} hook failed: {e}"),
+                    Map::new(),
+                );
+                Response::text(e).with_status(500)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unset_hooks_succeed() {
+        let hooks = Hooks::new();
+        assert_eq!(hooks.handle(INIT_PATH).await, Some(Response::new(200)));
+        assert_eq!(hooks.handle(SHUTDOWN_PATH).await, Some(Response::new(200)));
+        assert_eq!(hooks.handle("/hello").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_init_answers_the_error() {
+        let hooks = Hooks::new().on_init(|| async { Err("database unreachable".to_string()) });
+        let response = hooks.handle(INIT_PATH).await.unwrap();
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.body(), b"database unreachable");
+    }
+}
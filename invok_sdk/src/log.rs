@@ -0,0 +1,93 @@
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+
+tokio::task_local! {
+    /// Id of the request being handled, attached to everything logged while handling it
+    static REQUEST_ID: Option<String>;
+}
+
+/// Severity of a log line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        })
+    }
+}
+
+/// Logs `message` as a JSON line on stdout, where the controller collects function logs.
+///
+/// Lines logged while handling a request carry its `request_id`.
+pub fn log(level: Level, message: &str) {
+    write_line(level, message, Map::new());
+}
+
+/// Logs a line with extra fields
+pub(crate) fn write_line(level: Level, message: &str, fields: Map<String, Value>) {
+    let mut line = Map::new();
+    line.insert("level".to_string(), json!(level.to_string()));
+    line.insert("message".to_string(), json!(message));
+    if let Some(request_id) = current_request_id() {
+        line.insert("request_id".to_string(), json!(request_id));
+    }
+    line.extend(fields);
+
+    // Write the line in one go so concurrent requests don't interleave
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", Value::Object(line));
+    let _ = stdout.flush();
+}
+
+/// Runs `future` with `request_id` attached to what it logs
+pub(crate) async fn with_request_id<F: Future>(request_id: Option<String>, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok().flatten()
+}
+
+/// Logs a `debug` line, formatted like `format!`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log($crate::Level::Debug, &format!($($arg)*))
+    };
+}
+
+/// Logs an `info` line, formatted like `format!`
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log($crate::Level::Info, &format!($($arg)*))
+    };
+}
+
+/// Logs a `warn` line, formatted like `format!`
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log($crate::Level::Warn, &format!($($arg)*))
+    };
+}
+
+/// Logs an `error` line, formatted like `format!`
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log($crate::Level::Error, &format!($($arg)*))
+    };
+}
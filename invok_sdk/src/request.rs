@@ -0,0 +1,175 @@
+use hyper::http::request::Parts;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Headers the request's id is read from, in order of preference
+const REQUEST_ID_HEADERS: &[&str] = &["x-request-id", "x-correlation-id"];
+
+/// Header the controller sets to when it stops waiting, in milliseconds since the Unix epoch
+const DEADLINE_HEADER: &str = "x-invok-deadline";
+
+/// An invocation of the function, forwarded by the controller
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// Keyed by lowercase name
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    pub(crate) fn from_parts(parts: &Parts, body: Vec<u8>) -> Self {
+        let query = parts
+            .uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let headers = parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.as_str().to_string(), value.to_string()))
+            })
+            .collect();
+
+        Self {
+            method: parts.method.to_string(),
+            path: parts.uri.path().to_string(),
+            query,
+            headers,
+            body,
+        }
+    }
+
+    /// HTTP method, e.g. `GET`
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Path the function was invoked on, e.g. `/hello`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Decoded value of a query parameter
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+
+    /// All decoded query parameters
+    pub fn queries(&self) -> &HashMap<String, String> {
+        &self.query
+    }
+
+    /// Value of a header, looked up case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Id of the request, when the caller or a proxy in front of the controller set one
+    pub fn request_id(&self) -> Option<&str> {
+        REQUEST_ID_HEADERS
+            .iter()
+            .find_map(|name| self.header(name))
+            .filter(|id| !id.is_empty())
+    }
+
+    /// When the controller stops waiting for the response
+    pub fn deadline(&self) -> Option<SystemTime> {
+        let millis: u64 = self.header(DEADLINE_HEADER)?.parse().ok()?;
+        Some(SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    /// Time left until the [deadline](Request::deadline), zero once it passed
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.deadline().map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Raw body
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Body as UTF-8 text
+    pub fn text(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.body)
+    }
+
+    /// Body parsed as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)], body: &str) -> Request {
+        let mut builder = hyper::Request::builder().method("POST").uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let (parts, _) = builder.body(()).unwrap().into_parts();
+        Request::from_parts(&parts, body.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_request_from_parts() {
+        let request = request(
+            "/hello?name=Ada%20Lovelace&lang=en",
+            &[
+                ("X-Request-Id", "abc123"),
+                ("Content-Type", "application/json"),
+            ],
+            r#"{"count": 2}"#,
+        );
+
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.path(), "/hello");
+        assert_eq!(request.query("name"), Some("Ada Lovelace"));
+        assert_eq!(request.query("missing"), None);
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.request_id(), Some("abc123"));
+
+        let body: serde_json::Value = request.json().unwrap();
+        assert_eq!(body["count"], 2);
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let expired = request("/hello", &[("X-Invok-Deadline", "1700000000000")], "");
+        assert_eq!(
+            expired.deadline(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(expired.time_remaining(), Some(Duration::ZERO));
+
+        assert_eq!(request("/hello", &[], "").deadline(), None);
+    }
+
+    #[test]
+    fn test_request_id_is_optional() {
+        assert_eq!(request("/hello", &[], "").request_id(), None);
+        assert_eq!(
+            request("/hello", &[("X-Correlation-Id", "xyz")], "").request_id(),
+            Some("xyz")
+        );
+    }
+}
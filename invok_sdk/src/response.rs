@@ -0,0 +1,179 @@
+use crate::log::{log, Level};
+use hyper::Body;
+use serde::Serialize;
+use std::fmt::Display;
+
+/// What the function answers an invocation with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// An empty response with `status`
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `200` plain text response
+    pub fn text(body: impl Into<String>) -> Self {
+        Self::new(200)
+            .with_header("content-type", "text/plain; charset=utf-8")
+            .with_body(body.into().into_bytes())
+    }
+
+    /// A `200` JSON response; a `500` when `value` can't be serialized
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self::new(200)
+                .with_header("content-type", "application/json")
+                .with_body(body),
+            Err(e) => {
+                log(
+                    Level::Error,
+                    &format!("Failed to serialize the response: {e}"),
+                );
+                Self::new(500)
+            }
+        }
+    }
+
+    /// Replaces the status code
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Sets a header, replacing an earlier one of the same name
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Replaces the body
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub(crate) fn into_hyper(self) -> hyper::Response<Body> {
+        let mut builder = hyper::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder.body(Body::from(self.body)).unwrap_or_else(|e| {
+            log(Level::Error, &format!("Invalid response: {e}"));
+            let mut response = hyper::Response::new(Body::empty());
+            *response.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        })
+    }
+}
+
+/// Anything a handler can return
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response {
+        Response::new(204)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for serde_json::Value {
+    fn into_response(self) -> Response {
+        Response::json(&self)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (u16, T) {
+    fn into_response(self) -> Response {
+        self.1.into_response().with_status(self.0)
+    }
+}
+
+/// Errors are logged and answered with a `500`, keeping their details out of the response
+impl<T: IntoResponse, E: Display> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(response) => response.into_response(),
+            Err(e) => {
+                log(Level::Error, &format!("Handler failed: {e}"));
+                Response::text("Internal Server Error").with_status(500)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_response() {
+        let response = (201u16, "created").into_response();
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.body(), b"created");
+
+        let response = serde_json::json!({ "ok": true }).into_response();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers(),
+            &[("content-type".to_string(), "application/json".to_string())]
+        );
+
+        let response = Err::<String, _>("boom").into_response();
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.body(), b"Internal Server Error");
+
+        assert_eq!(().into_response().status(), 204);
+    }
+
+    #[test]
+    fn test_with_header_replaces() {
+        let response = Response::text("hi").with_header("Content-Type", "text/html");
+        assert_eq!(
+            response.headers(),
+            &[("Content-Type".to_string(), "text/html".to_string())]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::env::env_or;
+use crate::lifecycle::Hooks;
+use crate::log::{with_request_id, write_line, Level};
+use crate::request::Request;
+use crate::response::{IntoResponse, Response};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Server};
+use serde_json::{json, Map};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Printed once the function accepts connections; the controller waits for it before
+/// routing invocations to the container
+const READY_MARKER: &str = "<<READY_TO_ACCEPT_CONN>>";
+
+/// Port listened on when `PORT` is unset
+const DEFAULT_PORT: &str = "8080";
+
+/// Serves `handler` until the container is stopped; generated as `main` by
+/// [`handler`](crate::handler).
+///
+/// Exits the process when the port can't be listened on.
+pub fn serve<F, Fut, R>(handler: F)
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse + Send + 'static,
+{
+    serve_with_hooks(handler, Hooks::new());
+}
+
+/// Serves `handler` like [`serve`], running `hooks` when the controller starts and stops
+/// the container
+pub fn serve_with_hooks<F, Fut, R>(handler: F, hooks: Hooks)
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse + Send + 'static,
+{
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => fail(&format!("Failed to start the runtime: {e}")),
+    };
+    if let Err(e) = runtime.block_on(run(Arc::new(handler), Arc::new(hooks))) {
+        fail(&e);
+    }
+}
+
+async fn run<F, Fut, R>(handler: Arc<F>, hooks: Arc<Hooks>) -> Result<(), String>
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse + Send + 'static,
+{
+    let port = env_or("PORT", DEFAULT_PORT);
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("PORT must be a port number, got '{port}'"))?;
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|e| format!("Failed to listen on port {port}: {e}"))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to listen on port {port}: {e}"))?;
+
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        let hooks = hooks.clone();
+        let service = service_fn(move |request| handle(handler.clone(), hooks.clone(), request));
+        async move { Ok::<_, Infallible>(service) }
+    });
+    let server = Server::from_tcp(listener)
+        .map_err(|e| format!("Failed to listen on port {port}: {e}"))?
+        .serve(make_service);
+
+    // Signal the process fully started
+    println!("{}", READY_MARKER);
+    write_line(
+        Level::Info,
+        &format!("Function listening on port {port}"),
+        Map::new(),
+    );
+
+    server
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .map_err(|e| format!("Server error: {e}"))?;
+    write_line(Level::Info, "Function stopped", Map::new());
+    Ok(())
+}
+
+/// Runs the handler on one request and logs how it went
+async fn handle<F, Fut, R>(
+    handler: Arc<F>,
+    hooks: Arc<Hooks>,
+    request: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, Infallible>
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoResponse + Send + 'static,
+{
+    // Lifecycle calls from the controller never reach the handler
+    if request.method() == Method::POST {
+        if let Some(response) = hooks.handle(request.uri().path()).await {
+            return Ok(response.into_hyper());
+        }
+    }
+
+    let started = Instant::now();
+    let (parts, body) = request.into_parts();
+    let request = match hyper::body::to_bytes(body).await {
+        Ok(body) => Request::from_parts(&parts, body.to_vec()),
+        Err(e) => {
+            write_line(
+                Level::Warn,
+                &format!("Failed to read the request body: {e}"),
+                Map::new(),
+            );
+            return Ok(Response::new(400).into_hyper());
+        }
+    };
+    let request_id = request.request_id().map(str::to_string);
+    let method = request.method().to_string();
+    let path = request.path().to_string();
+
+    let future = handler(request);
+    with_request_id(request_id.clone(), async move {
+        // Run the handler on its own task, so a panic fails the request rather than the server
+        let task = with_request_id(request_id, async move { future.await.into_response() });
+        let response = match tokio::spawn(task).await {
+            Ok(response) => response,
+            Err(e) => {
+                write_line(Level::Error, &format!("Handler panicked: {e}"), Map::new());
+                Response::text("Internal Server Error").with_status(500)
+            }
+        };
+
+        let mut fields = Map::new();
+        fields.insert("method".to_string(), json!(method));
+        fields.insert("path".to_string(), json!(path));
+        fields.insert("status".to_string(), json!(response.status()));
+        fields.insert(
+            "duration_ms".to_string(),
+            json!(started.elapsed().as_millis() as u64),
+        );
+        write_line(Level::Info, "Request handled", fields);
+
+        Ok(response.into_hyper())
+    })
+    .await
+}
+
+/// Resolves on `SIGTERM`, sent when the container is stopped, or Ctrl+C
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    write_line(Level::Info, "Shutting down", Map::new());
+}
+
+fn fail(message: &str) -> ! {
+    write_line(Level::Error, message, Map::new());
+    std::process::exit(1);
+}
@@ -0,0 +1,93 @@
+//! Procedural macros of `invok-sdk`; use them through the `invok` crate.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Error, ItemFn, Path};
+
+/// Turns a function into the entrypoint of an invok function.
+///
+/// The function takes an `invok::Request`, or nothing, and returns anything implementing
+/// `invok::IntoResponse`; it may be `async`. A `main` serving it is generated next to it:
+///
+/// ```ignore
+/// #[invok::handler]
+/// async fn hello(request: invok::Request) -> String {
+///     format!("{} says Hello", request.query("name").unwrap_or("someone"))
+/// }
+/// ```
+///
+/// `init` and `shutdown` name async functions returning `Result<(), String>`, run when the
+/// controller starts and stops a container (see `invok::Hooks`):
+///
+/// ```ignore
+/// #[invok::handler(init = connect, shutdown = disconnect)]
+/// async fn hello(request: invok::Request) -> String { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn handler(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut init: Option<Path> = None;
+    let mut shutdown: Option<Path> = None;
+    let hooks_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("init") {
+            init = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("shutdown") {
+            shutdown = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("#[invok::handler] takes `init = ...` and `shutdown = ...`"))
+        }
+    });
+    parse_macro_input!(args with hooks_parser);
+
+    let function = parse_macro_input!(item as ItemFn);
+    let signature = &function.sig;
+    if signature.ident == "main" {
+        return Error::new(
+            signature.ident.span(),
+            "the handler can't be named `main`, one is generated for it",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if !signature.generics.params.is_empty() {
+        return Error::new(signature.generics.span(), "the handler can't be generic")
+            .to_compile_error()
+            .into();
+    }
+    if signature.inputs.len() > 1 {
+        return Error::new(
+            signature.inputs.span(),
+            "the handler takes an `invok::Request` or nothing",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let name = &signature.ident;
+    let (request, call) = if signature.inputs.is_empty() {
+        (quote! { _request }, quote! { #name() })
+    } else {
+        (quote! { request }, quote! { #name(request) })
+    };
+    let call = if signature.asyncness.is_some() {
+        call
+    } else {
+        quote! { async move { #call } }
+    };
+
+    let init = init.map(|init| quote! { .on_init(#init) });
+    let shutdown = shutdown.map(|shutdown| quote! { .on_shutdown(#shutdown) });
+
+    quote! {
+        #function
+
+        fn main() {
+            ::invok::serve_with_hooks(
+                |#request: ::invok::Request| #call,
+                ::invok::Hooks::new() #init #shutdown,
+            );
+        }
+    }
+    .into()
+}
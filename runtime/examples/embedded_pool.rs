@@ -0,0 +1,95 @@
+//! Runs the containers of one image in an autoscaling pool and sends them a few requests,
+//! without the invok controller.
+//!
+//! ```bash
+//! cargo run -p runtime --example embedded_pool -- my-image:latest
+//! ```
+//!
+//! The image serves HTTP on port 8080 and prints `runtime::FULL_START_MSG` once it accepts
+//! connections. Docker has to be reachable; Redis and Prometheus aren't needed.
+
+use futures_util::future::{join_all, BoxFuture};
+use runtime::{AppResult, AutoscalingRuntimeBuilder, ContainerMetrics, FunctionPolicy};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Reports every container as lightly loaded; with single concurrency the pool scales on
+/// the requests waiting for a container instead
+struct FixedMetrics;
+
+impl ContainerMetrics for FixedMetrics {
+    fn cpu_usage<'a>(&'a self, _container_id: &'a str) -> BoxFuture<'a, AppResult<f64>> {
+        Box::pin(async { Ok(20.0) })
+    }
+
+    fn memory_usage<'a>(&'a self, _container_id: &'a str) -> BoxFuture<'a, AppResult<f64>> {
+        Box::pin(async { Ok(30.0) })
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async { true })
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let image = std::env::args()
+        .nth(1)
+        .ok_or("usage: embedded_pool <image>")?;
+
+    let runtime = AutoscalingRuntimeBuilder::new()
+        .min_containers_per_function(0)
+        .max_containers_per_function(3)
+        .scale_check_interval(Duration::from_secs(2))
+        .persistence_enabled(false)
+        .metrics(Arc::new(FixedMetrics))
+        .build()
+        .await?;
+    runtime.start().await?;
+
+    // Pools are keyed by the image their containers run
+    let autoscaler = runtime.autoscaler().clone();
+    autoscaler.set_function_policy(
+        &image,
+        FunctionPolicy {
+            single_concurrency: true,
+            ..Default::default()
+        },
+    );
+
+    let requests = (0..6).map(|request| {
+        let autoscaler = autoscaler.clone();
+        let image = image.clone();
+        async move {
+            // The container goes back to the pool when the lease is dropped
+            let lease = match autoscaler.get_container_for_invocation(&image, None).await {
+                Ok(lease) => lease,
+                Err(e) => {
+                    println!("request {request}: no container available: {e}");
+                    return;
+                }
+            };
+            let url = format!("http://{}/", lease.address().authority());
+            match reqwest::get(&url).await {
+                Ok(response) => println!(
+                    "request {request}: {} from {}",
+                    response.status(),
+                    lease.address().container_name
+                ),
+                Err(e) => println!("request {request}: {e}"),
+            }
+        }
+    });
+    join_all(requests).await;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&autoscaler.get_all_pool_status())?
+    );
+
+    // Shutting down leaves containers running for the next start to adopt
+    let removed = autoscaler.remove_function(&image).await;
+    println!("removed {removed} containers");
+    runtime.shutdown().await?;
+    Ok(())
+}
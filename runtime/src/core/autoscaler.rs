@@ -1,18 +1,119 @@
-use crate::core::container_manager::{ContainerPool, MonitoringConfig};
+use crate::core::container_manager::{
+    ContainerAddress, ContainerLease, ContainerPool, MonitoringConfig,
+};
+use crate::core::deadlines::ExecutionDeadlines;
+use crate::core::dev::DevContainers;
+use crate::core::diagnostics::{collect_crash_report, BootLog, CrashReport};
+use crate::core::egress::{namespace_of, EgressConfig, EgressGateway};
+use crate::core::environment::AddressMode;
+use crate::core::events::{ContainerEvent, ContainerEventWatcher};
+use crate::core::exec::ExecSession;
+use crate::core::fairness::{CapacityStatus, FairScheduler, FairnessConfig};
+use crate::core::freeze::{FreezeWindow, FreezeWindows};
+use crate::core::internal_api::InternalApiConfig;
 use crate::core::logs::{ContainerLogStreamer, LogMessage};
-use crate::core::metrics_client::MetricsClient;
-use crate::core::persistence::{AutoscalerPersistence, PersistenceConfig, PersistenceMetadata};
-use crate::core::runner::ContainerDetails;
-use crate::shared::error::AppResult;
+use crate::core::metrics_client::ContainerMetrics;
+use crate::core::persistence::{
+    AutoscalerPersistence, PersistedPoolState, PersistenceConfig, PersistenceMetadata, SaveOutcome,
+};
+use crate::core::policy::{FunctionPolicy, IdleStrategy};
+use crate::core::polling::{PollSchedule, ScanStats, ScanStatsSnapshot};
+use crate::core::replicas::{PoolCommand, ReplicaConfig, Replicas};
+use crate::core::restore::RestoreReport;
+use crate::core::runner::clean_up;
+use crate::core::sandbox::Sandbox;
+use crate::core::scaling_history::{ScalingAction, ScalingEvent, ScalingHistory};
+use crate::core::start_queue::StartPriority;
+use crate::core::supervisor::{Supervisor, TaskStatus};
+use crate::core::usage::{Recommendation, ResourceUsage};
+use crate::shared::error::{AppResult, RuntimeError};
 use bollard::Docker;
 use dashmap::DashMap;
+use futures_util::future::join_all;
 use futures_util::stream::Stream;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::interval;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, mpsc, watch, Semaphore};
+use tokio::time::{interval, timeout_at};
 use tracing::{debug, error, info, warn};
 
+/// How long a request waits for a free container of a function with a concurrency limit
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long containers paused by the pause idle strategy keep their memory before they
+/// are removed
+pub const PAUSED_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Anomalies kept for subscribers that fall behind
+const ANOMALY_BUFFER: usize = 64;
+
+/// How often a follower waiting for a container reads the pool state again
+const SHARED_STATE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the leader waits for a follower's command before checking it still leads
+const COMMAND_WAIT: Duration = Duration::from_secs(1);
+
+/// Saves of a pool state retried after another controller saved it in between
+const MAX_SAVE_ATTEMPTS: usize = 3;
+
+/// Something unexpected the autoscaler saw in a function's pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalingAnomaly {
+    /// A container exited on its own
+    ContainerDied {
+        function_key: String,
+        summary: String,
+    },
+    /// A container the pool needed could not be added
+    ScaleUpFailed {
+        function_key: String,
+        reason: String,
+    },
+}
+
+/// What happened in the pools: the last crash of each function, a feed of anomalies for
+/// subscribers and the recent scaling events of each function
+#[derive(Clone)]
+struct Incidents {
+    /// Most recent crash report per function key
+    crash_reports: Arc<DashMap<String, CrashReport>>,
+    anomalies: broadcast::Sender<ScalingAnomaly>,
+    history: Arc<ScalingHistory>,
+}
+
+impl Incidents {
+    fn new() -> Self {
+        Self {
+            crash_reports: Arc::new(DashMap::new()),
+            anomalies: broadcast::channel(ANOMALY_BUFFER).0,
+            history: Arc::new(ScalingHistory::new()),
+        }
+    }
+
+    fn record_crash(&self, function_key: String, report: CrashReport) {
+        // Nobody may be subscribed
+        let _ = self.anomalies.send(ScalingAnomaly::ContainerDied {
+            function_key: function_key.clone(),
+            summary: report.summary(),
+        });
+        self.crash_reports.insert(function_key, report);
+    }
+
+    fn record_failed_scale_up(&self, function_key: &str, containers: usize, reason: String) {
+        self.history.record(
+            function_key,
+            ScalingAction::ScaleUpFailed,
+            containers,
+            Some(reason.clone()),
+        );
+        let _ = self.anomalies.send(ScalingAnomaly::ScaleUpFailed {
+            function_key: function_key.to_string(),
+            reason,
+        });
+    }
+}
+
 /// Autoscaler configuration
 #[derive(Debug, Clone)]
 pub struct AutoscalerConfig {
@@ -20,6 +121,18 @@ pub struct AutoscalerConfig {
     pub min_containers_per_function: usize,
     pub max_containers_per_function: usize,
     pub scale_check_interval: Duration,
+    /// Longest time between checks of a pool without traffic; pools back off from
+    /// `scale_check_interval` to it while they stay idle
+    pub idle_scale_check_interval: Duration,
+    /// Pools checked at the same time during a scaling pass
+    pub max_parallel_pool_checks: usize,
+    /// How long reading a pool's metrics may take before its check is skipped for the pass
+    pub pool_check_timeout: Duration,
+    /// How long a request waits for a container of its function to start before it fails
+    /// as unavailable
+    pub cold_start_timeout: Duration,
+    /// Namespace and host limits shared by every function
+    pub fairness: FairnessConfig,
 }
 
 /// Main autoscaler that manages container pools for all functions
@@ -32,10 +145,38 @@ pub struct Autoscaler {
     config: AutoscalerConfig,
     /// Network host for containers
     docker_compose_network_host: String,
-    /// Optional metrics client for Prometheus
-    metrics_client: Arc<MetricsClient>,
+    /// How the controller reaches function containers
+    address_mode: AddressMode,
+    /// Execution deadlines of the requests containers serve, when started
+    deadlines: Option<ExecutionDeadlines>,
+    /// Where the CPU and memory usage of containers is read
+    metrics_client: Arc<dyn ContainerMetrics>,
     /// Redis persistence handler
     persistence: Option<Arc<AutoscalerPersistence>>,
+    /// Crash reports and scaling anomalies
+    incidents: Incidents,
+    /// Routing policy per function key, applied to pools as they are created
+    policies: DashMap<String, FunctionPolicy>,
+    /// Set to `true` to stop the background tasks
+    shutdown: watch::Sender<bool>,
+    /// Restarts the background tasks when they fail
+    supervisor: Supervisor,
+    /// Counters of the scaling passes
+    scan_stats: Arc<ScanStats>,
+    /// What the latest restore of pools found
+    restore_report: Mutex<Option<RestoreReport>>,
+    /// Routes function traffic through per-namespace egress proxies, when enabled
+    egress: Option<Arc<EgressGateway>>,
+    /// Lets function containers call the controller's internal API, when enabled
+    internal_api: Option<Arc<InternalApiConfig>>,
+    /// Network rules applied to function containers, when enabled
+    sandbox: Option<Arc<Sandbox>>,
+    /// Admits scale-ups against the namespace and host limits
+    scheduler: Arc<FairScheduler>,
+    /// Windows during which idle containers are kept
+    freezes: Arc<FreezeWindows>,
+    /// The other controllers sharing the pools, when enabled
+    replicas: Option<Arc<Replicas>>,
 }
 
 impl Autoscaler {
@@ -43,16 +184,73 @@ impl Autoscaler {
         docker: Docker,
         config: AutoscalerConfig,
         docker_compose_network_host: String,
-        metrics_client: MetricsClient,
+        metrics_client: Arc<dyn ContainerMetrics>,
     ) -> Self {
+        let pools = Arc::new(DashMap::new());
+        let scheduler = Arc::new(FairScheduler::new(config.fairness.clone(), pools.clone()));
+        let shutdown = watch::channel(false).0;
+        let supervisor = Supervisor::new(shutdown.subscribe());
         Self {
-            pools: Arc::new(DashMap::new()),
+            pools,
             docker,
             config,
             docker_compose_network_host,
-            metrics_client: Arc::new(metrics_client),
+            address_mode: AddressMode::Network,
+            deadlines: None,
+            metrics_client,
             persistence: None,
+            incidents: Incidents::new(),
+            policies: DashMap::new(),
+            shutdown,
+            supervisor,
+            scan_stats: Arc::new(ScanStats::default()),
+            restore_report: Mutex::new(None),
+            egress: None,
+            internal_api: None,
+            sandbox: None,
+            scheduler,
+            freezes: Arc::new(FreezeWindows::new()),
+            replicas: None,
+        }
+    }
+
+    /// Force function traffic through per-namespace egress proxies
+    pub fn with_egress(mut self, egress_config: Option<EgressConfig>) -> Self {
+        if let Some(egress_config) = egress_config {
+            self.egress = Some(Arc::new(EgressGateway::new(
+                self.docker.clone(),
+                egress_config,
+                self.docker_compose_network_host.clone(),
+            )));
+            info!("Function egress goes through per-namespace proxies");
         }
+        self
+    }
+
+    /// Hand function containers the URL and their token of the controller's internal API
+    pub fn with_internal_api(mut self, internal_api: Option<InternalApiConfig>) -> Self {
+        self.internal_api = internal_api.map(Arc::new);
+        self
+    }
+
+    /// Keep function containers away from the Docker API, datastores and cloud metadata
+    pub fn with_sandbox(mut self, sandbox: Option<Sandbox>) -> Self {
+        self.sandbox = sandbox.map(Arc::new);
+        self
+    }
+
+    /// Remove containers still serving a request past their function's execution timeout
+    pub fn with_deadlines(mut self, deadlines: ExecutionDeadlines) -> Self {
+        self.deadlines = Some(deadlines);
+        self
+    }
+
+    /// Reach function containers at ports published on the daemon host instead of on
+    /// their network
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        info!("Function containers are {}", address_mode);
+        self.address_mode = address_mode;
+        self
     }
 
     /// Add Redis persistence to the autoscaler
@@ -67,6 +265,39 @@ impl Autoscaler {
         Ok(self)
     }
 
+    /// Share the pools with other controllers through Redis: the replica holding the
+    /// leader lease runs them, the others route invocations from the state it persists
+    pub fn with_replicas(mut self, config: Option<ReplicaConfig>) -> Self {
+        let Some(config) = config else {
+            return self;
+        };
+        if self.persistence.is_none() {
+            warn!("Replicas need persistence; this controller runs its own pools");
+            return self;
+        }
+        info!(
+            "Replica {} shares its pools with the other controllers",
+            config.replica_id
+        );
+        self.replicas = Some(Arc::new(Replicas::new(config)));
+        self
+    }
+
+    /// This controller's part among the replicas, when they are enabled
+    pub fn replicas(&self) -> Option<&Arc<Replicas>> {
+        self.replicas.as_ref()
+    }
+
+    /// The replica state and persistence of a follower; `None` on the leader and on a
+    /// controller running its own pools
+    fn follower(&self) -> Option<(&Arc<Replicas>, &Arc<AutoscalerPersistence>)> {
+        let replicas = self
+            .replicas
+            .as_ref()
+            .filter(|replicas| !replicas.is_leader())?;
+        Some((replicas, self.persistence.as_ref()?))
+    }
+
     /// Restore autoscaler state from Redis using individual pool loading
     pub async fn restore_from_redis(&self) -> AppResult<()> {
         let persistence = match &self.persistence {
@@ -94,62 +325,71 @@ impl Autoscaler {
             }
         };
 
+        let started = Instant::now();
+        let mut report = RestoreReport::default();
         if persisted_pools.is_empty() {
             info!("No pool states to restore from Redis, starting fresh");
+            report.finish(started.elapsed());
+            *self.restore_report.lock().unwrap() = Some(report);
             return Ok(());
         }
 
         info!("Restoring {} pools from Redis", persisted_pools.len());
 
-        let mut restored_count = 0;
-        let mut failed_count = 0;
-
         for (function_key, persisted_pool) in persisted_pools {
-            match ContainerPool::from_persisted_state(
-                persisted_pool,
-                self.docker.clone(),
-                self.docker_compose_network_host.clone(),
-                self.metrics_client.clone(),
-            )
-            .await
+            if persistence.is_stale(&persisted_pool) {
+                warn!(
+                    "Pool state of {} is stale, removing its containers instead of adopting them",
+                    function_key
+                );
+                self.discard_stale_pool(&function_key, &persisted_pool)
+                    .await;
+                report.pools_stale += 1;
+                continue;
+            }
+            match self
+                .adopt_persisted_pool(&function_key, persisted_pool, &mut report)
+                .await
             {
-                Ok(pool) => {
-                    // Validate containers are still running
-                    if let Err(e) = pool.validate_and_sync_containers().await {
-                        warn!("Failed to validate containers for {}: {}", function_key, e);
-                    }
-
-                    // Only insert if we still have containers after validation
-                    if pool.container_count() > 0 {
-                        self.pools.insert(function_key.clone(), Arc::new(pool));
-                        restored_count += 1;
-                        info!(
-                            "Restored pool for {} with {} containers",
-                            function_key,
-                            self.pools.get(&function_key).unwrap().container_count()
+                Ok(true) => {
+                    report.pools_restored += 1;
+                    info!(
+                        "Restored pool for {} with {} containers",
+                        function_key,
+                        self.pools.get(&function_key).unwrap().container_count()
+                    );
+                }
+                Ok(false) => {
+                    report.pools_empty += 1;
+                    warn!(
+                        "Pool for {} had no valid containers after validation, removing from Redis",
+                        function_key
+                    );
+                    // Clean up the empty pool from Redis
+                    if let Err(e) = persistence.delete_pool_state(&function_key).await {
+                        warn!(
+                            "Failed to delete empty pool state for {}: {}",
+                            function_key, e
                         );
-                    } else {
-                        warn!("Pool for {} had no valid containers after validation, removing from Redis", function_key);
-                        // Clean up the empty pool from Redis
-                        if let Err(e) = persistence.delete_pool_state(&function_key).await {
-                            warn!(
-                                "Failed to delete empty pool state for {}: {}",
-                                function_key, e
-                            );
-                        }
                     }
                 }
                 Err(e) => {
                     error!("Failed to restore pool for {}: {}", function_key, e);
-                    failed_count += 1;
+                    report.pools_failed += 1;
                 }
             }
         }
 
+        report.finish(started.elapsed());
         info!(
-            "State restoration complete: {} pools restored, {} failed",
-            restored_count, failed_count
+            "State restoration complete: {} pools restored, {} failed; {} containers ready, {} unhealthy, {} gone",
+            report.pools_restored,
+            report.pools_failed,
+            report.containers_ready,
+            report.containers_unhealthy,
+            report.containers_gone
         );
+        *self.restore_report.lock().unwrap() = Some(report);
 
         // Update metadata with current state
         let metadata = PersistenceMetadata::new(self.pools.len());
@@ -166,6 +406,114 @@ impl Autoscaler {
         Ok(())
     }
 
+    /// Remove a stale pool state and the containers it lists, which no controller tracks
+    /// any more; the function starts fresh on its next invocation
+    async fn discard_stale_pool(&self, function_key: &str, persisted_pool: &PersistedPoolState) {
+        for container in &persisted_pool.containers {
+            if let Err(e) = clean_up(&self.docker, &container.id).await {
+                debug!("Failed to remove stale container {}: {}", container.id, e);
+            }
+        }
+        if let Some(persistence) = &self.persistence {
+            if let Err(e) = persistence.delete_pool_state(function_key).await {
+                warn!(
+                    "Failed to delete stale pool state for {}: {}",
+                    function_key, e
+                );
+            }
+        }
+    }
+
+    /// Rebuild a pool from its persisted state and take it over if any of its containers
+    /// are still running. The running ones are probed first, so the pool only takes
+    /// requests once those whose server is gone are out of rotation. Returns whether the
+    /// pool was adopted.
+    async fn adopt_persisted_pool(
+        &self,
+        function_key: &str,
+        persisted_pool: PersistedPoolState,
+        report: &mut RestoreReport,
+    ) -> AppResult<bool> {
+        let pool = ContainerPool::from_persisted_state(
+            persisted_pool,
+            self.docker.clone(),
+            self.docker_compose_network_host.clone(),
+            self.metrics_client.clone(),
+        )
+        .await?
+        .with_egress(self.egress.clone())
+        .with_internal_api(self.internal_api.clone())
+        .with_sandbox(self.sandbox.clone())
+        .with_address_mode(self.address_mode.clone())
+        .with_deadlines(self.deadlines.clone());
+
+        // Validate containers are still running
+        let listed = pool.container_count();
+        if let Err(e) = pool.validate_and_sync_containers().await {
+            warn!("Failed to validate containers for {}: {}", function_key, e);
+        }
+        report.containers_gone += listed.saturating_sub(pool.container_count());
+
+        // Only insert if we still have containers after validation
+        if pool.container_count() == 0 {
+            return Ok(false);
+        }
+        pool.probe_containers(report).await;
+        self.pools.insert(function_key.to_string(), Arc::new(pool));
+        Ok(true)
+    }
+
+    /// Current state of every pool, as it would be persisted
+    pub fn snapshot_pool_states(&self) -> HashMap<String, PersistedPoolState> {
+        self.pools
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().to_persisted_state()))
+            .collect()
+    }
+
+    /// Replace pools with previously snapshotted states (e.g. from a backup).
+    ///
+    /// Pools whose containers are no longer running are skipped; they start from scratch
+    /// on their next invocation. Adopted pools are persisted right away. Returns how many
+    /// pools were adopted.
+    pub async fn restore_pool_states(&self, states: HashMap<String, PersistedPoolState>) -> usize {
+        let started = Instant::now();
+        let mut report = RestoreReport::default();
+        for (function_key, persisted_pool) in states {
+            match self
+                .adopt_persisted_pool(&function_key, persisted_pool, &mut report)
+                .await
+            {
+                Ok(true) => {
+                    report.pools_restored += 1;
+                    if let Some(pool) = self.pools.get(&function_key).map(|p| p.clone()) {
+                        if let Err(e) = self.save_pool_state(&function_key, &pool).await {
+                            warn!("Failed to persist restored pool {}: {}", function_key, e);
+                        }
+                    }
+                }
+                Ok(false) => {
+                    report.pools_empty += 1;
+                    debug!("No running containers left for {}, skipping", function_key);
+                }
+                Err(e) => {
+                    report.pools_failed += 1;
+                    error!("Failed to restore pool for {}: {}", function_key, e);
+                }
+            }
+        }
+        report.finish(started.elapsed());
+        let adopted = report.pools_restored;
+        info!("Restored {} pools from snapshot", adopted);
+        *self.restore_report.lock().unwrap() = Some(report);
+        adopted
+    }
+
+    /// What the latest restore of pools found, if there was one
+    pub fn restore_report(&self) -> Option<RestoreReport> {
+        self.restore_report.lock().unwrap().clone()
+    }
+
     /// Save individual pool state to Redis
     async fn save_pool_state(
         &self,
@@ -177,55 +525,247 @@ impl Autoscaler {
             None => return Ok(()),
         };
 
-        let persisted_pool = pool.to_persisted_state();
-        persistence
-            .save_pool_state(function_key, &persisted_pool)
-            .await
+        Self::persist_pool(persistence, function_key, pool).await
+    }
+
+    /// Save a pool's state unless another controller saved it since this one last did.
+    ///
+    /// On a conflict the pool takes in the stored state (see [`ContainerPool::reconcile`])
+    /// and the save is retried, so neither controller's containers are lost.
+    async fn persist_pool(
+        persistence: &AutoscalerPersistence,
+        function_key: &str,
+        pool: &ContainerPool,
+    ) -> AppResult<()> {
+        for _ in 0..MAX_SAVE_ATTEMPTS {
+            let state = pool.to_persisted_state();
+            match persistence.save_pool_state(function_key, &state).await? {
+                SaveOutcome::Saved { version } => {
+                    pool.mark_persisted(version, &state);
+                    return Ok(());
+                }
+                SaveOutcome::Conflict { version } => {
+                    warn!(
+                        "Pool state of {} was saved by another controller (version {}), merging it",
+                        function_key, version
+                    );
+                    pool.reconcile(persistence.load_pool_state(function_key).await?)
+                        .await;
+                }
+            }
+        }
+        Err(RuntimeError::RedisError(format!(
+            "Pool state of {} kept changing while saving it",
+            function_key
+        )))
     }
 
     /// Start the autoscaler background tasks (scaling only, no periodic snapshots)
     pub async fn start(&self) -> AppResult<()> {
         info!("Starting autoscaler with config: {:?}", self.config);
 
-        // Restore state from Redis if persistence is enabled
-        self.restore_from_redis().await?;
+        // Restore state from Redis if persistence is enabled. Replicas restore it when
+        // they take the lead.
+        if self.replicas.is_none() {
+            self.restore_from_redis().await?;
+        }
+
+        // React to container deaths as soon as Docker reports them. Each run subscribes
+        // anew; the previous subscription stops with its receiver.
+        let handles = EventWatch {
+            docker: self.docker.clone(),
+            pools: self.pools.clone(),
+            persistence: self.persistence.clone(),
+            incidents: self.incidents.clone(),
+            scheduler: self.scheduler.clone(),
+            replicas: self.replicas.clone(),
+        };
+        let shutdown = self.shutdown.subscribe();
+        self.supervisor.supervise("event-watcher", move || {
+            let events = ContainerEventWatcher::new(handles.docker.clone()).subscribe();
+            Self::handle_container_events(handles.clone(), events, shutdown.clone())
+        });
 
-        let pools = self.pools.clone();
-        let config = self.config.clone();
+        let pass = ScalePass {
+            pools: self.pools.clone(),
+            config: self.config.clone(),
+            persistence: self.persistence.clone(),
+            scheduler: self.scheduler.clone(),
+            incidents: self.incidents.clone(),
+            freezes: self.freezes.clone(),
+            replicas: self.replicas.clone(),
+            stats: self.scan_stats.clone(),
+        };
+        let shutdown = self.shutdown.subscribe();
+        self.supervisor
+            .supervise("scaler", move || pass.clone().run(shutdown.clone()));
 
-        tokio::spawn(async move {
-            let mut scale_interval = interval(config.scale_check_interval);
+        Ok(())
+    }
+
+    /// Stop the background tasks and flush every pool's state to persistence.
+    ///
+    /// Containers are left running so the next controller instance can adopt them when it
+    /// restores state from Redis.
+    pub async fn shutdown(&self) -> AppResult<()> {
+        info!("Shutting down autoscaler");
+        self.shutdown.send_replace(true);
+
+        let persistence = match &self.persistence {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        // The pools are the leader's to flush
+        if self.follower().is_some() {
+            return Ok(());
+        }
+
+        let pool_snapshot: Vec<_> = self
+            .pools
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut failed = 0;
+        for (function_key, pool) in &pool_snapshot {
+            if let Err(e) = Self::persist_pool(persistence, function_key, pool).await {
+                error!("Failed to flush pool state for {}: {}", function_key, e);
+                failed += 1;
+            }
+        }
+
+        persistence
+            .save_metadata(&PersistenceMetadata::new(pool_snapshot.len()))
+            .await?;
+
+        // Another replica takes the lead without waiting for the lease to expire
+        if let Some(replicas) = self.replicas.as_ref().filter(|r| r.is_leader()) {
+            if let Err(e) = persistence.release_leadership(replicas.replica_id()).await {
+                warn!("Failed to release the leader lease: {}", e);
+            }
+        }
+
+        info!(
+            "Flushed {} pool states ({} failed)",
+            pool_snapshot.len() - failed,
+            failed
+        );
+        Ok(())
+    }
+
+    /// Remove dead containers from their pools, record why they died and replace them if
+    /// the pool drops below its minimum size
+    async fn handle_container_events(
+        handles: EventWatch,
+        mut events: mpsc::UnboundedReceiver<ContainerEvent>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let EventWatch {
+            docker,
+            pools,
+            persistence,
+            incidents,
+            scheduler,
+            replicas,
+        } = handles;
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = shutdown.changed() => break,
+            };
+
+            // The leader handles the deaths of the containers it runs
+            if replicas
+                .as_ref()
+                .is_some_and(|replicas| !replicas.is_leader())
+            {
+                continue;
+            }
+
+            // OOM kills are always followed by a die event, which is what we act on
+            let ContainerEvent::Died {
+                container_id,
+                function_key,
+                exit_code,
+            } = event
+            else {
+                continue;
+            };
 
-            loop {
-                scale_interval.tick().await;
-                debug!("Autoscaler scan start...\n");
-                // Get a snapshot of current pools to avoid holding the lock across await
-                let pool_snapshot: Vec<_> = pools
+            let pool_entry = match function_key {
+                Some(key) => pools.get(&key).map(|pool| (key, pool.clone())),
+                None => pools
                     .iter()
-                    .map(|entry| (entry.key().clone(), entry.value().clone()))
-                    .collect();
-                // Process each pool without holding the main lock
-                for (function_key, pool) in pool_snapshot {
-                    // Update pool metrics
-                    let _ = pool.update_containers_metrics().await;
-                    info!("Autoscaler state: {:?} \n\n", pool.get_status());
-
-                    // Check for scale-up needs
-                    if pool.needs_scale_up() {
-                        if let Err(e) = Self::scale_up_function(&function_key, pool.clone()).await {
-                            error!("Failed to scale up pool for {}: {}", function_key, e);
-                        }
-                    }
+                    .find(|entry| entry.value().contains_container(&container_id))
+                    .map(|entry| (entry.key().clone(), entry.value().clone())),
+            };
+            let Some((function_key, pool)) = pool_entry else {
+                // Not tracked by any pool (e.g. left over from a previous run)
+                if let Err(e) = clean_up(&docker, &container_id).await {
+                    debug!(
+                        "Failed to clean up untracked container {}: {}",
+                        container_id, e
+                    );
+                }
+                continue;
+            };
+
+            // Containers removed by scale-down are no longer in the pool
+            if !pool.evict_container(&container_id) {
+                continue;
+            }
+
+            // Capture diagnostics before the container (and its logs) is removed
+            let report = collect_crash_report(&docker, &container_id, exit_code).await;
+            warn!(
+                "Container {} for function {} died: {}",
+                container_id,
+                function_key,
+                report.summary()
+            );
+            if let Err(e) = clean_up(&docker, &container_id).await {
+                debug!("Failed to remove dead container {}: {}", container_id, e);
+            }
+            incidents.history.record(
+                &function_key,
+                ScalingAction::ContainerDied,
+                pool.container_count(),
+                Some(report.summary()),
+            );
 
-                    // Check and scale down if needed
-                    let _ =
-                        Self::check_and_scale_down_pool(function_key.as_str(), pool, &config).await;
+            if pool.container_count() < pool.min_containers() {
+                if let Err(e) = Self::scale_up_function(
+                    &function_key,
+                    pool.clone(),
+                    persistence.as_ref(),
+                    &scheduler,
+                    &incidents,
+                )
+                .await
+                {
+                    error!(
+                        "Failed to replace dead container for {}: {}",
+                        function_key, e
+                    );
                 }
-                debug!("Autoscaler scan end\n");
             }
-        });
 
-        Ok(())
+            if let Some(persistence) = &persistence {
+                if let Err(e) = persistence.save_crash_report(&function_key, &report).await {
+                    warn!("Failed to save crash report for {}: {}", function_key, e);
+                }
+                if let Err(e) = Self::persist_pool(persistence, &function_key, &pool).await {
+                    warn!(
+                        "Failed to save pool state after container death for {}: {}",
+                        function_key, e
+                    );
+                }
+            }
+            incidents.record_crash(function_key, report);
+        }
     }
 
     /// Get or create a container pool for a function
@@ -247,7 +787,16 @@ impl Autoscaler {
             self.config.min_containers_per_function,
             self.config.max_containers_per_function,
             self.metrics_client.clone(),
-        );
+        )
+        .with_egress(self.egress.clone())
+        .with_internal_api(self.internal_api.clone())
+        .with_sandbox(self.sandbox.clone())
+        .with_address_mode(self.address_mode.clone())
+        .with_deadlines(self.deadlines.clone());
+
+        if let Some(policy) = self.policies.get(function_key) {
+            pool.set_policy(policy.clone());
+        }
 
         debug!("Creating new container pool for function: {}", function_key);
         let pool = Arc::new(pool);
@@ -262,118 +811,939 @@ impl Autoscaler {
         pool
     }
 
-    /// Get the best container for a function invocation
+    /// Claim the best container for a function invocation.
+    ///
+    /// The returned lease releases the container when dropped, so hold it until the
+    /// request has been served. With a concurrency limit (single concurrency, TCP and UDP
+    /// services), requests that find every container at its limit at max capacity wait up
+    /// to `QUEUE_TIMEOUT` for one to free up. `affinity` is the session key of sticky
+    /// functions.
+    ///
+    /// Requests arriving while a container of the function starts don't start their own:
+    /// they wait for it, and all take the first container ready. Waiting for a container
+    /// to start is bounded by the cold start timeout; a request that runs out of it, or
+    /// finds the pool at its maximum, fails with [`RuntimeError::Unavailable`].
     pub async fn get_container_for_invocation(
         &self,
         function_key: &str,
-    ) -> Option<ContainerDetails> {
-        let pool = self.get_or_create_pool(function_key).await;
-
-        // Try to get a healthy container
-        if let Some(container) = pool.get_healthiest_container() {
-            pool.mark_container_active(&container.container_id);
-
-            // Save updated pool state after marking container active
-            if let Err(e) = self.save_pool_state(function_key, &pool).await {
-                warn!(
-                    "Failed to save pool state after container activation for {}: {}",
-                    function_key, e
-                );
-            }
-
-            return Some(container);
+        affinity: Option<&str>,
+    ) -> AppResult<ContainerLease> {
+        if let Some((replicas, persistence)) = self.follower() {
+            return self
+                .route_through_leader(function_key, affinity, replicas, persistence)
+                .await;
         }
 
-        // If no containers available, try to scale up immediately
-        if pool.container_count() < self.config.max_containers_per_function {
-            match Self::scale_up_function(function_key, Arc::clone(&pool)).await {
-                Ok(container) => {
-                    pool.mark_container_active(&container.container_id);
+        self.touch_pool_state(function_key);
+        let pool = self.get_or_create_pool(function_key).await;
+        let deadline = Instant::now() + QUEUE_TIMEOUT;
+        let start_deadline = Instant::now() + self.config.cold_start_timeout;
+        let start_timed_out = || {
+            warn!(
+                "No container of function {} started within {} s",
+                function_key,
+                self.config.cold_start_timeout.as_secs()
+            );
+            RuntimeError::Unavailable(format!(
+                "No container of {function_key} started in time, try again"
+            ))
+        };
 
-                    // Save updated pool state after scaling up
+        loop {
+            // Try to get a healthy (or, with single concurrency, free) container
+            if let Some(container) = pool.acquire_container(affinity) {
+                // Handing out a container only marks it active, which the scaler saves
+                // now and then; saving on every invocation would have concurrent ones
+                // conflict with each other
+                if pool.persisted_state_changed() {
                     if let Err(e) = self.save_pool_state(function_key, &pool).await {
                         warn!(
-                            "Failed to save pool state after scale up for {}: {}",
+                            "Failed to save pool state after container activation for {}: {}",
                             function_key, e
                         );
                     }
+                }
+
+                return Ok(ContainerLease::new(pool, container));
+            }
 
-                    Some(container)
+            // Unpausing a container is much faster than starting one
+            if let Some(container_id) = pool.unpause_container().await {
+                self.incidents.history.record(
+                    function_key,
+                    ScalingAction::Unpaused,
+                    pool.container_count(),
+                    None,
+                );
+                if let Some(container) = pool.claim_container(&container_id) {
+                    return Ok(ContainerLease::new(pool, container));
                 }
-                Err(e) => {
-                    error!(
-                        "Failed to scale up function {} for immediate request: {}",
+                continue;
+            }
+
+            // If no containers available, try to scale up immediately
+            if pool.container_count() < pool.max_containers() {
+                let started = timeout_at(
+                    tokio::time::Instant::from_std(start_deadline),
+                    self.start_for_request(function_key, &pool),
+                );
+                let container = match started.await {
+                    Ok(Ok(Some(container))) => container,
+                    // Another request is starting one: take the first container ready
+                    // instead of starting another
+                    Ok(Ok(None)) => {
+                        let remaining = start_deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero()
+                            || !self.scheduler.starts().wait(function_key, remaining).await
+                        {
+                            return Err(start_timed_out());
+                        }
+                        continue;
+                    }
+                    Ok(Err(e)) => {
+                        error!(
+                            "Failed to scale up function {} for immediate request: {}",
+                            function_key, e
+                        );
+                        return Err(e);
+                    }
+                    // The start goes on for the requests after this one
+                    Err(_) => return Err(start_timed_out()),
+                };
+
+                // Save updated pool state after scaling up
+                if let Err(e) = self.save_pool_state(function_key, &pool).await {
+                    warn!(
+                        "Failed to save pool state after scale up for {}: {}",
                         function_key, e
                     );
-                    None
                 }
+
+                // A queued request may have claimed the new container first
+                if let Some(container) = pool.claim_container(&container.container_id) {
+                    return Ok(ContainerLease::new(pool, container));
+                }
+                continue;
+            }
+
+            if pool.policy().concurrency_limit().is_none() {
+                warn!(
+                    "No available containers for function {} and max capacity reached",
+                    function_key
+                );
+                return Err(RuntimeError::Unavailable(format!(
+                    "Every container of {function_key} is busy, try again"
+                )));
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !pool.wait_for_release(remaining).await {
+                warn!(
+                    "Timed out waiting for a free container for function {} ({} queued)",
+                    function_key,
+                    pool.queue_depth()
+                );
+                return Err(RuntimeError::Unavailable(format!(
+                    "No container of {function_key} freed up in time, try again"
+                )));
             }
-        } else {
-            warn!(
-                "No available containers for function {} and max capacity reached",
-                function_key
-            );
-            None
         }
     }
 
-    /// Get status of all pools for monitoring/debugging
-    pub fn get_all_pool_status(&self) -> HashMap<String, serde_json::Value> {
-        self.pools
-            .iter()
-            .map(|entry| {
-                (
-                    entry.key().clone(),
-                    serde_json::json!(entry.value().get_status()),
+    /// Start a container for a request, in a task of its own: the requests waiting for it
+    /// get the container even if the one that started it gives up or goes away
+    async fn start_for_request(
+        &self,
+        function_key: &str,
+        pool: &Arc<ContainerPool>,
+    ) -> AppResult<Option<ContainerAddress>> {
+        let start = {
+            let function_key = function_key.to_string();
+            let pool = pool.clone();
+            let persistence = self.persistence.clone();
+            let scheduler = self.scheduler.clone();
+            let incidents = self.incidents.clone();
+            tokio::spawn(async move {
+                Self::scale_up_function(
+                    &function_key,
+                    pool,
+                    persistence.as_ref(),
+                    &scheduler,
+                    &incidents,
                 )
+                .await
             })
-            .collect()
+        };
+        start.await.unwrap_or_else(|e| {
+            Err(RuntimeError::System(format!(
+                "Start of a container of {function_key} failed: {e}"
+            )))
+        })
     }
 
-    /// Get the autoscaler configuration
-    pub fn get_config(&self) -> &AutoscalerConfig {
-        &self.config
+    /// Keep an invoked function's persisted state from expiring, without holding up the
+    /// invocation
+    fn touch_pool_state(&self, function_key: &str) {
+        let Some(persistence) = self
+            .persistence
+            .clone()
+            .filter(|persistence| persistence.should_touch(function_key))
+        else {
+            return;
+        };
+        let function_key = function_key.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = persistence.touch_pool_state(&function_key).await {
+                debug!(
+                    "Failed to refresh pool state TTL of {}: {}",
+                    function_key, e
+                );
+            }
+        });
     }
 
-    /// Check and scale a specific pool
-    async fn check_and_scale_down_pool(
+    /// Route an invocation on a follower replica: to a container of the pool state the
+    /// leader persisted or, when there is none to route to, to the one the leader starts
+    /// on request
+    ///
+    /// The leases are detached: the leader doesn't know about them, so a concurrency
+    /// limit (single concurrency, TCP and UDP services) isn't enforced for invocations
+    /// routed by followers.
+    async fn route_through_leader(
+        &self,
         function_key: &str,
-        pool: Arc<ContainerPool>,
-        config: &AutoscalerConfig,
-    ) -> AppResult<()> {
-        // Check for scale-down opportunities
-        let candidates = pool.get_scaledown_candidates();
-        for container_id in candidates {
-            if pool.container_count() > config.min_containers_per_function {
-                if let Err(e) = pool.remove_container(&container_id).await {
-                    error!("Failed to scale down container {}: {}", container_id, e);
-                } else {
-                    info!(
-                        "Scaled down container {} for function {}",
+        affinity: Option<&str>,
+        replicas: &Replicas,
+        persistence: &AutoscalerPersistence,
+    ) -> AppResult<ContainerLease> {
+        let deadline = Instant::now() + self.config.cold_start_timeout;
+        let mut requested = false;
+
+        loop {
+            let state = match replicas.cached_state(function_key) {
+                Some(state) => Some(state),
+                None => match persistence.load_pool_state(function_key).await {
+                    Ok(state) => state.map(|state| {
+                        let state = Arc::new(state);
+                        replicas.cache_state(function_key, state.clone());
+                        state
+                    }),
+                    Err(e) => {
+                        error!("Failed to read pool state of {}: {}", function_key, e);
+                        return Err(e);
+                    }
+                },
+            };
+            if let Some(address) = state.and_then(|state| replicas.route(&state, affinity)) {
+                if replicas.should_report(&address.container_id) {
+                    if let Err(e) = persistence
+                        .record_activity(function_key, &address.container_id)
+                        .await
+                    {
+                        warn!("Failed to report activity of {}: {}", function_key, e);
+                    }
+                }
+                return Ok(ContainerLease::detached(address));
+            }
+
+            // Read the state again until the leader publishes the container it started
+            replicas.forget_state(function_key);
+            if !requested {
+                let command = PoolCommand::ScaleUp {
+                    function_key: function_key.to_string(),
+                    policy: self
+                        .policies
+                        .get(function_key)
+                        .map(|policy| policy.value().clone()),
+                };
+                if let Err(e) = persistence.push_pool_command(&command).await {
+                    error!("Failed to ask the leader to start {}: {}", function_key, e);
+                    return Err(e);
+                }
+                requested = true;
+            }
+            if Instant::now() >= deadline {
+                warn!(
+                    "The leader started no container of function {} within {} s",
+                    function_key,
+                    self.config.cold_start_timeout.as_secs()
+                );
+                return Err(RuntimeError::Unavailable(format!(
+                    "No container of {function_key} started in time, try again"
+                )));
+            }
+            tokio::time::sleep(SHARED_STATE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Run [`Self::run_leader_election`] under the supervisor, restarting it if it fails.
+    /// Does nothing without replicas.
+    pub fn start_leader_election(self: &Arc<Self>) {
+        if self.replicas.is_none() {
+            return;
+        }
+        let autoscaler = self.clone();
+        self.supervisor.supervise("leader-election", move || {
+            autoscaler.clone().run_leader_election()
+        });
+    }
+
+    /// Take part in the election of the replicas' scaling leader until shutdown, renewing
+    /// the lease every third of its TTL. Returns right away without replicas.
+    ///
+    /// The replica taking the lead adopts the pools from Redis and runs the commands the
+    /// followers queue. One losing it drops its pools without stopping their containers,
+    /// which the new leader adopts.
+    pub async fn run_leader_election(self: Arc<Self>) {
+        let (Some(replicas), Some(persistence)) = (self.replicas.clone(), self.persistence.clone())
+        else {
+            return;
+        };
+        let autoscaler = self.clone();
+        let (commands_replicas, commands_persistence) = (replicas.clone(), persistence.clone());
+        self.supervisor.supervise("pool-commands", move || {
+            autoscaler
+                .clone()
+                .run_pool_commands(commands_replicas.clone(), commands_persistence.clone())
+        });
+
+        let mut renew = interval(replicas.lease_ttl() / 3);
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            tokio::select! {
+                _ = renew.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+
+            // Without Redis the lease can't be renewed, and another replica may take it
+            let leading = persistence
+                .acquire_leadership(replicas.replica_id(), replicas.lease_ttl())
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to renew the leader lease: {}", e);
+                    false
+                });
+            if leading == replicas.is_leader() {
+                continue;
+            }
+            if leading {
+                info!("Replica {} took the scaling lead", replicas.replica_id());
+                if let Err(e) = self.restore_from_redis().await {
+                    error!("Failed to adopt the pools: {}", e);
+                }
+                replicas.set_leader(true);
+            } else {
+                warn!("Replica {} lost the scaling lead", replicas.replica_id());
+                replicas.set_leader(false);
+                self.pools.clear();
+            }
+        }
+    }
+
+    /// Run the commands followers queue while this replica leads, each in its own task so
+    /// a cold start doesn't hold up the others
+    async fn run_pool_commands(
+        self: Arc<Self>,
+        replicas: Arc<Replicas>,
+        persistence: Arc<AutoscalerPersistence>,
+    ) {
+        let mut leader = replicas.subscribe();
+        let mut shutdown = self.shutdown.subscribe();
+        while !*shutdown.borrow() {
+            if !*leader.borrow_and_update() {
+                tokio::select! {
+                    _ = leader.changed() => continue,
+                    _ = shutdown.changed() => break,
+                }
+            }
+            match persistence.pop_pool_command(COMMAND_WAIT).await {
+                Ok(Some(command)) => {
+                    tokio::spawn(self.clone().run_pool_command(command));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Failed to read pool commands: {}", e);
+                    tokio::time::sleep(COMMAND_WAIT).await;
+                }
+            }
+        }
+    }
+
+    async fn run_pool_command(self: Arc<Self>, command: PoolCommand) {
+        debug!("Running pool command {:?}", command);
+        match command {
+            PoolCommand::ScaleUp {
+                function_key,
+                policy,
+            } => {
+                if let Some(policy) = policy {
+                    self.set_function_policy(&function_key, policy);
+                }
+                let pool = self.get_or_create_pool(&function_key).await;
+                // Another follower's request may have started one already
+                if pool.get_healthiest_container(None).is_none() {
+                    if pool.unpause_container().await.is_some() {
+                        self.incidents.history.record(
+                            &function_key,
+                            ScalingAction::Unpaused,
+                            pool.container_count(),
+                            None,
+                        );
+                    } else if pool.container_count() < pool.max_containers() {
+                        if let Err(e) = Self::scale_up_function(
+                            &function_key,
+                            pool.clone(),
+                            self.persistence.as_ref(),
+                            &self.scheduler,
+                            &self.incidents,
+                        )
+                        .await
+                        {
+                            error!("Failed to scale up {} for a follower: {}", function_key, e);
+                        }
+                    }
+                }
+                if let Err(e) = self.save_pool_state(&function_key, &pool).await {
+                    warn!("Failed to publish pool state of {}: {}", function_key, e);
+                }
+            }
+            PoolCommand::SetPolicy {
+                function_key,
+                policy,
+            } => self.set_function_policy(&function_key, policy),
+            PoolCommand::RemoveFunction { function_key } => {
+                self.remove_function(&function_key).await;
+            }
+            PoolCommand::DrainNamespace { namespace } => {
+                self.drain_namespace(&namespace).await;
+            }
+        }
+    }
+
+    /// Queue a command for the leader, from a follower, without waiting for Redis
+    fn send_to_leader(&self, persistence: &Arc<AutoscalerPersistence>, command: PoolCommand) {
+        let persistence = persistence.clone();
+        tokio::spawn(async move {
+            if let Err(e) = persistence.push_pool_command(&command).await {
+                error!("Failed to send {:?} to the leader: {}", command, e);
+            }
+        });
+    }
+
+    /// State of the background tasks: scaling, container events and, with replicas, the
+    /// leader election and the followers' commands
+    pub fn background_tasks(&self) -> Vec<TaskStatus> {
+        self.supervisor.status()
+    }
+
+    /// Whether every background task is running, i.e. none failed and waits for its
+    /// restart
+    pub fn background_tasks_running(&self) -> bool {
+        self.supervisor.all_running()
+    }
+
+    /// How many scaling passes ran and pools they checked, and how many pool checks
+    /// overran their timeout
+    pub fn scan_stats(&self) -> ScanStatsSnapshot {
+        self.scan_stats.snapshot()
+    }
+
+    /// Whether the function has a running or paused container, i.e. an invocation won't
+    /// cold start. Followers answer from the pool states they read recently.
+    pub fn is_warm(&self, function_key: &str) -> bool {
+        if let Some((replicas, _)) = self.follower() {
+            return replicas.is_warm(function_key);
+        }
+        self.pools
+            .get(function_key)
+            .is_some_and(|pool| pool.container_count() > 0)
+    }
+
+    /// Set the routing policy of a function, applying it to its pool if one exists. On a
+    /// follower it's sent on to the leader.
+    pub fn set_function_policy(&self, function_key: &str, policy: FunctionPolicy) {
+        if let Some((_, persistence)) = self.follower() {
+            self.send_to_leader(
+                persistence,
+                PoolCommand::SetPolicy {
+                    function_key: function_key.to_string(),
+                    policy: policy.clone(),
+                },
+            );
+        }
+        if let Some(pool) = self.pools.get(function_key) {
+            pool.set_policy(policy.clone());
+        }
+        self.policies.insert(function_key.to_string(), policy);
+    }
+
+    /// Replace the windows during which idle containers are kept instead of scaled down
+    pub fn set_freeze_windows(&self, windows: Vec<FreezeWindow>) {
+        self.freezes.set(windows);
+    }
+
+    /// Stop serving a function: drop its pool, remove its containers and forget its
+    /// persisted state and policy.
+    ///
+    /// Returns how many containers were removed. The function's image is left alone. A
+    /// follower leaves the removal to the leader and returns 0.
+    pub async fn remove_function(&self, function_key: &str) -> usize {
+        self.policies.remove(function_key);
+        self.incidents.crash_reports.remove(function_key);
+        self.incidents.history.forget(function_key);
+
+        if let Some((replicas, persistence)) = self.follower() {
+            replicas.forget_state(function_key);
+            self.send_to_leader(
+                persistence,
+                PoolCommand::RemoveFunction {
+                    function_key: function_key.to_string(),
+                },
+            );
+            return 0;
+        }
+
+        let removed = self.drain_pool(function_key).await;
+        info!(
+            "Removed function {} ({} containers stopped)",
+            function_key, removed
+        );
+        removed
+    }
+
+    /// Drain every pool of a namespace, e.g. one hibernated for lack of traffic: remove
+    /// their containers and forget their persisted state. Policies are kept, so the
+    /// functions start as configured on their next request.
+    ///
+    /// Returns how many containers were removed; 0 on a follower, which leaves the
+    /// draining to the leader.
+    pub async fn drain_namespace(&self, namespace: &str) -> usize {
+        if let Some((_, persistence)) = self.follower() {
+            self.send_to_leader(
+                persistence,
+                PoolCommand::DrainNamespace {
+                    namespace: namespace.to_string(),
+                },
+            );
+            return 0;
+        }
+
+        let function_keys: Vec<String> = self
+            .pools
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|function_key| namespace_of(function_key) == namespace)
+            .collect();
+        if function_keys.is_empty() {
+            return 0;
+        }
+
+        let mut removed = 0;
+        for function_key in function_keys {
+            removed += self.drain_pool(&function_key).await;
+        }
+        info!(
+            "Drained namespace {} ({} containers stopped)",
+            namespace, removed
+        );
+        removed
+    }
+
+    /// Drop a function's pool, remove its containers and forget its persisted state
+    async fn drain_pool(&self, function_key: &str) -> usize {
+        let mut removed = 0;
+        if let Some((_, pool)) = self.pools.remove(function_key) {
+            for container_id in pool.container_ids() {
+                match pool.remove_container(&container_id).await {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!(
+                        "Failed to remove container {} of {}: {}",
+                        container_id, function_key, e
+                    ),
+                }
+            }
+        }
+
+        if let Some(persistence) = &self.persistence {
+            if let Err(e) = persistence.delete_pool_state(function_key).await {
+                warn!("Failed to delete pool state for {}: {}", function_key, e);
+            }
+        }
+        removed
+    }
+
+    /// Get status of all pools for monitoring/debugging
+    pub fn get_all_pool_status(&self) -> HashMap<String, serde_json::Value> {
+        self.pools
+            .iter()
+            .map(|entry| {
+                (
+                    entry.key().clone(),
+                    serde_json::json!(entry.value().get_status()),
+                )
+            })
+            .collect()
+    }
+
+    /// Get status of a single function's pool, if it has one. A pool waiting for host
+    /// capacity says why under `scale_up_refused`.
+    pub fn get_pool_status(
+        &self,
+        function_key: &str,
+    ) -> Option<HashMap<String, serde_json::Value>> {
+        let mut status = self.pools.get(function_key)?.value().get_status();
+        if let Some(refusal) = self.scheduler.refusal(function_key) {
+            status.insert(
+                "scale_up_refused".to_string(),
+                serde_json::Value::String(refusal.to_string()),
+            );
+        }
+        Some(status)
+    }
+
+    /// Containers, CPU and memory committed on the host against the configured limits,
+    /// and whether scale-ups are being refused for lack of capacity
+    pub fn capacity_status(&self) -> CapacityStatus {
+        self.scheduler.capacity_status()
+    }
+
+    /// Requests waiting for a free container, across functions
+    pub fn queued_requests(&self) -> usize {
+        self.pools
+            .iter()
+            .map(|entry| entry.value().queue_depth())
+            .sum()
+    }
+
+    /// Get the most recent crash report for a function
+    pub async fn get_crash_report(&self, function_key: &str) -> Option<CrashReport> {
+        if let Some(report) = self.incidents.crash_reports.get(function_key) {
+            return Some(report.clone());
+        }
+
+        let persistence = self.persistence.as_ref()?;
+        match persistence.load_crash_report(function_key).await {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Failed to load crash report for {}: {}", function_key, e);
+                None
+            }
+        }
+    }
+
+    /// Scaling events of a function at or after `since_ms` (unix milliseconds), oldest
+    /// first. Only the most recent events are kept, and only by this controller.
+    pub fn get_scaling_events(&self, function_key: &str, since_ms: u64) -> Vec<ScalingEvent> {
+        self.incidents.history.since(function_key, since_ms)
+    }
+
+    /// Subscribe to the anomalies seen from now on: dead containers and failed scale-ups.
+    /// Subscribers that fall behind miss the oldest ones.
+    pub fn subscribe_anomalies(&self) -> broadcast::Receiver<ScalingAnomaly> {
+        self.incidents.anomalies.subscribe()
+    }
+
+    /// Start a command in a running container of a function, for debugging.
+    ///
+    /// The container is claimed like an invocation's, and held for as long as the
+    /// session: it isn't scaled down meanwhile, nor removed at the execution timeout.
+    pub async fn exec_in_function(
+        &self,
+        function_key: &str,
+        command: Vec<String>,
+        tty: bool,
+    ) -> AppResult<ExecSession> {
+        let lease = self
+            .get_container_for_invocation(function_key, None)
+            .await?
+            .without_deadline();
+        let mut session = ExecSession::start(
+            self.docker.clone(),
+            &lease.address().container_id,
+            command,
+            tty,
+        )
+        .await?;
+        session.lease = Some(lease);
+        Ok(session)
+    }
+
+    /// Dev containers, started on the same networks, egress proxies, internal API access
+    /// and sandbox as the function containers
+    pub fn dev_containers(&self) -> DevContainers {
+        DevContainers::new(
+            self.docker.clone(),
+            self.docker_compose_network_host.clone(),
+            self.egress.clone(),
+            self.internal_api.clone(),
+            self.sandbox.clone(),
+        )
+    }
+
+    /// Get the boot output of the last container of a function that failed to become ready
+    pub async fn get_boot_log(&self, function_key: &str) -> Option<BootLog> {
+        if let Some(boot_log) = self
+            .pools
+            .get(function_key)
+            .and_then(|pool| pool.last_boot_log())
+        {
+            return Some(boot_log);
+        }
+
+        let persistence = self.persistence.as_ref()?;
+        match persistence.load_boot_log(function_key).await {
+            Ok(boot_log) => boot_log,
+            Err(e) => {
+                warn!("Failed to load boot log for {}: {}", function_key, e);
+                None
+            }
+        }
+    }
+
+    /// CPU and memory usage sampled for a function, with right-sizing recommendations
+    /// against the current container limits
+    pub async fn get_resource_usage(
+        &self,
+        function_key: &str,
+    ) -> Option<(ResourceUsage, Vec<Recommendation>)> {
+        let (usage, limits) = match self.pools.get(function_key) {
+            Some(pool) => (pool.resource_usage(), pool.resource_limits()),
+            None => {
+                let persistence = self.persistence.as_ref()?;
+                match persistence.load_pool_state(function_key).await {
+                    Ok(state) => {
+                        let state = state?;
+                        (state.usage, state.policy.resource_limits())
+                    }
+                    Err(e) => {
+                        warn!("Failed to load resource usage for {}: {}", function_key, e);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let recommendations = usage.recommendations(limits);
+        Some((usage, recommendations))
+    }
+
+    /// Check that the Docker daemon is reachable
+    pub async fn docker_health_check(&self) -> bool {
+        self.docker.ping().await.is_ok()
+    }
+
+    /// Check that the metrics backend (Prometheus) is reachable
+    pub async fn metrics_health_check(&self) -> bool {
+        self.metrics_client.health_check().await
+    }
+
+    /// Get the autoscaler configuration
+    pub fn get_config(&self) -> &AutoscalerConfig {
+        &self.config
+    }
+
+    /// Check and scale a specific pool
+    async fn check_and_scale_down_pool(
+        function_key: &str,
+        pool: Arc<ContainerPool>,
+        incidents: &Incidents,
+    ) -> AppResult<()> {
+        // Check for scale-down opportunities
+        let candidates = pool.get_scaledown_candidates();
+        let idle_strategy = pool.policy().idle_strategy;
+        for container_id in candidates {
+            if pool.serving_count() > pool.min_containers() {
+                let (scaled_down, action) = match idle_strategy {
+                    IdleStrategy::Remove => (
+                        pool.remove_container(&container_id).await,
+                        ScalingAction::ScaledDown,
+                    ),
+                    IdleStrategy::Pause => (
+                        pool.pause_container(&container_id).await,
+                        ScalingAction::Paused,
+                    ),
+                };
+                if let Err(e) = scaled_down {
+                    error!("Failed to scale down container {}: {}", container_id, e);
+                } else {
+                    incidents
+                        .history
+                        .record(function_key, action, pool.container_count(), None);
+                    info!(
+                        "Scaled down container {} for function {}",
                         container_id, function_key
                     );
                 }
             }
         }
 
+        // Paused containers give their memory back eventually
+        for container_id in pool.paused_longer_than(PAUSED_RETENTION) {
+            if let Err(e) = pool.remove_container(&container_id).await {
+                error!("Failed to remove paused container {}: {}", container_id, e);
+            } else {
+                incidents.history.record(
+                    function_key,
+                    ScalingAction::ScaledDown,
+                    pool.container_count(),
+                    Some("paused for too long".to_string()),
+                );
+            }
+        }
+
         Ok(())
     }
 
-    /// Scale up a function by adding a new container
+    /// Rolling replacement of the containers that outlived the function's lifetime policy
+    /// (max age or requests), one at a time.
+    ///
+    /// The replacement starts before the old container is retired, so the pool never
+    /// serves with fewer containers than it had, unless it is at its maximum, where the
+    /// old container is retired first as long as the pool stays above its minimum. Retired
+    /// containers take no new requests and are removed once their last one finishes.
+    async fn recycle_pool(
+        function_key: &str,
+        pool: Arc<ContainerPool>,
+        persistence: Option<&Arc<AutoscalerPersistence>>,
+        scheduler: &Arc<FairScheduler>,
+        incidents: &Incidents,
+    ) {
+        for container_id in pool.retired_containers() {
+            if let Err(e) = pool.remove_container(&container_id).await {
+                error!("Failed to remove retired container {}: {}", container_id, e);
+            } else {
+                incidents.history.record(
+                    function_key,
+                    ScalingAction::ScaledDown,
+                    pool.container_count(),
+                    Some("retired".to_string()),
+                );
+            }
+        }
+        // Wait for the last retired container to drain before the next
+        if pool.retiring_count() > 0 {
+            return;
+        }
+
+        let Some((container_id, reason)) = pool.containers_to_recycle().into_iter().next() else {
+            return;
+        };
+        if pool.container_count() < pool.max_containers() {
+            match Self::scale_up_function(
+                function_key,
+                pool.clone(),
+                persistence,
+                scheduler,
+                incidents,
+            )
+            .await
+            {
+                Ok(Some(_)) => {}
+                // Wait for the container already starting before recycling
+                Ok(None) => return,
+                Err(e) => {
+                    warn!(
+                        "Failed to start a replacement for container {} of {}, keeping it: {}",
+                        container_id, function_key, e
+                    );
+                    return;
+                }
+            }
+        } else if pool.serving_count() <= pool.min_containers() {
+            debug!(
+                "Container {} of {} is due for recycling, but the pool has no room for a replacement",
+                container_id, function_key
+            );
+            return;
+        }
+
+        if pool.retire_container(&container_id) {
+            info!(
+                "Recycling container {} of {}: {}",
+                container_id, function_key, reason
+            );
+            incidents.history.record(
+                function_key,
+                ScalingAction::Recycled,
+                pool.container_count(),
+                Some(reason),
+            );
+        }
+    }
+
+    /// Scale up a function by adding a new container, if the scheduler admits it.
+    ///
+    /// The start waits its turn in the start queue, ahead of the others if the pool has no
+    /// container serving. Returns `None` without starting anything if the pool already has
+    /// as many containers starting as its policy allows.
     async fn scale_up_function(
         function_key: &str,
         pool: Arc<ContainerPool>,
-    ) -> AppResult<ContainerDetails> {
+        persistence: Option<&Arc<AutoscalerPersistence>>,
+        scheduler: &Arc<FairScheduler>,
+        incidents: &Incidents,
+    ) -> AppResult<Option<ContainerAddress>> {
+        let priority = if pool.serving_count() == 0 {
+            StartPriority::Cold
+        } else {
+            StartPriority::ScaleUp
+        };
+        let Some(_ticket) = scheduler
+            .starts()
+            .begin(function_key, pool.policy().start_concurrency(), priority)
+            .await
+        else {
+            debug!("A container of {} is already starting", function_key);
+            return Ok(None);
+        };
+        // Held until the container is counted in its pool
+        let _permit = scheduler.admit(function_key).inspect_err(|e| {
+            incidents.history.record(
+                function_key,
+                ScalingAction::ScaleUpFailed,
+                pool.container_count(),
+                Some(e.to_string()),
+            );
+        })?;
         info!("Scaling up function: {}", function_key);
+        let earlier_boot_log = pool.last_boot_log().map(|boot_log| boot_log.container_id);
         // Add the container to the pool
-        let container_details = pool.add_container(function_key).await?;
+        let added = pool.add_container(function_key).await;
+
+        // Keep the boot output of containers that never became ready so users can debug
+        // them, including those that exited and weren't added
+        if let (Some(boot_log), Some(persistence)) = (pool.last_boot_log(), persistence) {
+            if earlier_boot_log.as_ref() != Some(&boot_log.container_id) {
+                if let Err(e) = persistence.save_boot_log(function_key, &boot_log).await {
+                    warn!("Failed to save boot log for {}: {}", function_key, e);
+                }
+            }
+        }
+
+        let address = match added {
+            Ok(address) => address,
+            Err(e) => {
+                incidents.record_failed_scale_up(
+                    function_key,
+                    pool.container_count(),
+                    e.to_string(),
+                );
+                return Err(e);
+            }
+        };
+        incidents.history.record(
+            function_key,
+            ScalingAction::ScaledUp,
+            pool.container_count(),
+            None,
+        );
 
         info!(
             "Successfully scaled up function {} with container {}",
-            function_key, container_details.container_name
+            function_key, address.container_name
         );
 
-        Ok(container_details)
+        Ok(Some(address))
     }
 
     /// Get a log stream for a function's container
@@ -389,12 +1759,18 @@ impl Autoscaler {
         &self,
         function_key: &str,
     ) -> Option<impl Stream<Item = LogMessage>> {
-        // Find a running container for this function
-        let container_details = self.get_container_for_invocation(function_key).await?;
+        // Find a running container for this function; streaming logs doesn't count as a
+        // request, so the claim is released straight away
+        let address = self
+            .get_container_for_invocation(function_key, None)
+            .await
+            .ok()?
+            .address()
+            .clone();
 
         info!(
             function_key = %function_key,
-            container_id = %container_details.container_id,
+            container_id = %address.container_id,
             "Getting log stream for function"
         );
 
@@ -402,15 +1778,12 @@ impl Autoscaler {
         let log_streamer = ContainerLogStreamer::with_docker(self.docker.clone());
 
         // Get streaming logs
-        match log_streamer
-            .stream_logs(&container_details.container_id, true)
-            .await
-        {
+        match log_streamer.stream_logs(&address.container_id, true).await {
             Ok(stream) => Some(stream),
             Err(e) => {
                 error!(
                     function_key = %function_key,
-                    container_id = %container_details.container_id,
+                    container_id = %address.container_id,
                     error = %e,
                     "Failed to create log stream for function"
                 );
@@ -420,10 +1793,241 @@ impl Autoscaler {
     }
 }
 
+/// What the container event watcher works with, cloned into each of its runs
+#[derive(Clone)]
+struct EventWatch {
+    docker: Docker,
+    pools: Arc<DashMap<String, Arc<ContainerPool>>>,
+    persistence: Option<Arc<AutoscalerPersistence>>,
+    incidents: Incidents,
+    scheduler: Arc<FairScheduler>,
+    replicas: Option<Arc<Replicas>>,
+}
+
+/// What the scaler task works with, cloned into each of its runs
+#[derive(Clone)]
+struct ScalePass {
+    pools: Arc<DashMap<String, Arc<ContainerPool>>>,
+    config: AutoscalerConfig,
+    persistence: Option<Arc<AutoscalerPersistence>>,
+    scheduler: Arc<FairScheduler>,
+    incidents: Incidents,
+    freezes: Arc<FreezeWindows>,
+    replicas: Option<Arc<Replicas>>,
+    stats: Arc<ScanStats>,
+}
+
+impl ScalePass {
+    /// Check the pools that are due every scale check interval until shutdown, then
+    /// scale up the ones that need it
+    async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        let mut scale_interval = interval(self.config.scale_check_interval);
+        let schedule = Mutex::new(PollSchedule::new(
+            self.config.scale_check_interval,
+            self.config.idle_scale_check_interval,
+        ));
+        let slots = Semaphore::new(self.config.max_parallel_pool_checks.max(1));
+
+        loop {
+            tokio::select! {
+                _ = scale_interval.tick() => {}
+                _ = shutdown.changed() => {
+                    info!("Autoscaler loop stopped");
+                    break;
+                }
+            }
+            // Followers leave the pools to the leader
+            if self
+                .replicas
+                .as_ref()
+                .is_some_and(|replicas| !replicas.is_leader())
+            {
+                continue;
+            }
+            debug!("Autoscaler scan start...\n");
+            let started = Instant::now();
+            // Get a snapshot of current pools to avoid holding the lock across await
+            let pool_snapshot: Vec<_> = self
+                .pools
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect();
+            schedule
+                .lock()
+                .unwrap()
+                .retain(|function_key| self.pools.contains_key(function_key));
+
+            // Pools are checked concurrently, a bounded number at a time, so one slow
+            // metrics query doesn't hold up the others
+            let (pass, schedule, slots) = (&self, &schedule, &slots);
+            let checks = pool_snapshot
+                .into_iter()
+                .map(|(function_key, pool)| async move {
+                    let _slot = slots.acquire().await.ok()?;
+                    pass.check_pool(schedule, function_key, pool).await
+                });
+            let mut scale_ups: Vec<_> = join_all(checks).await.into_iter().flatten().collect();
+
+            // Scale-ups compete for capacity, so the longest-waiting go first
+            self.scheduler.prioritize(&mut scale_ups);
+            for (function_key, pool) in scale_ups {
+                // A paused container takes the load faster than a new one
+                if pool.unpause_container().await.is_some() {
+                    self.incidents.history.record(
+                        &function_key,
+                        ScalingAction::Unpaused,
+                        pool.container_count(),
+                        None,
+                    );
+                    continue;
+                }
+                if let Err(e) = Autoscaler::scale_up_function(
+                    &function_key,
+                    pool,
+                    self.persistence.as_ref(),
+                    &self.scheduler,
+                    &self.incidents,
+                )
+                .await
+                {
+                    error!("Failed to scale up pool for {}: {}", function_key, e);
+                }
+            }
+            self.stats.record_pass(started.elapsed());
+            debug!("Autoscaler scan end\n");
+        }
+    }
+
+    /// Check a pool if it's due: read its metrics, scale it down and recycle its
+    /// containers as needed, and publish its state. Returns the pool if it needs to scale
+    /// up.
+    ///
+    /// Reading follower activity and metrics is given the pool check timeout; a pool
+    /// that overruns it is skipped until the next pass rather than scaled on what was read
+    /// so far. What the check starts after that runs to completion.
+    async fn check_pool(
+        &self,
+        schedule: &Mutex<PollSchedule>,
+        function_key: String,
+        pool: Arc<ContainerPool>,
+    ) -> Option<(String, Arc<ContainerPool>)> {
+        let now = Instant::now();
+        let deadline = tokio::time::Instant::from_std(now + self.config.pool_check_timeout);
+        let overrun = || {
+            warn!(
+                "Checking pool {} took longer than {:?}, skipping it this pass",
+                function_key, self.config.pool_check_timeout
+            );
+            self.stats.record_overrun();
+        };
+
+        // Containers the followers routed to are in use as well
+        if let (Some(_), Some(persistence)) = (&self.replicas, &self.persistence) {
+            match timeout_at(deadline, persistence.take_activity(&function_key)).await {
+                Ok(Ok(container_ids)) => {
+                    for container_id in container_ids {
+                        pool.mark_container_active(&container_id);
+                    }
+                }
+                Ok(Err(e)) => warn!(
+                    "Failed to read follower activity of {}: {}",
+                    function_key, e
+                ),
+                Err(_) => {
+                    overrun();
+                    return None;
+                }
+            }
+        }
+
+        // Idle pools are checked less often, until traffic or a refused scale-up makes
+        // them due again
+        let busy = {
+            let schedule = schedule.lock().unwrap();
+            let busy = schedule
+                .last_checked(&function_key)
+                .is_some_and(|checked_at| pool.has_traffic_since(checked_at))
+                || self.scheduler.is_starved(&function_key);
+            if !schedule.is_due(&function_key, busy, now) {
+                return None;
+            }
+            busy
+        };
+
+        // Update pool metrics
+        if timeout_at(deadline, pool.update_containers_metrics())
+            .await
+            .is_err()
+        {
+            overrun();
+            return None;
+        }
+        self.stats.record_check();
+        info!("Autoscaler state: {:?} \n\n", pool.get_status());
+        {
+            let mut schedule = schedule.lock().unwrap();
+            schedule.checked(
+                &function_key,
+                busy || pool.needs_scale_up(),
+                pool.policy().idle_poll_interval(),
+                now,
+            );
+            debug!(
+                "Next check of {} in {:?} unless it gets traffic",
+                function_key,
+                schedule.interval(&function_key)
+            );
+        }
+
+        // Check for scale-up needs; pools refused capacity earlier ask again
+        let scale_up = pool.needs_scale_up()
+            || (self.scheduler.is_starved(&function_key)
+                && pool.container_count() < pool.max_containers());
+
+        // Check and scale down if needed, unless a freeze window is open
+        if self.freezes.is_frozen(&function_key, SystemTime::now()) {
+            debug!("Scale-down of {} is frozen", function_key);
+        } else {
+            let _ = Autoscaler::check_and_scale_down_pool(
+                function_key.as_str(),
+                pool.clone(),
+                &self.incidents,
+            )
+            .await;
+        }
+
+        // Replace containers that outlived the function's lifetime policy
+        Autoscaler::recycle_pool(
+            &function_key,
+            pool.clone(),
+            self.persistence.as_ref(),
+            &self.scheduler,
+            &self.incidents,
+        )
+        .await;
+
+        // Followers route from the persisted state, so it is saved as soon as what they
+        // route by changes. Otherwise it is saved again now and then so it neither expires
+        // nor turns stale.
+        if let Some(persistence) = self.persistence.as_ref().filter(|persistence| {
+            pool.persisted_age()
+                .is_none_or(|age| age >= persistence.refresh_interval())
+                || (self.replicas.is_some() && pool.persisted_state_changed())
+        }) {
+            if let Err(e) = Autoscaler::persist_pool(persistence, &function_key, &pool).await {
+                warn!("Failed to publish pool state of {}: {}", function_key, e);
+            }
+        }
+
+        scale_up.then_some((function_key, pool))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::metrics_client::MetricsConfig;
+    use crate::core::container_manager::ContainerInfo;
+    use crate::core::metrics_client::{MetricsClient, MetricsConfig};
     use std::time::Duration;
 
     fn create_test_config() -> AutoscalerConfig {
@@ -438,6 +2042,11 @@ mod tests {
             min_containers_per_function: 1,
             max_containers_per_function: 5,
             scale_check_interval: Duration::from_secs(10),
+            idle_scale_check_interval: Duration::from_secs(30),
+            max_parallel_pool_checks: 16,
+            pool_check_timeout: Duration::from_secs(5),
+            cold_start_timeout: Duration::from_secs(30),
+            fairness: FairnessConfig::default(),
         }
     }
 
@@ -449,12 +2058,88 @@ mod tests {
             docker,
             config,
             "test-network".to_string(),
-            MetricsClient::new(MetricsConfig::default()),
+            Arc::new(MetricsClient::new(MetricsConfig::default())),
         );
 
         assert_eq!(autoscaler.pools.len(), 0);
     }
 
+    fn test_autoscaler(cold_start_timeout: Duration) -> Arc<Autoscaler> {
+        Arc::new(Autoscaler::new(
+            Docker::connect_with_http_defaults().unwrap(),
+            AutoscalerConfig {
+                cold_start_timeout,
+                ..create_test_config()
+            },
+            "test-network".to_string(),
+            Arc::new(MetricsClient::new(MetricsConfig::default())),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_requests_share_a_start() {
+        let autoscaler = test_autoscaler(Duration::from_secs(30));
+        // A container of the function is starting
+        let start = autoscaler
+            .scheduler
+            .starts()
+            .begin("test-function", 1, StartPriority::Cold)
+            .await
+            .unwrap();
+
+        let requests: Vec<_> = (0..3)
+            .map(|_| {
+                let autoscaler = autoscaler.clone();
+                tokio::spawn(async move {
+                    autoscaler
+                        .get_container_for_invocation("test-function", None)
+                        .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // They wait for it rather than start containers of their own
+        assert_eq!(autoscaler.scheduler.starts().status().waiting_requests, 3);
+        assert_eq!(autoscaler.scheduler.starts().in_flight("test-function"), 1);
+
+        let pool = autoscaler.get_or_create_pool("test-function").await;
+        pool.insert_container(ContainerInfo::new(
+            "started".to_string(),
+            "container-started".to_string(),
+            8080,
+        ));
+        drop(start);
+
+        for request in requests {
+            let lease = request.await.unwrap().unwrap();
+            assert_eq!(lease.address().container_id, "started");
+        }
+        assert_eq!(pool.container_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_gives_up_after_cold_start_timeout() {
+        let autoscaler = test_autoscaler(Duration::from_millis(200));
+        let _start = autoscaler
+            .scheduler
+            .starts()
+            .begin("test-function", 1, StartPriority::Cold)
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        let error = autoscaler
+            .get_container_for_invocation("test-function", None)
+            .await
+            .err()
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // Answered with a 503 by the controller
+        assert!(matches!(error, RuntimeError::Unavailable(_)));
+        assert_eq!(autoscaler.scheduler.starts().status().waiting_requests, 0);
+    }
+
     #[tokio::test]
     async fn test_pool_creation() {
         let docker = Docker::connect_with_http_defaults().unwrap();
@@ -463,7 +2148,7 @@ mod tests {
             docker,
             config,
             "test-network".to_string(),
-            MetricsClient::new(MetricsConfig::default()),
+            Arc::new(MetricsClient::new(MetricsConfig::default())),
         );
 
         let pool = autoscaler.get_or_create_pool("test-function").await;
@@ -474,4 +2159,64 @@ mod tests {
         let pool2 = autoscaler.get_or_create_pool("test-function").await;
         assert_eq!(autoscaler.pools.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_drain_namespace_keeps_other_namespaces_and_policies() {
+        let docker = Docker::connect_with_http_defaults().unwrap();
+        let autoscaler = Autoscaler::new(
+            docker,
+            create_test_config(),
+            "test-network".to_string(),
+            Arc::new(MetricsClient::new(MetricsConfig::default())),
+        );
+        let policy = FunctionPolicy {
+            single_concurrency: true,
+            ..Default::default()
+        };
+        autoscaler.set_function_policy("hello-abc123", policy.clone());
+        autoscaler.get_or_create_pool("hello-abc123").await;
+        autoscaler.get_or_create_pool("other-abc123").await;
+        autoscaler.get_or_create_pool("hello-def456").await;
+
+        assert_eq!(autoscaler.drain_namespace("abc123").await, 0);
+
+        assert_eq!(autoscaler.pools.len(), 1);
+        assert!(autoscaler.pools.contains_key("hello-def456"));
+        let pool = autoscaler.get_or_create_pool("hello-abc123").await;
+        assert_eq!(pool.policy(), policy);
+    }
+
+    #[tokio::test]
+    async fn test_crashes_are_broadcast_to_subscribers() {
+        let docker = Docker::connect_with_http_defaults().unwrap();
+        let autoscaler = Autoscaler::new(
+            docker,
+            create_test_config(),
+            "test-network".to_string(),
+            Arc::new(MetricsClient::new(MetricsConfig::default())),
+        );
+        let mut anomalies = autoscaler.subscribe_anomalies();
+
+        let report = CrashReport {
+            container_id: "abc".to_string(),
+            exit_code: Some(137),
+            oom_killed: true,
+            error: None,
+            last_logs: Vec::new(),
+            occurred_at: 0,
+        };
+        let summary = report.summary();
+        autoscaler
+            .incidents
+            .record_crash("test-function".to_string(), report);
+
+        assert_eq!(
+            anomalies.try_recv().unwrap(),
+            ScalingAnomaly::ContainerDied {
+                function_key: "test-function".to_string(),
+                summary,
+            }
+        );
+        assert!(autoscaler.get_crash_report("test-function").await.is_some());
+    }
 }
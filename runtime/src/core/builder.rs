@@ -1,44 +1,88 @@
 use crate::core::autoscaler::{Autoscaler, AutoscalerConfig};
 use crate::core::container_manager::MonitoringConfig;
-use crate::core::metrics_client::MetricsClient;
+use crate::core::deadlines::ExecutionDeadlines;
+use crate::core::egress::EgressConfig;
+use crate::core::environment::{connect_docker, detect_network, AddressMode};
+use crate::core::fairness::{FairnessConfig, HostCapacity};
+use crate::core::internal_api::InternalApiConfig;
+use crate::core::metrics_client::{ContainerMetrics, MetricsAuth, MetricsClient, MetricsSource};
 use crate::core::persistence::PersistenceConfig;
+use crate::core::replicas::ReplicaConfig;
+use crate::core::sandbox::{Reachable, Sandbox, SandboxConfig};
 use crate::shared::error::{AppResult, RuntimeError};
-use bollard::Docker;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use tracing::warn;
 
-/// The main autoscaling runtime
+/// The autoscaling container pools, built by [`AutoscalingRuntimeBuilder`]
 pub struct AutoscalingRuntime {
     pub autoscaler: Arc<Autoscaler>,
 }
 
 impl AutoscalingRuntime {
-    /// Start the runtime
+    /// Restore persisted pools and start the background tasks scaling them; must run
+    /// inside a Tokio runtime
     pub async fn start(&self) -> AppResult<()> {
-        self.autoscaler.start().await
+        self.autoscaler.start().await?;
+        self.autoscaler.start_leader_election();
+        Ok(())
     }
 
-    /// Get the autoscaler reference
+    /// Stop the background tasks and flush the pools' state, leaving their containers
+    /// running for the next start to adopt
+    pub async fn shutdown(&self) -> AppResult<()> {
+        self.autoscaler.shutdown().await
+    }
+
+    /// The autoscaler invocations claim containers from
     pub fn autoscaler(&self) -> &Arc<Autoscaler> {
         &self.autoscaler
     }
 }
 
-/// Builder for configuring and creating the autoscaling runtime
+/// Builder for configuring and creating the autoscaling runtime.
+///
+/// Every setting has a default, so `AutoscalingRuntimeBuilder::new().build()` gives
+/// pools of 1 to 10 containers per function, scaled on Prometheus metrics and persisted
+/// to Redis on localhost.
 #[derive(Default)]
 pub struct AutoscalingRuntimeBuilder {
     docker_compose_network_host: Option<String>,
+    address_mode: Option<AddressMode>,
     scale_check_interval: Option<Duration>,
+    idle_scale_check_interval: Option<Duration>,
+    max_parallel_pool_checks: Option<usize>,
+    pool_check_timeout: Option<Duration>,
+    cold_start_timeout: Option<Duration>,
     min_containers_per_function: Option<usize>,
     max_containers_per_function: Option<usize>,
+    max_containers_per_namespace: Option<usize>,
+    max_containers_per_host: Option<usize>,
+    max_host_cpu_percent: Option<f64>,
+    max_host_memory_percent: Option<f64>,
+    max_concurrent_starts: Option<usize>,
     persistence_enabled: Option<bool>,
     redis_url: Option<String>,
     persistence_key_prefix: Option<String>,
     persistence_batch_size: Option<usize>,
+    persistence_scan_page_size: Option<usize>,
+    persistence_state_ttl: Option<Duration>,
+    persistence_max_state_age: Option<Option<Duration>>,
+    replicas: Option<ReplicaConfig>,
     cpu_overload_threshold: Option<f64>,
     memory_overload_threshold: Option<f64>,
     cooldown_cpu_threshold: Option<f64>,
     cooldown_duration: Option<Duration>,
+    metrics: Option<Arc<dyn ContainerMetrics>>,
+    metrics_source: Option<MetricsSource>,
+    prometheus_url: Option<String>,
+    prometheus_container_id_pattern: Option<String>,
+    prometheus_auth: Option<MetricsAuth>,
+    prometheus_ca_cert: Option<PathBuf>,
+    egress: Option<EgressConfig>,
+    internal_api: Option<InternalApiConfig>,
+    sandbox: Option<SandboxConfig>,
 }
 
 impl AutoscalingRuntimeBuilder {
@@ -46,72 +90,243 @@ impl AutoscalingRuntimeBuilder {
         Default::default()
     }
 
+    /// CPU usage, in percent, past which a container takes no new requests and the pool
+    /// scales up; 80 unless set
     pub fn cpu_overload_threshold(mut self, threshold: f64) -> Self {
         self.cpu_overload_threshold = Some(threshold);
         self
     }
 
+    /// Memory usage, in percent of the limit, past which a container is overloaded; 80
+    /// unless set
     pub fn memory_overload_threshold(mut self, threshold: f64) -> Self {
         self.memory_overload_threshold = Some(threshold);
         self
     }
 
+    /// CPU usage, in percent, at or below which a container counts as idle; 0 unless set
     pub fn cooldown_cpu_threshold(mut self, threshold: f64) -> Self {
         self.cooldown_cpu_threshold = Some(threshold);
         self
     }
 
+    /// How long a container stays idle before it is scaled down; 60 seconds unless set
     pub fn cooldown_duration(mut self, duration: Duration) -> Self {
         self.cooldown_duration = Some(duration);
         self
     }
 
-    pub fn docker_compose_network_host(mut self, host: String) -> Self {
-        self.docker_compose_network_host = Some(host);
+    /// Docker network function containers join. Detected when not set: the controller's
+    /// own network when it runs in a container, the default bridge network otherwise.
+    pub fn docker_compose_network_host(mut self, network: Option<String>) -> Self {
+        self.docker_compose_network_host = network;
+        self
+    }
+
+    /// How the controller reaches function containers; on their network when not set
+    pub fn address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode = Some(address_mode);
         self
     }
 
+    /// How often pools with traffic are checked and scaled; 10 seconds unless set
     pub fn scale_check_interval(mut self, interval: Duration) -> Self {
         self.scale_check_interval = Some(interval);
         self
     }
 
+    /// Longest time between checks of a pool without traffic, which backs off from the
+    /// scale check interval to it; 30 seconds unless set, never below the scale check
+    /// interval. Functions can set their own with
+    /// [`FunctionPolicy::idle_poll_interval_secs`](crate::FunctionPolicy).
+    pub fn idle_scale_check_interval(mut self, interval: Duration) -> Self {
+        self.idle_scale_check_interval = Some(interval);
+        self
+    }
+
+    /// Pools checked at the same time during a scaling pass; 16 unless set
+    pub fn max_parallel_pool_checks(mut self, max: usize) -> Self {
+        self.max_parallel_pool_checks = Some(max);
+        self
+    }
+
+    /// How long reading a pool's metrics may take before the pool is skipped until the
+    /// next pass; 5 seconds unless set
+    pub fn pool_check_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_check_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a request waits for a container of its function to start, its own or one
+    /// another request started, before it fails as unavailable; 30 seconds unless set
+    pub fn cold_start_timeout(mut self, timeout: Duration) -> Self {
+        self.cold_start_timeout = Some(timeout);
+        self
+    }
+
+    /// Containers each function keeps even when idle; 1 unless set
     pub fn min_containers_per_function(mut self, min: usize) -> Self {
         self.min_containers_per_function = Some(min);
         self
     }
 
+    /// Most containers a function scales to; 10 unless set
     pub fn max_containers_per_function(mut self, max: usize) -> Self {
         self.max_containers_per_function = Some(max);
         self
     }
 
+    /// Most containers the functions of one namespace run together; unlimited when not set
+    pub fn max_containers_per_namespace(mut self, max: Option<usize>) -> Self {
+        self.max_containers_per_namespace = max;
+        self
+    }
+
+    /// Most function containers on the Docker host; unlimited when not set
+    pub fn max_containers_per_host(mut self, max: Option<usize>) -> Self {
+        self.max_containers_per_host = max;
+        self
+    }
+
+    /// Most CPU the containers' limits may commit, in percent of the host's CPUs;
+    /// unlimited when not set
+    pub fn max_host_cpu_percent(mut self, percent: Option<f64>) -> Self {
+        self.max_host_cpu_percent = percent;
+        self
+    }
+
+    /// Most memory the containers' limits may commit, in percent of the host's memory;
+    /// unlimited when not set
+    pub fn max_host_memory_percent(mut self, percent: Option<f64>) -> Self {
+        self.max_host_memory_percent = percent;
+        self
+    }
+
+    /// Containers started on the Docker host at the same time, across functions; further
+    /// starts queue, cold starts first. 4 unless set
+    pub fn max_concurrent_starts(mut self, max: usize) -> Self {
+        self.max_concurrent_starts = Some(max);
+        self
+    }
+
+    /// Save the pools to Redis so the next start adopts their containers; on unless set,
+    /// or off in builds without the `redis` feature
     pub fn persistence_enabled(mut self, enabled: bool) -> Self {
         self.persistence_enabled = Some(enabled);
         self
     }
 
+    /// Redis the pools are saved to; `redis://localhost:6379` unless set
     pub fn redis_url(mut self, url: String) -> Self {
         self.redis_url = Some(url);
         self
     }
 
+    /// Prefix of the Redis keys; `autoscaler` unless set
     pub fn persistence_key_prefix(mut self, prefix: String) -> Self {
         self.persistence_key_prefix = Some(prefix);
         self
     }
 
+    /// Pools restored in parallel on start; 50 unless set
     pub fn persistence_batch_size(mut self, batch_size: usize) -> Self {
         self.persistence_batch_size = Some(batch_size);
         self
     }
 
-    pub async fn build(self) -> AppResult<AutoscalingRuntime> {
-        let docker_compose_network_host = self
-            .docker_compose_network_host
-            .unwrap_or_else(|| "host.docker.internal".to_string());
+    /// Keys Redis looks at per `SCAN` call when listing the persisted pools; 500 unless
+    /// set
+    pub fn persistence_scan_page_size(mut self, page_size: usize) -> Self {
+        self.persistence_scan_page_size = Some(page_size);
+        self
+    }
+
+    /// Share the pools with other controllers through Redis, which needs persistence; this
+    /// controller runs its own pools when not set
+    pub fn replicas(mut self, replicas: Option<ReplicaConfig>) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    /// How long a pool state is kept after its last save or invocation; 7 days unless set
+    pub fn persistence_state_ttl(mut self, ttl: Duration) -> Self {
+        self.persistence_state_ttl = Some(ttl);
+        self
+    }
+
+    /// Age past which a pool state isn't restored and its containers are removed; `None`
+    /// restores states of any age. 24 hours unless set.
+    pub fn persistence_max_state_age(mut self, max_age: Option<Duration>) -> Self {
+        self.persistence_max_state_age = Some(max_age);
+        self
+    }
+
+    /// Read container metrics from `metrics` instead of Prometheus or cgroup files; the
+    /// `metrics_source` and `prometheus_*` settings are ignored then
+    pub fn metrics(mut self, metrics: Arc<dyn ContainerMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Where container metrics come from; Prometheus unless set, or the Docker stats API
+    /// in builds without the `prometheus` feature
+    pub fn metrics_source(mut self, source: MetricsSource) -> Self {
+        self.metrics_source = Some(source);
+        self
+    }
+
+    /// Prometheus-compatible query API; `http://prometheus:9090` unless set
+    pub fn prometheus_url(mut self, url: String) -> Self {
+        self.prometheus_url = Some(url);
+        self
+    }
+
+    /// Regex for the cAdvisor `id` label with `{id}` as the container ID placeholder,
+    /// e.g. `/system.slice/docker-{id}.*`. Auto-detected when not set.
+    pub fn prometheus_container_id_pattern(mut self, pattern: Option<String>) -> Self {
+        self.prometheus_container_id_pattern = pattern;
+        self
+    }
+
+    /// Credentials for an authenticated metrics endpoint (VictoriaMetrics, Thanos, ...)
+    pub fn prometheus_auth(mut self, auth: MetricsAuth) -> Self {
+        self.prometheus_auth = Some(auth);
+        self
+    }
 
+    /// PEM bundle with extra CA certificates for the metrics endpoint
+    pub fn prometheus_ca_cert(mut self, path: Option<PathBuf>) -> Self {
+        self.prometheus_ca_cert = path;
+        self
+    }
+
+    /// Route function traffic through per-namespace egress proxies; direct when not set
+    pub fn egress(mut self, egress: Option<EgressConfig>) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Give function containers the URL and a token of the controller's internal API; they
+    /// get neither when not set
+    pub fn internal_api(mut self, internal_api: Option<InternalApiConfig>) -> Self {
+        self.internal_api = internal_api;
+        self
+    }
+
+    /// Confine function containers with network rules; unconfined when not set
+    pub fn sandbox(mut self, sandbox: Option<SandboxConfig>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Connect to Docker and create the runtime; call [`AutoscalingRuntime::start`] to
+    /// run it
+    pub async fn build(self) -> AppResult<AutoscalingRuntime> {
         let scale_check_interval = self.scale_check_interval.unwrap_or(Duration::from_secs(10));
+        let idle_scale_check_interval = self
+            .idle_scale_check_interval
+            .unwrap_or(Duration::from_secs(30))
+            .max(scale_check_interval);
 
         let min_containers = self.min_containers_per_function.unwrap_or(1);
         let max_containers = self.max_containers_per_function.unwrap_or(10);
@@ -122,7 +337,7 @@ impl AutoscalingRuntimeBuilder {
         let cooldown_duration = self.cooldown_duration.unwrap_or(Duration::from_secs(60));
 
         // Configure persistence
-        let persistence_enabled = self.persistence_enabled.unwrap_or(true);
+        let persistence_enabled = self.persistence_enabled.unwrap_or(cfg!(feature = "redis"));
         let redis_url = self
             .redis_url
             .unwrap_or_else(|| "redis://localhost:6379".to_string());
@@ -130,26 +345,52 @@ impl AutoscalingRuntimeBuilder {
             .persistence_key_prefix
             .unwrap_or_else(|| "autoscaler".to_string());
         let persistence_batch_size = self.persistence_batch_size.unwrap_or(50);
+        let persistence_scan_page_size = self.persistence_scan_page_size.unwrap_or(500);
+        let persistence_defaults = PersistenceConfig::default();
 
         let persistence_config = PersistenceConfig {
             enabled: persistence_enabled,
             redis_url,
             key_prefix: persistence_key_prefix,
             batch_size: persistence_batch_size,
+            scan_page_size: persistence_scan_page_size,
+            state_ttl: self
+                .persistence_state_ttl
+                .unwrap_or(persistence_defaults.state_ttl),
+            max_state_age: self
+                .persistence_max_state_age
+                .unwrap_or(persistence_defaults.max_state_age),
         };
 
         // Initialize Docker client
-        let docker = Docker::connect_with_http_defaults()
+        let docker = connect_docker()
             .map_err(|e| RuntimeError::System(format!("Failed to connect to Docker: {}", e)))?;
 
-        // Initialize metrics client
-        let metrics_config = crate::core::metrics_client::MetricsConfig {
-            prometheus_url: "http://prometheus:9090".to_string(),
-            query_timeout: Duration::from_secs(3),
-            cache_ttl: Duration::from_secs(5),
-            max_retries: 3,
+        let docker_compose_network_host = match self.docker_compose_network_host {
+            Some(network) => network,
+            None => detect_network(&docker).await,
+        };
+
+        // Initialize metrics client, unless the embedder brings its own
+        let metrics_client: Arc<dyn ContainerMetrics> = match self.metrics {
+            Some(metrics) => metrics,
+            None => {
+                let metrics_config = crate::core::metrics_client::MetricsConfig {
+                    source: self.metrics_source.unwrap_or_default(),
+                    prometheus_url: self
+                        .prometheus_url
+                        .unwrap_or_else(|| "http://prometheus:9090".to_string()),
+                    query_timeout: Duration::from_secs(3),
+                    cache_ttl: Duration::from_secs(5),
+                    max_retries: 3,
+                    container_id_pattern: self.prometheus_container_id_pattern,
+                    auth: self.prometheus_auth.unwrap_or_default(),
+                    ca_cert_path: self.prometheus_ca_cert,
+                    ..Default::default()
+                };
+                Arc::new(MetricsClient::try_new(metrics_config)?)
+            }
         };
-        let metrics_client = MetricsClient::new(metrics_config);
 
         // Initialize monitoring configuration
         let monitoring = MonitoringConfig {
@@ -159,12 +400,54 @@ impl AutoscalingRuntimeBuilder {
             poll_interval: scale_check_interval,
             cooldown_duration,
         };
+        // The ceilings need the host's size; without it they can't be enforced
+        let ceilings =
+            self.max_host_cpu_percent.is_some() || self.max_host_memory_percent.is_some();
+        let host_capacity = if ceilings {
+            HostCapacity::detect(&docker).await
+        } else {
+            None
+        };
+        if ceilings && host_capacity.is_none() {
+            warn!("Host CPU and memory ceilings are not enforced: host capacity unknown");
+        }
+
         // Create autoscaler config
         let autoscaler_config = AutoscalerConfig {
             monitoring,
             min_containers_per_function: min_containers,
             max_containers_per_function: max_containers,
             scale_check_interval,
+            idle_scale_check_interval,
+            max_parallel_pool_checks: self.max_parallel_pool_checks.unwrap_or(16).max(1),
+            pool_check_timeout: self.pool_check_timeout.unwrap_or(Duration::from_secs(5)),
+            cold_start_timeout: self.cold_start_timeout.unwrap_or(Duration::from_secs(30)),
+            fairness: FairnessConfig {
+                max_containers_per_namespace: self.max_containers_per_namespace,
+                max_containers_per_host: self.max_containers_per_host,
+                max_host_cpu_percent: self.max_host_cpu_percent,
+                max_host_memory_percent: self.max_host_memory_percent,
+                host_capacity,
+                max_concurrent_starts: Some(self.max_concurrent_starts.unwrap_or(4).max(1)),
+            },
+        };
+
+        let sandbox = match self.sandbox {
+            Some(mut sandbox_config) => {
+                // Functions reach the platform's own services in private ranges
+                if let Some(internal_api) = &self.internal_api {
+                    sandbox_config
+                        .reachable
+                        .extend(Reachable::from_url(&internal_api.url));
+                }
+                if let Some(egress) = &self.egress {
+                    sandbox_config
+                        .reachable
+                        .push(Reachable::NetworkPort(egress.proxy_port));
+                }
+                Some(Sandbox::new(docker.clone(), sandbox_config).await?)
+            }
+            None => None,
         };
 
         // Create autoscaler with persistence
@@ -174,7 +457,13 @@ impl AutoscalingRuntimeBuilder {
             docker_compose_network_host.clone(),
             metrics_client,
         )
-        .with_persistence(persistence_config)?;
+        .with_persistence(persistence_config)?
+        .with_replicas(self.replicas)
+        .with_egress(self.egress)
+        .with_internal_api(self.internal_api)
+        .with_sandbox(sandbox)
+        .with_address_mode(self.address_mode.unwrap_or_default())
+        .with_deadlines(ExecutionDeadlines::start(docker.clone()));
 
         Ok(AutoscalingRuntime {
             autoscaler: Arc::new(autoscaler),
@@ -189,7 +478,7 @@ mod tests {
     #[tokio::test]
     async fn test_builder_pattern() {
         let runtime = AutoscalingRuntimeBuilder::new()
-            .docker_compose_network_host("test-network".to_string())
+            .docker_compose_network_host(Some("test-network".to_string()))
             .min_containers_per_function(2)
             .max_containers_per_function(20)
             .build()
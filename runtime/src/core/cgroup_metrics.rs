@@ -0,0 +1,381 @@
+use crate::shared::error::{AppResult, RuntimeError};
+use dashmap::DashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::debug;
+
+/// Where the host's cgroup hierarchy is usually mounted
+pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Directories under a hierarchy checked for nested container cgroups (rootless Docker,
+/// custom parents), e.g. `user.slice/user-1000.slice/user@1000.service/docker-<id>.scope`
+const MAX_SEARCH_DEPTH: usize = 4;
+
+/// Memory limits at or above this are cgroup v1's way of saying "unlimited"
+const V1_UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// Layout of the cgroup hierarchy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgroupVersion {
+    /// One hierarchy per controller (`cpuacct/`, `memory/`)
+    V1,
+    /// A single unified hierarchy
+    V2,
+}
+
+impl CgroupVersion {
+    /// Detect the layout of the hierarchy mounted at `root`
+    pub fn detect(root: &Path) -> Self {
+        if root.join("cgroup.controllers").is_file() {
+            CgroupVersion::V2
+        } else {
+            CgroupVersion::V1
+        }
+    }
+}
+
+/// Cgroup directories of a container
+#[derive(Debug, Clone)]
+struct ContainerCgroup {
+    cpu: PathBuf,
+    memory: PathBuf,
+}
+
+/// CPU time a container had used at some point
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CpuSample {
+    pub(crate) usage_ns: u64,
+    pub(crate) at: Instant,
+}
+
+/// Reads container CPU and memory usage straight from the host's cgroup files, for
+/// single-node installs that run neither cAdvisor nor Prometheus.
+///
+/// Usage is reported like the cAdvisor queries of the metrics client: CPU as a
+/// percentage of one core since the previous reading, memory as a percentage of the
+/// container's limit (including page cache).
+pub struct CgroupReader {
+    root: PathBuf,
+    version: CgroupVersion,
+    cgroups: DashMap<String, ContainerCgroup>,
+    cpu_samples: DashMap<String, CpuSample>,
+}
+
+impl CgroupReader {
+    pub fn new(root: PathBuf) -> Self {
+        let version = CgroupVersion::detect(&root);
+        debug!(
+            "Reading container metrics from {:?} cgroups at {}",
+            version,
+            root.display()
+        );
+        Self {
+            root,
+            version,
+            cgroups: DashMap::new(),
+            cpu_samples: DashMap::new(),
+        }
+    }
+
+    pub fn version(&self) -> CgroupVersion {
+        self.version
+    }
+
+    /// CPU usage percentage of a container since the previous call; 0 on the first one
+    pub fn cpu_usage(&self, container_id: &str) -> AppResult<f64> {
+        let cgroup = self.container_cgroup(container_id)?;
+        let usage_ns = match self.version {
+            CgroupVersion::V2 => {
+                let stat = read_file(&cgroup.cpu.join("cpu.stat"))?;
+                parse_cpu_stat_usage_usec(&stat)
+                    .ok_or_else(|| {
+                        RuntimeError::System(format!(
+                            "No usage_usec in {}",
+                            cgroup.cpu.join("cpu.stat").display()
+                        ))
+                    })?
+                    .saturating_mul(1000)
+            }
+            CgroupVersion::V1 => read_u64(&cgroup.cpu.join("cpuacct.usage"))?,
+        };
+
+        let sample = CpuSample {
+            usage_ns,
+            at: Instant::now(),
+        };
+        let previous = self.cpu_samples.insert(container_id.to_string(), sample);
+        Ok(previous.map_or(0.0, |previous| cpu_percentage(previous, sample)))
+    }
+
+    /// Drop what is known of a container that left its pool: where its cgroup is and its
+    /// last CPU sample
+    pub fn forget_container(&self, container_id: &str) {
+        self.cgroups.remove(container_id);
+        self.cpu_samples.remove(container_id);
+    }
+
+    /// Containers whose cgroup or CPU sample is kept
+    pub fn known_containers(&self) -> usize {
+        self.cgroups.len().max(self.cpu_samples.len())
+    }
+
+    /// Memory usage of a container as a percentage of its limit; 0 when it has none
+    pub fn memory_usage(&self, container_id: &str) -> AppResult<f64> {
+        let cgroup = self.container_cgroup(container_id)?;
+        let (usage, limit) = match self.version {
+            CgroupVersion::V2 => (
+                read_u64(&cgroup.memory.join("memory.current"))?,
+                parse_v2_memory_max(&read_file(&cgroup.memory.join("memory.max"))?),
+            ),
+            CgroupVersion::V1 => (
+                read_u64(&cgroup.memory.join("memory.usage_in_bytes"))?,
+                Some(read_u64(&cgroup.memory.join("memory.limit_in_bytes"))?)
+                    .filter(|&limit| limit < V1_UNLIMITED_MEMORY),
+            ),
+        };
+        Ok(memory_percentage(usage, limit))
+    }
+
+    /// Whether the cgroup hierarchy is mounted and readable
+    pub fn is_readable(&self) -> bool {
+        fs::read_dir(&self.root).is_ok()
+    }
+
+    /// Find the container's cgroup directories, remembering them once found
+    fn container_cgroup(&self, container_id: &str) -> AppResult<ContainerCgroup> {
+        if let Some(cgroup) = self.cgroups.get(container_id) {
+            if cgroup.cpu.is_dir() {
+                return Ok(cgroup.clone());
+            }
+        }
+
+        let (cpu_base, memory_base) = match self.version {
+            CgroupVersion::V2 => (self.root.clone(), self.root.clone()),
+            CgroupVersion::V1 => (self.root.join("cpuacct"), self.root.join("memory")),
+        };
+        let not_found = |base: &Path| {
+            RuntimeError::System(format!(
+                "No cgroup found for container {} under {}",
+                container_id,
+                base.display()
+            ))
+        };
+        let cgroup = ContainerCgroup {
+            cpu: find_container_dir(&cpu_base, container_id).ok_or_else(|| not_found(&cpu_base))?,
+            memory: find_container_dir(&memory_base, container_id)
+                .ok_or_else(|| not_found(&memory_base))?,
+        };
+        self.cgroups
+            .insert(container_id.to_string(), cgroup.clone());
+        Ok(cgroup)
+    }
+}
+
+/// Find a container's directory in a cgroup hierarchy, trying the cgroupfs driver layout
+/// (`docker/<id>`) and the systemd driver layout (`system.slice/docker-<id>.scope`) before
+/// searching nested slices
+fn find_container_dir(base: &Path, container_id: &str) -> Option<PathBuf> {
+    [
+        base.join("docker").join(container_id),
+        base.join("system.slice")
+            .join(format!("docker-{}.scope", container_id)),
+    ]
+    .into_iter()
+    .find(|dir| dir.is_dir())
+    .or_else(|| search_container_dir(base, container_id, MAX_SEARCH_DEPTH))
+}
+
+fn search_container_dir(dir: &Path, container_id: &str, depth: usize) -> Option<PathBuf> {
+    if depth == 0 {
+        return None;
+    }
+    let mut children = Vec::new();
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if is_container_dir_name(&name, container_id) {
+            return Some(path);
+        }
+        children.push(path);
+    }
+    children
+        .iter()
+        .find_map(|child| search_container_dir(child, container_id, depth - 1))
+}
+
+/// Whether a cgroup directory belongs to the container: `<id>` or `docker-<id>.scope`
+fn is_container_dir_name(name: &str, container_id: &str) -> bool {
+    let id = name
+        .strip_prefix("docker-")
+        .and_then(|name| name.strip_suffix(".scope"))
+        .unwrap_or(name);
+    !container_id.is_empty() && id == container_id
+}
+
+fn read_file(path: &Path) -> AppResult<String> {
+    fs::read_to_string(path)
+        .map_err(|e| RuntimeError::System(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+fn read_u64(path: &Path) -> AppResult<u64> {
+    let content = read_file(path)?;
+    content.trim().parse().map_err(|e| {
+        RuntimeError::System(format!(
+            "Failed to parse {} ('{}'): {}",
+            path.display(),
+            content.trim(),
+            e
+        ))
+    })
+}
+
+/// `usage_usec` of a cgroup v2 `cpu.stat` file
+fn parse_cpu_stat_usage_usec(stat: &str) -> Option<u64> {
+    stat.lines().find_map(|line| {
+        line.strip_prefix("usage_usec ")
+            .and_then(|value| value.trim().parse().ok())
+    })
+}
+
+/// A cgroup v2 `memory.max`, `None` when unlimited
+fn parse_v2_memory_max(max: &str) -> Option<u64> {
+    max.trim().parse().ok()
+}
+
+/// CPU used between two samples as a percentage of one core
+pub(crate) fn cpu_percentage(previous: CpuSample, current: CpuSample) -> f64 {
+    let elapsed_ns = current.at.duration_since(previous.at).as_nanos() as f64;
+    if elapsed_ns == 0.0 {
+        return 0.0;
+    }
+    let used_ns = current.usage_ns.saturating_sub(previous.usage_ns) as f64;
+    used_ns / elapsed_ns * 100.0
+}
+
+pub(crate) fn memory_percentage(usage: u64, limit: Option<u64>) -> f64 {
+    match limit {
+        Some(limit) if limit > 0 => usage as f64 / limit as f64 * 100.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_parse_cgroup_files() {
+        let stat = "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\n";
+        assert_eq!(parse_cpu_stat_usage_usec(stat), Some(1500));
+        assert_eq!(parse_cpu_stat_usage_usec("user_usec 1000\n"), None);
+
+        assert_eq!(parse_v2_memory_max("max\n"), None);
+        assert_eq!(parse_v2_memory_max("268435456\n"), Some(268435456));
+    }
+
+    #[test]
+    fn test_usage_percentages() {
+        let at = Instant::now();
+        let previous = CpuSample { usage_ns: 0, at };
+        let current = CpuSample {
+            usage_ns: 500_000_000,
+            at: at + Duration::from_secs(1),
+        };
+        assert_eq!(cpu_percentage(previous, current), 50.0);
+        assert_eq!(cpu_percentage(previous, previous), 0.0);
+
+        assert_eq!(memory_percentage(64, Some(256)), 25.0);
+        assert_eq!(memory_percentage(64, None), 0.0);
+    }
+
+    #[test]
+    fn test_container_dir_names() {
+        assert!(is_container_dir_name(ID, ID));
+        assert!(is_container_dir_name(&format!("docker-{}.scope", ID), ID));
+        assert!(!is_container_dir_name("docker-other.scope", ID));
+        assert!(!is_container_dir_name("", ""));
+    }
+
+    #[test]
+    fn test_reads_cgroup_v2() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("cgroup.controllers"), "cpu memory\n");
+        let dir = root
+            .path()
+            .join("system.slice")
+            .join(format!("docker-{}.scope", ID));
+        write(&dir.join("cpu.stat"), "usage_usec 1000\n");
+        write(&dir.join("memory.current"), "50\n");
+        write(&dir.join("memory.max"), "200\n");
+
+        let reader = CgroupReader::new(root.path().to_path_buf());
+        assert_eq!(reader.version(), CgroupVersion::V2);
+        assert_eq!(reader.cpu_usage(ID).unwrap(), 0.0);
+        assert_eq!(reader.memory_usage(ID).unwrap(), 25.0);
+
+        write(&dir.join("memory.max"), "max\n");
+        assert_eq!(reader.memory_usage(ID).unwrap(), 0.0);
+        assert!(reader.memory_usage("missing").is_err());
+
+        assert_eq!(reader.known_containers(), 1);
+        reader.forget_container(ID);
+        assert_eq!(reader.known_containers(), 0);
+        // A forgotten container starts over with no previous sample
+        assert_eq!(reader.cpu_usage(ID).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_reads_cgroup_v1() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            &root
+                .path()
+                .join("cpuacct/docker")
+                .join(ID)
+                .join("cpuacct.usage"),
+            "1000\n",
+        );
+        let memory = root.path().join("memory/docker").join(ID);
+        write(&memory.join("memory.usage_in_bytes"), "100\n");
+        write(&memory.join("memory.limit_in_bytes"), "400\n");
+
+        let reader = CgroupReader::new(root.path().to_path_buf());
+        assert_eq!(reader.version(), CgroupVersion::V1);
+        assert_eq!(reader.cpu_usage(ID).unwrap(), 0.0);
+        assert_eq!(reader.memory_usage(ID).unwrap(), 25.0);
+
+        write(
+            &memory.join("memory.limit_in_bytes"),
+            "9223372036854771712\n",
+        );
+        assert_eq!(reader.memory_usage(ID).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_finds_nested_cgroup() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("cgroup.controllers"), "cpu memory\n");
+        let dir = root
+            .path()
+            .join("user.slice/user-1000.slice/user@1000.service")
+            .join(format!("docker-{}.scope", ID));
+        write(&dir.join("memory.current"), "10\n");
+        write(&dir.join("memory.max"), "100\n");
+
+        let reader = CgroupReader::new(root.path().to_path_buf());
+        assert_eq!(reader.memory_usage(ID).unwrap(), 10.0);
+    }
+}
@@ -1,15 +1,30 @@
-use crate::core::metrics_client::MetricsClient;
-use crate::core::runner::{clean_up, runner, ContainerDetails};
-use crate::shared::error::AppResult;
-use crate::shared::utils::{random_container_name, random_port};
+use crate::core::deadlines::{DeadlineId, ExecutionDeadlines};
+use crate::core::diagnostics::{collect_boot_log, BootLog};
+use crate::core::egress::EgressGateway;
+use crate::core::environment::AddressMode;
+use crate::core::hooks::{call_init, call_shutdown, probe, HookOutcome};
+use crate::core::internal_api::InternalApiConfig;
+use crate::core::metrics_client::ContainerMetrics;
+use crate::core::policy::{FunctionPolicy, Service};
+use crate::core::restore::RestoreReport;
+use crate::core::runner::{clean_up, runner, ContainerDetails, ResourceLimits};
+use crate::core::sandbox::Sandbox;
+use crate::core::usage::ResourceUsage;
+use crate::shared::error::{AppResult, RuntimeError};
+use crate::shared::utils::random_container_name;
 use bollard::Docker;
 use dashmap::DashMap;
 use futures_util::future::join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
 use tokio::task::JoinError;
 use tracing::{debug, error, info, warn};
 
@@ -22,6 +37,35 @@ pub enum ContainerStatus {
     Overloaded,
     /// Container is idle and candidate for scale-down
     Idle,
+    /// Its server didn't answer the probe after a restore; it takes no requests and is
+    /// removed on the pool's next check
+    Unhealthy,
+}
+
+/// Where invocations reach a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerAddress {
+    pub container_id: String,
+    pub container_name: String,
+    /// Host the controller dials: the container's IP or name on its network, or the
+    /// daemon host its port is published on
+    pub host: String,
+    pub port: u32,
+}
+
+impl ContainerAddress {
+    /// `host:port`
+    pub fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// A container port published on the daemon host, for the [`AddressMode::PublishedPort`]
+/// mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishedPort {
+    pub host: String,
+    pub port: u16,
 }
 
 /// Information about a running container
@@ -33,12 +77,25 @@ pub struct ContainerInfo {
     pub name: String,
     /// Container port
     pub container_port: u32,
+    /// Address of the container on its network; dialed instead of the name when known
+    pub ip_address: Option<String>,
+    /// Where its port is published, dialed instead of its network address when set
+    pub published: Option<PublishedPort>,
     /// Container status
     pub status: ContainerStatus,
     /// Last time this container handled a request
     pub last_active: Instant,
     /// Time when container became idle (for cooldown tracking)
     pub idle_since: Option<Instant>,
+    /// Requests (or TCP/UDP connections) currently being served by this container
+    pub in_flight: usize,
+    /// When the container started
+    pub started_at: Instant,
+    /// Requests (or TCP/UDP connections) the container was claimed for so far
+    pub requests: u64,
+    /// Replaced by a fresh container: takes no new requests and is removed once the ones
+    /// it serves finish
+    pub retiring: bool,
 }
 
 impl ContainerInfo {
@@ -47,9 +104,33 @@ impl ContainerInfo {
             id,
             name,
             container_port,
+            ip_address: None,
+            published: None,
             status: ContainerStatus::Healthy,
             last_active: Instant::now(),
             idle_since: None,
+            in_flight: 0,
+            started_at: Instant::now(),
+            requests: 0,
+            retiring: false,
+        }
+    }
+
+    /// Where the controller dials the container: its published port if it has one,
+    /// otherwise its port on its network, by IP when known
+    pub fn address(&self) -> ContainerAddress {
+        let (host, port) = match &self.published {
+            Some(published) => (published.host.clone(), published.port as u32),
+            None => (
+                self.ip_address.clone().unwrap_or_else(|| self.name.clone()),
+                self.container_port,
+            ),
+        };
+        ContainerAddress {
+            container_id: self.id.clone(),
+            container_name: self.name.clone(),
+            host,
+            port,
         }
     }
 
@@ -62,6 +143,10 @@ impl ContainerInfo {
         memory_threshold: f64,
         cooldown_cpu_threshold: f64,
     ) {
+        // Usage doesn't bring back a container whose server is gone
+        if self.status == ContainerStatus::Unhealthy {
+            return;
+        }
         let old_status = self.status.clone();
 
         // Determine new status based on thresholds
@@ -104,6 +189,19 @@ impl ContainerInfo {
         }
     }
 
+    /// Why the container has outlived the function's lifetime policy and should be
+    /// recycled, if it has
+    pub fn outlived(&self, policy: &FunctionPolicy) -> Option<String> {
+        if let Some(max_requests) = policy.max_container_requests {
+            if max_requests > 0 && self.requests >= max_requests {
+                return Some(format!("served {} requests", self.requests));
+            }
+        }
+        let max_age = policy.max_container_age()?;
+        (self.started_at.elapsed() >= max_age)
+            .then(|| format!("running for {} s", self.started_at.elapsed().as_secs()))
+    }
+
     /// Check if container is within safe window
     pub fn is_within_safe_window(&self, cooldown_duration: Duration) -> bool {
         let safe_window = Duration::from_secs(5);
@@ -148,14 +246,44 @@ pub struct ContainerPool {
     docker: Docker,
     /// Docker network
     network_host: String,
+    /// How the controller reaches the containers
+    address_mode: AddressMode,
+    /// Removes containers still serving a request past the function's execution timeout
+    deadlines: Option<ExecutionDeadlines>,
     /// Monitoring configuration
     config: MonitoringConfig,
     /// Minimum containers to maintain
     min_containers: usize,
     /// Maximum containers allowed
     max_containers: usize,
-    /// Optional metrics client for Prometheus
-    metrics_client: Arc<MetricsClient>,
+    /// Where the CPU and memory usage of the containers is read
+    metrics_client: Arc<dyn ContainerMetrics>,
+    /// Boot output of the last container that failed to become ready
+    last_boot_log: Mutex<Option<BootLog>>,
+    /// CPU and memory sampled from this pool's containers
+    usage: Arc<Mutex<ResourceUsage>>,
+    /// Routing and scaling behaviour of the function
+    policy: RwLock<FunctionPolicy>,
+    /// Requests waiting for a free container (pools with a concurrency limit only)
+    waiting: AtomicUsize,
+    /// Signalled whenever a container finishes a request
+    released: Notify,
+    /// Routes the containers' outbound traffic through the namespace's egress proxy
+    egress: Option<Arc<EgressGateway>>,
+    /// Hands the containers the URL and token of the controller's internal API
+    internal_api: Option<Arc<InternalApiConfig>>,
+    /// Keeps the containers away from the installation's infrastructure
+    sandbox: Option<Arc<Sandbox>>,
+    /// Containers frozen by the pause idle strategy, and when they were paused
+    paused: DashMap<String, Instant>,
+    /// Version of the persisted state this pool was restored from or last saved
+    persisted_version: AtomicU64,
+    /// When this pool's state was last saved
+    persisted_at: Mutex<Option<Instant>>,
+    /// [`PersistedPoolState::routing_digest`] of the state last saved
+    ///
+    /// [`PersistedPoolState::routing_digest`]: crate::core::persistence::PersistedPoolState::routing_digest
+    persisted_digest: Mutex<Option<u64>>,
 }
 
 impl ContainerPool {
@@ -166,7 +294,7 @@ impl ContainerPool {
         config: MonitoringConfig,
         min_containers: usize,
         max_containers: usize,
-        metrics_client: Arc<MetricsClient>,
+        metrics_client: Arc<dyn ContainerMetrics>,
     ) -> Self {
         // TODO: fetch from cache if already existing and build the pool
 
@@ -175,48 +303,159 @@ impl ContainerPool {
             containers: Arc::new(DashMap::new()),
             docker,
             network_host,
+            address_mode: AddressMode::Network,
+            deadlines: None,
             config,
             min_containers,
             max_containers,
             metrics_client,
+            last_boot_log: Mutex::new(None),
+            usage: Arc::new(Mutex::new(ResourceUsage::default())),
+            policy: RwLock::new(FunctionPolicy::default()),
+            waiting: AtomicUsize::new(0),
+            released: Notify::new(),
+            egress: None,
+            internal_api: None,
+            sandbox: None,
+            paused: DashMap::new(),
+            persisted_version: AtomicU64::new(0),
+            persisted_at: Mutex::new(None),
+            persisted_digest: Mutex::new(None),
+        }
+    }
+
+    /// Start the pool's containers on their namespace's egress network
+    pub fn with_egress(mut self, egress: Option<Arc<EgressGateway>>) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Give the pool's containers access to the controller's internal API
+    pub fn with_internal_api(mut self, internal_api: Option<Arc<InternalApiConfig>>) -> Self {
+        self.internal_api = internal_api;
+        self
+    }
+
+    /// Confine the pool's containers before they join their network
+    pub fn with_sandbox(mut self, sandbox: Option<Arc<Sandbox>>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Reach the pool's new containers the given way
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    /// Enforce the function's execution timeout on the requests its containers serve
+    pub fn with_deadlines(mut self, deadlines: Option<ExecutionDeadlines>) -> Self {
+        self.deadlines = deadlines;
+        self
+    }
+
+    /// Arms the execution deadline of a request a container was claimed for, if the
+    /// function has a timeout
+    fn arm_deadline(&self, container_id: &str) -> Option<DeadlineId> {
+        let timeout = self.policy().execution_timeout()?;
+        Some(self.deadlines.as_ref()?.arm(container_id, timeout))
+    }
+
+    fn disarm_deadline(&self, id: DeadlineId) {
+        if let Some(deadlines) = &self.deadlines {
+            deadlines.disarm(id);
         }
     }
 
     /// Add a container to the pool
-    pub async fn add_container(&self, function_key: &str) -> AppResult<ContainerDetails> {
+    pub async fn add_container(&self, function_key: &str) -> AppResult<ContainerAddress> {
         // Generate container details
         let mut container_details = ContainerDetails {
-            container_id: "".to_string(),
             container_port: 8080,
-            bind_port: random_port(),
+            publish_ip: self.address_mode.publish_ip(),
             container_name: random_container_name(),
-            timeout: 0,
             docker_compose_network_host: self.network_host.to_string(),
+            env: Vec::new(),
         };
+        if let Some(egress) = &self.egress {
+            let function_egress = egress.prepare(function_key).await?;
+            container_details.env = function_egress.env();
+            container_details.docker_compose_network_host = function_egress.network;
+        }
+        if let Some(internal_api) = &self.internal_api {
+            container_details.env.extend(internal_api.env(function_key));
+        }
 
-        let container_id = runner(
+        let started = runner(
             Some(self.docker.clone()),
             function_key,
             container_details.clone(),
+            self.resource_limits(),
+            self.sandbox.as_deref(),
         )
         .await?;
-        container_details.container_id = container_id.clone();
-
-        let container_info = ContainerInfo::new(
+        let container_id = started.container_id;
+        let mut container_info = ContainerInfo::new(
             container_id.clone(),
             container_details.container_name.clone(),
             container_details.container_port,
         );
+        container_info.ip_address = started.ip_address;
+        container_info.published = published_port(&self.address_mode, started.host_port);
+        let address = container_info.address();
+
+        if let Some(boot_log) = started.boot_log {
+            warn!(
+                "Container {} for function {} did not become ready: {}",
+                container_details.container_name, self.function_name, boot_log.reason
+            );
+            let reason = boot_log.reason.clone();
+            *self.last_boot_log.lock().unwrap() = Some(boot_log);
+            // The runner already removed it
+            if started.exited {
+                return Err(RuntimeError::Exec(format!(
+                    "Function {} failed to start: {}",
+                    self.function_name, reason
+                )));
+            }
+        } else if let Some(HookOutcome::Failed(reason)) = self.init_hook(&address).await {
+            // The boot log tells the author why, since the container is gone
+            let reason = format!("init hook failed: {reason}");
+            warn!(
+                "Container {} for function {} failed to start: {}",
+                container_details.container_name, self.function_name, reason
+            );
+            let boot_log = collect_boot_log(&self.docker, &container_id, &reason).await;
+            *self.last_boot_log.lock().unwrap() = Some(boot_log);
+            if let Err(e) = clean_up(&self.docker, &container_id).await {
+                warn!("Failed to remove container {}: {}", container_id, e);
+            }
+            return Err(RuntimeError::Exec(format!(
+                "Function {} failed to start: {}",
+                self.function_name, reason
+            )));
+        }
 
         self.containers
-            .insert(container_info.id.clone(), container_info.clone());
+            .insert(container_info.id.clone(), container_info);
 
         info!(
-            "Added container {} to pool for function {}",
-            container_details.container_name, self.function_name
+            "Added container {} to pool for function {} at {}",
+            address.container_name,
+            self.function_name,
+            address.authority()
         );
 
-        Ok(container_details)
+        Ok(address)
+    }
+
+    /// Calls the init hook of a new container; TCP and UDP services have none
+    async fn init_hook(&self, container: &ContainerAddress) -> Option<HookOutcome> {
+        let policy = self.policy();
+        if !policy.service.is_http() {
+            return None;
+        }
+        Some(call_init(&container.host, container.port, policy.protocol).await)
     }
 
     /// Update container metrics
@@ -245,11 +484,19 @@ impl ContainerPool {
                 let containers = Arc::clone(&self.containers);
                 let cfg = self.config.clone();
                 let metrics_client = self.metrics_client.clone();
+                let usage = Arc::clone(&self.usage);
+                let limits = self.resource_limits();
 
                 tokio::spawn(async move {
-                    if let Err(e) =
-                        update_container_resources(id.clone(), cfg, &mut info, &metrics_client)
-                            .await
+                    if let Err(e) = update_container_resources(
+                        id.clone(),
+                        cfg,
+                        &mut info,
+                        &metrics_client,
+                        &usage,
+                        limits,
+                    )
+                    .await
                     {
                         error!("Failed to monitor container {}: {}", id, e);
                     }
@@ -280,17 +527,22 @@ impl ContainerPool {
         Ok(())
     }
 
-    /// Get the healthiest container for load balancing
-    pub fn get_healthiest_container(&self) -> Option<ContainerDetails> {
+    /// Get the healthiest container for load balancing.
+    ///
+    /// With an `affinity` key (sticky routing) the same key keeps landing on the same
+    /// container. Containers idle past their safe window are not eligible, so sessions move
+    /// off a container before it is scaled down.
+    pub fn get_healthiest_container(&self, affinity: Option<&str>) -> Option<ContainerAddress> {
         // Filter healthy containers and sort by last active time
         let mut healthy_containers: Vec<_> = self
             .containers
             .iter()
             .filter(|entry| {
                 let container = entry.value();
-                container.status == ContainerStatus::Healthy
-                    || (container.status == ContainerStatus::Idle
-                        && container.is_within_safe_window(self.config.cooldown_duration))
+                self.is_serving(container)
+                    && (container.status == ContainerStatus::Healthy
+                        || (container.status == ContainerStatus::Idle
+                            && container.is_within_safe_window(self.config.cooldown_duration)))
             })
             .map(|entry| entry.value().clone())
             .collect();
@@ -300,7 +552,10 @@ impl ContainerPool {
             let overloaded: Vec<_> = self
                 .containers
                 .iter()
-                .filter(|entry| entry.value().status == ContainerStatus::Overloaded)
+                .filter(|entry| {
+                    entry.value().status == ContainerStatus::Overloaded
+                        && self.is_serving(entry.value())
+                })
                 .map(|entry| entry.value().clone())
                 .collect();
 
@@ -309,15 +564,109 @@ impl ContainerPool {
                     "No healthy containers available for {}, using overloaded container",
                     self.function_name
                 );
-                return Some(to_container_details(&overloaded[0]));
+                return Some(overloaded[0].address());
             }
             return None;
         }
 
+        if let Some(key) = affinity {
+            return healthy_containers
+                .iter()
+                .max_by_key(|container| affinity_weight(key, &container.id))
+                .map(ContainerInfo::address);
+        }
+
         // Sort by last active time (oldest first for round-robin)
         healthy_containers.sort_by(|a, b| a.last_active.cmp(&b.last_active));
 
-        Some(to_container_details(&healthy_containers[0]))
+        Some(healthy_containers[0].address())
+    }
+
+    /// Claim a container for one request.
+    ///
+    /// With a concurrency limit (single concurrency, TCP and UDP services) only containers
+    /// below it are eligible: the sticky container first, if it has room, otherwise the
+    /// least busy one. Without a limit this is [`Self::get_healthiest_container`]. The
+    /// claim must be given back with [`Self::release_container`].
+    pub fn acquire_container(&self, affinity: Option<&str>) -> Option<ContainerAddress> {
+        let Some(limit) = self.policy().concurrency_limit() else {
+            let container = self.get_healthiest_container(affinity)?;
+            return self.claim_container(&container.container_id);
+        };
+
+        let mut free: Vec<_> = self
+            .containers
+            .iter()
+            .filter(|entry| entry.value().in_flight < limit && self.is_serving(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        match affinity {
+            Some(key) => free.sort_by_key(|c| Reverse(affinity_weight(key, &c.id))),
+            None => free.sort_by_key(|c| (c.in_flight, c.last_active)),
+        }
+
+        // Another request may claim a container between the scan and the claim
+        free.into_iter()
+            .find_map(|container| self.claim_container(&container.id))
+    }
+
+    /// Claim a specific container for one request.
+    ///
+    /// Returns `None` if the container is gone, retiring, or at the pool's concurrency
+    /// limit.
+    pub fn claim_container(&self, container_id: &str) -> Option<ContainerAddress> {
+        let limit = self.policy().concurrency_limit();
+        let mut entry = self.containers.get_mut(container_id)?;
+        if entry.retiring || limit.is_some_and(|limit| entry.in_flight >= limit) {
+            return None;
+        }
+        entry.in_flight += 1;
+        entry.requests += 1;
+        entry.mark_active();
+        Some(entry.address())
+    }
+
+    /// Give back a container claimed for a request and wake one waiting request
+    pub fn release_container(&self, container_id: &str) {
+        if let Some(mut entry) = self.containers.get_mut(container_id) {
+            entry.in_flight = entry.in_flight.saturating_sub(1);
+        }
+        self.released.notify_one();
+    }
+
+    /// Wait until a container is released or `timeout` elapses, counting towards the
+    /// queue depth meanwhile. Returns `false` on timeout.
+    pub async fn wait_for_release(&self, timeout: Duration) -> bool {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let released = tokio::time::timeout(timeout, self.released.notified())
+            .await
+            .is_ok();
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        released
+    }
+
+    /// Number of requests waiting for a free container
+    pub fn queue_depth(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Whether the pool serves or waits for requests, or served one since `since`
+    pub fn has_traffic_since(&self, since: Instant) -> bool {
+        self.queue_depth() > 0
+            || self.containers.iter().any(|entry| {
+                let container = entry.value();
+                container.in_flight > 0 || container.last_active > since
+            })
+    }
+
+    /// Routing and scaling behaviour of the function
+    pub fn policy(&self) -> FunctionPolicy {
+        self.policy.read().unwrap().clone()
+    }
+
+    /// Replace the function's policy (e.g. after a redeploy)
+    pub fn set_policy(&self, policy: FunctionPolicy) {
+        *self.policy.write().unwrap() = policy;
     }
 
     /// Mark a container as active (just handled a request)
@@ -327,17 +676,23 @@ impl ContainerPool {
         }
     }
 
-    /// Check if we need to scale up (all containers overloaded)
+    /// Check if we need to scale up: all containers overloaded or, with a concurrency
+    /// limit, requests (connections) queued for a container with room
     pub fn needs_scale_up(&self) -> bool {
-        if self.containers.len() >= self.max_containers {
+        if self.containers.len() >= self.max_containers() {
             return false;
         }
 
-        // Scale up if all containers are overloaded
-        !self.containers.is_empty()
+        if self.policy().concurrency_limit().is_some() {
+            return self.queue_depth() > 0;
+        }
+
+        // Scale up if all serving containers are overloaded
+        self.serving_count() > 0
             && self
                 .containers
                 .iter()
+                .filter(|entry| self.is_serving(entry.value()))
                 .all(|entry| entry.value().status == ContainerStatus::Overloaded)
     }
 
@@ -350,17 +705,27 @@ impl ContainerPool {
         self.containers
             .iter()
             .filter(|entry| {
-                entry
-                    .value()
-                    .is_eligible_for_scaledown(self.config.cooldown_duration)
+                let container = entry.value();
+                container.in_flight == 0
+                    && self.is_serving(container)
+                    && container.is_eligible_for_scaledown(self.config.cooldown_duration)
             })
             .map(|entry| entry.key().clone())
             .collect()
     }
 
-    /// Remove a container from the pool
+    /// Remove a container from the pool, letting the function release its resources first
     pub async fn remove_container(&self, container_id: &str) -> AppResult<()> {
-        self.containers.remove(container_id);
+        self.metrics_client.forget_container(container_id);
+        if let Some((_, container)) = self.containers.remove(container_id) {
+            // A frozen function can't answer its shutdown hook
+            let frozen = self.paused.remove(container_id).is_some()
+                && self.docker.unpause_container(container_id).await.is_err();
+            if !frozen && self.policy().service.is_http() {
+                let address = container.address();
+                call_shutdown(&address.host, address.port, self.policy().protocol).await;
+            }
+        }
 
         // Remove from Docker (now safe to await without holding lock)
         clean_up(&self.docker, container_id).await?;
@@ -373,11 +738,201 @@ impl ContainerPool {
         Ok(())
     }
 
+    /// Drop a container that is already gone from Docker (e.g. it died) from the pool.
+    ///
+    /// Returns `true` if the container was part of this pool.
+    pub fn evict_container(&self, container_id: &str) -> bool {
+        self.paused.remove(container_id);
+        self.metrics_client.forget_container(container_id);
+        let evicted = self.containers.remove(container_id).is_some();
+        if evicted {
+            info!(
+                "Evicted dead container {} from pool for function {}",
+                container_id, self.function_name
+            );
+        }
+        evicted
+    }
+
+    /// Freeze an idle container instead of removing it (the pause idle strategy). It keeps
+    /// its memory, and is unpaused when a request finds no running container.
+    pub async fn pause_container(&self, container_id: &str) -> AppResult<()> {
+        self.docker
+            .pause_container(container_id)
+            .await
+            .map_err(|e| RuntimeError::System(format!("Failed to pause container: {e}")))?;
+        self.paused.insert(container_id.to_string(), Instant::now());
+
+        info!(
+            "Paused container {} of function {}",
+            container_id, self.function_name
+        );
+        Ok(())
+    }
+
+    /// Unpause the most recently paused container.
+    ///
+    /// Returns its ID, or `None` when no container is paused or unpausing failed, in
+    /// which case the container is removed.
+    pub async fn unpause_container(&self) -> Option<String> {
+        let container_id = self
+            .paused
+            .iter()
+            .max_by_key(|entry| *entry.value())
+            .map(|entry| entry.key().clone())?;
+        // Another request may be unpausing the same container
+        self.paused.remove(&container_id)?;
+
+        let started = Instant::now();
+        if let Err(e) = self.docker.unpause_container(&container_id).await {
+            warn!(
+                "Failed to unpause container {} of function {}: {}",
+                container_id, self.function_name, e
+            );
+            self.containers.remove(&container_id);
+            self.metrics_client.forget_container(&container_id);
+            if let Err(e) = clean_up(&self.docker, &container_id).await {
+                debug!("Failed to remove container {}: {}", container_id, e);
+            }
+            return None;
+        }
+
+        info!(
+            "Unpaused container {} of function {} in {} ms",
+            container_id,
+            self.function_name,
+            started.elapsed().as_millis()
+        );
+        Some(container_id)
+    }
+
+    /// IDs of the containers paused for longer than `retention`
+    pub fn paused_longer_than(&self, retention: Duration) -> Vec<String> {
+        self.paused
+            .iter()
+            .filter(|entry| entry.value().elapsed() >= retention)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Whether a container is paused
+    fn is_paused(&self, container_id: &str) -> bool {
+        self.paused.contains_key(container_id)
+    }
+
+    /// Whether a container takes new requests: it is neither paused nor retiring
+    fn is_serving(&self, container: &ContainerInfo) -> bool {
+        !container.retiring && !self.is_paused(&container.id)
+    }
+
+    /// Serving containers that outlived the function's lifetime policy, oldest first,
+    /// with why
+    pub fn containers_to_recycle(&self) -> Vec<(String, String)> {
+        let policy = self.policy();
+        let mut outlived: Vec<_> = self
+            .containers
+            .iter()
+            .filter(|entry| self.is_serving(entry.value()))
+            .filter_map(|entry| {
+                let container = entry.value();
+                let reason = container.outlived(&policy)?;
+                Some((container.started_at, container.id.clone(), reason))
+            })
+            .collect();
+        outlived.sort();
+        outlived
+            .into_iter()
+            .map(|(_, container_id, reason)| (container_id, reason))
+            .collect()
+    }
+
+    /// Stop routing new requests to a container, which is removed once the ones it
+    /// serves finish.
+    ///
+    /// Returns `false` if the container is gone.
+    pub fn retire_container(&self, container_id: &str) -> bool {
+        let Some(mut entry) = self.containers.get_mut(container_id) else {
+            return false;
+        };
+        entry.retiring = true;
+        true
+    }
+
+    /// Number of retiring containers still in the pool
+    pub fn retiring_count(&self) -> usize {
+        self.containers
+            .iter()
+            .filter(|entry| entry.value().retiring)
+            .count()
+    }
+
+    /// Retiring containers done with their last requests, ready to be removed
+    pub fn retired_containers(&self) -> Vec<String> {
+        self.containers
+            .iter()
+            .filter(|entry| entry.value().retiring && entry.value().in_flight == 0)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// IDs of every container in the pool
+    pub fn container_ids(&self) -> Vec<String> {
+        self.containers
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Put a container in the pool without starting it
+    #[cfg(test)]
+    pub(crate) fn insert_container(&self, container: ContainerInfo) {
+        self.containers.insert(container.id.clone(), container);
+    }
+
+    /// Check whether a container belongs to this pool
+    pub fn contains_container(&self, container_id: &str) -> bool {
+        self.containers.contains_key(container_id)
+    }
+
+    /// Get minimum containers to maintain, from the function's policy if it sets one
+    pub fn min_containers(&self) -> usize {
+        self.policy()
+            .min_containers
+            .unwrap_or(self.min_containers)
+            .min(self.max_containers())
+    }
+
+    /// Get maximum containers allowed. A function may lower the pool's limit but not
+    /// raise it.
+    pub fn max_containers(&self) -> usize {
+        self.policy()
+            .max_containers
+            .map_or(self.max_containers, |max| max.min(self.max_containers))
+    }
+
+    /// Memory and CPU limits of the function's containers
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.policy().resource_limits()
+    }
+
     /// Get current container count
     pub fn container_count(&self) -> usize {
         self.containers.len()
     }
 
+    /// Number of containers that aren't paused
+    pub fn running_count(&self) -> usize {
+        self.containers.len().saturating_sub(self.paused.len())
+    }
+
+    /// Number of containers taking new requests: neither paused nor retiring
+    pub fn serving_count(&self) -> usize {
+        self.containers
+            .iter()
+            .filter(|entry| self.is_serving(entry.value()))
+            .count()
+    }
+
     /// Get function name
     pub fn get_function_name(&self) -> &str {
         &self.function_name
@@ -406,6 +961,11 @@ impl ContainerPool {
             .iter()
             .filter(|c| c.status == ContainerStatus::Idle)
             .count();
+        let unhealthy_count = containers_snapshot
+            .iter()
+            .filter(|c| c.status == ContainerStatus::Unhealthy)
+            .count();
+        let connections: usize = containers_snapshot.iter().map(|c| c.in_flight).sum();
 
         status.insert(
             "function_name".to_string(),
@@ -427,13 +987,27 @@ impl ContainerPool {
             "idle_containers".to_string(),
             Value::Number(serde_json::Number::from(idle_count)),
         );
+        status.insert(
+            "unhealthy_containers".to_string(),
+            Value::Number(serde_json::Number::from(unhealthy_count)),
+        );
+        status.insert(
+            "paused_containers".to_string(),
+            Value::Number(serde_json::Number::from(self.paused.len())),
+        );
+        status.insert(
+            "retiring_containers".to_string(),
+            Value::Number(serde_json::Number::from(
+                containers_snapshot.iter().filter(|c| c.retiring).count(),
+            )),
+        );
         status.insert(
             "min_containers".to_string(),
-            Value::Number(serde_json::Number::from(self.min_containers)),
+            Value::Number(serde_json::Number::from(self.min_containers())),
         );
         status.insert(
             "max_containers".to_string(),
-            Value::Number(serde_json::Number::from(self.max_containers)),
+            Value::Number(serde_json::Number::from(self.max_containers())),
         );
 
         let containers_detail: Vec<Value> = containers_snapshot
@@ -446,15 +1020,45 @@ impl ContainerPool {
                     "status": format!("{:?}", c.status),
                     "last_active_ago_secs": c.last_active.elapsed().as_secs(),
                     "idle_since_secs": c.idle_since.map(|i| i.elapsed().as_secs()),
+                    "in_flight": c.in_flight,
+                    "paused": self.is_paused(&c.id),
+                    "age_secs": c.started_at.elapsed().as_secs(),
+                    "requests": c.requests,
+                    "retiring": c.retiring,
                 })
             })
             .collect();
 
         status.insert("containers".to_string(), Value::Array(containers_detail));
+        status.insert(
+            "single_concurrency".to_string(),
+            Value::Bool(self.policy().single_concurrency),
+        );
+        status.insert(
+            "idle_strategy".to_string(),
+            serde_json::to_value(self.policy().idle_strategy).unwrap_or(Value::Null),
+        );
+        status.insert(
+            "protocol".to_string(),
+            serde_json::to_value(self.policy().protocol).unwrap_or(Value::Null),
+        );
+        status.insert(
+            "service".to_string(),
+            serde_json::to_value(self.policy().service).unwrap_or(Value::Null),
+        );
+        status.insert(
+            "connections".to_string(),
+            Value::Number(serde_json::Number::from(connections)),
+        );
+        status.insert(
+            "queue_depth".to_string(),
+            Value::Number(serde_json::Number::from(self.queue_depth())),
+        );
 
         // Pool utilization metrics
-        let capacity_utilization = if self.max_containers > 0 {
-            (total_containers as f64 / self.max_containers as f64) * 100.0
+        let max_containers = self.max_containers();
+        let capacity_utilization = if max_containers > 0 {
+            (total_containers as f64 / max_containers as f64) * 100.0
         } else {
             0.0
         };
@@ -468,8 +1072,8 @@ impl ContainerPool {
         );
 
         // Scale recommendations
-        let needs_scale_up = healthy_count == 0 && total_containers < self.max_containers;
-        let can_scale_down = idle_count > 0 && total_containers > self.min_containers;
+        let needs_scale_up = healthy_count == 0 && total_containers < max_containers;
+        let can_scale_down = idle_count > 0 && total_containers > self.min_containers();
 
         status.insert("needs_scale_up".to_string(), Value::Bool(needs_scale_up));
         status.insert("can_scale_down".to_string(), Value::Bool(can_scale_down));
@@ -477,6 +1081,16 @@ impl ContainerPool {
         status
     }
 
+    /// Boot output of the last container in this pool that failed to become ready
+    pub fn last_boot_log(&self) -> Option<BootLog> {
+        self.last_boot_log.lock().unwrap().clone()
+    }
+
+    /// CPU and memory usage sampled from this pool's containers so far
+    pub fn resource_usage(&self) -> ResourceUsage {
+        self.usage.lock().unwrap().clone()
+    }
+
     /// Convert current pool state to persistable format
     pub fn to_persisted_state(&self) -> crate::core::persistence::PersistedPoolState {
         use crate::core::persistence::{PersistedContainerInfo, PersistedPoolState};
@@ -484,7 +1098,10 @@ impl ContainerPool {
         let containers: Vec<PersistedContainerInfo> = self
             .containers
             .iter()
-            .map(|entry| PersistedContainerInfo::from_container_info(entry.value()))
+            .map(|entry| PersistedContainerInfo {
+                paused: self.is_paused(entry.key()),
+                ..PersistedContainerInfo::from_container_info(entry.value())
+            })
             .collect();
 
         PersistedPoolState {
@@ -497,7 +1114,76 @@ impl ContainerPool {
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs() as i64,
+            usage: self.resource_usage(),
+            policy: self.policy(),
+            version: self.persisted_version.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Record that `state` of the pool was just saved, as `version`
+    pub fn mark_persisted(&self, version: u64, state: &crate::core::persistence::PersistedPoolState) {
+        self.persisted_version.store(version, Ordering::SeqCst);
+        *self.persisted_at.lock().unwrap() = Some(Instant::now());
+        *self.persisted_digest.lock().unwrap() = Some(state.routing_digest());
+    }
+
+    /// Whether what replicas route by changed since the pool's state was last saved
+    pub fn persisted_state_changed(&self) -> bool {
+        let digest = self.to_persisted_state().routing_digest();
+        self.persisted_digest
+            .lock()
+            .unwrap()
+            .is_none_or(|persisted| persisted != digest)
+    }
+
+    /// How long ago the pool's state was last saved; `None` if it wasn't since the pool
+    /// was created or restored
+    pub fn persisted_age(&self) -> Option<Duration> {
+        self.persisted_at
+            .lock()
+            .unwrap()
+            .map(|persisted_at| persisted_at.elapsed())
+    }
+
+    /// Merge the state another controller saved in place of this pool's last one: its
+    /// containers this pool doesn't know are adopted if they still run, and the next save
+    /// expects its version. Containers this pool removed are gone from Docker, so they
+    /// don't come back.
+    pub async fn reconcile(&self, stored: Option<crate::core::persistence::PersistedPoolState>) {
+        let Some(stored) = stored else {
+            // Removed by another controller; the next save recreates it
+            self.persisted_version.store(0, Ordering::SeqCst);
+            return;
+        };
+
+        for container in stored.containers {
+            if self.containers.contains_key(&container.id) {
+                continue;
+            }
+            let state = match self.docker.inspect_container(&container.id, None).await {
+                Ok(inspect_response) => inspect_response.state,
+                Err(e) => {
+                    debug!("Not adopting container {}: {}", container.id, e);
+                    continue;
+                }
+            };
+            let running = state.as_ref().and_then(|state| state.running);
+            if !running.unwrap_or(false) {
+                continue;
+            }
+            let paused = state.as_ref().and_then(|state| state.paused);
+            if paused.unwrap_or(false) {
+                self.paused.insert(container.id.clone(), Instant::now());
+            }
+            info!(
+                "Adopted container {} of {} from another controller's state",
+                container.id, self.function_name
+            );
+            self.containers
+                .insert(container.id.clone(), container.to_container_info());
         }
+        self.persisted_version
+            .store(stored.version, Ordering::SeqCst);
     }
 
     /// Create pool from persisted state
@@ -505,17 +1191,31 @@ impl ContainerPool {
         persisted: crate::core::persistence::PersistedPoolState,
         docker: Docker,
         network_host: String,
-        metrics_client: Arc<MetricsClient>,
+        metrics_client: Arc<dyn ContainerMetrics>,
     ) -> AppResult<Self> {
         let pool = Self {
             function_name: persisted.function_name,
             containers: Arc::new(DashMap::new()),
             docker,
             network_host,
+            address_mode: AddressMode::Network,
+            deadlines: None,
             config: persisted.config,
             min_containers: persisted.min_containers,
             max_containers: persisted.max_containers,
             metrics_client,
+            last_boot_log: Mutex::new(None),
+            usage: Arc::new(Mutex::new(persisted.usage)),
+            policy: RwLock::new(persisted.policy),
+            waiting: AtomicUsize::new(0),
+            released: Notify::new(),
+            egress: None,
+            internal_api: None,
+            sandbox: None,
+            paused: DashMap::new(),
+            persisted_version: AtomicU64::new(persisted.version),
+            persisted_at: Mutex::new(None),
+            persisted_digest: Mutex::new(None),
         };
 
         // Restore containers from persisted state
@@ -554,11 +1254,24 @@ impl ContainerPool {
                         .and_then(|state| state.running)
                         .unwrap_or(false);
 
+                    // Containers paused by an earlier controller stay paused
+                    let is_paused = inspect_response
+                        .state
+                        .as_ref()
+                        .and_then(|state| state.paused)
+                        .unwrap_or(false);
+                    if is_paused {
+                        self.paused.insert(container_id.clone(), Instant::now());
+                    }
+
                     if !is_running {
                         warn!(
                             "Container {} for function {} is not running, removing from pool",
                             container_id, self.function_name
                         );
+                        if let Err(e) = clean_up(&self.docker, &container_id).await {
+                            debug!("Failed to remove stopped container {}: {}", container_id, e);
+                        }
                         invalid_containers.push(container_id);
                     } else {
                         debug!("Container {} validated as running", container_id);
@@ -577,6 +1290,8 @@ impl ContainerPool {
         // Remove invalid containers from pool
         for container_id in invalid_containers {
             self.containers.remove(&container_id);
+            self.paused.remove(&container_id);
+            self.metrics_client.forget_container(&container_id);
         }
 
         info!(
@@ -587,17 +1302,115 @@ impl ContainerPool {
 
         Ok(())
     }
+
+    /// Probe the running containers' servers after a restore. Those that don't answer
+    /// are marked unhealthy and retired, so they take no requests and are removed on the
+    /// pool's next check.
+    pub async fn probe_containers(&self, report: &mut RestoreReport) {
+        let policy = self.policy();
+        let mut targets = Vec::new();
+        for entry in self.containers.iter() {
+            if self.is_paused(entry.key()) || policy.service == Service::Udp {
+                report.containers_unprobed += 1;
+            } else {
+                targets.push(entry.value().address());
+            }
+        }
+
+        let probes = targets
+            .iter()
+            .map(|address| probe(&address.host, address.port, policy.service, policy.protocol));
+        for (address, probed) in targets.iter().zip(join_all(probes).await) {
+            match probed {
+                Ok(()) => report.containers_ready += 1,
+                Err(reason) => {
+                    warn!(
+                        "Restored container {} of {} didn't answer its probe ({}), marking it unhealthy",
+                        address.container_id, self.function_name, reason
+                    );
+                    if let Some(mut container) = self.containers.get_mut(&address.container_id) {
+                        container.status = ContainerStatus::Unhealthy;
+                        container.idle_since = None;
+                        container.retiring = true;
+                    }
+                    report.containers_unhealthy += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Rendezvous (highest random weight) hash of an affinity key and a container.
+///
+/// Each key goes to the container with the highest weight, so adding or removing a
+/// container only moves the keys that map to it.
+pub(crate) fn affinity_weight(key: &str, container_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    container_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A container claimed for one request, released back to its pool when dropped
+pub struct ContainerLease {
+    pool: Option<Arc<ContainerPool>>,
+    address: ContainerAddress,
+    /// Removes the container if the request outlives the function's execution timeout
+    deadline: Option<DeadlineId>,
+}
+
+impl ContainerLease {
+    pub fn new(pool: Arc<ContainerPool>, address: ContainerAddress) -> Self {
+        let deadline = pool.arm_deadline(&address.container_id);
+        Self {
+            pool: Some(pool),
+            address,
+            deadline,
+        }
+    }
+
+    /// A container a follower replica routed to from the leader's pool state; there is
+    /// no claim to give back
+    pub fn detached(address: ContainerAddress) -> Self {
+        Self {
+            pool: None,
+            address,
+            deadline: None,
+        }
+    }
+
+    pub fn address(&self) -> &ContainerAddress {
+        &self.address
+    }
+
+    /// Keep the container past the function's execution timeout, for claims that aren't
+    /// requests, like debug sessions
+    pub fn without_deadline(mut self) -> Self {
+        if let (Some(pool), Some(deadline)) = (&self.pool, self.deadline.take()) {
+            pool.disarm_deadline(deadline);
+        }
+        self
+    }
 }
 
-/// Fetch container statistics from Prometheus
+impl Drop for ContainerLease {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            if let Some(deadline) = self.deadline {
+                pool.disarm_deadline(deadline);
+            }
+            pool.release_container(&self.address.container_id);
+        }
+    }
+}
+
+/// Fetch container statistics from the metrics source
 async fn fetch_container_stats(
     container_id: &str,
-    metrics_client: &Arc<MetricsClient>,
+    metrics_client: &Arc<dyn ContainerMetrics>,
 ) -> AppResult<(f64, f64)> {
-    let cpu_percentage = metrics_client.get_container_cpu_usage(container_id).await?;
-    let memory_percentage = metrics_client
-        .get_container_memory_usage(container_id)
-        .await?;
+    let cpu_percentage = metrics_client.cpu_usage(container_id).await?;
+    let memory_percentage = metrics_client.memory_usage(container_id).await?;
     Ok((cpu_percentage, memory_percentage))
 }
 
@@ -606,12 +1419,18 @@ async fn update_container_resources(
     container_id: String,
     config: MonitoringConfig,
     container: &mut ContainerInfo,
-    metrics_client: &Arc<MetricsClient>,
+    metrics_client: &Arc<dyn ContainerMetrics>,
+    usage: &Mutex<ResourceUsage>,
+    limits: ResourceLimits,
 ) -> AppResult<()> {
     // Fetch container stats
     match fetch_container_stats(&container_id, metrics_client).await {
         Ok((cpu_percentage, memory_percentage)) => {
-            debug!("Updating container {} with CPU: {:.2}%, Memory: {:.2}% (source: Prometheus)",
+            usage
+                .lock()
+                .unwrap()
+                .record(cpu_percentage, memory_percentage, limits);
+            debug!("Updating container {} with CPU: {:.2}%, Memory: {:.2}%",
                                  container.name, cpu_percentage, memory_percentage);
             debug!("Docker stats comparison for {}: check `docker stats --no-stream {}`",
                                  container.name, &container_id[0..12]);
@@ -631,20 +1450,26 @@ async fn update_container_resources(
     Ok(())
 }
 
-fn to_container_details(container_info: &ContainerInfo) -> ContainerDetails {
-    ContainerDetails {
-        container_id: container_info.id.clone(),
-        container_port: container_info.container_port,
-        bind_port: "".to_string(),
-        container_name: container_info.name.clone(),
-        timeout: 0,
-        docker_compose_network_host: "".to_string(),
+/// Where a new container's port was published, when the controller dials published ports
+fn published_port(address_mode: &AddressMode, host_port: Option<u16>) -> Option<PublishedPort> {
+    match (address_mode, host_port) {
+        (AddressMode::PublishedPort { host }, Some(port)) => Some(PublishedPort {
+            host: host.clone(),
+            port,
+        }),
+        (AddressMode::PublishedPort { .. }, None) => {
+            // e.g. on an internal egress network, which has no published ports
+            warn!("Function port was not published, dialing the container on its network");
+            None
+        }
+        (AddressMode::Network, _) => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::policy::Service;
 
     #[test]
     fn test_container_info_status_transitions() {
@@ -664,6 +1489,37 @@ mod tests {
         assert!(container.idle_since.is_some());
     }
 
+    #[test]
+    fn test_container_address_on_its_network() {
+        // Docker Compose: the controller shares the network and resolves the name
+        let mut container = ContainerInfo::new("abc".to_string(), "fn-abc".to_string(), 8080);
+        assert_eq!(container.address().authority(), "fn-abc:8080");
+
+        // Once its IP is known, it is dialed directly
+        container.ip_address = Some("172.18.0.7".to_string());
+        let address = container.address();
+        assert_eq!(address.container_id, "abc");
+        assert_eq!(address.container_name, "fn-abc");
+        assert_eq!(address.authority(), "172.18.0.7:8080");
+        assert_eq!(published_port(&AddressMode::Network, Some(49153)), None);
+    }
+
+    #[test]
+    fn test_container_address_at_published_port() {
+        // A controller on the daemon host that can't route to the container's IP
+        let mode = AddressMode::PublishedPort {
+            host: "127.0.0.1".to_string(),
+        };
+        let mut container = ContainerInfo::new("abc".to_string(), "fn-abc".to_string(), 8080);
+        container.ip_address = Some("172.17.0.3".to_string());
+        container.published = published_port(&mode, Some(49153));
+        assert_eq!(container.address().authority(), "127.0.0.1:49153");
+
+        // Not published (an internal network): falls back to the network address
+        container.published = published_port(&mode, None);
+        assert_eq!(container.address().authority(), "172.17.0.3:8080");
+    }
+
     #[test]
     fn test_container_active_marking() {
         let mut container = ContainerInfo::new("test-id".to_string(), "test-name".to_string(), 0);
@@ -677,4 +1533,273 @@ mod tests {
         assert_eq!(container.status, ContainerStatus::Healthy);
         assert!(container.idle_since.is_none());
     }
+
+    fn test_pool(policy: FunctionPolicy) -> ContainerPool {
+        let pool = ContainerPool::new(
+            "test-function".to_string(),
+            Docker::connect_with_http_defaults().unwrap(),
+            "test-network".to_string(),
+            MonitoringConfig::default(),
+            1,
+            5,
+            Arc::new(crate::core::metrics_client::MetricsClient::new(
+                Default::default(),
+            )),
+        );
+        pool.set_policy(policy);
+        for id in ["a", "b"] {
+            pool.containers.insert(
+                id.to_string(),
+                ContainerInfo::new(id.to_string(), format!("container-{id}"), 8080),
+            );
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        let pool = test_pool(FunctionPolicy::default());
+        pool.mark_persisted(3, &pool.to_persisted_state());
+
+        // The stored state is taken in: containers this pool knows stay as they are, and
+        // those it can't find running aren't adopted
+        let mut stored = pool.to_persisted_state();
+        stored.version = 5;
+        let mut gone = stored.containers[0].clone();
+        gone.id = "gone".to_string();
+        stored.containers.push(gone);
+        pool.reconcile(Some(stored)).await;
+        assert_eq!(pool.to_persisted_state().version, 5);
+        assert_eq!(pool.container_count(), 2);
+        assert!(!pool.containers.contains_key("gone"));
+
+        // Removed by another controller: the next save creates it anew
+        pool.reconcile(None).await;
+        assert_eq!(pool.to_persisted_state().version, 0);
+    }
+
+    #[test]
+    fn test_persisted_state_changed() {
+        let pool = test_pool(FunctionPolicy::default());
+        assert!(pool.persisted_state_changed());
+
+        pool.mark_persisted(1, &pool.to_persisted_state());
+        assert!(!pool.persisted_state_changed());
+
+        // Activity alone doesn't need a save
+        pool.containers.get_mut("a").unwrap().mark_active();
+        assert!(!pool.persisted_state_changed());
+
+        pool.containers.remove("b");
+        assert!(pool.persisted_state_changed());
+    }
+
+    #[tokio::test]
+    async fn test_single_concurrency_routes_to_free_containers() {
+        let pool = test_pool(FunctionPolicy {
+            single_concurrency: true,
+            ..Default::default()
+        });
+
+        let first = pool.acquire_container(None).unwrap();
+        let second = pool.acquire_container(None).unwrap();
+        assert_ne!(first.container_id, second.container_id);
+        assert!(pool.acquire_container(None).is_none());
+        assert!(pool.claim_container(&first.container_id).is_none());
+
+        pool.release_container(&first.container_id);
+        let third = pool.acquire_container(None).unwrap();
+        assert_eq!(third.container_id, first.container_id);
+    }
+
+    #[tokio::test]
+    async fn test_paused_containers_are_not_routed_to() {
+        let pool = test_pool(FunctionPolicy::default());
+        pool.paused.insert("a".to_string(), Instant::now());
+
+        for _ in 0..3 {
+            assert_eq!(pool.acquire_container(None).unwrap().container_id, "b");
+        }
+        assert_eq!(pool.running_count(), 1);
+        assert!(pool.paused_longer_than(Duration::from_secs(60)).is_empty());
+        assert_eq!(
+            pool.paused_longer_than(Duration::ZERO),
+            vec!["a".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_containers_past_their_lifetime_are_recycled() {
+        let pool = test_pool(FunctionPolicy {
+            max_container_requests: Some(2),
+            ..Default::default()
+        });
+        assert!(pool.containers_to_recycle().is_empty());
+
+        for _ in 0..2 {
+            let container = pool.claim_container("a").unwrap();
+            pool.release_container(&container.container_id);
+        }
+        let due = pool.containers_to_recycle();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, "a");
+        assert_eq!(due[0].1, "served 2 requests");
+
+        let claimed = pool.claim_container("a").unwrap();
+        assert!(pool.retire_container("a"));
+        assert!(pool.containers_to_recycle().is_empty());
+        assert_eq!(pool.serving_count(), 1);
+        // Its request finishes before it goes
+        assert!(pool.retired_containers().is_empty());
+        assert!(pool.claim_container("a").is_none());
+        for _ in 0..3 {
+            assert_eq!(pool.acquire_container(None).unwrap().container_id, "b");
+        }
+        pool.release_container(&claimed.container_id);
+        assert_eq!(pool.retired_containers(), vec!["a".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_oldest_containers_are_recycled_first() {
+        let pool = test_pool(FunctionPolicy {
+            max_container_age_secs: Some(60),
+            ..Default::default()
+        });
+        assert!(pool.containers_to_recycle().is_empty());
+
+        for (id, age) in [("a", 90), ("b", 120)] {
+            pool.containers.get_mut(id).unwrap().started_at =
+                Instant::now() - Duration::from_secs(age);
+        }
+        let due: Vec<_> = pool
+            .containers_to_recycle()
+            .into_iter()
+            .map(|(container_id, _)| container_id)
+            .collect();
+        assert_eq!(due, vec!["b".to_string(), "a".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_default_policy_shares_containers() {
+        let pool = test_pool(FunctionPolicy::default());
+
+        for _ in 0..4 {
+            assert!(pool.acquire_container(None).is_some());
+        }
+        let in_flight: usize = pool.containers.iter().map(|c| c.in_flight).sum();
+        assert_eq!(in_flight, 4);
+        assert!(!pool.needs_scale_up());
+    }
+
+    #[tokio::test]
+    async fn test_single_concurrency_scales_on_queue_depth() {
+        let pool = Arc::new(test_pool(FunctionPolicy {
+            single_concurrency: true,
+            ..Default::default()
+        }));
+        assert!(!pool.needs_scale_up());
+
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move { pool.wait_for_release(Duration::from_secs(5)).await })
+        };
+        while pool.queue_depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(pool.needs_scale_up());
+
+        pool.release_container("a");
+        assert!(waiter.await.unwrap());
+        assert_eq!(pool.queue_depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_service_scales_on_connection_count() {
+        let pool = Arc::new(test_pool(FunctionPolicy {
+            service: Service::Tcp,
+            max_connections: Some(2),
+            ..Default::default()
+        }));
+
+        // Connections spread over the containers, up to the limit on each
+        let claimed: Vec<_> = (0..4)
+            .map(|_| pool.acquire_container(None).unwrap().container_id)
+            .collect();
+        assert_eq!(claimed.iter().filter(|id| *id == "a").count(), 2);
+        assert_eq!(claimed.iter().filter(|id| *id == "b").count(), 2);
+        assert!(pool.acquire_container(None).is_none());
+        assert!(!pool.needs_scale_up());
+
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move { pool.wait_for_release(Duration::from_secs(5)).await })
+        };
+        while pool.queue_depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(pool.needs_scale_up());
+
+        // A closed connection makes room again
+        pool.release_container("b");
+        assert!(waiter.await.unwrap());
+        assert_eq!(pool.acquire_container(None).unwrap().container_id, "b");
+    }
+
+    #[tokio::test]
+    async fn test_sticky_routing_keeps_key_on_same_container() {
+        let pool = test_pool(FunctionPolicy::default());
+        for id in ["c", "d"] {
+            pool.containers.insert(
+                id.to_string(),
+                ContainerInfo::new(id.to_string(), format!("container-{id}"), 8080),
+            );
+        }
+
+        let first = pool.get_healthiest_container(Some("session-1")).unwrap();
+        for _ in 0..10 {
+            let again = pool.get_healthiest_container(Some("session-1")).unwrap();
+            assert_eq!(again.container_id, first.container_id);
+        }
+
+        // Removing another container doesn't move the session
+        let other = ["a", "b", "c", "d"]
+            .into_iter()
+            .find(|id| *id != first.container_id)
+            .unwrap();
+        pool.containers.remove(other);
+        let after = pool.get_healthiest_container(Some("session-1")).unwrap();
+        assert_eq!(after.container_id, first.container_id);
+
+        // Removing its container moves it to a remaining one
+        pool.containers.remove(&first.container_id);
+        let moved = pool.get_healthiest_container(Some("session-1")).unwrap();
+        assert_ne!(moved.container_id, first.container_id);
+    }
+
+    #[tokio::test]
+    async fn test_policy_limits_override_pool_defaults() {
+        let pool = test_pool(FunctionPolicy::default());
+        assert_eq!(pool.min_containers(), 1);
+        assert_eq!(pool.max_containers(), 5);
+        assert_eq!(pool.resource_limits(), ResourceLimits::default());
+
+        pool.set_policy(FunctionPolicy {
+            memory_mb: Some(512),
+            min_containers: Some(2),
+            max_containers: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(pool.min_containers(), 2);
+        assert_eq!(pool.max_containers(), 2);
+        assert_eq!(pool.resource_limits().memory_bytes, 512 * 1024 * 1024);
+        // Two containers already running
+        assert!(!pool.needs_scale_up());
+
+        // A function can't raise the pool's maximum
+        pool.set_policy(FunctionPolicy {
+            max_containers: Some(50),
+            ..Default::default()
+        });
+        assert_eq!(pool.max_containers(), 5);
+    }
 }
@@ -0,0 +1,184 @@
+use crate::core::runner::clean_up;
+use bollard::Docker;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, warn};
+
+/// Identifies an armed deadline, to disarm it with
+pub type DeadlineId = u64;
+
+enum Command {
+    Arm {
+        id: DeadlineId,
+        container_id: String,
+        timeout: Duration,
+    },
+    Disarm(DeadlineId),
+}
+
+/// Execution deadlines of the requests containers are serving.
+///
+/// A single task keeps every deadline and sleeps until the next one, instead of a timer
+/// per request. A container still serving a request at its deadline is removed: the
+/// autoscaler sees it die, evicts it from its pool and replaces it as needed.
+#[derive(Debug, Clone)]
+pub struct ExecutionDeadlines {
+    commands: mpsc::UnboundedSender<Command>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl ExecutionDeadlines {
+    /// Starts the task keeping the deadlines; it stops once every handle is dropped
+    pub fn start(docker: Docker) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_deadlines(docker, receiver));
+        Self {
+            commands,
+            next_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Removes the container unless the deadline is disarmed within `timeout`
+    pub fn arm(&self, container_id: &str, timeout: Duration) -> DeadlineId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let _ = self.commands.send(Command::Arm {
+            id,
+            container_id: container_id.to_string(),
+            timeout,
+        });
+        id
+    }
+
+    /// Forgets a deadline, e.g. once its request finished
+    pub fn disarm(&self, id: DeadlineId) {
+        let _ = self.commands.send(Command::Disarm(id));
+    }
+}
+
+async fn run_deadlines(docker: Docker, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let mut wheel = DeadlineWheel::default();
+    loop {
+        let next = wheel.next();
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Arm { id, container_id, timeout }) => {
+                    wheel.arm(id, container_id, timeout, Instant::now());
+                }
+                Some(Command::Disarm(id)) => wheel.disarm(id),
+                None => return,
+            },
+            _ = sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                for (container_id, timeout) in wheel.expire(Instant::now()) {
+                    warn!(
+                        "Container {} is still serving a request after its execution timeout of {} s, removing it",
+                        container_id,
+                        timeout.as_secs()
+                    );
+                    let docker = docker.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = clean_up(&docker, &container_id).await {
+                            debug!("Failed to remove container {}: {}", container_id, e);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Armed deadlines in the order they expire
+#[derive(Debug, Default)]
+struct DeadlineWheel {
+    by_expiry: BTreeMap<(Instant, DeadlineId), (String, Duration)>,
+    expiries: HashMap<DeadlineId, Instant>,
+}
+
+impl DeadlineWheel {
+    fn arm(&mut self, id: DeadlineId, container_id: String, timeout: Duration, now: Instant) {
+        let at = now + timeout;
+        self.by_expiry.insert((at, id), (container_id, timeout));
+        self.expiries.insert(id, at);
+    }
+
+    fn disarm(&mut self, id: DeadlineId) {
+        if let Some(at) = self.expiries.remove(&id) {
+            self.by_expiry.remove(&(at, id));
+        }
+    }
+
+    /// When the next deadline expires
+    fn next(&self) -> Option<Instant> {
+        self.by_expiry.keys().next().map(|(at, _)| *at)
+    }
+
+    /// Removes the deadlines expired at `now`, returning each of their containers once
+    /// with its timeout
+    fn expire(&mut self, now: Instant) -> Vec<(String, Duration)> {
+        let mut expired: Vec<(String, Duration)> = Vec::new();
+        while let Some(entry) = self.by_expiry.first_entry() {
+            let (at, id) = *entry.key();
+            if at > now {
+                break;
+            }
+            let (container_id, timeout) = entry.remove();
+            self.expiries.remove(&id);
+            if !expired.iter().any(|(known, _)| *known == container_id) {
+                expired.push((container_id, timeout));
+            }
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadlines_expire_in_order() {
+        let mut wheel = DeadlineWheel::default();
+        let now = Instant::now();
+        wheel.arm(0, "b".to_string(), Duration::from_secs(20), now);
+        wheel.arm(1, "a".to_string(), Duration::from_secs(10), now);
+        assert_eq!(wheel.next(), Some(now + Duration::from_secs(10)));
+
+        assert!(wheel.expire(now + Duration::from_secs(5)).is_empty());
+        assert_eq!(
+            wheel.expire(now + Duration::from_secs(10)),
+            vec![("a".to_string(), Duration::from_secs(10))]
+        );
+        assert_eq!(wheel.next(), Some(now + Duration::from_secs(20)));
+        assert_eq!(wheel.expire(now + Duration::from_secs(30)).len(), 1);
+        assert_eq!(wheel.next(), None);
+    }
+
+    #[test]
+    fn test_disarmed_deadlines_never_expire() {
+        let mut wheel = DeadlineWheel::default();
+        let now = Instant::now();
+        wheel.arm(0, "a".to_string(), Duration::from_secs(10), now);
+        wheel.arm(1, "a".to_string(), Duration::from_secs(10), now);
+        wheel.disarm(0);
+        wheel.disarm(7);
+
+        // The container's other request is still running
+        assert_eq!(wheel.expire(now + Duration::from_secs(10)).len(), 1);
+        wheel.disarm(1);
+        assert_eq!(wheel.next(), None);
+    }
+
+    #[test]
+    fn test_container_expires_once() {
+        let mut wheel = DeadlineWheel::default();
+        let now = Instant::now();
+        for id in 0..3 {
+            wheel.arm(id, "a".to_string(), Duration::from_secs(1), now);
+        }
+        assert_eq!(wheel.expire(now + Duration::from_secs(1)).len(), 1);
+        assert!(wheel.expiries.is_empty());
+    }
+}
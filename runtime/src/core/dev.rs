@@ -0,0 +1,538 @@
+use crate::core::egress::{namespace_of, EgressGateway};
+use crate::core::internal_api::InternalApiConfig;
+use crate::core::logs::{ContainerLogStreamer, LogMessage};
+use crate::core::platform::daemon_platform;
+use crate::core::runner::{clean_up, container_ip, cpu_limits};
+use crate::core::sandbox::Sandbox;
+use crate::shared::error::{AppResult, RuntimeError};
+use bollard::container::{
+    Config, CreateContainerOptions, ListContainersOptions, UploadToContainerOptions,
+};
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::image::CreateImageOptions;
+use bollard::models::{ContainerSummary, HostConfig};
+use bollard::Docker;
+use futures_util::stream::{Stream, StreamExt};
+use shared_utils::unix_now;
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Label attached to every dev container, holding the function key
+pub const DEV_LABEL: &str = "invok.dev";
+
+/// Label holding when a dev container expires, in seconds since the epoch
+const DEV_EXPIRES_LABEL: &str = "invok.dev.expires";
+
+/// Directory of a dev container the function's sources are synced to
+pub const DEV_WORKDIR: &str = "/app";
+
+/// Port the function listens on in a dev container, as in its image
+pub const DEV_PORT: u16 = 8080;
+
+/// How long a dev container lives before it's removed
+pub const DEV_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Most dev containers a namespace may run at once
+pub const MAX_DEV_CONTAINERS_PER_NAMESPACE: usize = 3;
+
+/// Most paths one sync may delete
+const MAX_DELETED_PATHS: usize = 1000;
+
+/// Toolchains need far more room than the binaries they build
+const DEV_MEMORY_BYTES: i64 = 2 * 1024 * 1024 * 1024;
+const DEV_CPUS: f64 = 2.0;
+
+/// Directory the supervisor watches for the restart marker
+const MARKER_DIR: &str = "/tmp";
+
+/// File the supervisor (re)starts the function on
+const RESTART_MARKER: &str = "invok-restart";
+
+/// Environment variable holding the command building and running the function
+const RUN_ENV: &str = "INVOK_DEV_RUN";
+
+/// Runs the function once the restart marker appears, and kills and reruns it whenever
+/// the marker appears again. Job control puts the build and the function in a process
+/// group of their own, so a restart mid-build stops the compiler too.
+const SUPERVISOR_SCRIPT: &str = r#"set -m
+marker=/tmp/invok-restart
+while :; do
+  while [ ! -e "$marker" ]; do sleep 0.5; done
+  rm -f "$marker"
+  echo "[invok dev] building and starting the function"
+  sh -c "$INVOK_DEV_RUN" &
+  pid=$!
+  while kill -0 "$pid" 2>/dev/null && [ ! -e "$marker" ]; do sleep 0.5; done
+  if kill -0 "$pid" 2>/dev/null; then
+    echo "[invok dev] sources changed, restarting"
+    kill -TERM -- "-$pid" 2>/dev/null || kill -TERM "$pid"
+    wait "$pid"
+  else
+    wait "$pid"
+    echo "[invok dev] the function exited with status $?, waiting for changes"
+  fi
+done
+"#;
+
+/// Image and commands a runtime's functions are built and run with in dev containers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Toolchain {
+    /// The build stage image of the runtime's Dockerfile
+    pub image: &'static str,
+    /// Shell command building the function in [`DEV_WORKDIR`] and running it
+    pub run: &'static str,
+}
+
+/// Toolchain of a runtime, if it has one
+pub fn toolchain(runtime: &str) -> Option<Toolchain> {
+    match runtime {
+        "go" => Some(Toolchain {
+            image: "golang:1.23",
+            run: "{ [ -f go.mod ] || go mod init serverless-function; } && go mod tidy && go build -o /tmp/function . && exec /tmp/function",
+        }),
+        "nodejs" => Some(Toolchain {
+            image: "node:22-alpine",
+            run: "npm install --no-audit --no-fund && { [ -z \"$(ls -A .invok-shared 2>/dev/null)\" ] || npm run build --workspaces --if-present; } && npm run build && exec node dist/server.js",
+        }),
+        "rust" => Some(Toolchain {
+            image: "rust:1-slim",
+            run: "cargo build --bin function && exec ./target/debug/function",
+        }),
+        _ => None,
+    }
+}
+
+/// A running dev container
+#[derive(Debug, Clone)]
+pub struct DevContainer {
+    pub container_id: String,
+    /// The function key of the function being developed
+    pub function_key: String,
+    /// Address of the container on its network
+    pub ip_address: Option<String>,
+    /// When the container is removed, in seconds since the epoch
+    pub expires_at: u64,
+}
+
+impl DevContainer {
+    fn from_summary(summary: ContainerSummary) -> Option<Self> {
+        let labels = summary.labels?;
+        let function_key = labels.get(DEV_LABEL)?.clone();
+        let expires_at = labels
+            .get(DEV_EXPIRES_LABEL)
+            .and_then(|expires| expires.parse().ok())
+            .unwrap_or(0);
+        let ip_address = summary
+            .network_settings
+            .and_then(|settings| settings.networks)
+            .and_then(|networks| {
+                networks
+                    .into_values()
+                    .filter_map(|endpoint| endpoint.ip_address)
+                    .find(|ip| !ip.is_empty())
+            });
+        Some(Self {
+            container_id: summary.id?,
+            function_key,
+            ip_address,
+            expires_at,
+        })
+    }
+
+    /// Seconds until the container is removed
+    pub fn expires_in(&self) -> u64 {
+        self.expires_at.saturating_sub(unix_now())
+    }
+}
+
+/// Dedicated containers functions are developed in: the function's sources are synced
+/// into them and it's rebuilt and restarted on every change, on the same networks,
+/// egress allowlist, internal API access and sandbox as the deployed function.
+#[derive(Clone)]
+pub struct DevContainers {
+    docker: Docker,
+    /// Network the containers join when egress is disabled
+    network_host: String,
+    egress: Option<Arc<EgressGateway>>,
+    internal_api: Option<Arc<InternalApiConfig>>,
+    sandbox: Option<Arc<Sandbox>>,
+}
+
+impl DevContainers {
+    pub fn new(
+        docker: Docker,
+        network_host: String,
+        egress: Option<Arc<EgressGateway>>,
+        internal_api: Option<Arc<InternalApiConfig>>,
+        sandbox: Option<Arc<Sandbox>>,
+    ) -> Self {
+        Self {
+            docker,
+            network_host,
+            egress,
+            internal_api,
+            sandbox,
+        }
+    }
+
+    /// Every running dev container
+    pub async fn list(&self) -> AppResult<Vec<DevContainer>> {
+        self.list_labelled(DEV_LABEL.to_string(), false).await
+    }
+
+    /// The running dev container of a function, if it has one
+    pub async fn find(&self, function_key: &str) -> AppResult<Option<DevContainer>> {
+        Ok(self
+            .list_labelled(format!("{DEV_LABEL}={function_key}"), false)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Dev containers of the function key's namespace, other than the function's own
+    pub async fn count_in_namespace(&self, function_key: &str) -> AppResult<usize> {
+        let namespace = namespace_of(function_key);
+        Ok(self
+            .list()
+            .await?
+            .iter()
+            .filter(|dev| {
+                dev.function_key != function_key && namespace_of(&dev.function_key) == namespace
+            })
+            .count())
+    }
+
+    /// Start a dev container for a function, replacing the one it has, with `sources`
+    /// (a tar archive) extracted into [`DEV_WORKDIR`] and the function started from them.
+    ///
+    /// The container's output is the function's, along with the supervisor's notes on
+    /// each restart; see [`DevContainers::logs`].
+    pub async fn provision(
+        &self,
+        function_key: &str,
+        runtime: &str,
+        env: Vec<String>,
+        sources: Vec<u8>,
+    ) -> AppResult<DevContainer> {
+        let toolchain = toolchain(runtime).ok_or_else(|| {
+            RuntimeError::Invalid(format!("There's no dev toolchain for runtime {runtime}"))
+        })?;
+        self.remove(function_key).await?;
+        self.ensure_image(toolchain.image).await?;
+
+        let mut env = env;
+        let mut network = self.network_host.clone();
+        if let Some(egress) = &self.egress {
+            let function_egress = egress.prepare(function_key).await?;
+            env.extend(function_egress.env());
+            network = function_egress.network;
+        }
+        if let Some(internal_api) = &self.internal_api {
+            env.extend(internal_api.env(function_key));
+        }
+        env.push(format!("{RUN_ENV}={}", toolchain.run));
+
+        let network_mode = match &self.sandbox {
+            Some(sandbox) => sandbox.setup_network().await?.to_string(),
+            None => network.clone(),
+        };
+        let expires_at = unix_now() + DEV_TTL.as_secs();
+        let expires = expires_at.to_string();
+        let labels = HashMap::from([
+            (DEV_LABEL, function_key),
+            (DEV_EXPIRES_LABEL, expires.as_str()),
+        ]);
+        let (cpu_period, cpu_quota) = cpu_limits(DEV_CPUS);
+        let config = Config {
+            image: Some(toolchain.image),
+            cmd: Some(vec!["sh", "-c", SUPERVISOR_SCRIPT]),
+            working_dir: Some(DEV_WORKDIR),
+            env: Some(env.iter().map(String::as_str).collect()),
+            labels: Some(labels),
+            host_config: Some(HostConfig {
+                memory: Some(DEV_MEMORY_BYTES),
+                cpu_period: Some(cpu_period),
+                cpu_quota: Some(cpu_quota),
+                network_mode: Some(network_mode),
+                auto_remove: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let platform = match daemon_platform(&self.docker).await {
+            Ok(platform) => Some(platform.to_string()),
+            Err(e) => {
+                warn!("Creating dev container without a platform: {e}");
+                None
+            }
+        };
+        let container_name = format!("invok-dev-{function_key}");
+        let container_id = self
+            .docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: container_name.as_str(),
+                    platform: platform.as_deref(),
+                }),
+                config,
+            )
+            .await
+            .map_err(|e| RuntimeError::System(format!("Failed to create dev container: {e}")))?
+            .id;
+
+        let started = async {
+            self.docker
+                .start_container::<String>(&container_id, None)
+                .await
+                .map_err(|e| RuntimeError::System(format!("Failed to start dev container: {e}")))?;
+            if let Some(sandbox) = &self.sandbox {
+                sandbox.confine(&container_id, &network).await?;
+            }
+            self.upload(&container_id, sources).await?;
+            self.restart(&container_id).await
+        };
+        if let Err(e) = started.await {
+            let _ = clean_up(&self.docker, &container_id).await;
+            return Err(e);
+        }
+
+        info!(
+            function_key = %function_key,
+            container_id = %container_id,
+            image = %toolchain.image,
+            "Started dev container"
+        );
+        Ok(DevContainer {
+            ip_address: container_ip(&self.docker, &container_id, &network).await,
+            container_id,
+            function_key: function_key.to_string(),
+            expires_at,
+        })
+    }
+
+    /// Apply changes to the sources of a dev container and restart its function:
+    /// `deleted` paths (relative to [`DEV_WORKDIR`]) are removed, then `files` (a tar
+    /// archive, possibly empty) is extracted over the rest.
+    pub async fn sync(
+        &self,
+        container_id: &str,
+        files: Vec<u8>,
+        deleted: &[String],
+    ) -> AppResult<()> {
+        if deleted.len() > MAX_DELETED_PATHS {
+            return Err(RuntimeError::Invalid(format!(
+                "A sync may delete at most {MAX_DELETED_PATHS} paths"
+            )));
+        }
+        for path in deleted {
+            validate_source_path(path).map_err(RuntimeError::Invalid)?;
+        }
+
+        if !deleted.is_empty() {
+            let mut command = vec!["rm".to_string(), "-rf".to_string(), "--".to_string()];
+            command.extend(deleted.iter().map(|path| format!("{DEV_WORKDIR}/{path}")));
+            self.run(container_id, command).await?;
+        }
+        if !files.is_empty() {
+            self.upload(container_id, files).await?;
+        }
+        self.restart(container_id).await
+    }
+
+    /// Remove the dev container of a function; `false` if it had none
+    pub async fn remove(&self, function_key: &str) -> AppResult<bool> {
+        let containers = self
+            .list_labelled(format!("{DEV_LABEL}={function_key}"), true)
+            .await?;
+        for container in &containers {
+            clean_up(&self.docker, &container.container_id).await?;
+        }
+        Ok(!containers.is_empty())
+    }
+
+    /// Remove the dev containers past their expiry, returning how many were removed
+    pub async fn remove_expired(&self) -> AppResult<usize> {
+        let now = unix_now();
+        let mut removed = 0;
+        for container in self.list_labelled(DEV_LABEL.to_string(), true).await? {
+            if container.expires_at > now {
+                continue;
+            }
+            match clean_up(&self.docker, &container.container_id).await {
+                Ok(()) => {
+                    info!(function_key = %container.function_key, "Removed expired dev container");
+                    removed += 1;
+                }
+                Err(e) => warn!(
+                    function_key = %container.function_key,
+                    "Failed to remove expired dev container: {e}"
+                ),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Follow the output of a dev container: build output, the function's logs and the
+    /// supervisor's restarts
+    pub async fn logs(&self, container_id: &str) -> AppResult<impl Stream<Item = LogMessage>> {
+        ContainerLogStreamer::with_docker(self.docker.clone())
+            .stream_logs(container_id, true)
+            .await
+    }
+
+    async fn list_labelled(&self, label: String, all: bool) -> AppResult<Vec<DevContainer>> {
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all,
+                filters: HashMap::from([("label".to_string(), vec![label])]),
+                ..Default::default()
+            }))
+            .await
+            .map_err(|e| RuntimeError::System(format!("Failed to list dev containers: {e}")))?;
+        Ok(containers
+            .into_iter()
+            .filter_map(DevContainer::from_summary)
+            .collect())
+    }
+
+    async fn upload(&self, container_id: &str, archive: Vec<u8>) -> AppResult<()> {
+        self.docker
+            .upload_to_container(
+                container_id,
+                Some(UploadToContainerOptions {
+                    path: DEV_WORKDIR,
+                    ..Default::default()
+                }),
+                archive.into(),
+            )
+            .await
+            .map_err(|e| RuntimeError::System(format!("Failed to copy sources: {e}")))
+    }
+
+    /// Have the supervisor (re)start the function
+    async fn restart(&self, container_id: &str) -> AppResult<()> {
+        let mut marker = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        header.set_mode(0o644);
+        header.set_cksum();
+        marker
+            .append_data(&mut header, RESTART_MARKER, std::io::empty())
+            .map_err(|e| RuntimeError::System(e.to_string()))?;
+        let marker = marker
+            .into_inner()
+            .map_err(|e| RuntimeError::System(e.to_string()))?;
+
+        self.docker
+            .upload_to_container(
+                container_id,
+                Some(UploadToContainerOptions {
+                    path: MARKER_DIR,
+                    ..Default::default()
+                }),
+                marker.into(),
+            )
+            .await
+            .map_err(|e| RuntimeError::System(format!("Failed to restart the function: {e}")))
+    }
+
+    /// Run a command in a dev container and wait for it to finish
+    async fn run(&self, container_id: &str, command: Vec<String>) -> AppResult<()> {
+        let exec = self
+            .docker
+            .create_exec(
+                container_id,
+                CreateExecOptions {
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    cmd: Some(command),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to create exec: {e}")))?;
+        let started = self
+            .docker
+            .start_exec(&exec.id, None)
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to start exec: {e}")))?;
+        if let StartExecResults::Attached { mut output, .. } = started {
+            while output.next().await.is_some() {}
+        }
+        Ok(())
+    }
+
+    async fn ensure_image(&self, image: &str) -> AppResult<()> {
+        if self.docker.inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+        info!("Pulling dev toolchain image {image}");
+        let mut pull_stream = self.docker.create_image(
+            Some(CreateImageOptions {
+                from_image: image,
+                ..Default::default()
+            }),
+            None,
+            None,
+        );
+        while let Some(pull_info) = pull_stream.next().await {
+            if let Err(e) = pull_info {
+                return Err(RuntimeError::System(format!(
+                    "Failed to pull dev toolchain image {image}: {e}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check a path of a function's sources: it must be relative and stay inside
+/// [`DEV_WORKDIR`]
+pub fn validate_source_path(path: &str) -> Result<(), String> {
+    if path.is_empty() || path.contains('\0') {
+        return Err(format!("Invalid source path '{path}'"));
+    }
+    let inside = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !inside {
+        return Err(format!(
+            "Source path '{path}' leaves the function's directory"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toolchain_of_each_runtime() {
+        assert_eq!(toolchain("go").map(|t| t.image), Some("golang:1.23"));
+        assert_eq!(toolchain("nodejs").map(|t| t.image), Some("node:22-alpine"));
+        assert_eq!(toolchain("rust").map(|t| t.image), Some("rust:1-slim"));
+        assert_eq!(toolchain("python"), None);
+    }
+
+    #[test]
+    fn test_validate_source_path_accepts_relative_paths() {
+        assert!(validate_source_path("handler.go").is_ok());
+        assert!(validate_source_path("src/lib/util.ts").is_ok());
+    }
+
+    #[test]
+    fn test_validate_source_path_rejects_escapes() {
+        assert!(validate_source_path("").is_err());
+        assert!(validate_source_path("/etc/passwd").is_err());
+        assert!(validate_source_path("../outside").is_err());
+        assert!(validate_source_path("src/../../outside").is_err());
+        assert!(validate_source_path("./handler.go").is_err());
+        assert!(validate_source_path("bad\0name").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use bollard::container::LogsOptions;
+use bollard::Docker;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Number of trailing log lines kept in a crash report
+pub const CRASH_LOG_LINES: usize = 50;
+
+/// Number of trailing log lines kept for a container that failed to become ready
+pub const BOOT_LOG_LINES: usize = 200;
+
+/// Exit code reported by Docker when a container is SIGKILLed (e.g. by the OOM killer)
+const SIGKILL_EXIT_CODE: i64 = 137;
+
+/// Diagnostics captured when a function container exits unexpectedly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub container_id: String,
+    pub exit_code: Option<i64>,
+    pub oom_killed: bool,
+    /// Error reported by the Docker daemon, if any
+    pub error: Option<String>,
+    /// Last lines of the container's stdout/stderr
+    pub last_logs: Vec<String>,
+    /// When the crash was recorded (unix seconds)
+    pub occurred_at: i64,
+}
+
+impl CrashReport {
+    /// Short, user facing description of why the container stopped
+    pub fn summary(&self) -> String {
+        let exit_code = self
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        if self.oom_killed {
+            format!(
+                "container was killed after running out of memory (exit code {})",
+                exit_code
+            )
+        } else if self.exit_code == Some(SIGKILL_EXIT_CODE) {
+            format!("container was killed (exit code {})", exit_code)
+        } else {
+            match &self.error {
+                Some(error) if !error.is_empty() => {
+                    format!("container exited with code {}: {}", exit_code, error)
+                }
+                _ => format!("container exited with code {}", exit_code),
+            }
+        }
+    }
+}
+
+/// Output of a container that never signalled it was ready to accept connections
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BootLog {
+    pub container_id: String,
+    /// Why the container was considered not ready
+    pub reason: String,
+    /// Everything the container printed while booting (last `BOOT_LOG_LINES` lines)
+    pub lines: Vec<String>,
+    /// When the log was captured (unix seconds)
+    pub captured_at: i64,
+}
+
+/// Capture the boot output of a container that did not become ready
+pub async fn collect_boot_log(docker: &Docker, container_id: &str, reason: &str) -> BootLog {
+    BootLog {
+        container_id: container_id.to_string(),
+        reason: reason.to_string(),
+        lines: tail_logs(docker, container_id, BOOT_LOG_LINES).await,
+        captured_at: now_unix(),
+    }
+}
+
+/// Collect a crash report for a container that has exited.
+///
+/// Must run before the container is removed, since both the inspect data and the
+/// logs go away with it. Missing data is tolerated: whatever can be read is reported.
+pub async fn collect_crash_report(
+    docker: &Docker,
+    container_id: &str,
+    exit_code_hint: Option<i64>,
+) -> CrashReport {
+    let mut report = CrashReport {
+        container_id: container_id.to_string(),
+        exit_code: exit_code_hint,
+        oom_killed: false,
+        error: None,
+        last_logs: Vec::new(),
+        occurred_at: now_unix(),
+    };
+
+    match docker.inspect_container(container_id, None).await {
+        Ok(inspect) => {
+            if let Some(state) = inspect.state {
+                report.exit_code = state.exit_code.or(report.exit_code);
+                report.oom_killed = state.oom_killed.unwrap_or(false);
+                report.error = state.error.filter(|e| !e.is_empty());
+            }
+        }
+        Err(e) => warn!("Failed to inspect exited container {}: {}", container_id, e),
+    }
+
+    report.last_logs = tail_logs(docker, container_id, CRASH_LOG_LINES).await;
+    report
+}
+
+/// Read the last `lines` lines of a container's output
+pub async fn tail_logs(docker: &Docker, container_id: &str, lines: usize) -> Vec<String> {
+    let options = Some(LogsOptions::<String> {
+        stdout: true,
+        stderr: true,
+        tail: lines.to_string(),
+        ..Default::default()
+    });
+
+    let mut stream = docker.logs(container_id, options);
+    let mut output = Vec::new();
+    while let Some(result) = stream.next().await {
+        match result {
+            Ok(log) => output.extend(
+                log.to_string()
+                    .lines()
+                    .map(|line| line.trim_end().to_string())
+                    .filter(|line| !line.is_empty()),
+            ),
+            Err(e) => {
+                warn!("Failed to read logs for container {}: {}", container_id, e);
+                break;
+            }
+        }
+    }
+
+    let skip = output.len().saturating_sub(lines);
+    output.split_off(skip)
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(exit_code: Option<i64>, oom_killed: bool) -> CrashReport {
+        CrashReport {
+            container_id: "abc".to_string(),
+            exit_code,
+            oom_killed,
+            error: None,
+            last_logs: vec![],
+            occurred_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_crash_report_summary() {
+        assert_eq!(
+            report(Some(137), true).summary(),
+            "container was killed after running out of memory (exit code 137)"
+        );
+        assert_eq!(
+            report(Some(137), false).summary(),
+            "container was killed (exit code 137)"
+        );
+        assert_eq!(
+            report(Some(1), false).summary(),
+            "container exited with code 1"
+        );
+        assert_eq!(
+            report(None, false).summary(),
+            "container exited with code unknown"
+        );
+    }
+}
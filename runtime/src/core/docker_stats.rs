@@ -0,0 +1,81 @@
+use crate::core::cgroup_metrics::{cpu_percentage, memory_percentage, CpuSample};
+use crate::shared::error::{AppResult, RuntimeError};
+use bollard::container::{Stats, StatsOptions};
+use bollard::Docker;
+use dashmap::DashMap;
+use futures_util::StreamExt;
+use std::time::Instant;
+use tracing::debug;
+
+/// Reads container CPU and memory usage from the Docker daemon's stats API, for installs
+/// that run neither Prometheus nor have the host's cgroup files mounted.
+///
+/// Usage is reported like the cgroup reader: CPU as a percentage of one core since the
+/// previous reading, memory as a percentage of the container's limit (including page
+/// cache). Each reading is a one-shot snapshot, so it doesn't wait for the daemon's own
+/// one second sample.
+pub struct DockerStatsReader {
+    docker: Docker,
+    cpu_samples: DashMap<String, CpuSample>,
+}
+
+impl DockerStatsReader {
+    pub fn new(docker: Docker) -> Self {
+        debug!("Reading container metrics from the Docker stats API");
+        Self {
+            docker,
+            cpu_samples: DashMap::new(),
+        }
+    }
+
+    /// CPU usage percentage of a container since the previous call; 0 on the first one
+    pub async fn cpu_usage(&self, container_id: &str) -> AppResult<f64> {
+        let stats = self.stats(container_id).await?;
+        let sample = CpuSample {
+            usage_ns: stats.cpu_stats.cpu_usage.total_usage,
+            at: Instant::now(),
+        };
+        let previous = self.cpu_samples.insert(container_id.to_string(), sample);
+        Ok(previous.map_or(0.0, |previous| cpu_percentage(previous, sample)))
+    }
+
+    /// Memory usage of a container as a percentage of its limit; 0 when the daemon
+    /// reports none
+    pub async fn memory_usage(&self, container_id: &str) -> AppResult<f64> {
+        let stats = self.stats(container_id).await?;
+        Ok(memory_percentage(
+            stats.memory_stats.usage.unwrap_or_default(),
+            stats.memory_stats.limit,
+        ))
+    }
+
+    /// Drop the CPU sample of a container that left its pool
+    pub fn forget_container(&self, container_id: &str) {
+        self.cpu_samples.remove(container_id);
+    }
+
+    /// Whether the daemon answers
+    pub async fn is_reachable(&self) -> bool {
+        self.docker.ping().await.is_ok()
+    }
+
+    async fn stats(&self, container_id: &str) -> AppResult<Stats> {
+        let options = StatsOptions {
+            stream: false,
+            one_shot: true,
+        };
+        self.docker
+            .stats(container_id, Some(options))
+            .next()
+            .await
+            .ok_or_else(|| {
+                RuntimeError::System(format!("Docker returned no stats for {}", container_id))
+            })?
+            .map_err(|e| {
+                RuntimeError::System(format!(
+                    "Failed to read Docker stats of {}: {}",
+                    container_id, e
+                ))
+            })
+    }
+}
@@ -0,0 +1,305 @@
+use bollard::container::InspectContainerOptions;
+use bollard::models::ContainerInspectResponse;
+use bollard::{Docker, API_DEFAULT_VERSION};
+use std::fmt;
+use std::path::Path;
+use tracing::{info, warn};
+
+const DOCKER_HOST_ENV_VARIABLE: &str = "DOCKER_HOST";
+
+/// Socket the Docker daemon listens on by default, and where it is mounted into containers
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// Address the Docker client falls back to when there is neither `DOCKER_HOST` nor a socket
+const DEFAULT_TCP_ADDRESS: &str = "localhost:2375";
+
+/// Request timeout of socket connections, matching the client's HTTP default
+const SOCKET_TIMEOUT_SECS: u64 = 120;
+
+/// Files container runtimes create inside every container
+const CONTAINER_MARKERS: &[&str] = &["/.dockerenv", "/run/.containerenv"];
+
+/// Network every Docker daemon has; function containers on it are reachable by IP from the
+/// daemon host and from containers sharing its network namespace
+const DEFAULT_NETWORK: &str = "bridge";
+
+/// Networks function containers cannot be attached to
+const UNUSABLE_NETWORKS: &[&str] = &["host", "none"];
+
+/// Where the controller runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeploymentMode {
+    /// Directly on a machine (or inside a sysbox container running its own daemon)
+    Host,
+    /// Inside a container: Docker Compose, Kubernetes, a DinD sidecar, ...
+    Container,
+}
+
+impl DeploymentMode {
+    /// Detects whether the controller runs inside a container
+    pub fn detect() -> Self {
+        let in_container = CONTAINER_MARKERS
+            .iter()
+            .any(|marker| Path::new(marker).exists())
+            || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+            || std::fs::read_to_string("/proc/1/cgroup")
+                .is_ok_and(|cgroup| is_container_cgroup(&cgroup));
+        if in_container {
+            DeploymentMode::Container
+        } else {
+            DeploymentMode::Host
+        }
+    }
+}
+
+impl fmt::Display for DeploymentMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeploymentMode::Host => write!(f, "running on the host"),
+            DeploymentMode::Container => write!(f, "running in a container"),
+        }
+    }
+}
+
+/// Whether the cgroup of PID 1 belongs to a container runtime
+fn is_container_cgroup(cgroup: &str) -> bool {
+    ["docker", "kubepods", "containerd", "libpod", "lxc"]
+        .iter()
+        .any(|runtime| cgroup.contains(runtime))
+}
+
+/// How the controller reaches function containers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AddressMode {
+    /// On the network they join, by IP or else by name: the controller shares the
+    /// network (Docker Compose) or routes to it (the default bridge on a Linux host)
+    #[default]
+    Network,
+    /// At a port published on the daemon host, dialed at `host`: for controllers that
+    /// can't route to container IPs, e.g. with Docker Desktop or a remote daemon
+    PublishedPort { host: String },
+}
+
+impl AddressMode {
+    /// Whether containers are dialed at a loopback address, which is only the daemon
+    /// host's when the controller runs on it
+    pub fn is_loopback(&self) -> bool {
+        match self {
+            AddressMode::Network => false,
+            AddressMode::PublishedPort { host } => {
+                host == "localhost"
+                    || host
+                        .parse::<std::net::IpAddr>()
+                        .is_ok_and(|ip| ip.is_loopback())
+            }
+        }
+    }
+
+    /// Host IP function ports are published on: loopback when the controller dials them
+    /// there, every interface otherwise
+    pub fn publish_ip(&self) -> Option<String> {
+        match self {
+            AddressMode::Network => None,
+            AddressMode::PublishedPort { host } if host == "localhost" => {
+                Some("127.0.0.1".to_string())
+            }
+            AddressMode::PublishedPort { host } if self.is_loopback() => Some(host.clone()),
+            AddressMode::PublishedPort { .. } => Some("0.0.0.0".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for AddressMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressMode::Network => write!(f, "dialed on their network"),
+            AddressMode::PublishedPort { host } => {
+                write!(f, "dialed at published ports on {}", host)
+            }
+        }
+    }
+}
+
+/// How the controller reaches the Docker daemon that runs function containers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DockerEndpoint {
+    /// A Unix socket: the local daemon, the host's socket mounted into the controller's
+    /// container, or the inner daemon of a sysbox container
+    Socket(String),
+    /// A TCP address: a socket proxy, or a DinD daemon (`tcp://docker:2375`)
+    Tcp(String),
+}
+
+impl DockerEndpoint {
+    /// `DOCKER_HOST` when set; otherwise the default socket if it exists (e.g. mounted into
+    /// the controller's container); otherwise the daemon's default TCP port
+    pub fn detect() -> Self {
+        endpoint_from(
+            std::env::var(DOCKER_HOST_ENV_VARIABLE).ok(),
+            Path::new(DEFAULT_SOCKET).exists(),
+        )
+    }
+
+    pub fn connect(&self) -> Result<Docker, bollard::errors::Error> {
+        match self {
+            DockerEndpoint::Socket(path) => {
+                Docker::connect_with_socket(path, SOCKET_TIMEOUT_SECS, API_DEFAULT_VERSION)
+            }
+            // Reads the address from DOCKER_HOST, or uses the default port
+            DockerEndpoint::Tcp(_) => Docker::connect_with_http_defaults(),
+        }
+    }
+}
+
+impl fmt::Display for DockerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DockerEndpoint::Socket(path) => write!(f, "socket {}", path),
+            DockerEndpoint::Tcp(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+fn endpoint_from(docker_host: Option<String>, default_socket_exists: bool) -> DockerEndpoint {
+    match docker_host.filter(|host| !host.is_empty()) {
+        Some(host) => match host.strip_prefix("unix://") {
+            Some(path) => DockerEndpoint::Socket(path.to_string()),
+            None => DockerEndpoint::Tcp(host),
+        },
+        None if default_socket_exists => DockerEndpoint::Socket(DEFAULT_SOCKET.to_string()),
+        None => DockerEndpoint::Tcp(DEFAULT_TCP_ADDRESS.to_string()),
+    }
+}
+
+/// Connects to the Docker daemon at the [detected](DockerEndpoint::detect) endpoint
+pub fn connect_docker() -> Result<Docker, bollard::errors::Error> {
+    DockerEndpoint::detect().connect()
+}
+
+/// The controller's own container, as the daemon sees it.
+///
+/// `None` unless the controller runs as a container of the daemon it talks to (a mounted
+/// socket or a socket proxy); a DinD daemon doesn't know the controller's container.
+pub async fn own_container(docker: &Docker) -> Option<ContainerInspectResponse> {
+    // Docker sets a container's hostname to its short ID unless told otherwise
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())?;
+
+    docker
+        .inspect_container(&hostname, None::<InspectContainerOptions>)
+        .await
+        .ok()
+}
+
+/// Networks the controller's own container is attached to; empty when the daemon doesn't
+/// know the container (see [`own_container`])
+pub async fn own_networks(docker: &Docker) -> Vec<String> {
+    let Some(container) = own_container(docker).await else {
+        return Vec::new();
+    };
+    let mut networks: Vec<String> = container
+        .network_settings
+        .and_then(|settings| settings.networks)
+        .map(|networks| networks.into_keys().collect())
+        .unwrap_or_default();
+    networks.retain(|network| !UNUSABLE_NETWORKS.contains(&network.as_str()));
+    networks.sort();
+    networks
+}
+
+/// Picks the network function containers join when none is configured.
+///
+/// In a container, that is the controller's own network, so it can dial function
+/// containers directly. Otherwise it is the daemon's default bridge network, which the
+/// daemon host (and a DinD sidecar's pod) can reach by IP.
+pub async fn detect_network(docker: &Docker) -> String {
+    let mode = DeploymentMode::detect();
+    if mode == DeploymentMode::Container {
+        let networks = own_networks(docker).await;
+        // Prefer a user-defined network over the default bridge
+        let network = networks
+            .iter()
+            .find(|network| network.as_str() != DEFAULT_NETWORK)
+            .or_else(|| networks.first());
+        if let Some(network) = network {
+            if networks.len() > 1 {
+                warn!(
+                    "Controller is attached to networks {:?}; using '{}' for function containers. Set DOCKER_COMPOSE_NETWORK to pick another",
+                    networks, network
+                );
+            }
+            info!(
+                "Function containers join the controller's network '{}'",
+                network
+            );
+            return network.clone();
+        }
+        warn!(
+            "Controller runs in a container the Docker daemon doesn't know (DinD or sysbox); function containers join '{}' and must be reachable by IP",
+            DEFAULT_NETWORK
+        );
+    }
+    DEFAULT_NETWORK.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_mode_publish_ip() {
+        assert_eq!(AddressMode::Network.publish_ip(), None);
+        let published = |host: &str| {
+            AddressMode::PublishedPort {
+                host: host.to_string(),
+            }
+            .publish_ip()
+        };
+        assert_eq!(published("127.0.0.1").as_deref(), Some("127.0.0.1"));
+        assert_eq!(published("localhost").as_deref(), Some("127.0.0.1"));
+        assert_eq!(published("::1").as_deref(), Some("::1"));
+        assert_eq!(published("docker.internal").as_deref(), Some("0.0.0.0"));
+    }
+
+    #[test]
+    fn test_endpoint_from_docker_host() {
+        assert_eq!(
+            endpoint_from(Some("unix:///run/user/1000/docker.sock".to_string()), true),
+            DockerEndpoint::Socket("/run/user/1000/docker.sock".to_string())
+        );
+        assert_eq!(
+            endpoint_from(Some("tcp://docker:2375".to_string()), true),
+            DockerEndpoint::Tcp("tcp://docker:2375".to_string())
+        );
+        assert_eq!(
+            endpoint_from(Some("socat:2375".to_string()), false),
+            DockerEndpoint::Tcp("socat:2375".to_string())
+        );
+    }
+
+    #[test]
+    fn test_endpoint_without_docker_host() {
+        assert_eq!(
+            endpoint_from(None, true),
+            DockerEndpoint::Socket(DEFAULT_SOCKET.to_string())
+        );
+        assert_eq!(
+            endpoint_from(Some(String::new()), false),
+            DockerEndpoint::Tcp(DEFAULT_TCP_ADDRESS.to_string())
+        );
+    }
+
+    #[test]
+    fn test_container_cgroup() {
+        assert!(is_container_cgroup(
+            "0::/system.slice/docker-3f2a9c1b.scope\n"
+        ));
+        assert!(is_container_cgroup(
+            "12:memory:/kubepods/burstable/pod1234/abcd\n"
+        ));
+        assert!(!is_container_cgroup("0::/init.scope\n"));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::core::runner::FUNCTION_LABEL;
+use bollard::models::EventMessage;
+use bollard::system::EventsOptions;
+use bollard::Docker;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// Delay before re-subscribing after the Docker events stream drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Lifecycle events for containers managed by the runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerEvent {
+    /// The container's main process exited (crash, OOM kill or normal exit)
+    Died {
+        container_id: String,
+        function_key: Option<String>,
+        exit_code: Option<i64>,
+    },
+    /// The kernel OOM killer fired inside the container
+    OutOfMemory {
+        container_id: String,
+        function_key: Option<String>,
+    },
+}
+
+impl ContainerEvent {
+    /// ID of the container the event refers to
+    pub fn container_id(&self) -> &str {
+        match self {
+            ContainerEvent::Died { container_id, .. } => container_id,
+            ContainerEvent::OutOfMemory { container_id, .. } => container_id,
+        }
+    }
+
+    /// Function key taken from the container label, if present
+    pub fn function_key(&self) -> Option<&str> {
+        match self {
+            ContainerEvent::Died { function_key, .. } => function_key.as_deref(),
+            ContainerEvent::OutOfMemory { function_key, .. } => function_key.as_deref(),
+        }
+    }
+}
+
+/// Watches the Docker events stream for function containers
+pub struct ContainerEventWatcher {
+    docker: Docker,
+}
+
+impl ContainerEventWatcher {
+    pub fn new(docker: Docker) -> Self {
+        Self { docker }
+    }
+
+    /// Subscribe to container lifecycle events.
+    ///
+    /// Spawns a background task that follows the Docker events stream (re-subscribing
+    /// whenever the stream ends or errors) and forwards relevant events on the returned
+    /// channel. The task exits once the receiver is dropped.
+    pub fn subscribe(self) -> mpsc::UnboundedReceiver<ContainerEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                info!("Subscribing to Docker container events");
+                let mut stream = self.docker.events(Some(events_options()));
+
+                while let Some(result) = stream.next().await {
+                    match result {
+                        Ok(message) => {
+                            if let Some(event) = parse_event(&message) {
+                                debug!("Received container event: {:?}", event);
+                                if tx.send(event).is_err() {
+                                    debug!("Container event receiver dropped, stopping watcher");
+                                    return;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Docker events stream error: {}", e);
+                            break;
+                        }
+                    }
+                }
+
+                if tx.is_closed() {
+                    return;
+                }
+                warn!(
+                    "Docker events stream ended, reconnecting in {:?}",
+                    RECONNECT_DELAY
+                );
+                sleep(RECONNECT_DELAY).await;
+            }
+        });
+
+        rx
+    }
+}
+
+/// Only container die/oom events for containers carrying the function label
+fn events_options() -> EventsOptions<String> {
+    let mut filters = HashMap::new();
+    filters.insert("type".to_string(), vec!["container".to_string()]);
+    filters.insert(
+        "event".to_string(),
+        vec!["die".to_string(), "oom".to_string()],
+    );
+    filters.insert("label".to_string(), vec![FUNCTION_LABEL.to_string()]);
+
+    EventsOptions {
+        filters,
+        ..Default::default()
+    }
+}
+
+/// Convert a raw Docker event into a `ContainerEvent`, ignoring unrelated actions
+fn parse_event(message: &EventMessage) -> Option<ContainerEvent> {
+    let action = message.action.as_deref()?;
+    let actor = message.actor.as_ref()?;
+    let container_id = actor.id.clone()?;
+    let attributes = actor.attributes.clone().unwrap_or_default();
+    let function_key = attributes.get(FUNCTION_LABEL).cloned();
+
+    match action {
+        "die" => Some(ContainerEvent::Died {
+            container_id,
+            function_key,
+            exit_code: attributes
+                .get("exitCode")
+                .and_then(|code| code.parse::<i64>().ok()),
+        }),
+        "oom" => Some(ContainerEvent::OutOfMemory {
+            container_id,
+            function_key,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::models::EventActor;
+
+    fn event(action: &str, attributes: HashMap<String, String>) -> EventMessage {
+        EventMessage {
+            action: Some(action.to_string()),
+            actor: Some(EventActor {
+                id: Some("abc123".to_string()),
+                attributes: Some(attributes),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_die_event() {
+        let mut attributes = HashMap::new();
+        attributes.insert(FUNCTION_LABEL.to_string(), "hello-1234".to_string());
+        attributes.insert("exitCode".to_string(), "137".to_string());
+
+        let parsed = parse_event(&event("die", attributes)).unwrap();
+        assert_eq!(
+            parsed,
+            ContainerEvent::Died {
+                container_id: "abc123".to_string(),
+                function_key: Some("hello-1234".to_string()),
+                exit_code: Some(137),
+            }
+        );
+        assert_eq!(parsed.function_key(), Some("hello-1234"));
+    }
+
+    #[test]
+    fn test_parse_oom_and_ignored_events() {
+        let parsed = parse_event(&event("oom", HashMap::new())).unwrap();
+        assert_eq!(parsed.container_id(), "abc123");
+        assert_eq!(parsed.function_key(), None);
+
+        assert!(parse_event(&event("start", HashMap::new())).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::core::container_manager::ContainerLease;
+use crate::shared::error::{AppResult, RuntimeError};
+use bollard::container::LogOutput;
+use bollard::exec::{CreateExecOptions, ResizeExecOptions, StartExecOptions, StartExecResults};
+use bollard::Docker;
+use futures_util::stream::Stream;
+use std::pin::Pin;
+use tokio::io::AsyncWrite;
+use tracing::info;
+
+/// Most arguments a command may have
+const MAX_COMMAND_ARGS: usize = 64;
+
+/// Most bytes a command may have, arguments included
+const MAX_COMMAND_LENGTH: usize = 4096;
+
+/// Output of a command running in a container, stdout and stderr interleaved
+pub type ExecOutput = Pin<Box<dyn Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>;
+
+/// Stdin of a command running in a container
+pub type ExecInput = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// A command running in a function container, attached to its stdin and output
+pub struct ExecSession {
+    pub handle: ExecHandle,
+    pub output: ExecOutput,
+    pub input: ExecInput,
+    /// Claim on the container, keeping the autoscaler from scaling it down while the
+    /// session lasts
+    pub lease: Option<ContainerLease>,
+}
+
+/// Controls a running command, separately from its output and stdin
+pub struct ExecHandle {
+    docker: Docker,
+    /// Docker's ID of the exec instance
+    pub exec_id: String,
+    /// Container the command runs in
+    pub container_id: String,
+}
+
+impl ExecSession {
+    /// Start `command` in a container. With `tty`, the command gets a terminal and its
+    /// stderr is merged into stdout.
+    pub async fn start(
+        docker: Docker,
+        container_id: &str,
+        command: Vec<String>,
+        tty: bool,
+    ) -> AppResult<Self> {
+        validate_command(&command).map_err(RuntimeError::Exec)?;
+
+        let exec = docker
+            .create_exec(
+                container_id,
+                CreateExecOptions {
+                    attach_stdin: Some(true),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    tty: Some(tty),
+                    cmd: Some(command.clone()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to create exec: {}", e)))?;
+
+        let started = docker
+            .start_exec(
+                &exec.id,
+                Some(StartExecOptions {
+                    detach: false,
+                    tty,
+                    output_capacity: None,
+                }),
+            )
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to start exec: {}", e)))?;
+        let StartExecResults::Attached { output, input } = started else {
+            return Err(RuntimeError::Exec(
+                "Exec started detached from its output".to_string(),
+            ));
+        };
+
+        info!(
+            container_id = %container_id,
+            exec_id = %exec.id,
+            command = ?command,
+            "Started exec session"
+        );
+        Ok(Self {
+            handle: ExecHandle {
+                docker,
+                exec_id: exec.id,
+                container_id: container_id.to_string(),
+            },
+            output,
+            input,
+            lease: None,
+        })
+    }
+}
+
+impl ExecHandle {
+    /// Resize the terminal of a command started with `tty`
+    pub async fn resize(&self, width: u16, height: u16) -> AppResult<()> {
+        self.docker
+            .resize_exec(&self.exec_id, ResizeExecOptions { width, height })
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to resize exec: {}", e)))
+    }
+
+    /// Exit code of the command, once it has exited
+    pub async fn exit_code(&self) -> AppResult<Option<i64>> {
+        let inspected = self
+            .docker
+            .inspect_exec(&self.exec_id)
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to inspect exec: {}", e)))?;
+        Ok(match inspected.running {
+            Some(true) => None,
+            _ => inspected.exit_code,
+        })
+    }
+}
+
+/// Check a command before it's run: it must name a program and stay reasonably small
+pub fn validate_command(command: &[String]) -> Result<(), String> {
+    match command.first() {
+        None => return Err("The command is empty".to_string()),
+        Some(program) if program.trim().is_empty() => {
+            return Err("The command has no program".to_string())
+        }
+        Some(_) => {}
+    }
+    if command.len() > MAX_COMMAND_ARGS {
+        return Err(format!(
+            "The command has more than {} arguments",
+            MAX_COMMAND_ARGS
+        ));
+    }
+    if command.iter().map(String::len).sum::<usize>() > MAX_COMMAND_LENGTH {
+        return Err(format!(
+            "The command is longer than {} bytes",
+            MAX_COMMAND_LENGTH
+        ));
+    }
+    if command.iter().any(|arg| arg.contains('\0')) {
+        return Err("The command contains a NUL byte".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_validate_command_accepts_a_shell() {
+        assert!(validate_command(&command(&["sh"])).is_ok());
+        assert!(validate_command(&command(&["ls", "-la", "/app"])).is_ok());
+    }
+
+    #[test]
+    fn test_validate_command_rejects_missing_program() {
+        assert!(validate_command(&[]).is_err());
+        assert!(validate_command(&command(&["  ", "-c", "ls"])).is_err());
+    }
+
+    #[test]
+    fn test_validate_command_rejects_oversized_commands() {
+        let many = vec!["x".to_string(); MAX_COMMAND_ARGS + 1];
+        assert!(validate_command(&many).is_err());
+
+        let long = vec!["echo".to_string(), "x".repeat(MAX_COMMAND_LENGTH)];
+        assert!(validate_command(&long).is_err());
+
+        assert!(validate_command(&command(&["sh", "-c", "echo\0"])).is_err());
+    }
+}
@@ -0,0 +1,617 @@
+use crate::core::container_manager::ContainerPool;
+use crate::core::egress::namespace_of;
+use crate::core::runner::ResourceLimits;
+use crate::core::start_queue::{StartQueue, StartQueueStatus};
+use crate::shared::error::{AppResult, RuntimeError};
+use bollard::Docker;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// How long a refused function keeps its place in line without asking again
+const STARVATION_TTL: Duration = Duration::from_secs(60);
+
+/// Limits shared by every function, so one tenant can't take all the capacity
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FairnessConfig {
+    /// Most containers the functions of one namespace may run together
+    pub max_containers_per_namespace: Option<usize>,
+    /// Most function containers on the Docker host
+    pub max_containers_per_host: Option<usize>,
+    /// Most CPU the containers' limits may add up to, in percent of the host's CPUs
+    pub max_host_cpu_percent: Option<f64>,
+    /// Most memory the containers' limits may add up to, in percent of the host's memory
+    pub max_host_memory_percent: Option<f64>,
+    /// CPUs and memory of the Docker host, which the percentages apply to
+    pub host_capacity: Option<HostCapacity>,
+    /// Containers started on the host at the same time
+    pub max_concurrent_starts: Option<usize>,
+}
+
+/// CPUs and memory of the Docker host
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HostCapacity {
+    pub cpus: f64,
+    pub memory_bytes: i64,
+}
+
+impl HostCapacity {
+    /// Ask the daemon for its host's CPUs and memory
+    pub async fn detect(docker: &Docker) -> Option<Self> {
+        match docker.info().await {
+            Ok(info) => Self::new(info.ncpu? as f64, info.mem_total?),
+            Err(e) => {
+                warn!("Failed to read the Docker host's capacity: {e}");
+                None
+            }
+        }
+    }
+
+    /// The host's size, or `None` when the daemon reports no CPUs or no memory, which no
+    /// share can be taken of
+    fn new(cpus: f64, memory_bytes: i64) -> Option<Self> {
+        if cpus > 0.0 && memory_bytes > 0 {
+            Some(Self { cpus, memory_bytes })
+        } else {
+            warn!("The Docker host reports {cpus} CPUs and {memory_bytes} bytes of memory");
+            None
+        }
+    }
+}
+
+/// Why a scale-up was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Refusal {
+    /// The function's namespace runs all the containers it may
+    Namespace { namespace: String, limit: usize },
+    /// The host is full, or its last free slots are held for functions refused earlier
+    Host { limit: usize },
+    /// Another container would commit more of the host's CPU or memory than allowed
+    Resources {
+        resource: &'static str,
+        ceiling_percent: u64,
+    },
+}
+
+impl Refusal {
+    /// Whether the refusal is about the host as a whole, so the function waits in line
+    fn is_capacity(&self) -> bool {
+        !matches!(self, Refusal::Namespace { .. })
+    }
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Namespace { namespace, limit } => write!(
+                f,
+                "namespace {namespace} already runs its limit of {limit} containers"
+            ),
+            Refusal::Host { limit } => write!(
+                f,
+                "host capacity of {limit} containers is taken or held for waiting functions"
+            ),
+            Refusal::Resources {
+                resource,
+                ceiling_percent,
+            } => write!(
+                f,
+                "capacity exhausted: containers would commit more than {ceiling_percent}% of the host's {resource}"
+            ),
+        }
+    }
+}
+
+/// Containers of a function and the limits each is started with
+#[derive(Debug, Clone, Copy)]
+struct PoolLoad {
+    containers: usize,
+    limits: ResourceLimits,
+}
+
+/// A function refused capacity
+#[derive(Debug, Clone)]
+struct Starved {
+    /// First refusal since the function last got a container
+    since: Instant,
+    /// Latest refusal
+    last_refused: Instant,
+    reason: Refusal,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    /// Scale-ups admitted but not finished, per function key
+    starting: HashMap<String, usize>,
+    /// Functions waiting for host capacity
+    starved: HashMap<String, Starved>,
+}
+
+/// Host-wide capacity as the scheduler sees it, for the status API
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacityStatus {
+    /// Containers running or starting
+    pub containers: usize,
+    pub max_containers: Option<usize>,
+    /// CPUs the containers' limits add up to
+    pub committed_cpus: f64,
+    /// Memory the containers' limits add up to
+    pub committed_memory_bytes: i64,
+    /// Committed CPUs in percent of the host's, when its size is known
+    pub cpu_percent: Option<f64>,
+    /// Committed memory in percent of the host's, when its size is known
+    pub memory_percent: Option<f64>,
+    pub max_cpu_percent: Option<f64>,
+    pub max_memory_percent: Option<f64>,
+    pub host: Option<HostCapacity>,
+    /// Functions refused a container for lack of capacity, waiting for one
+    pub waiting_functions: usize,
+    /// Whether scale-ups are being refused for lack of capacity
+    pub exhausted: bool,
+    /// Container starts running and waiting for a slot
+    pub starts: StartQueueStatus,
+}
+
+/// Decides which scale-ups go ahead when pools compete for capacity.
+///
+/// Every scale-up asks for a [`ScalePermit`], counted against the namespace, host and
+/// CPU/memory limits together with the containers already running and the scale-ups in
+/// flight. Functions refused host capacity are remembered as starved: each namespace
+/// starved longer than the asking function holds back room for one container, and the
+/// autoscaler's scan serves starved pools first, longest-starved first, so a busy tenant
+/// can't keep taking everything that frees up. Admitted containers are then started
+/// through its [`StartQueue`].
+pub struct FairScheduler {
+    config: FairnessConfig,
+    pools: Arc<DashMap<String, Arc<ContainerPool>>>,
+    state: Mutex<SchedulerState>,
+    starts: Arc<StartQueue>,
+}
+
+/// Admission of one scale-up; releases its reservation when dropped
+pub struct ScalePermit {
+    scheduler: Arc<FairScheduler>,
+    function_key: String,
+}
+
+impl Drop for ScalePermit {
+    fn drop(&mut self) {
+        let mut state = self.scheduler.state.lock().unwrap();
+        if let Some(starting) = state.starting.get_mut(&self.function_key) {
+            *starting -= 1;
+            if *starting == 0 {
+                state.starting.remove(&self.function_key);
+            }
+        }
+    }
+}
+
+impl FairScheduler {
+    pub fn new(config: FairnessConfig, pools: Arc<DashMap<String, Arc<ContainerPool>>>) -> Self {
+        Self {
+            starts: Arc::new(StartQueue::new(config.max_concurrent_starts)),
+            config,
+            pools,
+            state: Mutex::new(SchedulerState::default()),
+        }
+    }
+
+    /// Paces the container starts
+    pub fn starts(&self) -> &Arc<StartQueue> {
+        &self.starts
+    }
+
+    /// Reserve capacity for one more container of a function.
+    ///
+    /// Hold the permit until the container is in its pool (or failed to start).
+    pub fn admit(self: &Arc<Self>, function_key: &str) -> AppResult<ScalePermit> {
+        let running = self.running();
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match decide(&self.config, &mut state, &running, function_key, now) {
+            Ok(()) => {
+                state.starved.remove(function_key);
+                *state.starting.entry(function_key.to_string()).or_default() += 1;
+                Ok(ScalePermit {
+                    scheduler: self.clone(),
+                    function_key: function_key.to_string(),
+                })
+            }
+            Err(refusal) => {
+                if refusal.is_capacity() {
+                    state
+                        .starved
+                        .entry(function_key.to_string())
+                        .and_modify(|starved| {
+                            starved.last_refused = now;
+                            starved.reason = refusal.clone();
+                        })
+                        .or_insert(Starved {
+                            since: now,
+                            last_refused: now,
+                            reason: refusal.clone(),
+                        });
+                }
+                debug!("Refused scale-up of {}: {}", function_key, refusal);
+                Err(RuntimeError::System(format!(
+                    "Scale-up of {function_key} refused: {refusal}"
+                )))
+            }
+        }
+    }
+
+    /// Whether a function is waiting for host capacity
+    pub fn is_starved(&self, function_key: &str) -> bool {
+        self.refusal(function_key).is_some()
+    }
+
+    /// Why a function is waiting for host capacity, if it is
+    pub fn refusal(&self, function_key: &str) -> Option<Refusal> {
+        let state = self.state.lock().unwrap();
+        state
+            .starved
+            .get(function_key)
+            .filter(|starved| starved.last_refused.elapsed() < STARVATION_TTL)
+            .map(|starved| starved.reason.clone())
+    }
+
+    /// Host-wide usage against the configured limits
+    pub fn capacity_status(&self) -> CapacityStatus {
+        let running = self.running();
+        let state = self.state.lock().unwrap();
+        let loads = loads(&running, &state);
+        let waiting_functions = state
+            .starved
+            .values()
+            .filter(|starved| starved.last_refused.elapsed() < STARVATION_TTL)
+            .count();
+        let committed_cpus: f64 = loads
+            .values()
+            .map(|load| load.containers as f64 * load.limits.cpus)
+            .sum();
+        let committed_memory_bytes: i64 = loads
+            .values()
+            .map(|load| load.containers as i64 * load.limits.memory_bytes)
+            .sum();
+        let host = self.config.host_capacity;
+        CapacityStatus {
+            containers: loads.values().map(|load| load.containers).sum(),
+            max_containers: self.config.max_containers_per_host,
+            committed_cpus,
+            committed_memory_bytes,
+            cpu_percent: host.and_then(|host| percent_of(committed_cpus, host.cpus)),
+            memory_percent: host.and_then(|host| {
+                percent_of(committed_memory_bytes as f64, host.memory_bytes as f64)
+            }),
+            max_cpu_percent: self.config.max_host_cpu_percent,
+            max_memory_percent: self.config.max_host_memory_percent,
+            host,
+            waiting_functions,
+            exhausted: waiting_functions > 0,
+            starts: self.starts.status(),
+        }
+    }
+
+    /// Order scale-up candidates: starved functions first, longest-starved first, then
+    /// functions of the namespaces running the fewest containers
+    pub fn prioritize(&self, candidates: &mut [(String, Arc<ContainerPool>)]) {
+        let running = self.running();
+        let mut per_namespace: HashMap<&str, usize> = HashMap::new();
+        for (function_key, load) in &running {
+            *per_namespace.entry(namespace_of(function_key)).or_default() += load.containers;
+        }
+
+        let state = self.state.lock().unwrap();
+        candidates.sort_by_cached_key(|(function_key, _)| {
+            let since = state
+                .starved
+                .get(function_key)
+                .filter(|starved| starved.last_refused.elapsed() < STARVATION_TTL)
+                .map(|starved| starved.since);
+            let namespace_load = per_namespace
+                .get(namespace_of(function_key))
+                .copied()
+                .unwrap_or(0);
+            // `false` sorts first, and earlier refusals before later ones
+            (since.is_none(), since, namespace_load)
+        });
+    }
+
+    /// Running containers and their limits per function key
+    fn running(&self) -> HashMap<String, PoolLoad> {
+        self.pools
+            .iter()
+            .map(|entry| {
+                let load = PoolLoad {
+                    containers: entry.value().container_count(),
+                    limits: entry.value().resource_limits(),
+                };
+                (entry.key().clone(), load)
+            })
+            .collect()
+    }
+}
+
+/// Running and starting containers per function key
+fn loads(running: &HashMap<String, PoolLoad>, state: &SchedulerState) -> HashMap<String, PoolLoad> {
+    let mut loads = running.clone();
+    for (function_key, starting) in &state.starting {
+        if let Some(load) = loads.get_mut(function_key) {
+            load.containers += starting;
+        }
+    }
+    loads
+}
+
+/// Whether one more container of `function_key` fits, given the running containers of
+/// every function
+fn decide(
+    config: &FairnessConfig,
+    state: &mut SchedulerState,
+    running: &HashMap<String, PoolLoad>,
+    function_key: &str,
+    now: Instant,
+) -> Result<(), Refusal> {
+    state
+        .starved
+        .retain(|_, starved| now.duration_since(starved.last_refused) < STARVATION_TTL);
+
+    let loads = loads(running, state);
+    let mut per_namespace: HashMap<&str, usize> = HashMap::new();
+    for (key, load) in &loads {
+        *per_namespace.entry(namespace_of(key)).or_default() += load.containers;
+    }
+    let namespace_full = |namespace: &str| {
+        config
+            .max_containers_per_namespace
+            .is_some_and(|limit| per_namespace.get(namespace).copied().unwrap_or(0) >= limit)
+    };
+
+    let namespace = namespace_of(function_key);
+    if namespace_full(namespace) {
+        return Err(Refusal::Namespace {
+            namespace: namespace.to_string(),
+            limit: config.max_containers_per_namespace.unwrap_or_default(),
+        });
+    }
+
+    // Namespaces refused before this function, and able to use room, hold one
+    // container's worth each
+    let own_since = state.starved.get(function_key).map(|starved| starved.since);
+    let mut earlier: Vec<(&String, &Starved)> = state
+        .starved
+        .iter()
+        .filter(|(_, starved)| own_since.is_none_or(|own| starved.since < own))
+        .collect();
+    earlier.sort_by_key(|(_, starved)| starved.since);
+    let mut held_namespaces = HashSet::new();
+    let mut held = Vec::new();
+    for (key, _) in earlier {
+        let other = namespace_of(key);
+        if other != namespace && !namespace_full(other) && held_namespaces.insert(other) {
+            held.push(loads.get(key.as_str()).map(|load| load.limits));
+        }
+    }
+
+    if let Some(limit) = config.max_containers_per_host {
+        let total: usize = per_namespace.values().sum();
+        if total + held.len() >= limit {
+            return Err(Refusal::Host { limit });
+        }
+    }
+
+    let (Some(host), Some(requested)) = (
+        config.host_capacity,
+        loads.get(function_key).map(|load| load.limits),
+    ) else {
+        return Ok(());
+    };
+    let held = held.into_iter().flatten();
+    if let Some(percent) = config.max_host_cpu_percent {
+        let committed: f64 = loads
+            .values()
+            .map(|load| load.containers as f64 * load.limits.cpus)
+            .chain(held.clone().map(|limits| limits.cpus))
+            .sum();
+        if committed + requested.cpus > host.cpus * percent / 100.0 {
+            return Err(Refusal::Resources {
+                resource: "CPU",
+                ceiling_percent: percent.round() as u64,
+            });
+        }
+    }
+    if let Some(percent) = config.max_host_memory_percent {
+        let committed: i64 = loads
+            .values()
+            .map(|load| load.containers as i64 * load.limits.memory_bytes)
+            .chain(held.map(|limits| limits.memory_bytes))
+            .sum();
+        let ceiling = host.memory_bytes as f64 * percent / 100.0;
+        if (committed + requested.memory_bytes) as f64 > ceiling {
+            return Err(Refusal::Resources {
+                resource: "memory",
+                ceiling_percent: percent.round() as u64,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `part` in percent of `total`, or `None` for an empty total
+fn percent_of(part: f64, total: f64) -> Option<f64> {
+    (total > 0.0).then(|| part / total * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: i64 = 1024 * 1024 * 1024;
+
+    fn config(per_namespace: Option<usize>, per_host: Option<usize>) -> FairnessConfig {
+        FairnessConfig {
+            max_containers_per_namespace: per_namespace,
+            max_containers_per_host: per_host,
+            ..Default::default()
+        }
+    }
+
+    fn running(counts: &[(&str, usize)]) -> HashMap<String, PoolLoad> {
+        counts
+            .iter()
+            .map(|(key, count)| {
+                let load = PoolLoad {
+                    containers: *count,
+                    limits: ResourceLimits {
+                        memory_bytes: GB,
+                        cpus: 1.0,
+                    },
+                };
+                (key.to_string(), load)
+            })
+            .collect()
+    }
+
+    fn starved(since: Instant) -> Starved {
+        Starved {
+            since,
+            last_refused: since,
+            reason: Refusal::Host { limit: 0 },
+        }
+    }
+
+    #[test]
+    fn test_unlimited_admits() {
+        let mut state = SchedulerState::default();
+        let running = running(&[("api-aaa", 40)]);
+        assert_eq!(
+            decide(
+                &config(None, None),
+                &mut state,
+                &running,
+                "api-aaa",
+                Instant::now()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_namespace_limit_counts_every_function_and_starting_containers() {
+        let mut state = SchedulerState::default();
+        state.starting.insert("worker-aaa".to_string(), 1);
+        let running = running(&[("api-aaa", 2), ("worker-aaa", 0), ("api-bbb", 5)]);
+        let config = config(Some(3), None);
+
+        assert_eq!(
+            decide(&config, &mut state, &running, "cron-aaa", Instant::now()),
+            Err(Refusal::Namespace {
+                namespace: "aaa".to_string(),
+                limit: 3
+            })
+        );
+        assert_eq!(
+            decide(&config, &mut state, &running, "api-ccc", Instant::now()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_host_slots_held_for_earlier_starved_namespaces() {
+        let now = Instant::now();
+        let mut state = SchedulerState::default();
+        state.starved.insert("quiet-bbb".to_string(), starved(now));
+        let running = running(&[("noisy-aaa", 8), ("quiet-bbb", 0)]);
+        let config = config(None, Some(9));
+
+        // The last slot is held for the starved namespace
+        assert_eq!(
+            decide(&config, &mut state, &running, "noisy-aaa", now),
+            Err(Refusal::Host { limit: 9 })
+        );
+        assert_eq!(
+            decide(&config, &mut state, &running, "quiet-bbb", now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_stale_starvation_is_forgotten() {
+        let now = Instant::now();
+        let mut state = SchedulerState::default();
+        state.starved.insert("quiet-bbb".to_string(), starved(now));
+        let running = running(&[("noisy-aaa", 8), ("quiet-bbb", 0)]);
+
+        let later = now + STARVATION_TTL;
+        assert_eq!(
+            decide(
+                &config(None, Some(9)),
+                &mut state,
+                &running,
+                "noisy-aaa",
+                later
+            ),
+            Ok(())
+        );
+        assert!(state.starved.is_empty());
+    }
+
+    #[test]
+    fn test_resource_ceilings() {
+        let mut state = SchedulerState::default();
+        let running = running(&[("api-aaa", 6)]);
+        let host = HostCapacity {
+            cpus: 8.0,
+            memory_bytes: 8 * GB,
+        };
+        let config = FairnessConfig {
+            max_host_memory_percent: Some(80.0),
+            host_capacity: Some(host),
+            ..Default::default()
+        };
+
+        // 7 GB committed would exceed 80% of 8 GB
+        assert_eq!(
+            decide(&config, &mut state, &running, "api-aaa", Instant::now()),
+            Err(Refusal::Resources {
+                resource: "memory",
+                ceiling_percent: 80
+            })
+        );
+
+        let config = FairnessConfig {
+            max_host_cpu_percent: Some(200.0),
+            host_capacity: Some(host),
+            ..Default::default()
+        };
+        assert_eq!(
+            decide(&config, &mut state, &running, "api-aaa", Instant::now()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_empty_host_has_no_capacity() {
+        assert_eq!(
+            HostCapacity::new(8.0, 8 * GB),
+            Some(HostCapacity {
+                cpus: 8.0,
+                memory_bytes: 8 * GB
+            })
+        );
+        assert_eq!(HostCapacity::new(0.0, 8 * GB), None);
+        assert_eq!(HostCapacity::new(8.0, 0), None);
+    }
+
+    #[test]
+    fn test_percent_of() {
+        assert_eq!(percent_of(6.0, 8.0), Some(75.0));
+        assert_eq!(percent_of(0.0, 8.0), Some(0.0));
+        assert_eq!(percent_of(6.0, 0.0), None);
+        assert_eq!(percent_of(0.0, 0.0), None);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::core::egress::namespace_of;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// A window during which idle containers are kept instead of scaled down, e.g. a
+/// change moratorium around a sales event
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreezeWindow {
+    /// Namespace hash the window applies to, or `None` for every namespace
+    pub namespace: Option<String>,
+    pub starts_at: SystemTime,
+    pub ends_at: SystemTime,
+}
+
+impl FreezeWindow {
+    /// Whether the window suspends scale-downs of `function_key` at `now`
+    pub fn covers(&self, function_key: &str, now: SystemTime) -> bool {
+        self.starts_at <= now
+            && now < self.ends_at
+            && self
+                .namespace
+                .as_deref()
+                .is_none_or(|namespace| namespace == namespace_of(function_key))
+    }
+}
+
+/// The freeze windows declared by operators, replaced as a whole whenever they change
+#[derive(Debug, Default)]
+pub struct FreezeWindows {
+    windows: RwLock<Vec<FreezeWindow>>,
+}
+
+impl FreezeWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the declared windows
+    pub fn set(&self, windows: Vec<FreezeWindow>) {
+        *self.windows.write().unwrap() = windows;
+    }
+
+    /// Whether scale-downs of `function_key` are suspended at `now`
+    pub fn is_frozen(&self, function_key: &str, now: SystemTime) -> bool {
+        self.windows
+            .read()
+            .unwrap()
+            .iter()
+            .any(|window| window.covers(function_key, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn window(namespace: Option<&str>, starts_in: u64, lasts: u64) -> FreezeWindow {
+        let starts_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000 + starts_in);
+        FreezeWindow {
+            namespace: namespace.map(str::to_string),
+            starts_at,
+            ends_at: starts_at + Duration::from_secs(lasts),
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_platform_window_freezes_every_function_while_open() {
+        let windows = FreezeWindows::new();
+        windows.set(vec![window(None, 0, 60)]);
+
+        assert!(!windows.is_frozen("hello-abc123", at(999)));
+        assert!(windows.is_frozen("hello-abc123", at(1_000)));
+        assert!(windows.is_frozen("other-def456", at(1_059)));
+        assert!(!windows.is_frozen("hello-abc123", at(1_060)));
+    }
+
+    #[test]
+    fn test_namespace_window_only_freezes_its_namespace() {
+        let windows = FreezeWindows::new();
+        windows.set(vec![window(Some("abc123"), 0, 60)]);
+
+        assert!(windows.is_frozen("hello-abc123", at(1_030)));
+        assert!(windows.is_frozen("my-api-abc123", at(1_030)));
+        assert!(!windows.is_frozen("hello-def456", at(1_030)));
+    }
+
+    #[test]
+    fn test_replacing_windows_lifts_the_freeze() {
+        let windows = FreezeWindows::new();
+        windows.set(vec![window(None, 0, 60)]);
+        windows.set(Vec::new());
+
+        assert!(!windows.is_frozen("hello-abc123", at(1_030)));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::core::policy::{Protocol, Service};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+/// Path a container is `POST`ed to once it is ready, before it serves invocations.
+/// Anything but a 200 (or a 404, for functions without the hook) fails the start.
+pub const INIT_PATH: &str = "/__invok/init";
+
+/// Path a container is `POST`ed to before it is removed, so it can release its resources
+pub const SHUTDOWN_PATH: &str = "/__invok/shutdown";
+
+/// Path restored containers are probed on before they take requests again. Any HTTP
+/// answer, a 404 included, shows the function's server is up.
+pub const READY_PATH: &str = "/__invok/ready";
+
+/// How long the init hook may take, e.g. to open connections
+const INIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the shutdown hook may take before the container is removed anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a container may take to answer the readiness probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What a container answered to a lifecycle hook
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookOutcome {
+    /// The hook ran and succeeded
+    Done,
+    /// The function doesn't implement the hook (404)
+    Missing,
+    /// The hook failed or couldn't be called
+    Failed(String),
+}
+
+/// Calls a container's init hook.
+///
+/// # Arguments
+///
+/// * `host` - Host the container is reached on.
+/// * `port` - Port the function listens on.
+/// * `protocol` - HTTP version the function serves.
+pub async fn call_init(host: &str, port: u32, protocol: Protocol) -> HookOutcome {
+    call_hook(host, port, protocol, INIT_PATH, INIT_TIMEOUT).await
+}
+
+/// Calls a container's shutdown hook, logging failures; the container is removed either way.
+///
+/// # Arguments
+///
+/// * `host` - Host the container is reached on.
+/// * `port` - Port the function listens on.
+/// * `protocol` - HTTP version the function serves.
+pub async fn call_shutdown(host: &str, port: u32, protocol: Protocol) {
+    match call_hook(host, port, protocol, SHUTDOWN_PATH, SHUTDOWN_TIMEOUT).await {
+        HookOutcome::Done => debug!("Shutdown hook of {host} done"),
+        HookOutcome::Missing => {}
+        HookOutcome::Failed(reason) => warn!("Shutdown hook of {host} failed: {reason}"),
+    }
+}
+
+/// Checks that a container's server answers: a `GET` of [`READY_PATH`] for HTTP
+/// functions, a connection for TCP services. UDP services can't be probed and pass.
+///
+/// # Arguments
+///
+/// * `host` - Host the container is reached on.
+/// * `port` - Port the function listens on.
+/// * `service` - What the function serves on its port.
+/// * `protocol` - HTTP version the function serves.
+pub async fn probe(
+    host: &str,
+    port: u32,
+    service: Service,
+    protocol: Protocol,
+) -> Result<(), String> {
+    let probed = match service {
+        Service::Http => tokio::time::timeout(PROBE_TIMEOUT, get_ready(host, port, protocol)).await,
+        Service::Tcp => {
+            let connect = async {
+                TcpStream::connect(format!("{host}:{port}"))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| format!("failed to connect: {e}"))
+            };
+            tokio::time::timeout(PROBE_TIMEOUT, connect).await
+        }
+        Service::Udp => return Ok(()),
+    };
+    probed.unwrap_or_else(|_| Err(format!("no answer within {} s", PROBE_TIMEOUT.as_secs())))
+}
+
+#[cfg(feature = "prometheus")]
+async fn get_ready(host: &str, port: u32, protocol: Protocol) -> Result<(), String> {
+    let mut builder = reqwest::Client::builder();
+    if protocol == Protocol::H2c {
+        builder = builder.http2_prior_knowledge();
+    }
+    let client = builder
+        .build()
+        .map_err(|e| format!("failed to build the client: {e}"))?;
+    client
+        .get(format!("http://{host}:{port}{READY_PATH}"))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("failed to call {READY_PATH}: {e}"))
+}
+
+/// Without reqwest, the probe is a bare HTTP/1.1 request; h2c servers only get a
+/// connection
+#[cfg(not(feature = "prometheus"))]
+async fn get_ready(host: &str, port: u32, protocol: Protocol) -> Result<(), String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = TcpStream::connect(format!("{host}:{port}"))
+        .await
+        .map_err(|e| format!("failed to connect: {e}"))?;
+    if protocol == Protocol::H2c {
+        return Ok(());
+    }
+    let request =
+        format!("GET {READY_PATH} HTTP/1.1\r\nHost: {host}:{port}\r\nConnection: close\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("failed to call {READY_PATH}: {e}"))?;
+    let mut head = [0; 5];
+    stream
+        .read_exact(&mut head)
+        .await
+        .map_err(|e| format!("{READY_PATH} got no answer: {e}"))?;
+    if &head == b"HTTP/" {
+        Ok(())
+    } else {
+        Err(format!("{READY_PATH} got an answer that isn't HTTP"))
+    }
+}
+
+#[cfg(feature = "prometheus")]
+async fn call_hook(
+    host: &str,
+    port: u32,
+    protocol: Protocol,
+    path: &str,
+    timeout: Duration,
+) -> HookOutcome {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+    if protocol == Protocol::H2c {
+        builder = builder.http2_prior_knowledge();
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => return HookOutcome::Failed(format!("failed to build the client: {e}")),
+    };
+    let response = match client
+        .post(format!("http://{host}:{port}{path}"))
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) if e.is_timeout() => {
+            return HookOutcome::Failed(format!(
+                "{path} did not answer within {} s",
+                timeout.as_secs()
+            ))
+        }
+        Err(e) => return HookOutcome::Failed(format!("failed to call {path}: {e}")),
+    };
+
+    match response.status() {
+        reqwest::StatusCode::OK => HookOutcome::Done,
+        reqwest::StatusCode::NOT_FOUND => HookOutcome::Missing,
+        status => {
+            let body = response.text().await.unwrap_or_default();
+            let body = body.trim();
+            if body.is_empty() {
+                HookOutcome::Failed(format!("{path} answered {status}"))
+            } else {
+                HookOutcome::Failed(format!("{path} answered {status}: {body}"))
+            }
+        }
+    }
+}
+
+/// Without reqwest (the `prometheus` feature), hooks are sent as bare HTTP/1.1 requests
+#[cfg(not(feature = "prometheus"))]
+async fn call_hook(
+    host: &str,
+    port: u32,
+    protocol: Protocol,
+    path: &str,
+    timeout: Duration,
+) -> HookOutcome {
+    if protocol == Protocol::H2c {
+        return HookOutcome::Failed(format!(
+            "{path} can't be called over h2c without the runtime's prometheus feature"
+        ));
+    }
+    let response = match tokio::time::timeout(timeout, post_http1(host, port, path)).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return HookOutcome::Failed(format!("failed to call {path}: {e}")),
+        Err(_) => {
+            return HookOutcome::Failed(format!(
+                "{path} did not answer within {} s",
+                timeout.as_secs()
+            ))
+        }
+    };
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .unwrap_or((response.as_str(), ""));
+    // e.g. `HTTP/1.1 500 Internal Server Error`
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_once(' '))
+        .map(|(_, status)| status.trim())
+        .unwrap_or_default();
+    match status.split(' ').next() {
+        Some("200") => HookOutcome::Done,
+        Some("404") => HookOutcome::Missing,
+        _ => {
+            let body = body.trim();
+            if body.is_empty() {
+                HookOutcome::Failed(format!("{path} answered {status}"))
+            } else {
+                HookOutcome::Failed(format!("{path} answered {status}: {body}"))
+            }
+        }
+    }
+}
+
+/// Sends an empty `POST` and reads the response until the container closes the connection
+#[cfg(not(feature = "prometheus"))]
+async fn post_http1(host: &str, port: u32, path: &str) -> std::io::Result<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(format!("{host}:{port}")).await?;
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: {host}:{port}\r\n\
+         Content-Length: 0\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers one request with `response` and returns the port it listened on
+    async fn answer_once(response: &'static str) -> u32 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+        port as u32
+    }
+
+    #[tokio::test]
+    async fn test_init_succeeds_on_ok() {
+        let port = answer_once("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        assert_eq!(
+            call_init("127.0.0.1", port, Protocol::Http1).await,
+            HookOutcome::Done
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_hook_is_not_a_failure() {
+        let port = answer_once("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n").await;
+        assert_eq!(
+            call_init("127.0.0.1", port, Protocol::Http1).await,
+            HookOutcome::Missing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_fails_with_the_function_error() {
+        let port = answer_once(
+            "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 13\r\n\r\nno connection",
+        )
+        .await;
+        assert_eq!(
+            call_init("127.0.0.1", port, Protocol::Http1).await,
+            HookOutcome::Failed(
+                "/__invok/init answered 500 Internal Server Error: no connection".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_accepts_any_http_answer() {
+        let port = answer_once("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n").await;
+        assert_eq!(
+            probe("127.0.0.1", port, Service::Http, Protocol::Http1).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_fails_when_nothing_listens() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port() as u32;
+        drop(listener);
+        for service in [Service::Http, Service::Tcp] {
+            assert!(probe("127.0.0.1", port, service, Protocol::Http1)
+                .await
+                .is_err());
+        }
+        assert_eq!(
+            probe("127.0.0.1", port, Service::Udp, Protocol::Http1).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_h2c_hook_fails_on_http1_server() {
+        let port = answer_once("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        assert!(matches!(
+            call_init("127.0.0.1", port, Protocol::H2c).await,
+            HookOutcome::Failed(_)
+        ));
+    }
+}
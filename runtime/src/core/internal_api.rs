@@ -0,0 +1,90 @@
+use crate::core::egress::proxy_credentials;
+
+/// Environment variable holding the URL of the controller's internal API
+pub const INTERNAL_API_URL_ENV: &str = "INVOK_API_URL";
+
+/// Environment variable holding the token a function authenticates to the internal API with
+pub const INTERNAL_API_TOKEN_ENV: &str = "INVOK_API_TOKEN";
+
+/// How function containers reach the controller's internal API, e.g. its key-value store
+#[derive(Debug, Clone)]
+pub struct InternalApiConfig {
+    /// URL of the controller as reachable from function containers
+    pub url: String,
+    /// Key the function tokens are derived with
+    pub secret: String,
+}
+
+impl InternalApiConfig {
+    /// Environment variables handing a function's containers the API URL and their token
+    pub fn env(&self, function_key: &str) -> Vec<String> {
+        vec![
+            format!("{INTERNAL_API_URL_ENV}={}", self.url.trim_end_matches('/')),
+            format!(
+                "{INTERNAL_API_TOKEN_ENV}={}",
+                function_token(&self.secret, function_key)
+            ),
+        ]
+    }
+}
+
+/// Token a function authenticates to the internal API with: its function key and a
+/// signature of it, so the controller can check it without storing it
+pub fn function_token(secret: &str, function_key: &str) -> String {
+    format!("{function_key}.{}", proxy_credentials(secret, function_key))
+}
+
+/// The function key a token was issued to, if it was issued with `secret`
+pub fn verify_function_token<'a>(secret: &str, token: &'a str) -> Option<&'a str> {
+    let (function_key, signature) = token.rsplit_once('.')?;
+    let expected = proxy_credentials(secret, function_key);
+    let matches = expected.len() == signature.len()
+        && expected
+            .bytes()
+            .zip(signature.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0;
+    matches.then_some(function_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_token_round_trip() {
+        let token = function_token("secret", "hello-0123456789abcdef0123");
+        assert_eq!(
+            verify_function_token("secret", &token),
+            Some("hello-0123456789abcdef0123")
+        );
+    }
+
+    #[test]
+    fn test_function_token_rejects_forgeries() {
+        let token = function_token("secret", "hello-0123456789abcdef0123");
+        assert_eq!(verify_function_token("another", &token), None);
+
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let forged = format!("other-0123456789abcdef0123.{signature}");
+        assert_eq!(verify_function_token("secret", &forged), None);
+        assert_eq!(verify_function_token("secret", "no-signature"), None);
+    }
+
+    #[test]
+    fn test_internal_api_env() {
+        let config = InternalApiConfig {
+            url: "http://invok:3000/".to_string(),
+            secret: "secret".to_string(),
+        };
+        let env = config.env("hello-0123456789abcdef0123");
+        assert_eq!(env[0], "INVOK_API_URL=http://invok:3000");
+        assert_eq!(
+            env[1],
+            format!(
+                "INVOK_API_TOKEN={}",
+                function_token("secret", "hello-0123456789abcdef0123")
+            )
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::core::environment::connect_docker;
 use crate::shared::error::{AppResult, RuntimeError};
 use bollard::{container::LogsOptions, Docker};
 use futures_util::stream::{Stream, StreamExt};
@@ -24,7 +25,7 @@ pub struct ContainerLogStreamer {
 impl ContainerLogStreamer {
     /// Create a new container log streamer
     pub fn new() -> AppResult<Self> {
-        let docker = Docker::connect_with_http_defaults()
+        let docker = connect_docker()
             .map_err(|e| RuntimeError::System(format!("Failed to connect to Docker: {}", e)))?;
 
         Ok(Self { docker })
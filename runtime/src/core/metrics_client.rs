@@ -1,43 +1,158 @@
+use crate::core::cgroup_metrics::CgroupReader;
+use crate::core::docker_stats::DockerStatsReader;
+use crate::core::environment::connect_docker;
 use crate::shared::error::{AppResult, RuntimeError};
 use dashmap::DashMap;
-use reqwest::Client;
+use futures_util::future::BoxFuture;
+#[cfg(feature = "prometheus")]
+use reqwest::{Certificate, Client, RequestBuilder};
+#[cfg(feature = "prometheus")]
 use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+#[cfg(feature = "prometheus")]
+use std::sync::RwLock;
 use std::time::{Duration, Instant};
+#[cfg(feature = "prometheus")]
 use tokio::time::sleep;
-use tracing::{debug, warn};
-
+use tracing::debug;
+#[cfg(feature = "prometheus")]
+use tracing::{info, warn};
+
+/// Placeholder for the (short) container ID in a cgroup id pattern
+pub const CONTAINER_ID_PLACEHOLDER: &str = "{id}";
+
+/// Known cgroup `id` label layouts, tried in order when auto-detecting:
+/// - cgroupfs driver (cgroup v1 and v2): `/docker/<id>`
+/// - systemd driver: `/system.slice/docker-<id>.scope`
+/// - systemd driver under other slices (rootless, nested): `.../docker-<id>.scope`
+pub const CGROUP_ID_PATTERNS: &[&str] = &[
+    "/docker/{id}.*",
+    "/system.slice/docker-{id}.*",
+    ".*/docker-{id}.*",
+];
+
+#[cfg(feature = "prometheus")]
 #[derive(Debug, Deserialize)]
 struct PrometheusResponse {
     status: String,
     data: PrometheusData,
 }
 
+#[cfg(feature = "prometheus")]
 #[derive(Debug, Deserialize)]
 struct PrometheusData {
     result: Vec<PrometheusResult>,
 }
 
+#[cfg(feature = "prometheus")]
 #[derive(Debug, Deserialize)]
 struct PrometheusResult {
     value: (f64, String), // [timestamp, value]
 }
 
+/// Source of the CPU and memory usage containers are scaled on.
+///
+/// [`MetricsClient`] reads them from Prometheus, cgroup files or the Docker stats API.
+/// Embedders reading them
+/// elsewhere implement this and hand it to
+/// [`AutoscalingRuntimeBuilder::metrics`](crate::core::builder::AutoscalingRuntimeBuilder::metrics).
+pub trait ContainerMetrics: Send + Sync {
+    /// CPU usage of a container, in percent of one core
+    fn cpu_usage<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, AppResult<f64>>;
+
+    /// Memory usage of a container, in percent of its limit
+    fn memory_usage<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, AppResult<f64>>;
+
+    /// Forget what is kept about a container that left its pool
+    fn forget_container(&self, _container_id: &str) {}
+
+    /// Whether the source answers, reported by the health check
+    fn health_check(&self) -> BoxFuture<'_, bool>;
+}
+
+/// Credentials sent with every query
+#[derive(Clone, Default, PartialEq)]
+pub enum MetricsAuth {
+    #[default]
+    None,
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+impl fmt::Debug for MetricsAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print secrets
+        match self {
+            MetricsAuth::None => write!(f, "None"),
+            MetricsAuth::Basic { username, .. } => write!(f, "Basic({username}, ***)"),
+            MetricsAuth::Bearer(_) => write!(f, "Bearer(***)"),
+        }
+    }
+}
+
+/// Where container metrics come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricsSource {
+    /// cAdvisor series queried from a Prometheus-compatible API; needs the `prometheus`
+    /// feature
+    Prometheus,
+    /// The host's cgroup files under `root` (usually `/sys/fs/cgroup`), cgroup v1 or v2
+    Cgroup { root: PathBuf },
+    /// The Docker daemon's stats API
+    DockerStats,
+}
+
+impl Default for MetricsSource {
+    /// Prometheus, or the Docker stats API in builds without the `prometheus` feature
+    fn default() -> Self {
+        if cfg!(feature = "prometheus") {
+            MetricsSource::Prometheus
+        } else {
+            MetricsSource::DockerStats
+        }
+    }
+}
+
 /// Configuration for the metrics client
+///
+/// Any Prometheus-compatible query API works: `prometheus_url` may include a path prefix,
+/// e.g. `http://vmselect:8481/select/0/prometheus` for a VictoriaMetrics cluster or a
+/// Thanos Query URL.
 #[derive(Debug, Clone)]
 pub struct MetricsConfig {
+    pub source: MetricsSource,
     pub prometheus_url: String,
     pub query_timeout: Duration,
     pub cache_ttl: Duration,
     pub max_retries: u32,
+    /// Regex matched against the cAdvisor `id` label, with `{id}` standing for the
+    /// container ID. `None` auto-detects the layout from `CGROUP_ID_PATTERNS`.
+    pub container_id_pattern: Option<String>,
+    /// Credentials for authenticated endpoints
+    pub auth: MetricsAuth,
+    /// PEM bundle with extra CA certificates to trust (for private CAs)
+    pub ca_cert_path: Option<PathBuf>,
+    /// Most containers whose metrics are cached; the least recently used are dropped
+    /// past it
+    pub max_cached_containers: usize,
 }
 
 impl Default for MetricsConfig {
     fn default() -> Self {
         Self {
+            source: MetricsSource::default(),
             prometheus_url: "http://prometheus:9090".to_string(),
             query_timeout: Duration::from_secs(5),
             cache_ttl: Duration::from_secs(5),
             max_retries: 3,
+            container_id_pattern: None,
+            auth: MetricsAuth::None,
+            ca_cert_path: None,
+            max_cached_containers: 1024,
         }
     }
 }
@@ -47,28 +162,153 @@ impl Default for MetricsConfig {
 struct CachedMetric {
     value: f64,
     timestamp: Instant,
+    last_used: Instant,
 }
 
-/// Client for fetching container metrics from Prometheus
+/// Metrics of one kind by container, valid for `ttl` and holding at most `capacity`
+/// containers
+#[derive(Debug)]
+struct MetricCache {
+    entries: DashMap<String, CachedMetric>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl MetricCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// The cached value if still valid
+    fn get(&self, container_id: &str) -> Option<f64> {
+        let mut cached = self.entries.get_mut(container_id)?;
+        if cached.timestamp.elapsed() >= self.ttl {
+            return None;
+        }
+        cached.last_used = Instant::now();
+        Some(cached.value)
+    }
+
+    fn insert(&self, container_id: &str, value: f64) {
+        let now = Instant::now();
+        self.entries.insert(
+            container_id.to_string(),
+            CachedMetric {
+                value,
+                timestamp: now,
+                last_used: now,
+            },
+        );
+        if self.entries.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    fn remove(&self, container_id: &str) {
+        self.entries.remove(container_id);
+    }
+
+    /// Drops expired entries, then the least recently used ones until the cache is back
+    /// within its capacity
+    fn evict(&self) {
+        self.entries
+            .retain(|_, cached| cached.timestamp.elapsed() < self.ttl);
+        let excess = self.entries.len().saturating_sub(self.capacity);
+        if excess == 0 {
+            return;
+        }
+        let mut by_use: Vec<(Instant, String)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.last_used, entry.key().clone()))
+            .collect();
+        by_use.sort_unstable();
+        for (_, container_id) in by_use.into_iter().take(excess) {
+            self.entries.remove(&container_id);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Where a client reads the metrics it doesn't have cached
+enum Reader {
+    #[cfg(feature = "prometheus")]
+    Prometheus(Client),
+    Cgroup(CgroupReader),
+    DockerStats(DockerStatsReader),
+}
+
+/// Client for fetching container metrics from Prometheus, cgroup files or the Docker
+/// stats API
 pub struct MetricsClient {
+    #[cfg_attr(not(feature = "prometheus"), allow(dead_code))]
     config: MetricsConfig,
-    client: Client,
-    cpu_cache: DashMap<String, CachedMetric>,
-    memory_cache: DashMap<String, CachedMetric>,
+    reader: Reader,
+    cpu_cache: MetricCache,
+    memory_cache: MetricCache,
+    /// Cgroup id pattern found by auto-detection
+    #[cfg(feature = "prometheus")]
+    detected_pattern: RwLock<Option<String>>,
 }
 
 impl MetricsClient {
+    /// Create a client, panicking if the configuration is unusable. Prefer [`Self::try_new`]
+    /// when the config comes from user input.
     pub fn new(config: MetricsConfig) -> Self {
-        let client = Client::builder()
-            .timeout(config.query_timeout)
-            .build()
-            .expect("Failed to create HTTP client");
+        Self::try_new(config).expect("Failed to create HTTP client")
+    }
 
-        Self {
+    /// Create a client, failing if the CA bundle can't be loaded or the source isn't
+    /// available in this build
+    pub fn try_new(mut config: MetricsConfig) -> AppResult<Self> {
+        config.prometheus_url = config.prometheus_url.trim_end_matches('/').to_string();
+
+        let reader = match &config.source {
+            #[cfg(feature = "prometheus")]
+            MetricsSource::Prometheus => Reader::Prometheus(prometheus_client(&config)?),
+            #[cfg(not(feature = "prometheus"))]
+            MetricsSource::Prometheus => {
+                return Err(RuntimeError::System(
+                    "Prometheus metrics need the runtime's prometheus feature".to_string(),
+                ))
+            }
+            MetricsSource::Cgroup { root } => Reader::Cgroup(CgroupReader::new(root.clone())),
+            MetricsSource::DockerStats => {
+                let docker = connect_docker().map_err(|e| {
+                    RuntimeError::System(format!("Failed to connect to Docker: {}", e))
+                })?;
+                Reader::DockerStats(DockerStatsReader::new(docker))
+            }
+        };
+
+        let cache = || MetricCache::new(config.cache_ttl, config.max_cached_containers);
+        Ok(Self {
+            cpu_cache: cache(),
+            memory_cache: cache(),
             config,
-            client,
-            cpu_cache: DashMap::new(),
-            memory_cache: DashMap::new(),
+            reader,
+            #[cfg(feature = "prometheus")]
+            detected_pattern: RwLock::new(None),
+        })
+    }
+
+    /// Start a GET request with the configured credentials attached
+    #[cfg(feature = "prometheus")]
+    fn get(&self, client: &Client, url: &str) -> RequestBuilder {
+        let request = client.get(url);
+        match &self.config.auth {
+            MetricsAuth::None => request,
+            MetricsAuth::Basic { username, password } => {
+                request.basic_auth(username, password.as_ref())
+            }
+            MetricsAuth::Bearer(token) => request.bearer_auth(token),
         }
     }
 
@@ -80,14 +320,20 @@ impl MetricsClient {
             return Ok(cached);
         }
 
-        // Query Prometheus for CPU usage
-        // Using rate over 30 seconds to get a more stable metric
-        let query = format!(
-            "rate(container_cpu_usage_seconds_total{{id=~\"/docker/{}.*\"}}[30s]) * 100",
-            &container_id[0..12] // Use shortened container ID
-        );
-
-        let result = self.query_prometheus(&query).await?;
+        let result = match &self.reader {
+            #[cfg(feature = "prometheus")]
+            Reader::Prometheus(client) => {
+                // Using rate over 30 seconds to get a more stable metric
+                let id_selector = self.container_id_selector(client, container_id).await;
+                let query = format!(
+                    "rate(container_cpu_usage_seconds_total{{id=~\"{}\"}}[30s]) * 100",
+                    id_selector
+                );
+                self.query_prometheus(client, &query).await?
+            }
+            Reader::Cgroup(cgroup) => cgroup.cpu_usage(container_id)?,
+            Reader::DockerStats(docker) => docker.cpu_usage(container_id).await?,
+        };
 
         // Cache the result
         self.cache_cpu_metric(container_id, result);
@@ -104,13 +350,19 @@ impl MetricsClient {
             return Ok(cached);
         }
 
-        // Query Prometheus for memory usage percentage
-        let query = format!(
-            "(container_memory_usage_bytes{{id=~\"/docker/{}.*\"}} / container_spec_memory_limit_bytes{{id=~\"/docker/{}.*\"}}) * 100",
-            &container_id[0..12], &container_id[0..12]
-        );
-
-        let result = self.query_prometheus(&query).await?;
+        let result = match &self.reader {
+            #[cfg(feature = "prometheus")]
+            Reader::Prometheus(client) => {
+                let id_selector = self.container_id_selector(client, container_id).await;
+                let query = format!(
+                    "(container_memory_usage_bytes{{id=~\"{0}\"}} / container_spec_memory_limit_bytes{{id=~\"{0}\"}}) * 100",
+                    id_selector
+                );
+                self.query_prometheus(client, &query).await?
+            }
+            Reader::Cgroup(cgroup) => cgroup.memory_usage(container_id)?,
+            Reader::DockerStats(docker) => docker.memory_usage(container_id).await?,
+        };
 
         // Cache the result
         self.cache_memory_metric(container_id, result);
@@ -119,12 +371,48 @@ impl MetricsClient {
         Ok(result)
     }
 
+    /// Build the `id` label regex for a container.
+    ///
+    /// Uses the configured pattern if there is one; otherwise tries each known cgroup
+    /// layout until one matches a series for this container and remembers it. Until a
+    /// layout is detected (e.g. the container hasn't been scraped yet) the first pattern
+    /// is used.
+    #[cfg(feature = "prometheus")]
+    async fn container_id_selector(&self, client: &Client, container_id: &str) -> String {
+        let short_id = short_container_id(container_id);
+
+        if let Some(pattern) = &self.config.container_id_pattern {
+            return render_id_pattern(pattern, short_id);
+        }
+        if let Some(pattern) = self.detected_pattern.read().unwrap().as_ref() {
+            return render_id_pattern(pattern, short_id);
+        }
+
+        let url = format!("{}/api/v1/query", self.config.prometheus_url);
+        for pattern in CGROUP_ID_PATTERNS {
+            let selector = render_id_pattern(pattern, short_id);
+            let query = format!("container_last_seen{{id=~\"{}\"}}", selector);
+            if let Ok(Some(_)) = self.fetch_first_value(client, &url, &query).await {
+                info!("Detected cAdvisor cgroup id layout: {}", pattern);
+                *self.detected_pattern.write().unwrap() = Some(pattern.to_string());
+                return selector;
+            }
+        }
+
+        debug!(
+            "Could not detect cgroup id layout for container {}, using default",
+            container_id
+        );
+        render_id_pattern(CGROUP_ID_PATTERNS[0], short_id)
+    }
+
     /// Query Prometheus and return the first result value
-    async fn query_prometheus(&self, query: &str) -> AppResult<f64> {
+    #[cfg(feature = "prometheus")]
+    async fn query_prometheus(&self, client: &Client, query: &str) -> AppResult<f64> {
         let url = format!("{}/api/v1/query", self.config.prometheus_url);
 
         for attempt in 1..=self.config.max_retries {
-            match self.execute_query(&url, query).await {
+            match self.execute_query(client, &url, query).await {
                 Ok(value) => return Ok(value),
                 Err(e) => {
                     if attempt == self.config.max_retries {
@@ -144,11 +432,33 @@ impl MetricsClient {
         ))
     }
 
-    /// Execute a single Prometheus query
-    async fn execute_query(&self, url: &str, query: &str) -> AppResult<f64> {
+    /// Execute a single Prometheus query, treating "no series" and NaN as 0
+    #[cfg(feature = "prometheus")]
+    async fn execute_query(&self, client: &Client, url: &str, query: &str) -> AppResult<f64> {
+        match self.fetch_first_value(client, url, query).await? {
+            Some(value) if value.is_nan() || value.is_infinite() => {
+                // Common when containers just started
+                debug!("Received NaN/Infinite value from Prometheus, returning 0.0");
+                Ok(0.0)
+            }
+            Some(value) => Ok(value),
+            None => {
+                debug!("No metrics found for query: {}", query);
+                Ok(0.0) // Return 0 if no metrics found (container might be starting)
+            }
+        }
+    }
+
+    /// Run a query and return the value of the first series, if any
+    #[cfg(feature = "prometheus")]
+    async fn fetch_first_value(
+        &self,
+        client: &Client,
+        url: &str,
+        query: &str,
+    ) -> AppResult<Option<f64>> {
         let response = self
-            .client
-            .get(url)
+            .get(client, url)
             .query(&[("query", query)])
             .send()
             .await
@@ -173,79 +483,136 @@ impl MetricsClient {
         }
 
         // Extract the first result value
-        if let Some(result) = prom_response.data.result.first() {
-            let value_str = &result.value.1;
-            let value = value_str.parse::<f64>().map_err(|e| {
-                RuntimeError::System(format!("Failed to parse metric value: {}", e))
-            })?;
-
-            // Handle NaN values (common when containers just started)
-            if value.is_nan() || value.is_infinite() {
-                debug!("Received NaN/Infinite value from Prometheus, returning 0.0");
-                return Ok(0.0);
-            }
-
-            Ok(value)
-        } else {
-            debug!("No metrics found for query: {}", query);
-            Ok(0.0) // Return 0 if no metrics found (container might be starting)
-        }
+        prom_response
+            .data
+            .result
+            .first()
+            .map(|result| {
+                result.value.1.parse::<f64>().map_err(|e| {
+                    RuntimeError::System(format!("Failed to parse metric value: {}", e))
+                })
+            })
+            .transpose()
     }
 
     /// Get cached CPU metric if still valid
     fn get_cached_cpu(&self, container_id: &str) -> Option<f64> {
-        let cached = self.cpu_cache.get(container_id)?;
-
-        if cached.timestamp.elapsed() < self.config.cache_ttl {
-            Some(cached.value)
-        } else {
-            None
-        }
+        self.cpu_cache.get(container_id)
     }
 
     /// Get cached memory metric if still valid
     fn get_cached_memory(&self, container_id: &str) -> Option<f64> {
-        let cached = self.memory_cache.get(container_id)?;
-
-        if cached.timestamp.elapsed() < self.config.cache_ttl {
-            Some(cached.value)
-        } else {
-            None
-        }
+        self.memory_cache.get(container_id)
     }
 
     /// Cache CPU metric
     fn cache_cpu_metric(&self, container_id: &str, value: f64) {
-        self.cpu_cache.insert(
-            container_id.to_string(),
-            CachedMetric {
-                value,
-                timestamp: Instant::now(),
-            },
-        );
+        self.cpu_cache.insert(container_id, value);
     }
 
     /// Cache memory metric
     fn cache_memory_metric(&self, container_id: &str, value: f64) {
-        self.memory_cache.insert(
-            container_id.to_string(),
-            CachedMetric {
-                value,
-                timestamp: Instant::now(),
-            },
-        );
+        self.memory_cache.insert(container_id, value);
+    }
+
+    /// Drop the cached metrics of a container that left its pool
+    pub fn forget_container(&self, container_id: &str) {
+        self.cpu_cache.remove(container_id);
+        self.memory_cache.remove(container_id);
+        match &self.reader {
+            Reader::Cgroup(cgroup) => cgroup.forget_container(container_id),
+            Reader::DockerStats(docker) => docker.forget_container(container_id),
+            #[cfg(feature = "prometheus")]
+            Reader::Prometheus(_) => {}
+        }
+    }
+
+    /// Containers with cached metrics
+    pub fn cached_containers(&self) -> usize {
+        self.cpu_cache.len().max(self.memory_cache.len())
     }
 
     /// Health check for the metrics client
     pub async fn health_check(&self) -> bool {
-        let url = format!("{}/api/v1/query", self.config.prometheus_url);
-        match self.client.get(&url).query(&[("query", "up")]).send().await {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
+        match &self.reader {
+            #[cfg(feature = "prometheus")]
+            Reader::Prometheus(client) => {
+                let url = format!("{}/api/v1/query", self.config.prometheus_url);
+                match self
+                    .get(client, &url)
+                    .query(&[("query", "up")])
+                    .send()
+                    .await
+                {
+                    Ok(response) => response.status().is_success(),
+                    Err(_) => false,
+                }
+            }
+            Reader::Cgroup(cgroup) => cgroup.is_readable(),
+            Reader::DockerStats(docker) => docker.is_reachable().await,
         }
     }
 }
 
+impl ContainerMetrics for MetricsClient {
+    fn cpu_usage<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, AppResult<f64>> {
+        Box::pin(self.get_container_cpu_usage(container_id))
+    }
+
+    fn memory_usage<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, AppResult<f64>> {
+        Box::pin(self.get_container_memory_usage(container_id))
+    }
+
+    fn forget_container(&self, container_id: &str) {
+        MetricsClient::forget_container(self, container_id)
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, bool> {
+        Box::pin(MetricsClient::health_check(self))
+    }
+}
+
+/// HTTP client for the query API, trusting the configured CA bundle
+#[cfg(feature = "prometheus")]
+fn prometheus_client(config: &MetricsConfig) -> AppResult<Client> {
+    let mut builder = Client::builder().timeout(config.query_timeout);
+    if let Some(path) = &config.ca_cert_path {
+        let pem = std::fs::read(path).map_err(|e| {
+            RuntimeError::System(format!(
+                "Failed to read metrics CA bundle {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let certs = Certificate::from_pem_bundle(&pem).map_err(|e| {
+            RuntimeError::System(format!(
+                "Invalid metrics CA bundle {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| RuntimeError::System(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// Docker's 12 character short ID (the full ID if it is shorter)
+#[cfg(feature = "prometheus")]
+fn short_container_id(container_id: &str) -> &str {
+    container_id.get(..12).unwrap_or(container_id)
+}
+
+/// Substitute the container ID into a cgroup id pattern
+#[cfg(feature = "prometheus")]
+fn render_id_pattern(pattern: &str, short_id: &str) -> String {
+    pattern.replace(CONTAINER_ID_PLACEHOLDER, short_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,10 +620,44 @@ mod tests {
     #[test]
     fn test_metrics_config_default() {
         let config = MetricsConfig::default();
+        #[cfg(feature = "prometheus")]
+        assert_eq!(config.source, MetricsSource::Prometheus);
+        #[cfg(not(feature = "prometheus"))]
+        assert_eq!(config.source, MetricsSource::DockerStats);
         assert_eq!(config.prometheus_url, "http://prometheus:9090");
         assert_eq!(config.query_timeout, Duration::from_secs(5));
         assert_eq!(config.cache_ttl, Duration::from_secs(5));
         assert_eq!(config.max_retries, 3);
+        assert_eq!(config.container_id_pattern, None);
+        assert_eq!(config.auth, MetricsAuth::None);
+        assert_eq!(config.max_cached_containers, 1024);
+    }
+
+    #[test]
+    fn test_auth_debug_hides_secrets() {
+        let basic = MetricsAuth::Basic {
+            username: "admin".to_string(),
+            password: Some("hunter2".to_string()),
+        };
+        assert_eq!(format!("{:?}", basic), "Basic(admin, ***)");
+        assert!(!format!("{:?}", MetricsAuth::Bearer("secret".to_string())).contains("secret"));
+    }
+
+    #[test]
+    #[cfg(feature = "prometheus")]
+    fn test_render_id_pattern() {
+        let id = "0123456789abcdef0123";
+        assert_eq!(short_container_id(id), "0123456789ab");
+        assert_eq!(short_container_id("abc"), "abc");
+
+        assert_eq!(
+            render_id_pattern(CGROUP_ID_PATTERNS[0], short_container_id(id)),
+            "/docker/0123456789ab.*"
+        );
+        assert_eq!(
+            render_id_pattern(CGROUP_ID_PATTERNS[1], short_container_id(id)),
+            "/system.slice/docker-0123456789ab.*"
+        );
     }
 
     #[test]
@@ -270,5 +671,46 @@ mod tests {
 
         client.cache_memory_metric("test-container", 75.0);
         assert_eq!(client.get_cached_memory("test-container"), Some(75.0));
+
+        client.forget_container("test-container");
+        assert_eq!(client.get_cached_cpu("test-container"), None);
+        assert_eq!(client.get_cached_memory("test-container"), None);
+        assert_eq!(client.cached_containers(), 0);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let client = MetricsClient::new(MetricsConfig {
+            max_cached_containers: 2,
+            ..Default::default()
+        });
+
+        client.cache_cpu_metric("a", 1.0);
+        client.cache_cpu_metric("b", 2.0);
+        // Reading `a` makes `b` the least recently used
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(client.get_cached_cpu("a"), Some(1.0));
+        client.cache_cpu_metric("c", 3.0);
+
+        assert_eq!(client.cached_containers(), 2);
+        assert_eq!(client.get_cached_cpu("a"), Some(1.0));
+        assert_eq!(client.get_cached_cpu("b"), None);
+        assert_eq!(client.get_cached_cpu("c"), Some(3.0));
+    }
+
+    #[test]
+    fn test_cache_drops_expired_entries_first() {
+        let client = MetricsClient::new(MetricsConfig {
+            cache_ttl: Duration::from_millis(1),
+            max_cached_containers: 2,
+            ..Default::default()
+        });
+
+        client.cache_memory_metric("a", 1.0);
+        client.cache_memory_metric("b", 2.0);
+        std::thread::sleep(Duration::from_millis(5));
+        client.cache_memory_metric("c", 3.0);
+
+        assert_eq!(client.cached_containers(), 1);
     }
 }
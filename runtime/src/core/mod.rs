@@ -1,8 +1,32 @@
 pub mod autoscaler;
 pub mod builder;
+pub mod cgroup_metrics;
 pub mod container_manager;
+pub mod deadlines;
+pub mod dev;
+pub mod diagnostics;
+pub mod docker_stats;
+pub mod egress;
+pub mod environment;
+pub mod events;
+pub mod exec;
+pub mod fairness;
+pub mod freeze;
+pub mod hooks;
+pub mod internal_api;
 pub mod logs;
 pub mod metrics_client;
 pub mod persistence;
+pub mod platform;
+pub mod policy;
+pub mod polling;
+pub mod preflight;
 pub mod provisioning;
+pub mod replicas;
+pub mod restore;
 pub mod runner;
+pub mod sandbox;
+pub mod scaling_history;
+pub mod start_queue;
+pub mod supervisor;
+pub mod usage;
@@ -1,12 +1,77 @@
-use crate::core::container_manager::{ContainerInfo, ContainerStatus, MonitoringConfig};
+use crate::core::container_manager::{
+    ContainerInfo, ContainerStatus, MonitoringConfig, PublishedPort,
+};
+use crate::core::diagnostics::{BootLog, CrashReport};
+use crate::core::policy::FunctionPolicy;
+use crate::core::replicas::PoolCommand;
+use crate::core::usage::ResourceUsage;
 use crate::shared::error::{AppResult, RuntimeError};
+#[cfg(feature = "redis")]
+use dashmap::DashMap;
+#[cfg(feature = "redis")]
 use futures_util::future::join_all;
-use redis::{aio::MultiplexedConnection, AsyncCommands, Client};
+#[cfg(feature = "redis")]
+use redis::{aio::MultiplexedConnection, AsyncCommands, Client, Script};
+#[cfg(feature = "redis")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "redis")]
+use std::time::Instant;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "redis")]
 use tracing::{debug, error, info, warn};
 
+/// How long crash reports and boot logs are kept in Redis (7 days)
+#[cfg(feature = "redis")]
+const DIAGNOSTICS_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Writes a pool state (ARGV[2]) for ARGV[3] seconds if the stored one is still at the
+/// version ARGV[1] the writer last saw; a missing state is at version 0. Returns whether
+/// it was written and the version stored before.
+#[cfg(feature = "redis")]
+const SAVE_POOL_STATE_SCRIPT: &str = r"
+local stored = redis.call('GET', KEYS[1])
+local version = 0
+if stored then
+    local ok, state = pcall(cjson.decode, stored)
+    if ok and type(state.version) == 'number' then
+        version = state.version
+    end
+end
+if version ~= tonumber(ARGV[1]) then
+    return {0, version}
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+return {1, version}
+";
+
+/// Takes the leader lease for ARGV[1] if it's free or already its own, for ARGV[2] ms
+#[cfg(feature = "redis")]
+const ACQUIRE_LEADERSHIP_SCRIPT: &str = r"
+local holder = redis.call('GET', KEYS[1])
+if holder == false or holder == ARGV[1] then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+end
+return 0
+";
+
+/// Frees the leader lease if ARGV[1] holds it
+#[cfg(feature = "redis")]
+const RELEASE_LEADERSHIP_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// How long containers followers routed to are kept for the leader to see (1 hour)
+#[cfg(feature = "redis")]
+const ACTIVITY_TTL_SECS: i64 = 60 * 60;
+
 /// Configuration for autoscaler persistence
 #[derive(Debug, Clone)]
 pub struct PersistenceConfig {
@@ -14,15 +79,25 @@ pub struct PersistenceConfig {
     pub redis_url: String,
     pub key_prefix: String,
     pub batch_size: usize, // Number of pools to load in parallel during recovery
+    /// Keys Redis looks at per `SCAN` call when listing pool states
+    pub scan_page_size: usize,
+    /// How long a pool state is kept after its last save or invocation
+    pub state_ttl: Duration,
+    /// Pool states not saved for longer are not restored: their controller was down so
+    /// long that their containers can't be trusted. Never when `None`.
+    pub max_state_age: Option<Duration>,
 }
 
 impl Default for PersistenceConfig {
     fn default() -> Self {
         Self {
-            enabled: true,
+            enabled: cfg!(feature = "redis"),
             redis_url: "redis://localhost:6379".to_string(),
             key_prefix: "autoscaler".to_string(),
             batch_size: 50, // Load 50 pools at a time during recovery
+            scan_page_size: 500,
+            state_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+            max_state_age: Some(Duration::from_secs(24 * 60 * 60)),
         }
     }
 }
@@ -33,9 +108,28 @@ pub struct PersistedContainerInfo {
     pub id: String,
     pub name: String,
     pub container_port: u32,
+    /// Missing in states saved before containers were dialed by IP
+    #[serde(default)]
+    pub ip_address: Option<String>,
+    /// Where its port is published, when the controller dials published ports
+    #[serde(default)]
+    pub published: Option<PublishedPort>,
     pub status: ContainerStatus,
     pub last_active_unix: i64,
     pub idle_since_unix: Option<i64>,
+    /// Frozen by the pause idle strategy, so replicas don't route to it
+    #[serde(default)]
+    pub paused: bool,
+    /// When the container started; missing in states saved by older versions, whose
+    /// containers count their age from their restore
+    #[serde(default)]
+    pub started_unix: Option<i64>,
+    /// Requests the container was claimed for so far
+    #[serde(default)]
+    pub requests: u64,
+    /// Replaced by a fresh container, so replicas don't route to it
+    #[serde(default)]
+    pub retiring: bool,
 }
 
 impl PersistedContainerInfo {
@@ -57,13 +151,24 @@ impl PersistedContainerInfo {
             ) as i64
         });
 
+        let started_unix = SystemTime::now()
+            .checked_sub(container.started_at.elapsed())
+            .and_then(|started| started.duration_since(UNIX_EPOCH).ok())
+            .map(|started| started.as_secs() as i64);
+
         Self {
             id: container.id.clone(),
             name: container.name.clone(),
             container_port: container.container_port,
+            ip_address: container.ip_address.clone(),
+            published: container.published.clone(),
             status: container.status.clone(),
             last_active_unix,
             idle_since_unix,
+            paused: false,
+            started_unix,
+            requests: container.requests,
+            retiring: container.retiring,
         }
     }
 
@@ -92,13 +197,33 @@ impl PersistedContainerInfo {
             ))
         });
 
+        let started_at = self
+            .started_unix
+            .and_then(|unix_time| {
+                now.checked_sub(Duration::from_secs(
+                    (SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs() as i64
+                        - unix_time)
+                        .max(0) as u64,
+                ))
+            })
+            .unwrap_or(now);
+
         ContainerInfo {
             id: self.id.clone(),
             name: self.name.clone(),
             container_port: self.container_port,
+            ip_address: self.ip_address.clone(),
+            published: self.published.clone(),
             status: self.status.clone(),
             last_active,
             idle_since,
+            in_flight: 0,
+            started_at,
+            requests: self.requests,
+            retiring: self.retiring,
         }
     }
 }
@@ -112,6 +237,59 @@ pub struct PersistedPoolState {
     pub max_containers: usize,
     pub config: MonitoringConfig,
     pub last_updated: i64, // When this pool was last updated
+    /// Resource usage sampled so far (absent in states saved by older versions)
+    #[serde(default)]
+    pub usage: ResourceUsage,
+    #[serde(default)]
+    pub policy: FunctionPolicy,
+    /// Incremented by every save; a save from a writer that saw another version is refused
+    /// (0 in states saved by older versions)
+    #[serde(default)]
+    pub version: u64,
+}
+
+impl PersistedPoolState {
+    /// Digest of what replicas route by: each container's address and state, and the
+    /// pool's settings. Activity times, request counts and usage samples are left out, as
+    /// they change on every pass without changing where invocations go.
+    pub fn routing_digest(&self) -> u64 {
+        let mut containers: Vec<serde_json::Value> = self
+            .containers
+            .iter()
+            .map(|container| {
+                serde_json::json!([
+                    container.id,
+                    container.name,
+                    container.container_port,
+                    container.ip_address,
+                    container.published,
+                    container.status,
+                    container.paused,
+                    container.retiring,
+                ])
+            })
+            .collect();
+        containers.sort_by(|a, b| a[0].as_str().cmp(&b[0].as_str()));
+        let routing = serde_json::json!([
+            containers,
+            self.min_containers,
+            self.max_containers,
+            self.config,
+            self.policy,
+        ]);
+        let mut hasher = DefaultHasher::new();
+        routing.to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Result of a save of a pool state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOutcome {
+    /// Written as this version
+    Saved { version: u64 },
+    /// Another writer saved since; Redis holds this version instead of the expected one
+    Conflict { version: u64 },
 }
 
 /// Lightweight metadata for the persistence system
@@ -136,11 +314,15 @@ impl PersistenceMetadata {
 }
 
 /// Redis persistence handler for autoscaler state using individual pool storage
+#[cfg(feature = "redis")]
 pub struct AutoscalerPersistence {
     redis_client: Client,
     config: PersistenceConfig,
+    /// When the TTL of each pool state was last refreshed on an invocation
+    touched: DashMap<String, Instant>,
 }
 
+#[cfg(feature = "redis")]
 impl AutoscalerPersistence {
     /// Create new persistence handler
     pub fn new(config: PersistenceConfig) -> AppResult<Self> {
@@ -152,6 +334,7 @@ impl AutoscalerPersistence {
         Ok(Self {
             redis_client,
             config,
+            touched: DashMap::new(),
         })
     }
 
@@ -176,49 +359,134 @@ impl AutoscalerPersistence {
         format!("{}:metadata", self.config.key_prefix)
     }
 
-    /// Save individual pool state to Redis
+    /// Generate crash report key
+    fn crash_report_key(&self, function_key: &str) -> String {
+        format!("{}:crash:{}", self.config.key_prefix, function_key)
+    }
+
+    /// Generate boot log key
+    fn boot_log_key(&self, function_key: &str) -> String {
+        format!("{}:bootlog:{}", self.config.key_prefix, function_key)
+    }
+
+    /// Generate the key of the replicas' leader lease
+    fn leader_key(&self) -> String {
+        format!("{}:leader", self.config.key_prefix)
+    }
+
+    /// Generate the key of the queue of commands followers send the leader
+    fn commands_key(&self) -> String {
+        format!("{}:commands", self.config.key_prefix)
+    }
+
+    /// Generate the key of the containers followers routed to since the leader last looked
+    fn activity_key(&self, function_key: &str) -> String {
+        format!("{}:activity:{}", self.config.key_prefix, function_key)
+    }
+
+    /// Save individual pool state to Redis, unless another writer saved it since the
+    /// version in `pool_state`; the state is written as the next version.
     pub async fn save_pool_state(
         &self,
         function_key: &str,
         pool_state: &PersistedPoolState,
-    ) -> AppResult<()> {
+    ) -> AppResult<SaveOutcome> {
         if !self.config.enabled {
-            return Ok(());
+            return Ok(SaveOutcome::Saved {
+                version: pool_state.version,
+            });
         }
 
         let mut conn = self.get_connection().await?;
         let key = self.pool_key(function_key);
+        let version = pool_state.version + 1;
 
-        let serialized = serde_json::to_string(pool_state).map_err(|e| {
+        let mut value = serde_json::to_value(pool_state).map_err(|e| {
             error!("Failed to serialize pool state for {}: {}", function_key, e);
             RuntimeError::SerializationError(format!("Failed to serialize pool state: {}", e))
         })?;
-
-        conn.set::<_, _, ()>(&key, &serialized).await.map_err(|e| {
-            error!(
-                "Failed to save pool state for {} to Redis: {}",
-                function_key, e
-            );
-            RuntimeError::RedisError(format!("Failed to save pool state: {}", e))
-        })?;
-
-        // Set expiration (24 hours)
-        conn.expire::<_, ()>(&key, 24 * 60 * 60)
+        value["version"] = version.into();
+
+        let (written, stored): (i32, u64) = Script::new(SAVE_POOL_STATE_SCRIPT)
+            .key(&key)
+            .arg(pool_state.version)
+            .arg(value.to_string())
+            .arg(self.config.state_ttl.as_secs())
+            .invoke_async(&mut conn)
             .await
             .map_err(|e| {
-                warn!(
-                    "Failed to set expiration on pool state for {}: {}",
+                error!(
+                    "Failed to save pool state for {} to Redis: {}",
                     function_key, e
                 );
-                RuntimeError::RedisError(format!("Failed to set expiration: {}", e))
+                RuntimeError::RedisError(format!("Failed to save pool state: {}", e))
             })?;
+        if written == 0 {
+            debug!(
+                "Pool state for {} is at version {}, not {}",
+                function_key, stored, pool_state.version
+            );
+            return Ok(SaveOutcome::Conflict { version: stored });
+        }
 
         debug!(
-            "Saved pool state for {} with {} containers",
+            "Saved pool state for {} with {} containers as version {}",
             function_key,
-            pool_state.containers.len()
+            pool_state.containers.len(),
+            version
         );
-        Ok(())
+        Ok(SaveOutcome::Saved { version })
+    }
+
+    /// Whether the expiry of a pool state should be pushed back on an invocation now,
+    /// i.e. it wasn't in the last [`Self::refresh_interval`]
+    pub fn should_touch(&self, function_key: &str) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        let recent = self
+            .touched
+            .get(function_key)
+            .is_some_and(|touched| touched.elapsed() < self.refresh_interval());
+        if !recent {
+            self.touched
+                .insert(function_key.to_string(), Instant::now());
+        }
+        !recent
+    }
+
+    /// Push back the expiry of a pool state, so functions invoked less often than the
+    /// TTL keep their state
+    pub async fn touch_pool_state(&self, function_key: &str) -> AppResult<()> {
+        let mut conn = self.get_connection().await?;
+        conn.expire::<_, ()>(
+            self.pool_key(function_key),
+            self.config.state_ttl.as_secs() as i64,
+        )
+        .await
+        .map_err(|e| RuntimeError::RedisError(format!("Failed to refresh pool state TTL: {}", e)))
+    }
+
+    /// How often pool states are saved again while nothing changes, and their TTL
+    /// refreshed, so they neither expire nor turn stale while their controller runs
+    pub fn refresh_interval(&self) -> Duration {
+        let lifetime = match self.config.max_state_age {
+            Some(max_age) => max_age.min(self.config.state_ttl),
+            None => self.config.state_ttl,
+        };
+        lifetime / 2
+    }
+
+    /// Whether a pool state wasn't saved for longer than the configured maximum age
+    pub fn is_stale(&self, pool_state: &PersistedPoolState) -> bool {
+        let Some(max_age) = self.config.max_state_age else {
+            return false;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        now.saturating_sub(pool_state.last_updated) > max_age.as_secs() as i64
     }
 
     /// Load individual pool state from Redis
@@ -275,21 +543,39 @@ impl AutoscalerPersistence {
         }
 
         let mut conn = self.get_connection().await?;
-        let pattern = format!("{}:pool:*", self.config.key_prefix);
-
-        let keys: Vec<String> = conn.keys(&pattern).await.map_err(|e| {
-            error!("Failed to get pool keys from Redis: {}", e);
-            RuntimeError::RedisError(format!("Failed to get pool keys: {}", e))
-        })?;
-
-        // Extract function keys from Redis keys
-        let function_keys: Vec<String> = keys
-            .into_iter()
-            .filter_map(|key| {
-                let pool_prefix = format!("{}:pool:", self.config.key_prefix);
-                key.strip_prefix(&pool_prefix).map(|s| s.to_string())
-            })
-            .collect();
+        let pool_prefix = format!("{}:pool:", self.config.key_prefix);
+        let pattern = format!("{}*", pool_prefix);
+
+        // SCAN a page at a time rather than KEYS, which blocks Redis while it walks the
+        // whole keyspace
+        let mut function_keys = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(self.config.scan_page_size)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| {
+                    error!("Failed to get pool keys from Redis: {}", e);
+                    RuntimeError::RedisError(format!("Failed to get pool keys: {}", e))
+                })?;
+            function_keys.extend(
+                keys.iter()
+                    .filter_map(|key| key.strip_prefix(&pool_prefix))
+                    .map(|function_key| function_key.to_string()),
+            );
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        // A key may be returned more than once while the keyspace is rehashed
+        function_keys.sort_unstable();
+        function_keys.dedup();
 
         info!(
             "Found {} persisted pool states in Redis",
@@ -484,12 +770,313 @@ impl AutoscalerPersistence {
         }
     }
 
+    /// Save the most recent crash report for a function
+    pub async fn save_crash_report(
+        &self,
+        function_key: &str,
+        report: &CrashReport,
+    ) -> AppResult<()> {
+        let key = self.crash_report_key(function_key);
+        self.save_diagnostic(&key, "crash report", function_key, report)
+            .await
+    }
+
+    /// Load the most recent crash report for a function
+    pub async fn load_crash_report(&self, function_key: &str) -> AppResult<Option<CrashReport>> {
+        let key = self.crash_report_key(function_key);
+        self.load_diagnostic(&key, "crash report", function_key)
+            .await
+    }
+
+    /// Save the boot log of the last container of a function that failed to become ready
+    pub async fn save_boot_log(&self, function_key: &str, boot_log: &BootLog) -> AppResult<()> {
+        let key = self.boot_log_key(function_key);
+        self.save_diagnostic(&key, "boot log", function_key, boot_log)
+            .await
+    }
+
+    /// Load the last recorded boot log for a function
+    pub async fn load_boot_log(&self, function_key: &str) -> AppResult<Option<BootLog>> {
+        let key = self.boot_log_key(function_key);
+        self.load_diagnostic(&key, "boot log", function_key).await
+    }
+
+    /// Store a diagnostic record as JSON, expiring after `DIAGNOSTICS_TTL_SECS`
+    async fn save_diagnostic<T: Serialize>(
+        &self,
+        key: &str,
+        kind: &str,
+        function_key: &str,
+        value: &T,
+    ) -> AppResult<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let mut conn = self.get_connection().await?;
+
+        let serialized = serde_json::to_string(value).map_err(|e| {
+            error!("Failed to serialize {} for {}: {}", kind, function_key, e);
+            RuntimeError::SerializationError(format!("Failed to serialize {}: {}", kind, e))
+        })?;
+
+        conn.set_ex::<_, _, ()>(key, &serialized, DIAGNOSTICS_TTL_SECS)
+            .await
+            .map_err(|e| {
+                error!("Failed to save {} for {}: {}", kind, function_key, e);
+                RuntimeError::RedisError(format!("Failed to save {}: {}", kind, e))
+            })?;
+
+        debug!("Saved {} for {}", kind, function_key);
+        Ok(())
+    }
+
+    /// Load a diagnostic record stored by `save_diagnostic`
+    async fn load_diagnostic<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        kind: &str,
+        function_key: &str,
+    ) -> AppResult<Option<T>> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+
+        let mut conn = self.get_connection().await?;
+
+        let serialized: Option<String> = conn.get(key).await.map_err(|e| {
+            error!("Failed to load {} for {}: {}", kind, function_key, e);
+            RuntimeError::RedisError(format!("Failed to load {}: {}", kind, e))
+        })?;
+
+        serialized
+            .map(|data| {
+                serde_json::from_str(&data).map_err(|e| {
+                    RuntimeError::SerializationError(format!(
+                        "Failed to deserialize {}: {}",
+                        kind, e
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Take or renew the lease of the replicas' scaling leader.
+    ///
+    /// Returns whether `replica_id` holds it for the next `ttl`.
+    pub async fn acquire_leadership(&self, replica_id: &str, ttl: Duration) -> AppResult<bool> {
+        let mut conn = self.get_connection().await?;
+        let acquired: i32 = Script::new(ACQUIRE_LEADERSHIP_SCRIPT)
+            .key(self.leader_key())
+            .arg(replica_id)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|e| {
+                RuntimeError::RedisError(format!("Failed to acquire leader lease: {}", e))
+            })?;
+        Ok(acquired == 1)
+    }
+
+    /// Give up the leader lease, if `replica_id` holds it, so another replica takes over
+    /// without waiting for it to expire
+    pub async fn release_leadership(&self, replica_id: &str) -> AppResult<()> {
+        let mut conn = self.get_connection().await?;
+        let _released: i32 = Script::new(RELEASE_LEADERSHIP_SCRIPT)
+            .key(self.leader_key())
+            .arg(replica_id)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|e| {
+                RuntimeError::RedisError(format!("Failed to release leader lease: {}", e))
+            })?;
+        Ok(())
+    }
+
+    /// Queue a command for the leader
+    pub async fn push_pool_command(&self, command: &PoolCommand) -> AppResult<()> {
+        let mut conn = self.get_connection().await?;
+        let serialized = serde_json::to_string(command).map_err(|e| {
+            RuntimeError::SerializationError(format!("Failed to serialize pool command: {}", e))
+        })?;
+        conn.rpush::<_, _, ()>(self.commands_key(), serialized)
+            .await
+            .map_err(|e| RuntimeError::RedisError(format!("Failed to queue pool command: {}", e)))
+    }
+
+    /// Wait up to `timeout` for the next command followers queued. Commands that don't
+    /// parse are dropped.
+    pub async fn pop_pool_command(&self, timeout: Duration) -> AppResult<Option<PoolCommand>> {
+        let mut conn = self.get_connection().await?;
+        let popped: Option<(String, String)> = conn
+            .blpop(self.commands_key(), timeout.as_secs_f64())
+            .await
+            .map_err(|e| RuntimeError::RedisError(format!("Failed to read pool command: {}", e)))?;
+        Ok(
+            popped.and_then(|(_, command)| match serde_json::from_str(&command) {
+                Ok(command) => Some(command),
+                Err(e) => {
+                    warn!("Dropped invalid pool command {}: {}", command, e);
+                    None
+                }
+            }),
+        )
+    }
+
+    /// Tell the leader a follower routed an invocation to a container, so it isn't
+    /// scaled down as idle
+    pub async fn record_activity(&self, function_key: &str, container_id: &str) -> AppResult<()> {
+        let mut conn = self.get_connection().await?;
+        let key = self.activity_key(function_key);
+        redis::pipe()
+            .sadd(&key, container_id)
+            .ignore()
+            .expire(&key, ACTIVITY_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| RuntimeError::RedisError(format!("Failed to record activity: {}", e)))
+    }
+
+    /// Containers of a function followers routed to since the last call
+    pub async fn take_activity(&self, function_key: &str) -> AppResult<Vec<String>> {
+        let mut conn = self.get_connection().await?;
+        let key = self.activity_key(function_key);
+        let (container_ids,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .smembers(&key)
+            .del(&key)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| RuntimeError::RedisError(format!("Failed to read activity: {}", e)))?;
+        Ok(container_ids)
+    }
+
     /// Check if persistence is enabled
     pub fn is_enabled(&self) -> bool {
         self.config.enabled
     }
 }
 
+/// Stands in for the Redis persistence handler in builds without the `redis` feature.
+/// It can't be created, so pools only live in memory.
+#[cfg(not(feature = "redis"))]
+pub enum AutoscalerPersistence {}
+
+#[cfg(not(feature = "redis"))]
+impl AutoscalerPersistence {
+    /// Always fails: persistence needs the `redis` feature
+    pub fn new(_config: PersistenceConfig) -> AppResult<Self> {
+        Err(RuntimeError::System(
+            "Autoscaler persistence needs the runtime's redis feature".to_string(),
+        ))
+    }
+
+    pub async fn save_pool_state(
+        &self,
+        _function_key: &str,
+        _pool_state: &PersistedPoolState,
+    ) -> AppResult<SaveOutcome> {
+        match *self {}
+    }
+
+    pub fn should_touch(&self, _function_key: &str) -> bool {
+        match *self {}
+    }
+
+    pub async fn touch_pool_state(&self, _function_key: &str) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        match *self {}
+    }
+
+    pub fn is_stale(&self, _pool_state: &PersistedPoolState) -> bool {
+        match *self {}
+    }
+
+    pub async fn load_pool_state(
+        &self,
+        _function_key: &str,
+    ) -> AppResult<Option<PersistedPoolState>> {
+        match *self {}
+    }
+
+    pub async fn get_all_pool_keys(&self) -> AppResult<Vec<String>> {
+        match *self {}
+    }
+
+    pub async fn load_all_pool_states(&self) -> AppResult<HashMap<String, PersistedPoolState>> {
+        match *self {}
+    }
+
+    pub async fn delete_pool_state(&self, _function_key: &str) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn cleanup_stale_pools(&self, _active_function_keys: &[String]) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn save_metadata(&self, _metadata: &PersistenceMetadata) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn load_metadata(&self) -> AppResult<Option<PersistenceMetadata>> {
+        match *self {}
+    }
+
+    pub async fn save_crash_report(
+        &self,
+        _function_key: &str,
+        _report: &CrashReport,
+    ) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn load_crash_report(&self, _function_key: &str) -> AppResult<Option<CrashReport>> {
+        match *self {}
+    }
+
+    pub async fn save_boot_log(&self, _function_key: &str, _boot_log: &BootLog) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn load_boot_log(&self, _function_key: &str) -> AppResult<Option<BootLog>> {
+        match *self {}
+    }
+
+    pub async fn acquire_leadership(&self, _replica_id: &str, _ttl: Duration) -> AppResult<bool> {
+        match *self {}
+    }
+
+    pub async fn release_leadership(&self, _replica_id: &str) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn push_pool_command(&self, _command: &PoolCommand) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn pop_pool_command(&self, _timeout: Duration) -> AppResult<Option<PoolCommand>> {
+        match *self {}
+    }
+
+    pub async fn record_activity(&self, _function_key: &str, _container_id: &str) -> AppResult<()> {
+        match *self {}
+    }
+
+    pub async fn take_activity(&self, _function_key: &str) -> AppResult<Vec<String>> {
+        match *self {}
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        match *self {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -501,9 +1088,18 @@ mod tests {
             id: "test-id".to_string(),
             name: "test-container".to_string(),
             container_port: 8080,
+            ip_address: Some("172.18.0.5".to_string()),
+            published: Some(PublishedPort {
+                host: "127.0.0.1".to_string(),
+                port: 49153,
+            }),
             status: ContainerStatus::Healthy,
             last_active: Instant::now(),
             idle_since: None,
+            in_flight: 0,
+            started_at: Instant::now() - Duration::from_secs(600),
+            requests: 12,
+            retiring: true,
         };
 
         let persisted = PersistedContainerInfo::from_container_info(&original);
@@ -512,7 +1108,14 @@ mod tests {
         assert_eq!(original.id, converted.id);
         assert_eq!(original.name, converted.name);
         assert_eq!(original.container_port, converted.container_port);
+        assert_eq!(original.ip_address, converted.ip_address);
+        assert_eq!(original.published, converted.published);
+        assert_eq!(converted.address().authority(), "127.0.0.1:49153");
         assert_eq!(original.status, converted.status);
+        assert_eq!(converted.requests, 12);
+        assert!(converted.retiring);
+        let age = converted.started_at.elapsed().as_secs();
+        assert!((599..=601).contains(&age), "age {}", age);
     }
 
     #[test]
@@ -521,9 +1124,15 @@ mod tests {
             id: "test-id-idle".to_string(),
             name: "test-container-idle".to_string(),
             container_port: 3000,
+            ip_address: None,
+            published: None,
             status: ContainerStatus::Idle,
             last_active: Instant::now(),
             idle_since: Some(Instant::now()),
+            in_flight: 0,
+            started_at: Instant::now(),
+            requests: 0,
+            retiring: false,
         };
 
         let persisted = PersistedContainerInfo::from_container_info(&original);
@@ -539,10 +1148,12 @@ mod tests {
     #[test]
     fn test_persistence_config_default() {
         let config = PersistenceConfig::default();
-        assert!(config.enabled);
+        assert_eq!(config.enabled, cfg!(feature = "redis"));
         assert_eq!(config.redis_url, "redis://localhost:6379");
         assert_eq!(config.key_prefix, "autoscaler");
         assert_eq!(config.batch_size, 50);
+        assert_eq!(config.scan_page_size, 500);
+        assert!(config.max_state_age.unwrap() < config.state_ttl);
     }
 
     #[test]
@@ -553,14 +1164,23 @@ mod tests {
                 id: "container-1".to_string(),
                 name: "test-container-1".to_string(),
                 container_port: 8080,
+                ip_address: None,
+                published: None,
                 status: ContainerStatus::Healthy,
                 last_active_unix: 1000,
                 idle_since_unix: None,
+                paused: false,
+                started_unix: None,
+                requests: 0,
+                retiring: false,
             }],
             min_containers: 1,
             max_containers: 5,
             config: MonitoringConfig::default(),
             last_updated: 1703001234,
+            usage: ResourceUsage::default(),
+            policy: FunctionPolicy::default(),
+            version: 7,
         };
 
         // Test serialization
@@ -575,6 +1195,55 @@ mod tests {
         assert_eq!(deserialized.containers.len(), 1);
         assert_eq!(deserialized.containers[0].id, "container-1");
         assert_eq!(deserialized.last_updated, 1703001234);
+        assert_eq!(deserialized.version, 7);
+
+        // States saved before versioning count as version 0
+        let mut legacy: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+        legacy.as_object_mut().unwrap().remove("version");
+        let legacy: PersistedPoolState = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.version, 0);
+    }
+
+    #[test]
+    fn test_stale_pool_states() {
+        let persistence = AutoscalerPersistence::new(PersistenceConfig {
+            state_ttl: Duration::from_secs(3600),
+            max_state_age: Some(Duration::from_secs(600)),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(persistence.refresh_interval(), Duration::from_secs(300));
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut pool_state = PersistedPoolState {
+            function_name: "test-function".to_string(),
+            containers: Vec::new(),
+            min_containers: 1,
+            max_containers: 5,
+            config: MonitoringConfig::default(),
+            last_updated: now - 60,
+            usage: ResourceUsage::default(),
+            policy: FunctionPolicy::default(),
+            version: 1,
+        };
+        assert!(!persistence.is_stale(&pool_state));
+
+        pool_state.last_updated = now - 3600;
+        assert!(persistence.is_stale(&pool_state));
+
+        let unlimited = AutoscalerPersistence::new(PersistenceConfig {
+            max_state_age: None,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!unlimited.is_stale(&pool_state));
+        assert_eq!(
+            unlimited.refresh_interval(),
+            Duration::from_secs(7 * 24 * 60 * 60 / 2)
+        );
     }
 
     #[test]
@@ -584,4 +1253,125 @@ mod tests {
         assert_eq!(metadata.total_pools, 42);
         assert!(metadata.last_cleanup > 0);
     }
+
+    fn persisted_container(id: &str) -> PersistedContainerInfo {
+        PersistedContainerInfo {
+            id: id.to_string(),
+            name: format!("container-{id}"),
+            container_port: 8080,
+            ip_address: Some("172.18.0.5".to_string()),
+            published: None,
+            status: ContainerStatus::Healthy,
+            last_active_unix: 1000,
+            idle_since_unix: None,
+            paused: false,
+            started_unix: Some(900),
+            requests: 3,
+            retiring: false,
+        }
+    }
+
+    fn persisted_pool(containers: Vec<PersistedContainerInfo>) -> PersistedPoolState {
+        PersistedPoolState {
+            function_name: "test-function".to_string(),
+            containers,
+            min_containers: 1,
+            max_containers: 5,
+            config: MonitoringConfig::default(),
+            last_updated: 1703001234,
+            usage: ResourceUsage::default(),
+            policy: FunctionPolicy::default(),
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn test_routing_digest() {
+        let state = persisted_pool(vec![persisted_container("a"), persisted_container("b")]);
+        let digest = state.routing_digest();
+
+        // Container order, activity, request counts and timestamps don't matter
+        let mut busier = persisted_pool(vec![persisted_container("b"), persisted_container("a")]);
+        busier.containers[0].last_active_unix = 2000;
+        busier.containers[0].requests = 40;
+        busier.last_updated += 60;
+        busier.version = 9;
+        assert_eq!(busier.routing_digest(), digest);
+
+        // Where invocations go does
+        let mut paused = state.clone();
+        paused.containers[1].paused = true;
+        assert_ne!(paused.routing_digest(), digest);
+        let mut moved = state.clone();
+        moved.containers[0].ip_address = Some("172.18.0.6".to_string());
+        assert_ne!(moved.routing_digest(), digest);
+        let fewer = persisted_pool(vec![persisted_container("a")]);
+        assert_ne!(fewer.routing_digest(), digest);
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL (redis://localhost:6379 by default); run with --ignored"]
+    async fn test_save_pool_state_compare_and_set() {
+        let persistence = AutoscalerPersistence::new(PersistenceConfig {
+            enabled: true,
+            redis_url: std::env::var("REDIS_URL")
+                .unwrap_or_else(|_| "redis://localhost:6379".to_string()),
+            key_prefix: format!("autoscaler-test-{}", std::process::id()),
+            ..Default::default()
+        })
+        .unwrap();
+        let function_key = "cas-function";
+        let mut state = persisted_pool(vec![persisted_container("a")]);
+
+        // A missing state is at version 0
+        assert_eq!(
+            persistence
+                .save_pool_state(function_key, &state)
+                .await
+                .unwrap(),
+            SaveOutcome::Saved { version: 1 }
+        );
+        // A writer that didn't see version 1 is refused, and told the stored version
+        assert_eq!(
+            persistence
+                .save_pool_state(function_key, &state)
+                .await
+                .unwrap(),
+            SaveOutcome::Conflict { version: 1 }
+        );
+        state.version = 1;
+        assert_eq!(
+            persistence
+                .save_pool_state(function_key, &state)
+                .await
+                .unwrap(),
+            SaveOutcome::Saved { version: 2 }
+        );
+        let stored = persistence
+            .load_pool_state(function_key)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.version, 2);
+
+        // Once removed, the next save starts over
+        persistence.delete_pool_state(function_key).await.unwrap();
+        assert_eq!(
+            persistence
+                .save_pool_state(function_key, &state)
+                .await
+                .unwrap(),
+            SaveOutcome::Conflict { version: 0 }
+        );
+        state.version = 0;
+        assert_eq!(
+            persistence
+                .save_pool_state(function_key, &state)
+                .await
+                .unwrap(),
+            SaveOutcome::Saved { version: 1 }
+        );
+        persistence.delete_pool_state(function_key).await.unwrap();
+    }
 }
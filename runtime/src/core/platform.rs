@@ -0,0 +1,181 @@
+use crate::shared::error::{AppResult, RuntimeError};
+use bollard::Docker;
+use std::fmt;
+use std::str::FromStr;
+use tokio::sync::OnceCell;
+
+/// Architectures function images can be built for, as Docker and Go name them
+const ARCHITECTURES: &[&str] = &[
+    "amd64", "arm64", "arm", "386", "ppc64le", "s390x", "riscv64",
+];
+
+/// Platform of the Docker daemon, looked up once
+static DAEMON_PLATFORM: OnceCell<Platform> = OnceCell::const_new();
+
+/// A platform images are built for and containers run on, e.g. `linux/arm64`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Platform {
+    pub os: String,
+    pub arch: String,
+    /// CPU variant, e.g. `v7` for `linux/arm/v7`
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// Tag an image built for this platform carries next to the untagged native build,
+    /// e.g. `linux-arm64`
+    pub fn tag(&self) -> String {
+        self.to_string().replace('/', "-")
+    }
+
+    /// Build arguments BuildKit sets on its own; passed explicitly so Dockerfiles can
+    /// cross-compile with the classic builder too
+    pub fn build_args(&self, build_platform: &Platform) -> Vec<(&'static str, String)> {
+        vec![
+            ("BUILDPLATFORM", build_platform.to_string()),
+            ("BUILDOS", build_platform.os.clone()),
+            ("BUILDARCH", build_platform.arch.clone()),
+            ("TARGETPLATFORM", self.to_string()),
+            ("TARGETOS", self.os.clone()),
+            ("TARGETARCH", self.arch.clone()),
+            ("TARGETVARIANT", self.variant.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.arch)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{}", variant)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split('/').collect();
+        let (os, arch, variant) = match parts.as_slice() {
+            [os, arch] => (*os, *arch, None),
+            [os, arch, variant] if !variant.is_empty() => (*os, *arch, Some(variant.to_string())),
+            _ => return Err(format!("platform '{}' must be <os>/<arch>[/<variant>]", s)),
+        };
+        // Function images are Linux images
+        if os != "linux" {
+            return Err(format!("platform '{}' is not a linux platform", s));
+        }
+        if !ARCHITECTURES.contains(&arch) {
+            return Err(format!(
+                "platform '{}' has an unknown architecture, expected one of {}",
+                s,
+                ARCHITECTURES.join(", ")
+            ));
+        }
+        Ok(Self {
+            os: os.to_string(),
+            arch: arch.to_string(),
+            variant,
+        })
+    }
+}
+
+/// Parses a comma-separated platform list such as `linux/amd64,linux/arm64`
+pub fn parse_platforms(list: &str) -> Result<Vec<Platform>, String> {
+    let mut platforms: Vec<Platform> = Vec::new();
+    for platform in list.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let platform: Platform = platform.parse()?;
+        if !platforms.contains(&platform) {
+            platforms.push(platform);
+        }
+    }
+    Ok(platforms)
+}
+
+/// Platform of the Docker daemon: the one function containers run on, and the one images
+/// are built on
+pub async fn daemon_platform(docker: &Docker) -> AppResult<Platform> {
+    DAEMON_PLATFORM
+        .get_or_try_init(|| async {
+            let version = docker.version().await.map_err(|e| {
+                RuntimeError::System(format!("Failed to query the Docker daemon: {e}"))
+            })?;
+            Ok(Platform {
+                os: version.os.unwrap_or_else(|| "linux".to_string()),
+                arch: version.arch.unwrap_or_else(|| "amd64".to_string()),
+                variant: None,
+            })
+        })
+        .await
+        .cloned()
+}
+
+/// Platforms to build an image for: the daemon's own, so it can run the image, followed by
+/// the configured ones
+pub fn build_targets(native: &Platform, configured: &[Platform]) -> Vec<Platform> {
+    let mut targets = vec![native.clone()];
+    targets.extend(
+        configured
+            .iter()
+            .filter(|platform| !same_platform(platform, native))
+            .cloned(),
+    );
+    targets
+}
+
+/// Whether two platforms are the same, treating a missing variant as the default one
+pub fn same_platform(a: &Platform, b: &Platform) -> bool {
+    a.os == b.os
+        && a.arch == b.arch
+        && (a.variant.is_none() || b.variant.is_none() || a.variant == b.variant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(s: &str) -> Platform {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_platform() {
+        assert_eq!(
+            platform("linux/arm/v7"),
+            Platform {
+                os: "linux".to_string(),
+                arch: "arm".to_string(),
+                variant: Some("v7".to_string()),
+            }
+        );
+        assert_eq!(platform(" linux/amd64 ").to_string(), "linux/amd64");
+        assert_eq!(platform("linux/arm/v7").tag(), "linux-arm-v7");
+        assert!("linux".parse::<Platform>().is_err());
+        assert!("windows/amd64".parse::<Platform>().is_err());
+        assert!("linux/x86_64".parse::<Platform>().is_err());
+        assert!("linux/arm/".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn test_parse_platforms() {
+        assert_eq!(
+            parse_platforms("linux/amd64, linux/arm64,linux/amd64,").unwrap(),
+            vec![platform("linux/amd64"), platform("linux/arm64")]
+        );
+        assert!(parse_platforms("").unwrap().is_empty());
+        assert!(parse_platforms("linux/amd64,darwin/arm64").is_err());
+    }
+
+    #[test]
+    fn test_build_targets_start_with_native() {
+        let native = platform("linux/arm64");
+        let targets = build_targets(
+            &native,
+            &[platform("linux/amd64"), platform("linux/arm64/v8")],
+        );
+        assert_eq!(targets, vec![native.clone(), platform("linux/amd64")]);
+        assert_eq!(build_targets(&native, &[]), vec![native]);
+    }
+}
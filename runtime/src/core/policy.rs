@@ -0,0 +1,175 @@
+use crate::core::runner::ResourceLimits;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const BYTES_IN_MB: i64 = 1024 * 1024;
+
+/// Connections each container of a TCP or UDP service takes when the function doesn't say
+const DEFAULT_MAX_CONNECTIONS: usize = 100;
+
+/// Per-function routing and scaling behaviour, taken from the function's deploy config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionPolicy {
+    /// Send at most one request at a time to each container. Requests that find every
+    /// container busy wait for one to free up, and the pool scales on the queue depth
+    /// instead of CPU/memory.
+    #[serde(default)]
+    pub single_concurrency: bool,
+    /// Route requests carrying the same session key to the same container
+    #[serde(default)]
+    pub sticky: Option<StickyKey>,
+    /// Container memory limit in MB (the runtime default when unset)
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Containers kept even when idle (the autoscaler's minimum when unset)
+    #[serde(default)]
+    pub min_containers: Option<usize>,
+    /// Most containers the function scales to, capped by the autoscaler's maximum
+    #[serde(default)]
+    pub max_containers: Option<usize>,
+    /// What happens to containers that stayed idle past the cooldown
+    #[serde(default)]
+    pub idle_strategy: IdleStrategy,
+    /// HTTP version the function's containers serve, lifecycle hooks included
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// What the function's containers serve on their port
+    #[serde(default)]
+    pub service: Service,
+    /// Connections (UDP: client sessions) each container of a TCP or UDP service takes
+    /// before the pool scales up
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Seconds a request may run; the container still serving it afterwards is removed
+    #[serde(default)]
+    pub execution_timeout_secs: Option<u64>,
+    /// Seconds a container serves before it is replaced by a fresh one
+    #[serde(default)]
+    pub max_container_age_secs: Option<u64>,
+    /// Requests (connections) a container takes before it is replaced by a fresh one
+    #[serde(default)]
+    pub max_container_requests: Option<u64>,
+    /// Longest time in seconds between scaling checks of the pool while it has no
+    /// traffic (the autoscaler's idle interval when unset)
+    #[serde(default)]
+    pub idle_poll_interval_secs: Option<u64>,
+    /// Containers of the function started at the same time (1 when unset); requests
+    /// finding that many starting wait for the first to be ready
+    #[serde(default)]
+    pub start_concurrency: Option<usize>,
+}
+
+impl FunctionPolicy {
+    /// How long a request may keep its container busy, for HTTP functions; connections
+    /// to TCP and UDP services last as long as their clients keep them
+    pub fn execution_timeout(&self) -> Option<Duration> {
+        if !self.service.is_http() {
+            return None;
+        }
+        self.execution_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// How long a container serves before it is recycled, if it ever is
+    pub fn max_container_age(&self) -> Option<Duration> {
+        self.max_container_age_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Longest time between scaling checks of the idle pool, if the function sets one
+    pub fn idle_poll_interval(&self) -> Option<Duration> {
+        self.idle_poll_interval_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Containers of the function started at the same time
+    pub fn start_concurrency(&self) -> usize {
+        self.start_concurrency.unwrap_or(1).max(1)
+    }
+
+    /// Limits new containers of the function are started with
+    pub fn resource_limits(&self) -> ResourceLimits {
+        let mut limits = ResourceLimits::default();
+        if let Some(memory_mb) = self.memory_mb {
+            limits.memory_bytes = memory_mb as i64 * BYTES_IN_MB;
+        }
+        limits
+    }
+
+    /// Requests (connections, for TCP and UDP services) each container takes at once, or
+    /// `None` when containers are shared without a limit. Pools with a limit route to
+    /// containers below it and scale on the requests waiting for one.
+    pub fn concurrency_limit(&self) -> Option<usize> {
+        if self.single_concurrency {
+            return Some(1);
+        }
+        match self.service {
+            Service::Http => None,
+            Service::Tcp | Service::Udp => Some(
+                self.max_connections
+                    .unwrap_or(DEFAULT_MAX_CONNECTIONS)
+                    .max(1),
+            ),
+        }
+    }
+}
+
+/// How the autoscaler scales down idle containers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleStrategy {
+    /// Remove them; the next request past the remaining containers cold starts
+    #[default]
+    Remove,
+    /// Freeze them (`docker pause`) and unpause them on the next request: much faster
+    /// than a cold start, but they keep their memory until they are removed after an
+    /// hour paused
+    Pause,
+}
+
+/// HTTP version a function serves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// HTTP/1.1, a connection per request in flight
+    #[default]
+    Http1,
+    /// Cleartext HTTP/2 with prior knowledge, e.g. gRPC servers. Requests share one
+    /// connection per container as concurrent streams, and trailers are passed through.
+    H2c,
+}
+
+/// What a function serves on its container port
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Service {
+    /// HTTP invocations, proxied by the controller
+    #[default]
+    Http,
+    /// Raw TCP: each connection to the function's published port is relayed to a
+    /// container for as long as it stays open
+    Tcp,
+    /// Raw UDP: datagrams to the function's published port are relayed to a container,
+    /// one session per client address
+    Udp,
+}
+
+impl Service {
+    /// Whether containers answer HTTP, lifecycle hooks included
+    pub fn is_http(&self) -> bool {
+        *self == Service::Http
+    }
+}
+
+/// Where the session key for sticky routing is read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StickyKey {
+    /// A request header, e.g. `{"header": "x-session-id"}`
+    Header(String),
+    /// A cookie, e.g. `{"cookie": "session"}`
+    Cookie(String),
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// When each pool is checked next.
+///
+/// The scaling pass runs every `base` interval but only checks the pools that are due:
+/// reading metrics from Prometheus or Docker is the expensive part, and most pools of a
+/// large install are idle. A pool that saw traffic since its last check is due at once
+/// and goes back to the base interval; an idle one waits twice as long after each check,
+/// up to its idle interval.
+#[derive(Debug)]
+pub struct PollSchedule {
+    base: Duration,
+    idle: Duration,
+    pools: HashMap<String, PollState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PollState {
+    checked_at: Instant,
+    interval: Duration,
+}
+
+impl PollSchedule {
+    /// `idle` is the longest interval of idle pools without one of their own; it's never
+    /// shorter than `base`
+    pub fn new(base: Duration, idle: Duration) -> Self {
+        Self {
+            base,
+            idle: idle.max(base),
+            pools: HashMap::new(),
+        }
+    }
+
+    /// When the pool was last checked, if it was
+    pub fn last_checked(&self, function_key: &str) -> Option<Instant> {
+        self.pools.get(function_key).map(|state| state.checked_at)
+    }
+
+    /// Whether the pool is checked at `now`: it never was, it's `busy` (had traffic since
+    /// its last check), or its interval elapsed
+    pub fn is_due(&self, function_key: &str, busy: bool, now: Instant) -> bool {
+        match self.pools.get(function_key) {
+            Some(state) => busy || now.duration_since(state.checked_at) >= state.interval,
+            None => true,
+        }
+    }
+
+    /// Record a check of the pool at `now`. A `busy` pool is checked again after the base
+    /// interval, an idle one after twice its previous interval, capped at `idle` (the
+    /// pool's own when set, otherwise the schedule's).
+    pub fn checked(
+        &mut self,
+        function_key: &str,
+        busy: bool,
+        idle: Option<Duration>,
+        now: Instant,
+    ) {
+        let ceiling = idle.map_or(self.idle, |idle| idle.max(self.base));
+        let interval = match self.pools.get(function_key) {
+            Some(state) if !busy => (state.interval * 2).min(ceiling),
+            _ => self.base,
+        };
+        self.pools.insert(
+            function_key.to_string(),
+            PollState {
+                checked_at: now,
+                interval,
+            },
+        );
+    }
+
+    /// Time until the pool's next check unless it gets busy first
+    pub fn interval(&self, function_key: &str) -> Duration {
+        self.pools
+            .get(function_key)
+            .map_or(self.base, |state| state.interval)
+    }
+
+    /// Forget the pools that no longer exist
+    pub fn retain(&mut self, mut exists: impl FnMut(&str) -> bool) {
+        self.pools.retain(|function_key, _| exists(function_key));
+    }
+}
+
+/// Counters of the scaling passes since startup
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    passes: AtomicU64,
+    pool_checks: AtomicU64,
+    overruns: AtomicU64,
+    last_pass_ms: AtomicU64,
+}
+
+/// [`ScanStats`] as reported by the health endpoints
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ScanStatsSnapshot {
+    /// Scaling passes run
+    pub passes: u64,
+    /// Pools checked, i.e. whose metrics were read
+    pub pool_checks: u64,
+    /// Pool checks given up because they overran the pool check timeout; the pool is
+    /// checked again on the next pass
+    pub overruns: u64,
+    /// How long the latest pass took, in milliseconds
+    pub last_pass_ms: u64,
+}
+
+impl ScanStats {
+    pub fn record_pass(&self, took: Duration) {
+        self.passes.fetch_add(1, Ordering::Relaxed);
+        self.last_pass_ms
+            .store(took.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_check(&self) {
+        self.pool_checks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_overrun(&self) {
+        self.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ScanStatsSnapshot {
+        ScanStatsSnapshot {
+            passes: self.passes.load(Ordering::Relaxed),
+            pool_checks: self.pool_checks.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            last_pass_ms: self.last_pass_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_secs(1);
+    const IDLE: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_idle_pool_backs_off_to_idle_interval() {
+        let mut schedule = PollSchedule::new(BASE, IDLE);
+        let start = Instant::now();
+        assert!(schedule.is_due("fn", false, start));
+
+        let mut now = start;
+        for expected in [1, 2, 4, 8, 16, 30, 30] {
+            schedule.checked("fn", false, None, now);
+            assert_eq!(schedule.interval("fn"), Duration::from_secs(expected));
+            assert!(!schedule.is_due("fn", false, now + Duration::from_millis(500)));
+            now += schedule.interval("fn");
+            assert!(schedule.is_due("fn", false, now));
+        }
+    }
+
+    #[test]
+    fn test_traffic_resets_interval() {
+        let mut schedule = PollSchedule::new(BASE, IDLE);
+        let now = Instant::now();
+        for _ in 0..5 {
+            schedule.checked("fn", false, None, now);
+        }
+        assert_eq!(schedule.interval("fn"), Duration::from_secs(16));
+
+        // Traffic makes the pool due before its interval elapsed
+        assert!(schedule.is_due("fn", true, now + BASE));
+        schedule.checked("fn", true, None, now + BASE);
+        assert_eq!(schedule.interval("fn"), BASE);
+    }
+
+    #[test]
+    fn test_pool_idle_interval_overrides_default() {
+        let mut schedule = PollSchedule::new(BASE, IDLE);
+        let now = Instant::now();
+        for _ in 0..10 {
+            schedule.checked("fn", false, Some(Duration::from_secs(5)), now);
+            schedule.checked("batch", false, Some(Duration::from_secs(300)), now);
+        }
+        assert_eq!(schedule.interval("fn"), Duration::from_secs(5));
+        assert_eq!(schedule.interval("batch"), Duration::from_secs(300));
+
+        schedule.retain(|function_key| function_key == "fn");
+        assert_eq!(schedule.last_checked("batch"), None);
+        assert!(schedule.last_checked("fn").is_some());
+    }
+}
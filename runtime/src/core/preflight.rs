@@ -0,0 +1,38 @@
+use crate::core::environment::connect_docker;
+use bollard::network::InspectNetworkOptions;
+use bollard::Docker;
+
+/// Connect to the Docker daemon (`DOCKER_HOST`, or a mounted socket) and check that it responds.
+///
+/// Returns the daemon version on success.
+pub async fn check_docker() -> Result<(Docker, String), String> {
+    let docker = connect_docker().map_err(|e| format!("Failed to create Docker client: {}", e))?;
+
+    docker
+        .ping()
+        .await
+        .map_err(|e| format!("Docker daemon is unreachable: {}", e))?;
+
+    let version = docker
+        .version()
+        .await
+        .ok()
+        .and_then(|v| v.version)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    Ok((docker, version))
+}
+
+/// Check that the network function containers are attached to exists
+pub async fn check_network(docker: &Docker, network: &str) -> Result<(), String> {
+    docker
+        .inspect_network(network, None::<InspectNetworkOptions<String>>)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            format!(
+                "Docker network '{}' not found ({}); run `docker network ls` to find the right name",
+                network, e
+            )
+        })
+}
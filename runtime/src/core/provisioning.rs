@@ -1,9 +1,17 @@
+use crate::core::environment::connect_docker;
+use crate::core::platform::{build_targets, daemon_platform, Platform};
 use crate::shared::error::{AppResult, RuntimeError};
+use bollard::auth::DockerCredentials;
 use bollard::errors::Error as BollardError;
-use bollard::image::BuildImageOptions;
+use bollard::image::{
+    BuildImageOptions, CreateImageOptions, ListImagesOptions, PushImageOptions, RemoveImageOptions,
+    TagImageOptions,
+};
+use bollard::models::{CreateImageInfo, ImageInspect, PushImageInfo};
 use bollard::Docker;
 use futures_util::StreamExt;
 use shared_utils;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -18,7 +26,7 @@ use tar::Builder as TarBuilder;
 /// # Returns
 /// * On success, returns `Body` where `Body` is the tar'd build context,
 /// * On failure, returns an `AppError`.
-fn create_build_context(path: &Path, dockerfile_content: &str) -> AppResult<Vec<u8>> {
+pub fn create_build_context(path: &Path, dockerfile_content: &str) -> AppResult<Vec<u8>> {
     // Write the Dockerfile content into that directory.
     let dockerfile_path = path.join("Dockerfile");
     {
@@ -48,27 +56,132 @@ fn create_build_context(path: &Path, dockerfile_content: &str) -> AppResult<Vec<
 
 /// Builds a Docker image from the given Dockerfile content using Bollard.
 ///
+/// The image is always built for the daemon's own platform and tagged `runner_type`, so
+/// the daemon can run it. Every other platform in `platforms` gets a build of its own,
+/// tagged `runner_type:<os>-<arch>` (the native build is tagged that way too). Builds for
+/// foreign platforms run under emulation, which needs QEMU registered with binfmt_misc on
+/// the daemon host.
+///
 /// # Arguments
 /// * `runner_type`        - The Docker image name/tag (e.g., "python-runner").
 /// * `dockerfile_content` - The Dockerfile contents as a string.
+/// * `platforms`          - Extra platforms to build the image for.
 ///
 /// # Returns
-/// * `Ok(())` if the image build succeeds.
+/// * `Ok(())` if every build succeeds.
 /// * `AppError` if there's a problem connecting to Docker or building the image.
 pub async fn provisioning(
     path: &Path,
     runner_type: &str,
     dockerfile_content: &str,
+    platforms: &[Platform],
 ) -> AppResult<()> {
-    let docker = Docker::connect_with_http_defaults()
-        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
-
     // Create the build context as a tar archive (in memory).
     let build_context = create_build_context(path, dockerfile_content)?;
+    build_from_context(build_context, runner_type, platforms, &HashMap::new()).await?;
+
+    println!("Environment provisioned (Docker image built successfully).");
+    Ok(())
+}
+
+/// Builds an image from a tar'd build context holding a `Dockerfile`, the way
+/// [`provisioning`] does.
+///
+/// `build_args` are passed to every build next to the platform ones. They only reach the
+/// stages that declare them with `ARG`, and aren't kept in the image unless a stage
+/// copies them into its environment.
+///
+/// # Returns
+/// * The platforms the image was built for, the daemon's own first.
+pub async fn build_from_context(
+    build_context: Vec<u8>,
+    runner_type: &str,
+    platforms: &[Platform],
+    build_args: &HashMap<String, String>,
+) -> AppResult<Vec<Platform>> {
+    let mut log = Vec::new();
+    build_from_context_with_log(build_context, runner_type, platforms, build_args, &mut log).await
+}
 
+/// Like [`build_from_context`], also collecting what the build steps print into `log`,
+/// chunk by chunk as the daemon streams it. The log is kept whether or not the build
+/// succeeds, so callers can find out why a step failed.
+pub async fn build_from_context_with_log(
+    build_context: Vec<u8>,
+    runner_type: &str,
+    platforms: &[Platform],
+    build_args: &HashMap<String, String>,
+    log: &mut Vec<String>,
+) -> AppResult<Vec<Platform>> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    let native = daemon_platform(&docker).await?;
+
+    let targets = build_targets(&native, platforms);
+    for platform in &targets {
+        if *platform == native {
+            build_image(
+                &docker,
+                runner_type,
+                None,
+                &native,
+                build_args,
+                build_context.clone(),
+                log,
+            )
+            .await?;
+            let options = TagImageOptions {
+                repo: runner_type.to_string(),
+                tag: native.tag(),
+            };
+            docker
+                .tag_image(runner_type, Some(options))
+                .await
+                .map_err(|e| RuntimeError::Exec(format!("Failed to tag image: {e}")))?;
+        } else {
+            let tag = format!("{runner_type}:{}", platform.tag());
+            build_image(
+                &docker,
+                &tag,
+                Some(platform),
+                &native,
+                build_args,
+                build_context.clone(),
+                log,
+            )
+            .await?;
+        }
+    }
+    Ok(targets)
+}
+
+/// Builds one image; `platform` is `None` for the daemon's own platform
+async fn build_image(
+    docker: &Docker,
+    tag: &str,
+    platform: Option<&Platform>,
+    native: &Platform,
+    extra_build_args: &HashMap<String, String>,
+    build_context: Vec<u8>,
+    log: &mut Vec<String>,
+) -> AppResult<()> {
+    let target = platform.unwrap_or(native);
+    let build_args = target.build_args(native);
+    let platform_name = platform.map(Platform::to_string).unwrap_or_default();
     let build_options = BuildImageOptions {
-        t: runner_type,
+        t: tag,
         rm: true, // remove intermediate containers on success
+        platform: &platform_name,
+        // The platform args win over extra ones of the same name
+        buildargs: extra_build_args
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(
+                build_args
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_str())),
+            )
+            .collect(),
         ..Default::default()
     };
 
@@ -80,20 +193,440 @@ pub async fn provisioning(
             Ok(build_info) => {
                 // Bollard returns JSON about each build step.
                 println!("Status: {:?}", build_info.status);
+                // What the step printed, e.g. the output of a `RUN`
+                if let Some(output) = build_info.stream {
+                    print!("{output}");
+                    log.push(output);
+                }
             }
             Err(BollardError::DockerResponseServerError { message, .. }) => {
-                return Err(RuntimeError::Exec(format!("Docker build error: {message}")));
+                return Err(RuntimeError::Invalid(format!(
+                    "Docker build error ({target}): {message}"
+                )));
             }
             Err(e) => {
                 return Err(RuntimeError::Exec(format!("Build stream error: {e}")));
             }
         }
     }
+    Ok(())
+}
 
-    println!("Environment provisioned (Docker image built successfully).");
+/// Credentials of a container registry
+#[derive(Debug, Clone, Default)]
+pub struct RegistryAuth {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl RegistryAuth {
+    fn credentials(&self, repository: &str) -> Option<DockerCredentials> {
+        self.username.as_ref()?;
+        Some(DockerCredentials {
+            username: self.username.clone(),
+            password: self.password.clone(),
+            serveraddress: repository.split('/').next().map(str::to_string),
+            ..Default::default()
+        })
+    }
+}
+
+/// Tag of the manifest list of an image's per-platform builds in the registry
+pub const MANIFEST_LIST_TAG: &str = "latest";
+
+/// A per-platform build pushed by [`push_image`], as the registry holds it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushedImage {
+    pub platform: Platform,
+    /// e.g. `registry.example.com/invok/app:linux-arm64`
+    pub reference: String,
+    /// Media type of the build's manifest
+    pub media_type: String,
+    /// Digest of the build's manifest, e.g. `sha256:...`
+    pub digest: String,
+    /// Size of the build's manifest in bytes
+    pub size: i64,
+}
+
+/// Pushes the per-platform builds of an image to a registry.
+///
+/// Each build `image:<os>-<arch>` is pushed as `repository/image:<os>-<arch>`. The
+/// manifest list tying them together as `repository/image:latest` is pushed by the
+/// caller from the returned manifests, since the Docker API can't push one.
+///
+/// # Arguments
+/// * `image`      - The local image name, as given to [`build_from_context`].
+/// * `platforms`  - The platforms the image was built for.
+/// * `repository` - Registry and path to push to, e.g. `registry.example.com/invok`.
+/// * `auth`       - Credentials of the registry.
+///
+/// # Returns
+/// * The builds pushed, with their manifests.
+pub async fn push_image(
+    image: &str,
+    platforms: &[Platform],
+    repository: &str,
+    auth: &RegistryAuth,
+) -> AppResult<Vec<PushedImage>> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    let remote = remote_repository(image, repository);
+
+    let mut pushed = Vec::new();
+    for platform in platforms {
+        let tag = platform.tag();
+        docker
+            .tag_image(
+                &format!("{image}:{tag}"),
+                Some(TagImageOptions {
+                    repo: remote.as_str(),
+                    tag: tag.as_str(),
+                }),
+            )
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to tag image: {e}")))?;
+
+        let mut push_stream = docker.push_image(
+            &remote,
+            Some(PushImageOptions { tag: tag.as_str() }),
+            auth.credentials(repository),
+        );
+        while let Some(push_info) = push_stream.next().await {
+            match push_info {
+                Ok(PushImageInfo {
+                    error: Some(error), ..
+                }) => return Err(RuntimeError::Exec(format!("Docker push error: {error}"))),
+                Ok(_) => {}
+                Err(e) => return Err(RuntimeError::Exec(format!("Docker push error: {e}"))),
+            }
+        }
+
+        let reference = format!("{remote}:{tag}");
+        let descriptor = docker
+            .inspect_registry_image(&reference, auth.credentials(repository))
+            .await
+            .map_err(|e| RuntimeError::Exec(format!("Failed to inspect '{reference}': {e}")))?
+            .descriptor;
+        let (Some(media_type), Some(digest), Some(size)) =
+            (descriptor.media_type, descriptor.digest, descriptor.size)
+        else {
+            return Err(RuntimeError::Exec(format!(
+                "The registry did not describe the manifest of '{reference}'"
+            )));
+        };
+        pushed.push(PushedImage {
+            platform: platform.clone(),
+            reference,
+            media_type,
+            digest,
+            size,
+        });
+    }
+    Ok(pushed)
+}
+
+/// The repository [`push_image`] pushes `image` to under `repository`, e.g.
+/// `registry.example.com/invok/<image>`
+pub fn remote_repository(image: &str, repository: &str) -> String {
+    format!("{}/{image}", repository.trim_end_matches('/'))
+}
+
+/// Pulls the variant of an image pushed by [`push_image`] that matches the daemon's
+/// platform, through its manifest list, and tags it as the local image `image`.
+///
+/// # Arguments
+/// * `image`      - The local image name.
+/// * `repository` - Registry and path the image was pushed to.
+/// * `auth`       - Credentials of the registry.
+pub async fn pull_image(image: &str, repository: &str, auth: &RegistryAuth) -> AppResult<()> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    let native = daemon_platform(&docker).await?;
+    let reference = format!(
+        "{}:{MANIFEST_LIST_TAG}",
+        remote_repository(image, repository)
+    );
+
+    pull(&docker, &reference, &native, auth.credentials(repository)).await?;
+
+    docker
+        .tag_image(
+            &reference,
+            Some(TagImageOptions {
+                repo: image,
+                tag: "latest",
+            }),
+        )
+        .await
+        .map_err(|e| RuntimeError::Exec(format!("Failed to tag image: {e}")))?;
+    Ok(())
+}
+
+/// What an image built outside the platform declares, for the controller to check it
+/// can run as a function
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageDetails {
+    /// Content-addressed references of the image, e.g. `registry/app@sha256:...`
+    pub digests: Vec<String>,
+    /// Ports it exposes, e.g. `8080/tcp`, sorted
+    pub exposed_ports: Vec<String>,
+    /// Its `HEALTHCHECK` command; `None` when it declares none or disables it
+    pub healthcheck: Option<Vec<String>>,
+    /// Whether it has an entrypoint or command to start containers with
+    pub runs_command: bool,
+}
+
+impl From<ImageInspect> for ImageDetails {
+    fn from(inspect: ImageInspect) -> Self {
+        let config = inspect.config.unwrap_or_default();
+        let mut exposed_ports: Vec<String> = config
+            .exposed_ports
+            .unwrap_or_default()
+            .into_keys()
+            .collect();
+        exposed_ports.sort();
+        // `["NONE"]` disables a healthcheck inherited from the base image
+        let healthcheck = config
+            .healthcheck
+            .and_then(|healthcheck| healthcheck.test)
+            .filter(|test| test.first().is_some_and(|kind| kind != "NONE"));
+        let has_command =
+            |command: Option<Vec<String>>| command.is_some_and(|command| !command.is_empty());
+        Self {
+            digests: inspect.repo_digests.unwrap_or_default(),
+            exposed_ports,
+            healthcheck,
+            runs_command: has_command(config.entrypoint) || has_command(config.cmd),
+        }
+    }
+}
+
+/// Pulls an image built outside the platform, e.g. by a function's own CI, in the
+/// variant for the daemon's platform.
+///
+/// # Arguments
+/// * `reference` - The image to pull, e.g. `registry.example.com/app:1.4`.
+/// * `auth`      - Credentials of its registry.
+///
+/// # Returns
+/// * What the pulled image declares.
+pub async fn pull_external_image(reference: &str, auth: &RegistryAuth) -> AppResult<ImageDetails> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    let native = daemon_platform(&docker).await?;
+    pull(&docker, reference, &native, auth.credentials(reference)).await?;
+
+    let inspect = docker
+        .inspect_image(reference)
+        .await
+        .map_err(|e| RuntimeError::Exec(format!("Failed to inspect {reference}: {e}")))?;
+    Ok(inspect.into())
+}
+
+/// Reference to pull a Docker Hub image by from a pull-through registry mirror, e.g.
+/// `mirror.internal:5000/library/golang:1.23` for `golang:1.23`.
+///
+/// Returns `None` for images of other registries and images pinned by digest, which
+/// are pulled as they are.
+pub fn mirrored_reference(image: &str, mirror: &str) -> Option<String> {
+    if image.contains('@') {
+        return None;
+    }
+    let path = match image.split_once('/') {
+        Some((host, _)) if host.contains(['.', ':']) || host == "localhost" => return None,
+        Some(_) => image.to_string(),
+        // Official images live under `library/`
+        None => format!("library/{image}"),
+    };
+    Some(format!("{}/{path}", mirror.trim_end_matches('/')))
+}
+
+/// Pulls an image ahead of the builds needing it, for the daemon's platform.
+///
+/// With a `mirror`, Docker Hub images are pulled from it (see [`mirrored_reference`])
+/// and tagged with their own name, which Dockerfiles refer to them by.
+///
+/// # Arguments
+/// * `image`  - The image, e.g. `golang:1.23`.
+/// * `mirror` - Pull-through registry mirror of Docker Hub, e.g. `mirror.internal:5000`.
+pub async fn prepull_image(image: &str, mirror: Option<&str>) -> AppResult<()> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    let native = daemon_platform(&docker).await?;
+
+    let Some(reference) = mirror.and_then(|mirror| mirrored_reference(image, mirror)) else {
+        return pull(&docker, image, &native, None).await;
+    };
+    pull(&docker, &reference, &native, None).await?;
+    let (repo, tag) = match image.rsplit_once(':') {
+        Some((repo, tag)) if !tag.contains('/') => (repo, tag),
+        _ => (image, "latest"),
+    };
+    docker
+        .tag_image(&reference, Some(TagImageOptions { repo, tag }))
+        .await
+        .map_err(|e| RuntimeError::Exec(format!("Failed to tag image: {e}")))?;
     Ok(())
 }
 
+/// Pulls `reference` for `platform`, failing on the first error the daemon reports
+async fn pull(
+    docker: &Docker,
+    reference: &str,
+    platform: &Platform,
+    credentials: Option<DockerCredentials>,
+) -> AppResult<()> {
+    let platform = platform.to_string();
+    let options = CreateImageOptions {
+        from_image: reference,
+        platform: platform.as_str(),
+        ..Default::default()
+    };
+    let mut pull_stream = docker.create_image(Some(options), None, credentials);
+    while let Some(pull_info) = pull_stream.next().await {
+        match pull_info {
+            Ok(CreateImageInfo {
+                error: Some(error), ..
+            }) => return Err(RuntimeError::Exec(format!("Docker pull error: {error}"))),
+            Ok(_) => {}
+            Err(e) => {
+                return Err(RuntimeError::Exec(format!(
+                    "Failed to pull {reference}: {e}"
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes a function's Docker image, along with its builds for other platforms. An
+/// image that doesn't exist is not an error.
+///
+/// # Arguments
+/// * `image` - The Docker image name/tag.
+///
+/// # Returns
+/// * `Ok(())` once the image is gone.
+/// * `AppError` if Docker refuses to remove it.
+pub async fn remove_image(image: &str) -> AppResult<()> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+
+    // Every tag of the repository: the native build and the per-platform ones
+    let mut filters = HashMap::new();
+    filters.insert("reference", vec![image]);
+    let mut tags: Vec<String> = docker
+        .list_images(Some(ListImagesOptions {
+            filters,
+            ..Default::default()
+        }))
+        .await
+        .map_err(|e| RuntimeError::Exec(format!("Failed to list images: {e}")))?
+        .into_iter()
+        .flat_map(|summary| summary.repo_tags)
+        .filter(|tag| is_tag_of(tag, image))
+        .collect();
+    if tags.is_empty() {
+        tags.push(image.to_string());
+    }
+
+    for tag in tags {
+        let options = RemoveImageOptions {
+            force: true,
+            ..Default::default()
+        };
+        match docker.remove_image(&tag, Some(options), None).await {
+            Ok(_) => {}
+            Err(BollardError::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            Err(e) => return Err(RuntimeError::Exec(format!("Failed to remove image: {e}"))),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `tag`, e.g. `registry:5000/invok/app:linux-amd64`, is a tag of the
+/// repository `image`. The registry's port is not taken for the tag.
+fn is_tag_of(tag: &str, image: &str) -> bool {
+    tag.rsplit_once(':').is_some_and(|(repo, _)| repo == image)
+}
+
+/// Tag [`keep_image`] sets the current build of an image aside under
+const PREVIOUS_TAG: &str = "previous";
+
+/// Sets aside the current build of a function's image before it is rebuilt, so
+/// [`restore_image`] can bring it back if the new build is not used.
+///
+/// # Arguments
+/// * `image` - The Docker image name.
+///
+/// # Returns
+/// * `Ok(true)` once the build is set aside, `Ok(false)` when there is none.
+/// * `AppError` if Docker refuses to tag it.
+pub async fn keep_image(image: &str) -> AppResult<bool> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    match docker
+        .tag_image(
+            image,
+            Some(TagImageOptions {
+                repo: image,
+                tag: PREVIOUS_TAG,
+            }),
+        )
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(BollardError::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(false),
+        Err(e) => Err(RuntimeError::Exec(format!("Failed to tag image: {e}"))),
+    }
+}
+
+/// Makes the build [`keep_image`] set aside the function's image again.
+///
+/// # Arguments
+/// * `image` - The Docker image name.
+pub async fn restore_image(image: &str) -> AppResult<()> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    docker
+        .tag_image(
+            &format!("{image}:{PREVIOUS_TAG}"),
+            Some(TagImageOptions {
+                repo: image,
+                tag: "latest",
+            }),
+        )
+        .await
+        .map_err(|e| RuntimeError::Exec(format!("Failed to tag image: {e}")))?;
+    release_image(image).await
+}
+
+/// Drops the build [`keep_image`] set aside. It is deleted unless another tag or a
+/// container still uses it.
+///
+/// # Arguments
+/// * `image` - The Docker image name.
+pub async fn release_image(image: &str) -> AppResult<()> {
+    let docker = connect_docker()
+        .map_err(|e| RuntimeError::System(format!("Unable to connect to Docker: {e}")))?;
+    match docker
+        .remove_image(&format!("{image}:{PREVIOUS_TAG}"), None, None)
+        .await
+    {
+        Ok(_) => Ok(()),
+        // Gone already, or still used by a container: the next deploy's tag replaces it
+        Err(BollardError::DockerResponseServerError {
+            status_code: 404 | 409,
+            ..
+        }) => Ok(()),
+        Err(e) => Err(RuntimeError::Exec(format!("Failed to remove image: {e}"))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,7 +646,76 @@ mod tests {
         "###;
 
         let temp_dir = tempfile::tempdir().unwrap().into_path();
-        let result = provisioning(&temp_dir, "test-runner", dockerfile_content).await;
+        let result = provisioning(&temp_dir, "test-runner", dockerfile_content, &[]).await;
         assert!(result.is_ok(), "Expected provisioning to succeed");
     }
+
+    #[test]
+    fn test_is_tag_of() {
+        assert!(is_tag_of("app:latest", "app"));
+        assert!(is_tag_of("app:linux-arm64", "app"));
+        assert!(!is_tag_of("app2:latest", "app"));
+        assert!(!is_tag_of("app", "app"));
+
+        let remote = remote_repository("app", "registry.internal:5000/invok/");
+        assert_eq!(remote, "registry.internal:5000/invok/app");
+        assert!(is_tag_of(
+            "registry.internal:5000/invok/app:linux-amd64",
+            &remote
+        ));
+        assert!(!is_tag_of(
+            "registry.internal:5000/invok/app:linux-amd64",
+            "app"
+        ));
+    }
+
+    #[test]
+    fn test_mirrored_reference() {
+        let mirror = "mirror.internal:5000/";
+        assert_eq!(
+            mirrored_reference("golang:1.23", mirror).as_deref(),
+            Some("mirror.internal:5000/library/golang:1.23")
+        );
+        assert_eq!(
+            mirrored_reference("acme/tool:1", mirror).as_deref(),
+            Some("mirror.internal:5000/acme/tool:1")
+        );
+        assert_eq!(
+            mirrored_reference("gcr.io/distroless/static-debian12", mirror),
+            None
+        );
+        assert_eq!(mirrored_reference("localhost/app:1", mirror), None);
+        assert_eq!(mirrored_reference("node@sha256:abc", mirror), None);
+    }
+
+    #[test]
+    fn test_image_details() {
+        // As the daemon answers `GET /images/{name}/json`
+        let inspect: ImageInspect = serde_json::from_value(serde_json::json!({
+            "RepoDigests": ["registry.example.com/app@sha256:abc"],
+            "Config": {
+                "ExposedPorts": { "9090/tcp": {}, "8080/tcp": {} },
+                "Healthcheck": { "Test": ["CMD", "/healthz"] },
+                "Cmd": ["./main"]
+            }
+        }))
+        .unwrap();
+        let details = ImageDetails::from(inspect);
+        assert_eq!(details.exposed_ports, vec!["8080/tcp", "9090/tcp"]);
+        assert_eq!(
+            details.healthcheck,
+            Some(vec!["CMD".to_string(), "/healthz".to_string()])
+        );
+        assert!(details.runs_command);
+        assert_eq!(details.digests.len(), 1);
+
+        let disabled: ImageInspect = serde_json::from_value(serde_json::json!({
+            "Config": { "Healthcheck": { "Test": ["NONE"] } }
+        }))
+        .unwrap();
+        let details = ImageDetails::from(disabled);
+        assert_eq!(details.healthcheck, None);
+        assert!(details.exposed_ports.is_empty());
+        assert!(!details.runs_command);
+    }
 }
@@ -0,0 +1,348 @@
+use crate::core::container_manager::{affinity_weight, ContainerAddress, ContainerStatus};
+use crate::core::persistence::{PersistedContainerInfo, PersistedPoolState};
+use crate::core::policy::FunctionPolicy;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How long a follower routes from a pool state it read before reading it again
+const ROUTES_TTL: Duration = Duration::from_secs(1);
+
+/// How long a pool state a follower read still says whether the function is warm
+const WARM_TTL: Duration = Duration::from_secs(30);
+
+/// How often a follower tells the leader a container is in use, at most
+const ACTIVITY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Containers whose last report is remembered before the old ones are forgotten
+const MAX_REPORTED: usize = 4096;
+
+/// Controllers sharing one Docker host and Redis, any of which serves invocations
+#[derive(Debug, Clone)]
+pub struct ReplicaConfig {
+    /// Identifies this controller in the leader lease
+    pub replica_id: String,
+    /// How long the leader holds the lease without renewing it; it renews every third
+    pub lease_ttl: Duration,
+}
+
+/// What followers ask of the scaling leader, which alone starts, stops and scales
+/// containers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum PoolCommand {
+    /// An invocation found no container to route to
+    ScaleUp {
+        function_key: String,
+        #[serde(default)]
+        policy: Option<FunctionPolicy>,
+    },
+    SetPolicy {
+        function_key: String,
+        policy: FunctionPolicy,
+    },
+    RemoveFunction {
+        function_key: String,
+    },
+    DrainNamespace {
+        namespace: String,
+    },
+}
+
+/// This controller's part among the replicas, and the pool states it routes from while
+/// it follows
+#[derive(Debug)]
+pub struct Replicas {
+    config: ReplicaConfig,
+    leader: watch::Sender<bool>,
+    /// Pool states read from Redis, by function key
+    routes: DashMap<String, (Instant, Arc<PersistedPoolState>)>,
+    /// When activity of each container was last reported to the leader
+    reported: DashMap<String, Instant>,
+    /// Spreads invocations without an affinity key over the containers
+    turn: AtomicUsize,
+}
+
+impl Replicas {
+    pub fn new(config: ReplicaConfig) -> Self {
+        Self {
+            config,
+            leader: watch::channel(false).0,
+            routes: DashMap::new(),
+            reported: DashMap::new(),
+            turn: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn replica_id(&self) -> &str {
+        &self.config.replica_id
+    }
+
+    pub fn lease_ttl(&self) -> Duration {
+        self.config.lease_ttl
+    }
+
+    /// Whether this controller holds the leader lease and runs the pools
+    pub fn is_leader(&self) -> bool {
+        *self.leader.borrow()
+    }
+
+    pub(crate) fn set_leader(&self, leader: bool) {
+        self.leader.send_replace(leader);
+        self.routes.clear();
+    }
+
+    /// Follows whether this controller leads
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.leader.subscribe()
+    }
+
+    /// The pool state of a function read less than [`ROUTES_TTL`] ago
+    pub(crate) fn cached_state(&self, function_key: &str) -> Option<Arc<PersistedPoolState>> {
+        self.routes
+            .get(function_key)
+            .filter(|entry| entry.0.elapsed() < ROUTES_TTL)
+            .map(|entry| entry.1.clone())
+    }
+
+    pub(crate) fn cache_state(&self, function_key: &str, state: Arc<PersistedPoolState>) {
+        self.routes
+            .insert(function_key.to_string(), (Instant::now(), state));
+    }
+
+    pub(crate) fn forget_state(&self, function_key: &str) {
+        self.routes.remove(function_key);
+    }
+
+    /// Whether a recently read pool state of the function has a container to route to
+    pub fn is_warm(&self, function_key: &str) -> bool {
+        self.routes.get(function_key).is_some_and(|entry| {
+            entry.0.elapsed() < WARM_TTL && routable(&entry.1).next().is_some()
+        })
+    }
+
+    /// Picks the next container to route to from a pool state, see [`route_from_state`]
+    pub(crate) fn route(
+        &self,
+        state: &PersistedPoolState,
+        affinity: Option<&str>,
+    ) -> Option<ContainerAddress> {
+        let turn = self.turn.fetch_add(1, Ordering::Relaxed);
+        route_from_state(state, affinity, turn)
+    }
+
+    /// Whether the use of a container should be reported to the leader now, i.e. it
+    /// wasn't in the last [`ACTIVITY_INTERVAL`]
+    pub(crate) fn should_report(&self, container_id: &str) -> bool {
+        let recent = self
+            .reported
+            .get(container_id)
+            .is_some_and(|reported| reported.elapsed() < ACTIVITY_INTERVAL);
+        if recent {
+            return false;
+        }
+        if self.reported.len() > MAX_REPORTED {
+            self.reported
+                .retain(|_, reported| reported.elapsed() < ACTIVITY_INTERVAL);
+        }
+        self.reported
+            .insert(container_id.to_string(), Instant::now());
+        true
+    }
+}
+
+/// Containers of a pool state invocations may go to, the ones the leader would route to
+/// itself: healthy or recently idle and neither paused nor retiring, or overloaded ones
+/// when there is nothing else
+fn routable(state: &PersistedPoolState) -> impl Iterator<Item = &PersistedContainerInfo> {
+    let cooldown = state.config.cooldown_duration;
+    let eligible = move |container: &&PersistedContainerInfo| {
+        container.status == ContainerStatus::Healthy
+            || container
+                .to_container_info()
+                .is_within_safe_window(cooldown)
+    };
+    let running = || {
+        state
+            .containers
+            .iter()
+            .filter(|container| !container.paused && !container.retiring)
+    };
+    let available = running().any(|container| eligible(&container));
+    running().filter(move |container| {
+        if available {
+            eligible(container)
+        } else {
+            container.status == ContainerStatus::Overloaded
+        }
+    })
+}
+
+/// Picks the container an invocation goes to from a pool state the leader published.
+///
+/// The pick only depends on the state, the affinity key and `turn`: replicas reading the
+/// same state send an affinity key to the same container, like the leader itself does,
+/// and spread the rest over the same containers in turn.
+pub fn route_from_state(
+    state: &PersistedPoolState,
+    affinity: Option<&str>,
+    turn: usize,
+) -> Option<ContainerAddress> {
+    let mut candidates: Vec<_> = routable(state).collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    // Every replica sees the containers in the same order
+    candidates.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let container = match affinity {
+        Some(key) => candidates
+            .iter()
+            .copied()
+            .max_by_key(|container| affinity_weight(key, &container.id))?,
+        None => candidates[turn % candidates.len()],
+    };
+    Some(container.to_container_info().address())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::container_manager::MonitoringConfig;
+    use crate::core::usage::ResourceUsage;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn container(id: &str, status: ContainerStatus, paused: bool) -> PersistedContainerInfo {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        PersistedContainerInfo {
+            id: id.to_string(),
+            name: format!("fn-{}", id),
+            container_port: 8080,
+            ip_address: Some(format!("172.18.0.{}", id.len())),
+            published: None,
+            last_active_unix: now,
+            idle_since_unix: (status == ContainerStatus::Idle).then_some(now),
+            status,
+            paused,
+            started_unix: Some(now),
+            requests: 0,
+            retiring: false,
+        }
+    }
+
+    fn state(containers: Vec<PersistedContainerInfo>) -> PersistedPoolState {
+        PersistedPoolState {
+            function_name: "fn-abc".to_string(),
+            containers,
+            min_containers: 1,
+            max_containers: 5,
+            config: MonitoringConfig::default(),
+            last_updated: 0,
+            usage: ResourceUsage::default(),
+            policy: FunctionPolicy::default(),
+            version: 1,
+        }
+    }
+
+    fn replica(id: &str) -> Replicas {
+        Replicas::new(ReplicaConfig {
+            replica_id: id.to_string(),
+            lease_ttl: Duration::from_secs(15),
+        })
+    }
+
+    #[test]
+    fn test_replicas_route_to_the_same_containers() {
+        let shared = state(vec![
+            container("c", ContainerStatus::Healthy, false),
+            container("a", ContainerStatus::Idle, false),
+            container("b", ContainerStatus::Healthy, false),
+        ]);
+        // The same containers, listed in another order
+        let mut reordered = shared.clone();
+        reordered.containers.reverse();
+        let (first, second) = (replica("first"), replica("second"));
+
+        for session in ["alice", "bob", "carol", "dave"] {
+            let a = first.route(&shared, Some(session)).unwrap();
+            let b = second.route(&reordered, Some(session)).unwrap();
+            assert_eq!(a.container_id, b.container_id, "session {}", session);
+        }
+
+        let mut routed: Vec<String> = (0..6)
+            .map(|_| first.route(&shared, None).unwrap().container_id)
+            .collect();
+        routed.sort();
+        routed.dedup();
+        assert_eq!(routed, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_route_from_state_skips_paused_and_overloaded() {
+        let shared = state(vec![
+            container("a", ContainerStatus::Healthy, true),
+            container("b", ContainerStatus::Overloaded, false),
+            container("c", ContainerStatus::Healthy, false),
+        ]);
+        for turn in 0..4 {
+            let routed = route_from_state(&shared, None, turn).unwrap();
+            assert_eq!(routed.container_id, "c");
+            assert_eq!(routed.host, "172.18.0.1");
+        }
+
+        // Overloaded containers still answer when nothing else can
+        let overloaded = state(vec![
+            container("a", ContainerStatus::Healthy, true),
+            container("b", ContainerStatus::Overloaded, false),
+        ]);
+        assert_eq!(
+            route_from_state(&overloaded, None, 0).unwrap().container_id,
+            "b"
+        );
+
+        let paused = state(vec![container("a", ContainerStatus::Healthy, true)]);
+        assert!(route_from_state(&paused, None, 0).is_none());
+
+        // Containers idle for long are about to be scaled down
+        let mut idle = container("a", ContainerStatus::Idle, false);
+        idle.idle_since_unix = Some(0);
+        assert!(route_from_state(&state(vec![idle]), None, 0).is_none());
+    }
+
+    #[test]
+    fn test_is_warm() {
+        let replicas = replica("follower");
+        assert!(!replicas.is_warm("fn-abc"));
+
+        replicas.cache_state(
+            "fn-abc",
+            Arc::new(state(vec![container("a", ContainerStatus::Idle, false)])),
+        );
+        assert!(replicas.is_warm("fn-abc"));
+        assert!(replicas.cached_state("fn-abc").is_some());
+
+        // Followers route from what the new leader publishes
+        replicas.set_leader(true);
+        assert!(!replicas.is_warm("fn-abc"));
+    }
+
+    #[test]
+    fn test_pool_command_serialization() {
+        let command = PoolCommand::ScaleUp {
+            function_key: "fn-abc".to_string(),
+            policy: None,
+        };
+        let json = serde_json::to_string(&command).unwrap();
+        assert!(json.contains("\"command\":\"scale_up\""));
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            PoolCommand::ScaleUp { function_key, policy: None } if function_key == "fn-abc"
+        ));
+    }
+}
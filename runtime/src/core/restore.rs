@@ -0,0 +1,42 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What the latest restore of pools found, from Redis when the controller starts or takes
+/// the lead, or from a snapshot.
+///
+/// Restored containers are checked before the pools take requests: those that stopped
+/// are removed, and the running ones are probed so a container whose app died inside it
+/// isn't routed to.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    /// Pools adopted with at least one container left
+    pub pools_restored: usize,
+    /// Pools whose state was too old to trust; their containers were removed
+    pub pools_stale: usize,
+    /// Pools none of whose containers was still running
+    pub pools_empty: usize,
+    /// Pools that failed to restore
+    pub pools_failed: usize,
+    /// Containers no longer running, removed
+    pub containers_gone: usize,
+    /// Running containers whose server answered the probe
+    pub containers_ready: usize,
+    /// Running containers whose server didn't answer, marked unhealthy and replaced
+    pub containers_unhealthy: usize,
+    /// Paused containers and those of UDP services, which can't be probed
+    pub containers_unprobed: usize,
+    /// How long the restore took, in milliseconds
+    pub took_ms: u64,
+    /// When it finished, in seconds since the Unix epoch
+    pub finished_at: u64,
+}
+
+impl RestoreReport {
+    /// Record that the restore finished after `took`
+    pub fn finish(&mut self, took: Duration) {
+        self.took_ms = took.as_millis() as u64;
+        self.finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+    }
+}
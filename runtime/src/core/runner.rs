@@ -1,77 +1,142 @@
+use crate::core::diagnostics::{collect_boot_log, BootLog};
+use crate::core::environment::connect_docker;
+use crate::core::platform::daemon_platform;
+use crate::core::sandbox::Sandbox;
 use crate::shared::error::{AppResult, RuntimeError};
 use bollard::container::{
     AttachContainerOptions, AttachContainerResults, Config, CreateContainerOptions,
-    RemoveContainerOptions,
+    InspectContainerOptions, RemoveContainerOptions,
 };
-use bollard::models::{HostConfig, PortBinding, PortMap};
-use bollard::network::ConnectNetworkOptions;
+use bollard::models::{HostConfig, NetworkSettings, PortBinding, PortMap};
 use bollard::Docker;
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::mpsc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::spawn;
 use tokio::sync::oneshot;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, warn};
 
 const BYTES_IN_MB: i64 = 1024 * 1024; // 1 MB in bytes
 const SIZE_256_MB: i64 = 256 * BYTES_IN_MB; // 256 MB in bytes
 const NUM_CPUS: f64 = 2.0;
-const FULL_START_MSG: &str = "<<READY_TO_ACCEPT_CONN>>";
+/// Printed by a function container once it accepts connections
+pub const FULL_START_MSG: &str = "<<READY_TO_ACCEPT_CONN>>";
 const STARTUP_TIMEOUT_S: u64 = 1;
+/// Label attached to every function container, holding the function key
+pub const FUNCTION_LABEL: &str = "invok.function";
+
+/// Memory and CPU limits applied to function containers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    pub memory_bytes: i64,
+    pub cpus: f64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            memory_bytes: SIZE_256_MB,
+            cpus: NUM_CPUS,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ContainerDetails {
-    pub container_id: String,
     pub container_port: u32,
-    pub bind_port: String,
+    /// Host IP to publish the container port on, at a port the daemon picks; `None`
+    /// publishes nothing
+    pub publish_ip: Option<String>,
     pub container_name: String,
-    pub timeout: u64,
     pub docker_compose_network_host: String,
+    /// Extra environment variables, e.g. the egress proxy settings
+    pub env: Vec<String>,
 }
 
-/// Spawns a Docker container with given image and ports, attaches to it,
-/// and sets up a timeout/cleanup mechanism.
+/// A container started by [`runner`]
+#[derive(Debug, Clone)]
+pub struct StartedContainer {
+    pub container_id: String,
+    /// Address of the container on its network
+    pub ip_address: Option<String>,
+    /// Daemon host port the container port is published on, if it was
+    pub host_port: Option<u16>,
+    /// Boot output, captured when the container did not print the readiness marker in time
+    pub boot_log: Option<BootLog>,
+    /// Whether the container exited before it was ready. It is removed once its boot
+    /// output is captured.
+    pub exited: bool,
+}
+
+/// Spawns a Docker container with given image and ports, and attaches to it.
 ///
 /// # Arguments
 ///
 /// * `image_name` - Name of the Docker image to run.
 /// * `container_details` - Details of the Docker container to run.
 ///
-/// * `port_binding` - Port mapping string of the form "HOST_PORT:CONTAINER_PORT".
-/// * `timeout` - Optional duration after which to trigger a timeout. Defaults to 5s.
+/// The container is created directly on `docker_compose_network_host`. The controller
+/// dials it by IP, which works whether the controller shares that network, runs on the
+/// daemon host, or shares the network namespace of a DinD daemon, so usually no host port
+/// needs to be published. With `publish_ip` set, the daemon publishes the port on a free
+/// host port, so none can collide on a shared daemon.
+///
+/// With a `sandbox`, the container is created on the sandbox's setup network instead and
+/// only moved to `docker_compose_network_host` once its network rules are installed, so
+/// the function never runs unconfined. A container that can't be confined is removed.
+///
+/// The container is created for the daemon's own platform, so an image that only exists
+/// for another architecture fails to start with a clear error instead of crashing with
+/// `exec format error`, and a multi-platform image resolves to the right variant.
 ///
 /// # Returns
 ///
-/// * On success, returns the container ID as a `String`.
+/// * On success, returns the started container. If the container never printed the
+///   readiness marker, its boot output is attached so it can be shown to the user. If it
+///   exited instead, it has been removed.
 /// * On error, returns an `AppError`.
 ///
 pub async fn runner(
     docker: Option<Docker>,
     image_name: &str,
     container_details: ContainerDetails,
-) -> AppResult<String> {
-    // Connect to Docker via Unix socket (or named pipe on Windows).
-    let docker = docker.unwrap_or(
-        Docker::connect_with_http_defaults()
+    limits: ResourceLimits,
+    sandbox: Option<&Sandbox>,
+) -> AppResult<StartedContainer> {
+    // Connect to Docker via DOCKER_HOST or the (mounted) Unix socket.
+    let docker = match docker {
+        Some(docker) => docker,
+        None => connect_docker()
             .map_err(|e| RuntimeError::System(format!("Failed to connect to Docker: {e}")))?,
-    );
-
-    let start_time = Instant::now();
+    };
 
-    // Set up port bindings.
-    let mut port_map = PortMap::new();
-    port_map.insert(
-        format!("{}/tcp", container_details.container_port),
-        Some(vec![PortBinding {
-            host_ip: Some("".to_string()),
-            host_port: Some(container_details.bind_port),
-        }]),
-    );
+    // Set up port bindings, if the caller wants the port published.
+    let port_map = container_details.publish_ip.as_ref().map(|publish_ip| {
+        let mut port_map = PortMap::new();
+        port_map.insert(
+            format!("{}/tcp", container_details.container_port),
+            Some(vec![PortBinding {
+                host_ip: Some(publish_ip.clone()),
+                // Picked by the daemon, so containers never compete for a port
+                host_port: None,
+            }]),
+        );
+        port_map
+    });
 
     let mut exposed_ports = HashMap::new();
     exposed_ports.insert("8080/tcp", HashMap::new());
 
-    let (cpu_period, cpu_quota) = cpu_limits(NUM_CPUS);
+    let mut labels = HashMap::new();
+    labels.insert(FUNCTION_LABEL, image_name);
+
+    let network_mode = match sandbox {
+        Some(sandbox) => sandbox.setup_network().await?.to_string(),
+        None => container_details.docker_compose_network_host.clone(),
+    };
+
+    let (cpu_period, cpu_quota) = cpu_limits(limits.cpus);
     // Configure the container.
     let container_config = Config {
         image: Some(image_name),
@@ -79,23 +144,39 @@ pub async fn runner(
         attach_stdout: Some(true),
         attach_stderr: Some(true),
         exposed_ports: Some(exposed_ports),
+        labels: Some(labels),
+        env: (!container_details.env.is_empty())
+            .then(|| container_details.env.iter().map(String::as_str).collect()),
         host_config: Some(HostConfig {
-            memory: Some(SIZE_256_MB),
+            memory: Some(limits.memory_bytes),
             cpu_period: Some(cpu_period),
             cpu_quota: Some(cpu_quota),
-            port_bindings: Some(port_map),
-            auto_remove: Some(true),
+            port_bindings: port_map,
+            // Join the network at creation; connecting afterwards would also leave the
+            // container on the default bridge
+            network_mode: Some(network_mode),
+            // Exited containers are kept until their boot output or crash diagnostics
+            // are collected, and removed afterwards
+            auto_remove: Some(false),
             ..Default::default()
         }),
         ..Default::default()
     };
 
+    let platform = match daemon_platform(&docker).await {
+        Ok(platform) => Some(platform.to_string()),
+        Err(e) => {
+            warn!("Creating container without a platform: {e}");
+            None
+        }
+    };
+
     // Create the container.
     let create_response = docker
         .create_container::<&str, &str>(
             Some(CreateContainerOptions {
                 name: &container_details.container_name,
-                platform: None,
+                platform: platform.as_deref(),
             }),
             container_config,
         )
@@ -103,24 +184,6 @@ pub async fn runner(
         .map_err(|e| RuntimeError::System(format!("Failed to create container: {e}")))?;
     let container_id = create_response.id.clone();
 
-    // connect it to the network (inner compose network)
-    let network_options = ConnectNetworkOptions {
-        container: container_id.clone(),
-        ..Default::default()
-    };
-
-    docker
-        .connect_network(
-            &container_details.docker_compose_network_host,
-            network_options,
-        )
-        .await
-        .map_err(|e| {
-            RuntimeError::System(format!(
-                "Failed to connect the container to the docker compose network: {e}"
-            ))
-        })?;
-
     // Start the container.
     docker
         .start_container::<String>(&container_id, None)
@@ -159,63 +222,121 @@ pub async fn runner(
         }
     });
 
-    if container_details.timeout > 0 {
-        // Spawn a separate task to handle timeout/cleanup.
-        let docker_clone = docker.clone();
-        let container_id_clone = container_id.clone();
-        spawn(async move {
-            let timeout_val = Duration::from_secs(container_details.timeout);
-
-            // Create a channel-based timeout; trigger_timeout() starts the countdown.
-            let (rx, trigger_timeout) = crate::shared::utils::timeout(timeout_val);
-            trigger_timeout();
-
-            match monitor_container_process(&docker_clone, &container_id_clone, rx).await {
-                Ok(_) => {
-                    let elapsed_time = start_time.elapsed();
-                    info!(
-                        "Execution took {:.2} seconds.",
-                        elapsed_time.as_millis() as f64 / 1000.0
-                    );
-                }
-                Err(e) => eprintln!("Failed to monitor child process: {e}"),
-            }
-        });
+    // Attached before confining, so no early output is missed
+    if let Some(sandbox) = sandbox {
+        if let Err(e) = sandbox
+            .confine(
+                &container_id,
+                &container_details.docker_compose_network_host,
+            )
+            .await
+        {
+            let _ = clean_up(&docker, &container_id).await;
+            return Err(e);
+        }
     }
 
-    if let Err(_) = tokio::time::timeout(Duration::from_secs(STARTUP_TIMEOUT_S), rx).await {
-        warn!("Container startup timeout after {STARTUP_TIMEOUT_S} s");
+    let (ip_address, host_port) = container_addresses(
+        &docker,
+        &container_id,
+        &container_details.docker_compose_network_host,
+        container_details.container_port,
+    )
+    .await;
+
+    let (not_ready_reason, exited) =
+        match tokio::time::timeout(Duration::from_secs(STARTUP_TIMEOUT_S), rx).await {
+            Ok(Ok(())) => (None, false),
+            // The output stream ended without the marker: the process exited
+            Ok(Err(_)) => (
+                Some("container exited before it was ready".to_string()),
+                true,
+            ),
+            Err(_) => {
+                warn!("Container startup timeout after {STARTUP_TIMEOUT_S} s");
+                (
+                    Some(format!(
+                        "container did not report ready within {STARTUP_TIMEOUT_S} s"
+                    )),
+                    false,
+                )
+            }
+        };
+
+    let boot_log = match not_ready_reason {
+        Some(reason) => Some(collect_boot_log(&docker, &container_id, &reason).await),
+        None => None,
+    };
+    // No pool takes an exited container, so nothing else would remove it
+    if exited {
+        if let Err(e) = clean_up(&docker, &container_id).await {
+            warn!("Failed to remove exited container {container_id}: {e}");
+        }
     }
 
-    Ok(container_id)
+    Ok(StartedContainer {
+        container_id,
+        ip_address,
+        host_port,
+        boot_log,
+        exited,
+    })
 }
 
-/// Monitors the container process using a timeout channel.
-/// If a message is received, we assume the process completed or timed out,
-/// and then we remove the container.
-///
-/// # Arguments
-///
-/// * `docker` - Reference to the Docker client.
-/// * `container_id` - ID of the running container.
-/// * `timeout_rx` - A channel receiver for timeout signals.
-async fn monitor_container_process(
+/// Address of a running container on a network, if it has one
+pub(crate) async fn container_ip(
     docker: &Docker,
     container_id: &str,
-    timeout_rx: mpsc::Receiver<()>,
-) -> AppResult<()> {
-    loop {
-        match timeout_rx.try_recv() {
-            Ok(_) => {
-                clean_up(docker, container_id).await?;
-                return Ok(());
-            }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(e) => return Err(RuntimeError::System(format!("mpsc channel error: {e}"))),
-        }
+    network: &str,
+) -> Option<String> {
+    let settings = inspect_network_settings(docker, container_id).await?;
+    addresses_from(settings, network, 0).0
+}
+
+/// Address of a running container on a network and the daemon host port its
+/// `container_port` is published on, each if it has one
+async fn container_addresses(
+    docker: &Docker,
+    container_id: &str,
+    network: &str,
+    container_port: u32,
+) -> (Option<String>, Option<u16>) {
+    match inspect_network_settings(docker, container_id).await {
+        Some(settings) => addresses_from(settings, network, container_port),
+        None => (None, None),
     }
 }
 
+async fn inspect_network_settings(docker: &Docker, container_id: &str) -> Option<NetworkSettings> {
+    docker
+        .inspect_container(container_id, None::<InspectContainerOptions>)
+        .await
+        .map_err(|e| warn!("Failed to inspect container {container_id}: {e}"))
+        .ok()?
+        .network_settings
+}
+
+fn addresses_from(
+    mut settings: NetworkSettings,
+    network: &str,
+    container_port: u32,
+) -> (Option<String>, Option<u16>) {
+    let ip_address = settings
+        .networks
+        .as_mut()
+        .and_then(|networks| networks.remove(network))
+        .and_then(|endpoint| endpoint.ip_address)
+        .filter(|ip| !ip.is_empty());
+    let host_port = settings
+        .ports
+        .and_then(|mut ports| ports.remove(&format!("{container_port}/tcp")))
+        .flatten()
+        .into_iter()
+        .flatten()
+        .find_map(|binding| binding.host_port?.parse().ok());
+    (ip_address, host_port)
+}
+
 /// Removes a container forcefully.
 ///
 /// # Arguments
@@ -246,7 +367,7 @@ pub async fn clean_up(docker: &Docker, container_id: &str) -> AppResult<()> {
 /// # Returns
 ///
 /// A tuple `(cpu_period, cpu_quota)` suitable for use in Docker’s HostConfig.
-fn cpu_limits(x: f64) -> (i64, i64) {
+pub(crate) fn cpu_limits(x: f64) -> (i64, i64) {
     // Docker's default CPU period is 100,000 microseconds (100ms).
     let cpu_period = 100_000_u64;
 
@@ -277,6 +398,42 @@ mod tests {
         assert_eq!(period, 100_000);
         assert_eq!(quota, 50_000);
     }
+
+    #[test]
+    fn test_addresses_from_network_settings() {
+        use bollard::models::EndpointSettings;
+
+        let settings = NetworkSettings {
+            networks: Some(HashMap::from([(
+                "invok_default".to_string(),
+                EndpointSettings {
+                    ip_address: Some("172.18.0.4".to_string()),
+                    ..Default::default()
+                },
+            )])),
+            ports: Some(HashMap::from([(
+                "8080/tcp".to_string(),
+                Some(vec![PortBinding {
+                    host_ip: Some("127.0.0.1".to_string()),
+                    host_port: Some("49153".to_string()),
+                }]),
+            )])),
+            ..Default::default()
+        };
+        assert_eq!(
+            addresses_from(settings.clone(), "invok_default", 8080),
+            (Some("172.18.0.4".to_string()), Some(49153))
+        );
+        // Another network, and a port that isn't published
+        assert_eq!(addresses_from(settings, "bridge", 9000), (None, None));
+
+        // Exposed but not published
+        let unpublished = NetworkSettings {
+            ports: Some(HashMap::from([("8080/tcp".to_string(), None)])),
+            ..Default::default()
+        };
+        assert_eq!(addresses_from(unpublished, "bridge", 8080), (None, None));
+    }
 }
 
 #[tokio::test]
@@ -286,13 +443,14 @@ async fn test_runner() {
         None,
         "test-runner",
         ContainerDetails {
-            container_id: "".to_string(),
             container_port: 8080,
-            bind_port: 8080.to_string(),
+            publish_ip: Some("127.0.0.1".to_string()),
             container_name: "c-test".to_string(),
-            timeout: 50,
             docker_compose_network_host: "asdf".to_string(),
+            env: Vec::new(),
         },
+        ResourceLimits::default(),
+        None,
     )
     .await;
     assert!(result.is_ok(), "Container should start successfully.");
@@ -0,0 +1,181 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Events kept per function; older ones are dropped first
+const EVENTS_PER_FUNCTION: usize = 256;
+
+/// What the autoscaler did to a function's pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingAction {
+    /// A container was started
+    ScaledUp,
+    /// An idle container was removed
+    ScaledDown,
+    /// An idle container was paused
+    Paused,
+    /// A paused container was resumed to take load
+    Unpaused,
+    /// A container the pool needed could not be added
+    ScaleUpFailed,
+    /// A container exited on its own
+    ContainerDied,
+    /// A container past its lifetime was replaced and takes no new requests
+    Recycled,
+}
+
+/// One change to a function's pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalingEvent {
+    /// When it happened (unix milliseconds)
+    pub at_ms: u64,
+    pub action: ScalingAction,
+    /// Containers in the pool once it happened, paused ones included
+    pub containers: usize,
+    /// Why it happened, or why it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// The most recent scaling events of every function, so load tests can be lined up
+/// against what the autoscaler did
+#[derive(Debug, Default)]
+pub struct ScalingHistory {
+    events: DashMap<String, VecDeque<ScalingEvent>>,
+}
+
+impl ScalingHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event of a function's pool, happening now. Repeats of the function's
+    /// last event are skipped.
+    pub fn record(
+        &self,
+        function_key: &str,
+        action: ScalingAction,
+        containers: usize,
+        detail: Option<String>,
+    ) {
+        self.push(
+            function_key,
+            ScalingEvent {
+                at_ms: now_unix_ms(),
+                action,
+                containers,
+                detail,
+            },
+        );
+    }
+
+    fn push(&self, function_key: &str, event: ScalingEvent) {
+        let mut events = self.events.entry(function_key.to_string()).or_default();
+        // Refused scale-ups are retried on every scan; the first one says it all
+        if events.back().is_some_and(|last| {
+            last.action == event.action
+                && last.containers == event.containers
+                && last.detail == event.detail
+        }) {
+            return;
+        }
+        if events.len() == EVENTS_PER_FUNCTION {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Events of a function at or after `since_ms` (unix milliseconds), oldest first
+    pub fn since(&self, function_key: &str, since_ms: u64) -> Vec<ScalingEvent> {
+        self.events
+            .get(function_key)
+            .map(|events| {
+                events
+                    .iter()
+                    .filter(|event| event.at_ms >= since_ms)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drop the events of a function that is no longer served
+    pub fn forget(&self, function_key: &str) {
+        self.events.remove(function_key);
+    }
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(at_ms: u64, containers: usize) -> ScalingEvent {
+        ScalingEvent {
+            at_ms,
+            action: ScalingAction::ScaledUp,
+            containers,
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn test_since_filters_by_time_and_function() {
+        let history = ScalingHistory::new();
+        history.push("fn-a", event(1_000, 1));
+        history.push("fn-a", event(2_000, 2));
+        history.push("fn-b", event(1_500, 1));
+
+        assert_eq!(history.since("fn-a", 1_500), vec![event(2_000, 2)]);
+        assert_eq!(history.since("fn-a", 0).len(), 2);
+        assert!(history.since("fn-c", 0).is_empty());
+    }
+
+    #[test]
+    fn test_repeated_events_are_skipped() {
+        let history = ScalingHistory::new();
+        let refused = |at_ms| ScalingEvent {
+            at_ms,
+            action: ScalingAction::ScaleUpFailed,
+            containers: 2,
+            detail: Some("host is full".to_string()),
+        };
+        history.push("fn", refused(1_000));
+        history.push("fn", refused(2_000));
+        history.push("fn", event(3_000, 3));
+        history.push("fn", refused(4_000));
+
+        let at: Vec<u64> = history.since("fn", 0).iter().map(|e| e.at_ms).collect();
+        assert_eq!(at, vec![1_000, 3_000, 4_000]);
+    }
+
+    #[test]
+    fn test_oldest_events_are_dropped() {
+        let history = ScalingHistory::new();
+        for i in 0..EVENTS_PER_FUNCTION + 10 {
+            history.push("fn", event(i as u64, i));
+        }
+
+        let events = history.since("fn", 0);
+        assert_eq!(events.len(), EVENTS_PER_FUNCTION);
+        assert_eq!(events[0].at_ms, 10);
+    }
+
+    #[test]
+    fn test_forget_drops_events() {
+        let history = ScalingHistory::new();
+        history.record("fn", ScalingAction::Paused, 1, None);
+        assert_eq!(history.since("fn", 0).len(), 1);
+
+        history.forget("fn");
+        assert!(history.since("fn", 0).is_empty());
+    }
+}
@@ -0,0 +1,325 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+
+/// Which queued starts get a slot first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StartPriority {
+    /// A request waits on a pool with no container serving
+    Cold,
+    /// A pool that already serves gets another container
+    ScaleUp,
+}
+
+/// Container starts queued and running, for the status API
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StartQueueStatus {
+    /// Starts holding a slot
+    pub running: usize,
+    /// Starts waiting for a slot
+    pub queued: usize,
+    pub max_running: Option<usize>,
+    /// Requests waiting for a start of their function to finish
+    pub waiting_requests: usize,
+}
+
+/// Starts of one function, queued or running
+#[derive(Debug)]
+struct FunctionStarts {
+    in_flight: usize,
+    /// Notified whenever one of them finishes
+    finished: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    running: usize,
+    functions: HashMap<String, FunctionStarts>,
+    /// Starts waiting for a slot, cold starts first, then in arrival order
+    waiting: BTreeMap<(StartPriority, u64), oneshot::Sender<()>>,
+    next_seq: u64,
+    /// Requests in [`StartQueue::wait`]
+    waiting_requests: usize,
+}
+
+/// Paces container starts so a burst of cold starts, e.g. every function invoked at once
+/// after a restart, doesn't flood the Docker API.
+///
+/// A function has at most its start concurrency of starts in flight: a request finding
+/// one already running waits for it and takes the container it brings instead of
+/// starting its own. Across functions, at most `max_running` starts run at once; the rest
+/// queue, cold starts ahead of scale-ups of pools that already serve.
+#[derive(Debug)]
+pub struct StartQueue {
+    max_running: Option<usize>,
+    state: Mutex<QueueState>,
+}
+
+/// One container start; frees its slot, or its place in line, when dropped
+pub struct StartTicket {
+    queue: Arc<StartQueue>,
+    function_key: String,
+    /// Its place in line, if it had to queue for a slot
+    place: Option<(StartPriority, u64)>,
+}
+
+impl StartQueue {
+    /// `max_running` starts run at once; unlimited when not set
+    pub fn new(max_running: Option<usize>) -> Self {
+        Self {
+            max_running: max_running.map(|max| max.max(1)),
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    /// Start a container of a function once a slot is free.
+    ///
+    /// Returns `None` straight away if the function already has `per_function` starts in
+    /// flight; [`wait`](Self::wait) for one of them instead. Hold the ticket until the
+    /// container is in its pool (or failed to start).
+    pub async fn begin(
+        self: &Arc<Self>,
+        function_key: &str,
+        per_function: usize,
+        priority: StartPriority,
+    ) -> Option<StartTicket> {
+        let granted = {
+            let mut state = self.state.lock().unwrap();
+            let starts = state
+                .functions
+                .entry(function_key.to_string())
+                .or_insert_with(|| FunctionStarts {
+                    in_flight: 0,
+                    finished: Arc::new(Notify::new()),
+                });
+            if starts.in_flight >= per_function.max(1) {
+                return None;
+            }
+            starts.in_flight += 1;
+
+            if self.max_running.is_none_or(|max| state.running < max) {
+                state.running += 1;
+                None
+            } else {
+                let place = (priority, state.next_seq);
+                state.next_seq += 1;
+                let (grant, granted) = oneshot::channel();
+                state.waiting.insert(place, grant);
+                Some((place, granted))
+            }
+        };
+
+        let mut ticket = StartTicket {
+            queue: self.clone(),
+            function_key: function_key.to_string(),
+            place: None,
+        };
+        if let Some((place, granted)) = granted {
+            // Dropping the ticket while queued gives up its place
+            ticket.place = Some(place);
+            let _ = granted.await;
+        }
+        Some(ticket)
+    }
+
+    /// Wait up to `timeout` for a start of the function to finish. Returns `true` at once
+    /// if none is in flight, and `false` if none finished in time.
+    pub async fn wait(&self, function_key: &str, timeout: Duration) -> bool {
+        let finished;
+        let notified = {
+            let mut state = self.state.lock().unwrap();
+            let Some(starts) = state.functions.get(function_key) else {
+                return true;
+            };
+            finished = starts.finished.clone();
+            // Listening before the lock is released, so a start finishing now isn't missed
+            let mut notified = Box::pin(finished.notified());
+            notified.as_mut().enable();
+            state.waiting_requests += 1;
+            notified
+        };
+        let _waiting = WaitingRequest(self);
+        tokio::time::timeout(timeout, notified).await.is_ok()
+    }
+
+    /// Starts of a function queued or running
+    pub fn in_flight(&self, function_key: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .functions
+            .get(function_key)
+            .map_or(0, |starts| starts.in_flight)
+    }
+
+    pub fn status(&self) -> StartQueueStatus {
+        let state = self.state.lock().unwrap();
+        StartQueueStatus {
+            running: state.running,
+            queued: state.waiting.len(),
+            max_running: self.max_running,
+            waiting_requests: state.waiting_requests,
+        }
+    }
+}
+
+/// Counts a request in [`StartQueue::wait`] until it stops waiting, cancelled or not
+struct WaitingRequest<'a>(&'a StartQueue);
+
+impl Drop for WaitingRequest<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().waiting_requests -= 1;
+    }
+}
+
+impl Drop for StartTicket {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        // Still in line unless a finished start handed it a slot
+        let queued = self
+            .place
+            .is_some_and(|place| state.waiting.remove(&place).is_some());
+        if !queued {
+            // Hand the slot to the next start in line, or give it back. A start in line
+            // being cancelled right now finds its place gone and passes the slot on.
+            match state.waiting.pop_first() {
+                Some((_, grant)) => {
+                    let _ = grant.send(());
+                }
+                None => state.running -= 1,
+            }
+        }
+
+        if let Some(starts) = state.functions.get_mut(&self.function_key) {
+            starts.in_flight -= 1;
+            starts.finished.notify_waiters();
+            if starts.in_flight == 0 {
+                state.functions.remove(&self.function_key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::sleep;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_single_flight_per_function() {
+        let queue = Arc::new(StartQueue::new(None));
+        let ticket = queue.begin("fn", 1, StartPriority::Cold).await.unwrap();
+        assert!(queue.begin("fn", 1, StartPriority::Cold).await.is_none());
+        // A policy allowing two starts lets a second one through
+        let second = queue.begin("fn", 2, StartPriority::ScaleUp).await.unwrap();
+        assert_eq!(queue.in_flight("fn"), 2);
+        drop(second);
+
+        // Waiters wake up when the start finishes
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.wait("fn", Duration::from_secs(30)).await })
+        };
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(queue.status().waiting_requests, 1);
+        drop(ticket);
+        assert!(waiter.await.unwrap());
+        assert_eq!(queue.in_flight("fn"), 0);
+        assert_eq!(queue.status().waiting_requests, 0);
+        assert!(queue.wait("fn", Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cold_starts_get_slots_first() {
+        let queue = Arc::new(StartQueue::new(Some(1)));
+        let running = queue.begin("a", 1, StartPriority::Cold).await.unwrap();
+
+        let (order, mut started) = tokio::sync::mpsc::unbounded_channel();
+        for (function_key, priority) in [("b", StartPriority::ScaleUp), ("c", StartPriority::Cold)]
+        {
+            let (queue, order) = (queue.clone(), order.clone());
+            tokio::spawn(async move {
+                let ticket = queue.begin(function_key, 1, priority).await;
+                order.send(function_key).unwrap();
+                sleep(Duration::from_millis(10)).await;
+                drop(ticket);
+            });
+            sleep(Duration::from_millis(1)).await;
+        }
+        let status = queue.status();
+        assert_eq!((status.running, status.queued), (1, 2));
+
+        drop(running);
+        assert_eq!(started.recv().await, Some("c"));
+        assert_eq!(started.recv().await, Some("b"));
+        sleep(Duration::from_millis(20)).await;
+        let status = queue.status();
+        assert_eq!((status.running, status.queued), (0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_start_gives_up_its_place() {
+        let queue = Arc::new(StartQueue::new(Some(1)));
+        let running = queue.begin("a", 1, StartPriority::Cold).await.unwrap();
+        let queued = queue.begin("b", 1, StartPriority::Cold);
+        assert!(tokio::time::timeout(Duration::from_secs(1), queued)
+            .await
+            .is_err());
+        assert_eq!(queue.status().queued, 0);
+        assert_eq!(queue.in_flight("b"), 0);
+
+        drop(running);
+        assert_eq!(queue.status().running, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_times_out() {
+        let queue = Arc::new(StartQueue::new(None));
+        let ticket = queue.begin("fn", 1, StartPriority::Cold).await.unwrap();
+
+        let started = tokio::time::Instant::now();
+        assert!(!queue.wait("fn", Duration::from_secs(5)).await);
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+        // The request stopped waiting, and the start is still in flight
+        assert_eq!(queue.status().waiting_requests, 0);
+        assert_eq!(queue.in_flight("fn"), 1);
+
+        // Other functions' starts don't wake it up
+        let other = queue.begin("other", 1, StartPriority::Cold).await.unwrap();
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.wait("fn", Duration::from_secs(5)).await })
+        };
+        sleep(Duration::from_millis(10)).await;
+        drop(other);
+        assert!(!waiter.await.unwrap());
+        drop(ticket);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_same_priority_starts_in_arrival_order() {
+        let queue = Arc::new(StartQueue::new(Some(1)));
+        let running = queue.begin("a", 1, StartPriority::ScaleUp).await.unwrap();
+
+        let (order, mut started) = tokio::sync::mpsc::unbounded_channel();
+        for function_key in ["b", "c", "d"] {
+            let (queue, order) = (queue.clone(), order.clone());
+            tokio::spawn(async move {
+                let ticket = queue.begin(function_key, 1, StartPriority::ScaleUp).await;
+                order.send(function_key).unwrap();
+                drop(ticket);
+            });
+            sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(queue.status().queued, 3);
+
+        drop(running);
+        for function_key in ["b", "c", "d"] {
+            assert_eq!(started.recv().await, Some(function_key));
+        }
+        sleep(Duration::from_millis(1)).await;
+        let status = queue.status();
+        assert_eq!((status.running, status.queued), (0, 0));
+    }
+}
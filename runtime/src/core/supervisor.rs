@@ -0,0 +1,225 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// Delay before the first restart of a failed task, doubled on each failure after
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between restarts; a task that ran this long resets the backoff
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Whether a supervised task is doing its work
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// It panicked or returned before shutdown and waits for its restart
+    Restarting,
+    /// It returned after shutdown
+    Stopped,
+}
+
+/// Liveness of a supervised task, as reported by `/healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub name: &'static str,
+    pub state: TaskState,
+    /// Times it was restarted since the controller started
+    pub restarts: u32,
+    /// Why it last failed, e.g. the panic message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<String>,
+    /// When it last failed, in seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure_at: Option<u64>,
+}
+
+impl TaskStatus {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            state: TaskState::Running,
+            restarts: 0,
+            last_failure: None,
+            last_failure_at: None,
+        }
+    }
+}
+
+/// Owns the autoscaler's long-running background tasks.
+///
+/// Each task runs in its own Tokio task, started from a factory so it can be started
+/// again: one that panics, or returns while the runtime isn't shutting down, is restarted
+/// after a backoff of 1 s doubling up to 60 s. Their state is kept by name for health
+/// checks. Tasks are expected to return once shutdown is signalled.
+#[derive(Clone)]
+pub struct Supervisor {
+    tasks: Arc<DashMap<&'static str, TaskStatus>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Supervisor {
+    pub fn new(shutdown: watch::Receiver<bool>) -> Self {
+        Self {
+            tasks: Arc::new(DashMap::new()),
+            shutdown,
+        }
+    }
+
+    /// Run the future `task` returns under `name` until shutdown, restarting it whenever
+    /// it fails. Does nothing if a task of that name is already supervised and not
+    /// stopped, so starting twice doesn't run it twice.
+    pub fn supervise<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        {
+            let mut added = false;
+            let mut status = self.tasks.entry(name).or_insert_with(|| {
+                added = true;
+                TaskStatus::new(name)
+            });
+            if !added && status.state != TaskState::Stopped {
+                return;
+            }
+            status.state = TaskState::Running;
+        }
+        let tasks = self.tasks.clone();
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let started = Instant::now();
+                let failure = match tokio::spawn(task()).await {
+                    Ok(()) if *shutdown.borrow() => None,
+                    Ok(()) => Some("returned before shutdown".to_string()),
+                    Err(e) if e.is_panic() => Some(panic_message(e.into_panic())),
+                    // Only cancelled when the Tokio runtime itself shuts down
+                    Err(_) => None,
+                };
+                let Some(failure) = failure else {
+                    info!("Background task {} stopped", name);
+                    set_state(&tasks, name, TaskState::Stopped);
+                    return;
+                };
+
+                if started.elapsed() >= MAX_BACKOFF {
+                    backoff = INITIAL_BACKOFF;
+                }
+                error!(
+                    "Background task {} failed ({}), restarting it in {:?}",
+                    name, failure, backoff
+                );
+                if let Some(mut status) = tasks.get_mut(name) {
+                    status.state = TaskState::Restarting;
+                    status.last_failure = Some(failure);
+                    status.last_failure_at = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .ok()
+                        .map(|since| since.as_secs());
+                }
+
+                tokio::select! {
+                    _ = sleep(backoff) => {}
+                    _ = shutdown.wait_for(|stopping| *stopping) => {
+                        set_state(&tasks, name, TaskState::Stopped);
+                        return;
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                if let Some(mut status) = tasks.get_mut(name) {
+                    status.state = TaskState::Running;
+                    status.restarts += 1;
+                }
+                warn!("Restarting background task {}", name);
+            }
+        });
+    }
+
+    /// State of every supervised task, by name
+    pub fn status(&self) -> Vec<TaskStatus> {
+        let mut tasks: Vec<_> = self.tasks.iter().map(|task| task.value().clone()).collect();
+        tasks.sort_by_key(|task| task.name);
+        tasks
+    }
+
+    /// Whether no task is waiting for a restart
+    pub fn all_running(&self) -> bool {
+        self.tasks
+            .iter()
+            .all(|task| task.state != TaskState::Restarting)
+    }
+}
+
+fn set_state(tasks: &DashMap<&'static str, TaskStatus>, name: &'static str, state: TaskState) {
+    if let Some(mut status) = tasks.get_mut(name) {
+        status.state = state;
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned());
+    match message {
+        Some(message) => format!("panicked: {}", message),
+        None => "panicked".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicking_task_is_restarted() {
+        let (stop, _) = watch::channel(false);
+        let supervisor = Supervisor::new(stop.subscribe());
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let shutdown = stop.subscribe();
+        let task = move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            let mut shutdown = shutdown.clone();
+            async move {
+                if run == 0 {
+                    panic!("first run fails");
+                }
+                let _ = shutdown.wait_for(|stopping| *stopping).await;
+            }
+        };
+        supervisor.supervise("scaler", task.clone());
+
+        sleep(Duration::from_millis(10)).await;
+        let status = supervisor.status().remove(0);
+        assert_eq!(status.state, TaskState::Restarting);
+        assert_eq!(
+            status.last_failure.as_deref(),
+            Some("panicked: first run fails")
+        );
+        assert!(!supervisor.all_running());
+
+        sleep(INITIAL_BACKOFF).await;
+        // Already running, so not started a second time
+        supervisor.supervise("scaler", task);
+        sleep(Duration::from_millis(10)).await;
+        let status = supervisor.status().remove(0);
+        assert_eq!(status.state, TaskState::Running);
+        assert_eq!(status.restarts, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        stop.send_replace(true);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(supervisor.status()[0].state, TaskState::Stopped);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::core::runner::ResourceLimits;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Samples needed before recommendations are made (one per poll, per container)
+pub const MIN_SAMPLES_FOR_RECOMMENDATION: u64 = 60;
+
+/// Headroom kept above the observed peak when suggesting a limit
+const HEADROOM: f64 = 1.25;
+
+/// Memory limits offered as recommendations, in MB
+const MEMORY_STEPS_MB: &[i64] = &[64, 128, 256, 512, 1024, 2048, 4096];
+
+/// CPU limits offered as recommendations, in cores
+const CPU_STEPS: &[f64] = &[0.25, 0.5, 1.0, 1.5, 2.0, 4.0];
+
+const BYTES_IN_MB: f64 = 1024.0 * 1024.0;
+
+/// Resource usage of a function's containers, aggregated over its lifetime
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub samples: u64,
+    pub peak_cpu_cores: f64,
+    pub peak_memory_bytes: f64,
+    total_cpu_cores: f64,
+    total_memory_bytes: f64,
+    /// When the first sample was taken (unix seconds)
+    pub first_sample_at: i64,
+    /// When the last sample was taken (unix seconds)
+    pub last_sample_at: i64,
+}
+
+impl ResourceUsage {
+    /// Record one metrics sample of a container running with `limits`.
+    ///
+    /// `cpu_percentage` is relative to one core and `memory_percentage` to the memory
+    /// limit, as reported by the metrics client.
+    pub fn record(&mut self, cpu_percentage: f64, memory_percentage: f64, limits: ResourceLimits) {
+        let cpu_cores = (cpu_percentage / 100.0).max(0.0);
+        let memory_bytes = (memory_percentage / 100.0 * limits.memory_bytes as f64).max(0.0);
+
+        let now = now_unix();
+        if self.samples == 0 {
+            self.first_sample_at = now;
+        }
+        self.last_sample_at = now;
+        self.samples += 1;
+        self.peak_cpu_cores = self.peak_cpu_cores.max(cpu_cores);
+        self.peak_memory_bytes = self.peak_memory_bytes.max(memory_bytes);
+        self.total_cpu_cores += cpu_cores;
+        self.total_memory_bytes += memory_bytes;
+    }
+
+    pub fn avg_cpu_cores(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        self.total_cpu_cores / self.samples as f64
+    }
+
+    pub fn avg_memory_bytes(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        self.total_memory_bytes / self.samples as f64
+    }
+
+    /// Suggest limits that fit the observed peaks.
+    ///
+    /// Returns nothing until enough samples have been collected, or when the current
+    /// limits already match.
+    pub fn recommendations(&self, limits: ResourceLimits) -> Vec<Recommendation> {
+        if self.samples < MIN_SAMPLES_FOR_RECOMMENDATION {
+            return vec![];
+        }
+
+        let mut recommendations = Vec::new();
+
+        let current_mb = limits.memory_bytes as f64 / BYTES_IN_MB;
+        let peak_mb = self.peak_memory_bytes / BYTES_IN_MB;
+        let suggested_mb = MEMORY_STEPS_MB
+            .iter()
+            .map(|&mb| mb as f64)
+            .find(|&mb| mb >= peak_mb * HEADROOM)
+            .unwrap_or(current_mb.max(peak_mb * HEADROOM));
+        if suggested_mb != current_mb {
+            let verb = if suggested_mb < current_mb {
+                "reduce"
+            } else {
+                "increase"
+            };
+            recommendations.push(Recommendation {
+                resource: Resource::Memory,
+                current: current_mb,
+                suggested: suggested_mb,
+                message: format!(
+                    "{} memory to {}MB (peak {:.0}MB of {}MB)",
+                    verb, suggested_mb, peak_mb, current_mb
+                ),
+            });
+        }
+
+        let suggested_cpus = CPU_STEPS
+            .iter()
+            .copied()
+            .find(|&cpus| cpus >= self.peak_cpu_cores * HEADROOM)
+            .unwrap_or(limits.cpus.max(self.peak_cpu_cores * HEADROOM));
+        if suggested_cpus != limits.cpus {
+            let verb = if suggested_cpus < limits.cpus {
+                "reduce"
+            } else {
+                "increase"
+            };
+            recommendations.push(Recommendation {
+                resource: Resource::Cpu,
+                current: limits.cpus,
+                suggested: suggested_cpus,
+                message: format!(
+                    "{} CPU to {} (peak {:.2} of {} cores)",
+                    verb, suggested_cpus, self.peak_cpu_cores, limits.cpus
+                ),
+            });
+        }
+
+        recommendations
+    }
+}
+
+/// Resource a recommendation applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    /// Values are in MB
+    Memory,
+    /// Values are in cores
+    Cpu,
+}
+
+/// A suggested change to a function's resource limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub resource: Resource,
+    pub current: f64,
+    pub suggested: f64,
+    /// User facing description, e.g. "reduce memory to 128MB (peak 61MB of 256MB)"
+    pub message: String,
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: ResourceLimits = ResourceLimits {
+        memory_bytes: 256 * 1024 * 1024,
+        cpus: 2.0,
+    };
+
+    fn usage(cpu_percentage: f64, memory_percentage: f64, samples: u64) -> ResourceUsage {
+        let mut usage = ResourceUsage::default();
+        for _ in 0..samples {
+            usage.record(cpu_percentage, memory_percentage, LIMITS);
+        }
+        usage
+    }
+
+    #[test]
+    fn test_record_tracks_peaks_and_averages() {
+        let mut usage = ResourceUsage::default();
+        usage.record(50.0, 25.0, LIMITS);
+        usage.record(150.0, 75.0, LIMITS);
+
+        assert_eq!(usage.samples, 2);
+        assert_eq!(usage.peak_cpu_cores, 1.5);
+        assert_eq!(usage.peak_memory_bytes, 192.0 * BYTES_IN_MB);
+        assert_eq!(usage.avg_cpu_cores(), 1.0);
+        assert_eq!(usage.avg_memory_bytes(), 128.0 * BYTES_IN_MB);
+    }
+
+    #[test]
+    fn test_no_recommendations_without_enough_samples() {
+        let usage = usage(5.0, 10.0, MIN_SAMPLES_FOR_RECOMMENDATION - 1);
+        assert!(usage.recommendations(LIMITS).is_empty());
+    }
+
+    #[test]
+    fn test_recommends_smaller_limits_for_light_functions() {
+        // 10% of 256MB ≈ 26MB peak, 0.1 cores peak
+        let recommendations =
+            usage(10.0, 10.0, MIN_SAMPLES_FOR_RECOMMENDATION).recommendations(LIMITS);
+
+        assert_eq!(recommendations.len(), 2);
+        assert_eq!(recommendations[0].resource, Resource::Memory);
+        assert_eq!(recommendations[0].suggested, 64.0);
+        assert!(recommendations[0]
+            .message
+            .starts_with("reduce memory to 64MB"));
+        assert_eq!(recommendations[1].resource, Resource::Cpu);
+        assert_eq!(recommendations[1].suggested, 0.25);
+    }
+
+    #[test]
+    fn test_recommends_larger_limits_for_saturated_functions() {
+        // 95% of the memory limit and more than one core
+        let recommendations =
+            usage(130.0, 95.0, MIN_SAMPLES_FOR_RECOMMENDATION).recommendations(LIMITS);
+
+        let memory = &recommendations[0];
+        assert_eq!(memory.resource, Resource::Memory);
+        assert_eq!(memory.suggested, 512.0);
+        assert!(memory.message.starts_with("increase memory"));
+
+        // 1.3 cores * 1.25 headroom fits in the current 2 cores
+        assert_eq!(recommendations.len(), 1);
+    }
+}
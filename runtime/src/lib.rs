@@ -1,2 +1,74 @@
+//! Autoscaling pools of function containers on Docker.
+//!
+//! Each function gets a pool of containers started from its image, scaled between a
+//! minimum and a maximum on their CPU and memory usage, and handed out one request at a
+//! time as [`ContainerLease`]s. The invok controller is built on it, but nothing here
+//! depends on the controller: any service with container images to run on demand can
+//! embed the pools.
+//!
+//! ```no_run
+//! use runtime::{AutoscalingRuntimeBuilder, FunctionPolicy};
+//!
+//! # async fn run() -> runtime::AppResult<()> {
+//! let runtime = AutoscalingRuntimeBuilder::new()
+//!     .min_containers_per_function(0)
+//!     .max_containers_per_function(5)
+//!     .persistence_enabled(false)
+//!     .build()
+//!     .await?;
+//! runtime.start().await?;
+//!
+//! // Pools are keyed by the image their containers run
+//! let autoscaler = runtime.autoscaler();
+//! autoscaler.set_function_policy("orders-api", FunctionPolicy::default());
+//! let lease = autoscaler
+//!     .get_container_for_invocation("orders-api", None)
+//!     .await?;
+//! // Send the request to the container; dropping the lease releases it
+//! println!("http://{}/", lease.address().authority());
+//!
+//! runtime.shutdown().await
+//! # }
+//! ```
+//!
+//! Images serve HTTP on port 8080 and print [`FULL_START_MSG`] once they accept
+//! connections; containers are only routed to after that. A complete program is in
+//! `examples/embedded_pool.rs`.
+//!
+//! # Extension points
+//!
+//! Container metrics come from Prometheus, cgroup files or the Docker stats API, or from
+//! any [`ContainerMetrics`] given to [`AutoscalingRuntimeBuilder::metrics`]. That is the
+//! only one so far: containers always run on Docker, and pool state is persisted to Redis
+//! or kept in memory. Neither the container executor nor the persistence store is behind
+//! a trait, so another container engine or state store needs changes to this crate.
+//!
+//! # Features
+//!
+//! - `redis` (default): persists pools to Redis and shares them between replicas.
+//!   Without it, pools only live in memory.
+//! - `prometheus` (default): reads metrics from a Prometheus-compatible API. Without it,
+//!   `reqwest` isn't needed and metrics come from the Docker stats API unless set.
+//!
+//! # Stability
+//!
+//! The items re-exported at the crate root are the supported API and follow semver:
+//! until 1.0, breaking changes bump the minor version and are listed in `CHANGELOG.md`.
+//! The modules under [`core`](crate::core) are public for the invok controller and may
+//! change in any release.
+
 pub mod core;
 pub mod shared;
+
+pub use crate::core::autoscaler::{Autoscaler, ScalingAnomaly};
+pub use crate::core::builder::{AutoscalingRuntime, AutoscalingRuntimeBuilder};
+pub use crate::core::container_manager::{ContainerAddress, ContainerLease};
+pub use crate::core::environment::AddressMode;
+pub use crate::core::metrics_client::{ContainerMetrics, MetricsAuth, MetricsSource};
+pub use crate::core::policy::{FunctionPolicy, IdleStrategy, Protocol, Service, StickyKey};
+pub use crate::core::polling::ScanStatsSnapshot;
+pub use crate::core::restore::RestoreReport;
+pub use crate::core::runner::FULL_START_MSG;
+pub use crate::core::scaling_history::{ScalingAction, ScalingEvent};
+pub use crate::core::supervisor::{TaskState, TaskStatus};
+pub use crate::shared::error::{AppResult, RuntimeError};
@@ -1,23 +1,53 @@
-use std::fmt;
+use shared_utils::error::{Classify, ErrorClass};
+use thiserror::Error;
 
 // Error
 pub type AppResult<T> = Result<T, RuntimeError>;
 
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum RuntimeError {
+    /// A Docker operation failed, with a message fit to show
+    #[error("{0}")]
     Exec(String),
+    /// The caller asked for something that can't be done, e.g. a Dockerfile that doesn't
+    /// build or a dev sync outside the function's directory
+    #[error("{0}")]
+    Invalid(String),
+    /// Nothing can serve the request right now, e.g. a pool without a free container
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("System Error: {0}")]
     System(String),
+    #[error("Redis Error: {0}")]
     RedisError(String),
+    #[error("Serialization Error: {0}")]
     SerializationError(String),
 }
 
-impl fmt::Display for RuntimeError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Classify for RuntimeError {
+    fn class(&self) -> ErrorClass {
         match self {
-            RuntimeError::Exec(e) => write!(f, "{e}"),
-            RuntimeError::System(e) => write!(f, "System Error: {e}"),
-            RuntimeError::RedisError(e) => write!(f, "Redis Error: {e}"),
-            RuntimeError::SerializationError(e) => write!(f, "Serialization Error: {e}"),
+            RuntimeError::Invalid(_) => ErrorClass::User,
+            RuntimeError::Unavailable(_) | RuntimeError::RedisError(_) => ErrorClass::Retryable,
+            RuntimeError::Exec(_)
+            | RuntimeError::System(_)
+            | RuntimeError::SerializationError(_) => ErrorClass::System,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_keep_their_messages() {
+        let invalid = RuntimeError::Invalid("A sync may delete at most 10 paths".to_string());
+        assert_eq!(invalid.to_string(), "A sync may delete at most 10 paths");
+        assert_eq!(invalid.class(), ErrorClass::User);
+
+        let redis = RuntimeError::RedisError("connection refused".to_string());
+        assert_eq!(redis.to_string(), "Redis Error: connection refused");
+        assert!(redis.class().is_retryable());
+    }
+}
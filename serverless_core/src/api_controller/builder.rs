@@ -0,0 +1,268 @@
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, FromRef, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use runtime::core::platform::parse_platforms;
+use runtime::core::preflight::check_docker;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::config::InvokConfig;
+use super::middlewares::admin::constant_time_eq;
+use super::middlewares::jwt::AuthError;
+use super::{shutdown_signal, InvokAppError};
+use crate::lifecycle_manager::base_images::{run_base_image_refresh_loop, BaseImages};
+use crate::lifecycle_manager::build_queue::{BuildQueue, BuildQueueError, BUILD_ARGS_HEADER};
+
+/// Largest build context accepted (1GB)
+const MAX_BUILD_CONTEXT_SIZE: usize = 1024 * 1024 * 1024;
+
+/// State of the builder service
+#[derive(Clone, FromRef)]
+pub struct BuilderState {
+    queue: Arc<BuildQueue>,
+    token: Arc<String>,
+    base_images: Arc<BaseImages>,
+}
+
+/// Extractor guarding the builder API.
+///
+/// Requires `Authorization: Bearer <builder token>`, the secret shared with the controller.
+pub struct BuilderClient;
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for BuilderClient
+where
+    BuilderState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = BuilderState::from_ref(state);
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                AuthError(
+                    StatusCode::UNAUTHORIZED,
+                    "Missing builder token".to_string(),
+                )
+            })?;
+
+        if !constant_time_eq(token.as_bytes(), state.token.as_bytes()) {
+            warn!("Rejected build request with an invalid token");
+            return Err(AuthError(
+                StatusCode::UNAUTHORIZED,
+                "Invalid builder token".to_string(),
+            ));
+        }
+        Ok(BuilderClient)
+    }
+}
+
+/// Query of a build submission; the body is the tar'd build context
+#[derive(Debug, Deserialize)]
+pub struct SubmitBuildQuery {
+    image: String,
+    namespace: String,
+    /// Comma-separated platforms to build for besides the builder daemon's own
+    #[serde(default)]
+    platforms: String,
+}
+
+/// Starts the builder service.
+///
+/// The service builds the images controllers submit, a few at a time, pushes them to the
+/// registry and keeps each build's outcome for an hour:
+/// - `POST /builds?image=..&namespace=..&platforms=..` with the tar'd build context
+///   queues a build and answers `202` with it, or `429` when the namespace has too many
+///   builds queued or running. Build args go in the `x-invok-build-args` header.
+/// - `GET /builds/:id` answers with the build and its status.
+/// - `GET /base-images` answers with the last pull of each runtime base image, and
+///   `POST /base-images/pull` pulls them again.
+pub async fn start_builder() -> Result<(), InvokAppError> {
+    tracing_subscriber::fmt::init();
+
+    let config = InvokConfig::load_builder()?;
+
+    let (_, version) = check_docker()
+        .await
+        .map_err(|e| InvokAppError::Preflight(vec![e]))?;
+    info!("Docker daemon {} reachable", version);
+
+    let queue = BuildQueue::new(
+        config.concurrency,
+        config.namespace_quota,
+        Duration::from_secs(config.timeout_secs),
+        config.registry.clone().unwrap_or_default(),
+        config.registry_auth.clone(),
+    );
+    let state = BuilderState {
+        queue: Arc::new(queue),
+        token: Arc::new(config.token.clone().unwrap_or_default()),
+        base_images: Arc::new(BaseImages::new(
+            &config.base_images,
+            config.base_image_mirror.clone(),
+        )),
+    };
+
+    // Keep the runtime base images pulled, so builds only fetch the functions' own layers
+    if config.prepull_base_images {
+        tokio::spawn(run_base_image_refresh_loop(
+            state.base_images.clone(),
+            Duration::from_secs(config.base_image_refresh_secs),
+        ));
+    }
+
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route(
+            "/builds",
+            post(submit_build).layer(DefaultBodyLimit::max(MAX_BUILD_CONTEXT_SIZE)),
+        )
+        .route("/builds/:id", get(get_build))
+        .route("/base-images", get(list_base_images))
+        .route("/base-images/pull", post(pull_base_images))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    info!(
+        "Builder listening on {} ({} concurrent builds, {} per namespace, pushing to {})",
+        addr,
+        config.concurrency,
+        config.namespace_quota,
+        config.registry.as_deref().unwrap_or_default()
+    );
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    info!("Builder stopped");
+    Ok(())
+}
+
+async fn healthz(State(state): State<BuilderState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "status": "ok",
+        "pending": state.queue.pending(),
+    }))
+}
+
+async fn submit_build(
+    State(state): State<BuilderState>,
+    _client: BuilderClient,
+    Query(query): Query<SubmitBuildQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let platforms = match parse_platforms(&query.platforms) {
+        Ok(platforms) => platforms,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+    };
+    let build_args = match read_build_args(&headers) {
+        Ok(build_args) => build_args,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+    };
+    match state.queue.submit(
+        query.image,
+        query.namespace,
+        platforms,
+        build_args,
+        body.to_vec(),
+    ) {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e @ BuildQueueError::QuotaExceeded(_)) => {
+            json_error(StatusCode::TOO_MANY_REQUESTS, &e.to_string())
+        }
+        Err(e @ BuildQueueError::InvalidBuild(_)) => {
+            json_error(StatusCode::BAD_REQUEST, &e.to_string())
+        }
+    }
+}
+
+async fn get_build(
+    State(state): State<BuilderState>,
+    _client: BuilderClient,
+    Path(id): Path<Uuid>,
+) -> Response {
+    match state.queue.get(id) {
+        Some(job) => (StatusCode::OK, Json(job)).into_response(),
+        None => json_error(StatusCode::NOT_FOUND, "Build not found"),
+    }
+}
+
+async fn list_base_images(State(state): State<BuilderState>, _client: BuilderClient) -> Response {
+    (StatusCode::OK, Json(state.base_images.status())).into_response()
+}
+
+async fn pull_base_images(State(state): State<BuilderState>, _client: BuilderClient) -> Response {
+    let base_images = state.base_images.clone();
+    tokio::spawn(async move {
+        if let Some(pulled) = base_images.pull_all().await {
+            info!(
+                "Pulled {} of {} runtime base images",
+                pulled,
+                base_images.count()
+            );
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Reads the build args the controller sent along with a build
+fn read_build_args(headers: &HeaderMap) -> Result<HashMap<String, String>, String> {
+    let Some(value) = headers.get(BUILD_ARGS_HEADER) else {
+        return Ok(HashMap::new());
+    };
+    let json = BASE64
+        .decode(value.as_bytes())
+        .map_err(|e| format!("invalid {}: {}", BUILD_ARGS_HEADER, e))?;
+    serde_json::from_slice(&json).map_err(|e| format!("invalid {}: {}", BUILD_ARGS_HEADER, e))
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_read_build_args() {
+        let mut headers = HeaderMap::new();
+        assert!(read_build_args(&headers).unwrap().is_empty());
+
+        let json = serde_json::json!({ "NPM_TOKEN": "secret" }).to_string();
+        headers.insert(
+            BUILD_ARGS_HEADER,
+            HeaderValue::from_str(&BASE64.encode(json)).unwrap(),
+        );
+        let build_args = read_build_args(&headers).unwrap();
+        assert_eq!(build_args["NPM_TOKEN"], "secret");
+
+        headers.insert(BUILD_ARGS_HEADER, HeaderValue::from_static("not base64!"));
+        assert!(read_build_args(&headers).is_err());
+        headers.insert(
+            BUILD_ARGS_HEADER,
+            HeaderValue::from_str(&BASE64.encode("[1, 2]")).unwrap(),
+        );
+        assert!(read_build_args(&headers).is_err());
+    }
+}
@@ -1,24 +1,42 @@
 use std::env;
+use std::str::FromStr;
 
+use auth::InvokAuthConfig;
+use builder::InvokBuilderConfig;
+use database::InvokDatabaseConfig;
+use egress::InvokEgressConfig;
+use file::FileConfig;
 use function::InvokFunctionConfig;
+use payloads::InvokPayloadConfig;
+use runtime::core::sandbox::{Protected, SandboxConfig};
+use sandbox::InvokSandboxConfig;
 use server::InvokServerConfig;
+use services::InvokServiceConfig;
 use thiserror::Error;
 
+mod auth;
+mod builder;
+mod database;
+mod egress;
+mod file;
 mod function;
+mod payloads;
+mod sandbox;
 mod server;
+mod services;
 
 /// Error that can occur during configuration loading
 #[derive(Debug, Error)]
 pub enum InvokConfigError {
-    #[error("Missing environment variable: {0}")]
-    MissingVar(String),
-
-    #[error("The port {0} provided is invalid")]
-    InvalidPort(String),
-
     #[error("Invalid configuration value: {0}")]
     InvalidValue(String),
 
+    #[error("Invalid configuration file: {0}")]
+    File(String),
+
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+
     #[error("Environment error: {0}")]
     EnvError(#[from] env::VarError),
 }
@@ -29,19 +47,147 @@ pub struct InvokConfig {
     /// Server configuration
     pub server_config: InvokServerConfig,
 
+    /// Database connection pools and read replica
+    pub database_config: InvokDatabaseConfig,
+
     /// Function configuration
     pub function_config: InvokFunctionConfig,
+
+    /// Remote image builder configuration
+    pub builder_config: InvokBuilderConfig,
+
+    /// Registration and login hardening
+    pub auth_config: InvokAuthConfig,
+
+    /// Outbound traffic control of function containers
+    pub egress_config: InvokEgressConfig,
+
+    /// Network rules applied to function containers
+    pub sandbox_config: InvokSandboxConfig,
+
+    /// Object storage large invocation payloads pass through
+    pub payload_config: InvokPayloadConfig,
+
+    /// Ports published for TCP and UDP service functions
+    pub service_config: InvokServiceConfig,
 }
 
 impl InvokConfig {
-    /// Load complete configuration from environment
+    /// Load complete configuration.
+    ///
+    /// Settings come from `invok.yaml` (or the file named by `INVOK_CONFIG`), with
+    /// environment variables taking precedence. All problems found are reported together.
     pub fn load() -> Result<Self, InvokConfigError> {
-        let server_config = InvokServerConfig::from_env()?;
-        let function_config = InvokFunctionConfig::from_env();
+        let file = FileConfig::discover()?;
+        let mut errors = Vec::new();
+
+        let server_config = InvokServerConfig::load(&file.server, &mut errors);
+        let database_config = InvokDatabaseConfig::load(&file.database, &mut errors);
+        let function_config = InvokFunctionConfig::load(&file, &mut errors);
+        let builder_config = InvokBuilderConfig::load(&file.builder, &mut errors);
+        let auth_config = InvokAuthConfig::load(&file.auth, &mut errors);
+        let egress_config = InvokEgressConfig::load(&file.egress, &mut errors);
+        let sandbox_config = InvokSandboxConfig::load(&file.sandbox, &mut errors);
+        let payload_config = InvokPayloadConfig::load(&file.payloads, &mut errors);
+        let service_config = InvokServiceConfig::load(&file.services, &mut errors);
+
+        if !errors.is_empty() {
+            return Err(InvokConfigError::Invalid(errors));
+        }
 
         Ok(Self {
             server_config,
+            database_config,
             function_config,
+            builder_config,
+            auth_config,
+            egress_config,
+            sandbox_config,
+            payload_config,
+            service_config,
         })
     }
+
+    /// Sandbox settings for the autoscaler, if enabled, blocking the datastores and metrics
+    /// server this controller is configured with
+    pub fn sandbox_policy(&self) -> Option<SandboxConfig> {
+        let mut services = vec![
+            (Protected::Redis, self.server_config.redis_url.as_str()),
+            (
+                Protected::Postgres,
+                self.server_config.database_url.as_str(),
+            ),
+            (
+                Protected::Prometheus,
+                self.function_config.autoscaling.prometheus_url.as_str(),
+            ),
+        ];
+        if let Some(replica_url) = &self.database_config.read_replica_url {
+            services.push((Protected::Postgres, replica_url.as_str()));
+        }
+        if let Some(redis_url) = &self.egress_config.redis_url {
+            services.push((Protected::Redis, redis_url.as_str()));
+        }
+        self.sandbox_config.policy(&services)
+    }
+
+    /// Load the configuration of the builder service.
+    ///
+    /// Only the `builder` section is read: the builder service doesn't use the database,
+    /// Redis or the autoscaler.
+    pub fn load_builder() -> Result<InvokBuilderConfig, InvokConfigError> {
+        let file = FileConfig::discover()?;
+        let mut errors = Vec::new();
+
+        let builder_config = InvokBuilderConfig::load(&file.builder, &mut errors);
+        builder_config.validate_service(&mut errors);
+
+        if !errors.is_empty() {
+            return Err(InvokConfigError::Invalid(errors));
+        }
+        Ok(builder_config)
+    }
+}
+
+/// Resolve a setting from its environment variable, falling back to the config file.
+///
+/// A value that fails to parse is recorded in `errors` (naming both the env variable and
+/// the file key) and treated as unset.
+fn resolve<T: FromStr>(
+    env_var: &str,
+    file_key: &str,
+    file_value: Option<T>,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    match env::var(env_var) {
+        Ok(raw) => match raw.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                errors.push(format!(
+                    "{} (overrides {}): cannot parse '{}'",
+                    env_var, file_key, raw
+                ));
+                None
+            }
+        },
+        Err(_) => file_value,
+    }
+}
+
+/// Like [`resolve`], but records an error when the setting is missing everywhere
+fn resolve_required<T: FromStr>(
+    env_var: &str,
+    file_key: &str,
+    file_value: Option<T>,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    let had_error = errors.len();
+    let value = resolve(env_var, file_key, file_value, errors);
+    if value.is_none() && errors.len() == had_error {
+        errors.push(format!(
+            "{} is required: set it in the config file or via {}",
+            file_key, env_var
+        ));
+    }
+    value
 }
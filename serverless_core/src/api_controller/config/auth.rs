@@ -0,0 +1,180 @@
+use super::file::AuthSection;
+use super::resolve;
+use crate::lifecycle_manager::login_guard::{
+    CaptchaVerifier, LoginGuard, LoginThrottle, PasswordPolicy,
+};
+use std::time::Duration;
+
+const PASSWORD_MIN_LENGTH_ENV_VARIABLE: &str = "PASSWORD_MIN_LENGTH";
+const PASSWORD_MIN_CLASSES_ENV_VARIABLE: &str = "PASSWORD_MIN_CLASSES";
+const LOGIN_MAX_FAILURES_PER_ACCOUNT_ENV_VARIABLE: &str = "LOGIN_MAX_FAILURES_PER_ACCOUNT";
+const LOGIN_MAX_FAILURES_PER_SOURCE_ENV_VARIABLE: &str = "LOGIN_MAX_FAILURES_PER_SOURCE";
+const LOGIN_FAILURE_WINDOW_SECS_ENV_VARIABLE: &str = "LOGIN_FAILURE_WINDOW_SECS";
+const LOGIN_LOCKOUT_SECS_ENV_VARIABLE: &str = "LOGIN_LOCKOUT_SECS";
+const CAPTCHA_VERIFY_URL_ENV_VARIABLE: &str = "CAPTCHA_VERIFY_URL";
+const CAPTCHA_SECRET_ENV_VARIABLE: &str = "CAPTCHA_SECRET";
+
+/// Default fewest characters of a new password
+const DEFAULT_PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Default fewest character classes a new password mixes
+const DEFAULT_PASSWORD_MIN_CLASSES: usize = 1;
+
+/// Default failed logins of one account before it is locked out
+const DEFAULT_LOGIN_MAX_FAILURES_PER_ACCOUNT: u32 = 5;
+
+/// Default failed logins from one client IP before it is locked out
+const DEFAULT_LOGIN_MAX_FAILURES_PER_SOURCE: u32 = 20;
+
+/// Default time failed logins are counted for
+const DEFAULT_LOGIN_FAILURE_WINDOW_SECS: u64 = 15 * 60;
+
+/// Default lockout once a limit is reached
+const DEFAULT_LOGIN_LOCKOUT_SECS: u64 = 15 * 60;
+
+/// Registration and login hardening
+#[derive(Debug, Clone)]
+pub struct InvokAuthConfig {
+    /// Fewest characters of a new password
+    pub password_min_length: usize,
+
+    /// Fewest character classes (lowercase, uppercase, digits, symbols) a new password mixes
+    pub password_min_classes: usize,
+
+    /// Failed logins of one account within the window before it is locked out (0 = never)
+    pub login_max_failures_per_account: u32,
+
+    /// Failed logins from one client IP within the window before it is locked out (0 = never)
+    pub login_max_failures_per_source: u32,
+
+    /// Seconds failed logins are counted for, from the first one
+    pub login_failure_window_secs: u64,
+
+    /// Seconds an account or client IP stays locked out
+    pub login_lockout_secs: u64,
+
+    /// `siteverify` endpoint of the captcha provider; registrations need a solved captcha
+    /// when set
+    pub captcha_verify_url: Option<String>,
+
+    /// Secret key of the site at the captcha provider
+    pub captcha_secret: Option<String>,
+}
+
+impl InvokAuthConfig {
+    /// Load configuration from environment variables, falling back to the `auth` section
+    /// of the config file. Problems are appended to `errors`.
+    pub fn load(file: &AuthSection, errors: &mut Vec<String>) -> Self {
+        let config = Self {
+            password_min_length: resolve(
+                PASSWORD_MIN_LENGTH_ENV_VARIABLE,
+                "auth.password_min_length",
+                file.password_min_length,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PASSWORD_MIN_LENGTH),
+            password_min_classes: resolve(
+                PASSWORD_MIN_CLASSES_ENV_VARIABLE,
+                "auth.password_min_classes",
+                file.password_min_classes,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PASSWORD_MIN_CLASSES),
+            login_max_failures_per_account: resolve(
+                LOGIN_MAX_FAILURES_PER_ACCOUNT_ENV_VARIABLE,
+                "auth.login_max_failures_per_account",
+                file.login_max_failures_per_account,
+                errors,
+            )
+            .unwrap_or(DEFAULT_LOGIN_MAX_FAILURES_PER_ACCOUNT),
+            login_max_failures_per_source: resolve(
+                LOGIN_MAX_FAILURES_PER_SOURCE_ENV_VARIABLE,
+                "auth.login_max_failures_per_source",
+                file.login_max_failures_per_source,
+                errors,
+            )
+            .unwrap_or(DEFAULT_LOGIN_MAX_FAILURES_PER_SOURCE),
+            login_failure_window_secs: resolve(
+                LOGIN_FAILURE_WINDOW_SECS_ENV_VARIABLE,
+                "auth.login_failure_window_secs",
+                file.login_failure_window_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_LOGIN_FAILURE_WINDOW_SECS),
+            login_lockout_secs: resolve(
+                LOGIN_LOCKOUT_SECS_ENV_VARIABLE,
+                "auth.login_lockout_secs",
+                file.login_lockout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_LOGIN_LOCKOUT_SECS),
+            captcha_verify_url: resolve(
+                CAPTCHA_VERIFY_URL_ENV_VARIABLE,
+                "auth.captcha_verify_url",
+                file.captcha_verify_url.clone(),
+                errors,
+            ),
+            captcha_secret: resolve(
+                CAPTCHA_SECRET_ENV_VARIABLE,
+                "auth.captcha_secret",
+                file.captcha_secret.clone(),
+                errors,
+            ),
+        };
+        config.validate(errors);
+        config
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.password_min_length == 0 {
+            errors.push("auth.password_min_length must be at least 1".to_string());
+        }
+        if !(1..=4).contains(&self.password_min_classes) {
+            errors.push(format!(
+                "auth.password_min_classes must be between 1 and 4, got {}",
+                self.password_min_classes
+            ));
+        }
+        if self.login_failure_window_secs == 0 {
+            errors.push("auth.login_failure_window_secs must be at least 1".to_string());
+        }
+        if self.login_lockout_secs == 0 {
+            errors.push("auth.login_lockout_secs must be at least 1".to_string());
+        }
+
+        match (&self.captcha_verify_url, &self.captcha_secret) {
+            (Some(url), Some(_)) => {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    errors.push(
+                        "auth.captcha_verify_url must be an http:// or https:// URL".to_string(),
+                    );
+                }
+            }
+            (None, None) => {}
+            _ => errors.push(
+                "auth.captcha_verify_url and auth.captcha_secret must be set together".to_string(),
+            ),
+        }
+    }
+
+    /// The guard enforcing these settings on registrations and logins
+    pub fn login_guard(&self) -> LoginGuard {
+        LoginGuard {
+            password_policy: PasswordPolicy {
+                min_length: self.password_min_length,
+                min_classes: self.password_min_classes,
+            },
+            throttle: LoginThrottle {
+                max_failures_per_account: self.login_max_failures_per_account,
+                max_failures_per_source: self.login_max_failures_per_source,
+                window: Duration::from_secs(self.login_failure_window_secs),
+                lockout: Duration::from_secs(self.login_lockout_secs),
+            },
+            captcha: match (&self.captcha_verify_url, &self.captcha_secret) {
+                (Some(url), Some(secret)) => Some(CaptchaVerifier::new(url, secret)),
+                _ => None,
+            },
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use super::file::BuilderSection;
+use super::resolve;
+use runtime::core::provisioning::RegistryAuth;
+
+const BUILDER_URL_ENV_VARIABLE: &str = "BUILDER_URL";
+const BUILDER_TOKEN_ENV_VARIABLE: &str = "BUILDER_TOKEN";
+const BUILDER_PORT_ENV_VARIABLE: &str = "BUILDER_PORT";
+const BUILDER_CONCURRENCY_ENV_VARIABLE: &str = "BUILDER_CONCURRENCY";
+const BUILDER_NAMESPACE_QUOTA_ENV_VARIABLE: &str = "BUILDER_NAMESPACE_QUOTA";
+const BUILDER_TIMEOUT_SECS_ENV_VARIABLE: &str = "BUILDER_TIMEOUT_SECS";
+const REGISTRY_ENV_VARIABLE: &str = "REGISTRY";
+const REGISTRY_USERNAME_ENV_VARIABLE: &str = "REGISTRY_USERNAME";
+const REGISTRY_PASSWORD_ENV_VARIABLE: &str = "REGISTRY_PASSWORD";
+const PREPULL_BASE_IMAGES_ENV_VARIABLE: &str = "PREPULL_BASE_IMAGES";
+const BASE_IMAGES_ENV_VARIABLE: &str = "BASE_IMAGES";
+const BASE_IMAGE_MIRROR_ENV_VARIABLE: &str = "BASE_IMAGE_MIRROR";
+const BASE_IMAGE_REFRESH_SECS_ENV_VARIABLE: &str = "BASE_IMAGE_REFRESH_SECS";
+
+/// Default port the builder service listens on
+const DEFAULT_BUILDER_PORT: u16 = 3100;
+
+/// Default builds the builder service runs at once
+const DEFAULT_BUILDER_CONCURRENCY: usize = 2;
+
+/// Default builds a namespace may have queued or running at once
+const DEFAULT_BUILDER_NAMESPACE_QUOTA: usize = 2;
+
+/// Default time a build may take, queueing included
+const DEFAULT_BUILDER_TIMEOUT_SECS: u64 = 15 * 60;
+
+/// Default time between pulls of the base images, picking up moved tags (6 hours)
+const DEFAULT_BASE_IMAGE_REFRESH_SECS: u64 = 6 * 60 * 60;
+
+/// Remote image builder configuration, shared by the controller and the builder service
+#[derive(Debug, Clone)]
+pub struct InvokBuilderConfig {
+    /// Builder service the controller submits builds to; images are built locally when unset
+    pub url: Option<String>,
+
+    /// Shared secret the controller authenticates to the builder service with
+    pub token: Option<String>,
+
+    /// Port the builder service listens on
+    pub port: u16,
+
+    /// Builds the builder service runs at once; the rest wait in its queue
+    pub concurrency: usize,
+
+    /// Builds a namespace may have queued or running at once
+    pub namespace_quota: usize,
+
+    /// Seconds a build may take, queueing included
+    pub timeout_secs: u64,
+
+    /// Registry and path built images are pushed to and pulled from,
+    /// e.g. `registry.example.com/invok`
+    pub registry: Option<String>,
+
+    /// Credentials of the registry
+    pub registry_auth: RegistryAuth,
+
+    /// Pull the base images of the runtimes ahead of builds, on the controller and the
+    /// builder service
+    pub prepull_base_images: bool,
+
+    /// Images pulled besides those of the runtime templates, e.g. of a runtime version
+    /// being enabled
+    pub base_images: Vec<String>,
+
+    /// Pull-through registry mirror of Docker Hub base images are pulled from,
+    /// e.g. `mirror.internal:5000`
+    pub base_image_mirror: Option<String>,
+
+    /// Seconds between pulls of the base images; `0` pulls them at startup only
+    pub base_image_refresh_secs: u64,
+}
+
+impl InvokBuilderConfig {
+    /// Load configuration from environment variables, falling back to the `builder`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &BuilderSection, errors: &mut Vec<String>) -> Self {
+        let url: Option<String> = resolve(
+            BUILDER_URL_ENV_VARIABLE,
+            "builder.url",
+            file.url.clone(),
+            errors,
+        );
+        let url = url.map(|url| url.trim_end_matches('/').to_string());
+        if url
+            .as_ref()
+            .is_some_and(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            errors.push("builder.url must be an http:// or https:// URL".to_string());
+        }
+
+        let token: Option<String> = resolve(
+            BUILDER_TOKEN_ENV_VARIABLE,
+            "builder.token",
+            file.token.clone(),
+            errors,
+        );
+        if token.as_ref().is_some_and(|token| token.len() < 16) {
+            errors.push("builder.token must be at least 16 characters".to_string());
+        }
+
+        let port = resolve(BUILDER_PORT_ENV_VARIABLE, "builder.port", file.port, errors)
+            .unwrap_or(DEFAULT_BUILDER_PORT);
+
+        let concurrency = resolve(
+            BUILDER_CONCURRENCY_ENV_VARIABLE,
+            "builder.concurrency",
+            file.concurrency,
+            errors,
+        )
+        .unwrap_or(DEFAULT_BUILDER_CONCURRENCY);
+        if concurrency == 0 {
+            errors.push("builder.concurrency must be at least 1".to_string());
+        }
+
+        let namespace_quota = resolve(
+            BUILDER_NAMESPACE_QUOTA_ENV_VARIABLE,
+            "builder.namespace_quota",
+            file.namespace_quota,
+            errors,
+        )
+        .unwrap_or(DEFAULT_BUILDER_NAMESPACE_QUOTA);
+        if namespace_quota == 0 {
+            errors.push("builder.namespace_quota must be at least 1".to_string());
+        }
+
+        let timeout_secs = resolve(
+            BUILDER_TIMEOUT_SECS_ENV_VARIABLE,
+            "builder.timeout_secs",
+            file.timeout_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_BUILDER_TIMEOUT_SECS);
+        if timeout_secs == 0 {
+            errors.push("builder.timeout_secs must be at least 1".to_string());
+        }
+
+        let registry: Option<String> = resolve(
+            REGISTRY_ENV_VARIABLE,
+            "builder.registry",
+            file.registry.clone(),
+            errors,
+        );
+        let registry = registry.map(|registry| registry.trim_end_matches('/').to_string());
+
+        let registry_auth = RegistryAuth {
+            username: resolve(
+                REGISTRY_USERNAME_ENV_VARIABLE,
+                "builder.registry_username",
+                file.registry_username.clone(),
+                errors,
+            ),
+            password: resolve(
+                REGISTRY_PASSWORD_ENV_VARIABLE,
+                "builder.registry_password",
+                file.registry_password.clone(),
+                errors,
+            ),
+        };
+        if registry_auth.password.is_some() && registry_auth.username.is_none() {
+            errors.push("builder.registry_password requires builder.registry_username".to_string());
+        }
+
+        let prepull_base_images = resolve(
+            PREPULL_BASE_IMAGES_ENV_VARIABLE,
+            "builder.prepull_base_images",
+            file.prepull_base_images,
+            errors,
+        )
+        .unwrap_or(true);
+
+        let base_images: Option<String> = resolve(
+            BASE_IMAGES_ENV_VARIABLE,
+            "builder.base_images",
+            file.base_images.clone(),
+            errors,
+        );
+        let base_images: Vec<String> = base_images
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|image| !image.is_empty())
+            .map(str::to_string)
+            .collect();
+        if base_images
+            .iter()
+            .any(|image| image.contains(char::is_whitespace))
+        {
+            errors.push("builder.base_images must be a comma-separated list of images".to_string());
+        }
+
+        let base_image_mirror: Option<String> = resolve(
+            BASE_IMAGE_MIRROR_ENV_VARIABLE,
+            "builder.base_image_mirror",
+            file.base_image_mirror.clone(),
+            errors,
+        );
+        let base_image_mirror = base_image_mirror.map(|mirror| {
+            mirror
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/')
+                .to_string()
+        });
+
+        let base_image_refresh_secs = resolve(
+            BASE_IMAGE_REFRESH_SECS_ENV_VARIABLE,
+            "builder.base_image_refresh_secs",
+            file.base_image_refresh_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_BASE_IMAGE_REFRESH_SECS);
+
+        // Images built remotely only reach the controller through the registry
+        if url.is_some() {
+            if token.is_none() {
+                errors.push("builder.url requires builder.token".to_string());
+            }
+            if registry.is_none() {
+                errors.push("builder.url requires builder.registry".to_string());
+            }
+        }
+
+        Self {
+            url,
+            token,
+            port,
+            concurrency,
+            namespace_quota,
+            timeout_secs,
+            registry,
+            registry_auth,
+            prepull_base_images,
+            base_images,
+            base_image_mirror,
+            base_image_refresh_secs,
+        }
+    }
+
+    /// Checks the settings the builder service itself needs
+    pub fn validate_service(&self, errors: &mut Vec<String>) {
+        if self.token.is_none() {
+            errors.push(format!(
+                "builder.token is required: set it in the config file or via {}",
+                BUILDER_TOKEN_ENV_VARIABLE
+            ));
+        }
+        if self.registry.is_none() {
+            errors.push(format!(
+                "builder.registry is required: set it in the config file or via {}",
+                REGISTRY_ENV_VARIABLE
+            ));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use super::file::DatabaseSection;
+use super::resolve;
+use sea_orm::ConnectOptions;
+use std::time::Duration;
+
+const DATABASE_MAX_CONNECTIONS_ENV_VARIABLE: &str = "DATABASE_MAX_CONNECTIONS";
+const DATABASE_MIN_CONNECTIONS_ENV_VARIABLE: &str = "DATABASE_MIN_CONNECTIONS";
+const DATABASE_CONNECT_TIMEOUT_SECS_ENV_VARIABLE: &str = "DATABASE_CONNECT_TIMEOUT_SECS";
+const DATABASE_ACQUIRE_TIMEOUT_SECS_ENV_VARIABLE: &str = "DATABASE_ACQUIRE_TIMEOUT_SECS";
+const DATABASE_IDLE_TIMEOUT_SECS_ENV_VARIABLE: &str = "DATABASE_IDLE_TIMEOUT_SECS";
+const DATABASE_READ_REPLICA_URL_ENV_VARIABLE: &str = "DATABASE_READ_REPLICA_URL";
+
+/// Default maximum connections per pool, SeaORM's own default
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+/// Default time to wait for a new connection to the database
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 8;
+
+/// Default time a query waits for a free connection of the pool
+const DEFAULT_ACQUIRE_TIMEOUT_SECS: u64 = 8;
+
+/// Default time an unused connection is kept open
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+
+/// Connection pools of the database
+#[derive(Debug, Clone)]
+pub struct InvokDatabaseConfig {
+    /// Maximum connections of each pool
+    pub max_connections: u32,
+
+    /// Connections each pool keeps open even when idle
+    pub min_connections: u32,
+
+    /// Seconds to wait for a new connection to the database
+    pub connect_timeout_secs: u64,
+
+    /// Seconds a query waits for a free connection before failing
+    pub acquire_timeout_secs: u64,
+
+    /// Seconds an unused connection is kept open; 0 keeps them open
+    pub idle_timeout_secs: u64,
+
+    /// Read-only replica serving listings, descriptions and invocation history; every
+    /// query goes to `server.database_url` when unset
+    pub read_replica_url: Option<String>,
+}
+
+impl InvokDatabaseConfig {
+    /// Load configuration from environment variables, falling back to the `database`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &DatabaseSection, errors: &mut Vec<String>) -> Self {
+        let config = Self {
+            max_connections: resolve(
+                DATABASE_MAX_CONNECTIONS_ENV_VARIABLE,
+                "database.max_connections",
+                file.max_connections,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MAX_CONNECTIONS),
+            min_connections: resolve(
+                DATABASE_MIN_CONNECTIONS_ENV_VARIABLE,
+                "database.min_connections",
+                file.min_connections,
+                errors,
+            )
+            .unwrap_or(0),
+            connect_timeout_secs: resolve(
+                DATABASE_CONNECT_TIMEOUT_SECS_ENV_VARIABLE,
+                "database.connect_timeout_secs",
+                file.connect_timeout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+            acquire_timeout_secs: resolve(
+                DATABASE_ACQUIRE_TIMEOUT_SECS_ENV_VARIABLE,
+                "database.acquire_timeout_secs",
+                file.acquire_timeout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_ACQUIRE_TIMEOUT_SECS),
+            idle_timeout_secs: resolve(
+                DATABASE_IDLE_TIMEOUT_SECS_ENV_VARIABLE,
+                "database.idle_timeout_secs",
+                file.idle_timeout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS),
+            read_replica_url: resolve(
+                DATABASE_READ_REPLICA_URL_ENV_VARIABLE,
+                "database.read_replica_url",
+                file.read_replica_url.clone(),
+                errors,
+            ),
+        };
+        config.validate(errors);
+        config
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.min_connections > self.max_connections {
+            errors.push(format!(
+                "database.min_connections ({}) must not exceed database.max_connections ({})",
+                self.min_connections, self.max_connections
+            ));
+        }
+        if self.connect_timeout_secs == 0 {
+            errors.push("database.connect_timeout_secs must be at least 1".to_string());
+        }
+        if self.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be at least 1".to_string());
+        }
+        if let Some(url) = &self.read_replica_url {
+            if !url.starts_with("postgres://") && !url.starts_with("postgresql://") {
+                errors.push("database.read_replica_url must be a postgres:// URL".to_string());
+            }
+        }
+    }
+
+    /// Options of a pool connecting to `url`, the primary or the replica
+    pub fn connect_options(&self, url: &str) -> ConnectOptions {
+        let mut options = ConnectOptions::new(url.to_string());
+        options
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs));
+        if self.idle_timeout_secs > 0 {
+            options.idle_timeout(Duration::from_secs(self.idle_timeout_secs));
+        }
+        options
+    }
+}
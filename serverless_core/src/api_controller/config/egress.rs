@@ -0,0 +1,97 @@
+use super::file::EgressSection;
+use super::resolve;
+use runtime::core::egress::{proxy_credentials, EgressConfig};
+
+const EGRESS_ENABLED_ENV_VARIABLE: &str = "EGRESS_ENABLED";
+const EGRESS_PROXY_IMAGE_ENV_VARIABLE: &str = "EGRESS_PROXY_IMAGE";
+const EGRESS_PROXY_PORT_ENV_VARIABLE: &str = "EGRESS_PROXY_PORT";
+const EGRESS_REDIS_URL_ENV_VARIABLE: &str = "EGRESS_REDIS_URL";
+
+/// Default port the egress proxies listen on
+const DEFAULT_EGRESS_PROXY_PORT: u16 = 3128;
+
+/// Label the proxy secret is derived from the JWT secret with, so it differs from it
+const EGRESS_SECRET_LABEL: &str = "invok-egress-proxy";
+
+/// Outbound traffic control of function containers
+#[derive(Debug, Clone)]
+pub struct InvokEgressConfig {
+    /// Route function traffic through per-namespace egress proxies
+    pub enabled: bool,
+
+    /// Image the proxies run, holding the `serverless-core` binary
+    pub proxy_image: Option<String>,
+
+    /// Port the proxies listen on
+    pub proxy_port: u16,
+
+    /// Redis the proxies read allowlists from, as reachable from the container network;
+    /// `server.redis_url` when unset
+    pub redis_url: Option<String>,
+}
+
+impl InvokEgressConfig {
+    /// Load configuration from environment variables, falling back to the `egress` section
+    /// of the config file. Problems are appended to `errors`.
+    pub fn load(file: &EgressSection, errors: &mut Vec<String>) -> Self {
+        let config = Self {
+            enabled: resolve(
+                EGRESS_ENABLED_ENV_VARIABLE,
+                "egress.enabled",
+                file.enabled,
+                errors,
+            )
+            .unwrap_or(false),
+            proxy_image: resolve(
+                EGRESS_PROXY_IMAGE_ENV_VARIABLE,
+                "egress.proxy_image",
+                file.proxy_image.clone(),
+                errors,
+            ),
+            proxy_port: resolve(
+                EGRESS_PROXY_PORT_ENV_VARIABLE,
+                "egress.proxy_port",
+                file.proxy_port,
+                errors,
+            )
+            .unwrap_or(DEFAULT_EGRESS_PROXY_PORT),
+            redis_url: resolve(
+                EGRESS_REDIS_URL_ENV_VARIABLE,
+                "egress.redis_url",
+                file.redis_url.clone(),
+                errors,
+            ),
+        };
+        config.validate(errors);
+        config
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.enabled && self.proxy_image.is_none() {
+            errors.push("egress.proxy_image is required when egress.enabled is set".to_string());
+        }
+        if self.proxy_port == 0 {
+            errors.push("egress.proxy_port must be at least 1".to_string());
+        }
+    }
+
+    /// Settings of the proxies for the autoscaler, if egress control is enabled.
+    ///
+    /// The proxy secret is derived from `jwt_auth_secret`, so every controller hands out
+    /// the same credentials without another setting to share.
+    pub fn proxy_config(&self, redis_url: &str, jwt_auth_secret: &str) -> Option<EgressConfig> {
+        if !self.enabled {
+            return None;
+        }
+        Some(EgressConfig {
+            proxy_image: self.proxy_image.clone()?,
+            proxy_port: self.proxy_port,
+            redis_url: self
+                .redis_url
+                .clone()
+                .unwrap_or_else(|| redis_url.to_string()),
+            secret: proxy_credentials(jwt_auth_secret, EGRESS_SECRET_LABEL),
+        })
+    }
+}
@@ -0,0 +1,360 @@
+use super::InvokConfigError;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Env variable pointing at the config file
+const CONFIG_FILE_ENV_VARIABLE: &str = "INVOK_CONFIG";
+
+/// Config file looked up in the working directory when `INVOK_CONFIG` is not set
+const DEFAULT_CONFIG_FILE: &str = "invok.yaml";
+
+/// `server` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct ServerSection {
+    pub redis_url: Option<String>,
+    pub database_url: Option<String>,
+    pub jwt_auth_secret: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub docker_host: Option<String>,
+    pub docker_compose_network: Option<String>,
+    pub published_ports_host: Option<String>,
+    pub shutdown_timeout_secs: Option<u64>,
+    pub trust_forwarded_for: Option<bool>,
+    pub hide_internal_addresses: Option<bool>,
+    pub admin_token: Option<String>,
+    pub max_restore_size: Option<usize>,
+    pub oidc_issuer: Option<String>,
+    pub oidc_audience: Option<String>,
+    pub oidc_token_ttl_secs: Option<u64>,
+    pub build_platforms: Option<String>,
+    pub build_args_key: Option<String>,
+    pub internal_api_url: Option<String>,
+}
+
+/// `function` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct FunctionSection {
+    pub max_function_size: Option<usize>,
+    pub max_request_size: Option<usize>,
+    pub max_response_size: Option<usize>,
+    pub cold_start_budget_per_source: Option<u32>,
+    pub cold_start_budget_per_namespace: Option<u32>,
+    pub max_import_size: Option<usize>,
+    pub trash_retention_hours: Option<u64>,
+    pub preview_ttl_days: Option<u64>,
+    pub response_cache_max_bytes: Option<usize>,
+    pub response_cache_max_ttl_secs: Option<u64>,
+    pub purge_webhooks: Option<String>,
+    pub purge_webhook_secret: Option<String>,
+    pub allow_exec: Option<bool>,
+    pub keep_versions: Option<usize>,
+    pub max_artifact_bytes_per_namespace: Option<u64>,
+    pub dependency_advisories: Option<String>,
+    pub dependency_advisories_refresh_secs: Option<u64>,
+}
+
+/// `autoscaling` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct AutoscalingSection {
+    pub cpu_overload_threshold: Option<f64>,
+    pub memory_overload_threshold: Option<f64>,
+    pub cooldown_cpu_threshold: Option<f64>,
+    pub cooldown_duration_secs: Option<u64>,
+    pub min_containers_per_function: Option<usize>,
+    pub max_containers_per_function: Option<usize>,
+    pub max_containers_per_namespace: Option<usize>,
+    pub max_containers_per_host: Option<usize>,
+    pub max_host_cpu_percent: Option<f64>,
+    pub max_host_memory_percent: Option<f64>,
+    pub max_concurrent_starts: Option<usize>,
+    pub hibernate_after_days: Option<u64>,
+    pub poll_interval_secs: Option<u64>,
+    pub idle_poll_interval_secs: Option<u64>,
+    pub max_parallel_pool_checks: Option<usize>,
+    pub pool_check_timeout_secs: Option<u64>,
+    pub cold_start_timeout_secs: Option<u64>,
+    pub metrics_source: Option<String>,
+    pub cgroup_root: Option<PathBuf>,
+    pub use_prometheus_metrics: Option<bool>,
+    pub prometheus_url: Option<String>,
+    pub fallback_to_docker: Option<bool>,
+    pub prometheus_container_id_pattern: Option<String>,
+    pub prometheus_username: Option<String>,
+    pub prometheus_password: Option<String>,
+    pub prometheus_bearer_token: Option<String>,
+    pub prometheus_ca_cert: Option<PathBuf>,
+}
+
+/// `persistence` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct PersistenceSection {
+    pub enabled: Option<bool>,
+    pub batch_size: Option<usize>,
+    pub scan_page_size: Option<usize>,
+    pub state_ttl_secs: Option<u64>,
+    pub state_max_age_secs: Option<u64>,
+    pub shared_routing: Option<bool>,
+    pub leader_lease_secs: Option<u64>,
+}
+
+/// `builder` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct BuilderSection {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    pub port: Option<u16>,
+    pub concurrency: Option<usize>,
+    pub namespace_quota: Option<usize>,
+    pub timeout_secs: Option<u64>,
+    pub registry: Option<String>,
+    pub registry_username: Option<String>,
+    pub registry_password: Option<String>,
+    pub prepull_base_images: Option<bool>,
+    pub base_images: Option<String>,
+    pub base_image_mirror: Option<String>,
+    pub base_image_refresh_secs: Option<u64>,
+}
+
+/// `auth` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct AuthSection {
+    pub password_min_length: Option<usize>,
+    pub password_min_classes: Option<usize>,
+    pub login_max_failures_per_account: Option<u32>,
+    pub login_max_failures_per_source: Option<u32>,
+    pub login_failure_window_secs: Option<u64>,
+    pub login_lockout_secs: Option<u64>,
+    pub captcha_verify_url: Option<String>,
+    pub captcha_secret: Option<String>,
+}
+
+/// `database` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct DatabaseSection {
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub connect_timeout_secs: Option<u64>,
+    pub acquire_timeout_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub read_replica_url: Option<String>,
+}
+
+/// `egress` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct EgressSection {
+    pub enabled: Option<bool>,
+    pub proxy_image: Option<String>,
+    pub proxy_port: Option<u16>,
+    pub redis_url: Option<String>,
+}
+
+/// `sandbox` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct SandboxSection {
+    pub enabled: Option<bool>,
+    pub helper_image: Option<String>,
+    pub allow: Option<String>,
+}
+
+/// `payloads` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct PayloadsSection {
+    pub store_url: Option<String>,
+    pub region: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub threshold: Option<u64>,
+    pub max_size: Option<u64>,
+    pub url_ttl_secs: Option<u64>,
+}
+
+/// `services` section of `invok.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct ServicesSection {
+    pub bind_address: Option<IpAddr>,
+    pub port_range_start: Option<u16>,
+    pub port_range_end: Option<u16>,
+}
+
+/// Structured configuration file.
+///
+/// Every value is optional: anything missing falls back to the environment variable of
+/// the same setting and then to the built-in default. Environment variables always win
+/// over the file.
+#[derive(Debug, Default, Deserialize)]
+pub struct FileConfig {
+    #[serde(default)]
+    pub server: ServerSection,
+    #[serde(default)]
+    pub database: DatabaseSection,
+    #[serde(default)]
+    pub function: FunctionSection,
+    #[serde(default)]
+    pub autoscaling: AutoscalingSection,
+    #[serde(default)]
+    pub persistence: PersistenceSection,
+    #[serde(default)]
+    pub builder: BuilderSection,
+    #[serde(default)]
+    pub auth: AuthSection,
+    #[serde(default)]
+    pub egress: EgressSection,
+    #[serde(default)]
+    pub sandbox: SandboxSection,
+    #[serde(default)]
+    pub payloads: PayloadsSection,
+    #[serde(default)]
+    pub services: ServicesSection,
+}
+
+impl FileConfig {
+    /// Load the config file named by `INVOK_CONFIG`, or `./invok.yaml` if it exists.
+    ///
+    /// Returns an empty config when no file is configured, so env-only setups keep working.
+    pub fn discover() -> Result<Self, InvokConfigError> {
+        let path = match env::var(CONFIG_FILE_ENV_VARIABLE) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => PathBuf::from(DEFAULT_CONFIG_FILE),
+            Err(_) => return Ok(Self::default()),
+        };
+
+        let contents = fs::read_to_string(&path).map_err(|e| {
+            InvokConfigError::File(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        Self::parse(&contents)
+            .map_err(|e| InvokConfigError::File(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse and validate the YAML contents of a config file
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let value: Value = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+
+        let unknown = unknown_keys(&value);
+        if !unknown.is_empty() {
+            return Err(format!("unknown keys: {}", unknown.join(", ")));
+        }
+
+        serde_yaml::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Sections of the file and the keys each takes, read off the section structs
+fn sections() -> [(&'static str, &'static [&'static str]); 11] {
+    [
+        ("server", fields::<ServerSection>()),
+        ("database", fields::<DatabaseSection>()),
+        ("function", fields::<FunctionSection>()),
+        ("autoscaling", fields::<AutoscalingSection>()),
+        ("persistence", fields::<PersistenceSection>()),
+        ("builder", fields::<BuilderSection>()),
+        ("auth", fields::<AuthSection>()),
+        ("egress", fields::<EgressSection>()),
+        ("sandbox", fields::<SandboxSection>()),
+        ("payloads", fields::<PayloadsSection>()),
+        ("services", fields::<ServicesSection>()),
+    ]
+}
+
+/// Names of the fields of a struct deriving `Deserialize`, as they appear in the file.
+///
+/// The derived impl hands them to [`Deserializer::deserialize_struct`], so a deserializer
+/// that keeps them and fails gets them without any data.
+fn fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(StructFields(&mut fields));
+    fields
+}
+
+struct StructFields<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for StructFields<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the field names are read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Collect every key in the file that doesn't match a known setting (as `section.key`)
+fn unknown_keys(value: &Value) -> Vec<String> {
+    let sections = sections();
+
+    let Some(root) = value.as_mapping() else {
+        return vec!["<root> (expected a mapping of sections)".to_string()];
+    };
+
+    let mut unknown = Vec::new();
+    for (section, body) in root {
+        let section = section.as_str().unwrap_or_default();
+        let Some((_, known)) = sections.iter().find(|(name, _)| *name == section) else {
+            unknown.push(section.to_string());
+            continue;
+        };
+
+        if let Some(body) = body.as_mapping() {
+            for key in body.keys() {
+                let key = key.as_str().unwrap_or_default();
+                if !known.contains(&key) {
+                    unknown.push(format!("{}.{}", section, key));
+                }
+            }
+        }
+    }
+    unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_cover_the_file() {
+        let listed: Vec<_> = sections().iter().map(|(name, _)| *name).collect();
+        assert_eq!(listed, fields::<FileConfig>());
+        assert!(sections().iter().all(|(_, keys)| !keys.is_empty()));
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let config = FileConfig::parse(
+            "server:\n  port: 8080\n  prot: 8081\nautoscaling:\n  max_host_cpu_percent: 80\nbogus: {}\n",
+        );
+        assert_eq!(config.unwrap_err(), "unknown keys: server.prot, bogus");
+
+        let config =
+            FileConfig::parse("server:\n  port: 8080\nservices:\n  port_range_end: 9000\n")
+                .unwrap();
+        assert_eq!(config.server.port, Some(8080));
+        assert_eq!(config.services.port_range_end, Some(9000));
+    }
+
+    #[test]
+    fn test_example_config_parses() {
+        FileConfig::parse(include_str!("../../../../invok.example.yaml")).unwrap();
+    }
+}
@@ -1,6 +1,29 @@
-use std::env;
+use super::file::FileConfig;
+use super::resolve;
+use runtime::core::cgroup_metrics::DEFAULT_CGROUP_ROOT;
+use runtime::core::metrics_client::{MetricsAuth, MetricsSource};
+use runtime::core::replicas::ReplicaConfig;
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
 
 const MAX_FUNCTION_SIZE_ENV_VARIABLE: &str = "MAX_FUNCTION_SIZE";
+const MAX_REQUEST_SIZE_ENV_VARIABLE: &str = "MAX_REQUEST_SIZE";
+const MAX_RESPONSE_SIZE_ENV_VARIABLE: &str = "MAX_RESPONSE_SIZE";
+const COLD_START_BUDGET_PER_SOURCE_ENV_VARIABLE: &str = "COLD_START_BUDGET_PER_SOURCE";
+const COLD_START_BUDGET_PER_NAMESPACE_ENV_VARIABLE: &str = "COLD_START_BUDGET_PER_NAMESPACE";
+const MAX_IMPORT_SIZE_ENV_VARIABLE: &str = "MAX_IMPORT_SIZE";
+const TRASH_RETENTION_HOURS_ENV_VARIABLE: &str = "TRASH_RETENTION_HOURS";
+const PREVIEW_TTL_DAYS_ENV_VARIABLE: &str = "PREVIEW_TTL_DAYS";
+const RESPONSE_CACHE_MAX_BYTES_ENV_VARIABLE: &str = "RESPONSE_CACHE_MAX_BYTES";
+const RESPONSE_CACHE_MAX_TTL_SECS_ENV_VARIABLE: &str = "RESPONSE_CACHE_MAX_TTL_SECS";
+const PURGE_WEBHOOKS_ENV_VARIABLE: &str = "PURGE_WEBHOOKS";
+const PURGE_WEBHOOK_SECRET_ENV_VARIABLE: &str = "PURGE_WEBHOOK_SECRET";
+const ALLOW_EXEC_ENV_VARIABLE: &str = "ALLOW_EXEC";
+const KEEP_VERSIONS_ENV_VARIABLE: &str = "KEEP_VERSIONS";
+const MAX_ARTIFACT_BYTES_PER_NAMESPACE_ENV_VARIABLE: &str = "MAX_ARTIFACT_BYTES_PER_NAMESPACE";
+const DEPENDENCY_ADVISORIES_ENV_VARIABLE: &str = "DEPENDENCY_ADVISORIES";
+const DEPENDENCY_ADVISORIES_REFRESH_SECS_ENV_VARIABLE: &str = "DEPENDENCY_ADVISORIES_REFRESH_SECS";
 // Autoscaling configuration environment variables
 const CPU_OVERLOAD_THRESHOLD_ENV: &str = "CPU_OVERLOAD_THRESHOLD";
 const MEMORY_OVERLOAD_THRESHOLD_ENV: &str = "MEMORY_OVERLOAD_THRESHOLD";
@@ -8,17 +31,73 @@ const COOLDOWN_CPU_THRESHOLD_ENV: &str = "COOLDOWN_CPU_THRESHOLD";
 const COOLDOWN_DURATION_SECS_ENV: &str = "COOLDOWN_DURATION_SECS";
 const MIN_CONTAINERS_PER_FUNCTION_ENV: &str = "MIN_CONTAINERS_PER_FUNCTION";
 const MAX_CONTAINERS_PER_FUNCTION_ENV: &str = "MAX_CONTAINERS_PER_FUNCTION";
+const MAX_CONTAINERS_PER_NAMESPACE_ENV: &str = "MAX_CONTAINERS_PER_NAMESPACE";
+const MAX_CONTAINERS_PER_HOST_ENV: &str = "MAX_CONTAINERS_PER_HOST";
+const MAX_HOST_CPU_PERCENT_ENV: &str = "MAX_HOST_CPU_PERCENT";
+const MAX_HOST_MEMORY_PERCENT_ENV: &str = "MAX_HOST_MEMORY_PERCENT";
+const MAX_CONCURRENT_STARTS_ENV: &str = "MAX_CONCURRENT_STARTS";
+const HIBERNATE_AFTER_DAYS_ENV: &str = "HIBERNATE_AFTER_DAYS";
 const POLL_INTERVAL_SECS_ENV: &str = "POLL_INTERVAL_SECS";
+const IDLE_POLL_INTERVAL_SECS_ENV: &str = "IDLE_POLL_INTERVAL_SECS";
+const MAX_PARALLEL_POOL_CHECKS_ENV: &str = "MAX_PARALLEL_POOL_CHECKS";
+const POOL_CHECK_TIMEOUT_SECS_ENV: &str = "POOL_CHECK_TIMEOUT_SECS";
+const COLD_START_TIMEOUT_SECS_ENV: &str = "COLD_START_TIMEOUT_SECS";
 const PERSISTENCE_ENABLED_ENV: &str = "PERSISTENCE_ENABLED";
+const PERSISTENCE_BATCH_SIZE_ENV: &str = "PERSISTENCE_BATCH_SIZE";
+const PERSISTENCE_SCAN_PAGE_SIZE_ENV: &str = "PERSISTENCE_SCAN_PAGE_SIZE";
+const POOL_STATE_TTL_SECS_ENV: &str = "POOL_STATE_TTL_SECS";
+const POOL_STATE_MAX_AGE_SECS_ENV: &str = "POOL_STATE_MAX_AGE_SECS";
+const SHARED_ROUTING_ENV: &str = "SHARED_ROUTING";
+const LEADER_LEASE_SECS_ENV: &str = "LEADER_LEASE_SECS";
 
-// Prometheus configuration environment variables
+// Metrics configuration environment variables
+const METRICS_SOURCE_ENV: &str = "METRICS_SOURCE";
+const CGROUP_ROOT_ENV: &str = "CGROUP_ROOT";
 const USE_PROMETHEUS_METRICS_ENV: &str = "USE_PROMETHEUS_METRICS";
 const PROMETHEUS_URL_ENV: &str = "PROMETHEUS_URL";
 const FALLBACK_TO_DOCKER_ENV: &str = "FALLBACK_TO_DOCKER";
+const PROMETHEUS_CONTAINER_ID_PATTERN_ENV: &str = "PROMETHEUS_CONTAINER_ID_PATTERN";
+const PROMETHEUS_USERNAME_ENV: &str = "PROMETHEUS_USERNAME";
+const PROMETHEUS_PASSWORD_ENV: &str = "PROMETHEUS_PASSWORD";
+const PROMETHEUS_BEARER_TOKEN_ENV: &str = "PROMETHEUS_BEARER_TOKEN";
+const PROMETHEUS_CA_CERT_ENV: &str = "PROMETHEUS_CA_CERT";
 
 /// Default maximum function size (10MB)
 pub const DEFAULT_MAX_FUNCTION_SIZE_VALUE: usize = 10 * 1024 * 1024;
 
+/// Default maximum invocation request body size (32MB)
+pub const DEFAULT_MAX_REQUEST_SIZE_VALUE: usize = 32 * 1024 * 1024;
+
+/// Default maximum invocation response body size (32MB)
+pub const DEFAULT_MAX_RESPONSE_SIZE_VALUE: usize = 32 * 1024 * 1024;
+
+/// Default cold starts a single client IP may trigger per minute
+pub const DEFAULT_COLD_START_BUDGET_PER_SOURCE: u32 = 30;
+
+/// Default cold starts a single namespace may see per minute
+pub const DEFAULT_COLD_START_BUDGET_PER_NAMESPACE: u32 = 120;
+
+/// Default maximum namespace import archive size (512MB)
+pub const DEFAULT_MAX_IMPORT_SIZE_VALUE: usize = 512 * 1024 * 1024;
+
+/// Default hours a deleted function stays restorable (7 days)
+pub const DEFAULT_TRASH_RETENTION_HOURS: u64 = 168;
+
+/// Default days a preview instance lives after its latest deploy
+pub const DEFAULT_PREVIEW_TTL_DAYS: u64 = 7;
+
+/// Longest a preview instance may be configured to live, ten years
+const MAX_PREVIEW_TTL_DAYS: u64 = 3650;
+
+/// Default largest response body kept in the response cache (1MB)
+pub const DEFAULT_RESPONSE_CACHE_MAX_BYTES: usize = 1024 * 1024;
+
+/// Default longest a response stays in the response cache (1 hour)
+pub const DEFAULT_RESPONSE_CACHE_MAX_TTL_SECS: u64 = 60 * 60;
+
+/// Default time between reloads of the dependency advisories (1 hour)
+pub const DEFAULT_DEPENDENCY_ADVISORIES_REFRESH_SECS: u64 = 60 * 60;
+
 // Autoscaling defaults
 pub const DEFAULT_CPU_OVERLOAD_THRESHOLD: f64 = 70.0;
 pub const DEFAULT_MEMORY_OVERLOAD_THRESHOLD: f64 = 70.0; // 200 MB
@@ -26,10 +105,22 @@ pub const DEFAULT_COOLDOWN_CPU_THRESHOLD: f64 = 0.0;
 pub const DEFAULT_COOLDOWN_DURATION_SECS: u64 = 30;
 pub const DEFAULT_MIN_CONTAINERS_PER_FUNCTION: usize = 1;
 pub const DEFAULT_MAX_CONTAINERS_PER_FUNCTION: usize = 10;
+pub const DEFAULT_MAX_CONCURRENT_STARTS: usize = 4;
 pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 1;
+pub const DEFAULT_IDLE_POLL_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_MAX_PARALLEL_POOL_CHECKS: usize = 16;
+pub const DEFAULT_POOL_CHECK_TIMEOUT_SECS: u64 = 5;
+pub const DEFAULT_COLD_START_TIMEOUT_SECS: u64 = 30;
 pub const DEFAULT_PERSISTENCE_ENABLED: bool = true;
+pub const DEFAULT_PERSISTENCE_BATCH_SIZE: usize = 20;
+pub const DEFAULT_PERSISTENCE_SCAN_PAGE_SIZE: usize = 500;
+pub const DEFAULT_POOL_STATE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_POOL_STATE_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+pub const DEFAULT_SHARED_ROUTING: bool = false;
+pub const DEFAULT_LEADER_LEASE_SECS: u64 = 15;
 
-// Prometheus defaults
+// Metrics defaults
+pub const DEFAULT_METRICS_SOURCE: &str = "prometheus";
 pub const DEFAULT_USE_PROMETHEUS_METRICS: bool = false;
 pub const DEFAULT_PROMETHEUS_URL: &str = "http://prometheus:9090";
 pub const DEFAULT_FALLBACK_TO_DOCKER: bool = true;
@@ -49,16 +140,67 @@ pub struct AutoscalingConfig {
     pub min_containers_per_function: usize,
     /// Maximum number of containers allowed per function
     pub max_containers_per_function: usize,
+    /// Most containers the functions of one namespace run together; unlimited when unset
+    pub max_containers_per_namespace: Option<usize>,
+    /// Most function containers on the Docker host; unlimited when unset
+    pub max_containers_per_host: Option<usize>,
+    /// Most CPU the containers' limits may commit, in percent of the host's CPUs
+    pub max_host_cpu_percent: Option<f64>,
+    /// Most memory the containers' limits may commit, in percent of the host's memory
+    pub max_host_memory_percent: Option<f64>,
+    /// Containers started on the Docker host at the same time; further starts queue
+    pub max_concurrent_starts: usize,
+    /// Days without invocations after which a namespace is hibernated; never when unset
+    pub hibernate_after_days: Option<u64>,
     /// Interval for polling container metrics (seconds)
     pub poll_interval_secs: u64,
+    /// Longest interval between metric polls of a pool without traffic (seconds)
+    pub idle_poll_interval_secs: u64,
+    /// Pools whose metrics are read at the same time
+    pub max_parallel_pool_checks: usize,
+    /// How long reading a pool's metrics may take before the pool is skipped until the
+    /// next poll (seconds)
+    pub pool_check_timeout_secs: u64,
+    /// How long a request waits for its function's container to start before it's
+    /// answered with a 503 (seconds)
+    pub cold_start_timeout_secs: u64,
+    /// Where container metrics come from: `prometheus`, `cgroup` or `docker`
+    pub metrics_source: String,
+    /// Host cgroup hierarchy read when `metrics_source` is `cgroup`
+    pub cgroup_root: PathBuf,
     /// Whether to use Prometheus for metrics collection
     pub use_prometheus_metrics: bool,
     /// Prometheus server URL
     pub prometheus_url: String,
     /// Whether to fallback to Docker stats if Prometheus fails
     pub fallback_to_docker: bool,
+    /// cAdvisor `id` label pattern (`{id}` = container ID); auto-detected when unset
+    pub prometheus_container_id_pattern: Option<String>,
+    /// Basic auth user for the metrics endpoint
+    pub prometheus_username: Option<String>,
+    /// Basic auth password for the metrics endpoint
+    pub prometheus_password: Option<String>,
+    /// Bearer token for the metrics endpoint (exclusive with basic auth)
+    pub prometheus_bearer_token: Option<String>,
+    /// PEM bundle with extra CA certificates for the metrics endpoint
+    pub prometheus_ca_cert: Option<PathBuf>,
     /// Whether to enable persistence for autoscaling state
     pub persistence_enabled: bool,
+    /// Number of pools loaded in parallel when restoring persisted state
+    pub persistence_batch_size: usize,
+    /// Keys Redis looks at per `SCAN` call when listing persisted pools
+    pub persistence_scan_page_size: usize,
+    /// How long a pool state is kept after its last save or invocation (seconds)
+    pub pool_state_ttl_secs: u64,
+    /// Pool states older than this are not restored and their containers are removed
+    /// (seconds, 0 restores states of any age)
+    pub pool_state_max_age_secs: u64,
+    /// Whether controller replicas share the pools: one leader scales them, every replica
+    /// routes invocations from the state it persists. Concurrency limits are only
+    /// enforced on the leader
+    pub shared_routing: bool,
+    /// How long the scaling leader holds its lease without renewing it
+    pub leader_lease_secs: u64,
 }
 
 impl Default for AutoscalingConfig {
@@ -70,11 +212,232 @@ impl Default for AutoscalingConfig {
             cooldown_duration_secs: DEFAULT_COOLDOWN_DURATION_SECS,
             min_containers_per_function: DEFAULT_MIN_CONTAINERS_PER_FUNCTION,
             max_containers_per_function: DEFAULT_MAX_CONTAINERS_PER_FUNCTION,
+            max_containers_per_namespace: None,
+            max_containers_per_host: None,
+            max_host_cpu_percent: None,
+            max_host_memory_percent: None,
+            max_concurrent_starts: DEFAULT_MAX_CONCURRENT_STARTS,
+            hibernate_after_days: None,
             poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            idle_poll_interval_secs: DEFAULT_IDLE_POLL_INTERVAL_SECS,
+            max_parallel_pool_checks: DEFAULT_MAX_PARALLEL_POOL_CHECKS,
+            pool_check_timeout_secs: DEFAULT_POOL_CHECK_TIMEOUT_SECS,
+            cold_start_timeout_secs: DEFAULT_COLD_START_TIMEOUT_SECS,
+            metrics_source: DEFAULT_METRICS_SOURCE.to_string(),
+            cgroup_root: PathBuf::from(DEFAULT_CGROUP_ROOT),
             use_prometheus_metrics: DEFAULT_USE_PROMETHEUS_METRICS,
             prometheus_url: DEFAULT_PROMETHEUS_URL.to_string(),
             fallback_to_docker: DEFAULT_FALLBACK_TO_DOCKER,
+            prometheus_container_id_pattern: None,
+            prometheus_username: None,
+            prometheus_password: None,
+            prometheus_bearer_token: None,
+            prometheus_ca_cert: None,
             persistence_enabled: DEFAULT_PERSISTENCE_ENABLED,
+            persistence_batch_size: DEFAULT_PERSISTENCE_BATCH_SIZE,
+            persistence_scan_page_size: DEFAULT_PERSISTENCE_SCAN_PAGE_SIZE,
+            pool_state_ttl_secs: DEFAULT_POOL_STATE_TTL_SECS,
+            pool_state_max_age_secs: DEFAULT_POOL_STATE_MAX_AGE_SECS,
+            shared_routing: DEFAULT_SHARED_ROUTING,
+            leader_lease_secs: DEFAULT_LEADER_LEASE_SECS,
+        }
+    }
+}
+
+impl AutoscalingConfig {
+    /// Credentials for the metrics endpoint
+    pub fn metrics_auth(&self) -> MetricsAuth {
+        match (&self.prometheus_bearer_token, &self.prometheus_username) {
+            (Some(token), _) => MetricsAuth::Bearer(token.clone()),
+            (None, Some(username)) => MetricsAuth::Basic {
+                username: username.clone(),
+                password: self.prometheus_password.clone(),
+            },
+            (None, None) => MetricsAuth::None,
+        }
+    }
+
+    /// This controller's part among the replicas, named after its host, when they share
+    /// the pools
+    pub fn replica_config(&self) -> Option<ReplicaConfig> {
+        if !self.shared_routing {
+            return None;
+        }
+        // Replicas may share a host name, e.g. containers sharing a network namespace
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "controller".to_string());
+        let suffix = Uuid::new_v4().simple().to_string();
+        Some(ReplicaConfig {
+            replica_id: format!("{}-{}", host, &suffix[..8]),
+            lease_ttl: Duration::from_secs(self.leader_lease_secs),
+        })
+    }
+
+    /// Age past which persisted pool states aren't restored, `None` for no limit
+    pub fn pool_state_max_age(&self) -> Option<Duration> {
+        (self.pool_state_max_age_secs > 0)
+            .then(|| Duration::from_secs(self.pool_state_max_age_secs))
+    }
+
+    /// Where container metrics are read from
+    pub fn metrics_source(&self) -> MetricsSource {
+        match self.metrics_source.as_str() {
+            "cgroup" => MetricsSource::Cgroup {
+                root: self.cgroup_root.clone(),
+            },
+            "docker" => MetricsSource::DockerStats,
+            _ => MetricsSource::Prometheus,
+        }
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        for (key, value) in [
+            ("cpu_overload_threshold", self.cpu_overload_threshold),
+            ("memory_overload_threshold", self.memory_overload_threshold),
+            ("cooldown_cpu_threshold", self.cooldown_cpu_threshold),
+        ] {
+            if !(0.0..=100.0).contains(&value) {
+                errors.push(format!(
+                    "autoscaling.{} must be a percentage between 0 and 100, got {}",
+                    key, value
+                ));
+            }
+        }
+
+        if self.cooldown_cpu_threshold > self.cpu_overload_threshold {
+            errors.push(format!(
+                "autoscaling.cooldown_cpu_threshold ({}) must not exceed autoscaling.cpu_overload_threshold ({})",
+                self.cooldown_cpu_threshold, self.cpu_overload_threshold
+            ));
+        }
+
+        if self.max_containers_per_function == 0 {
+            errors.push("autoscaling.max_containers_per_function must be at least 1".to_string());
+        }
+
+        for (key, value) in [
+            (
+                "max_containers_per_namespace",
+                self.max_containers_per_namespace,
+            ),
+            ("max_containers_per_host", self.max_containers_per_host),
+        ] {
+            if value == Some(0) {
+                errors.push(format!("autoscaling.{} must be at least 1", key));
+            }
+        }
+
+        for (key, value) in [
+            ("max_host_cpu_percent", self.max_host_cpu_percent),
+            ("max_host_memory_percent", self.max_host_memory_percent),
+        ] {
+            if value.is_some_and(|percent| percent <= 0.0) {
+                errors.push(format!("autoscaling.{} must be above 0", key));
+            }
+        }
+
+        if self.hibernate_after_days == Some(0) {
+            errors.push("autoscaling.hibernate_after_days must be at least 1".to_string());
+        }
+
+        if self.min_containers_per_function > self.max_containers_per_function {
+            errors.push(format!(
+                "autoscaling.min_containers_per_function ({}) must not exceed autoscaling.max_containers_per_function ({})",
+                self.min_containers_per_function, self.max_containers_per_function
+            ));
+        }
+
+        match self.metrics_source.as_str() {
+            "prometheus" | "docker" => {}
+            "cgroup" => {
+                if !self.cgroup_root.is_dir() {
+                    errors.push(format!(
+                        "autoscaling.cgroup_root: {} does not exist; mount the host's /sys/fs/cgroup",
+                        self.cgroup_root.display()
+                    ));
+                }
+            }
+            other => errors.push(format!(
+                "autoscaling.metrics_source must be 'prometheus', 'cgroup' or 'docker', got '{}'",
+                other
+            )),
+        }
+
+        if let Some(pattern) = &self.prometheus_container_id_pattern {
+            if !pattern.contains("{id}") {
+                errors.push(format!(
+                    "autoscaling.prometheus_container_id_pattern must contain the {{id}} placeholder, got '{}'",
+                    pattern
+                ));
+            }
+        }
+
+        if self.prometheus_bearer_token.is_some() && self.prometheus_username.is_some() {
+            errors.push(
+                "autoscaling.prometheus_bearer_token and autoscaling.prometheus_username are mutually exclusive"
+                    .to_string(),
+            );
+        }
+        if self.prometheus_password.is_some() && self.prometheus_username.is_none() {
+            errors.push(
+                "autoscaling.prometheus_password requires autoscaling.prometheus_username"
+                    .to_string(),
+            );
+        }
+        if let Some(path) = &self.prometheus_ca_cert {
+            if !path.is_file() {
+                errors.push(format!(
+                    "autoscaling.prometheus_ca_cert: {} does not exist",
+                    path.display()
+                ));
+            }
+        }
+
+        if self.poll_interval_secs == 0 {
+            errors.push("autoscaling.poll_interval_secs must be at least 1".to_string());
+        }
+        if self.idle_poll_interval_secs < self.poll_interval_secs {
+            errors.push(
+                "autoscaling.idle_poll_interval_secs must be at least poll_interval_secs"
+                    .to_string(),
+            );
+        }
+        if self.max_parallel_pool_checks == 0 {
+            errors.push("autoscaling.max_parallel_pool_checks must be at least 1".to_string());
+        }
+        if self.pool_check_timeout_secs == 0 {
+            errors.push("autoscaling.pool_check_timeout_secs must be at least 1".to_string());
+        }
+        if self.cold_start_timeout_secs == 0 {
+            errors.push("autoscaling.cold_start_timeout_secs must be at least 1".to_string());
+        }
+        if self.max_concurrent_starts == 0 {
+            errors.push("autoscaling.max_concurrent_starts must be at least 1".to_string());
+        }
+
+        if self.persistence_batch_size == 0 {
+            errors.push("persistence.batch_size must be at least 1".to_string());
+        }
+        if self.persistence_scan_page_size == 0 {
+            errors.push("persistence.scan_page_size must be at least 1".to_string());
+        }
+        if self.pool_state_ttl_secs < 60 {
+            errors.push("persistence.state_ttl_secs must be at least 60".to_string());
+        }
+        // States expire before they could turn stale
+        if self.pool_state_max_age_secs > self.pool_state_ttl_secs {
+            errors.push(
+                "persistence.state_max_age_secs must not exceed persistence.state_ttl_secs"
+                    .to_string(),
+            );
+        }
+
+        // Replicas share the pools through the persisted state
+        if self.shared_routing && !self.persistence_enabled {
+            errors.push("persistence.shared_routing requires persistence.enabled".to_string());
+        }
+        if self.leader_lease_secs < 3 {
+            errors.push("persistence.leader_lease_secs must be at least 3".to_string());
         }
     }
 }
@@ -85,66 +448,502 @@ pub struct InvokFunctionConfig {
     /// Maximum function size in bytes
     pub max_function_size: usize,
 
+    /// Maximum invocation request body in bytes (functions may lower it)
+    pub max_request_size: usize,
+
+    /// Maximum invocation response body in bytes (functions may lower it)
+    pub max_response_size: usize,
+
+    /// Cold starts a single client IP may trigger per minute (0 disables the limit)
+    pub cold_start_budget_per_source: u32,
+
+    /// Cold starts a single namespace may see per minute (0 disables the limit)
+    pub cold_start_budget_per_namespace: u32,
+
+    /// Maximum namespace import archive in bytes, compressed and unpacked
+    pub max_import_size: usize,
+
+    /// Hours a deleted function stays in the trash before it is purged
+    pub trash_retention_hours: u64,
+
+    /// Days a preview instance lives after its latest deploy before it is removed
+    pub preview_ttl_days: u64,
+
+    /// Largest response body the response cache keeps, in bytes (0 disables the cache)
+    pub response_cache_max_bytes: usize,
+
+    /// Longest a response stays cached, whatever its `Cache-Control` allows
+    pub response_cache_max_ttl_secs: u64,
+
+    /// URLs notified of every purge, e.g. to purge an external CDN as well
+    pub purge_webhooks: Vec<String>,
+
+    /// Key the purge notifications are signed with (HMAC-SHA256), if set
+    pub purge_webhook_secret: Option<String>,
+
+    /// Whether owners may run commands in their functions' containers (`invok exec`)
+    pub allow_exec: bool,
+
+    /// Recorded versions kept per function, newest first; unlimited when unset
+    pub keep_versions: Option<usize>,
+
+    /// Most bytes of recorded archives a namespace keeps; unlimited when unset
+    pub max_artifact_bytes_per_namespace: Option<u64>,
+
+    /// File or http(s) URL of the advisory database deployed dependencies are checked
+    /// against; no checks when unset
+    pub dependency_advisories: Option<String>,
+
+    /// Seconds between reloads of the dependency advisories
+    pub dependency_advisories_refresh_secs: u64,
+
     /// Autoscaling configuration
     pub autoscaling: AutoscalingConfig,
 }
 
 impl InvokFunctionConfig {
-    /// Load function configuration from environment
-    pub fn from_env() -> Self {
-        let max_function_size = env::var(MAX_FUNCTION_SIZE_ENV_VARIABLE)
-            .ok()
-            .and_then(|s| s.parse::<usize>().ok())
-            .unwrap_or(DEFAULT_MAX_FUNCTION_SIZE_VALUE);
+    /// Load function configuration from environment variables, falling back to the
+    /// `function`, `autoscaling` and `persistence` sections of the config file.
+    /// Problems are appended to `errors`.
+    pub fn load(file: &FileConfig, errors: &mut Vec<String>) -> Self {
+        let max_function_size = resolve(
+            MAX_FUNCTION_SIZE_ENV_VARIABLE,
+            "function.max_function_size",
+            file.function.max_function_size,
+            errors,
+        )
+        .unwrap_or(DEFAULT_MAX_FUNCTION_SIZE_VALUE);
+
+        let max_request_size = resolve(
+            MAX_REQUEST_SIZE_ENV_VARIABLE,
+            "function.max_request_size",
+            file.function.max_request_size,
+            errors,
+        )
+        .unwrap_or(DEFAULT_MAX_REQUEST_SIZE_VALUE);
+
+        let max_response_size = resolve(
+            MAX_RESPONSE_SIZE_ENV_VARIABLE,
+            "function.max_response_size",
+            file.function.max_response_size,
+            errors,
+        )
+        .unwrap_or(DEFAULT_MAX_RESPONSE_SIZE_VALUE);
+
+        let cold_start_budget_per_source = resolve(
+            COLD_START_BUDGET_PER_SOURCE_ENV_VARIABLE,
+            "function.cold_start_budget_per_source",
+            file.function.cold_start_budget_per_source,
+            errors,
+        )
+        .unwrap_or(DEFAULT_COLD_START_BUDGET_PER_SOURCE);
+
+        let cold_start_budget_per_namespace = resolve(
+            COLD_START_BUDGET_PER_NAMESPACE_ENV_VARIABLE,
+            "function.cold_start_budget_per_namespace",
+            file.function.cold_start_budget_per_namespace,
+            errors,
+        )
+        .unwrap_or(DEFAULT_COLD_START_BUDGET_PER_NAMESPACE);
 
+        let max_import_size = resolve(
+            MAX_IMPORT_SIZE_ENV_VARIABLE,
+            "function.max_import_size",
+            file.function.max_import_size,
+            errors,
+        )
+        .unwrap_or(DEFAULT_MAX_IMPORT_SIZE_VALUE);
+
+        let trash_retention_hours = resolve(
+            TRASH_RETENTION_HOURS_ENV_VARIABLE,
+            "function.trash_retention_hours",
+            file.function.trash_retention_hours,
+            errors,
+        )
+        .unwrap_or(DEFAULT_TRASH_RETENTION_HOURS);
+
+        let preview_ttl_days = resolve(
+            PREVIEW_TTL_DAYS_ENV_VARIABLE,
+            "function.preview_ttl_days",
+            file.function.preview_ttl_days,
+            errors,
+        )
+        .unwrap_or(DEFAULT_PREVIEW_TTL_DAYS);
+
+        let response_cache_max_bytes = resolve(
+            RESPONSE_CACHE_MAX_BYTES_ENV_VARIABLE,
+            "function.response_cache_max_bytes",
+            file.function.response_cache_max_bytes,
+            errors,
+        )
+        .unwrap_or(DEFAULT_RESPONSE_CACHE_MAX_BYTES);
+
+        let response_cache_max_ttl_secs = resolve(
+            RESPONSE_CACHE_MAX_TTL_SECS_ENV_VARIABLE,
+            "function.response_cache_max_ttl_secs",
+            file.function.response_cache_max_ttl_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_RESPONSE_CACHE_MAX_TTL_SECS);
+
+        let purge_webhooks: String = resolve(
+            PURGE_WEBHOOKS_ENV_VARIABLE,
+            "function.purge_webhooks",
+            file.function.purge_webhooks.clone(),
+            errors,
+        )
+        .unwrap_or_default();
+        let purge_webhooks: Vec<String> = purge_webhooks
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+        for url in &purge_webhooks {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!(
+                    "function.purge_webhooks: '{}' is not an http(s) URL",
+                    url
+                ));
+            }
+        }
+
+        let purge_webhook_secret = resolve(
+            PURGE_WEBHOOK_SECRET_ENV_VARIABLE,
+            "function.purge_webhook_secret",
+            file.function.purge_webhook_secret.clone(),
+            errors,
+        );
+
+        let allow_exec = resolve(
+            ALLOW_EXEC_ENV_VARIABLE,
+            "function.allow_exec",
+            file.function.allow_exec,
+            errors,
+        )
+        .unwrap_or(false);
+
+        let keep_versions = resolve(
+            KEEP_VERSIONS_ENV_VARIABLE,
+            "function.keep_versions",
+            file.function.keep_versions,
+            errors,
+        );
+
+        let max_artifact_bytes_per_namespace = resolve(
+            MAX_ARTIFACT_BYTES_PER_NAMESPACE_ENV_VARIABLE,
+            "function.max_artifact_bytes_per_namespace",
+            file.function.max_artifact_bytes_per_namespace,
+            errors,
+        );
+
+        let dependency_advisories = resolve(
+            DEPENDENCY_ADVISORIES_ENV_VARIABLE,
+            "function.dependency_advisories",
+            file.function.dependency_advisories.clone(),
+            errors,
+        );
+
+        let dependency_advisories_refresh_secs = resolve(
+            DEPENDENCY_ADVISORIES_REFRESH_SECS_ENV_VARIABLE,
+            "function.dependency_advisories_refresh_secs",
+            file.function.dependency_advisories_refresh_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_DEPENDENCY_ADVISORIES_REFRESH_SECS);
+
+        if max_request_size == 0 {
+            errors.push("function.max_request_size must be at least 1".to_string());
+        }
+        if max_response_size == 0 {
+            errors.push("function.max_response_size must be at least 1".to_string());
+        }
+        if max_import_size == 0 {
+            errors.push("function.max_import_size must be at least 1".to_string());
+        }
+        if trash_retention_hours == 0 {
+            errors.push("function.trash_retention_hours must be at least 1".to_string());
+        }
+        if !(1..=MAX_PREVIEW_TTL_DAYS).contains(&preview_ttl_days) {
+            errors.push(format!(
+                "function.preview_ttl_days must be between 1 and {}",
+                MAX_PREVIEW_TTL_DAYS
+            ));
+        }
+        if response_cache_max_ttl_secs == 0 {
+            errors.push("function.response_cache_max_ttl_secs must be at least 1".to_string());
+        }
+        if keep_versions == Some(0) {
+            errors.push("function.keep_versions must be at least 1".to_string());
+        }
+        if max_artifact_bytes_per_namespace == Some(0) {
+            errors.push("function.max_artifact_bytes_per_namespace must be at least 1".to_string());
+        }
+        if dependency_advisories.as_deref() == Some("") {
+            errors.push("function.dependency_advisories must not be empty".to_string());
+        }
+        if dependency_advisories_refresh_secs == 0 {
+            errors
+                .push("function.dependency_advisories_refresh_secs must be at least 1".to_string());
+        }
+
+        let scaling = &file.autoscaling;
         let autoscaling = AutoscalingConfig {
-            cpu_overload_threshold: env::var(CPU_OVERLOAD_THRESHOLD_ENV)
-                .ok()
-                .and_then(|s| s.parse::<f64>().ok())
-                .unwrap_or(DEFAULT_CPU_OVERLOAD_THRESHOLD),
-            memory_overload_threshold: env::var(MEMORY_OVERLOAD_THRESHOLD_ENV)
-                .ok()
-                .and_then(|s| s.parse::<f64>().ok())
-                .unwrap_or(DEFAULT_MEMORY_OVERLOAD_THRESHOLD),
-            cooldown_cpu_threshold: env::var(COOLDOWN_CPU_THRESHOLD_ENV)
-                .ok()
-                .and_then(|s| s.parse::<f64>().ok())
-                .unwrap_or(DEFAULT_COOLDOWN_CPU_THRESHOLD),
-            cooldown_duration_secs: env::var(COOLDOWN_DURATION_SECS_ENV)
-                .ok()
-                .and_then(|s| s.parse::<u64>().ok())
-                .unwrap_or(DEFAULT_COOLDOWN_DURATION_SECS),
-            min_containers_per_function: env::var(MIN_CONTAINERS_PER_FUNCTION_ENV)
-                .ok()
-                .and_then(|s| s.parse::<usize>().ok())
-                .unwrap_or(DEFAULT_MIN_CONTAINERS_PER_FUNCTION),
-            max_containers_per_function: env::var(MAX_CONTAINERS_PER_FUNCTION_ENV)
-                .ok()
-                .and_then(|s| s.parse::<usize>().ok())
-                .unwrap_or(DEFAULT_MAX_CONTAINERS_PER_FUNCTION),
-            poll_interval_secs: env::var(POLL_INTERVAL_SECS_ENV)
-                .ok()
-                .and_then(|s| s.parse::<u64>().ok())
-                .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
-            use_prometheus_metrics: env::var(USE_PROMETHEUS_METRICS_ENV)
-                .ok()
-                .and_then(|s| s.parse::<bool>().ok())
-                .unwrap_or(DEFAULT_USE_PROMETHEUS_METRICS),
-            prometheus_url: env::var(PROMETHEUS_URL_ENV)
-                .unwrap_or_else(|_| DEFAULT_PROMETHEUS_URL.to_string()),
-            fallback_to_docker: env::var(FALLBACK_TO_DOCKER_ENV)
-                .ok()
-                .and_then(|s| s.parse::<bool>().ok())
-                .unwrap_or(DEFAULT_FALLBACK_TO_DOCKER),
-            persistence_enabled: env::var(PERSISTENCE_ENABLED_ENV)
-                .ok()
-                .and_then(|s| s.parse::<bool>().ok())
-                .unwrap_or(DEFAULT_PERSISTENCE_ENABLED),
+            cpu_overload_threshold: resolve(
+                CPU_OVERLOAD_THRESHOLD_ENV,
+                "autoscaling.cpu_overload_threshold",
+                scaling.cpu_overload_threshold,
+                errors,
+            )
+            .unwrap_or(DEFAULT_CPU_OVERLOAD_THRESHOLD),
+            memory_overload_threshold: resolve(
+                MEMORY_OVERLOAD_THRESHOLD_ENV,
+                "autoscaling.memory_overload_threshold",
+                scaling.memory_overload_threshold,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MEMORY_OVERLOAD_THRESHOLD),
+            cooldown_cpu_threshold: resolve(
+                COOLDOWN_CPU_THRESHOLD_ENV,
+                "autoscaling.cooldown_cpu_threshold",
+                scaling.cooldown_cpu_threshold,
+                errors,
+            )
+            .unwrap_or(DEFAULT_COOLDOWN_CPU_THRESHOLD),
+            cooldown_duration_secs: resolve(
+                COOLDOWN_DURATION_SECS_ENV,
+                "autoscaling.cooldown_duration_secs",
+                scaling.cooldown_duration_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_COOLDOWN_DURATION_SECS),
+            min_containers_per_function: resolve(
+                MIN_CONTAINERS_PER_FUNCTION_ENV,
+                "autoscaling.min_containers_per_function",
+                scaling.min_containers_per_function,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MIN_CONTAINERS_PER_FUNCTION),
+            max_containers_per_function: resolve(
+                MAX_CONTAINERS_PER_FUNCTION_ENV,
+                "autoscaling.max_containers_per_function",
+                scaling.max_containers_per_function,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MAX_CONTAINERS_PER_FUNCTION),
+            max_containers_per_namespace: resolve(
+                MAX_CONTAINERS_PER_NAMESPACE_ENV,
+                "autoscaling.max_containers_per_namespace",
+                scaling.max_containers_per_namespace,
+                errors,
+            ),
+            max_containers_per_host: resolve(
+                MAX_CONTAINERS_PER_HOST_ENV,
+                "autoscaling.max_containers_per_host",
+                scaling.max_containers_per_host,
+                errors,
+            ),
+            max_host_cpu_percent: resolve(
+                MAX_HOST_CPU_PERCENT_ENV,
+                "autoscaling.max_host_cpu_percent",
+                scaling.max_host_cpu_percent,
+                errors,
+            ),
+            max_host_memory_percent: resolve(
+                MAX_HOST_MEMORY_PERCENT_ENV,
+                "autoscaling.max_host_memory_percent",
+                scaling.max_host_memory_percent,
+                errors,
+            ),
+            max_concurrent_starts: resolve(
+                MAX_CONCURRENT_STARTS_ENV,
+                "autoscaling.max_concurrent_starts",
+                scaling.max_concurrent_starts,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_STARTS),
+            hibernate_after_days: resolve(
+                HIBERNATE_AFTER_DAYS_ENV,
+                "autoscaling.hibernate_after_days",
+                scaling.hibernate_after_days,
+                errors,
+            ),
+            poll_interval_secs: resolve(
+                POLL_INTERVAL_SECS_ENV,
+                "autoscaling.poll_interval_secs",
+                scaling.poll_interval_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+            idle_poll_interval_secs: resolve(
+                IDLE_POLL_INTERVAL_SECS_ENV,
+                "autoscaling.idle_poll_interval_secs",
+                scaling.idle_poll_interval_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_IDLE_POLL_INTERVAL_SECS),
+            max_parallel_pool_checks: resolve(
+                MAX_PARALLEL_POOL_CHECKS_ENV,
+                "autoscaling.max_parallel_pool_checks",
+                scaling.max_parallel_pool_checks,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MAX_PARALLEL_POOL_CHECKS),
+            pool_check_timeout_secs: resolve(
+                POOL_CHECK_TIMEOUT_SECS_ENV,
+                "autoscaling.pool_check_timeout_secs",
+                scaling.pool_check_timeout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_POOL_CHECK_TIMEOUT_SECS),
+            cold_start_timeout_secs: resolve(
+                COLD_START_TIMEOUT_SECS_ENV,
+                "autoscaling.cold_start_timeout_secs",
+                scaling.cold_start_timeout_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_COLD_START_TIMEOUT_SECS),
+            metrics_source: resolve(
+                METRICS_SOURCE_ENV,
+                "autoscaling.metrics_source",
+                scaling.metrics_source.clone(),
+                errors,
+            )
+            .unwrap_or_else(|| DEFAULT_METRICS_SOURCE.to_string()),
+            cgroup_root: resolve(
+                CGROUP_ROOT_ENV,
+                "autoscaling.cgroup_root",
+                scaling.cgroup_root.clone(),
+                errors,
+            )
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CGROUP_ROOT)),
+            use_prometheus_metrics: resolve(
+                USE_PROMETHEUS_METRICS_ENV,
+                "autoscaling.use_prometheus_metrics",
+                scaling.use_prometheus_metrics,
+                errors,
+            )
+            .unwrap_or(DEFAULT_USE_PROMETHEUS_METRICS),
+            prometheus_url: resolve(
+                PROMETHEUS_URL_ENV,
+                "autoscaling.prometheus_url",
+                scaling.prometheus_url.clone(),
+                errors,
+            )
+            .unwrap_or_else(|| DEFAULT_PROMETHEUS_URL.to_string()),
+            fallback_to_docker: resolve(
+                FALLBACK_TO_DOCKER_ENV,
+                "autoscaling.fallback_to_docker",
+                scaling.fallback_to_docker,
+                errors,
+            )
+            .unwrap_or(DEFAULT_FALLBACK_TO_DOCKER),
+            prometheus_container_id_pattern: resolve(
+                PROMETHEUS_CONTAINER_ID_PATTERN_ENV,
+                "autoscaling.prometheus_container_id_pattern",
+                scaling.prometheus_container_id_pattern.clone(),
+                errors,
+            ),
+            prometheus_username: resolve(
+                PROMETHEUS_USERNAME_ENV,
+                "autoscaling.prometheus_username",
+                scaling.prometheus_username.clone(),
+                errors,
+            ),
+            prometheus_password: resolve(
+                PROMETHEUS_PASSWORD_ENV,
+                "autoscaling.prometheus_password",
+                scaling.prometheus_password.clone(),
+                errors,
+            ),
+            prometheus_bearer_token: resolve(
+                PROMETHEUS_BEARER_TOKEN_ENV,
+                "autoscaling.prometheus_bearer_token",
+                scaling.prometheus_bearer_token.clone(),
+                errors,
+            ),
+            prometheus_ca_cert: resolve(
+                PROMETHEUS_CA_CERT_ENV,
+                "autoscaling.prometheus_ca_cert",
+                scaling.prometheus_ca_cert.clone(),
+                errors,
+            ),
+            persistence_enabled: resolve(
+                PERSISTENCE_ENABLED_ENV,
+                "persistence.enabled",
+                file.persistence.enabled,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PERSISTENCE_ENABLED),
+            persistence_batch_size: resolve(
+                PERSISTENCE_BATCH_SIZE_ENV,
+                "persistence.batch_size",
+                file.persistence.batch_size,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PERSISTENCE_BATCH_SIZE),
+            persistence_scan_page_size: resolve(
+                PERSISTENCE_SCAN_PAGE_SIZE_ENV,
+                "persistence.scan_page_size",
+                file.persistence.scan_page_size,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PERSISTENCE_SCAN_PAGE_SIZE),
+            pool_state_ttl_secs: resolve(
+                POOL_STATE_TTL_SECS_ENV,
+                "persistence.state_ttl_secs",
+                file.persistence.state_ttl_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_POOL_STATE_TTL_SECS),
+            pool_state_max_age_secs: resolve(
+                POOL_STATE_MAX_AGE_SECS_ENV,
+                "persistence.state_max_age_secs",
+                file.persistence.state_max_age_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_POOL_STATE_MAX_AGE_SECS),
+            shared_routing: resolve(
+                SHARED_ROUTING_ENV,
+                "persistence.shared_routing",
+                file.persistence.shared_routing,
+                errors,
+            )
+            .unwrap_or(DEFAULT_SHARED_ROUTING),
+            leader_lease_secs: resolve(
+                LEADER_LEASE_SECS_ENV,
+                "persistence.leader_lease_secs",
+                file.persistence.leader_lease_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_LEADER_LEASE_SECS),
         };
+        autoscaling.validate(errors);
 
         Self {
             max_function_size,
+            max_request_size,
+            max_response_size,
+            cold_start_budget_per_source,
+            cold_start_budget_per_namespace,
+            max_import_size,
+            trash_retention_hours,
+            preview_ttl_days,
+            response_cache_max_bytes,
+            response_cache_max_ttl_secs,
+            purge_webhooks,
+            purge_webhook_secret,
+            allow_exec,
+            keep_versions,
+            max_artifact_bytes_per_namespace,
+            dependency_advisories,
+            dependency_advisories_refresh_secs,
             autoscaling,
         }
     }
+
+    /// How long a preview instance lives after its latest deploy
+    pub fn preview_ttl(&self) -> Duration {
+        Duration::from_secs(self.preview_ttl_days.saturating_mul(24 * 60 * 60))
+    }
 }
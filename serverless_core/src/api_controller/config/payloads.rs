@@ -0,0 +1,205 @@
+use super::file::PayloadsSection;
+use super::resolve;
+
+const PAYLOAD_STORE_URL_ENV_VARIABLE: &str = "PAYLOAD_STORE_URL";
+const PAYLOAD_STORE_REGION_ENV_VARIABLE: &str = "PAYLOAD_STORE_REGION";
+const PAYLOAD_STORE_ACCESS_KEY_ENV_VARIABLE: &str = "PAYLOAD_STORE_ACCESS_KEY";
+const PAYLOAD_STORE_SECRET_KEY_ENV_VARIABLE: &str = "PAYLOAD_STORE_SECRET_KEY";
+const PAYLOAD_THRESHOLD_ENV_VARIABLE: &str = "PAYLOAD_THRESHOLD";
+const MAX_PAYLOAD_SIZE_ENV_VARIABLE: &str = "MAX_PAYLOAD_SIZE";
+const PAYLOAD_URL_TTL_SECS_ENV_VARIABLE: &str = "PAYLOAD_URL_TTL_SECS";
+
+/// Default region payload URLs are signed for
+const DEFAULT_PAYLOAD_STORE_REGION: &str = "us-east-1";
+
+/// Default size above which request bodies go through the store (8MB)
+const DEFAULT_PAYLOAD_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Default largest payload passed through the store (1GB)
+const DEFAULT_MAX_PAYLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Default lifetime of pre-signed payload URLs (15 minutes)
+const DEFAULT_PAYLOAD_URL_TTL_SECS: u64 = 15 * 60;
+
+/// Longest lifetime S3 allows pre-signed URLs (7 days)
+const MAX_PAYLOAD_URL_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Object storage large invocation payloads pass through
+#[derive(Debug, Clone)]
+pub struct InvokPayloadConfig {
+    /// S3-compatible bucket URL, path-style (e.g. `https://s3.eu-west-1.amazonaws.com/bucket`);
+    /// payloads are proxied in memory when unset
+    pub store_url: Option<String>,
+
+    /// Region the pre-signed URLs are signed for
+    pub region: String,
+
+    /// Access key id the URLs are signed with
+    pub access_key: Option<String>,
+
+    /// Secret access key the URLs are signed with
+    pub secret_key: Option<String>,
+
+    /// Request bodies declaring a larger `Content-Length` go through the store
+    pub threshold: u64,
+
+    /// Largest payload passed through the store, each way
+    pub max_size: u64,
+
+    /// How long pre-signed URLs stay valid
+    pub url_ttl_secs: u64,
+}
+
+impl InvokPayloadConfig {
+    /// Load configuration from environment variables, falling back to the `payloads`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &PayloadsSection, errors: &mut Vec<String>) -> Self {
+        let config = Self {
+            store_url: resolve(
+                PAYLOAD_STORE_URL_ENV_VARIABLE,
+                "payloads.store_url",
+                file.store_url.clone(),
+                errors,
+            ),
+            region: resolve(
+                PAYLOAD_STORE_REGION_ENV_VARIABLE,
+                "payloads.region",
+                file.region.clone(),
+                errors,
+            )
+            .unwrap_or_else(|| DEFAULT_PAYLOAD_STORE_REGION.to_string()),
+            access_key: resolve(
+                PAYLOAD_STORE_ACCESS_KEY_ENV_VARIABLE,
+                "payloads.access_key",
+                file.access_key.clone(),
+                errors,
+            ),
+            secret_key: resolve(
+                PAYLOAD_STORE_SECRET_KEY_ENV_VARIABLE,
+                "payloads.secret_key",
+                file.secret_key.clone(),
+                errors,
+            ),
+            threshold: resolve(
+                PAYLOAD_THRESHOLD_ENV_VARIABLE,
+                "payloads.threshold",
+                file.threshold,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PAYLOAD_THRESHOLD),
+            max_size: resolve(
+                MAX_PAYLOAD_SIZE_ENV_VARIABLE,
+                "payloads.max_size",
+                file.max_size,
+                errors,
+            )
+            .unwrap_or(DEFAULT_MAX_PAYLOAD_SIZE),
+            url_ttl_secs: resolve(
+                PAYLOAD_URL_TTL_SECS_ENV_VARIABLE,
+                "payloads.url_ttl_secs",
+                file.url_ttl_secs,
+                errors,
+            )
+            .unwrap_or(DEFAULT_PAYLOAD_URL_TTL_SECS),
+        };
+        config.validate(errors);
+        config
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        if let Some(url) = &self.store_url {
+            let parsed = reqwest::Url::parse(url).ok();
+            if !parsed.as_ref().is_some_and(|parsed| {
+                matches!(parsed.scheme(), "http" | "https") && parsed.has_host()
+            }) {
+                errors.push(format!(
+                    "payloads.store_url: '{}' is not an http(s) URL",
+                    url
+                ));
+            }
+            if self.access_key.is_none() || self.secret_key.is_none() {
+                errors.push(
+                    "payloads.access_key and payloads.secret_key are required with payloads.store_url"
+                        .to_string(),
+                );
+            }
+        }
+        if self.threshold == 0 {
+            errors.push("payloads.threshold must be at least 1".to_string());
+        }
+        if self.max_size < self.threshold {
+            errors.push(format!(
+                "payloads.max_size ({}) must not be below payloads.threshold ({})",
+                self.max_size, self.threshold
+            ));
+        }
+        if !(1..=MAX_PAYLOAD_URL_TTL_SECS).contains(&self.url_ttl_secs) {
+            errors.push(format!(
+                "payloads.url_ttl_secs must be between 1 and {}",
+                MAX_PAYLOAD_URL_TTL_SECS
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> InvokPayloadConfig {
+        InvokPayloadConfig {
+            store_url: Some("https://s3.eu-west-1.amazonaws.com/payloads".to_string()),
+            region: DEFAULT_PAYLOAD_STORE_REGION.to_string(),
+            access_key: Some("access".to_string()),
+            secret_key: Some("secret".to_string()),
+            threshold: DEFAULT_PAYLOAD_THRESHOLD,
+            max_size: DEFAULT_MAX_PAYLOAD_SIZE,
+            url_ttl_secs: DEFAULT_PAYLOAD_URL_TTL_SECS,
+        }
+    }
+
+    fn errors(config: InvokPayloadConfig) -> Vec<String> {
+        let mut errors = Vec::new();
+        config.validate(&mut errors);
+        errors
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(errors(config()).is_empty());
+        assert!(errors(InvokPayloadConfig {
+            store_url: None,
+            access_key: None,
+            secret_key: None,
+            ..config()
+        })
+        .is_empty());
+
+        let invalid = [
+            InvokPayloadConfig {
+                store_url: Some("ftp://bucket".to_string()),
+                ..config()
+            },
+            InvokPayloadConfig {
+                secret_key: None,
+                ..config()
+            },
+            InvokPayloadConfig {
+                threshold: 0,
+                ..config()
+            },
+            InvokPayloadConfig {
+                max_size: DEFAULT_PAYLOAD_THRESHOLD - 1,
+                ..config()
+            },
+            InvokPayloadConfig {
+                url_ttl_secs: MAX_PAYLOAD_URL_TTL_SECS + 1,
+                ..config()
+            },
+        ];
+        for config in invalid {
+            assert_eq!(errors(config.clone()).len(), 1, "{config:?}");
+        }
+    }
+}
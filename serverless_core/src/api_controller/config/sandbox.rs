@@ -0,0 +1,83 @@
+use super::file::SandboxSection;
+use super::resolve;
+use runtime::core::sandbox::{Protected, SandboxConfig, ServiceEndpoint};
+
+const SANDBOX_ENABLED_ENV_VARIABLE: &str = "SANDBOX_ENABLED";
+const SANDBOX_HELPER_IMAGE_ENV_VARIABLE: &str = "SANDBOX_HELPER_IMAGE";
+const SANDBOX_ALLOW_ENV_VARIABLE: &str = "SANDBOX_ALLOW";
+
+/// Network rules keeping function containers away from the installation's infrastructure
+#[derive(Debug, Clone)]
+pub struct InvokSandboxConfig {
+    /// Block the Docker API, Redis, Postgres, Prometheus, cloud metadata endpoints and
+    /// private ranges from functions
+    pub enabled: bool,
+
+    /// Image with `sh` and `iptables` the rules are installed with; the controller's own
+    /// image when unset
+    pub helper_image: Option<String>,
+
+    /// Protected services functions may reach anyway
+    pub allow: Vec<Protected>,
+}
+
+impl InvokSandboxConfig {
+    /// Load configuration from environment variables, falling back to the `sandbox`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &SandboxSection, errors: &mut Vec<String>) -> Self {
+        let allow: String = resolve(
+            SANDBOX_ALLOW_ENV_VARIABLE,
+            "sandbox.allow",
+            file.allow.clone(),
+            errors,
+        )
+        .unwrap_or_default();
+        let allow = allow
+            .split(',')
+            .filter(|service| !service.trim().is_empty())
+            .filter_map(|service| match service.parse::<Protected>() {
+                Ok(service) => Some(service),
+                Err(e) => {
+                    errors.push(format!("sandbox.allow: {}", e));
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            enabled: resolve(
+                SANDBOX_ENABLED_ENV_VARIABLE,
+                "sandbox.enabled",
+                file.enabled,
+                errors,
+            )
+            .unwrap_or(true),
+            helper_image: resolve(
+                SANDBOX_HELPER_IMAGE_ENV_VARIABLE,
+                "sandbox.helper_image",
+                file.helper_image.clone(),
+                errors,
+            ),
+            allow,
+        }
+    }
+
+    /// Sandbox settings for the autoscaler, if enabled.
+    ///
+    /// `services` are the URLs the controller reaches the protected services at; the
+    /// Docker API and cloud metadata endpoints are found by the runtime itself.
+    pub fn policy(&self, services: &[(Protected, &str)]) -> Option<SandboxConfig> {
+        if !self.enabled {
+            return None;
+        }
+        Some(SandboxConfig {
+            helper_image: self.helper_image.clone(),
+            endpoints: services
+                .iter()
+                .filter_map(|(service, url)| ServiceEndpoint::from_url(*service, url))
+                .collect(),
+            allow: self.allow.clone(),
+            reachable: Vec::new(),
+        })
+    }
+}
@@ -1,5 +1,10 @@
-use super::InvokConfigError;
-use std::env;
+use super::file::ServerSection;
+use super::{resolve, resolve_required};
+use crate::lifecycle_manager::build_args::BuildArgCipher;
+use runtime::core::egress::proxy_credentials;
+use runtime::core::environment::AddressMode;
+use runtime::core::internal_api::InternalApiConfig;
+use runtime::core::platform::{parse_platforms, Platform};
 
 // Env variables
 const REDIS_URL_ENV_VARIABLE: &str = "REDIS_URL";
@@ -7,8 +12,20 @@ const DATABASE_URL_ENV_VARIABLE: &str = "DATABASE_URL";
 const PORT_ENV_VARIABLE: &str = "SERVER_PORT";
 const SERVER_HOST_ENV_VARIABLE: &str = "SERVER_HOST";
 const AUTH_JWT_SECRET_ENV_VARIABLE: &str = "AUTH_JWT_SECRET";
+const SHUTDOWN_TIMEOUT_SECS_ENV_VARIABLE: &str = "SHUTDOWN_TIMEOUT_SECS";
+const TRUST_FORWARDED_FOR_ENV_VARIABLE: &str = "TRUST_FORWARDED_FOR";
+const HIDE_INTERNAL_ADDRESSES_ENV_VARIABLE: &str = "HIDE_INTERNAL_ADDRESSES";
+const ADMIN_TOKEN_ENV_VARIABLE: &str = "ADMIN_TOKEN";
+const MAX_RESTORE_SIZE_ENV_VARIABLE: &str = "MAX_RESTORE_SIZE";
+const OIDC_ISSUER_ENV_VARIABLE: &str = "OIDC_ISSUER";
+const OIDC_AUDIENCE_ENV_VARIABLE: &str = "OIDC_AUDIENCE";
+const OIDC_TOKEN_TTL_SECS_ENV_VARIABLE: &str = "OIDC_TOKEN_TTL_SECS";
+const BUILD_PLATFORMS_ENV_VARIABLE: &str = "BUILD_PLATFORMS";
+const BUILD_ARGS_KEY_ENV_VARIABLE: &str = "BUILD_ARGS_KEY";
+const INTERNAL_API_URL_ENV_VARIABLE: &str = "INTERNAL_API_URL";
 
 const DOCKER_COMPOSE_NETWORK_ENV_VARIABLE: &str = "DOCKER_COMPOSE_NETWORK";
+const PUBLISHED_PORTS_HOST_ENV_VARIABLE: &str = "PUBLISHED_PORTS_HOST";
 const DOCKER_HOST_ENV_VARIABLE: &str = "DOCKER_HOST";
 
 /// Default port to use if not configured
@@ -17,6 +34,25 @@ const DEFAULT_PORT_VALUE: u16 = 3000;
 /// Default host to bind to if not configured
 const DEFAULT_HOST_VALUE: &str = "0.0.0.0";
 
+/// Default time to wait for in-flight requests on shutdown
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Default maximum backup archive accepted by a restore (2GB)
+const DEFAULT_MAX_RESTORE_SIZE: usize = 2 * 1024 * 1024 * 1024;
+
+/// Default issuer of the OIDC tokens exchanged for deploy credentials
+const DEFAULT_OIDC_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// Default audience OIDC tokens must be issued for
+const DEFAULT_OIDC_AUDIENCE: &str = "invok";
+
+/// Default lifetime of deploy credentials obtained with an OIDC token
+const DEFAULT_OIDC_TOKEN_TTL_SECS: u64 = 15 * 60;
+
+/// Label the function token secret is derived from the JWT secret with, so it differs
+/// from it
+const INTERNAL_API_SECRET_LABEL: &str = "invok-internal-api";
+
 /// Server configuration
 #[derive(Debug, Clone)]
 pub struct InvokServerConfig {
@@ -32,51 +68,282 @@ pub struct InvokServerConfig {
     /// Server listen address
     pub host: String,
 
-    /// Docker network  address
-    pub docker_compose_network_host: String,
+    /// Docker network function containers join; detected when unset
+    pub docker_compose_network_host: Option<String>,
+
+    /// Host function ports are published on and dialed at; unset dials containers on
+    /// their network
+    pub published_ports_host: Option<String>,
 
     /// Server listen port
     pub port: u16,
+
+    /// Seconds to wait for in-flight requests to finish on shutdown
+    pub shutdown_timeout_secs: u64,
+
+    /// Take the client address from `X-Forwarded-For` (set when behind a reverse proxy)
+    pub trust_forwarded_for: bool,
+
+    /// Replace function container addresses in error responses and redirects
+    pub hide_internal_addresses: bool,
+
+    /// Bearer token for the admin API (backup/restore); the API is disabled when unset
+    pub admin_token: Option<String>,
+
+    /// Maximum backup archive accepted by a restore in bytes, compressed and unpacked
+    pub max_restore_size: usize,
+
+    /// Issuer of the OIDC tokens CI workflows exchange for deploy credentials
+    pub oidc_issuer: String,
+
+    /// Audience OIDC tokens must be issued for
+    pub oidc_audience: String,
+
+    /// Lifetime of deploy credentials obtained with an OIDC token, in seconds
+    pub oidc_token_ttl_secs: u64,
+
+    /// Platforms function images are built for besides the Docker daemon's own
+    pub build_platforms: Vec<Platform>,
+
+    /// Base64-encoded 32-byte key namespace build args are encrypted with; build args
+    /// are disabled when unset
+    pub build_args_key: Option<String>,
+
+    /// URL function containers reach this controller at (e.g. `http://invok:3000`); the
+    /// internal API functions call, e.g. the key-value store, is disabled when unset
+    pub internal_api_url: Option<String>,
 }
 
 impl InvokServerConfig {
-    /// Load configuration from environment variables
-    pub fn from_env() -> Result<Self, InvokConfigError> {
+    /// Load configuration from environment variables, falling back to the `server`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &ServerSection, errors: &mut Vec<String>) -> Self {
         // Required variables
-        let redis_url = env::var(REDIS_URL_ENV_VARIABLE)
-            .map_err(|_| InvokConfigError::MissingVar(REDIS_URL_ENV_VARIABLE.to_string()))?;
+        let redis_url = resolve_required(
+            REDIS_URL_ENV_VARIABLE,
+            "server.redis_url",
+            file.redis_url.clone(),
+            errors,
+        );
+
+        let database_url = resolve_required(
+            DATABASE_URL_ENV_VARIABLE,
+            "server.database_url",
+            file.database_url.clone(),
+            errors,
+        );
+
+        // The Docker client reads DOCKER_HOST itself, so a value from the file is exported.
+        // Unset, the daemon socket is used when present (e.g. mounted into the container)
+        let docker_host: Option<String> = resolve(
+            DOCKER_HOST_ENV_VARIABLE,
+            "server.docker_host",
+            file.docker_host.clone(),
+            errors,
+        );
+        if let Some(docker_host) = docker_host {
+            std::env::set_var(DOCKER_HOST_ENV_VARIABLE, docker_host);
+        }
+
+        let docker_compose_network_host = resolve(
+            DOCKER_COMPOSE_NETWORK_ENV_VARIABLE,
+            "server.docker_compose_network",
+            file.docker_compose_network.clone(),
+            errors,
+        );
+
+        let published_ports_host: Option<String> = resolve(
+            PUBLISHED_PORTS_HOST_ENV_VARIABLE,
+            "server.published_ports_host",
+            file.published_ports_host.clone(),
+            errors,
+        );
+        if let Some(host) = &published_ports_host {
+            // A host, without scheme or port
+            if host.is_empty() || host.contains(['/', ' ']) {
+                errors.push(format!(
+                    "server.published_ports_host: '{}' is not a host name or IP address",
+                    host
+                ));
+            }
+        }
+
+        let jwt_auth_secret = resolve_required(
+            AUTH_JWT_SECRET_ENV_VARIABLE,
+            "server.jwt_auth_secret",
+            file.jwt_auth_secret.clone(),
+            errors,
+        );
+
+        let host = resolve(
+            SERVER_HOST_ENV_VARIABLE,
+            "server.host",
+            file.host.clone(),
+            errors,
+        )
+        .unwrap_or_else(|| DEFAULT_HOST_VALUE.to_string());
+
+        let port = resolve(PORT_ENV_VARIABLE, "server.port", file.port, errors)
+            .unwrap_or(DEFAULT_PORT_VALUE);
+
+        let shutdown_timeout_secs = resolve(
+            SHUTDOWN_TIMEOUT_SECS_ENV_VARIABLE,
+            "server.shutdown_timeout_secs",
+            file.shutdown_timeout_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
 
-        let database_url = env::var(DATABASE_URL_ENV_VARIABLE)
-            .map_err(|_| InvokConfigError::MissingVar(DATABASE_URL_ENV_VARIABLE.to_string()))?;
+        let trust_forwarded_for = resolve(
+            TRUST_FORWARDED_FOR_ENV_VARIABLE,
+            "server.trust_forwarded_for",
+            file.trust_forwarded_for,
+            errors,
+        )
+        .unwrap_or(false);
 
-        env::var(DOCKER_HOST_ENV_VARIABLE)
-            .map_err(|_| InvokConfigError::MissingVar(DOCKER_HOST_ENV_VARIABLE.to_string()))?;
+        let hide_internal_addresses = resolve(
+            HIDE_INTERNAL_ADDRESSES_ENV_VARIABLE,
+            "server.hide_internal_addresses",
+            file.hide_internal_addresses,
+            errors,
+        )
+        .unwrap_or(true);
 
-        let docker_compose_network_host =
-            env::var(DOCKER_COMPOSE_NETWORK_ENV_VARIABLE).map_err(|_| {
-                InvokConfigError::MissingVar(DOCKER_COMPOSE_NETWORK_ENV_VARIABLE.to_string())
-            })?;
+        let admin_token: Option<String> = resolve(
+            ADMIN_TOKEN_ENV_VARIABLE,
+            "server.admin_token",
+            file.admin_token.clone(),
+            errors,
+        );
+        if admin_token.as_ref().is_some_and(|token| token.len() < 16) {
+            errors.push("server.admin_token must be at least 16 characters".to_string());
+        }
 
-        let jwt_auth_secret = env::var(AUTH_JWT_SECRET_ENV_VARIABLE)
-            .map_err(|_| InvokConfigError::MissingVar(AUTH_JWT_SECRET_ENV_VARIABLE.to_string()))?;
+        let max_restore_size = resolve(
+            MAX_RESTORE_SIZE_ENV_VARIABLE,
+            "server.max_restore_size",
+            file.max_restore_size,
+            errors,
+        )
+        .unwrap_or(DEFAULT_MAX_RESTORE_SIZE);
+        if max_restore_size == 0 {
+            errors.push("server.max_restore_size must be at least 1".to_string());
+        }
 
-        let host =
-            env::var(SERVER_HOST_ENV_VARIABLE).unwrap_or_else(|_| DEFAULT_HOST_VALUE.to_string());
+        let oidc_issuer = resolve(
+            OIDC_ISSUER_ENV_VARIABLE,
+            "server.oidc_issuer",
+            file.oidc_issuer.clone(),
+            errors,
+        )
+        .unwrap_or_else(|| DEFAULT_OIDC_ISSUER.to_string());
+        if !oidc_issuer.starts_with("https://") {
+            errors.push("server.oidc_issuer must be an https:// URL".to_string());
+        }
 
-        let port = match env::var(PORT_ENV_VARIABLE) {
-            Ok(port_str) => port_str
-                .parse::<u16>()
-                .map_err(|_| InvokConfigError::InvalidPort(port_str))?,
-            Err(_) => DEFAULT_PORT_VALUE,
+        let oidc_audience = resolve(
+            OIDC_AUDIENCE_ENV_VARIABLE,
+            "server.oidc_audience",
+            file.oidc_audience.clone(),
+            errors,
+        )
+        .unwrap_or_else(|| DEFAULT_OIDC_AUDIENCE.to_string());
+        if oidc_audience.is_empty() {
+            errors.push("server.oidc_audience must not be empty".to_string());
+        }
+
+        let oidc_token_ttl_secs = resolve(
+            OIDC_TOKEN_TTL_SECS_ENV_VARIABLE,
+            "server.oidc_token_ttl_secs",
+            file.oidc_token_ttl_secs,
+            errors,
+        )
+        .unwrap_or(DEFAULT_OIDC_TOKEN_TTL_SECS);
+        if !(60..=86400).contains(&oidc_token_ttl_secs) {
+            errors.push("server.oidc_token_ttl_secs must be between 60 and 86400".to_string());
+        }
+
+        let build_platforms: String = resolve(
+            BUILD_PLATFORMS_ENV_VARIABLE,
+            "server.build_platforms",
+            file.build_platforms.clone(),
+            errors,
+        )
+        .unwrap_or_default();
+        let build_platforms = match parse_platforms(&build_platforms) {
+            Ok(platforms) => platforms,
+            Err(e) => {
+                errors.push(format!("server.build_platforms: {}", e));
+                Vec::new()
+            }
         };
 
-        Ok(Self {
-            redis_url,
-            database_url,
-            jwt_auth_secret,
+        let build_args_key: Option<String> = resolve(
+            BUILD_ARGS_KEY_ENV_VARIABLE,
+            "server.build_args_key",
+            file.build_args_key.clone(),
+            errors,
+        );
+        if let Some(Err(e)) = build_args_key.as_deref().map(BuildArgCipher::from_base64) {
+            errors.push(format!("server.build_args_key: {}", e));
+        }
+
+        let internal_api_url: Option<String> = resolve(
+            INTERNAL_API_URL_ENV_VARIABLE,
+            "server.internal_api_url",
+            file.internal_api_url.clone(),
+            errors,
+        );
+        if let Some(url) = &internal_api_url {
+            if !reqwest::Url::parse(url).is_ok_and(|parsed| {
+                matches!(parsed.scheme(), "http" | "https") && parsed.has_host()
+            }) {
+                errors.push(format!(
+                    "server.internal_api_url: '{}' is not an http(s) URL",
+                    url
+                ));
+            }
+        }
+
+        Self {
+            redis_url: redis_url.unwrap_or_default(),
+            database_url: database_url.unwrap_or_default(),
+            jwt_auth_secret: jwt_auth_secret.unwrap_or_default(),
             docker_compose_network_host,
+            published_ports_host,
             host,
             port,
+            shutdown_timeout_secs,
+            trust_forwarded_for,
+            hide_internal_addresses,
+            admin_token,
+            max_restore_size,
+            oidc_issuer,
+            oidc_audience,
+            oidc_token_ttl_secs,
+            build_platforms,
+            build_args_key,
+            internal_api_url,
+        }
+    }
+
+    /// How function containers reach the internal API, if it is enabled.
+    ///
+    /// The token secret is derived from `jwt_auth_secret`, so every controller accepts the
+    /// tokens any of them handed out.
+    pub fn internal_api(&self) -> Option<InternalApiConfig> {
+        Some(InternalApiConfig {
+            url: self.internal_api_url.clone()?,
+            secret: proxy_credentials(&self.jwt_auth_secret, INTERNAL_API_SECRET_LABEL),
         })
     }
+
+    /// How the controller reaches function containers
+    pub fn address_mode(&self) -> AddressMode {
+        match &self.published_ports_host {
+            Some(host) => AddressMode::PublishedPort { host: host.clone() },
+            None => AddressMode::Network,
+        }
+    }
 }
@@ -0,0 +1,74 @@
+use super::file::ServicesSection;
+use super::resolve;
+use std::net::IpAddr;
+
+const SERVICE_BIND_ADDRESS_ENV_VARIABLE: &str = "SERVICE_BIND_ADDRESS";
+const SERVICE_PORT_RANGE_START_ENV_VARIABLE: &str = "SERVICE_PORT_RANGE_START";
+const SERVICE_PORT_RANGE_END_ENV_VARIABLE: &str = "SERVICE_PORT_RANGE_END";
+
+/// Default address service ports are bound on
+const DEFAULT_SERVICE_BIND_ADDRESS: &str = "0.0.0.0";
+
+/// Default first port assigned to a service function
+const DEFAULT_SERVICE_PORT_RANGE_START: u16 = 20000;
+
+/// Default last port assigned to a service function
+const DEFAULT_SERVICE_PORT_RANGE_END: u16 = 20099;
+
+/// Ports published for TCP and UDP service functions
+#[derive(Debug, Clone)]
+pub struct InvokServiceConfig {
+    /// Address the controller binds service ports on
+    pub bind_address: IpAddr,
+
+    /// First port assigned to a service function
+    pub port_range_start: u16,
+
+    /// Last port assigned to a service function
+    pub port_range_end: u16,
+}
+
+impl InvokServiceConfig {
+    /// Load configuration from environment variables, falling back to the `services`
+    /// section of the config file. Problems are appended to `errors`.
+    pub fn load(file: &ServicesSection, errors: &mut Vec<String>) -> Self {
+        let config = Self {
+            bind_address: resolve(
+                SERVICE_BIND_ADDRESS_ENV_VARIABLE,
+                "services.bind_address",
+                file.bind_address,
+                errors,
+            )
+            .unwrap_or_else(|| DEFAULT_SERVICE_BIND_ADDRESS.parse().unwrap()),
+            port_range_start: resolve(
+                SERVICE_PORT_RANGE_START_ENV_VARIABLE,
+                "services.port_range_start",
+                file.port_range_start,
+                errors,
+            )
+            .unwrap_or(DEFAULT_SERVICE_PORT_RANGE_START),
+            port_range_end: resolve(
+                SERVICE_PORT_RANGE_END_ENV_VARIABLE,
+                "services.port_range_end",
+                file.port_range_end,
+                errors,
+            )
+            .unwrap_or(DEFAULT_SERVICE_PORT_RANGE_END),
+        };
+        config.validate(errors);
+        config
+    }
+
+    /// Check value ranges and cross-field constraints
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.port_range_start == 0 {
+            errors.push("services.port_range_start must be at least 1".to_string());
+        }
+        if self.port_range_end < self.port_range_start {
+            errors.push(format!(
+                "services.port_range_end ({}) must not be below services.port_range_start ({})",
+                self.port_range_end, self.port_range_start
+            ));
+        }
+    }
+}
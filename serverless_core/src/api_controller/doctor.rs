@@ -0,0 +1,362 @@
+use db_migrations::{Migrator, MigratorTrait};
+use runtime::core::cgroup_metrics::CgroupVersion;
+use runtime::core::environment::{detect_network, DeploymentMode, DockerEndpoint};
+use runtime::core::metrics_client::{MetricsClient, MetricsConfig, MetricsSource};
+use runtime::core::platform::{build_targets, daemon_platform};
+use runtime::core::preflight::{check_docker, check_network};
+use sea_orm::Database;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::config::InvokConfig;
+
+/// Free space below which the disk check fails (function builds need room for images)
+const MIN_FREE_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Free space below which the disk check warns
+const LOW_FREE_DISK_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Time the builder service has to answer its health check
+const BUILDER_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    /// Degraded but the controller can still run
+    Warn,
+    /// The controller will not work until this is fixed
+    Fail,
+}
+
+/// A single line of the report
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Result of all preflight checks
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(CheckResult {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    /// Whether any check failed outright
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Fail)
+    }
+
+    /// One line per failed check, for error messages
+    pub fn failures(&self) -> Vec<String> {
+        self.checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Fail)
+            .map(|c| format!("{}: {}", c.name, c.detail))
+            .collect()
+    }
+
+    /// Write every check to the log at a level matching its status
+    pub fn log(&self) {
+        for check in &self.checks {
+            match check.status {
+                CheckStatus::Ok => info!("Preflight {}: {}", check.name, check.detail),
+                CheckStatus::Warn => warn!("Preflight {}: {}", check.name, check.detail),
+                CheckStatus::Fail => error!("Preflight {}: {}", check.name, check.detail),
+            }
+        }
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let marker = match check.status {
+                CheckStatus::Ok => "[ OK ]",
+                CheckStatus::Warn => "[WARN]",
+                CheckStatus::Fail => "[FAIL]",
+            };
+            writeln!(f, "{} {:<12} {}", marker, check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check against the given configuration.
+///
+/// Checks never return early: each dependency is probed independently so the report
+/// shows everything that is wrong at once.
+pub async fn run_checks(config: &InvokConfig) -> DoctorReport {
+    let mut report = DoctorReport::default();
+    let server = &config.server_config;
+
+    // Docker daemon and the network function containers join
+    let endpoint = DockerEndpoint::detect();
+    let mode = DeploymentMode::detect();
+    match check_docker().await {
+        Ok((docker, version)) => {
+            report.push(
+                "docker",
+                CheckStatus::Ok,
+                format!("daemon {} reachable via {}, {}", version, endpoint, mode),
+            );
+            match &server.docker_compose_network_host {
+                Some(network) => match check_network(&docker, network).await {
+                    Ok(()) => {
+                        report.push("network", CheckStatus::Ok, format!("'{}' exists", network))
+                    }
+                    Err(e) => report.push("network", CheckStatus::Fail, e),
+                },
+                None => {
+                    let network = detect_network(&docker).await;
+                    report.push(
+                        "network",
+                        CheckStatus::Ok,
+                        format!(
+                            "'{}' (detected; set DOCKER_COMPOSE_NETWORK to override)",
+                            network
+                        ),
+                    )
+                }
+            }
+            let address_mode = server.address_mode();
+            if mode == DeploymentMode::Container && address_mode.is_loopback() {
+                report.push(
+                    "addresses",
+                    CheckStatus::Warn,
+                    format!(
+                        "containers are {}, which is the controller's own container, not the daemon host",
+                        address_mode
+                    ),
+                )
+            } else {
+                report.push(
+                    "addresses",
+                    CheckStatus::Ok,
+                    format!("containers are {}", address_mode),
+                )
+            }
+            match daemon_platform(&docker).await {
+                Ok(native) => {
+                    let mut platforms = vec![format!("{} (native)", native)];
+                    platforms.extend(
+                        build_targets(&native, &server.build_platforms)
+                            .iter()
+                            .skip(1)
+                            .map(|platform| format!("{} (emulated)", platform)),
+                    );
+                    report.push("platforms", CheckStatus::Ok, platforms.join(", "))
+                }
+                Err(e) => report.push("platforms", CheckStatus::Fail, e.to_string()),
+            }
+        }
+        Err(e) => {
+            report.push(
+                "docker",
+                CheckStatus::Fail,
+                format!("{} (via {}, {})", e, endpoint, mode),
+            );
+            report.push(
+                "network",
+                CheckStatus::Fail,
+                "skipped: Docker is unreachable",
+            );
+            report.push(
+                "platforms",
+                CheckStatus::Fail,
+                "skipped: Docker is unreachable",
+            );
+        }
+    }
+
+    // Metrics are needed for scaling decisions but not for serving requests
+    let autoscaling = &config.function_config.autoscaling;
+    let source = autoscaling.metrics_source();
+    let metrics_client = MetricsClient::try_new(MetricsConfig {
+        source: source.clone(),
+        prometheus_url: autoscaling.prometheus_url.clone(),
+        container_id_pattern: autoscaling.prometheus_container_id_pattern.clone(),
+        auth: autoscaling.metrics_auth(),
+        ca_cert_path: autoscaling.prometheus_ca_cert.clone(),
+        ..Default::default()
+    });
+    match (source, metrics_client) {
+        (MetricsSource::Cgroup { root }, Ok(client)) if client.health_check().await => {
+            let version = match CgroupVersion::detect(&root) {
+                CgroupVersion::V1 => "v1",
+                CgroupVersion::V2 => "v2",
+            };
+            report.push(
+                "metrics",
+                CheckStatus::Ok,
+                format!("cgroup {} at {} readable", version, root.display()),
+            )
+        }
+        (MetricsSource::Cgroup { root }, Ok(_)) => report.push(
+            "metrics",
+            CheckStatus::Warn,
+            format!(
+                "{} unreadable; autoscaling will not react to load",
+                root.display()
+            ),
+        ),
+        (MetricsSource::DockerStats, Ok(client)) if client.health_check().await => {
+            report.push("metrics", CheckStatus::Ok, "Docker stats API reachable")
+        }
+        (MetricsSource::DockerStats, Ok(_)) => report.push(
+            "metrics",
+            CheckStatus::Warn,
+            "Docker stats API unreachable; autoscaling will not react to load",
+        ),
+        (MetricsSource::Prometheus, Ok(client)) if client.health_check().await => report.push(
+            "prometheus",
+            CheckStatus::Ok,
+            format!("{} reachable", autoscaling.prometheus_url),
+        ),
+        (MetricsSource::Prometheus, Ok(_)) => report.push(
+            "prometheus",
+            CheckStatus::Warn,
+            format!(
+                "{} unreachable or rejected credentials; autoscaling will not react to load",
+                autoscaling.prometheus_url
+            ),
+        ),
+        (_, Err(e)) => report.push("metrics", CheckStatus::Fail, e.to_string()),
+    }
+
+    // Redis
+    let redis_result = async {
+        let client = redis::Client::open(server.redis_url.clone())?;
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        let pong: redis::RedisResult<String> = redis::cmd("PING").query_async(&mut conn).await;
+        pong
+    }
+    .await;
+    match redis_result {
+        Ok(_) => report.push("redis", CheckStatus::Ok, "reachable"),
+        Err(e) => report.push("redis", CheckStatus::Fail, format!("unreachable: {}", e)),
+    }
+
+    // Database and migrations
+    match Database::connect(server.database_url.clone()).await {
+        Ok(db) => {
+            report.push("database", CheckStatus::Ok, "reachable");
+            match Migrator::get_pending_migrations(&db).await {
+                Ok(pending) if pending.is_empty() => {
+                    report.push("migrations", CheckStatus::Ok, "up to date")
+                }
+                Ok(pending) => report.push(
+                    "migrations",
+                    CheckStatus::Warn,
+                    format!(
+                        "{} pending (applied automatically on startup)",
+                        pending.len()
+                    ),
+                ),
+                Err(e) => report.push(
+                    "migrations",
+                    CheckStatus::Fail,
+                    format!("cannot read migration status: {}", e),
+                ),
+            }
+        }
+        Err(e) => {
+            report.push("database", CheckStatus::Fail, format!("unreachable: {}", e));
+            report.push(
+                "migrations",
+                CheckStatus::Fail,
+                "skipped: database is unreachable",
+            );
+        }
+    }
+
+    // Read replica, when listings and descriptions are served from one
+    if let Some(replica_url) = &config.database_config.read_replica_url {
+        match Database::connect(replica_url.clone()).await {
+            Ok(_) => report.push("database replica", CheckStatus::Ok, "reachable"),
+            Err(e) => report.push(
+                "database replica",
+                CheckStatus::Fail,
+                format!("unreachable: {}", e),
+            ),
+        }
+    }
+
+    // Builder service, when images aren't built on this host
+    if let Some(url) = &config.builder_config.url {
+        let health = async {
+            reqwest::Client::builder()
+                .timeout(BUILDER_CHECK_TIMEOUT)
+                .build()?
+                .get(format!("{}/healthz", url))
+                .send()
+                .await?
+                .error_for_status()
+        }
+        .await;
+        match health {
+            Ok(_) => report.push("builder", CheckStatus::Ok, format!("{} reachable", url)),
+            Err(e) => report.push(
+                "builder",
+                CheckStatus::Fail,
+                format!("{} unreachable: {}", url, e),
+            ),
+        }
+    }
+
+    // Free disk where function archives are unpacked and built
+    let build_dir = std::env::temp_dir();
+    match free_disk_bytes(&build_dir) {
+        Some(free) => {
+            let detail = format!("{} MB free in {}", free / 1024 / 1024, build_dir.display());
+            let status = if free < MIN_FREE_DISK_BYTES {
+                CheckStatus::Fail
+            } else if free < LOW_FREE_DISK_BYTES {
+                CheckStatus::Warn
+            } else {
+                CheckStatus::Ok
+            };
+            report.push("disk", status, detail);
+        }
+        None => report.push(
+            "disk",
+            CheckStatus::Warn,
+            format!("could not determine free space in {}", build_dir.display()),
+        ),
+    }
+
+    report
+}
+
+/// Free bytes available to unprivileged users on the filesystem holding `path`
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // field widths differ between platforms
+fn free_disk_bytes(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_disk_bytes(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,308 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hyper::header::{HeaderValue, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use redis::aio::MultiplexedConnection;
+use runtime::core::egress::{namespace_of, proxy_credentials};
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpStream};
+use tracing::{error, info, warn};
+
+use super::config::InvokConfigError;
+use super::middlewares::admin::constant_time_eq;
+use super::{shutdown_signal, InvokAppError};
+use crate::db::egress::EgressAllowlistRepo;
+use crate::utils::egress::{is_public, EgressAllowlist};
+
+const EGRESS_PROXY_PORT_ENV_VARIABLE: &str = "EGRESS_PROXY_PORT";
+const EGRESS_PROXY_SECRET_ENV_VARIABLE: &str = "EGRESS_PROXY_SECRET";
+const EGRESS_NAMESPACE_ENV_VARIABLE: &str = "EGRESS_NAMESPACE";
+const REDIS_URL_ENV_VARIABLE: &str = "REDIS_URL";
+
+/// Time to wait for a destination to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Headers meant for the proxy, never passed on to the destination
+const PROXY_HEADERS: &[&str] = &["proxy-authorization", "proxy-connection"];
+
+/// State of an egress proxy
+struct EgressProxy {
+    /// Key the proxy credentials of functions are derived with
+    secret: String,
+    /// Namespace whose functions the proxy serves
+    namespace: String,
+    cache_conn: MultiplexedConnection,
+}
+
+/// A destination a function asked for, checked against its allowlist and resolved
+struct Destination {
+    host: String,
+    port: u16,
+    addr: SocketAddr,
+}
+
+/// Starts the egress proxy of a namespace.
+///
+/// The autoscaler runs one per namespace, as `serverless-core egress-proxy`, on the only
+/// network its function containers can leave through. Functions authenticate with the
+/// credentials in their `HTTP(S)_PROXY` URL; each request is let through when the
+/// function's allowlist (read from Redis on every connection) names the destination and
+/// the destination resolves to public addresses only.
+/// - `CONNECT host:port` opens a tunnel, used for HTTPS.
+/// - Plain HTTP requests in absolute form are forwarded.
+pub async fn start_egress_proxy() -> Result<(), InvokAppError> {
+    tracing_subscriber::fmt::init();
+
+    let port: u16 = required_env(EGRESS_PROXY_PORT_ENV_VARIABLE)?
+        .parse()
+        .map_err(|_| {
+            InvokConfigError::InvalidValue(format!(
+                "{} must be a port",
+                EGRESS_PROXY_PORT_ENV_VARIABLE
+            ))
+        })?;
+    let client = redis::Client::open(required_env(REDIS_URL_ENV_VARIABLE)?)?;
+    let proxy = Arc::new(EgressProxy {
+        secret: required_env(EGRESS_PROXY_SECRET_ENV_VARIABLE)?,
+        namespace: required_env(EGRESS_NAMESPACE_ENV_VARIABLE)?,
+        cache_conn: client.get_multiplexed_async_connection().await?,
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let proxy = proxy.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let proxy = proxy.clone();
+                async move { Ok::<_, Infallible>(proxy.handle(req).await) }
+            }))
+        }
+    });
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("Egress proxy listening on {}", addr);
+    hyper::Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    info!("Egress proxy stopped");
+    Ok(())
+}
+
+impl EgressProxy {
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let Some(function_key) = self.authenticate(&req) else {
+            let mut response = plain(
+                StatusCode::PROXY_AUTHENTICATION_REQUIRED,
+                "Proxy credentials required",
+            );
+            response.headers_mut().insert(
+                PROXY_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"invok-egress\""),
+            );
+            return response;
+        };
+
+        let destination = match self.destination(&function_key, &req).await {
+            Ok(destination) => destination,
+            Err(response) => return response,
+        };
+        let connect = TcpStream::connect(destination.addr);
+        let stream = match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                warn!(
+                    "Failed to connect '{}' to {}:{}: {}",
+                    function_key, destination.host, destination.port, e
+                );
+                return plain(StatusCode::BAD_GATEWAY, "Destination unreachable");
+            }
+            Err(_) => {
+                warn!(
+                    "Timed out connecting '{}' to {}:{}",
+                    function_key, destination.host, destination.port
+                );
+                return plain(StatusCode::GATEWAY_TIMEOUT, "Destination unreachable");
+            }
+        };
+
+        if req.method() == Method::CONNECT {
+            tunnel(req, stream);
+            Response::new(Body::empty())
+        } else {
+            forward(req, stream).await
+        }
+    }
+
+    /// Function key the request's proxy credentials belong to, if they are valid for this
+    /// proxy's namespace
+    fn authenticate(&self, req: &Request<Body>) -> Option<String> {
+        let encoded = req
+            .headers()
+            .get(PROXY_AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Basic ")?;
+        let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+        let (function_key, password) = decoded.split_once(':')?;
+        if namespace_of(function_key) != self.namespace {
+            return None;
+        }
+        let expected = proxy_credentials(&self.secret, function_key);
+        let valid = constant_time_eq(password.as_bytes(), expected.as_bytes());
+        valid.then(|| function_key.to_string())
+    }
+
+    /// Where the request goes, once the function's allowlist and the resolved addresses
+    /// allow it
+    async fn destination(
+        &self,
+        function_key: &str,
+        req: &Request<Body>,
+    ) -> Result<Destination, Response<Body>> {
+        let uri = req.uri();
+        let (host, port) = if req.method() == Method::CONNECT {
+            let authority = uri
+                .authority()
+                .ok_or_else(|| plain(StatusCode::BAD_REQUEST, "CONNECT needs host:port"))?;
+            (authority.host(), authority.port_u16().unwrap_or(443))
+        } else {
+            if uri.scheme_str() != Some("http") {
+                return Err(plain(
+                    StatusCode::BAD_REQUEST,
+                    "Only absolute http:// URLs are forwarded; use CONNECT for https",
+                ));
+            }
+            let host = uri
+                .host()
+                .ok_or_else(|| plain(StatusCode::BAD_REQUEST, "Request URL has no host"))?;
+            (host, uri.port_u16().unwrap_or(80))
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let mut cache_conn = self.cache_conn.clone();
+        let allowlist = match EgressAllowlistRepo::get(&mut cache_conn, function_key).await {
+            Ok(allowlist) => allowlist
+                .and_then(|allowlist| serde_json::from_str::<EgressAllowlist>(&allowlist).ok())
+                .unwrap_or_default(),
+            Err(e) => {
+                error!(
+                    "Failed to load egress allowlist of '{}': {}",
+                    function_key, e
+                );
+                return Err(plain(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Egress allowlist unavailable",
+                ));
+            }
+        };
+        if !allowlist.allows(host, port) {
+            info!("Denied '{}' egress to {}:{}", function_key, host, port);
+            return Err(plain(
+                StatusCode::FORBIDDEN,
+                "Destination not on the function's egress allowlist",
+            ));
+        }
+
+        // Checked on the resolved addresses, which are then dialled directly, so a name
+        // can't be re-pointed at an internal address between the check and the connection
+        let addrs: Vec<SocketAddr> = match lookup_host((host, port)).await {
+            Ok(addrs) => addrs.collect(),
+            Err(e) => {
+                warn!("Failed to resolve {} for '{}': {}", host, function_key, e);
+                return Err(plain(StatusCode::BAD_GATEWAY, "Destination not found"));
+            }
+        };
+        match addrs.first() {
+            Some(addr) if addrs.iter().all(|addr| is_public(addr.ip())) => Ok(Destination {
+                host: host.to_string(),
+                port,
+                addr: *addr,
+            }),
+            _ => {
+                warn!(
+                    "Denied '{}' egress to {}:{}: not a public address",
+                    function_key, host, port
+                );
+                Err(plain(
+                    StatusCode::FORBIDDEN,
+                    "Destination does not resolve to a public address",
+                ))
+            }
+        }
+    }
+}
+
+/// Relays the bytes of a `CONNECT` tunnel once the client has taken the `200` response
+fn tunnel(req: Request<Body>, mut stream: TcpStream) {
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(mut upgraded) => {
+                if let Err(e) = tokio::io::copy_bidirectional(&mut upgraded, &mut stream).await {
+                    warn!("Egress tunnel closed: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to open egress tunnel: {}", e),
+        }
+    });
+}
+
+/// Sends a plain HTTP request on to the destination, without the proxy's headers
+async fn forward(mut req: Request<Body>, stream: TcpStream) -> Response<Body> {
+    let (mut sender, connection) = match hyper::client::conn::handshake(stream).await {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            warn!("Failed to forward egress request: {}", e);
+            return plain(StatusCode::BAD_GATEWAY, "Destination unreachable");
+        }
+    };
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("Egress connection closed: {}", e);
+        }
+    });
+
+    if !req.headers().contains_key(HOST) {
+        if let Some(authority) = req
+            .uri()
+            .authority()
+            .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        {
+            req.headers_mut().insert(HOST, authority);
+        }
+    }
+    for header in PROXY_HEADERS {
+        req.headers_mut().remove(*header);
+    }
+    // Origin servers expect the path alone
+    *req.uri_mut() = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/")
+        .parse()
+        .unwrap_or_default();
+
+    match sender.send_request(req).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to forward egress request: {}", e);
+            plain(StatusCode::BAD_GATEWAY, "Destination unreachable")
+        }
+    }
+}
+
+fn plain(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+fn required_env(name: &str) -> Result<String, InvokConfigError> {
+    env::var(name).map_err(|_| InvokConfigError::InvalidValue(format!("{} is required", name)))
+}
@@ -1,2 +1,27 @@
+pub mod admin;
 pub mod auth;
+pub mod bench;
+pub mod build_args;
+pub mod credentials;
+pub mod deploy_gate;
+pub mod dev;
+pub mod egress;
+pub mod exec;
+pub mod flags;
 pub mod functions;
+pub mod gateway;
+pub mod health;
+pub mod kv;
+pub mod namespace;
+pub mod oidc;
+pub mod openapi;
+pub mod preview;
+pub mod promote;
+pub mod purge;
+pub mod replay;
+pub mod report;
+pub mod routing;
+pub mod status;
+pub mod storage;
+pub mod totp;
+pub mod trash;
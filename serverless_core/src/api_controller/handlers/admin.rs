@@ -0,0 +1,252 @@
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::functions::read_field_chunks;
+use crate::api_controller::middlewares::admin::AdminUser;
+use crate::api_controller::AppState;
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::job::{Job, JobRepo, JobStatus};
+use crate::lifecycle_manager::backup::{create_backup, restore_backup};
+use crate::lifecycle_manager::base_images::BaseImageStatus;
+use crate::lifecycle_manager::freeze::{
+    declare_freeze_window, lift_freeze_window, list_freeze_windows, NewFreezeWindow,
+};
+use crate::lifecycle_manager::invalidation::Invalidation;
+
+/// Downloads a backup of the whole control plane: every user, function and stored
+/// function archive, plus the autoscaler pool states
+pub(crate) async fn backup(State(state): State<AppState>, _admin: AdminUser) -> impl IntoResponse {
+    match create_backup(&state.db_conn, &state.autoscaler).await {
+        Ok(archive) => {
+            info!("Backup downloaded ({} bytes)", archive.len());
+            (
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, "application/gzip"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"invok-backup.tar.gz\"",
+                    ),
+                ],
+                archive,
+            )
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Replaces the control plane state with a backup produced by [`backup`].
+///
+/// Expects a multipart request with the archive as a file field. Existing users and
+/// functions that aren't in the backup are removed.
+pub(crate) async fn restore(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let max_size = state.config.server_config.max_restore_size;
+
+    let archive = loop {
+        match multipart.next_field().await {
+            Ok(Some(mut field)) if field.file_name().is_some() => {
+                match read_field_chunks(&mut field, max_size).await {
+                    Ok(buffer) => break buffer,
+                    Err(e) => {
+                        error!("Error reading backup archive: {}", e);
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            format!("Error reading backup archive: {}", e),
+                        )
+                            .into_response();
+                    }
+                }
+            }
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Missing backup archive").into_response();
+            }
+        }
+    };
+
+    match restore_backup(&state.db_conn, &state.autoscaler, &archive, max_size).await {
+        Ok(report) => {
+            // Cached settings may belong to functions that no longer exist or changed
+            state.function_settings.write().unwrap().clear();
+            state.function_versions.write().unwrap().clear();
+            state.routing_rules.write().unwrap().clear();
+            state.feature_flags.write().unwrap().clear();
+            let mut cache_conn = state.cache_conn.clone();
+            let _ = FunctionCacheRepo::clear(&mut cache_conn).await;
+            state
+                .cache_invalidator
+                .publish(&mut cache_conn, Invalidation::All)
+                .await;
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Jobs listed per state when the request doesn't say
+const DEFAULT_JOB_LIMIT: usize = 50;
+
+/// Most jobs listed per state
+const MAX_JOB_LIMIT: usize = 500;
+
+/// Options of a job listing
+#[derive(Debug, Deserialize)]
+pub(crate) struct JobListOptions {
+    /// Only list jobs in this state; all states when unset
+    status: Option<JobStatus>,
+    /// Jobs listed per state
+    limit: Option<usize>,
+}
+
+/// The jobs in one state
+#[derive(Debug, Serialize)]
+pub(crate) struct JobList {
+    status: JobStatus,
+    /// Jobs in this state, including those not listed
+    total: usize,
+    jobs: Vec<Job>,
+}
+
+/// Lists the background jobs that are queued, running or failed. Queued and running
+/// jobs are listed oldest first, failed ones most recent first
+pub(crate) async fn list_jobs(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    Query(options): Query<JobListOptions>,
+) -> impl IntoResponse {
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_JOB_LIMIT)
+        .min(MAX_JOB_LIMIT);
+    let statuses = match options.status {
+        Some(status) => vec![status],
+        None => vec![JobStatus::Queued, JobStatus::Running, JobStatus::Failed],
+    };
+
+    let mut cache_conn = state.cache_conn.clone();
+    let mut lists = Vec::with_capacity(statuses.len());
+    for status in statuses {
+        match JobRepo::list(&mut cache_conn, status, limit).await {
+            Ok((jobs, total)) => lists.push(JobList {
+                status,
+                total,
+                jobs,
+            }),
+            Err(e) => {
+                error!("Failed to list jobs: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list jobs").into_response();
+            }
+        }
+    }
+    (StatusCode::OK, Json(lists)).into_response()
+}
+
+/// Lists the declared scaling freeze windows, earliest first
+pub(crate) async fn list_freezes(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+) -> impl IntoResponse {
+    let mut cache_conn = state.cache_conn.clone();
+    match list_freeze_windows(&mut cache_conn).await {
+        Ok(windows) => (StatusCode::OK, Json(windows)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Declares a window during which idle containers are kept instead of scaled down,
+/// platform-wide or for one namespace
+pub(crate) async fn create_freeze(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    Json(new): Json<NewFreezeWindow>,
+) -> impl IntoResponse {
+    if let Err(e) = new.validate() {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+
+    let mut cache_conn = state.cache_conn.clone();
+    match declare_freeze_window(&mut cache_conn, &state.autoscaler, new).await {
+        Ok(window) => (StatusCode::CREATED, Json(window)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Lifts a freeze window before it ends
+pub(crate) async fn delete_freeze(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut cache_conn = state.cache_conn.clone();
+    match lift_freeze_window(&mut cache_conn, &state.autoscaler, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "No such freeze window".to_string()).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Base images pulled on the controller and, when one is configured, the builder service
+#[derive(Debug, Serialize)]
+pub(crate) struct BaseImagesReport {
+    controller: Vec<BaseImageStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    builder: Option<Vec<BaseImageStatus>>,
+}
+
+/// Shows when each runtime base image was last pulled, on the controller and the builder
+/// service
+pub(crate) async fn list_base_images(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+) -> impl IntoResponse {
+    let builder = match &state.image_builder.remote {
+        Some(remote) => match remote.base_images().await {
+            Ok(images) => Some(images),
+            Err(e) => return e.into_response(),
+        },
+        None => None,
+    };
+    (
+        StatusCode::OK,
+        Json(BaseImagesReport {
+            controller: state.base_images.status(),
+            builder,
+        }),
+    )
+        .into_response()
+}
+
+/// Pulls the runtime base images again, on the controller and the builder service, e.g.
+/// right after enabling a new runtime version. Answers before the pulls finish.
+pub(crate) async fn pull_base_images(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+) -> impl IntoResponse {
+    if let Some(remote) = &state.image_builder.remote {
+        if let Err(e) = remote.pull_base_images().await {
+            return e.into_response();
+        }
+    }
+    let base_images = state.base_images.clone();
+    tokio::spawn(async move {
+        match base_images.pull_all().await {
+            Some(pulled) => info!(
+                "Pulled {} of {} runtime base images",
+                pulled,
+                base_images.count()
+            ),
+            None => warn!("Base images are already being pulled"),
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
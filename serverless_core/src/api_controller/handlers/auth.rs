@@ -1,25 +1,35 @@
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Json, State},
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
     response::IntoResponse,
 };
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::api_controller::handlers::totp::verify_second_factor;
 use crate::api_controller::AppState;
 use crate::db::auth::AuthDBRepo;
+use crate::lifecycle_manager::login_guard::{CaptchaError, MAX_PASSWORD_LENGTH};
+use crate::utils::utils::client_ip;
 
 // JWT token validity period in seconds (24 hours)
 const TOKEN_VALIDITY: u64 = 24 * 60 * 60;
 
+/// Scope of tokens that may only deploy, issued in exchange for CI OIDC tokens
+pub const DEPLOY_SCOPE: &str = "deploy";
+
 /// User registration request
 #[derive(Debug, Deserialize)]
 pub struct RegisterRequest {
     email: String,
     password: String,
+    /// Token of the solved captcha, required when the server has a captcha provider
+    #[serde(default)]
+    captcha_token: Option<String>,
 }
 
 /// Login request
@@ -27,6 +37,10 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     email: String,
     password: String,
+    /// Code from the authenticator app or a recovery code, for accounts with two-factor
+    /// authentication enabled
+    #[serde(default)]
+    totp_code: Option<String>,
 }
 
 /// Response containing an authentication token
@@ -49,11 +63,16 @@ pub struct Claims {
     sub: String, // Subject (user UUID)
     exp: u64,    // Expiration time (Unix timestamp)
     iat: u64,    // Issued at (Unix timestamp)
+    // Restricts what the token may do; unscoped tokens may do anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
 }
 
 /// Handles user registration
 pub async fn register(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<RegisterRequest>,
 ) -> impl IntoResponse {
     // Validate email and password
@@ -67,12 +86,43 @@ pub async fn register(
             .into_response();
     }
 
-    // Check password length
-    if payload.password.len() < 6 {
+    // Check the password against the policy
+    if let Err(reason) = state
+        .login_guard
+        .password_policy
+        .check(&payload.email, &payload.password)
+    {
         return (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({
-                "error": "Password must be at least 6 characters"
+                "error": reason
+            })),
+        )
+            .into_response();
+    }
+
+    // Check the captcha, if registrations need one
+    let client_ip = client_ip(
+        peer,
+        &headers,
+        state.config.server_config.trust_forwarded_for,
+    );
+    if let Err(e) = state
+        .login_guard
+        .verify_captcha(payload.captcha_token.as_deref(), client_ip)
+        .await
+    {
+        let status = match e {
+            CaptchaError::Unavailable(_) => {
+                error!("Failed to verify captcha: {}", e);
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            CaptchaError::Missing | CaptchaError::Rejected => StatusCode::BAD_REQUEST,
+        };
+        return (
+            status,
+            Json(serde_json::json!({
+                "error": e.to_string()
             })),
         )
             .into_response();
@@ -139,11 +189,91 @@ pub async fn register(
 /// Handles user login
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    match AuthDBRepo::login(&state.db_conn, payload.email, payload.password).await {
+    let client_ip = client_ip(
+        peer,
+        &headers,
+        state.config.server_config.trust_forwarded_for,
+    );
+    let mut cache_conn = state.cache_conn.clone();
+
+    // Refuse logins while the account or the client IP is locked out
+    if let Some(remaining) = state
+        .login_guard
+        .locked_out(&mut cache_conn, &payload.email, client_ip)
+        .await
+    {
+        let retry_after_secs = remaining.as_secs().max(1);
+        warn!(client_ip = ?client_ip, "Login refused during lockout");
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after_secs.to_string())],
+            Json(serde_json::json!({
+                "error": format!("Too many failed logins, retry in {}s", retry_after_secs)
+            })),
+        )
+            .into_response();
+    }
+
+    // No password of an account is this long, and hashing it would be costly
+    let result = if payload.password.len() > MAX_PASSWORD_LENGTH {
+        Err(sea_orm::DbErr::Custom("Invalid credentials".to_string()))
+    } else {
+        AuthDBRepo::login(&state.db_conn, payload.email.clone(), payload.password).await
+    };
+
+    match result {
         Ok(user) => {
+            // Accounts with two-factor authentication also need a code
+            if user.totp_enabled {
+                let Some(code) = payload.totp_code.as_deref().filter(|code| !code.is_empty())
+                else {
+                    return (
+                        StatusCode::UNAUTHORIZED,
+                        Json(serde_json::json!({
+                            "error": "Two-factor code required",
+                            "totp_required": true
+                        })),
+                    )
+                        .into_response();
+                };
+                match verify_second_factor(&state, &user, code).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        state
+                            .login_guard
+                            .record_failure(&mut cache_conn, &payload.email, client_ip)
+                            .await;
+                        return (
+                            StatusCode::UNAUTHORIZED,
+                            Json(serde_json::json!({
+                                "error": "Invalid two-factor code",
+                                "totp_required": true
+                            })),
+                        )
+                            .into_response();
+                    }
+                    Err(e) => {
+                        error!("Failed to check two-factor code: {}", e);
+                        return (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Json(serde_json::json!({
+                                "error": "Failed to authenticate user"
+                            })),
+                        )
+                            .into_response();
+                    }
+                }
+            }
+
             info!("User logged in: {}", user.email);
+            state
+                .login_guard
+                .record_success(&mut cache_conn, &payload.email)
+                .await;
 
             // Generate a token for the user
             match generate_token(
@@ -177,6 +307,10 @@ pub async fn login(
         }
         Err(e) => {
             if e.to_string().contains("Invalid credentials") {
+                state
+                    .login_guard
+                    .record_failure(&mut cache_conn, &payload.email, client_ip)
+                    .await;
                 return (
                     StatusCode::UNAUTHORIZED,
                     Json(serde_json::json!({
@@ -198,11 +332,11 @@ pub async fn login(
     }
 }
 
-/// Validates a JWT token
+/// Validates a JWT token, returning the user UUID and the token's scope
 pub fn validate_token(
     token: &str,
     auth_jwt_secret: &str,
-) -> Result<Uuid, jsonwebtoken::errors::Error> {
+) -> Result<(Uuid, Option<String>), jsonwebtoken::errors::Error> {
     // Decode and validate the token
     let token_data = decode::<Claims>(
         token,
@@ -214,13 +348,31 @@ pub fn validate_token(
     let uuid = Uuid::parse_str(&token_data.claims.sub)
         .map_err(|_| jsonwebtoken::errors::ErrorKind::InvalidSubject)?;
 
-    Ok(uuid)
+    Ok((uuid, token_data.claims.scope))
 }
 
 /// Generates a JWT token for a user
 fn generate_token(
     user_uuid: &str,
     auth_jwt_secret: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    issue_token(user_uuid, auth_jwt_secret, TOKEN_VALIDITY, None)
+}
+
+/// Generates a short-lived JWT token that may only deploy into the user's namespace
+pub(crate) fn generate_deploy_token(
+    user_uuid: &str,
+    auth_jwt_secret: &str,
+    validity: u64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    issue_token(user_uuid, auth_jwt_secret, validity, Some(DEPLOY_SCOPE))
+}
+
+fn issue_token(
+    user_uuid: &str,
+    auth_jwt_secret: &str,
+    validity: u64,
+    scope: Option<&str>,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -229,8 +381,9 @@ fn generate_token(
 
     let claims = Claims {
         sub: user_uuid.to_string(),
-        exp: now + TOKEN_VALIDITY,
+        exp: now + validity,
         iat: now,
+        scope: scope.map(str::to_string),
     };
 
     encode(
@@ -0,0 +1,48 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::bench::{build_report, BenchRun, MAX_BENCH_SAMPLES};
+use crate::lifecycle_manager::error::ServelessCoreError;
+use crate::utils::utils::generate_hash;
+
+/// Lines up the latencies `invok bench` measured against one of the authenticated user's
+/// functions with the scaling events of its pool during the run.
+///
+/// Only the events seen by this controller are reported, so with several controllers the
+/// report covers the pools this one manages.
+pub(crate) async fn bench_report(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+    Json(run): Json<BenchRun>,
+) -> impl IntoResponse {
+    if run.samples.len() > MAX_BENCH_SAMPLES {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("A run may report at most {MAX_BENCH_SAMPLES} requests"),
+        )
+            .into_response();
+    }
+    if let Err(err) = run.validate() {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    if FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid)
+        .await
+        .is_none()
+    {
+        return ServelessCoreError::FunctionNotRegistered(format!(
+            "{} in namespace {}",
+            function_name, user_uuid
+        ))
+        .into_response();
+    }
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let events = state.autoscaler.get_scaling_events(&function_key, 0);
+    (StatusCode::OK, Json(build_report(&run, &events))).into_response()
+}
@@ -0,0 +1,170 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::db::build_arg::BuildArgDBRepo;
+use crate::lifecycle_manager::build_args::{validate_build_arg, MAX_BUILD_ARGS};
+
+/// Request setting the value of a build arg
+#[derive(Debug, Deserialize)]
+pub struct SetBuildArgRequest {
+    value: String,
+}
+
+/// A build arg of the namespace; its value is never sent back
+#[derive(Debug, Serialize)]
+pub struct BuildArgSummary {
+    name: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+/// Lists the names of the authenticated user's build args
+pub(crate) async fn list_build_args(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match BuildArgDBRepo::find_by_user(&state.db_conn, user.id).await {
+        Ok(build_args) => {
+            let build_args: Vec<BuildArgSummary> = build_args
+                .into_iter()
+                .map(|build_arg| BuildArgSummary {
+                    name: build_arg.name,
+                    updated_at: build_arg.updated_at.to_rfc3339(),
+                })
+                .collect();
+            (StatusCode::OK, Json(build_args)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to list build args of {}: {}", user_uuid, e);
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list build args",
+            )
+        }
+    }
+}
+
+/// Sets a build arg of the authenticated user's namespace, passed to the build stage of
+/// every image built for it from the next deploy on.
+///
+/// The value is encrypted before it's stored.
+pub(crate) async fn set_build_arg(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(name): Path<String>,
+    Json(payload): Json<SetBuildArgRequest>,
+) -> impl IntoResponse {
+    let Some(cipher) = state.image_builder.build_arg_cipher.as_ref() else {
+        return json_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "Build args are disabled; the server has no BUILD_ARGS_KEY",
+        );
+    };
+    if let Err(e) = validate_build_arg(&name, &payload.value) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let existing = match BuildArgDBRepo::find_by_user(&state.db_conn, user.id).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            error!("Failed to list build args of {}: {}", user_uuid, e);
+            return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to set build arg");
+        }
+    };
+    if existing.len() >= MAX_BUILD_ARGS && !existing.iter().any(|arg| arg.name == name) {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            &format!("A namespace can have at most {} build args", MAX_BUILD_ARGS),
+        );
+    }
+
+    let value = match cipher.seal(user.id, &name, &payload.value) {
+        Ok(value) => value,
+        Err(e) => return e.into_response(),
+    };
+    match BuildArgDBRepo::set(&state.db_conn, user.id, name, value).await {
+        Ok(build_arg) => {
+            info!(
+                "Namespace '{}' set build arg '{}'",
+                user_uuid, build_arg.name
+            );
+            (
+                StatusCode::OK,
+                Json(BuildArgSummary {
+                    name: build_arg.name,
+                    updated_at: build_arg.updated_at.to_rfc3339(),
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to set build arg of {}: {}", user_uuid, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to set build arg")
+        }
+    }
+}
+
+/// Removes a build arg of the authenticated user's namespace; images already built keep
+/// whatever they were built with
+pub(crate) async fn remove_build_arg(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match BuildArgDBRepo::delete(&state.db_conn, user.id, &name).await {
+        Ok(true) => {
+            info!("Namespace '{}' removed build arg '{}'", user_uuid, name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => json_error(StatusCode::NOT_FOUND, "Build arg not found"),
+        Err(e) => {
+            error!(
+                "Failed to remove build arg {} of {}: {}",
+                name, user_uuid, e
+            );
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove build arg",
+            )
+        }
+    }
+}
+
+async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(json_error(StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user",
+            ))
+        }
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
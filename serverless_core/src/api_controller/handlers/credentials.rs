@@ -0,0 +1,199 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use db_entities::egress_credential::Model as EgressCredential;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::db::egress_credential::EgressCredentialDBRepo;
+use crate::lifecycle_manager::gateway::{
+    sealed_name, validate_credential, DEFAULT_HEADER, MAX_CREDENTIALS,
+};
+
+/// Request setting a credential functions' gateway requests are signed with
+#[derive(Debug, Deserialize)]
+pub struct SetCredentialRequest {
+    base_url: String,
+    /// Header the value is sent in; `Authorization` when unset
+    header: Option<String>,
+    /// Complete header value, e.g. `Bearer sk_live_...`
+    value: String,
+}
+
+/// A credential of the namespace; its value is never sent back
+#[derive(Debug, Serialize)]
+pub struct CredentialSummary {
+    name: String,
+    base_url: String,
+    header: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+impl From<EgressCredential> for CredentialSummary {
+    fn from(credential: EgressCredential) -> Self {
+        Self {
+            name: credential.name,
+            base_url: credential.base_url,
+            header: credential.header,
+            updated_at: credential.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Lists the credentials of the authenticated user's namespace
+pub(crate) async fn list_credentials(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match EgressCredentialDBRepo::find_by_user(&state.db_conn, user.id).await {
+        Ok(credentials) => {
+            let credentials: Vec<CredentialSummary> = credentials
+                .into_iter()
+                .map(CredentialSummary::from)
+                .collect();
+            (StatusCode::OK, Json(credentials)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to list credentials of {}: {}", user_uuid, e);
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list credentials",
+            )
+        }
+    }
+}
+
+/// Sets a credential of the authenticated user's namespace, which its functions can then
+/// send requests with through `/internal/fetch/{name}/...` without ever seeing it.
+///
+/// The value is encrypted with the build args key before it's stored.
+pub(crate) async fn set_credential(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(name): Path<String>,
+    Json(payload): Json<SetCredentialRequest>,
+) -> impl IntoResponse {
+    let Some(cipher) = state.image_builder.build_arg_cipher.as_ref() else {
+        return json_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "Credentials are disabled; the server has no BUILD_ARGS_KEY",
+        );
+    };
+    let header = payload.header.unwrap_or_else(|| DEFAULT_HEADER.to_string());
+    if let Err(e) = validate_credential(&name, &payload.base_url, &header, &payload.value) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let existing = match EgressCredentialDBRepo::find_by_user(&state.db_conn, user.id).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            error!("Failed to list credentials of {}: {}", user_uuid, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to set credential",
+            );
+        }
+    };
+    if existing.len() >= MAX_CREDENTIALS && !existing.iter().any(|c| c.name == name) {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            &format!(
+                "A namespace can have at most {} credentials",
+                MAX_CREDENTIALS
+            ),
+        );
+    }
+
+    let value = match cipher.seal(user.id, &sealed_name(&name), &payload.value) {
+        Ok(value) => value,
+        Err(e) => return e.into_response(),
+    };
+    match EgressCredentialDBRepo::set(
+        &state.db_conn,
+        user.id,
+        name,
+        payload.base_url,
+        header,
+        value,
+    )
+    .await
+    {
+        Ok(credential) => {
+            info!(
+                "Namespace '{}' set credential '{}' for {}",
+                user_uuid, credential.name, credential.base_url
+            );
+            (StatusCode::OK, Json(CredentialSummary::from(credential))).into_response()
+        }
+        Err(e) => {
+            error!("Failed to set credential of {}: {}", user_uuid, e);
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to set credential",
+            )
+        }
+    }
+}
+
+/// Removes a credential of the authenticated user's namespace; gateway requests naming it
+/// answer 404 from then on
+pub(crate) async fn remove_credential(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match EgressCredentialDBRepo::delete(&state.db_conn, user.id, &name).await {
+        Ok(true) => {
+            info!("Namespace '{}' removed credential '{}'", user_uuid, name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => json_error(StatusCode::NOT_FOUND, "Credential not found"),
+        Err(e) => {
+            error!(
+                "Failed to remove credential {} of {}: {}",
+                name, user_uuid, e
+            );
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove credential",
+            )
+        }
+    }
+}
+
+async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(json_error(StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user",
+            ))
+        }
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,480 @@
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use std::net::SocketAddr;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::functions::deploy_and_apply;
+use crate::api_controller::middlewares::admin::AdminUser;
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::audit::AuditLogDBRepo;
+use crate::db::auth::AuthDBRepo;
+use crate::db::models::{DeployApprovers, DeployableFunction, PendingDeployStatus};
+use crate::lifecycle_manager::deploy_gate::{
+    check_deploy_lock, find_for_decision, list_approvals, list_deploy_locks, lock_deploys,
+    record_decision, set_deploy_approvers, unlock_deploys, DeployGateError, LockRequest,
+    PendingDeployInfo,
+};
+use crate::lifecycle_manager::slo::deploy_freeze;
+use crate::utils::utils::client_ip;
+
+/// Audit log actions of the deploy gates, recorded in the namespace deployed into
+pub const LOCK_AUDIT_ACTION: &str = "deploy.lock";
+pub const UNLOCK_AUDIT_ACTION: &str = "deploy.unlock";
+pub const APPROVERS_AUDIT_ACTION: &str = "deploy.approvers";
+pub const REQUEST_AUDIT_ACTION: &str = "deploy.request";
+pub const APPROVE_AUDIT_ACTION: &str = "deploy.approve";
+pub const REJECT_AUDIT_ACTION: &str = "deploy.reject";
+
+/// Lists the deploy locks of the authenticated user's namespace
+pub(crate) async fn list_locks(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match list_deploy_locks(&state.db_conn, &user).await {
+        Ok(locks) => (StatusCode::OK, Json(locks)).into_response(),
+        Err(e) => deploy_gate_error(e),
+    }
+}
+
+/// Locks deploys of every function of the authenticated user's namespace
+pub(crate) async fn lock_namespace(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<LockRequest>,
+) -> impl IntoResponse {
+    lock(
+        &state,
+        user_uuid,
+        source_ip(&state, peer, &headers),
+        None,
+        request,
+    )
+    .await
+}
+
+/// Locks deploys of one of the authenticated user's functions; it needn't be deployed yet
+pub(crate) async fn lock_function(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(function_name): Path<String>,
+    Json(request): Json<LockRequest>,
+) -> impl IntoResponse {
+    let source_ip = source_ip(&state, peer, &headers);
+    lock(&state, user_uuid, source_ip, Some(function_name), request).await
+}
+
+/// Lifts the namespace lock; locks of single functions stay
+pub(crate) async fn unlock_namespace(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    unlock(&state, user_uuid, source_ip(&state, peer, &headers), None).await
+}
+
+/// Lifts the lock of one of the authenticated user's functions
+pub(crate) async fn unlock_function(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    let source_ip = source_ip(&state, peer, &headers);
+    unlock(&state, user_uuid, source_ip, Some(function_name)).await
+}
+
+async fn lock(
+    state: &AppState,
+    user_uuid: Uuid,
+    source_ip: Option<String>,
+    function_name: Option<String>,
+    request: LockRequest,
+) -> Response {
+    let user = match find_user(state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match lock_deploys(&state.db_conn, &user, function_name.clone(), request.reason).await {
+        Ok(lock) => {
+            info!(
+                namespace = %user_uuid,
+                function = ?function_name,
+                "Deploys locked"
+            );
+            let details = serde_json::json!({ "reason": lock.reason });
+            audit(
+                state,
+                &user,
+                &user.email,
+                LOCK_AUDIT_ACTION,
+                function_name,
+                details,
+                source_ip,
+            )
+            .await;
+            (StatusCode::OK, Json(lock)).into_response()
+        }
+        Err(e) => deploy_gate_error(e),
+    }
+}
+
+async fn unlock(
+    state: &AppState,
+    user_uuid: Uuid,
+    source_ip: Option<String>,
+    function_name: Option<String>,
+) -> Response {
+    let user = match find_user(state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match unlock_deploys(&state.db_conn, &user, function_name.as_deref()).await {
+        Ok(()) => {
+            info!(
+                namespace = %user_uuid,
+                function = ?function_name,
+                "Deploys unlocked"
+            );
+            audit(
+                state,
+                &user,
+                &user.email,
+                UNLOCK_AUDIT_ACTION,
+                function_name,
+                serde_json::Value::Null,
+                source_ip,
+            )
+            .await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => deploy_gate_error(e),
+    }
+}
+
+/// Returns the accounts that approve the authenticated user's deploys
+pub(crate) async fn get_approvers(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match find_user(&state, user_uuid).await {
+        Ok(user) => (StatusCode::OK, Json(DeployApprovers::from_model(&user))).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Sets the accounts that approve the authenticated user's deploys; see
+/// [`set_deploy_approvers`]. Approvers can only be added this way, removing them takes
+/// an admin.
+pub(crate) async fn set_approvers(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(approvers): Json<DeployApprovers>,
+) -> impl IntoResponse {
+    let source_ip = source_ip(&state, peer, &headers);
+    replace_approvers(&state, user_uuid, None, source_ip, approvers).await
+}
+
+/// Replaces the accounts that approve a namespace's deploys, removing approvers included.
+/// An empty list deploys without approval again.
+pub(crate) async fn set_namespace_approvers(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(namespace): Path<Uuid>,
+    Json(approvers): Json<DeployApprovers>,
+) -> impl IntoResponse {
+    let source_ip = source_ip(&state, peer, &headers);
+    replace_approvers(&state, namespace, Some("admin"), source_ip, approvers).await
+}
+
+/// Sets a namespace's approvers; `admin` is the actor when an admin does
+async fn replace_approvers(
+    state: &AppState,
+    user_uuid: Uuid,
+    admin: Option<&str>,
+    source_ip: Option<String>,
+    approvers: DeployApprovers,
+) -> Response {
+    let user = match find_user(state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match set_deploy_approvers(&state.db_conn, user.clone(), approvers, admin.is_some()).await {
+        Ok(approvers) => {
+            let details = serde_json::json!({ "approvers": approvers.approvers });
+            audit(
+                state,
+                &user,
+                admin.unwrap_or(&user.email),
+                APPROVERS_AUDIT_ACTION,
+                None,
+                details,
+                source_ip,
+            )
+            .await;
+            (StatusCode::OK, Json(approvers)).into_response()
+        }
+        Err(e) => deploy_gate_error(e),
+    }
+}
+
+/// Lists the deploys waiting for approval that the authenticated user requested, or may
+/// approve as an approver of another namespace
+pub(crate) async fn list_pending_deploys(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match list_approvals(&state.db_conn, &user).await {
+        Ok(approvals) => (StatusCode::OK, Json(approvals)).into_response(),
+        Err(e) => deploy_gate_error(e),
+    }
+}
+
+/// Approves a deploy waiting in another namespace, which deploys the archive it was
+/// requested with.
+///
+/// The function is checked against the namespace's locks and, unless the deploy was
+/// requested with `force`, its SLO deploy freeze again, as either may have changed while
+/// it waited.
+pub(crate) async fn approve_deploy(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let approver = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let (deploy, owner) = match find_for_decision(&state.db_conn, id, &approver).await {
+        Ok(found) => found,
+        Err(e) => return deploy_gate_error(e),
+    };
+    let function_name = deploy.function_name.clone();
+
+    if let Err(e) = check_deploy_lock(&state.db_conn, &owner, &function_name).await {
+        return deploy_gate_error(e);
+    }
+    if !deploy.force {
+        let mut cache_conn = state.cache_conn.clone();
+        if let Some(report) =
+            deploy_freeze(&state.db_conn, &mut cache_conn, &function_name, owner.uuid).await
+        {
+            return (
+                StatusCode::CONFLICT,
+                format!(
+                    "Deploys of '{}' are frozen: its error budget is spent ({:.3}% of invocations good over {} days, objective {}%)",
+                    function_name, report.compliance, report.window_days, report.objective
+                ),
+            )
+                .into_response();
+        }
+    }
+
+    // Claimed before deploying, so a second approval doesn't deploy it twice
+    if let Err(e) = record_decision(
+        &state.db_conn,
+        &deploy,
+        PendingDeployStatus::Pending,
+        PendingDeployStatus::Approved,
+        &approver,
+    )
+    .await
+    {
+        return deploy_gate_error(e);
+    }
+    let source_ip = source_ip(&state, peer, &headers);
+    let details = serde_json::json!({ "approval": deploy.id, "requested_by": deploy.requested_by });
+    audit(
+        &state,
+        &owner,
+        &approver.email,
+        APPROVE_AUDIT_ACTION,
+        Some(function_name.clone()),
+        details,
+        source_ip,
+    )
+    .await;
+
+    let function = DeployableFunction {
+        name: function_name.clone(),
+        content: deploy.archive.clone(),
+        user_uuid: owner.uuid,
+        history: Vec::new(),
+        preview: None,
+    };
+    match deploy_and_apply(&state, function).await {
+        Ok(res) => {
+            info!(
+                function = %function_name,
+                namespace = %owner.uuid,
+                approver = %approver.email,
+                "Approved deploy succeeded"
+            );
+            (
+                StatusCode::OK,
+                format!(
+                    "{}\nFunction: {}\nUser UUID: {}",
+                    res, function_name, owner.uuid
+                ),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            if let Err(e) = record_decision(
+                &state.db_conn,
+                &deploy,
+                PendingDeployStatus::Approved,
+                PendingDeployStatus::Failed,
+                &approver,
+            )
+            .await
+            {
+                error!(
+                    "Failed to record the failure of deploy {}: {}",
+                    deploy.id, e
+                );
+            }
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to deploy function: {}", e),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Rejects a deploy waiting in another namespace; its archive is never deployed
+pub(crate) async fn reject_deploy(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let approver = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let (deploy, owner) = match find_for_decision(&state.db_conn, id, &approver).await {
+        Ok(found) => found,
+        Err(e) => return deploy_gate_error(e),
+    };
+    if let Err(e) = record_decision(
+        &state.db_conn,
+        &deploy,
+        PendingDeployStatus::Pending,
+        PendingDeployStatus::Rejected,
+        &approver,
+    )
+    .await
+    {
+        return deploy_gate_error(e);
+    }
+
+    let source_ip = source_ip(&state, peer, &headers);
+    let details = serde_json::json!({ "approval": deploy.id, "requested_by": deploy.requested_by });
+    audit(
+        &state,
+        &owner,
+        &approver.email,
+        REJECT_AUDIT_ACTION,
+        Some(deploy.function_name.clone()),
+        details,
+        source_ip,
+    )
+    .await;
+
+    let mut rejected = PendingDeployInfo::new((&deploy).into(), owner.uuid);
+    rejected.status = PendingDeployStatus::Rejected.as_str().to_string();
+    rejected.decided_by = Some(approver.email);
+    (StatusCode::OK, Json(rejected)).into_response()
+}
+
+/// Maps a refused deploy or decision to its response
+pub(crate) fn deploy_gate_error(e: DeployGateError) -> Response {
+    let status = match &e {
+        DeployGateError::Locked(_) => StatusCode::LOCKED,
+        DeployGateError::ApprovalRequired(_) | DeployGateError::Forbidden(_) => {
+            StatusCode::FORBIDDEN
+        }
+        DeployGateError::NotFound(_) => StatusCode::NOT_FOUND,
+        DeployGateError::AlreadyDecided(_) => StatusCode::CONFLICT,
+        DeployGateError::InvalidApprovers(_) => StatusCode::BAD_REQUEST,
+        DeployGateError::SystemError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string()).into_response()
+}
+
+/// Records a change to a namespace's deploy gates, or a decision on one of its deploys,
+/// in its audit log; the change is made already, so a failure is only logged
+pub(crate) async fn audit(
+    state: &AppState,
+    namespace: &AuthUser,
+    actor: &str,
+    action: &str,
+    target: Option<String>,
+    details: serde_json::Value,
+    source_ip: Option<String>,
+) {
+    if let Err(e) = AuditLogDBRepo::record(
+        &state.db_conn,
+        namespace.id,
+        actor.to_string(),
+        action,
+        target,
+        Some(details),
+        source_ip,
+    )
+    .await
+    {
+        error!("Failed to record {} in {}: {}", action, namespace.uuid, e);
+    }
+}
+
+pub(crate) fn source_ip(state: &AppState, peer: SocketAddr, headers: &HeaderMap) -> Option<String> {
+    client_ip(
+        peer,
+        headers,
+        state.config.server_config.trust_forwarded_for,
+    )
+    .map(|ip| ip.to_string())
+}
+
+pub(crate) async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "User not found".to_string()).into_response()),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user".to_string(),
+            )
+                .into_response())
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use axum::body::Body;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::Json;
+use futures_util::stream::StreamExt;
+use runtime::core::dev::{toolchain, DEV_PORT, MAX_DEV_CONTAINERS_PER_NAMESPACE};
+use runtime::core::logs::LogMessage;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::handlers::functions::{
+    read_field_chunks, validate_function_call_inputs,
+};
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::dev::{dev_sources, DevInstance};
+use crate::lifecycle_manager::error::ServelessCoreError;
+use crate::lifecycle_manager::invoke::InvocationContext;
+use crate::utils::utils::{
+    generate_hash, make_request, BodyLimits, ProxyOptions, DEFAULT_TIMEOUT_SECS,
+};
+
+/// Starts a dev container for one of the authenticated user's functions, replacing the
+/// one it has, from the bundle `invok dev --remote` uploads: a multipart form with the
+/// function's archive, as deployed, in `file`.
+///
+/// The function needn't be deployed, but its dev container gets the deployed function's
+/// egress allowlist and sandbox. Returns the container and the path it's invoked on.
+pub(crate) async fn start_dev_container(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&user_uuid.to_string(), &function_name) {
+        return response;
+    }
+
+    let mut bundle = None;
+    while let Ok(Some(mut field)) = multipart.next_field().await {
+        if field.name() != Some("file") {
+            continue;
+        }
+        match read_field_chunks(&mut field, state.config.function_config.max_function_size).await {
+            Ok(buffer) => bundle = Some(buffer),
+            Err(e) => {
+                error!("Error reading file chunk: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Error reading file: {}", e),
+                )
+                    .into_response();
+            }
+        }
+    }
+    let Some(bundle) = bundle else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Missing the function's archive".to_string(),
+        )
+            .into_response();
+    };
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let dev_containers = state.autoscaler.dev_containers();
+    match dev_containers.count_in_namespace(&function_key).await {
+        Ok(running) if running >= MAX_DEV_CONTAINERS_PER_NAMESPACE => {
+            return (
+                StatusCode::CONFLICT,
+                format!(
+                    "Your namespace already runs {} dev containers; stop one first",
+                    MAX_DEV_CONTAINERS_PER_NAMESPACE
+                ),
+            )
+                .into_response();
+        }
+        Ok(_) => {}
+        Err(e) => return ServelessCoreError::from(e).into_response(),
+    }
+
+    let sources = match dev_sources(&state.db_conn, &function_name, user_uuid, bundle).await {
+        Ok(sources) => sources,
+        Err(e) => return e.into_response(),
+    };
+    if toolchain(&sources.runtime).is_none() {
+        return ServelessCoreError::BadFunction(format!(
+            "Runtime {} has no dev mode",
+            sources.runtime
+        ))
+        .into_response();
+    }
+
+    match dev_containers
+        .provision(
+            &function_key,
+            &sources.runtime,
+            sources.env,
+            sources.archive,
+        )
+        .await
+    {
+        Ok(container) => {
+            info!(
+                user_uuid = %user_uuid,
+                function = %function_name,
+                container_id = %container.container_id,
+                "Dev container started"
+            );
+            Json(DevInstance::new(&function_name, user_uuid, &container)).into_response()
+        }
+        Err(e) => {
+            error!(
+                user_uuid = %user_uuid,
+                function = %function_name,
+                "Failed to start dev container: {}",
+                e
+            );
+            ServelessCoreError::from(e).into_response()
+        }
+    }
+}
+
+/// Applies local changes to a function's dev container and restarts the function.
+///
+/// Takes a multipart form with the paths removed since the last sync as a JSON array in
+/// `deleted`, and the files added or changed as a tar archive in `files`. Paths are
+/// relative to the function's directory.
+pub(crate) async fn sync_dev_container(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&user_uuid.to_string(), &function_name) {
+        return response;
+    }
+
+    let mut deleted: Vec<String> = Vec::new();
+    let mut files = Vec::new();
+    while let Ok(Some(mut field)) = multipart.next_field().await {
+        match field.name() {
+            Some("deleted") => {
+                let parsed = match field.text().await {
+                    Ok(text) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                match parsed {
+                    Ok(paths) => deleted = paths,
+                    Err(e) => {
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid deleted paths: {}", e),
+                        )
+                            .into_response()
+                    }
+                }
+            }
+            Some("files") => {
+                match read_field_chunks(&mut field, state.config.function_config.max_function_size)
+                    .await
+                {
+                    Ok(buffer) => files = buffer,
+                    Err(e) => {
+                        error!("Error reading file chunk: {}", e);
+                        return (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            format!("Error reading file: {}", e),
+                        )
+                            .into_response();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let dev_containers = state.autoscaler.dev_containers();
+    let container = match dev_containers.find(&function_key).await {
+        Ok(Some(container)) => container,
+        Ok(None) => return no_dev_container(&function_name),
+        Err(e) => return ServelessCoreError::from(e).into_response(),
+    };
+
+    match dev_containers
+        .sync(&container.container_id, files, &deleted)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => ServelessCoreError::from(e).into_response(),
+    }
+}
+
+/// Removes a function's dev container
+pub(crate) async fn stop_dev_container(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&user_uuid.to_string(), &function_name) {
+        return response;
+    }
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    match state
+        .autoscaler
+        .dev_containers()
+        .remove(&function_key)
+        .await
+    {
+        Ok(true) => {
+            info!(user_uuid = %user_uuid, function = %function_name, "Dev container stopped");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => no_dev_container(&function_name),
+        Err(e) => ServelessCoreError::from(e).into_response(),
+    }
+}
+
+/// Streams the output of a function's dev container, builds included, via Server-Sent
+/// Events
+pub(crate) async fn stream_dev_container_logs(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&user_uuid.to_string(), &function_name) {
+        return response;
+    }
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let dev_containers = state.autoscaler.dev_containers();
+    let container = match dev_containers.find(&function_key).await {
+        Ok(Some(container)) => container,
+        Ok(None) => return no_dev_container(&function_name),
+        Err(e) => return ServelessCoreError::from(e).into_response(),
+    };
+    let log_stream = match dev_containers.logs(&container.container_id).await {
+        Ok(stream) => stream,
+        Err(e) => return ServelessCoreError::from(e).into_response(),
+    };
+
+    let sse_stream = log_stream.map(|log_msg| {
+        let event_data = match log_msg {
+            LogMessage::Content(content) => content,
+            LogMessage::Error(error) => format!("ERROR: {}", error),
+            LogMessage::End => "Log stream ended".to_string(),
+        };
+
+        Ok::<Event, Infallible>(Event::default().data(event_data))
+    });
+
+    let mut response = Sse::new(sse_stream)
+        .keep_alive(KeepAlive::default())
+        .into_response();
+
+    // Add headers to prevent NGINX buffering
+    let headers = response.headers_mut();
+    headers.insert("X-Accel-Buffering", HeaderValue::from_static("no"));
+    headers.insert("Cache-Control", HeaderValue::from_static("no-cache"));
+
+    response
+}
+
+/// Forwards an invocation to a function's dev container.
+///
+/// Dev containers are invoked like the deployed function, with the invocation context
+/// headers, but without its settings: no response cache, compression, recording or
+/// custom timeout.
+pub(crate) async fn call_dev_container(
+    State(state): State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    mut headers: HeaderMap,
+    request: Request<Body>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+    let user_uuid: Uuid = match namespace.parse() {
+        Ok(uuid) => uuid,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid function namespace format: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let addr = match state.autoscaler.dev_containers().find(&function_key).await {
+        Ok(Some(container)) => match container.ip_address {
+            Some(ip) => format!("{ip}:{DEV_PORT}"),
+            None => {
+                return ServelessCoreError::SystemError(
+                    "The dev container has no address".to_string(),
+                )
+                .into_response()
+            }
+        },
+        Ok(None) => return no_dev_container(&function_name),
+        Err(e) => return ServelessCoreError::from(e).into_response(),
+    };
+
+    let options = ProxyOptions {
+        limits: BodyLimits {
+            max_request_bytes: state.config.function_config.max_request_size,
+            max_response_bytes: state.config.function_config.max_response_size,
+        },
+        compression: false,
+        timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        hide_internal_addresses: state.config.server_config.hide_internal_addresses,
+        raw_body: false,
+    };
+    InvocationContext::new(&headers, user_uuid, &function_name, options.timeout)
+        .apply(&mut headers);
+    make_request(&addr, &function_name, query, headers, request, options)
+        .await
+        .into_response()
+}
+
+fn no_dev_container(function_name: &str) -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!(
+            "{} has no dev container; start one with `invok dev {} --remote`",
+            function_name, function_name
+        ),
+    )
+        .into_response()
+}
@@ -0,0 +1,103 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::function::Model as Function;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::egress::{function_key, publish_allowlist};
+use crate::utils::egress::EgressAllowlist;
+
+/// Returns the destinations a function may reach through the egress proxy
+pub(crate) async fn get_egress_allowlist(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    match find_function(&state, &function_name, user_uuid).await {
+        Ok(function) => (StatusCode::OK, Json(allowlist_of(&function))).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Replaces the destinations a function may reach through the egress proxy.
+///
+/// Takes effect on the function's next outbound connection: the proxies read allowlists
+/// from Redis, where the new list is mirrored once stored.
+pub(crate) async fn set_egress_allowlist(
+    State(mut state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+    Json(allowlist): Json<EgressAllowlist>,
+) -> impl IntoResponse {
+    if let Err(e) = allowlist.validate() {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            &format!("Invalid egress allowlist: {}", e),
+        );
+    }
+    let function = match find_function(&state, &function_name, user_uuid).await {
+        Ok(function) => function,
+        Err(response) => return response,
+    };
+
+    let stored = if allowlist.allow.is_empty() {
+        None
+    } else {
+        serde_json::to_value(&allowlist).ok()
+    };
+    let function =
+        match FunctionDBRepo::update_egress_allowlist(&state.db_conn, function, stored).await {
+            Ok(function) => function,
+            Err(e) => {
+                error!(
+                    "Failed to update egress allowlist of '{}': {}",
+                    function_name, e
+                );
+                return json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to update egress allowlist",
+                );
+            }
+        };
+
+    let function_key = function_key(&function);
+    if let Err(e) = publish_allowlist(&mut state.cache_conn, &function).await {
+        error!(
+            "Failed to publish egress allowlist of '{}': {}",
+            function_key, e
+        );
+        return json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Egress allowlist stored but not yet applied; retry the update",
+        );
+    }
+    info!("Updated egress allowlist of '{}'", function_key);
+    (StatusCode::OK, Json(allowlist)).into_response()
+}
+
+fn allowlist_of(function: &Function) -> EgressAllowlist {
+    function
+        .egress_allowlist
+        .clone()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+async fn find_function(
+    state: &AppState,
+    function_name: &str,
+    user_uuid: Uuid,
+) -> Result<Function, Response> {
+    FunctionDBRepo::find_function_by_name(&state.db_conn, function_name, user_uuid)
+        .await
+        .ok_or_else(|| json_error(StatusCode::NOT_FOUND, "Function not found"))
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,142 @@
+use axum::extract::ws::{WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use db_entities::auth::Model as AuthUser;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::audit::AuditLogDBRepo;
+use crate::db::auth::AuthDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::error::ServelessCoreError;
+use crate::lifecycle_manager::exec::{read_start, relay, ExecEvent};
+use crate::utils::utils::{client_ip, generate_hash};
+
+/// Audit log action of exec sessions
+const EXEC_AUDIT_ACTION: &str = "function.exec";
+
+/// Runs a command in one of the authenticated user's function containers, over a
+/// websocket, for debugging issues that only show up in production.
+///
+/// Once connected, the client sends a JSON start message (`command`, `tty`, and the
+/// terminal's `width` and `height`). Binary messages then carry the command's stdin one
+/// way and its output the other; `resize` and `eof` control messages and the `started`,
+/// `exited` and `error` events are JSON text messages. Every session is recorded in the
+/// audit log before the command runs.
+///
+/// Disabled unless the server sets `ALLOW_EXEC`.
+pub(crate) async fn exec_function(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(function_name): Path<String>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    if !state.config.function_config.allow_exec {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            "Exec is disabled; the server has no ALLOW_EXEC".to_string(),
+        )
+            .into_response();
+    }
+    if FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid)
+        .await
+        .is_none()
+    {
+        return ServelessCoreError::FunctionNotRegistered(format!(
+            "{} in namespace {}",
+            function_name, user_uuid
+        ))
+        .into_response();
+    }
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => return ServelessCoreError::SystemError(e.to_string()).into_response(),
+    };
+    let source_ip = client_ip(
+        peer,
+        &headers,
+        state.config.server_config.trust_forwarded_for,
+    )
+    .map(|ip| ip.to_string());
+
+    ws.on_upgrade(move |socket| run_session(state, socket, user, function_name, source_ip))
+}
+
+async fn run_session(
+    state: AppState,
+    mut socket: WebSocket,
+    user: AuthUser,
+    function_name: String,
+    source_ip: Option<String>,
+) {
+    let start = match read_start(&mut socket).await {
+        Ok(start) => start,
+        Err(message) => {
+            let _ = socket.send(ExecEvent::Error { message }.message()).await;
+            return;
+        }
+    };
+
+    // Nothing runs unless it's on record
+    if let Err(e) = AuditLogDBRepo::record(
+        &state.db_conn,
+        user.id,
+        user.email.clone(),
+        EXEC_AUDIT_ACTION,
+        Some(function_name.clone()),
+        Some(serde_json::json!({ "command": start.command, "tty": start.tty })),
+        source_ip,
+    )
+    .await
+    {
+        error!("Failed to record exec session of {}: {}", user.uuid, e);
+        let message = "The session could not be recorded in the audit log".to_string();
+        let _ = socket.send(ExecEvent::Error { message }.message()).await;
+        return;
+    }
+
+    let function_key = format!("{function_name}-{}", generate_hash(user.uuid));
+    let session = match state
+        .autoscaler
+        .exec_in_function(&function_key, start.command.clone(), start.tty)
+        .await
+    {
+        Ok(session) => session,
+        Err(e) => {
+            let message = e.to_string();
+            let _ = socket.send(ExecEvent::Error { message }.message()).await;
+            return;
+        }
+    };
+    if let (true, Some(width), Some(height)) = (start.tty, start.width, start.height) {
+        // The terminal is usable at its default size, so a failure isn't fatal
+        let _ = session.handle.resize(width, height).await;
+    }
+
+    let container: String = session.handle.container_id.chars().take(12).collect();
+    let started = ExecEvent::Started {
+        container: container.clone(),
+    };
+    if socket.send(started.message()).await.is_err() {
+        return;
+    }
+
+    let started_at = Instant::now();
+    let exit_code = relay(socket, session).await;
+    info!(
+        user = %user.uuid,
+        function = %function_name,
+        container = %container,
+        command = ?start.command,
+        exit_code = ?exit_code,
+        duration_secs = started_at.elapsed().as_secs(),
+        "Exec session ended"
+    );
+}
@@ -0,0 +1,135 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::lifecycle_manager::invalidation::Invalidation;
+use crate::utils::flags::{FeatureFlag, FeatureFlags};
+
+/// Lists the feature flags of the authenticated user's namespace
+pub(crate) async fn list_flags(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match find_user(&state, user_uuid).await {
+        Ok(user) => (StatusCode::OK, Json(FeatureFlags::from_model(&user))).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Sets a feature flag of the authenticated user's namespace, sent to its functions from
+/// the next invocation on
+pub(crate) async fn set_flag(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(key): Path<String>,
+    Json(flag): Json<FeatureFlag>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let mut flags = FeatureFlags::from_model(&user);
+    flags.flags.insert(key.clone(), flag.clone());
+    if let Err(e) = flags.validate() {
+        return json_error(StatusCode::BAD_REQUEST, &format!("Invalid flag: {}", e));
+    }
+    match store_flags(&state, user, flags).await {
+        Ok(()) => {
+            info!(
+                "Namespace '{}' set flag '{}' to '{}' for {}% of requests",
+                user_uuid, key, flag.value, flag.rollout
+            );
+            (StatusCode::OK, Json(flag)).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+/// Removes a feature flag of the authenticated user's namespace; its functions stop
+/// receiving its header from the next invocation on
+pub(crate) async fn remove_flag(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+
+    let mut flags = FeatureFlags::from_model(&user);
+    if flags.flags.remove(&key).is_none() {
+        return json_error(StatusCode::NOT_FOUND, "Flag not found");
+    }
+    match store_flags(&state, user, flags).await {
+        Ok(()) => {
+            info!("Namespace '{}' removed flag '{}'", user_uuid, key);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+/// Stores a namespace's flags and refreshes the caches of every controller
+async fn store_flags(
+    state: &AppState,
+    user: AuthUser,
+    flags: FeatureFlags,
+) -> Result<(), Response> {
+    let user_uuid = user.uuid;
+    let stored = if flags.flags.is_empty() {
+        None
+    } else {
+        serde_json::to_value(&flags).ok()
+    };
+    if let Err(e) = AuthDBRepo::update_feature_flags(&state.db_conn, user, stored).await {
+        error!("Failed to update flags of {}: {}", user_uuid, e);
+        return Err(json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update flags",
+        ));
+    }
+
+    state
+        .feature_flags
+        .write()
+        .unwrap()
+        .insert(user_uuid, flags);
+    let mut cache_conn = state.cache_conn.clone();
+    state
+        .cache_invalidator
+        .publish(
+            &mut cache_conn,
+            Invalidation::FeatureFlags {
+                namespace: user_uuid,
+            },
+        )
+        .await;
+    Ok(())
+}
+
+async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(json_error(StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user",
+            ))
+        }
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
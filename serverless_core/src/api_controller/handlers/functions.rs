@@ -1,21 +1,67 @@
 use axum::body::Body;
-use axum::extract::{Multipart, Path, Query, State};
-use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::extract::{ConnectInfo, Multipart, Path, Query, State};
+use axum::http::header::{
+    AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE,
+    IF_NONE_MATCH, RETRY_AFTER,
+};
+use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode};
 use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::IntoResponse;
 use futures_util::stream::StreamExt;
 use runtime::core::logs::LogMessage;
+use runtime::core::policy::Protocol;
+use runtime::core::runner::ResourceLimits;
+use runtime::core::usage::MIN_SAMPLES_FOR_RECOMMENDATION;
 
-use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use super::deploy_gate::{audit, deploy_gate_error, source_ip, REQUEST_AUDIT_ACTION};
+use super::storage::retention_policy;
+use crate::api_controller::middlewares::jwt::{AuthenticatedUser, DeployUser};
 use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::db::cache::FunctionCacheRepo;
 use crate::db::function::FunctionDBRepo;
-use crate::db::models::DeployableFunction;
+use crate::db::models::{DeployableFunction, FunctionSettings, NotificationKind};
 use crate::lifecycle_manager::deploy::deploy_function;
-use crate::lifecycle_manager::invoke::{check_function_status, start_function};
-use crate::utils::utils::{generate_hash, make_request};
+use crate::lifecycle_manager::deploy_gate::{check_deploy_lock, needs_approval, request_approval};
+use crate::lifecycle_manager::deploy_queue::{find_deployment, queue_deploy};
+use crate::lifecycle_manager::docs::{
+    openapi_content_type, render_html, FunctionDocs, DOCS_CSP,
+};
+use crate::lifecycle_manager::dry_run::{diff_deploy, DeployManifest};
+use crate::lifecycle_manager::error::ServelessCoreResult;
+use crate::lifecycle_manager::invoke::{
+    affinity_key, check_function_status, load_feature_flags, load_function_settings,
+    load_function_version, load_routing_rules, start_function, InvocationContext,
+    InvocationTimings, DEBUG_HEADER, TIMINGS_HEADER,
+};
+use crate::lifecycle_manager::notify::{notify, Notification};
+use crate::lifecycle_manager::payloads::{
+    PAYLOAD_HEADERS, PAYLOAD_SIZE_HEADER, PAYLOAD_URL_HEADER, RESPONSE_URL_HEADER, STORED_PAYLOAD,
+    STORED_RESPONSE_HEADER,
+};
+use crate::lifecycle_manager::preview::{
+    preview_name, version_instance_name, MAX_PREVIEW_SUFFIX_LENGTH, PREVIEW_SEPARATOR,
+};
+use crate::lifecycle_manager::replay::{
+    record_invocation, RecordedInvocation, ReplayOf, MAX_RECORDED_BODY_SIZE, REPLAY_HEADER,
+};
+use crate::lifecycle_manager::response_cache::{
+    cached_response, response_cache_key, store_response, CACHE_STATUS_HEADER,
+};
+use crate::lifecycle_manager::retention::prune_namespace;
+use crate::lifecycle_manager::slo::{deploy_freeze, slo_report};
+use crate::utils::flags::rollout_key;
+use crate::utils::http2::{forward_h2c, UpstreamPath};
+use crate::utils::http_cache::{add_validators, conditional_response};
+use crate::utils::utils::{
+    buffer_body, client_ip, generate_hash, make_request, FunctionTime, ProxyOptions,
+};
 use std::collections::HashMap;
 use std::convert::Infallible;
-use tracing::{error, info, warn};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 /// Handles uploading a function as a ZIP file with authentication.
@@ -24,17 +70,37 @@ use uuid::Uuid;
 /// If a file with a name ending in ".zip" is found, it reads its content
 /// and deploys the function for the authenticated user.
 ///
+/// A `preview` text field deploys a temporary preview instance of the function for that
+/// branch instead, invoked as `<name>--<branch>`.
+///
+/// The deploy is queued and the response is `202 Accepted` with a `Deployment: <id>`
+/// line; a job runner builds and deploys the function, and `GET /invok/deployments/:id`
+/// reports how it went. The archive is stored with the deploy, so a controller restart
+/// doesn't lose it.
+///
+/// Deploys are refused while the function or its namespace is locked. When the namespace
+/// has approvers, the archive is held until one of them approves it, and the response has
+/// an `Approval: <id>` line instead.
+///
+/// Accepts the deploy-only tokens CI workflows obtain with an OIDC token.
+///
 /// Returns an HTTP response indicating success or an appropriate error.
 pub(crate) async fn upload_function(
     State(state): State<AppState>,
-    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    DeployUser(user_uuid): DeployUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
     // Get configuration from state
     let supported_archive_ext = ".zip"; // Currently we only support ZIP
     let max_size = state.config.function_config.max_function_size;
+    let mut preview_branch: Option<String> = None;
+    let mut force = false;
+    let mut archive = None;
 
-    // Iterate over the fields in the multipart request.
+    // Iterate over the fields in the multipart request, in any order; the deploy starts
+    // once all of them are read
     while let Ok(Some(mut field)) = multipart.next_field().await {
         // Check if the field has a file name.
         if let Some(file_name) = field.file_name() {
@@ -53,43 +119,298 @@ pub(crate) async fn upload_function(
                             .into_response();
                     }
                 };
+                archive = Some((file_name, buffer));
+            }
+        } else if field.name() == Some("preview") {
+            match field.text().await {
+                Ok(branch) => preview_branch = Some(branch),
+                Err(e) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid preview branch: {}", e),
+                    )
+                        .into_response();
+                }
+            }
+        } else if field.name() == Some("force") {
+            // Deploys anyway when an SLO freezes them
+            force = matches!(field.text().await.as_deref(), Ok("true"));
+        } else {
+            error!("Encountered a multipart field without a filename");
+        }
+    }
+
+    let Some((file_name, buffer)) = archive else {
+        return (StatusCode::BAD_REQUEST, "Unexpected request").into_response();
+    };
+    let function_name = file_name
+        .strip_suffix(supported_archive_ext)
+        .unwrap_or(&file_name);
+    info!("Received service: {}", function_name);
+
+    // Names containing the separator would clash with preview instances
+    if function_name.contains(PREVIEW_SEPARATOR) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Function names may not contain '{}', it is reserved for previews",
+                PREVIEW_SEPARATOR
+            ),
+        )
+            .into_response();
+    }
 
-                let function_name = file_name
-                    .strip_suffix(supported_archive_ext)
-                    .unwrap_or(&file_name);
-                info!("Received service: {}", function_name);
+    let (deployed_name, preview_of) = match &preview_branch {
+        Some(branch) => match preview_name(function_name, branch) {
+            Ok(name) => (name, Some(function_name.to_string())),
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        },
+        None => (function_name.to_string(), None),
+    };
 
-                let function = DeployableFunction {
-                    name: function_name.to_string(),
-                    content: buffer,
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    // Previews don't touch the live function, so they are never locked,
+    // frozen or held for approval
+    if preview_of.is_none() {
+        if let Err(e) = check_deploy_lock(&state.db_conn, &user, &deployed_name).await {
+            return deploy_gate_error(e);
+        }
+        if !force {
+            let mut cache_conn = state.cache_conn.clone();
+            if let Some(report) =
+                deploy_freeze(&state.db_conn, &mut cache_conn, &deployed_name, user_uuid).await
+            {
+                return (
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Deploys of '{}' are frozen: its error budget is spent ({:.3}% of invocations good over {} days, objective {}%). Deploy with --force to override",
+                        deployed_name, report.compliance, report.window_days, report.objective
+                    ),
+                )
+                    .into_response();
+            }
+        }
+        if needs_approval(&user) {
+            let pending = match request_approval(
+                &state.db_conn,
+                &user,
+                &deployed_name,
+                buffer,
+                force,
+            )
+            .await
+            {
+                Ok(pending) => pending,
+                Err(e) => return deploy_gate_error(e),
+            };
+            let details = serde_json::json!({ "approval": pending.id, "force": force });
+            audit(
+                &state,
+                &user,
+                &user.email,
+                REQUEST_AUDIT_ACTION,
+                Some(deployed_name.clone()),
+                details,
+                source_ip(&state, peer, &headers),
+            )
+            .await;
+            notify(
+                &state.db_conn,
+                &state.jobs,
+                Notification::new(
+                    NotificationKind::DeployPendingApproval,
                     user_uuid,
-                };
+                    &deployed_name,
+                    format!("approve it with `invok approve {}`", pending.id),
+                ),
+            );
+            return (
+                StatusCode::ACCEPTED,
+                format!(
+                    "Deploy of '{}' is waiting for approval\nApproval: {}\nFunction: {}\nUser UUID: {}",
+                    deployed_name, pending.id, deployed_name, user_uuid
+                ),
+            )
+                .into_response();
+        }
+    }
 
-                // Deploy the function
-                return match deploy_function(&state.db_conn, function).await {
-                    Ok(res) => (
-                        StatusCode::OK,
-                        format!(
-                            "{}\nFunction: {}\nUser UUID: {}",
-                            res, function_name, user_uuid
-                        ),
-                    )
-                        .into_response(),
-                    Err(e) => {
-                        error!("Error deploying function {}: {}", function_name, e);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Failed to deploy function: {}", e),
-                        )
-                            .into_response()
-                    }
-                };
+    // A job runner builds and deploys it, even if this controller restarts
+    match queue_deploy(
+        &state.db_conn,
+        &state.jobs,
+        &user,
+        &deployed_name,
+        preview_of,
+        buffer,
+    )
+    .await
+    {
+        Ok(deployment) => (
+            StatusCode::ACCEPTED,
+            format!(
+                "Deploy of '{}' queued\nDeployment: {}\nFunction: {}\nUser UUID: {}",
+                deployed_name, deployment.id, deployed_name, user_uuid
+            ),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Reports where a deploy queued by [`upload_function`] is at, and its output once it
+/// finished. Deploys of other namespaces are not found.
+///
+/// Accepts deploy-only tokens, like the deploy itself.
+pub(crate) async fn get_deployment(
+    State(state): State<AppState>,
+    DeployUser(user_uuid): DeployUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user".to_string(),
+            )
+                .into_response();
+        }
+    };
+    match find_deployment(&state.db_conn, &user, id).await {
+        Ok(Some(deployment)) => (StatusCode::OK, axum::Json(deployment)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("Deployment {} not found", id),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Deploys a function and applies its settings to its running containers right away,
+/// notifying the namespace of the outcome.
+///
+/// # Returns
+///
+/// The message of the deploy.
+pub(crate) async fn deploy_and_apply(
+    state: &AppState,
+    function: DeployableFunction,
+) -> ServelessCoreResult<String> {
+    let deployed_name = function.name.clone();
+    let user_uuid = function.user_uuid;
+    match deploy_function(&state.db_conn, function, &state.image_builder).await {
+        Ok((mut res, settings)) => {
+            // Flag deprecated runtimes and vulnerable dependencies the new version ships
+            let deployed = if state.dependency_advisories.is_configured() {
+                FunctionDBRepo::find_function_by_name(&state.db_conn, &deployed_name, user_uuid)
+                    .await
+            } else {
+                None
+            };
+            if let Some(deployed) = deployed {
+                for warning in state.dependency_advisories.warnings(&deployed) {
+                    res.push('\n');
+                    res.push_str(&warning);
+                }
             }
-        } else {
-            error!("Encountered a multipart field without a filename");
+            let function_key = format!("{deployed_name}-{}", generate_hash(user_uuid));
+            state
+                .autoscaler
+                .set_function_policy(&function_key, settings.policy());
+            state
+                .function_versions
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state
+                .function_settings
+                .write()
+                .unwrap()
+                .insert(function_key, settings);
+            // A new TCP or UDP service gets its port right away
+            state.service_listeners.refresh();
+            // Drops the old version, or a lookup that found nothing
+            let mut cache_conn = state.cache_conn.clone();
+            let _ = FunctionCacheRepo::remove_function(&mut cache_conn, user_uuid, &deployed_name)
+                .await;
+            state
+                .cache_invalidator
+                .publish_function(&mut cache_conn, &deployed_name, user_uuid)
+                .await;
+            notify(
+                &state.db_conn,
+                &state.jobs,
+                Notification::new(
+                    NotificationKind::DeploySucceeded,
+                    user_uuid,
+                    &deployed_name,
+                    res.clone(),
+                ),
+            );
+            // The new version may take the namespace past its retention policy
+            let (conn, autoscaler) = (state.db_conn.clone(), state.autoscaler.clone());
+            let policy = retention_policy(state);
+            tokio::spawn(async move {
+                if let Err(e) =
+                    prune_namespace(&conn, &mut cache_conn, &autoscaler, user_uuid, policy).await
+                {
+                    warn!("Failed to prune versions of namespace {}: {}", user_uuid, e);
+                }
+            });
+            Ok(res)
+        }
+        Err(e) => {
+            error!("Error deploying function {}: {}", deployed_name, e);
+            notify(
+                &state.db_conn,
+                &state.jobs,
+                Notification::new(
+                    NotificationKind::DeployFailed,
+                    user_uuid,
+                    &deployed_name,
+                    e.to_string(),
+                ),
+            );
+            Err(e)
         }
     }
-    (StatusCode::BAD_REQUEST, "Unexpected request").into_response()
+}
+
+/// Shows what deploying a function would change, without deploying it.
+///
+/// Takes the digests of the archive `invok deploy --dry-run` would have uploaded and of
+/// its config's environment, see [`diff_deploy`], and answers with the changed files,
+/// config fields, environment variables and resources. Accepts deploy-only tokens, like
+/// the deploy itself.
+pub(crate) async fn diff_function_deploy(
+    State(state): State<AppState>,
+    DeployUser(user_uuid): DeployUser,
+    Path(function_name): Path<String>,
+    axum::Json(manifest): axum::Json<DeployManifest>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&user_uuid.to_string(), &function_name) {
+        return response;
+    }
+    match diff_deploy(&state.db_conn, &function_name, user_uuid, manifest).await {
+        Ok(diff) => (StatusCode::OK, axum::Json(diff)).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
 /// List functions for an authenticated user
@@ -97,8 +418,15 @@ pub(crate) async fn list_functions(
     State(state): State<AppState>,
     AuthenticatedUser(user_uuid): AuthenticatedUser,
 ) -> impl IntoResponse {
+    // Functions of a hibernated namespace are listed as such until its next request
+    let hibernated = AuthDBRepo::find_by_uuid(&state.db_read_conn, user_uuid)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|user| user.hibernated_at.is_some());
+
     // Get functions for this user
-    match FunctionDBRepo::find_functions_by_user_uuid(&state.db_conn, user_uuid).await {
+    match FunctionDBRepo::find_functions_by_user_uuid(&state.db_read_conn, user_uuid).await {
         Ok(functions) => {
             // Convert to a simpler representation
             let function_list = functions
@@ -107,7 +435,8 @@ pub(crate) async fn list_functions(
                     serde_json::json!({
                         "uuid": f.uuid.to_string(),
                         "name": f.name,
-                        "runtime": f.runtime
+                        "runtime": f.runtime,
+                        "hibernated": hibernated
                     })
                 })
                 .collect::<Vec<_>>();
@@ -126,7 +455,7 @@ pub(crate) async fn list_functions(
 }
 
 /// Reads all chunks from a multipart field into a buffer.
-async fn read_field_chunks(
+pub(crate) async fn read_field_chunks(
     field: &mut axum::extract::multipart::Field<'_>,
     max_size: usize,
 ) -> Result<Vec<u8>, String> {
@@ -162,22 +491,56 @@ async fn read_field_chunks(
 ///
 /// # Parameters
 ///
+/// * `peer` - Address of the connecting client, charged for any cold start
 /// * `namespace` - The user's UUID serving as a namespace for their functions
 /// * `function_name` - The name of the function to invoke
 /// * `query` - Query parameters to forward to the function
 /// * `headers` - HTTP headers to forward to the function
 /// * `request` - The complete HTTP request to forward
 ///
+/// Callers sending `x-invok-debug: timings` get the time spent in each phase of the
+/// invocation back in `x-invok-timings`.
+///
+/// Requests to functions with `record_invocations` set are recorded, so they can be
+/// replayed with `invok replay`.
+///
+/// Requests matching one of the function's routing rules are served by the version the
+/// rule names instead of the current one.
+///
+/// `GET` responses the function marks cacheable for shared caches (`Cache-Control:
+/// public, max-age=60`) are kept in the response cache and served from it until they
+/// expire or `invok purge` drops them. Successful `GET` responses get an `ETag` unless the
+/// function set one, and conditional requests naming it get `304 Not Modified`.
+///
+/// Functions with `"protocol": "h2c"` are called over HTTP/2, with both bodies streamed
+/// and trailers passed through, so they can serve gRPC. gRPC clients reach them on the
+/// service's own paths, see [`route_grpc`](crate::api_controller::middlewares::grpc::route_grpc).
+///
+/// With a payload store configured, request bodies above its threshold are streamed into
+/// it and the function reads them from the pre-signed URL in `x-invok-payload-url`.
+/// Functions may upload a large response to the URL in `x-invok-response-url` and answer
+/// with `x-invok-payload: stored`; it is then streamed from the store to the caller.
+///
 /// # Returns
 ///
 /// The service's response or an appropriate error response
 pub(crate) async fn call_function(
     mut state: State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     Path((namespace, function_name)): Path<(String, String)>,
     Query(query): Query<HashMap<String, String>>,
-    headers: HeaderMap,
-    request: Request<Body>,
+    mut headers: HeaderMap,
+    mut request: Request<Body>,
 ) -> impl IntoResponse {
+    let received_at = Instant::now();
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is shutting down, retry shortly".to_string(),
+        )
+            .into_response();
+    }
+
     // Validate input parameters
     if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
         return response;
@@ -201,6 +564,19 @@ pub(crate) async fn call_function(
         }
     };
 
+    // Requests matching one of the function's routing rules are served by the instance of
+    // the version the rule names
+    let client_ip = client_ip(
+        peer,
+        &headers,
+        state.config.server_config.trust_forwarded_for,
+    );
+    let rules = load_routing_rules(&state, &function_name, user_uuid).await;
+    let function_name = match rules.route(&headers, client_ip) {
+        Some(version) => version_instance_name(&function_name, version),
+        None => function_name,
+    };
+
     // Check function existence and authorization
     if let Err(e) = check_function_status(&mut state, &function_name, user_uuid).await {
         error!(
@@ -212,6 +588,37 @@ pub(crate) async fn call_function(
         );
         return e.into_response();
     }
+    state.namespace_activity.record(user_uuid);
+
+    // The debug header is for the platform, not the function, and only the platform
+    // hands out payload URLs
+    let report_timings = InvocationTimings::requested(&headers);
+    headers.remove(DEBUG_HEADER);
+    for name in PAYLOAD_HEADERS {
+        headers.remove(name);
+    }
+    // Only the platform marks replays; a caller's marker would skip recording and the
+    // response cache
+    let replay = match request.extensions().get::<ReplayOf>() {
+        Some(ReplayOf(id)) => {
+            debug!("Replaying invocation {} of {}", id, function_name);
+            true
+        }
+        None => {
+            headers.remove(REPLAY_HEADER);
+            false
+        }
+    };
+
+    // The namespace's feature flags replace any flag headers the caller sent
+    let rollout = rollout_key(&headers, client_ip);
+    load_feature_flags(&state, user_uuid)
+        .await
+        .apply(&mut headers, &rollout);
+    let mut timings = InvocationTimings {
+        auth: received_at.elapsed(),
+        ..Default::default()
+    };
 
     info!(
         namespace = %namespace,
@@ -220,12 +627,132 @@ pub(crate) async fn call_function(
         "Starting function invocation"
     );
 
-    let start_time = std::time::Instant::now();
-    let function_address =
-        start_function(state.autoscaler.clone(), &function_name, user_uuid).await;
+    let pool_start = Instant::now();
+    let settings = load_function_settings(&state, &function_name, user_uuid).await;
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let affinity = affinity_key(&settings.policy(), &headers);
+    let options = ProxyOptions {
+        limits: settings.body_limits(
+            state.config.function_config.max_request_size,
+            state.config.function_config.max_response_size,
+        ),
+        compression: settings.compression,
+        timeout: settings.timeout(),
+        hide_internal_addresses: state.config.server_config.hide_internal_addresses,
+        raw_body: settings.raw_body,
+    };
+    let context = InvocationContext::new(&headers, user_uuid, &function_name, options.timeout);
+
+    // Responses the function let shared caches keep are served without waking it.
+    // Replays always reach the function.
+    let method = request.method().clone();
+    let if_none_match = headers.get(IF_NONE_MATCH).cloned();
+    let authorized = headers.contains_key(AUTHORIZATION);
+    let version = load_function_version(&state, &function_name, user_uuid).await;
+    let cache_key = if state.config.function_config.response_cache_max_bytes > 0 && !replay {
+        response_cache_key(
+            &mut state.cache_conn,
+            &function_key,
+            version,
+            &method,
+            &headers,
+            &query,
+        )
+        .await
+    } else {
+        None
+    };
+    if let Some(key) = &cache_key {
+        if let Some((parts, body)) = cached_response(&mut state.cache_conn, key).await {
+            let mut response = conditional_response(&method, if_none_match.as_ref(), parts, body);
+            context.annotate(
+                response.headers_mut(),
+                version,
+                Duration::ZERO,
+                received_at.elapsed(),
+            );
+            return response;
+        }
+    }
+
+    // Starting a container is expensive; don't let one client (or one namespace's
+    // traffic) force an unbounded number of them
+    let warm = state.autoscaler.is_warm(&function_key);
+    if !warm {
+        if let Err(retry_after) = state.cold_start_budget.try_consume(client_ip, user_uuid) {
+            warn!(
+                namespace = %namespace,
+                function = %function_name,
+                client_ip = ?client_ip,
+                "Cold start budget exhausted"
+            );
+            let retry_after_secs = retry_after.as_secs().max(1);
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after_secs.to_string())],
+                format!("Too many cold starts, retry in {}s", retry_after_secs),
+            )
+                .into_response();
+        }
+    }
+
+    // Large request bodies are streamed into the payload store rather than through the
+    // proxy, and the function reads them from a pre-signed URL. Every invocation may
+    // upload a large response the same way.
+    let payload_keys = state
+        .payload_store
+        .as_ref()
+        .map(|store| store.keys(user_uuid, &function_name));
+    let mut stored_request = false;
+    if let (Some(store), Some(keys)) = (&state.payload_store, &payload_keys) {
+        if let Some(size) = store.stored_size(&method, &headers) {
+            if size > store.max_size() {
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Request body exceeds the {} byte limit", store.max_size()),
+                )
+                    .into_response();
+            }
+            let (parts, body) = request.into_parts();
+            if let Err(e) = store.upload(&keys.request, body, size, &headers).await {
+                error!(
+                    function = %function_name,
+                    request_id = %context.request_id,
+                    error = %e,
+                    "Failed to store request payload"
+                );
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    "Could not store request payload".to_string(),
+                )
+                    .into_response();
+            }
+            request = Request::from_parts(parts, Body::empty());
+            headers.remove(CONTENT_LENGTH);
+            headers.remove(CONTENT_ENCODING);
+            if let Ok(url) = HeaderValue::from_str(&store.presign(&Method::GET, &keys.request)) {
+                headers.insert(PAYLOAD_URL_HEADER, url);
+            }
+            headers.insert(PAYLOAD_SIZE_HEADER, HeaderValue::from(size));
+            stored_request = true;
+        }
+        if let Ok(url) = HeaderValue::from_str(&store.presign(&Method::PUT, &keys.response)) {
+            headers.insert(RESPONSE_URL_HEADER, url);
+        }
+    }
+
+    let start_time = Instant::now();
+    let function_address = start_function(
+        state.autoscaler.clone(),
+        &function_name,
+        user_uuid,
+        affinity.as_deref(),
+    )
+    .await;
 
-    let addr = match function_address {
-        Ok(addr) => {
+    // The lease keeps the container claimed until the request has been forwarded
+    let (addr, _lease) = match function_address {
+        Ok((addr, lease)) => {
             let duration = start_time.elapsed();
             info!(
                 namespace = %namespace,
@@ -235,7 +762,7 @@ pub(crate) async fn call_function(
                 startup_duration_ms = duration.as_millis(),
                 "Function started successfully"
             );
-            addr
+            (addr, lease)
         }
         Err(e) => {
             let duration = start_time.elapsed();
@@ -248,11 +775,17 @@ pub(crate) async fn call_function(
                 "Failed to start function"
             );
 
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to start function: {}", e),
-            )
-                .into_response();
+            // Tell the caller why the last container went away, if we know
+            let message = match state.autoscaler.get_crash_report(&function_key).await {
+                Some(report) => format!(
+                    "Failed to start function: {} (last crash: {})",
+                    e,
+                    report.summary()
+                ),
+                None => format!("Failed to start function: {}", e),
+            };
+
+            return (StatusCode::INTERNAL_SERVER_ERROR, message).into_response();
         }
     };
 
@@ -261,17 +794,181 @@ pub(crate) async fn call_function(
         function = %function_name,
         user_uuid = %user_uuid,
         address = %addr,
+        request_id = %context.request_id,
         "Function started successfully, forwarding request"
     );
 
-    // Forward the request to the service
-    make_request(&addr, &function_name, query, headers, request)
+    // Forward the request to the service, with the invocation context
+    let startup = start_time.elapsed();
+    if warm {
+        timings.pool = pool_start.elapsed();
+    } else {
+        timings.pool = start_time.duration_since(pool_start);
+        timings.cold_start = startup;
+    }
+    // Functions recording their invocations get the body buffered, so it can be stored
+    // with the status the function responds with. Replays aren't recorded again, nor are
+    // the streams of h2c functions. Bodies over the recording limit aren't recorded.
+    let mut recording = None;
+    if settings.record_invocations && settings.protocol == Protocol::Http1 && !replay {
+        let (parts, body) = request.into_parts();
+        let body = match buffer_body(body, options.limits.max_request_bytes).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        if body.len() <= MAX_RECORDED_BODY_SIZE {
+            recording = Some(RecordedInvocation::new(
+                &context,
+                &parts.method,
+                &query,
+                &headers,
+                &body,
+            ));
+        } else {
+            debug!(
+                function = %function_name,
+                bytes = body.len(),
+                "Request body too large to record"
+            );
+        }
+        request = Request::from_parts(parts, Body::from(body));
+    }
+
+    context.apply(&mut headers);
+    let response_start = Instant::now();
+    if settings.protocol == Protocol::H2c {
+        // Streams and trailers pass through as they come, so the response is neither read
+        // nor cached. gRPC calls go to the path they were made on.
+        let path_and_query = match request.extensions().get::<UpstreamPath>() {
+            Some(path) => path.0.clone(),
+            None => match request.uri().query() {
+                Some(query) => format!("/{}?{}", function_name, query),
+                None => format!("/{}", function_name),
+            },
+        };
+        let (parts, body) = request.into_parts();
+        let mut response = forward_h2c(
+            &state.h2c_client,
+            &addr,
+            &path_and_query,
+            headers,
+            parts.method,
+            body,
+            options.timeout,
+        )
+        .await;
+        context.annotate(
+            response.headers_mut(),
+            version,
+            startup,
+            response_start.elapsed(),
+        );
+        return response;
+    }
+    let response = make_request(&addr, &function_name, query, headers, request, options)
         .await
-        .into_response()
+        .into_response();
+    let forwarding = response_start.elapsed();
+    timings.function = response
+        .extensions()
+        .get::<FunctionTime>()
+        .map_or(Duration::ZERO, |time| time.0);
+    timings.proxy = forwarding.saturating_sub(timings.function);
+    if let (true, Some(store), Some(keys)) = (stored_request, &state.payload_store, &payload_keys) {
+        let store = store.clone();
+        let key = keys.request.clone();
+        tokio::spawn(async move { store.delete(&key).await });
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let stored_response = parts
+        .headers
+        .remove(STORED_RESPONSE_HEADER)
+        .is_some_and(|value| value == STORED_PAYLOAD);
+    let mut response = match (stored_response, &state.payload_store, payload_keys) {
+        // Responses the function uploaded to the payload store are streamed from it as
+        // they are, past the response cache
+        (true, Some(store), Some(keys)) => match store.download(keys.response, parts).await {
+            Ok(response) => response,
+            Err(e) => {
+                error!(
+                    function = %function_name,
+                    request_id = %context.request_id,
+                    error = %e,
+                    "Failed to read stored function response"
+                );
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    "Could not read function response".to_string(),
+                )
+                    .into_response();
+            }
+        },
+        _ => {
+            // The proxy buffered the response already, so validating and caching it only
+            // copies the body
+            let body = match hyper::body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    error!(
+                        function = %function_name,
+                        request_id = %context.request_id,
+                        error = %e,
+                        "Failed to read function response"
+                    );
+                    return (
+                        StatusCode::BAD_GATEWAY,
+                        "Could not read function response".to_string(),
+                    )
+                        .into_response();
+                }
+            };
+            add_validators(&method, parts.status, &mut parts.headers, &body);
+            if let Some(key) = cache_key {
+                store_response(
+                    state.cache_conn.clone(),
+                    key,
+                    authorized,
+                    &parts,
+                    &body,
+                    state.config.function_config.response_cache_max_bytes,
+                    Duration::from_secs(state.config.function_config.response_cache_max_ttl_secs),
+                );
+                parts
+                    .headers
+                    .insert(CACHE_STATUS_HEADER, HeaderValue::from_static("miss"));
+            }
+            conditional_response(&method, if_none_match.as_ref(), parts, body)
+        }
+    };
+    context.annotate(response.headers_mut(), version, startup, forwarding);
+
+    if let Some(mut invocation) = recording {
+        invocation.version = version;
+        invocation.status = response.status().as_u16();
+        let mut cache_conn = state.cache_conn.clone();
+        tokio::spawn(async move {
+            record_invocation(&mut cache_conn, user_uuid, &invocation).await;
+        });
+    }
+
+    let breakdown = timings.header_value(received_at.elapsed());
+    debug!(
+        function = %function_name,
+        request_id = %context.request_id,
+        timings = %breakdown,
+        "Invocation timings"
+    );
+    if report_timings {
+        if let Ok(value) = HeaderValue::from_str(&breakdown) {
+            response.headers_mut().insert(TIMINGS_HEADER, value);
+        }
+    }
+    response
 }
 
 /// Validates the input parameters for function calls
-fn validate_function_call_inputs(
+pub(crate) fn validate_function_call_inputs(
     namespace: &str,
     function_name: &str,
 ) -> Result<(), axum::response::Response> {
@@ -309,8 +1006,12 @@ fn validate_function_call_inputs(
             .into_response());
     }
 
-    // Check function name length (reasonable limits)
-    if function_name.len() > 25 {
+    // Check function name length (reasonable limits); preview instances carry their
+    // branch after the name, e.g. `hello--feature-login`
+    let base_name = function_name
+        .split_once(PREVIEW_SEPARATOR)
+        .map_or(function_name, |(base, _)| base);
+    if base_name.len() > 25 || function_name.len() > 25 + MAX_PREVIEW_SUFFIX_LENGTH {
         warn!(
             namespace = %namespace,
             function = %function_name,
@@ -327,6 +1028,50 @@ fn validate_function_call_inputs(
     Ok(())
 }
 
+/// Ensures the namespace in the path belongs to the authenticated user
+fn validate_namespace_owner(
+    namespace: &str,
+    function_name: &str,
+    user_uuid: Uuid,
+) -> Result<(), Box<axum::response::Response>> {
+    let namespace_uuid: Uuid = match namespace.parse() {
+        Ok(uuid) => uuid,
+        Err(e) => {
+            error!(
+                namespace = %namespace,
+                function = %function_name,
+                error = %e,
+                "Invalid function namespace format"
+            );
+            return Err(Box::new(
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid function namespace format: {}", e),
+                )
+                    .into_response(),
+            ));
+        }
+    };
+
+    if namespace_uuid != user_uuid {
+        error!(
+            namespace = %namespace,
+            function = %function_name,
+            user_uuid = %user_uuid,
+            "Namespace doesn't match authenticated user"
+        );
+        return Err(Box::new(
+            (
+                StatusCode::FORBIDDEN,
+                "You can only access your own functions".to_string(),
+            )
+                .into_response(),
+        ));
+    }
+
+    Ok(())
+}
+
 /// Stream logs from a deployed function in real-time
 ///
 /// This endpoint:
@@ -354,35 +1099,8 @@ pub(crate) async fn stream_function_logs(
     }
 
     // Validate namespace matches authenticated user
-    let namespace_uuid: Uuid = match namespace.parse() {
-        Ok(uuid) => uuid,
-        Err(e) => {
-            error!(
-                namespace = %namespace,
-                function = %function_name,
-                error = %e,
-                "Invalid function namespace format"
-            );
-            return (
-                StatusCode::BAD_REQUEST,
-                format!("Invalid function namespace format: {}", e),
-            )
-                .into_response();
-        }
-    };
-
-    if namespace_uuid != user_uuid {
-        error!(
-            namespace = %namespace,
-            function = %function_name,
-            user_uuid = %user_uuid,
-            "Namespace doesn't match authenticated user"
-        );
-        return (
-            StatusCode::FORBIDDEN,
-            "You can only access logs for your own functions".to_string(),
-        )
-            .into_response();
+    if let Err(response) = validate_namespace_owner(&namespace, &function_name, user_uuid) {
+        return *response;
     }
 
     // Check function existence
@@ -456,3 +1174,307 @@ pub(crate) async fn stream_function_logs(
 
     response
 }
+
+/// Describe a deployed function: its container pool and the last recorded crash
+///
+/// The crash report contains the exit code, whether the container was OOM killed and
+/// the last lines it logged, so users can see why a function keeps failing.
+pub(crate) async fn function_status(
+    State(state): State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+    if let Err(response) = validate_namespace_owner(&namespace, &function_name, user_uuid) {
+        return *response;
+    }
+
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid).await
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' not found in namespace '{}'",
+                function_name, namespace
+            ),
+        )
+            .into_response();
+    };
+
+    let uuid_short = generate_hash(user_uuid);
+    let function_key = format!("{function_name}-{uuid_short}");
+    let pool = state.autoscaler.get_pool_status(&function_key);
+    let last_crash = state.autoscaler.get_crash_report(&function_key).await;
+    let settings = FunctionSettings::from_model(&function);
+    let hibernated_at = AuthDBRepo::find_by_uuid(&state.db_read_conn, user_uuid)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|user| user.hibernated_at)
+        .map(|at| at.to_rfc3339());
+    let slo = match &settings.slo {
+        Some(slo) => {
+            let mut cache_conn = state.cache_conn.clone();
+            slo_report(&mut cache_conn, &function_key, slo).await.ok()
+        }
+        None => None,
+    };
+
+    (
+        StatusCode::OK,
+        axum::Json(serde_json::json!({
+            "name": function.name,
+            "namespace": namespace,
+            "runtime": function.runtime,
+            "settings": settings,
+            "service_port": function.service_port,
+            "hibernated_at": hibernated_at,
+            "slo": slo,
+            "pool": pool,
+            "last_crash": last_crash.map(|report| serde_json::json!({
+                "summary": report.summary(),
+                "report": report,
+            })),
+        })),
+    )
+        .into_response()
+}
+
+/// Boot output of the last container of a function that never became ready
+///
+/// Containers that don't print the readiness marker (missing env vars, listening on the
+/// wrong port, crashing on start) have their startup logs recorded by the runtime; this
+/// endpoint returns them for the authenticated user's function.
+pub(crate) async fn function_boot_logs(
+    State(state): State<AppState>,
+    Path(function_name): Path<String>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let namespace = user_uuid.to_string();
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+
+    if FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid)
+        .await
+        .is_none()
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' not found in namespace '{}'",
+                function_name, namespace
+            ),
+        )
+            .into_response();
+    }
+
+    let uuid_short = generate_hash(user_uuid);
+    let function_key = format!("{function_name}-{uuid_short}");
+
+    match state.autoscaler.get_boot_log(&function_key).await {
+        Some(boot_log) => (StatusCode::OK, axum::Json(boot_log)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            "No failed startups recorded for this function".to_string(),
+        )
+            .into_response(),
+    }
+}
+
+/// Resource usage sampled for a function and suggested memory/CPU limits
+///
+/// Peaks and averages are collected from every container of the function while the
+/// autoscaler monitors it. Recommendations only appear once enough samples exist.
+pub(crate) async fn function_recommendations(
+    State(state): State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+    if let Err(response) = validate_namespace_owner(&namespace, &function_name, user_uuid) {
+        return *response;
+    }
+
+    if FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid)
+        .await
+        .is_none()
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' not found in namespace '{}'",
+                function_name, namespace
+            ),
+        )
+            .into_response();
+    }
+
+    let uuid_short = generate_hash(user_uuid);
+    let function_key = format!("{function_name}-{uuid_short}");
+    let limits = ResourceLimits::default();
+    let (usage, recommendations) = state
+        .autoscaler
+        .get_resource_usage(&function_key)
+        .await
+        .unwrap_or_default();
+
+    (
+        StatusCode::OK,
+        axum::Json(serde_json::json!({
+            "name": function_name,
+            "namespace": namespace,
+            "limits": {
+                "memory_mb": limits.memory_bytes / 1024 / 1024,
+                "cpus": limits.cpus,
+            },
+            "usage": {
+                "samples": usage.samples,
+                "peak_cpu_cores": usage.peak_cpu_cores,
+                "avg_cpu_cores": usage.avg_cpu_cores(),
+                "peak_memory_mb": usage.peak_memory_bytes / 1024.0 / 1024.0,
+                "avg_memory_mb": usage.avg_memory_bytes() / 1024.0 / 1024.0,
+                "first_sample_at": usage.first_sample_at,
+                "last_sample_at": usage.last_sample_at,
+            },
+            "min_samples": MIN_SAMPLES_FOR_RECOMMENDATION,
+            "recommendations": recommendations,
+        })),
+    )
+        .into_response()
+}
+
+/// Compliance of a function with its SLO: invocations counted over the window, error
+/// budget left and burn rates
+pub(crate) async fn function_slo(
+    State(state): State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+    if let Err(response) = validate_namespace_owner(&namespace, &function_name, user_uuid) {
+        return *response;
+    }
+
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid).await
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' not found in namespace '{}'",
+                function_name, namespace
+            ),
+        )
+            .into_response();
+    };
+    let Some(slo) = FunctionSettings::from_model(&function).slo else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' has no SLO; set `slo` in its config.json",
+                function_name
+            ),
+        )
+            .into_response();
+    };
+
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    let mut cache_conn = state.cache_conn.clone();
+    match slo_report(&mut cache_conn, &function_key, &slo).await {
+        Ok(report) => (StatusCode::OK, axum::Json(report)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Documentation of a function, for anyone who wants to call it
+///
+/// Serves the README and OpenAPI document shipped in the function's bundle as an HTML
+/// page. `?format=markdown` returns the raw README and `?format=openapi` the raw OpenAPI
+/// document. Like invocations, docs don't require authentication.
+pub(crate) async fn function_docs(
+    State(state): State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    if let Err(response) = validate_function_call_inputs(&namespace, &function_name) {
+        return response;
+    }
+    let Ok(user_uuid) = namespace.parse::<Uuid>() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Invalid function namespace format".to_string(),
+        )
+            .into_response();
+    };
+
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid).await
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Function '{}' not found in namespace '{}'",
+                function_name, namespace
+            ),
+        )
+            .into_response();
+    };
+    let docs = FunctionDocs {
+        readme: function.readme,
+        openapi: function.openapi,
+    };
+
+    match query.get("format").map(String::as_str) {
+        None | Some("html") => (
+            StatusCode::OK,
+            [
+                (CONTENT_TYPE, "text/html; charset=utf-8"),
+                (CONTENT_SECURITY_POLICY, DOCS_CSP),
+            ],
+            render_html(&namespace, &function_name, &docs),
+        )
+            .into_response(),
+        Some("markdown") => match docs.readme {
+            Some(readme) => (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "text/markdown; charset=utf-8")],
+                readme,
+            )
+                .into_response(),
+            None => (
+                StatusCode::NOT_FOUND,
+                format!("Function '{}' has no README", function_name),
+            )
+                .into_response(),
+        },
+        Some("openapi") => match docs.openapi {
+            Some(spec) => (
+                StatusCode::OK,
+                [(CONTENT_TYPE, openapi_content_type(&spec))],
+                spec,
+            )
+                .into_response(),
+            None => (
+                StatusCode::NOT_FOUND,
+                format!("Function '{}' has no OpenAPI document", function_name),
+            )
+                .into_response(),
+        },
+        Some(other) => (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unknown docs format '{}' (expected html, markdown or openapi)",
+                other
+            ),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,118 @@
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{Path, State};
+use axum::http::{header::RETRY_AFTER, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::api_controller::middlewares::internal::FunctionCaller;
+use crate::api_controller::AppState;
+use crate::db::egress_credential::EgressCredentialDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::gateway::{sealed_name, Credential, GatewayError};
+use crate::utils::utils::strip_hop_by_hop;
+
+/// Path of a gateway request; whatever follows the credential is sent on under its base
+/// URL
+#[derive(Debug, Deserialize)]
+pub struct FetchPath {
+    credential: String,
+}
+
+/// Sends the calling function's request on under the base URL of one of its namespace's
+/// credentials, signed with it.
+///
+/// `/internal/fetch/stripe/charges?limit=3` with a `stripe` credential for
+/// `https://api.stripe.com/v1` reaches `https://api.stripe.com/v1/charges?limit=3`. The
+/// destination's response is passed back as is; the gateway answers 502 when it can't be
+/// reached and 503 while its circuit is open.
+pub(crate) async fn fetch(
+    State(state): State<AppState>,
+    FunctionCaller(function_key): FunctionCaller,
+    Path(FetchPath { credential: name }): Path<FetchPath>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let Some(cipher) = state.image_builder.build_arg_cipher.as_ref() else {
+        return json_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "Credentials are disabled; the server has no BUILD_ARGS_KEY",
+        );
+    };
+    let function = match FunctionDBRepo::find_by_function_key(&state.db_conn, &function_key).await {
+        Ok(Some(function)) => function,
+        Ok(None) => return json_error(StatusCode::NOT_FOUND, "Function not found"),
+        Err(e) => {
+            error!("Failed to load function '{}': {}", function_key, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load credential",
+            );
+        }
+    };
+    let stored = match EgressCredentialDBRepo::find(&state.db_conn, function.auth_id, &name).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return json_error(StatusCode::NOT_FOUND, "Credential not found"),
+        Err(e) => {
+            error!("Failed to load credential of '{}': {}", function_key, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load credential",
+            );
+        }
+    };
+    let value = match cipher.open(function.auth_id, &sealed_name(&name), &stored.value) {
+        Ok(value) => value,
+        Err(e) => return e.into_response(),
+    };
+    let credential = Credential {
+        base_url: stored.base_url,
+        header: stored.header,
+        value,
+    };
+
+    // Taken from the raw URI, so percent-encoding reaches the destination untouched
+    let mut path = uri
+        .path()
+        .strip_prefix(&format!("/internal/fetch/{}", name))
+        .unwrap_or_default()
+        .to_string();
+    if let Some(query) = uri.query() {
+        path = format!("{}?{}", path, query);
+    }
+
+    let circuit = format!("{}:{}", function.auth_id, name);
+    match state
+        .egress_gateway
+        .send(&circuit, &credential, method, &path, headers, body)
+        .await
+    {
+        Ok(response) => {
+            let status = response.status();
+            let mut headers = response.headers().clone();
+            strip_hop_by_hop(&mut headers);
+            (status, headers, StreamBody::new(response.bytes_stream())).into_response()
+        }
+        Err(e @ GatewayError::CircuitOpen(retry_after)) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+        Err(e @ GatewayError::Forbidden(_)) => {
+            warn!("Denied gateway request of '{}': {}", function_key, e);
+            json_error(StatusCode::FORBIDDEN, &e.to_string())
+        }
+        Err(e @ GatewayError::Unreachable(_)) => {
+            warn!("Gateway request of '{}' failed: {}", function_key, e);
+            json_error(StatusCode::BAD_GATEWAY, &e.to_string())
+        }
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
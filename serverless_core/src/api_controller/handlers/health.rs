@@ -0,0 +1,184 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use runtime::core::fairness::CapacityStatus;
+use runtime::{RestoreReport, ScanStatsSnapshot, TaskStatus};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::invoke::LookupStatsSnapshot;
+
+/// Maximum time a single dependency check may take before it is reported as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Result of checking a single dependency
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    healthy: bool,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// This controller's part among the replicas sharing the pools
+#[derive(Debug, Serialize)]
+pub struct ReplicaStatus {
+    id: String,
+    /// Whether it holds the leader lease and scales the pools; followers route
+    /// invocations from the state the leader persists
+    leader: bool,
+}
+
+/// Status of the controller and each of its dependencies
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    status: &'static str,
+    database: DependencyStatus,
+    redis: DependencyStatus,
+    docker: DependencyStatus,
+    prometheus: DependencyStatus,
+    /// Containers, CPU and memory committed on the Docker host; `exhausted` when
+    /// scale-ups are being refused. Doesn't affect readiness: warm functions still serve.
+    capacity: CapacityStatus,
+    /// How function lookups on the invocation path were answered since startup
+    function_lookups: LookupStatsSnapshot,
+    /// Set when replicas share the pools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replica: Option<ReplicaStatus>,
+    /// The autoscaler's background tasks; `restarting` ones failed and are started again
+    /// after a backoff. Doesn't affect readiness: invocations are still routed meanwhile.
+    background_tasks: Vec<TaskStatus>,
+    /// Scaling passes and pool checks since startup, with the checks that overran their
+    /// timeout and were skipped
+    scaling_passes: ScanStatsSnapshot,
+    /// What restoring the pools at startup or on taking the lead found, including the
+    /// restored containers that failed their probe and are being replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restore: Option<RestoreReport>,
+}
+
+impl HealthReport {
+    fn all_healthy(&self) -> bool {
+        self.database.healthy
+            && self.redis.healthy
+            && self.docker.healthy
+            && self.prometheus.healthy
+    }
+
+    /// Whether each dependency is reachable, without the details
+    pub(super) fn components(&self) -> [(&'static str, bool); 4] {
+        [
+            ("database", self.database.healthy),
+            ("redis", self.redis.healthy),
+            ("docker", self.docker.healthy),
+            ("prometheus", self.prometheus.healthy),
+        ]
+    }
+
+    pub(super) fn capacity_exhausted(&self) -> bool {
+        self.capacity.exhausted
+    }
+}
+
+/// Liveness probe.
+///
+/// Always returns 200 while the process is serving requests, so an orchestrator doesn't
+/// restart the controller because a dependency is down. Dependency status is still
+/// included in the body (`"status": "degraded"` when any check fails).
+pub(crate) async fn healthz(State(state): State<AppState>) -> impl IntoResponse {
+    let report = check_dependencies(&state).await;
+    (StatusCode::OK, Json(report))
+}
+
+/// Readiness probe.
+///
+/// Returns 503 unless the database, Redis, Docker and Prometheus are all reachable,
+/// so load balancers stop routing traffic to a controller that can't serve it. Also
+/// returns 503 while the controller is shutting down.
+pub(crate) async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let mut report = check_dependencies(&state).await;
+    if state.shutting_down.load(Ordering::SeqCst) {
+        report.status = "shutting_down";
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(report));
+    }
+
+    let status = if report.all_healthy() {
+        StatusCode::OK
+    } else {
+        warn!("Readiness check failed: {:?}", report);
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Check all dependencies concurrently
+pub(super) async fn check_dependencies(state: &AppState) -> HealthReport {
+    let mut cache_conn = state.cache_conn.clone();
+
+    let (database, redis, docker, prometheus) = tokio::join!(
+        timed_check(async { state.db_conn.ping().await.map_err(|e| e.to_string()) }),
+        timed_check(async move {
+            let pong: redis::RedisResult<String> =
+                redis::cmd("PING").query_async(&mut cache_conn).await;
+            pong.map(|_| ()).map_err(|e| e.to_string())
+        }),
+        timed_check(async {
+            if state.autoscaler.docker_health_check().await {
+                Ok(())
+            } else {
+                Err("Docker daemon is unreachable".to_string())
+            }
+        }),
+        timed_check(async {
+            if state.autoscaler.metrics_health_check().await {
+                Ok(())
+            } else {
+                Err("Prometheus is unreachable".to_string())
+            }
+        }),
+    );
+
+    let mut report = HealthReport {
+        status: "ok",
+        database,
+        redis,
+        docker,
+        prometheus,
+        capacity: state.autoscaler.capacity_status(),
+        function_lookups: state.lookup_stats.snapshot(),
+        replica: state.autoscaler.replicas().map(|replicas| ReplicaStatus {
+            id: replicas.replica_id().to_string(),
+            leader: replicas.is_leader(),
+        }),
+        background_tasks: state.autoscaler.background_tasks(),
+        scaling_passes: state.autoscaler.scan_stats(),
+        restore: state.autoscaler.restore_report(),
+    };
+    if !report.all_healthy() || !state.autoscaler.background_tasks_running() {
+        report.status = "degraded";
+    }
+    report
+}
+
+/// Run a dependency check with a timeout, recording how long it took
+async fn timed_check<F>(check: F) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {:?}", CHECK_TIMEOUT)),
+    };
+
+    DependencyStatus {
+        healthy: result.is_ok(),
+        latency_ms: start.elapsed().as_millis(),
+        error: result.err(),
+    }
+}
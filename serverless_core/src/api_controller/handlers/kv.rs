@@ -0,0 +1,163 @@
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::api_controller::middlewares::internal::FunctionCaller;
+use crate::api_controller::AppState;
+use crate::db::kv::{KvRepo, QUOTA_ERROR_CODE};
+
+/// Largest value a function may store under one key (64KB)
+pub const MAX_VALUE_SIZE: usize = 64 * 1024;
+
+/// Longest key a function may store a value under
+const MAX_KEY_LENGTH: usize = 256;
+
+/// Options of a write
+#[derive(Debug, Deserialize)]
+pub struct SetEntryOptions {
+    /// Seconds until the entry expires; it's kept until deleted when unset
+    ttl: Option<u64>,
+    /// Only write the entry if it doesn't exist, e.g. to take a lock
+    #[serde(default)]
+    if_absent: bool,
+}
+
+/// Options of an increment
+#[derive(Debug, Deserialize)]
+pub struct IncrementOptions {
+    /// What to add, negative to subtract; 1 when unset
+    by: Option<i64>,
+}
+
+/// Returns the value the calling function stored under a key
+pub(crate) async fn get_entry(
+    State(mut state): State<AppState>,
+    FunctionCaller(function_key): FunctionCaller,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = validate_key(&key) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    match KvRepo::get(&mut state.cache_conn, &function_key, &key).await {
+        Ok(Some(value)) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/octet-stream")],
+            value,
+        )
+            .into_response(),
+        Ok(None) => json_error(StatusCode::NOT_FOUND, "Key not found"),
+        Err(e) => {
+            error!("Failed to read key of '{}': {}", function_key, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read key")
+        }
+    }
+}
+
+/// Stores the request body, at most [`MAX_VALUE_SIZE`] bytes, under a key of the calling
+/// function.
+///
+/// With `if_absent`, a key that already exists is left alone and the request answers 409,
+/// so a function can take a lock by writing a key with a `ttl`.
+pub(crate) async fn set_entry(
+    State(mut state): State<AppState>,
+    FunctionCaller(function_key): FunctionCaller,
+    Path(key): Path<String>,
+    Query(options): Query<SetEntryOptions>,
+    value: Bytes,
+) -> impl IntoResponse {
+    if let Err(e) = validate_key(&key) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    if options.ttl == Some(0) {
+        return json_error(StatusCode::BAD_REQUEST, "ttl must be at least 1");
+    }
+
+    match KvRepo::set(
+        &mut state.cache_conn,
+        &function_key,
+        &key,
+        &value,
+        options.ttl,
+        options.if_absent,
+    )
+    .await
+    {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => json_error(StatusCode::CONFLICT, "Key already exists"),
+        Err(e) if e.code() == Some(QUOTA_ERROR_CODE) => quota_exceeded(),
+        Err(e) => {
+            error!("Failed to write key of '{}': {}", function_key, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to write key")
+        }
+    }
+}
+
+/// Adds to a counter of the calling function and returns its new value; a missing key
+/// counts from 0
+pub(crate) async fn increment_entry(
+    State(mut state): State<AppState>,
+    FunctionCaller(function_key): FunctionCaller,
+    Path(key): Path<String>,
+    Query(options): Query<IncrementOptions>,
+) -> impl IntoResponse {
+    if let Err(e) = validate_key(&key) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    let by = options.by.unwrap_or(1);
+    match KvRepo::increment(&mut state.cache_conn, &function_key, &key, by).await {
+        Ok(value) => (StatusCode::OK, Json(serde_json::json!({ "value": value }))).into_response(),
+        Err(e) if e.code() == Some(QUOTA_ERROR_CODE) => quota_exceeded(),
+        // The only error Redis answers INCRBY with
+        Err(e) if e.code() == Some("ERR") => {
+            json_error(StatusCode::CONFLICT, "Key doesn't hold an integer")
+        }
+        Err(e) => {
+            error!("Failed to increment key of '{}': {}", function_key, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to increment key")
+        }
+    }
+}
+
+/// Removes a key of the calling function
+pub(crate) async fn delete_entry(
+    State(mut state): State<AppState>,
+    FunctionCaller(function_key): FunctionCaller,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = validate_key(&key) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    match KvRepo::delete(&mut state.cache_conn, &function_key, &key).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => json_error(StatusCode::NOT_FOUND, "Key not found"),
+        Err(e) => {
+            error!("Failed to delete key of '{}': {}", function_key, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete key")
+        }
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(format!("Keys must be 1 to {} bytes long", MAX_KEY_LENGTH));
+    }
+    if key.chars().any(char::is_control) {
+        return Err("Keys must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+fn quota_exceeded() -> Response {
+    json_error(
+        StatusCode::INSUFFICIENT_STORAGE,
+        "The function's key-value quota is used up; delete keys or let them expire",
+    )
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,285 @@
+use axum::extract::{Multipart, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use tracing::{error, info};
+
+use super::deploy_gate::deploy_gate_error;
+use super::functions::read_field_chunks;
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::models::{NamespaceDefaults, NotificationSettings};
+use crate::lifecycle_manager::deploy_gate::check_direct_deploy;
+use crate::lifecycle_manager::transfer::{export_namespace, import_namespace};
+use crate::utils::utils::generate_hash;
+
+/// Returns the defaults the authenticated user's functions inherit at deploy time
+pub(crate) async fn get_namespace_defaults(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => {
+            (StatusCode::OK, Json(NamespaceDefaults::from_model(&user))).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load namespace defaults for {}: {}", user_uuid, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load namespace defaults".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Replaces the authenticated user's namespace defaults.
+///
+/// Defaults are resolved at deploy time, so already deployed functions keep their
+/// settings until they are redeployed.
+pub(crate) async fn set_namespace_defaults(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Json(defaults): Json<NamespaceDefaults>,
+) -> impl IntoResponse {
+    if let Err(e) = defaults.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid namespace defaults: {}", e),
+        )
+            .into_response();
+    }
+
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update namespace defaults".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let stored = if defaults == NamespaceDefaults::default() {
+        None
+    } else {
+        serde_json::to_value(&defaults).ok()
+    };
+    match AuthDBRepo::update_defaults(&state.db_conn, user, stored).await {
+        Ok(_) => (StatusCode::OK, Json(defaults)).into_response(),
+        Err(e) => {
+            error!(
+                "Failed to update namespace defaults for {}: {}",
+                user_uuid, e
+            );
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update namespace defaults".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Returns where the authenticated user's deploys and scaling anomalies are reported
+pub(crate) async fn get_notifications(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => (
+            StatusCode::OK,
+            Json(NotificationSettings::from_model(&user)),
+        )
+            .into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load notifications for {}: {}", user_uuid, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load notifications".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Replaces the authenticated user's notification targets; an empty list stops
+/// notifications
+pub(crate) async fn set_notifications(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Json(notifications): Json<NotificationSettings>,
+) -> impl IntoResponse {
+    if let Err(e) = notifications.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid notifications: {}", e),
+        )
+            .into_response();
+    }
+
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update notifications".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let stored = if notifications.targets.is_empty() {
+        None
+    } else {
+        serde_json::to_value(&notifications).ok()
+    };
+    match AuthDBRepo::update_notifications(&state.db_conn, user, stored).await {
+        Ok(_) => (StatusCode::OK, Json(notifications)).into_response(),
+        Err(e) => {
+            error!("Failed to update notifications for {}: {}", user_uuid, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update notifications".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Downloads every function of the authenticated user's namespace, with its version
+/// history and the namespace defaults, as a gzipped tarball
+pub(crate) async fn export_functions(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match export_namespace(&state.db_conn, user_uuid).await {
+        Ok(archive) => {
+            info!("Exported namespace {} ({} bytes)", user_uuid, archive.len());
+            (
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, "application/gzip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"invok-{}.tar.gz\"", user_uuid),
+                    ),
+                ],
+                archive,
+            )
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Restores an archive produced by [`export_functions`] into the authenticated user's
+/// namespace.
+///
+/// Expects a multipart request with the archive as a file field. Every function is
+/// rebuilt, so this can take a while; the response lists what was imported, skipped and
+/// failed. Refused while any deploy lock of the namespace is set, and into namespaces
+/// whose deploys need approval.
+pub(crate) async fn import_functions(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let max_size = state.config.function_config.max_import_size;
+
+    let archive = loop {
+        match multipart.next_field().await {
+            Ok(Some(mut field)) if field.file_name().is_some() => {
+                match read_field_chunks(&mut field, max_size).await {
+                    Ok(buffer) => break buffer,
+                    Err(e) => {
+                        error!("Error reading import archive: {}", e);
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            format!("Error reading import archive: {}", e),
+                        )
+                            .into_response();
+                    }
+                }
+            }
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Missing import archive").into_response();
+            }
+        }
+    };
+
+    // Imports skip approval, so namespaces that need it only take deploys
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to import functions".to_string(),
+            )
+                .into_response();
+        }
+    };
+    if let Err(e) = check_direct_deploy(&state.db_conn, &user, None).await {
+        return deploy_gate_error(e);
+    }
+
+    let report = match import_namespace(
+        &state.db_conn,
+        user_uuid,
+        archive,
+        max_size,
+        &state.image_builder,
+    )
+    .await
+    {
+        Ok(report) => report,
+        Err(e) => return e.into_response(),
+    };
+
+    // Apply the imported settings to running containers right away
+    let mut cache_conn = state.cache_conn.clone();
+    for function in &report.imported {
+        let function_key = format!("{}-{}", function.name, generate_hash(user_uuid));
+        state
+            .autoscaler
+            .set_function_policy(&function_key, function.settings.policy());
+        state
+            .function_versions
+            .write()
+            .unwrap()
+            .remove(&function_key);
+        state.routing_rules.write().unwrap().remove(&function_key);
+        state
+            .function_settings
+            .write()
+            .unwrap()
+            .insert(function_key, function.settings.clone());
+        let _ =
+            FunctionCacheRepo::remove_function(&mut cache_conn, user_uuid, &function.name).await;
+        state
+            .cache_invalidator
+            .publish_function(&mut cache_conn, &function.name, user_uuid)
+            .await;
+    }
+
+    info!(
+        "Imported {} functions into namespace {} ({} skipped, {} failed)",
+        report.imported.len(),
+        user_uuid,
+        report.skipped.len(),
+        report.failed.len()
+    );
+    (StatusCode::OK, Json(report)).into_response()
+}
@@ -0,0 +1,233 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::auth::generate_deploy_token;
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::db::oidc_trust::OidcTrustDBRepo;
+use crate::lifecycle_manager::oidc::{exchange_token, validate_trust, OidcError, TrustPolicy};
+
+/// Request exchanging a CI workflow's OIDC token for deploy credentials
+#[derive(Debug, Deserialize)]
+pub struct ExchangeRequest {
+    /// The OIDC token issued to the workflow
+    token: String,
+    /// The namespace (user UUID) to deploy into
+    namespace: Uuid,
+}
+
+/// Deploy credentials obtained with an OIDC token
+#[derive(Debug, Serialize)]
+pub struct ExchangeResponse {
+    token: String,
+    user: ExchangedUser,
+    /// Seconds until the token expires
+    expires_in: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExchangedUser {
+    uuid: String,
+    email: String,
+}
+
+/// Request trusting a CI workflow to deploy into the namespace
+#[derive(Debug, Deserialize)]
+pub struct AddTrustRequest {
+    repository: String,
+    /// Id of the repository owner, which the token's `repository_owner_id` must carry
+    #[serde(default)]
+    repository_owner_id: Option<String>,
+    /// Id of the repository, which the token's `repository_id` must carry; required
+    /// unless `repository` is a pattern
+    #[serde(default)]
+    repository_id: Option<String>,
+    #[serde(default)]
+    ref_pattern: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+}
+
+/// Exchanges a CI workflow's OIDC token for a short-lived token that may only deploy.
+///
+/// The namespace must trust the workflow's repository, by id (and ref and environment,
+/// when the trust names them); see `/invok/oidc/trusts`.
+pub(crate) async fn exchange(
+    State(state): State<AppState>,
+    Json(payload): Json<ExchangeRequest>,
+) -> impl IntoResponse {
+    let user = match exchange_token(
+        &state.db_conn,
+        &state.oidc_verifier,
+        &payload.token,
+        payload.namespace,
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => return oidc_error(e),
+    };
+
+    let server_config = &state.config.server_config;
+    match generate_deploy_token(
+        &user.uuid.to_string(),
+        &server_config.jwt_auth_secret,
+        server_config.oidc_token_ttl_secs,
+    ) {
+        Ok(token) => (
+            StatusCode::OK,
+            Json(ExchangeResponse {
+                token,
+                user: ExchangedUser {
+                    uuid: user.uuid.to_string(),
+                    email: user.email,
+                },
+                expires_in: server_config.oidc_token_ttl_secs,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to generate token: {}", e);
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to generate authentication token",
+            )
+        }
+    }
+}
+
+/// Lists the CI workflows trusted to deploy into the authenticated user's namespace
+pub(crate) async fn list_trusts(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match OidcTrustDBRepo::find_by_user(&state.db_conn, user.id).await {
+        Ok(trusts) => {
+            let trusts: Vec<TrustPolicy> = trusts.into_iter().map(TrustPolicy::from).collect();
+            (StatusCode::OK, Json(trusts)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to list trusts of {}: {}", user_uuid, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list trusts")
+        }
+    }
+}
+
+/// Trusts a CI workflow to deploy into the authenticated user's namespace
+pub(crate) async fn add_trust(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Json(payload): Json<AddTrustRequest>,
+) -> impl IntoResponse {
+    let Some(repository_owner_id) = payload.repository_owner_id else {
+        return json_error(StatusCode::BAD_REQUEST, "repository_owner_id is required");
+    };
+    if let Err(e) = validate_trust(
+        &payload.repository,
+        &repository_owner_id,
+        payload.repository_id.as_deref(),
+        payload.ref_pattern.as_deref(),
+    ) {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match OidcTrustDBRepo::create(
+        &state.db_conn,
+        user.id,
+        payload.repository,
+        repository_owner_id,
+        payload.repository_id,
+        payload.ref_pattern,
+        payload.environment,
+    )
+    .await
+    {
+        Ok(trust) => {
+            info!(
+                "Namespace '{}' now trusts '{}' to deploy",
+                user_uuid, trust.repository
+            );
+            (StatusCode::CREATED, Json(TrustPolicy::from(trust))).into_response()
+        }
+        Err(e) => {
+            error!("Failed to add trust for {}: {}", user_uuid, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to add trust")
+        }
+    }
+}
+
+/// Stops trusting a CI workflow; deploy tokens it already holds stay valid until they expire
+pub(crate) async fn remove_trust(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    match OidcTrustDBRepo::delete(&state.db_conn, user.id, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => json_error(StatusCode::NOT_FOUND, "Trust not found"),
+        Err(e) => {
+            error!("Failed to remove trust {} of {}: {}", id, user_uuid, e);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove trust")
+        }
+    }
+}
+
+async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(json_error(StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user",
+            ))
+        }
+    }
+}
+
+fn oidc_error(e: OidcError) -> Response {
+    match e {
+        OidcError::InvalidToken(_) => {
+            warn!("Rejected OIDC token: {}", e);
+            json_error(StatusCode::UNAUTHORIZED, &e.to_string())
+        }
+        OidcError::NotTrusted(_) => {
+            warn!("Rejected OIDC token: {}", e);
+            json_error(StatusCode::FORBIDDEN, &e.to_string())
+        }
+        OidcError::IssuerUnavailable(_) => {
+            error!("{}", e);
+            json_error(
+                StatusCode::BAD_GATEWAY,
+                "Could not reach the OIDC issuer to verify the token",
+            )
+        }
+        OidcError::SystemError(_) => json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to exchange the token",
+        ),
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,106 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+/// OpenAPI document of the management routes; `invok_client_ts` is generated from it
+const OPENAPI_SPEC: &str = include_str!("../../../openapi.yaml");
+
+/// Serves the OpenAPI document of the management API, for generating clients against
+/// the controller they'll talk to
+pub(crate) async fn openapi_spec() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/yaml")],
+        OPENAPI_SPEC,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Routes left out of the document: those functions call back into, invocations,
+    /// which take whatever the function accepts, and `invok exec`'s websocket
+    const UNDOCUMENTED: &[(&str, &str)] = &[
+        ("delete", "/internal/kv/{key}"),
+        ("get", "/internal/kv/{key}"),
+        ("put", "/internal/kv/{key}"),
+        ("post", "/internal/kv/{key}/increment"),
+        ("any", "/internal/fetch/{credential}"),
+        ("any", "/internal/fetch/{credential}/{path}"),
+        ("get", "/invok/exec/{function_name}"),
+        ("any", "/invok/{namespace}/{function_name}"),
+        ("any", "/invok/{namespace}/{function_name}/dev"),
+    ];
+
+    /// `(method, path)` of every route of the router in `api_controller/mod.rs`, with its
+    /// path parameters written the OpenAPI way
+    fn router_operations() -> BTreeSet<(String, String)> {
+        let source = include_str!("../mod.rs");
+        let start = source.find("let app = Router::new()").unwrap();
+        let end = start + source[start..].find(".with_state(app_state)").unwrap();
+        let method = regex::Regex::new(r"\b(get|post|put|patch|delete|any)\(").unwrap();
+
+        let mut operations = BTreeSet::new();
+        for route in source[start..end].split(".route(").skip(1) {
+            let path = route.split('"').nth(1).unwrap();
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix([':', '*']) {
+                    Some(param) => format!("{{{param}}}"),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for captures in method.captures_iter(route) {
+                operations.insert((captures[1].to_string(), path.clone()));
+            }
+        }
+        operations
+    }
+
+    fn documented_operations() -> BTreeSet<(String, String)> {
+        let spec: serde_yaml::Value = serde_yaml::from_str(OPENAPI_SPEC).unwrap();
+        let mut operations = BTreeSet::new();
+        for (path, item) in spec["paths"].as_mapping().unwrap() {
+            for (method, _) in item.as_mapping().unwrap() {
+                operations.insert((
+                    method.as_str().unwrap().to_string(),
+                    path.as_str().unwrap().to_string(),
+                ));
+            }
+        }
+        operations
+    }
+
+    #[test]
+    fn test_spec_documents_every_route() {
+        let routes = router_operations();
+        let documented = documented_operations();
+        let undocumented: BTreeSet<_> = UNDOCUMENTED
+            .iter()
+            .map(|(method, path)| (method.to_string(), path.to_string()))
+            .collect();
+
+        let missing: Vec<_> = routes
+            .difference(&documented)
+            .filter(|operation| !undocumented.contains(*operation))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "routes missing from openapi.yaml: {missing:?}"
+        );
+        let stale: Vec<_> = documented.difference(&routes).collect();
+        assert!(
+            stale.is_empty(),
+            "openapi.yaml documents no such routes: {stale:?}"
+        );
+        let listed: Vec<_> = undocumented.difference(&routes).collect();
+        assert!(
+            listed.is_empty(),
+            "UNDOCUMENTED lists no such routes: {listed:?}"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use crate::api_controller::middlewares::jwt::{AuthenticatedUser, DeployUser};
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::preview::{delete_preview, list_previews};
+use crate::utils::utils::generate_hash;
+
+/// Lists the preview instances in the authenticated user's namespace
+pub(crate) async fn list_function_previews(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match list_previews(&state.db_read_conn, user_uuid).await {
+        Ok(previews) => (StatusCode::OK, Json(previews)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Removes the preview instance of one of the authenticated user's functions for a branch.
+///
+/// Deploy-only tokens may call this too, so CI can clean up when a branch is merged.
+pub(crate) async fn delete_function_preview(
+    State(mut state): State<AppState>,
+    Path((function_name, branch)): Path<(String, String)>,
+    DeployUser(user_uuid): DeployUser,
+) -> impl IntoResponse {
+    match delete_preview(
+        &state.db_conn,
+        &mut state.cache_conn,
+        &state.autoscaler,
+        &function_name,
+        &branch,
+        user_uuid,
+    )
+    .await
+    {
+        Ok(preview) => {
+            let function_key = format!("{}-{}", preview.name, generate_hash(user_uuid));
+            state
+                .function_settings
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state
+                .function_versions
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state
+                .cache_invalidator
+                .publish_function(&mut state.cache_conn, &preview.name, user_uuid)
+                .await;
+            (StatusCode::OK, Json(preview)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
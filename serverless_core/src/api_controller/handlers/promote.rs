@@ -0,0 +1,245 @@
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use std::net::SocketAddr;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::handlers::auth::validate_token;
+use crate::api_controller::handlers::deploy_gate::deploy_gate_error;
+use crate::api_controller::middlewares::jwt::DeployUser;
+use crate::api_controller::AppState;
+use crate::db::audit::AuditLogDBRepo;
+use crate::db::auth::AuthDBRepo;
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::models::NotificationKind;
+use crate::lifecycle_manager::deploy_gate::check_direct_deploy;
+use crate::lifecycle_manager::notify::{notify, Notification};
+use crate::lifecycle_manager::promote::{promote_function, PromoteRequest};
+use crate::lifecycle_manager::slo::deploy_freeze;
+use crate::utils::utils::{client_ip, generate_hash};
+
+/// Header with a token of the namespace a function is promoted from
+pub const SOURCE_TOKEN_HEADER: &str = "X-Invok-Source-Token";
+
+/// Audit log action of promotions, recorded in both namespaces
+const PROMOTE_AUDIT_ACTION: &str = "function.promote";
+
+/// Promotes the deployed version of a function from another namespace into the
+/// authenticated user's, e.g. from staging to production, along with the environment
+/// variables named in the request. The function isn't built from source again; see
+/// [`promote_function`].
+///
+/// The namespace it's promoted from is authenticated with a token of its own in the
+/// `X-Invok-Source-Token` header, which must be a full token: deploy-only tokens can't
+/// read another namespace's functions. Accepts the deploy-only tokens CI workflows
+/// obtain with an OIDC token for the target namespace.
+///
+/// Refused while the function or the target namespace is locked, and into namespaces
+/// whose deploys need approval.
+pub(crate) async fn promote(
+    State(state): State<AppState>,
+    DeployUser(user_uuid): DeployUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(function_name): Path<String>,
+    Json(request): Json<PromoteRequest>,
+) -> impl IntoResponse {
+    let source_uuid = match source_namespace(&state, &headers).await {
+        Ok(uuid) => uuid,
+        Err(response) => return response,
+    };
+
+    // Promotions skip approval, so namespaces that need it only take deploys
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found".to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user".to_string(),
+            )
+                .into_response();
+        }
+    };
+    if let Err(e) = check_direct_deploy(&state.db_conn, &user, Some(&function_name)).await {
+        return deploy_gate_error(e);
+    }
+
+    if !request.force {
+        let mut cache_conn = state.cache_conn.clone();
+        if let Some(report) =
+            deploy_freeze(&state.db_conn, &mut cache_conn, &function_name, user_uuid).await
+        {
+            return (
+                StatusCode::CONFLICT,
+                format!(
+                    "Deploys of '{}' are frozen: its error budget is spent ({:.3}% of invocations good over {} days, objective {}%). Promote with --force to override",
+                    function_name, report.compliance, report.window_days, report.objective
+                ),
+            )
+                .into_response();
+        }
+    }
+
+    let promotion = match promote_function(
+        &state.db_conn,
+        &function_name,
+        source_uuid,
+        user_uuid,
+        &request,
+    )
+    .await
+    {
+        Ok(promotion) => promotion,
+        Err(e) => {
+            error!("Error promoting function {}: {}", function_name, e);
+            notify(
+                &state.db_conn,
+                &state.jobs,
+                Notification::new(
+                    NotificationKind::DeployFailed,
+                    user_uuid,
+                    &function_name,
+                    e.to_string(),
+                ),
+            );
+            return e.into_response();
+        }
+    };
+
+    // Apply the promoted settings to running containers right away
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    state
+        .autoscaler
+        .set_function_policy(&function_key, promotion.settings.policy());
+    state
+        .function_versions
+        .write()
+        .unwrap()
+        .remove(&function_key);
+    state
+        .function_settings
+        .write()
+        .unwrap()
+        .insert(function_key, promotion.settings.clone());
+    let mut cache_conn = state.cache_conn.clone();
+    let _ = FunctionCacheRepo::remove_function(&mut cache_conn, user_uuid, &function_name).await;
+    state
+        .cache_invalidator
+        .publish_function(&mut cache_conn, &function_name, user_uuid)
+        .await;
+    notify(
+        &state.db_conn,
+        &state.jobs,
+        Notification::new(
+            NotificationKind::DeploySucceeded,
+            user_uuid,
+            &function_name,
+            format!(
+                "Function '{}' promoted from namespace {}",
+                function_name, source_uuid
+            ),
+        ),
+    );
+
+    let source_ip = client_ip(
+        peer,
+        &headers,
+        state.config.server_config.trust_forwarded_for,
+    )
+    .map(|ip| ip.to_string());
+    let details = serde_json::json!({
+        "from": promotion.promoted_from,
+        "to": { "namespace": user_uuid, "version": promotion.version },
+    });
+    for namespace in [source_uuid, user_uuid] {
+        audit_promotion(
+            &state,
+            namespace,
+            &function_name,
+            &details,
+            source_ip.clone(),
+        )
+        .await;
+    }
+
+    info!(
+        function = %function_name,
+        source = %source_uuid,
+        target = %user_uuid,
+        version = promotion.version,
+        "Function promoted"
+    );
+    (StatusCode::OK, Json(promotion)).into_response()
+}
+
+/// The namespace the source token belongs to, if it may read its functions
+async fn source_namespace(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Uuid, axum::response::Response> {
+    let unauthorized = |message: &str| (StatusCode::UNAUTHORIZED, message.to_string());
+    let token = headers
+        .get(SOURCE_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| unauthorized("Missing the token of the source namespace").into_response())?;
+    let (source_uuid, scope) = validate_token(token, &state.config.server_config.jwt_auth_secret)
+        .map_err(|e| {
+        error!("Source token validation error: {}", e);
+        unauthorized("Invalid or expired source token").into_response()
+    })?;
+    if scope.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "The source token can only deploy functions".to_string(),
+        )
+            .into_response());
+    }
+    match AuthDBRepo::find_by_uuid(&state.db_conn, source_uuid).await {
+        Ok(Some(_)) => Ok(source_uuid),
+        Ok(None) => Err(unauthorized("Source user not found").into_response()),
+        Err(e) => {
+            error!("Error finding user by UUID: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Records a promotion in the audit log of one of the namespaces involved; the
+/// promotion already happened, so a failure is only logged
+async fn audit_promotion(
+    state: &AppState,
+    namespace: Uuid,
+    function_name: &str,
+    details: &serde_json::Value,
+    source_ip: Option<String>,
+) {
+    let user = match AuthDBRepo::find_by_uuid(&state.db_conn, namespace).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to load namespace {}: {}", namespace, e);
+            return;
+        }
+    };
+    if let Err(e) = AuditLogDBRepo::record(
+        &state.db_conn,
+        user.id,
+        user.email,
+        PROMOTE_AUDIT_ACTION,
+        Some(function_name.to_string()),
+        Some(details.clone()),
+        source_ip,
+    )
+    .await
+    {
+        error!("Failed to record promotion of {}: {}", function_name, e);
+    }
+}
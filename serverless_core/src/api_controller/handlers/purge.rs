@@ -0,0 +1,40 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::response_cache::{notify_purge, purge_function};
+
+/// Drops the cached responses of one of the authenticated user's functions, and of its
+/// preview and version instances.
+///
+/// The next invocations reach the function again. The configured purge webhooks are
+/// notified in the background, so CDNs in front of the controller can drop their copies
+/// too.
+pub(crate) async fn purge_function_cache(
+    State(mut state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    match purge_function(
+        &state.db_conn,
+        &mut state.cache_conn,
+        &function_name,
+        user_uuid,
+    )
+    .await
+    {
+        Ok(report) => {
+            let config = &state.config.function_config;
+            notify_purge(
+                &config.purge_webhooks,
+                config.purge_webhook_secret.as_deref(),
+                &report,
+            );
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
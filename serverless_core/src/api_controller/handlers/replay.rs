@@ -0,0 +1,124 @@
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use tracing::info;
+
+use super::functions::call_function;
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::error::ServelessCoreError;
+use crate::lifecycle_manager::replay::{
+    find_invocation, list_invocations, replay_target, REPLAY_HEADER,
+};
+use crate::utils::utils::generate_hash;
+
+/// Options of a replay
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReplayOptions {
+    /// Version of the function to replay on; the current one when unset
+    version: Option<i32>,
+}
+
+/// Lists the invocations recorded for one of the authenticated user's functions, newest
+/// first
+pub(crate) async fn list_recorded_invocations(
+    State(mut state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    if FunctionDBRepo::find_function_by_name(&state.db_read_conn, &function_name, user_uuid)
+        .await
+        .is_none()
+    {
+        return ServelessCoreError::FunctionNotRegistered(format!(
+            "{} in namespace {}",
+            function_name, user_uuid
+        ))
+        .into_response();
+    }
+
+    match list_invocations(&mut state.cache_conn, &function_name, user_uuid).await {
+        Ok(invocations) => (StatusCode::OK, Json(invocations)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Sends a recorded invocation to the function again and returns the function's response.
+///
+/// The request goes through the same path as any invocation, with the recorded method,
+/// query, headers and body, plus `x-invok-replay-of` naming the recorded invocation.
+/// `?version=N` replays it on another version of the function, deployed as a preview
+/// instance when it isn't the current one.
+pub(crate) async fn replay_invocation(
+    State(mut state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(invocation_id): Path<String>,
+    Query(options): Query<ReplayOptions>,
+) -> impl IntoResponse {
+    let invocation = match find_invocation(&mut state.cache_conn, &invocation_id, user_uuid).await {
+        Ok(invocation) => invocation,
+        Err(e) => return e.into_response(),
+    };
+
+    let preview_ttl = state.config.function_config.preview_ttl();
+    let (function_name, deployed) = match replay_target(
+        &state.db_conn,
+        &state.image_builder,
+        &invocation,
+        options.version,
+        user_uuid,
+        preview_ttl,
+    )
+    .await
+    {
+        Ok(target) => target,
+        Err(e) => return e.into_response(),
+    };
+    if let Some(settings) = deployed {
+        let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+        state
+            .autoscaler
+            .set_function_policy(&function_key, settings.policy());
+        state
+            .function_settings
+            .write()
+            .unwrap()
+            .insert(function_key, settings);
+        let _ =
+            FunctionCacheRepo::remove_function(&mut state.cache_conn, user_uuid, &function_name)
+                .await;
+        state
+            .cache_invalidator
+            .publish_function(&mut state.cache_conn, &function_name, user_uuid)
+            .await;
+    }
+
+    let (headers, request) = match invocation.request() {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+    info!(
+        "Replaying invocation '{}' of '{}' on '{}'",
+        invocation.id, invocation.function, function_name
+    );
+    let mut response = call_function(
+        State(state),
+        connect_info,
+        Path((user_uuid.to_string(), function_name)),
+        Query(invocation.query.clone()),
+        headers,
+        request,
+    )
+    .await
+    .into_response();
+    if let Ok(id) = HeaderValue::from_str(&invocation.id) {
+        response.headers_mut().insert(REPLAY_HEADER, id);
+    }
+    response
+}
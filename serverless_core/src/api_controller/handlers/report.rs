@@ -0,0 +1,44 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::admin::AdminUser;
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::dependencies::dependency_report;
+
+/// Lists the authenticated user's functions using deprecated runtimes or vulnerable
+/// dependency versions
+pub(crate) async fn namespace_dependency_report(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    report(&state, Some(user_uuid)).await
+}
+
+/// Lists the functions of every namespace using deprecated runtimes or vulnerable
+/// dependency versions, for platform owners driving upgrades
+pub(crate) async fn platform_dependency_report(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+) -> impl IntoResponse {
+    report(&state, None).await
+}
+
+async fn report(state: &AppState, namespace: Option<Uuid>) -> Response {
+    if !state.dependency_advisories.is_configured() {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({
+                "error": "Dependency advisories are not configured; set DEPENDENCY_ADVISORIES"
+            })),
+        )
+            .into_response();
+    }
+    match dependency_report(&state.db_read_conn, &state.dependency_advisories, namespace).await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
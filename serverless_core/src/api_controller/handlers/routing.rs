@@ -0,0 +1,114 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::api_controller::handlers::deploy_gate::{deploy_gate_error, find_user};
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::deploy_gate::check_direct_deploy;
+use crate::lifecycle_manager::routing::update_routing_rules;
+use crate::utils::routing::RoutingRules;
+use crate::utils::utils::generate_hash;
+
+/// Returns the rules routing invocations of a function to its versions
+pub(crate) async fn get_routing_rules(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+) -> impl IntoResponse {
+    match FunctionDBRepo::find_function_by_name(&state.db_conn, &function_name, user_uuid).await {
+        Some(function) => {
+            (StatusCode::OK, Json(RoutingRules::from_model(&function))).into_response()
+        }
+        None => json_error(StatusCode::NOT_FOUND, "Function not found"),
+    }
+}
+
+/// Replaces the rules routing invocations of a function to its versions.
+///
+/// Versions the rules name are deployed as instances first, so the update can take a
+/// while; the new rules apply to invocations received once it returns.
+pub(crate) async fn set_routing_rules(
+    State(mut state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Path(function_name): Path<String>,
+    Json(rules): Json<RoutingRules>,
+) -> impl IntoResponse {
+    if let Err(e) = rules.validate() {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            &format!("Invalid routing rules: {}", e),
+        );
+    }
+
+    // Routing rules deploy and serve versions without a deploy, so they pass the same gates
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if let Err(e) = check_direct_deploy(&state.db_conn, &user, Some(&function_name)).await {
+        return deploy_gate_error(e);
+    }
+
+    let update = match update_routing_rules(
+        &state.db_conn,
+        &mut state.cache_conn,
+        &state.autoscaler,
+        &state.image_builder,
+        &function_name,
+        user_uuid,
+        &rules,
+    )
+    .await
+    {
+        Ok(update) => update,
+        Err(e) => return e.into_response(),
+    };
+
+    let namespace = generate_hash(user_uuid);
+    let mut changed = vec![function_name.clone()];
+    for (name, settings) in update.deployed {
+        changed.push(name.clone());
+        let function_key = format!("{name}-{namespace}");
+        state
+            .autoscaler
+            .set_function_policy(&function_key, settings.policy());
+        state
+            .function_settings
+            .write()
+            .unwrap()
+            .insert(function_key, settings);
+    }
+    for name in update.removed {
+        changed.push(name.clone());
+        let function_key = format!("{name}-{namespace}");
+        state
+            .function_settings
+            .write()
+            .unwrap()
+            .remove(&function_key);
+        state
+            .function_versions
+            .write()
+            .unwrap()
+            .remove(&function_key);
+    }
+    state
+        .routing_rules
+        .write()
+        .unwrap()
+        .insert(format!("{function_name}-{namespace}"), rules.clone());
+    for name in changed {
+        state
+            .cache_invalidator
+            .publish_function(&mut state.cache_conn, &name, user_uuid)
+            .await;
+    }
+    (StatusCode::OK, Json(rules)).into_response()
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,165 @@
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use tracing::error;
+use uuid::Uuid;
+
+use super::health::check_dependencies;
+use crate::api_controller::middlewares::admin::AdminUser;
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::status::{
+    list_incidents, open_incident, resolve_incident, ErrorRate, Incident, IncidentSeverity,
+    NewIncident, PlatformState, STATUS_TTL,
+};
+
+/// Work waiting on the platform
+#[derive(Debug, Serialize)]
+struct QueueDepths {
+    /// Functions waiting for host capacity to scale up
+    waiting_scale_ups: usize,
+    /// Requests waiting for a free container of a single-concurrency function
+    queued_requests: usize,
+}
+
+/// What tenants see on the status page
+#[derive(Debug, Serialize)]
+struct StatusPage {
+    status: PlatformState,
+    components: BTreeMap<&'static str, PlatformState>,
+    queues: QueueDepths,
+    errors: ErrorRate,
+    incidents: Vec<Incident>,
+    /// RFC 3339 time the page was computed
+    updated_at: String,
+}
+
+/// Public summary of the platform's health, so tenants can tell their own failures from
+/// the platform's.
+///
+/// Reports whether the controller's dependencies are up, the queue depths, this
+/// controller's invocation error rate and the incidents operators flagged. The page is
+/// computed at most every 15 seconds and may be cached as long.
+pub(crate) async fn platform_status(State(state): State<AppState>) -> impl IntoResponse {
+    let cache_control = format!("public, max-age={}", STATUS_TTL.as_secs());
+    if let Some(page) = state.status_tracker.cached() {
+        return (
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, cache_control)],
+            Json(page),
+        )
+            .into_response();
+    }
+
+    let health = check_dependencies(&state).await;
+    let mut cache_conn = state.cache_conn.clone();
+    // The page is most useful during outages, so it is served without incidents rather
+    // than not at all when Redis is down
+    let incidents = list_incidents(&mut cache_conn).await.unwrap_or_default();
+    let errors = state.status_tracker.error_rate();
+    let capacity = state.autoscaler.capacity_status();
+
+    let components: BTreeMap<_, _> = health
+        .components()
+        .into_iter()
+        .map(|(name, healthy)| {
+            let component_state = match (healthy, name) {
+                (true, _) => PlatformState::Operational,
+                // Only autoscaling decisions need the metrics
+                (false, "prometheus") => PlatformState::Degraded,
+                (false, _) => PlatformState::MajorOutage,
+            };
+            (name, component_state)
+        })
+        .collect();
+    let mut status = components
+        .values()
+        .copied()
+        .max()
+        .unwrap_or(PlatformState::Operational);
+    if health.capacity_exhausted() || errors.is_elevated() {
+        status = status.max(PlatformState::Degraded);
+    }
+    for incident in &incidents {
+        status = status.max(match incident.severity {
+            IncidentSeverity::Minor => PlatformState::Degraded,
+            IncidentSeverity::Major => PlatformState::MajorOutage,
+        });
+    }
+
+    let page = StatusPage {
+        status,
+        components,
+        queues: QueueDepths {
+            waiting_scale_ups: capacity.waiting_functions,
+            queued_requests: state.autoscaler.queued_requests(),
+        },
+        errors,
+        incidents,
+        updated_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+    };
+    let page = match serde_json::to_value(&page) {
+        Ok(page) => page,
+        Err(e) => {
+            error!("Failed to serialize the status page: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to compute the platform status".to_string(),
+            )
+                .into_response();
+        }
+    };
+    state.status_tracker.cache(page.clone());
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, cache_control)],
+        Json(page),
+    )
+        .into_response()
+}
+
+/// Flags an incident on the status page
+pub(crate) async fn create_incident(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    Json(new): Json<NewIncident>,
+) -> impl IntoResponse {
+    if new.title.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "An incident needs a title".to_string(),
+        )
+            .into_response();
+    }
+
+    let mut cache_conn = state.cache_conn.clone();
+    match open_incident(&mut cache_conn, new).await {
+        Ok(incident) => {
+            // Show the incident right away instead of after the cached page expires
+            state.status_tracker.invalidate();
+            (StatusCode::CREATED, Json(incident)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Takes a resolved incident off the status page
+pub(crate) async fn delete_incident(
+    State(state): State<AppState>,
+    _admin: AdminUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut cache_conn = state.cache_conn.clone();
+    match resolve_incident(&mut cache_conn, id).await {
+        Ok(true) => {
+            state.status_tracker.invalidate();
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No such incident".to_string()).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
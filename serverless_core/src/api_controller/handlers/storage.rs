@@ -0,0 +1,28 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::retention::{storage_usage, RetentionPolicy};
+
+/// Reports the storage the authenticated user's recorded versions take, per function,
+/// with the retention limits applied to them
+pub(crate) async fn get_storage(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match storage_usage(&state.db_read_conn, user_uuid, retention_policy(&state)).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub(crate) fn retention_policy(state: &AppState) -> RetentionPolicy {
+    let config = &state.config.function_config;
+    RetentionPolicy {
+        keep_versions: config.keep_versions,
+        max_bytes_per_namespace: config.max_artifact_bytes_per_namespace,
+    }
+}
@@ -0,0 +1,253 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use db_entities::auth::Model as AuthUser;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::auth::AuthDBRepo;
+use crate::lifecycle_manager::totp;
+
+/// Request carrying a code from the authenticator app (or, to disable, a recovery code)
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequest {
+    code: String,
+}
+
+/// A new secret to add to an authenticator app
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    /// Base32, for apps the secret is typed into
+    secret: String,
+    /// `otpauth://` URI, for apps that scan a QR code
+    uri: String,
+}
+
+/// Recovery codes issued when two-factor authentication is enabled; only shown once
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+/// Starts enrolling the authenticated user in two-factor authentication.
+///
+/// The new secret isn't required at login until `enable` confirms the app produces
+/// matching codes; enrolling again replaces a pending secret. Secrets are stored sealed
+/// with the server's `BUILD_ARGS_KEY`, so two-factor authentication needs one.
+pub(crate) async fn enroll_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if user.totp_enabled {
+        return json_error(
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled",
+        );
+    }
+
+    let Some(cipher) = state.image_builder.build_arg_cipher.as_ref() else {
+        return json_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "Two-factor authentication is disabled; the server has no BUILD_ARGS_KEY",
+        );
+    };
+
+    let secret = match totp::generate_secret() {
+        Ok(secret) => secret,
+        Err(e) => {
+            error!("{}", e);
+            return json_error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+        }
+    };
+    let sealed = match cipher.seal(user.id, totp::SEALED_NAME, &secret) {
+        Ok(sealed) => sealed,
+        Err(e) => {
+            error!("Failed to seal two-factor secret of {}: {}", user_uuid, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start two-factor enrollment",
+            );
+        }
+    };
+    let uri = totp::provisioning_uri(&user.email, &secret);
+    match AuthDBRepo::set_pending_totp(&state.db_conn, user, sealed).await {
+        Ok(_) => (StatusCode::OK, Json(TotpEnrollment { secret, uri })).into_response(),
+        Err(e) => {
+            error!("Failed to store two-factor secret of {}: {}", user_uuid, e);
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start two-factor enrollment",
+            )
+        }
+    }
+}
+
+/// Confirms a pending enrollment with a code from the app, requiring a second factor at
+/// every login from then on, and issues the recovery codes
+pub(crate) async fn enable_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if user.totp_enabled {
+        return json_error(
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled",
+        );
+    }
+    let secret = match open_secret(&state, &user) {
+        Ok(Some(secret)) => secret,
+        Ok(None) => {
+            return json_error(
+                StatusCode::BAD_REQUEST,
+                "No pending enrollment; enroll first",
+            )
+        }
+        Err(e) => {
+            error!("Failed to open two-factor secret of {}: {}", user_uuid, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to enable two-factor authentication",
+            );
+        }
+    };
+    let Some(step) = totp::verify_code(&secret, &payload.code) else {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid two-factor code");
+    };
+
+    let (recovery_codes, hashes) = match totp::generate_recovery_codes() {
+        Ok(codes) => codes,
+        Err(e) => {
+            error!("{}", e);
+            return json_error(StatusCode::INTERNAL_SERVER_ERROR, &e);
+        }
+    };
+    match AuthDBRepo::enable_totp(&state.db_conn, user, hashes, step).await {
+        Ok(user) => {
+            info!("User {} enabled two-factor authentication", user.email);
+            (StatusCode::OK, Json(RecoveryCodes { recovery_codes })).into_response()
+        }
+        Err(e) => {
+            error!(
+                "Failed to enable two-factor authentication of {}: {}",
+                user_uuid, e
+            );
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to enable two-factor authentication",
+            )
+        }
+    }
+}
+
+/// Stops requiring a second factor at login; needs a current code or a recovery code
+pub(crate) async fn disable_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> impl IntoResponse {
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if !user.totp_enabled {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            "Two-factor authentication is not enabled",
+        );
+    }
+    match verify_second_factor(&state, &user, &payload.code).await {
+        Ok(true) => {}
+        Ok(false) => return json_error(StatusCode::BAD_REQUEST, "Invalid two-factor code"),
+        Err(e) => {
+            error!("Failed to check two-factor code of {}: {}", user_uuid, e);
+            return json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to disable two-factor authentication",
+            );
+        }
+    }
+
+    match AuthDBRepo::disable_totp(&state.db_conn, user).await {
+        Ok(user) => {
+            info!("User {} disabled two-factor authentication", user.email);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            error!(
+                "Failed to disable two-factor authentication of {}: {}",
+                user_uuid, e
+            );
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to disable two-factor authentication",
+            )
+        }
+    }
+}
+
+/// Checks a second factor of a user with two-factor authentication enabled: a code from
+/// the app, or an unused recovery code. Either is used up, so it can't be given again.
+pub(crate) async fn verify_second_factor(
+    state: &AppState,
+    user: &AuthUser,
+    code: &str,
+) -> Result<bool, String> {
+    let Some(secret) = open_secret(state, user)? else {
+        return Ok(false);
+    };
+    if let Some(step) = totp::verify_code(&secret, code) {
+        return AuthDBRepo::accept_totp_step(&state.db_conn, user.id, step)
+            .await
+            .map_err(|e| e.to_string());
+    }
+    AuthDBRepo::consume_recovery_code(&state.db_conn, user, code)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Decrypts the TOTP secret of a user, if they have one
+fn open_secret(state: &AppState, user: &AuthUser) -> Result<Option<String>, String> {
+    let Some(sealed) = user.totp_secret.as_deref() else {
+        return Ok(None);
+    };
+    let cipher = state
+        .image_builder
+        .build_arg_cipher
+        .as_ref()
+        .ok_or("the server has no BUILD_ARGS_KEY to decrypt two-factor secrets")?;
+    cipher
+        .open(user.id, totp::SEALED_NAME, sealed)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+async fn find_user(state: &AppState, user_uuid: Uuid) -> Result<AuthUser, Response> {
+    match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(json_error(StatusCode::NOT_FOUND, "User not found")),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_uuid, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load user",
+            ))
+        }
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,120 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use std::time::Duration;
+use tracing::error;
+
+use crate::api_controller::handlers::deploy_gate::{deploy_gate_error, find_user};
+use crate::api_controller::middlewares::jwt::AuthenticatedUser;
+use crate::api_controller::AppState;
+use crate::db::cache::FunctionCacheRepo;
+use crate::lifecycle_manager::deploy_gate::check_direct_deploy;
+use crate::lifecycle_manager::egress::publish_allowlist;
+use crate::lifecycle_manager::trash::{list_trash, restore_function, trash_function};
+use crate::utils::utils::generate_hash;
+
+/// Moves one of the authenticated user's functions to the trash.
+///
+/// The function stops serving immediately but can be restored until the retention
+/// period ends.
+pub(crate) async fn delete_function(
+    State(mut state): State<AppState>,
+    Path(function_name): Path<String>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    let retention = trash_retention(&state);
+    match trash_function(
+        &state.db_conn,
+        &mut state.cache_conn,
+        &state.autoscaler,
+        &function_name,
+        user_uuid,
+        retention,
+    )
+    .await
+    {
+        Ok(trashed) => {
+            let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+            state
+                .function_settings
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state
+                .function_versions
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state.routing_rules.write().unwrap().remove(&function_key);
+            state
+                .cache_invalidator
+                .publish_function(&mut state.cache_conn, &function_name, user_uuid)
+                .await;
+            (StatusCode::OK, Json(trashed)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Lists the authenticated user's deleted functions that can still be restored
+pub(crate) async fn list_trashed_functions(
+    State(state): State<AppState>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    match list_trash(&state.db_read_conn, user_uuid, trash_retention(&state)).await {
+        Ok(functions) => (StatusCode::OK, Json(functions)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Brings one of the authenticated user's functions back from the trash
+pub(crate) async fn restore_trashed_function(
+    State(mut state): State<AppState>,
+    Path(function_name): Path<String>,
+    AuthenticatedUser(user_uuid): AuthenticatedUser,
+) -> impl IntoResponse {
+    // Restores serve a version without a deploy, so they pass the same gates
+    let user = match find_user(&state, user_uuid).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    if let Err(e) = check_direct_deploy(&state.db_conn, &user, Some(&function_name)).await {
+        return deploy_gate_error(e);
+    }
+
+    match restore_function(&state.db_conn, &function_name, user_uuid).await {
+        Ok(function) => {
+            // Invocations while it was in the trash may have cached it as missing
+            let _ = FunctionCacheRepo::remove_function(
+                &mut state.cache_conn,
+                user_uuid,
+                &function_name,
+            )
+            .await;
+            state
+                .cache_invalidator
+                .publish_function(&mut state.cache_conn, &function_name, user_uuid)
+                .await;
+            if let Err(e) = publish_allowlist(&mut state.cache_conn, &function).await {
+                error!(
+                    "Failed to publish egress allowlist of '{}': {}",
+                    function_name, e
+                );
+            }
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "name": function.name,
+                    "runtime": function.runtime,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+fn trash_retention(state: &AppState) -> Duration {
+    Duration::from_secs(state.config.function_config.trash_retention_hours * 60 * 60)
+}
@@ -1 +1,6 @@
+pub(crate) mod admin;
+pub(crate) mod firewall;
+pub(crate) mod grpc;
+pub(crate) mod internal;
 pub(crate) mod jwt;
+pub(crate) mod status;
@@ -0,0 +1,58 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+};
+use tracing::warn;
+
+use super::jwt::AuthError;
+use crate::api_controller::AppState;
+
+/// Extractor guarding the admin API.
+///
+/// Requires `Authorization: Bearer <admin token>` matching the configured `admin_token`.
+/// When no admin token is configured, every admin endpoint answers 404.
+#[derive(Debug, Clone)]
+pub struct AdminUser;
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app_state = AppState::from_ref(state);
+        let Some(admin_token) = app_state.config.server_config.admin_token.as_deref() else {
+            return Err(AuthError(
+                StatusCode::NOT_FOUND,
+                "Admin API is disabled".to_string(),
+            ));
+        };
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                AuthError(StatusCode::UNAUTHORIZED, "Missing admin token".to_string())
+            })?;
+
+        if !constant_time_eq(token.as_bytes(), admin_token.as_bytes()) {
+            warn!("Rejected admin request with an invalid token");
+            return Err(AuthError(
+                StatusCode::UNAUTHORIZED,
+                "Invalid admin token".to_string(),
+            ));
+        }
+
+        Ok(AdminUser)
+    }
+}
+
+/// Compare secrets without leaking how much of them matched through timing
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,61 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    api_controller::AppState, lifecycle_manager::invoke::load_function_settings,
+    utils::utils::client_ip,
+};
+
+/// Applies a function's firewall rules before the request reaches the invocation handler.
+///
+/// Rejected requests never wake a container, so scanners hitting a private function
+/// don't cause cold starts. Requests with a malformed namespace are passed through for
+/// the handler to reject.
+pub async fn function_firewall(
+    state: State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Ok(user_uuid) = namespace.parse::<Uuid>() else {
+        return next.run(request).await;
+    };
+
+    let settings = load_function_settings(&state, &function_name, user_uuid).await;
+    let Some(rules) = settings.firewall else {
+        return next.run(request).await;
+    };
+
+    let client_ip = client_ip(
+        peer,
+        request.headers(),
+        state.config.server_config.trust_forwarded_for,
+    );
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    match rules.check(client_ip, request.method(), path_and_query) {
+        Ok(()) => next.run(request).await,
+        Err(rejection) => {
+            warn!(
+                namespace = %namespace,
+                function = %function_name,
+                reason = %rejection.reason,
+                "Request rejected by function firewall"
+            );
+            (rejection.status, rejection.reason).into_response()
+        }
+    }
+}
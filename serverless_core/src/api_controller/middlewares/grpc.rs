@@ -0,0 +1,56 @@
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, HeaderMap, Request, Uri},
+};
+use uuid::Uuid;
+
+use crate::{
+    lifecycle_manager::invoke::{FUNCTION_HEADER, NAMESPACE_HEADER},
+    utils::http2::UpstreamPath,
+};
+
+/// Routes gRPC calls to the function their `x-invok-namespace` and `x-invok-function`
+/// metadata name.
+///
+/// gRPC clients call the service's own paths (`/package.Service/Method`) and can't put
+/// them under `/invok/<namespace>/<function>`, so the call is rewritten to that route
+/// before routing, and its path kept as [`UpstreamPath`] to be forwarded to the function.
+/// Other requests, and calls without the metadata, are passed through untouched.
+pub async fn route_grpc(mut request: Request<Body>) -> Request<Body> {
+    if request.uri().path().starts_with("/invok/") || !is_grpc(request.headers()) {
+        return request;
+    }
+    let Some(uri) = function_uri(request.headers()) else {
+        return request;
+    };
+
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |p| p.as_str())
+        .to_string();
+    request
+        .extensions_mut()
+        .insert(UpstreamPath(path_and_query));
+    *request.uri_mut() = uri;
+    request
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"))
+}
+
+/// The invocation route of the function the metadata names, if it names one
+fn function_uri(headers: &HeaderMap) -> Option<Uri> {
+    let namespace: Uuid = headers.get(NAMESPACE_HEADER)?.to_str().ok()?.parse().ok()?;
+    let function_name = headers.get(FUNCTION_HEADER)?.to_str().ok()?;
+    if function_name.is_empty() || function_name.contains(['/', '?', '#', '%']) {
+        return None;
+    }
+    format!("/invok/{}/{}", namespace, function_name)
+        .parse()
+        .ok()
+}
@@ -0,0 +1,59 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+};
+use runtime::core::internal_api::verify_function_token;
+use tracing::warn;
+
+use super::jwt::AuthError;
+use crate::api_controller::AppState;
+
+/// Extractor guarding the internal API functions call, holding the calling function's key.
+///
+/// Requires `Authorization: Bearer <token>` with the token handed to the function's
+/// containers in `INVOK_API_TOKEN`. When `internal_api_url` isn't configured, every
+/// internal endpoint answers 404.
+#[derive(Debug, Clone)]
+pub struct FunctionCaller(pub String);
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for FunctionCaller
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app_state = AppState::from_ref(state);
+        let Some(internal_api) = app_state.config.server_config.internal_api() else {
+            return Err(AuthError(
+                StatusCode::NOT_FOUND,
+                "Internal API is disabled".to_string(),
+            ));
+        };
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                AuthError(
+                    StatusCode::UNAUTHORIZED,
+                    "Missing function token".to_string(),
+                )
+            })?;
+
+        match verify_function_token(&internal_api.secret, token) {
+            Some(function_key) => Ok(FunctionCaller(function_key.to_string())),
+            None => {
+                warn!("Rejected internal API request with an invalid token");
+                Err(AuthError(
+                    StatusCode::UNAUTHORIZED,
+                    "Invalid function token".to_string(),
+                ))
+            }
+        }
+    }
+}
@@ -9,7 +9,10 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-    api_controller::{handlers::auth::validate_token, AppState},
+    api_controller::{
+        handlers::auth::{validate_token, DEPLOY_SCOPE},
+        AppState,
+    },
     db::auth::AuthDBRepo,
 };
 
@@ -17,6 +20,11 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser(pub Uuid);
 
+/// Extractor for a user allowed to deploy: like [`AuthenticatedUser`], but also accepts
+/// the deploy-only tokens CI workflows obtain with an OIDC token
+#[derive(Debug, Clone)]
+pub struct DeployUser(pub Uuid);
+
 /// Error response for authentication failures
 #[derive(Debug)]
 pub struct AuthError(pub StatusCode, pub String);
@@ -38,56 +46,90 @@ where
     type Rejection = AuthError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        // Extract the authorization header
-        let auth_header = parts
-            .headers
-            .get(header::AUTHORIZATION)
-            .and_then(|value| value.to_str().ok())
-            .ok_or_else(|| {
-                AuthError(
-                    StatusCode::UNAUTHORIZED,
-                    "Missing authorization header".to_string(),
-                )
-            })?;
+        let (user_uuid, scope) = authenticate(parts, &AppState::from_ref(state)).await?;
+        if scope.is_some() {
+            return Err(AuthError(
+                StatusCode::FORBIDDEN,
+                "This token can only deploy functions".to_string(),
+            ));
+        }
+        Ok(AuthenticatedUser(user_uuid))
+    }
+}
 
-        // Check if the authorization header starts with "Bearer "
-        if !auth_header.starts_with("Bearer ") {
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for DeployUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let (user_uuid, scope) = authenticate(parts, &AppState::from_ref(state)).await?;
+        if scope.is_some_and(|scope| scope != DEPLOY_SCOPE) {
             return Err(AuthError(
-                StatusCode::UNAUTHORIZED,
-                "Invalid authorization header format".to_string(),
+                StatusCode::FORBIDDEN,
+                "This token cannot deploy functions".to_string(),
             ));
         }
-        let app_state = AppState::from_ref(state);
+        Ok(DeployUser(user_uuid))
+    }
+}
 
-        // Extract the token
-        let token = &auth_header[7..];
+/// Validates the bearer token of a request and checks its user still exists.
+///
+/// Returns the user UUID and the token's scope.
+async fn authenticate(
+    parts: &Parts,
+    app_state: &AppState,
+) -> Result<(Uuid, Option<String>), AuthError> {
+    // Extract the authorization header
+    let auth_header = parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| {
+            AuthError(
+                StatusCode::UNAUTHORIZED,
+                "Missing authorization header".to_string(),
+            )
+        })?;
 
-        // Validate the token
-        let user_uuid = validate_token(token, &app_state.config.server_config.jwt_auth_secret)
-            .map_err(|e| {
-                error!("Token validation error: {}", e);
-                AuthError(
-                    StatusCode::UNAUTHORIZED,
-                    "Invalid or expired token".to_string(),
-                )
-            })?;
+    // Check if the authorization header starts with "Bearer "
+    if !auth_header.starts_with("Bearer ") {
+        return Err(AuthError(
+            StatusCode::UNAUTHORIZED,
+            "Invalid authorization header format".to_string(),
+        ));
+    }
 
-        // Get the app state
+    // Extract the token
+    let token = &auth_header[7..];
 
-        // Verify the user exists in the database
-        match AuthDBRepo::find_by_uuid(&app_state.db_conn, user_uuid).await {
-            Ok(Some(_)) => Ok(AuthenticatedUser(user_uuid)),
-            Ok(None) => Err(AuthError(
+    // Validate the token
+    let (user_uuid, scope) = validate_token(token, &app_state.config.server_config.jwt_auth_secret)
+        .map_err(|e| {
+            error!("Token validation error: {}", e);
+            AuthError(
                 StatusCode::UNAUTHORIZED,
-                "User not found".to_string(),
-            )),
-            Err(e) => {
-                error!("Error finding user by UUID: {}", e);
-                Err(AuthError(
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                ))
-            }
+                "Invalid or expired token".to_string(),
+            )
+        })?;
+
+    // Verify the user exists in the database
+    match AuthDBRepo::find_by_uuid(&app_state.db_conn, user_uuid).await {
+        Ok(Some(_)) => Ok((user_uuid, scope)),
+        Ok(None) => Err(AuthError(
+            StatusCode::UNAUTHORIZED,
+            "User not found".to_string(),
+        )),
+        Err(e) => {
+            error!("Error finding user by UUID: {}", e);
+            Err(AuthError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            ))
         }
     }
 }
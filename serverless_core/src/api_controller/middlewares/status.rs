@@ -0,0 +1,79 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::api_controller::AppState;
+use crate::lifecycle_manager::invoke::load_function_settings;
+use crate::lifecycle_manager::slo::record_invocation;
+use crate::utils::utils::{generate_hash, FunctionTime};
+
+/// Counts each invocation's outcome for the status page's error rate and, for functions
+/// with an SLO, against their error budget
+pub async fn count_invocation(
+    state: State<AppState>,
+    Path((namespace, function_name)): Path<(String, String)>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let received_at = Instant::now();
+    let response = next.run(request).await;
+    let latency = received_at.elapsed();
+    state.status_tracker.record(is_platform_error(&response));
+
+    if let Ok(user_uuid) = namespace.parse::<Uuid>() {
+        let settings = load_function_settings(&state, &function_name, user_uuid).await;
+        if let Some(slo) = &settings.slo {
+            record_invocation(
+                state.cache_conn.clone(),
+                format!("{function_name}-{}", generate_hash(user_uuid)),
+                slo,
+                response.status().as_u16(),
+                latency,
+            );
+        }
+    }
+    response
+}
+
+/// Whether the platform failed the invocation. A 5xx the function answered with itself is
+/// the function's, not the platform's, so it doesn't count against the status page.
+fn is_platform_error(response: &Response) -> bool {
+    response.status().is_server_error() && response.extensions().get::<FunctionTime>().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use std::time::Duration;
+
+    fn function_response(status: StatusCode) -> Response {
+        let mut response = status.into_response();
+        response
+            .extensions_mut()
+            .insert(FunctionTime(Duration::from_millis(5)));
+        response
+    }
+
+    #[test]
+    fn test_is_platform_error() {
+        assert!(is_platform_error(&StatusCode::BAD_GATEWAY.into_response()));
+        assert!(is_platform_error(
+            &StatusCode::SERVICE_UNAVAILABLE.into_response()
+        ));
+        assert!(!is_platform_error(&StatusCode::NOT_FOUND.into_response()));
+
+        // Functions failing on their own don't make the platform degraded
+        assert!(!is_platform_error(&function_response(
+            StatusCode::INTERNAL_SERVER_ERROR
+        )));
+        assert!(!is_platform_error(&function_response(StatusCode::OK)));
+    }
+}
@@ -1,39 +1,184 @@
+mod builder;
 mod config;
+mod doctor;
+mod egress_proxy;
 mod handlers;
 mod middlewares;
 
+pub use builder::start_builder;
+pub use egress_proxy::start_egress_proxy;
+
+use crate::db::models::FunctionSettings;
+use crate::lifecycle_manager::base_images::{run_base_image_refresh_loop, BaseImages};
+use crate::lifecycle_manager::bench::MAX_BENCH_BODY_SIZE;
+use crate::lifecycle_manager::build_args::BuildArgCipher;
+use crate::lifecycle_manager::cold_start::ColdStartBudget;
+use crate::lifecycle_manager::dependencies::{run_advisory_refresh_loop, DependencyAdvisories};
+use crate::lifecycle_manager::deploy_queue::{run_deploy, DEPLOY_JOB};
+use crate::lifecycle_manager::dev::run_dev_expiry_loop;
+use crate::lifecycle_manager::egress::run_allowlist_sync_loop;
+use crate::lifecycle_manager::freeze::run_freeze_sync_loop;
+use crate::lifecycle_manager::gateway::{EgressGateway, MAX_BODY_SIZE};
+use crate::lifecycle_manager::hibernation::{run_hibernation_loop, NamespaceActivity};
+use crate::lifecycle_manager::invalidation::CacheInvalidator;
+use crate::lifecycle_manager::invoke::LookupStats;
+use crate::lifecycle_manager::jobs::{JobQueue, JobRunner, DEFAULT_WORKERS};
+use crate::lifecycle_manager::login_guard::LoginGuard;
+use crate::lifecycle_manager::notify::{run_anomaly_loop, run_delivery, NOTIFICATION_JOB};
+use crate::lifecycle_manager::oidc::OidcVerifier;
+use crate::lifecycle_manager::payloads::PayloadStore;
+use crate::lifecycle_manager::preview::run_expiry_loop;
+use crate::lifecycle_manager::remote_build::{ImageBuilder, RemoteBuilder};
+use crate::lifecycle_manager::retention::run_retention_loop;
+use crate::lifecycle_manager::services::ServiceListeners;
+use crate::lifecycle_manager::status::StatusTracker;
+use crate::lifecycle_manager::trash::{run_purge, run_purge_loop, PURGE_JOB};
+use crate::lifecycle_manager::warmup::{run_warmup_loop, WarmupOptions};
+use crate::utils::flags::FeatureFlags;
+use crate::utils::http2::{h2c_client, H2cClient};
+use crate::utils::presign::PresignCredentials;
+use crate::utils::routing::RoutingRules;
 use axum::{
-    extract::FromRef,
-    routing::{any, get, post},
-    Router,
+    extract::{DefaultBodyLimit, FromRef},
+    middleware,
+    routing::{any, delete, get, post, put},
+    Router, ServiceExt,
 };
 use config::{InvokConfig, InvokConfigError};
 use db_migrations::{Migrator, MigratorTrait};
 use handlers::{
+    admin::{
+        backup, create_freeze, delete_freeze, list_base_images, list_freezes, list_jobs,
+        pull_base_images, restore,
+    },
     auth::{login, register},
-    functions::{call_function, list_functions, stream_function_logs, upload_function},
+    bench::bench_report,
+    build_args::{list_build_args, remove_build_arg, set_build_arg},
+    credentials::{list_credentials, remove_credential, set_credential},
+    deploy_gate::{
+        approve_deploy, get_approvers, list_locks, list_pending_deploys, lock_function,
+        lock_namespace, reject_deploy, set_approvers, set_namespace_approvers, unlock_function,
+        unlock_namespace,
+    },
+    dev::{
+        call_dev_container, start_dev_container, stop_dev_container, stream_dev_container_logs,
+        sync_dev_container,
+    },
+    egress::{get_egress_allowlist, set_egress_allowlist},
+    exec::exec_function,
+    flags::{list_flags, remove_flag, set_flag},
+    functions::{
+        call_function, deploy_and_apply, diff_function_deploy, function_boot_logs, function_docs,
+        function_recommendations, function_slo, function_status, get_deployment, list_functions,
+        stream_function_logs, upload_function,
+    },
+    gateway::fetch,
+    health::{healthz, readyz},
+    kv::{delete_entry, get_entry, increment_entry, set_entry, MAX_VALUE_SIZE},
+    namespace::{
+        export_functions, get_namespace_defaults, get_notifications, import_functions,
+        set_namespace_defaults, set_notifications,
+    },
+    oidc::{add_trust, exchange, list_trusts, remove_trust},
+    openapi::openapi_spec,
+    preview::{delete_function_preview, list_function_previews},
+    promote::promote,
+    purge::purge_function_cache,
+    replay::{list_recorded_invocations, replay_invocation},
+    report::{namespace_dependency_report, platform_dependency_report},
+    routing::{get_routing_rules, set_routing_rules},
+    status::{create_incident, delete_incident, platform_status},
+    storage::{get_storage, retention_policy},
+    totp::{disable_totp, enable_totp, enroll_totp},
+    trash::{delete_function, list_trashed_functions, restore_trashed_function},
 };
+use middlewares::firewall::function_firewall;
+use middlewares::grpc::route_grpc;
+use middlewares::status::count_invocation;
 use redis::aio::MultiplexedConnection;
 use runtime::core::autoscaler::Autoscaler;
 use runtime::core::builder::AutoscalingRuntimeBuilder;
+use runtime::RuntimeError;
 use sea_orm::{Database, DatabaseConnection};
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use thiserror::Error;
-use tracing::{error, info};
+use tokio::sync::watch;
+use tower::Layer;
+use tracing::{error, info, warn};
+use uuid::Uuid;
 
 /// Application state shared across handlers.
 #[derive(Clone, FromRef)]
 pub struct AppState {
     /// Database connection for persisting data.
     pub db_conn: DatabaseConnection,
+    /// Database connection for read-heavy queries that tolerate replication lag: the read
+    /// replica when configured, `db_conn` otherwise.
+    pub db_read_conn: ReadDb,
     /// Redis connection for caching.
     pub cache_conn: MultiplexedConnection,
     /// Application configuration
     pub config: InvokConfig,
     // TODO: added autoscaler runtime
     pub autoscaler: Arc<Autoscaler>,
+    /// Set once a shutdown signal is received; new invocations are rejected
+    pub shutting_down: Arc<AtomicBool>,
+    /// Settings of functions invoked or deployed since startup, by function key
+    pub function_settings: Arc<RwLock<HashMap<String, FunctionSettings>>>,
+    /// Latest versions of functions invoked since startup or their last deploy, by function key
+    pub function_versions: Arc<RwLock<HashMap<String, Option<i32>>>>,
+    /// Routing rules of functions invoked since startup or their last update, by function key
+    pub routing_rules: Arc<RwLock<HashMap<String, RoutingRules>>>,
+    /// Feature flags of namespaces invoked since startup or their last change, by namespace
+    pub feature_flags: Arc<RwLock<HashMap<Uuid, FeatureFlags>>>,
+    /// Cold starts left per client IP and namespace
+    pub cold_start_budget: Arc<ColdStartBudget>,
+    /// Verifies the OIDC tokens CI workflows exchange for deploy credentials
+    pub oidc_verifier: Arc<OidcVerifier>,
+    /// Builds function images, locally or on the builder service
+    pub image_builder: Arc<ImageBuilder>,
+    /// Enforces password rules, login lockouts and the registration captcha
+    pub login_guard: Arc<LoginGuard>,
+    /// Invocation error rate and the last status page computed
+    pub status_tracker: Arc<StatusTracker>,
+    /// Hit rate of the function lookup cache
+    pub lookup_stats: Arc<LookupStats>,
+    /// Tells the other controllers to drop their cached settings of changed functions
+    pub cache_invalidator: Arc<CacheInvalidator>,
+    /// Queues background jobs, run by the job runner of any controller
+    pub jobs: JobQueue,
+    /// Object storage large payloads pass through, when configured
+    pub payload_store: Option<Arc<PayloadStore>>,
+    /// Pooled HTTP/2 connections to the containers of functions serving h2c
+    pub h2c_client: H2cClient,
+    /// Ports published for TCP and UDP service functions
+    pub service_listeners: Arc<ServiceListeners>,
+    /// Which namespaces receive traffic, for hibernation
+    pub namespace_activity: Arc<NamespaceActivity>,
+    /// Signs functions' outbound requests with their namespace's credentials
+    pub egress_gateway: Arc<EgressGateway>,
+    /// Advisories the dependencies of deployed functions are checked against
+    pub dependency_advisories: Arc<DependencyAdvisories>,
+    /// Base images of the runtimes, kept pulled on this host
+    pub base_images: Arc<BaseImages>,
+}
+
+/// Connection for reads that tolerate replication lag, kept apart from the primary's type so
+/// each field of [`AppState`] has its own.
+#[derive(Clone)]
+pub struct ReadDb(pub DatabaseConnection);
+
+impl Deref for ReadDb {
+    type Target = DatabaseConnection;
+
+    fn deref(&self) -> &DatabaseConnection {
+        &self.0
+    }
 }
 
 /// Custom error type for server initialization.
@@ -53,6 +198,12 @@ pub enum InvokAppError {
 
     #[error("HTTP server error: {0}")]
     Http(#[from] hyper::Error),
+
+    #[error("Autoscaling runtime error: {0}")]
+    Runtime(#[from] RuntimeError),
+
+    #[error("Startup checks failed:\n  - {}", .0.join("\n  - "))]
+    Preflight(Vec<String>),
 }
 
 /// Starts the server and sets up the necessary connections and routes.
@@ -60,22 +211,43 @@ pub enum InvokAppError {
 /// This function performs the following:
 /// - Initializes structured logging.
 /// - Loads application configuration
+/// - Runs the preflight checks (same as `doctor`) and refuses to start if any fail.
 /// - Connects to Redis and the database.
 /// - Runs database migrations.
 /// - Sets up the Axum router with defined routes.
 /// - Binds the server to a socket address and starts serving requests.
+/// - On SIGTERM/SIGINT, stops accepting new work, drains in-flight requests (bounded by
+///   the shutdown timeout), then stops the autoscaler and flushes its state.
 pub async fn start_server() -> Result<(), InvokAppError> {
     tracing_subscriber::fmt::init();
 
     // Load application configuration
     let config = InvokConfig::load()?;
 
+    // Verify dependencies up front instead of failing later with opaque errors
+    let report = doctor::run_checks(&config).await;
+    report.log();
+    if report.has_failures() {
+        return Err(InvokAppError::Preflight(report.failures()));
+    }
+
     // Connect to Redis.
     let client = redis::Client::open(config.server_config.redis_url.clone())?;
     let cache_conn = client.get_multiplexed_async_connection().await?;
+    let jobs = JobQueue::new(cache_conn.clone());
 
-    // Connect to the database.
-    let db_conn = Database::connect(config.server_config.database_url.clone()).await?;
+    // Connect to the database, and to the read replica when one is configured.
+    let database_config = &config.database_config;
+    let db_conn =
+        Database::connect(database_config.connect_options(&config.server_config.database_url))
+            .await?;
+    let db_read_conn = ReadDb(match &database_config.read_replica_url {
+        Some(replica_url) => {
+            info!("Listings and descriptions are read from the database replica");
+            Database::connect(database_config.connect_options(replica_url)).await?
+        }
+        None => db_conn.clone(),
+    });
 
     // Run database migrations.
     Migrator::up(&db_conn, None).await?;
@@ -84,7 +256,8 @@ pub async fn start_server() -> Result<(), InvokAppError> {
     let runtime = AutoscalingRuntimeBuilder::new()
         .cpu_overload_threshold(config.function_config.autoscaling.cpu_overload_threshold)
         .memory_overload_threshold(config.function_config.autoscaling.memory_overload_threshold)
-        .docker_compose_network_host(config.server_config.docker_compose_network_host.to_string())
+        .docker_compose_network_host(config.server_config.docker_compose_network_host.clone())
+        .address_mode(config.server_config.address_mode())
         .min_containers_per_function(
             config
                 .function_config
@@ -97,6 +270,16 @@ pub async fn start_server() -> Result<(), InvokAppError> {
                 .autoscaling
                 .max_containers_per_function,
         )
+        .max_containers_per_namespace(
+            config
+                .function_config
+                .autoscaling
+                .max_containers_per_namespace,
+        )
+        .max_containers_per_host(config.function_config.autoscaling.max_containers_per_host)
+        .max_host_cpu_percent(config.function_config.autoscaling.max_host_cpu_percent)
+        .max_host_memory_percent(config.function_config.autoscaling.max_host_memory_percent)
+        .max_concurrent_starts(config.function_config.autoscaling.max_concurrent_starts)
         .cooldown_duration(Duration::from_secs(
             config.function_config.autoscaling.cooldown_duration_secs,
         ))
@@ -104,50 +287,510 @@ pub async fn start_server() -> Result<(), InvokAppError> {
         .scale_check_interval(Duration::from_secs(
             config.function_config.autoscaling.poll_interval_secs,
         ))
+        .idle_scale_check_interval(Duration::from_secs(
+            config.function_config.autoscaling.idle_poll_interval_secs,
+        ))
+        .max_parallel_pool_checks(config.function_config.autoscaling.max_parallel_pool_checks)
+        .pool_check_timeout(Duration::from_secs(
+            config.function_config.autoscaling.pool_check_timeout_secs,
+        ))
+        .cold_start_timeout(Duration::from_secs(
+            config.function_config.autoscaling.cold_start_timeout_secs,
+        ))
         .persistence_enabled(config.function_config.autoscaling.persistence_enabled)
         .redis_url(config.server_config.redis_url.clone())
-        .persistence_batch_size(20) // Load 20 pools at a time during recovery
+        .persistence_batch_size(config.function_config.autoscaling.persistence_batch_size)
+        .persistence_scan_page_size(
+            config
+                .function_config
+                .autoscaling
+                .persistence_scan_page_size,
+        )
+        .persistence_state_ttl(Duration::from_secs(
+            config.function_config.autoscaling.pool_state_ttl_secs,
+        ))
+        .persistence_max_state_age(config.function_config.autoscaling.pool_state_max_age())
+        .replicas(config.function_config.autoscaling.replica_config())
+        .metrics_source(config.function_config.autoscaling.metrics_source())
+        .prometheus_url(config.function_config.autoscaling.prometheus_url.clone())
+        .prometheus_container_id_pattern(
+            config
+                .function_config
+                .autoscaling
+                .prometheus_container_id_pattern
+                .clone(),
+        )
+        .prometheus_auth(config.function_config.autoscaling.metrics_auth())
+        .prometheus_ca_cert(
+            config
+                .function_config
+                .autoscaling
+                .prometheus_ca_cert
+                .clone(),
+        )
+        .egress(config.egress_config.proxy_config(
+            &config.server_config.redis_url,
+            &config.server_config.jwt_auth_secret,
+        ))
+        .internal_api(config.server_config.internal_api())
+        .sandbox(config.sandbox_policy())
         .build()
         .await
-        .map_err(|e| {
-            error!("Failed to build autoscaling runtime: {}", e);
-            InvokAppError::Config(InvokConfigError::InvalidValue(format!(
-                "Runtime build error: {}",
-                e
-            )))
-        })?;
+        .inspect_err(|e| error!("Failed to build autoscaling runtime: {}", e))?;
 
     // Start runtime
-    runtime.start().await.map_err(|e| {
-        error!("Failed to start autoscaling runtime: {}", e);
-        InvokAppError::Config(InvokConfigError::InvalidValue(format!(
-            "Runtime start error: {}",
-            e
-        )))
-    })?;
+    runtime
+        .start()
+        .await
+        .inspect_err(|e| error!("Failed to start autoscaling runtime: {}", e))?;
 
+    // Purge functions whose trash retention has ended
+    let trash_retention =
+        Duration::from_secs(config.function_config.trash_retention_hours * 60 * 60);
+    tokio::spawn(run_purge_loop(
+        db_conn.clone(),
+        jobs.clone(),
+        trash_retention,
+    ));
+
+    // Build images on the builder service when one is configured
+    let builder_config = &config.builder_config;
+    let remote_builder = match (
+        &builder_config.url,
+        &builder_config.token,
+        &builder_config.registry,
+    ) {
+        (Some(url), Some(token), Some(registry)) => {
+            info!(
+                "Function images are built by the builder service at {}",
+                url
+            );
+            Some(RemoteBuilder::new(
+                url,
+                token,
+                registry,
+                builder_config.registry_auth.clone(),
+                Duration::from_secs(builder_config.timeout_secs),
+            ))
+        }
+        _ => None,
+    };
+    // The key was checked when the config was loaded
+    let build_arg_cipher = config
+        .server_config
+        .build_args_key
+        .as_deref()
+        .and_then(|key| BuildArgCipher::from_base64(key).ok());
+    if build_arg_cipher.is_none() {
+        info!("Build args are disabled; set BUILD_ARGS_KEY to enable them");
+    }
+    let image_builder = ImageBuilder {
+        platforms: config.server_config.build_platforms.clone(),
+        remote: remote_builder,
+        build_arg_cipher,
+    };
+
+    // Pass large payloads through object storage when a bucket is configured. The URL
+    // was checked when the config was loaded.
+    let payload_config = &config.payload_config;
+    let payload_store = match (
+        &payload_config.store_url,
+        &payload_config.access_key,
+        &payload_config.secret_key,
+    ) {
+        (Some(url), Some(access_key), Some(secret_key)) => PayloadStore::new(
+            url,
+            PresignCredentials {
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+                region: payload_config.region.clone(),
+            },
+            payload_config.threshold,
+            payload_config.max_size,
+            Duration::from_secs(payload_config.url_ttl_secs),
+        )
+        .map(Arc::new),
+        _ => None,
+    };
+    if let Some(url) = &payload_config.store_url {
+        info!(
+            "Payloads above {} bytes pass through {}",
+            payload_config.threshold, url
+        );
+    }
+
+    let namespace_activity = Arc::new(NamespaceActivity::new(db_conn.clone(), cache_conn.clone()));
+    let service_config = &config.service_config;
+    let service_listeners = Arc::new(ServiceListeners::new(
+        db_conn.clone(),
+        runtime.autoscaler().clone(),
+        namespace_activity.clone(),
+        service_config.bind_address,
+        service_config.port_range_start..=service_config.port_range_end,
+    ));
+
+    let shutting_down = Arc::new(AtomicBool::new(false));
     let app_state = AppState {
         db_conn,
+        db_read_conn,
         cache_conn,
         config: config.clone(),
         autoscaler: runtime.autoscaler().clone(),
+        shutting_down: shutting_down.clone(),
+        function_settings: Arc::new(RwLock::new(HashMap::new())),
+        function_versions: Arc::new(RwLock::new(HashMap::new())),
+        routing_rules: Arc::new(RwLock::new(HashMap::new())),
+        feature_flags: Arc::new(RwLock::new(HashMap::new())),
+        cold_start_budget: Arc::new(ColdStartBudget::new(
+            config.function_config.cold_start_budget_per_source,
+            config.function_config.cold_start_budget_per_namespace,
+        )),
+        oidc_verifier: Arc::new(OidcVerifier::new(
+            &config.server_config.oidc_issuer,
+            &config.server_config.oidc_audience,
+        )),
+        image_builder: Arc::new(image_builder),
+        login_guard: Arc::new(config.auth_config.login_guard()),
+        status_tracker: Arc::new(StatusTracker::new()),
+        lookup_stats: Arc::new(LookupStats::new()),
+        cache_invalidator: Arc::new(CacheInvalidator::new()),
+        jobs,
+        payload_store,
+        h2c_client: h2c_client(),
+        service_listeners,
+        namespace_activity,
+        egress_gateway: Arc::new(EgressGateway::new()),
+        dependency_advisories: Arc::new(DependencyAdvisories::new(
+            config.function_config.dependency_advisories.clone(),
+        )),
+        base_images: Arc::new(BaseImages::new(
+            &builder_config.base_images,
+            builder_config.base_image_mirror.clone(),
+        )),
     };
 
+    // Run the background jobs queued by any controller
+    let mut job_runner = JobRunner::new(DEFAULT_WORKERS);
+    let conn = app_state.db_conn.clone();
+    job_runner.register(NOTIFICATION_JOB, move |payload| {
+        let conn = conn.clone();
+        async move { run_delivery(&conn, payload).await }
+    });
+    let conn = app_state.db_conn.clone();
+    let cache_conn = app_state.cache_conn.clone();
+    job_runner.register(PURGE_JOB, move |payload| {
+        let conn = conn.clone();
+        let mut cache_conn = cache_conn.clone();
+        async move { run_purge(&conn, &mut cache_conn, trash_retention, payload).await }
+    });
+    let state = app_state.clone();
+    let preview_ttl = config.function_config.preview_ttl();
+    job_runner.register(DEPLOY_JOB, move |payload| {
+        let state = state.clone();
+        async move {
+            run_deploy(&state.db_conn, preview_ttl, payload, |function| {
+                deploy_and_apply(&state, function)
+            })
+            .await
+        }
+    });
+    tokio::spawn(job_runner.run(app_state.cache_conn.clone()));
+
+    // The egress proxies read allowlists from Redis, which may have lost them
+    if config.egress_config.enabled {
+        tokio::spawn(run_allowlist_sync_loop(
+            app_state.db_conn.clone(),
+            app_state.cache_conn.clone(),
+        ));
+    }
+
+    // Remove preview instances once they expire
+    tokio::spawn(run_expiry_loop(app_state.clone()));
+
+    // Prune old function versions down to the retention policy
+    tokio::spawn(run_retention_loop(
+        app_state.db_conn.clone(),
+        app_state.cache_conn.clone(),
+        app_state.autoscaler.clone(),
+        retention_policy(&app_state),
+    ));
+
+    // Keep idle containers through the scaling freeze windows operators declared
+    tokio::spawn(run_freeze_sync_loop(
+        app_state.cache_conn.clone(),
+        app_state.autoscaler.clone(),
+    ));
+
+    // Drain namespaces that went without traffic for too long
+    if let Some(days) = config.function_config.autoscaling.hibernate_after_days {
+        tokio::spawn(run_hibernation_loop(
+            app_state.db_conn.clone(),
+            app_state.cache_conn.clone(),
+            app_state.autoscaler.clone(),
+            Duration::from_secs(days * 24 * 60 * 60),
+        ));
+    }
+
+    // Remove dev containers once they expire
+    tokio::spawn(run_dev_expiry_loop(app_state.autoscaler.clone()));
+
+    // Ping functions on their warmup schedules
+    tokio::spawn(run_warmup_loop(
+        app_state.db_conn.clone(),
+        app_state.cache_conn.clone(),
+        app_state.autoscaler.clone(),
+        WarmupOptions {
+            max_request_size: config.function_config.max_request_size,
+            max_response_size: config.function_config.max_response_size,
+            hide_internal_addresses: config.server_config.hide_internal_addresses,
+        },
+    ));
+
+    // Publish the ports of TCP and UDP service functions
+    tokio::spawn(app_state.service_listeners.clone().run());
+
+    // Drop cached settings of functions other controllers changed
+    let cache_invalidator = app_state.cache_invalidator.clone();
+    let invalidation_state = app_state.clone();
+    let redis_url = config.server_config.redis_url.clone();
+    tokio::spawn(async move { cache_invalidator.run(redis_url, invalidation_state).await });
+
+    // Check deployed dependencies against the latest advisories
+    tokio::spawn(run_advisory_refresh_loop(
+        app_state.db_conn.clone(),
+        app_state.dependency_advisories.clone(),
+        Duration::from_secs(config.function_config.dependency_advisories_refresh_secs),
+    ));
+
+    // Keep the runtime base images pulled, so builds and function images pulled on this
+    // host only fetch their own layers
+    if builder_config.prepull_base_images {
+        tokio::spawn(run_base_image_refresh_loop(
+            app_state.base_images.clone(),
+            Duration::from_secs(builder_config.base_image_refresh_secs),
+        ));
+    }
+
+    // Tell namespaces about crashing and unschedulable functions
+    tokio::spawn(run_anomaly_loop(
+        app_state.db_conn.clone(),
+        app_state.jobs.clone(),
+        app_state.autoscaler.clone(),
+    ));
+
     // Create a router with all our routes
     let app = Router::new()
+        // Liveness and readiness probes
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        // Public status page
+        .route("/status", get(platform_status))
+        // OpenAPI document of the management routes
+        .route("/openapi.yaml", get(openapi_spec))
         // Auth routes
         .route("/auth/register", post(register))
         .route("/auth/login", post(login))
+        .route("/auth/totp/enroll", post(enroll_totp))
+        .route("/auth/totp/enable", post(enable_totp))
+        .route("/auth/totp/disable", post(disable_totp))
+        // CI workflows exchange OIDC tokens for short-lived deploy credentials
+        .route("/auth/oidc/exchange", post(exchange))
+        .route("/invok/oidc/trusts", get(list_trusts).post(add_trust))
+        .route("/invok/oidc/trusts/:id", delete(remove_trust))
         // Function management routes
         .route("/invok/list", get(list_functions))
         .route("/invok/deploy", post(upload_function))
+        // Where a queued deploy is at
+        .route("/invok/deployments/:id", get(get_deployment))
+        // What a deploy would change, from the digests of its archive
+        .route("/invok/diff/:function_name", post(diff_function_deploy))
+        // Deploys a function's running version from another namespace, e.g. staging
+        .route("/invok/promote/:function_name", post(promote))
+        // Locks refusing deploys of a function, or of the whole namespace
+        .route("/invok/locks", get(list_locks))
+        .route("/invok/lock", put(lock_namespace).delete(unlock_namespace))
+        .route(
+            "/invok/lock/:function_name",
+            put(lock_function).delete(unlock_function),
+        )
+        // Deploys held until another account approves them
+        .route("/invok/approvers", get(get_approvers).put(set_approvers))
+        .route("/invok/approvals", get(list_pending_deploys))
+        .route("/invok/approvals/:id/approve", post(approve_deploy))
+        .route("/invok/approvals/:id/reject", post(reject_deploy))
+        // Encrypted variables passed to the build stage of the namespace's images
+        .route("/invok/buildargs", get(list_build_args))
+        .route(
+            "/invok/buildargs/:name",
+            put(set_build_arg).delete(remove_build_arg),
+        )
+        // Third-party credentials the namespace's functions send requests with through
+        // the gateway
+        .route("/invok/credentials", get(list_credentials))
+        .route(
+            "/invok/credentials/:name",
+            put(set_credential).delete(remove_credential),
+        )
+        // Temporary per-branch instances of functions
+        .route("/invok/previews", get(list_function_previews))
+        .route(
+            "/invok/previews/:function_name/:branch",
+            delete(delete_function_preview),
+        )
+        // Destinations functions may reach through the egress proxy
+        .route(
+            "/invok/egress/:function_name",
+            get(get_egress_allowlist).put(set_egress_allowlist),
+        )
+        // Storage taken by recorded versions, pruned down to the retention policy
+        .route("/invok/storage", get(get_storage))
+        // Functions using deprecated runtimes or vulnerable dependency versions
+        .route(
+            "/invok/report/dependencies",
+            get(namespace_dependency_report),
+        )
+        // Deleted functions stay in the trash until the retention period ends
+        .route("/invok/delete/:function_name", delete(delete_function))
+        .route("/invok/trash", get(list_trashed_functions))
+        .route(
+            "/invok/restore/:function_name",
+            post(restore_trashed_function),
+        )
+        // Settings inherited by every function of the namespace
+        .route(
+            "/invok/defaults",
+            get(get_namespace_defaults).put(set_namespace_defaults),
+        )
+        // Where deploys and scaling anomalies are reported
+        .route(
+            "/invok/notifications",
+            get(get_notifications).put(set_notifications),
+        )
+        // Values sent to the namespace's functions as headers, rolled out gradually
+        .route("/invok/flags", get(list_flags))
+        .route("/invok/flags/:key", put(set_flag).delete(remove_flag))
+        // Moving a whole namespace between installations
+        .route("/invok/export", get(export_functions))
+        .route(
+            "/invok/import",
+            post(import_functions).layer(DefaultBodyLimit::max(
+                config.function_config.max_import_size,
+            )),
+        )
+        // Key-value store of the calling function (disabled unless an internal API URL is
+        // configured)
+        .route(
+            "/internal/kv/:key",
+            get(get_entry)
+                .put(set_entry)
+                .delete(delete_entry)
+                .layer(DefaultBodyLimit::max(MAX_VALUE_SIZE)),
+        )
+        .route("/internal/kv/:key/increment", post(increment_entry))
+        // Outbound requests of the calling function, signed with a namespace credential
+        .route(
+            "/internal/fetch/:credential",
+            any(fetch).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        .route(
+            "/internal/fetch/:credential/*path",
+            any(fetch).layer(DefaultBodyLimit::max(MAX_BODY_SIZE)),
+        )
+        // Admin routes (disabled unless an admin token is configured)
+        .route("/admin/backup", get(backup))
+        .route("/admin/base-images", get(list_base_images))
+        .route("/admin/base-images/pull", post(pull_base_images))
+        .route("/admin/approvers/:namespace", put(set_namespace_approvers))
+        .route("/admin/freezes", get(list_freezes).post(create_freeze))
+        .route("/admin/freezes/:id", delete(delete_freeze))
+        .route("/admin/incidents", post(create_incident))
+        .route("/admin/incidents/:id", delete(delete_incident))
+        .route("/admin/jobs", get(list_jobs))
+        .route(
+            "/admin/report/dependencies",
+            get(platform_dependency_report),
+        )
+        .route(
+            "/admin/restore",
+            post(restore).layer(DefaultBodyLimit::max(config.server_config.max_restore_size)),
+        )
         // Function logs route
         .route(
             "/invok/logs/:namespace/:function_name",
             get(stream_function_logs),
         )
+        // Interactive commands in a function's containers, over a websocket
+        .route("/invok/exec/:function_name", get(exec_function))
+        // Dev containers `invok dev --remote` syncs local changes into
+        .route(
+            "/invok/dev/:function_name",
+            put(start_dev_container).delete(stop_dev_container),
+        )
+        .route("/invok/dev/:function_name/sync", post(sync_dev_container))
+        .route(
+            "/invok/dev/:function_name/logs",
+            get(stream_dev_container_logs),
+        )
+        // Boot logs of containers that failed to become ready
+        .route("/invok/bootlogs/:function_name", get(function_boot_logs))
+        // Function status route
+        .route(
+            "/invok/status/:namespace/:function_name",
+            get(function_status),
+        )
+        // Resource usage and right-sizing recommendations
+        .route(
+            "/invok/recommendations/:namespace/:function_name",
+            get(function_recommendations),
+        )
+        // Load tests lined up against the autoscaler's reaction
+        .route(
+            "/invok/bench/:function_name",
+            post(bench_report).layer(DefaultBodyLimit::max(MAX_BENCH_BODY_SIZE)),
+        )
+        // Compliance with the function's SLO and its error budget
+        .route("/invok/slo/:namespace/:function_name", get(function_slo))
+        // Requests recorded for functions with `record_invocations`, and their replays
+        .route(
+            "/invok/invocations/:function_name",
+            get(list_recorded_invocations),
+        )
+        .route("/invok/replay/:invocation_id", post(replay_invocation))
+        // Drops a function's cached responses and notifies the purge webhooks
+        .route("/invok/purge/:function_name", post(purge_function_cache))
+        // Rules sending matching invocations of a function to one of its versions
+        .route(
+            "/invok/routing/:function_name",
+            get(get_routing_rules).put(set_routing_rules),
+        )
+        // README and OpenAPI document shipped with a function
+        .route("/invok/docs/:namespace/:function_name", get(function_docs))
+        // Invocations of functions' dev containers
+        .route(
+            "/invok/:namespace/:function_name/dev",
+            any(call_dev_container)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    function_firewall,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    count_invocation,
+                )),
+        )
         // Function invocation routes
-        .route("/invok/:namespace/:function_name", any(call_function))
+        .route(
+            "/invok/:namespace/:function_name",
+            any(call_function)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    function_firewall,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    count_invocation,
+                )),
+        )
         .with_state(app_state);
 
     // Build socket address from configuration
@@ -162,9 +805,95 @@ pub async fn start_server() -> Result<(), InvokAppError> {
 
     info!("Server listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await?;
+    // gRPC calls are made on the function's own paths, so they are routed before the
+    // router sees them
+    let app = middleware::map_request(route_grpc).layer(app);
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    let server = axum::Server::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            shutting_down.store(true, Ordering::SeqCst);
+            let _ = shutdown_tx.send(true);
+        });
+
+    // Once shutdown starts, give in-flight requests a bounded amount of time to finish
+    let drain_timeout = Duration::from_secs(config.server_config.shutdown_timeout_secs);
+    let drain_deadline = async move {
+        let _ = shutdown_rx.changed().await;
+        info!(
+            "Shutdown signal received, draining in-flight requests (timeout {:?})",
+            drain_timeout
+        );
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => {
+            warn!(
+                "In-flight requests did not finish within {:?}, shutting down anyway",
+                drain_timeout
+            );
+        }
+    }
+
+    if let Err(e) = runtime.shutdown().await {
+        error!("Failed to shut down autoscaling runtime cleanly: {}", e);
+    }
+    info!("Server stopped");
 
     Ok(())
 }
+
+/// Checks configuration and every dependency, printing a report.
+///
+/// Backs the `doctor` subcommand. Returns `true` when nothing failed outright.
+pub async fn run_doctor() -> bool {
+    let config = match InvokConfig::load() {
+        Ok(config) => {
+            println!("[ OK ] {:<12} loaded", "config");
+            config
+        }
+        Err(e) => {
+            println!("[FAIL] {:<12} {}", "config", e);
+            return false;
+        }
+    };
+
+    let report = doctor::run_checks(&config).await;
+    print!("{}", report);
+    !report.has_failures()
+}
+
+/// Resolves when the process receives SIGINT (Ctrl+C) or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -1,4 +1,24 @@
+pub(crate) mod activity;
+pub(crate) mod audit;
 pub(crate) mod auth;
+pub(crate) mod backup;
+pub(crate) mod build_arg;
 pub(crate) mod cache;
+pub(crate) mod deploy_lock;
+pub(crate) mod deployment;
+pub(crate) mod egress;
+pub(crate) mod egress_credential;
+pub(crate) mod freeze;
 pub(crate) mod function;
+pub(crate) mod function_version;
+pub(crate) mod incident;
+pub(crate) mod invocation;
+pub(crate) mod job;
+pub(crate) mod kv;
+pub(crate) mod login_attempts;
 pub(crate) mod models;
+pub(crate) mod oidc_trust;
+pub(crate) mod pending_deploy;
+pub(crate) mod response_cache;
+pub(crate) mod slo;
+pub(crate) mod warmup;
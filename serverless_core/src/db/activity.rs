@@ -0,0 +1,54 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use std::collections::HashMap;
+
+/// Redis hash holding when each namespace was last invoked (unix seconds), by namespace
+const ACTIVITY_KEY: &str = "namespace:last_invoked";
+
+/// When namespaces last received traffic, written by every controller
+pub struct ActivityRepo;
+
+impl ActivityRepo {
+    /// Lists when each namespace was last invoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    ///
+    /// # Returns
+    ///
+    /// * The unix time of the last invocation by namespace, or a `redis::RedisError`.
+    pub async fn all(conn: &mut MultiplexedConnection) -> redis::RedisResult<HashMap<String, u64>> {
+        conn.hgetall(ACTIVITY_KEY).await
+    }
+
+    /// Records that a namespace was invoked at `at`.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The namespace that was invoked.
+    /// * `at` - The unix time of the invocation.
+    pub async fn touch(
+        conn: &mut MultiplexedConnection,
+        namespace: &str,
+        at: u64,
+    ) -> redis::RedisResult<()> {
+        conn.hset(ACTIVITY_KEY, namespace, at).await
+    }
+
+    /// Records `at` for a namespace that has no recorded invocation yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The namespace.
+    /// * `at` - The unix time to record.
+    pub async fn touch_if_missing(
+        conn: &mut MultiplexedConnection,
+        namespace: &str,
+        at: u64,
+    ) -> redis::RedisResult<()> {
+        let _: bool = conn.hset_nx(ACTIVITY_KEY, namespace, at).await?;
+        Ok(())
+    }
+}
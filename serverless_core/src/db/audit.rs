@@ -0,0 +1,43 @@
+use db_entities::audit_log::{ActiveModel as AuditLogModel, Model};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DbConn};
+
+pub struct AuditLogDBRepo;
+
+impl AuditLogDBRepo {
+    /// Records an action taken on a user's namespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the action was taken on.
+    /// * `actor` - Who took the action, e.g. the user's email.
+    /// * `action` - What was done, e.g. `function.exec`.
+    /// * `target` - The name of what it was done to, if anything.
+    /// * `details` - Specifics of the action, if any.
+    /// * `source_ip` - The client IP the action came from, if known.
+    ///
+    /// # Returns
+    ///
+    /// * The recorded entry, or an error of type `sea_orm::DbErr` if insertion fails.
+    pub async fn record(
+        conn: &DbConn,
+        auth_id: i32,
+        actor: String,
+        action: &str,
+        target: Option<String>,
+        details: Option<serde_json::Value>,
+        source_ip: Option<String>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        AuditLogModel {
+            auth_id: Set(auth_id),
+            actor: Set(actor),
+            action: Set(action.to_string()),
+            target: Set(target),
+            details: Set(details),
+            source_ip: Set(source_ip),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+}
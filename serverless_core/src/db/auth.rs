@@ -1,3 +1,4 @@
+use crate::lifecycle_manager::totp;
 use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
@@ -7,8 +8,10 @@ use db_entities::{
     prelude::Auth as AuthEntity,
 };
 use rand_core::OsRng;
+use sea_orm::prelude::{DateTimeWithTimeZone, Expr};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, DbErr, EntityTrait, QueryFilter,
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, DbConn, DbErr, EntityTrait,
+    QueryFilter, QuerySelect,
 };
 use uuid::Uuid;
 
@@ -51,6 +54,15 @@ impl AuthDBRepo {
             email: Set(email),
             password: Set(hashed_password),
             uuid: Set(Uuid::new_v4()),
+            defaults: Set(None),
+            totp_secret: Set(None),
+            totp_enabled: Set(false),
+            recovery_codes: Set(None),
+            totp_last_step: Set(None),
+            notifications: Set(None),
+            deploy_approvers: Set(None),
+            hibernated_at: Set(None),
+            feature_flags: Set(None),
         };
 
         // Save the user to the database
@@ -107,6 +119,393 @@ impl AuthDBRepo {
             .await
     }
 
+    /// Replace the namespace defaults of a user
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `defaults` - The new defaults, or `None` to clear them
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn update_defaults(
+        conn: &DbConn,
+        user: AuthUser,
+        defaults: Option<serde_json::Value>,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.defaults = Set(defaults);
+        user.update(conn).await
+    }
+
+    /// Replace the notification targets of a user's namespace
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `notifications` - The new targets, or `None` to stop notifying
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn update_notifications(
+        conn: &DbConn,
+        user: AuthUser,
+        notifications: Option<serde_json::Value>,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.notifications = Set(notifications);
+        user.update(conn).await
+    }
+
+    /// Find the namespaces that have notification targets
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<Uuid>)` - The UUIDs of the users with notifications configured
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_notified_namespaces(conn: &DbConn) -> Result<Vec<Uuid>, DbErr> {
+        AuthEntity::find()
+            .select_only()
+            .column(AuthColumn::Uuid)
+            .filter(AuthColumn::Notifications.is_not_null())
+            .into_tuple::<Uuid>()
+            .all(conn)
+            .await
+    }
+
+    /// Replace the accounts that approve deploys of a user's namespace
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `approvers` - The approvers' emails, or `None` to deploy without approval
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn update_deploy_approvers(
+        conn: &DbConn,
+        user: AuthUser,
+        approvers: Option<serde_json::Value>,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.deploy_approvers = Set(approvers);
+        user.update(conn).await
+    }
+
+    /// Find the users whose deploys an account approves
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `email` - The approver's email, as registered
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<AuthUser>)` - The users listing the account as an approver
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_by_approver(conn: &DbConn, email: &str) -> Result<Vec<AuthUser>, DbErr> {
+        let approver = serde_json::json!({ "approvers": [email] });
+        AuthEntity::find()
+            .filter(Expr::cust_with_values(
+                "deploy_approvers @> $1",
+                [sea_orm::Value::Json(Some(Box::new(approver)))],
+            ))
+            .all(conn)
+            .await
+    }
+
+    /// Replace the feature flags of a user's namespace
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `flags` - The flags, or `None` to remove them all
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn update_feature_flags(
+        conn: &DbConn,
+        user: AuthUser,
+        flags: Option<serde_json::Value>,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.feature_flags = Set(flags);
+        user.update(conn).await
+    }
+
+    /// Find every user, hibernated namespaces included
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<AuthUser>)` - The users
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_all(conn: &DbConn) -> Result<Vec<AuthUser>, DbErr> {
+        AuthEntity::find().all(conn).await
+    }
+
+    /// Find the users whose namespace is hibernated
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<AuthUser>)` - The users with a hibernated namespace
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_hibernated(conn: &DbConn) -> Result<Vec<AuthUser>, DbErr> {
+        AuthEntity::find()
+            .filter(AuthColumn::HibernatedAt.is_not_null())
+            .all(conn)
+            .await
+    }
+
+    /// Mark a user's namespace hibernated, unless it already is
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `uuid` - The UUID of the user
+    /// * `at` - When the namespace was hibernated
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(bool)` - Whether the namespace was awake
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn hibernate(
+        conn: &DbConn,
+        uuid: Uuid,
+        at: DateTimeWithTimeZone,
+    ) -> Result<bool, DbErr> {
+        let result = AuthEntity::update_many()
+            .col_expr(AuthColumn::HibernatedAt, Expr::value(at))
+            .filter(AuthColumn::Uuid.eq(uuid))
+            .filter(AuthColumn::HibernatedAt.is_null())
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Clear the hibernation mark of a user's namespace
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `uuid` - The UUID of the user
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(bool)` - Whether the namespace was hibernated
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn wake(conn: &DbConn, uuid: Uuid) -> Result<bool, DbErr> {
+        let result = AuthEntity::update_many()
+            .col_expr(
+                AuthColumn::HibernatedAt,
+                Expr::value(Option::<DateTimeWithTimeZone>::None),
+            )
+            .filter(AuthColumn::Uuid.eq(uuid))
+            .filter(AuthColumn::HibernatedAt.is_not_null())
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Find a user by id
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `id` - The id of the user to find
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(AuthUser))` - The user, if found
+    /// * `Ok(None)` - If no user with the id exists
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_by_id(conn: &DbConn, id: i32) -> Result<Option<AuthUser>, DbErr> {
+        AuthEntity::find_by_id(id).one(conn).await
+    }
+
+    /// Find a user by email
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `email` - The email of the user to find
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(AuthUser))` - The user, if found
+    /// * `Ok(None)` - If no user with the email exists
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn find_by_email(conn: &DbConn, email: &str) -> Result<Option<AuthUser>, DbErr> {
+        AuthEntity::find()
+            .filter(AuthColumn::Email.eq(email))
+            .one(conn)
+            .await
+    }
+
+    /// Store a new TOTP secret for a user, pending until `enable_totp` confirms it
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `sealed_secret` - The base32-encoded secret, sealed with the server's key
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn set_pending_totp(
+        conn: &DbConn,
+        user: AuthUser,
+        sealed_secret: String,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.totp_secret = Set(Some(sealed_secret));
+        user.totp_enabled = Set(false);
+        user.recovery_codes = Set(None);
+        user.totp_last_step = Set(None);
+        user.update(conn).await
+    }
+
+    /// Require the pending TOTP secret of a user at login
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    /// * `recovery_codes` - Hashes of the recovery codes issued with it
+    /// * `step` - Time step of the code that confirmed the secret, which is used up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn enable_totp(
+        conn: &DbConn,
+        user: AuthUser,
+        recovery_codes: Vec<String>,
+        step: u64,
+    ) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.totp_enabled = Set(true);
+        user.recovery_codes = Set(Some(serde_json::json!(recovery_codes)));
+        user.totp_last_step = Set(Some(step as i64));
+        user.update(conn).await
+    }
+
+    /// Stop requiring a second factor at login, forgetting the secret and recovery codes
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user to update
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AuthUser)` - The updated user
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn disable_totp(conn: &DbConn, user: AuthUser) -> Result<AuthUser, DbErr> {
+        let mut user: AuthModel = user.into();
+        user.totp_secret = Set(None);
+        user.totp_enabled = Set(false);
+        user.recovery_codes = Set(None);
+        user.totp_last_step = Set(None);
+        user.update(conn).await
+    }
+
+    /// Use up the TOTP code of time step `step`, unless a code of that step or a later
+    /// one was already accepted. Concurrent logins with the same code can't both succeed.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user_id` - The user logging in
+    /// * `step` - Time step of the code they gave, see `totp::verify_code`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the code wasn't used before
+    /// * `Ok(false)` - If it was, or an earlier code was used after it
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn accept_totp_step(conn: &DbConn, user_id: i32, step: u64) -> Result<bool, DbErr> {
+        let step = step as i64;
+        let result = AuthEntity::update_many()
+            .col_expr(AuthColumn::TotpLastStep, Expr::value(step))
+            .filter(AuthColumn::Id.eq(user_id))
+            .filter(
+                Condition::any()
+                    .add(AuthColumn::TotpLastStep.is_null())
+                    .add(AuthColumn::TotpLastStep.lt(step)),
+            )
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Use up one of a user's recovery codes.
+    ///
+    /// The codes left are only written if they are still the ones read, so two logins
+    /// with the same code can't both succeed.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection
+    /// * `user` - The user logging in
+    /// * `code` - The recovery code they gave
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the code was unused; it can't be used again
+    /// * `Ok(false)` - If the user has no such code
+    /// * `Err(DbErr)` - If an error occurs during the database operation
+    pub async fn consume_recovery_code(
+        conn: &DbConn,
+        user: &AuthUser,
+        code: &str,
+    ) -> Result<bool, DbErr> {
+        let Some(stored) = user.recovery_codes.clone() else {
+            return Ok(false);
+        };
+        let mut remaining: Vec<String> = serde_json::from_value(stored.clone()).unwrap_or_default();
+        if !totp::take_recovery_code(&mut remaining, code) {
+            return Ok(false);
+        }
+
+        let result = AuthEntity::update_many()
+            .col_expr(
+                AuthColumn::RecoveryCodes,
+                Expr::value(serde_json::json!(remaining)),
+            )
+            .filter(AuthColumn::Id.eq(user.id))
+            .filter(AuthColumn::RecoveryCodes.eq(stored))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
     /// Hash a password using Argon2
     fn hash_password(password: &str) -> Result<String, DbErr> {
         let salt = SaltString::generate(&mut OsRng);
@@ -130,3 +529,76 @@ impl AuthDBRepo {
             .is_ok())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db_migrations::{Migrator, MigratorTrait};
+    use sea_orm::Database;
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::postgres::Postgres;
+
+    #[tokio::test]
+    #[ignore = "needs Docker for Postgres; run with --ignored"]
+    async fn test_second_factors_are_single_use() {
+        let postgres = Postgres::default().start().await.unwrap();
+        let url = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            postgres.get_host().await.unwrap(),
+            postgres.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let conn = Database::connect(url).await.unwrap();
+        Migrator::up(&conn, None).await.unwrap();
+        let user = AuthDBRepo::register(&conn, "dev@example.com".into(), "password".into())
+            .await
+            .unwrap();
+        let user = AuthDBRepo::set_pending_totp(&conn, user, "v1:sealed".into())
+            .await
+            .unwrap();
+        let (codes, hashes) = totp::generate_recovery_codes().unwrap();
+        let user = AuthDBRepo::enable_totp(&conn, user, hashes, 100)
+            .await
+            .unwrap();
+        assert_eq!(user.totp_last_step, Some(100));
+
+        // A code is accepted once, and nor are the codes of earlier steps after it
+        assert!(!AuthDBRepo::accept_totp_step(&conn, user.id, 100)
+            .await
+            .unwrap());
+        assert!(AuthDBRepo::accept_totp_step(&conn, user.id, 101)
+            .await
+            .unwrap());
+        assert!(!AuthDBRepo::accept_totp_step(&conn, user.id, 101)
+            .await
+            .unwrap());
+        assert!(!AuthDBRepo::accept_totp_step(&conn, user.id, 100)
+            .await
+            .unwrap());
+
+        // Of two logins that read the same recovery codes, only the first uses one; the
+        // other, whatever code it gives, doesn't write the stale codes back
+        assert!(AuthDBRepo::consume_recovery_code(&conn, &user, &codes[0])
+            .await
+            .unwrap());
+        assert!(!AuthDBRepo::consume_recovery_code(&conn, &user, &codes[0])
+            .await
+            .unwrap());
+        assert!(!AuthDBRepo::consume_recovery_code(&conn, &user, &codes[1])
+            .await
+            .unwrap());
+        let user = AuthDBRepo::find_by_uuid(&conn, user.uuid)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!AuthDBRepo::consume_recovery_code(&conn, &user, &codes[0])
+            .await
+            .unwrap());
+        assert!(AuthDBRepo::consume_recovery_code(&conn, &user, &codes[1])
+            .await
+            .unwrap());
+
+        // Disabling forgets the last step, so a new enrollment starts over
+        let user = AuthDBRepo::disable_totp(&conn, user).await.unwrap();
+        assert_eq!(user.totp_last_step, None);
+    }
+}
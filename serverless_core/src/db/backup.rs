@@ -0,0 +1,261 @@
+use db_entities::prelude::{
+    ApiToken, AuditLog, Auth, BuildArg, DeployLock, Deployment, Domain, EgressCredential,
+    EventSource, Function, FunctionVersion, OidcTrust, PendingDeploy, Schedule, Usage,
+};
+use db_entities::{
+    api_token, audit_log, auth, build_arg, deploy_lock, deployment, domain, egress_credential,
+    event_source, function, function_version, oidc_trust, pending_deploy, schedule, usage,
+};
+use sea_orm::{
+    AccessMode, ActiveModelTrait, ConnectionTrait, DbConn, DbErr, EntityTrait, IntoActiveModel,
+    IsolationLevel, QueryOrder, Statement, TransactionTrait,
+};
+
+/// Tables with a serial `id`, in insertion order (parents first)
+const TABLES: &[&str] = &[
+    "auth",
+    "function",
+    "function_version",
+    "oidc_trust",
+    "build_arg",
+    "deploy_lock",
+    "egress_credential",
+    "schedule",
+    "event_source",
+    "api_token",
+    "domain",
+    "usage",
+    "audit_log",
+    "pending_deploy",
+    "deployment",
+];
+
+/// Every row of the control plane tables
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DbSnapshot {
+    pub users: Vec<auth::Model>,
+    pub functions: Vec<function::Model>,
+    pub versions: Vec<function_version::Model>,
+    pub trusts: Vec<oidc_trust::Model>,
+    /// Values stay encrypted
+    pub build_args: Vec<build_arg::Model>,
+    pub locks: Vec<deploy_lock::Model>,
+    /// Values stay encrypted
+    pub credentials: Vec<egress_credential::Model>,
+    pub schedules: Vec<schedule::Model>,
+    pub event_sources: Vec<event_source::Model>,
+    /// Only hashes of the tokens
+    pub tokens: Vec<api_token::Model>,
+    pub domains: Vec<domain::Model>,
+    pub usage: Vec<usage::Model>,
+    pub audit_log: Vec<audit_log::Model>,
+    /// Deploys waiting for, or given, approval
+    pub pending_deploys: Vec<pending_deploy::Model>,
+    /// Deploys run by the job runners
+    pub deployments: Vec<deployment::Model>,
+}
+
+pub struct BackupDBRepo;
+
+impl BackupDBRepo {
+    /// Reads every row of the control plane tables.
+    ///
+    /// The reads run in one read-only, repeatable-read transaction so the snapshot is
+    /// consistent even while deploys are happening.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    ///
+    /// # Returns
+    ///
+    /// * The snapshot, or an error of type `sea_orm::DbErr` if a read fails.
+    pub async fn snapshot(conn: &DbConn) -> Result<DbSnapshot, DbErr> {
+        let txn = conn
+            .begin_with_config(
+                Some(IsolationLevel::RepeatableRead),
+                Some(AccessMode::ReadOnly),
+            )
+            .await?;
+
+        let snapshot = DbSnapshot {
+            users: Auth::find()
+                .order_by_asc(auth::Column::Id)
+                .all(&txn)
+                .await?,
+            functions: Function::find()
+                .order_by_asc(function::Column::Id)
+                .all(&txn)
+                .await?,
+            versions: FunctionVersion::find()
+                .order_by_asc(function_version::Column::Id)
+                .all(&txn)
+                .await?,
+            trusts: OidcTrust::find()
+                .order_by_asc(oidc_trust::Column::Id)
+                .all(&txn)
+                .await?,
+            build_args: BuildArg::find()
+                .order_by_asc(build_arg::Column::Id)
+                .all(&txn)
+                .await?,
+            locks: DeployLock::find()
+                .order_by_asc(deploy_lock::Column::Id)
+                .all(&txn)
+                .await?,
+            credentials: EgressCredential::find()
+                .order_by_asc(egress_credential::Column::Id)
+                .all(&txn)
+                .await?,
+            schedules: Schedule::find()
+                .order_by_asc(schedule::Column::Id)
+                .all(&txn)
+                .await?,
+            event_sources: EventSource::find()
+                .order_by_asc(event_source::Column::Id)
+                .all(&txn)
+                .await?,
+            tokens: ApiToken::find()
+                .order_by_asc(api_token::Column::Id)
+                .all(&txn)
+                .await?,
+            domains: Domain::find()
+                .order_by_asc(domain::Column::Id)
+                .all(&txn)
+                .await?,
+            usage: Usage::find()
+                .order_by_asc(usage::Column::Id)
+                .all(&txn)
+                .await?,
+            audit_log: AuditLog::find()
+                .order_by_asc(audit_log::Column::Id)
+                .all(&txn)
+                .await?,
+            pending_deploys: PendingDeploy::find()
+                .order_by_asc(pending_deploy::Column::Id)
+                .all(&txn)
+                .await?,
+            deployments: Deployment::find()
+                .order_by_asc(deployment::Column::Id)
+                .all(&txn)
+                .await?,
+        };
+
+        txn.commit().await?;
+        Ok(snapshot)
+    }
+
+    /// Replaces every row of the control plane tables with the snapshot.
+    ///
+    /// Runs in a single transaction: on any error nothing is changed. Row ids are kept,
+    /// and the id sequences are moved past them so new rows don't collide.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `snapshot` - The rows to restore.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or an error of type `sea_orm::DbErr` if the restore fails.
+    pub async fn restore(conn: &DbConn, snapshot: DbSnapshot) -> Result<(), DbErr> {
+        let txn = conn.begin().await?;
+
+        // Children first; the foreign keys cascade anyway, but be explicit
+        Deployment::delete_many().exec(&txn).await?;
+        PendingDeploy::delete_many().exec(&txn).await?;
+        AuditLog::delete_many().exec(&txn).await?;
+        Usage::delete_many().exec(&txn).await?;
+        Domain::delete_many().exec(&txn).await?;
+        ApiToken::delete_many().exec(&txn).await?;
+        EventSource::delete_many().exec(&txn).await?;
+        Schedule::delete_many().exec(&txn).await?;
+        EgressCredential::delete_many().exec(&txn).await?;
+        DeployLock::delete_many().exec(&txn).await?;
+        BuildArg::delete_many().exec(&txn).await?;
+        OidcTrust::delete_many().exec(&txn).await?;
+        FunctionVersion::delete_many().exec(&txn).await?;
+        Function::delete_many().exec(&txn).await?;
+        Auth::delete_many().exec(&txn).await?;
+
+        for user in snapshot.users {
+            user.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for function in snapshot.functions {
+            function
+                .into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await?;
+        }
+        for version in snapshot.versions {
+            version.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for trust in snapshot.trusts {
+            trust.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for build_arg in snapshot.build_args {
+            build_arg
+                .into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await?;
+        }
+        for lock in snapshot.locks {
+            lock.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for credential in snapshot.credentials {
+            credential
+                .into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await?;
+        }
+        for schedule in snapshot.schedules {
+            schedule
+                .into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await?;
+        }
+        for source in snapshot.event_sources {
+            source.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for token in snapshot.tokens {
+            token.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for domain in snapshot.domains {
+            domain.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for usage in snapshot.usage {
+            usage.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for entry in snapshot.audit_log {
+            entry.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for deploy in snapshot.pending_deploys {
+            deploy.into_active_model().reset_all().insert(&txn).await?;
+        }
+        for deployment in snapshot.deployments {
+            deployment
+                .into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await?;
+        }
+
+        let backend = txn.get_database_backend();
+        for table in TABLES {
+            txn.execute(Statement::from_string(
+                backend,
+                format!(
+                    "SELECT setval(pg_get_serial_sequence('\"{table}\"', 'id'), \
+                     COALESCE((SELECT MAX(id) FROM \"{table}\"), 0) + 1, false)"
+                ),
+            ))
+            .await?;
+        }
+
+        txn.commit().await
+    }
+}
@@ -0,0 +1,95 @@
+use db_entities::build_arg::{ActiveModel as BuildArgModel, Column, Model};
+use db_entities::prelude::BuildArg;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder,
+};
+use std::time::SystemTime;
+
+pub struct BuildArgDBRepo;
+
+impl BuildArgDBRepo {
+    /// Sets a build arg of a user's namespace, replacing an earlier value of the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the build arg belongs to.
+    /// * `name` - The name of the build arg.
+    /// * `value` - The encrypted value.
+    ///
+    /// # Returns
+    ///
+    /// * The stored build arg, or an error of type `sea_orm::DbErr` if the write fails.
+    pub async fn set(
+        conn: &DbConn,
+        auth_id: i32,
+        name: String,
+        value: String,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let existing = BuildArg::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::Name.eq(name.as_str()))
+            .one(conn)
+            .await?;
+        match existing {
+            Some(existing) => {
+                let updated_at: DateTimeWithTimeZone =
+                    ChronoDateTimeUtc::from(SystemTime::now()).into();
+                let mut build_arg: BuildArgModel = existing.into();
+                build_arg.value = Set(value);
+                build_arg.updated_at = Set(updated_at);
+                build_arg.update(conn).await
+            }
+            None => {
+                BuildArgModel {
+                    auth_id: Set(auth_id),
+                    name: Set(name),
+                    value: Set(value),
+                    ..Default::default()
+                }
+                .insert(conn)
+                .await
+            }
+        }
+    }
+
+    /// Finds the build args of a user's namespace, by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose build args to list.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of build args, their values still encrypted
+    pub async fn find_by_user(conn: &DbConn, auth_id: i32) -> Result<Vec<Model>, sea_orm::DbErr> {
+        BuildArg::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .order_by_asc(Column::Name)
+            .all(conn)
+            .await
+    }
+
+    /// Removes one of a user's build args.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user the build arg belongs to.
+    /// * `name` - The build arg to remove.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the build arg existed, or an error of type `sea_orm::DbErr` if the delete
+    ///   fails.
+    pub async fn delete(conn: &DbConn, auth_id: i32, name: &str) -> Result<bool, sea_orm::DbErr> {
+        let result = BuildArg::delete_many()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::Name.eq(name))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
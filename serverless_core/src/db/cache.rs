@@ -1,22 +1,79 @@
-use redis::{aio::MultiplexedConnection, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
 use tracing::error;
+use uuid::Uuid;
 
+/// Prefix of the Redis keys holding the outcome of a function lookup
+const LOOKUP_PREFIX: &str = "function_lookup:";
+
+/// Stored for functions that were looked up and not found
+const MISSING_VALUE: &str = "missing";
+
+/// Stored for functions found without any recorded version
+const UNVERSIONED_VALUE: &str = "unversioned";
+
+/// Outcome of a cached function lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachedLookup {
+    /// The function exists, with its latest version
+    Found(Option<i32>),
+    /// The function didn't exist when it was looked up
+    Missing,
+}
+
+impl CachedLookup {
+    fn to_value(self) -> String {
+        match self {
+            Self::Found(Some(version)) => version.to_string(),
+            Self::Found(None) => UNVERSIONED_VALUE.to_string(),
+            Self::Missing => MISSING_VALUE.to_string(),
+        }
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            MISSING_VALUE => Some(Self::Missing),
+            UNVERSIONED_VALUE => Some(Self::Found(None)),
+            version => version
+                .parse()
+                .ok()
+                .map(|version| Self::Found(Some(version))),
+        }
+    }
+}
+
+/// Cached outcomes of function lookups on the invocation path.
+///
+/// Entries are keyed by namespace and function name, since names are only unique within a
+/// namespace, and carry the function's latest version so invocations don't look it up
+/// either.
 pub struct FunctionCacheRepo;
 
 impl FunctionCacheRepo {
-    /// Retrieves the cached function address by its name.
+    fn key(namespace: Uuid, name: &str) -> String {
+        format!("{LOOKUP_PREFIX}{namespace}:{name}")
+    }
+
+    /// Retrieves the cached lookup of a function.
     ///
     /// # Arguments
     ///
     /// * `conn` - A mutable reference to the Redis connection.
-    /// * `name` - The key representing the function.
+    /// * `namespace` - The namespace the function belongs to.
+    /// * `name` - The name of the function.
     ///
     /// # Returns
     ///
-    /// * `Some(String)` containing the cached address if found, or `None` if not found or an error occurs.
-    pub async fn get_function(conn: &mut MultiplexedConnection, name: &str) -> Option<()> {
-        match conn.exists::<&str, usize>(name).await {
-            Ok(_) => Some(()),
+    /// * The cached lookup, or `None` if it isn't cached or an error occurs.
+    pub async fn get_function(
+        conn: &mut MultiplexedConnection,
+        namespace: Uuid,
+        name: &str,
+    ) -> Option<CachedLookup> {
+        match conn
+            .get::<String, Option<String>>(Self::key(namespace, name))
+            .await
+        {
+            Ok(value) => value.as_deref().and_then(CachedLookup::from_value),
             Err(e) => {
                 error!("Failed to retrieve function '{}' from cache: {}", name, e);
                 None
@@ -24,14 +81,15 @@ impl FunctionCacheRepo {
         }
     }
 
-    /// Adds a function address to the cache with a specified time-to-live (TTL).
-    ///
-    /// The entry is only added if it does not already exist.
+    /// Caches the lookup of a function with a specified time-to-live (TTL), replacing
+    /// any previous one.
     ///
     /// # Arguments
     ///
     /// * `conn` - A mutable reference to the Redis connection.
-    /// * `name` - The key representing the function.
+    /// * `namespace` - The namespace the function belongs to.
+    /// * `name` - The name of the function.
+    /// * `lookup` - What the lookup found.
     /// * `ttl` - Time-to-live in seconds.
     ///
     /// # Returns
@@ -39,16 +97,66 @@ impl FunctionCacheRepo {
     /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
     pub async fn add_function(
         conn: &mut MultiplexedConnection,
+        namespace: Uuid,
         name: &str,
+        lookup: CachedLookup,
         ttl: u64,
     ) -> redis::RedisResult<()> {
-        let opts = SetOptions::default()
-            .conditional_set(ExistenceCheck::NX)
-            .get(true)
-            .with_expiration(SetExpiry::EX(ttl));
-        conn.set_options(name, 1, opts).await.map_err(|e| {
-            error!("Failed to add function '{}' to cache: {}", name, e);
-            e
-        })
+        conn.set_ex(Self::key(namespace, name), lookup.to_value(), ttl)
+            .await
+            .map_err(|e| {
+                error!("Failed to add function '{}' to cache: {}", name, e);
+                e
+            })
+    }
+
+    /// Removes a function from the cache, so the next invocation checks the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The namespace the function belongs to.
+    /// * `name` - The name of the function.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn remove_function(
+        conn: &mut MultiplexedConnection,
+        namespace: Uuid,
+        name: &str,
+    ) -> redis::RedisResult<()> {
+        conn.del::<String, ()>(Self::key(namespace, name))
+            .await
+            .map_err(|e| {
+                error!("Failed to remove function '{}' from cache: {}", name, e);
+                e
+            })
+    }
+
+    /// Removes every cached lookup, e.g. after a restore replaced the functions.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn clear(conn: &mut MultiplexedConnection) -> redis::RedisResult<()> {
+        let keys: Vec<String> = {
+            let mut keys = Vec::new();
+            let mut iter = conn
+                .scan_match::<String, String>(format!("{LOOKUP_PREFIX}*"))
+                .await?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if keys.is_empty() {
+            return Ok(());
+        }
+        conn.del::<Vec<String>, ()>(keys).await
     }
 }
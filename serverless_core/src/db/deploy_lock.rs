@@ -0,0 +1,119 @@
+use db_entities::deploy_lock::{ActiveModel as DeployLockModel, Column, Model};
+use db_entities::prelude::DeployLock;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, DbConn, EntityTrait, QueryFilter,
+    QueryOrder,
+};
+
+pub struct DeployLockDBRepo;
+
+impl DeployLockDBRepo {
+    /// Locks a function, or a whole namespace, against deploys. Locking again replaces
+    /// the reason and who locked it.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace is locked.
+    /// * `function_name` - The function to lock, or `None` for every function.
+    /// * `reason` - Why deploys are locked, shown to whoever tries to deploy.
+    /// * `locked_by` - Who locked it, e.g. the user's email.
+    ///
+    /// # Returns
+    ///
+    /// * The lock, or an error of type `sea_orm::DbErr` if storing it fails.
+    pub async fn lock(
+        conn: &DbConn,
+        auth_id: i32,
+        function_name: Option<String>,
+        reason: Option<String>,
+        locked_by: String,
+    ) -> Result<Model, sea_orm::DbErr> {
+        Self::unlock(conn, auth_id, function_name.as_deref()).await?;
+        DeployLockModel {
+            auth_id: Set(auth_id),
+            function_name: Set(function_name),
+            reason: Set(reason),
+            locked_by: Set(locked_by),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+
+    /// Removes the lock of a function, or of a whole namespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace is unlocked.
+    /// * `function_name` - The function to unlock, or `None` for the namespace lock; the
+    ///   namespace lock doesn't lift the locks of single functions.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if there was a lock, or an error of type `sea_orm::DbErr` if the delete fails.
+    pub async fn unlock(
+        conn: &DbConn,
+        auth_id: i32,
+        function_name: Option<&str>,
+    ) -> Result<bool, sea_orm::DbErr> {
+        let name_filter = match function_name {
+            Some(name) => Column::FunctionName.eq(name),
+            None => Column::FunctionName.is_null(),
+        };
+        let result = DeployLock::delete_many()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(name_filter)
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Finds the lock refusing deploys of a function: its own, or else the namespace's.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the function is in.
+    /// * `function_name` - The function to deploy.
+    ///
+    /// # Returns
+    ///
+    /// * The lock, if deploys of the function are locked.
+    pub async fn find_blocking(
+        conn: &DbConn,
+        auth_id: i32,
+        function_name: &str,
+    ) -> Result<Option<Model>, sea_orm::DbErr> {
+        // Namespace locks sort last, so the function's own reason is the one shown
+        DeployLock::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(
+                Condition::any()
+                    .add(Column::FunctionName.eq(function_name))
+                    .add(Column::FunctionName.is_null()),
+            )
+            .order_by_asc(Column::FunctionName)
+            .one(conn)
+            .await
+    }
+
+    /// Finds the locks of a user's namespace, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose locks to list.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of locks
+    pub async fn find_by_user(conn: &DbConn, auth_id: i32) -> Result<Vec<Model>, sea_orm::DbErr> {
+        DeployLock::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .order_by_asc(Column::Id)
+            .all(conn)
+            .await
+    }
+}
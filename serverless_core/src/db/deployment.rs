@@ -0,0 +1,114 @@
+use crate::db::models::DeploymentStatus;
+use db_entities::deployment::{ActiveModel as DeploymentModel, Column, Model};
+use db_entities::prelude::Deployment;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone, Expr};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter};
+use std::time::SystemTime;
+
+pub struct DeploymentDBRepo;
+
+impl DeploymentDBRepo {
+    /// Stores a deploy to be run by a job runner.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the function is deployed into.
+    /// * `function_name` - The name the function is deployed under.
+    /// * `preview_of` - The function previewed, when the deploy is a preview instance.
+    /// * `archive` - The function's archive, kept until the deploy finishes.
+    ///
+    /// # Returns
+    ///
+    /// * The queued deploy, or an error of type `sea_orm::DbErr` if storing it fails.
+    pub async fn create(
+        conn: &DbConn,
+        auth_id: i32,
+        function_name: String,
+        preview_of: Option<String>,
+        archive: Vec<u8>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        DeploymentModel {
+            auth_id: Set(auth_id),
+            function_name: Set(function_name),
+            preview_of: Set(preview_of),
+            archive: Set(archive),
+            status: Set(DeploymentStatus::Queued.as_str().to_string()),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+
+    /// Finds a deploy, queued or finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `id` - The deploy to find.
+    ///
+    /// # Returns
+    ///
+    /// * The deploy, if it exists.
+    pub async fn find_by_id(conn: &DbConn, id: i32) -> Result<Option<Model>, sea_orm::DbErr> {
+        Deployment::find_by_id(id).one(conn).await
+    }
+
+    /// Marks a deploy as running, unless it finished already. A deploy whose runner
+    /// died while running it is started again.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `id` - The deploy to start.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the deploy hadn't finished, or an error of type `sea_orm::DbErr` if
+    ///   the update fails.
+    pub async fn start(conn: &DbConn, id: i32) -> Result<bool, sea_orm::DbErr> {
+        let result = Deployment::update_many()
+            .col_expr(
+                Column::Status,
+                Expr::value(DeploymentStatus::Running.as_str()),
+            )
+            .filter(Column::Id.eq(id))
+            .filter(Column::Status.is_in([
+                DeploymentStatus::Queued.as_str(),
+                DeploymentStatus::Running.as_str(),
+            ]))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Records how a deploy ended, dropping its archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `id` - The deploy that finished.
+    /// * `status` - Whether it succeeded or failed.
+    /// * `message` - The output of the deploy, or why it failed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn finish(
+        conn: &DbConn,
+        id: i32,
+        status: DeploymentStatus,
+        message: String,
+    ) -> Result<(), sea_orm::DbErr> {
+        let finished_at: DateTimeWithTimeZone = ChronoDateTimeUtc::from(SystemTime::now()).into();
+        Deployment::update_many()
+            .col_expr(Column::Status, Expr::value(status.as_str()))
+            .col_expr(Column::Message, Expr::value(message))
+            .col_expr(Column::FinishedAt, Expr::value(finished_at))
+            .col_expr(Column::Archive, Expr::value(Vec::<u8>::new()))
+            .filter(Column::Id.eq(id))
+            .exec(conn)
+            .await?;
+        Ok(())
+    }
+}
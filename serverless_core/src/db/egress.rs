@@ -0,0 +1,73 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+/// Prefix of the Redis keys holding the egress allowlist of a function
+const ALLOWLIST_PREFIX: &str = "egress:allow:";
+
+/// Egress allowlists as read by the proxies, mirrored from the function records
+pub struct EgressAllowlistRepo;
+
+impl EgressAllowlistRepo {
+    /// Gets the allowlist of a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    ///
+    /// # Returns
+    ///
+    /// * The allowlist as JSON, `None` if the function has none.
+    pub async fn get(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+    ) -> redis::RedisResult<Option<String>> {
+        conn.get(format!("{ALLOWLIST_PREFIX}{function_key}")).await
+    }
+
+    /// Replaces the allowlist of a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    /// * `allowlist` - The allowlist as JSON, or `None` to remove it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn set(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        allowlist: Option<&str>,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{ALLOWLIST_PREFIX}{function_key}");
+        match allowlist {
+            Some(allowlist) => conn.set(key, allowlist).await,
+            None => conn.del(key).await,
+        }
+    }
+
+    /// Lists the functions with an allowlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    ///
+    /// # Returns
+    ///
+    /// * The keys of the functions, or a `redis::RedisError` if the operation fails.
+    pub async fn function_keys(
+        conn: &mut MultiplexedConnection,
+    ) -> redis::RedisResult<Vec<String>> {
+        let mut function_keys = Vec::new();
+        let mut iter = conn
+            .scan_match::<String, String>(format!("{ALLOWLIST_PREFIX}*"))
+            .await?;
+        while let Some(key) = iter.next_item().await {
+            if let Some(function_key) = key.strip_prefix(ALLOWLIST_PREFIX) {
+                function_keys.push(function_key.to_string());
+            }
+        }
+        Ok(function_keys)
+    }
+}
@@ -0,0 +1,122 @@
+use db_entities::egress_credential::{ActiveModel as EgressCredentialModel, Column, Model};
+use db_entities::prelude::EgressCredential;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder,
+};
+use std::time::SystemTime;
+
+pub struct EgressCredentialDBRepo;
+
+impl EgressCredentialDBRepo {
+    /// Sets a credential of a user's namespace, replacing an earlier one of the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the credential belongs to.
+    /// * `name` - The name functions refer to the credential by.
+    /// * `base_url` - The URL requests signed with the credential are sent under.
+    /// * `header` - The header the credential is sent in.
+    /// * `value` - The encrypted header value.
+    ///
+    /// # Returns
+    ///
+    /// * The stored credential, or an error of type `sea_orm::DbErr` if the write fails.
+    pub async fn set(
+        conn: &DbConn,
+        auth_id: i32,
+        name: String,
+        base_url: String,
+        header: String,
+        value: String,
+    ) -> Result<Model, sea_orm::DbErr> {
+        match Self::find(conn, auth_id, &name).await? {
+            Some(existing) => {
+                let updated_at: DateTimeWithTimeZone =
+                    ChronoDateTimeUtc::from(SystemTime::now()).into();
+                let mut credential: EgressCredentialModel = existing.into();
+                credential.base_url = Set(base_url);
+                credential.header = Set(header);
+                credential.value = Set(value);
+                credential.updated_at = Set(updated_at);
+                credential.update(conn).await
+            }
+            None => {
+                EgressCredentialModel {
+                    auth_id: Set(auth_id),
+                    name: Set(name),
+                    base_url: Set(base_url),
+                    header: Set(header),
+                    value: Set(value),
+                    ..Default::default()
+                }
+                .insert(conn)
+                .await
+            }
+        }
+    }
+
+    /// Finds one of a user's credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user the credential belongs to.
+    /// * `name` - The credential to find.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Model)` with the value still encrypted if the credential exists; otherwise,
+    ///   `None`.
+    pub async fn find(
+        conn: &DbConn,
+        auth_id: i32,
+        name: &str,
+    ) -> Result<Option<Model>, sea_orm::DbErr> {
+        EgressCredential::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::Name.eq(name))
+            .one(conn)
+            .await
+    }
+
+    /// Finds the credentials of a user's namespace, by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose credentials to list.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of credentials, their values still encrypted
+    pub async fn find_by_user(conn: &DbConn, auth_id: i32) -> Result<Vec<Model>, sea_orm::DbErr> {
+        EgressCredential::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .order_by_asc(Column::Name)
+            .all(conn)
+            .await
+    }
+
+    /// Removes one of a user's credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user the credential belongs to.
+    /// * `name` - The credential to remove.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the credential existed, or an error of type `sea_orm::DbErr` if the
+    ///   delete fails.
+    pub async fn delete(conn: &DbConn, auth_id: i32, name: &str) -> Result<bool, sea_orm::DbErr> {
+        let result = EgressCredential::delete_many()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::Name.eq(name))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
@@ -0,0 +1,52 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+/// Redis hash holding the scaling freeze windows, by id
+const FREEZES_KEY: &str = "autoscaler:freezes";
+
+/// Windows during which the autoscaler keeps idle containers, stored as JSON
+pub struct FreezeRepo;
+
+impl FreezeRepo {
+    /// Lists the declared freeze windows.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    ///
+    /// # Returns
+    ///
+    /// * The windows as JSON, in no particular order, or a `redis::RedisError`.
+    pub async fn list(conn: &mut MultiplexedConnection) -> redis::RedisResult<Vec<String>> {
+        conn.hvals(FREEZES_KEY).await
+    }
+
+    /// Stores a freeze window, replacing one with the same id.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `id` - The id of the window.
+    /// * `window` - The window as JSON.
+    pub async fn store(
+        conn: &mut MultiplexedConnection,
+        id: &str,
+        window: &str,
+    ) -> redis::RedisResult<()> {
+        conn.hset(FREEZES_KEY, id, window).await
+    }
+
+    /// Removes a freeze window.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `id` - The id of the window.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the window was declared, or a `redis::RedisError`.
+    pub async fn remove(conn: &mut MultiplexedConnection, id: &str) -> redis::RedisResult<bool> {
+        let removed: u64 = conn.hdel(FREEZES_KEY, id).await?;
+        Ok(removed > 0)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::utils::utils::generate_hash;
 use db_entities::prelude::Function;
 use db_entities::{
     auth::Column as AuthColumn,
@@ -5,13 +6,17 @@ use db_entities::{
     prelude::Auth as AuthEntity,
 };
 use db_migrations::Condition;
-use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbConn, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
 use uuid::Uuid;
 
 pub struct FunctionDBRepo;
 
 impl FunctionDBRepo {
-    /// Finds a function by its name in the database.
+    /// Finds a function by its name in the database. Trashed functions are ignored.
     ///
     /// # Arguments
     ///
@@ -25,6 +30,34 @@ impl FunctionDBRepo {
         conn: &DbConn,
         name: &str,
         user_uuid: Uuid,
+    ) -> Option<Model> {
+        Function::find()
+            .filter(
+                Condition::all()
+                    .add(Column::Name.eq(name))
+                    .add(Column::Uuid.eq(user_uuid))
+                    .add(Column::DeletedAt.is_null()),
+            )
+            .one(conn)
+            .await
+            .ok()?
+    }
+
+    /// Finds a function by its name, whether or not it is in the trash.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `name` - The name of the function to find.
+    /// * `user_uuid` - The UUID of the user who owns the function.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Model)` if the function exists; otherwise, `None`.
+    pub async fn find_function_including_trashed(
+        conn: &DbConn,
+        name: &str,
+        user_uuid: Uuid,
     ) -> Option<Model> {
         Function::find()
             .filter(
@@ -37,7 +70,37 @@ impl FunctionDBRepo {
             .ok()?
     }
 
-    /// Finds functions by user's UUID in the database.
+    /// Finds a function by its function key (`{name}-{namespace hash}`), e.g. the key a
+    /// container's internal API token was issued to. Trashed functions are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_key` - The key of the function to find.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Model)` if the function exists; otherwise, `None`.
+    pub async fn find_by_function_key(
+        conn: &DbConn,
+        function_key: &str,
+    ) -> Result<Option<Model>, sea_orm::DbErr> {
+        let Some((name, hash)) = function_key.rsplit_once('-') else {
+            return Ok(None);
+        };
+        // The key only holds a hash of the namespace, so match it against every namespace
+        // with a function of that name
+        let functions = Function::find()
+            .filter(Column::Name.eq(name))
+            .filter(Column::DeletedAt.is_null())
+            .all(conn)
+            .await?;
+        Ok(functions
+            .into_iter()
+            .find(|function| generate_hash(function.uuid) == hash))
+    }
+
+    /// Finds functions by user's UUID in the database, leaving out trashed ones.
     ///
     /// # Arguments
     ///
@@ -66,6 +129,49 @@ impl FunctionDBRepo {
         // Find all functions for this user
         Function::find()
             .filter(Column::AuthId.eq(user.id))
+            .filter(Column::DeletedAt.is_null())
+            .all(conn)
+            .await
+    }
+
+    /// Finds the functions a user has moved to the trash.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `user_uuid` - The UUID of the user.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of trashed functions, most recently deleted first
+    pub async fn find_trashed_functions(
+        conn: &DbConn,
+        user_uuid: Uuid,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::Uuid.eq(user_uuid))
+            .filter(Column::DeletedAt.is_not_null())
+            .order_by_desc(Column::DeletedAt)
+            .all(conn)
+            .await
+    }
+
+    /// Finds functions that have been in the trash since before `cutoff`, across all users.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `cutoff` - Functions trashed before this time are returned.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of functions due to be purged
+    pub async fn find_trashed_before(
+        conn: &DbConn,
+        cutoff: DateTimeWithTimeZone,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::DeletedAt.lt(cutoff))
             .all(conn)
             .await
     }
@@ -81,8 +187,8 @@ impl FunctionDBRepo {
     /// # Returns
     ///
     /// * `Ok(())` on success, or an error of type `sea_orm::DbErr` if insertion fails.
-    pub async fn create_function_for_user(
-        conn: &DbConn,
+    pub async fn create_function_for_user<C: ConnectionTrait>(
+        conn: &C,
         function: Model,
         user_uuid: Uuid,
     ) -> Result<Model, sea_orm::DbErr> {
@@ -99,10 +205,349 @@ impl FunctionDBRepo {
             name: Set(function.name),
             runtime: Set(function.runtime),
             uuid: Set(user_uuid),
+            settings: Set(function.settings),
             ..Default::default()
         };
 
         // Insert and return the created function
         function_model.insert(conn).await
     }
+
+    /// Replaces the stored settings of a function (e.g. on redeploy).
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `settings` - The new settings, as JSON.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn update_function_settings<C: ConnectionTrait>(
+        conn: &C,
+        function: Model,
+        settings: Option<serde_json::Value>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.settings = Set(settings);
+        // Redeploying a trashed function brings it back
+        function_model.deleted_at = Set(None);
+        function_model.update(conn).await
+    }
+
+    /// Stores the README and OpenAPI document a function was deployed with.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `readme` - The README, or `None` if the bundle had none.
+    /// * `openapi` - The OpenAPI document, or `None` if the bundle had none.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn update_function_docs<C: ConnectionTrait>(
+        conn: &C,
+        function: Model,
+        readme: Option<String>,
+        openapi: Option<String>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.readme = Set(readme);
+        function_model.openapi = Set(openapi);
+        function_model.update(conn).await
+    }
+
+    /// Replaces the dependency inventory of a function with that of its latest deploy.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `dependencies` - The inventory, as JSON, or `None` if the bundle had no manifest.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn update_function_dependencies<C: ConnectionTrait>(
+        conn: &C,
+        function: Model,
+        dependencies: Option<serde_json::Value>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.dependencies = Set(dependencies);
+        function_model.update(conn).await
+    }
+
+    /// Replaces the destinations a function may reach through the egress proxy.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `allowlist` - The allowlist entries, as JSON, or `None` to allow nothing.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn update_egress_allowlist(
+        conn: &DbConn,
+        function: Model,
+        allowlist: Option<serde_json::Value>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.egress_allowlist = Set(allowlist);
+        function_model.update(conn).await
+    }
+
+    /// Replaces the rules routing matching invocations of a function to its versions.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `rules` - The rules, as JSON, or `None` to route every invocation to the current
+    ///   version.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn update_routing_rules(
+        conn: &DbConn,
+        function: Model,
+        rules: Option<serde_json::Value>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.routing_rules = Set(rules);
+        function_model.update(conn).await
+    }
+
+    /// Assigns the port a TCP or UDP service function is published on, or releases it.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `port` - The port, or `None` to release the function's port.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails,
+    ///   e.g. because another function took the port first.
+    pub async fn set_service_port(
+        conn: &DbConn,
+        function: Model,
+        port: Option<i32>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.service_port = Set(port);
+        function_model.update(conn).await
+    }
+
+    /// Finds the ports assigned to service functions, across all users, trashed ones
+    /// included so they get their port back when restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of assigned ports
+    pub async fn find_service_ports(conn: &DbConn) -> Result<Vec<i32>, sea_orm::DbErr> {
+        Function::find()
+            .select_only()
+            .column(Column::ServicePort)
+            .filter(Column::ServicePort.is_not_null())
+            .into_tuple::<i32>()
+            .all(conn)
+            .await
+    }
+
+    /// Marks a function as a preview instance of another function, or clears the mark.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `preview_of` - The function being previewed.
+    /// * `expires_at` - When the preview instance is removed.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn set_preview<C: ConnectionTrait>(
+        conn: &C,
+        function: Model,
+        preview_of: Option<String>,
+        expires_at: Option<DateTimeWithTimeZone>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.preview_of = Set(preview_of);
+        function_model.expires_at = Set(expires_at);
+        function_model.update(conn).await
+    }
+
+    /// Finds the preview instances of a user's functions.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `user_uuid` - The UUID of the user.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of preview instances, soonest to expire first
+    pub async fn find_previews(
+        conn: &DbConn,
+        user_uuid: Uuid,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::Uuid.eq(user_uuid))
+            .filter(Column::PreviewOf.is_not_null())
+            .filter(Column::DeletedAt.is_null())
+            .order_by_asc(Column::ExpiresAt)
+            .all(conn)
+            .await
+    }
+
+    /// Finds the namespaces that have functions, trashed ones aside.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of namespace UUIDs, each once
+    pub async fn find_namespaces(conn: &DbConn) -> Result<Vec<Uuid>, sea_orm::DbErr> {
+        Function::find()
+            .select_only()
+            .column(Column::Uuid)
+            .distinct()
+            .filter(Column::DeletedAt.is_null())
+            .into_tuple::<Uuid>()
+            .all(conn)
+            .await
+    }
+
+    /// Finds the functions with an egress allowlist, across all users.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of functions with an egress allowlist, trashed ones excluded
+    pub async fn find_with_egress_allowlist(conn: &DbConn) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::EgressAllowlist.is_not_null())
+            .filter(Column::DeletedAt.is_null())
+            .all(conn)
+            .await
+    }
+
+    /// Finds the functions with settings, across all users.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of functions with settings, trashed ones excluded
+    pub async fn find_with_settings(conn: &DbConn) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::Settings.is_not_null())
+            .filter(Column::DeletedAt.is_null())
+            .all(conn)
+            .await
+    }
+
+    /// Finds the functions with a dependency inventory, of one namespace or across all.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `user_uuid` - The namespace to look in, or `None` for every namespace.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of functions with an inventory, trashed ones excluded
+    pub async fn find_with_dependencies(
+        conn: &DbConn,
+        user_uuid: Option<Uuid>,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        let mut query = Function::find()
+            .filter(Column::Dependencies.is_not_null())
+            .filter(Column::DeletedAt.is_null());
+        if let Some(user_uuid) = user_uuid {
+            query = query.filter(Column::Uuid.eq(user_uuid));
+        }
+        query
+            .order_by_asc(Column::Uuid)
+            .order_by_asc(Column::Name)
+            .all(conn)
+            .await
+    }
+
+    /// Finds preview instances that expired before `now`, across all users, trashed or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of expired preview instances
+    pub async fn find_expired_previews(
+        conn: &DbConn,
+        now: DateTimeWithTimeZone,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        Function::find()
+            .filter(Column::PreviewOf.is_not_null())
+            .filter(Column::ExpiresAt.lt(now))
+            .all(conn)
+            .await
+    }
+
+    /// Moves a function to the trash, or back out of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to update.
+    /// * `deleted_at` - When the function was trashed, or `None` to restore it.
+    ///
+    /// # Returns
+    ///
+    /// * The updated function, or an error of type `sea_orm::DbErr` if the update fails.
+    pub async fn set_deleted_at(
+        conn: &DbConn,
+        function: Model,
+        deleted_at: Option<DateTimeWithTimeZone>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut function_model: FunctionModel = function.into();
+        function_model.deleted_at = Set(deleted_at);
+        function_model.update(conn).await
+    }
+
+    /// Permanently deletes a function; its versions go with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function` - The function to delete.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or an error of type `sea_orm::DbErr` if the delete fails.
+    pub async fn delete_function(conn: &DbConn, function: Model) -> Result<(), sea_orm::DbErr> {
+        Function::delete_by_id(function.id).exec(conn).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,200 @@
+use crate::db::models::VersionSize;
+use db_entities::function_version::{ActiveModel as FunctionVersionModel, Column, Model};
+use db_entities::prelude::FunctionVersion;
+use sea_orm::prelude::{DateTimeWithTimeZone, Expr};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbConn, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+
+pub struct FunctionVersionDBRepo;
+
+impl FunctionVersionDBRepo {
+    /// Records a deployed archive as the function's next version.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_id` - The function the archive was deployed to.
+    /// * `archive` - The zipped function source, as uploaded.
+    /// * `settings` - The settings the archive was deployed with, as JSON.
+    /// * `created_at` - When the version was created; `None` means now. Imports pass the
+    ///   original deploy time.
+    /// * `test_results` - Results of the tests run while building it, as JSON, if any.
+    ///
+    /// # Returns
+    ///
+    /// * The recorded version, or an error of type `sea_orm::DbErr` if insertion fails.
+    pub async fn record<C: ConnectionTrait>(
+        conn: &C,
+        function_id: i32,
+        archive: Vec<u8>,
+        settings: Option<serde_json::Value>,
+        created_at: Option<DateTimeWithTimeZone>,
+        test_results: Option<serde_json::Value>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut version = Self::next_version(conn, function_id, archive, settings).await?;
+        if let Some(created_at) = created_at {
+            version.created_at = Set(created_at);
+        }
+        version.test_results = Set(test_results);
+
+        version.insert(conn).await
+    }
+
+    /// Records an archive promoted from another namespace as the function's next
+    /// version.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_id` - The function the archive was promoted to.
+    /// * `archive` - The zipped function source, with the promoted environment.
+    /// * `settings` - The settings the archive was promoted with, as JSON.
+    /// * `promoted_from` - The namespace and version it was promoted from, as JSON.
+    ///
+    /// # Returns
+    ///
+    /// * The recorded version, or an error of type `sea_orm::DbErr` if insertion fails.
+    pub async fn record_promoted<C: ConnectionTrait>(
+        conn: &C,
+        function_id: i32,
+        archive: Vec<u8>,
+        settings: Option<serde_json::Value>,
+        promoted_from: serde_json::Value,
+    ) -> Result<Model, sea_orm::DbErr> {
+        let mut version = Self::next_version(conn, function_id, archive, settings).await?;
+        version.promoted_from = Set(Some(promoted_from));
+
+        version.insert(conn).await
+    }
+
+    async fn next_version<C: ConnectionTrait>(
+        conn: &C,
+        function_id: i32,
+        archive: Vec<u8>,
+        settings: Option<serde_json::Value>,
+    ) -> Result<FunctionVersionModel, sea_orm::DbErr> {
+        let latest = Self::latest_version(conn, function_id).await?;
+
+        Ok(FunctionVersionModel {
+            function_id: Set(function_id),
+            version: Set(latest.map_or(1, |latest| latest + 1)),
+            archive: Set(archive),
+            settings: Set(settings),
+            ..Default::default()
+        })
+    }
+
+    /// Finds the number of a function's latest recorded version.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_id` - The function whose versions to look at.
+    ///
+    /// # Returns
+    ///
+    /// * The latest version, or `None` if none was recorded yet
+    pub async fn latest_version<C: ConnectionTrait>(
+        conn: &C,
+        function_id: i32,
+    ) -> Result<Option<i32>, sea_orm::DbErr> {
+        let latest = FunctionVersion::find()
+            .filter(Column::FunctionId.eq(function_id))
+            .order_by_desc(Column::Version)
+            .one(conn)
+            .await?;
+        Ok(latest.map(|latest| latest.version))
+    }
+
+    /// Finds one recorded version of a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_id` - The function the version belongs to.
+    /// * `version` - The version number.
+    ///
+    /// # Returns
+    ///
+    /// * The version, including its archive, or `None` if it wasn't recorded
+    pub async fn find_version(
+        conn: &DbConn,
+        function_id: i32,
+        version: i32,
+    ) -> Result<Option<Model>, sea_orm::DbErr> {
+        FunctionVersion::find()
+            .filter(Column::FunctionId.eq(function_id))
+            .filter(Column::Version.eq(version))
+            .one(conn)
+            .await
+    }
+
+    /// Finds every recorded version of a function, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_id` - The function whose versions to list.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of the function's versions, including their archives
+    pub async fn find_by_function(
+        conn: &DbConn,
+        function_id: i32,
+    ) -> Result<Vec<Model>, sea_orm::DbErr> {
+        FunctionVersion::find()
+            .filter(Column::FunctionId.eq(function_id))
+            .order_by_asc(Column::Version)
+            .all(conn)
+            .await
+    }
+
+    /// Finds the archive size of every recorded version of some functions, without
+    /// loading the archives.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `function_ids` - The functions whose versions to measure.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of version sizes, oldest recorded first
+    pub async fn find_sizes(
+        conn: &DbConn,
+        function_ids: Vec<i32>,
+    ) -> Result<Vec<VersionSize>, sea_orm::DbErr> {
+        FunctionVersion::find()
+            .select_only()
+            .column(Column::Id)
+            .column(Column::FunctionId)
+            .column(Column::Version)
+            .column_as(Expr::cust("CAST(octet_length(archive) AS BIGINT)"), "size")
+            .filter(Column::FunctionId.is_in(function_ids))
+            .order_by_asc(Column::Id)
+            .into_model::<VersionSize>()
+            .all(conn)
+            .await
+    }
+
+    /// Deletes recorded versions, archives included.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `ids` - The ids of the versions to delete.
+    ///
+    /// # Returns
+    ///
+    /// * How many versions were deleted
+    pub async fn delete_versions(conn: &DbConn, ids: Vec<i32>) -> Result<u64, sea_orm::DbErr> {
+        let result = FunctionVersion::delete_many()
+            .filter(Column::Id.is_in(ids))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
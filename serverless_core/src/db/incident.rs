@@ -0,0 +1,52 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+/// Redis hash holding the ongoing incidents, by id
+const INCIDENTS_KEY: &str = "status:incidents";
+
+/// Incidents flagged by operators on the status page, stored as JSON
+pub struct IncidentRepo;
+
+impl IncidentRepo {
+    /// Lists the ongoing incidents.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    ///
+    /// # Returns
+    ///
+    /// * The incidents as JSON, in no particular order, or a `redis::RedisError`.
+    pub async fn list(conn: &mut MultiplexedConnection) -> redis::RedisResult<Vec<String>> {
+        conn.hvals(INCIDENTS_KEY).await
+    }
+
+    /// Stores an incident, replacing one with the same id.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `id` - The id of the incident.
+    /// * `incident` - The incident as JSON.
+    pub async fn store(
+        conn: &mut MultiplexedConnection,
+        id: &str,
+        incident: &str,
+    ) -> redis::RedisResult<()> {
+        conn.hset(INCIDENTS_KEY, id, incident).await
+    }
+
+    /// Removes a resolved incident.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `id` - The id of the incident.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the incident was ongoing, or a `redis::RedisError`.
+    pub async fn remove(conn: &mut MultiplexedConnection, id: &str) -> redis::RedisResult<bool> {
+        let removed: u64 = conn.hdel(INCIDENTS_KEY, id).await?;
+        Ok(removed > 0)
+    }
+}
@@ -0,0 +1,112 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+/// Prefix of the Redis keys holding a recorded invocation
+const INVOCATION_PREFIX: &str = "invocation:";
+
+/// Prefix of the Redis lists of the invocations recorded for a function, newest first
+const FUNCTION_INVOCATIONS_PREFIX: &str = "invocations:";
+
+/// Requests recorded for functions that opted in, kept so they can be replayed
+pub struct RecordedInvocationRepo;
+
+impl RecordedInvocationRepo {
+    /// Stores a recorded invocation and lists it first among the function's invocations.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The hash of the namespace the function belongs to.
+    /// * `function_key` - The key of the function.
+    /// * `id` - The invocation's request id.
+    /// * `invocation` - The recorded invocation as JSON.
+    /// * `max_kept` - How many invocations the function's list keeps.
+    /// * `ttl_secs` - How long the invocation and the list are kept.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn store(
+        conn: &mut MultiplexedConnection,
+        namespace: &str,
+        function_key: &str,
+        id: &str,
+        invocation: &str,
+        max_kept: usize,
+        ttl_secs: u64,
+    ) -> redis::RedisResult<()> {
+        let list = format!("{FUNCTION_INVOCATIONS_PREFIX}{function_key}");
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                format!("{INVOCATION_PREFIX}{namespace}:{id}"),
+                invocation,
+                ttl_secs,
+            )
+            .ignore()
+            .lrem(&list, 0, id)
+            .ignore()
+            .lpush(&list, id)
+            .ignore()
+            .ltrim(&list, 0, max_kept as isize - 1)
+            .ignore()
+            .expire(&list, ttl_secs as i64)
+            .ignore()
+            .query_async(conn)
+            .await
+    }
+
+    /// Gets a recorded invocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The hash of the namespace the invocation was recorded in.
+    /// * `id` - The invocation's request id.
+    ///
+    /// # Returns
+    ///
+    /// * The invocation as JSON, `None` if it was never recorded or has expired.
+    pub async fn get(
+        conn: &mut MultiplexedConnection,
+        namespace: &str,
+        id: &str,
+    ) -> redis::RedisResult<Option<String>> {
+        conn.get(format!("{INVOCATION_PREFIX}{namespace}:{id}"))
+            .await
+    }
+
+    /// Lists the invocations recorded for a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `namespace` - The hash of the namespace the function belongs to.
+    /// * `function_key` - The key of the function.
+    ///
+    /// # Returns
+    ///
+    /// * The invocations as JSON, newest first. Expired ones are left out.
+    pub async fn list(
+        conn: &mut MultiplexedConnection,
+        namespace: &str,
+        function_key: &str,
+    ) -> redis::RedisResult<Vec<String>> {
+        let ids: Vec<String> = conn
+            .lrange(
+                format!("{FUNCTION_INVOCATIONS_PREFIX}{function_key}"),
+                0,
+                -1,
+            )
+            .await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| format!("{INVOCATION_PREFIX}{namespace}:{id}"))
+            .collect();
+        let invocations: Vec<Option<String>> =
+            redis::cmd("MGET").arg(keys).query_async(conn).await?;
+        Ok(invocations.into_iter().flatten().collect())
+    }
+}
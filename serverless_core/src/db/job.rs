@@ -0,0 +1,393 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+/// Prefix of the Redis keys holding a job, by id
+const JOB_PREFIX: &str = "jobs:job:";
+
+/// Sorted set of queued job ids, scored by when they may run
+const QUEUED_KEY: &str = "jobs:queued";
+
+/// Sorted set of running job ids, scored by when their visibility timeout ends
+const RUNNING_KEY: &str = "jobs:running";
+
+/// Sorted set of failed job ids, scored by when they failed
+const FAILED_KEY: &str = "jobs:failed";
+
+/// Prefix of the Redis keys marking a job as queued for a dedup key
+const DEDUP_PREFIX: &str = "jobs:dedup:";
+
+/// Stores and queues a job unless one was queued for the same dedup key before the key
+/// expired, so controllers queueing the same work don't run it more than once
+const ENQUEUE_UNIQUE_SCRIPT: &str = r#"
+if not redis.call('SET', KEYS[1], ARGV[1], 'NX', 'EX', ARGV[2]) then
+    return 0
+end
+redis.call('SET', KEYS[2], ARGV[3])
+redis.call('ZREM', KEYS[3], ARGV[1])
+redis.call('ZADD', KEYS[4], ARGV[4], ARGV[1])
+return 1
+"#;
+
+/// Moves the first due job from the queue to the running set, so only one worker of one
+/// controller gets it
+const CLAIM_SCRIPT: &str = r#"
+local id = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, 1)[1]
+if not id then
+    return false
+end
+redis.call('ZREM', KEYS[1], id)
+redis.call('ZADD', KEYS[2], ARGV[2], id)
+return id
+"#;
+
+/// Moves the running jobs whose visibility timeout ended back to the queue
+const REQUEUE_SCRIPT: &str = r#"
+local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1])
+for _, id in ipairs(ids) do
+    redis.call('ZREM', KEYS[1], id)
+    redis.call('ZADD', KEYS[2], ARGV[1], id)
+end
+return #ids
+"#;
+
+/// Where a job is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Failed,
+}
+
+impl JobStatus {
+    fn key(self) -> &'static str {
+        match self {
+            Self::Queued => QUEUED_KEY,
+            Self::Running => RUNNING_KEY,
+            Self::Failed => FAILED_KEY,
+        }
+    }
+}
+
+/// A unit of background work. Jobs that succeed are removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    /// Picks the handler that runs the job
+    pub kind: String,
+    /// Input of the handler
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    /// Runs started so far, including the current one
+    pub attempts: u32,
+    /// Runs after which a failing job is given up on
+    pub max_attempts: u32,
+    /// Why the last run failed
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Seconds since the Unix epoch
+    pub enqueued_at: u64,
+}
+
+/// Background jobs, shared by every controller through Redis.
+///
+/// A job id sits in exactly one of the queued, running or failed sets; the job itself is
+/// stored under its own key.
+pub struct JobRepo;
+
+impl JobRepo {
+    fn key(id: &str) -> String {
+        format!("{JOB_PREFIX}{id}")
+    }
+
+    /// Stores a job and queues it.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `job` - The job to queue.
+    /// * `run_at` - When the job may run, in seconds since the Unix epoch.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn enqueue(
+        conn: &mut MultiplexedConnection,
+        job: &Job,
+        run_at: u64,
+    ) -> redis::RedisResult<()> {
+        let id = job.id.to_string();
+        redis::pipe()
+            .atomic()
+            .set(Self::key(&id), Self::encode(job)?)
+            .ignore()
+            .zrem(RUNNING_KEY, &id)
+            .ignore()
+            .zadd(QUEUED_KEY, &id, run_at)
+            .ignore()
+            .query_async(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to queue job {}: {}", id, e);
+                e
+            })
+    }
+
+    /// Stores a job and queues it, unless a job was queued for the same dedup key in the
+    /// last `dedup_secs`.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `job` - The job to queue.
+    /// * `run_at` - When the job may run, in seconds since the Unix epoch.
+    /// * `dedup_key` - Names the work the job does.
+    /// * `dedup_secs` - How long no other job is queued for the same key.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the job was queued, or a `redis::RedisError` if the operation fails.
+    pub async fn enqueue_unique(
+        conn: &mut MultiplexedConnection,
+        job: &Job,
+        run_at: u64,
+        dedup_key: &str,
+        dedup_secs: u64,
+    ) -> redis::RedisResult<bool> {
+        let id = job.id.to_string();
+        Script::new(ENQUEUE_UNIQUE_SCRIPT)
+            .key(format!("{DEDUP_PREFIX}{dedup_key}"))
+            .key(Self::key(&id))
+            .key(RUNNING_KEY)
+            .key(QUEUED_KEY)
+            .arg(&id)
+            .arg(dedup_secs.max(1))
+            .arg(Self::encode(job)?)
+            .arg(run_at)
+            .invoke_async(conn)
+            .await
+            .map_err(|e| {
+                error!("Failed to queue job {}: {}", id, e);
+                e
+            })
+    }
+
+    /// Takes the next job that is due, making it invisible to other workers until
+    /// `visible_at`.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    /// * `visible_at` - When the job is queued again unless its worker reports back.
+    ///
+    /// # Returns
+    ///
+    /// * The job, or `None` if none is due.
+    pub async fn claim(
+        conn: &mut MultiplexedConnection,
+        now: u64,
+        visible_at: u64,
+    ) -> redis::RedisResult<Option<Job>> {
+        let id: Option<String> = Script::new(CLAIM_SCRIPT)
+            .key(QUEUED_KEY)
+            .key(RUNNING_KEY)
+            .arg(now)
+            .arg(visible_at)
+            .invoke_async(conn)
+            .await?;
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        let job = conn
+            .get::<_, Option<String>>(Self::key(&id))
+            .await?
+            .and_then(|value| serde_json::from_str::<Job>(&value).ok());
+        if job.is_none() {
+            // Nothing to run; don't let the id come back after its timeout
+            error!("Job {} has no readable record, dropping it", id);
+            conn.zrem::<_, _, ()>(RUNNING_KEY, &id).await?;
+        }
+        Ok(job)
+    }
+
+    /// Replaces the stored record of a running job, and pushes back the end of its
+    /// visibility timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `job` - The running job.
+    /// * `visible_at` - When the job is queued again unless its worker reports back.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn touch(
+        conn: &mut MultiplexedConnection,
+        job: &Job,
+        visible_at: u64,
+    ) -> redis::RedisResult<()> {
+        let id = job.id.to_string();
+        redis::pipe()
+            .atomic()
+            .set(Self::key(&id), Self::encode(job)?)
+            .ignore()
+            .cmd("ZADD")
+            .arg(RUNNING_KEY)
+            .arg("XX")
+            .arg(visible_at)
+            .arg(&id)
+            .ignore()
+            .query_async(conn)
+            .await
+    }
+
+    /// Removes a job that ran successfully.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `id` - The id of the job.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn complete(conn: &mut MultiplexedConnection, id: Uuid) -> redis::RedisResult<()> {
+        let id = id.to_string();
+        redis::pipe()
+            .atomic()
+            .zrem(RUNNING_KEY, &id)
+            .ignore()
+            .del(Self::key(&id))
+            .ignore()
+            .query_async(conn)
+            .await
+    }
+
+    /// Moves a job to the failed set, dropping the oldest failed jobs beyond `keep`.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `job` - The job that failed for the last time.
+    /// * `failed_at` - When it failed, in seconds since the Unix epoch.
+    /// * `keep` - How many failed jobs are kept around for inspection.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn fail(
+        conn: &mut MultiplexedConnection,
+        job: &Job,
+        failed_at: u64,
+        keep: usize,
+    ) -> redis::RedisResult<()> {
+        let id = job.id.to_string();
+        redis::pipe()
+            .atomic()
+            .set(Self::key(&id), Self::encode(job)?)
+            .ignore()
+            .zrem(RUNNING_KEY, &id)
+            .ignore()
+            .zadd(FAILED_KEY, &id, failed_at)
+            .ignore()
+            .query_async::<()>(conn)
+            .await?;
+
+        let dropped: Vec<String> = conn.zrange(FAILED_KEY, 0, -(keep as isize) - 1).await?;
+        if dropped.is_empty() {
+            return Ok(());
+        }
+        let keys: Vec<String> = dropped.iter().map(|id| Self::key(id)).collect();
+        redis::pipe()
+            .atomic()
+            .zrem(FAILED_KEY, &dropped)
+            .ignore()
+            .del(keys)
+            .ignore()
+            .query_async(conn)
+            .await
+    }
+
+    /// Queues the running jobs whose worker stopped reporting back, e.g. because its
+    /// controller died.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    ///
+    /// # Returns
+    ///
+    /// * How many jobs were queued again.
+    pub async fn requeue_expired(
+        conn: &mut MultiplexedConnection,
+        now: u64,
+    ) -> redis::RedisResult<usize> {
+        Script::new(REQUEUE_SCRIPT)
+            .key(RUNNING_KEY)
+            .key(QUEUED_KEY)
+            .arg(now)
+            .invoke_async(conn)
+            .await
+    }
+
+    /// Lists the jobs in one state, oldest first for queued and running jobs and most
+    /// recent first for failed ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `status` - The state to list.
+    /// * `limit` - How many jobs to return at most.
+    ///
+    /// # Returns
+    ///
+    /// * The jobs, and how many are in that state in total.
+    pub async fn list(
+        conn: &mut MultiplexedConnection,
+        status: JobStatus,
+        limit: usize,
+    ) -> redis::RedisResult<(Vec<Job>, usize)> {
+        let total: usize = conn.zcard(status.key()).await?;
+        if limit == 0 || total == 0 {
+            return Ok((Vec::new(), total));
+        }
+        let stop = limit as isize - 1;
+        let ids: Vec<String> = match status {
+            JobStatus::Failed => conn.zrevrange(status.key(), 0, stop).await?,
+            JobStatus::Queued | JobStatus::Running => conn.zrange(status.key(), 0, stop).await?,
+        };
+        if ids.is_empty() {
+            return Ok((Vec::new(), total));
+        }
+
+        let keys: Vec<String> = ids.iter().map(|id| Self::key(id)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query_async(conn).await?;
+        let jobs = values
+            .into_iter()
+            .flatten()
+            .filter_map(|value| serde_json::from_str::<Job>(&value).ok())
+            .map(|mut job| {
+                // A job requeued after its timeout still has the status of its last run
+                job.status = status;
+                job
+            })
+            .collect();
+        Ok((jobs, total))
+    }
+
+    fn encode(job: &Job) -> redis::RedisResult<String> {
+        serde_json::to_string(job).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Failed to encode job",
+                e.to_string(),
+            ))
+        })
+    }
+}
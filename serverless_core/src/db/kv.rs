@@ -0,0 +1,271 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands, Script};
+
+/// Most keys a function may store
+pub const MAX_KEYS: usize = 10_000;
+
+/// Most bytes of values a function may store (16MB)
+pub const MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Code of the error writes answer with when the function's quota is used up
+pub const QUOTA_ERROR_CODE: &str = "QUOTA";
+
+/// Prefix of the Redis keys holding functions' key-value entries, followed by the
+/// function key and the entry's key
+const KV_PREFIX: &str = "kv:";
+
+/// Prefix of the hash holding the size of each of a function's entries, by key
+const INDEX_PREFIX: &str = "kvindex:";
+
+/// Prefix of the counter of the bytes a function's entries hold
+const BYTES_PREFIX: &str = "kvbytes:";
+
+/// Bytes a counter is accounted for, the length of the longest `i64`
+const COUNTER_SIZE: usize = 20;
+
+/// Quota bookkeeping the write scripts share. Entries that expire stay in the index
+/// until a write finds the function over its quota and prunes them.
+///
+/// KEYS: entry, index, bytes; ARGV: key, entry prefix, max keys, max bytes, then the
+/// script's own arguments
+const QUOTA_LUA: &str = r#"
+local index, bytes_key, field, prefix = KEYS[2], KEYS[3], ARGV[1], ARGV[2]
+local max_keys, max_bytes = tonumber(ARGV[3]), tonumber(ARGV[4])
+
+local function fits(size)
+    local old = redis.call('HGET', index, field)
+    local count = redis.call('HLEN', index)
+    if not old then
+        count = count + 1
+    end
+    local bytes = tonumber(redis.call('GET', bytes_key) or '0') - tonumber(old or '0') + size
+    return count <= max_keys and bytes <= max_bytes
+end
+
+local function prune()
+    local entries = redis.call('HGETALL', index)
+    for i = 1, #entries, 2 do
+        if redis.call('EXISTS', prefix .. entries[i]) == 0 then
+            redis.call('HDEL', index, entries[i])
+            redis.call('DECRBY', bytes_key, entries[i + 1])
+        end
+    end
+end
+
+local function reserve(size)
+    if fits(size) then
+        return true
+    end
+    prune()
+    return fits(size)
+end
+
+local function record(size)
+    local old = tonumber(redis.call('HGET', index, field) or '0')
+    redis.call('HSET', index, field, size)
+    redis.call('INCRBY', bytes_key, size - old)
+end
+
+local quota_error = 'QUOTA the function stores too many keys or bytes'
+"#;
+
+/// Writes an entry within the quota. ARGV: value, ttl in seconds or 0, `1` if absent only
+const SET_SCRIPT: &str = r#"
+if ARGV[7] == '1' and redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+local size = string.len(ARGV[5])
+if not reserve(size) then
+    return redis.error_reply(quota_error)
+end
+if ARGV[6] == '0' then
+    redis.call('SET', KEYS[1], ARGV[5])
+else
+    redis.call('SET', KEYS[1], ARGV[5], 'EX', ARGV[6])
+end
+record(size)
+return 1
+"#;
+
+/// Accounts for a counter about to be created within the quota. ARGV: its size.
+/// Incrementing is left to INCRBY, since Lua numbers lose precision past 2^53.
+const RESERVE_COUNTER_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    if not reserve(tonumber(ARGV[5])) then
+        return redis.error_reply(quota_error)
+    end
+    record(tonumber(ARGV[5]))
+end
+return 1
+"#;
+
+/// Removes an entry and its accounting
+const DELETE_SCRIPT: &str = r#"
+local removed = redis.call('DEL', KEYS[1])
+local old = redis.call('HGET', index, field)
+if old then
+    redis.call('HDEL', index, field)
+    redis.call('DECRBY', bytes_key, old)
+end
+return removed
+"#;
+
+fn entry_key(function_key: &str, key: &str) -> String {
+    format!("{KV_PREFIX}{function_key}:{key}")
+}
+
+/// One of the scripts above with the quota bookkeeping in front
+fn quota_script(body: &str) -> Script {
+    Script::new(&format!("{QUOTA_LUA}{body}"))
+}
+
+/// An invocation of a [`quota_script`] on an entry, with the shared arguments set
+fn invoke_on<'a>(script: &'a Script, function_key: &str, key: &str) -> redis::ScriptInvocation<'a> {
+    let mut invocation = script.prepare_invoke();
+    invocation
+        .key(entry_key(function_key, key))
+        .key(format!("{INDEX_PREFIX}{function_key}"))
+        .key(format!("{BYTES_PREFIX}{function_key}"))
+        .arg(key)
+        .arg(format!("{KV_PREFIX}{function_key}:"))
+        .arg(MAX_KEYS)
+        .arg(MAX_BYTES);
+    invocation
+}
+
+/// The key-value entries functions store through the internal API, kept apart per
+/// function
+pub struct KvRepo;
+
+impl KvRepo {
+    /// Reads an entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The function the entry belongs to.
+    /// * `key` - The entry's key.
+    ///
+    /// # Returns
+    ///
+    /// * The entry's value, `None` if it doesn't exist, or a `redis::RedisError`.
+    pub async fn get(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        key: &str,
+    ) -> redis::RedisResult<Option<Vec<u8>>> {
+        conn.get(entry_key(function_key, key)).await
+    }
+
+    /// Writes an entry, within the function's quota of [`MAX_KEYS`] and [`MAX_BYTES`].
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The function the entry belongs to.
+    /// * `key` - The entry's key.
+    /// * `value` - The value to store.
+    /// * `ttl_secs` - When set, the entry expires after this many seconds.
+    /// * `if_absent` - Only write the entry if it doesn't exist, e.g. to take a lock.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the entry was written, or a `redis::RedisError`, with the code
+    ///   [`QUOTA_ERROR_CODE`] when the quota is used up.
+    pub async fn set(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        key: &str,
+        value: &[u8],
+        ttl_secs: Option<u64>,
+        if_absent: bool,
+    ) -> redis::RedisResult<bool> {
+        let script = quota_script(SET_SCRIPT);
+        let written: i32 = invoke_on(&script, function_key, key)
+            .arg(value)
+            .arg(ttl_secs.unwrap_or(0))
+            .arg(if if_absent { 1 } else { 0 })
+            .invoke_async(conn)
+            .await?;
+        Ok(written == 1)
+    }
+
+    /// Adds to a counter, starting it at 0 if the entry doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The function the entry belongs to.
+    /// * `key` - The entry's key.
+    /// * `by` - What to add, negative to subtract.
+    ///
+    /// # Returns
+    ///
+    /// * The counter's new value, or a `redis::RedisError`, also when the entry doesn't
+    ///   hold an integer, or with the code [`QUOTA_ERROR_CODE`] when a new counter would
+    ///   exceed the function's quota.
+    pub async fn increment(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        key: &str,
+        by: i64,
+    ) -> redis::RedisResult<i64> {
+        let script = quota_script(RESERVE_COUNTER_SCRIPT);
+        let _: i32 = invoke_on(&script, function_key, key)
+            .arg(COUNTER_SIZE)
+            .invoke_async(conn)
+            .await?;
+        conn.incr(entry_key(function_key, key), by).await
+    }
+
+    /// Removes an entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The function the entry belongs to.
+    /// * `key` - The entry's key.
+    ///
+    /// # Returns
+    ///
+    /// * Whether the entry existed, or a `redis::RedisError`.
+    pub async fn delete(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        key: &str,
+    ) -> redis::RedisResult<bool> {
+        let script = quota_script(DELETE_SCRIPT);
+        let removed: u64 = invoke_on(&script, function_key, key)
+            .invoke_async(conn)
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Removes every entry of a function, e.g. once it's purged.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The function whose entries to remove.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn clear(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+    ) -> redis::RedisResult<()> {
+        let mut keys: Vec<String> = {
+            let mut keys = Vec::new();
+            let mut iter = conn
+                .scan_match::<String, String>(format!("{KV_PREFIX}{function_key}:*"))
+                .await?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        keys.push(format!("{INDEX_PREFIX}{function_key}"));
+        keys.push(format!("{BYTES_PREFIX}{function_key}"));
+        conn.del::<Vec<String>, ()>(keys).await
+    }
+}
@@ -0,0 +1,164 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands, Script};
+
+/// Prefix of the Redis keys counting failed logins
+const FAILURES_PREFIX: &str = "login:failures:";
+
+/// Prefix of the Redis keys marking a lockout
+const LOCKOUT_PREFIX: &str = "login:lockout:";
+
+/// Counts a failure and starts the window on the first one, in one step, so a counter
+/// is never left without an expiry. A counter found without one, e.g. written by an
+/// older controller, gets it too.
+const RECORD_FAILURE_SCRIPT: &str = r#"
+local failures = redis.call('INCR', KEYS[1])
+if failures == 1 or redis.call('TTL', KEYS[1]) == -1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return failures
+"#;
+
+pub struct LoginAttemptsRepo;
+
+impl LoginAttemptsRepo {
+    /// Finds how long a login source (an account or a client IP) stays locked out.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The source, e.g. `account:<email>` or `ip:<address>`.
+    ///
+    /// # Returns
+    ///
+    /// * The seconds left on the lockout, `None` if the source isn't locked out.
+    pub async fn lockout_remaining(
+        conn: &mut MultiplexedConnection,
+        key: &str,
+    ) -> redis::RedisResult<Option<u64>> {
+        let ttl: i64 = conn.ttl(format!("{LOCKOUT_PREFIX}{key}")).await?;
+        Ok((ttl > 0).then_some(ttl as u64))
+    }
+
+    /// Counts a failed login of a source, within a window starting at its first failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The source of the failed login.
+    /// * `window_secs` - How long failures are counted for.
+    ///
+    /// # Returns
+    ///
+    /// * The failures of the source in the current window.
+    pub async fn record_failure(
+        conn: &mut MultiplexedConnection,
+        key: &str,
+        window_secs: u64,
+    ) -> redis::RedisResult<u64> {
+        Script::new(RECORD_FAILURE_SCRIPT)
+            .key(format!("{FAILURES_PREFIX}{key}"))
+            .arg(window_secs.max(1))
+            .invoke_async(conn)
+            .await
+    }
+
+    /// Locks a source out, starting its failure count over.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The source to lock out.
+    /// * `lockout_secs` - How long the lockout lasts.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn lock(
+        conn: &mut MultiplexedConnection,
+        key: &str,
+        lockout_secs: u64,
+    ) -> redis::RedisResult<()> {
+        conn.set_ex::<_, _, ()>(format!("{LOCKOUT_PREFIX}{key}"), 1, lockout_secs)
+            .await?;
+        conn.del(format!("{FAILURES_PREFIX}{key}")).await
+    }
+
+    /// Forgets the failed logins of a source, e.g. after it logged in.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The source.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn clear(conn: &mut MultiplexedConnection, key: &str) -> redis::RedisResult<()> {
+        conn.del(format!("{FAILURES_PREFIX}{key}")).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL (redis://localhost:6379 by default); run with --ignored"]
+    async fn test_failures_expire_with_their_window() {
+        let url =
+            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+        let mut conn = redis::Client::open(url)
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let key = format!("test:{}", Uuid::new_v4());
+        let failures_key = format!("{FAILURES_PREFIX}{key}");
+
+        assert_eq!(
+            LoginAttemptsRepo::record_failure(&mut conn, &key, 60)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            LoginAttemptsRepo::record_failure(&mut conn, &key, 60)
+                .await
+                .unwrap(),
+            2
+        );
+        let ttl: i64 = conn.ttl(&failures_key).await.unwrap();
+        assert!((1..=60).contains(&ttl), "ttl {ttl}");
+
+        // A counter left without an expiry gets one on the next failure
+        conn.persist::<_, ()>(&failures_key).await.unwrap();
+        LoginAttemptsRepo::record_failure(&mut conn, &key, 60)
+            .await
+            .unwrap();
+        let ttl: i64 = conn.ttl(&failures_key).await.unwrap();
+        assert!((1..=60).contains(&ttl), "ttl {ttl}");
+
+        // Locking out starts the count over
+        assert_eq!(
+            LoginAttemptsRepo::lockout_remaining(&mut conn, &key)
+                .await
+                .unwrap(),
+            None
+        );
+        LoginAttemptsRepo::lock(&mut conn, &key, 30).await.unwrap();
+        let remaining = LoginAttemptsRepo::lockout_remaining(&mut conn, &key)
+            .await
+            .unwrap();
+        assert!(remaining.is_some_and(|secs| secs <= 30));
+        assert_eq!(
+            LoginAttemptsRepo::record_failure(&mut conn, &key, 60)
+                .await
+                .unwrap(),
+            1
+        );
+        LoginAttemptsRepo::clear(&mut conn, &key).await.unwrap();
+        conn.del::<_, ()>(format!("{LOCKOUT_PREFIX}{key}"))
+            .await
+            .unwrap();
+    }
+}
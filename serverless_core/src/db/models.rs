@@ -1,18 +1,76 @@
+use crate::lifecycle_manager::test_gate::TestConfig;
+use crate::utils::cron::CronSchedule;
+use crate::utils::firewall::FirewallRules;
+use crate::utils::image::FunctionImage;
+use crate::utils::registries::PrivateRegistries;
+use crate::utils::utils::{BodyLimits, DEFAULT_TIMEOUT_SECS};
+use db_entities::auth::Model as AuthModel;
+use db_entities::function::Model as FunctionModel;
+use db_entities::pending_deploy::Model as PendingDeployModel;
+use runtime::core::policy::{FunctionPolicy, IdleStrategy, Protocol, Service, StickyKey};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::FromQueryResult;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Smallest container memory limit accepted, in MB
+const MIN_MEMORY_MB: u64 = 64;
+
+/// Shortest container lifetime accepted, so recycling doesn't turn into constant churn
+const MIN_CONTAINER_AGE_SECS: u64 = 60;
+
 /// Represents a deployable function.
 ///
 /// # Fields
 /// - `name`: The unique name of the function.
 /// - `runtime`: The runtime environment required by the function (e.g., "go").
 /// - `content`: The zipped binary content of the function.
+/// - `history`: Earlier versions recorded before this one (set when importing).
+/// - `preview`: Set when deploying a preview instance; `name` is then the instance name.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeployableFunction {
     pub name: String,
     pub content: Vec<u8>,
     pub user_uuid: Uuid,
+    #[serde(default)]
+    pub history: Vec<PriorVersion>,
+    #[serde(default)]
+    pub preview: Option<DeployPreview>,
+}
+
+/// A temporary instance of a function deployed from a branch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployPreview {
+    /// The function being previewed; its handler is the one built
+    pub of: String,
+    /// How long the instance lives before it is removed; `None` keeps it until it is
+    /// removed explicitly
+    pub ttl: Option<Duration>,
+}
+
+/// A version of a function carried over from another installation.
+///
+/// Only recorded in the version history; the image is built from the latest version alone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PriorVersion {
+    /// The zipped function source
+    pub content: Vec<u8>,
+    /// The settings the version was deployed with
+    pub settings: Option<serde_json::Value>,
+    /// When the version was originally deployed (RFC 3339)
+    pub created_at: Option<String>,
+}
+
+/// The archive size of a recorded version, as measured by the database
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct VersionSize {
+    pub id: i32,
+    pub function_id: i32,
+    pub version: i32,
+    /// Bytes of the zipped function source
+    pub size: i64,
 }
 
 /// Represents the configuration for a function.
@@ -24,9 +82,552 @@ pub struct DeployableFunction {
 /// - `function_name`: The name of the function (should correspond to the `Function`'s name).
 /// - `runtime`: The runtime environment for the function.
 /// - `env`: Optional key-value pairs representing environment variables.
+/// - `registries`: Private package registries the dependencies are installed from.
+/// - `shared`: Directories next to the function's whose code it uses, archived with it.
+/// - `test`: Whether, and with what command, its unit tests run during the build.
+/// - `image`: The image functions of the `image` runtime run, deployed without a build.
+/// - `settings`: Per-function behaviour, given as top-level keys of the config file.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DeployableFunctionConfig {
     function_name: String,
     pub(crate) runtime: String,
     pub(crate) env: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub(crate) registries: Option<PrivateRegistries>,
+    #[serde(default)]
+    pub(crate) shared: Vec<String>,
+    #[serde(default)]
+    pub(crate) test: Option<TestConfig>,
+    #[serde(default)]
+    pub(crate) image: Option<FunctionImage>,
+    #[serde(flatten)]
+    pub(crate) settings: FunctionSettings,
+}
+
+/// Per-function settings from `config.json`, stored with the function record.
+///
+/// Every field is optional in the file so existing functions keep the defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FunctionSettings {
+    /// Serve one request at a time per container (e.g. functions wrapping SQLite)
+    #[serde(default)]
+    pub single_concurrency: bool,
+    /// Sticky routing on a header or cookie, e.g. `{"header": "x-session-id"}`
+    #[serde(default)]
+    pub sticky: Option<StickyKey>,
+    /// Largest request body accepted, in bytes (capped by the server limit)
+    #[serde(default)]
+    pub max_request_size: Option<usize>,
+    /// Largest response body returned, in bytes (capped by the server limit)
+    #[serde(default)]
+    pub max_response_size: Option<usize>,
+    /// Let the proxy compress responses (gzip/br) and decode compressed request bodies
+    #[serde(default)]
+    pub compression: bool,
+    /// Forward request bodies and query strings exactly as the client sent them
+    #[serde(default)]
+    pub raw_body: bool,
+    /// `"h2c"` for functions serving cleartext HTTP/2, e.g. gRPC servers
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// `"tcp"` or `"udp"` for functions serving raw connections on a published port
+    /// instead of HTTP invocations
+    #[serde(default)]
+    pub service: Service,
+    /// Connections (UDP: client sessions) each container of a TCP or UDP service takes
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// IP allow/deny lists and request filters applied before the function is woken
+    #[serde(default)]
+    pub firewall: Option<FirewallRules>,
+    /// Container memory limit in MB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Seconds the proxy waits for the function to respond
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Remove the container of an invocation still running after `timeout_secs`
+    #[serde(default)]
+    pub kill_on_timeout: bool,
+    /// Containers kept running even when idle
+    #[serde(default)]
+    pub min_containers: Option<usize>,
+    /// Most containers the function scales to (capped by the server's maximum)
+    #[serde(default)]
+    pub max_containers: Option<usize>,
+    /// `"pause"` freezes idle containers instead of removing them, so the next request
+    /// unpauses one rather than cold starting
+    #[serde(default)]
+    pub idle_strategy: IdleStrategy,
+    /// Seconds a container serves before it is replaced by a fresh one, e.g. to contain
+    /// memory leaks
+    #[serde(default)]
+    pub max_container_age_secs: Option<u64>,
+    /// Requests (connections) a container takes before it is replaced by a fresh one
+    #[serde(default)]
+    pub max_container_requests: Option<u64>,
+    /// Longest time in seconds between scaling checks of the function's pool while it
+    /// has no traffic
+    #[serde(default)]
+    pub idle_poll_interval_secs: Option<u64>,
+    /// Containers of the function started at the same time; 1 when unset, so a burst of
+    /// requests to a cold function shares the first container
+    #[serde(default)]
+    pub start_concurrency: Option<usize>,
+    /// Keep recent requests so `invok replay` can send them again
+    #[serde(default)]
+    pub record_invocations: bool,
+    /// Ping the function on a cron schedule to keep it warm, e.g.
+    /// `{"schedule": "*/5 9-17 * * 1-5"}`
+    #[serde(default)]
+    pub warmup: Option<WarmupSchedule>,
+    /// Availability (and optionally latency) objective tracked against an error budget,
+    /// e.g. `{"objective": 99.9, "latency_ms": 500}`
+    #[serde(default)]
+    pub slo: Option<Slo>,
+}
+
+/// When the scheduler pings a function so a container is running when traffic arrives.
+///
+/// Lighter than `min_containers`: containers only stay up while the schedule keeps
+/// pinging, and idle out as usual between pings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WarmupSchedule {
+    /// Five-field cron expression, in UTC
+    pub schedule: String,
+}
+
+impl WarmupSchedule {
+    /// The parsed schedule
+    pub fn cron(&self) -> Result<CronSchedule, String> {
+        self.schedule.parse()
+    }
+}
+
+/// Longest window an SLO is measured over, in days
+const MAX_SLO_WINDOW_DAYS: u32 = 90;
+
+fn default_slo_window_days() -> u32 {
+    28
+}
+
+/// A function's service level objective.
+///
+/// An invocation is good when it isn't answered with a 5xx and, with `latency_ms` set,
+/// is answered within that time. The error budget is the share of invocations allowed to
+/// be bad: 0.1% for an objective of 99.9.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Slo {
+    /// Percent of invocations that must be good, e.g. `99.9`
+    pub objective: f64,
+    /// Invocations answered slower than this count against the budget
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// Days the objective is measured over
+    #[serde(default = "default_slo_window_days")]
+    pub window_days: u32,
+    /// Refuse deploys while the error budget is spent, unless forced
+    #[serde(default)]
+    pub freeze_deploys: bool,
+}
+
+impl Slo {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.objective > 0.0 && self.objective < 100.0) {
+            return Err("objective must be a percentage between 0 and 100".to_string());
+        }
+        if self.latency_ms == Some(0) {
+            return Err("latency_ms must be at least 1".to_string());
+        }
+        if self.window_days == 0 || self.window_days > MAX_SLO_WINDOW_DAYS {
+            return Err(format!(
+                "window_days must be between 1 and {}",
+                MAX_SLO_WINDOW_DAYS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether an invocation answered with `status` after `latency` meets the objective
+    pub fn is_good(&self, status: u16, latency: Duration) -> bool {
+        status < 500
+            && !matches!(self.latency_ms, Some(limit) if latency > Duration::from_millis(limit))
+    }
+}
+
+impl FunctionSettings {
+    /// Read the settings stored on a function record, falling back to the defaults
+    pub fn from_model(function: &FunctionModel) -> Self {
+        function
+            .settings
+            .clone()
+            .and_then(|settings| serde_json::from_value(settings).ok())
+            .unwrap_or_default()
+    }
+
+    /// Fill in settings the function's config leaves unset from its namespace's defaults
+    pub fn inherit(&mut self, defaults: &NamespaceDefaults) {
+        self.memory_mb = self.memory_mb.or(defaults.memory_mb);
+        self.timeout_secs = self.timeout_secs.or(defaults.timeout_secs);
+        self.min_containers = self.min_containers.or(defaults.min_containers);
+        self.max_containers = self.max_containers.or(defaults.max_containers);
+    }
+
+    /// Check the settings before a deploy accepts them
+    pub fn validate(&self) -> Result<(), String> {
+        validate_resources(
+            self.memory_mb,
+            self.timeout_secs,
+            self.min_containers,
+            self.max_containers,
+        )?;
+        if self.max_connections == Some(0) {
+            return Err("max_connections must be at least 1".to_string());
+        }
+        if !self.service.is_http() && self.protocol != Protocol::Http1 {
+            return Err("protocol only applies to HTTP functions".to_string());
+        }
+        if !self.service.is_http() && self.kill_on_timeout {
+            return Err("kill_on_timeout only applies to HTTP functions".to_string());
+        }
+        if self
+            .max_container_age_secs
+            .is_some_and(|secs| secs < MIN_CONTAINER_AGE_SECS)
+        {
+            return Err(format!(
+                "max_container_age_secs must be at least {}",
+                MIN_CONTAINER_AGE_SECS
+            ));
+        }
+        if self.max_container_requests == Some(0) {
+            return Err("max_container_requests must be at least 1".to_string());
+        }
+        if self.idle_poll_interval_secs == Some(0) {
+            return Err("idle_poll_interval_secs must be at least 1".to_string());
+        }
+        if self.start_concurrency == Some(0) {
+            return Err("start_concurrency must be at least 1".to_string());
+        }
+        if let Some(firewall) = &self.firewall {
+            firewall
+                .validate()
+                .map_err(|e| format!("invalid firewall: {}", e))?;
+        }
+        if let Some(warmup) = &self.warmup {
+            warmup
+                .cron()
+                .map_err(|e| format!("invalid warmup schedule: {}", e))?;
+        }
+        if let Some(slo) = &self.slo {
+            slo.validate().map_err(|e| format!("invalid slo: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// How long the proxy waits for the function to respond
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    /// Body limits for invocations: the function's own limits, never above the server's
+    pub fn body_limits(&self, max_request_size: usize, max_response_size: usize) -> BodyLimits {
+        BodyLimits {
+            max_request_bytes: self
+                .max_request_size
+                .map_or(max_request_size, |size| size.min(max_request_size)),
+            max_response_bytes: self
+                .max_response_size
+                .map_or(max_response_size, |size| size.min(max_response_size)),
+        }
+    }
+
+    /// Routing policy the autoscaler applies to the function's containers
+    pub fn policy(&self) -> FunctionPolicy {
+        FunctionPolicy {
+            single_concurrency: self.single_concurrency,
+            sticky: self.sticky.clone(),
+            memory_mb: self.memory_mb,
+            min_containers: self.min_containers,
+            max_containers: self.max_containers,
+            idle_strategy: self.idle_strategy,
+            protocol: self.protocol,
+            service: self.service,
+            max_connections: self.max_connections,
+            execution_timeout_secs: self.kill_on_timeout.then(|| self.timeout().as_secs()),
+            max_container_age_secs: self.max_container_age_secs,
+            max_container_requests: self.max_container_requests,
+            idle_poll_interval_secs: self.idle_poll_interval_secs,
+            start_concurrency: self.start_concurrency,
+        }
+    }
+}
+
+/// Namespace (user) level defaults, inherited at deploy time by every function whose
+/// `config.json` doesn't set the same key.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NamespaceDefaults {
+    /// Container memory limit in MB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Seconds the proxy waits for a function to respond
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Containers kept running even when idle
+    #[serde(default)]
+    pub min_containers: Option<usize>,
+    /// Most containers a function scales to
+    #[serde(default)]
+    pub max_containers: Option<usize>,
+    /// Environment variables; a function's own `env` wins on conflicts
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+impl NamespaceDefaults {
+    /// Read the defaults stored on a user record, falling back to none
+    pub fn from_model(user: &AuthModel) -> Self {
+        user.defaults
+            .clone()
+            .and_then(|defaults| serde_json::from_value(defaults).ok())
+            .unwrap_or_default()
+    }
+
+    /// Check the defaults before they are stored
+    pub fn validate(&self) -> Result<(), String> {
+        validate_resources(
+            self.memory_mb,
+            self.timeout_secs,
+            self.min_containers,
+            self.max_containers,
+        )
+    }
+
+    /// The function's environment layered over the namespace's
+    pub fn merge_env(
+        &self,
+        env: Option<HashMap<String, String>>,
+    ) -> Option<HashMap<String, String>> {
+        if env.is_none() && self.env.is_empty() {
+            return None;
+        }
+        let mut merged = self.env.clone();
+        merged.extend(env.unwrap_or_default());
+        Some(merged)
+    }
+}
+
+/// Something a namespace can be notified about
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A function was built and deployed
+    DeploySucceeded,
+    /// A function failed to build or deploy
+    DeployFailed,
+    /// A deploy of a function waits for one of the namespace's approvers
+    DeployPendingApproval,
+    /// A function's container exited on its own
+    ContainerCrashed,
+    /// A container a function needed could not be started
+    ScaleUpFailed,
+}
+
+/// Where a namespace's notifications are sent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum NotificationTarget {
+    /// The notification is posted as JSON, signed with `secret` in `x-invok-signature`
+    /// when one is set
+    Webhook {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+        /// Kinds sent to this target; all of them when empty
+        #[serde(default)]
+        events: Vec<NotificationKind>,
+    },
+    /// The notification is posted as a message to a Slack incoming webhook
+    Slack {
+        webhook_url: String,
+        #[serde(default)]
+        events: Vec<NotificationKind>,
+    },
+}
+
+impl NotificationTarget {
+    /// Whether the target takes notifications of this kind
+    pub fn wants(&self, kind: NotificationKind) -> bool {
+        let (Self::Webhook { events, .. } | Self::Slack { events, .. }) = self;
+        events.is_empty() || events.contains(&kind)
+    }
+
+    /// Where the notifications are posted
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Webhook { url, .. } => url,
+            Self::Slack { webhook_url, .. } => webhook_url,
+        }
+    }
+}
+
+/// Namespace (user) level notification targets
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub targets: Vec<NotificationTarget>,
+}
+
+impl NotificationSettings {
+    /// Read the targets stored on a user record, falling back to none
+    pub fn from_model(user: &AuthModel) -> Self {
+        user.notifications
+            .clone()
+            .and_then(|notifications| serde_json::from_value(notifications).ok())
+            .unwrap_or_default()
+    }
+
+    /// Check the targets before they are stored
+    pub fn validate(&self) -> Result<(), String> {
+        for target in &self.targets {
+            let url = target.url();
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("'{}' is not an http(s) URL", url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Accounts whose approval a namespace's deploys wait for
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeployApprovers {
+    /// Emails of the approving accounts; deploys need no approval when empty
+    #[serde(default)]
+    pub approvers: Vec<String>,
+}
+
+impl DeployApprovers {
+    /// Read the approvers stored on a user record, falling back to none
+    pub fn from_model(user: &AuthModel) -> Self {
+        user.deploy_approvers
+            .clone()
+            .and_then(|approvers| serde_json::from_value(approvers).ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether the account with this email may approve the namespace's deploys
+    pub fn includes(&self, email: &str) -> bool {
+        self.approvers
+            .iter()
+            .any(|approver| approver.eq_ignore_ascii_case(email))
+    }
+}
+
+/// A deploy waiting for, or given, approval, without its archive
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct PendingDeploySummary {
+    pub id: i32,
+    pub auth_id: i32,
+    pub function_name: String,
+    pub requested_by: String,
+    pub status: String,
+    pub decided_by: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl From<&PendingDeployModel> for PendingDeploySummary {
+    fn from(deploy: &PendingDeployModel) -> Self {
+        Self {
+            id: deploy.id,
+            auth_id: deploy.auth_id,
+            function_name: deploy.function_name.clone(),
+            requested_by: deploy.requested_by.clone(),
+            status: deploy.status.clone(),
+            decided_by: deploy.decided_by.clone(),
+            created_at: deploy.created_at,
+        }
+    }
+}
+
+/// Where a deploy waiting for approval is at
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingDeployStatus {
+    /// Waiting for an approver
+    Pending,
+    /// Approved and deployed
+    Approved,
+    Rejected,
+    /// A later deploy of the function replaced it before it was decided on
+    Superseded,
+    /// Approved, but the deploy failed
+    Failed,
+}
+
+impl PendingDeployStatus {
+    /// The status as stored in the `pending_deploy` table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+            Self::Superseded => "superseded",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Where a deploy queued for the job runners is at
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentStatus {
+    Queued,
+    /// Being built and deployed
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl DeploymentStatus {
+    /// The status as stored in the `deployment` table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Range checks shared by function settings and namespace defaults
+fn validate_resources(
+    memory_mb: Option<u64>,
+    timeout_secs: Option<u64>,
+    min_containers: Option<usize>,
+    max_containers: Option<usize>,
+) -> Result<(), String> {
+    if memory_mb.is_some_and(|memory| memory < MIN_MEMORY_MB) {
+        return Err(format!("memory_mb must be at least {}", MIN_MEMORY_MB));
+    }
+    if timeout_secs == Some(0) {
+        return Err("timeout_secs must be at least 1".to_string());
+    }
+    if max_containers == Some(0) {
+        return Err("max_containers must be at least 1".to_string());
+    }
+    if let (Some(min), Some(max)) = (min_containers, max_containers) {
+        if min > max {
+            return Err(format!(
+                "min_containers ({}) is above max_containers ({})",
+                min, max
+            ));
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,84 @@
+use db_entities::oidc_trust::{ActiveModel as OidcTrustModel, Column, Model};
+use db_entities::prelude::OidcTrust;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder,
+};
+
+pub struct OidcTrustDBRepo;
+
+impl OidcTrustDBRepo {
+    /// Trusts a CI workflow to deploy into a user's namespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the workflow may deploy to.
+    /// * `repository` - The `owner/name` repository pattern the token must come from.
+    /// * `repository_owner_id` - The id of the repository owner the token must carry.
+    /// * `repository_id` - The id of the repository the token must carry, or `None` for a pattern.
+    /// * `ref_pattern` - The git ref pattern the token must carry, or `None` for any.
+    /// * `environment` - The deployment environment the token must carry, or `None` for any.
+    ///
+    /// # Returns
+    ///
+    /// * The created trust, or an error of type `sea_orm::DbErr` if insertion fails.
+    pub async fn create(
+        conn: &DbConn,
+        auth_id: i32,
+        repository: String,
+        repository_owner_id: String,
+        repository_id: Option<String>,
+        ref_pattern: Option<String>,
+        environment: Option<String>,
+    ) -> Result<Model, sea_orm::DbErr> {
+        OidcTrustModel {
+            auth_id: Set(auth_id),
+            repository: Set(repository),
+            repository_owner_id: Set(Some(repository_owner_id)),
+            repository_id: Set(repository_id),
+            ref_pattern: Set(ref_pattern),
+            environment: Set(environment),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+
+    /// Finds the trusts of a user's namespace, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose trusts to list.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of trusts
+    pub async fn find_by_user(conn: &DbConn, auth_id: i32) -> Result<Vec<Model>, sea_orm::DbErr> {
+        OidcTrust::find()
+            .filter(Column::AuthId.eq(auth_id))
+            .order_by_asc(Column::Id)
+            .all(conn)
+            .await
+    }
+
+    /// Removes one of a user's trusts.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user the trust belongs to.
+    /// * `id` - The trust to remove.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the trust existed, or an error of type `sea_orm::DbErr` if the delete fails.
+    pub async fn delete(conn: &DbConn, auth_id: i32, id: i32) -> Result<bool, sea_orm::DbErr> {
+        let result = OidcTrust::delete_many()
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::Id.eq(id))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::db::models::{PendingDeployStatus, PendingDeploySummary};
+use db_entities::pending_deploy::{ActiveModel as PendingDeployModel, Column, Model};
+use db_entities::prelude::PendingDeploy;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone, Expr};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use std::time::SystemTime;
+
+pub struct PendingDeployDBRepo;
+
+impl PendingDeployDBRepo {
+    /// Stores a deploy until an approver decides on it. Deploys of the same function
+    /// still pending are superseded, so only the latest one can be approved.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_id` - The user whose namespace the function is deployed into.
+    /// * `function_name` - The function to deploy.
+    /// * `archive` - The function's archive, deployed once approved.
+    /// * `force` - Whether the deploy overrides SLO deploy freezes.
+    /// * `requested_by` - Who requested the deploy, e.g. the user's email.
+    ///
+    /// # Returns
+    ///
+    /// * The pending deploy, or an error of type `sea_orm::DbErr` if storing it fails.
+    pub async fn create(
+        conn: &DbConn,
+        auth_id: i32,
+        function_name: String,
+        archive: Vec<u8>,
+        force: bool,
+        requested_by: String,
+    ) -> Result<Model, sea_orm::DbErr> {
+        PendingDeploy::update_many()
+            .col_expr(
+                Column::Status,
+                Expr::value(PendingDeployStatus::Superseded.as_str()),
+            )
+            .filter(Column::AuthId.eq(auth_id))
+            .filter(Column::FunctionName.eq(&function_name))
+            .filter(Column::Status.eq(PendingDeployStatus::Pending.as_str()))
+            .exec(conn)
+            .await?;
+
+        PendingDeployModel {
+            auth_id: Set(auth_id),
+            function_name: Set(function_name),
+            archive: Set(archive),
+            force: Set(force),
+            requested_by: Set(requested_by),
+            status: Set(PendingDeployStatus::Pending.as_str().to_string()),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+
+    /// Finds a deploy waiting for, or given, approval.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `id` - The pending deploy to find.
+    ///
+    /// # Returns
+    ///
+    /// * The pending deploy, if it exists.
+    pub async fn find_by_id(conn: &DbConn, id: i32) -> Result<Option<Model>, sea_orm::DbErr> {
+        PendingDeploy::find_by_id(id).one(conn).await
+    }
+
+    /// Finds the deploys of some namespaces still waiting for approval, oldest first,
+    /// without their archives.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `auth_ids` - The namespaces to look in.
+    ///
+    /// # Returns
+    ///
+    /// * Vector of pending deploys
+    pub async fn find_pending(
+        conn: &DbConn,
+        auth_ids: Vec<i32>,
+    ) -> Result<Vec<PendingDeploySummary>, sea_orm::DbErr> {
+        PendingDeploy::find()
+            .select_only()
+            .column(Column::Id)
+            .column(Column::AuthId)
+            .column(Column::FunctionName)
+            .column(Column::RequestedBy)
+            .column(Column::Status)
+            .column(Column::DecidedBy)
+            .column(Column::CreatedAt)
+            .filter(Column::AuthId.is_in(auth_ids))
+            .filter(Column::Status.eq(PendingDeployStatus::Pending.as_str()))
+            .order_by_asc(Column::Id)
+            .into_model::<PendingDeploySummary>()
+            .all(conn)
+            .await
+    }
+
+    /// Records the decision on a deploy, unless it was already decided on.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A reference to the database connection.
+    /// * `id` - The deploy decided on.
+    /// * `from` - The status the deploy must still have.
+    /// * `to` - Its new status.
+    /// * `decided_by` - Who decided, e.g. the approver's email.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the deploy had the `from` status, or an error of type
+    ///   `sea_orm::DbErr` if the update fails.
+    pub async fn decide(
+        conn: &DbConn,
+        id: i32,
+        from: PendingDeployStatus,
+        to: PendingDeployStatus,
+        decided_by: &str,
+    ) -> Result<bool, sea_orm::DbErr> {
+        let decided_at: DateTimeWithTimeZone = ChronoDateTimeUtc::from(SystemTime::now()).into();
+        let result = PendingDeploy::update_many()
+            .col_expr(Column::Status, Expr::value(to.as_str()))
+            .col_expr(Column::DecidedBy, Expr::value(decided_by))
+            .col_expr(Column::DecidedAt, Expr::value(decided_at))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Status.eq(from.as_str()))
+            .exec(conn)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
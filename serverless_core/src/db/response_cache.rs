@@ -0,0 +1,92 @@
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+/// Prefix of the Redis keys holding a cached function response
+const RESPONSE_PREFIX: &str = "response:";
+
+/// Prefix of the Redis keys counting how often a function's responses were purged
+const GENERATION_PREFIX: &str = "response_generation:";
+
+/// Function responses kept for shared caching.
+///
+/// Entries are keyed by the function's purge generation, so a purge drops all of them
+/// at once by moving to the next generation; the old entries expire on their own.
+pub struct ResponseCacheRepo;
+
+impl ResponseCacheRepo {
+    /// Gets the current purge generation of a function.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    ///
+    /// # Returns
+    ///
+    /// * The generation, 0 for functions never purged, or a `redis::RedisError`.
+    pub async fn generation(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+    ) -> redis::RedisResult<u64> {
+        let generation: Option<u64> = conn
+            .get(format!("{GENERATION_PREFIX}{function_key}"))
+            .await?;
+        Ok(generation.unwrap_or_default())
+    }
+
+    /// Gets a cached response.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The key of the response within its function's generation.
+    ///
+    /// # Returns
+    ///
+    /// * The response as JSON if it is cached, or a `redis::RedisError`.
+    pub async fn get(
+        conn: &mut MultiplexedConnection,
+        key: &str,
+    ) -> redis::RedisResult<Option<String>> {
+        conn.get(format!("{RESPONSE_PREFIX}{key}")).await
+    }
+
+    /// Caches a response.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `key` - The key of the response within its function's generation.
+    /// * `response` - The response as JSON.
+    /// * `ttl_secs` - How long the response is kept.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` on success, or a `redis::RedisError` if the operation fails.
+    pub async fn store(
+        conn: &mut MultiplexedConnection,
+        key: &str,
+        response: &str,
+        ttl_secs: u64,
+    ) -> redis::RedisResult<()> {
+        conn.set_ex(format!("{RESPONSE_PREFIX}{key}"), response, ttl_secs)
+            .await
+    }
+
+    /// Drops every cached response of a function by starting a new generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    ///
+    /// # Returns
+    ///
+    /// * The new generation, or a `redis::RedisError` if the operation fails.
+    pub async fn purge(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+    ) -> redis::RedisResult<u64> {
+        conn.incr(format!("{GENERATION_PREFIX}{function_key}"), 1)
+            .await
+    }
+}
@@ -0,0 +1,121 @@
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+
+/// Prefix of the Redis hashes counting a function's invocations for one day, by hour
+const SLO_PREFIX: &str = "slo:";
+
+/// Invocation outcomes of functions with an SLO, in hourly buckets.
+///
+/// Each day of a function has its own hash, with `<hour>:total` and `<hour>:bad` fields
+/// (hours since the Unix epoch), expiring once it leaves the SLO window.
+pub struct SloRepo;
+
+impl SloRepo {
+    /// Counts an invocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    /// * `hour` - Hours since the Unix epoch the invocation was answered.
+    /// * `bad` - Whether the invocation missed the objective.
+    /// * `ttl_secs` - How long the day's counts are kept.
+    pub async fn record(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        hour: u64,
+        bad: bool,
+        ttl_secs: u64,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{SLO_PREFIX}{function_key}:{}", hour / 24);
+        redis::pipe()
+            .hincr(&key, format!("{hour}:total"), 1)
+            .ignore()
+            .hincr(&key, format!("{hour}:bad"), i64::from(bad))
+            .ignore()
+            .expire(&key, ttl_secs as i64)
+            .ignore()
+            .query_async(conn)
+            .await
+    }
+
+    /// Gets the hourly counts of a function's days.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `function_key` - The key of the function.
+    /// * `days` - Days since the Unix epoch to read.
+    ///
+    /// # Returns
+    ///
+    /// * The `<hour>:total` and `<hour>:bad` counts of every day, or a `redis::RedisError`.
+    pub async fn counts(
+        conn: &mut MultiplexedConnection,
+        function_key: &str,
+        days: impl Iterator<Item = u64>,
+    ) -> redis::RedisResult<HashMap<String, u64>> {
+        let mut pipe = redis::pipe();
+        for day in days {
+            pipe.hgetall(format!("{SLO_PREFIX}{function_key}:{day}"));
+        }
+        let per_day: Vec<HashMap<String, u64>> = pipe.query_async(conn).await?;
+        Ok(per_day.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::AsyncCommands;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL (redis://localhost:6379 by default); run with --ignored"]
+    async fn test_record_and_count() {
+        let url =
+            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+        let mut conn = redis::Client::open(url)
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let function_key = format!("slo-test-{}", Uuid::new_v4());
+        // The last hour of a day and the first of the next
+        let hour = 500_000 * 24 + 23;
+
+        for bad in [false, true, false] {
+            SloRepo::record(&mut conn, &function_key, hour, bad, 60)
+                .await
+                .unwrap();
+        }
+        SloRepo::record(&mut conn, &function_key, hour + 1, true, 60)
+            .await
+            .unwrap();
+
+        let counts = SloRepo::counts(&mut conn, &function_key, hour / 24..=hour / 24 + 1)
+            .await
+            .unwrap();
+        assert_eq!(counts[&format!("{hour}:total")], 3);
+        assert_eq!(counts[&format!("{hour}:bad")], 1);
+        assert_eq!(counts[&format!("{}:total", hour + 1)], 1);
+        assert_eq!(counts[&format!("{}:bad", hour + 1)], 1);
+
+        // Days are read separately
+        let first_day = SloRepo::counts(&mut conn, &function_key, hour / 24..=hour / 24)
+            .await
+            .unwrap();
+        assert_eq!(first_day.len(), 2);
+
+        let key = format!("{SLO_PREFIX}{function_key}:{}", hour / 24);
+        let ttl: i64 = conn.ttl(&key).await.unwrap();
+        assert!((1..=60).contains(&ttl), "ttl {ttl}");
+
+        for day in [hour / 24, hour / 24 + 1] {
+            let _: () = conn
+                .del(format!("{SLO_PREFIX}{function_key}:{day}"))
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use redis::aio::MultiplexedConnection;
+
+/// Prefix of the Redis keys marking a minute's warmup pings as claimed, by unix minute
+const CLAIM_PREFIX: &str = "warmup:claimed:";
+
+/// How long a claim is kept, long enough for every controller to have checked the minute
+const CLAIM_TTL_SECS: u64 = 120;
+
+/// Which controller pings the functions due in a minute, so they are pinged once however
+/// many controllers run
+pub struct WarmupRepo;
+
+impl WarmupRepo {
+    /// Claims the pings of a minute for a controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - A mutable reference to the Redis connection.
+    /// * `minute` - The unix time the minute starts at.
+    /// * `controller` - Identifies the claiming controller, for debugging.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if no controller claimed the minute before, or a `redis::RedisError`.
+    pub async fn claim_minute(
+        conn: &mut MultiplexedConnection,
+        minute: u64,
+        controller: &str,
+    ) -> redis::RedisResult<bool> {
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(format!("{CLAIM_PREFIX}{minute}"))
+            .arg(controller)
+            .arg("NX")
+            .arg("EX")
+            .arg(CLAIM_TTL_SECS)
+            .query_async(conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::AsyncCommands;
+    use uuid::Uuid;
+
+    async fn release_minute(conn: &mut MultiplexedConnection, minute: u64) {
+        let _: () = conn.del(format!("{CLAIM_PREFIX}{minute}")).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL (redis://localhost:6379 by default); run with --ignored"]
+    async fn test_one_controller_claims_a_minute() {
+        let url =
+            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+        let mut conn = redis::Client::open(url)
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        // A minute no running controller is at
+        let minute = u64::from(Uuid::new_v4().as_u128() as u32) * 60;
+
+        assert!(WarmupRepo::claim_minute(&mut conn, minute, "a")
+            .await
+            .unwrap());
+        assert!(!WarmupRepo::claim_minute(&mut conn, minute, "b")
+            .await
+            .unwrap());
+        assert!(!WarmupRepo::claim_minute(&mut conn, minute, "a")
+            .await
+            .unwrap());
+        // The next minute is up for grabs again
+        assert!(WarmupRepo::claim_minute(&mut conn, minute + 60, "b")
+            .await
+            .unwrap());
+
+        let ttl: i64 = conn.ttl(format!("{CLAIM_PREFIX}{minute}")).await.unwrap();
+        assert!((1..=CLAIM_TTL_SECS as i64).contains(&ttl), "ttl {ttl}");
+
+        release_minute(&mut conn, minute).await;
+        release_minute(&mut conn, minute + 60).await;
+    }
+}
@@ -2,4 +2,4 @@ mod api_controller;
 mod db;
 mod lifecycle_manager;
 mod utils;
-pub use api_controller::start_server;
+pub use api_controller::{run_doctor, start_builder, start_egress_proxy, start_server};
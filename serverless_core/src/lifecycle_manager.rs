@@ -1,3 +1,42 @@
+pub(crate) mod backup;
+pub(crate) mod base_images;
+pub(crate) mod bench;
+pub(crate) mod build_args;
+pub(crate) mod build_queue;
+pub(crate) mod cold_start;
+pub(crate) mod dependencies;
 pub(crate) mod deploy;
+pub(crate) mod deploy_gate;
+pub(crate) mod deploy_queue;
+pub(crate) mod dev;
+pub(crate) mod docs;
+pub(crate) mod dry_run;
+pub(crate) mod egress;
 pub(crate) mod error;
+pub(crate) mod exec;
+pub(crate) mod freeze;
+pub(crate) mod gateway;
+pub(crate) mod hibernation;
+pub(crate) mod invalidation;
 pub(crate) mod invoke;
+pub(crate) mod jobs;
+pub(crate) mod login_guard;
+pub(crate) mod manifest_list;
+pub(crate) mod notify;
+pub(crate) mod oidc;
+pub(crate) mod payloads;
+pub(crate) mod preview;
+pub(crate) mod promote;
+pub(crate) mod remote_build;
+pub(crate) mod replay;
+pub(crate) mod response_cache;
+pub(crate) mod retention;
+pub(crate) mod routing;
+pub(crate) mod services;
+pub(crate) mod slo;
+pub(crate) mod status;
+pub(crate) mod test_gate;
+pub(crate) mod totp;
+pub(crate) mod transfer;
+pub(crate) mod trash;
+pub(crate) mod warmup;
@@ -0,0 +1,1437 @@
+use crate::db::backup::{BackupDBRepo, DbSnapshot};
+use crate::db::models::DeploymentStatus;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::utils::archive::{pack, unpack};
+use db_entities::{
+    api_token, audit_log, auth, build_arg, deploy_lock, deployment, domain, egress_credential,
+    event_source, function, function_version, oidc_trust, pending_deploy, schedule, usage,
+};
+use db_migrations::{Migrator, MigratorTrait};
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::persistence::PersistedPoolState;
+use sea_orm::prelude::{ChronoDateTimeUtc, Date, DateTimeWithTimeZone};
+use sea_orm::DatabaseConnection;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Layout version of backup archives; bumped on incompatible changes
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "backup.json";
+const USERS_PATH: &str = "db/auth.json";
+const FUNCTIONS_PATH: &str = "db/function.json";
+const VERSIONS_PATH: &str = "db/function_version.json";
+const TRUSTS_PATH: &str = "db/oidc_trust.json";
+const BUILD_ARGS_PATH: &str = "db/build_arg.json";
+const LOCKS_PATH: &str = "db/deploy_lock.json";
+const CREDENTIALS_PATH: &str = "db/egress_credential.json";
+const SCHEDULES_PATH: &str = "db/schedule.json";
+const EVENT_SOURCES_PATH: &str = "db/event_source.json";
+const TOKENS_PATH: &str = "db/api_token.json";
+const DOMAINS_PATH: &str = "db/domain.json";
+const USAGE_PATH: &str = "db/usage.json";
+const AUDIT_LOG_PATH: &str = "db/audit_log.json";
+const PENDING_DEPLOYS_PATH: &str = "db/pending_deploy.json";
+const DEPLOYMENTS_PATH: &str = "db/deployment.json";
+const POOLS_PATH: &str = "redis/pools.json";
+
+/// Describes a backup archive.
+///
+/// The archive is a gzipped tarball holding this manifest as `backup.json`, the database
+/// rows as JSON under `db/`, every stored function archive under `artifacts/`, the
+/// archives of deploys waiting for approval under `pending/` and the autoscaler pool
+/// states as `redis/pools.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// RFC 3339 time of the backup
+    pub created_at: String,
+    /// Version of the controller that took the backup
+    pub invok_version: String,
+    /// Latest database migration the rows were read with
+    pub schema: String,
+    pub users: usize,
+    pub functions: usize,
+    pub versions: usize,
+    #[serde(default)]
+    pub trusts: usize,
+    #[serde(default)]
+    pub build_args: usize,
+    #[serde(default)]
+    pub locks: usize,
+    #[serde(default)]
+    pub credentials: usize,
+    #[serde(default)]
+    pub schedules: usize,
+    #[serde(default)]
+    pub event_sources: usize,
+    #[serde(default)]
+    pub tokens: usize,
+    #[serde(default)]
+    pub domains: usize,
+    #[serde(default)]
+    pub usage: usize,
+    #[serde(default)]
+    pub audit_log: usize,
+    #[serde(default)]
+    pub pending_deploys: usize,
+    #[serde(default)]
+    pub deployments: usize,
+    pub pools: usize,
+}
+
+/// What a restore brought back
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub users: usize,
+    pub functions: usize,
+    pub versions: usize,
+    pub trusts: usize,
+    pub build_args: usize,
+    pub locks: usize,
+    pub credentials: usize,
+    pub schedules: usize,
+    pub event_sources: usize,
+    pub tokens: usize,
+    pub domains: usize,
+    pub usage: usize,
+    pub audit_log: usize,
+    pub pending_deploys: usize,
+    pub deployments: usize,
+    /// Pools whose containers were still running and were taken over
+    pub pools_adopted: usize,
+    /// Pools without running containers; they start fresh on their next invocation
+    pub pools_skipped: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserRow {
+    id: i32,
+    email: String,
+    /// Argon2 hash, never the plain password
+    password: String,
+    uuid: Uuid,
+    #[serde(default)]
+    defaults: Option<serde_json::Value>,
+    /// Sealed with the server's `BUILD_ARGS_KEY`
+    #[serde(default)]
+    totp_secret: Option<String>,
+    #[serde(default)]
+    totp_enabled: bool,
+    /// SHA-256 hashes of the unused recovery codes
+    #[serde(default)]
+    recovery_codes: Option<serde_json::Value>,
+    /// Time step of the last TOTP code accepted
+    #[serde(default)]
+    totp_last_step: Option<i64>,
+    #[serde(default)]
+    notifications: Option<serde_json::Value>,
+    /// Emails of the accounts approving the namespace's deploys
+    #[serde(default)]
+    deploy_approvers: Option<serde_json::Value>,
+    #[serde(default)]
+    feature_flags: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FunctionRow {
+    id: i32,
+    name: String,
+    runtime: String,
+    uuid: Uuid,
+    auth_id: i32,
+    #[serde(default)]
+    settings: Option<serde_json::Value>,
+    /// RFC 3339 time the function was moved to the trash
+    #[serde(default)]
+    deleted_at: Option<String>,
+    #[serde(default)]
+    readme: Option<String>,
+    #[serde(default)]
+    openapi: Option<String>,
+    /// Function this row is a preview instance of
+    #[serde(default)]
+    preview_of: Option<String>,
+    /// RFC 3339 time a preview instance is removed
+    #[serde(default)]
+    expires_at: Option<String>,
+    /// Destinations the function may reach through the egress proxy
+    #[serde(default)]
+    egress_allowlist: Option<serde_json::Value>,
+    /// Rules routing matching invocations to a version of the function
+    #[serde(default)]
+    routing_rules: Option<serde_json::Value>,
+    /// Port published for a TCP or UDP service
+    #[serde(default)]
+    service_port: Option<i32>,
+    /// Dependency inventory of the latest deploy
+    #[serde(default)]
+    dependencies: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionRow {
+    id: i32,
+    function_id: i32,
+    version: i32,
+    #[serde(default)]
+    settings: Option<serde_json::Value>,
+    /// RFC 3339
+    created_at: String,
+    /// Path of the zipped source inside the backup archive
+    archive: String,
+    /// Where a version promoted from another namespace came from
+    #[serde(default)]
+    promoted_from: Option<serde_json::Value>,
+    /// Results of the tests run while building the version
+    #[serde(default)]
+    test_results: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TrustRow {
+    id: i32,
+    auth_id: i32,
+    repository: String,
+    #[serde(default)]
+    ref_pattern: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    /// RFC 3339
+    created_at: String,
+    #[serde(default)]
+    repository_owner_id: Option<String>,
+    #[serde(default)]
+    repository_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BuildArgRow {
+    id: i32,
+    auth_id: i32,
+    name: String,
+    /// Encrypted with the controller's build args key; restoring needs the same key
+    value: String,
+    /// RFC 3339
+    created_at: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CredentialRow {
+    id: i32,
+    auth_id: i32,
+    name: String,
+    base_url: String,
+    header: String,
+    /// Encrypted with the controller's build args key; restoring needs the same key
+    value: String,
+    /// RFC 3339
+    created_at: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockRow {
+    id: i32,
+    auth_id: i32,
+    /// Unset for a lock of the whole namespace
+    #[serde(default)]
+    function_name: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+    locked_by: String,
+    /// RFC 3339
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScheduleRow {
+    id: i32,
+    function_id: i32,
+    name: String,
+    cron: String,
+    timezone: String,
+    #[serde(default)]
+    payload: Option<serde_json::Value>,
+    enabled: bool,
+    /// RFC 3339
+    #[serde(default)]
+    last_run_at: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    next_run_at: Option<String>,
+    /// RFC 3339
+    created_at: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EventSourceRow {
+    id: i32,
+    function_id: i32,
+    name: String,
+    kind: String,
+    config: serde_json::Value,
+    enabled: bool,
+    /// RFC 3339
+    created_at: String,
+    /// RFC 3339
+    updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenRow {
+    id: i32,
+    auth_id: i32,
+    name: String,
+    prefix: String,
+    /// SHA-256 hash, never the token itself
+    token_hash: String,
+    scopes: serde_json::Value,
+    /// RFC 3339
+    #[serde(default)]
+    expires_at: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    last_used_at: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    revoked_at: Option<String>,
+    /// RFC 3339
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DomainRow {
+    id: i32,
+    auth_id: i32,
+    function_id: i32,
+    hostname: String,
+    verification_token: String,
+    /// RFC 3339
+    #[serde(default)]
+    verified_at: Option<String>,
+    /// RFC 3339
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UsageRow {
+    id: i32,
+    function_id: i32,
+    /// `YYYY-MM-DD`
+    day: String,
+    invocations: i64,
+    errors: i64,
+    duration_ms: i64,
+    cpu_ms: i64,
+    memory_mb_seconds: i64,
+    egress_bytes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditRow {
+    id: i32,
+    auth_id: i32,
+    actor: String,
+    action: String,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    details: Option<serde_json::Value>,
+    #[serde(default)]
+    source_ip: Option<String>,
+    /// RFC 3339
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingDeployRow {
+    id: i32,
+    auth_id: i32,
+    function_name: String,
+    /// Path of the zipped source inside the backup archive, unset once it was dropped
+    #[serde(default)]
+    archive: Option<String>,
+    force: bool,
+    requested_by: String,
+    status: String,
+    #[serde(default)]
+    decided_by: Option<String>,
+    /// RFC 3339
+    #[serde(default)]
+    decided_at: Option<String>,
+    /// RFC 3339
+    created_at: String,
+}
+
+/// A deploy run by a job runner. Its archive isn't kept: finished deploys have dropped
+/// it, and unfinished ones are restored as failed, since their jobs aren't backed up.
+#[derive(Debug, Serialize, Deserialize)]
+struct DeploymentRow {
+    id: i32,
+    auth_id: i32,
+    function_name: String,
+    #[serde(default)]
+    preview_of: Option<String>,
+    status: String,
+    #[serde(default)]
+    message: Option<String>,
+    /// RFC 3339
+    created_at: String,
+    /// RFC 3339
+    #[serde(default)]
+    finished_at: Option<String>,
+}
+
+/// Snapshots the database rows, the stored function archives and the autoscaler pool
+/// states into a versioned, gzipped tarball.
+///
+/// Pool states are taken from the running autoscaler, so they are as fresh as possible.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `autoscaler` - The running autoscaler.
+///
+/// # Returns
+///
+/// The archive bytes.
+pub async fn create_backup(
+    conn: &DatabaseConnection,
+    autoscaler: &Autoscaler,
+) -> ServelessCoreResult<Vec<u8>> {
+    let snapshot = BackupDBRepo::snapshot(conn).await.map_err(|e| {
+        error!("Failed to snapshot database: {}", e);
+        ServelessCoreError::SystemError("Failed to snapshot database".to_string())
+    })?;
+    let pools = autoscaler.snapshot_pool_states();
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        created_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+        invok_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema: current_schema(),
+        users: snapshot.users.len(),
+        functions: snapshot.functions.len(),
+        versions: snapshot.versions.len(),
+        trusts: snapshot.trusts.len(),
+        build_args: snapshot.build_args.len(),
+        locks: snapshot.locks.len(),
+        credentials: snapshot.credentials.len(),
+        schedules: snapshot.schedules.len(),
+        event_sources: snapshot.event_sources.len(),
+        tokens: snapshot.tokens.len(),
+        domains: snapshot.domains.len(),
+        usage: snapshot.usage.len(),
+        audit_log: snapshot.audit_log.len(),
+        pending_deploys: snapshot.pending_deploys.len(),
+        deployments: snapshot.deployments.len(),
+        pools: pools.len(),
+    };
+
+    let mut files = vec![
+        (MANIFEST_PATH.to_string(), to_json(&manifest)?),
+        (POOLS_PATH.to_string(), to_json(&pools)?),
+    ];
+    files.extend(snapshot_files(snapshot)?);
+
+    info!(
+        "Backup taken: {} users, {} functions, {} versions, {} pools",
+        manifest.users, manifest.functions, manifest.versions, manifest.pools
+    );
+    pack(files)
+        .map_err(|e| ServelessCoreError::SystemError(format!("Failed to build backup: {}", e)))
+}
+
+/// Replaces the control plane state with a backup.
+///
+/// The whole archive is read and checked before anything is changed. The database rows
+/// are then replaced in a single transaction, and finally the pool states are handed to
+/// the autoscaler, which takes over pools whose containers are still running.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `autoscaler` - The running autoscaler.
+/// * `archive` - An archive produced by [`create_backup`].
+/// * `max_unpacked_size` - Most bytes the archive may unpack to.
+///
+/// # Returns
+///
+/// What was restored.
+pub async fn restore_backup(
+    conn: &DatabaseConnection,
+    autoscaler: &Autoscaler,
+    archive: &[u8],
+    max_unpacked_size: usize,
+) -> ServelessCoreResult<RestoreReport> {
+    let mut files = unpack(archive, max_unpacked_size).map_err(invalid_backup)?;
+
+    let manifest: BackupManifest = read_json(&mut files, MANIFEST_PATH)?;
+    if manifest.format_version != BACKUP_FORMAT_VERSION {
+        return Err(invalid_backup(format!(
+            "unsupported format version {}",
+            manifest.format_version
+        )));
+    }
+    if !Migrator::migrations()
+        .iter()
+        .any(|migration| migration.name() == manifest.schema)
+    {
+        return Err(invalid_backup(format!(
+            "taken with a newer schema ({}) than this controller supports",
+            manifest.schema
+        )));
+    }
+
+    let pools: HashMap<String, PersistedPoolState> = read_json(&mut files, POOLS_PATH)?;
+    let snapshot = read_snapshot(&mut files)?;
+
+    let mut report = RestoreReport {
+        users: snapshot.users.len(),
+        functions: snapshot.functions.len(),
+        versions: snapshot.versions.len(),
+        trusts: snapshot.trusts.len(),
+        build_args: snapshot.build_args.len(),
+        locks: snapshot.locks.len(),
+        credentials: snapshot.credentials.len(),
+        schedules: snapshot.schedules.len(),
+        event_sources: snapshot.event_sources.len(),
+        tokens: snapshot.tokens.len(),
+        domains: snapshot.domains.len(),
+        usage: snapshot.usage.len(),
+        audit_log: snapshot.audit_log.len(),
+        pending_deploys: snapshot.pending_deploys.len(),
+        deployments: snapshot.deployments.len(),
+        pools_adopted: 0,
+        pools_skipped: 0,
+    };
+
+    BackupDBRepo::restore(conn, snapshot).await.map_err(|e| {
+        error!("Failed to restore database: {}", e);
+        ServelessCoreError::SystemError("Failed to restore database".to_string())
+    })?;
+
+    let total_pools = pools.len();
+    report.pools_adopted = autoscaler.restore_pool_states(pools).await;
+    report.pools_skipped = total_pools - report.pools_adopted;
+
+    info!(
+        "Restored backup from {} (invok {}): {} users, {} functions, {} versions, {}/{} pools adopted",
+        manifest.created_at,
+        manifest.invok_version,
+        report.users,
+        report.functions,
+        report.versions,
+        report.pools_adopted,
+        total_pools
+    );
+    Ok(report)
+}
+
+/// The rows of a database snapshot as backup archive files: JSON under `db/`, and the
+/// archives they hold under `artifacts/` and `pending/`
+fn snapshot_files(snapshot: DbSnapshot) -> ServelessCoreResult<Vec<(String, Vec<u8>)>> {
+    let users: Vec<UserRow> = snapshot
+        .users
+        .into_iter()
+        .map(|user| UserRow {
+            id: user.id,
+            email: user.email,
+            password: user.password,
+            uuid: user.uuid,
+            defaults: user.defaults,
+            totp_secret: user.totp_secret,
+            totp_enabled: user.totp_enabled,
+            recovery_codes: user.recovery_codes,
+            totp_last_step: user.totp_last_step,
+            notifications: user.notifications,
+            deploy_approvers: user.deploy_approvers,
+            feature_flags: user.feature_flags,
+        })
+        .collect();
+    let functions: Vec<FunctionRow> = snapshot
+        .functions
+        .into_iter()
+        .map(|function| FunctionRow {
+            id: function.id,
+            name: function.name,
+            runtime: function.runtime,
+            uuid: function.uuid,
+            auth_id: function.auth_id,
+            settings: function.settings,
+            deleted_at: function.deleted_at.map(|at| at.to_rfc3339()),
+            readme: function.readme,
+            openapi: function.openapi,
+            preview_of: function.preview_of,
+            expires_at: function.expires_at.map(|at| at.to_rfc3339()),
+            egress_allowlist: function.egress_allowlist,
+            routing_rules: function.routing_rules,
+            service_port: function.service_port,
+            dependencies: function.dependencies,
+        })
+        .collect();
+
+    let mut artifacts = Vec::with_capacity(snapshot.versions.len());
+    let versions: Vec<VersionRow> = snapshot
+        .versions
+        .into_iter()
+        .map(|version| {
+            let path = format!("artifacts/{}.zip", version.id);
+            artifacts.push((path.clone(), version.archive));
+            VersionRow {
+                id: version.id,
+                function_id: version.function_id,
+                version: version.version,
+                settings: version.settings,
+                created_at: version.created_at.to_rfc3339(),
+                archive: path,
+                promoted_from: version.promoted_from,
+                test_results: version.test_results,
+            }
+        })
+        .collect();
+    let trusts: Vec<TrustRow> = snapshot
+        .trusts
+        .into_iter()
+        .map(|trust| TrustRow {
+            id: trust.id,
+            auth_id: trust.auth_id,
+            repository: trust.repository,
+            ref_pattern: trust.ref_pattern,
+            environment: trust.environment,
+            created_at: trust.created_at.to_rfc3339(),
+            repository_owner_id: trust.repository_owner_id,
+            repository_id: trust.repository_id,
+        })
+        .collect();
+    let build_args: Vec<BuildArgRow> = snapshot
+        .build_args
+        .into_iter()
+        .map(|build_arg| BuildArgRow {
+            id: build_arg.id,
+            auth_id: build_arg.auth_id,
+            name: build_arg.name,
+            value: build_arg.value,
+            created_at: build_arg.created_at.to_rfc3339(),
+            updated_at: build_arg.updated_at.to_rfc3339(),
+        })
+        .collect();
+    let locks: Vec<LockRow> = snapshot
+        .locks
+        .into_iter()
+        .map(|lock| LockRow {
+            id: lock.id,
+            auth_id: lock.auth_id,
+            function_name: lock.function_name,
+            reason: lock.reason,
+            locked_by: lock.locked_by,
+            created_at: lock.created_at.to_rfc3339(),
+        })
+        .collect();
+    let credentials: Vec<CredentialRow> = snapshot
+        .credentials
+        .into_iter()
+        .map(|credential| CredentialRow {
+            id: credential.id,
+            auth_id: credential.auth_id,
+            name: credential.name,
+            base_url: credential.base_url,
+            header: credential.header,
+            value: credential.value,
+            created_at: credential.created_at.to_rfc3339(),
+            updated_at: credential.updated_at.to_rfc3339(),
+        })
+        .collect();
+    let schedules: Vec<ScheduleRow> = snapshot
+        .schedules
+        .into_iter()
+        .map(|schedule| ScheduleRow {
+            id: schedule.id,
+            function_id: schedule.function_id,
+            name: schedule.name,
+            cron: schedule.cron,
+            timezone: schedule.timezone,
+            payload: schedule.payload,
+            enabled: schedule.enabled,
+            last_run_at: schedule.last_run_at.map(|at| at.to_rfc3339()),
+            next_run_at: schedule.next_run_at.map(|at| at.to_rfc3339()),
+            created_at: schedule.created_at.to_rfc3339(),
+            updated_at: schedule.updated_at.to_rfc3339(),
+        })
+        .collect();
+    let event_sources: Vec<EventSourceRow> = snapshot
+        .event_sources
+        .into_iter()
+        .map(|source| EventSourceRow {
+            id: source.id,
+            function_id: source.function_id,
+            name: source.name,
+            kind: source.kind,
+            config: source.config,
+            enabled: source.enabled,
+            created_at: source.created_at.to_rfc3339(),
+            updated_at: source.updated_at.to_rfc3339(),
+        })
+        .collect();
+    let tokens: Vec<TokenRow> = snapshot
+        .tokens
+        .into_iter()
+        .map(|token| TokenRow {
+            id: token.id,
+            auth_id: token.auth_id,
+            name: token.name,
+            prefix: token.prefix,
+            token_hash: token.token_hash,
+            scopes: token.scopes,
+            expires_at: token.expires_at.map(|at| at.to_rfc3339()),
+            last_used_at: token.last_used_at.map(|at| at.to_rfc3339()),
+            revoked_at: token.revoked_at.map(|at| at.to_rfc3339()),
+            created_at: token.created_at.to_rfc3339(),
+        })
+        .collect();
+    let domains: Vec<DomainRow> = snapshot
+        .domains
+        .into_iter()
+        .map(|domain| DomainRow {
+            id: domain.id,
+            auth_id: domain.auth_id,
+            function_id: domain.function_id,
+            hostname: domain.hostname,
+            verification_token: domain.verification_token,
+            verified_at: domain.verified_at.map(|at| at.to_rfc3339()),
+            created_at: domain.created_at.to_rfc3339(),
+        })
+        .collect();
+    let usage: Vec<UsageRow> = snapshot
+        .usage
+        .into_iter()
+        .map(|usage| UsageRow {
+            id: usage.id,
+            function_id: usage.function_id,
+            day: usage.day.format("%Y-%m-%d").to_string(),
+            invocations: usage.invocations,
+            errors: usage.errors,
+            duration_ms: usage.duration_ms,
+            cpu_ms: usage.cpu_ms,
+            memory_mb_seconds: usage.memory_mb_seconds,
+            egress_bytes: usage.egress_bytes,
+        })
+        .collect();
+    let audit_log: Vec<AuditRow> = snapshot
+        .audit_log
+        .into_iter()
+        .map(|entry| AuditRow {
+            id: entry.id,
+            auth_id: entry.auth_id,
+            actor: entry.actor,
+            action: entry.action,
+            target: entry.target,
+            details: entry.details,
+            source_ip: entry.source_ip,
+            created_at: entry.created_at.to_rfc3339(),
+        })
+        .collect();
+    let pending_deploys: Vec<PendingDeployRow> = snapshot
+        .pending_deploys
+        .into_iter()
+        .map(|deploy| PendingDeployRow {
+            id: deploy.id,
+            auth_id: deploy.auth_id,
+            function_name: deploy.function_name,
+            archive: store_artifact(
+                &mut artifacts,
+                format!("pending/{}.zip", deploy.id),
+                deploy.archive,
+            ),
+            force: deploy.force,
+            requested_by: deploy.requested_by,
+            status: deploy.status,
+            decided_by: deploy.decided_by,
+            decided_at: deploy.decided_at.map(|at| at.to_rfc3339()),
+            created_at: deploy.created_at.to_rfc3339(),
+        })
+        .collect();
+    let deployments: Vec<DeploymentRow> = snapshot
+        .deployments
+        .into_iter()
+        .map(|deployment| DeploymentRow {
+            id: deployment.id,
+            auth_id: deployment.auth_id,
+            function_name: deployment.function_name,
+            preview_of: deployment.preview_of,
+            status: deployment.status,
+            message: deployment.message,
+            created_at: deployment.created_at.to_rfc3339(),
+            finished_at: deployment.finished_at.map(|at| at.to_rfc3339()),
+        })
+        .collect();
+
+    let files = vec![
+        (USERS_PATH.to_string(), to_json(&users)?),
+        (FUNCTIONS_PATH.to_string(), to_json(&functions)?),
+        (VERSIONS_PATH.to_string(), to_json(&versions)?),
+        (TRUSTS_PATH.to_string(), to_json(&trusts)?),
+        (BUILD_ARGS_PATH.to_string(), to_json(&build_args)?),
+        (LOCKS_PATH.to_string(), to_json(&locks)?),
+        (CREDENTIALS_PATH.to_string(), to_json(&credentials)?),
+        (SCHEDULES_PATH.to_string(), to_json(&schedules)?),
+        (EVENT_SOURCES_PATH.to_string(), to_json(&event_sources)?),
+        (TOKENS_PATH.to_string(), to_json(&tokens)?),
+        (DOMAINS_PATH.to_string(), to_json(&domains)?),
+        (USAGE_PATH.to_string(), to_json(&usage)?),
+        (AUDIT_LOG_PATH.to_string(), to_json(&audit_log)?),
+        (PENDING_DEPLOYS_PATH.to_string(), to_json(&pending_deploys)?),
+        (DEPLOYMENTS_PATH.to_string(), to_json(&deployments)?),
+    ];
+    Ok(files.into_iter().chain(artifacts).collect())
+}
+
+/// Reads the rows of a database snapshot back from the files of a backup archive
+fn read_snapshot(files: &mut HashMap<String, Vec<u8>>) -> ServelessCoreResult<DbSnapshot> {
+    let users: Vec<UserRow> = read_json(files, USERS_PATH)?;
+    let functions: Vec<FunctionRow> = read_json(files, FUNCTIONS_PATH)?;
+    let versions: Vec<VersionRow> = read_json(files, VERSIONS_PATH)?;
+    // Backups taken before a table existed have no file for it
+    let trusts: Vec<TrustRow> = read_optional_rows(files, TRUSTS_PATH)?;
+    let build_args: Vec<BuildArgRow> = read_optional_rows(files, BUILD_ARGS_PATH)?;
+    let locks: Vec<LockRow> = read_optional_rows(files, LOCKS_PATH)?;
+    let credentials: Vec<CredentialRow> = read_optional_rows(files, CREDENTIALS_PATH)?;
+    let schedules: Vec<ScheduleRow> = read_optional_rows(files, SCHEDULES_PATH)?;
+    let event_sources: Vec<EventSourceRow> = read_optional_rows(files, EVENT_SOURCES_PATH)?;
+    let tokens: Vec<TokenRow> = read_optional_rows(files, TOKENS_PATH)?;
+    let domains: Vec<DomainRow> = read_optional_rows(files, DOMAINS_PATH)?;
+    let usage: Vec<UsageRow> = read_optional_rows(files, USAGE_PATH)?;
+    let audit_log: Vec<AuditRow> = read_optional_rows(files, AUDIT_LOG_PATH)?;
+    let pending_deploys: Vec<PendingDeployRow> = read_optional_rows(files, PENDING_DEPLOYS_PATH)?;
+    let deployments: Vec<DeploymentRow> = read_optional_rows(files, DEPLOYMENTS_PATH)?;
+
+    Ok(DbSnapshot {
+        users: users
+            .into_iter()
+            .map(|user| auth::Model {
+                id: user.id,
+                email: user.email,
+                password: user.password,
+                uuid: user.uuid,
+                defaults: user.defaults,
+                totp_secret: user.totp_secret,
+                totp_enabled: user.totp_enabled,
+                recovery_codes: user.recovery_codes,
+                totp_last_step: user.totp_last_step,
+                notifications: user.notifications,
+                deploy_approvers: user.deploy_approvers,
+                feature_flags: user.feature_flags,
+                // Restored namespaces start awake and hibernate again if they stay idle
+                hibernated_at: None,
+            })
+            .collect(),
+        functions: functions
+            .into_iter()
+            .map(|function| {
+                let deleted_at = parse_optional_time(function.deleted_at, "deleted_at")?;
+                let expires_at = parse_optional_time(function.expires_at, "expires_at")?;
+                Ok(function::Model {
+                    id: function.id,
+                    name: function.name,
+                    runtime: function.runtime,
+                    uuid: function.uuid,
+                    auth_id: function.auth_id,
+                    settings: function.settings,
+                    deleted_at,
+                    readme: function.readme,
+                    openapi: function.openapi,
+                    preview_of: function.preview_of,
+                    expires_at,
+                    egress_allowlist: function.egress_allowlist,
+                    routing_rules: function.routing_rules,
+                    service_port: function.service_port,
+                    dependencies: function.dependencies,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        versions: versions
+            .into_iter()
+            .map(|version| {
+                let archive = files
+                    .remove(&version.archive)
+                    .ok_or_else(|| invalid_backup(format!("missing {}", version.archive)))?;
+                let created_at = DateTimeWithTimeZone::parse_from_rfc3339(&version.created_at)
+                    .map_err(|e| invalid_backup(format!("invalid created_at: {}", e)))?;
+                Ok(function_version::Model {
+                    id: version.id,
+                    function_id: version.function_id,
+                    version: version.version,
+                    archive,
+                    settings: version.settings,
+                    created_at,
+                    promoted_from: version.promoted_from,
+                    test_results: version.test_results,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        trusts: trusts
+            .into_iter()
+            .map(|trust| {
+                let created_at = DateTimeWithTimeZone::parse_from_rfc3339(&trust.created_at)
+                    .map_err(|e| invalid_backup(format!("invalid created_at: {}", e)))?;
+                Ok(oidc_trust::Model {
+                    id: trust.id,
+                    auth_id: trust.auth_id,
+                    repository: trust.repository,
+                    ref_pattern: trust.ref_pattern,
+                    environment: trust.environment,
+                    created_at,
+                    repository_owner_id: trust.repository_owner_id,
+                    repository_id: trust.repository_id,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        build_args: build_args
+            .into_iter()
+            .map(|build_arg| {
+                let created_at = DateTimeWithTimeZone::parse_from_rfc3339(&build_arg.created_at)
+                    .map_err(|e| invalid_backup(format!("invalid created_at: {}", e)))?;
+                let updated_at = DateTimeWithTimeZone::parse_from_rfc3339(&build_arg.updated_at)
+                    .map_err(|e| invalid_backup(format!("invalid updated_at: {}", e)))?;
+                Ok(build_arg::Model {
+                    id: build_arg.id,
+                    auth_id: build_arg.auth_id,
+                    name: build_arg.name,
+                    value: build_arg.value,
+                    created_at,
+                    updated_at,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        locks: locks
+            .into_iter()
+            .map(|lock| {
+                let created_at = DateTimeWithTimeZone::parse_from_rfc3339(&lock.created_at)
+                    .map_err(|e| invalid_backup(format!("invalid created_at: {}", e)))?;
+                Ok(deploy_lock::Model {
+                    id: lock.id,
+                    auth_id: lock.auth_id,
+                    function_name: lock.function_name,
+                    reason: lock.reason,
+                    locked_by: lock.locked_by,
+                    created_at,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        credentials: credentials
+            .into_iter()
+            .map(|credential| {
+                let created_at =
+                    DateTimeWithTimeZone::parse_from_rfc3339(&credential.created_at)
+                        .map_err(|e| invalid_backup(format!("invalid created_at: {}", e)))?;
+                let updated_at =
+                    DateTimeWithTimeZone::parse_from_rfc3339(&credential.updated_at)
+                        .map_err(|e| invalid_backup(format!("invalid updated_at: {}", e)))?;
+                Ok(egress_credential::Model {
+                    id: credential.id,
+                    auth_id: credential.auth_id,
+                    name: credential.name,
+                    base_url: credential.base_url,
+                    header: credential.header,
+                    value: credential.value,
+                    created_at,
+                    updated_at,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        schedules: schedules
+            .into_iter()
+            .map(|schedule| {
+                Ok(schedule::Model {
+                    id: schedule.id,
+                    function_id: schedule.function_id,
+                    name: schedule.name,
+                    cron: schedule.cron,
+                    timezone: schedule.timezone,
+                    payload: schedule.payload,
+                    enabled: schedule.enabled,
+                    last_run_at: parse_optional_time(schedule.last_run_at, "last_run_at")?,
+                    next_run_at: parse_optional_time(schedule.next_run_at, "next_run_at")?,
+                    created_at: parse_time(&schedule.created_at, "created_at")?,
+                    updated_at: parse_time(&schedule.updated_at, "updated_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        event_sources: event_sources
+            .into_iter()
+            .map(|source| {
+                Ok(event_source::Model {
+                    id: source.id,
+                    function_id: source.function_id,
+                    name: source.name,
+                    kind: source.kind,
+                    config: source.config,
+                    enabled: source.enabled,
+                    created_at: parse_time(&source.created_at, "created_at")?,
+                    updated_at: parse_time(&source.updated_at, "updated_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        tokens: tokens
+            .into_iter()
+            .map(|token| {
+                Ok(api_token::Model {
+                    id: token.id,
+                    auth_id: token.auth_id,
+                    name: token.name,
+                    prefix: token.prefix,
+                    token_hash: token.token_hash,
+                    scopes: token.scopes,
+                    expires_at: parse_optional_time(token.expires_at, "expires_at")?,
+                    last_used_at: parse_optional_time(token.last_used_at, "last_used_at")?,
+                    revoked_at: parse_optional_time(token.revoked_at, "revoked_at")?,
+                    created_at: parse_time(&token.created_at, "created_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        domains: domains
+            .into_iter()
+            .map(|domain| {
+                Ok(domain::Model {
+                    id: domain.id,
+                    auth_id: domain.auth_id,
+                    function_id: domain.function_id,
+                    hostname: domain.hostname,
+                    verification_token: domain.verification_token,
+                    verified_at: parse_optional_time(domain.verified_at, "verified_at")?,
+                    created_at: parse_time(&domain.created_at, "created_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        usage: usage
+            .into_iter()
+            .map(|usage| {
+                let day = Date::parse_from_str(&usage.day, "%Y-%m-%d")
+                    .map_err(|e| invalid_backup(format!("invalid day: {}", e)))?;
+                Ok(usage::Model {
+                    id: usage.id,
+                    function_id: usage.function_id,
+                    day,
+                    invocations: usage.invocations,
+                    errors: usage.errors,
+                    duration_ms: usage.duration_ms,
+                    cpu_ms: usage.cpu_ms,
+                    memory_mb_seconds: usage.memory_mb_seconds,
+                    egress_bytes: usage.egress_bytes,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        audit_log: audit_log
+            .into_iter()
+            .map(|entry| {
+                Ok(audit_log::Model {
+                    id: entry.id,
+                    auth_id: entry.auth_id,
+                    actor: entry.actor,
+                    action: entry.action,
+                    target: entry.target,
+                    details: entry.details,
+                    source_ip: entry.source_ip,
+                    created_at: parse_time(&entry.created_at, "created_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        pending_deploys: pending_deploys
+            .into_iter()
+            .map(|deploy| {
+                Ok(pending_deploy::Model {
+                    id: deploy.id,
+                    auth_id: deploy.auth_id,
+                    function_name: deploy.function_name,
+                    archive: read_artifact(files, deploy.archive)?,
+                    force: deploy.force,
+                    requested_by: deploy.requested_by,
+                    status: deploy.status,
+                    decided_by: deploy.decided_by,
+                    decided_at: parse_optional_time(deploy.decided_at, "decided_at")?,
+                    created_at: parse_time(&deploy.created_at, "created_at")?,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+        deployments: deployments
+            .into_iter()
+            .map(|deployment| {
+                let created_at = parse_time(&deployment.created_at, "created_at")?;
+                let finished_at = parse_optional_time(deployment.finished_at, "finished_at")?;
+                let (status, message, finished_at) = match finished_at {
+                    Some(_) => (deployment.status, deployment.message, finished_at),
+                    None => (
+                        DeploymentStatus::Failed.as_str().to_string(),
+                        Some("Interrupted by a backup restore".to_string()),
+                        Some(created_at),
+                    ),
+                };
+                Ok(deployment::Model {
+                    id: deployment.id,
+                    auth_id: deployment.auth_id,
+                    function_name: deployment.function_name,
+                    preview_of: deployment.preview_of,
+                    archive: Vec::new(),
+                    status,
+                    message,
+                    created_at,
+                    finished_at,
+                })
+            })
+            .collect::<ServelessCoreResult<_>>()?,
+    })
+}
+
+/// Name of the latest migration this controller knows about
+fn current_schema() -> String {
+    Migrator::migrations()
+        .last()
+        .map(|migration| migration.name().to_string())
+        .unwrap_or_default()
+}
+
+fn to_json<T: Serialize>(value: &T) -> ServelessCoreResult<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| ServelessCoreError::SystemError(e.to_string()))
+}
+
+fn read_json<T: DeserializeOwned>(
+    files: &mut HashMap<String, Vec<u8>>,
+    path: &str,
+) -> ServelessCoreResult<T> {
+    let content = files
+        .remove(path)
+        .ok_or_else(|| invalid_backup(format!("missing {}", path)))?;
+    serde_json::from_slice(&content).map_err(|e| invalid_backup(format!("{}: {}", path, e)))
+}
+
+/// Like [`read_json`], for lists of rows that backups taken before their table existed
+/// don't have
+fn read_optional_rows<T: DeserializeOwned>(
+    files: &mut HashMap<String, Vec<u8>>,
+    path: &str,
+) -> ServelessCoreResult<Vec<T>> {
+    if files.contains_key(path) {
+        read_json(files, path)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Queues `archive` to be stored at `path`, unless it's empty. Returns where it's stored.
+fn store_artifact(
+    artifacts: &mut Vec<(String, Vec<u8>)>,
+    path: String,
+    archive: Vec<u8>,
+) -> Option<String> {
+    if archive.is_empty() {
+        return None;
+    }
+    artifacts.push((path.clone(), archive));
+    Some(path)
+}
+
+/// Reads back an archive stored by [`store_artifact`]
+fn read_artifact(
+    files: &mut HashMap<String, Vec<u8>>,
+    path: Option<String>,
+) -> ServelessCoreResult<Vec<u8>> {
+    match path {
+        Some(path) => files
+            .remove(&path)
+            .ok_or_else(|| invalid_backup(format!("missing {}", path))),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_time(value: &str, field: &str) -> ServelessCoreResult<DateTimeWithTimeZone> {
+    DateTimeWithTimeZone::parse_from_rfc3339(value)
+        .map_err(|e| invalid_backup(format!("invalid {}: {}", field, e)))
+}
+
+fn parse_optional_time(
+    value: Option<String>,
+    field: &str,
+) -> ServelessCoreResult<Option<DateTimeWithTimeZone>> {
+    value
+        .map(|at| DateTimeWithTimeZone::parse_from_rfc3339(&at))
+        .transpose()
+        .map_err(|e| invalid_backup(format!("invalid {}: {}", field, e)))
+}
+
+fn invalid_backup(reason: String) -> ServelessCoreError {
+    ServelessCoreError::BadFunction(format!("Invalid backup archive: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(time: &str) -> DateTimeWithTimeZone {
+        DateTimeWithTimeZone::parse_from_rfc3339(time).unwrap()
+    }
+
+    fn sample_snapshot() -> DbSnapshot {
+        let created_at = at("2024-05-01T12:00:00+00:00");
+        DbSnapshot {
+            users: vec![auth::Model {
+                id: 1,
+                email: "dev@example.com".to_string(),
+                password: "$argon2id$hash".to_string(),
+                uuid: Uuid::new_v4(),
+                defaults: Some(json!({"memory": "256m"})),
+                totp_secret: None,
+                totp_enabled: false,
+                recovery_codes: None,
+                totp_last_step: None,
+                notifications: None,
+                deploy_approvers: Some(json!(["lead@example.com"])),
+                hibernated_at: None,
+                feature_flags: None,
+            }],
+            functions: vec![function::Model {
+                id: 2,
+                name: "hello".to_string(),
+                runtime: "go".to_string(),
+                uuid: Uuid::new_v4(),
+                auth_id: 1,
+                settings: None,
+                deleted_at: Some(at("2024-05-03T08:30:00+02:00")),
+                readme: Some("# hello".to_string()),
+                openapi: None,
+                preview_of: None,
+                expires_at: None,
+                egress_allowlist: Some(json!(["api.example.com"])),
+                routing_rules: None,
+                service_port: None,
+                dependencies: None,
+            }],
+            versions: vec![function_version::Model {
+                id: 3,
+                function_id: 2,
+                version: 1,
+                archive: vec![0x50, 0x4b, 0x03, 0x04, 0xff],
+                settings: None,
+                created_at,
+                promoted_from: None,
+                test_results: None,
+            }],
+            trusts: vec![oidc_trust::Model {
+                id: 4,
+                auth_id: 1,
+                repository: "acme/hello".to_string(),
+                ref_pattern: Some("refs/heads/main".to_string()),
+                environment: None,
+                created_at,
+                repository_owner_id: Some("1342004".to_string()),
+                repository_id: Some("75104123".to_string()),
+            }],
+            build_args: vec![build_arg::Model {
+                id: 5,
+                auth_id: 1,
+                name: "NPM_TOKEN".to_string(),
+                value: "encrypted".to_string(),
+                created_at,
+                updated_at: created_at,
+            }],
+            locks: vec![deploy_lock::Model {
+                id: 6,
+                auth_id: 1,
+                function_name: None,
+                reason: Some("freeze".to_string()),
+                locked_by: "dev@example.com".to_string(),
+                created_at,
+            }],
+            credentials: vec![egress_credential::Model {
+                id: 7,
+                auth_id: 1,
+                name: "stripe".to_string(),
+                base_url: "https://api.stripe.com".to_string(),
+                header: "Authorization".to_string(),
+                value: "encrypted".to_string(),
+                created_at,
+                updated_at: created_at,
+            }],
+            schedules: vec![schedule::Model {
+                id: 8,
+                function_id: 2,
+                name: "nightly".to_string(),
+                cron: "0 0 * * *".to_string(),
+                timezone: "Europe/Berlin".to_string(),
+                payload: Some(json!({"full": true})),
+                enabled: true,
+                last_run_at: None,
+                next_run_at: Some(created_at),
+                created_at,
+                updated_at: created_at,
+            }],
+            event_sources: vec![event_source::Model {
+                id: 9,
+                function_id: 2,
+                name: "orders".to_string(),
+                kind: "redis_stream".to_string(),
+                config: json!({"stream": "orders"}),
+                enabled: false,
+                created_at,
+                updated_at: created_at,
+            }],
+            tokens: vec![api_token::Model {
+                id: 10,
+                auth_id: 1,
+                name: "ci".to_string(),
+                prefix: "invk_ab12".to_string(),
+                token_hash: "sha256".to_string(),
+                scopes: json!(["deploy"]),
+                expires_at: Some(created_at),
+                last_used_at: None,
+                revoked_at: None,
+                created_at,
+            }],
+            domains: vec![domain::Model {
+                id: 11,
+                auth_id: 1,
+                function_id: 2,
+                hostname: "hello.example.com".to_string(),
+                verification_token: "token".to_string(),
+                verified_at: Some(created_at),
+                created_at,
+            }],
+            usage: vec![usage::Model {
+                id: 12,
+                function_id: 2,
+                day: Date::from_ymd_opt(2024, 5, 1).unwrap(),
+                invocations: 10,
+                errors: 1,
+                duration_ms: 1_200,
+                cpu_ms: 300,
+                memory_mb_seconds: 64,
+                egress_bytes: 4_096,
+            }],
+            audit_log: vec![audit_log::Model {
+                id: 13,
+                auth_id: 1,
+                actor: "dev@example.com".to_string(),
+                action: "function.deploy".to_string(),
+                target: Some("hello".to_string()),
+                details: Some(json!({"version": 1})),
+                source_ip: Some("203.0.113.7".to_string()),
+                created_at,
+            }],
+            pending_deploys: vec![
+                pending_deploy::Model {
+                    id: 14,
+                    auth_id: 1,
+                    function_name: "hello".to_string(),
+                    archive: vec![0x50, 0x4b, 0x05, 0x06],
+                    force: false,
+                    requested_by: "dev@example.com".to_string(),
+                    status: "pending".to_string(),
+                    decided_by: None,
+                    decided_at: None,
+                    created_at,
+                },
+                pending_deploy::Model {
+                    id: 15,
+                    auth_id: 1,
+                    function_name: "hello".to_string(),
+                    archive: Vec::new(),
+                    force: true,
+                    requested_by: "dev@example.com".to_string(),
+                    status: "rejected".to_string(),
+                    decided_by: Some("lead@example.com".to_string()),
+                    decided_at: Some(created_at),
+                    created_at,
+                },
+            ],
+            deployments: vec![deployment::Model {
+                id: 16,
+                auth_id: 1,
+                function_name: "hello".to_string(),
+                preview_of: None,
+                archive: Vec::new(),
+                status: "succeeded".to_string(),
+                message: Some("Deployed version 1".to_string()),
+                created_at,
+                finished_at: Some(at("2024-05-01T12:01:30+00:00")),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = sample_snapshot();
+        let mut files: HashMap<String, Vec<u8>> = snapshot_files(snapshot.clone())
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            files.get("artifacts/3.zip"),
+            Some(&snapshot.versions[0].archive)
+        );
+
+        assert_eq!(read_snapshot(&mut files).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_without_newer_tables() {
+        let snapshot = sample_snapshot();
+        let mut files: HashMap<String, Vec<u8>> = snapshot_files(snapshot.clone())
+            .unwrap()
+            .into_iter()
+            .collect();
+        for path in [
+            SCHEDULES_PATH,
+            EVENT_SOURCES_PATH,
+            TOKENS_PATH,
+            DOMAINS_PATH,
+            USAGE_PATH,
+            AUDIT_LOG_PATH,
+            PENDING_DEPLOYS_PATH,
+            DEPLOYMENTS_PATH,
+        ] {
+            files.remove(path);
+        }
+
+        let restored = read_snapshot(&mut files).unwrap();
+        assert_eq!(restored.users, snapshot.users);
+        assert!(restored.schedules.is_empty());
+        assert!(restored.usage.is_empty());
+    }
+
+    #[test]
+    fn test_unfinished_deployments_restore_as_failed() {
+        let mut snapshot = sample_snapshot();
+        snapshot.deployments[0].archive = vec![0x50, 0x4b];
+        snapshot.deployments[0].status = "running".to_string();
+        snapshot.deployments[0].finished_at = None;
+        let mut files: HashMap<String, Vec<u8>> =
+            snapshot_files(snapshot).unwrap().into_iter().collect();
+
+        let restored = read_snapshot(&mut files).unwrap();
+        let deployment = &restored.deployments[0];
+        assert_eq!(deployment.status, "failed");
+        assert!(deployment.archive.is_empty());
+        assert!(deployment.finished_at.is_some());
+    }
+
+    #[test]
+    fn test_snapshot_missing_artifact() {
+        let mut files: HashMap<String, Vec<u8>> = snapshot_files(sample_snapshot())
+            .unwrap()
+            .into_iter()
+            .collect();
+        files.remove("artifacts/3.zip");
+
+        assert!(read_snapshot(&mut files).is_err());
+    }
+}
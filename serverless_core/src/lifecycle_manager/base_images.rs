@@ -0,0 +1,157 @@
+use runtime::core::provisioning::prepull_image;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use templates::{go_template, nodejs_template, rust_template};
+use tracing::{error, info};
+
+/// Outcome of the last pull of a base image
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaseImageStatus {
+    pub image: String,
+    /// When the image was last pulled, RFC 3339
+    #[serde(default)]
+    pub pulled_at: Option<String>,
+    /// Why the last pull failed, if it did
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Base images of the runtimes, pulled ahead of builds so the first deploy on a fresh
+/// host doesn't wait minutes on them
+#[derive(Debug)]
+pub struct BaseImages {
+    images: Vec<String>,
+    mirror: Option<String>,
+    status: RwLock<HashMap<String, BaseImageStatus>>,
+    pulling: tokio::sync::Mutex<()>,
+}
+
+impl BaseImages {
+    /// The base images of the runtime templates and `extra`, pulled through `mirror` when
+    /// set
+    pub fn new(extra: &[String], mirror: Option<String>) -> Self {
+        let mut images = template_base_images();
+        for image in extra {
+            if !images.contains(image) {
+                images.push(image.clone());
+            }
+        }
+        Self {
+            images,
+            mirror,
+            status: RwLock::new(HashMap::new()),
+            pulling: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Pulls every base image, one at a time, keeping going past failures.
+    ///
+    /// Returns how many were pulled, or `None` when a pull of them is already running.
+    pub async fn pull_all(&self) -> Option<usize> {
+        let _pulling = self.pulling.try_lock().ok()?;
+        let mut pulled = 0;
+        for image in &self.images {
+            let result = prepull_image(image, self.mirror.as_deref()).await;
+            let mut status = self.status.write().unwrap();
+            let entry = status
+                .entry(image.clone())
+                .or_insert_with(|| BaseImageStatus {
+                    image: image.clone(),
+                    ..Default::default()
+                });
+            match result {
+                Ok(()) => {
+                    entry.pulled_at = Some(ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339());
+                    entry.error = None;
+                    pulled += 1;
+                }
+                Err(e) => {
+                    error!("Failed to pull base image '{}': {}", image, e);
+                    entry.error = Some(e.to_string());
+                }
+            }
+        }
+        Some(pulled)
+    }
+
+    /// The last pull of each base image, in the order they are pulled
+    pub fn status(&self) -> Vec<BaseImageStatus> {
+        let status = self.status.read().unwrap();
+        self.images
+            .iter()
+            .map(|image| {
+                status
+                    .get(image)
+                    .cloned()
+                    .unwrap_or_else(|| BaseImageStatus {
+                        image: image.clone(),
+                        ..Default::default()
+                    })
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> usize {
+        self.images.len()
+    }
+}
+
+/// Images the Dockerfiles of the runtime templates start from, in the order they appear
+pub fn template_base_images() -> Vec<String> {
+    let dockerfiles = [
+        go_template::DOCKERFILE_TEMPLATE,
+        go_template::PROMOTE_DOCKERFILE_TEMPLATE,
+        nodejs_template::DOCKERFILE_TEMPLATE,
+        nodejs_template::PROMOTE_DOCKERFILE_TEMPLATE,
+        rust_template::DOCKERFILE_TEMPLATE,
+        rust_template::PROMOTE_DOCKERFILE_TEMPLATE,
+    ];
+    let mut images: Vec<String> = Vec::new();
+    for line in dockerfiles.iter().flat_map(|dockerfile| dockerfile.lines()) {
+        let mut words = line.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|instruction| instruction.eq_ignore_ascii_case("FROM"))
+        {
+            continue;
+        }
+        // `FROM --platform=.. image AS stage`; placeholders are filled in per function
+        let Some(image) = words.find(|word| !word.starts_with("--")) else {
+            continue;
+        };
+        if image.contains("{{") || images.iter().any(|known| known == image) {
+            continue;
+        }
+        images.push(image.to_string());
+    }
+    images
+}
+
+/// Pulls the base images right away, then every `interval` to pick up moved tags; only
+/// once when `interval` is zero.
+///
+/// # Arguments
+///
+/// * `base_images` - The images to pull.
+/// * `interval` - Time between pulls.
+pub async fn run_base_image_refresh_loop(base_images: Arc<BaseImages>, interval: Duration) {
+    let mut ticker = (!interval.is_zero()).then(|| tokio::time::interval(interval));
+    loop {
+        if let Some(ticker) = ticker.as_mut() {
+            ticker.tick().await;
+        }
+        if let Some(pulled) = base_images.pull_all().await {
+            info!(
+                "Pulled {} of {} runtime base images",
+                pulled,
+                base_images.count()
+            );
+        }
+        if ticker.is_none() {
+            return;
+        }
+    }
+}
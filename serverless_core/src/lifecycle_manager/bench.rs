@@ -0,0 +1,355 @@
+use runtime::core::scaling_history::{ScalingAction, ScalingEvent};
+use serde::{Deserialize, Serialize};
+
+/// Samples a single load test may report
+pub const MAX_BENCH_SAMPLES: usize = 200_000;
+
+/// Request bodies of load test reports, sized for [`MAX_BENCH_SAMPLES`]
+pub const MAX_BENCH_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+/// Longest run a report covers, and longest latency it accepts
+pub const MAX_BENCH_DURATION_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Width of the windows the run is split into
+const WINDOW_MS: u64 = 1000;
+
+/// How far before and after a scaling event latencies are compared
+const IMPACT_SPAN_MS: u64 = 10_000;
+
+/// A load test run by `invok bench`, as measured by the client
+#[derive(Debug, Deserialize)]
+pub struct BenchRun {
+    /// When the first request was sent (unix milliseconds)
+    pub started_at_ms: u64,
+    /// `[sent_at_ms, latency_ms, status]` of each request, `sent_at_ms` relative to
+    /// `started_at_ms`. Requests that got no response have status 0.
+    pub samples: Vec<(u64, u64, u16)>,
+}
+
+impl BenchRun {
+    /// Checks the run is one a report can be built for: samples sent within
+    /// [`MAX_BENCH_DURATION_MS`] of its start, with latencies no longer than that, and
+    /// a start that offsets of scaling events can be taken from
+    pub fn validate(&self) -> Result<(), String> {
+        if i64::try_from(self.started_at_ms).is_err() {
+            return Err(format!("Invalid start time {}", self.started_at_ms));
+        }
+        let outside = self.samples.iter().find(|(sent_at, latency, _)| {
+            *sent_at > MAX_BENCH_DURATION_MS || *latency > MAX_BENCH_DURATION_MS
+        });
+        if let Some((sent_at, latency, _)) = outside {
+            return Err(format!(
+                "Request sent at {sent_at}ms taking {latency}ms is outside the run; runs \
+                 last at most {MAX_BENCH_DURATION_MS}ms"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Latency percentiles, in milliseconds
+#[derive(Debug, Serialize, Default, PartialEq)]
+pub struct Latencies {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// One second of the run
+#[derive(Debug, Serialize)]
+pub struct BenchWindow {
+    /// Seconds since the start of the run
+    pub second: u64,
+    pub requests: usize,
+    /// Requests answered with a 5xx or not answered at all
+    pub errors: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    /// Containers in the pool at the end of the window, when known
+    pub containers: Option<usize>,
+}
+
+/// A scaling event and the latencies around it
+#[derive(Debug, Serialize)]
+pub struct EventImpact {
+    /// Milliseconds since the start of the run; negative for the last event before it
+    pub offset_ms: i64,
+    #[serde(flatten)]
+    pub event: ScalingEvent,
+    /// 95th percentile latency of the requests sent shortly before the event
+    pub p95_before_ms: Option<u64>,
+    /// 95th percentile latency of the requests sent shortly after the event
+    pub p95_after_ms: Option<u64>,
+}
+
+/// Client latencies of a load test lined up against what the autoscaler did meanwhile
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub requests: usize,
+    pub errors: usize,
+    pub duration_ms: u64,
+    pub latency: Latencies,
+    pub windows: Vec<BenchWindow>,
+    /// Scaling events from the last one before the run to the end of the run
+    pub impacts: Vec<EventImpact>,
+    /// Most containers the pool had during the run
+    pub peak_containers: Option<usize>,
+    /// Seconds from the start of the run until the pool stopped growing
+    pub settled_after_secs: Option<u64>,
+}
+
+/// Build the report of a load test.
+///
+/// Samples past [`MAX_BENCH_DURATION_MS`] are left out of the windows; runs are meant to
+/// be [validated](BenchRun::validate) first.
+///
+/// # Arguments
+///
+/// * `run` - The latencies measured by the client.
+/// * `events` - The scaling events of the function, oldest first. The last event before
+///   the run, if any, gives the pool's size when it started.
+pub fn build_report(run: &BenchRun, events: &[ScalingEvent]) -> BenchReport {
+    let duration_ms = run
+        .samples
+        .iter()
+        .map(|(sent_at, latency, _)| sent_at.saturating_add(*latency))
+        .max()
+        .unwrap_or(0);
+    let end_ms = run.started_at_ms.saturating_add(duration_ms);
+
+    let first_in_run = events
+        .iter()
+        .position(|event| event.at_ms >= run.started_at_ms)
+        .unwrap_or(events.len());
+    let relevant = &events[first_in_run.saturating_sub(1)..];
+
+    let window_count = duration_ms
+        .min(2 * MAX_BENCH_DURATION_MS)
+        .div_ceil(WINDOW_MS);
+    let mut by_window: Vec<Vec<(u64, u16)>> = vec![Vec::new(); window_count as usize];
+    for (sent_at, latency, status) in &run.samples {
+        if let Some(window) = by_window.get_mut((sent_at / WINDOW_MS) as usize) {
+            window.push((*latency, *status));
+        }
+    }
+    let windows = by_window
+        .into_iter()
+        .zip(0..)
+        .map(|(window, second)| {
+            let latencies = percentiles(window.iter().map(|(latency, _)| *latency));
+            BenchWindow {
+                second,
+                requests: window.len(),
+                errors: window
+                    .iter()
+                    .filter(|(_, status)| is_error(*status))
+                    .count(),
+                p50_ms: latencies.p50,
+                p95_ms: latencies.p95,
+                containers: containers_at(
+                    relevant,
+                    run.started_at_ms.saturating_add((second + 1) * WINDOW_MS),
+                ),
+            }
+        })
+        .collect();
+
+    let impacts = relevant
+        .iter()
+        .filter(|event| event.at_ms <= end_ms)
+        .map(|event| {
+            let offset_ms = offset_from(run.started_at_ms, event.at_ms);
+            let sent_between = |from: i64, to: i64| {
+                let latencies: Vec<u64> = run
+                    .samples
+                    .iter()
+                    .filter(|(sent_at, _, _)| {
+                        i64::try_from(*sent_at).is_ok_and(|sent_at| (from..to).contains(&sent_at))
+                    })
+                    .map(|(_, latency, _)| *latency)
+                    .collect();
+                (!latencies.is_empty()).then(|| percentiles(latencies).p95)
+            };
+            let span = IMPACT_SPAN_MS as i64;
+            EventImpact {
+                offset_ms,
+                event: event.clone(),
+                p95_before_ms: sent_between(offset_ms.saturating_sub(span), offset_ms),
+                p95_after_ms: sent_between(offset_ms, offset_ms.saturating_add(span)),
+            }
+        })
+        .collect();
+
+    let peak_containers = relevant
+        .iter()
+        .filter(|event| event.at_ms <= end_ms)
+        .map(|event| event.containers)
+        .max();
+    let settled_after_secs = relevant
+        .iter()
+        .filter(|event| event.at_ms >= run.started_at_ms && event.at_ms <= end_ms)
+        .rfind(|event| {
+            matches!(
+                event.action,
+                ScalingAction::ScaledUp | ScalingAction::Unpaused
+            )
+        })
+        .map(|event| (event.at_ms - run.started_at_ms).div_ceil(1000));
+
+    BenchReport {
+        requests: run.samples.len(),
+        errors: run
+            .samples
+            .iter()
+            .filter(|(_, _, status)| is_error(*status))
+            .count(),
+        duration_ms,
+        latency: percentiles(run.samples.iter().map(|(_, latency, _)| *latency)),
+        windows,
+        impacts,
+        peak_containers,
+        settled_after_secs,
+    }
+}
+
+/// Milliseconds from `start_ms` to `at_ms`, negative when `at_ms` is earlier
+fn offset_from(start_ms: u64, at_ms: u64) -> i64 {
+    let offset = i128::from(at_ms) - i128::from(start_ms);
+    offset.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+}
+
+fn is_error(status: u16) -> bool {
+    status == 0 || status >= 500
+}
+
+/// Containers in the pool at `at_ms`, after the last event before it
+fn containers_at(events: &[ScalingEvent], at_ms: u64) -> Option<usize> {
+    events
+        .iter()
+        .take_while(|event| event.at_ms < at_ms)
+        .last()
+        .map(|event| event.containers)
+}
+
+fn percentiles(latencies: impl IntoIterator<Item = u64>) -> Latencies {
+    let mut latencies: Vec<u64> = latencies.into_iter().collect();
+    if latencies.is_empty() {
+        return Latencies::default();
+    }
+    latencies.sort_unstable();
+    let at = |quantile: f64| {
+        let rank = (quantile * latencies.len() as f64).ceil() as usize;
+        latencies[rank.clamp(1, latencies.len()) - 1]
+    };
+    Latencies {
+        p50: at(0.50),
+        p95: at(0.95),
+        p99: at(0.99),
+        max: latencies[latencies.len() - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(at_ms: u64, action: ScalingAction, containers: usize) -> ScalingEvent {
+        ScalingEvent {
+            at_ms,
+            action,
+            containers,
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn test_build_report_windows_and_impacts() {
+        let run = BenchRun {
+            started_at_ms: 10_000,
+            samples: vec![
+                (0, 100, 200),
+                (500, 300, 200),
+                (1_200, 50, 503),
+                (2_100, 0, 0),
+            ],
+        };
+        let events = vec![
+            event(5_000, ScalingAction::ScaledUp, 1),
+            event(11_000, ScalingAction::ScaledUp, 2),
+        ];
+        let report = build_report(&run, &events);
+
+        assert_eq!(report.requests, 4);
+        assert_eq!(report.errors, 2);
+        assert_eq!(report.duration_ms, 2_100);
+        assert_eq!(report.windows.len(), 3);
+        assert_eq!(report.windows[0].requests, 2);
+        assert_eq!(report.windows[0].containers, Some(1));
+        assert_eq!(report.windows[1].errors, 1);
+        assert_eq!(report.windows[1].containers, Some(2));
+        assert_eq!(report.impacts.len(), 2);
+        assert_eq!(report.impacts[0].offset_ms, -5_000);
+        assert_eq!(report.impacts[1].offset_ms, 1_000);
+        assert_eq!(report.impacts[1].p95_before_ms, Some(300));
+        assert_eq!(report.impacts[1].p95_after_ms, Some(50));
+        assert_eq!(report.peak_containers, Some(2));
+        assert_eq!(report.settled_after_secs, Some(1));
+    }
+
+    #[test]
+    fn test_build_report_does_not_overflow() {
+        let run = BenchRun {
+            started_at_ms: u64::MAX - 10,
+            samples: vec![(u64::MAX, u64::MAX, 200), (0, 5, 200)],
+        };
+        let events = vec![event(0, ScalingAction::ScaledUp, 1)];
+        let report = build_report(&run, &events);
+
+        assert_eq!(report.duration_ms, u64::MAX);
+        assert_eq!(
+            report.windows.len() as u64,
+            (2 * MAX_BENCH_DURATION_MS).div_ceil(WINDOW_MS)
+        );
+        assert_eq!(report.windows[0].requests, 1);
+        assert_eq!(report.impacts[0].offset_ms, i64::MIN);
+    }
+
+    #[test]
+    fn test_validate_rejects_samples_outside_the_run() {
+        let mut run = BenchRun {
+            started_at_ms: 1_700_000_000_000,
+            samples: vec![
+                (0, 10, 200),
+                (MAX_BENCH_DURATION_MS, MAX_BENCH_DURATION_MS, 200),
+            ],
+        };
+        assert!(run.validate().is_ok());
+
+        run.samples.push((MAX_BENCH_DURATION_MS + 1, 10, 200));
+        assert!(run.validate().is_err());
+
+        run.samples.pop();
+        run.samples.push((0, MAX_BENCH_DURATION_MS + 1, 200));
+        assert!(run.validate().is_err());
+
+        run.samples.pop();
+        run.started_at_ms = u64::MAX;
+        assert!(run.validate().is_err());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert_eq!(percentiles(Vec::new()), Latencies::default());
+        let latencies = percentiles((1..=100).rev());
+        assert_eq!(
+            latencies,
+            Latencies {
+                p50: 50,
+                p95: 95,
+                p99: 99,
+                max: 100,
+            }
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::db::build_arg::BuildArgDBRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use tracing::error;
+
+/// Most build args a namespace can have
+pub const MAX_BUILD_ARGS: usize = 32;
+
+/// Longest build arg value, in bytes
+const MAX_VALUE_LENGTH: usize = 4096;
+
+/// Longest build arg name
+const MAX_NAME_LENGTH: usize = 128;
+
+/// Build args the image builder sets itself, see `Platform::build_args`
+const RESERVED_NAMES: &[&str] = &[
+    "BUILDPLATFORM",
+    "BUILDOS",
+    "BUILDARCH",
+    "TARGETPLATFORM",
+    "TARGETOS",
+    "TARGETARCH",
+    "TARGETVARIANT",
+];
+
+/// Prefix of sealed values, naming the scheme they were sealed with
+const SEALED_PREFIX: &str = "v1:";
+
+/// Encrypts build args at rest with AES-256-GCM.
+///
+/// Each value is sealed with a random nonce and bound to its namespace and name, so a
+/// sealed value copied to another row doesn't decrypt.
+pub struct BuildArgCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl BuildArgCipher {
+    /// Creates the cipher from a base64-encoded 32-byte key
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let key = BASE64
+            .decode(key.trim())
+            .map_err(|e| format!("not valid base64: {}", e))?;
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .map_err(|_| format!("must decode to 32 bytes, got {}", key.len()))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Encrypts the value of build arg `name` of the namespace `auth_id`
+    pub fn seal(&self, auth_id: i32, name: &str, value: &str) -> ServelessCoreResult<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| ServelessCoreError::SystemError("Failed to generate a nonce".into()))?;
+
+        let mut sealed = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(associated_data(auth_id, name)),
+                &mut sealed,
+            )
+            .map_err(|_| ServelessCoreError::SystemError("Failed to encrypt build arg".into()))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend(sealed);
+        Ok(format!("{}{}", SEALED_PREFIX, BASE64.encode(payload)))
+    }
+
+    /// Decrypts a value sealed with [`BuildArgCipher::seal`]
+    pub fn open(&self, auth_id: i32, name: &str, sealed: &str) -> ServelessCoreResult<String> {
+        let undecryptable = || {
+            ServelessCoreError::SystemError(format!(
+                "Failed to decrypt build arg '{}'; was BUILD_ARGS_KEY changed?",
+                name
+            ))
+        };
+        let payload = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(|sealed| BASE64.decode(sealed).ok())
+            .filter(|payload| payload.len() >= NONCE_LEN)
+            .ok_or_else(undecryptable)?;
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| undecryptable())?;
+        let mut ciphertext = ciphertext.to_vec();
+        let value = self
+            .key
+            .open_in_place(
+                nonce,
+                Aad::from(associated_data(auth_id, name)),
+                &mut ciphertext,
+            )
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(value.to_vec()).map_err(|_| undecryptable())
+    }
+}
+
+fn associated_data(auth_id: i32, name: &str) -> Vec<u8> {
+    format!("{}:{}", auth_id, name).into_bytes()
+}
+
+/// Checks a build arg can be declared with `ARG` and passed to the image build
+pub fn validate_build_arg(name: &str, value: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid_name = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        return Err(format!(
+            "'{}' is not a valid name: use letters, digits and '_', not starting with a digit",
+            name
+        ));
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "name is too long (max {} characters)",
+            MAX_NAME_LENGTH
+        ));
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(format!("'{}' is set by the image builder", name));
+    }
+    if value.len() > MAX_VALUE_LENGTH {
+        return Err(format!(
+            "value is too long (max {} bytes)",
+            MAX_VALUE_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// Decrypts the build args of a namespace for an image build.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cipher` - Decrypts the values; `None` when build args aren't configured.
+/// * `auth_id` - The user whose namespace is being built.
+///
+/// # Returns
+///
+/// The build args by name, or an error when the namespace has build args that can't be
+/// decrypted.
+pub async fn namespace_build_args(
+    conn: &DatabaseConnection,
+    cipher: Option<&BuildArgCipher>,
+    auth_id: i32,
+) -> ServelessCoreResult<HashMap<String, String>> {
+    let build_args = BuildArgDBRepo::find_by_user(conn, auth_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to load build args: {}", e);
+            ServelessCoreError::SystemError("Failed to load build args".to_string())
+        })?;
+    if build_args.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let cipher = cipher.ok_or_else(|| {
+        ServelessCoreError::SystemError(
+            "The namespace has build args but BUILD_ARGS_KEY is not set".to_string(),
+        )
+    })?;
+    build_args
+        .into_iter()
+        .map(|build_arg| {
+            let value = cipher.open(auth_id, &build_arg.name, &build_arg.value)?;
+            Ok((build_arg.name, value))
+        })
+        .collect()
+}
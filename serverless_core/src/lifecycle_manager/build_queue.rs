@@ -0,0 +1,430 @@
+use crate::lifecycle_manager::manifest_list::push_manifest_list;
+use crate::lifecycle_manager::test_gate::TestResults;
+use runtime::core::platform::Platform;
+use runtime::core::provisioning::{
+    build_from_context_with_log, push_image, remote_repository, remove_image, RegistryAuth,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How long finished builds can still be looked up
+const FINISHED_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Header carrying the build args of a submitted build, as base64-encoded JSON.
+///
+/// Sent as a header rather than in the query so the values stay out of access logs.
+pub const BUILD_ARGS_HEADER: &str = "x-invok-build-args";
+
+/// Why a build could not be submitted
+#[derive(Debug, Error)]
+pub enum BuildQueueError {
+    /// The namespace already has as many builds queued or running as it may
+    #[error("Build quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("Invalid build: {0}")]
+    InvalidBuild(String),
+}
+
+/// Where a build is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl BuildStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, BuildStatus::Succeeded | BuildStatus::Failed)
+    }
+}
+
+/// Step of a build that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStep {
+    /// Building the image from the function's Dockerfile
+    Build,
+    /// Pushing the built image to the registry
+    Push,
+}
+
+/// A build submitted to the builder service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildJob {
+    pub id: Uuid,
+    /// Name of the image, the function key
+    pub image: String,
+    /// Namespace the function belongs to; quotas are counted per namespace
+    pub namespace: String,
+    /// Platforms to build for besides the builder daemon's own
+    pub platforms: Vec<String>,
+    pub status: BuildStatus,
+    /// Why the build failed
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The step the build failed at
+    #[serde(default)]
+    pub failed_step: Option<BuildStep>,
+    /// References of the pushed manifest list, followed by those of the per-platform
+    /// images it lists
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// Results of the function's tests, when the Dockerfile ran them; the controller
+    /// fills in the command
+    #[serde(default)]
+    pub tests: Option<TestResults>,
+    /// Seconds since the Unix epoch
+    pub submitted_at: u64,
+}
+
+struct QueuedJob {
+    job: BuildJob,
+    /// Set once the build finished
+    finished_at: Option<Instant>,
+}
+
+/// Queue of image builds, run a few at a time and pushed to the registry.
+///
+/// Backs the builder service. Each namespace may only have a limited number of builds
+/// queued or running, so one busy namespace can't starve the others.
+pub struct BuildQueue {
+    jobs: RwLock<HashMap<Uuid, QueuedJob>>,
+    slots: Arc<Semaphore>,
+    namespace_quota: usize,
+    timeout: Duration,
+    registry: String,
+    registry_auth: RegistryAuth,
+    /// Pushes manifest lists to the registry
+    client: reqwest::Client,
+}
+
+impl BuildQueue {
+    pub fn new(
+        concurrency: usize,
+        namespace_quota: usize,
+        timeout: Duration,
+        registry: String,
+        registry_auth: RegistryAuth,
+    ) -> Self {
+        Self {
+            jobs: RwLock::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(concurrency)),
+            namespace_quota,
+            timeout,
+            registry,
+            registry_auth,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Queues a build of `build_context`, a tar archive holding the `Dockerfile`.
+    ///
+    /// `build_args` are passed to the build and never kept with the job.
+    ///
+    /// # Returns
+    ///
+    /// The queued build, to be looked up with [`BuildQueue::get`].
+    pub fn submit(
+        self: &Arc<Self>,
+        image: String,
+        namespace: String,
+        platforms: Vec<Platform>,
+        build_args: HashMap<String, String>,
+        build_context: Vec<u8>,
+    ) -> Result<BuildJob, BuildQueueError> {
+        if image.is_empty() || namespace.is_empty() {
+            return Err(BuildQueueError::InvalidBuild(
+                "image and namespace are required".to_string(),
+            ));
+        }
+        if build_context.is_empty() {
+            return Err(BuildQueueError::InvalidBuild(
+                "the build context is empty".to_string(),
+            ));
+        }
+
+        let job = BuildJob {
+            id: Uuid::new_v4(),
+            image,
+            namespace,
+            platforms: platforms.iter().map(Platform::to_string).collect(),
+            status: BuildStatus::Queued,
+            error: None,
+            failed_step: None,
+            references: Vec::new(),
+            tests: None,
+            submitted_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        {
+            let mut jobs = self.jobs.write().unwrap();
+            jobs.retain(|_, queued| {
+                queued
+                    .finished_at
+                    .is_none_or(|finished_at| finished_at.elapsed() < FINISHED_RETENTION)
+            });
+            let pending = jobs
+                .values()
+                .filter(|queued| {
+                    queued.job.namespace == job.namespace && !queued.job.status.is_finished()
+                })
+                .count();
+            if pending >= self.namespace_quota {
+                return Err(BuildQueueError::QuotaExceeded(format!(
+                    "namespace '{}' already has {} builds queued or running",
+                    job.namespace, pending
+                )));
+            }
+            jobs.insert(
+                job.id,
+                QueuedJob {
+                    job: job.clone(),
+                    finished_at: None,
+                },
+            );
+        }
+
+        info!(
+            "Build {} of '{}' queued for namespace '{}'",
+            job.id, job.image, job.namespace
+        );
+        let queue = self.clone();
+        let id = job.id;
+        tokio::spawn(async move { queue.run(id, platforms, build_args, build_context).await });
+        Ok(job)
+    }
+
+    /// Looks up a build queued within the last hour
+    pub fn get(&self, id: Uuid) -> Option<BuildJob> {
+        self.jobs
+            .read()
+            .unwrap()
+            .get(&id)
+            .map(|queued| queued.job.clone())
+    }
+
+    /// Builds queued or running, across namespaces
+    pub fn pending(&self) -> usize {
+        self.jobs
+            .read()
+            .unwrap()
+            .values()
+            .filter(|queued| !queued.job.status.is_finished())
+            .count()
+    }
+
+    async fn run(
+        self: Arc<Self>,
+        id: Uuid,
+        platforms: Vec<Platform>,
+        build_args: HashMap<String, String>,
+        build_context: Vec<u8>,
+    ) {
+        let image = match self.get(id) {
+            Some(job) => job.image,
+            None => return,
+        };
+        let started = Instant::now();
+        let mut log = Vec::new();
+        let mut step = BuildStep::Build;
+        let result = tokio::time::timeout(self.timeout, async {
+            let _slot = self
+                .slots
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|e| e.to_string())?;
+            self.update(id, |job| job.status = BuildStatus::Running);
+
+            let built = build_from_context_with_log(
+                build_context,
+                &image,
+                &platforms,
+                &build_args,
+                &mut log,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            step = BuildStep::Push;
+            let pushed = push_image(&image, &built, &self.registry, &self.registry_auth)
+                .await
+                .map_err(|e| format!("pushing to {} failed: {}", self.registry, e))?;
+            let list = push_manifest_list(
+                &self.client,
+                &image,
+                &self.registry,
+                &self.registry_auth,
+                &pushed,
+            )
+            .await
+            .map_err(|e| {
+                format!(
+                    "pushing the manifest list to {} failed: {}",
+                    self.registry, e
+                )
+            })?;
+            let mut references = vec![list];
+            references.extend(pushed.into_iter().map(|image| image.reference));
+            Ok::<_, String>(references)
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(format!(
+                "build did not finish within {} s",
+                self.timeout.as_secs()
+            ))
+        });
+
+        // The registry holds the image once pushed, and a failed build is of no use:
+        // the builder keeps neither the image nor the tags it pushed under
+        for repository in [image.clone(), remote_repository(&image, &self.registry)] {
+            if let Err(e) = remove_image(&repository).await {
+                warn!(
+                    "Failed to remove image '{}' after build {}: {}",
+                    repository, id, e
+                );
+            }
+        }
+
+        let tests = TestResults::from_build_log(&log);
+        match result {
+            Ok(references) => {
+                info!(
+                    "Build {} of '{}' pushed in {:.1} s",
+                    id,
+                    image,
+                    started.elapsed().as_secs_f64()
+                );
+                self.update(id, |job| {
+                    job.status = BuildStatus::Succeeded;
+                    job.references = references;
+                    job.tests = tests;
+                });
+            }
+            Err(e) => {
+                error!("Build {} of '{}' failed: {}", id, image, e);
+                self.update(id, |job| {
+                    job.status = BuildStatus::Failed;
+                    job.error = Some(e);
+                    job.failed_step = Some(step);
+                    job.tests = tests;
+                });
+            }
+        }
+    }
+
+    fn update(&self, id: Uuid, change: impl FnOnce(&mut BuildJob)) {
+        if let Some(queued) = self.jobs.write().unwrap().get_mut(&id) {
+            change(&mut queued.job);
+            if queued.job.status.is_finished() {
+                queued.finished_at = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A queue that never starts its builds, so they stay queued
+    fn test_queue(namespace_quota: usize) -> Arc<BuildQueue> {
+        Arc::new(BuildQueue::new(
+            0,
+            namespace_quota,
+            Duration::from_secs(3600),
+            "registry.internal:5000/invok".to_string(),
+            RegistryAuth::default(),
+        ))
+    }
+
+    fn submit(queue: &Arc<BuildQueue>, namespace: &str) -> Result<BuildJob, BuildQueueError> {
+        queue.submit(
+            "app".to_string(),
+            namespace.to_string(),
+            Vec::new(),
+            HashMap::new(),
+            b"context".to_vec(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_invalid_builds() {
+        let queue = test_queue(1);
+        let empty_image = queue.submit(
+            String::new(),
+            "ns".to_string(),
+            Vec::new(),
+            HashMap::new(),
+            b"context".to_vec(),
+        );
+        assert!(matches!(empty_image, Err(BuildQueueError::InvalidBuild(_))));
+        let empty_context = queue.submit(
+            "app".to_string(),
+            "ns".to_string(),
+            Vec::new(),
+            HashMap::new(),
+            Vec::new(),
+        );
+        assert!(matches!(
+            empty_context,
+            Err(BuildQueueError::InvalidBuild(_))
+        ));
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_namespace_quota() {
+        let queue = test_queue(2);
+        let first = submit(&queue, "ns").unwrap();
+        assert_eq!(first.status, BuildStatus::Queued);
+        assert_eq!(queue.get(first.id).unwrap().id, first.id);
+        submit(&queue, "ns").unwrap();
+        assert!(matches!(
+            submit(&queue, "ns"),
+            Err(BuildQueueError::QuotaExceeded(_))
+        ));
+        // Other namespaces have their own quota
+        submit(&queue, "other").unwrap();
+        assert_eq!(queue.pending(), 3);
+
+        // Finished builds don't count
+        queue.update(first.id, |job| job.status = BuildStatus::Failed);
+        assert!(queue.jobs.read().unwrap()[&first.id].finished_at.is_some());
+        assert_eq!(queue.pending(), 2);
+        submit(&queue, "ns").unwrap();
+    }
+
+    #[test]
+    fn test_failed_step_defaults_to_none() {
+        let job: BuildJob = serde_json::from_value(serde_json::json!({
+            "id": Uuid::nil(),
+            "image": "app",
+            "namespace": "ns",
+            "platforms": [],
+            "status": "failed",
+            "error": "boom",
+            "submitted_at": 0
+        }))
+        .unwrap();
+        assert_eq!(job.failed_step, None);
+
+        let job = serde_json::to_value(BuildJob {
+            failed_step: Some(BuildStep::Push),
+            ..job
+        })
+        .unwrap();
+        assert_eq!(job["failed_step"], "push");
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Buckets kept before idle (full) ones are dropped
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Limits how many cold starts a single client IP and a single namespace may trigger.
+///
+/// Each source gets a token bucket holding a minute's worth of budget that refills
+/// continuously. Invocations served by a running container don't consume budget, so
+/// normal traffic to warm functions is never throttled.
+#[derive(Debug)]
+pub struct ColdStartBudget {
+    per_source: u32,
+    per_namespace: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl ColdStartBudget {
+    /// Budgets are cold starts per minute; 0 disables that limit
+    pub fn new(per_source: u32, per_namespace: u32) -> Self {
+        Self {
+            per_source,
+            per_namespace,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one cold start from the client's and the namespace's budget.
+    ///
+    /// Nothing is consumed unless both have budget left. On rejection, returns how long
+    /// until the exhausted budget allows another cold start.
+    pub fn try_consume(&self, client_ip: Option<IpAddr>, namespace: Uuid) -> Result<(), Duration> {
+        let mut limits = Vec::with_capacity(2);
+        if let (Some(ip), true) = (client_ip, self.per_source > 0) {
+            limits.push((format!("ip:{}", ip), self.per_source));
+        }
+        if self.per_namespace > 0 {
+            limits.push((format!("ns:{}", namespace), self.per_namespace));
+        }
+        if limits.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_TRACKED_BUCKETS {
+            // A bucket untouched for a minute has refilled, so forgetting it changes nothing
+            buckets.retain(|_, bucket| {
+                now.duration_since(bucket.updated_at) < Duration::from_secs(60)
+            });
+        }
+
+        let mut retry_after = Duration::ZERO;
+        for (key, per_minute) in &limits {
+            let bucket = refill(buckets.get(key), *per_minute, now);
+            if bucket.tokens < 1.0 {
+                let wait = (1.0 - bucket.tokens) * 60.0 / *per_minute as f64;
+                retry_after = retry_after.max(Duration::from_secs_f64(wait));
+            }
+        }
+        if !retry_after.is_zero() {
+            return Err(retry_after);
+        }
+
+        for (key, per_minute) in limits {
+            let mut bucket = refill(buckets.get(&key), per_minute, now);
+            bucket.tokens -= 1.0;
+            buckets.insert(key, bucket);
+        }
+        Ok(())
+    }
+}
+
+/// A bucket's state at `now`, starting full for sources seen for the first time
+fn refill(bucket: Option<&Bucket>, per_minute: u32, now: Instant) -> Bucket {
+    let capacity = per_minute as f64;
+    match bucket {
+        Some(bucket) => {
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            Bucket {
+                tokens: (bucket.tokens + elapsed * capacity / 60.0).min(capacity),
+                updated_at: now,
+            }
+        }
+        None => Bucket {
+            tokens: capacity,
+            updated_at: now,
+        },
+    }
+}
@@ -0,0 +1,248 @@
+use crate::db::function::FunctionDBRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::utils::dependencies::{
+    AdvisoryDatabase, DependencyFindings, DependencyInventory, VulnerableDependency,
+};
+use db_entities::function::Model;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Largest advisory database read, from a file or a URL (16MB)
+const MAX_DATABASE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long fetching the advisory database from a URL may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct LoadedDatabase {
+    database: Arc<AdvisoryDatabase>,
+    loaded_at: String,
+}
+
+/// The advisory database functions' dependency inventories are checked against, reloaded
+/// from the file or URL operators maintain it at
+#[derive(Debug)]
+pub struct DependencyAdvisories {
+    source: Option<String>,
+    loaded: RwLock<Option<LoadedDatabase>>,
+}
+
+/// A function flagged by the advisories
+#[derive(Debug, Serialize)]
+pub struct FlaggedFunction {
+    pub namespace: Uuid,
+    pub name: String,
+    pub runtime: String,
+    pub runtime_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated_runtime: Option<String>,
+    pub vulnerable: Vec<VulnerableDependency>,
+}
+
+/// Functions using deprecated runtimes or vulnerable dependency versions
+#[derive(Debug, Serialize)]
+pub struct DependencyReport {
+    /// When the advisories were last loaded, RFC 3339
+    pub advisories_loaded_at: Option<String>,
+    /// Functions with an inventory that were checked
+    pub scanned: usize,
+    pub functions: Vec<FlaggedFunction>,
+}
+
+impl DependencyAdvisories {
+    /// Advisories read from `source`, a file path or an http(s) URL; none are loaded until
+    /// [`DependencyAdvisories::reload`] is called
+    pub fn new(source: Option<String>) -> Self {
+        Self {
+            source,
+            loaded: RwLock::new(None),
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Reads the advisory database again, keeping the previous one if that fails
+    pub async fn reload(&self) -> Result<(), String> {
+        let Some(source) = &self.source else {
+            return Ok(());
+        };
+        let content = if source.starts_with("http://") || source.starts_with("https://") {
+            fetch(source).await?
+        } else {
+            fs::read_to_string(source).map_err(|e| format!("failed to read {}: {}", source, e))?
+        };
+        if content.len() > MAX_DATABASE_SIZE {
+            return Err(format!(
+                "{} is larger than {} bytes",
+                source, MAX_DATABASE_SIZE
+            ));
+        }
+        // YAML is a superset of JSON, so either format parses
+        let database: AdvisoryDatabase = serde_yaml::from_str(&content)
+            .map_err(|e| format!("invalid advisory database {}: {}", source, e))?;
+
+        *self.loaded.write().unwrap() = Some(LoadedDatabase {
+            database: Arc::new(database),
+            loaded_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+        });
+        Ok(())
+    }
+
+    /// What the current advisories say about a function's recorded inventory, or `None`
+    /// when it has none or no advisories are loaded
+    pub fn check(&self, function: &Model) -> Option<DependencyFindings> {
+        let inventory: DependencyInventory =
+            serde_json::from_value(function.dependencies.clone()?).ok()?;
+        let database = self.database()?;
+        Some(database.check(&function.runtime, &inventory))
+    }
+
+    /// One warning per finding of the function's latest deploy, for its deploy message
+    pub fn warnings(&self, function: &Model) -> Vec<String> {
+        self.check(function)
+            .map(|findings| findings.warnings())
+            .unwrap_or_default()
+    }
+
+    fn database(&self) -> Option<Arc<AdvisoryDatabase>> {
+        self.loaded
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|loaded| loaded.database.clone())
+    }
+
+    fn loaded_at(&self) -> Option<String> {
+        self.loaded
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|loaded| loaded.loaded_at.clone())
+    }
+}
+
+/// Lists the functions using deprecated runtimes or vulnerable dependency versions.
+///
+/// Functions deployed before inventories were recorded, or with a runtime that has no
+/// supported manifest, are not scanned.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `advisories` - The advisories to check against.
+/// * `namespace` - The namespace to report on, or `None` for the whole platform.
+pub async fn dependency_report(
+    conn: &DatabaseConnection,
+    advisories: &DependencyAdvisories,
+    namespace: Option<Uuid>,
+) -> ServelessCoreResult<DependencyReport> {
+    let functions = FunctionDBRepo::find_with_dependencies(conn, namespace)
+        .await
+        .map_err(|e| {
+            error!("Failed to list function dependencies: {}", e);
+            ServelessCoreError::SystemError("Failed to list function dependencies".to_string())
+        })?;
+
+    let scanned = functions.len();
+    let functions = functions
+        .into_iter()
+        .filter_map(|function| {
+            let findings = advisories.check(&function)?;
+            if findings.is_empty() {
+                return None;
+            }
+            let runtime_version = function
+                .dependencies
+                .as_ref()
+                .and_then(|dependencies| dependencies.get("runtime_version"))
+                .and_then(|version| version.as_str())
+                .map(|version| version.to_string());
+            Some(FlaggedFunction {
+                namespace: function.uuid,
+                name: function.name,
+                runtime: function.runtime,
+                runtime_version,
+                deprecated_runtime: findings.deprecated_runtime,
+                vulnerable: findings.vulnerable,
+            })
+        })
+        .collect();
+
+    Ok(DependencyReport {
+        advisories_loaded_at: advisories.loaded_at(),
+        scanned,
+        functions,
+    })
+}
+
+/// Reloads the advisories every `interval`, starting right away, and logs how many
+/// functions they flag. Returns right away when no advisory database is configured.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `advisories` - The advisories to reload.
+/// * `interval` - Time between reloads.
+pub async fn run_advisory_refresh_loop(
+    conn: DatabaseConnection,
+    advisories: Arc<DependencyAdvisories>,
+    interval: Duration,
+) {
+    if !advisories.is_configured() {
+        return;
+    }
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(e) = advisories.reload().await {
+            error!("Failed to load dependency advisories: {}", e);
+            continue;
+        }
+        match dependency_report(&conn, &advisories, None).await {
+            Ok(report) if report.functions.is_empty() => info!(
+                "Dependency advisories loaded; none of {} functions are flagged",
+                report.scanned
+            ),
+            Ok(report) => warn!(
+                "Dependency advisories flag {} of {} functions; see GET /admin/report/dependencies",
+                report.functions.len(),
+                report.scanned
+            ),
+            Err(e) => error!("Failed to check function dependencies: {}", e),
+        }
+    }
+}
+
+async fn fetch(url: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("failed to fetch {}: {}", url, e))?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_DATABASE_SIZE as u64)
+    {
+        return Err(format!(
+            "{} is larger than {} bytes",
+            url, MAX_DATABASE_SIZE
+        ));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("failed to fetch {}: {}", url, e))
+}
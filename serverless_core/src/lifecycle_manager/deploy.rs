@@ -1,17 +1,63 @@
+use crate::db::auth::AuthDBRepo;
 use crate::db::function::FunctionDBRepo;
-use crate::db::models::{DeployableFunction, DeployableFunctionConfig};
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::{
+    DeployableFunction, DeployableFunctionConfig, FunctionSettings, NamespaceDefaults,
+};
+use crate::lifecycle_manager::build_args::namespace_build_args;
+use crate::lifecycle_manager::docs::FunctionDocs;
 use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
-use crate::utils::utils::{create_fn_files_base, envs_to_string, generate_hash};
+use crate::lifecycle_manager::remote_build::ImageBuilder;
+use crate::lifecycle_manager::test_gate::{dockerfile_step, TestConfig, TestResults};
+use crate::utils::bundled_data::prepare_bundled_data;
+use crate::utils::dependencies::DependencyInventory;
+use crate::utils::image::{FunctionImage, IMAGE_RUNTIME};
+use crate::utils::registries::PrivateRegistries;
+use crate::utils::shared_packages::link_shared_packages;
+use crate::utils::utils::{
+    build_args_to_string, create_fn_files_base, envs_to_string, generate_hash,
+};
 use db_entities::function::Model as FunctionModel;
-use runtime::core::provisioning::provisioning;
-use sea_orm::DatabaseConnection;
+use runtime::core::provisioning::{
+    build_from_context, create_build_context, keep_image, pull_external_image, release_image,
+    remove_image, restore_image,
+};
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::{DatabaseConnection, TransactionTrait};
 use shared_utils::{extract_zip_from_cursor, find_file_in_path, to_camel_case_handler};
 use std::collections::HashMap;
 use std::fs;
-use std::io::{Cursor, Write};
+use std::io::{self, Cursor, Write};
 use std::path::PathBuf;
-use templates::{go_template, nodejs_template};
-use tracing::{error, info};
+use std::time::SystemTime;
+use templates::{go_template, image_template, nodejs_template, rust_template};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Environment variable holding the namespace of the function
+pub const NAMESPACE_ENV: &str = "INVOK_NAMESPACE";
+
+/// Environment variable holding the name of the function
+pub const FUNCTION_ENV: &str = "INVOK_FUNCTION";
+
+/// Environment variable holding the version of the function the image was built from
+pub const FUNCTION_VERSION_ENV: &str = "INVOK_FUNCTION_VERSION";
+
+/// Environment variable holding where the function's bundled data files are
+pub const DATA_DIR_ENV: &str = "INVOK_DATA_DIR";
+
+/// Where images keep the function's bundled data files
+pub const DATA_PATH: &str = "/invok/data";
+
+/// What only the build stage of a function's image sees
+struct BuildStage {
+    /// The namespace's build args
+    args: HashMap<String, String>,
+    /// Steps setting up private registries, run before dependencies are installed
+    dependency_steps: String,
+    /// Command running the function's unit tests once it's built, if they're run
+    tests: Option<String>,
+}
 
 /// Creates a function file structure and extracts its configuration.
 ///
@@ -20,11 +66,18 @@ use tracing::{error, info};
 /// 2. Creates the base function file (using a main template) and writes it to disk.
 /// 3. Extracts the provided ZIP content into the temporary directory.
 /// 4. Searches for and parses a `config.json` file within the extracted files.
+/// 5. Wires the shared packages the function uses into its build.
+/// 6. Prepares its bundled data files to be copied into the image, read-only.
+/// 7. Resolves the command its unit tests are run with during the build, if any.
+///
+/// Functions of the image runtime have no sources: their bundle is just the config,
+/// naming the image to deploy.
 ///
 /// # Arguments
 ///
-/// * `name` - The name of the function.
-/// * `runtime` - The runtime used by the function (e.g. "go").
+/// * `name` - The name of the function, which is also the route it is served on.
+/// * `handler_of` - The function whose handler the bundle defines; differs from `name`
+///   for preview instances.
 /// * `function_content` - The zipped function content.
 ///
 /// # Returns
@@ -32,10 +85,24 @@ use tracing::{error, info};
 /// A tuple containing:
 /// - An optional map of environment variables extracted from the configuration.
 /// - The path to the function files.
-async fn create_function(
+/// - The function's runtime.
+/// - The per-function settings from the configuration.
+/// - The private registries its dependencies are installed from.
+/// - The command its unit tests are run with during the build, if they are.
+/// - The image deployed instead of building the function, for the image runtime.
+pub(crate) async fn create_function(
     name: &str,
+    handler_of: &str,
     function_content: Vec<u8>,
-) -> ServelessCoreResult<(Option<HashMap<String, String>>, PathBuf, String)> {
+) -> ServelessCoreResult<(
+    Option<HashMap<String, String>>,
+    PathBuf,
+    String,
+    FunctionSettings,
+    PrivateRegistries,
+    Option<String>,
+    Option<FunctionImage>,
+)> {
     // Create a temporary directory for this function.
     let temp_dir = tempfile::tempdir()
         .map_err(|e| ServelessCoreError::SystemError(format!("Failed to create temp dir: {e}")))?
@@ -56,134 +123,535 @@ async fn create_function(
     let mut config: DeployableFunctionConfig = serde_json::from_str(&config_content)
         .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
 
+    // Functions of the image runtime run the image their CI built, the others are built here
+    match (config.runtime.as_str(), &config.image) {
+        (IMAGE_RUNTIME, Some(image)) => image
+            .validate()
+            .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid image: {}", e)))?,
+        (IMAGE_RUNTIME, None) => {
+            return Err(ServelessCoreError::BadFunction(
+                "Functions of the image runtime need an image to deploy".to_string(),
+            ))
+        }
+        (runtime, Some(_)) => {
+            return Err(ServelessCoreError::BadFunction(format!(
+                "An image is only deployed for functions of the image runtime, not {}",
+                runtime
+            )))
+        }
+        (_, None) => {}
+    }
+    if config.runtime == IMAGE_RUNTIME
+        && !matches!(config.test, None | Some(TestConfig::Enabled(false)))
+    {
+        return Err(ServelessCoreError::BadFunction(
+            "Images are deployed as built; run their tests in the CI building them".to_string(),
+        ));
+    }
+
+    // Place the shared packages archived with the function where its build finds them.
+    link_shared_packages(&temp_dir, &config.runtime, &config.shared).map_err(|e| {
+        match e.kind() {
+            io::ErrorKind::InvalidInput => ServelessCoreError::BadFunction(e.to_string()),
+            _ => ServelessCoreError::SystemError(e.to_string()),
+        }
+    })?;
+
+    // The data files get their own layer of the image, see the Dockerfiles.
+    prepare_bundled_data(&temp_dir).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidInput => ServelessCoreError::BadFunction(e.to_string()),
+        _ => ServelessCoreError::SystemError(e.to_string()),
+    })?;
+
+    let tests = config
+        .test
+        .as_ref()
+        .map(|test| test.command(&config.runtime))
+        .transpose()
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid test config: {}", e)))?
+        .flatten();
+
     // Convert function name into a CamelCase handler name.
-    let handler_name = to_camel_case_handler(name);
+    let handler_name = to_camel_case_handler(handler_of);
     let runtime = config.runtime;
 
     // Create the base function file (e.g., main.go) using the provided template.
     let file = create_fn_files_base(&temp_dir, &runtime)
         .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
-    let mut file_writer = std::io::BufWriter::new(file);
-
-    match runtime.as_str() {
-        "go" => {
-            file_writer
-                .write_all(
-                    go_template::MAIN_TEMPLATE
-                        .replace("{{ROUTE}}", name)
-                        .replace("{{HANDLER}}", &handler_name)
-                        .as_bytes(),
-                )
-                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
-        }
-        "nodejs" => {
-            file_writer
-                .write_all(nodejs_template::SERVER_TEMPLATE.as_bytes())
-                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
-        }
-        _ => {}
-    };
+    if let Some(file) = file {
+        let mut file_writer = std::io::BufWriter::new(file);
 
-    file_writer
-        .flush()
-        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+        match runtime.as_str() {
+            "go" => {
+                file_writer
+                    .write_all(
+                        go_template::MAIN_TEMPLATE
+                            .replace("{{ROUTE}}", name)
+                            .replace("{{HANDLER}}", &handler_name)
+                            .as_bytes(),
+                    )
+                    .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+            }
+            "nodejs" => {
+                file_writer
+                    .write_all(nodejs_template::SERVER_TEMPLATE.as_bytes())
+                    .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+            }
+            _ => {}
+        };
+
+        file_writer
+            .flush()
+            .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    }
+
+    Ok((
+        config.env.take(),
+        temp_dir,
+        runtime.clone(),
+        config.settings,
+        config.registries.unwrap_or_default(),
+        tests,
+        config.image,
+    ))
+}
 
-    Ok((config.env.take(), temp_dir, runtime.clone()))
+/// Environment variables describing the deployed function, set on every function and
+/// overriding those of its config.
+///
+/// They complement the per-invocation headers set by the proxy, see
+/// [`InvocationContext`](crate::lifecycle_manager::invoke::InvocationContext).
+pub fn context_envs(namespace: Uuid, name: &str, version: i32) -> HashMap<String, String> {
+    HashMap::from([
+        (NAMESPACE_ENV.to_string(), namespace.to_string()),
+        (FUNCTION_ENV.to_string(), name.to_string()),
+        (FUNCTION_VERSION_ENV.to_string(), version.to_string()),
+        (DATA_DIR_ENV.to_string(), DATA_PATH.to_string()),
+    ])
 }
 
 /// Provisions a Docker container for the function using the provided configuration.
 ///
 /// This function generates a Dockerfile by replacing placeholders in the template
-/// with the function's name and its environment variables, and then calls the provisioning
-/// routine to build the Docker image.
+/// with the function's environment variables, the build args its build stage declares,
+/// the private registry setup and its test step, and then has the image builder build the
+/// Docker image, locally or on the builder service.
 ///
 /// # Arguments
 ///
 /// * `path` - The file path to the function files.
-/// * `name` - The function's name.
+/// * `name` - The function's image name.
+/// * `namespace` - The namespace the function belongs to.
 /// * `envs` - A map of environment variables for the function.
+/// * `build_stage` - The build args, private registry setup and tests of the build stage.
+/// * `builder` - Builds the image.
 ///
 /// # Returns
 ///
-/// A result indicating success or failure.
+/// The results of the function's tests when they were run, or an error if the build or
+/// the tests failed.
 async fn provision_docker(
     runtime: &str,
     path: PathBuf,
     name: &str,
+    namespace: &str,
     envs: HashMap<String, String>,
-) -> ServelessCoreResult<()> {
+    build_stage: &BuildStage,
+    builder: &ImageBuilder,
+) -> ServelessCoreResult<Option<TestResults>> {
     let docker_file = match runtime {
         "go" => go_template::DOCKERFILE_TEMPLATE,
         "nodejs" => nodejs_template::DOCKERFILE_TEMPLATE,
+        "rust" => rust_template::DOCKERFILE_TEMPLATE,
         _ => "",
     };
-    let dockerfile_content = docker_file.replace("{{ENV}}", &envs_to_string(envs));
+    let dockerfile_content = docker_file
+        .replace("{{ENV}}", &envs_to_string(envs))
+        .replace("{{BUILD_ARGS}}", &build_args_to_string(&build_stage.args))
+        .replace("{{PRIVATE_DEPS}}", &build_stage.dependency_steps)
+        .replace(
+            "{{TESTS}}",
+            &build_stage
+                .tests
+                .as_deref()
+                .map(dockerfile_step)
+                .unwrap_or_default(),
+        );
+
+    let test_results = builder
+        .build(
+            &path,
+            name,
+            namespace,
+            &dockerfile_content,
+            &build_stage.args,
+            build_stage.tests.as_deref(),
+        )
+        .await?;
+    info!("Function docker image built");
+    Ok(test_results)
+}
 
-    provisioning(&path, name, &dockerfile_content)
+/// Provisions the image of a function of the image runtime from the image its CI built,
+/// without building anything of the function's.
+///
+/// The image is pulled for the daemon's platform, with the registry credentials of the
+/// namespace's build args the config names, and checked to run as a function. The
+/// function's image is derived from it with the function's environment variables, like
+/// promotions derive theirs. Remote builders and extra build platforms are not used.
+///
+/// # Arguments
+///
+/// * `path` - The file path to the function files, used as the (empty) build context.
+/// * `name` - The function's image name.
+/// * `envs` - A map of environment variables for the function.
+/// * `image` - The image to deploy.
+/// * `build_args` - The namespace's build args, holding the registry credentials.
+///
+/// # Returns
+///
+/// A line describing the deployed image, for the deploy message.
+async fn provision_image(
+    path: PathBuf,
+    name: &str,
+    envs: HashMap<String, String>,
+    image: &FunctionImage,
+    build_args: &HashMap<String, String>,
+) -> ServelessCoreResult<String> {
+    let auth = image
+        .auth(build_args)
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid image: {}", e)))?;
+    let details = pull_external_image(&image.reference, &auth)
         .await
+        .map_err(|e| ServelessCoreError::BadFunction(e.to_string()))?;
+    let summary = image
+        .check(&details)
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid image: {}", e)))?;
+
+    let dockerfile_content = image_template::DOCKERFILE_TEMPLATE
+        .replace("{{IMAGE}}", &image.reference)
+        .replace("{{ENV}}", &envs_to_string(envs));
+    let build_context = create_build_context(&path, &dockerfile_content)
         .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
-    info!("Function docker image built");
-    Ok(())
+    build_from_context(build_context, name, &[], &HashMap::new()).await?;
+    info!("Function docker image derived from '{}'", image.reference);
+    Ok(summary)
 }
 
 /// Deploys a function by building its files, provisioning a Docker container, and
 /// registering it in the database if necessary.
 ///
 /// This function:
-/// 1. Creates the function's file structure and extracts its configuration.
-/// 2. Provisions the Docker container for the function using the configuration.
-/// 3. Registers the function in the database if it does not already exist.
+/// 1. Creates the function's file structure and extracts its configuration, filling in
+///    unset settings and environment variables from the namespace defaults.
+/// 2. Provisions the Docker container for the function using the configuration, with
+///    the namespace's build args passed to the image build and the private registries
+///    of the config set up with them. When the config asks for it, the function's unit
+///    tests run in the build stage and a failure fails the deploy. Functions of the
+///    image runtime are provisioned from the image their CI built instead.
+/// 3. Registers the function in the database if it does not already exist, along with
+///    the README and OpenAPI document found in the bundle. Preview instances also get
+///    their expiry pushed back.
+/// 4. Records the uploaded archive as the function's next version, with the results of
+///    its tests, after any earlier versions carried over in `function.history`.
+///
+/// Steps 3 and 4 run in one transaction. If any of them fails nothing is registered:
+/// the image built for a new function is removed again, and a redeployed function gets
+/// back the image it had.
 ///
 /// # Arguments
 ///
 /// * `conn` - A reference to the database connection.
 /// * `function` - The function metadata and content.
+/// * `builder` - Builds the function's image.
 ///
 /// # Returns
 ///
-/// A success message indicating that the function was deployed.
+/// A success message indicating that the function was deployed, and the settings it was
+/// deployed with.
 pub async fn deploy_function(
     conn: &DatabaseConnection,
     function: DeployableFunction,
-) -> ServelessCoreResult<String> {
+    builder: &ImageBuilder,
+) -> ServelessCoreResult<(String, FunctionSettings)> {
     let name = function.name;
     let content = function.content;
     let user_uuid = function.user_uuid;
+    let preview = function.preview;
+    let handler_of = preview
+        .as_ref()
+        .map_or(name.as_str(), |preview| &preview.of);
 
     // Create the function files and extract configuration.
-    let (envs, path, runtime) = create_function(&name, content).await?;
+    let (envs, path, runtime, mut settings, registries, tests, image) =
+        create_function(&name, handler_of, content.clone()).await?;
+    let docs = FunctionDocs::read(&path)?;
+    let dependencies = DependencyInventory::read(&path, &runtime)
+        .map_err(|e| ServelessCoreError::BadFunction(e.to_string()))?
+        .and_then(|inventory| serde_json::to_value(inventory).ok());
+
+    let user = AuthDBRepo::find_by_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| {
+            error!("Failed to load namespace: {}", e);
+            ServelessCoreError::SystemError("Failed to load namespace".to_string())
+        })?;
+
+    // Anything the config leaves unset is inherited from the namespace defaults
+    let defaults = user
+        .as_ref()
+        .map(NamespaceDefaults::from_model)
+        .unwrap_or_default();
+    settings.inherit(&defaults);
+    settings
+        .validate()
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid settings: {}", e)))?;
+
     // Ensure environment variables are available.
-    let envs = envs.ok_or_else(|| {
+    let mut envs = defaults.merge_env(envs).ok_or_else(|| {
         ServelessCoreError::BadFunction("Missing environment configuration in function".to_string())
     })?;
+
+    let existing = FunctionDBRepo::find_function_including_trashed(conn, &name, user_uuid).await;
+
+    // The uploaded archive becomes the version after any recorded ones and the history
+    let latest_version = match &existing {
+        Some(existing) => FunctionVersionDBRepo::latest_version(conn, existing.id)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up function versions: {}", e);
+                ServelessCoreError::SystemError("Failed to look up function versions".to_string())
+            })?,
+        None => None,
+    };
+    let version = latest_version.unwrap_or(0) + function.history.len() as i32 + 1;
+    envs.extend(context_envs(user_uuid, &name, version));
+
+    // Build args only reach the build stage, never the function's environment
+    let build_args = match &user {
+        Some(user) => {
+            namespace_build_args(conn, builder.build_arg_cipher.as_ref(), user.id).await?
+        }
+        None => HashMap::new(),
+    };
+    registries
+        .validate(&runtime, &build_args)
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid registries: {}", e)))?;
+    registries
+        .write_npmrc(&path)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    let build_stage = BuildStage {
+        args: build_args,
+        dependency_steps: registries.dockerfile_steps(),
+        tests,
+    };
+
     // Build the function Docker image.
     let uuid_short = generate_hash(user_uuid);
     let function_image_name = format!("{name}-{uuid_short}");
-    provision_docker(&runtime, path, &function_image_name, envs).await?;
+    let namespace = user_uuid.to_string();
+    // A redeploy replaces the image its registration runs; keep the build it replaces
+    // until the new one is registered
+    let kept_image = match &existing {
+        Some(_) => keep_image(&function_image_name).await.unwrap_or_else(|e| {
+            warn!("Failed to keep the previous image of '{}': {}", name, e);
+            false
+        }),
+        None => false,
+    };
+    let build = async {
+        match &image {
+            Some(image) => {
+                let summary =
+                    provision_image(path, &function_image_name, envs, image, &build_stage.args)
+                        .await?;
+                Ok((None, Some(summary)))
+            }
+            None => {
+                let test_results = provision_docker(
+                    &runtime,
+                    path,
+                    &function_image_name,
+                    &namespace,
+                    envs,
+                    &build_stage,
+                    builder,
+                )
+                .await?;
+                Ok((test_results, None))
+            }
+        }
+    };
+    let (test_results, image_summary) = match build.await {
+        Ok(built) => built,
+        Err(e) => {
+            if kept_image {
+                restore_previous_image(&function_image_name, &name).await;
+            }
+            return Err(e);
+        }
+    };
 
-    // Register the function in the database if it's not already registered.
-    if FunctionDBRepo::find_function_by_name(conn, &name, user_uuid)
-        .await
-        .is_none()
-    {
-        // Create a function model for the user
-        let model = FunctionModel {
-            name: name.to_string(),
-            runtime,
-            ..Default::default()
+    let settings_json = serde_json::to_value(&settings).ok();
+    let test_results_json = test_results
+        .as_ref()
+        .and_then(|results| serde_json::to_value(results).ok());
+
+    // The registration is all or nothing: a failure rolls back every row written for
+    // this deploy, so the function never lists without a version to run
+    let is_new = existing.is_none();
+    let history = function.history;
+    let registration = async {
+        let txn = conn.begin().await.map_err(|e| {
+            error!("Failed to start deploy transaction: {}", e);
+            ServelessCoreError::SystemError("Failed to register function".to_string())
+        })?;
+
+        // Register the function in the database if it's not already registered,
+        // otherwise record the settings it was redeployed with (a trashed function is restored).
+        let registered = match existing {
+            None => {
+                // Create a function model for the user
+                let model = FunctionModel {
+                    name: name.to_string(),
+                    runtime,
+                    settings: settings_json.clone(),
+                    ..Default::default()
+                };
+
+                // Save the function to the database for the authenticated user
+                FunctionDBRepo::create_function_for_user(&txn, model, user_uuid)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to register function in database: {}", e);
+                        ServelessCoreError::BadFunction(
+                            "Failed to register function in database".to_string(),
+                        )
+                    })?
+            }
+            Some(existing) => {
+                FunctionDBRepo::update_function_settings(&txn, existing, settings_json.clone())
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to update function settings in database: {}", e);
+                        ServelessCoreError::SystemError(
+                            "Failed to update function settings".to_string(),
+                        )
+                    })?
+            }
         };
 
-        // Save the function to the database for the authenticated user
-        FunctionDBRepo::create_function_for_user(conn, model, user_uuid)
+        // Serve the docs shipped with this version, dropping those of earlier ones
+        let registered =
+            FunctionDBRepo::update_function_docs(&txn, registered, docs.readme, docs.openapi)
+                .await
+                .map_err(|e| {
+                    error!("Failed to store function docs in database: {}", e);
+                    ServelessCoreError::SystemError("Failed to store function docs".to_string())
+                })?;
+
+        // Record what this version depends on, for the dependency report
+        let registered =
+            FunctionDBRepo::update_function_dependencies(&txn, registered, dependencies)
+                .await
+                .map_err(|e| {
+                    error!("Failed to store function dependencies in database: {}", e);
+                    ServelessCoreError::SystemError(
+                        "Failed to store function dependencies".to_string(),
+                    )
+                })?;
+
+        // Previews expire a fixed time after their latest deploy
+        let registered = match preview {
+            Some(preview) => {
+                let expires_at = preview
+                    .ttl
+                    .map(|ttl| ChronoDateTimeUtc::from(SystemTime::now() + ttl).into());
+                FunctionDBRepo::set_preview(&txn, registered, Some(preview.of), expires_at)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to record preview in database: {}", e);
+                        ServelessCoreError::SystemError("Failed to record preview".to_string())
+                    })?
+            }
+            None => registered,
+        };
+
+        // Keep the sources so the namespace can be exported and restored elsewhere
+        let versions = history
+            .into_iter()
+            .map(|prior| {
+                let created_at = prior
+                    .created_at
+                    .and_then(|at| DateTimeWithTimeZone::parse_from_rfc3339(&at).ok());
+                (prior.content, prior.settings, created_at, None)
+            })
+            .chain(std::iter::once((
+                content,
+                settings_json,
+                None,
+                test_results_json,
+            )));
+        for (archive, settings, created_at, test_results) in versions {
+            FunctionVersionDBRepo::record(
+                &txn,
+                registered.id,
+                archive,
+                settings,
+                created_at,
+                test_results,
+            )
             .await
             .map_err(|e| {
-                error!("Failed to register function in database: {}", e);
-                ServelessCoreError::BadFunction(
-                    "Failed to register function in database".to_string(),
-                )
+                error!("Failed to record function version in database: {}", e);
+                ServelessCoreError::SystemError("Failed to record function version".to_string())
             })?;
+        }
+
+        txn.commit().await.map_err(|e| {
+            error!("Failed to commit deploy transaction: {}", e);
+            ServelessCoreError::SystemError("Failed to register function".to_string())
+        })
+    };
+
+    if let Err(e) = registration.await {
+        // Compensate for the build: an image nothing refers to would only take up disk.
+        // A redeployed function keeps its image, which its next deploy replaces
+        if is_new {
+            if let Err(e) = remove_image(&function_image_name).await {
+                error!(
+                    "Failed to remove image of unregistered function '{}': {}",
+                    name, e
+                );
+            }
+        } else if kept_image {
+            restore_previous_image(&function_image_name, &name).await;
+        }
+        return Err(e);
+    }
+    if kept_image {
+        if let Err(e) = release_image(&function_image_name).await {
+            warn!("Failed to drop the previous image of '{}': {}", name, e);
+        }
     }
 
     info!("Function '{}' deployed successfully", name);
-    Ok(format!("Function '{}' deployed successfully", name))
+    let mut message = format!("Function '{}' deployed successfully", name);
+    for line in image_summary
+        .into_iter()
+        .chain(test_results.map(|results| results.summary()))
+    {
+        message.push('\n');
+        message.push_str(&line);
+    }
+    Ok((message, settings))
+}
+
+/// Brings back the image a failed redeploy replaced, so the function keeps running the
+/// build its registration describes
+async fn restore_previous_image(image: &str, name: &str) {
+    match restore_image(image).await {
+        Ok(()) => info!("Function '{}' keeps its previous image", name),
+        Err(e) => error!("Failed to restore the previous image of '{}': {}", name, e),
+    }
 }
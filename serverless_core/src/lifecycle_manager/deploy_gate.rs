@@ -0,0 +1,521 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::deploy_lock::DeployLockDBRepo;
+use crate::db::models::{DeployApprovers, PendingDeployStatus, PendingDeploySummary};
+use crate::db::pending_deploy::PendingDeployDBRepo;
+use db_entities::auth::Model as AuthUser;
+use db_entities::deploy_lock::Model as DeployLock;
+use db_entities::pending_deploy::Model as PendingDeploy;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::error;
+use uuid::Uuid;
+
+/// Why a deploy, or a decision on one, was refused
+#[derive(Debug, Error)]
+pub enum DeployGateError {
+    /// A lock of the function or its namespace refuses deploys
+    #[error("{0}")]
+    Locked(String),
+    /// Deploys of the namespace wait for approval, which this way of deploying can't
+    #[error("{0}")]
+    ApprovalRequired(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// The user may not decide on the deploy
+    #[error("{0}")]
+    Forbidden(String),
+    /// The deploy was decided on already
+    #[error("{0}")]
+    AlreadyDecided(String),
+    #[error("Invalid approvers: {0}")]
+    InvalidApprovers(String),
+    #[error("System error: {0}")]
+    SystemError(String),
+}
+
+/// Request locking deploys
+#[derive(Debug, Default, Deserialize)]
+pub struct LockRequest {
+    /// Shown to whoever tries to deploy
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A lock refusing deploys
+#[derive(Debug, Serialize)]
+pub struct DeployLockInfo {
+    /// The function locked; unset when it's the whole namespace
+    pub function: Option<String>,
+    pub reason: Option<String>,
+    pub locked_by: String,
+    /// RFC 3339
+    pub created_at: String,
+}
+
+impl From<DeployLock> for DeployLockInfo {
+    fn from(lock: DeployLock) -> Self {
+        Self {
+            function: lock.function_name,
+            reason: lock.reason,
+            locked_by: lock.locked_by,
+            created_at: lock.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// A deploy waiting for, or given, approval
+#[derive(Debug, Serialize)]
+pub struct PendingDeployInfo {
+    pub id: i32,
+    /// The namespace (user UUID) the function is deployed into
+    pub namespace: Uuid,
+    pub function: String,
+    pub requested_by: String,
+    pub status: String,
+    pub decided_by: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+}
+
+impl PendingDeployInfo {
+    pub fn new(deploy: PendingDeploySummary, namespace: Uuid) -> Self {
+        Self {
+            id: deploy.id,
+            namespace,
+            function: deploy.function_name,
+            requested_by: deploy.requested_by,
+            status: deploy.status,
+            decided_by: deploy.decided_by,
+            created_at: deploy.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Refuses deploys of a function while it, or its namespace, is locked.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace the function is deployed into.
+/// * `function_name` - The function to deploy.
+pub async fn check_deploy_lock(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    function_name: &str,
+) -> Result<(), DeployGateError> {
+    let lock = DeployLockDBRepo::find_blocking(conn, user.id, function_name)
+        .await
+        .map_err(|e| database_error("Failed to look up deploy locks", e))?;
+    match lock {
+        Some(lock) => Err(locked(&lock, function_name)),
+        None => Ok(()),
+    }
+}
+
+/// Why a deploy of `function_name` is refused by `lock`
+fn locked(lock: &DeployLock, function_name: &str) -> DeployGateError {
+    let locked = match &lock.function_name {
+        Some(_) => format!("Deploys of '{}' are locked", function_name),
+        None => "Deploys of the namespace are locked".to_string(),
+    };
+    DeployGateError::Locked(match &lock.reason {
+        Some(reason) => format!("{} by {}: {}", locked, lock.locked_by, reason),
+        None => format!("{} by {}", locked, lock.locked_by),
+    })
+}
+
+/// Refuses changes that bypass approval, like promotions, imports, restores from the
+/// trash and routing rules, in a namespace whose deploys must be approved, or while
+/// deploys are locked.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace deployed into.
+/// * `function_name` - The function deployed, or `None` when it's any of them; then a
+///   lock of any function refuses the deploy.
+pub async fn check_direct_deploy(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    function_name: Option<&str>,
+) -> Result<(), DeployGateError> {
+    match function_name {
+        Some(function_name) => check_deploy_lock(conn, user, function_name).await?,
+        None => {
+            let locks = DeployLockDBRepo::find_by_user(conn, user.id)
+                .await
+                .map_err(|e| database_error("Failed to look up deploy locks", e))?;
+            if let Some(lock) = locks.first() {
+                return Err(DeployGateError::Locked(format!(
+                    "Deploys of {} are locked by {}",
+                    lock.function_name
+                        .as_deref()
+                        .map_or("the namespace".to_string(), |name| format!("'{}'", name)),
+                    lock.locked_by
+                )));
+            }
+        }
+    }
+    if needs_approval(user) {
+        return Err(DeployGateError::ApprovalRequired(
+            "Deploys of the namespace need approval; deploy the function with `invok deploy` instead"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the namespace's deploys wait for an approver
+pub fn needs_approval(user: &AuthUser) -> bool {
+    !DeployApprovers::from_model(user).approvers.is_empty()
+}
+
+/// Locks a function, or with no name the whole namespace, against deploys until it's
+/// unlocked. Previews are never locked, they don't touch the live function.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace to lock deploys of.
+/// * `function_name` - The function to lock, or `None` for all of them.
+/// * `reason` - Why, shown to whoever tries to deploy.
+pub async fn lock_deploys(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    function_name: Option<String>,
+    reason: Option<String>,
+) -> Result<DeployLockInfo, DeployGateError> {
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    DeployLockDBRepo::lock(conn, user.id, function_name, reason, user.email.clone())
+        .await
+        .map(DeployLockInfo::from)
+        .map_err(|e| database_error("Failed to lock deploys", e))
+}
+
+/// Lifts the lock of a function, or with no name the namespace's own lock.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace to unlock deploys of.
+/// * `function_name` - The function to unlock, or `None` for the namespace.
+pub async fn unlock_deploys(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    function_name: Option<&str>,
+) -> Result<(), DeployGateError> {
+    let unlocked = DeployLockDBRepo::unlock(conn, user.id, function_name)
+        .await
+        .map_err(|e| database_error("Failed to unlock deploys", e))?;
+    if !unlocked {
+        return Err(DeployGateError::NotFound(match function_name {
+            Some(name) => format!("Deploys of '{}' aren't locked", name),
+            None => "Deploys of the namespace aren't locked".to_string(),
+        }));
+    }
+    Ok(())
+}
+
+/// The deploy locks of a namespace, oldest first
+pub async fn list_deploy_locks(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+) -> Result<Vec<DeployLockInfo>, DeployGateError> {
+    DeployLockDBRepo::find_by_user(conn, user.id)
+        .await
+        .map(|locks| locks.into_iter().map(DeployLockInfo::from).collect())
+        .map_err(|e| database_error("Failed to list deploy locks", e))
+}
+
+/// Replaces the accounts that approve a namespace's deploys; with none, deploys no
+/// longer wait for approval, though those already waiting still do.
+///
+/// Approvers must be registered accounts other than the namespace's own, so that every
+/// deploy is seen by two people. The namespace itself may only add approvers: removing
+/// one would let it deploy unreviewed, so that takes an admin.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace whose approvers to set.
+/// * `approvers` - The approvers' emails.
+/// * `by_admin` - Whether an admin changes them, who may remove approvers.
+///
+/// # Returns
+///
+/// The approvers stored, with their emails as registered.
+pub async fn set_deploy_approvers(
+    conn: &DatabaseConnection,
+    user: AuthUser,
+    approvers: DeployApprovers,
+    by_admin: bool,
+) -> Result<DeployApprovers, DeployGateError> {
+    if !by_admin {
+        let removed = removed_approvers(&DeployApprovers::from_model(&user), &approvers);
+        if !removed.is_empty() {
+            return Err(DeployGateError::Forbidden(format!(
+                "Removing approvers ({}) needs an admin",
+                removed.join(", ")
+            )));
+        }
+    }
+
+    let mut stored = DeployApprovers::default();
+    for email in &approvers.approvers {
+        let email = email.trim();
+        if email.eq_ignore_ascii_case(&user.email) {
+            return Err(DeployGateError::InvalidApprovers(
+                "a namespace can't approve its own deploys".to_string(),
+            ));
+        }
+        let approver = AuthDBRepo::find_by_email(conn, email)
+            .await
+            .map_err(|e| database_error("Failed to look up approvers", e))?
+            .ok_or_else(|| {
+                DeployGateError::InvalidApprovers(format!("'{}' has no account", email))
+            })?;
+        if !stored.includes(&approver.email) {
+            stored.approvers.push(approver.email);
+        }
+    }
+
+    let value = if stored.approvers.is_empty() {
+        None
+    } else {
+        serde_json::to_value(&stored).ok()
+    };
+    AuthDBRepo::update_deploy_approvers(conn, user, value)
+        .await
+        .map_err(|e| database_error("Failed to update approvers", e))?;
+    Ok(stored)
+}
+
+/// Holds a deploy until one of the namespace's approvers approves it; an earlier deploy
+/// of the function still waiting is superseded.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace the function is deployed into.
+/// * `function_name` - The function to deploy.
+/// * `archive` - The function's archive, deployed as is once approved.
+/// * `force` - Whether the deploy overrides an SLO deploy freeze.
+pub async fn request_approval(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    function_name: &str,
+    archive: Vec<u8>,
+    force: bool,
+) -> Result<PendingDeployInfo, DeployGateError> {
+    PendingDeployDBRepo::create(
+        conn,
+        user.id,
+        function_name.to_string(),
+        archive,
+        force,
+        user.email.clone(),
+    )
+    .await
+    .map(|deploy| PendingDeployInfo::new((&deploy).into(), user.uuid))
+    .map_err(|e| database_error("Failed to store the deploy", e))
+}
+
+/// The deploys waiting for approval that a user requested, or may approve, oldest first
+pub async fn list_approvals(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+) -> Result<Vec<PendingDeployInfo>, DeployGateError> {
+    let approved = AuthDBRepo::find_by_approver(conn, &user.email)
+        .await
+        .map_err(|e| database_error("Failed to load namespaces", e))?;
+    let namespaces: HashMap<i32, Uuid> = approved
+        .iter()
+        .chain([user])
+        .map(|namespace| (namespace.id, namespace.uuid))
+        .collect();
+
+    let pending = PendingDeployDBRepo::find_pending(conn, namespaces.keys().copied().collect())
+        .await
+        .map_err(|e| database_error("Failed to list pending deploys", e))?;
+    Ok(pending
+        .into_iter()
+        .filter_map(|deploy| {
+            let namespace = *namespaces.get(&deploy.auth_id)?;
+            Some(PendingDeployInfo::new(deploy, namespace))
+        })
+        .collect())
+}
+
+/// Finds a deploy waiting for approval that a user may decide on.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `id` - The pending deploy.
+/// * `approver` - Who decides; one of the approvers of the deploy's namespace, other
+///   than the namespace itself.
+///
+/// # Returns
+///
+/// The deploy and the namespace it's for.
+pub async fn find_for_decision(
+    conn: &DatabaseConnection,
+    id: i32,
+    approver: &AuthUser,
+) -> Result<(PendingDeploy, AuthUser), DeployGateError> {
+    let not_found = || DeployGateError::NotFound(format!("Pending deploy {} not found", id));
+    let deploy = PendingDeployDBRepo::find_by_id(conn, id)
+        .await
+        .map_err(|e| database_error("Failed to load the pending deploy", e))?
+        .ok_or_else(not_found)?;
+    let owner = AuthDBRepo::find_by_id(conn, deploy.auth_id)
+        .await
+        .map_err(|e| database_error("Failed to load namespace", e))?
+        .ok_or_else(not_found)?;
+
+    if owner.id == approver.id {
+        return Err(DeployGateError::Forbidden(
+            "Deploys can't be decided on from their own namespace".to_string(),
+        ));
+    }
+    if !DeployApprovers::from_model(&owner).includes(&approver.email) {
+        // Other namespaces don't learn the deploy exists
+        return Err(not_found());
+    }
+    if deploy.status != PendingDeployStatus::Pending.as_str() {
+        return Err(DeployGateError::AlreadyDecided(format!(
+            "The deploy is {} already",
+            deploy.status
+        )));
+    }
+    Ok((deploy, owner))
+}
+
+/// Moves a pending deploy from one status to the next, unless another decision got there
+/// first.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `deploy` - The deploy decided on.
+/// * `from` - The status it must still have.
+/// * `to` - Its new status.
+/// * `approver` - Who decided.
+pub async fn record_decision(
+    conn: &DatabaseConnection,
+    deploy: &PendingDeploy,
+    from: PendingDeployStatus,
+    to: PendingDeployStatus,
+    approver: &AuthUser,
+) -> Result<(), DeployGateError> {
+    let recorded = PendingDeployDBRepo::decide(conn, deploy.id, from, to, &approver.email)
+        .await
+        .map_err(|e| database_error("Failed to record the decision", e))?;
+    if !recorded {
+        return Err(DeployGateError::AlreadyDecided(
+            "The deploy was decided on meanwhile".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Approvers of `current` that `requested` leaves out
+fn removed_approvers(current: &DeployApprovers, requested: &DeployApprovers) -> Vec<String> {
+    current
+        .approvers
+        .iter()
+        .filter(|approver| {
+            !requested
+                .approvers
+                .iter()
+                .any(|email| email.trim().eq_ignore_ascii_case(approver))
+        })
+        .cloned()
+        .collect()
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> DeployGateError {
+    error!("{}: {}", context, e);
+    DeployGateError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::prelude::DateTimeWithTimeZone;
+
+    fn approvers(emails: &[&str]) -> DeployApprovers {
+        DeployApprovers {
+            approvers: emails.iter().map(|email| email.to_string()).collect(),
+        }
+    }
+
+    fn lock(function_name: Option<&str>, reason: Option<&str>) -> DeployLock {
+        DeployLock {
+            id: 1,
+            auth_id: 1,
+            function_name: function_name.map(str::to_string),
+            reason: reason.map(str::to_string),
+            locked_by: "ops@example.com".to_string(),
+            created_at: DateTimeWithTimeZone::parse_from_rfc3339("2024-05-01T12:00:00+00:00")
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_removed_approvers() {
+        let current = approvers(&["lead@example.com", "cto@example.com"]);
+
+        assert!(removed_approvers(&current, &current).is_empty());
+        assert!(removed_approvers(
+            &current,
+            &approvers(&["CTO@example.com", " lead@example.com", "new@example.com"])
+        )
+        .is_empty());
+        assert_eq!(
+            removed_approvers(&current, &approvers(&["lead@example.com"])),
+            vec!["cto@example.com"]
+        );
+        assert_eq!(removed_approvers(&current, &approvers(&[])).len(), 2);
+        assert!(removed_approvers(&approvers(&[]), &approvers(&["lead@example.com"])).is_empty());
+    }
+
+    #[test]
+    fn test_locked_message() {
+        let message = |lock: DeployLock| locked(&lock, "api").to_string();
+
+        assert_eq!(
+            message(lock(Some("api"), None)),
+            "Deploys of 'api' are locked by ops@example.com"
+        );
+        assert_eq!(
+            message(lock(None, Some("release freeze"))),
+            "Deploys of the namespace are locked by ops@example.com: release freeze"
+        );
+    }
+
+    #[test]
+    fn test_pending_deploy_info() {
+        let namespace = Uuid::new_v4();
+        let deploy = PendingDeploySummary {
+            id: 7,
+            auth_id: 1,
+            function_name: "api".to_string(),
+            requested_by: "dev@example.com".to_string(),
+            status: PendingDeployStatus::Pending.as_str().to_string(),
+            decided_by: None,
+            created_at: DateTimeWithTimeZone::parse_from_rfc3339("2024-05-01T12:00:00+00:00")
+                .unwrap(),
+        };
+
+        let info = PendingDeployInfo::new(deploy, namespace);
+        assert_eq!(info.id, 7);
+        assert_eq!(info.namespace, namespace);
+        assert_eq!(info.function, "api");
+        assert_eq!(info.created_at, "2024-05-01T12:00:00+00:00");
+    }
+}
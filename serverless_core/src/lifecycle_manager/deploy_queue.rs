@@ -0,0 +1,342 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::deployment::DeploymentDBRepo;
+use crate::db::models::{DeployPreview, DeployableFunction, DeploymentStatus};
+use crate::lifecycle_manager::deploy_gate::{check_deploy_lock, DeployGateError};
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::jobs::JobQueue;
+use db_entities::auth::Model as AuthUser;
+use db_entities::deployment::Model as Deployment;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Kind of the jobs building and deploying an uploaded function
+pub const DEPLOY_JOB: &str = "deploy_function";
+
+/// Payload of a [`DEPLOY_JOB`]; the archive stays with the deploy in the database
+#[derive(Debug, Serialize, Deserialize)]
+struct QueuedDeploy {
+    deployment: i32,
+}
+
+/// A deploy queued for the job runners, as `GET /invok/deployments/:id` reports it
+#[derive(Debug, Serialize)]
+pub struct DeploymentInfo {
+    pub id: i32,
+    /// Name the function is deployed under; the instance's name for previews
+    pub function: String,
+    /// `queued`, `running`, `succeeded` or `failed`
+    pub status: String,
+    /// Output of the deploy once it succeeded, or why it failed
+    pub message: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+    /// RFC 3339
+    pub finished_at: Option<String>,
+}
+
+impl From<Deployment> for DeploymentInfo {
+    fn from(deployment: Deployment) -> Self {
+        Self {
+            id: deployment.id,
+            function: deployment.function_name,
+            status: deployment.status,
+            message: deployment.message,
+            created_at: deployment.created_at.to_rfc3339(),
+            finished_at: deployment.finished_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+/// Queues the deploy of an uploaded function for the job runners of every controller.
+///
+/// The archive is stored with the deploy, so a deploy queued or running when its
+/// controller restarts is picked up again instead of being lost.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `jobs` - The queue the deploy is run from.
+/// * `user` - The namespace the function is deployed into.
+/// * `function_name` - The name the function is deployed under.
+/// * `preview_of` - The function previewed, when deploying a preview instance.
+/// * `archive` - The function's archive.
+///
+/// # Returns
+///
+/// * The queued deploy, to be looked up with [`find_deployment`].
+pub async fn queue_deploy(
+    conn: &DatabaseConnection,
+    jobs: &JobQueue,
+    user: &AuthUser,
+    function_name: &str,
+    preview_of: Option<String>,
+    archive: Vec<u8>,
+) -> ServelessCoreResult<DeploymentInfo> {
+    let deployment = DeploymentDBRepo::create(
+        conn,
+        user.id,
+        function_name.to_string(),
+        preview_of,
+        archive,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to store the deploy of {}: {}", function_name, e);
+        ServelessCoreError::SystemError("Failed to store the deploy".to_string())
+    })?;
+
+    let queued = QueuedDeploy {
+        deployment: deployment.id,
+    };
+    if let Err(e) = jobs.enqueue(DEPLOY_JOB, queued).await {
+        // Nothing would ever run it
+        let reason = "Failed to queue the deploy".to_string();
+        if let Err(e) =
+            DeploymentDBRepo::finish(conn, deployment.id, DeploymentStatus::Failed, reason).await
+        {
+            error!("Failed to record deploy {} as failed: {}", deployment.id, e);
+        }
+        return Err(e);
+    }
+
+    info!(
+        "Deploy {} of '{}' queued for namespace {}",
+        deployment.id, function_name, user.uuid
+    );
+    Ok(DeploymentInfo::from(deployment))
+}
+
+/// Finds a deploy of a namespace.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user` - The namespace the deploy must belong to.
+/// * `id` - The deploy to find.
+///
+/// # Returns
+///
+/// * The deploy, or `None` if it doesn't exist or belongs to another namespace.
+pub async fn find_deployment(
+    conn: &DatabaseConnection,
+    user: &AuthUser,
+    id: i32,
+) -> ServelessCoreResult<Option<DeploymentInfo>> {
+    let deployment = DeploymentDBRepo::find_by_id(conn, id).await.map_err(|e| {
+        error!("Failed to load deploy {}: {}", id, e);
+        ServelessCoreError::SystemError("Failed to load the deploy".to_string())
+    })?;
+    Ok(deployment
+        .filter(|deployment| deployment.auth_id == user.id)
+        .map(DeploymentInfo::from))
+}
+
+/// Runs a [`DEPLOY_JOB`]: builds and deploys a queued function, and records how it went.
+///
+/// Deploys are checked against the deploy locks again before they run, so one queued
+/// before its function or namespace was locked fails instead of going live.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `preview_ttl` - How long preview instances live.
+/// * `payload` - The [`QueuedDeploy`] to run.
+/// * `deploy` - Deploys the function, returning the output of the deploy.
+///
+/// # Returns
+///
+/// * `Ok(())` once the deploy finished, whether it succeeded or not, or if it had
+///   finished already; otherwise why it couldn't be run, so it is retried.
+pub async fn run_deploy<F, Fut>(
+    conn: &DatabaseConnection,
+    preview_ttl: Duration,
+    payload: serde_json::Value,
+    deploy: F,
+) -> Result<(), String>
+where
+    F: FnOnce(DeployableFunction) -> Fut,
+    Fut: Future<Output = ServelessCoreResult<String>>,
+{
+    let queued: QueuedDeploy =
+        serde_json::from_value(payload).map_err(|e| format!("Invalid deploy: {e}"))?;
+    let Some(deployment) = DeploymentDBRepo::find_by_id(conn, queued.deployment)
+        .await
+        .map_err(|e| format!("Failed to load deploy {}: {e}", queued.deployment))?
+    else {
+        // Removed along with its namespace
+        return Ok(());
+    };
+    // A deploy that finished before its runner could report back isn't run again
+    let started = DeploymentDBRepo::start(conn, deployment.id)
+        .await
+        .map_err(|e| format!("Failed to start deploy {}: {e}", deployment.id))?;
+    if !started {
+        return Ok(());
+    }
+    if deployment.status == DeploymentStatus::Running.as_str() {
+        warn!(
+            "Deploy {} of '{}' was interrupted, running it again",
+            deployment.id, deployment.function_name
+        );
+    }
+    let Some(user) = AuthDBRepo::find_by_id(conn, deployment.auth_id)
+        .await
+        .map_err(|e| format!("Failed to load namespace {}: {e}", deployment.auth_id))?
+    else {
+        // Removed after the deploy was loaded
+        return Ok(());
+    };
+
+    // Previews don't touch the live function, so they are never locked
+    let lock = match deployment.preview_of {
+        Some(_) => Ok(()),
+        None => check_deploy_lock(conn, &user, &deployment.function_name).await,
+    };
+    let (status, message) = match lock {
+        Ok(()) => {
+            let function = DeployableFunction {
+                name: deployment.function_name.clone(),
+                content: deployment.archive,
+                user_uuid: user.uuid,
+                history: Vec::new(),
+                preview: deployment.preview_of.map(|of| DeployPreview {
+                    of,
+                    ttl: Some(preview_ttl),
+                }),
+            };
+            match deploy(function).await {
+                Ok(output) => (DeploymentStatus::Succeeded, output),
+                Err(e) => (DeploymentStatus::Failed, e.to_string()),
+            }
+        }
+        Err(DeployGateError::Locked(reason)) => (DeploymentStatus::Failed, reason),
+        Err(e) => {
+            return Err(format!(
+                "Failed to check the locks of deploy {}: {e}",
+                deployment.id
+            ))
+        }
+    };
+    info!(
+        "Deploy {} of '{}' {}",
+        deployment.id,
+        deployment.function_name,
+        status.as_str()
+    );
+    DeploymentDBRepo::finish(conn, deployment.id, status, message)
+        .await
+        .map_err(|e| format!("Failed to record the end of deploy {}: {e}", deployment.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::deploy_lock::DeployLockDBRepo;
+    use db_migrations::{Migrator, MigratorTrait};
+    use sea_orm::Database;
+    use std::sync::Mutex;
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers_modules::postgres::Postgres;
+
+    const PREVIEW_TTL: Duration = Duration::from_secs(3600);
+
+    /// Runs a deploy, recording the functions deployed. Returns them and the deploy as
+    /// it ended up.
+    async fn run(
+        conn: &DatabaseConnection,
+        deployment: &Deployment,
+        outcome: ServelessCoreResult<String>,
+    ) -> (Vec<DeployableFunction>, Deployment) {
+        let deployed = Mutex::new(Vec::new());
+        let payload = serde_json::to_value(QueuedDeploy {
+            deployment: deployment.id,
+        })
+        .unwrap();
+        run_deploy(conn, PREVIEW_TTL, payload, |function| {
+            deployed.lock().unwrap().push(function);
+            async move { outcome }
+        })
+        .await
+        .unwrap();
+        let finished = DeploymentDBRepo::find_by_id(conn, deployment.id)
+            .await
+            .unwrap()
+            .unwrap();
+        (deployed.into_inner().unwrap(), finished)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Docker for Postgres; run with --ignored"]
+    async fn test_run_deploy() {
+        let postgres = Postgres::default().start().await.unwrap();
+        let url = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            postgres.get_host().await.unwrap(),
+            postgres.get_host_port_ipv4(5432).await.unwrap()
+        );
+        let conn = Database::connect(url).await.unwrap();
+        Migrator::up(&conn, None).await.unwrap();
+        let user = AuthDBRepo::register(&conn, "dev@example.com".into(), "password".into())
+            .await
+            .unwrap();
+        let queue = |name: &str, preview_of: Option<&str>| {
+            DeploymentDBRepo::create(
+                &conn,
+                user.id,
+                name.to_string(),
+                preview_of.map(str::to_string),
+                b"archive".to_vec(),
+            )
+        };
+
+        // The queued archive is deployed and the output recorded
+        let deployment = queue("hello", None).await.unwrap();
+        let (deployed, finished) = run(&conn, &deployment, Ok("Deployed".into())).await;
+        assert_eq!(deployed.len(), 1);
+        assert_eq!(deployed[0].name, "hello");
+        assert_eq!(deployed[0].content, b"archive");
+        assert_eq!(deployed[0].user_uuid, user.uuid);
+        assert!(deployed[0].preview.is_none());
+        assert_eq!(finished.status, DeploymentStatus::Succeeded.as_str());
+        assert_eq!(finished.message.as_deref(), Some("Deployed"));
+        assert!(finished.archive.is_empty());
+
+        // Finished deploys aren't run again
+        let (deployed, _) = run(&conn, &deployment, Ok("Deployed".into())).await;
+        assert!(deployed.is_empty());
+
+        // A failed deploy is recorded as such
+        let deployment = queue("hello", None).await.unwrap();
+        let error = ServelessCoreError::SystemError("Build failed".into());
+        let (_, finished) = run(&conn, &deployment, Err(error)).await;
+        assert_eq!(finished.status, DeploymentStatus::Failed.as_str());
+        assert!(finished.message.unwrap().contains("Build failed"));
+
+        // Locking the function after the deploy was queued stops it
+        let deployment = queue("hello", None).await.unwrap();
+        let preview = queue("hello-preview", Some("hello")).await.unwrap();
+        DeployLockDBRepo::lock(
+            &conn,
+            user.id,
+            Some("hello".into()),
+            Some("release freeze".into()),
+            "ops@example.com".into(),
+        )
+        .await
+        .unwrap();
+        let (deployed, finished) = run(&conn, &deployment, Ok("Deployed".into())).await;
+        assert!(deployed.is_empty());
+        assert_eq!(finished.status, DeploymentStatus::Failed.as_str());
+        assert!(finished.message.unwrap().contains("release freeze"));
+
+        // Previews don't touch the live function, so they still go out
+        let (deployed, finished) = run(&conn, &preview, Ok("Preview deployed".into())).await;
+        assert_eq!(deployed.len(), 1);
+        assert_eq!(deployed[0].preview.as_ref().unwrap().of, "hello");
+        assert_eq!(deployed[0].preview.as_ref().unwrap().ttl, Some(PREVIEW_TTL));
+        assert_eq!(finished.status, DeploymentStatus::Succeeded.as_str());
+    }
+}
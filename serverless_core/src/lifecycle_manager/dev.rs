@@ -0,0 +1,116 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::models::NamespaceDefaults;
+use crate::lifecycle_manager::deploy::{context_envs, create_function, DATA_DIR_ENV};
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::dev::{DevContainer, DEV_WORKDIR};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use shared_utils::{add_dir_to_tar, BUNDLED_DATA_DIR};
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// How often expired dev containers are looked for
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Version dev containers report in `INVOK_FUNCTION_VERSION`; deployed versions start at 1
+const DEV_VERSION: i32 = 0;
+
+/// A function's dev container, as shown to its owner
+#[derive(Debug, Serialize)]
+pub struct DevInstance {
+    pub name: String,
+    /// Short ID of the container
+    pub container: String,
+    /// Path the dev container is invoked on
+    pub path: String,
+    /// Seconds until the container is removed
+    pub expires_in: u64,
+}
+
+impl DevInstance {
+    pub fn new(name: &str, namespace: Uuid, container: &DevContainer) -> Self {
+        Self {
+            name: name.to_string(),
+            container: container.container_id.chars().take(12).collect(),
+            path: format!("/invok/{namespace}/{name}/dev"),
+            expires_in: container.expires_in(),
+        }
+    }
+}
+
+/// What a dev container is started with
+pub struct DevSources {
+    pub runtime: String,
+    /// `KEY=value` environment of the function
+    pub env: Vec<String>,
+    /// The function's files, entrypoint included, as a tar archive
+    pub archive: Vec<u8>,
+}
+
+/// Prepares the bundle `invok dev` uploads for a dev container.
+///
+/// The bundle is the archive a deploy takes, and gets the same entrypoint and
+/// environment: the config's variables over the namespace defaults, and the function's
+/// context with version 0. Build args and private registries aren't set up; they only
+/// reach image builds.
+pub async fn dev_sources(
+    conn: &DatabaseConnection,
+    name: &str,
+    user_uuid: Uuid,
+    bundle: Vec<u8>,
+) -> ServelessCoreResult<DevSources> {
+    let (envs, path, runtime, _, _, _, _) = create_function(name, name, bundle).await?;
+
+    let user = AuthDBRepo::find_by_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| {
+            error!("Failed to load namespace: {}", e);
+            ServelessCoreError::SystemError("Failed to load namespace".to_string())
+        })?;
+    let defaults = user
+        .as_ref()
+        .map(NamespaceDefaults::from_model)
+        .unwrap_or_default();
+    let mut envs = defaults.merge_env(envs).unwrap_or_default();
+    envs.extend(context_envs(user_uuid, name, DEV_VERSION));
+    // Dev containers run the synced sources, data files included, where they are
+    envs.insert(
+        DATA_DIR_ENV.to_string(),
+        format!("{DEV_WORKDIR}/{BUNDLED_DATA_DIR}"),
+    );
+
+    let mut archive = tar::Builder::new(Vec::new());
+    let archived =
+        add_dir_to_tar(&mut archive, &path, &path, &[]).and_then(|_| archive.into_inner());
+    if let Some(temp_dir) = path.parent() {
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+    let archive = archived.map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+
+    Ok(DevSources {
+        runtime,
+        env: envs
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect(),
+        archive,
+    })
+}
+
+/// Removes dev containers once they expire
+pub async fn run_dev_expiry_loop(autoscaler: Arc<Autoscaler>) {
+    let dev_containers = autoscaler.dev_containers();
+    let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        match dev_containers.remove_expired().await {
+            Ok(0) => {}
+            Ok(removed) => info!("Removed {} expired dev containers", removed),
+            Err(e) => error!("Failed to remove expired dev containers: {}", e),
+        }
+    }
+}
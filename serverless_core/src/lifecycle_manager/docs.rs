@@ -0,0 +1,294 @@
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use serde_yaml::Value;
+use shared_utils::find_file_in_path;
+use std::fs;
+use std::path::PathBuf;
+
+/// Names a bundle's README may have, in order of preference
+const README_FILES: &[&str] = &["README.md", "Readme.md", "readme.md"];
+
+/// Names a bundle's OpenAPI document may have, in order of preference
+const OPENAPI_FILES: &[&str] = &["openapi.yaml", "openapi.yml", "openapi.json"];
+
+/// Largest README or OpenAPI document stored (1MB each)
+const MAX_DOC_SIZE: usize = 1024 * 1024;
+
+/// HTTP methods listed from an OpenAPI `paths` entry
+const OPERATION_METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// URL schemes README links and images may use; relative URLs are kept too
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Content Security Policy the docs page is served with: no scripts, frames or forms,
+/// only its inline styles and images from the web. A README that gets something past
+/// the renderer still can't run code on the controller's origin.
+pub const DOCS_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; \
+                            img-src http: https:; base-uri 'none'; form-action 'none'; \
+                            frame-ancestors 'none'";
+
+/// Documentation shipped in a function bundle
+#[derive(Debug, Default, Clone)]
+pub struct FunctionDocs {
+    pub readme: Option<String>,
+    pub openapi: Option<String>,
+}
+
+impl FunctionDocs {
+    /// Reads the README and OpenAPI document from the root of an extracted bundle.
+    ///
+    /// Both are optional. An OpenAPI document that doesn't parse, or doesn't look like
+    /// OpenAPI, fails the deploy so broken docs are caught by the author.
+    pub fn read(path: &PathBuf) -> ServelessCoreResult<Self> {
+        let readme = read_doc(path, README_FILES)?;
+        let openapi = read_doc(path, OPENAPI_FILES)?;
+        if let Some(spec) = &openapi {
+            parse_openapi(spec).map_err(|e| {
+                ServelessCoreError::BadFunction(format!("Invalid OpenAPI document: {}", e))
+            })?;
+        }
+        Ok(Self { readme, openapi })
+    }
+}
+
+fn read_doc(path: &PathBuf, names: &[&str]) -> ServelessCoreResult<Option<String>> {
+    let Some(file) = names.iter().find_map(|name| find_file_in_path(name, path)) else {
+        return Ok(None);
+    };
+    let content =
+        fs::read_to_string(&file).map_err(|e| ServelessCoreError::BadFunction(e.to_string()))?;
+    if content.len() > MAX_DOC_SIZE {
+        return Err(ServelessCoreError::BadFunction(format!(
+            "{} is larger than {} bytes",
+            file, MAX_DOC_SIZE
+        )));
+    }
+    Ok(Some(content))
+}
+
+/// Parses an OpenAPI (or Swagger 2) document, JSON or YAML
+fn parse_openapi(spec: &str) -> Result<Value, String> {
+    let document: Value = serde_yaml::from_str(spec).map_err(|e| e.to_string())?;
+    if document.get("openapi").is_none() && document.get("swagger").is_none() {
+        return Err("missing the `openapi` version field".to_string());
+    }
+    if !document.get("paths").is_none_or(Value::is_mapping) {
+        return Err("`paths` must be a map".to_string());
+    }
+    Ok(document)
+}
+
+/// Media type of a stored OpenAPI document
+pub fn openapi_content_type(spec: &str) -> &'static str {
+    if spec.trim_start().starts_with('{') {
+        "application/json"
+    } else {
+        "application/yaml"
+    }
+}
+
+/// Renders a function's docs as a standalone HTML page.
+///
+/// Raw HTML in the README is shown as text and links and images are kept only when they
+/// are relative or use an [`ALLOWED_SCHEMES`] scheme, since the page is served from the
+/// controller's origin. Serve it with [`DOCS_CSP`].
+pub fn render_html(namespace: &str, function_name: &str, docs: &FunctionDocs) -> String {
+    let title = escape_html(function_name);
+    let mut body = String::new();
+
+    match &docs.readme {
+        Some(readme) => body.push_str(&render_markdown(readme)),
+        None => body.push_str(&format!(
+            "<h1>{}</h1>\n<p class=\"empty\">This function has no README.</p>\n",
+            title
+        )),
+    }
+
+    if let Some(document) = docs.openapi.as_deref().and_then(|s| parse_openapi(s).ok()) {
+        body.push_str(&render_operations(&document));
+    }
+    body.push_str(&format!(
+        "<hr>\n<p>Invoke at <code>/invok/{}/{}</code></p>\n",
+        escape_html(namespace),
+        title
+    ));
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title} - invok</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 860px; margin: 2em auto; padding: 0 1em; line-height: 1.5; }}\n\
+         pre, code {{ background: #f4f4f4; }}\n\
+         pre {{ padding: 0.75em; overflow-x: auto; }}\n\
+         table {{ border-collapse: collapse; width: 100%; }}\n\
+         th, td {{ border: 1px solid #ddd; padding: 0.4em; text-align: left; }}\n\
+         .empty {{ color: #777; }}\n\
+         </style>\n</head>\n<body>\n{body}</body>\n</html>\n"
+    )
+}
+
+fn render_markdown(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        Event::Start(Tag::Link(kind, dest, title)) => {
+            Event::Start(Tag::Link(kind, safe_url(dest), title))
+        }
+        Event::Start(Tag::Image(kind, dest, title)) => {
+            Event::Start(Tag::Image(kind, safe_url(dest), title))
+        }
+        event => event,
+    });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser);
+    rendered
+}
+
+/// `dest` when it is relative or uses an allowed scheme, `#` otherwise
+fn safe_url(dest: CowStr) -> CowStr {
+    // Browsers skip leading spaces and control characters and drop tabs and newlines
+    // anywhere, so `java\tscript:` is still a `javascript:` URL
+    let normalized: String = dest
+        .trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    // A scheme ends at the first `:` that comes before any `/`, `?` or `#`
+    let scheme = normalized
+        .split(['/', '?', '#'])
+        .next()
+        .and_then(|head| head.split_once(':'))
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    match scheme {
+        Some(scheme) if !ALLOWED_SCHEMES.contains(&scheme.as_str()) => CowStr::Borrowed("#"),
+        _ => dest,
+    }
+}
+
+/// Table of the operations declared in an OpenAPI document
+fn render_operations(document: &Value) -> String {
+    let mut html = String::from("<h2>API</h2>\n");
+    if let Some(info) = document.get("info") {
+        let title = info.get("title").and_then(Value::as_str).unwrap_or("");
+        let version = info.get("version").and_then(Value::as_str).unwrap_or("");
+        html.push_str(&format!(
+            "<p><strong>{}</strong> {}</p>\n",
+            escape_html(title),
+            escape_html(version)
+        ));
+    }
+
+    let mut rows = String::new();
+    if let Some(paths) = document.get("paths").and_then(Value::as_mapping) {
+        for (path, item) in paths {
+            let path = path.as_str().unwrap_or_default();
+            for method in OPERATION_METHODS {
+                let Some(operation) = item.get(*method) else {
+                    continue;
+                };
+                let summary = operation
+                    .get("summary")
+                    .or_else(|| operation.get("description"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("");
+                rows.push_str(&format!(
+                    "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td></tr>\n",
+                    method.to_uppercase(),
+                    escape_html(path),
+                    escape_html(summary)
+                ));
+            }
+        }
+    }
+
+    if rows.is_empty() {
+        html.push_str("<p class=\"empty\">The OpenAPI document declares no operations.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Method</th><th>Path</th><th>Summary</th></tr>\n");
+        html.push_str(&rows);
+        html.push_str("</table>\n");
+    }
+    html.push_str("<p>The full document is available with <code>?format=openapi</code>.</p>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn safe(dest: &str) -> String {
+        safe_url(CowStr::Borrowed(dest)).to_string()
+    }
+
+    #[test]
+    fn test_safe_url_allows_web_links() {
+        for dest in [
+            "https://example.com/a?b#c",
+            "HTTP://example.com",
+            "mailto:dev@example.com",
+            "/invok/ns/fn",
+            "./docs/usage.md",
+            "usage.md",
+            "#section",
+            "?format=openapi",
+            "//cdn.example.com/logo.png",
+            "docs/a:b.md",
+        ] {
+            assert_eq!(safe(dest), dest, "{dest}");
+        }
+    }
+
+    #[test]
+    fn test_safe_url_drops_other_schemes() {
+        for dest in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            " javascript:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "java\nscript:alert(1)",
+            "data:text/html;base64,PHNjcmlwdD4=",
+            "vbscript:msgbox(1)",
+            "file:///etc/passwd",
+            "blob:https://example.com/uuid",
+        ] {
+            assert_eq!(safe(dest), "#", "{dest:?}");
+        }
+    }
+
+    #[test]
+    fn test_render_html_neutralizes_readme() {
+        let docs = FunctionDocs {
+            readme: Some(
+                "# Hi\n\n<script>alert(1)</script>\n\n[bad](javascript:alert(1)) \
+                 [good](https://example.com) ![img](data:image/svg+xml,x)\n"
+                    .to_string(),
+            ),
+            openapi: None,
+        };
+        let html = render_html("ns", "<fn>", &docs);
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains(r##"<a href="#">bad</a>"##));
+        assert!(html.contains(r#"<a href="https://example.com">good</a>"#));
+        assert!(html.contains(r##"<img src="#""##));
+        assert!(html.contains("<title>&lt;fn&gt; - invok</title>"));
+    }
+
+    #[test]
+    fn test_docs_csp_blocks_scripts() {
+        let directives: Vec<&str> = DOCS_CSP.split(';').map(str::trim).collect();
+        assert!(directives.contains(&"default-src 'none'"));
+        assert!(directives.contains(&"frame-ancestors 'none'"));
+        assert!(!DOCS_CSP.contains("script-src"));
+        assert!(!DOCS_CSP.contains("data:"));
+    }
+}
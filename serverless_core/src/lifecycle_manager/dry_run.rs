@@ -0,0 +1,226 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::{FunctionSettings, NamespaceDefaults};
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use shared_utils::{read_zip_file, sha256_hex, zip_digests};
+use std::collections::BTreeMap;
+use tracing::error;
+use uuid::Uuid;
+
+/// Path of the function's config inside its archive
+const CONFIG_PATH: &str = "config.json";
+
+/// What `invok deploy --dry-run` uploads instead of the function's archive
+#[derive(Debug, Deserialize)]
+pub struct DeployManifest {
+    /// [`sha256_hex`] digest of every file of the archive a deploy would upload, by path
+    pub files: BTreeMap<String, String>,
+    /// The function's `config.json`, with each value of `env` replaced by its digest
+    pub config: Value,
+}
+
+/// What a deploy would change about the deployed function
+#[derive(Debug, Default, Serialize)]
+pub struct DeployDiff {
+    /// Version the diff is against; unset when the function has no stored version
+    pub deployed_version: Option<i32>,
+    pub files: FileChanges,
+    /// Fields of `config.json` changed, apart from `env`
+    pub config: Vec<ValueChange>,
+    /// Names of the environment variables changed; their values are never sent
+    pub env: FileChanges,
+    /// Resource settings changed, namespace defaults included
+    pub resources: Vec<ValueChange>,
+}
+
+/// Paths (or names) added, modified and removed, sorted
+#[derive(Debug, Default, Serialize)]
+pub struct FileChanges {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A value before and after the deploy; `null` when unset
+#[derive(Debug, Serialize)]
+pub struct ValueChange {
+    pub key: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// Computes what deploying a function with the given manifest would change, without
+/// deploying it.
+///
+/// The manifest is compared with the latest recorded version of the function: its files
+/// by digest, its config field by field and its environment by the digests of the
+/// values. Resources are compared as they'd be applied, with unset ones inherited from
+/// the namespace defaults. A function that isn't deployed yet gets everything listed as
+/// added.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `name` - The function to deploy.
+/// * `user_uuid` - The namespace it would be deployed into.
+/// * `manifest` - Digests of the archive and its config.
+///
+/// # Returns
+///
+/// The changes the deploy would make.
+pub async fn diff_deploy(
+    conn: &DatabaseConnection,
+    name: &str,
+    user_uuid: Uuid,
+    manifest: DeployManifest,
+) -> ServelessCoreResult<DeployDiff> {
+    let Value::Object(mut config) = manifest.config else {
+        return Err(ServelessCoreError::BadFunction(
+            "Invalid config.json: not an object".to_string(),
+        ));
+    };
+
+    let user = AuthDBRepo::find_by_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to load namespace", e))?;
+    let defaults = user
+        .as_ref()
+        .map(NamespaceDefaults::from_model)
+        .unwrap_or_default();
+    let mut settings: FunctionSettings = serde_json::from_value(Value::Object(config.clone()))
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid settings: {}", e)))?;
+    settings.inherit(&defaults);
+    settings
+        .validate()
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid settings: {}", e)))?;
+
+    // What's deployed now; nothing for a new function
+    let mut deployed_version = None;
+    let mut deployed_files = BTreeMap::new();
+    let mut deployed_config = Map::new();
+    let mut deployed_settings = None;
+    let existing = FunctionDBRepo::find_function_including_trashed(conn, name, user_uuid).await;
+    if let Some(existing) = &existing {
+        let latest = FunctionVersionDBRepo::latest_version(conn, existing.id)
+            .await
+            .map_err(|e| database_error("Failed to look up function versions", e))?;
+        let version = match latest {
+            Some(latest) => FunctionVersionDBRepo::find_version(conn, existing.id, latest)
+                .await
+                .map_err(|e| database_error("Failed to load function version", e))?,
+            None => None,
+        };
+        if let Some(version) = version {
+            deployed_files = zip_digests(&version.archive)
+                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+            deployed_config = read_zip_file(&version.archive, CONFIG_PATH)
+                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?
+                .and_then(|content| serde_json::from_slice(&content).ok())
+                .and_then(|config: Value| match config {
+                    Value::Object(config) => Some(config),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            deployed_settings = version
+                .settings
+                .and_then(|settings| serde_json::from_value(settings).ok());
+            deployed_version = Some(version.version);
+        }
+        // Functions deployed before versions were recorded still have their settings
+        if deployed_settings.is_none() {
+            deployed_settings = Some(FunctionSettings::from_model(existing));
+        }
+    }
+
+    let env = env_digests(config.remove("env"), false);
+    let deployed_env = env_digests(deployed_config.remove("env"), true);
+
+    Ok(DeployDiff {
+        deployed_version,
+        files: compare(&deployed_files, &manifest.files),
+        config: changed_values(&deployed_config, &config),
+        env: compare(&deployed_env, &env),
+        resources: resources(&deployed_settings.unwrap_or_default())
+            .into_iter()
+            .zip(resources(&settings))
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((key, before), (_, after))| ValueChange {
+                key: key.to_string(),
+                before,
+                after,
+            })
+            .collect(),
+    })
+}
+
+/// The environment of a config by name, with the digest of each value; `hash` digests
+/// plain values, the manifest's are digests already
+fn env_digests(env: Option<Value>, hash: bool) -> BTreeMap<String, String> {
+    let Some(Value::Object(env)) = env else {
+        return BTreeMap::new();
+    };
+    env.into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            let digest = if hash {
+                sha256_hex(value.as_bytes())
+            } else {
+                value
+            };
+            (key, digest)
+        })
+        .collect()
+}
+
+fn compare(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> FileChanges {
+    let mut changes = FileChanges::default();
+    for (key, digest) in after {
+        match before.get(key) {
+            None => changes.added.push(key.clone()),
+            Some(previous) if previous != digest => changes.modified.push(key.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = before
+        .keys()
+        .filter(|key| !after.contains_key(*key))
+        .cloned()
+        .collect();
+    changes
+}
+
+fn changed_values(before: &Map<String, Value>, after: &Map<String, Value>) -> Vec<ValueChange> {
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| ValueChange {
+            key: key.clone(),
+            before: before.get(key).cloned().unwrap_or(Value::Null),
+            after: after.get(key).cloned().unwrap_or(Value::Null),
+        })
+        .collect()
+}
+
+/// The settings a function's container is sized and scaled with
+fn resources(settings: &FunctionSettings) -> [(&'static str, Value); 4] {
+    [
+        ("memory_mb", settings.memory_mb.into()),
+        ("timeout_secs", settings.timeout_secs.into()),
+        ("min_containers", settings.min_containers.into()),
+        ("max_containers", settings.max_containers.into()),
+    ]
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
@@ -0,0 +1,88 @@
+use crate::db::egress::EgressAllowlistRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::utils::utils::generate_hash;
+use db_entities::function::Model;
+use redis::aio::MultiplexedConnection;
+use sea_orm::DatabaseConnection;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// How often the allowlists in Redis are brought back in line with the stored ones
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Key of a function, as its containers and the proxy credentials know it
+pub fn function_key(function: &Model) -> String {
+    format!("{}-{}", function.name, generate_hash(function.uuid))
+}
+
+/// Copies the allowlist stored on a function to Redis, where the egress proxies read it
+pub async fn publish_allowlist(
+    cache_conn: &mut MultiplexedConnection,
+    function: &Model,
+) -> redis::RedisResult<()> {
+    let allowlist = function
+        .egress_allowlist
+        .as_ref()
+        .map(|allowlist| allowlist.to_string());
+    EgressAllowlistRepo::set(cache_conn, &function_key(function), allowlist.as_deref()).await
+}
+
+/// Publishes every stored allowlist, so the proxies see them after Redis lost its data or
+/// allowlists were restored from a backup, and removes those of functions that no longer
+/// have one, were deleted or are in the trash
+pub async fn sync_allowlists(db_conn: &DatabaseConnection, cache_conn: &mut MultiplexedConnection) {
+    let functions = match FunctionDBRepo::find_with_egress_allowlist(db_conn).await {
+        Ok(functions) => functions,
+        Err(e) => {
+            error!("Failed to load egress allowlists: {}", e);
+            return;
+        }
+    };
+    for function in &functions {
+        if let Err(e) = publish_allowlist(cache_conn, function).await {
+            error!(
+                "Failed to publish egress allowlist of '{}': {}",
+                function_key(function),
+                e
+            );
+        }
+    }
+
+    let published = match EgressAllowlistRepo::function_keys(cache_conn).await {
+        Ok(published) => published,
+        Err(e) => {
+            error!("Failed to list published egress allowlists: {}", e);
+            return;
+        }
+    };
+    let stored: HashSet<String> = functions.iter().map(function_key).collect();
+    let mut removed = 0;
+    for key in published.iter().filter(|key| !stored.contains(*key)) {
+        match EgressAllowlistRepo::set(cache_conn, key, None).await {
+            Ok(()) => removed += 1,
+            Err(e) => error!("Failed to remove egress allowlist of '{}': {}", key, e),
+        }
+    }
+    debug!(
+        "Published {} egress allowlists, removed {} stale ones",
+        functions.len(),
+        removed
+    );
+}
+
+/// Syncs the allowlists at startup and every few minutes after, for as long as the server
+/// runs
+pub async fn run_allowlist_sync_loop(
+    db_conn: DatabaseConnection,
+    mut cache_conn: MultiplexedConnection,
+) {
+    let mut interval = tokio::time::interval(SYNC_INTERVAL);
+    interval.tick().await;
+    sync_allowlists(&db_conn, &mut cache_conn).await;
+    info!("Published the egress allowlists");
+    loop {
+        interval.tick().await;
+        sync_allowlists(&db_conn, &mut cache_conn).await;
+    }
+}
@@ -2,6 +2,8 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use runtime::RuntimeError;
+use shared_utils::error::{Classify, ErrorClass};
 use thiserror::Error;
 use tracing::{debug, error};
 
@@ -11,8 +13,8 @@ pub type ServelessCoreResult<T> = core::result::Result<T, ServelessCoreError>;
 /// Custom error type for function-related failures.
 ///
 /// Variants cover cases such as a function not being registered,
-/// failure to start a function, malformed function input, or
-/// system-level errors.
+/// failure to start a function, malformed function input,
+/// unavailable dependencies, or system-level errors.
 #[derive(Debug, Error)]
 pub enum ServelessCoreError {
     #[error("Function not found: {0}")]
@@ -21,33 +23,60 @@ pub enum ServelessCoreError {
     FunctionFailedToStart(String),
     #[error("Bad function: {0}")]
     BadFunction(String),
+    /// A dependency (Docker, Redis, a free container) is unavailable for now
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
     #[error("System error: {0}")]
     SystemError(String),
 }
 
+impl Classify for ServelessCoreError {
+    fn class(&self) -> ErrorClass {
+        match self {
+            ServelessCoreError::FunctionNotRegistered(_) | ServelessCoreError::BadFunction(_) => {
+                ErrorClass::User
+            }
+            ServelessCoreError::Unavailable(_) => ErrorClass::Retryable,
+            ServelessCoreError::FunctionFailedToStart(_) | ServelessCoreError::SystemError(_) => {
+                ErrorClass::System
+            }
+        }
+    }
+}
+
+impl ServelessCoreError {
+    /// HTTP status the error is answered with: its class's, except for missing functions
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServelessCoreError::FunctionNotRegistered(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::from_u16(self.class().status_code())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+}
+
+/// Runtime errors keep their class: refused input is a bad function, an unavailable
+/// pool or Redis is worth retrying, anything else is on us
+impl From<RuntimeError> for ServelessCoreError {
+    fn from(e: RuntimeError) -> Self {
+        match e.class() {
+            ErrorClass::User => ServelessCoreError::BadFunction(e.to_string()),
+            ErrorClass::Retryable => ServelessCoreError::Unavailable(e.to_string()),
+            ErrorClass::System => ServelessCoreError::SystemError(e.to_string()),
+        }
+    }
+}
+
 impl IntoResponse for ServelessCoreError {
     fn into_response(self) -> Response {
         debug!("Converting error into response: {:?}", self);
+        let status = self.status_code();
         match self {
-            ServelessCoreError::FunctionNotRegistered(f) => {
-                (StatusCode::NOT_FOUND, format!("Function not found: {f}")).into_response()
-            }
-            ServelessCoreError::FunctionFailedToStart(s) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to start function: {s}"),
-            )
-                .into_response(),
-            ServelessCoreError::BadFunction(b) => {
-                (StatusCode::BAD_REQUEST, format!("Bad function: {b}")).into_response()
-            }
             ServelessCoreError::SystemError(s) => {
                 error!("System error occurred: {}", s);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "This is on us and we are working on it".to_string(),
-                )
-                    .into_response()
+                (status, "This is on us and we are working on it".to_string()).into_response()
             }
+            error => (status, error.to_string()).into_response(),
         }
     }
 }
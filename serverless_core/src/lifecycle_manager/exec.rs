@@ -0,0 +1,187 @@
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use runtime::core::exec::{ExecHandle, ExecSession};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+/// How long the client has to say what to run once connected
+const EXEC_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sessions without input or output for this long are closed
+const EXEC_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Longest a session may last
+const EXEC_MAX_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait for Docker to report the exit code of a command whose output ended
+const EXIT_CODE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// First message of an exec session: what to run
+#[derive(Debug, Deserialize)]
+pub struct ExecStart {
+    pub command: Vec<String>,
+    /// Run the command with a terminal
+    #[serde(default)]
+    pub tty: bool,
+    /// Size of the client's terminal, for commands run with one
+    #[serde(default)]
+    pub width: Option<u16>,
+    #[serde(default)]
+    pub height: Option<u16>,
+}
+
+/// Text messages the client sends during a session; binary messages are the command's
+/// stdin
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecControl {
+    /// The client's terminal was resized
+    Resize { width: u16, height: u16 },
+    /// The client's stdin ended
+    Eof,
+}
+
+/// Text messages sent to the client; binary messages are the command's output
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecEvent {
+    /// The command is running
+    Started { container: String },
+    /// The session is over; `exit_code` is missing when the command was still running
+    Exited { exit_code: Option<i64> },
+    /// The session couldn't start or was cut short
+    Error { message: String },
+}
+
+impl ExecEvent {
+    pub fn message(&self) -> Message {
+        Message::Text(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// Wait for the client to say what to run
+pub async fn read_start(socket: &mut WebSocket) -> Result<ExecStart, String> {
+    let message = tokio::time::timeout(EXEC_START_TIMEOUT, socket.recv())
+        .await
+        .map_err(|_| "No command received".to_string())?;
+    match message {
+        Some(Ok(Message::Text(text))) => {
+            serde_json::from_str(&text).map_err(|e| format!("Invalid start message: {}", e))
+        }
+        Some(Ok(_)) => Err("Expected a start message".to_string()),
+        Some(Err(e)) => Err(e.to_string()),
+        None => Err("The client disconnected".to_string()),
+    }
+}
+
+/// Relay a session between the client and the command until the command ends, the
+/// client leaves or the session times out, then tell the client how it ended.
+///
+/// Returns the command's exit code, when it exited. Docker can't stop a command it
+/// started, so commands still running when the client leaves only get their stdin
+/// closed; shells exit on that.
+pub async fn relay(socket: WebSocket, session: ExecSession) -> Option<i64> {
+    // The lease is held until the relay ends
+    let ExecSession {
+        handle,
+        mut output,
+        mut input,
+        lease: _lease,
+    } = session;
+    let (mut sender, mut receiver) = socket.split();
+
+    let deadline = tokio::time::sleep(EXEC_MAX_DURATION);
+    tokio::pin!(deadline);
+    let idle = tokio::time::sleep(EXEC_IDLE_TIMEOUT);
+    tokio::pin!(idle);
+
+    let mut cut_short: Option<&str> = None;
+    let mut command_ended = false;
+    loop {
+        tokio::select! {
+            chunk = output.next() => match chunk {
+                Some(Ok(chunk)) => {
+                    idle.as_mut().reset(tokio::time::Instant::now() + EXEC_IDLE_TIMEOUT);
+                    if sender.send(Message::Binary(chunk.into_bytes().to_vec())).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Err(e)) => {
+                    warn!(exec_id = %handle.exec_id, "Exec output failed: {}", e);
+                    break;
+                }
+                None => {
+                    command_ended = true;
+                    break;
+                }
+            },
+            message = receiver.next() => match message {
+                Some(Ok(Message::Binary(data))) => {
+                    idle.as_mut().reset(tokio::time::Instant::now() + EXEC_IDLE_TIMEOUT);
+                    if input.write_all(&data).await.is_err() || input.flush().await.is_err() {
+                        debug!(exec_id = %handle.exec_id, "Exec stdin closed");
+                    }
+                }
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ExecControl::Resize { width, height }) => {
+                        if let Err(e) = handle.resize(width, height).await {
+                            debug!(exec_id = %handle.exec_id, "{}", e);
+                        }
+                    }
+                    Ok(ExecControl::Eof) => {
+                        let _ = input.shutdown().await;
+                    }
+                    Err(e) => debug!(exec_id = %handle.exec_id, "Ignoring control message: {}", e),
+                },
+                // Pings are answered by axum
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            _ = &mut idle => {
+                cut_short = Some("The session was idle for too long");
+                break;
+            }
+            _ = &mut deadline => {
+                cut_short = Some("The session reached its maximum duration");
+                break;
+            }
+        }
+    }
+    let _ = input.shutdown().await;
+    drop(input);
+
+    let exit_code = if command_ended {
+        wait_for_exit_code(&handle).await
+    } else {
+        None
+    };
+    if let Some(message) = cut_short {
+        let event = ExecEvent::Error {
+            message: message.to_string(),
+        };
+        let _ = sender.send(event.message()).await;
+    }
+    let _ = sender.send(ExecEvent::Exited { exit_code }.message()).await;
+    let _ = sender.close().await;
+    exit_code
+}
+
+/// Docker reports the exit code shortly after the output ends
+async fn wait_for_exit_code(handle: &ExecHandle) -> Option<i64> {
+    let deadline = tokio::time::Instant::now() + EXIT_CODE_TIMEOUT;
+    loop {
+        match handle.exit_code().await {
+            Ok(Some(exit_code)) => return Some(exit_code),
+            Ok(None) if tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Ok(None) => return None,
+            Err(e) => {
+                warn!(exec_id = %handle.exec_id, "{}", e);
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::db::freeze::FreezeRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::utils::utils::generate_hash;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::freeze;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How often the windows are reloaded, so windows declared through other controllers
+/// apply here too
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A window during which the autoscaler keeps idle containers instead of scaling them
+/// down, platform-wide or for one namespace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FreezeWindow {
+    pub id: Uuid,
+    /// The namespace the window applies to, or `None` for every namespace
+    #[serde(default)]
+    pub namespace: Option<Uuid>,
+    /// Why scale-downs are suspended, e.g. "Black Friday"
+    #[serde(default)]
+    pub reason: String,
+    /// RFC 3339 time the window opens
+    pub starts_at: String,
+    /// RFC 3339 time the window closes
+    pub ends_at: String,
+}
+
+impl FreezeWindow {
+    fn bounds(&self) -> Option<(SystemTime, SystemTime)> {
+        parse_bounds(&self.starts_at, &self.ends_at).ok()
+    }
+}
+
+/// A freeze window as submitted by an operator
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NewFreezeWindow {
+    #[serde(default)]
+    pub namespace: Option<Uuid>,
+    #[serde(default)]
+    pub reason: String,
+    pub starts_at: String,
+    pub ends_at: String,
+}
+
+impl NewFreezeWindow {
+    /// Checks that the window parses, ends after it starts and hasn't ended yet
+    pub fn validate(&self) -> Result<(), String> {
+        let (_, ends_at) = parse_bounds(&self.starts_at, &self.ends_at)?;
+        if ends_at <= SystemTime::now() {
+            return Err("The window has already ended".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn parse_bounds(starts_at: &str, ends_at: &str) -> Result<(SystemTime, SystemTime), String> {
+    let parse = |field: &str, value: &str| {
+        DateTimeWithTimeZone::parse_from_rfc3339(value)
+            .map(SystemTime::from)
+            .map_err(|e| format!("{field} is not an RFC 3339 time: {e}"))
+    };
+    let starts_at = parse("starts_at", starts_at)?;
+    let ends_at = parse("ends_at", ends_at)?;
+    if ends_at <= starts_at {
+        return Err("ends_at must be after starts_at".to_string());
+    }
+    Ok((starts_at, ends_at))
+}
+
+/// Lists the declared freeze windows, earliest first. Windows that no longer parse are
+/// skipped.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the windows.
+pub async fn list_freeze_windows(
+    cache_conn: &mut MultiplexedConnection,
+) -> ServelessCoreResult<Vec<FreezeWindow>> {
+    let stored = FreezeRepo::list(cache_conn).await.map_err(|e| {
+        error!("Failed to list freeze windows: {}", e);
+        ServelessCoreError::SystemError("Failed to list freeze windows".to_string())
+    })?;
+    let mut windows: Vec<FreezeWindow> = stored
+        .iter()
+        .filter_map(|window| match serde_json::from_str(window) {
+            Ok(window) => Some(window),
+            Err(e) => {
+                warn!("Skipping unreadable freeze window: {}", e);
+                None
+            }
+        })
+        .collect();
+    windows.sort_by(|a, b| a.starts_at.cmp(&b.starts_at));
+    Ok(windows)
+}
+
+/// Declares a freeze window and applies it to this controller's autoscaler.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the windows.
+/// * `autoscaler` - The autoscaler the windows apply to.
+/// * `new` - The window to declare; [`NewFreezeWindow::validate`] must have passed.
+pub async fn declare_freeze_window(
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    new: NewFreezeWindow,
+) -> ServelessCoreResult<FreezeWindow> {
+    let (starts_at, ends_at) =
+        parse_bounds(&new.starts_at, &new.ends_at).map_err(ServelessCoreError::SystemError)?;
+    let window = FreezeWindow {
+        id: Uuid::new_v4(),
+        namespace: new.namespace,
+        reason: new.reason,
+        starts_at: ChronoDateTimeUtc::from(starts_at).to_rfc3339(),
+        ends_at: ChronoDateTimeUtc::from(ends_at).to_rfc3339(),
+    };
+    let stored = serde_json::to_string(&window)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    FreezeRepo::store(cache_conn, &window.id.to_string(), &stored)
+        .await
+        .map_err(|e| {
+            error!("Failed to store freeze window: {}", e);
+            ServelessCoreError::SystemError("Failed to store freeze window".to_string())
+        })?;
+
+    info!(
+        "Declared freeze window {} from {} to {}",
+        window.id, window.starts_at, window.ends_at
+    );
+    sync_freeze_windows(cache_conn, autoscaler).await;
+    Ok(window)
+}
+
+/// Lifts a freeze window, letting idle containers be scaled down again.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the windows.
+/// * `autoscaler` - The autoscaler the windows apply to.
+/// * `id` - The window to lift.
+///
+/// # Returns
+///
+/// Whether the window was declared.
+pub async fn lift_freeze_window(
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    id: Uuid,
+) -> ServelessCoreResult<bool> {
+    let removed = FreezeRepo::remove(cache_conn, &id.to_string())
+        .await
+        .map_err(|e| {
+            error!("Failed to lift freeze window {}: {}", id, e);
+            ServelessCoreError::SystemError("Failed to lift freeze window".to_string())
+        })?;
+    if removed {
+        info!("Lifted freeze window {}", id);
+        sync_freeze_windows(cache_conn, autoscaler).await;
+    }
+    Ok(removed)
+}
+
+/// Hands the declared windows to the autoscaler and forgets the ones that ended. When
+/// Redis can't be read, the windows the autoscaler already has are kept.
+async fn sync_freeze_windows(cache_conn: &mut MultiplexedConnection, autoscaler: &Autoscaler) {
+    let windows = match list_freeze_windows(cache_conn).await {
+        Ok(windows) => windows,
+        Err(_) => return,
+    };
+
+    let now = SystemTime::now();
+    let mut open = Vec::new();
+    for window in windows {
+        let Some((starts_at, ends_at)) = window.bounds() else {
+            continue;
+        };
+        if ends_at <= now {
+            if let Err(e) = FreezeRepo::remove(cache_conn, &window.id.to_string()).await {
+                warn!("Failed to forget ended freeze window {}: {}", window.id, e);
+            }
+            continue;
+        }
+        open.push(freeze::FreezeWindow {
+            namespace: window.namespace.map(generate_hash),
+            starts_at,
+            ends_at,
+        });
+    }
+    autoscaler.set_freeze_windows(open);
+}
+
+/// Reloads the freeze windows every [`SYNC_INTERVAL`], starting right away.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the windows.
+/// * `autoscaler` - The autoscaler the windows apply to.
+pub async fn run_freeze_sync_loop(
+    mut cache_conn: MultiplexedConnection,
+    autoscaler: Arc<Autoscaler>,
+) {
+    let mut interval = tokio::time::interval(SYNC_INTERVAL);
+    loop {
+        interval.tick().await;
+        sync_freeze_windows(&mut cache_conn, &autoscaler).await;
+    }
+}
@@ -0,0 +1,153 @@
+use crate::db::activity::ActivityRepo;
+use crate::db::auth::AuthDBRepo;
+use crate::utils::utils::generate_hash;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use sea_orm::DatabaseConnection;
+use shared_utils::unix_now;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How often idle namespaces are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often a controller writes a namespace's latest invocation to Redis
+const RECORD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Notes which namespaces receive traffic, so idle ones can be hibernated and
+/// hibernated ones woken by their next request
+pub struct NamespaceActivity {
+    conn: DatabaseConnection,
+    cache_conn: MultiplexedConnection,
+    /// When each namespace's activity was last written by this controller
+    recorded: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl NamespaceActivity {
+    pub fn new(conn: DatabaseConnection, cache_conn: MultiplexedConnection) -> Self {
+        Self {
+            conn,
+            cache_conn,
+            recorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Notes an invocation of a function in `namespace`.
+    ///
+    /// At most once every [`RECORD_INTERVAL`] per namespace, the time is written to
+    /// Redis and a hibernated namespace is marked awake again, off the request path.
+    /// The request itself cold-starts its function like any other.
+    pub fn record(&self, namespace: Uuid) {
+        {
+            let mut recorded = self.recorded.lock().unwrap();
+            if recorded
+                .get(&namespace)
+                .is_some_and(|at| at.elapsed() < RECORD_INTERVAL)
+            {
+                return;
+            }
+            recorded.insert(namespace, Instant::now());
+        }
+
+        let conn = self.conn.clone();
+        let mut cache_conn = self.cache_conn.clone();
+        tokio::spawn(async move {
+            let key = namespace.to_string();
+            if let Err(e) = ActivityRepo::touch(&mut cache_conn, &key, unix_now()).await {
+                warn!(
+                    "Failed to record activity of namespace {}: {}",
+                    namespace, e
+                );
+            }
+            match AuthDBRepo::wake(&conn, namespace).await {
+                Ok(true) => info!("Namespace {} woke from hibernation", namespace),
+                Ok(false) => {}
+                Err(e) => error!("Failed to wake namespace {}: {}", namespace, e),
+            }
+        });
+    }
+}
+
+/// Hibernates namespaces that received no invocation for `idle_after`, every
+/// [`SWEEP_INTERVAL`] for as long as the server runs.
+///
+/// A hibernated namespace has every container drained and its functions reported as
+/// hibernated until its next request. Namespaces without recorded activity, e.g. on the
+/// first sweep after upgrading, start counting from the sweep that finds them.
+///
+/// # Arguments
+///
+/// * `conn` - The database connection.
+/// * `cache_conn` - The Redis connection holding the namespaces' activity.
+/// * `autoscaler` - The autoscaler whose pools are drained.
+/// * `idle_after` - How long a namespace may go without invocations.
+pub async fn run_hibernation_loop(
+    conn: DatabaseConnection,
+    mut cache_conn: MultiplexedConnection,
+    autoscaler: Arc<Autoscaler>,
+    idle_after: Duration,
+) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) =
+            hibernate_idle_namespaces(&conn, &mut cache_conn, &autoscaler, idle_after).await
+        {
+            error!("Failed to hibernate idle namespaces: {}", e);
+        }
+    }
+}
+
+async fn hibernate_idle_namespaces(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    idle_after: Duration,
+) -> Result<(), String> {
+    let users = AuthDBRepo::find_all(conn)
+        .await
+        .map_err(|e| format!("Failed to load namespaces: {e}"))?;
+    let activity = ActivityRepo::all(cache_conn)
+        .await
+        .map_err(|e| format!("Failed to load namespace activity: {e}"))?;
+
+    let now = unix_now();
+    for user in users {
+        // Another controller may have hibernated the namespace; its pools here go too
+        if user.hibernated_at.is_some() {
+            autoscaler.drain_namespace(&generate_hash(user.uuid)).await;
+            continue;
+        }
+
+        let key = user.uuid.to_string();
+        let Some(last_invoked) = activity.get(&key) else {
+            if let Err(e) = ActivityRepo::touch_if_missing(cache_conn, &key, now).await {
+                warn!("Failed to record activity of namespace {}: {}", key, e);
+            }
+            continue;
+        };
+        if now.saturating_sub(*last_invoked) < idle_after.as_secs() {
+            continue;
+        }
+
+        let hibernated_at = ChronoDateTimeUtc::from(SystemTime::now()).into();
+        match AuthDBRepo::hibernate(conn, user.uuid, hibernated_at).await {
+            Ok(true) => {
+                let drained = autoscaler.drain_namespace(&generate_hash(user.uuid)).await;
+                info!(
+                    "Hibernated namespace {} after {} days without invocations ({} containers stopped)",
+                    user.uuid,
+                    idle_after.as_secs() / (24 * 60 * 60),
+                    drained
+                );
+            }
+            Ok(false) => {}
+            Err(e) => error!("Failed to hibernate namespace {}: {}", user.uuid, e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use crate::api_controller::AppState;
+use crate::utils::utils::generate_hash;
+use futures_util::stream::StreamExt;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Redis channel controllers announce changed functions on
+const INVALIDATION_CHANNEL: &str = "invok:invalidations";
+
+/// How long to wait before subscribing again after losing the subscription
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// What the other controllers must stop serving from their caches
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum Invalidation {
+    /// One function was deployed, deleted, restored or had its routing changed
+    Function { namespace: Uuid, name: String },
+    /// A namespace's feature flags changed
+    FeatureFlags { namespace: Uuid },
+    /// Every function may have changed, e.g. after a backup was restored
+    All,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Message {
+    /// Controller that made the change and already updated its caches
+    origin: Uuid,
+    invalidation: Invalidation,
+}
+
+/// Keeps the per-controller caches of function settings, versions, routing rules and
+/// feature flags in step across controllers.
+///
+/// A controller that changes a function updates its own caches and announces the change
+/// on Redis pub/sub; the others drop their entries, so the next invocation reloads them
+/// instead of serving stale settings until the controller restarts.
+#[derive(Debug)]
+pub struct CacheInvalidator {
+    controller_id: Uuid,
+}
+
+impl Default for CacheInvalidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheInvalidator {
+    pub fn new() -> Self {
+        Self {
+            controller_id: Uuid::new_v4(),
+        }
+    }
+
+    /// Announces a change to the other controllers. Failures are logged: the other
+    /// controllers then serve their caches until the entries are reloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_conn` - The Redis connection to publish on.
+    /// * `invalidation` - What changed.
+    pub async fn publish(
+        &self,
+        cache_conn: &mut MultiplexedConnection,
+        invalidation: Invalidation,
+    ) {
+        let message = Message {
+            origin: self.controller_id,
+            invalidation,
+        };
+        let Ok(payload) = serde_json::to_string(&message) else {
+            return;
+        };
+        if let Err(e) = cache_conn
+            .publish::<_, _, ()>(INVALIDATION_CHANNEL, payload)
+            .await
+        {
+            error!(
+                "Failed to announce {:?} to the other controllers: {}",
+                message.invalidation, e
+            );
+        }
+    }
+
+    /// Announces that a function changed
+    pub async fn publish_function(
+        &self,
+        cache_conn: &mut MultiplexedConnection,
+        name: &str,
+        user_uuid: Uuid,
+    ) {
+        let invalidation = Invalidation::Function {
+            namespace: user_uuid,
+            name: name.to_string(),
+        };
+        self.publish(cache_conn, invalidation).await;
+    }
+
+    /// Drops the cache entries the other controllers announce changes to, for as long as
+    /// the server runs. The subscription is renewed when the connection drops; caches
+    /// are flushed then, since announcements may have been missed meanwhile.
+    pub async fn run(&self, redis_url: String, state: AppState) {
+        let mut subscribed_before = false;
+        loop {
+            let mut pubsub = match subscribe(&redis_url).await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    warn!("Failed to subscribe to cache invalidations: {}", e);
+                    tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                    continue;
+                }
+            };
+            if subscribed_before {
+                info!("Resubscribed to cache invalidations, flushing caches");
+                invalidate(&state, &Invalidation::All);
+            }
+            subscribed_before = true;
+
+            let mut messages = pubsub.on_message();
+            while let Some(message) = messages.next().await {
+                let message: Message = match message
+                    .get_payload::<String>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|payload| serde_json::from_str(&payload).map_err(|e| e.to_string()))
+                {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("Skipping unreadable cache invalidation: {}", e);
+                        continue;
+                    }
+                };
+                if message.origin != self.controller_id {
+                    invalidate(&state, &message.invalidation);
+                }
+            }
+            warn!("Lost the cache invalidation subscription");
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+}
+
+async fn subscribe(redis_url: &str) -> redis::RedisResult<redis::aio::PubSub> {
+    let client = redis::Client::open(redis_url)?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(INVALIDATION_CHANNEL).await?;
+    Ok(pubsub)
+}
+
+/// Drops this controller's cache entries covered by an invalidation
+pub(crate) fn invalidate(state: &AppState, invalidation: &Invalidation) {
+    match invalidation {
+        Invalidation::Function { namespace, name } => {
+            let function_key = format!("{name}-{}", generate_hash(*namespace));
+            state
+                .function_settings
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state
+                .function_versions
+                .write()
+                .unwrap()
+                .remove(&function_key);
+            state.routing_rules.write().unwrap().remove(&function_key);
+        }
+        Invalidation::FeatureFlags { namespace } => {
+            state.feature_flags.write().unwrap().remove(namespace);
+        }
+        Invalidation::All => {
+            state.function_settings.write().unwrap().clear();
+            state.function_versions.write().unwrap().clear();
+            state.routing_rules.write().unwrap().clear();
+            state.feature_flags.write().unwrap().clear();
+        }
+    }
+}
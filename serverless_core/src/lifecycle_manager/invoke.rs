@@ -1,21 +1,254 @@
 use crate::api_controller::AppState;
-use crate::db::cache::FunctionCacheRepo;
+use crate::db::auth::AuthDBRepo;
+use crate::db::cache::{CachedLookup, FunctionCacheRepo};
 use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::FunctionSettings;
 use crate::lifecycle_manager::error::ServelessCoreError::FunctionFailedToStart;
 use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
-use crate::utils::utils::generate_hash;
+use crate::utils::flags::FeatureFlags;
+use crate::utils::routing::RoutingRules;
+use crate::utils::utils::{cookie_value, generate_hash};
 use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue};
 use runtime::core::autoscaler::Autoscaler;
+use runtime::core::container_manager::ContainerLease;
+use runtime::core::policy::{FunctionPolicy, StickyKey};
+use runtime::RuntimeError;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tracing::{error, info};
 use uuid::Uuid;
 
 const TIMEOUT_DEFAULT_IN_SECONDS: u64 = 60 * 60; // 1 hour timeout for function cache
 
+/// How long a function that wasn't found is remembered as missing
+const MISSING_FUNCTION_TTL_IN_SECONDS: u64 = 10;
+
+/// Header carrying the id of an invocation to the function
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Response header carrying the id of the invocation back to the caller
+pub const RESPONSE_REQUEST_ID_HEADER: &str = "x-invok-request-id";
+
+/// Response header carrying the version of the function that answered
+pub const VERSION_HEADER: &str = "x-invok-version";
+
+/// Response header timing the invocation's startup and the function's response
+const SERVER_TIMING_HEADER: &str = "server-timing";
+
+/// Header carrying the namespace of the invoked function
+pub const NAMESPACE_HEADER: &str = "x-invok-namespace";
+
+/// Header carrying the name of the invoked function, to the function and back to the
+/// caller
+pub const FUNCTION_HEADER: &str = "x-invok-function";
+
+/// Header carrying when the proxy gives up on the invocation, in milliseconds since the
+/// Unix epoch
+pub const DEADLINE_HEADER: &str = "x-invok-deadline";
+
+/// Request header asking for platform debug output; `timings` returns the timing
+/// breakdown of the invocation in [`TIMINGS_HEADER`]
+pub const DEBUG_HEADER: &str = "x-invok-debug";
+
+/// Response header carrying the timing breakdown of an invocation, in `Server-Timing`
+/// syntax
+pub const TIMINGS_HEADER: &str = "x-invok-timings";
+
+/// Longest request id kept from the caller
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// What every function learns about the invocation it handles, through request headers.
+///
+/// Functions learn what doesn't change between invocations (namespace, name, version)
+/// from environment variables too, see `deploy::context_envs`.
+#[derive(Debug, Clone)]
+pub struct InvocationContext {
+    /// Kept from the caller's `X-Request-Id` when usable, generated otherwise
+    pub request_id: String,
+    pub namespace: Uuid,
+    pub function: String,
+    /// When the proxy stops waiting for the function to respond
+    pub deadline: SystemTime,
+}
+
+impl InvocationContext {
+    /// Context of an invocation the proxy waits `timeout` for
+    pub fn new(headers: &HeaderMap, namespace: Uuid, function: &str, timeout: Duration) -> Self {
+        let request_id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id.bytes().all(|b| b.is_ascii_graphic())
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        Self {
+            request_id,
+            namespace,
+            function: function.to_string(),
+            deadline: SystemTime::now() + timeout,
+        }
+    }
+
+    /// Sets the context headers on a request forwarded to the function, replacing any the
+    /// caller sent
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        let deadline_ms = self
+            .deadline
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let values = [
+            (REQUEST_ID_HEADER, self.request_id.clone()),
+            (NAMESPACE_HEADER, self.namespace.to_string()),
+            (FUNCTION_HEADER, self.function.clone()),
+            (DEADLINE_HEADER, deadline_ms.to_string()),
+        ];
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    /// Sets the platform headers on the function's response, replacing any the function
+    /// set itself: the request id, the function and its version, and a `Server-Timing`
+    /// entry for the time taken to get a container (`startup`) and to respond (`function`).
+    pub fn annotate(
+        &self,
+        headers: &mut HeaderMap,
+        version: Option<i32>,
+        startup: Duration,
+        function: Duration,
+    ) {
+        let mut values = vec![
+            (RESPONSE_REQUEST_ID_HEADER, self.request_id.clone()),
+            (FUNCTION_HEADER, self.function.clone()),
+        ];
+        match version {
+            Some(version) => values.push((VERSION_HEADER, version.to_string())),
+            None => {
+                headers.remove(VERSION_HEADER);
+            }
+        }
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+
+        let timing = format!(
+            "startup;dur={:.1}, function;dur={:.1}",
+            startup.as_secs_f64() * 1000.0,
+            function.as_secs_f64() * 1000.0
+        );
+        if let Ok(timing) = HeaderValue::from_str(&timing) {
+            headers.append(SERVER_TIMING_HEADER, timing);
+        }
+    }
+}
+
+/// How long each phase of an invocation took
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InvocationTimings {
+    /// Validating the request and checking the function is registered
+    pub auth: Duration,
+    /// Loading the function's settings and getting a warm container
+    pub pool: Duration,
+    /// Starting a container when none was warm
+    pub cold_start: Duration,
+    /// Forwarding the request and the response, without the function's own time
+    pub proxy: Duration,
+    /// The function's response, from sending it the request until all of it arrived
+    pub function: Duration,
+}
+
+impl InvocationTimings {
+    /// Whether the caller asked for the breakdown with `x-invok-debug: timings`
+    pub fn requested(headers: &HeaderMap) -> bool {
+        headers
+            .get_all(DEBUG_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|option| option.trim().eq_ignore_ascii_case("timings"))
+    }
+
+    /// The breakdown in `Server-Timing` syntax, e.g.
+    /// `auth;dur=0.8, pool;dur=0.1, cold_start;dur=0.0, proxy;dur=1.2, function;dur=12.5, total;dur=14.6`
+    pub fn header_value(&self, total: Duration) -> String {
+        [
+            ("auth", self.auth),
+            ("pool", self.pool),
+            ("cold_start", self.cold_start),
+            ("proxy", self.proxy),
+            ("function", self.function),
+            ("total", total),
+        ]
+        .iter()
+        .map(|(name, duration)| format!("{};dur={:.1}", name, duration.as_secs_f64() * 1000.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// Counts how function lookups on the invocation path were answered
+#[derive(Debug, Default)]
+pub struct LookupStats {
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// [`LookupStats`] as reported by the health endpoints
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LookupStatsSnapshot {
+    /// Lookups answered from the cache with a function
+    pub hits: u64,
+    /// Lookups answered from the cache with a function known not to exist
+    pub negative_hits: u64,
+    /// Lookups that went to the database
+    pub misses: u64,
+    /// Share of lookups answered from the cache; 0 before the first lookup
+    pub hit_rate: f64,
+}
+
+impl LookupStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> LookupStatsSnapshot {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let negative_hits = self.negative_hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let total = hits + negative_hits + misses;
+        LookupStatsSnapshot {
+            hits,
+            negative_hits,
+            misses,
+            hit_rate: if total == 0 {
+                0.0
+            } else {
+                (hits + negative_hits) as f64 / total as f64
+            },
+        }
+    }
+}
+
 /// Checks if a function is registered in the database.
 ///
 /// Returns `Ok(())` if the function exists; otherwise, returns an error
-/// indicating that the function is not registered.
+/// indicating that the function is not registered. Both outcomes are cached, a missing
+/// function only briefly, so a function deployed right after a failed call is found
+/// soon. The function's latest version is cached with it.
 ///
 /// # Arguments
 ///
@@ -27,26 +260,70 @@ pub async fn check_function_status(
     name: &str,
     user_uuid: Uuid,
 ) -> ServelessCoreResult<()> {
-    if FunctionCacheRepo::get_function(&mut state.cache_conn, name)
-        .await
-        .is_some()
-    {
-        return Ok(());
-    }
-
-    let function = FunctionDBRepo::find_function_by_name(&state.db_conn, name, user_uuid).await;
-    if function.is_none() {
-        error!("Function '{}' not found in namespace '{}'", name, user_uuid);
-        return Err(ServelessCoreError::FunctionNotRegistered(format!(
+    let not_registered = || {
+        ServelessCoreError::FunctionNotRegistered(format!(
             "Function '{}' not found in namespace '{}'",
             name, user_uuid
-        )));
+        ))
+    };
+
+    match FunctionCacheRepo::get_function(&mut state.cache_conn, user_uuid, name).await {
+        Some(CachedLookup::Found(version)) => {
+            state.lookup_stats.hits.fetch_add(1, Ordering::Relaxed);
+            let function_key = format!("{name}-{}", generate_hash(user_uuid));
+            state
+                .function_versions
+                .write()
+                .unwrap()
+                .entry(function_key)
+                .or_insert(version);
+            return Ok(());
+        }
+        Some(CachedLookup::Missing) => {
+            state
+                .lookup_stats
+                .negative_hits
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(not_registered());
+        }
+        None => {
+            state.lookup_stats.misses.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_conn, name, user_uuid).await
+    else {
+        error!("Function '{}' not found in namespace '{}'", name, user_uuid);
+        let _ = FunctionCacheRepo::add_function(
+            &mut state.cache_conn,
+            user_uuid,
+            name,
+            CachedLookup::Missing,
+            MISSING_FUNCTION_TTL_IN_SECONDS,
+        )
+        .await;
+        return Err(not_registered());
+    };
+
+    // Without its version the function isn't cached; the next invocation looks again
+    let version = match FunctionVersionDBRepo::latest_version(&state.db_conn, function.id).await {
+        Ok(version) => version,
+        Err(e) => {
+            error!("Failed to look up the version of '{}': {}", name, e);
+            return Ok(());
+        }
+    };
+
     // If the function exists in the database, add it to the cache with a TTL.
-    if let Err(e) =
-        FunctionCacheRepo::add_function(&mut state.cache_conn, name, TIMEOUT_DEFAULT_IN_SECONDS)
-            .await
+    if let Err(e) = FunctionCacheRepo::add_function(
+        &mut state.cache_conn,
+        user_uuid,
+        name,
+        CachedLookup::Found(version),
+        TIMEOUT_DEFAULT_IN_SECONDS,
+    )
+    .await
     {
         error!("Failed to cache function '{}': {}", name, e);
         return Err(ServelessCoreError::SystemError(format!(
@@ -58,6 +335,138 @@ pub async fn check_function_status(
     Ok(())
 }
 
+/// Loads a function's settings, caching them for later invocations.
+///
+/// The first load also hands the routing policy to the autoscaler. Deploys refresh the
+/// cache directly; this covers functions deployed before the controller (re)started. A
+/// missing record yields the defaults.
+pub async fn load_function_settings(
+    state: &State<AppState>,
+    name: &str,
+    user_uuid: Uuid,
+) -> FunctionSettings {
+    let function_key = format!("{name}-{}", generate_hash(user_uuid));
+    if let Some(settings) = state.function_settings.read().unwrap().get(&function_key) {
+        return settings.clone();
+    }
+
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_conn, name, user_uuid).await
+    else {
+        return FunctionSettings::default();
+    };
+
+    let settings = FunctionSettings::from_model(&function);
+    state
+        .autoscaler
+        .set_function_policy(&function_key, settings.policy());
+    state
+        .function_settings
+        .write()
+        .unwrap()
+        .insert(function_key, settings.clone());
+    settings
+}
+
+/// Loads the version a function's image was built from, caching it for later invocations.
+///
+/// Deploys drop the cached version, so the next invocation looks the new one up. Functions
+/// without recorded versions yield `None`.
+pub async fn load_function_version(
+    state: &State<AppState>,
+    name: &str,
+    user_uuid: Uuid,
+) -> Option<i32> {
+    let function_key = format!("{name}-{}", generate_hash(user_uuid));
+    if let Some(version) = state.function_versions.read().unwrap().get(&function_key) {
+        return *version;
+    }
+
+    let function = FunctionDBRepo::find_function_by_name(&state.db_conn, name, user_uuid).await?;
+    let version = match FunctionVersionDBRepo::latest_version(&state.db_conn, function.id).await {
+        Ok(version) => version,
+        Err(e) => {
+            // Not cached, so the lookup is retried on the next invocation
+            error!("Failed to look up the version of '{}': {}", name, e);
+            return None;
+        }
+    };
+    state
+        .function_versions
+        .write()
+        .unwrap()
+        .insert(function_key, version);
+    version
+}
+
+/// Loads the rules routing a function's invocations to its versions, caching them for
+/// later invocations.
+///
+/// Updating the rules refreshes the cache directly. A missing record yields no rules.
+pub async fn load_routing_rules(
+    state: &State<AppState>,
+    name: &str,
+    user_uuid: Uuid,
+) -> RoutingRules {
+    let function_key = format!("{name}-{}", generate_hash(user_uuid));
+    if let Some(rules) = state.routing_rules.read().unwrap().get(&function_key) {
+        return rules.clone();
+    }
+
+    let Some(function) =
+        FunctionDBRepo::find_function_by_name(&state.db_conn, name, user_uuid).await
+    else {
+        return RoutingRules::default();
+    };
+
+    let rules = RoutingRules::from_model(&function);
+    state
+        .routing_rules
+        .write()
+        .unwrap()
+        .insert(function_key, rules.clone());
+    rules
+}
+
+/// Loads the feature flags of a namespace, caching them for later invocations.
+///
+/// Changing the flags refreshes the cache directly. A missing record yields no flags; a
+/// failed lookup too, and is retried on the next invocation.
+pub async fn load_feature_flags(state: &State<AppState>, user_uuid: Uuid) -> FeatureFlags {
+    if let Some(flags) = state.feature_flags.read().unwrap().get(&user_uuid) {
+        return flags.clone();
+    }
+
+    let flags = match AuthDBRepo::find_by_uuid(&state.db_conn, user_uuid).await {
+        Ok(Some(user)) => FeatureFlags::from_model(&user),
+        Ok(None) => FeatureFlags::default(),
+        Err(e) => {
+            error!("Failed to load the flags of namespace {}: {}", user_uuid, e);
+            return FeatureFlags::default();
+        }
+    };
+    state
+        .feature_flags
+        .write()
+        .unwrap()
+        .insert(user_uuid, flags.clone());
+    flags
+}
+
+/// Extracts the session key a sticky function routes on from the request headers.
+///
+/// Returns `None` for functions without sticky routing, or when the request doesn't carry
+/// the header/cookie (such requests are load balanced normally).
+pub fn affinity_key(policy: &FunctionPolicy, headers: &HeaderMap) -> Option<String> {
+    match policy.sticky.as_ref()? {
+        StickyKey::Header(name) => headers
+            .get(name.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        StickyKey::Cookie(name) => cookie_value(headers, name).map(str::to_string),
+    }
+}
+
 /// Starts a function service if it's not already running.
 ///
 ///
@@ -66,36 +475,41 @@ pub async fn check_function_status(
 /// * `runtime` - An `Arc` reference to the `Autoscaler` runtime, which manages function execution.
 /// * `name` - The name of the function to start.
 /// * `user_uuid` - The UUID of the user (namespace) who owns this function.
+/// * `affinity` - Session key for sticky functions (see [`affinity_key`]).
 ///
 /// # Returns
 ///
-/// A `Result` containing the function's address (e.g., "localhost:PORT") on success,
-/// or an error if the function fails to start.
+/// A `Result` containing the function's address (e.g., "localhost:PORT") and the lease on
+/// its container on success, or an error if the function fails to start. The container
+/// is released when the lease is dropped.
 pub async fn start_function(
     runtime: Arc<Autoscaler>,
     name: &str,
     user_uuid: Uuid,
-) -> ServelessCoreResult<String> {
+    affinity: Option<&str>,
+) -> ServelessCoreResult<(String, ContainerLease)> {
     // Generate a shorter hash of the UUID for better container names
     let uuid_short = generate_hash(user_uuid);
 
     // Create a unique function name based on function name and user's UUID hash
     let function_key = format!("{name}-{uuid_short}");
 
-    if let Some(container_details) = runtime.get_container_for_invocation(&function_key).await {
-        // Register the function in the cache.
-        let function_address = format!(
-            "{}:{}",
-            &container_details.container_name, &container_details.container_port
-        );
+    let lease = runtime
+        .get_container_for_invocation(&function_key, affinity)
+        .await
+        .map_err(|e| match e {
+            // No container in time: answered with a 503 the client may retry
+            RuntimeError::Unavailable(_) => ServelessCoreError::from(e),
+            _ => FunctionFailedToStart("Function did not start".to_string()),
+        })?;
 
-        info!(
-            "Function '{}' for user '{}' started at: {}",
-            name, user_uuid, function_address
-        );
+    // Register the function in the cache.
+    let function_address = lease.address().authority();
 
-        return Ok(function_address);
-    }
+    info!(
+        "Function '{}' for user '{}' started at: {}",
+        name, user_uuid, function_address
+    );
 
-    Err(FunctionFailedToStart("Function did not start".to_string()))
+    Ok((function_address, lease))
 }
@@ -0,0 +1,351 @@
+use crate::db::job::{Job, JobRepo, JobStatus};
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use futures_util::future::BoxFuture;
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use shared_utils::unix_now;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Runs a failing job is given, unless it is queued with another limit
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Workers each controller runs jobs with
+pub const DEFAULT_WORKERS: usize = 4;
+
+/// How long a running job stays invisible to other workers without a heartbeat. A job
+/// whose controller dies is run again once it ends
+const VISIBILITY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often a worker reports that its job is still running
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+
+/// How long an idle worker waits before looking for due jobs again
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often jobs past their visibility timeout are queued again
+const REQUEUE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Delay before the first retry of a failed job; doubled on every further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest delay between two runs of a failing job
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// Failed jobs kept for inspection through the admin API
+const FAILED_RETENTION: usize = 1000;
+
+type JobHandler =
+    Arc<dyn Fn(serde_json::Value) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Queues background jobs for the runners of every controller.
+#[derive(Clone)]
+pub struct JobQueue {
+    conn: MultiplexedConnection,
+}
+
+impl JobQueue {
+    pub fn new(conn: MultiplexedConnection) -> Self {
+        Self { conn }
+    }
+
+    /// Queues a job to run as soon as a worker is free.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of job, picking the handler that runs it.
+    /// * `payload` - The input of the handler.
+    ///
+    /// # Returns
+    ///
+    /// The id of the queued job.
+    pub async fn enqueue(&self, kind: &str, payload: impl Serialize) -> ServelessCoreResult<Uuid> {
+        let job = new_job(kind, payload)?;
+        let mut conn = self.conn.clone();
+        JobRepo::enqueue(&mut conn, &job, job.enqueued_at)
+            .await
+            .map_err(|_| ServelessCoreError::SystemError("Failed to queue job".to_string()))?;
+        Ok(job.id)
+    }
+
+    /// Queues a job, unless one with the same dedup key was queued, by any controller,
+    /// within `dedup_for`.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of job, picking the handler that runs it.
+    /// * `dedup_key` - Names the work the job does, e.g. the function it purges.
+    /// * `dedup_for` - How long no other job is queued for the same key.
+    /// * `payload` - The input of the handler.
+    ///
+    /// # Returns
+    ///
+    /// The id of the queued job, or `None` if one was queued already.
+    pub async fn enqueue_unique(
+        &self,
+        kind: &str,
+        dedup_key: &str,
+        dedup_for: Duration,
+        payload: impl Serialize,
+    ) -> ServelessCoreResult<Option<Uuid>> {
+        let job = new_job(kind, payload)?;
+        let mut conn = self.conn.clone();
+        let queued = JobRepo::enqueue_unique(
+            &mut conn,
+            &job,
+            job.enqueued_at,
+            &format!("{kind}:{dedup_key}"),
+            dedup_for.as_secs(),
+        )
+        .await
+        .map_err(|_| ServelessCoreError::SystemError("Failed to queue job".to_string()))?;
+        Ok(queued.then_some(job.id))
+    }
+}
+
+fn new_job(kind: &str, payload: impl Serialize) -> ServelessCoreResult<Job> {
+    let payload = serde_json::to_value(payload)
+        .map_err(|e| ServelessCoreError::SystemError(format!("Invalid job payload: {e}")))?;
+    Ok(Job {
+        id: Uuid::new_v4(),
+        kind: kind.to_string(),
+        payload,
+        status: JobStatus::Queued,
+        attempts: 0,
+        max_attempts: DEFAULT_MAX_ATTEMPTS,
+        error: None,
+        enqueued_at: unix_now(),
+    })
+}
+
+/// Runs the queued jobs of every controller, a few at a time.
+///
+/// Each kind of job has one handler. A handler that returns an error, or panics, has its
+/// job retried with an exponential backoff until the job runs out of attempts, after
+/// which it is kept in the failed set for inspection.
+pub struct JobRunner {
+    handlers: HashMap<&'static str, JobHandler>,
+    workers: usize,
+}
+
+impl JobRunner {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            handlers: HashMap::new(),
+            workers: workers.max(1),
+        }
+    }
+
+    /// Sets the handler that runs the jobs of a kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of job.
+    /// * `handler` - Runs one job, given its payload. Jobs may run more than once, e.g.
+    ///   when a controller dies while running one, so handlers must be idempotent.
+    pub fn register<F, Fut>(&mut self, kind: &'static str, handler: F)
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let handler: JobHandler = Arc::new(move |payload| Box::pin(handler(payload)));
+        self.handlers.insert(kind, handler);
+    }
+
+    /// Runs jobs for as long as the server runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The Redis connection holding the queue.
+    pub async fn run(self, conn: MultiplexedConnection) {
+        let runner = Arc::new(self);
+        info!("Running background jobs with {} workers", runner.workers);
+        for _ in 0..runner.workers {
+            tokio::spawn(runner.clone().work(conn.clone()));
+        }
+
+        let mut conn = conn;
+        let mut interval = tokio::time::interval(REQUEUE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match JobRepo::requeue_expired(&mut conn, unix_now()).await {
+                Ok(0) => {}
+                Ok(requeued) => warn!("Queued {} timed out jobs again", requeued),
+                Err(e) => error!("Failed to queue timed out jobs: {}", e),
+            }
+        }
+    }
+
+    async fn work(self: Arc<Self>, mut conn: MultiplexedConnection) {
+        loop {
+            let now = unix_now();
+            match JobRepo::claim(&mut conn, now, now + VISIBILITY_TIMEOUT.as_secs()).await {
+                Ok(Some(job)) => self.execute(&mut conn, job).await,
+                Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(e) => {
+                    error!("Failed to claim a job: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn execute(&self, conn: &mut MultiplexedConnection, mut job: Job) {
+        job.attempts += 1;
+        job.status = JobStatus::Running;
+
+        // Runs that timed out count as attempts too
+        if job.attempts > job.max_attempts {
+            job.error.get_or_insert_with(|| "Timed out".to_string());
+            self.give_up(conn, job).await;
+            return;
+        }
+        if let Err(e) = JobRepo::touch(conn, &job, unix_now() + VISIBILITY_TIMEOUT.as_secs()).await
+        {
+            error!("Failed to record the start of job {}: {}", job.id, e);
+        }
+
+        let result = match self.handlers.get(job.kind.as_str()) {
+            Some(handler) => self.run_with_heartbeat(conn, &job, handler).await,
+            // Possibly queued by a newer controller; another one may know it
+            None => Err(format!("No handler for jobs of kind '{}'", job.kind)),
+        };
+
+        match result {
+            Ok(()) => {
+                if let Err(e) = JobRepo::complete(conn, job.id).await {
+                    error!("Failed to remove finished job {}: {}", job.id, e);
+                }
+            }
+            Err(reason) => {
+                job.error = Some(reason);
+                if job.attempts >= job.max_attempts {
+                    self.give_up(conn, job).await;
+                    return;
+                }
+                let delay = retry_delay(job.attempts);
+                warn!(
+                    "Job {} ({}) failed, retrying in {}s: {}",
+                    job.id,
+                    job.kind,
+                    delay.as_secs(),
+                    job.error.as_deref().unwrap_or_default()
+                );
+                job.status = JobStatus::Queued;
+                if let Err(e) = JobRepo::enqueue(conn, &job, unix_now() + delay.as_secs()).await {
+                    error!("Failed to queue job {} for a retry: {}", job.id, e);
+                }
+            }
+        }
+    }
+
+    /// Runs the handler of a job in its own task, so a panic only fails the job, and
+    /// keeps the job invisible to other workers until it finishes
+    async fn run_with_heartbeat(
+        &self,
+        conn: &mut MultiplexedConnection,
+        job: &Job,
+        handler: &JobHandler,
+    ) -> Result<(), String> {
+        let mut run = tokio::spawn(handler(job.payload.clone()));
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        // The first tick completes right away
+        heartbeat.tick().await;
+        loop {
+            tokio::select! {
+                result = &mut run => {
+                    return result.unwrap_or_else(|e| Err(format!("Job panicked: {e}")));
+                }
+                _ = heartbeat.tick() => {
+                    let visible_at = unix_now() + VISIBILITY_TIMEOUT.as_secs();
+                    if let Err(e) = JobRepo::touch(conn, job, visible_at).await {
+                        warn!("Failed to extend the timeout of job {}: {}", job.id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn give_up(&self, conn: &mut MultiplexedConnection, mut job: Job) {
+        job.status = JobStatus::Failed;
+        error!(
+            "Job {} ({}) failed after {} attempts: {}",
+            job.id,
+            job.kind,
+            job.max_attempts,
+            job.error.as_deref().unwrap_or_default()
+        );
+        if let Err(e) = JobRepo::fail(conn, &job, unix_now(), FAILED_RETENTION).await {
+            error!("Failed to record failed job {}: {}", job.id, e);
+        }
+    }
+}
+
+/// Backoff before the retry following the given attempt
+fn retry_delay(attempts: u32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(4), RETRY_BASE_DELAY * 8);
+        // Capped, also where doubling would overflow
+        assert_eq!(retry_delay(9), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+        // A job is only retried after running, but the delay is defined for 0 too
+        assert_eq!(retry_delay(0), RETRY_BASE_DELAY);
+    }
+
+    #[test]
+    fn test_new_job() {
+        let job = new_job("purge_function", serde_json::json!({ "name": "hello" })).unwrap();
+        assert_eq!(job.kind, "purge_function");
+        assert_eq!(job.payload["name"], "hello");
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.attempts, 0);
+        assert_eq!(job.max_attempts, DEFAULT_MAX_ATTEMPTS);
+        assert!(job.enqueued_at > 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL (redis://localhost:6379 by default); run with --ignored"]
+    async fn test_enqueue_unique() {
+        let url =
+            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+        let conn = redis::Client::open(url)
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let queue = JobQueue::new(conn);
+        let dedup_key = Uuid::new_v4().to_string();
+        let dedup_for = Duration::from_secs(5);
+
+        let first = queue
+            .enqueue_unique("test", &dedup_key, dedup_for, "first")
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        // Another controller queueing the same work is turned away
+        let second = queue
+            .enqueue_unique("test", &dedup_key, dedup_for, "second")
+            .await
+            .unwrap();
+        assert!(second.is_none());
+
+        let mut conn = queue.conn.clone();
+        JobRepo::complete(&mut conn, first.unwrap()).await.unwrap();
+    }
+}
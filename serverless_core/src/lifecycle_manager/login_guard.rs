@@ -0,0 +1,369 @@
+use crate::db::login_attempts::LoginAttemptsRepo;
+use redis::aio::MultiplexedConnection;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, warn};
+
+/// Longest password accepted, in bytes; hashing is deliberately slow, so unbounded input
+/// would be a cheap way to load the server
+pub const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Time to wait for the captcha provider
+const CAPTCHA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Passwords rejected whatever the policy, compared case-insensitively
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "password",
+    "password1",
+    "password123",
+    "qwerty",
+    "qwerty123",
+    "qwertyuiop",
+    "abc123",
+    "111111",
+    "11111111",
+    "000000",
+    "00000000",
+    "letmein",
+    "welcome",
+    "iloveyou",
+    "admin",
+    "admin123",
+    "changeme",
+];
+
+/// Rules the password of a new account must follow
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// Fewest characters
+    pub min_length: usize,
+    /// Fewest character classes (lowercase, uppercase, digits, other) it must mix
+    pub min_classes: usize,
+}
+
+impl PasswordPolicy {
+    /// Checks a password chosen for the account `email`, describing the first rule it breaks
+    pub fn check(&self, email: &str, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            ));
+        }
+        if password.len() > MAX_PASSWORD_LENGTH {
+            return Err(format!(
+                "Password must be at most {} bytes",
+                MAX_PASSWORD_LENGTH
+            ));
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .into_iter()
+        .filter(|&present| present)
+        .count();
+        if classes < self.min_classes {
+            return Err(format!(
+                "Password must mix at least {} of lowercase letters, uppercase letters, digits and symbols",
+                self.min_classes
+            ));
+        }
+
+        let lowercase = password.to_lowercase();
+        if COMMON_PASSWORDS.contains(&lowercase.as_str()) {
+            return Err("Password is too common".to_string());
+        }
+        let local_part = email.split('@').next().unwrap_or_default().to_lowercase();
+        if local_part.len() >= 3 && lowercase.contains(&local_part) {
+            return Err("Password must not contain the email address".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Failed logins tolerated before a temporary lockout; a limit of 0 disables it
+#[derive(Debug, Clone)]
+pub struct LoginThrottle {
+    /// Failures of one account, from any address
+    pub max_failures_per_account: u32,
+    /// Failures from one client IP, for any account
+    pub max_failures_per_source: u32,
+    /// How long failures are counted, from the first one
+    pub window: Duration,
+    /// How long a source is locked out once it reaches its limit
+    pub lockout: Duration,
+}
+
+/// Why a registration's captcha wasn't accepted
+#[derive(Debug, Error)]
+pub enum CaptchaError {
+    #[error("Captcha token is required")]
+    Missing,
+    #[error("Captcha verification failed")]
+    Rejected,
+    /// The provider couldn't be asked
+    #[error("Captcha provider unavailable: {0}")]
+    Unavailable(String),
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+/// Verifies captcha tokens with a provider's `siteverify` endpoint (hCaptcha, reCAPTCHA,
+/// Cloudflare Turnstile and compatible ones)
+pub struct CaptchaVerifier {
+    url: String,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl CaptchaVerifier {
+    pub fn new(url: &str, secret: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(CAPTCHA_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Asks the provider whether `token` was issued for a solved challenge
+    pub async fn verify(&self, token: &str, client_ip: Option<IpAddr>) -> Result<(), CaptchaError> {
+        let mut form = vec![
+            ("secret", self.secret.clone()),
+            ("response", token.to_string()),
+        ];
+        if let Some(ip) = client_ip {
+            form.push(("remoteip", ip.to_string()));
+        }
+        let response = self
+            .client
+            .post(&self.url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| CaptchaError::Unavailable(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(CaptchaError::Unavailable(format!(
+                "status {}",
+                response.status()
+            )));
+        }
+        let verdict: SiteVerifyResponse = response
+            .json()
+            .await
+            .map_err(|e| CaptchaError::Unavailable(e.to_string()))?;
+        if verdict.success {
+            Ok(())
+        } else {
+            Err(CaptchaError::Rejected)
+        }
+    }
+}
+
+/// Keeps open instances from being abused through `/auth/register` and `/auth/login`:
+/// password rules for new accounts, lockouts after repeated failed logins (tracked in
+/// Redis, so every controller sees them) and an optional captcha on registration.
+pub struct LoginGuard {
+    pub password_policy: PasswordPolicy,
+    pub throttle: LoginThrottle,
+    /// Registrations need a solved captcha when set
+    pub captcha: Option<CaptchaVerifier>,
+}
+
+impl LoginGuard {
+    /// Checks the captcha token sent with a registration, if registrations need one
+    pub async fn verify_captcha(
+        &self,
+        token: Option<&str>,
+        client_ip: Option<IpAddr>,
+    ) -> Result<(), CaptchaError> {
+        let Some(captcha) = &self.captcha else {
+            return Ok(());
+        };
+        match token.filter(|token| !token.is_empty()) {
+            Some(token) => captcha.verify(token, client_ip).await,
+            None => Err(CaptchaError::Missing),
+        }
+    }
+
+    /// How long logins to the account `email` from `client_ip` stay locked out, if they are.
+    ///
+    /// Lockouts can't be checked while Redis is unreachable; logins are let through then.
+    pub async fn locked_out(
+        &self,
+        conn: &mut MultiplexedConnection,
+        email: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Duration> {
+        let mut remaining = None;
+        for (key, _) in self.limits(email, client_ip) {
+            match LoginAttemptsRepo::lockout_remaining(conn, &key).await {
+                Ok(Some(secs)) => remaining = remaining.max(Some(Duration::from_secs(secs))),
+                Ok(None) => {}
+                Err(e) => error!("Failed to check login lockout of {}: {}", key, e),
+            }
+        }
+        remaining
+    }
+
+    /// Counts a failed login against the account and the client IP, locking out whichever
+    /// reached its limit
+    pub async fn record_failure(
+        &self,
+        conn: &mut MultiplexedConnection,
+        email: &str,
+        client_ip: Option<IpAddr>,
+    ) {
+        let window = self.throttle.window.as_secs().max(1);
+        let lockout = self.throttle.lockout.as_secs().max(1);
+        for (key, max_failures) in self.limits(email, client_ip) {
+            let failures = match LoginAttemptsRepo::record_failure(conn, &key, window).await {
+                Ok(failures) => failures,
+                Err(e) => {
+                    error!("Failed to record failed login of {}: {}", key, e);
+                    continue;
+                }
+            };
+            if failures >= u64::from(max_failures) {
+                warn!(
+                    "Locking out {} for {}s after {} failed logins",
+                    key, lockout, failures
+                );
+                if let Err(e) = LoginAttemptsRepo::lock(conn, &key, lockout).await {
+                    error!("Failed to lock out {}: {}", key, e);
+                }
+            }
+        }
+    }
+
+    /// Forgets the failed logins of an account that logged in; those of the client IP
+    /// still count
+    pub async fn record_success(&self, conn: &mut MultiplexedConnection, email: &str) {
+        let key = account_key(email);
+        if let Err(e) = LoginAttemptsRepo::clear(conn, &key).await {
+            error!("Failed to clear failed logins of {}: {}", key, e);
+        }
+    }
+
+    /// Sources a login is throttled by, with their limits
+    fn limits(&self, email: &str, client_ip: Option<IpAddr>) -> Vec<(String, u32)> {
+        let mut limits = Vec::with_capacity(2);
+        if self.throttle.max_failures_per_account > 0 {
+            limits.push((account_key(email), self.throttle.max_failures_per_account));
+        }
+        if let (Some(ip), true) = (client_ip, self.throttle.max_failures_per_source > 0) {
+            limits.push((format!("ip:{}", ip), self.throttle.max_failures_per_source));
+        }
+        limits
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(max_failures_per_account: u32, max_failures_per_source: u32) -> LoginGuard {
+        LoginGuard {
+            password_policy: PasswordPolicy {
+                min_length: 10,
+                min_classes: 3,
+            },
+            throttle: LoginThrottle {
+                max_failures_per_account,
+                max_failures_per_source,
+                window: Duration::from_secs(900),
+                lockout: Duration::from_secs(900),
+            },
+            captcha: None,
+        }
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let policy = guard(5, 20).password_policy;
+        let email = "jane.doe@example.com";
+        assert!(policy.check(email, "Tr0ub4dor&3x").is_ok());
+        assert!(policy
+            .check(email, "Sh0rt!")
+            .unwrap_err()
+            .contains("at least 10"));
+        assert!(policy
+            .check(email, "alllowercaseletters")
+            .unwrap_err()
+            .contains("mix at least 3"));
+        assert!(policy.check(email, &"Aa1!".repeat(300)).is_err());
+        assert!(policy
+            .check(email, "Jane.Doe-2024!")
+            .unwrap_err()
+            .contains("email"));
+
+        let lenient = PasswordPolicy {
+            min_length: 6,
+            min_classes: 1,
+        };
+        assert_eq!(
+            lenient.check(email, "Password123").unwrap_err(),
+            "Password is too common"
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(
+            guard(5, 20).limits(" Dev@Example.com ", Some(ip)),
+            vec![
+                ("account:dev@example.com".to_string(), 5),
+                ("ip:203.0.113.7".to_string(), 20),
+            ]
+        );
+        // Without a client IP, or with a limit of 0, that source isn't throttled
+        assert_eq!(
+            guard(5, 20).limits("dev@example.com", None),
+            vec![("account:dev@example.com".to_string(), 5)]
+        );
+        assert_eq!(
+            guard(0, 20).limits("dev@example.com", Some(ip)),
+            vec![("ip:203.0.113.7".to_string(), 20)]
+        );
+        assert!(guard(0, 0).limits("dev@example.com", Some(ip)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_captcha_needs_a_token_when_configured() {
+        let mut guard = guard(5, 20);
+        assert!(guard.verify_captcha(None, None).await.is_ok());
+
+        guard.captcha = Some(CaptchaVerifier::new(
+            "http://127.0.0.1:9/siteverify",
+            "secret",
+        ));
+        assert!(matches!(
+            guard.verify_captcha(None, None).await,
+            Err(CaptchaError::Missing)
+        ));
+        assert!(matches!(
+            guard.verify_captcha(Some(""), None).await,
+            Err(CaptchaError::Missing)
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use runtime::core::provisioning::{
+    remote_repository, PushedImage, RegistryAuth, MANIFEST_LIST_TAG,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Media type of the manifest lists pushed, an OCI image index
+pub const IMAGE_INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// Host Docker Hub's registry API is served from, for repositories under `docker.io`
+const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+/// The manifest list of an image's per-platform builds, so pulling
+/// `repository/image:latest` gets the build of the puller's platform
+pub fn manifest_list(pushed: &[PushedImage]) -> Value {
+    let manifests: Vec<Value> = pushed
+        .iter()
+        .map(|image| {
+            let mut platform = json!({
+                "architecture": image.platform.arch,
+                "os": image.platform.os,
+            });
+            if let Some(variant) = &image.platform.variant {
+                platform["variant"] = json!(variant);
+            }
+            json!({
+                "mediaType": image.media_type,
+                "digest": image.digest,
+                "size": image.size,
+                "platform": platform,
+            })
+        })
+        .collect();
+    json!({
+        "schemaVersion": 2,
+        "mediaType": IMAGE_INDEX_MEDIA_TYPE,
+        "manifests": manifests,
+    })
+}
+
+/// URL of the registry API manifest `reference` of the repository `repository/image`
+fn manifest_url(image: &str, repository: &str, reference: &str) -> String {
+    let remote = remote_repository(image, repository);
+    let (host, name) = remote.split_once('/').unwrap_or(("", &remote));
+    let host = if host == "docker.io" {
+        DOCKER_HUB_REGISTRY
+    } else {
+        host
+    };
+    // Like Docker, only a registry on the host itself is spoken to without TLS
+    let scheme = if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+        "http"
+    } else {
+        "https"
+    };
+    format!("{scheme}://{host}/v2/{name}/manifests/{reference}")
+}
+
+/// Where to get a token for the registry from, read from a `WWW-Authenticate` header
+/// such as `Bearer realm="https://auth.docker.io/token",service="registry.docker.io"`
+#[derive(Debug, PartialEq)]
+struct BearerChallenge {
+    realm: String,
+    /// Query parameters of the token request, `service` and `scope`
+    params: Vec<(String, String)>,
+}
+
+fn parse_bearer_challenge(header: &str) -> Option<BearerChallenge> {
+    let (scheme, rest) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let mut realm = None;
+    let mut params = Vec::new();
+    let mut rest = rest.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        if key == "realm" {
+            realm = Some(value.to_string());
+        } else {
+            params.push((key, value.to_string()));
+        }
+        rest = remainder.trim_start_matches(',').trim();
+    }
+    Some(BearerChallenge {
+        realm: realm?,
+        params,
+    })
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+/// Pushes the manifest list of the builds [`runtime::core::provisioning::push_image`]
+/// pushed as `repository/image:latest`.
+///
+/// Registries asking for a token get one from their token service, with the registry
+/// credentials when there are some.
+///
+/// # Returns
+///
+/// The reference of the manifest list.
+pub async fn push_manifest_list(
+    client: &reqwest::Client,
+    image: &str,
+    repository: &str,
+    auth: &RegistryAuth,
+    pushed: &[PushedImage],
+) -> Result<String, String> {
+    let url = manifest_url(image, repository, MANIFEST_LIST_TAG);
+    let body = serde_json::to_vec(&manifest_list(pushed)).map_err(|e| e.to_string())?;
+    let put = |bearer: Option<&str>| {
+        let request = client
+            .put(&url)
+            .header(reqwest::header::CONTENT_TYPE, IMAGE_INDEX_MEDIA_TYPE)
+            .body(body.clone());
+        match (bearer, &auth.username) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some(username)) => request.basic_auth(username, auth.password.as_ref()),
+            (None, None) => request,
+        }
+    };
+
+    let mut response = put(None).send().await.map_err(|e| e.to_string())?;
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        let challenge = response
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|header| header.to_str().ok())
+            .and_then(parse_bearer_challenge)
+            .ok_or_else(|| format!("the registry refused the manifest list at {url}"))?;
+        let mut request = client.get(&challenge.realm).query(&challenge.params);
+        if let Some(username) = &auth.username {
+            request = request.basic_auth(username, auth.password.as_ref());
+        }
+        let token = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("failed to get a registry token: {e}"))?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| format!("invalid registry token: {e}"))?;
+        let token = token
+            .token
+            .or(token.access_token)
+            .ok_or("the registry token service answered no token")?;
+        response = put(Some(&token)).send().await.map_err(|e| e.to_string())?;
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "the registry answered {status} to the manifest list: {body}"
+        ));
+    }
+    Ok(format!(
+        "{}:{MANIFEST_LIST_TAG}",
+        remote_repository(image, repository)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pushed(platform: &str, digest: &str) -> PushedImage {
+        let platform: runtime::core::platform::Platform = platform.parse().unwrap();
+        PushedImage {
+            reference: format!("registry.internal/invok/app:{}", platform.tag()),
+            platform,
+            media_type: "application/vnd.docker.distribution.manifest.v2+json".to_string(),
+            digest: digest.to_string(),
+            size: 1234,
+        }
+    }
+
+    #[test]
+    fn test_manifest_list() {
+        let list = manifest_list(&[
+            pushed("linux/amd64", "sha256:aaa"),
+            pushed("linux/arm/v7", "sha256:bbb"),
+        ]);
+        assert_eq!(list["schemaVersion"], 2);
+        assert_eq!(list["mediaType"], IMAGE_INDEX_MEDIA_TYPE);
+        let manifests = list["manifests"].as_array().unwrap();
+        assert_eq!(manifests.len(), 2);
+        assert_eq!(manifests[0]["digest"], "sha256:aaa");
+        assert_eq!(manifests[0]["size"], 1234);
+        assert_eq!(
+            manifests[0]["platform"],
+            json!({ "architecture": "amd64", "os": "linux" })
+        );
+        assert_eq!(
+            manifests[1]["platform"],
+            json!({ "architecture": "arm", "os": "linux", "variant": "v7" })
+        );
+    }
+
+    #[test]
+    fn test_manifest_url() {
+        assert_eq!(
+            manifest_url("app", "registry.example.com/invok/", "latest"),
+            "https://registry.example.com/v2/invok/app/manifests/latest"
+        );
+        assert_eq!(
+            manifest_url("app", "localhost:5000", "latest"),
+            "http://localhost:5000/v2/app/manifests/latest"
+        );
+        assert_eq!(
+            manifest_url("app", "docker.io/acme", "latest"),
+            "https://registry-1.docker.io/v2/acme/app/manifests/latest"
+        );
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:acme/app:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "https://auth.docker.io/token");
+        assert_eq!(
+            challenge.params,
+            vec![
+                ("service".to_string(), "registry.docker.io".to_string()),
+                (
+                    "scope".to_string(),
+                    "repository:acme/app:pull,push".to_string()
+                ),
+            ]
+        );
+        assert_eq!(parse_bearer_challenge(r#"Basic realm="registry""#), None);
+        assert_eq!(parse_bearer_challenge("Bearer service=registry"), None);
+    }
+}
@@ -0,0 +1,528 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::models::{NotificationKind, NotificationSettings, NotificationTarget};
+use crate::lifecycle_manager::jobs::JobQueue;
+use crate::utils::egress::is_public;
+use crate::utils::utils::generate_hash;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use ring::hmac;
+use runtime::core::autoscaler::{Autoscaler, ScalingAnomaly};
+use sea_orm::prelude::ChronoDateTimeUtc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::lookup_host;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Header carrying the HMAC-SHA256 of a webhook's body, as `sha256=<hex>`
+pub(crate) const SIGNATURE_HEADER: &str = "x-invok-signature";
+
+/// How long a target may take to accept a notification
+pub(crate) const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kind of the jobs delivering a notification to one target
+pub const NOTIFICATION_JOB: &str = "notification";
+
+/// A function crash-looping would otherwise notify on every restart; the same anomaly of
+/// a function is sent at most once per window
+const ANOMALY_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// How long the anomaly loop trusts its list of the namespaces that have targets
+const NAMESPACE_INDEX_TTL: Duration = Duration::from_secs(60);
+
+/// An event sent to a namespace's notification targets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub namespace: Uuid,
+    pub function: String,
+    /// Human readable details, e.g. the deploy error
+    pub message: String,
+    /// RFC 3339 time of the event
+    pub occurred_at: String,
+}
+
+impl Notification {
+    pub fn new(
+        kind: NotificationKind,
+        namespace: Uuid,
+        function: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            namespace,
+            function: function.into(),
+            message: message.into(),
+            occurred_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+        }
+    }
+
+    /// One-line text for chat targets
+    fn text(&self) -> String {
+        let event = match self.kind {
+            // The message already says it all
+            NotificationKind::DeploySucceeded => return self.message.clone(),
+            NotificationKind::DeployFailed => "failed to deploy",
+            NotificationKind::DeployPendingApproval => "waits for approval to deploy",
+            NotificationKind::ContainerCrashed => "crashed",
+            NotificationKind::ScaleUpFailed => "failed to scale up",
+        };
+        format!("Function '{}' {}: {}", self.function, event, self.message)
+    }
+}
+
+/// A channel notifications are delivered through.
+///
+/// [`WebhookNotifier`] posts them as signed JSON and [`SlackNotifier`] as Slack
+/// messages; [`notifier`] picks the one for a namespace's target.
+pub trait Notifier: Send + Sync {
+    /// Where the notifications are posted
+    fn url(&self) -> &str;
+
+    /// The request delivering `notification`, made with `client`
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification,
+    ) -> Result<reqwest::Request, String>;
+}
+
+/// Posts notifications as JSON, signed with the target's secret when it has one
+pub struct WebhookNotifier {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+impl Notifier for WebhookNotifier {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification,
+    ) -> Result<reqwest::Request, String> {
+        let payload = serde_json::to_vec(notification)
+            .map_err(|e| format!("Failed to encode notification: {e}"))?;
+        let mut request = client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign_payload(secret, &payload));
+        }
+        request
+            .body(payload)
+            .build()
+            .map_err(|e| format!("Invalid notification request: {e}"))
+    }
+}
+
+/// Posts notifications as messages to a Slack incoming webhook
+pub struct SlackNotifier {
+    pub webhook_url: String,
+}
+
+impl Notifier for SlackNotifier {
+    fn url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification,
+    ) -> Result<reqwest::Request, String> {
+        let message = serde_json::json!({ "text": notification.text() });
+        client
+            .post(&self.webhook_url)
+            .json(&message)
+            .build()
+            .map_err(|e| format!("Invalid notification request: {e}"))
+    }
+}
+
+/// The channel a target's notifications go through
+pub fn notifier(target: &NotificationTarget) -> Box<dyn Notifier> {
+    match target {
+        NotificationTarget::Webhook { url, secret, .. } => Box::new(WebhookNotifier {
+            url: url.clone(),
+            secret: secret.clone(),
+        }),
+        NotificationTarget::Slack { webhook_url, .. } => Box::new(SlackNotifier {
+            webhook_url: webhook_url.clone(),
+        }),
+    }
+}
+
+/// `sha256=<hex>` HMAC of a webhook payload, for [`SIGNATURE_HEADER`]
+pub(crate) fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, payload);
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// Payload of a [`NOTIFICATION_JOB`].
+///
+/// The target is named by its URL and looked up when the job runs, so webhook secrets
+/// stay out of the queue and targets removed in the meantime aren't notified.
+#[derive(Debug, Serialize, Deserialize)]
+struct Delivery {
+    notification: Notification,
+    target_url: String,
+}
+
+/// Sends a notification to the targets of its namespace that want it, in the
+/// background. Each target gets its own job, so failed deliveries are retried without
+/// notifying the other targets twice.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `jobs` - The queue the deliveries are run from.
+/// * `notification` - The event to send.
+pub fn notify(conn: &DatabaseConnection, jobs: &JobQueue, notification: Notification) {
+    let conn = conn.clone();
+    let jobs = jobs.clone();
+    tokio::spawn(async move {
+        let settings = match AuthDBRepo::find_by_uuid(&conn, notification.namespace).await {
+            Ok(Some(user)) => NotificationSettings::from_model(&user),
+            Ok(None) => return,
+            Err(e) => {
+                error!(
+                    "Failed to load notification targets of namespace {}: {}",
+                    notification.namespace, e
+                );
+                return;
+            }
+        };
+        deliver(&jobs, &settings, &notification).await;
+    });
+}
+
+/// Notifies namespaces of the anomalies the autoscaler reports in their functions'
+/// pools, for as long as the server runs.
+pub async fn run_anomaly_loop(
+    conn: DatabaseConnection,
+    jobs: JobQueue,
+    autoscaler: Arc<Autoscaler>,
+) {
+    let mut anomalies = autoscaler.subscribe_anomalies();
+    let mut last_sent: HashMap<(String, NotificationKind), Instant> = HashMap::new();
+    let mut namespaces = NamespaceIndex::default();
+
+    loop {
+        let anomaly = match anomalies.recv().await {
+            Ok(anomaly) => anomaly,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Missed {} scaling anomalies", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        let (function_key, kind, message) = match anomaly {
+            ScalingAnomaly::ContainerDied {
+                function_key,
+                summary,
+            } => (function_key, NotificationKind::ContainerCrashed, summary),
+            ScalingAnomaly::ScaleUpFailed {
+                function_key,
+                reason,
+            } => (function_key, NotificationKind::ScaleUpFailed, reason),
+        };
+
+        last_sent.retain(|_, sent| sent.elapsed() < ANOMALY_WINDOW);
+        let key = (function_key, kind);
+        if last_sent.contains_key(&key) {
+            continue;
+        }
+        last_sent.insert(key.clone(), Instant::now());
+        let (function_key, _) = &key;
+
+        if namespaces.is_stale() {
+            match AuthDBRepo::find_notified_namespaces(&conn).await {
+                Ok(uuids) => namespaces = NamespaceIndex::new(uuids),
+                Err(e) => {
+                    error!("Failed to load notified namespaces: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some((function, namespace)) = namespaces.resolve(function_key) else {
+            continue;
+        };
+        let settings = match AuthDBRepo::find_by_uuid(&conn, namespace).await {
+            Ok(Some(user)) => NotificationSettings::from_model(&user),
+            Ok(None) => continue,
+            Err(e) => {
+                error!(
+                    "Failed to load notification targets of namespace {}: {}",
+                    namespace, e
+                );
+                continue;
+            }
+        };
+        let notification = Notification::new(kind, namespace, function, message);
+        deliver(&jobs, &settings, &notification).await;
+    }
+}
+
+/// The namespaces with notification targets, by the hash function keys carry, so an
+/// anomaly is matched to its namespace without loading every account
+#[derive(Default)]
+struct NamespaceIndex {
+    by_hash: HashMap<String, Uuid>,
+    loaded_at: Option<Instant>,
+}
+
+impl NamespaceIndex {
+    fn new(uuids: Vec<Uuid>) -> Self {
+        Self {
+            by_hash: uuids
+                .into_iter()
+                .map(|uuid| (generate_hash(uuid), uuid))
+                .collect(),
+            loaded_at: Some(Instant::now()),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.loaded_at
+            .is_none_or(|loaded_at| loaded_at.elapsed() >= NAMESPACE_INDEX_TTL)
+    }
+
+    /// The function and namespace of a `<function>-<namespace hash>` key, if the
+    /// namespace has targets
+    fn resolve<'a>(&self, function_key: &'a str) -> Option<(&'a str, Uuid)> {
+        let (function, hash) = function_key.rsplit_once('-')?;
+        let namespace = self.by_hash.get(hash)?;
+        (!function.is_empty()).then_some((function, *namespace))
+    }
+}
+
+/// Queues a delivery of the notification to every target that wants it
+async fn deliver(jobs: &JobQueue, settings: &NotificationSettings, notification: &Notification) {
+    for target in settings
+        .targets
+        .iter()
+        .filter(|target| target.wants(notification.kind))
+    {
+        let delivery = Delivery {
+            notification: notification.clone(),
+            target_url: target.url().to_string(),
+        };
+        if let Err(e) = jobs.enqueue(NOTIFICATION_JOB, delivery).await {
+            error!("Failed to queue notification to {}: {}", target.url(), e);
+        }
+    }
+}
+
+/// Runs a [`NOTIFICATION_JOB`]: posts the notification to its target.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `payload` - The [`Delivery`] to make.
+///
+/// # Returns
+///
+/// * `Ok(())` once the target accepted the notification, or if the namespace no longer
+///   has the target; otherwise why the delivery failed, so it is retried.
+pub async fn run_delivery(
+    conn: &DatabaseConnection,
+    payload: serde_json::Value,
+) -> Result<(), String> {
+    let delivery: Delivery =
+        serde_json::from_value(payload).map_err(|e| format!("Invalid delivery: {e}"))?;
+    let notification = &delivery.notification;
+    let settings = match AuthDBRepo::find_by_uuid(conn, notification.namespace).await {
+        Ok(Some(user)) => NotificationSettings::from_model(&user),
+        Ok(None) => return Ok(()),
+        Err(e) => return Err(format!("Failed to load notification targets: {e}")),
+    };
+    let Some(target) = settings
+        .targets
+        .iter()
+        .find(|target| target.url() == delivery.target_url && target.wants(notification.kind))
+    else {
+        return Ok(());
+    };
+
+    let notifier = notifier(target);
+    let client = pinned_client(notifier.url()).await?;
+    let request = notifier.request(&client, notification)?;
+    match client.execute(request).await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => {
+            warn!(
+                "Notification target {} answered {}",
+                delivery.target_url,
+                response.status()
+            );
+            Err(format!("Target answered {}", response.status()))
+        }
+        Err(e) => {
+            warn!("Failed to notify {}: {}", delivery.target_url, e);
+            Err(format!("Failed to notify target: {e}"))
+        }
+    }
+}
+
+/// A client for the target at `url`, pinned to the address its host resolves to once
+/// that is checked to be public, so targets can't reach the host, the container network
+/// or the cloud metadata service, also not by redirecting
+async fn pinned_client(url: &str) -> Result<reqwest::Client, String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid target URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("'{}' is not an http(s) URL", url));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "Target URL has no host".to_string())?
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addrs: Vec<_> = lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| format!("Failed to resolve {host}: {e}"))?
+        .collect();
+    let addr = match addrs.first() {
+        Some(addr) if addrs.iter().all(|addr| is_public(addr.ip())) => *addr,
+        _ => return Err(format!("{host} does not resolve to a public address")),
+    };
+    reqwest::Client::builder()
+        .resolve(&host, addr)
+        .redirect(Policy::none())
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build the notification client: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(kind: NotificationKind) -> Notification {
+        Notification::new(kind, Uuid::nil(), "api", "exit code 137")
+    }
+
+    #[test]
+    fn test_sign_payload() {
+        // The widely published HMAC-SHA256 example
+        assert_eq!(
+            sign_payload("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            notification(NotificationKind::ContainerCrashed).text(),
+            "Function 'api' crashed: exit code 137"
+        );
+        let deployed = Notification::new(
+            NotificationKind::DeploySucceeded,
+            Uuid::nil(),
+            "api",
+            "Deployed 'api'",
+        );
+        assert_eq!(deployed.text(), "Deployed 'api'");
+    }
+
+    #[test]
+    fn test_webhook_request() {
+        let client = reqwest::Client::new();
+        let notification = notification(NotificationKind::ScaleUpFailed);
+        let signed = WebhookNotifier {
+            url: "https://hooks.example.com/invok".to_string(),
+            secret: Some("s3cret".to_string()),
+        }
+        .request(&client, &notification)
+        .unwrap();
+
+        let body = signed.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(signed.url().as_str(), "https://hooks.example.com/invok");
+        assert_eq!(
+            signed.headers()[SIGNATURE_HEADER],
+            sign_payload("s3cret", body).as_str()
+        );
+        let sent: Notification = serde_json::from_slice(body).unwrap();
+        assert_eq!(sent.kind, NotificationKind::ScaleUpFailed);
+        assert_eq!(sent.function, "api");
+
+        let unsigned = WebhookNotifier {
+            url: "https://hooks.example.com/invok".to_string(),
+            secret: None,
+        }
+        .request(&client, &notification)
+        .unwrap();
+        assert!(!unsigned.headers().contains_key(SIGNATURE_HEADER));
+    }
+
+    #[test]
+    fn test_slack_request() {
+        let target = NotificationTarget::Slack {
+            webhook_url: "https://hooks.slack.com/services/T/B/X".to_string(),
+            events: Vec::new(),
+        };
+        let notifier = notifier(&target);
+        let request = notifier
+            .request(
+                &reqwest::Client::new(),
+                &notification(NotificationKind::ContainerCrashed),
+            )
+            .unwrap();
+
+        assert_eq!(notifier.url(), "https://hooks.slack.com/services/T/B/X");
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().and_then(|body| body.as_bytes()).unwrap())
+                .unwrap();
+        assert_eq!(body["text"], "Function 'api' crashed: exit code 137");
+    }
+
+    #[tokio::test]
+    async fn test_pinned_client_refuses_internal_targets() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5/hook",
+            "http://localhost/hook",
+            "http://[::1]/hook",
+            "ftp://example.com/hook",
+            "not a url",
+        ] {
+            assert!(pinned_client(url).await.is_err(), "{url} was allowed");
+        }
+    }
+
+    #[test]
+    fn test_namespace_index() {
+        let namespace = Uuid::new_v4();
+        let index = NamespaceIndex::new(vec![namespace]);
+        let hash = generate_hash(namespace);
+
+        assert!(!index.is_stale());
+        assert!(NamespaceIndex::default().is_stale());
+        assert_eq!(
+            index.resolve(&format!("my-api-{hash}")),
+            Some(("my-api", namespace))
+        );
+        assert_eq!(index.resolve(&format!("-{hash}")), None);
+        assert_eq!(
+            index.resolve(&format!("api-{}", generate_hash(Uuid::new_v4()))),
+            None
+        );
+        assert_eq!(index.resolve("api"), None);
+    }
+}
@@ -0,0 +1,539 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::oidc_trust::OidcTrustDBRepo;
+use db_entities::auth::Model as AuthUser;
+use db_entities::oidc_trust::Model as OidcTrust;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How long the issuer's signing keys are used before they are fetched again
+const KEYS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Least time between two fetches triggered by a token signed with an unknown key
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout of requests to the issuer
+const ISSUER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why an OIDC token could not be exchanged
+#[derive(Debug, Error)]
+pub enum OidcError {
+    /// The token is malformed, expired, or not signed by the configured issuer
+    #[error("Invalid OIDC token: {0}")]
+    InvalidToken(String),
+    /// The token is valid but no trust of the namespace accepts it
+    #[error("Not trusted: {0}")]
+    NotTrusted(String),
+    /// The issuer's signing keys could not be fetched
+    #[error("OIDC issuer unavailable: {0}")]
+    IssuerUnavailable(String),
+    #[error("System error: {0}")]
+    SystemError(String),
+}
+
+/// Claims of a CI workflow's OIDC token that trusts are checked against
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkflowClaims {
+    /// `repo:<owner>/<name>:...`, identifies the workflow run
+    pub sub: String,
+    /// `<owner>/<name>` of the repository the workflow runs in
+    pub repository: String,
+    /// Id of the repository, which stays the same across renames
+    #[serde(default)]
+    pub repository_id: Option<String>,
+    /// Id of the repository owner, which stays the same across renames
+    #[serde(default)]
+    pub repository_owner_id: Option<String>,
+    /// Git ref the workflow runs for, e.g. `refs/heads/main`
+    #[serde(rename = "ref", default)]
+    pub git_ref: Option<String>,
+    /// Deployment environment of the job, if it has one
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+/// A CI workflow trusted to deploy into a namespace
+#[derive(Debug, Serialize)]
+pub struct TrustPolicy {
+    pub id: i32,
+    /// `<owner>/<name>`; `*` in the name matches any characters
+    pub repository: String,
+    /// Id of the repository owner, `None` for trusts added before ids were stored
+    pub repository_owner_id: Option<String>,
+    /// Id of the repository, `None` when `repository` is a pattern
+    pub repository_id: Option<String>,
+    /// Git ref the workflow must run for; `*` matches any characters
+    pub ref_pattern: Option<String>,
+    /// Deployment environment the job must run in
+    pub environment: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+}
+
+impl From<OidcTrust> for TrustPolicy {
+    fn from(trust: OidcTrust) -> Self {
+        Self {
+            id: trust.id,
+            repository: trust.repository,
+            repository_owner_id: trust.repository_owner_id,
+            repository_id: trust.repository_id,
+            ref_pattern: trust.ref_pattern,
+            environment: trust.environment,
+            created_at: trust.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Checks a trust before it is stored.
+///
+/// The repository owner must be spelled out: a pattern like `*/*` would let every
+/// repository on the issuer deploy into the namespace. Names can be renamed and then
+/// taken by someone else, so the trust also pins the owner's id, and the repository's
+/// id unless the name is a pattern.
+pub fn validate_trust(
+    repository: &str,
+    repository_owner_id: &str,
+    repository_id: Option<&str>,
+    ref_pattern: Option<&str>,
+) -> Result<(), String> {
+    let Some((owner, name)) = repository.split_once('/') else {
+        return Err(format!(
+            "repository '{}' must be <owner>/<name>",
+            repository
+        ));
+    };
+    if owner.is_empty() || owner.contains('*') || name.is_empty() || name.contains('/') {
+        return Err(format!(
+            "repository '{}' must be <owner>/<name>, with `*` allowed only in the name",
+            repository
+        ));
+    }
+    if !is_id(repository_owner_id) {
+        return Err(format!(
+            "repository owner id '{}' must be numeric",
+            repository_owner_id
+        ));
+    }
+    match repository_id {
+        Some(id) if name.contains('*') => {
+            return Err(format!(
+                "repository id '{}' can't be given for the pattern '{}'",
+                id, repository
+            ))
+        }
+        Some(id) if !is_id(id) => {
+            return Err(format!("repository id '{}' must be numeric", id));
+        }
+        None if !name.contains('*') => {
+            return Err(format!("repository id of '{}' is required", repository));
+        }
+        _ => {}
+    }
+    if ref_pattern.is_some_and(|pattern| !pattern.starts_with("refs/")) {
+        return Err("ref pattern must start with refs/, e.g. refs/heads/main".to_string());
+    }
+    Ok(())
+}
+
+fn is_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether a trust accepts a workflow's token.
+///
+/// The token's immutable ids must match the trust's: a repository renamed keeps
+/// deploying, and whoever takes over its old name can't. The name is only checked for
+/// patterns, within the owner the trust pins.
+fn allows(trust: &OidcTrust, claims: &WorkflowClaims) -> bool {
+    // Trusts without an owner id predate ids and must be added again
+    let Some(owner_id) = &trust.repository_owner_id else {
+        return false;
+    };
+    if claims.repository_owner_id.as_ref() != Some(owner_id) {
+        return false;
+    }
+    match &trust.repository_id {
+        Some(repository_id) => {
+            if claims.repository_id.as_ref() != Some(repository_id) {
+                return false;
+            }
+        }
+        // GitHub repository names are case-insensitive
+        None => {
+            if !matches_pattern(
+                &trust.repository.to_ascii_lowercase(),
+                &claims.repository.to_ascii_lowercase(),
+            ) {
+                return false;
+            }
+        }
+    }
+    if let Some(pattern) = &trust.ref_pattern {
+        if !claims
+            .git_ref
+            .as_deref()
+            .is_some_and(|git_ref| matches_pattern(pattern, git_ref))
+        {
+            return false;
+        }
+    }
+    if let Some(environment) = &trust.environment {
+        if claims.environment.as_ref() != Some(environment) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Matches `value` against a pattern in which `*` stands for any run of characters
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the pattern must match exactly
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Verifies OIDC tokens against the signing keys the issuer publishes.
+///
+/// The keys are discovered through the issuer's `/.well-known/openid-configuration`
+/// and cached; a token signed with a key that isn't cached triggers a refetch, so key
+/// rotation is picked up without a restart.
+pub struct OidcVerifier {
+    issuer: String,
+    audience: String,
+    client: reqwest::Client,
+    keys: RwLock<Option<CachedKeys>>,
+}
+
+struct CachedKeys {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct OpenIdConfiguration {
+    jwks_uri: String,
+}
+
+impl OidcVerifier {
+    pub fn new(issuer: &str, audience: &str) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            audience: audience.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(ISSUER_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            keys: RwLock::new(None),
+        }
+    }
+
+    /// Checks the token's signature, issuer, audience and expiry, and returns its claims
+    pub async fn verify(&self, token: &str) -> Result<WorkflowClaims, OidcError> {
+        let header = decode_header(token).map_err(|e| OidcError::InvalidToken(e.to_string()))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| OidcError::InvalidToken("token has no key id".to_string()))?;
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        let token_data = decode::<WorkflowClaims>(token, &key, &validation)
+            .map_err(|e| OidcError::InvalidToken(e.to_string()))?;
+        Ok(token_data.claims)
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, OidcError> {
+        {
+            let cached = self.keys.read().await;
+            if let Some(cached) = cached.as_ref() {
+                let fresh = cached.fetched_at.elapsed() < KEYS_TTL;
+                match cached.keys.find(kid) {
+                    Some(jwk) if fresh => return decoding_key_from(jwk),
+                    // Keys were fetched moments ago; don't let bogus kids hammer the issuer
+                    None if cached.fetched_at.elapsed() < MIN_REFETCH_INTERVAL => {
+                        return Err(OidcError::InvalidToken(format!("unknown key id '{}'", kid)))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut cached = self.keys.write().await;
+        // Another request may have refreshed the keys while this one waited
+        let refreshed = cached
+            .as_ref()
+            .is_some_and(|cached| cached.fetched_at.elapsed() < MIN_REFETCH_INTERVAL);
+        if !refreshed {
+            match self.fetch_keys().await {
+                Ok(keys) => {
+                    *cached = Some(CachedKeys {
+                        keys,
+                        fetched_at: Instant::now(),
+                    })
+                }
+                // Stale keys are still good for tokens signed with a known key
+                Err(e) if cached.is_some() => {
+                    warn!("Failed to refresh OIDC keys of {}: {}", self.issuer, e)
+                }
+                Err(e) => return Err(OidcError::IssuerUnavailable(e.to_string())),
+            }
+        }
+
+        let jwk = cached
+            .as_ref()
+            .and_then(|cached| cached.keys.find(kid))
+            .ok_or_else(|| OidcError::InvalidToken(format!("unknown key id '{}'", kid)))?;
+        decoding_key_from(jwk)
+    }
+
+    async fn fetch_keys(&self) -> Result<JwkSet, reqwest::Error> {
+        let configuration: OpenIdConfiguration = self
+            .client
+            .get(format!("{}/.well-known/openid-configuration", self.issuer))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let keys: JwkSet = self
+            .client
+            .get(configuration.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        info!("Fetched {} OIDC keys of {}", keys.keys.len(), self.issuer);
+        Ok(keys)
+    }
+}
+
+fn decoding_key_from(jwk: &jsonwebtoken::jwk::Jwk) -> Result<DecodingKey, OidcError> {
+    DecodingKey::from_jwk(jwk).map_err(|e| OidcError::InvalidToken(e.to_string()))
+}
+
+/// Exchanges a CI workflow's OIDC token for access to a namespace.
+///
+/// The token must be valid and accepted by at least one of the namespace's trusts.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `verifier` - The verifier of the configured issuer.
+/// * `token` - The workflow's OIDC token.
+/// * `namespace` - The namespace the workflow wants to deploy to.
+///
+/// # Returns
+///
+/// The owner of the namespace.
+pub async fn exchange_token(
+    conn: &DatabaseConnection,
+    verifier: &OidcVerifier,
+    token: &str,
+    namespace: Uuid,
+) -> Result<AuthUser, OidcError> {
+    let claims = verifier.verify(token).await?;
+    let not_trusted = || {
+        OidcError::NotTrusted(format!(
+            "namespace '{}' does not trust {} ({})",
+            namespace,
+            claims.repository,
+            claims.git_ref.as_deref().unwrap_or("no ref")
+        ))
+    };
+
+    // An unknown namespace looks the same as an untrusting one
+    let user = AuthDBRepo::find_by_uuid(conn, namespace)
+        .await
+        .map_err(|e| database_error("Failed to find namespace", e))?
+        .ok_or_else(not_trusted)?;
+    let trusts = OidcTrustDBRepo::find_by_user(conn, user.id)
+        .await
+        .map_err(|e| database_error("Failed to list trusts", e))?;
+    if !trusts.iter().any(|trust| allows(trust, &claims)) {
+        return Err(not_trusted());
+    }
+
+    info!(
+        "OIDC token of '{}' exchanged for namespace '{}'",
+        claims.sub, namespace
+    );
+    Ok(user)
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> OidcError {
+    error!("{}: {}", context, e);
+    OidcError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::prelude::DateTimeWithTimeZone;
+
+    const OWNER_ID: &str = "1342004";
+    const REPOSITORY_ID: &str = "75104123";
+
+    fn trust(repository: &str, ref_pattern: Option<&str>) -> OidcTrust {
+        OidcTrust {
+            id: 1,
+            auth_id: 1,
+            repository: repository.to_string(),
+            ref_pattern: ref_pattern.map(str::to_string),
+            environment: None,
+            created_at: DateTimeWithTimeZone::default(),
+            repository_owner_id: Some(OWNER_ID.to_string()),
+            repository_id: (!repository.contains('*')).then(|| REPOSITORY_ID.to_string()),
+        }
+    }
+
+    fn claims(repository: &str, git_ref: Option<&str>) -> WorkflowClaims {
+        WorkflowClaims {
+            sub: format!("repo:{}:ref:refs/heads/main", repository),
+            repository: repository.to_string(),
+            repository_id: Some(REPOSITORY_ID.to_string()),
+            repository_owner_id: Some(OWNER_ID.to_string()),
+            git_ref: git_ref.map(str::to_string),
+            environment: None,
+        }
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        // Without `*` only the exact value matches
+        assert!(matches_pattern("acme/api", "acme/api"));
+        assert!(!matches_pattern("acme/api", "acme/api2"));
+        assert!(!matches_pattern("acme/api", "acme/ap"));
+        assert!(!matches_pattern("acme/api", "xacme/api"));
+
+        // A trailing `*`
+        assert!(matches_pattern("refs/heads/*", "refs/heads/main"));
+        assert!(matches_pattern("refs/heads/*", "refs/heads/feature/x"));
+        assert!(!matches_pattern("refs/heads/*", "refs/tags/v1"));
+
+        // A `*` in the middle
+        assert!(matches_pattern("acme/svc-*-api", "acme/svc-orders-api"));
+        assert!(!matches_pattern("acme/svc-*-api", "acme/svc-x-api-old"));
+        assert!(!matches_pattern("acme/svc-*-api", "acme/svc-orders"));
+        assert!(matches_pattern("refs/*/v*", "refs/tags/v1.2"));
+
+        // `*` matches nothing too, but the parts around it can't overlap
+        assert!(matches_pattern("acme/api*", "acme/api"));
+        assert!(matches_pattern("acme/*api", "acme/api"));
+        assert!(matches_pattern("acme/a*pi", "acme/api"));
+        assert!(!matches_pattern("acme/api*i", "acme/api"));
+    }
+
+    #[test]
+    fn test_validate_trust() {
+        let id = Some(REPOSITORY_ID);
+        assert!(validate_trust("acme/api", OWNER_ID, id, None).is_ok());
+        assert!(validate_trust("acme/*", OWNER_ID, None, Some("refs/heads/main")).is_ok());
+
+        // Owners are spelled out, so no pattern reaches every repository
+        assert!(validate_trust("*/*", OWNER_ID, None, None).is_err());
+        assert!(validate_trust("*/api", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("ac*/api", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("acme", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("/api", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("acme/", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("acme/api/x", OWNER_ID, id, None).is_err());
+        assert!(validate_trust("acme/api", OWNER_ID, id, Some("main")).is_err());
+
+        // Ids are numeric; a single repository needs its id, a pattern can't have one
+        assert!(validate_trust("acme/api", "", id, None).is_err());
+        assert!(validate_trust("acme/api", "acme", id, None).is_err());
+        assert!(validate_trust("acme/api", OWNER_ID, Some("api"), None).is_err());
+        assert!(validate_trust("acme/api", OWNER_ID, None, None).is_err());
+        assert!(validate_trust("acme/*", OWNER_ID, id, None).is_err());
+    }
+
+    #[test]
+    fn test_allows_matches_ids_not_names() {
+        let trust = trust("acme/api", None);
+        assert!(allows(&trust, &claims("acme/api", None)));
+
+        // The repository was renamed: its ids are the same
+        assert!(allows(&trust, &claims("acme/api-v2", None)));
+
+        // Someone else took the old name: the ids differ
+        let mut impostor = claims("acme/api", None);
+        impostor.repository_id = Some("99999999".to_string());
+        assert!(!allows(&trust, &impostor));
+        impostor.repository_owner_id = Some("7".to_string());
+        assert!(!allows(&trust, &impostor));
+
+        // Tokens without ids can't satisfy a trust
+        let mut anonymous = claims("acme/api", None);
+        anonymous.repository_id = None;
+        anonymous.repository_owner_id = None;
+        assert!(!allows(&trust, &anonymous));
+
+        // Trusts stored before ids were stored accept nothing
+        let mut legacy = trust;
+        legacy.repository_owner_id = None;
+        legacy.repository_id = None;
+        assert!(!allows(&legacy, &claims("acme/api", None)));
+    }
+
+    #[test]
+    fn test_allows_pins_owner_of_patterns() {
+        let trust = trust("acme/api-*", None);
+        assert!(allows(&trust, &claims("acme/api-orders", None)));
+
+        // An owner renamed to, or recreated as, `acme` has another id
+        let mut other_owner = claims("acme/api-orders", None);
+        other_owner.repository_owner_id = Some("7".to_string());
+        assert!(!allows(&trust, &other_owner));
+    }
+
+    #[test]
+    fn test_allows_folds_repository_case() {
+        let trust = trust("Acme/API-*", None);
+        assert!(allows(&trust, &claims("acme/api-orders", None)));
+        assert!(allows(&trust, &claims("ACME/Api-Orders", None)));
+        assert!(!allows(&trust, &claims("acme/web-orders", None)));
+    }
+
+    #[test]
+    fn test_allows_checks_ref_and_environment() {
+        let trust = trust("acme/api", Some("refs/heads/main"));
+        assert!(allows(&trust, &claims("acme/api", Some("refs/heads/main"))));
+        assert!(!allows(&trust, &claims("acme/api", Some("refs/heads/dev"))));
+        // A token without a ref can't satisfy a trust requiring one
+        assert!(!allows(&trust, &claims("acme/api", None)));
+        // Refs are case-sensitive
+        let upper = claims("acme/api", Some("refs/heads/MAIN"));
+        assert!(!allows(&trust, &upper));
+
+        let mut trust = trust;
+        trust.environment = Some("production".to_string());
+        let mut workflow = claims("acme/api", Some("refs/heads/main"));
+        assert!(!allows(&trust, &workflow));
+        workflow.environment = Some("staging".to_string());
+        assert!(!allows(&trust, &workflow));
+        workflow.environment = Some("production".to_string());
+        assert!(allows(&trust, &workflow));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::api_controller::AppState;
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::{DeployPreview, DeployableFunction, FunctionSettings};
+use crate::lifecycle_manager::deploy::deploy_function;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::invalidation::{invalidate, Invalidation};
+use crate::lifecycle_manager::remote_build::ImageBuilder;
+use crate::utils::utils::generate_hash;
+use db_entities::function::Model;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::provisioning::remove_image;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Separates a function's name from the branch in a preview instance name
+pub const PREVIEW_SEPARATOR: &str = "--";
+
+/// Longest branch part of a preview instance name
+const MAX_BRANCH_LENGTH: usize = 20;
+
+/// Most characters a preview adds to a function name
+pub const MAX_PREVIEW_SUFFIX_LENGTH: usize = PREVIEW_SEPARATOR.len() + MAX_BRANCH_LENGTH;
+
+/// How often expired previews are looked for
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A preview instance of a function
+#[derive(Debug, Serialize)]
+pub struct PreviewInstance {
+    /// Name the instance is invoked by
+    pub name: String,
+    /// The function being previewed
+    pub function: String,
+    pub branch: String,
+    /// RFC 3339 time the instance is removed
+    pub expires_at: Option<String>,
+}
+
+impl PreviewInstance {
+    fn from_model(function: Model) -> Self {
+        let base = function.preview_of.unwrap_or_default();
+        let branch = function
+            .name
+            .strip_prefix(&format!("{base}{PREVIEW_SEPARATOR}"))
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            name: function.name,
+            function: base,
+            branch,
+            expires_at: function.expires_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+/// Turns a branch name into the part of a preview instance name that follows the function
+/// name: lowercase letters, digits and single dashes, at most 20 characters.
+///
+/// `feature/Login_Form` becomes `feature-login-form`.
+pub fn branch_slug(branch: &str) -> Result<String, String> {
+    let mut slug = String::with_capacity(branch.len());
+    for c in branch.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_BRANCH_LENGTH);
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        return Err(format!(
+            "preview branch '{}' has no letters or digits",
+            branch
+        ));
+    }
+    Ok(slug)
+}
+
+/// Name a function's preview instance for a branch is deployed and invoked as.
+///
+/// Branches named like `v3` are refused: those names belong to version instances.
+pub fn preview_name(function_name: &str, branch: &str) -> Result<String, String> {
+    let slug = branch_slug(branch)?;
+    if is_version_slug(&slug) {
+        return Err(format!(
+            "preview branch '{}' is reserved for version instances",
+            branch
+        ));
+    }
+    Ok(format!("{function_name}{PREVIEW_SEPARATOR}{slug}"))
+}
+
+/// Name of the instance a recorded version of a function is deployed as, e.g. `hello--v3`
+pub fn version_instance_name(function_name: &str, version: i32) -> String {
+    format!("{function_name}{PREVIEW_SEPARATOR}v{version}")
+}
+
+fn is_version_slug(slug: &str) -> bool {
+    slug.strip_prefix('v')
+        .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Deploys a recorded version of a function as a preview instance, unless it is deployed
+/// already.
+///
+/// Version instances serve invocations replayed on, or routed to, a version other than
+/// the current one; see [`version_instance_name`]. With a `ttl` the instance expires like
+/// any preview, without one it stays until [`remove_version_instance`]. An existing
+/// instance that would expire is kept for good when asked for without a `ttl`.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `builder` - Builds the instance's image.
+/// * `function` - The function whose version to deploy.
+/// * `version` - The recorded version to deploy.
+/// * `ttl` - How long the instance lives.
+///
+/// # Returns
+///
+/// The instance name, and the settings it was deployed with if it was deployed now.
+pub async fn deploy_version_instance(
+    conn: &DatabaseConnection,
+    builder: &ImageBuilder,
+    function: &Model,
+    version: i32,
+    ttl: Option<Duration>,
+) -> ServelessCoreResult<(String, Option<FunctionSettings>)> {
+    let name = version_instance_name(&function.name, version);
+    if let Some(instance) = FunctionDBRepo::find_function_by_name(conn, &name, function.uuid).await
+    {
+        if ttl.is_none() && instance.expires_at.is_some() {
+            FunctionDBRepo::set_preview(conn, instance, Some(function.name.clone()), None)
+                .await
+                .map_err(|e| database_error("Failed to keep version instance", e))?;
+        }
+        return Ok((name, None));
+    }
+
+    let archive = FunctionVersionDBRepo::find_version(conn, function.id, version)
+        .await
+        .map_err(|e| database_error("Failed to load function version", e))?
+        .ok_or_else(|| {
+            ServelessCoreError::BadFunction(format!(
+                "'{}' has no version {}",
+                function.name, version
+            ))
+        })?
+        .archive;
+
+    info!(
+        "Deploying version {} of '{}' as '{}'",
+        version, function.name, name
+    );
+    let instance = DeployableFunction {
+        name: name.clone(),
+        content: archive,
+        user_uuid: function.uuid,
+        history: Vec::new(),
+        preview: Some(DeployPreview {
+            of: function.name.clone(),
+            ttl,
+        }),
+    };
+    let (_, settings) = deploy_function(conn, instance, builder).await?;
+    Ok((name, Some(settings)))
+}
+
+/// Removes the instance a version of a function was deployed as, if there is one.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function cache.
+/// * `autoscaler` - The running autoscaler.
+/// * `function_name` - The function the version belongs to.
+/// * `version` - The version whose instance to remove.
+/// * `user_uuid` - The namespace the function belongs to.
+pub async fn remove_version_instance(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    function_name: &str,
+    version: i32,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<()> {
+    let name = version_instance_name(function_name, version);
+    match FunctionDBRepo::find_function_including_trashed(conn, &name, user_uuid)
+        .await
+        .filter(|instance| instance.preview_of.as_deref() == Some(function_name))
+    {
+        Some(instance) => remove_preview(conn, cache_conn, autoscaler, instance).await,
+        None => Ok(()),
+    }
+}
+
+/// Lists the preview instances of a namespace, soonest to expire first.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user_uuid` - The namespace to list.
+pub async fn list_previews(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<Vec<PreviewInstance>> {
+    let previews = FunctionDBRepo::find_previews(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to list previews", e))?;
+    Ok(previews
+        .into_iter()
+        .map(PreviewInstance::from_model)
+        .collect())
+}
+
+/// Removes a function's preview instance for a branch right away.
+///
+/// Unlike deleting a function, nothing is kept: the containers, the image, the record and
+/// its versions are all removed.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function cache.
+/// * `autoscaler` - The running autoscaler.
+/// * `function_name` - The function the preview belongs to.
+/// * `branch` - The branch the preview was deployed from.
+/// * `user_uuid` - The namespace the function belongs to.
+pub async fn delete_preview(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    function_name: &str,
+    branch: &str,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<PreviewInstance> {
+    let name = preview_name(function_name, branch).map_err(ServelessCoreError::BadFunction)?;
+    let preview = FunctionDBRepo::find_function_including_trashed(conn, &name, user_uuid)
+        .await
+        .filter(|function| function.preview_of.as_deref() == Some(function_name))
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "No preview of '{}' for branch '{}' in namespace '{}'",
+                function_name, branch, user_uuid
+            ))
+        })?;
+
+    remove_preview(conn, cache_conn, autoscaler, preview.clone()).await?;
+    Ok(PreviewInstance::from_model(preview))
+}
+
+/// Removes every preview instance whose expiry has passed.
+///
+/// # Returns
+///
+/// The name and namespace of each preview removed.
+pub async fn remove_expired_previews(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+) -> ServelessCoreResult<Vec<(String, Uuid)>> {
+    let now: DateTimeWithTimeZone = ChronoDateTimeUtc::from(SystemTime::now()).into();
+    let expired = FunctionDBRepo::find_expired_previews(conn, now)
+        .await
+        .map_err(|e| database_error("Failed to find expired previews", e))?;
+
+    let mut removed = Vec::new();
+    for preview in expired {
+        let (name, namespace) = (preview.name.clone(), preview.uuid);
+        match remove_preview(conn, cache_conn, autoscaler, preview).await {
+            Ok(()) => removed.push((name, namespace)),
+            Err(e) => warn!("Failed to remove expired preview '{}': {}", name, e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Removes expired previews every hour, for as long as the server runs, and drops
+/// their cached settings on every controller.
+pub async fn run_expiry_loop(state: AppState) {
+    let mut cache_conn = state.cache_conn.clone();
+    let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        let removed =
+            match remove_expired_previews(&state.db_conn, &mut cache_conn, &state.autoscaler).await
+            {
+                Ok(removed) => removed,
+                Err(e) => {
+                    error!("Failed to remove expired previews: {}", e);
+                    continue;
+                }
+            };
+        if removed.is_empty() {
+            continue;
+        }
+        info!("Removed {} expired previews", removed.len());
+        for (name, namespace) in removed {
+            invalidate(
+                &state,
+                &Invalidation::Function {
+                    namespace,
+                    name: name.clone(),
+                },
+            );
+            state
+                .cache_invalidator
+                .publish_function(&mut cache_conn, &name, namespace)
+                .await;
+        }
+    }
+}
+
+async fn remove_preview(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    preview: Model,
+) -> ServelessCoreResult<()> {
+    let function_key = format!("{}-{}", preview.name, generate_hash(preview.uuid));
+    let _ = FunctionCacheRepo::remove_function(cache_conn, preview.uuid, &preview.name).await;
+    autoscaler.remove_function(&function_key).await;
+    remove_image(&function_key)
+        .await
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    FunctionDBRepo::delete_function(conn, preview)
+        .await
+        .map_err(|e| database_error("Failed to delete preview", e))?;
+    info!("Preview '{}' removed", function_key);
+    Ok(())
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_slug() {
+        assert_eq!(
+            branch_slug("feature/Login_Form").unwrap(),
+            "feature-login-form"
+        );
+        assert_eq!(branch_slug("--main--").unwrap(), "main");
+        assert_eq!(
+            branch_slug("a-very-long-branch-name-indeed").unwrap(),
+            "a-very-long-branch-n"
+        );
+        assert_eq!(
+            branch_slug("abcdefghijklmnopqrs-tu").unwrap(),
+            "abcdefghijklmnopqrs"
+        );
+        assert!(branch_slug("/_/").is_err());
+    }
+
+    #[test]
+    fn test_preview_name() {
+        assert_eq!(preview_name("hello", "Fix/Bug").unwrap(), "hello--fix-bug");
+        assert_eq!(preview_name("hello", "v3-fix").unwrap(), "hello--v3-fix");
+        assert!(preview_name("hello", "v3").is_err());
+        assert!(preview_name("hello", "V12").is_err());
+    }
+
+    #[test]
+    fn test_version_instance_name() {
+        let name = version_instance_name("hello", 3);
+        assert_eq!(name, "hello--v3");
+        assert!(is_version_slug(
+            name.rsplit(PREVIEW_SEPARATOR).next().unwrap()
+        ));
+        assert!(!is_version_slug("v"));
+        assert!(!is_version_slug("v1a"));
+    }
+}
@@ -0,0 +1,376 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::{DeployableFunctionConfig, FunctionSettings, NamespaceDefaults};
+use crate::lifecycle_manager::deploy::context_envs;
+use crate::lifecycle_manager::docs::FunctionDocs;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::utils::bundled_data::prepare_bundled_data;
+use crate::utils::image::IMAGE_RUNTIME;
+use crate::utils::utils::{envs_to_string, generate_hash};
+use db_entities::function::Model as FunctionModel;
+use runtime::core::provisioning::{build_from_context, create_build_context, remove_image};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use shared_utils::{
+    compress_dir_with_excludes, extract_zip_from_cursor, find_file_in_path, BUNDLED_DATA_DIR,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use templates::{go_template, image_template, nodejs_template, rust_template};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// What to promote, as `invok promote` sends it
+#[derive(Debug, Default, Deserialize)]
+pub struct PromoteRequest {
+    /// Version of the source function to promote; its deployed version when unset
+    #[serde(default)]
+    pub version: Option<i32>,
+    /// Environment variables of the source function to carry over
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Promote even when the target function's deploys are frozen by its SLO
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// A promoted function, as shown to the user
+#[derive(Debug, Serialize)]
+pub struct Promotion {
+    pub name: String,
+    /// Version the promoted function was recorded as in the target namespace
+    pub version: i32,
+    /// Namespace and version it was promoted from, with the variables carried over
+    pub promoted_from: Value,
+    /// Settings the function was deployed with
+    #[serde(skip)]
+    pub settings: FunctionSettings,
+}
+
+/// Copies the deployed version of a function from one namespace into another, without
+/// building it from source again.
+///
+/// The target's image is derived from the source's: the built application is copied
+/// out of it onto a fresh runtime stage, so none of the source's environment comes
+/// along. The target keeps the environment of its own config, with the variables named
+/// in `request.env` taken from the source's config. The settings of the config are
+/// filled in from the target namespace's defaults.
+///
+/// The promoted archive, with its config rewritten to the target's environment, is
+/// recorded as the target's next version along with where it came from. As with
+/// deploys, the registration runs in one transaction, and the image built for a new
+/// function is removed again if it fails.
+///
+/// Only the deployed version can be promoted: earlier versions have no image left to
+/// copy.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `name` - The function to promote.
+/// * `source_uuid` - The namespace it's promoted from.
+/// * `target_uuid` - The namespace it's promoted to.
+/// * `request` - The version and the variables to promote.
+///
+/// # Returns
+///
+/// The promoted function.
+pub async fn promote_function(
+    conn: &DatabaseConnection,
+    name: &str,
+    source_uuid: Uuid,
+    target_uuid: Uuid,
+    request: &PromoteRequest,
+) -> ServelessCoreResult<Promotion> {
+    if source_uuid == target_uuid {
+        return Err(ServelessCoreError::BadFunction(
+            "A function can't be promoted into its own namespace".to_string(),
+        ));
+    }
+
+    let source = FunctionDBRepo::find_function_by_name(conn, name, source_uuid)
+        .await
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "{} in namespace {}",
+                name, source_uuid
+            ))
+        })?;
+    if source.preview_of.is_some() {
+        return Err(ServelessCoreError::BadFunction(
+            "Preview instances can't be promoted".to_string(),
+        ));
+    }
+
+    let deployed = FunctionVersionDBRepo::latest_version(conn, source.id)
+        .await
+        .map_err(|e| database_error("Failed to look up function versions", e))?
+        .ok_or_else(|| {
+            ServelessCoreError::BadFunction(format!(
+                "'{}' has no stored source; redeploy it before promoting it",
+                name
+            ))
+        })?;
+    if request.version.is_some_and(|version| version != deployed) {
+        return Err(ServelessCoreError::BadFunction(format!(
+            "Only the deployed version of '{}' (v{}) can be promoted",
+            name, deployed
+        )));
+    }
+    let source_version = FunctionVersionDBRepo::find_version(conn, source.id, deployed)
+        .await
+        .map_err(|e| database_error("Failed to load function version", e))?
+        .ok_or_else(|| {
+            ServelessCoreError::SystemError(format!("Version {} of '{}' is gone", deployed, name))
+        })?;
+
+    let existing = FunctionDBRepo::find_function_including_trashed(conn, name, target_uuid).await;
+    if let Some(existing) = &existing {
+        if existing.runtime != source.runtime {
+            return Err(ServelessCoreError::BadFunction(format!(
+                "'{}' runs on {} in the target namespace, but on {} in the source",
+                name, existing.runtime, source.runtime
+            )));
+        }
+    }
+
+    // The target keeps its own environment, apart from the promoted variables
+    let (target_env, latest_version) = match &existing {
+        Some(existing) => {
+            let latest = FunctionVersionDBRepo::latest_version(conn, existing.id)
+                .await
+                .map_err(|e| database_error("Failed to look up function versions", e))?;
+            let env = match latest {
+                Some(version) => FunctionVersionDBRepo::find_version(conn, existing.id, version)
+                    .await
+                    .map_err(|e| database_error("Failed to load function version", e))?
+                    .map(|version| archived_env(version.archive))
+                    .transpose()?
+                    .unwrap_or_default(),
+                None => Map::new(),
+            };
+            (env, latest)
+        }
+        None => (Map::new(), None),
+    };
+    let version = latest_version.unwrap_or(0) + 1;
+
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| ServelessCoreError::SystemError(format!("Failed to create temp dir: {e}")))?;
+    let path = temp_dir.path().join(name);
+    let (archive, config, docs) =
+        rewrite_archive(&path, source_version.archive, target_env, &request.env)?;
+    // Images deployed as is are promoted from the image they were deployed from, which
+    // doesn't carry the source's environment
+    let deployed_image = config.image.as_ref().map(|image| image.reference.clone());
+
+    let user = AuthDBRepo::find_by_uuid(conn, target_uuid)
+        .await
+        .map_err(|e| database_error("Failed to load namespace", e))?;
+    let defaults = user
+        .as_ref()
+        .map(NamespaceDefaults::from_model)
+        .unwrap_or_default();
+    let mut settings = config.settings;
+    settings.inherit(&defaults);
+    settings
+        .validate()
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid settings: {}", e)))?;
+    let mut envs = defaults.merge_env(config.env).unwrap_or_default();
+    envs.extend(context_envs(target_uuid, name, version));
+
+    // Derive the target's image from the one the source runs
+    let source_image = format!("{name}-{}", generate_hash(source_uuid));
+    let function_image_name = format!("{name}-{}", generate_hash(target_uuid));
+    let dockerfile_content = match source.runtime.as_str() {
+        "go" => go_template::PROMOTE_DOCKERFILE_TEMPLATE,
+        "nodejs" => nodejs_template::PROMOTE_DOCKERFILE_TEMPLATE,
+        "rust" => rust_template::PROMOTE_DOCKERFILE_TEMPLATE,
+        IMAGE_RUNTIME => image_template::DOCKERFILE_TEMPLATE,
+        _ => "",
+    }
+    .replace("{{SOURCE_IMAGE}}", &source_image)
+    .replace(
+        "{{IMAGE}}",
+        deployed_image.as_deref().unwrap_or(&source_image),
+    )
+    .replace("{{ENV}}", &envs_to_string(envs));
+    let build_dir = temp_dir.path().join("promote");
+    fs::create_dir_all(&build_dir).map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    // Data files are copied from the archive, like a deploy does; the rest of the image
+    // comes from the source's
+    prepare_bundled_data(&path)
+        .and_then(|_| {
+            fs::rename(
+                path.join(BUNDLED_DATA_DIR),
+                build_dir.join(BUNDLED_DATA_DIR),
+            )
+        })
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    let build_context = create_build_context(&build_dir, &dockerfile_content)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    // The source image only exists for the controller's platform, so only that is built
+    build_from_context(build_context, &function_image_name, &[], &HashMap::new())
+        .await
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    info!("Function docker image promoted from '{}'", source_image);
+
+    let promoted_from = json!({
+        "namespace": source_uuid,
+        "version": deployed,
+        "env": request.env,
+    });
+    let settings_json = serde_json::to_value(&settings).ok();
+
+    let is_new = existing.is_none();
+    let registration = async {
+        let txn = conn.begin().await.map_err(|e| {
+            error!("Failed to start promotion transaction: {}", e);
+            ServelessCoreError::SystemError("Failed to register function".to_string())
+        })?;
+
+        let registered = match existing {
+            None => {
+                let model = FunctionModel {
+                    name: name.to_string(),
+                    runtime: source.runtime.clone(),
+                    settings: settings_json.clone(),
+                    ..Default::default()
+                };
+                FunctionDBRepo::create_function_for_user(&txn, model, target_uuid)
+                    .await
+                    .map_err(|e| database_error("Failed to register function in database", e))?
+            }
+            Some(existing) => {
+                FunctionDBRepo::update_function_settings(&txn, existing, settings_json.clone())
+                    .await
+                    .map_err(|e| database_error("Failed to update function settings", e))?
+            }
+        };
+        let registered =
+            FunctionDBRepo::update_function_docs(&txn, registered, docs.readme, docs.openapi)
+                .await
+                .map_err(|e| database_error("Failed to store function docs", e))?;
+        FunctionVersionDBRepo::record_promoted(
+            &txn,
+            registered.id,
+            archive,
+            settings_json,
+            promoted_from.clone(),
+        )
+        .await
+        .map_err(|e| database_error("Failed to record function version", e))?;
+
+        txn.commit()
+            .await
+            .map_err(|e| database_error("Failed to register function", e))
+    };
+
+    if let Err(e) = registration.await {
+        if is_new {
+            if let Err(e) = remove_image(&function_image_name).await {
+                error!(
+                    "Failed to remove image of unregistered function '{}': {}",
+                    name, e
+                );
+            }
+        } else {
+            error!(
+                "Function '{}' was promoted but keeps its previous registration",
+                name
+            );
+        }
+        return Err(e);
+    }
+
+    info!(
+        "Function '{}' v{} promoted from namespace {} to {} as v{}",
+        name, deployed, source_uuid, target_uuid, version
+    );
+    Ok(Promotion {
+        name: name.to_string(),
+        version,
+        promoted_from,
+        settings,
+    })
+}
+
+/// Extracts the promoted archive at `path` and gives its config the target's
+/// environment, with the `promoted` variables of the source's environment over it.
+///
+/// Returns the rewritten archive, its config and the docs it ships.
+fn rewrite_archive(
+    path: &Path,
+    archive: Vec<u8>,
+    mut env: Map<String, Value>,
+    promoted: &[String],
+) -> ServelessCoreResult<(Vec<u8>, DeployableFunctionConfig, FunctionDocs)> {
+    let path = path.to_path_buf();
+    extract_zip_from_cursor(Cursor::new(archive), &path)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    let config_file = find_file_in_path("config.json", &path).ok_or_else(|| {
+        ServelessCoreError::BadFunction("Function does not include config file".to_string())
+    })?;
+    let mut config: Value = fs::read_to_string(&config_file)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))
+        .and_then(|content| {
+            serde_json::from_str(&content)
+                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))
+        })?;
+    let Some(fields) = config.as_object_mut() else {
+        return Err(ServelessCoreError::BadFunction(
+            "Invalid config.json: not an object".to_string(),
+        ));
+    };
+
+    let source_env = fields.get("env").and_then(Value::as_object);
+    for key in promoted {
+        let value = source_env.and_then(|env| env.get(key)).ok_or_else(|| {
+            ServelessCoreError::BadFunction(format!(
+                "'{}' isn't set in the source function's config",
+                key
+            ))
+        })?;
+        env.insert(key.clone(), value.clone());
+    }
+    fields.insert("env".to_string(), Value::Object(env));
+
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    fs::write(&config_file, &content)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    let config: DeployableFunctionConfig = serde_json::from_str(&content)
+        .map_err(|e| ServelessCoreError::BadFunction(format!("Invalid config.json: {}", e)))?;
+    let docs = FunctionDocs::read(&path)?;
+
+    let mut cursor = Cursor::new(Vec::new());
+    compress_dir_with_excludes(&path, &mut cursor, &[])
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    Ok((cursor.into_inner(), config, docs))
+}
+
+/// The environment of the config in a stored archive
+fn archived_env(archive: Vec<u8>) -> ServelessCoreResult<Map<String, Value>> {
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| ServelessCoreError::SystemError(format!("Failed to create temp dir: {e}")))?;
+    extract_zip_from_cursor(Cursor::new(archive), temp_dir.path())
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    let env = find_file_in_path("config.json", &temp_dir.path().to_path_buf())
+        .and_then(|config_file| fs::read_to_string(config_file).ok())
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .and_then(|mut config| match config.get_mut("env").map(Value::take) {
+            Some(Value::Object(env)) => Some(env),
+            _ => None,
+        })
+        .unwrap_or_default();
+    Ok(env)
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
@@ -0,0 +1,333 @@
+use crate::lifecycle_manager::base_images::BaseImageStatus;
+use crate::lifecycle_manager::build_args::BuildArgCipher;
+use crate::lifecycle_manager::build_queue::{BuildJob, BuildStatus, BuildStep, BUILD_ARGS_HEADER};
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::test_gate::TestResults;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use runtime::core::environment::connect_docker;
+use runtime::core::platform::{build_targets, daemon_platform, Platform};
+use runtime::core::provisioning::{
+    build_from_context_with_log, create_build_context, pull_image, RegistryAuth,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// How often the builder service is asked whether a build finished
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Timeout of a single request to the builder service
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Builder service the controller submits builds to, see `serverless-core builder`
+pub struct RemoteBuilder {
+    url: String,
+    token: String,
+    registry: String,
+    registry_auth: RegistryAuth,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl RemoteBuilder {
+    pub fn new(
+        url: &str,
+        token: &str,
+        registry: &str,
+        registry_auth: RegistryAuth,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            registry: registry.to_string(),
+            registry_auth,
+            timeout,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Submits a build, waits for the builder service to push the image, and pulls the
+    /// variant for the local daemon's platform as `image`.
+    ///
+    /// Returns the results of the tests when the Dockerfile runs `tests`.
+    async fn build(
+        &self,
+        build_context: Vec<u8>,
+        image: &str,
+        namespace: &str,
+        platforms: &[Platform],
+        build_args: &HashMap<String, String>,
+        tests: Option<&str>,
+    ) -> ServelessCoreResult<Option<TestResults>> {
+        let platforms = platforms
+            .iter()
+            .map(Platform::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut request = self
+            .client
+            .post(format!("{}/builds", self.url))
+            .bearer_auth(&self.token)
+            .query(&[
+                ("image", image),
+                ("namespace", namespace),
+                ("platforms", platforms.as_str()),
+            ])
+            .header(reqwest::header::CONTENT_TYPE, "application/x-tar");
+        if !build_args.is_empty() {
+            let build_args = serde_json::to_vec(build_args)
+                .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+            request = request.header(BUILD_ARGS_HEADER, BASE64.encode(build_args));
+        }
+        let response = request
+            .body(build_context)
+            .send()
+            .await
+            .map_err(|e| builder_error("Failed to submit build", e))?;
+        let mut job = job_from(response).await?;
+        info!("Build {} of '{}' submitted to {}", job.id, image, self.url);
+
+        let started = Instant::now();
+        while !job.status.is_finished() {
+            if started.elapsed() > self.timeout {
+                return Err(ServelessCoreError::SystemError(format!(
+                    "Build {} did not finish within {} s",
+                    job.id,
+                    self.timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let response = self
+                .client
+                .get(format!("{}/builds/{}", self.url, job.id))
+                .bearer_auth(&self.token)
+                .send()
+                .await
+                .map_err(|e| builder_error("Failed to look up build", e))?;
+            job = job_from(response).await?;
+        }
+
+        let built = job.status == BuildStatus::Succeeded;
+        let results =
+            tests.and_then(|command| TestResults::of_build(command, job.tests.take(), built));
+        if !built {
+            if let Some(results) = results.filter(|results| !results.passed) {
+                return Err(tests_failed(results));
+            }
+            return Err(build_failed(job));
+        }
+        pull_image(image, &self.registry, &self.registry_auth)
+            .await
+            .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+        info!(
+            "Image '{}' built remotely in {:.1} s",
+            image,
+            started.elapsed().as_secs_f64()
+        );
+        Ok(results)
+    }
+
+    /// Asks the builder service to pull its base images again, e.g. after a runtime
+    /// version was enabled
+    pub async fn pull_base_images(&self) -> ServelessCoreResult<()> {
+        let response = self
+            .client
+            .post(format!("{}/base-images/pull", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| builder_error("Failed to trigger base image pull", e))?;
+        if !response.status().is_success() {
+            return Err(ServelessCoreError::SystemError(format!(
+                "Builder service answered {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The last pull of each of the builder service's base images
+    pub async fn base_images(&self) -> ServelessCoreResult<Vec<BaseImageStatus>> {
+        let response = self
+            .client
+            .get(format!("{}/base-images", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| builder_error("Failed to look up base images", e))?;
+        response
+            .json()
+            .await
+            .map_err(|e| builder_error("Invalid answer from the builder service", e))
+    }
+}
+
+/// Why a build the builder service ran failed
+fn build_failed(job: BuildJob) -> ServelessCoreError {
+    let error = job.error.unwrap_or_default();
+    match job.failed_step {
+        // The image was built; the registry or the builder's access to it is at fault
+        Some(BuildStep::Push) => {
+            ServelessCoreError::SystemError(format!("Pushing the image failed: {}", error))
+        }
+        // Build errors come from the function's own Dockerfile steps
+        _ => ServelessCoreError::BadFunction(format!("Build failed: {}", error)),
+    }
+}
+
+/// Builds function images, on the controller's own Docker daemon or on the builder service
+pub struct ImageBuilder {
+    /// Platforms images are built for besides the Docker daemon's own
+    pub platforms: Vec<Platform>,
+    /// Builder service builds are submitted to; images are built locally when unset
+    pub remote: Option<RemoteBuilder>,
+    /// Decrypts the namespaces' build args; they can't be set when unset
+    pub build_arg_cipher: Option<BuildArgCipher>,
+}
+
+impl ImageBuilder {
+    /// Builds `image` from the function files at `path` and its Dockerfile.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The function files, the build context.
+    /// * `image` - The image to build, the function key.
+    /// * `namespace` - The namespace the function belongs to.
+    /// * `dockerfile_content` - The Dockerfile.
+    /// * `build_args` - Build args of the namespace, passed to the build only.
+    /// * `tests` - The test command the Dockerfile runs, if any.
+    ///
+    /// # Returns
+    ///
+    /// The results of the tests when the Dockerfile runs them. A failure of the tests
+    /// fails the build with their output.
+    pub async fn build(
+        &self,
+        path: &Path,
+        image: &str,
+        namespace: &str,
+        dockerfile_content: &str,
+        build_args: &HashMap<String, String>,
+        tests: Option<&str>,
+    ) -> ServelessCoreResult<Option<TestResults>> {
+        let build_context = create_build_context(path, dockerfile_content)
+            .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+        match &self.remote {
+            Some(remote) => {
+                // The local daemon's platform is always built, so the image can run here
+                let docker = connect_docker().map_err(|e| {
+                    ServelessCoreError::SystemError(format!("Unable to connect to Docker: {e}"))
+                })?;
+                let native = daemon_platform(&docker)
+                    .await
+                    .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+                let platforms = build_targets(&native, &self.platforms);
+                remote
+                    .build(
+                        build_context,
+                        image,
+                        namespace,
+                        &platforms,
+                        build_args,
+                        tests,
+                    )
+                    .await
+            }
+            None => {
+                let mut log = Vec::new();
+                let built = build_from_context_with_log(
+                    build_context,
+                    image,
+                    &self.platforms,
+                    build_args,
+                    &mut log,
+                )
+                .await;
+                let results = tests.and_then(|command| {
+                    TestResults::of_build(command, TestResults::from_build_log(&log), built.is_ok())
+                });
+                match (built, results) {
+                    (Ok(_), results) => Ok(results),
+                    (Err(_), Some(results)) if !results.passed => Err(tests_failed(results)),
+                    (Err(e), _) => Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+/// The error failing a deploy whose tests failed, with their output
+fn tests_failed(results: TestResults) -> ServelessCoreError {
+    ServelessCoreError::BadFunction(format!("{}\n{}", results.summary(), results.output))
+}
+
+/// Reads the build the builder service answered with
+async fn job_from(response: reqwest::Response) -> ServelessCoreResult<BuildJob> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        error!("Builder service answered {}: {}", status, body);
+        return Err(match status {
+            reqwest::StatusCode::TOO_MANY_REQUESTS => ServelessCoreError::BadFunction(format!(
+                "Too many builds in progress, try again later: {}",
+                body
+            )),
+            _ => ServelessCoreError::SystemError(format!("Builder service answered {}", status)),
+        });
+    }
+    response
+        .json::<BuildJob>()
+        .await
+        .map_err(|e| builder_error("Invalid answer from the builder service", e))
+}
+
+fn builder_error(context: &str, e: reqwest::Error) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn failed_job(failed_step: Option<BuildStep>) -> BuildJob {
+        BuildJob {
+            id: Uuid::new_v4(),
+            image: "app".to_string(),
+            namespace: "ns".to_string(),
+            platforms: Vec::new(),
+            status: BuildStatus::Failed,
+            error: Some("boom".to_string()),
+            failed_step,
+            references: Vec::new(),
+            tests: None,
+            submitted_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_build_failed() {
+        match build_failed(failed_job(Some(BuildStep::Push))) {
+            ServelessCoreError::SystemError(message) => {
+                assert_eq!(message, "Pushing the image failed: boom")
+            }
+            e => panic!("unexpected error: {e:?}"),
+        }
+        for step in [Some(BuildStep::Build), None] {
+            match build_failed(failed_job(step)) {
+                ServelessCoreError::BadFunction(message) => {
+                    assert_eq!(message, "Build failed: boom")
+                }
+                e => panic!("unexpected error: {e:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::invocation::RecordedInvocationRepo;
+use crate::db::models::FunctionSettings;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::invoke::{InvocationContext, REQUEST_ID_HEADER};
+use crate::lifecycle_manager::preview::deploy_version_instance;
+use crate::lifecycle_manager::remote_build::ImageBuilder;
+use crate::utils::utils::{generate_hash, strip_hop_by_hop};
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, Request};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hyper::body::Bytes;
+use redis::aio::MultiplexedConnection;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Header marking a request as the replay of a recorded invocation, carrying its id.
+///
+/// Replays are sent to the function with it. Callers can't set it: requests are only
+/// treated as replays when they carry [`ReplayOf`].
+pub const REPLAY_HEADER: &str = "x-invok-replay-of";
+
+/// Largest request body recorded (1MB); larger requests are forwarded without recording
+pub const MAX_RECORDED_BODY_SIZE: usize = 1024 * 1024;
+
+/// Invocations kept per function; older ones are dropped as new ones are recorded
+const MAX_RECORDED_INVOCATIONS: usize = 50;
+
+/// How long a recorded invocation can be replayed
+const RECORDING_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Credentials are never recorded, so replays carry none
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// Request extension of the replays the platform sends, with the id of the invocation
+/// replayed. Replays are not recorded again, nor served from the response cache.
+#[derive(Debug, Clone)]
+pub struct ReplayOf(pub String);
+
+/// A request a function was sent, as recorded for functions with `record_invocations`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedInvocation {
+    /// Generated when the invocation is recorded, so callers can't pick one to overwrite
+    pub id: String,
+    /// The invocation's request id, as the function saw it
+    #[serde(default)]
+    pub request_id: String,
+    pub function: String,
+    /// Version of the function that handled the request
+    pub version: Option<i32>,
+    pub method: String,
+    pub query: HashMap<String, String>,
+    /// Headers as the caller sent them, without credentials and connection details
+    pub headers: Vec<(String, String)>,
+    /// Request body, base64-encoded
+    pub body: String,
+    /// Status the function responded with
+    pub status: u16,
+    /// RFC 3339 time the request was received
+    pub recorded_at: String,
+}
+
+/// A recorded invocation as listed, without its headers and body
+#[derive(Debug, Serialize)]
+pub struct InvocationSummary {
+    pub id: String,
+    pub request_id: String,
+    pub version: Option<i32>,
+    pub method: String,
+    pub status: u16,
+    pub body_bytes: usize,
+    pub recorded_at: String,
+}
+
+impl RecordedInvocation {
+    /// Records a request before it is forwarded; the response status and the version are
+    /// filled in once the function answered
+    pub fn new(
+        context: &InvocationContext,
+        method: &Method,
+        query: &HashMap<String, String>,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) -> Self {
+        let mut headers = headers.clone();
+        strip_hop_by_hop(&mut headers);
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| {
+                !REDACTED_HEADERS.contains(&name.as_str())
+                    && name.as_str() != REQUEST_ID_HEADER
+                    && !name.as_str().starts_with("x-invok-")
+            })
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            request_id: context.request_id.clone(),
+            function: context.function.clone(),
+            version: None,
+            method: method.to_string(),
+            query: query.clone(),
+            headers,
+            body: BASE64.encode(body),
+            status: 0,
+            recorded_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+        }
+    }
+
+    fn summary(self) -> InvocationSummary {
+        let body_bytes = BASE64.decode(&self.body).map_or(0, |body| body.len());
+        InvocationSummary {
+            id: self.id,
+            request_id: self.request_id,
+            version: self.version,
+            method: self.method,
+            status: self.status,
+            body_bytes,
+            recorded_at: self.recorded_at,
+        }
+    }
+
+    /// Rebuilds the recorded request, marked as a replay with [`ReplayOf`] and
+    /// [`REPLAY_HEADER`], with the headers it is invoked with
+    pub fn request(&self) -> ServelessCoreResult<(HeaderMap, Request<Body>)> {
+        let method: Method = self
+            .method
+            .parse()
+            .map_err(|_| ServelessCoreError::BadFunction("Invalid recorded method".to_string()))?;
+        let body = BASE64
+            .decode(&self.body)
+            .map_err(|_| ServelessCoreError::BadFunction("Invalid recorded body".to_string()))?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        if let Ok(id) = HeaderValue::from_str(&self.id) {
+            headers.insert(REPLAY_HEADER, id);
+        }
+
+        let mut request = Request::new(Body::from(body));
+        *request.method_mut() = method;
+        *request.headers_mut() = headers.clone();
+        request.extensions_mut().insert(ReplayOf(self.id.clone()));
+        Ok((headers, request))
+    }
+}
+
+/// Stores a recorded invocation of one of a namespace's functions. Failures are logged:
+/// recording never fails the invocation.
+pub async fn record_invocation(
+    cache_conn: &mut MultiplexedConnection,
+    user_uuid: Uuid,
+    invocation: &RecordedInvocation,
+) {
+    let namespace = generate_hash(user_uuid);
+    let function_key = format!("{}-{}", invocation.function, namespace);
+    let stored = match serde_json::to_string(invocation) {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!("Failed to serialize invocation '{}': {}", invocation.id, e);
+            return;
+        }
+    };
+    if let Err(e) = RecordedInvocationRepo::store(
+        cache_conn,
+        &namespace,
+        &function_key,
+        &invocation.id,
+        &stored,
+        MAX_RECORDED_INVOCATIONS,
+        RECORDING_TTL.as_secs(),
+    )
+    .await
+    {
+        warn!(
+            "Failed to record invocation '{}' of '{}': {}",
+            invocation.id, function_key, e
+        );
+    }
+}
+
+/// Lists the invocations recorded for a function, newest first.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the recordings.
+/// * `function_name` - The function whose invocations to list.
+/// * `user_uuid` - The namespace the function belongs to.
+pub async fn list_invocations(
+    cache_conn: &mut MultiplexedConnection,
+    function_name: &str,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<Vec<InvocationSummary>> {
+    let namespace = generate_hash(user_uuid);
+    let function_key = format!("{function_name}-{namespace}");
+    let invocations = RecordedInvocationRepo::list(cache_conn, &namespace, &function_key)
+        .await
+        .map_err(cache_error)?;
+    Ok(invocations
+        .iter()
+        .filter_map(|invocation| serde_json::from_str::<RecordedInvocation>(invocation).ok())
+        .map(RecordedInvocation::summary)
+        .collect())
+}
+
+/// Finds an invocation recorded in a namespace.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the recordings.
+/// * `id` - The invocation's request id.
+/// * `user_uuid` - The namespace the invocation was recorded in.
+pub async fn find_invocation(
+    cache_conn: &mut MultiplexedConnection,
+    id: &str,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<RecordedInvocation> {
+    RecordedInvocationRepo::get(cache_conn, &generate_hash(user_uuid), id)
+        .await
+        .map_err(cache_error)?
+        .and_then(|invocation| serde_json::from_str(&invocation).ok())
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "No recorded invocation '{}' in namespace '{}'",
+                id, user_uuid
+            ))
+        })
+}
+
+/// Finds the function a recorded invocation is replayed on.
+///
+/// Without a `version`, or for the function's latest one, that is the function itself.
+/// Other versions are served by their version instance, `<name>--v<version>`, deployed
+/// from the recorded archive when missing and then expiring like any preview.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `builder` - Builds the instance's image when it has to be deployed.
+/// * `invocation` - The invocation being replayed.
+/// * `version` - The version to replay it on.
+/// * `user_uuid` - The namespace the invocation was recorded in.
+/// * `preview_ttl` - How long a deployed instance lives.
+///
+/// # Returns
+///
+/// The name of the function to invoke, and the settings of the instance if one was
+/// deployed.
+pub async fn replay_target(
+    conn: &DatabaseConnection,
+    builder: &ImageBuilder,
+    invocation: &RecordedInvocation,
+    version: Option<i32>,
+    user_uuid: Uuid,
+    preview_ttl: Duration,
+) -> ServelessCoreResult<(String, Option<FunctionSettings>)> {
+    let function = FunctionDBRepo::find_function_by_name(conn, &invocation.function, user_uuid)
+        .await
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "{} in namespace {}",
+                invocation.function, user_uuid
+            ))
+        })?;
+    let Some(version) = version else {
+        return Ok((function.name, None));
+    };
+
+    let latest = FunctionVersionDBRepo::latest_version(conn, function.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to load versions of '{}': {}", function.name, e);
+            ServelessCoreError::SystemError("Failed to load function versions".to_string())
+        })?;
+    if latest == Some(version) {
+        return Ok((function.name, None));
+    }
+    if function.preview_of.is_some() {
+        return Err(ServelessCoreError::BadFunction(
+            "Invocations of preview instances can only be replayed on their current version"
+                .to_string(),
+        ));
+    }
+
+    deploy_version_instance(conn, builder, &function, version, Some(preview_ttl)).await
+}
+
+fn cache_error(e: redis::RedisError) -> ServelessCoreError {
+    error!("Failed to access recorded invocations: {}", e);
+    ServelessCoreError::SystemError("Failed to access recorded invocations".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(headers: &[(&str, &str)], body: &'static [u8]) -> RecordedInvocation {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        let context =
+            InvocationContext::new(&header_map, Uuid::nil(), "api", Duration::from_secs(30));
+        RecordedInvocation::new(
+            &context,
+            &Method::POST,
+            &HashMap::from([("page".to_string(), "2".to_string())]),
+            &header_map,
+            &Bytes::from_static(body),
+        )
+    }
+
+    #[test]
+    fn test_ids_are_generated() {
+        let first = recorded(&[(REQUEST_ID_HEADER, "chosen-by-caller")], b"");
+        let second = recorded(&[(REQUEST_ID_HEADER, "chosen-by-caller")], b"");
+
+        assert_eq!(first.request_id, "chosen-by-caller");
+        assert_ne!(first.id, "chosen-by-caller");
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_credentials_are_not_recorded() {
+        let invocation = recorded(
+            &[
+                ("authorization", "Bearer secret"),
+                ("cookie", "session=1"),
+                (REPLAY_HEADER, "other"),
+                ("x-invok-debug", "1"),
+                ("content-type", "application/json"),
+            ],
+            b"{}",
+        );
+
+        assert_eq!(
+            invocation.headers,
+            vec![("content-type".to_string(), "application/json".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_request_is_marked_as_replay() {
+        let invocation = recorded(&[("content-type", "text/plain")], b"hello");
+        let (headers, request) = invocation.request().unwrap();
+
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(headers[REPLAY_HEADER], invocation.id.as_str());
+        assert_eq!(headers["content-type"], "text/plain");
+        assert_eq!(
+            request
+                .extensions()
+                .get::<ReplayOf>()
+                .map(|replay| replay.0.as_str()),
+            Some(invocation.id.as_str())
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let invocation = recorded(&[], b"hello");
+        let id = invocation.id.clone();
+        let summary = invocation.summary();
+
+        assert_eq!(summary.id, id);
+        assert_eq!(summary.body_bytes, 5);
+        assert_eq!(summary.method, "POST");
+    }
+}
@@ -0,0 +1,294 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::response_cache::ResponseCacheRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::notify::{sign_payload, SIGNATURE_HEADER, WEBHOOK_TIMEOUT};
+use crate::utils::http_cache::{is_cacheable_request, shared_lifetime};
+use crate::utils::utils::generate_hash;
+use axum::http::header::{ACCEPT_ENCODING, AGE};
+use axum::http::response::Parts;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, Response as AxumResponse};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hyper::body::Bytes;
+use redis::aio::MultiplexedConnection;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use shared_utils::unix_now;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Response header telling whether a cacheable invocation was answered from the response
+/// cache (`hit`) or by the function (`miss`)
+pub const CACHE_STATUS_HEADER: &str = "x-invok-cache";
+
+/// A function response as kept in the response cache
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Response body, base64-encoded
+    body: String,
+    /// Seconds since the Unix epoch the response was cached
+    stored_at: u64,
+}
+
+/// A purge of a function's cached responses, as reported to the caller and the webhooks
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeReport {
+    pub namespace: Uuid,
+    pub function: String,
+    /// Invocation paths whose responses were dropped: the function's and those of its
+    /// preview and version instances
+    pub paths: Vec<String>,
+    /// RFC 3339 time of the purge
+    pub purged_at: String,
+}
+
+/// Finds the key an invocation's response is cached under, or `None` when the request
+/// can't use the response cache.
+///
+/// Responses are cached per version of the function and per purge generation, so deploys
+/// and purges never serve stale responses; the query and `Accept-Encoding` tell requests
+/// apart.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the response cache.
+/// * `function_key` - The key of the invoked function.
+/// * `version` - The version of the function serving the request.
+/// * `method` - The method of the request.
+/// * `headers` - The headers of the request.
+/// * `query` - The query of the request.
+pub async fn response_cache_key(
+    cache_conn: &mut MultiplexedConnection,
+    function_key: &str,
+    version: Option<i32>,
+    method: &Method,
+    headers: &HeaderMap,
+    query: &HashMap<String, String>,
+) -> Option<String> {
+    if !is_cacheable_request(method, headers) {
+        return None;
+    }
+    let generation = match ResponseCacheRepo::generation(cache_conn, function_key).await {
+        Ok(generation) => generation,
+        Err(e) => {
+            warn!(
+                "Failed to read the cache generation of '{}': {}",
+                function_key, e
+            );
+            return None;
+        }
+    };
+
+    let query: BTreeMap<_, _> = query.iter().collect();
+    let accept_encoding = headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let request = format!("{} {:?} {}", method, query, accept_encoding);
+    Some(format!(
+        "{}:{}:{}:{:x}",
+        function_key,
+        generation,
+        version.unwrap_or_default(),
+        md5::compute(request)
+    ))
+}
+
+/// Looks a response up in the response cache.
+///
+/// # Returns
+///
+/// The cached response, with its `Age` and `x-invok-cache: hit`, if there is one.
+pub async fn cached_response(
+    cache_conn: &mut MultiplexedConnection,
+    key: &str,
+) -> Option<(Parts, Bytes)> {
+    let cached = match ResponseCacheRepo::get(cache_conn, key).await {
+        Ok(cached) => cached?,
+        Err(e) => {
+            warn!("Failed to read cached response '{}': {}", key, e);
+            return None;
+        }
+    };
+    let cached: CachedResponse = serde_json::from_str(&cached).ok()?;
+    let body = BASE64.decode(&cached.body).ok()?;
+
+    let mut response = AxumResponse::builder()
+        .status(cached.status)
+        .body(())
+        .ok()?;
+    let headers = response.headers_mut();
+    for (name, value) in &cached.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    let age = unix_now().saturating_sub(cached.stored_at);
+    headers.insert(AGE, HeaderValue::from(age));
+    headers.insert(CACHE_STATUS_HEADER, HeaderValue::from_static("hit"));
+
+    let (parts, ()) = response.into_parts();
+    Some((parts, Bytes::from(body)))
+}
+
+/// Caches a function's response in the background, if its `Cache-Control` lets shared
+/// caches keep it and it is at most `max_bytes` long. Failures are logged: caching never
+/// fails the invocation.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the response cache.
+/// * `key` - The key from [`response_cache_key`].
+/// * `authorized` - Whether the request carried credentials.
+/// * `parts` - The status and headers of the response.
+/// * `body` - The response's body.
+/// * `max_bytes` - The largest body cached.
+/// * `max_ttl` - The longest a response is cached.
+pub fn store_response(
+    mut cache_conn: MultiplexedConnection,
+    key: String,
+    authorized: bool,
+    parts: &Parts,
+    body: &Bytes,
+    max_bytes: usize,
+    max_ttl: Duration,
+) {
+    let Some(lifetime) = shared_lifetime(parts.status, authorized, &parts.headers) else {
+        return;
+    };
+    if body.len() > max_bytes {
+        return;
+    }
+    let cached = CachedResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect(),
+        body: BASE64.encode(body),
+        stored_at: unix_now(),
+    };
+    let Ok(cached) = serde_json::to_string(&cached) else {
+        return;
+    };
+    let ttl_secs = lifetime.min(max_ttl).as_secs().max(1);
+    tokio::spawn(async move {
+        if let Err(e) = ResponseCacheRepo::store(&mut cache_conn, &key, &cached, ttl_secs).await {
+            warn!("Failed to cache response '{}': {}", key, e);
+        }
+    });
+}
+
+/// Drops the cached responses of a function and of its preview and version instances.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the response cache.
+/// * `function_name` - The function to purge.
+/// * `user_uuid` - The namespace the function belongs to.
+pub async fn purge_function(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    function_name: &str,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<PurgeReport> {
+    let function = FunctionDBRepo::find_function_by_name(conn, function_name, user_uuid)
+        .await
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "{} in namespace {}",
+                function_name, user_uuid
+            ))
+        })?;
+    let previews = FunctionDBRepo::find_previews(conn, user_uuid)
+        .await
+        .map_err(|e| {
+            error!("Failed to list previews of '{}': {}", function_name, e);
+            ServelessCoreError::SystemError("Failed to list previews".to_string())
+        })?;
+    let names = std::iter::once(function.name.clone()).chain(
+        previews
+            .into_iter()
+            .filter(|preview| preview.preview_of.as_deref() == Some(function.name.as_str()))
+            .map(|preview| preview.name),
+    );
+
+    let namespace = generate_hash(user_uuid);
+    let mut paths = Vec::new();
+    for name in names {
+        let function_key = format!("{name}-{namespace}");
+        ResponseCacheRepo::purge(cache_conn, &function_key)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to purge cached responses of '{}': {}",
+                    function_key, e
+                );
+                ServelessCoreError::SystemError("Failed to purge cached responses".to_string())
+            })?;
+        paths.push(format!("/invok/{}/{}", user_uuid, name));
+    }
+    info!(
+        "Purged cached responses of '{}' in namespace {}",
+        function_name, user_uuid
+    );
+
+    Ok(PurgeReport {
+        namespace: user_uuid,
+        function: function.name,
+        paths,
+        purged_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+    })
+}
+
+/// Sends a purge to every configured webhook in the background, so CDNs in front of the
+/// controller can drop their copies too.
+///
+/// The report is posted as JSON, signed with `secret` in `x-invok-signature` when one is
+/// configured. Failed deliveries are logged and not retried.
+pub fn notify_purge(webhooks: &[String], secret: Option<&str>, report: &PurgeReport) {
+    if webhooks.is_empty() {
+        return;
+    }
+    let Ok(payload) = serde_json::to_vec(report) else {
+        return;
+    };
+    let signature = secret.map(|secret| sign_payload(secret, &payload));
+    let client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to build the purge webhook client: {}", e);
+            return;
+        }
+    };
+
+    for url in webhooks {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let url = url.clone();
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!("Purge webhook {} answered {}", url, response.status()),
+                Err(e) => warn!("Failed to notify purge webhook {}: {}", url, e),
+            }
+        });
+    }
+}
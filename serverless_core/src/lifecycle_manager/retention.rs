@@ -0,0 +1,451 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::VersionSize;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::preview::remove_version_instance;
+use crate::utils::routing::RoutingRules;
+use db_entities::function::Model;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How often every namespace is pruned, besides after each of its deploys
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How much of their version history namespaces keep
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    /// Recorded versions kept per function, newest first; unlimited when unset
+    pub keep_versions: Option<usize>,
+    /// Bytes of recorded archives kept per namespace; unlimited when unset
+    pub max_bytes_per_namespace: Option<u64>,
+}
+
+impl RetentionPolicy {
+    fn is_unlimited(&self) -> bool {
+        self.keep_versions.is_none() && self.max_bytes_per_namespace.is_none()
+    }
+}
+
+/// Storage taken by the recorded versions of one function
+#[derive(Debug, Serialize)]
+pub struct FunctionStorage {
+    pub name: String,
+    /// Recorded versions, archives included
+    pub versions: usize,
+    /// Bytes of the recorded archives
+    pub bytes: u64,
+    /// The version currently deployed, if any was recorded
+    pub latest_version: Option<i32>,
+}
+
+/// Storage taken by the recorded versions of a namespace, with the limits applied to it
+#[derive(Debug, Serialize)]
+pub struct StorageUsage {
+    /// Largest first
+    pub functions: Vec<FunctionStorage>,
+    pub total_versions: usize,
+    pub total_bytes: u64,
+    pub keep_versions: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+/// What a pruning removed
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub versions: usize,
+    pub bytes: u64,
+}
+
+/// Reports the storage a namespace's recorded versions take, per function.
+///
+/// Trashed functions are left out; their versions go when they are purged.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user_uuid` - The namespace to report on.
+/// * `policy` - The limits to report along.
+pub async fn storage_usage(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+    policy: RetentionPolicy,
+) -> ServelessCoreResult<StorageUsage> {
+    let (functions, mut sizes) = load_versions(conn, user_uuid).await?;
+
+    let mut usage: Vec<FunctionStorage> = functions
+        .into_iter()
+        .map(|function| {
+            let versions = sizes.remove(&function.id).unwrap_or_default();
+            FunctionStorage {
+                name: function.name,
+                versions: versions.len(),
+                bytes: versions.iter().map(archive_bytes).sum(),
+                latest_version: versions.iter().map(|version| version.version).max(),
+            }
+        })
+        .collect();
+    usage.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(StorageUsage {
+        total_versions: usage.iter().map(|function| function.versions).sum(),
+        total_bytes: usage.iter().map(|function| function.bytes).sum(),
+        functions: usage,
+        keep_versions: policy.keep_versions,
+        max_bytes: policy.max_bytes_per_namespace,
+    })
+}
+
+/// Prunes the recorded versions of a namespace down to the retention policy.
+///
+/// Each function first loses the versions beyond its newest `keep_versions`; if the
+/// namespace still takes more than `max_bytes_per_namespace`, its oldest versions go
+/// until it fits. A function's latest version and the versions its routing rules name
+/// are never pruned, so the namespace may stay above its limit. Any instance a pruned
+/// version was deployed as is removed along with it, image included.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function cache.
+/// * `autoscaler` - The running autoscaler.
+/// * `user_uuid` - The namespace to prune.
+/// * `policy` - How much history to keep.
+pub async fn prune_namespace(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    user_uuid: Uuid,
+    policy: RetentionPolicy,
+) -> ServelessCoreResult<PruneReport> {
+    if policy.is_unlimited() {
+        return Ok(PruneReport::default());
+    }
+    let (functions, mut sizes) = load_versions(conn, user_uuid).await?;
+
+    let histories = functions
+        .iter()
+        .map(|function| {
+            let versions = sizes.remove(&function.id).unwrap_or_default();
+            (
+                function,
+                RoutingRules::from_model(function).versions(),
+                versions,
+            )
+        })
+        .collect();
+    let (pruned, total) = select_pruned(histories, policy);
+    if let Some(max_bytes) = policy.max_bytes_per_namespace.filter(|max| total > *max) {
+        warn!(
+            "Namespace {} keeps {} bytes of versions, above its {} byte limit, as they are all in use",
+            user_uuid, total, max_bytes
+        );
+    }
+
+    if pruned.is_empty() {
+        return Ok(PruneReport::default());
+    }
+    for (function, version) in &pruned {
+        if let Err(e) = remove_version_instance(
+            conn,
+            cache_conn,
+            autoscaler,
+            &function.name,
+            version.version,
+            user_uuid,
+        )
+        .await
+        {
+            warn!(
+                "Failed to remove the instance of pruned version {} of '{}': {}",
+                version.version, function.name, e
+            );
+        }
+    }
+
+    let report = PruneReport {
+        versions: pruned.len(),
+        bytes: pruned
+            .iter()
+            .map(|(_, version)| archive_bytes(version))
+            .sum(),
+    };
+    let ids = pruned.iter().map(|(_, version)| version.id).collect();
+    FunctionVersionDBRepo::delete_versions(conn, ids)
+        .await
+        .map_err(|e| database_error("Failed to prune versions", e))?;
+    info!(
+        "Pruned {} versions ({} bytes) of namespace {}",
+        report.versions, report.bytes, user_uuid
+    );
+    Ok(report)
+}
+
+/// Picks the versions pruning removes from a namespace, as [`prune_namespace`] describes.
+///
+/// # Arguments
+///
+/// * `histories` - Each function, the versions its routing rules name and its versions.
+/// * `policy` - How much history to keep.
+///
+/// # Returns
+///
+/// The versions to prune with their function, and the bytes the namespace keeps.
+fn select_pruned<F: Copy>(
+    histories: Vec<(F, BTreeSet<i32>, Vec<VersionSize>)>,
+    policy: RetentionPolicy,
+) -> (Vec<(F, VersionSize)>, u64) {
+    let mut total: u64 = histories
+        .iter()
+        .flat_map(|(_, _, versions)| versions)
+        .map(archive_bytes)
+        .sum();
+    let mut pruned = Vec::new();
+    let mut prunable = Vec::new();
+    for (function, mut protected, mut versions) in histories {
+        versions.sort_by_key(|version| version.version);
+        let Some(latest) = versions.last().map(|version| version.version) else {
+            continue;
+        };
+        protected.insert(latest);
+
+        let kept_from = policy
+            .keep_versions
+            .map_or(0, |keep| versions.len().saturating_sub(keep));
+        for (index, version) in versions.into_iter().enumerate() {
+            if protected.contains(&version.version) {
+                continue;
+            }
+            if index < kept_from {
+                total -= archive_bytes(&version);
+                pruned.push((function, version));
+            } else {
+                prunable.push((function, version));
+            }
+        }
+    }
+
+    if let Some(max_bytes) = policy.max_bytes_per_namespace {
+        // Ids grow with every recorded version, so the oldest go first
+        prunable.sort_by_key(|(_, version)| version.id);
+        for (function, version) in prunable {
+            if total <= max_bytes {
+                break;
+            }
+            total -= archive_bytes(&version);
+            pruned.push((function, version));
+        }
+    }
+    (pruned, total)
+}
+
+/// Prunes every namespace down to the retention policy.
+///
+/// # Returns
+///
+/// How many versions were pruned.
+pub async fn prune_all(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    policy: RetentionPolicy,
+) -> ServelessCoreResult<usize> {
+    let namespaces = FunctionDBRepo::find_namespaces(conn)
+        .await
+        .map_err(|e| database_error("Failed to list namespaces", e))?;
+
+    let mut pruned = 0;
+    for user_uuid in namespaces {
+        match prune_namespace(conn, cache_conn, autoscaler, user_uuid, policy).await {
+            Ok(report) => pruned += report.versions,
+            Err(e) => warn!("Failed to prune namespace {}: {}", user_uuid, e),
+        }
+    }
+    Ok(pruned)
+}
+
+/// Prunes every namespace every hour, for as long as the server runs. Returns right away
+/// when the policy keeps everything.
+pub async fn run_retention_loop(
+    conn: DatabaseConnection,
+    mut cache_conn: MultiplexedConnection,
+    autoscaler: Arc<Autoscaler>,
+    policy: RetentionPolicy,
+) {
+    if policy.is_unlimited() {
+        return;
+    }
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match prune_all(&conn, &mut cache_conn, &autoscaler, policy).await {
+            Ok(0) => {}
+            Ok(pruned) => info!("Pruned {} old function versions", pruned),
+            Err(e) => error!("Failed to prune function versions: {}", e),
+        }
+    }
+}
+
+/// The namespace's functions, and the sizes of their versions by function id
+async fn load_versions(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<(Vec<Model>, HashMap<i32, Vec<VersionSize>>)> {
+    let functions = FunctionDBRepo::find_functions_by_user_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to list functions", e))?;
+    let ids = functions.iter().map(|function| function.id).collect();
+    let sizes = FunctionVersionDBRepo::find_sizes(conn, ids)
+        .await
+        .map_err(|e| database_error("Failed to measure function versions", e))?;
+
+    let mut by_function: HashMap<i32, Vec<VersionSize>> = HashMap::new();
+    for size in sizes {
+        by_function.entry(size.function_id).or_default().push(size);
+    }
+    Ok((functions, by_function))
+}
+
+fn archive_bytes(version: &VersionSize) -> u64 {
+    version.size.max(0) as u64
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Versions `1..=count` of a function, each of `size` bytes, with ids counting up from
+    /// `id_offset`, so the function with the lower offset holds the older versions
+    fn versions(id_offset: i32, count: i32, size: i64) -> Vec<VersionSize> {
+        (1..=count)
+            .map(|version| VersionSize {
+                id: id_offset + version,
+                function_id: id_offset,
+                version,
+                size,
+            })
+            .collect()
+    }
+
+    fn policy(keep_versions: Option<usize>, max_bytes: Option<u64>) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_versions,
+            max_bytes_per_namespace: max_bytes,
+        }
+    }
+
+    fn pruned_versions(pruned: &[(&'static str, VersionSize)]) -> Vec<(&'static str, i32)> {
+        let mut pruned: Vec<_> = pruned
+            .iter()
+            .map(|(function, version)| (*function, version.version))
+            .collect();
+        pruned.sort();
+        pruned
+    }
+
+    #[test]
+    fn test_keep_versions_boundary() {
+        let history = || vec![("hello", BTreeSet::new(), versions(0, 5, 10))];
+
+        // Keeping exactly as many versions as there are prunes nothing
+        let (pruned, total) = select_pruned(history(), policy(Some(5), None));
+        assert!(pruned.is_empty());
+        assert_eq!(total, 50);
+
+        // One fewer prunes the oldest only
+        let (pruned, total) = select_pruned(history(), policy(Some(4), None));
+        assert_eq!(pruned_versions(&pruned), vec![("hello", 1)]);
+        assert_eq!(total, 40);
+
+        // Keeping none still keeps the latest
+        let (pruned, total) = select_pruned(history(), policy(Some(0), None));
+        assert_eq!(
+            pruned_versions(&pruned),
+            vec![("hello", 1), ("hello", 2), ("hello", 3), ("hello", 4)]
+        );
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn test_keep_versions_spares_routed_versions() {
+        // Version 2 is routed to: it stays, and still counts towards the kept ones
+        let history = vec![("hello", BTreeSet::from([2]), versions(0, 5, 10))];
+        let (pruned, _) = select_pruned(history, policy(Some(2), None));
+        assert_eq!(pruned_versions(&pruned), vec![("hello", 1), ("hello", 3)]);
+    }
+
+    #[test]
+    fn test_max_bytes_boundary() {
+        let history = || {
+            vec![
+                ("hello", BTreeSet::new(), versions(0, 3, 10)),
+                ("world", BTreeSet::new(), versions(100, 3, 10)),
+            ]
+        };
+
+        // Exactly at the limit, nothing goes
+        let (pruned, total) = select_pruned(history(), policy(None, Some(60)));
+        assert!(pruned.is_empty());
+        assert_eq!(total, 60);
+
+        // One byte above, the oldest version of the namespace goes
+        let (pruned, total) = select_pruned(history(), policy(None, Some(59)));
+        assert_eq!(pruned_versions(&pruned), vec![("hello", 1)]);
+        assert_eq!(total, 50);
+
+        // Oldest first across functions, until it fits
+        let (pruned, total) = select_pruned(history(), policy(None, Some(35)));
+        assert_eq!(
+            pruned_versions(&pruned),
+            vec![("hello", 1), ("hello", 2), ("world", 1)]
+        );
+        assert_eq!(total, 30);
+    }
+
+    #[test]
+    fn test_max_bytes_keeps_versions_in_use() {
+        let history = vec![
+            ("hello", BTreeSet::from([1]), versions(0, 3, 10)),
+            ("world", BTreeSet::new(), versions(100, 1, 10)),
+        ];
+        // Only hello's version 2 may go: the rest are latest or routed to
+        let (pruned, total) = select_pruned(history, policy(None, Some(0)));
+        assert_eq!(pruned_versions(&pruned), vec![("hello", 2)]);
+        assert_eq!(total, 30);
+    }
+
+    #[test]
+    fn test_both_limits() {
+        let history = vec![
+            ("hello", BTreeSet::new(), versions(0, 4, 10)),
+            ("world", BTreeSet::new(), versions(100, 2, 25)),
+        ];
+        // keep_versions prunes hello's 1 and 2 first; of the 70 bytes left, the oldest
+        // remaining version, hello's 3, goes to fit
+        let (pruned, total) = select_pruned(history, policy(Some(2), Some(60)));
+        assert_eq!(
+            pruned_versions(&pruned),
+            vec![("hello", 1), ("hello", 2), ("hello", 3)]
+        );
+        assert_eq!(total, 60);
+    }
+
+    #[test]
+    fn test_functions_without_versions() {
+        let history = vec![("hello", BTreeSet::from([1]), Vec::new())];
+        let (pruned, total) = select_pruned(history, policy(Some(1), Some(0)));
+        assert!(pruned.is_empty());
+        assert_eq!(total, 0);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::FunctionSettings;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::preview::{
+    deploy_version_instance, remove_version_instance, version_instance_name,
+};
+use crate::lifecycle_manager::remote_build::ImageBuilder;
+use crate::utils::routing::RoutingRules;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use sea_orm::DatabaseConnection;
+use std::collections::BTreeSet;
+use std::future::Future;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// The version instances an update of a function's routing rules changed
+#[derive(Debug, Default)]
+pub struct RoutingUpdate {
+    /// Name and settings of the instances deployed for the new rules
+    pub deployed: Vec<(String, FunctionSettings)>,
+    /// Names of the instances removed as the rules no longer name their version
+    pub removed: Vec<String>,
+}
+
+/// Replaces the rules routing invocations of a function to its recorded versions.
+///
+/// Every version the rules name is served by its version instance, `<name>--v<version>`,
+/// deployed from the recorded archive unless it is running already and kept for as long
+/// as the rules name it. Instances only the previous rules named are removed. All versions
+/// are checked before anything is deployed, so an unknown version fails the update
+/// without effect; an update failing later removes the instances it deployed again.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function cache.
+/// * `autoscaler` - The running autoscaler.
+/// * `builder` - Builds the images of deployed instances.
+/// * `function_name` - The function whose rules to replace.
+/// * `user_uuid` - The namespace the function belongs to.
+/// * `rules` - The new rules, already validated.
+///
+/// # Returns
+///
+/// The instances deployed and removed for the update.
+pub async fn update_routing_rules(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    builder: &ImageBuilder,
+    function_name: &str,
+    user_uuid: Uuid,
+    rules: &RoutingRules,
+) -> ServelessCoreResult<RoutingUpdate> {
+    let function = FunctionDBRepo::find_function_by_name(conn, function_name, user_uuid)
+        .await
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "{} in namespace {}",
+                function_name, user_uuid
+            ))
+        })?;
+    if function.preview_of.is_some() {
+        return Err(ServelessCoreError::BadFunction(
+            "Preview instances can't have routing rules".to_string(),
+        ));
+    }
+
+    let versions = rules.versions();
+    for &version in &versions {
+        let recorded = FunctionVersionDBRepo::find_version(conn, function.id, version)
+            .await
+            .map_err(|e| database_error("Failed to load function version", e))?;
+        if recorded.is_none() {
+            return Err(ServelessCoreError::BadFunction(format!(
+                "'{}' has no version {}",
+                function_name, version
+            )));
+        }
+    }
+
+    let remove = |version: i32| {
+        let mut cache_conn = cache_conn.clone();
+        async move {
+            remove_version_instance(
+                conn,
+                &mut cache_conn,
+                autoscaler,
+                function_name,
+                version,
+                user_uuid,
+            )
+            .await
+        }
+    };
+    let function_ref = &function;
+    let deployed = deploy_instances(
+        &versions,
+        |version| async move {
+            let (name, settings) =
+                deploy_version_instance(conn, builder, function_ref, version, None).await?;
+            Ok(settings.map(|settings| (name, settings)))
+        },
+        remove,
+    )
+    .await?;
+
+    let previous = RoutingRules::from_model(&function).versions();
+    let stored = if rules.rules.is_empty() {
+        None
+    } else {
+        serde_json::to_value(rules).ok()
+    };
+    if let Err(e) = FunctionDBRepo::update_routing_rules(conn, function, stored).await {
+        remove_instances(deployed.iter().map(|(version, _)| *version), remove).await;
+        return Err(database_error("Failed to update routing rules", e));
+    }
+
+    let mut update = RoutingUpdate {
+        deployed: deployed.into_iter().map(|(_, instance)| instance).collect(),
+        removed: Vec::new(),
+    };
+    for &version in previous.difference(&versions) {
+        match remove(version).await {
+            Ok(()) => update
+                .removed
+                .push(version_instance_name(function_name, version)),
+            Err(e) => warn!(
+                "Failed to remove the instance of version {} of '{}': {}",
+                version, function_name, e
+            ),
+        }
+    }
+    info!(
+        "Updated routing rules of '{}' in namespace {}",
+        function_name, user_uuid
+    );
+    Ok(update)
+}
+
+/// Deploys the instance of every version with `deploy`, which answers `None` for an
+/// instance that was running already. When a deploy fails, the instances deployed before
+/// it are removed again with `remove`, and the error is returned.
+///
+/// # Returns
+///
+/// The versions deployed now, with what `deploy` answered for them.
+async fn deploy_instances<T, Deploy, DeployFuture, Remove, RemoveFuture>(
+    versions: &BTreeSet<i32>,
+    mut deploy: Deploy,
+    remove: Remove,
+) -> ServelessCoreResult<Vec<(i32, T)>>
+where
+    Deploy: FnMut(i32) -> DeployFuture,
+    DeployFuture: Future<Output = ServelessCoreResult<Option<T>>>,
+    Remove: FnMut(i32) -> RemoveFuture,
+    RemoveFuture: Future<Output = ServelessCoreResult<()>>,
+{
+    let mut deployed = Vec::new();
+    for &version in versions {
+        match deploy(version).await {
+            Ok(Some(instance)) => deployed.push((version, instance)),
+            Ok(None) => {}
+            Err(e) => {
+                remove_instances(deployed.iter().map(|(version, _)| *version), remove).await;
+                return Err(e);
+            }
+        }
+    }
+    Ok(deployed)
+}
+
+/// Removes the instances an update deployed before it failed; failures are only logged,
+/// as the update's error is what gets reported
+async fn remove_instances<Remove, RemoveFuture>(
+    versions: impl Iterator<Item = i32>,
+    mut remove: Remove,
+) where
+    Remove: FnMut(i32) -> RemoveFuture,
+    RemoveFuture: Future<Output = ServelessCoreResult<()>>,
+{
+    for version in versions {
+        if let Err(e) = remove(version).await {
+            warn!(
+                "Failed to remove the instance of version {} after a failed update: {}",
+                version, e
+            );
+        }
+    }
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A deploy that fails for `failing`, finds `running` running already and deploys
+    /// the other versions
+    fn deploy(
+        running: &'static [i32],
+        failing: i32,
+    ) -> impl FnMut(i32) -> std::future::Ready<ServelessCoreResult<Option<String>>> {
+        move |version| {
+            std::future::ready(if version == failing {
+                Err(ServelessCoreError::SystemError("build failed".to_string()))
+            } else if running.contains(&version) {
+                Ok(None)
+            } else {
+                Ok(Some(format!("hello--v{version}")))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deploy_instances() {
+        let removed = Mutex::new(Vec::new());
+        let remove = |version| {
+            removed.lock().unwrap().push(version);
+            std::future::ready(Ok(()))
+        };
+
+        let deployed = deploy_instances(&BTreeSet::from([1, 2, 3]), deploy(&[2], 0), remove)
+            .await
+            .unwrap();
+        assert_eq!(
+            deployed,
+            vec![(1, "hello--v1".to_string()), (3, "hello--v3".to_string())]
+        );
+        assert!(removed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_instances_rolls_back_on_failure() {
+        let removed = Mutex::new(Vec::new());
+        let remove = |version| {
+            removed.lock().unwrap().push(version);
+            std::future::ready(Ok(()))
+        };
+
+        // Version 4 fails: 1 and 3 were deployed for the update, 2 was running before
+        let result =
+            deploy_instances(&BTreeSet::from([1, 2, 3, 4, 5]), deploy(&[2], 4), remove).await;
+        assert!(matches!(result, Err(ServelessCoreError::SystemError(_))));
+        assert_eq!(*removed.lock().unwrap(), vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_instances_keeps_rolling_back_after_a_failed_removal() {
+        let removed = Mutex::new(Vec::new());
+        let remove = |version| {
+            removed.lock().unwrap().push(version);
+            std::future::ready(if version == 1 {
+                Err(ServelessCoreError::SystemError(
+                    "docker is down".to_string(),
+                ))
+            } else {
+                Ok(())
+            })
+        };
+
+        let result = deploy_instances(&BTreeSet::from([1, 2, 3]), deploy(&[], 3), remove).await;
+        assert!(result.is_err());
+        assert_eq!(*removed.lock().unwrap(), vec![1, 2]);
+    }
+}
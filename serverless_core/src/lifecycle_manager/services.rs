@@ -0,0 +1,599 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::models::FunctionSettings;
+use crate::lifecycle_manager::hibernation::NamespaceActivity;
+use crate::lifecycle_manager::invoke::start_function;
+use crate::utils::utils::generate_hash;
+use db_entities::function::Model as FunctionModel;
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::container_manager::ContainerLease;
+use runtime::core::policy::Service;
+use sea_orm::DatabaseConnection;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::copy_bidirectional;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// How often the listeners are brought in line with the deployed functions
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long connecting to a container may take once it is running
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a UDP session lives without a datagram either way
+const UDP_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Datagrams queued for a UDP session while its container starts; more are dropped
+const UDP_SESSION_BUFFER: usize = 64;
+
+/// Largest datagram relayed
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/// Most UDP sessions a listener holds at once. Source addresses of datagrams are easily
+/// spoofed, and every session claims a container, so new clients are dropped beyond it.
+const MAX_UDP_SESSIONS: usize = 1024;
+
+/// Most UDP sessions of one client address at once
+const MAX_UDP_SESSIONS_PER_IP: usize = 16;
+
+/// Publishes a port for each TCP and UDP service function and relays what arrives on it
+/// to the function's containers.
+///
+/// Each TCP connection, and each UDP client address, holds a container for as long as it
+/// lasts, so pools scale on open connections instead of HTTP requests (see
+/// `FunctionPolicy::concurrency_limit`). Ports are assigned once from the configured
+/// range and stored with the function, so they survive redeploys and restarts.
+pub struct ServiceListeners {
+    conn: DatabaseConnection,
+    autoscaler: Arc<Autoscaler>,
+    activity: Arc<NamespaceActivity>,
+    bind_address: IpAddr,
+    ports: RangeInclusive<u16>,
+    /// Listeners by function id
+    listeners: Mutex<HashMap<i32, Listener>>,
+    /// Signalled to reconcile before the next interval, e.g. after a deploy
+    refresh: Notify,
+}
+
+/// A published port and the task accepting on it
+struct Listener {
+    port: u16,
+    service: Service,
+    target: Arc<ServiceTarget>,
+    task: JoinHandle<()>,
+}
+
+/// The function a listener relays to
+struct ServiceTarget {
+    autoscaler: Arc<Autoscaler>,
+    activity: Arc<NamespaceActivity>,
+    name: String,
+    user_uuid: Uuid,
+    /// Replaced when the function is redeployed
+    settings: RwLock<FunctionSettings>,
+}
+
+impl ServiceListeners {
+    pub fn new(
+        conn: DatabaseConnection,
+        autoscaler: Arc<Autoscaler>,
+        activity: Arc<NamespaceActivity>,
+        bind_address: IpAddr,
+        ports: RangeInclusive<u16>,
+    ) -> Self {
+        Self {
+            conn,
+            autoscaler,
+            activity,
+            bind_address,
+            ports,
+            listeners: Mutex::new(HashMap::new()),
+            refresh: Notify::new(),
+        }
+    }
+
+    /// Reconcile the listeners now instead of at the next interval
+    pub fn refresh(&self) {
+        self.refresh.notify_one();
+    }
+
+    /// Keeps a listener open for every service function, for as long as the server runs
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            self.reconcile().await;
+            tokio::select! {
+                _ = tokio::time::sleep(RECONCILE_INTERVAL) => {}
+                _ = self.refresh.notified() => {}
+            }
+        }
+    }
+
+    /// Assigns ports to new service functions, opens their listeners and closes those of
+    /// functions that were removed, trashed or serve HTTP again
+    async fn reconcile(&self) {
+        let functions = match FunctionDBRepo::find_with_settings(&self.conn).await {
+            Ok(functions) => functions,
+            Err(e) => {
+                error!("Failed to load service functions: {}", e);
+                return;
+            }
+        };
+        let mut taken: HashSet<i32> = match FunctionDBRepo::find_service_ports(&self.conn).await {
+            Ok(ports) => ports.into_iter().collect(),
+            Err(e) => {
+                error!("Failed to load service ports: {}", e);
+                return;
+            }
+        };
+
+        let mut served = HashSet::new();
+        for function in functions {
+            let settings = FunctionSettings::from_model(&function);
+            if settings.service.is_http() {
+                if let Some(port) = function.service_port {
+                    self.release_port(function, port, &mut taken).await;
+                }
+                continue;
+            }
+
+            let id = function.id;
+            let port = match function.service_port {
+                Some(port) => port,
+                None => match self.assign_port(function.clone(), &mut taken).await {
+                    Some(port) => port,
+                    None => continue,
+                },
+            };
+            let Ok(port) = u16::try_from(port) else {
+                warn!(
+                    "Function '{}' has invalid service port {}",
+                    function.name, port
+                );
+                continue;
+            };
+            served.insert(id);
+            self.listen(&function, settings, port).await;
+        }
+
+        self.listeners.lock().unwrap().retain(|id, listener| {
+            let keep = served.contains(id);
+            if !keep {
+                listener.task.abort();
+                info!(
+                    "Closed {:?} port {} of function '{}'",
+                    listener.service, listener.port, listener.target.name
+                );
+            }
+            keep
+        });
+    }
+
+    /// Takes the lowest free port of the range for a function. Another controller may
+    /// take it first, in which case the function gets one on the next reconcile.
+    async fn assign_port(&self, function: FunctionModel, taken: &mut HashSet<i32>) -> Option<i32> {
+        let Some(port) = self
+            .ports
+            .clone()
+            .map(i32::from)
+            .find(|port| !taken.contains(port))
+        else {
+            error!(
+                "No service port left for function '{}' in {}-{}",
+                function.name,
+                self.ports.start(),
+                self.ports.end()
+            );
+            return None;
+        };
+
+        let name = function.name.clone();
+        match FunctionDBRepo::set_service_port(&self.conn, function, Some(port)).await {
+            Ok(_) => {
+                taken.insert(port);
+                info!("Assigned service port {} to function '{}'", port, name);
+                Some(port)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to assign port {} to function '{}': {}",
+                    port, name, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Gives back the port of a function that serves HTTP again
+    async fn release_port(&self, function: FunctionModel, port: i32, taken: &mut HashSet<i32>) {
+        let name = function.name.clone();
+        match FunctionDBRepo::set_service_port(&self.conn, function, None).await {
+            Ok(_) => {
+                taken.remove(&port);
+                info!("Released service port {} of function '{}'", port, name);
+            }
+            Err(e) => warn!(
+                "Failed to release port {} of function '{}': {}",
+                port, name, e
+            ),
+        }
+    }
+
+    /// Opens the function's listener, or updates the settings of the open one
+    async fn listen(&self, function: &FunctionModel, settings: FunctionSettings, port: u16) {
+        let function_key = format!("{}-{}", function.name, generate_hash(function.uuid));
+        self.autoscaler
+            .set_function_policy(&function_key, settings.policy());
+
+        let service = settings.service;
+        if let Some(listener) = self.listeners.lock().unwrap().get(&function.id) {
+            if listener.port == port && listener.service == service {
+                *listener.target.settings.write().unwrap() = settings;
+                return;
+            }
+        }
+
+        let target = Arc::new(ServiceTarget {
+            autoscaler: self.autoscaler.clone(),
+            activity: self.activity.clone(),
+            name: function.name.clone(),
+            user_uuid: function.uuid,
+            settings: RwLock::new(settings),
+        });
+        let address = SocketAddr::new(self.bind_address, port);
+        let task = match service {
+            Service::Tcp => match TcpListener::bind(address).await {
+                Ok(listener) => tokio::spawn(serve_tcp(listener, target.clone())),
+                Err(e) => {
+                    error!(
+                        "Failed to bind TCP port {} for '{}': {}",
+                        port, function.name, e
+                    );
+                    return;
+                }
+            },
+            Service::Udp => match UdpSocket::bind(address).await {
+                Ok(socket) => tokio::spawn(serve_udp(socket, target.clone())),
+                Err(e) => {
+                    error!(
+                        "Failed to bind UDP port {} for '{}': {}",
+                        port, function.name, e
+                    );
+                    return;
+                }
+            },
+            Service::Http => return,
+        };
+        info!(
+            "Function '{}' in namespace {} serves {:?} on port {}",
+            function.name, function.uuid, service, port
+        );
+
+        let listener = Listener {
+            port,
+            service,
+            target,
+            task,
+        };
+        if let Some(previous) = self.listeners.lock().unwrap().insert(function.id, listener) {
+            previous.task.abort();
+        }
+    }
+}
+
+impl ServiceTarget {
+    /// Whether the function's firewall lets the client in
+    fn admits(&self, client: IpAddr) -> bool {
+        let settings = self.settings.read().unwrap();
+        let Some(firewall) = &settings.firewall else {
+            return true;
+        };
+        match firewall.check_ip(Some(client)) {
+            Ok(()) => true,
+            Err(rejection) => {
+                debug!(
+                    "Rejected {} for '{}': {}",
+                    client, self.name, rejection.reason
+                );
+                false
+            }
+        }
+    }
+
+    /// A container for one connection or session, with its address. It counts towards
+    /// the container's connections until the lease is dropped.
+    async fn claim(&self) -> Option<(String, ContainerLease)> {
+        self.activity.record(self.user_uuid);
+        match start_function(self.autoscaler.clone(), &self.name, self.user_uuid, None).await {
+            Ok(started) => Some(started),
+            Err(e) => {
+                warn!(
+                    "Failed to start '{}' in namespace {} for a connection: {:?}",
+                    self.name, self.user_uuid, e
+                );
+                None
+            }
+        }
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, target: Arc<ServiceTarget>) {
+    loop {
+        match listener.accept().await {
+            Ok((client, peer)) => {
+                tokio::spawn(relay_tcp(client, peer, target.clone()));
+            }
+            Err(e) => {
+                // Typically out of file descriptors; give connections time to close
+                warn!("Failed to accept a connection for '{}': {}", target.name, e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Relays one TCP connection to a container until either side closes it
+async fn relay_tcp(mut client: TcpStream, peer: SocketAddr, target: Arc<ServiceTarget>) {
+    if !target.admits(peer.ip()) {
+        return;
+    }
+    let Some((addr, _lease)) = target.claim().await else {
+        return;
+    };
+    let mut upstream = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr)).await
+    {
+        Ok(Ok(upstream)) => upstream,
+        Ok(Err(e)) => {
+            warn!("Failed to connect to '{}' at {}: {}", target.name, addr, e);
+            return;
+        }
+        Err(_) => {
+            warn!("Timed out connecting to '{}' at {}", target.name, addr);
+            return;
+        }
+    };
+    let _ = client.set_nodelay(true);
+    let _ = upstream.set_nodelay(true);
+
+    match copy_bidirectional(&mut client, &mut upstream).await {
+        Ok((sent, received)) => debug!(
+            "Connection from {} to '{}' closed ({} bytes in, {} out)",
+            peer, target.name, sent, received
+        ),
+        Err(e) => debug!(
+            "Connection from {} to '{}' failed: {}",
+            peer, target.name, e
+        ),
+    }
+}
+
+async fn serve_udp(socket: UdpSocket, target: Arc<ServiceTarget>) {
+    let socket = Arc::new(socket);
+    let mut sessions = UdpSessions::default();
+    let (ended_tx, mut ended) = mpsc::unbounded_channel();
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buffer) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("Failed to receive a datagram for '{}': {}", target.name, e);
+                        continue;
+                    }
+                };
+                let mut datagram = buffer[..len].to_vec();
+                if let Some(session) = sessions.get(&peer) {
+                    match session.try_send(datagram) {
+                        // Like the network, a session that can't keep up loses datagrams
+                        Ok(()) | Err(TrySendError::Full(_)) => continue,
+                        Err(TrySendError::Closed(returned)) => {
+                            sessions.remove(&peer);
+                            datagram = returned;
+                        }
+                    }
+                }
+                if !sessions.admits(peer) {
+                    debug!(
+                        "Dropped a datagram from {} for '{}': too many UDP sessions",
+                        peer, target.name
+                    );
+                    continue;
+                }
+                if !target.admits(peer.ip()) {
+                    continue;
+                }
+
+                let (session, datagrams) = mpsc::channel(UDP_SESSION_BUFFER);
+                let _ = session.try_send(datagram);
+                sessions.insert(peer, session);
+                tokio::spawn(relay_udp(
+                    socket.clone(),
+                    peer,
+                    datagrams,
+                    target.clone(),
+                    ended_tx.clone(),
+                ));
+            }
+            Some(peer) = ended.recv() => {
+                // The client may have started a new session meanwhile
+                if sessions.get(&peer).is_some_and(|session| session.is_closed()) {
+                    sessions.remove(&peer);
+                }
+            }
+        }
+    }
+}
+
+/// The UDP sessions of a listener, counted per client address
+#[derive(Default)]
+struct UdpSessions {
+    sessions: HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl UdpSessions {
+    fn get(&self, peer: &SocketAddr) -> Option<&mpsc::Sender<Vec<u8>>> {
+        self.sessions.get(peer)
+    }
+
+    /// Whether a new session of `peer` stays within the limits
+    fn admits(&self, peer: SocketAddr) -> bool {
+        self.sessions.len() < MAX_UDP_SESSIONS
+            && self.per_ip.get(&peer.ip()).copied().unwrap_or(0) < MAX_UDP_SESSIONS_PER_IP
+    }
+
+    fn insert(&mut self, peer: SocketAddr, session: mpsc::Sender<Vec<u8>>) {
+        if self.sessions.insert(peer, session).is_none() {
+            *self.per_ip.entry(peer.ip()).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, peer: &SocketAddr) {
+        if self.sessions.remove(peer).is_none() {
+            return;
+        }
+        if let Some(count) = self.per_ip.get_mut(&peer.ip()) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_ip.remove(&peer.ip());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.sessions.len()
+    }
+}
+
+/// Relays one client's datagrams to a container, and the container's back to the
+/// client, until the session idles out
+async fn relay_udp(
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    mut datagrams: mpsc::Receiver<Vec<u8>>,
+    target: Arc<ServiceTarget>,
+    ended: mpsc::UnboundedSender<SocketAddr>,
+) {
+    relay_session(&socket, peer, &mut datagrams, &target).await;
+    datagrams.close();
+    let _ = ended.send(peer);
+}
+
+async fn relay_session(
+    socket: &UdpSocket,
+    peer: SocketAddr,
+    datagrams: &mut mpsc::Receiver<Vec<u8>>,
+    target: &ServiceTarget,
+) {
+    // A session waiting on a container that never starts idles out like any other
+    let Ok(Some((addr, _lease))) =
+        tokio::time::timeout(UDP_SESSION_IDLE_TIMEOUT, target.claim()).await
+    else {
+        debug!(
+            "UDP session of {} with '{}' got no container",
+            peer, target.name
+        );
+        return;
+    };
+    let upstream = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            warn!("Failed to open a UDP socket for '{}': {}", target.name, e);
+            return;
+        }
+    };
+    if let Err(e) = upstream.connect(&addr).await {
+        warn!("Failed to reach '{}' at {}: {}", target.name, addr, e);
+        return;
+    }
+
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    loop {
+        tokio::select! {
+            datagram = datagrams.recv() => {
+                let Some(datagram) = datagram else {
+                    return;
+                };
+                if let Err(e) = upstream.send(&datagram).await {
+                    debug!("Failed to relay a datagram to '{}': {}", target.name, e);
+                    return;
+                }
+            }
+            received = upstream.recv(&mut buffer) => match received {
+                Ok(len) => {
+                    if let Err(e) = socket.send_to(&buffer[..len], peer).await {
+                        debug!("Failed to relay a datagram to {}: {}", peer, e);
+                    }
+                }
+                Err(e) => {
+                    debug!("UDP session of {} with '{}' failed: {}", peer, target.name, e);
+                    return;
+                }
+            },
+            _ = tokio::time::sleep(UDP_SESSION_IDLE_TIMEOUT) => {
+                debug!("UDP session of {} with '{}' idled out", peer, target.name);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(ip: [u8; 4], port: u16) -> SocketAddr {
+        SocketAddr::from((ip, port))
+    }
+
+    #[test]
+    fn test_udp_sessions_per_ip_limit() {
+        let mut sessions = UdpSessions::default();
+        for port in 0..MAX_UDP_SESSIONS_PER_IP as u16 {
+            assert!(sessions.admits(peer([203, 0, 113, 7], port)));
+            sessions.insert(peer([203, 0, 113, 7], port), mpsc::channel(1).0);
+        }
+
+        assert!(!sessions.admits(peer([203, 0, 113, 7], 9999)));
+        assert!(sessions.admits(peer([203, 0, 113, 8], 9999)));
+
+        sessions.remove(&peer([203, 0, 113, 7], 0));
+        assert!(sessions.admits(peer([203, 0, 113, 7], 9999)));
+    }
+
+    #[test]
+    fn test_udp_sessions_listener_limit() {
+        let mut sessions = UdpSessions::default();
+        for i in 0..MAX_UDP_SESSIONS as u32 {
+            let [_, a, b, c] = i.to_be_bytes();
+            sessions.insert(peer([10, a, b, c], 5000), mpsc::channel(1).0);
+        }
+
+        assert_eq!(sessions.len(), MAX_UDP_SESSIONS);
+        assert!(!sessions.admits(peer([192, 0, 2, 1], 5000)));
+    }
+
+    #[test]
+    fn test_udp_sessions_replace_and_remove() {
+        let mut sessions = UdpSessions::default();
+        let client = peer([198, 51, 100, 1], 4000);
+        sessions.insert(client, mpsc::channel(1).0);
+        // A closed session replaced by a new one of the same client counts once
+        sessions.insert(client, mpsc::channel(1).0);
+        assert_eq!(sessions.per_ip[&client.ip()], 1);
+
+        sessions.remove(&client);
+        sessions.remove(&client);
+        assert_eq!(sessions.len(), 0);
+        assert!(sessions.per_ip.is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::db::function::FunctionDBRepo;
+use crate::db::models::{FunctionSettings, Slo};
+use crate::db::slo::SloRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::utils::utils::generate_hash;
+use redis::aio::MultiplexedConnection;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use shared_utils::unix_now;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Short and long windows the burn rate is reported over, in hours
+const BURN_RATE_WINDOWS: [u64; 2] = [1, 6];
+
+/// How a function is doing against its SLO
+#[derive(Debug, Clone, Serialize)]
+pub struct SloReport {
+    pub objective: f64,
+    pub latency_ms: Option<u64>,
+    pub window_days: u32,
+    /// Invocations counted over the window
+    pub invocations: u64,
+    /// Invocations that missed the objective
+    pub bad: u64,
+    /// Percent of good invocations; 100 without invocations
+    pub compliance: f64,
+    /// Share of the error budget left: 1 when untouched, 0 or less once spent
+    pub budget_remaining: f64,
+    /// How fast the budget burns over the last hour and the last 6 hours; 1 spends
+    /// exactly the budget over the window
+    pub burn_rate_1h: f64,
+    pub burn_rate_6h: f64,
+    pub budget_exhausted: bool,
+    /// Deploys are refused until the budget recovers or the deploy is forced
+    pub deploys_frozen: bool,
+}
+
+/// Counts an invocation of a function with an SLO, in the background.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the counts.
+/// * `function_key` - The key of the invoked function.
+/// * `slo` - The function's objective.
+/// * `status` - The status the invocation was answered with.
+/// * `latency` - How long the invocation took to answer.
+pub fn record_invocation(
+    mut cache_conn: MultiplexedConnection,
+    function_key: String,
+    slo: &Slo,
+    status: u16,
+    latency: Duration,
+) {
+    let bad = !slo.is_good(status, latency);
+    // Counts outlive the window by a day, so the oldest hours are still complete
+    let ttl_secs = (u64::from(slo.window_days) + 1) * 24 * 60 * 60;
+    tokio::spawn(async move {
+        if let Err(e) =
+            SloRepo::record(&mut cache_conn, &function_key, unix_hour(), bad, ttl_secs).await
+        {
+            warn!("Failed to count an invocation of '{}': {}", function_key, e);
+        }
+    });
+}
+
+/// Computes how a function is doing against its SLO.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the counts.
+/// * `function_key` - The key of the function.
+/// * `slo` - The function's objective.
+pub async fn slo_report(
+    cache_conn: &mut MultiplexedConnection,
+    function_key: &str,
+    slo: &Slo,
+) -> ServelessCoreResult<SloReport> {
+    let now = unix_hour();
+    let window_hours = u64::from(slo.window_days) * 24;
+    let first_hour = now.saturating_sub(window_hours - 1);
+    let counts = SloRepo::counts(cache_conn, function_key, first_hour / 24..=now / 24)
+        .await
+        .map_err(|e| {
+            error!("Failed to read the SLO counts of '{}': {}", function_key, e);
+            ServelessCoreError::SystemError("Failed to read the SLO counts".to_string())
+        })?;
+    Ok(report(slo, &counts, now))
+}
+
+/// The report of an SLO at hour `now` (since the epoch), from the `<hour>:total` and
+/// `<hour>:bad` counts of its window
+fn report(slo: &Slo, counts: &HashMap<String, u64>, now: u64) -> SloReport {
+    let window_hours = u64::from(slo.window_days) * 24;
+    let first_hour = now.saturating_sub(window_hours - 1);
+
+    // Invocations and bad ones since `since` (an hour since the epoch)
+    let totals = |since: u64| {
+        (since..=now).fold((0, 0), |(total, bad), hour| {
+            let count = |field: &str| counts.get(&format!("{hour}:{field}")).copied();
+            (
+                total + count("total").unwrap_or_default(),
+                bad + count("bad").unwrap_or_default(),
+            )
+        })
+    };
+    // Subtracted first, so an objective of 99 leaves exactly 1% of the invocations
+    let budget = (100.0 - slo.objective) / 100.0;
+    let burn_rate = |hours: u64| {
+        let (total, bad) = totals(now + 1 - hours);
+        if total == 0 {
+            0.0
+        } else {
+            bad as f64 / total as f64 / budget
+        }
+    };
+
+    let (invocations, bad) = totals(first_hour);
+    let bad_share = if invocations == 0 {
+        0.0
+    } else {
+        bad as f64 / invocations as f64
+    };
+    let budget_remaining = 1.0 - bad_share / budget;
+    let budget_exhausted = budget_remaining <= 0.0;
+    let [short, long] = BURN_RATE_WINDOWS;
+    SloReport {
+        objective: slo.objective,
+        latency_ms: slo.latency_ms,
+        window_days: slo.window_days,
+        invocations,
+        bad,
+        compliance: 100.0 * (1.0 - bad_share),
+        budget_remaining,
+        burn_rate_1h: burn_rate(short),
+        burn_rate_6h: burn_rate(long),
+        budget_exhausted,
+        deploys_frozen: budget_exhausted && slo.freeze_deploys,
+    }
+}
+
+/// Finds whether deploys of a function are frozen because it spent its error budget.
+/// Counts that can't be read don't freeze deploys.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the counts.
+/// * `function_name` - The function about to be deployed.
+/// * `user_uuid` - The namespace the function belongs to.
+///
+/// # Returns
+///
+/// The function's SLO report when its deploys are frozen.
+pub async fn deploy_freeze(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    function_name: &str,
+    user_uuid: Uuid,
+) -> Option<SloReport> {
+    let function = FunctionDBRepo::find_function_by_name(conn, function_name, user_uuid).await?;
+    let slo = FunctionSettings::from_model(&function).slo?;
+    if !slo.freeze_deploys {
+        return None;
+    }
+    let function_key = format!("{function_name}-{}", generate_hash(user_uuid));
+    slo_report(cache_conn, &function_key, &slo)
+        .await
+        .ok()
+        .filter(|report| report.deploys_frozen)
+}
+
+fn unix_hour() -> u64 {
+    unix_now() / 3600
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hours since the epoch the reports are computed at, the last hour of a day
+    const NOW: u64 = 500_000 * 24 + 23;
+
+    fn slo(objective: f64, window_days: u32, freeze_deploys: bool) -> Slo {
+        Slo {
+            objective,
+            latency_ms: Some(300),
+            window_days,
+            freeze_deploys,
+        }
+    }
+
+    /// Counts of `(hours before now, total, bad)`
+    fn counts(hours: &[(u64, u64, u64)]) -> HashMap<String, u64> {
+        hours
+            .iter()
+            .flat_map(|&(ago, total, bad)| {
+                let hour = NOW - ago;
+                [
+                    (format!("{hour}:total"), total),
+                    (format!("{hour}:bad"), bad),
+                ]
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_report_without_invocations() {
+        let summary = report(&slo(99.9, 30, true), &HashMap::new(), NOW);
+        assert_eq!((summary.invocations, summary.bad), (0, 0));
+        assert_close(summary.compliance, 100.0);
+        assert_close(summary.budget_remaining, 1.0);
+        assert_close(summary.burn_rate_1h, 0.0);
+        assert_close(summary.burn_rate_6h, 0.0);
+        assert!(!summary.budget_exhausted);
+        assert!(!summary.deploys_frozen);
+    }
+
+    #[test]
+    fn test_report_budget() {
+        // 99% allows 1 bad invocation in 100; 2 in 1000 spend a fifth of it
+        let summary = report(
+            &slo(99.0, 7, true),
+            &counts(&[(0, 500, 1), (30, 500, 1)]),
+            NOW,
+        );
+        assert_eq!((summary.invocations, summary.bad), (1000, 2));
+        assert_close(summary.compliance, 99.8);
+        assert_close(summary.budget_remaining, 0.8);
+        assert!(!summary.budget_exhausted);
+        assert!(!summary.deploys_frozen);
+    }
+
+    #[test]
+    fn test_report_exhausted_budget() {
+        let overspent = counts(&[(2, 100, 1), (3, 100, 2)]);
+        let summary = report(&slo(99.0, 7, true), &overspent, NOW);
+        assert_close(summary.budget_remaining, -0.5);
+        assert!(summary.budget_exhausted);
+        assert!(summary.deploys_frozen);
+
+        // Deploys are only frozen when the SLO asks for it
+        let summary = report(&slo(99.0, 7, false), &overspent, NOW);
+        assert!(summary.budget_exhausted);
+        assert!(!summary.deploys_frozen);
+
+        // Spending exactly the budget exhausts it
+        let summary = report(&slo(99.0, 7, true), &counts(&[(0, 100, 1)]), NOW);
+        assert_close(summary.budget_remaining, 0.0);
+        assert!(summary.budget_exhausted);
+    }
+
+    #[test]
+    fn test_report_window() {
+        // The window is the last `window_days * 24` hours, the current one included
+        let counts = counts(&[(0, 10, 0), (23, 10, 5), (24, 1000, 1000)]);
+        let summary = report(&slo(99.0, 1, false), &counts, NOW);
+        assert_eq!((summary.invocations, summary.bad), (20, 5));
+
+        let summary = report(&slo(99.0, 2, false), &counts, NOW);
+        assert_eq!((summary.invocations, summary.bad), (1020, 1005));
+    }
+
+    #[test]
+    fn test_report_burn_rates() {
+        // The last hour spends the budget 5 times as fast as the window allows; the 6
+        // hours before include a clean hour, and older hours count for neither
+        let counts = counts(&[(0, 100, 5), (5, 100, 0), (6, 800, 800)]);
+        let summary = report(&slo(99.0, 30, false), &counts, NOW);
+        assert_close(summary.burn_rate_1h, 5.0);
+        assert_close(summary.burn_rate_6h, 2.5);
+    }
+
+    #[test]
+    fn test_is_good() {
+        let slo = slo(99.0, 30, false);
+        assert!(slo.is_good(200, Duration::from_millis(300)));
+        assert!(slo.is_good(404, Duration::from_millis(10)));
+        assert!(!slo.is_good(500, Duration::from_millis(10)));
+        assert!(!slo.is_good(200, Duration::from_millis(301)));
+
+        let any_latency = Slo {
+            latency_ms: None,
+            ..slo
+        };
+        assert!(any_latency.is_good(200, Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::db::incident::IncidentRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use redis::aio::MultiplexedConnection;
+use sea_orm::prelude::ChronoDateTimeUtc;
+use serde::{Deserialize, Serialize};
+use shared_utils::unix_now;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How far back the status page's error rate looks
+pub const ERROR_RATE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// How long a computed status page is served before the dependencies are checked again;
+/// the page is public, so it must not turn every request into a round of health checks
+pub const STATUS_TTL: Duration = Duration::from_secs(15);
+
+/// Share of invocations the platform fails with a 5xx above which it counts as degraded
+const DEGRADED_ERROR_RATE: f64 = 0.05;
+
+/// Fewer invocations than this say nothing about the error rate
+const MIN_INVOCATIONS_FOR_ERROR_RATE: u64 = 20;
+
+/// How bad an ongoing incident is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentSeverity {
+    /// Some functions or features are affected
+    #[default]
+    Minor,
+    /// Most invocations or deploys fail
+    Major,
+}
+
+/// An incident an operator flagged on the status page
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Incident {
+    pub id: Uuid,
+    pub title: String,
+    /// What is affected and what is being done about it
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub severity: IncidentSeverity,
+    /// RFC 3339 time the incident was opened
+    pub started_at: String,
+}
+
+/// An incident as submitted by an operator
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NewIncident {
+    pub title: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub severity: IncidentSeverity,
+}
+
+/// Overall state shown on the status page
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PlatformState {
+    Operational,
+    Degraded,
+    MajorOutage,
+}
+
+/// Invocations and server errors seen by this controller over [`ERROR_RATE_WINDOW`]
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+pub struct ErrorRate {
+    pub window_secs: u64,
+    pub invocations: u64,
+    /// Invocations the platform answered with a 5xx. Functions answering with one
+    /// themselves aren't counted.
+    pub errors: u64,
+    pub rate: f64,
+}
+
+impl ErrorRate {
+    /// Whether enough invocations failed to call the platform degraded
+    pub fn is_elevated(&self) -> bool {
+        self.invocations >= MIN_INVOCATIONS_FOR_ERROR_RATE && self.rate > DEGRADED_ERROR_RATE
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MinuteBucket {
+    minute: u64,
+    invocations: u64,
+    errors: u64,
+}
+
+/// Counts invocation outcomes for the status page and keeps the last page computed.
+///
+/// Counts are per controller, in one-minute buckets, and only cover the last
+/// [`ERROR_RATE_WINDOW`].
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    buckets: Mutex<VecDeque<MinuteBucket>>,
+    snapshot: Mutex<Option<(Instant, serde_json::Value)>>,
+}
+
+impl StatusTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an invocation, failed when the platform answered it with a 5xx
+    pub fn record(&self, failed: bool) {
+        let minute = unix_now() / 60;
+        let mut buckets = self.buckets.lock().unwrap();
+        match buckets.back_mut() {
+            Some(bucket) if bucket.minute == minute => {
+                bucket.invocations += 1;
+                bucket.errors += u64::from(failed);
+            }
+            _ => buckets.push_back(MinuteBucket {
+                minute,
+                invocations: 1,
+                errors: u64::from(failed),
+            }),
+        }
+        let oldest = minute.saturating_sub(ERROR_RATE_WINDOW.as_secs() / 60);
+        while buckets.front().is_some_and(|bucket| bucket.minute < oldest) {
+            buckets.pop_front();
+        }
+    }
+
+    /// Invocations and errors over the window
+    pub fn error_rate(&self) -> ErrorRate {
+        let oldest = (unix_now() / 60).saturating_sub(ERROR_RATE_WINDOW.as_secs() / 60);
+        let (invocations, errors) = self
+            .buckets
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|bucket| bucket.minute >= oldest)
+            .fold((0, 0), |(invocations, errors), bucket| {
+                (invocations + bucket.invocations, errors + bucket.errors)
+            });
+        ErrorRate {
+            window_secs: ERROR_RATE_WINDOW.as_secs(),
+            invocations,
+            errors,
+            rate: if invocations == 0 {
+                0.0
+            } else {
+                errors as f64 / invocations as f64
+            },
+        }
+    }
+
+    /// The last page computed, if it is younger than [`STATUS_TTL`]
+    pub fn cached(&self) -> Option<serde_json::Value> {
+        match &*self.snapshot.lock().unwrap() {
+            Some((computed_at, page)) if computed_at.elapsed() < STATUS_TTL => Some(page.clone()),
+            _ => None,
+        }
+    }
+
+    /// Keep a freshly computed page for the next requests
+    pub fn cache(&self, page: serde_json::Value) {
+        *self.snapshot.lock().unwrap() = Some((Instant::now(), page));
+    }
+
+    /// Forget the last page, e.g. after an incident changed
+    pub fn invalidate(&self) {
+        *self.snapshot.lock().unwrap() = None;
+    }
+}
+
+/// Lists the ongoing incidents, oldest first. Incidents that no longer parse are skipped.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the incidents.
+pub async fn list_incidents(
+    cache_conn: &mut MultiplexedConnection,
+) -> ServelessCoreResult<Vec<Incident>> {
+    let stored = IncidentRepo::list(cache_conn).await.map_err(|e| {
+        error!("Failed to list incidents: {}", e);
+        ServelessCoreError::SystemError("Failed to list incidents".to_string())
+    })?;
+    let mut incidents: Vec<Incident> = stored
+        .iter()
+        .filter_map(|incident| match serde_json::from_str(incident) {
+            Ok(incident) => Some(incident),
+            Err(e) => {
+                warn!("Skipping unreadable incident: {}", e);
+                None
+            }
+        })
+        .collect();
+    // RFC 3339 times in UTC sort chronologically
+    incidents.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Ok(incidents)
+}
+
+/// Flags an incident on the status page until it is resolved.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the incidents.
+/// * `new` - The incident to open.
+pub async fn open_incident(
+    cache_conn: &mut MultiplexedConnection,
+    new: NewIncident,
+) -> ServelessCoreResult<Incident> {
+    let incident = Incident {
+        id: Uuid::new_v4(),
+        title: new.title,
+        message: new.message,
+        severity: new.severity,
+        started_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+    };
+    let stored = serde_json::to_string(&incident)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    IncidentRepo::store(cache_conn, &incident.id.to_string(), &stored)
+        .await
+        .map_err(|e| {
+            error!("Failed to store incident: {}", e);
+            ServelessCoreError::SystemError("Failed to store incident".to_string())
+        })?;
+
+    info!("Opened incident {}: {}", incident.id, incident.title);
+    Ok(incident)
+}
+
+/// Takes a resolved incident off the status page.
+///
+/// # Arguments
+///
+/// * `cache_conn` - The Redis connection holding the incidents.
+/// * `id` - The incident to resolve.
+///
+/// # Returns
+///
+/// Whether the incident was ongoing.
+pub async fn resolve_incident(
+    cache_conn: &mut MultiplexedConnection,
+    id: Uuid,
+) -> ServelessCoreResult<bool> {
+    let removed = IncidentRepo::remove(cache_conn, &id.to_string())
+        .await
+        .map_err(|e| {
+            error!("Failed to resolve incident {}: {}", id, e);
+            ServelessCoreError::SystemError("Failed to resolve incident".to_string())
+        })?;
+    if removed {
+        info!("Resolved incident {}", id);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_rate() {
+        let tracker = StatusTracker::new();
+        assert_eq!(tracker.error_rate().rate, 0.0);
+
+        for i in 0..40 {
+            tracker.record(i % 10 == 0);
+        }
+        let error_rate = tracker.error_rate();
+        assert_eq!(error_rate.invocations, 40);
+        assert_eq!(error_rate.errors, 4);
+        assert_eq!(error_rate.rate, 0.1);
+        assert!(error_rate.is_elevated());
+    }
+
+    #[test]
+    fn test_error_rate_needs_enough_invocations() {
+        let tracker = StatusTracker::new();
+        for _ in 0..MIN_INVOCATIONS_FOR_ERROR_RATE - 1 {
+            tracker.record(true);
+        }
+        assert!(!tracker.error_rate().is_elevated());
+        tracker.record(true);
+        assert!(tracker.error_rate().is_elevated());
+    }
+
+    #[test]
+    fn test_old_buckets_leave_the_window() {
+        let tracker = StatusTracker::new();
+        let outside = unix_now() / 60 - ERROR_RATE_WINDOW.as_secs() / 60 - 1;
+        tracker.buckets.lock().unwrap().push_back(MinuteBucket {
+            minute: outside,
+            invocations: 100,
+            errors: 100,
+        });
+        assert_eq!(tracker.error_rate().invocations, 0);
+
+        // Recording drops them
+        tracker.record(false);
+        assert_eq!(tracker.buckets.lock().unwrap().len(), 1);
+        assert_eq!(tracker.error_rate().errors, 0);
+    }
+
+    #[test]
+    fn test_cached_page() {
+        let tracker = StatusTracker::new();
+        assert!(tracker.cached().is_none());
+
+        let page = serde_json::json!({ "state": "operational" });
+        tracker.cache(page.clone());
+        assert_eq!(tracker.cached(), Some(page));
+
+        tracker.invalidate();
+        assert!(tracker.cached().is_none());
+    }
+}
@@ -0,0 +1,327 @@
+use serde::{Deserialize, Serialize};
+
+/// Most bytes of test output kept with a version; longer output keeps its end, where
+/// test runners print failures and the summary
+pub const MAX_TEST_OUTPUT: usize = 64 * 1024;
+
+/// Printed by the test step before the test command runs
+const BEGIN_MARKER: &str = "::invok-tests-begin::";
+
+/// Printed by the test step after the test command, followed by its exit code
+const END_MARKER: &str = "::invok-tests-end::";
+
+/// `test` of `config.json`: `true` runs the runtime's usual test command during the
+/// build, a string runs that command instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TestConfig {
+    Enabled(bool),
+    Command(String),
+}
+
+impl TestConfig {
+    /// The command to run in the build stage, `None` when tests are off
+    pub fn command(&self, runtime: &str) -> Result<Option<String>, String> {
+        let command = match self {
+            TestConfig::Enabled(false) => return Ok(None),
+            TestConfig::Enabled(true) => default_command(runtime)
+                .ok_or_else(|| format!("no default test command for the {} runtime", runtime))?
+                .to_string(),
+            TestConfig::Command(command) => command.trim().to_string(),
+        };
+        if command.is_empty() {
+            return Err("test command is empty".to_string());
+        }
+        // The command becomes a single `RUN` instruction
+        if command.contains(['\n', '\r']) {
+            return Err("test command must be a single line".to_string());
+        }
+        Ok(Some(command))
+    }
+}
+
+/// What a runtime's functions run their unit tests with
+fn default_command(runtime: &str) -> Option<&'static str> {
+    match runtime {
+        "go" => Some("go test ./..."),
+        "nodejs" => Some("npm test"),
+        "rust" => Some("cargo test --release"),
+        _ => None,
+    }
+}
+
+/// Dockerfile instruction running `command` in the build stage, between markers
+/// [`TestResults::from_build_log`] finds its output and exit code by.
+///
+/// The markers are printed with `printf` so the instruction itself, which the build
+/// output echoes, doesn't contain them.
+pub fn dockerfile_step(command: &str) -> String {
+    format!(
+        "RUN printf '::invok-tests-%s::\\n' begin; ({command}); status=$?; \
+         printf '::invok-tests-%s:: %s\\n' end \"$status\"; exit $status"
+    )
+}
+
+/// Outcome of the tests run while building a version, recorded with it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestResults {
+    pub command: String,
+    pub passed: bool,
+    /// Exit code of the test command; unset when it didn't finish or didn't run
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// What the test command printed, at most [`MAX_TEST_OUTPUT`] bytes of its end
+    #[serde(default)]
+    pub output: String,
+    /// Docker reused the test step of an earlier build of the same sources, which passed
+    #[serde(default)]
+    pub cached: bool,
+}
+
+impl TestResults {
+    /// Finds the outcome of the test step in the output of a build, leaving `command`
+    /// for the caller to fill in.
+    ///
+    /// Returns `None` when the test step didn't print anything: the build failed before
+    /// it, or reused it from an earlier build of the same sources.
+    pub fn from_build_log(log: &[String]) -> Option<Self> {
+        let log = log.concat();
+        let mut lines = log.lines().skip_while(|line| line.trim() != BEGIN_MARKER);
+        lines.next()?;
+
+        let mut output = Vec::new();
+        let mut exit_code = None;
+        for line in lines {
+            if let Some(code) = line.trim().strip_prefix(END_MARKER) {
+                exit_code = code.trim().parse().ok();
+                break;
+            }
+            output.push(line);
+        }
+        Some(Self {
+            command: String::new(),
+            passed: exit_code == Some(0),
+            exit_code,
+            output: tail(&output.join("\n"), MAX_TEST_OUTPUT),
+            cached: false,
+        })
+    }
+
+    /// Results of a test step Docker reused from an earlier build that passed it
+    pub fn cached(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            passed: true,
+            cached: true,
+            ..Default::default()
+        }
+    }
+
+    /// The results of a build that ran `command` as its test step, given the results
+    /// found in its output, if any, and whether it succeeded
+    pub fn of_build(command: &str, found: Option<TestResults>, built: bool) -> Option<TestResults> {
+        match found {
+            Some(results) => Some(TestResults {
+                command: command.to_string(),
+                ..results
+            }),
+            None if built => Some(Self::cached(command)),
+            None => None,
+        }
+    }
+
+    /// One-line summary, for the deploy message
+    pub fn summary(&self) -> String {
+        match (self.passed, self.cached, self.exit_code) {
+            (true, true, _) => format!("Tests passed (`{}`, cached)", self.command),
+            (true, false, _) => format!("Tests passed (`{}`)", self.command),
+            (false, _, Some(code)) => format!("Tests failed (`{}` exited {})", self.command, code),
+            (false, _, None) => format!("Tests did not finish (`{}`)", self.command),
+        }
+    }
+}
+
+/// The last `max` bytes of `output`, cut at a character boundary
+fn tail(output: &str, max: usize) -> String {
+    if output.len() <= max {
+        return output.to_string();
+    }
+    let mut start = output.len() - max;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...\n{}", &output[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// Runs the shell command of the test step, as the `RUN` instruction would, and
+    /// returns what it printed, as build output, and its exit code
+    fn run_step(command: &str) -> (Vec<String>, Option<i32>) {
+        let step = dockerfile_step(command);
+        let script = step.strip_prefix("RUN ").unwrap();
+        let output = Command::new("sh").arg("-c").arg(script).output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        // The daemon streams the output in chunks that may split lines
+        let chunks = stdout
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+            .collect();
+        (chunks, output.status.code())
+    }
+
+    #[test]
+    fn test_command() {
+        let enabled = TestConfig::Enabled(true);
+        assert_eq!(
+            enabled.command("go").unwrap().as_deref(),
+            Some("go test ./...")
+        );
+        assert_eq!(
+            enabled.command("nodejs").unwrap().as_deref(),
+            Some("npm test")
+        );
+        assert_eq!(
+            enabled.command("rust").unwrap().as_deref(),
+            Some("cargo test --release")
+        );
+        assert!(enabled.command("image").is_err());
+        assert_eq!(TestConfig::Enabled(false).command("go").unwrap(), None);
+
+        let custom = TestConfig::Command("  make test  ".to_string());
+        assert_eq!(custom.command("go").unwrap().as_deref(), Some("make test"));
+        assert!(TestConfig::Command(" ".to_string()).command("go").is_err());
+        assert!(TestConfig::Command("make\nrm -rf /".to_string())
+            .command("go")
+            .is_err());
+    }
+
+    #[test]
+    fn test_config_deserialize() {
+        let config: TestConfig = serde_json::from_str("true").unwrap();
+        assert!(matches!(config, TestConfig::Enabled(true)));
+        let config: TestConfig = serde_json::from_str(r#""go test -short ./...""#).unwrap();
+        assert!(
+            matches!(config, TestConfig::Command(command) if command == "go test -short ./...")
+        );
+    }
+
+    #[test]
+    fn test_every_runtime_template_has_a_test_step() {
+        for template in [
+            templates::go_template::DOCKERFILE_TEMPLATE,
+            templates::nodejs_template::DOCKERFILE_TEMPLATE,
+            templates::rust_template::DOCKERFILE_TEMPLATE,
+        ] {
+            assert!(template.contains("{{TESTS}}"));
+        }
+    }
+
+    #[test]
+    fn test_step_results_round_trip() {
+        let (log, code) = run_step("echo ok 1 - adds; echo ok 2 - subtracts");
+        assert_eq!(code, Some(0));
+        let results = TestResults::from_build_log(&log).unwrap();
+        assert!(results.passed);
+        assert_eq!(results.exit_code, Some(0));
+        assert_eq!(results.output, "ok 1 - adds\nok 2 - subtracts");
+
+        // The step fails the build with the command's exit code
+        let (log, code) = run_step("echo 'not ok 1 - adds'; exit 3");
+        assert_eq!(code, Some(3));
+        let results = TestResults::from_build_log(&log).unwrap();
+        assert!(!results.passed);
+        assert_eq!(results.exit_code, Some(3));
+        assert_eq!(results.output, "not ok 1 - adds");
+    }
+
+    #[test]
+    fn test_from_build_log() {
+        // The echoed instruction doesn't contain the markers, only its output does
+        let step = dockerfile_step("npm test");
+        assert!(!step.contains(BEGIN_MARKER) && !step.contains(END_MARKER));
+
+        let log = vec![
+            "Step 7/9 : ".to_string(),
+            format!("{step}\n"),
+            format!(" ---> Running in 1a2b\n{BEGIN_MARKER}\n"),
+            "PASS sum.test.js\n".to_string(),
+            format!("{END_MARKER} 0\n"),
+            "Removing intermediate container 1a2b\n".to_string(),
+        ];
+        let results = TestResults::from_build_log(&log).unwrap();
+        assert_eq!(results.output, "PASS sum.test.js");
+        assert!(results.passed);
+
+        // Killed before the end marker: didn't finish
+        let log = vec![format!("{BEGIN_MARKER}\nrunning...\n")];
+        let results = TestResults::from_build_log(&log).unwrap();
+        assert!(!results.passed);
+        assert_eq!(results.exit_code, None);
+        assert_eq!(results.output, "running...");
+
+        // The step didn't run, or Docker reused it
+        let log = vec![
+            "Step 7/9 : RUN npm test\n".to_string(),
+            " ---> Using cache\n".to_string(),
+        ];
+        assert_eq!(TestResults::from_build_log(&log), None);
+    }
+
+    #[test]
+    fn test_of_build() {
+        let found = TestResults {
+            passed: false,
+            exit_code: Some(1),
+            ..Default::default()
+        };
+        let results = TestResults::of_build("go test ./...", Some(found), false).unwrap();
+        assert_eq!(results.command, "go test ./...");
+        assert!(!results.passed);
+
+        // Nothing printed but the build succeeded: the cached step passed before
+        let results = TestResults::of_build("go test ./...", None, true).unwrap();
+        assert!(results.passed && results.cached);
+        // Nothing printed and the build failed before the step: no results
+        assert_eq!(TestResults::of_build("go test ./...", None, false), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let results = |passed, cached, exit_code| TestResults {
+            command: "npm test".to_string(),
+            passed,
+            exit_code,
+            cached,
+            output: String::new(),
+        };
+        assert_eq!(
+            results(true, false, Some(0)).summary(),
+            "Tests passed (`npm test`)"
+        );
+        assert_eq!(
+            results(true, true, None).summary(),
+            "Tests passed (`npm test`, cached)"
+        );
+        assert_eq!(
+            results(false, false, Some(2)).summary(),
+            "Tests failed (`npm test` exited 2)"
+        );
+        assert_eq!(
+            results(false, false, None).summary(),
+            "Tests did not finish (`npm test`)"
+        );
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail("short", 10), "short");
+        assert_eq!(tail("0123456789", 4), "...\n6789");
+        // Never cuts a character in half
+        assert_eq!(tail("aéb", 2), "...\nb");
+    }
+}
@@ -0,0 +1,273 @@
+use ring::digest::{digest, SHA256};
+use ring::hmac::{self, HMAC_SHA1_FOR_LEGACY_USE_ONLY};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name authenticator apps show next to the account
+const ISSUER: &str = "invok";
+
+/// Length of generated secrets, in bytes (RFC 4226 recommends 160 bits)
+const SECRET_LENGTH: usize = 20;
+
+/// Seconds each code is valid for
+const STEP_SECS: u64 = 30;
+
+/// Digits of a code
+const DIGITS: u32 = 6;
+
+/// Steps before and after the current one whose codes are still accepted, to allow for
+/// clock drift
+const ALLOWED_DRIFT: u64 = 1;
+
+/// Recovery codes issued when two-factor authentication is enabled
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Name the secret is sealed under with the server's `BuildArgCipher`, so a sealed build
+/// arg copied into the secret's column doesn't decrypt
+pub const SEALED_NAME: &str = "totp-secret";
+
+/// RFC 4648 base32 alphabet, used for secrets and recovery codes
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generates a new base32-encoded TOTP secret
+pub fn generate_secret() -> Result<String, String> {
+    let mut secret = [0u8; SECRET_LENGTH];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| "Failed to generate a two-factor secret".to_string())?;
+    Ok(base32_encode(&secret))
+}
+
+/// `otpauth://` URI authenticator apps enroll the secret from, usually shown as a QR code
+pub fn provisioning_uri(email: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        ISSUER,
+        urlencoding::encode(email),
+        secret,
+        ISSUER,
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+/// Checks a code from an authenticator app against the base32 `secret` (RFC 6238, SHA-1,
+/// 6 digits, 30 second steps).
+///
+/// # Returns
+///
+/// The time step the code is for, when it matches. A code is only good once: callers
+/// accept it only if the step is later than the last one accepted.
+pub fn verify_code(secret: &str, code: &str) -> Option<u64> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    verify_code_at(secret, code, now)
+}
+
+/// Checks a code like [`verify_code`], at `now` seconds since the Unix epoch
+fn verify_code_at(secret: &str, code: &str, now: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+    let key = base32_decode(secret)?;
+    let step = now / STEP_SECS;
+
+    // Compare against every step in the window so timing doesn't tell which one matched
+    (step.saturating_sub(ALLOWED_DRIFT)..=step + ALLOWED_DRIFT).fold(None, |matched, step| {
+        if hotp(&key, step) == code {
+            Some(step)
+        } else {
+            matched
+        }
+    })
+}
+
+/// Generates a set of single-use recovery codes, returning them with the hashes to store
+pub fn generate_recovery_codes() -> Result<(Vec<String>, Vec<String>), String> {
+    let rng = SystemRandom::new();
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let mut bytes = [0u8; 8];
+        rng.fill(&mut bytes)
+            .map_err(|_| "Failed to generate recovery codes".to_string())?;
+        let encoded = base32_encode(&bytes).to_lowercase();
+        codes.push(format!("{}-{}", &encoded[..5], &encoded[5..10]));
+    }
+    let hashes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+    Ok((codes, hashes))
+}
+
+/// Hash a recovery code is stored as; codes are random, so a plain digest is enough
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    digest(&SHA256, normalized.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Uses up `code` if its hash is one of the recovery code `hashes` left, so it can't be
+/// used again
+pub fn take_recovery_code(hashes: &mut Vec<String>, code: &str) -> bool {
+    let hash = hash_recovery_code(code);
+    let Some(index) = hashes.iter().position(|stored| *stored == hash) else {
+        return false;
+    };
+    hashes.remove(index);
+    true
+}
+
+/// HOTP value of `counter` (RFC 4226)
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let key = hmac::Key::new(HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let hash = tag.as_ref();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    truncated % 10u32.pow(DIGITS)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u64::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+/// Decodes base32, ignoring case, padding and the spaces apps group secrets with
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    (!decoded.is_empty()).then_some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seed of the SHA-1 test vectors of RFC 6238, Appendix B
+    const RFC_SEED: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // The RFC lists 8 digit codes; 6 digit codes are their last 6 digits
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+        let secret = base32_encode(RFC_SEED);
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        for (time, expected) in vectors {
+            let code = expected % 1_000_000;
+            assert_eq!(hotp(RFC_SEED, time / STEP_SECS), code, "at {}", time);
+            assert_eq!(
+                verify_code_at(&secret, &format!("{:06}", code), time),
+                Some(time / STEP_SECS)
+            );
+        }
+    }
+
+    #[test]
+    fn test_drift_window() {
+        let secret = base32_encode(RFC_SEED);
+        let now = 1234567890;
+        let step = now / STEP_SECS;
+        let code = |step| format!("{:06}", hotp(RFC_SEED, step));
+
+        // One step either side is accepted, two are not; the step matched is returned
+        assert_eq!(
+            verify_code_at(&secret, &code(step - 1), now),
+            Some(step - 1)
+        );
+        assert_eq!(
+            verify_code_at(&secret, &code(step + 1), now),
+            Some(step + 1)
+        );
+        assert_eq!(verify_code_at(&secret, &code(step - 2), now), None);
+        assert_eq!(verify_code_at(&secret, &code(step + 2), now), None);
+
+        // The window moves with the step, not the second
+        let last_second = (step + 1) * STEP_SECS - 1;
+        assert!(verify_code_at(&secret, &code(step - 1), last_second).is_some());
+        assert!(verify_code_at(&secret, &code(step - 1), last_second + 1).is_none());
+    }
+
+    #[test]
+    fn test_malformed_codes_rejected() {
+        let secret = base32_encode(RFC_SEED);
+        assert!(verify_code_at(&secret, " 287082 ", 59).is_some());
+        assert!(verify_code_at(&secret, "28708", 59).is_none());
+        assert!(verify_code_at(&secret, "94287082", 59).is_none());
+        assert!(verify_code_at(&secret, "28708a", 59).is_none());
+        assert!(verify_code_at("not base32!", "287082", 59).is_none());
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        let bytes = [0u8, 1, 2, 253, 254, 255, 42];
+        let encoded = base32_encode(&bytes);
+        assert_eq!(base32_decode(&encoded).unwrap(), bytes);
+        // Apps show secrets lowercase, padded or grouped with spaces
+        let grouped = "gezd gnbv gy3t qojq gezd gnbv gy3t qojq====";
+        assert_eq!(base32_decode(grouped).unwrap(), RFC_SEED);
+        assert_eq!(base32_decode(""), None);
+    }
+
+    #[test]
+    fn test_recovery_code_used_once() {
+        let (codes, mut hashes) = generate_recovery_codes().unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 11));
+
+        assert!(take_recovery_code(&mut hashes, &codes[0]));
+        assert!(!take_recovery_code(&mut hashes, &codes[0]));
+        assert_eq!(hashes.len(), RECOVERY_CODE_COUNT - 1);
+
+        // Case and the dash don't matter
+        let typed = codes[1].replace('-', "").to_uppercase();
+        assert!(take_recovery_code(&mut hashes, &typed));
+        assert!(!take_recovery_code(&mut hashes, &codes[1]));
+        assert!(!take_recovery_code(&mut hashes, "aaaaa-aaaaa"));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::function_version::FunctionVersionDBRepo;
+use crate::db::models::{DeployableFunction, FunctionSettings, NamespaceDefaults, PriorVersion};
+use crate::lifecycle_manager::deploy::deploy_function;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::remote_build::ImageBuilder;
+use crate::utils::archive::{pack_blocking, unpack_blocking};
+use db_entities::function_version::Model as FunctionVersion;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Layout version of export archives; bumped on incompatible changes
+const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Path of the manifest inside an export archive
+const MANIFEST_PATH: &str = "manifest.json";
+
+/// Longest function name accepted on import (same limit as invocations)
+const MAX_FUNCTION_NAME_LENGTH: usize = 25;
+
+/// Describes the contents of an export archive.
+///
+/// The archive is a gzipped tarball holding this manifest as `manifest.json` and every
+/// recorded version of every function as `functions/<name>/v<version>.zip`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub format_version: u32,
+    /// Namespace the functions were exported from
+    pub namespace: Uuid,
+    /// RFC 3339 time of the export
+    pub exported_at: String,
+    #[serde(default)]
+    pub defaults: NamespaceDefaults,
+    pub functions: Vec<ExportedFunction>,
+}
+
+/// A function and its version history, oldest version first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedFunction {
+    pub name: String,
+    pub runtime: String,
+    pub versions: Vec<ExportedVersion>,
+}
+
+/// One deployed archive of a function
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedVersion {
+    pub version: i32,
+    /// RFC 3339 time the version was deployed
+    pub created_at: String,
+    /// Settings the version was deployed with, namespace defaults included
+    pub settings: Option<serde_json::Value>,
+    /// Path of the zipped source inside the export archive
+    pub archive: String,
+}
+
+/// Outcome of an import, per function
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: Vec<ImportedFunction>,
+    /// Functions the archive has no source for
+    pub skipped: Vec<FunctionIssue>,
+    pub failed: Vec<FunctionIssue>,
+}
+
+/// A function deployed from an import archive
+#[derive(Debug, Serialize)]
+pub struct ImportedFunction {
+    pub name: String,
+    /// Versions restored, including the deployed one
+    pub versions: usize,
+    /// Settings the function was deployed with
+    #[serde(skip)]
+    pub settings: FunctionSettings,
+}
+
+/// A function that wasn't imported, and why
+#[derive(Debug, Serialize)]
+pub struct FunctionIssue {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Packs every function of a namespace, with its version history, and the namespace
+/// defaults into a gzipped tarball.
+///
+/// Functions deployed before versions were recorded have no stored source; they are
+/// listed in the manifest without versions and skipped on import.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user_uuid` - The namespace to export.
+///
+/// # Returns
+///
+/// The archive bytes.
+pub async fn export_namespace(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<Vec<u8>> {
+    let user = AuthDBRepo::find_by_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to load namespace", e))?
+        .ok_or_else(|| ServelessCoreError::SystemError(format!("User {} not found", user_uuid)))?;
+    let functions = FunctionDBRepo::find_functions_by_user_uuid(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to list functions", e))?;
+
+    let mut manifest = ExportManifest {
+        format_version: EXPORT_FORMAT_VERSION,
+        namespace: user_uuid,
+        exported_at: ChronoDateTimeUtc::from(SystemTime::now()).to_rfc3339(),
+        defaults: NamespaceDefaults::from_model(&user),
+        functions: Vec::with_capacity(functions.len()),
+    };
+    let mut archives = Vec::new();
+
+    // Preview instances are temporary and not worth moving
+    for function in functions.into_iter().filter(|f| f.preview_of.is_none()) {
+        let versions = FunctionVersionDBRepo::find_by_function(conn, function.id)
+            .await
+            .map_err(|e| database_error("Failed to load function versions", e))?;
+        if versions.is_empty() {
+            warn!(
+                "Function '{}' has no stored source; redeploy it to include it in exports",
+                function.name
+            );
+        }
+
+        let mut exported = ExportedFunction {
+            name: function.name,
+            runtime: function.runtime,
+            versions: Vec::with_capacity(versions.len()),
+        };
+        for version in versions {
+            let path = format!("functions/{}/v{}.zip", exported.name, version.version);
+            exported.versions.push(ExportedVersion {
+                version: version.version,
+                created_at: version.created_at.to_rfc3339(),
+                settings: version.settings,
+                archive: path.clone(),
+            });
+            archives.push((path, version.archive));
+        }
+        manifest.functions.push(exported);
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| ServelessCoreError::SystemError(e.to_string()))?;
+    archives.insert(0, (MANIFEST_PATH.to_string(), manifest_json));
+    pack_blocking(archives)
+        .await
+        .map_err(|e| ServelessCoreError::SystemError(format!("Failed to build export: {}", e)))
+}
+
+/// Restores an export archive into a namespace.
+///
+/// The namespace defaults are restored first so functions inherit the same values they
+/// had. Each function is then deployed from its latest version, with the earlier versions
+/// recorded as its history. Functions that already exist are redeployed, keeping the
+/// versions they have recorded; earlier versions they already have, as after importing
+/// the same archive before, aren't recorded again. A function failing to deploy doesn't
+/// stop the others.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user_uuid` - The namespace to import into.
+/// * `archive` - An archive produced by [`export_namespace`].
+/// * `max_unpacked_size` - Most bytes the archive may unpack to.
+/// * `builder` - Builds the functions' images.
+///
+/// # Returns
+///
+/// What was imported, skipped and failed.
+pub async fn import_namespace(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+    archive: Vec<u8>,
+    max_unpacked_size: usize,
+    builder: &ImageBuilder,
+) -> ServelessCoreResult<ImportReport> {
+    let mut files = unpack_blocking(archive, max_unpacked_size)
+        .await
+        .map_err(invalid_archive)?;
+    let manifest = files
+        .remove(MANIFEST_PATH)
+        .ok_or_else(|| invalid_archive(format!("missing {}", MANIFEST_PATH)))?;
+    let manifest: ExportManifest =
+        serde_json::from_slice(&manifest).map_err(|e| invalid_archive(e.to_string()))?;
+    if manifest.format_version != EXPORT_FORMAT_VERSION {
+        return Err(invalid_archive(format!(
+            "unsupported format version {}",
+            manifest.format_version
+        )));
+    }
+    manifest
+        .defaults
+        .validate()
+        .map_err(|e| invalid_archive(format!("invalid namespace defaults: {}", e)))?;
+
+    if manifest.defaults != NamespaceDefaults::default() {
+        let user = AuthDBRepo::find_by_uuid(conn, user_uuid)
+            .await
+            .map_err(|e| database_error("Failed to load namespace", e))?
+            .ok_or_else(|| {
+                ServelessCoreError::SystemError(format!("User {} not found", user_uuid))
+            })?;
+        AuthDBRepo::update_defaults(conn, user, serde_json::to_value(&manifest.defaults).ok())
+            .await
+            .map_err(|e| database_error("Failed to restore namespace defaults", e))?;
+    }
+
+    let mut report = ImportReport::default();
+    for function in manifest.functions {
+        let name = function.name;
+        if let Err(reason) = validate_function_name(&name) {
+            report.failed.push(FunctionIssue { name, reason });
+            continue;
+        }
+
+        let mut versions = function.versions;
+        versions.sort_by_key(|version| version.version);
+        let Some(latest) = versions.pop() else {
+            report.skipped.push(FunctionIssue {
+                name,
+                reason: "no stored source in the export".to_string(),
+            });
+            continue;
+        };
+
+        let recorded =
+            match FunctionDBRepo::find_function_including_trashed(conn, &name, user_uuid).await {
+                Some(existing) => FunctionVersionDBRepo::find_by_function(conn, existing.id)
+                    .await
+                    .map_err(|e| database_error("Failed to load function versions", e))?,
+                None => Vec::new(),
+            };
+        let history = versions
+            .into_iter()
+            .filter_map(|version| {
+                let Some(content) = files.remove(&version.archive) else {
+                    return Some(Err(version.archive));
+                };
+                if is_recorded(&recorded, &version.created_at, &content) {
+                    return None;
+                }
+                Some(Ok(PriorVersion {
+                    content,
+                    settings: version.settings,
+                    created_at: Some(version.created_at),
+                }))
+            })
+            .collect::<Result<Vec<_>, String>>();
+        let (content, history) = match (files.remove(&latest.archive), history) {
+            (Some(content), Ok(history)) => (content, history),
+            (None, _) => {
+                report.failed.push(missing_archive(name, &latest.archive));
+                continue;
+            }
+            (_, Err(path)) => {
+                report.failed.push(missing_archive(name, &path));
+                continue;
+            }
+        };
+
+        let versions = history.len() + 1;
+        let function = DeployableFunction {
+            name: name.clone(),
+            content,
+            user_uuid,
+            history,
+            preview: None,
+        };
+        match deploy_function(conn, function, builder).await {
+            Ok((_, settings)) => {
+                info!("Imported function '{}' ({} versions)", name, versions);
+                report.imported.push(ImportedFunction {
+                    name,
+                    versions,
+                    settings,
+                });
+            }
+            Err(e) => {
+                error!("Failed to import function '{}': {}", name, e);
+                report.failed.push(FunctionIssue {
+                    name,
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Whether a function already recorded a version of an export archive: the same source,
+/// deployed at the same time
+fn is_recorded(recorded: &[FunctionVersion], created_at: &str, archive: &[u8]) -> bool {
+    let Ok(created_at) = DateTimeWithTimeZone::parse_from_rfc3339(created_at) else {
+        return false;
+    };
+    recorded
+        .iter()
+        .any(|version| version.created_at == created_at && version.archive == archive)
+}
+
+/// Names end up in paths and image tags, so reject anything the invocation route would
+fn validate_function_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("function name is empty".to_string());
+    }
+    if name.contains("..") || name.contains('/') || name.contains('\\') {
+        return Err("function name contains invalid characters".to_string());
+    }
+    if name.len() > MAX_FUNCTION_NAME_LENGTH {
+        return Err(format!(
+            "function name is too long (max {} characters)",
+            MAX_FUNCTION_NAME_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn missing_archive(name: String, path: &str) -> FunctionIssue {
+    FunctionIssue {
+        name,
+        reason: format!("archive {} is missing from the export", path),
+    }
+}
+
+fn invalid_archive(reason: String) -> ServelessCoreError {
+    ServelessCoreError::BadFunction(format!("Invalid import archive: {}", reason))
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(archive: &[u8], created_at: &str) -> FunctionVersion {
+        FunctionVersion {
+            id: 1,
+            function_id: 1,
+            version: 1,
+            archive: archive.to_vec(),
+            settings: None,
+            created_at: DateTimeWithTimeZone::parse_from_rfc3339(created_at).unwrap(),
+            promoted_from: None,
+            test_results: None,
+        }
+    }
+
+    #[test]
+    fn test_is_recorded() {
+        let versions = [recorded(b"v1", "2025-09-01T12:00:00+00:00")];
+        assert!(is_recorded(&versions, "2025-09-01T12:00:00+00:00", b"v1"));
+        // The same instant, written in another offset
+        assert!(is_recorded(&versions, "2025-09-01T14:00:00+02:00", b"v1"));
+        assert!(!is_recorded(&versions, "2025-09-01T12:00:00+00:00", b"v2"));
+        assert!(!is_recorded(&versions, "2025-09-01T12:00:01+00:00", b"v1"));
+        assert!(!is_recorded(&versions, "yesterday", b"v1"));
+        assert!(!is_recorded(&[], "2025-09-01T12:00:00+00:00", b"v1"));
+    }
+}
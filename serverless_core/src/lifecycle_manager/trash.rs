@@ -0,0 +1,260 @@
+use crate::db::cache::FunctionCacheRepo;
+use crate::db::egress::EgressAllowlistRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::kv::KvRepo;
+use crate::lifecycle_manager::error::{ServelessCoreError, ServelessCoreResult};
+use crate::lifecycle_manager::jobs::JobQueue;
+use crate::utils::utils::generate_hash;
+use db_entities::function::Model;
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use runtime::core::provisioning::remove_image;
+use sea_orm::prelude::{ChronoDateTimeUtc, DateTimeWithTimeZone};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// How often expired functions are looked for
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Kind of the jobs purging one function from the trash
+pub const PURGE_JOB: &str = "purge_function";
+
+/// Payload of a [`PURGE_JOB`]
+#[derive(Debug, Serialize, Deserialize)]
+struct Purge {
+    namespace: Uuid,
+    name: String,
+}
+
+/// A function in the trash
+#[derive(Debug, Serialize)]
+pub struct TrashedFunction {
+    pub name: String,
+    pub runtime: String,
+    /// RFC 3339 time the function was deleted
+    pub deleted_at: String,
+    /// RFC 3339 time after which the function is purged for good
+    pub purge_at: String,
+}
+
+impl TrashedFunction {
+    fn from_model(function: Model, retention: Duration) -> Self {
+        let deleted_at = function.deleted_at.unwrap_or_default();
+        let purge_at = ChronoDateTimeUtc::from(SystemTime::from(deleted_at) + retention);
+        Self {
+            name: function.name,
+            runtime: function.runtime,
+            deleted_at: deleted_at.to_rfc3339(),
+            purge_at: purge_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Moves a function to the trash.
+///
+/// The function stops being invocable right away: its containers are removed and it no
+/// longer shows up in listings. The record, its versions and its image are kept until
+/// the retention period ends, so [`restore_function`] can bring it back.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function cache.
+/// * `autoscaler` - The running autoscaler.
+/// * `name` - The function to delete.
+/// * `user_uuid` - The namespace the function belongs to.
+/// * `retention` - How long the function stays restorable.
+pub async fn trash_function(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    autoscaler: &Autoscaler,
+    name: &str,
+    user_uuid: Uuid,
+    retention: Duration,
+) -> ServelessCoreResult<TrashedFunction> {
+    let function = FunctionDBRepo::find_function_by_name(conn, name, user_uuid)
+        .await
+        .ok_or_else(|| not_found(name, user_uuid))?;
+
+    let deleted_at: DateTimeWithTimeZone = ChronoDateTimeUtc::from(SystemTime::now()).into();
+    let function = FunctionDBRepo::set_deleted_at(conn, function, Some(deleted_at))
+        .await
+        .map_err(|e| database_error("Failed to delete function", e))?;
+
+    // The next invocation has to go back to the database, which no longer finds it
+    let _ = FunctionCacheRepo::remove_function(cache_conn, user_uuid, name).await;
+    let function_key = format!("{name}-{}", generate_hash(user_uuid));
+    autoscaler.remove_function(&function_key).await;
+    // Restoring publishes it again
+    let _ = EgressAllowlistRepo::set(cache_conn, &function_key, None).await;
+
+    info!("Function '{}' moved to the trash", function_key);
+    Ok(TrashedFunction::from_model(function, retention))
+}
+
+/// Brings a function back from the trash.
+///
+/// Its containers start again on the next invocation, from the image it was last
+/// deployed with.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `name` - The function to restore.
+/// * `user_uuid` - The namespace the function belongs to.
+pub async fn restore_function(
+    conn: &DatabaseConnection,
+    name: &str,
+    user_uuid: Uuid,
+) -> ServelessCoreResult<Model> {
+    let function = FunctionDBRepo::find_function_including_trashed(conn, name, user_uuid)
+        .await
+        .filter(|function| function.deleted_at.is_some())
+        .ok_or_else(|| {
+            ServelessCoreError::FunctionNotRegistered(format!(
+                "Function '{}' is not in the trash of namespace '{}'",
+                name, user_uuid
+            ))
+        })?;
+
+    let function = FunctionDBRepo::set_deleted_at(conn, function, None)
+        .await
+        .map_err(|e| database_error("Failed to restore function", e))?;
+    info!("Function '{}' restored from the trash", name);
+    Ok(function)
+}
+
+/// Lists the functions of a namespace that are in the trash, most recently deleted first.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `user_uuid` - The namespace to list.
+/// * `retention` - How long functions stay restorable.
+pub async fn list_trash(
+    conn: &DatabaseConnection,
+    user_uuid: Uuid,
+    retention: Duration,
+) -> ServelessCoreResult<Vec<TrashedFunction>> {
+    let functions = FunctionDBRepo::find_trashed_functions(conn, user_uuid)
+        .await
+        .map_err(|e| database_error("Failed to list trash", e))?;
+    Ok(functions
+        .into_iter()
+        .map(|function| TrashedFunction::from_model(function, retention))
+        .collect())
+}
+
+/// Queues a purge of every function that has been in the trash longer than `retention`.
+///
+/// Every controller looks for expired functions, so each purge is queued once per
+/// [`PURGE_INTERVAL`], by whichever controller gets to it first.
+///
+/// # Returns
+///
+/// How many purges this controller queued.
+pub async fn purge_expired(
+    conn: &DatabaseConnection,
+    jobs: &JobQueue,
+    retention: Duration,
+) -> ServelessCoreResult<usize> {
+    let cutoff: DateTimeWithTimeZone =
+        ChronoDateTimeUtc::from(SystemTime::now() - retention).into();
+    let expired = FunctionDBRepo::find_trashed_before(conn, cutoff)
+        .await
+        .map_err(|e| database_error("Failed to find expired functions", e))?;
+
+    let mut queued = 0;
+    for function in expired {
+        let dedup_key = format!("{}/{}", function.uuid, function.name);
+        let purge = Purge {
+            namespace: function.uuid,
+            name: function.name,
+        };
+        if jobs
+            .enqueue_unique(PURGE_JOB, &dedup_key, PURGE_INTERVAL, purge)
+            .await?
+            .is_some()
+        {
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
+
+/// Runs a [`PURGE_JOB`]: permanently deletes a function from the trash, together with
+/// its versions, image and key-value entries.
+///
+/// # Arguments
+///
+/// * `conn` - A reference to the database connection.
+/// * `cache_conn` - The Redis connection holding the function's key-value entries.
+/// * `retention` - How long functions stay restorable.
+/// * `payload` - The [`Purge`] to make.
+///
+/// # Returns
+///
+/// * `Ok(())` once the function is gone, or if it was restored or purged in the
+///   meantime; otherwise why it couldn't be purged, so the purge is retried.
+pub async fn run_purge(
+    conn: &DatabaseConnection,
+    cache_conn: &mut MultiplexedConnection,
+    retention: Duration,
+    payload: serde_json::Value,
+) -> Result<(), String> {
+    let purge: Purge =
+        serde_json::from_value(payload).map_err(|e| format!("Invalid purge: {e}"))?;
+    let cutoff = SystemTime::now() - retention;
+    let Some(function) =
+        FunctionDBRepo::find_function_including_trashed(conn, &purge.name, purge.namespace)
+            .await
+            .filter(|function| {
+                function
+                    .deleted_at
+                    .is_some_and(|deleted_at| SystemTime::from(deleted_at) <= cutoff)
+            })
+    else {
+        return Ok(());
+    };
+
+    let function_key = format!("{}-{}", function.name, generate_hash(function.uuid));
+    // Keep the record until the image is gone, so the retry finds it again
+    remove_image(&function_key)
+        .await
+        .map_err(|e| format!("Failed to remove image of '{function_key}': {e}"))?;
+    KvRepo::clear(cache_conn, &function_key)
+        .await
+        .map_err(|e| format!("Failed to remove key-value entries of '{function_key}': {e}"))?;
+    FunctionDBRepo::delete_function(conn, function)
+        .await
+        .map_err(|e| format!("Failed to purge '{function_key}': {e}"))?;
+    info!("Function '{}' purged from the trash", function_key);
+    Ok(())
+}
+
+/// Looks for expired functions every hour, for as long as the server runs, and queues
+/// their purges.
+pub async fn run_purge_loop(conn: DatabaseConnection, jobs: JobQueue, retention: Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = purge_expired(&conn, &jobs, retention).await {
+            error!("Failed to purge the trash: {}", e);
+        }
+    }
+}
+
+fn not_found(name: &str, user_uuid: Uuid) -> ServelessCoreError {
+    ServelessCoreError::FunctionNotRegistered(format!(
+        "Function '{}' not found in namespace '{}'",
+        name, user_uuid
+    ))
+}
+
+fn database_error(context: &str, e: sea_orm::DbErr) -> ServelessCoreError {
+    error!("{}: {}", context, e);
+    ServelessCoreError::SystemError(context.to_string())
+}
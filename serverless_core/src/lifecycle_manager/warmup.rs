@@ -0,0 +1,184 @@
+use crate::db::auth::AuthDBRepo;
+use crate::db::function::FunctionDBRepo;
+use crate::db::models::FunctionSettings;
+use crate::db::warmup::WarmupRepo;
+use crate::lifecycle_manager::invoke::{start_function, InvocationContext};
+use crate::utils::utils::{make_request, ProxyOptions};
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, Request};
+use redis::aio::MultiplexedConnection;
+use runtime::core::autoscaler::Autoscaler;
+use sea_orm::DatabaseConnection;
+use shared_utils::unix_now;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Request header marking the scheduler's keep-warm pings, so functions can answer them
+/// without doing any work
+pub const WARMUP_HEADER: &str = "x-invok-warmup";
+
+/// Server-wide settings the pings are proxied with
+#[derive(Debug, Clone, Copy)]
+pub struct WarmupOptions {
+    pub max_request_size: usize,
+    pub max_response_size: usize,
+    pub hide_internal_addresses: bool,
+}
+
+/// Pings every function whose warmup schedule fires, at the start of each minute, for as
+/// long as the server runs.
+///
+/// A ping is a `GET` to the function with `x-invok-warmup: 1`. It wakes a container
+/// when none is running and keeps a running one from idling out; pings don't count
+/// against the cold start budget. Functions of hibernated namespaces aren't pinged.
+/// Every controller runs the loop, but only the one claiming a minute in Redis pings.
+pub async fn run_warmup_loop(
+    conn: DatabaseConnection,
+    mut cache_conn: MultiplexedConnection,
+    autoscaler: Arc<Autoscaler>,
+    options: WarmupOptions,
+) {
+    let controller = Uuid::new_v4().to_string();
+    loop {
+        let now = unix_now();
+        tokio::time::sleep(Duration::from_secs(60 - now % 60)).await;
+        let minute = unix_now() / 60 * 60;
+
+        match WarmupRepo::claim_minute(&mut cache_conn, minute, &controller).await {
+            Ok(true) => {}
+            // Another controller pings this minute
+            Ok(false) => continue,
+            Err(e) => {
+                error!("Failed to claim the warmup pings of the minute: {}", e);
+                continue;
+            }
+        }
+
+        let functions = match FunctionDBRepo::find_with_settings(&conn).await {
+            Ok(functions) => functions,
+            Err(e) => {
+                error!("Failed to load warmup schedules: {}", e);
+                continue;
+            }
+        };
+        let hibernated: HashSet<Uuid> = match AuthDBRepo::find_hibernated(&conn).await {
+            Ok(users) => users.into_iter().map(|user| user.uuid).collect(),
+            Err(e) => {
+                error!("Failed to load hibernated namespaces: {}", e);
+                continue;
+            }
+        };
+        for function in functions {
+            if hibernated.contains(&function.uuid) {
+                continue;
+            }
+            let settings = FunctionSettings::from_model(&function);
+            if !is_due(&settings, minute) {
+                continue;
+            }
+            tokio::spawn(ping(
+                autoscaler.clone(),
+                function.name,
+                function.uuid,
+                settings,
+                options,
+            ));
+        }
+    }
+}
+
+/// Whether a function's warmup schedule fires in the minute starting at `minute`.
+///
+/// Schedules are checked on deploy; one that no longer parses never fires.
+fn is_due(settings: &FunctionSettings, minute: u64) -> bool {
+    settings
+        .warmup
+        .as_ref()
+        .and_then(|warmup| warmup.cron().ok())
+        .is_some_and(|schedule| schedule.matches(minute))
+}
+
+async fn ping(
+    autoscaler: Arc<Autoscaler>,
+    name: String,
+    user_uuid: Uuid,
+    settings: FunctionSettings,
+    options: WarmupOptions,
+) {
+    let (addr, _lease) = match start_function(autoscaler, &name, user_uuid, None).await {
+        Ok(started) => started,
+        Err(e) => {
+            warn!(
+                "Failed to start '{}' in namespace {} for a warmup ping: {:?}",
+                name, user_uuid, e
+            );
+            return;
+        }
+    };
+
+    let proxy_options = ProxyOptions {
+        limits: settings.body_limits(options.max_request_size, options.max_response_size),
+        compression: false,
+        timeout: settings.timeout(),
+        hide_internal_addresses: options.hide_internal_addresses,
+        raw_body: false,
+    };
+    let mut headers = HeaderMap::new();
+    InvocationContext::new(&headers, user_uuid, &name, proxy_options.timeout).apply(&mut headers);
+    headers.insert(WARMUP_HEADER, HeaderValue::from_static("1"));
+
+    let response = make_request(
+        &addr,
+        &name,
+        HashMap::new(),
+        headers,
+        Request::new(Body::empty()),
+        proxy_options,
+    )
+    .await;
+    if response.status().is_server_error() {
+        warn!(
+            "Warmup ping of '{}' in namespace {} answered {}",
+            name,
+            user_uuid,
+            response.status()
+        );
+    } else {
+        info!("Warmup ping of '{}' in namespace {}", name, user_uuid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::WarmupSchedule;
+
+    fn settings(schedule: Option<&str>) -> FunctionSettings {
+        FunctionSettings {
+            warmup: schedule.map(|schedule| WarmupSchedule {
+                schedule: schedule.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_due() {
+        // 2024-01-01T09:00:00Z, a Monday
+        let monday_nine = 1_704_099_600;
+        let every_five = settings(Some("*/5 9-17 * * 1-5"));
+        assert!(is_due(&every_five, monday_nine));
+        assert!(is_due(&every_five, monday_nine + 5 * 60));
+        assert!(!is_due(&every_five, monday_nine + 60));
+        // Outside the hours and on the weekend
+        assert!(!is_due(&every_five, monday_nine - 60 * 60));
+        assert!(!is_due(&every_five, monday_nine + 5 * 24 * 60 * 60));
+
+        // Without a schedule, or with one that no longer parses, nothing fires
+        assert!(!is_due(&settings(None), monday_nine));
+        assert!(!is_due(&settings(Some("*/5 9-17")), monday_nine));
+    }
+}
@@ -1,7 +1,35 @@
 #[tokio::main]
 async fn main() {
-    if let Err(err) = serverless_core::start_server().await {
-        eprintln!("Error starting server: {}", err);
-        std::process::exit(1);
+    match std::env::args().nth(1).as_deref() {
+        None => {
+            if let Err(err) = serverless_core::start_server().await {
+                eprintln!("Error starting server: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some("doctor") => {
+            if !serverless_core::run_doctor().await {
+                std::process::exit(1);
+            }
+        }
+        Some("builder") => {
+            if let Err(err) = serverless_core::start_builder().await {
+                eprintln!("Error starting builder: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some("egress-proxy") => {
+            if let Err(err) = serverless_core::start_egress_proxy().await {
+                eprintln!("Error starting egress proxy: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(other) => {
+            eprintln!(
+                "Unknown command '{}'. Usage: serverless-core [doctor|builder|egress-proxy]",
+                other
+            );
+            std::process::exit(2);
+        }
     }
 }
@@ -0,0 +1,77 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use shared_utils::unix_now;
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Write files into a gzipped tarball, in order
+pub fn pack(files: impl IntoIterator<Item = (String, Vec<u8>)>) -> io::Result<Vec<u8>> {
+    let mtime = unix_now();
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content.as_slice())?;
+    }
+    builder.into_inner()?.finish()
+}
+
+/// [`pack`] on the blocking thread pool, so packing large archives doesn't hold up the
+/// executor's other requests
+pub async fn pack_blocking(files: Vec<(String, Vec<u8>)>) -> io::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || pack(files))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Read every regular file of a gzipped tarball into memory, by path.
+///
+/// Fails once the files add up to more than `max_size` bytes, so a small archive can't
+/// expand into something that exhausts memory.
+pub fn unpack(archive: &[u8], max_size: usize) -> Result<HashMap<String, Vec<u8>>, String> {
+    let mut tarball = tar::Archive::new(GzDecoder::new(archive));
+    let mut files = HashMap::new();
+    let mut total_size = 0;
+
+    for entry in tarball.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .into_owned();
+
+        let mut content = Vec::new();
+        entry
+            .by_ref()
+            .take((max_size - total_size) as u64 + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| e.to_string())?;
+        total_size += content.len();
+        if total_size > max_size {
+            return Err(format!("unpacks to more than {} bytes", max_size));
+        }
+        files.insert(path, content);
+    }
+
+    Ok(files)
+}
+
+/// [`unpack`] on the blocking thread pool, so unpacking large archives doesn't hold up
+/// the executor's other requests
+pub async fn unpack_blocking(
+    archive: Vec<u8>,
+    max_size: usize,
+) -> Result<HashMap<String, Vec<u8>>, String> {
+    tokio::task::spawn_blocking(move || unpack(&archive, max_size))
+        .await
+        .map_err(|e| e.to_string())?
+}
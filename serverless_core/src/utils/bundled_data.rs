@@ -0,0 +1,36 @@
+use shared_utils::BUNDLED_DATA_DIR;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Prepares the [`BUNDLED_DATA_DIR`] of the function files at `path` for the image: an
+/// empty one is created when the archive has none, as the Dockerfiles copy it either
+/// way, and its files are made read-only, which the image keeps.
+///
+/// Errors of kind `InvalidInput` are mistakes of the function's.
+pub fn prepare_bundled_data(path: &Path) -> io::Result<()> {
+    let data_dir = path.join(BUNDLED_DATA_DIR);
+    match fs::symlink_metadata(&data_dir) {
+        Ok(metadata) if metadata.is_dir() => make_read_only(&data_dir),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' must be a directory", BUNDLED_DATA_DIR),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir_all(&data_dir),
+        Err(e) => Err(e),
+    }
+}
+
+fn make_read_only(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            make_read_only(&entry.path())?;
+        } else if file_type.is_file() {
+            fs::set_permissions(entry.path(), fs::Permissions::from_mode(0o444))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use flate2::read::{GzDecoder, GzEncoder};
+use flate2::Compression;
+use hyper::body::Bytes;
+use std::io::{self, Read};
+
+/// Responses smaller than this are sent as-is; compressing them rarely pays off
+pub const MIN_COMPRESSIBLE_SIZE: usize = 1024;
+
+/// Brotli quality used for responses (0-11). Mid-range keeps latency low for large bodies.
+const BROTLI_QUALITY: u32 = 5;
+
+/// Brotli window size (log2)
+const BROTLI_WINDOW: u32 = 22;
+
+/// Buffer size for the brotli encoder/decoder
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Content encodings the proxy can produce and decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    /// Value used in `Content-Encoding`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+        }
+    }
+
+    /// Parse a `Content-Encoding` value. Returns `None` for identity and encodings the
+    /// proxy doesn't handle.
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "br" => Some(Encoding::Brotli),
+            _ => None,
+        }
+    }
+}
+
+/// Why decoding a compressed body failed
+#[derive(Debug)]
+pub enum DecompressError {
+    /// The decoded body grew past the limit
+    TooLarge,
+    /// The body is not valid for its encoding
+    Invalid(io::Error),
+}
+
+/// Pick the encoding to answer with from the client's `Accept-Encoding`.
+///
+/// Brotli wins over gzip when both are accepted with the same weight. Encodings with
+/// `q=0` are refused, and `*` stands for anything not listed explicitly.
+pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut gzip = None;
+    let mut brotli = None;
+    let mut wildcard = None;
+
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match name.as_str() {
+            "gzip" | "x-gzip" => gzip = Some(quality),
+            "br" => brotli = Some(quality),
+            "*" => wildcard = Some(quality),
+            _ => {}
+        }
+    }
+
+    let gzip = gzip.or(wildcard).unwrap_or(0.0);
+    let brotli = brotli.or(wildcard).unwrap_or(0.0);
+    if brotli > 0.0 && brotli >= gzip {
+        Some(Encoding::Brotli)
+    } else if gzip > 0.0 {
+        Some(Encoding::Gzip)
+    } else {
+        None
+    }
+}
+
+/// Whether a response of this content type is worth compressing.
+///
+/// Text formats compress well; images, archives and media are already compressed.
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/x-ndjson"
+                | "image/svg+xml"
+        )
+}
+
+/// Compress a body with the given encoding
+pub fn compress(body: &[u8], encoding: Encoding) -> io::Result<Vec<u8>> {
+    let mut compressed = Vec::new();
+    match encoding {
+        Encoding::Gzip => {
+            GzEncoder::new(body, Compression::default()).read_to_end(&mut compressed)?;
+        }
+        Encoding::Brotli => {
+            brotli::CompressorReader::new(body, BROTLI_BUFFER_SIZE, BROTLI_QUALITY, BROTLI_WINDOW)
+                .read_to_end(&mut compressed)?;
+        }
+    }
+    Ok(compressed)
+}
+
+/// [`compress`] on the blocking thread pool, so large bodies don't hold up the
+/// executor's other requests
+pub async fn compress_blocking(body: Bytes, encoding: Encoding) -> io::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || compress(&body, encoding))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Decode a compressed body, failing once more than `max_bytes` have been produced.
+///
+/// The limit applies to the decoded size so small compressed payloads can't expand into
+/// something larger than the function accepts.
+pub fn decompress(
+    body: &[u8],
+    encoding: Encoding,
+    max_bytes: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let decoder: Box<dyn Read + '_> = match encoding {
+        Encoding::Gzip => Box::new(GzDecoder::new(body)),
+        Encoding::Brotli => Box::new(brotli::Decompressor::new(body, BROTLI_BUFFER_SIZE)),
+    };
+
+    let mut decoded = Vec::new();
+    decoder
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(DecompressError::Invalid)?;
+    if decoded.len() > max_bytes {
+        return Err(DecompressError::TooLarge);
+    }
+    Ok(decoded)
+}
+
+/// [`decompress`] on the blocking thread pool, so large bodies don't hold up the
+/// executor's other requests
+pub async fn decompress_blocking(
+    body: Bytes,
+    encoding: Encoding,
+    max_bytes: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    tokio::task::spawn_blocking(move || decompress(&body, encoding, max_bytes))
+        .await
+        .map_err(|e| DecompressError::Invalid(io::Error::other(e)))?
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+/// A five-field cron expression: minute, hour, day of month, month and day of week, in UTC.
+///
+/// Fields take `*`, numbers, ranges (`9-17`), steps (`*/5`, `9-17/2`) and lists of those
+/// (`0,30`); Sunday is 0 or 7. As in cron, when both day fields are restricted, days
+/// matching either one match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Neither day field is `*`, so either may match
+    either_day: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "'{}' must have 5 fields: minute hour day-of-month month day-of-week",
+                expression
+            ));
+        };
+
+        let mut weekday_bits = parse_field(weekdays, "day-of-week", 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, "minute", 0, 59)?,
+            hours: parse_field(hours, "hour", 0, 23)?,
+            days: parse_field(days, "day-of-month", 1, 31)?,
+            months: parse_field(months, "month", 1, 12)?,
+            weekdays: weekday_bits,
+            either_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Whether the schedule fires during the minute holding `unix_secs`
+    pub fn matches(&self, unix_secs: u64) -> bool {
+        let minutes = unix_secs / 60;
+        let days = unix_secs / (24 * 60 * 60);
+        let (month, day) = month_and_day(days);
+        // The Unix epoch was a Thursday
+        let weekday = (days + 4) % 7;
+
+        let day_matches = if self.either_day {
+            is_set(self.days, day) || is_set(self.weekdays, weekday)
+        } else {
+            is_set(self.days, day) && is_set(self.weekdays, weekday)
+        };
+        is_set(self.minutes, minutes % 60)
+            && is_set(self.hours, (minutes / 60) % 24)
+            && is_set(self.months, month)
+            && day_matches
+    }
+}
+
+fn is_set(bits: u64, value: u64) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// The values a field selects, as a bitset
+fn parse_field(field: &str, name: &str, min: u64, max: u64) -> Result<u64, String> {
+    let invalid = || format!("invalid {} field '{}'", name, field);
+    let number = |value: &str| value.parse::<u64>().map_err(|_| invalid());
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, number(step)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/15` runs from 5 to the end of the range
+            None if part.contains('/') => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(format!(
+                "{} field '{}' must stay within {}-{}",
+                name, field, min, max
+            ));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// Month (1-12) and day of month of a day counted from the Unix epoch
+fn month_and_day(days: u64) -> (u64, u64) {
+    // Howard Hinnant's civil_from_days, counting from 0000-03-01 so leap days come last
+    let z = days + 719_468;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    (month, day)
+}
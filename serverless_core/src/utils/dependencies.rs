@@ -0,0 +1,526 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Dependencies of a deployed function, read from its `go.mod`, or its `package.json`
+/// and `package-lock.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DependencyInventory {
+    /// Language version the function declares: the `go` directive of `go.mod`, or the
+    /// lowest version `engines.node` of `package.json` allows
+    #[serde(default)]
+    pub runtime_version: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A module or package and the version the function requires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+}
+
+impl DependencyInventory {
+    /// Reads the manifest of a function's runtime from the root of its files. The
+    /// versions npm installed are taken from `package-lock.json` when there is one, the
+    /// ranges of `package.json` otherwise.
+    ///
+    /// Returns `None` for runtimes without a supported manifest or a bundle without one,
+    /// and an `InvalidInput` error for a `package.json` or `package-lock.json` that
+    /// doesn't parse.
+    pub fn read(path: &Path, runtime: &str) -> io::Result<Option<Self>> {
+        let file = match runtime {
+            "go" => "go.mod",
+            "nodejs" => "package.json",
+            _ => return Ok(None),
+        };
+        let Some(content) = read_optional(&path.join(file))? else {
+            return Ok(None);
+        };
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let inventory = match runtime {
+            "go" => Self::from_go_mod(&content),
+            _ => {
+                let mut inventory = Self::from_package_json(&content).map_err(invalid)?;
+                if let Some(lock) = read_optional(&path.join("package-lock.json"))? {
+                    inventory.dependencies = Self::from_package_lock(&lock).map_err(invalid)?;
+                }
+                inventory
+            }
+        };
+        Ok(Some(inventory))
+    }
+
+    /// Parses the `go` directive and the `require` directives of a `go.mod`, indirect
+    /// requirements included since they're compiled in as well
+    pub fn from_go_mod(content: &str) -> Self {
+        let mut inventory = Self::default();
+        let mut in_require_block = false;
+        for line in content.lines() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if in_require_block {
+                if line == ")" {
+                    in_require_block = false;
+                } else {
+                    inventory.push_requirement(line);
+                }
+                continue;
+            }
+            if let Some(version) = line.strip_prefix("go ") {
+                inventory.runtime_version = Some(version.trim().to_string());
+            } else if let Some(rest) = line.strip_prefix("require") {
+                let rest = rest.trim();
+                if rest == "(" {
+                    in_require_block = true;
+                } else {
+                    inventory.push_requirement(rest);
+                }
+            }
+        }
+        inventory
+    }
+
+    /// Parses `engines.node` and `dependencies` of a `package.json`. Development
+    /// dependencies are left out, they don't ship in the function's image.
+    pub fn from_package_json(content: &str) -> Result<Self, String> {
+        let package: serde_json::Value =
+            serde_json::from_str(content).map_err(|e| format!("Invalid package.json: {}", e))?;
+        let runtime_version = package
+            .pointer("/engines/node")
+            .and_then(|range| range.as_str())
+            .and_then(lowest_version)
+            .map(|version| version.to_string());
+        let dependencies = package
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.as_object())
+            .map(|dependencies| {
+                dependencies
+                    .iter()
+                    .filter_map(|(name, version)| {
+                        Some(Dependency {
+                            name: name.clone(),
+                            version: version.as_str()?.to_string(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            runtime_version,
+            dependencies,
+        })
+    }
+
+    /// Parses the installed packages of a `package-lock.json`, transitive ones included
+    /// since they ship as well, and development ones left out. Lockfile version 1 lists
+    /// them under `dependencies`, later versions under `packages`.
+    pub fn from_package_lock(content: &str) -> Result<Vec<Dependency>, String> {
+        let lock: serde_json::Value = serde_json::from_str(content)
+            .map_err(|e| format!("Invalid package-lock.json: {}", e))?;
+        let mut dependencies = Vec::new();
+        if let Some(packages) = lock
+            .get("packages")
+            .and_then(|packages| packages.as_object())
+        {
+            for (location, package) in packages {
+                // Packages are keyed by where they're installed, e.g.
+                // `node_modules/a/node_modules/@scope/b`; the root package is keyed ""
+                let Some((_, name)) = location.rsplit_once("node_modules/") else {
+                    continue;
+                };
+                push_locked(&mut dependencies, name, package);
+            }
+        } else if let Some(locked) = lock.get("dependencies") {
+            let mut pending = vec![locked];
+            while let Some(locked) = pending.pop() {
+                let Some(locked) = locked.as_object() else {
+                    continue;
+                };
+                for (name, package) in locked {
+                    push_locked(&mut dependencies, name, package);
+                    pending.extend(package.get("dependencies"));
+                }
+            }
+        }
+        dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        dependencies.dedup();
+        Ok(dependencies)
+    }
+
+    fn push_requirement(&mut self, requirement: &str) {
+        let mut parts = requirement.split_whitespace();
+        if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+            self.dependencies.push(Dependency {
+                name: name.to_string(),
+                version: version.to_string(),
+            });
+        }
+    }
+}
+
+/// Adds a locked package unless it is a development dependency or has no version
+fn push_locked(dependencies: &mut Vec<Dependency>, name: &str, package: &serde_json::Value) {
+    if package.get("dev").and_then(|dev| dev.as_bool()) == Some(true) {
+        return;
+    }
+    if let Some(version) = package.get("version").and_then(|version| version.as_str()) {
+        dependencies.push(Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+        });
+    }
+}
+
+/// Contents of a file, `None` when it doesn't exist
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Known problems of runtimes and dependencies, maintained by the platform's operators
+/// in the file or URL `function.dependency_advisories` points at
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdvisoryDatabase {
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeDeprecation>,
+    #[serde(default)]
+    pub advisories: Vec<Advisory>,
+}
+
+/// A runtime, or its versions below `below`, that functions should move off
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuntimeDeprecation {
+    pub runtime: String,
+    /// Versions below this one are deprecated; the whole runtime when unset
+    #[serde(default)]
+    pub below: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A vulnerability affecting the versions of a package below `fixed_in`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Advisory {
+    /// E.g. a CVE or GHSA identifier
+    pub id: String,
+    /// Runtime whose manifests name the package: `go` or `nodejs`
+    pub runtime: String,
+    pub package: String,
+    pub fixed_in: String,
+    #[serde(default)]
+    pub severity: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+}
+
+/// A dependency a function requires at a vulnerable version
+#[derive(Debug, Clone, Serialize)]
+pub struct VulnerableDependency {
+    pub package: String,
+    pub version: String,
+    pub advisory: String,
+    pub fixed_in: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+/// What the advisories say about a function
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DependencyFindings {
+    /// Why its runtime is deprecated, if it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated_runtime: Option<String>,
+    pub vulnerable: Vec<VulnerableDependency>,
+}
+
+impl DependencyFindings {
+    pub fn is_empty(&self) -> bool {
+        self.deprecated_runtime.is_none() && self.vulnerable.is_empty()
+    }
+
+    /// One line per finding, e.g. for the message of a deploy
+    pub fn warnings(&self) -> Vec<String> {
+        self.deprecated_runtime
+            .iter()
+            .map(|message| format!("Warning: deprecated runtime: {}", message))
+            .chain(self.vulnerable.iter().map(|dependency| {
+                format!(
+                    "Warning: {} {} is affected by {}, fixed in {}",
+                    dependency.package,
+                    dependency.version,
+                    dependency.advisory,
+                    dependency.fixed_in
+                )
+            }))
+            .collect()
+    }
+}
+
+impl AdvisoryDatabase {
+    /// Checks a function's runtime and inventory against the advisories. Versions that
+    /// aren't numbers (tags, paths, URLs) never match.
+    pub fn check(&self, runtime: &str, inventory: &DependencyInventory) -> DependencyFindings {
+        let deprecated_runtime = self
+            .runtimes
+            .iter()
+            .find(|deprecation| {
+                deprecation.runtime == runtime
+                    && match &deprecation.below {
+                        Some(below) => inventory
+                            .runtime_version
+                            .as_deref()
+                            .is_some_and(|version| is_below(version, below)),
+                        None => true,
+                    }
+            })
+            .map(|deprecation| {
+                deprecation.message.clone().unwrap_or_else(|| {
+                    match (&deprecation.below, &inventory.runtime_version) {
+                        (Some(below), Some(version)) => {
+                            format!(
+                                "{} {} is deprecated, upgrade to {} or later",
+                                runtime, version, below
+                            )
+                        }
+                        _ => format!("the {} runtime is deprecated", runtime),
+                    }
+                })
+            });
+
+        let vulnerable = inventory
+            .dependencies
+            .iter()
+            .flat_map(|dependency| {
+                self.advisories
+                    .iter()
+                    .filter(move |advisory| {
+                        advisory.runtime == runtime
+                            && advisory.package == dependency.name
+                            && is_below(&dependency.version, &advisory.fixed_in)
+                    })
+                    .map(move |advisory| VulnerableDependency {
+                        package: dependency.name.clone(),
+                        version: dependency.version.clone(),
+                        advisory: advisory.id.clone(),
+                        fixed_in: advisory.fixed_in.clone(),
+                        severity: advisory.severity.clone(),
+                        summary: advisory.summary.clone(),
+                    })
+            })
+            .collect();
+
+        DependencyFindings {
+            deprecated_runtime,
+            vulnerable,
+        }
+    }
+}
+
+/// Whether `version` (a version or the lowest one a range allows) is below `bound`
+fn is_below(version: &str, bound: &str) -> bool {
+    match (
+        lowest_version(version).and_then(numeric_parts),
+        numeric_parts(bound.trim_start_matches('v')),
+    ) {
+        (Some(version), Some(bound)) => compare_parts(&version, &bound) == Ordering::Less,
+        _ => false,
+    }
+}
+
+/// The version a requirement starts at: `v1.2.3` and `1.2.3` are themselves, `^1.2`,
+/// `~1.2` and `>=1.2` allow nothing older than `1.2`. Ranges joined with `||` or
+/// spaces are taken by their first part.
+fn lowest_version(requirement: &str) -> Option<&str> {
+    let first = requirement.split("||").next()?.split_whitespace().next()?;
+    let version = first.trim_start_matches(['^', '~', '>', '=', 'v']);
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some(version)
+}
+
+/// The numbers of `1.2.3`, ignoring pre-release and build suffixes and `x` wildcards
+fn numeric_parts(version: &str) -> Option<Vec<u64>> {
+    let core = version.split(['-', '+']).next()?;
+    let parts: Vec<u64> = core
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (!parts.is_empty()).then_some(parts)
+}
+
+fn compare_parts(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            a.get(i)
+                .copied()
+                .unwrap_or(0)
+                .cmp(&b.get(i).copied().unwrap_or(0))
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(name: &str, version: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_from_go_mod() {
+        let inventory = DependencyInventory::from_go_mod(
+            r#"module example.com/hello
+
+go 1.21
+
+require github.com/a/b v1.2.3 // pinned
+
+require (
+	github.com/c/d v0.4.0
+	golang.org/x/net v0.17.0 // indirect
+)
+"#,
+        );
+        assert_eq!(inventory.runtime_version.as_deref(), Some("1.21"));
+        assert_eq!(
+            inventory.dependencies,
+            vec![
+                dependency("github.com/a/b", "v1.2.3"),
+                dependency("github.com/c/d", "v0.4.0"),
+                dependency("golang.org/x/net", "v0.17.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_package_json() {
+        let inventory = DependencyInventory::from_package_json(
+            r#"{
+                "engines": { "node": ">=18.2 <21" },
+                "dependencies": { "express": "^4.18.2", "local": 3 },
+                "devDependencies": { "jest": "^29.0.0" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(inventory.runtime_version.as_deref(), Some("18.2"));
+        assert_eq!(
+            inventory.dependencies,
+            vec![dependency("express", "^4.18.2")]
+        );
+        assert!(DependencyInventory::from_package_json("{").is_err());
+    }
+
+    #[test]
+    fn test_from_package_lock() {
+        let v3 = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "fn", "version": "1.0.0" },
+                "node_modules/express": { "version": "4.18.2" },
+                "node_modules/express/node_modules/@scope/qs": { "version": "6.11.0" },
+                "node_modules/jest": { "version": "29.7.0", "dev": true }
+            }
+        }"#;
+        assert_eq!(
+            DependencyInventory::from_package_lock(v3).unwrap(),
+            vec![
+                dependency("@scope/qs", "6.11.0"),
+                dependency("express", "4.18.2")
+            ]
+        );
+
+        let v1 = r#"{
+            "lockfileVersion": 1,
+            "dependencies": {
+                "express": {
+                    "version": "4.17.1",
+                    "dependencies": { "qs": { "version": "6.7.0" } }
+                },
+                "jest": { "version": "29.7.0", "dev": true }
+            }
+        }"#;
+        assert_eq!(
+            DependencyInventory::from_package_lock(v1).unwrap(),
+            vec![dependency("express", "4.17.1"), dependency("qs", "6.7.0")]
+        );
+        assert!(DependencyInventory::from_package_lock("[").is_err());
+    }
+
+    #[test]
+    fn test_read_prefers_package_lock() {
+        let dir = std::env::temp_dir().join(format!("invok-deps-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("package.json"),
+            r#"{ "dependencies": { "express": "^4.0.0" } }"#,
+        )
+        .unwrap();
+        let ranges = DependencyInventory::read(&dir, "nodejs").unwrap().unwrap();
+        assert_eq!(ranges.dependencies, vec![dependency("express", "^4.0.0")]);
+
+        fs::write(
+            dir.join("package-lock.json"),
+            r#"{ "packages": { "node_modules/express": { "version": "4.18.2" } } }"#,
+        )
+        .unwrap();
+        let locked = DependencyInventory::read(&dir, "nodejs").unwrap().unwrap();
+        assert_eq!(locked.dependencies, vec![dependency("express", "4.18.2")]);
+
+        assert!(DependencyInventory::read(&dir, "go").unwrap().is_none());
+        assert!(DependencyInventory::read(&dir, "rust").unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(lowest_version("^1.2.3"), Some("1.2.3"));
+        assert_eq!(lowest_version(">=2.0 <3"), Some("2.0"));
+        assert_eq!(lowest_version("~1.4 || ^2"), Some("1.4"));
+        assert_eq!(lowest_version("latest"), None);
+        assert!(is_below("v1.2.3", "1.10.0"));
+        assert!(is_below("^4.17.1", "4.17.3"));
+        assert!(!is_below("4.17.3", "4.17.3"));
+        assert!(!is_below("1.2.3-beta", "1.2.3"));
+        assert!(!is_below("github:a/b", "1.0.0"));
+    }
+
+    #[test]
+    fn test_check() {
+        let database: AdvisoryDatabase = serde_json::from_str(
+            r#"{
+                "runtimes": [{ "runtime": "go", "below": "1.21" }],
+                "advisories": [{
+                    "id": "GHSA-1", "runtime": "go", "package": "golang.org/x/net",
+                    "fixed_in": "0.23.0"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let inventory = DependencyInventory {
+            runtime_version: Some("1.20".to_string()),
+            dependencies: vec![dependency("golang.org/x/net", "v0.17.0")],
+        };
+        let findings = database.check("go", &inventory);
+        assert_eq!(
+            findings.deprecated_runtime.as_deref(),
+            Some("go 1.20 is deprecated, upgrade to 1.21 or later")
+        );
+        assert_eq!(findings.vulnerable.len(), 1);
+        assert_eq!(findings.warnings().len(), 2);
+        assert!(database.check("nodejs", &inventory).is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Most entries an allowlist may hold
+const MAX_ENTRIES: usize = 256;
+
+/// Destinations a function may reach through its namespace's egress proxy.
+///
+/// Entries are `host`, `*.domain` (any subdomain, not the domain itself) or either of
+/// those with a `:port`; without a port every port is allowed. An empty list allows
+/// nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EgressAllowlist {
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl EgressAllowlist {
+    /// Check that every entry parses, so mistakes are reported instead of silently
+    /// blocking traffic
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow.len() > MAX_ENTRIES {
+            return Err(format!("at most {} entries are allowed", MAX_ENTRIES));
+        }
+        for entry in &self.allow {
+            parse_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a connection to `host` on `port` is allowed. Entries that fail to parse
+    /// are skipped; updates reject them through [`EgressAllowlist::validate`].
+    pub fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allow
+            .iter()
+            .filter_map(|entry| parse_entry(entry).ok())
+            .any(|(pattern, allowed_port)| {
+                allowed_port.unwrap_or(port) == port
+                    && match pattern.strip_prefix("*.") {
+                        Some(domain) => host
+                            .strip_suffix(domain)
+                            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                        None => host == pattern,
+                    }
+            })
+    }
+}
+
+/// Whether an address is on the public internet. The proxy refuses everything else, so
+/// allowlisted names can't be pointed at the host, the container network or the cloud
+/// metadata service.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                // IETF protocol assignments, e.g. DS-Lite and NAT64 discovery
+                || (a == 192 && b == 0 && ip.octets()[2] == 0)
+                // Benchmarking
+                || (a == 198 && (b == 18 || b == 19))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let segments = ip.segments();
+                let first = segments[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local (fc00::/7) and link-local (fe80::/10)
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+                    // NAT64 (64:ff9b::/96 and 64:ff9b:1::/48) and 6to4 (2002::/16) carry
+                    // an IPv4 address a gateway translates to, which may be private
+                    || (first == 0x64 && segments[1] == 0xff9b)
+                    || first == 0x2002)
+            }
+        },
+    }
+}
+
+/// Splits an entry into its lowercase host pattern and port, if any
+fn parse_entry(entry: &str) -> Result<(String, Option<u16>), String> {
+    let entry = entry.trim().to_ascii_lowercase();
+    let (host, port) = match entry.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port > 0)
+                .ok_or_else(|| format!("invalid port in '{}'", entry))?;
+            (host.to_string(), Some(port))
+        }
+        None => (entry.clone(), None),
+    };
+
+    let name = host.strip_prefix("*.").unwrap_or(&host);
+    let valid = !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        });
+    if !valid {
+        return Err(format!("invalid host in '{}'", entry));
+    }
+    Ok((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        let cases = [
+            ("8.8.8.8", true),
+            ("1.1.1.1", true),
+            ("2606:4700:4700::1111", true),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("127.0.0.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("100.127.255.255", false),
+            ("100.128.0.1", true),
+            ("192.0.0.8", false),
+            ("192.0.1.1", true),
+            ("198.18.0.1", false),
+            ("198.19.255.255", false),
+            ("198.20.0.1", true),
+            ("192.0.2.1", false),
+            ("0.1.2.3", false),
+            ("240.0.0.1", false),
+            ("255.255.255.255", false),
+            ("224.0.0.1", false),
+            ("::1", false),
+            ("::", false),
+            ("::ffff:10.0.0.1", false),
+            ("::ffff:8.8.8.8", true),
+            ("fd00:ec2::254", false),
+            ("fe80::1", false),
+            ("ff02::1", false),
+            ("64:ff9b::a00:1", false),
+            ("64:ff9b::808:808", false),
+            ("64:ff9b:1::1", false),
+            ("2002:a00:1::1", false),
+        ];
+        for (ip, public) in cases {
+            assert_eq!(is_public(ip.parse().unwrap()), public, "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        let allowlist = EgressAllowlist {
+            allow: vec![
+                "api.example.com:443".to_string(),
+                "*.example.org".to_string(),
+            ],
+        };
+        assert!(allowlist.validate().is_ok());
+        assert!(allowlist.allows("API.example.com.", 443));
+        assert!(!allowlist.allows("api.example.com", 80));
+        assert!(allowlist.allows("a.b.example.org", 8080));
+        assert!(!allowlist.allows("example.org", 443));
+        assert!(!allowlist.allows("evilexample.org", 443));
+    }
+}
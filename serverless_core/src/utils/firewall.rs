@@ -0,0 +1,168 @@
+use axum::http::{Method, StatusCode};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Network ACLs and request filters checked before a function's container is woken
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FirewallRules {
+    /// Only clients in these addresses/CIDRs may invoke the function (empty allows everyone)
+    #[serde(default)]
+    pub allow_ips: Vec<String>,
+    /// Clients in these addresses/CIDRs are always rejected, even when allowed above
+    #[serde(default)]
+    pub deny_ips: Vec<String>,
+    /// Requests whose path and query match any of these regexes are rejected
+    #[serde(default)]
+    pub deny_paths: Vec<String>,
+    /// HTTP methods the function accepts (empty allows every method)
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+/// Why a request was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub status: StatusCode,
+    pub reason: String,
+}
+
+impl FirewallRules {
+    /// Check that every CIDR, regex and method parses, so mistakes fail the deploy
+    /// instead of silently letting traffic through
+    pub fn validate(&self) -> Result<(), String> {
+        for entry in self.allow_ips.iter().chain(&self.deny_ips) {
+            Cidr::from_str(entry)?;
+        }
+        for pattern in &self.deny_paths {
+            Regex::new(pattern)
+                .map_err(|e| format!("invalid deny_paths regex '{}': {}", pattern, e))?;
+        }
+        for method in &self.methods {
+            Method::from_str(&method.to_ascii_uppercase())
+                .map_err(|_| format!("invalid method '{}'", method))?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate the rules for one request.
+    ///
+    /// Deny lists win over the allow list. A client address that can't be determined only
+    /// passes when no allow list is set. Entries that fail to parse are skipped; deploys
+    /// reject them through [`FirewallRules::validate`].
+    pub fn check(
+        &self,
+        client_ip: Option<IpAddr>,
+        method: &Method,
+        path_and_query: &str,
+    ) -> Result<(), Rejection> {
+        if !self.methods.is_empty()
+            && !self
+                .methods
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(method.as_str()))
+        {
+            return Err(Rejection {
+                status: StatusCode::METHOD_NOT_ALLOWED,
+                reason: format!("method {} is not allowed", method),
+            });
+        }
+
+        self.check_ip(client_ip)?;
+
+        let denied_path = self
+            .deny_paths
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .any(|regex| regex.is_match(path_and_query));
+        if denied_path {
+            return Err(forbidden(format!("path {} is denied", path_and_query)));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate the address lists alone, e.g. for connections to TCP and UDP services,
+    /// which have no method or path
+    pub fn check_ip(&self, client_ip: Option<IpAddr>) -> Result<(), Rejection> {
+        let in_list = |list: &[String], ip: IpAddr| {
+            list.iter()
+                .filter_map(|entry| Cidr::from_str(entry).ok())
+                .any(|cidr| cidr.contains(ip))
+        };
+        match client_ip {
+            Some(ip) if in_list(&self.deny_ips, ip) => {
+                Err(forbidden(format!("client {} is denied", ip)))
+            }
+            Some(ip) if !self.allow_ips.is_empty() && !in_list(&self.allow_ips, ip) => {
+                Err(forbidden(format!("client {} is not allowed", ip)))
+            }
+            None if !self.allow_ips.is_empty() => {
+                Err(forbidden("client address unknown".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn forbidden(reason: String) -> Rejection {
+    Rejection {
+        status: StatusCode::FORBIDDEN,
+        reason,
+    }
+}
+
+/// An address block such as `10.0.0.0/8` or `2001:db8::/32`. A bare address matches
+/// only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            // IPv4 clients reaching a dual-stack listener show up as mapped addresses
+            (IpAddr::V4(_), IpAddr::V6(ip)) => ip
+                .to_ipv4_mapped()
+                .is_some_and(|ip| self.contains(IpAddr::V4(ip))),
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let network: IpAddr = address
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid IP address '{}'", value))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("invalid CIDR prefix in '{}'", value))?,
+            None => max_prefix,
+        };
+        Ok(Self { network, prefix })
+    }
+}
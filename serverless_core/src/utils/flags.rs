@@ -0,0 +1,311 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use db_entities::auth::Model as AuthModel;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Prefix of the request headers carrying flag values, followed by the flag's key
+pub const FLAG_HEADER_PREFIX: &str = "x-invok-flag-";
+
+/// Request header a caller sets to keep its rollout buckets across requests, e.g. to a
+/// user id; the client address is used when it is missing
+pub const ROLLOUT_KEY_HEADER: &str = "x-invok-rollout-key";
+
+/// Most flags a namespace may have
+const MAX_FLAGS: usize = 64;
+
+/// Longest flag key
+const MAX_KEY_LENGTH: usize = 64;
+
+/// A namespace's feature flags, evaluated on every invocation of its functions and sent
+/// to them as `x-invok-flag-<key>` headers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureFlags {
+    #[serde(default)]
+    pub flags: BTreeMap<String, FeatureFlag>,
+}
+
+/// A flag's value and the share of requests that get it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureFlag {
+    /// Sent to requests inside the rollout
+    pub value: String,
+    /// Percent of requests inside the rollout, 100 for all of them
+    #[serde(default = "full_rollout")]
+    pub rollout: u8,
+    /// Sent to requests outside the rollout; they get no header when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+fn full_rollout() -> u8 {
+    100
+}
+
+impl FeatureFlags {
+    /// Read the flags stored on a user record, falling back to none
+    pub fn from_model(user: &AuthModel) -> Self {
+        user.feature_flags
+            .clone()
+            .and_then(|flags| serde_json::from_value(flags).ok())
+            .unwrap_or_default()
+    }
+
+    /// Check the flags before they are stored, so each one becomes a valid header
+    pub fn validate(&self) -> Result<(), String> {
+        if self.flags.len() > MAX_FLAGS {
+            return Err(format!("at most {} flags are allowed", MAX_FLAGS));
+        }
+        for (key, flag) in &self.flags {
+            validate_key(key)?;
+            flag.validate(key)?;
+        }
+        Ok(())
+    }
+
+    /// Drops flag headers the caller sent and adds the value of every flag for this
+    /// request. A request's bucket depends on the flag and `rollout_key`, so the same key
+    /// gets the same values until a rollout changes, and raising a rollout only adds
+    /// requests to it.
+    pub fn apply(&self, headers: &mut HeaderMap, rollout_key: &str) {
+        let spoofed: Vec<HeaderName> = headers
+            .keys()
+            .filter(|name| name.as_str().starts_with(FLAG_HEADER_PREFIX))
+            .cloned()
+            .collect();
+        for name in spoofed {
+            headers.remove(name);
+        }
+
+        for (key, flag) in &self.flags {
+            let value = if bucket(key, rollout_key) < flag.rollout {
+                &flag.value
+            } else {
+                match &flag.default {
+                    Some(default) => default,
+                    None => continue,
+                }
+            };
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_str(&format!("{FLAG_HEADER_PREFIX}{key}")),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+impl FeatureFlag {
+    /// Check the rollout and that the values can be sent as header values
+    pub fn validate(&self, key: &str) -> Result<(), String> {
+        if self.rollout > 100 {
+            return Err(format!(
+                "rollout of '{}' must be between 0 and 100, got {}",
+                key, self.rollout
+            ));
+        }
+        for value in std::iter::once(&self.value).chain(&self.default) {
+            HeaderValue::from_str(value)
+                .map_err(|_| format!("value '{}' of '{}' can't be sent as a header", value, key))?;
+        }
+        Ok(())
+    }
+}
+
+/// What a request's rollout buckets derive from: its rollout key header, else the client
+/// address, else a random key so the request still lands in some bucket
+pub fn rollout_key(headers: &HeaderMap, client_ip: Option<IpAddr>) -> String {
+    headers
+        .get(ROLLOUT_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .or_else(|| client_ip.map(|ip| ip.to_string()))
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Check that a key can end a header name: lowercase letters, digits, `-` and `_`
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(format!(
+            "flag keys must be 1 to {} characters long",
+            MAX_KEY_LENGTH
+        ));
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid flag key '{}': use lowercase letters, digits, '-' and '_'",
+            key
+        ));
+    }
+    Ok(())
+}
+
+/// The rollout bucket (0-99) of a request for a flag
+fn bucket(key: &str, rollout_key: &str) -> u8 {
+    let digest = md5::compute(format!("{key}:{rollout_key}"));
+    (u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag(value: &str, rollout: u8, default: Option<&str>) -> FeatureFlag {
+        FeatureFlag {
+            value: value.to_string(),
+            rollout,
+            default: default.map(str::to_string),
+        }
+    }
+
+    fn flags(entries: &[(&str, FeatureFlag)]) -> FeatureFlags {
+        FeatureFlags {
+            flags: entries
+                .iter()
+                .map(|(key, flag)| (key.to_string(), flag.clone()))
+                .collect(),
+        }
+    }
+
+    fn header<'a>(headers: &'a HeaderMap, key: &str) -> Option<&'a str> {
+        headers
+            .get(format!("{FLAG_HEADER_PREFIX}{key}"))
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn test_bucket_is_stable() {
+        // Buckets must not change between releases, or callers would flip between values
+        assert_eq!(bucket("new-checkout", "user-1"), 77);
+        assert_eq!(bucket("new-checkout", "user-2"), 43);
+        // Each flag buckets callers on its own
+        assert_eq!(bucket("dark-mode", "user-1"), 2);
+    }
+
+    #[test]
+    fn test_buckets_spread_evenly() {
+        let mut counts = [0; 10];
+        for caller in 0..10_000 {
+            counts[bucket("new-checkout", &format!("user-{caller}")) as usize / 10] += 1;
+        }
+        assert!(counts.iter().all(|count| (800..1200).contains(count)));
+    }
+
+    #[test]
+    fn test_raising_a_rollout_only_adds_callers() {
+        let callers: Vec<String> = (0..500).map(|caller| format!("user-{caller}")).collect();
+        let inside = |rollout: u8| -> Vec<&String> {
+            let flags = flags(&[("new-checkout", flag("on", rollout, None))]);
+            callers
+                .iter()
+                .filter(|caller| {
+                    let mut headers = HeaderMap::new();
+                    flags.apply(&mut headers, caller);
+                    header(&headers, "new-checkout").is_some()
+                })
+                .collect()
+        };
+
+        assert!(inside(0).is_empty());
+        let mut previous = inside(0);
+        for rollout in [10, 25, 50, 90, 100] {
+            let current = inside(rollout);
+            assert!(previous.iter().all(|caller| current.contains(caller)));
+            assert!(current.len() >= previous.len());
+            previous = current;
+        }
+        assert_eq!(previous.len(), callers.len());
+    }
+
+    #[test]
+    fn test_apply_sets_values_and_defaults() {
+        let flags = flags(&[
+            ("everyone", flag("on", 100, None)),
+            ("nobody", flag("on", 0, Some("off"))),
+            ("hidden", flag("on", 0, None)),
+        ]);
+        let mut headers = HeaderMap::new();
+        flags.apply(&mut headers, "user-1");
+
+        assert_eq!(header(&headers, "everyone"), Some("on"));
+        assert_eq!(header(&headers, "nobody"), Some("off"));
+        assert_eq!(header(&headers, "hidden"), None);
+    }
+
+    #[test]
+    fn test_apply_strips_spoofed_flag_headers() {
+        let flags = flags(&[("hidden", flag("on", 0, None))]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-invok-flag-hidden", HeaderValue::from_static("on"));
+        headers.insert("x-invok-flag-unknown", HeaderValue::from_static("on"));
+        headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        flags.apply(&mut headers, "user-1");
+
+        // Callers can't pick flag values the namespace didn't give them
+        assert_eq!(header(&headers, "hidden"), None);
+        assert_eq!(header(&headers, "unknown"), None);
+        assert_eq!(headers.get("x-request-id").unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("new-checkout").is_ok());
+        assert!(validate_key("beta_2").is_ok());
+        assert!(validate_key(&"a".repeat(MAX_KEY_LENGTH)).is_ok());
+
+        assert!(validate_key("").is_err());
+        assert!(validate_key(&"a".repeat(MAX_KEY_LENGTH + 1)).is_err());
+        assert!(validate_key("NewCheckout").is_err());
+        assert!(validate_key("new checkout").is_err());
+        assert!(validate_key("new:checkout").is_err());
+        assert!(validate_key("über").is_err());
+    }
+
+    #[test]
+    fn test_validate_flags() {
+        assert!(flags(&[("beta", flag("on", 50, Some("off")))])
+            .validate()
+            .is_ok());
+        assert!(flags(&[("beta", flag("on", 101, None))])
+            .validate()
+            .is_err());
+        assert!(flags(&[("beta", flag("on\n", 50, None))])
+            .validate()
+            .is_err());
+        assert!(flags(&[("beta", flag("on", 50, Some("off\r\n")))])
+            .validate()
+            .is_err());
+
+        let too_many = FeatureFlags {
+            flags: (0..=MAX_FLAGS)
+                .map(|i| (format!("flag-{i}"), flag("on", 100, None)))
+                .collect(),
+        };
+        assert!(too_many.validate().is_err());
+    }
+
+    #[test]
+    fn test_rollout_key() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!(rollout_key(&headers, Some(ip)), "203.0.113.7");
+
+        headers.insert(ROLLOUT_KEY_HEADER, HeaderValue::from_static("user-1"));
+        assert_eq!(rollout_key(&headers, Some(ip)), "user-1");
+
+        // Without either, every request lands in some bucket
+        assert_ne!(
+            rollout_key(&HeaderMap::new(), None),
+            rollout_key(&HeaderMap::new(), None)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use axum::body::{boxed, Body};
+use axum::http::header::{HeaderValue, HOST, TE};
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use hyper::client::HttpConnector;
+use hyper::Client;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+use super::utils::{strip_hop_by_hop, FunctionTime};
+
+/// How long a connection to a container may stay without streams before it is closed
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Client for the containers of functions serving h2c
+pub type H2cClient = Client<HttpConnector, Body>;
+
+/// Path and query a gRPC call was made on, kept when it is routed to
+/// `/invok/<namespace>/<function>` and forwarded to the function as is
+#[derive(Debug, Clone)]
+pub struct UpstreamPath(pub String);
+
+/// A client speaking cleartext HTTP/2 with prior knowledge. Each container gets one
+/// pooled connection, kept alive across requests, which share it as concurrent streams.
+pub fn h2c_client() -> H2cClient {
+    Client::builder()
+        .http2_only(true)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .build_http()
+}
+
+/// Forwards a request to a function serving h2c, streaming both bodies.
+///
+/// Nothing is buffered, so trailers (gRPC's `grpc-status`) and long-lived streams pass
+/// through untouched; body limits, compression and address hiding don't apply.
+///
+/// # Arguments
+///
+/// * `client` - The pooled h2c client.
+/// * `addr` - The container's address.
+/// * `path_and_query` - What the function is called on, e.g. `/package.Service/Method`.
+/// * `headers` - The headers to forward. Hop-by-hop headers are stripped, except
+///   `TE: trailers`, which gRPC servers expect.
+/// * `method` - The request method.
+/// * `body` - The request body, streamed as it arrives.
+/// * `timeout` - How long to wait for the response headers; streams may outlive it.
+pub async fn forward_h2c(
+    client: &H2cClient,
+    addr: &str,
+    path_and_query: &str,
+    mut headers: HeaderMap,
+    method: Method,
+    body: Body,
+    timeout: Duration,
+) -> Response {
+    let accepts_trailers = headers.get(TE).is_some_and(|te| te == "trailers");
+    strip_hop_by_hop(&mut headers);
+    // The authority is the container's
+    headers.remove(HOST);
+    if accepts_trailers {
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+    }
+
+    let mut request = match Request::builder()
+        .method(method)
+        .uri(format!("http://{}{}", addr, path_and_query))
+        .body(body)
+    {
+        Ok(request) => request,
+        Err(e) => {
+            error!("Invalid h2c request to {}: {}", addr, e);
+            return (StatusCode::BAD_REQUEST, "Invalid request path".to_string()).into_response();
+        }
+    };
+    *request.headers_mut() = headers;
+
+    let sent_at = Instant::now();
+    match tokio::time::timeout(timeout, client.request(request)).await {
+        Ok(Ok(response)) => {
+            let (mut parts, body) = response.into_parts();
+            strip_hop_by_hop(&mut parts.headers);
+            let mut response = Response::from_parts(parts, boxed(body));
+            response
+                .extensions_mut()
+                .insert(FunctionTime(sent_at.elapsed()));
+            response
+        }
+        Ok(Err(e)) => {
+            error!("Error making downstream h2c request: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to make downstream request".to_string(),
+            )
+                .into_response()
+        }
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("Function did not respond within {} s", timeout.as_secs()),
+        )
+            .into_response(),
+    }
+}
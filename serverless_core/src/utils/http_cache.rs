@@ -0,0 +1,384 @@
+use axum::body::{Body, Bytes};
+use axum::http::header::{CACHE_CONTROL, CONTENT_LENGTH, ETAG, SET_COOKIE, VARY};
+use axum::http::response::Parts;
+use axum::http::{HeaderMap, HeaderValue, Method, Response as AxumResponse, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::time::Duration;
+
+/// `Cache-Control` of function responses that don't set one: caches may keep them but
+/// have to revalidate first, which the proxy answers from the `ETag`
+const DEFAULT_CACHE_CONTROL: &str = "no-cache";
+
+/// The `Cache-Control` directives the proxy acts on
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub public: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    /// Reads the directives of every `Cache-Control` header; unknown ones are ignored
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut control = Self::default();
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = value.and_then(|value| value.parse().ok());
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => control.no_store = true,
+                "no-cache" => control.no_cache = true,
+                "private" => control.private = true,
+                "public" => control.public = true,
+                "max-age" => control.max_age = seconds,
+                "s-maxage" => control.s_maxage = seconds,
+                _ => {}
+            }
+        }
+        control
+    }
+}
+
+/// Whether a request may be answered from, and its response stored in, a shared cache.
+///
+/// Only plain `GET`s qualify; callers sending `Cache-Control: no-cache` or `no-store` get
+/// a fresh response.
+pub fn is_cacheable_request(method: &Method, headers: &HeaderMap) -> bool {
+    let control = CacheControl::from_headers(headers);
+    method == Method::GET && !control.no_cache && !control.no_store
+}
+
+/// How long a shared cache may keep a response, as its `Cache-Control` allows.
+///
+/// Successful responses only, without cookies and varying on nothing but
+/// `Accept-Encoding`. Responses to requests carrying credentials (`authorized`) have to
+/// be marked `public` (or carry `s-maxage`) explicitly.
+pub fn shared_lifetime(
+    status: StatusCode,
+    authorized: bool,
+    response_headers: &HeaderMap,
+) -> Option<Duration> {
+    if status != StatusCode::OK || response_headers.contains_key(SET_COOKIE) {
+        return None;
+    }
+    let varies_on_more = response_headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| !name.trim().eq_ignore_ascii_case("accept-encoding"));
+    if varies_on_more {
+        return None;
+    }
+
+    let control = CacheControl::from_headers(response_headers);
+    if control.no_store || control.no_cache || control.private {
+        return None;
+    }
+    if authorized && !control.public && control.s_maxage.is_none() {
+        return None;
+    }
+    control
+        .s_maxage
+        .or(control.max_age)
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
+
+/// A strong validator for a response body
+pub fn body_etag(body: &[u8]) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{:x}\"", md5::compute(body)))
+        .expect("hex digest is a valid header value")
+}
+
+/// The weak form of a validator, for representations the proxy transformed (e.g.
+/// compressed) after the function set it
+pub fn weaken_etag(etag: &HeaderValue) -> HeaderValue {
+    if etag.as_bytes().starts_with(b"W/") {
+        return etag.clone();
+    }
+    let mut weak = b"W/".to_vec();
+    weak.extend_from_slice(etag.as_bytes());
+    HeaderValue::from_bytes(&weak).unwrap_or_else(|_| etag.clone())
+}
+
+/// Whether `If-None-Match` names the response's `ETag`, using the weak comparison
+/// conditional `GET`s call for
+pub fn matches_if_none_match(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    if_none_match
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+/// Gives a successful `GET` response its caching headers, keeping those the function set:
+/// an `ETag` computed from the body when it has none, and `Cache-Control: no-cache` when
+/// it left caching unspecified.
+pub fn add_validators(method: &Method, status: StatusCode, headers: &mut HeaderMap, body: &[u8]) {
+    if method != Method::GET || status != StatusCode::OK {
+        return;
+    }
+    if !headers.contains_key(ETAG) {
+        headers.insert(ETAG, body_etag(body));
+    }
+    if !headers.contains_key(CACHE_CONTROL) {
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static(DEFAULT_CACHE_CONTROL),
+        );
+    }
+}
+
+/// Builds the response to a request, answering `304 Not Modified` without the body when
+/// the request's `If-None-Match` names the response's `ETag`
+pub fn conditional_response(
+    method: &Method,
+    if_none_match: Option<&HeaderValue>,
+    mut parts: Parts,
+    body: Bytes,
+) -> Response {
+    let not_modified = match (if_none_match, parts.headers.get(ETAG)) {
+        (Some(if_none_match), Some(etag)) => {
+            method == Method::GET
+                && parts.status == StatusCode::OK
+                && matches_if_none_match(if_none_match, etag)
+        }
+        _ => false,
+    };
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_LENGTH);
+        return AxumResponse::from_parts(parts, Body::empty()).into_response();
+    }
+    AxumResponse::from_parts(parts, Body::from(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(axum::http::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn response_parts(pairs: &[(axum::http::HeaderName, &str)]) -> Parts {
+        let mut parts = AxumResponse::new(()).into_parts().0;
+        parts.headers = headers(pairs);
+        parts
+    }
+
+    #[test]
+    fn test_cache_control_from_headers() {
+        let control = CacheControl::from_headers(&headers(&[
+            (CACHE_CONTROL, "Public, max-age=60"),
+            (CACHE_CONTROL, "s-maxage=\"300\", no-transform"),
+        ]));
+        assert_eq!(
+            control,
+            CacheControl {
+                public: true,
+                max_age: Some(60),
+                s_maxage: Some(300),
+                ..Default::default()
+            }
+        );
+
+        let control =
+            CacheControl::from_headers(&headers(&[(CACHE_CONTROL, "no-store,no-cache, private")]));
+        assert!(control.no_store && control.no_cache && control.private);
+        // A malformed age reads as none
+        let control = CacheControl::from_headers(&headers(&[(CACHE_CONTROL, "max-age=soon")]));
+        assert_eq!(control.max_age, None);
+    }
+
+    #[test]
+    fn test_is_cacheable_request() {
+        assert!(is_cacheable_request(&Method::GET, &HeaderMap::new()));
+        assert!(!is_cacheable_request(&Method::POST, &HeaderMap::new()));
+        assert!(!is_cacheable_request(&Method::HEAD, &HeaderMap::new()));
+        assert!(!is_cacheable_request(
+            &Method::GET,
+            &headers(&[(CACHE_CONTROL, "no-cache")])
+        ));
+        assert!(!is_cacheable_request(
+            &Method::GET,
+            &headers(&[(CACHE_CONTROL, "no-store")])
+        ));
+        assert!(is_cacheable_request(
+            &Method::GET,
+            &headers(&[(CACHE_CONTROL, "max-age=0")])
+        ));
+    }
+
+    #[test]
+    fn test_shared_lifetime() {
+        let minute = Some(Duration::from_secs(60));
+        let max_age = headers(&[(CACHE_CONTROL, "max-age=60")]);
+        assert_eq!(shared_lifetime(StatusCode::OK, false, &max_age), minute);
+        // s-maxage wins for shared caches
+        let both = headers(&[(CACHE_CONTROL, "max-age=60, s-maxage=300")]);
+        assert_eq!(
+            shared_lifetime(StatusCode::OK, false, &both),
+            Some(Duration::from_secs(300))
+        );
+
+        // Only successful responses, without cookies, that allow storing for a while
+        assert_eq!(
+            shared_lifetime(StatusCode::NOT_FOUND, false, &max_age),
+            None
+        );
+        assert_eq!(shared_lifetime(StatusCode::CREATED, false, &max_age), None);
+        let mut cookie = max_age.clone();
+        cookie.insert(SET_COOKIE, HeaderValue::from_static("session=1"));
+        assert_eq!(shared_lifetime(StatusCode::OK, false, &cookie), None);
+        for directive in [
+            "max-age=60, no-store",
+            "max-age=60, no-cache",
+            "max-age=60, private",
+        ] {
+            let control = headers(&[(CACHE_CONTROL, directive)]);
+            assert_eq!(shared_lifetime(StatusCode::OK, false, &control), None);
+        }
+        let zero = headers(&[(CACHE_CONTROL, "max-age=0")]);
+        assert_eq!(shared_lifetime(StatusCode::OK, false, &zero), None);
+        assert_eq!(
+            shared_lifetime(StatusCode::OK, false, &HeaderMap::new()),
+            None
+        );
+
+        // Varying on anything but the encoding can't be shared
+        let mut vary = max_age.clone();
+        vary.insert(VARY, HeaderValue::from_static("Accept-Encoding"));
+        assert_eq!(shared_lifetime(StatusCode::OK, false, &vary), minute);
+        vary.append(VARY, HeaderValue::from_static("accept-encoding, Cookie"));
+        assert_eq!(shared_lifetime(StatusCode::OK, false, &vary), None);
+
+        // Responses to credentialed requests must opt in
+        assert_eq!(shared_lifetime(StatusCode::OK, true, &max_age), None);
+        let public = headers(&[(CACHE_CONTROL, "public, max-age=60")]);
+        assert_eq!(shared_lifetime(StatusCode::OK, true, &public), minute);
+        let s_maxage = headers(&[(CACHE_CONTROL, "s-maxage=60")]);
+        assert_eq!(shared_lifetime(StatusCode::OK, true, &s_maxage), minute);
+    }
+
+    #[test]
+    fn test_etags() {
+        let etag = body_etag(b"hello");
+        assert_eq!(etag, "\"5d41402abc4b2a76b9719d911017c592\"");
+        assert_eq!(body_etag(b"hello"), etag);
+        assert_ne!(body_etag(b"hello!"), etag);
+
+        let weak = weaken_etag(&etag);
+        assert_eq!(weak, "W/\"5d41402abc4b2a76b9719d911017c592\"");
+        assert_eq!(weaken_etag(&weak), weak);
+    }
+
+    #[test]
+    fn test_matches_if_none_match() {
+        let etag = HeaderValue::from_static("\"abc\"");
+        let matches = |if_none_match: &'static str, etag: &HeaderValue| {
+            matches_if_none_match(&HeaderValue::from_static(if_none_match), etag)
+        };
+        assert!(matches("\"abc\"", &etag));
+        assert!(matches("\"xyz\", \"abc\"", &etag));
+        assert!(matches("*", &etag));
+        assert!(!matches("\"xyz\"", &etag));
+        assert!(!matches("\"ab\"", &etag));
+
+        // The weak comparison ignores the W/ prefix on either side
+        assert!(matches("W/\"abc\"", &etag));
+        assert!(matches("\"abc\"", &weaken_etag(&etag)));
+    }
+
+    #[test]
+    fn test_add_validators() {
+        let mut response_headers = HeaderMap::new();
+        add_validators(
+            &Method::GET,
+            StatusCode::OK,
+            &mut response_headers,
+            b"hello",
+        );
+        assert_eq!(response_headers[ETAG], body_etag(b"hello"));
+        assert_eq!(response_headers[CACHE_CONTROL], DEFAULT_CACHE_CONTROL);
+
+        // What the function set is kept
+        let mut response_headers = headers(&[(ETAG, "\"v1\""), (CACHE_CONTROL, "max-age=60")]);
+        add_validators(
+            &Method::GET,
+            StatusCode::OK,
+            &mut response_headers,
+            b"hello",
+        );
+        assert_eq!(response_headers[ETAG], "\"v1\"");
+        assert_eq!(response_headers[CACHE_CONTROL], "max-age=60");
+
+        // Only successful GETs get validators
+        for (method, status) in [
+            (Method::POST, StatusCode::OK),
+            (Method::GET, StatusCode::NOT_FOUND),
+        ] {
+            let mut response_headers = HeaderMap::new();
+            add_validators(&method, status, &mut response_headers, b"hello");
+            assert!(response_headers.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conditional_response() {
+        let body = Bytes::from_static(b"hello");
+        let etag = || response_parts(&[(ETAG, "\"abc\""), (CONTENT_LENGTH, "5")]);
+        let if_none_match = HeaderValue::from_static("W/\"abc\"");
+
+        let response =
+            conditional_response(&Method::GET, Some(&if_none_match), etag(), body.clone());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(!response.headers().contains_key(CONTENT_LENGTH));
+        assert_eq!(response.headers()[ETAG], "\"abc\"");
+        let sent = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(sent.is_empty());
+
+        // Another validator, no validator, or not a GET: the full response
+        let other = HeaderValue::from_static("\"xyz\"");
+        for (method, if_none_match) in [
+            (Method::GET, Some(&other)),
+            (Method::GET, None),
+            (Method::POST, Some(&if_none_match)),
+        ] {
+            let response = conditional_response(&method, if_none_match, etag(), body.clone());
+            assert_eq!(response.status(), StatusCode::OK);
+            let sent = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(sent, body);
+        }
+
+        // A response without an ETag can't be matched
+        let response = conditional_response(
+            &Method::GET,
+            Some(&HeaderValue::from_static("*")),
+            response_parts(&[]),
+            body.clone(),
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
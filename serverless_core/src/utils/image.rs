@@ -0,0 +1,107 @@
+use runtime::core::provisioning::{ImageDetails, RegistryAuth};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Runtime of functions deployed from an image built by their own CI, instead of from
+/// their sources
+pub const IMAGE_RUNTIME: &str = "image";
+
+/// Port invocations are forwarded to, which images have to expose
+const FUNCTION_PORT: &str = "8080/tcp";
+
+/// The image a function of the [`IMAGE_RUNTIME`] runs, given as `image` in `config.json`.
+///
+/// As with private registries, credentials are never part of the config: they name the
+/// namespace build args holding them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionImage {
+    /// e.g. `registry.example.com/orders:1.4` or `registry.example.com/orders@sha256:...`
+    pub reference: String,
+    /// Build arg holding the registry username
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Build arg holding the registry password or token
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl FunctionImage {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.reference.trim().is_empty() {
+            return Err("image reference is empty".to_string());
+        }
+        if self.reference.contains(char::is_whitespace) {
+            return Err(format!("invalid image reference '{}'", self.reference));
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err("image username and password are set together".to_string());
+        }
+        Ok(())
+    }
+
+    /// Credentials of the image's registry, read from the namespace's build args
+    pub fn auth(&self, build_args: &HashMap<String, String>) -> Result<RegistryAuth, String> {
+        let build_arg = |name: &Option<String>| match name {
+            Some(name) => build_args.get(name).cloned().map(Some).ok_or_else(|| {
+                format!(
+                    "build arg '{}' is not set; set it with 'invok buildarg set {}'",
+                    name, name
+                )
+            }),
+            None => Ok(None),
+        };
+        Ok(RegistryAuth {
+            username: build_arg(&self.username)?,
+            password: build_arg(&self.password)?,
+        })
+    }
+
+    /// Checks the pulled image can run as a function: it exposes the port invocations
+    /// are forwarded to and has a command to start with.
+    ///
+    /// # Returns
+    ///
+    /// A line describing the image for the deploy message.
+    pub fn check(&self, details: &ImageDetails) -> Result<String, String> {
+        if !details
+            .exposed_ports
+            .iter()
+            .any(|port| port == FUNCTION_PORT)
+        {
+            let exposed = if details.exposed_ports.is_empty() {
+                "none".to_string()
+            } else {
+                details.exposed_ports.join(", ")
+            };
+            return Err(format!(
+                "image {} doesn't expose port 8080, which functions serve on (exposed: {}); \
+                 add `EXPOSE 8080` and listen on it",
+                self.reference, exposed
+            ));
+        }
+        if !details.runs_command {
+            return Err(format!(
+                "image {} has no ENTRYPOINT or CMD to start the function with",
+                self.reference
+            ));
+        }
+
+        let digest = details
+            .digests
+            .iter()
+            .find_map(|digest| digest.split_once('@'))
+            .map(|(_, digest)| format!(" ({})", digest))
+            .unwrap_or_default();
+        let healthcheck = match details.healthcheck.as_deref() {
+            Some([kind, command @ ..]) if kind == "CMD" || kind == "CMD-SHELL" => {
+                format!("healthcheck `{}`", command.join(" "))
+            }
+            Some(test) => format!("healthcheck `{}`", test.join(" ")),
+            None => "no healthcheck".to_string(),
+        };
+        Ok(format!(
+            "Image {}{} deployed as is, {}",
+            self.reference, digest, healthcheck
+        ))
+    }
+}
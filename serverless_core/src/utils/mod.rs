@@ -1 +1,16 @@
+pub(crate) mod archive;
+pub(crate) mod bundled_data;
+pub(crate) mod compression;
+pub(crate) mod cron;
+pub(crate) mod dependencies;
+pub(crate) mod egress;
+pub(crate) mod firewall;
+pub(crate) mod flags;
+pub(crate) mod http2;
+pub(crate) mod http_cache;
+pub(crate) mod image;
+pub(crate) mod presign;
+pub(crate) mod registries;
+pub(crate) mod routing;
+pub(crate) mod shared_packages;
 pub(crate) mod utils;
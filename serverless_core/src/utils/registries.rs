@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Private package registries a function's dependencies are installed from, given as
+/// `registries` in `config.json`.
+///
+/// Credentials are never part of the config: each entry names the namespace build arg
+/// (see `invok buildarg set`) holding its token, which only the build stage sees.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrivateRegistries {
+    /// npm registries, for a package scope or every package (nodejs)
+    #[serde(default)]
+    pub npm: Vec<NpmRegistry>,
+    /// Private modules and module proxy (go)
+    #[serde(default)]
+    pub go: Option<GoModules>,
+    /// Hosts logged in to over HTTPS through `.netrc`, e.g. for git dependencies (go, nodejs)
+    #[serde(default)]
+    pub netrc: Vec<NetrcEntry>,
+}
+
+/// An npm registry, written to the function's `.npmrc`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NpmRegistry {
+    /// Package scope served by the registry, e.g. `@acme`; every package when unset
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// e.g. `https://npm.pkg.github.com`
+    pub url: String,
+    /// Build arg holding the registry's auth token
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// Where Go modules are downloaded from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GoModules {
+    /// Module path patterns fetched directly and not checked against the public checksum
+    /// database (`GOPRIVATE`), e.g. `github.com/acme/*`
+    #[serde(default)]
+    pub private: Vec<String>,
+    /// Module proxy URLs (`GOPROXY`), e.g. `https://goproxy.acme.dev,direct`
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+/// Credentials of a host, written to `.netrc` in the build stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetrcEntry {
+    /// e.g. `github.com`
+    pub machine: String,
+    /// e.g. `x-access-token` for a GitHub token
+    pub login: String,
+    /// Build arg holding the password or token
+    pub password: String,
+}
+
+impl PrivateRegistries {
+    /// Checks the registries apply to `runtime` and every credential they name is one of
+    /// the namespace's `build_args`, so mistakes fail the deploy before anything is built
+    pub fn validate(
+        &self,
+        runtime: &str,
+        build_args: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        if !self.npm.is_empty() && runtime != "nodejs" {
+            return Err("npm registries are only used by nodejs functions".to_string());
+        }
+        if self.go.is_some() && runtime != "go" {
+            return Err("go modules are only used by go functions".to_string());
+        }
+        if !self.netrc.is_empty() && runtime != "go" && runtime != "nodejs" {
+            return Err("netrc is only supported for go and nodejs functions".to_string());
+        }
+
+        let build_arg = |name: &str| {
+            if build_args.contains_key(name) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "build arg '{}' is not set; set it with 'invok buildarg set {}'",
+                    name, name
+                ))
+            }
+        };
+        for registry in &self.npm {
+            check_url("npm registry url", &registry.url)?;
+            if let Some(scope) = &registry.scope {
+                let name = scope.strip_prefix('@').unwrap_or_default();
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+                {
+                    return Err(format!("invalid npm scope '{}', e.g. '@acme'", scope));
+                }
+            }
+            if let Some(token) = &registry.token {
+                build_arg(token)?;
+            }
+        }
+        if let Some(go) = &self.go {
+            for pattern in &go.private {
+                check_plain("go private pattern", pattern)?;
+                if pattern.contains(',') {
+                    return Err(format!(
+                        "go private pattern '{}' must be a single pattern",
+                        pattern
+                    ));
+                }
+            }
+            if let Some(proxy) = &go.proxy {
+                check_plain("go proxy", proxy)?;
+            }
+        }
+        for entry in &self.netrc {
+            check_plain("netrc machine", &entry.machine)?;
+            check_plain("netrc login", &entry.login)?;
+            build_arg(&entry.password)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the npm registries to the `.npmrc` of the function files at `path`, after any
+    /// settings the function ships.
+    ///
+    /// Tokens are written as `${NAME}` references, which npm fills in from the build args
+    /// while installing, so the file never holds them.
+    pub fn write_npmrc(&self, path: &Path) -> io::Result<()> {
+        if self.npm.is_empty() {
+            return Ok(());
+        }
+        let mut npmrc = String::from("\n");
+        for registry in &self.npm {
+            let url = format!("{}/", registry.url.trim_end_matches('/'));
+            match &registry.scope {
+                Some(scope) => npmrc.push_str(&format!("{}:registry={}\n", scope, url)),
+                None => npmrc.push_str(&format!("registry={}\n", url)),
+            }
+            if let Some(token) = &registry.token {
+                let host = url.split_once("://").map_or(url.as_str(), |(_, host)| host);
+                npmrc.push_str(&format!("//{}:_authToken=${{{}}}\n", host, token));
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.join(".npmrc"))?;
+        file.write_all(npmrc.as_bytes())
+    }
+
+    /// Dockerfile steps of the build stage run before dependencies are installed: the Go
+    /// module settings and the `.netrc` filled in from the build args
+    pub fn dockerfile_steps(&self) -> String {
+        let mut steps = String::new();
+        if let Some(go) = &self.go {
+            if !go.private.is_empty() {
+                steps.push_str(&format!("ENV GOPRIVATE=\"{}\"\n", go.private.join(",")));
+            }
+            if let Some(proxy) = &go.proxy {
+                steps.push_str(&format!("ENV GOPROXY=\"{}\"\n", proxy));
+            }
+        }
+        for entry in &self.netrc {
+            steps.push_str(&format!(
+                "RUN printf 'machine %s login %s password %s\\n' '{}' '{}' \"${}\" >> \"$HOME/.netrc\" && chmod 600 \"$HOME/.netrc\"\n",
+                entry.machine, entry.login, entry.password
+            ));
+        }
+        steps
+    }
+}
+
+fn check_url(what: &str, url: &str) -> Result<(), String> {
+    check_plain(what, url)?;
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("{} '{}' must be an http(s):// URL", what, url));
+    }
+    Ok(())
+}
+
+/// Values end up in the Dockerfile and shell commands, so only plain characters pass
+fn check_plain(what: &str, value: &str) -> Result<(), String> {
+    if value.is_empty()
+        || !value
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"'\"`$\\".contains(c))
+    {
+        return Err(format!(
+            "invalid {} '{}': use printable characters without quotes, '$' or '\\'",
+            what, value
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use axum::http::{HeaderMap, HeaderName};
+use db_entities::function::Model as FunctionModel;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::firewall::Cidr;
+use super::utils::cookie_value;
+
+/// Most rules a function may have
+const MAX_RULES: usize = 32;
+
+/// Rules sending matching invocations of a function to one of its recorded versions.
+///
+/// Rules are tried in order and the first match wins; requests matching none are served
+/// by the current version. An empty list routes everything to the current version.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRules {
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+}
+
+/// A condition and the version requests meeting it are served by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub version: i32,
+    pub when: RouteMatch,
+}
+
+/// What a request has to carry to match a rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteMatch {
+    /// A header with exactly this value, e.g. `{"header": {"name": "x-beta", "equals": "1"}}`
+    Header { name: String, equals: String },
+    /// A cookie with this name, whatever its value, e.g. `{"cookie": "beta"}`
+    Cookie(String),
+    /// A client address in this address/CIDR, e.g. `{"ip": "10.0.0.0/8"}`
+    Ip(String),
+}
+
+impl RoutingRules {
+    /// Read the rules stored on a function record, falling back to none
+    pub fn from_model(function: &FunctionModel) -> Self {
+        function
+            .routing_rules
+            .clone()
+            .and_then(|rules| serde_json::from_value(rules).ok())
+            .unwrap_or_default()
+    }
+
+    /// Check that every rule names a version and parses, so mistakes are reported instead
+    /// of silently routing nothing
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.len() > MAX_RULES {
+            return Err(format!("at most {} rules are allowed", MAX_RULES));
+        }
+        for rule in &self.rules {
+            if rule.version < 1 {
+                return Err(format!("invalid version {}", rule.version));
+            }
+            match &rule.when {
+                RouteMatch::Header { name, .. } => {
+                    HeaderName::from_str(name)
+                        .map_err(|_| format!("invalid header name '{}'", name))?;
+                }
+                RouteMatch::Cookie(name) if name.is_empty() || name.contains(['=', ';']) => {
+                    return Err(format!("invalid cookie name '{}'", name));
+                }
+                RouteMatch::Cookie(_) => {}
+                RouteMatch::Ip(entry) => {
+                    Cidr::from_str(entry)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The versions the rules route to, each once
+    pub fn versions(&self) -> BTreeSet<i32> {
+        self.rules.iter().map(|rule| rule.version).collect()
+    }
+
+    /// The version of the first rule the request matches, if any. Entries that fail to
+    /// parse are skipped; updates reject them through [`RoutingRules::validate`].
+    pub fn route(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<i32> {
+        self.rules
+            .iter()
+            .find(|rule| match &rule.when {
+                RouteMatch::Header { name, equals } => headers
+                    .get(name.as_str())
+                    .is_some_and(|value| value.as_bytes() == equals.as_bytes()),
+                RouteMatch::Cookie(name) => cookie_value(headers, name).is_some(),
+                RouteMatch::Ip(entry) => client_ip
+                    .is_some_and(|ip| Cidr::from_str(entry).is_ok_and(|cidr| cidr.contains(ip))),
+            })
+            .map(|rule| rule.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::COOKIE;
+    use axum::http::HeaderValue;
+
+    fn rules(json: serde_json::Value) -> RoutingRules {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let parsed = rules(serde_json::json!({"rules": [
+            {"version": 3, "when": {"header": {"name": "x-beta", "equals": "1"}}},
+            {"version": 2, "when": {"cookie": "beta"}},
+            {"version": 1, "when": {"ip": "10.0.0.0/8"}},
+        ]}));
+        assert_eq!(
+            parsed.rules[0].when,
+            RouteMatch::Header {
+                name: "x-beta".to_string(),
+                equals: "1".to_string()
+            }
+        );
+        assert_eq!(parsed.rules[1].when, RouteMatch::Cookie("beta".to_string()));
+        assert_eq!(
+            parsed.rules[2].when,
+            RouteMatch::Ip("10.0.0.0/8".to_string())
+        );
+        assert_eq!(parsed.versions(), BTreeSet::from([1, 2, 3]));
+        assert_eq!(rules(serde_json::json!({})), RoutingRules::default());
+    }
+
+    #[test]
+    fn test_validate() {
+        let rule = |version: i32, when: serde_json::Value| {
+            rules(serde_json::json!({"rules": [{"version": version, "when": when}]}))
+        };
+        assert!(rule(2, serde_json::json!({"cookie": "beta"}))
+            .validate()
+            .is_ok());
+        assert!(rule(2, serde_json::json!({"ip": "192.168.1.1"}))
+            .validate()
+            .is_ok());
+        assert!(RoutingRules::default().validate().is_ok());
+
+        assert!(rule(0, serde_json::json!({"cookie": "beta"}))
+            .validate()
+            .is_err());
+        let bad_header = serde_json::json!({"header": {"name": "x beta", "equals": "1"}});
+        assert!(rule(2, bad_header).validate().is_err());
+        assert!(rule(2, serde_json::json!({"cookie": ""}))
+            .validate()
+            .is_err());
+        assert!(rule(2, serde_json::json!({"cookie": "a=b"}))
+            .validate()
+            .is_err());
+        assert!(rule(2, serde_json::json!({"ip": "10.0.0.0/33"}))
+            .validate()
+            .is_err());
+        assert!(rule(2, serde_json::json!({"ip": "intranet"}))
+            .validate()
+            .is_err());
+
+        let too_many = RoutingRules {
+            rules: vec![
+                RoutingRule {
+                    version: 1,
+                    when: RouteMatch::Cookie("beta".to_string()),
+                };
+                MAX_RULES + 1
+            ],
+        };
+        assert!(too_many.validate().is_err());
+    }
+
+    #[test]
+    fn test_route() {
+        let rules = rules(serde_json::json!({"rules": [
+            {"version": 3, "when": {"header": {"name": "x-beta", "equals": "1"}}},
+            {"version": 2, "when": {"cookie": "beta"}},
+            {"version": 1, "when": {"ip": "10.0.0.0/8"}},
+        ]}));
+        let internal: Option<IpAddr> = Some("10.1.2.3".parse().unwrap());
+        let external: Option<IpAddr> = Some("203.0.113.7".parse().unwrap());
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(rules.route(&headers, external), None);
+        assert_eq!(rules.route(&headers, None), None);
+        assert_eq!(rules.route(&headers, internal), Some(1));
+
+        // The first matching rule wins
+        headers.insert(COOKIE, HeaderValue::from_static("theme=dark; beta=yes"));
+        assert_eq!(rules.route(&headers, internal), Some(2));
+        headers.insert("x-beta", HeaderValue::from_static("1"));
+        assert_eq!(rules.route(&headers, internal), Some(3));
+
+        // Header values must be equal, cookies only present
+        headers.insert("x-beta", HeaderValue::from_static("10"));
+        assert_eq!(rules.route(&headers, external), Some(2));
+        headers.insert(COOKIE, HeaderValue::from_static("betas=yes"));
+        assert_eq!(rules.route(&headers, external), None);
+    }
+}
@@ -0,0 +1,415 @@
+use serde_json::{json, Value};
+use shared_utils::SHARED_PACKAGES_DIR;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+use templates::go_template;
+use toml_edit::{DocumentMut, Item};
+
+/// Wires the shared packages of a function, given as `shared` in `config.json` and
+/// unpacked under [`SHARED_PACKAGES_DIR`] of its files at `path`, into its build, in
+/// place of the sibling directories they're referenced from during local development:
+/// - go: a `go.mod` replacing each package's module with its directory
+/// - nodejs: each package becomes an npm workspace and `file:../<name>` dependencies
+///   point at it; the lockfile, which resolves them to the local paths, is dropped
+/// - rust: dependencies of `Cargo.toml` with the path `../<name>` point at it
+///
+/// Errors of kind `InvalidInput` are mistakes of the function's.
+pub fn link_shared_packages(path: &Path, runtime: &str, shared: &[String]) -> io::Result<()> {
+    let shared_dir = path.join(SHARED_PACKAGES_DIR);
+    if runtime == "nodejs" {
+        // The Dockerfile copies it whether or not there are packages
+        fs::create_dir_all(&shared_dir)?;
+    }
+    if shared.is_empty() {
+        return Ok(());
+    }
+
+    for name in shared {
+        let mut components = Path::new(name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(invalid(format!("Invalid shared package name '{}'", name)));
+        }
+        if !shared_dir.join(name).is_dir() {
+            return Err(invalid(format!(
+                "Shared package '{}' is missing from the archive",
+                name
+            )));
+        }
+    }
+
+    match runtime {
+        "go" => link_go_modules(path, &shared_dir, shared),
+        "nodejs" => link_npm_workspaces(path, shared),
+        "rust" => link_cargo_paths(path, shared),
+        _ => Err(invalid(format!(
+            "Shared packages aren't supported for {} functions",
+            runtime
+        ))),
+    }
+}
+
+/// Points the modules of the shared packages at their directories in the function's
+/// `go.mod`, which is created from the template when the function has none. Its own
+/// `replace` directives for those modules, e.g. to `../<name>`, are dropped.
+fn link_go_modules(path: &Path, shared_dir: &Path, shared: &[String]) -> io::Result<()> {
+    let mut modules = Vec::new();
+    for name in shared {
+        let module_file = fs::read_to_string(shared_dir.join(name).join("go.mod"))
+            .map_err(|_| invalid(format!("Shared package '{}' has no go.mod", name)))?;
+        let module = module_file
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))
+            .map(|module| module.trim().trim_matches('"'))
+            .filter(|module| !module.is_empty())
+            .ok_or_else(|| {
+                invalid(format!(
+                    "The go.mod of shared package '{}' names no module",
+                    name
+                ))
+            })?;
+        modules.push((module.to_string(), name));
+    }
+
+    let go_mod_file = path.join("go.mod");
+    let go_mod = match fs::read_to_string(&go_mod_file) {
+        Ok(go_mod) => go_mod,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            go_template::FUNCTION_MODULE_TEMPLATE.to_string()
+        }
+        Err(e) => return Err(e),
+    };
+    fs::write(go_mod_file, replace_go_modules(&go_mod, &modules))
+}
+
+/// `go_mod` with each `(module, package)` replaced by the package's directory
+fn replace_go_modules(go_mod: &str, modules: &[(String, &String)]) -> String {
+    let replaces = |line: &str, module: &str| {
+        let line = line.trim();
+        let target = line.strip_prefix("replace").map_or(line, str::trim_start);
+        target
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.starts_with([' ', '\t', '=']))
+    };
+    let mut linked: String = go_mod
+        .lines()
+        .filter(|line| !modules.iter().any(|(module, _)| replaces(line, module)))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    for (module, name) in modules {
+        linked.push_str(&format!(
+            "\nreplace {} => ./{}/{}\n",
+            module, SHARED_PACKAGES_DIR, name
+        ));
+    }
+    linked
+}
+
+fn link_npm_workspaces(path: &Path, shared: &[String]) -> io::Result<()> {
+    let package_file = path.join("package.json");
+    let contents = fs::read_to_string(&package_file)
+        .map_err(|_| invalid("The function has no package.json".to_string()))?;
+    let mut package: Value = serde_json::from_str(&contents)
+        .map_err(|e| invalid(format!("Invalid package.json: {}", e)))?;
+    let Some(fields) = package.as_object_mut() else {
+        return Err(invalid("Invalid package.json: not an object".to_string()));
+    };
+
+    let Some(workspaces) = fields
+        .entry("workspaces")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+    else {
+        return Err(invalid(
+            "Shared packages need the workspaces of package.json to be an array".to_string(),
+        ));
+    };
+    for name in shared {
+        let workspace = Value::String(format!("{}/{}", SHARED_PACKAGES_DIR, name));
+        if !workspaces.contains(&workspace) {
+            workspaces.push(workspace);
+        }
+    }
+
+    for field in ["dependencies", "devDependencies", "optionalDependencies"] {
+        let Some(Value::Object(dependencies)) = fields.get_mut(field) else {
+            continue;
+        };
+        for spec in dependencies.values_mut() {
+            let sibling = spec.as_str().and_then(sibling_name).map(str::to_string);
+            if let Some(name) = sibling.filter(|name| shared.contains(name)) {
+                *spec = Value::String(format!("file:./{}/{}", SHARED_PACKAGES_DIR, name));
+            }
+        }
+    }
+    fs::write(&package_file, serde_json::to_string_pretty(&package)?)?;
+
+    match fs::remove_file(path.join("package-lock.json")) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn link_cargo_paths(path: &Path, shared: &[String]) -> io::Result<()> {
+    let manifest_file = path.join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_file)
+        .map_err(|_| invalid("The function has no Cargo.toml".to_string()))?;
+    fs::write(&manifest_file, replace_cargo_paths(&manifest, shared)?)
+}
+
+/// The dependency tables of a `Cargo.toml`, at the top level and under each target
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// `manifest` with the dependencies on `../<name>` of each shared package pointed at its
+/// directory, in every dependency table including the target-specific, workspace and
+/// patch ones. The rest of the manifest is kept as it is written.
+fn replace_cargo_paths(manifest: &str, shared: &[String]) -> io::Result<String> {
+    let mut document: DocumentMut = manifest
+        .parse()
+        .map_err(|e| invalid(format!("Invalid Cargo.toml: {}", e)))?;
+    for (key, item) in document.as_table_mut().iter_mut() {
+        match key.get() {
+            key if DEPENDENCY_TABLES.contains(&key) => link_dependencies(item, shared),
+            // [target.'cfg(unix)'.dependencies]
+            "target" => {
+                for (_, target) in entries(item) {
+                    for (key, item) in entries(target) {
+                        if DEPENDENCY_TABLES.contains(&key.get()) {
+                            link_dependencies(item, shared);
+                        }
+                    }
+                }
+            }
+            "workspace" => {
+                if let Some(item) = item.get_mut("dependencies") {
+                    link_dependencies(item, shared);
+                }
+            }
+            // [patch.crates-io]
+            "patch" => {
+                for (_, source) in entries(item) {
+                    link_dependencies(source, shared);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// Points the dependencies of a table on `../<name>` of a shared package at its directory
+fn link_dependencies(dependencies: &mut Item, shared: &[String]) {
+    for (_, dependency) in entries(dependencies) {
+        let Some(dependency) = dependency.as_table_like_mut() else {
+            continue;
+        };
+        let sibling = dependency
+            .get("path")
+            .and_then(Item::as_str)
+            .and_then(|path| path.strip_prefix("../"))
+            .map(|name| name.trim_end_matches('/').to_string());
+        if let Some(name) = sibling.filter(|name| shared.contains(name)) {
+            dependency.insert(
+                "path",
+                toml_edit::value(format!("{}/{}", SHARED_PACKAGES_DIR, name)),
+            );
+        }
+    }
+}
+
+/// The entries of a table or inline table, none for other items
+fn entries(item: &mut Item) -> impl Iterator<Item = (toml_edit::KeyMut<'_>, &mut Item)> {
+    item.as_table_like_mut()
+        .into_iter()
+        .flat_map(|table| table.iter_mut())
+}
+
+/// The sibling directory a local npm dependency points at, e.g. `utils` for `file:../utils`
+fn sibling_name(spec: &str) -> Option<&str> {
+    spec.strip_prefix("file:")
+        .unwrap_or(spec)
+        .strip_prefix("../")
+        .map(|name| name.trim_end_matches('/'))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_go_modules() {
+        let go_mod = "module example.com/hello\n\ngo 1.23\n\nrequire example.com/utils v0.0.0\n\nreplace example.com/utils => ../utils\nreplace example.com/utilsx => ../utilsx\n";
+        let name = "utils".to_string();
+        let linked = replace_go_modules(go_mod, &[("example.com/utils".to_string(), &name)]);
+        assert_eq!(
+            linked,
+            format!(
+                "module example.com/hello\n\ngo 1.23\n\nrequire example.com/utils v0.0.0\n\nreplace example.com/utilsx => ../utilsx\n\nreplace example.com/utils => ./{SHARED_PACKAGES_DIR}/utils\n"
+            )
+        );
+    }
+
+    /// A function at `<dir>/function` whose archive holds the shared packages `names`,
+    /// each with `files`
+    fn function_with_shared(names: &[&str], files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in names {
+            let package = dir.path().join(SHARED_PACKAGES_DIR).join(name);
+            fs::create_dir_all(&package).unwrap();
+            for (file, contents) in files {
+                fs::write(package.join(file), contents.replace("{name}", name)).unwrap();
+            }
+        }
+        dir
+    }
+
+    fn shared(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_link_go_modules() {
+        let dir = function_with_shared(&["utils"], &[("go.mod", "module example.com/{name}\n")]);
+        fs::write(
+            dir.path().join("go.mod"),
+            "module example.com/hello\n\nreplace example.com/utils => ../utils\n",
+        )
+        .unwrap();
+        link_shared_packages(dir.path(), "go", &shared(&["utils"])).unwrap();
+        let go_mod = fs::read_to_string(dir.path().join("go.mod")).unwrap();
+        assert!(go_mod.starts_with("module example.com/hello\n"));
+        assert!(!go_mod.contains("../utils"));
+        assert!(go_mod.contains(&format!(
+            "replace example.com/utils => ./{SHARED_PACKAGES_DIR}/utils"
+        )));
+
+        // Without a go.mod, the function gets the template's
+        let dir = function_with_shared(&["utils"], &[("go.mod", "module example.com/{name}\n")]);
+        link_shared_packages(dir.path(), "go", &shared(&["utils"])).unwrap();
+        let go_mod = fs::read_to_string(dir.path().join("go.mod")).unwrap();
+        assert!(go_mod.contains("replace example.com/utils"));
+
+        // A package without a module can't be linked
+        let dir = function_with_shared(&["utils"], &[("go.mod", "go 1.23\n")]);
+        let error = link_shared_packages(dir.path(), "go", &shared(&["utils"])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_link_npm_workspaces() {
+        let dir = function_with_shared(&["utils", "auth"], &[("package.json", "{}")]);
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{
+                "name": "hello",
+                "workspaces": ["tools"],
+                "dependencies": {"utils": "file:../utils", "express": "^4.19.0"},
+                "devDependencies": {"auth": "../auth/", "other": "file:../other"}
+            }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("package-lock.json"), "{}").unwrap();
+
+        link_shared_packages(dir.path(), "nodejs", &shared(&["utils", "auth"])).unwrap();
+        let package: Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join("package.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            package["workspaces"],
+            json!([
+                "tools",
+                format!("{SHARED_PACKAGES_DIR}/utils"),
+                format!("{SHARED_PACKAGES_DIR}/auth")
+            ])
+        );
+        assert_eq!(
+            package["dependencies"],
+            json!({"utils": format!("file:./{SHARED_PACKAGES_DIR}/utils"), "express": "^4.19.0"})
+        );
+        assert_eq!(
+            package["devDependencies"],
+            json!({"auth": format!("file:./{SHARED_PACKAGES_DIR}/auth"), "other": "file:../other"})
+        );
+        // The lockfile resolved the packages to their local paths
+        assert!(!dir.path().join("package-lock.json").exists());
+
+        // Workspaces given as an object can't be extended
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"workspaces": {"packages": []}}"#,
+        )
+        .unwrap();
+        let error = link_shared_packages(dir.path(), "nodejs", &shared(&["utils"])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_replace_cargo_paths() {
+        let manifest = r#"[package]
+name = "hello"
+# The path of a comment: "../utils"
+description = "uses ../utils"
+
+[dependencies]
+utils = { path = "../utils", version = "0.1" }
+serde = "1.0"
+other = { path = "../other" }
+
+[dev-dependencies.auth]
+path = "../auth/"
+
+[target.'cfg(unix)'.dependencies]
+utils-unix = { path = "../utils", package = "utils" }
+
+[patch.crates-io]
+auth = { path = "../auth" }
+"#;
+        let linked = replace_cargo_paths(manifest, &shared(&["utils", "auth"])).unwrap();
+        let dir = SHARED_PACKAGES_DIR;
+        assert_eq!(
+            linked,
+            format!(
+                r#"[package]
+name = "hello"
+# The path of a comment: "../utils"
+description = "uses ../utils"
+
+[dependencies]
+utils = {{ path = "{dir}/utils", version = "0.1" }}
+serde = "1.0"
+other = {{ path = "../other" }}
+
+[dev-dependencies.auth]
+path = "{dir}/auth"
+
+[target.'cfg(unix)'.dependencies]
+utils-unix = {{ path = "{dir}/utils", package = "utils" }}
+
+[patch.crates-io]
+auth = {{ path = "{dir}/auth" }}
+"#
+            )
+        );
+
+        let error = replace_cargo_paths("[dependencies\n", &shared(&["utils"])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_link_shared_packages_checks_names() {
+        let dir = function_with_shared(&["utils"], &[]);
+        for name in ["../utils", "a/b", "missing"] {
+            let error = link_shared_packages(dir.path(), "rust", &shared(&[name])).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{name}");
+        }
+        let error = link_shared_packages(dir.path(), "python", &shared(&["utils"])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
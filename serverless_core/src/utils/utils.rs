@@ -1,22 +1,34 @@
 use axum::body::Body;
+use axum::http::header::{
+    HeaderValue, ACCEPT_ENCODING, CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+    COOKIE, ETAG, HOST, LOCATION, VARY,
+};
 use axum::http::{
     HeaderMap, Request as AxumRequest, Response as AxumResponse, StatusCode as AxumStatusCode,
     StatusCode,
 };
-use axum::response::IntoResponse;
-use hyper::body::to_bytes;
+use axum::response::{IntoResponse, Response};
+use futures_util::stream;
+use hyper::body::{Bytes, HttpBody};
 use reqwest::header::HeaderMap as ReqwestHeaderMap;
 use reqwest::Client;
 use reqwest::StatusCode as ReqwestStatusCode;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, warn};
 use urlencoding::encode;
 use uuid::Uuid;
 
+use super::compression::{self, DecompressError, Encoding, MIN_COMPRESSIBLE_SIZE};
+use super::http_cache::weaken_etag;
+use super::image::IMAGE_RUNTIME;
+
 /// A RAII guard that runs a closure when dropped.
 ///
 /// This is useful for deferring code until the scope exits.
@@ -45,6 +57,53 @@ pub fn defer_fn<T: FnMut()>(c: T) -> ScopeCall<T> {
     ScopeCall { c: Some(c) }
 }
 
+/// Body size limits enforced while proxying an invocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    /// Larger request bodies are rejected with 413
+    pub max_request_bytes: usize,
+    /// Larger response bodies are dropped and a 502 returned
+    pub max_response_bytes: usize,
+}
+
+/// Per-function behaviour of the invocation proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyOptions {
+    pub limits: BodyLimits,
+    /// Compress responses for clients that accept gzip/br and decode compressed request
+    /// bodies, for functions that don't handle encodings themselves
+    pub compression: bool,
+    /// How long to wait for the function to respond
+    pub timeout: Duration,
+    /// Replace the function container's address in error responses
+    pub hide_internal_addresses: bool,
+    /// Forward the request body and query string exactly as received: compressed bodies
+    /// aren't decoded and the query isn't parsed and re-encoded
+    pub raw_body: bool,
+}
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// Headers that only concern a single connection, never forwarded by a proxy
+/// (RFC 9110, section 7.6.1)
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// What a function container's address is replaced with when hidden
+const HIDDEN_ADDRESS: &str = "function";
+
+/// Seconds the proxy waits for a function that doesn't set `timeout_secs`
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
 /// Converts a map of environment variables into a string in the format:
 /// `ENV key="value"\n` for each variable.
 pub fn envs_to_string(envs: HashMap<String, String>) -> String {
@@ -55,6 +114,14 @@ pub fn envs_to_string(envs: HashMap<String, String>) -> String {
     envs_str
 }
 
+/// Converts the names of build args into a string in the format:
+/// `ARG name\n` for each, sorted so the Dockerfile doesn't change between builds.
+pub fn build_args_to_string(build_args: &HashMap<String, String>) -> String {
+    let mut names: Vec<&String> = build_args.keys().collect();
+    names.sort();
+    names.iter().map(|name| format!("ARG {}\n", name)).collect()
+}
+
 /// Converts a reqwest status code into an Axum status code.
 /// Falls back to `INTERNAL_SERVER_ERROR` if the conversion fails.
 fn convert_status_code(reqwest_status: ReqwestStatusCode) -> AxumStatusCode {
@@ -73,12 +140,12 @@ fn convert_axum_headers_to_req_header(headers: HeaderMap) -> ReqwestHeaderMap {
 
 /// Converts reqwest headers into Axum headers.
 ///
-/// Removes the `TRANSFER_ENCODING` header from the source before copying.
+/// Removes the hop-by-hop headers from the source before copying.
 fn convert_req_header_to_axum_headers(
     req_headers: &mut ReqwestHeaderMap,
     res_headers: &mut HeaderMap,
 ) {
-    req_headers.remove(http::header::TRANSFER_ENCODING);
+    strip_hop_by_hop(req_headers);
 
     for (hn, hv) in req_headers.iter() {
         debug!("Converting header - {}: {:?}", hn, hv.to_str());
@@ -86,6 +153,146 @@ fn convert_req_header_to_axum_headers(
     }
 }
 
+/// Value of a cookie the request carries, if any
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Removes hop-by-hop headers, along with those the `Connection` header lists.
+///
+/// Applied in both directions, so neither the function nor the client sees connection
+/// details meant for the other hop.
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<String> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    for name in HOP_BY_HOP_HEADERS
+        .iter()
+        .copied()
+        .chain(listed.iter().map(String::as_str))
+    {
+        headers.remove(name);
+    }
+}
+
+/// Replaces the function container's address (`host:port`, or the host alone) in an
+/// error response's body, so clients don't learn the internal network layout.
+///
+/// The host is only replaced where it stands on its own, so `10.0.0.1` doesn't touch
+/// `10.0.0.12`. Encoded and non-UTF-8 bodies are passed through untouched.
+fn hide_internal_address(
+    body: Vec<u8>,
+    addr: &str,
+    status: AxumStatusCode,
+    headers: &mut ReqwestHeaderMap,
+) -> Vec<u8> {
+    if !(status.is_client_error() || status.is_server_error())
+        || headers.contains_key(CONTENT_ENCODING)
+    {
+        return body;
+    }
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    match String::from_utf8(body) {
+        Ok(text) if !host.is_empty() && text.contains(host) => {
+            let hidden = replace_standalone(&replace_standalone(&text, addr), host);
+            if hidden != text {
+                headers.remove(CONTENT_LENGTH);
+            }
+            hidden.into_bytes()
+        }
+        Ok(text) => text.into_bytes(),
+        Err(e) => e.into_bytes(),
+    }
+}
+
+/// Replaces the occurrences of `needle` in `text` that aren't part of a longer name or
+/// address with [`HIDDEN_ADDRESS`]
+fn replace_standalone(text: &str, needle: &str) -> String {
+    let part_of_name = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_');
+    let mut hidden = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(needle) {
+        let (before, after) = (&rest[..index], &rest[index + needle.len()..]);
+        let standalone = !before.ends_with(part_of_name)
+            && !after
+                .strip_prefix('.')
+                .unwrap_or(after)
+                .starts_with(|c: char| c.is_ascii_alphanumeric())
+            && !after.starts_with(['-', '_']);
+        hidden.push_str(before);
+        hidden.push_str(if standalone { HIDDEN_ADDRESS } else { needle });
+        rest = after;
+    }
+    hidden.push_str(rest);
+    hidden
+}
+
+/// Points a `Location` the function answered with at the caller's `public_host` when it
+/// names the function container (`addr`, or its host on the scheme's default port).
+///
+/// Only the authority of an absolute URL is replaced; the scheme, path, query and
+/// fragment are kept, and relative references or other hosts are left alone.
+///
+/// # Returns
+///
+/// The rewritten location, or `None` when it doesn't need rewriting.
+fn rewrite_location(location: &str, addr: &str, public_host: &str) -> Option<String> {
+    let (scheme, rest) = location.split_once("://")?;
+    let scheme_is_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !scheme_is_valid {
+        return None;
+    }
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(end);
+    // Credentials in the authority go with it
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+
+    let default_port = match scheme.to_ascii_lowercase().as_str() {
+        "http" => "80",
+        "https" => "443",
+        _ => return None,
+    };
+    let (addr_host, addr_port) = addr.rsplit_once(':').unwrap_or((addr, default_port));
+    let (host, port) = match host_port.rsplit_once(':') {
+        // A trailing `]` is an IPv6 address without a port
+        Some((host, port)) if !port.ends_with(']') => (host, port),
+        _ => (host_port, default_port),
+    };
+    if !host.eq_ignore_ascii_case(addr_host) || port != addr_port {
+        return None;
+    }
+    Some(format!("{scheme}://{public_host}{tail}"))
+}
+
+/// Rewrites the response's `Location` header with [`rewrite_location`]
+fn hide_location(headers: &mut ReqwestHeaderMap, addr: &str, public_host: &str) {
+    let rewritten = headers
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| rewrite_location(location, addr, public_host))
+        .and_then(|location| HeaderValue::from_str(&location).ok());
+    if let Some(location) = rewritten {
+        headers.insert(LOCATION, location);
+    }
+}
+
 /// Creates a URL from the given address, key, and query parameters.
 ///
 /// The query parameters are URL-encoded.
@@ -116,21 +323,34 @@ fn create_url(addr: &str, key: &str, query: HashMap<String, String>) -> String {
     url
 }
 
+/// How long the function took to respond, from sending it the request until its whole
+/// response arrived. Set as an extension on responses returned by [`make_request`] that
+/// came from the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionTime(pub Duration);
+
 /// Forwards an incoming Axum request to a downstream service.
 ///
 /// This function builds an HTTP request to the given service address and key,
 /// forwarding the method, headers, and body of the original request.
 ///
-/// It supports GET, POST, PUT, PATCH, HEAD and DELETE methods. For other methods, a `METHOD_NOT_ALLOWED`
-/// response is returned.
+/// Any method is forwarded. Request bodies are streamed to the function byte for byte,
+/// binary and multipart alike, unless a compressed body has to be decoded first, which
+/// happens in memory.
 ///
 /// # Arguments
 ///
 /// * `addr` - The downstream service address.
 /// * `key` - The function key to call on the downstream service.
-/// * `query` - Query parameters to include in the request URL.
+/// * `query` - Query parameters to include in the request URL; with `options.raw_body`,
+///   the request's own query string is forwarded instead.
 /// * `headers` - The headers from the original request.
 /// * `req` - The original Axum request.
+/// * `options` - Body limits, compression, timeout and whether to hide the container's
+///   address. Bodies are counted as they stream, so a missing or wrong `Content-Length`
+///   doesn't get around the limits.
+///
+/// Hop-by-hop headers are stripped from both the request and the response.
 ///
 /// # Returns
 ///
@@ -139,111 +359,407 @@ pub async fn make_request(
     addr: &str,
     key: &str,
     query: HashMap<String, String>,
-    headers: HeaderMap,
+    mut headers: HeaderMap,
     req: AxumRequest<Body>,
-) -> impl IntoResponse {
+    options: ProxyOptions,
+) -> Response {
+    let limits = options.limits;
+    let accept_encoding = headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    // Redirects to the function container point at the host the caller reached instead
+    let public_host = headers
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(HIDDEN_ADDRESS)
+        .to_string();
+    strip_hop_by_hop(&mut headers);
+
     let client = Client::builder()
-        .timeout(Duration::from_secs(60))
+        .timeout(options.timeout)
         .build()
         .expect("Failed to build HTTP client");
 
-    // Choose the appropriate client method based on the request method.
-    let method = req.method().clone();
-    let response_result = match method {
-        http::Method::GET => {
-            client
-                .get(create_url(addr, key, query))
-                .headers(convert_axum_headers_to_req_header(headers))
-                .send()
-                .await
-        }
-        _ => {
-            let body_bytes = match to_bytes(req.into_body()).await {
+    // Functions in raw body mode get the query string as the client wrote it
+    let url = match req.uri().query() {
+        Some(raw_query) if options.raw_body => format!("http://{}/{}?{}", addr, key, raw_query),
+        _ => create_url(addr, key, query),
+    };
+    let (parts, body) = req.into_parts();
+
+    // Decode compressed bodies so the function sees plain content, unless it asked for
+    // them raw
+    let request_encoding = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Encoding::from_header)
+        .filter(|_| options.compression && !options.raw_body);
+    let too_large = Arc::new(AtomicBool::new(false));
+    let request_body = match request_encoding {
+        Some(encoding) => {
+            let body_bytes = match read_limited(body, limits.max_request_bytes).await {
                 Ok(bytes) => bytes,
-                Err(err) => {
+                Err(LimitedReadError::TooLarge) => {
+                    warn!(
+                        "Request body for {} exceeds {} bytes",
+                        key, limits.max_request_bytes
+                    );
+                    return request_too_large(limits.max_request_bytes);
+                }
+                Err(LimitedReadError::Body(err)) => {
                     error!("Error reading request body: {:?}", err);
                     return AxumResponse::builder()
                         .status(StatusCode::BAD_REQUEST)
                         .body("Could not read request body".to_owned())
-                        .unwrap();
+                        .unwrap()
+                        .into_response();
                 }
             };
-
-            let request_builder = match method {
-                http::Method::POST => client.post(create_url(addr, key, query)),
-                http::Method::PUT => client.put(create_url(addr, key, query)),
-                http::Method::PATCH => client.patch(create_url(addr, key, query)),
-                http::Method::DELETE => client.delete(create_url(addr, key, query)),
-                http::Method::HEAD => client.head(create_url(addr, key, query)),
-                _ => {
+            match compression::decompress_blocking(body_bytes, encoding, limits.max_request_bytes)
+                .await
+            {
+                Ok(decoded) => {
+                    headers.remove(CONTENT_ENCODING);
+                    headers.remove(CONTENT_LENGTH);
+                    Some(reqwest::Body::from(decoded))
+                }
+                Err(DecompressError::TooLarge) => {
+                    warn!(
+                        "Decoded request body for {} exceeds {} bytes",
+                        key, limits.max_request_bytes
+                    );
+                    return request_too_large(limits.max_request_bytes);
+                }
+                Err(DecompressError::Invalid(err)) => {
+                    warn!(
+                        "Invalid {} request body for {}: {}",
+                        encoding.as_str(),
+                        key,
+                        err
+                    );
                     return AxumResponse::builder()
-                        .status(StatusCode::METHOD_NOT_ALLOWED)
+                        .status(StatusCode::BAD_REQUEST)
                         .body(format!(
-                            "We don't currently support {} functions",
-                            method
+                            "Could not decode {} request body",
+                            encoding.as_str()
                         ))
-                        .unwrap();
-                },
-            };
-
-            request_builder
-                .headers(convert_axum_headers_to_req_header(headers))
-                .body(body_bytes)
-                .send()
-                .await            
+                        .unwrap()
+                        .into_response();
+                }
+            }
+        }
+        None if body.is_end_stream() => None,
+        None => {
+            if body
+                .size_hint()
+                .exact()
+                .is_some_and(|len| len > limits.max_request_bytes as u64)
+            {
+                warn!(
+                    "Request body for {} exceeds {} bytes",
+                    key, limits.max_request_bytes
+                );
+                return request_too_large(limits.max_request_bytes);
+            }
+            Some(stream_limited(
+                body,
+                limits.max_request_bytes,
+                too_large.clone(),
+            ))
         }
     };
 
+    let mut request = client
+        .request(parts.method, url)
+        .headers(convert_axum_headers_to_req_header(headers));
+    if let Some(body) = request_body {
+        request = request.body(body);
+    }
+
+    let sent_at = Instant::now();
+    let response_result = request.send().await;
+
     // Process the downstream service response.
     let response = match response_result {
         Ok(res) => {
             let status = convert_status_code(res.status());
             let mut downstream_headers = res.headers().clone();
 
-            // Attempt to read the response text.
-            match res.text().await {
-                Ok(text) => {
-                    let mut response = AxumResponse::builder().status(status).body(text).unwrap();
+            // Read the response, giving up as soon as it exceeds the limit.
+            match read_limited_response(res, limits.max_response_bytes).await {
+                Ok(body) => {
+                    let function_time = FunctionTime(sent_at.elapsed());
+                    let body = if options.hide_internal_addresses {
+                        hide_location(&mut downstream_headers, addr, &public_host);
+                        hide_internal_address(body, addr, status, &mut downstream_headers)
+                    } else {
+                        body
+                    };
+                    let body = if options.compression {
+                        compress_response(body, &mut downstream_headers, accept_encoding.as_deref())
+                            .await
+                    } else {
+                        Bytes::from(body)
+                    };
+                    let mut response = AxumResponse::builder()
+                        .status(status)
+                        .body(Body::from(body))
+                        .unwrap();
                     let headers_mut = response.headers_mut();
                     convert_req_header_to_axum_headers(&mut downstream_headers, headers_mut);
+                    let mut response = response.into_response();
+                    response.extensions_mut().insert(function_time);
                     response
                 }
-                Err(err) => {
+                Err(LimitedReadError::TooLarge) => {
+                    warn!(
+                        "Response from {} exceeds {} bytes",
+                        key, limits.max_response_bytes
+                    );
+                    AxumResponse::builder()
+                        .status(StatusCode::BAD_GATEWAY)
+                        .body(format!(
+                            "Function response exceeds the {} byte limit",
+                            limits.max_response_bytes
+                        ))
+                        .unwrap()
+                        .into_response()
+                }
+                Err(LimitedReadError::Body(err)) => {
                     error!("Failed to read downstream response: {:?}", err);
                     AxumResponse::builder()
                         .status(StatusCode::INTERNAL_SERVER_ERROR)
                         .body("Failed to read downstream response".to_owned())
                         .unwrap()
+                        .into_response()
                 }
             }
         }
+        // The body was cut off on its way to the function
+        Err(_) if too_large.load(Ordering::SeqCst) => {
+            warn!(
+                "Request body for {} exceeds {} bytes",
+                key, limits.max_request_bytes
+            );
+            request_too_large(limits.max_request_bytes)
+        }
         Err(e) => {
             error!("Error making downstream request: {:?}", e);
             AxumResponse::builder()
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .body("Failed to make downstream request".to_string())
                 .unwrap()
+                .into_response()
         }
     };
 
     response
 }
 
+/// Why a size-limited body read stopped
+#[derive(Debug)]
+enum LimitedReadError {
+    /// The body grew past the limit
+    TooLarge,
+    /// The body could not be read
+    Body(String),
+}
+
+/// Read an incoming request body, failing once more than `max_bytes` have arrived
+async fn read_limited(mut body: Body, max_bytes: usize) -> Result<Bytes, LimitedReadError> {
+    if body
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(LimitedReadError::TooLarge);
+    }
+
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| LimitedReadError::Body(e.to_string()))?;
+        if buffer.len() + chunk.len() > max_bytes {
+            return Err(LimitedReadError::TooLarge);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+/// Stream a request body to the function, failing once more than `max_bytes` have passed.
+///
+/// `too_large` is set when the body is cut off for exceeding the limit, so the caller can
+/// tell it apart from a failed request.
+fn stream_limited(body: Body, max_bytes: usize, too_large: Arc<AtomicBool>) -> reqwest::Body {
+    let chunks = stream::unfold(Some((body, 0)), move |state| {
+        let too_large = too_large.clone();
+        async move {
+            let (mut body, sent) = state?;
+            let chunk = match body.data().await? {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    let error = std::io::Error::other(e);
+                    return Some((Err(error), None));
+                }
+            };
+            let sent = sent + chunk.len();
+            if sent > max_bytes {
+                too_large.store(true, Ordering::SeqCst);
+                let error = std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("request body exceeds {} bytes", max_bytes),
+                );
+                return Some((Err(error), None));
+            }
+            Some((Ok(chunk), Some((body, sent))))
+        }
+    });
+    reqwest::Body::wrap_stream(chunks)
+}
+
+/// Read a request body into memory ahead of [`make_request`], e.g. to keep a copy of it.
+///
+/// Fails with the response [`make_request`] would have given when the body is over
+/// `max_bytes` or can't be read.
+pub async fn buffer_body(body: Body, max_bytes: usize) -> Result<Bytes, Response> {
+    match read_limited(body, max_bytes).await {
+        Ok(bytes) => Ok(bytes),
+        Err(LimitedReadError::TooLarge) => Err(request_too_large(max_bytes)),
+        Err(LimitedReadError::Body(err)) => {
+            error!("Error reading request body: {:?}", err);
+            Err(AxumResponse::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body("Could not read request body".to_owned())
+                .unwrap()
+                .into_response())
+        }
+    }
+}
+
+/// Read a downstream response body, failing once more than `max_bytes` have arrived
+async fn read_limited_response(
+    mut res: reqwest::Response,
+    max_bytes: usize,
+) -> Result<Vec<u8>, LimitedReadError> {
+    if res
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(LimitedReadError::TooLarge);
+    }
+
+    let mut buffer = Vec::new();
+    while let Some(chunk) = res
+        .chunk()
+        .await
+        .map_err(|e| LimitedReadError::Body(e.to_string()))?
+    {
+        if buffer.len() + chunk.len() > max_bytes {
+            return Err(LimitedReadError::TooLarge);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(buffer)
+}
+
+/// 413 response for a request body over the limit
+fn request_too_large(max_request_bytes: usize) -> Response {
+    AxumResponse::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(format!(
+            "Request body exceeds the {} byte limit",
+            max_request_bytes
+        ))
+        .unwrap()
+        .into_response()
+}
+
+/// Compress a function's response for the client, updating `headers` to match.
+///
+/// Bodies the function already encoded, small bodies and content that doesn't compress
+/// well (images, archives) are passed through untouched. Compression runs on the
+/// blocking thread pool.
+async fn compress_response(
+    body: Vec<u8>,
+    headers: &mut ReqwestHeaderMap,
+    accept_encoding: Option<&str>,
+) -> Bytes {
+    let body = Bytes::from(body);
+    if headers.contains_key(CONTENT_ENCODING) || body.len() < MIN_COMPRESSIBLE_SIZE {
+        return body;
+    }
+    let compressible = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(compression::is_compressible);
+    if !compressible {
+        return body;
+    }
+
+    // The representation now depends on Accept-Encoding, so caches must key on it
+    headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(encoding) = accept_encoding.and_then(compression::negotiate) else {
+        return body;
+    };
+
+    match compression::compress_blocking(body.clone(), encoding).await {
+        Ok(compressed) => {
+            headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            headers.remove(CONTENT_LENGTH);
+            // The function's validator no longer identifies these exact bytes
+            if let Some(etag) = headers.get(ETAG).map(weaken_etag) {
+                headers.insert(ETAG, etag);
+            }
+            Bytes::from(compressed)
+        }
+        Err(err) => {
+            warn!("Failed to {} response: {}", encoding.as_str(), err);
+            body
+        }
+    }
+}
+
+/// The client's address: the peer, or the address the proxy in front of the controller
+/// appended to `X-Forwarded-For` when that proxy is trusted
+pub fn client_ip(
+    peer: SocketAddr,
+    headers: &HeaderMap,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    if !trust_forwarded_for {
+        return Some(peer.ip());
+    }
+    let forwarded = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last();
+    match forwarded {
+        Some(ip) => ip.trim().parse().ok(),
+        None => Some(peer.ip()),
+    }
+}
+
 /// Creates a base file structure for a function.
 ///
-/// If the specified path already exists, an error is returned. Otherwise, the
-/// directory is created and a `main.go` file is initialized in that directory.
+/// The directory is created if needed, and the runtime's entrypoint file (`main.go`,
+/// `server.ts`) is initialized in it.
 ///
 /// # Arguments
 ///
 /// * `path` - The directory path where the function files will be created.
-/// * `name` - The name of the function (used in error messages).
-/// * `_runtime` - The runtime (currently unused, but reserved for future use).
+/// * `runtime` - The runtime of the function.
 ///
 /// # Returns
 ///
-/// A `Result` containing the created `File` handle for `main.go` or an `std::io::Error`.
-pub fn create_fn_files_base(path: &PathBuf, runtime: &str) -> std::io::Result<File> {
+/// The created entrypoint file, or `None` for Rust functions, whose `main` the
+/// `#[invok::handler]` macro generates, and functions deployed from an image.
+pub fn create_fn_files_base(path: &PathBuf, runtime: &str) -> std::io::Result<Option<File>> {
     if !path.exists() {
         fs::create_dir(path)?;
     }
@@ -251,12 +767,13 @@ pub fn create_fn_files_base(path: &PathBuf, runtime: &str) -> std::io::Result<Fi
     let function_file = match runtime {
         "go" => "main.go",
         "nodejs" => "server.ts",
+        "rust" | IMAGE_RUNTIME => return Ok(None),
         _ => "",
     };
     let main_file_path = path.join(function_file);
     let main_file = File::create(&main_file_path)?;
 
-    Ok(main_file)
+    Ok(Some(main_file))
 }
 
 pub fn generate_hash(source: Uuid) -> String {
@@ -265,3 +782,407 @@ pub fn generate_hash(source: Uuid) -> String {
 
     uuid_short.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    const ADDR: &str = "172.17.0.5:8080";
+
+    #[test]
+    fn test_rewrite_location() {
+        let rewrite = |location| rewrite_location(location, ADDR, "api.example.com");
+        assert_eq!(
+            rewrite("http://172.17.0.5:8080/invok/ns/fn/next?a=1#top").as_deref(),
+            Some("http://api.example.com/invok/ns/fn/next?a=1#top")
+        );
+        assert_eq!(
+            rewrite("http://user:pw@172.17.0.5:8080").as_deref(),
+            Some("http://api.example.com")
+        );
+        // The address appearing outside the authority is left as is
+        assert_eq!(
+            rewrite("http://172.17.0.5:8080/?next=http://172.17.0.5:8080/x").as_deref(),
+            Some("http://api.example.com/?next=http://172.17.0.5:8080/x")
+        );
+        assert_eq!(rewrite("https://example.com/?next=172.17.0.5:8080"), None);
+        assert_eq!(rewrite("http://172.17.0.5:9090/"), None);
+        assert_eq!(rewrite("http://172.17.0.50:8080/"), None);
+        assert_eq!(rewrite("/relative/172.17.0.5:8080"), None);
+        assert_eq!(rewrite("next"), None);
+
+        // Without a port, the address is the host on the scheme's default port
+        assert_eq!(
+            rewrite_location("http://10.0.0.1/a", "10.0.0.1", "api").as_deref(),
+            Some("http://api/a")
+        );
+        assert_eq!(
+            rewrite_location("http://10.0.0.1:81/a", "10.0.0.1", "api"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hide_location() {
+        let mut headers = ReqwestHeaderMap::new();
+        headers.insert(
+            LOCATION,
+            HeaderValue::from_static("http://172.17.0.5:8080/login"),
+        );
+        hide_location(&mut headers, ADDR, "localhost:3000");
+        assert_eq!(headers[LOCATION], "http://localhost:3000/login");
+
+        headers.insert(LOCATION, HeaderValue::from_static("/login"));
+        hide_location(&mut headers, ADDR, "localhost:3000");
+        assert_eq!(headers[LOCATION], "/login");
+    }
+
+    #[test]
+    fn test_hide_internal_address() {
+        let hide = |body: &str, status| {
+            let mut headers = ReqwestHeaderMap::new();
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+            let hidden =
+                hide_internal_address(body.as_bytes().to_vec(), ADDR, status, &mut headers);
+            (
+                String::from_utf8(hidden).unwrap(),
+                headers.contains_key(CONTENT_LENGTH),
+            )
+        };
+        assert_eq!(
+            hide(
+                "dial 172.17.0.5:8080 failed; 172.17.0.5 unreachable",
+                StatusCode::BAD_GATEWAY
+            ),
+            (
+                "dial function failed; function unreachable".to_string(),
+                false
+            )
+        );
+        // Longer addresses and names that merely contain the host are kept
+        assert_eq!(
+            hide("172.17.0.50 and 172.17.0.5.nip.io", StatusCode::NOT_FOUND),
+            ("172.17.0.50 and 172.17.0.5.nip.io".to_string(), true)
+        );
+        assert_eq!(
+            hide("at 172.17.0.5.", StatusCode::NOT_FOUND),
+            ("at function.".to_string(), false)
+        );
+        // Successful responses are not touched
+        assert_eq!(
+            hide("172.17.0.5:8080", StatusCode::OK),
+            ("172.17.0.5:8080".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("close, X-Internal"));
+        headers.insert("x-internal", HeaderValue::from_static("1"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("transfer-encoding", HeaderValue::from_static("chunked"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        strip_hop_by_hop(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[CONTENT_TYPE], "text/plain");
+    }
+
+    /// Size of the binary bodies sent through the proxy, large enough to arrive in many
+    /// chunks
+    const BINARY_FIXTURE_SIZE: usize = 8 * 1024 * 1024;
+
+    const MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+    /// Pseudo-random bytes covering every byte value, the same on every run
+    fn binary_fixture(size: usize) -> Vec<u8> {
+        use rand::{RngCore, SeedableRng};
+
+        let mut fixture = vec![0; size];
+        rand::rngs::StdRng::seed_from_u64(7).fill_bytes(&mut fixture);
+        fixture
+    }
+
+    fn proxy_options(raw_body: bool) -> ProxyOptions {
+        ProxyOptions {
+            limits: BodyLimits {
+                max_request_bytes: MAX_REQUEST_BYTES,
+                max_response_bytes: 4 * MAX_REQUEST_BYTES,
+            },
+            compression: true,
+            timeout: Duration::from_secs(30),
+            hide_internal_addresses: false,
+            raw_body,
+        }
+    }
+
+    /// A function answering with the request body it got, and with its method, path, raw
+    /// query, `Content-Type` and `Content-Encoding` in `X-Echo-*` headers
+    fn echo_function() -> String {
+        async fn echo(
+            method: Method,
+            uri: axum::http::Uri,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> AxumResponse<Body> {
+            let header = |name| {
+                headers
+                    .get(name)
+                    .cloned()
+                    .unwrap_or(HeaderValue::from_static(""))
+            };
+            AxumResponse::builder()
+                .header("x-echo-method", method.as_str())
+                .header("x-echo-path", uri.path())
+                .header("x-echo-query", uri.query().unwrap_or_default())
+                .header("x-echo-content-type", header(CONTENT_TYPE))
+                .header("x-echo-content-encoding", header(CONTENT_ENCODING))
+                .header(CONTENT_TYPE, "application/octet-stream")
+                .body(Body::from(body))
+                .unwrap()
+        }
+
+        let app = axum::Router::new()
+            .fallback(echo)
+            .layer(axum::extract::DefaultBodyLimit::disable());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        addr.to_string()
+    }
+
+    /// Proxies a request to `addr` the way an invocation is
+    async fn proxy(
+        addr: &str,
+        method: Method,
+        uri: &str,
+        headers: &[(axum::http::HeaderName, &str)],
+        body: Body,
+        options: ProxyOptions,
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        let request = AxumRequest::builder()
+            .method(method)
+            .uri(uri)
+            .body(body)
+            .unwrap();
+        let query = reqwest::Url::parse(&format!("http://localhost{uri}"))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect();
+        let response = make_request(addr, "ns/echo", query, header_map, request, options).await;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, headers, body)
+    }
+
+    /// A body sent in 64KB chunks, without a length
+    fn chunked(data: &[u8]) -> Body {
+        let chunks = data
+            .chunks(64 * 1024)
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        Body::wrap_stream(stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn test_make_request_binary_bodies() {
+        let addr = echo_function();
+        let fixture = binary_fixture(BINARY_FIXTURE_SIZE);
+        let octet_stream = [(CONTENT_TYPE, "application/octet-stream")];
+
+        for method in [
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ] {
+            let (status, headers, body) = proxy(
+                &addr,
+                method.clone(),
+                "/invok/ns/echo",
+                &octet_stream,
+                Body::from(fixture.clone()),
+                proxy_options(false),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{method}");
+            assert_eq!(headers["x-echo-method"], method.as_str());
+            assert_eq!(headers["x-echo-path"], "/ns/echo");
+            assert!(
+                body == fixture,
+                "{method} body changed on the way: {} bytes came back",
+                body.len()
+            );
+        }
+
+        // Streamed bodies without a length arrive whole too
+        let (status, _, body) = proxy(
+            &addr,
+            Method::POST,
+            "/invok/ns/echo",
+            &octet_stream,
+            chunked(&fixture),
+            proxy_options(false),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body == fixture, "streamed body changed on the way");
+
+        let (status, headers, body) = proxy(
+            &addr,
+            Method::HEAD,
+            "/invok/ns/echo",
+            &[],
+            Body::empty(),
+            proxy_options(false),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-echo-method"], "HEAD");
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_make_request_multipart() {
+        let addr = echo_function();
+        let fixture = binary_fixture(BINARY_FIXTURE_SIZE);
+        let boundary = "invok-fixture-boundary";
+        let mut form = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nfixture\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; \
+             filename=\"fixture.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        form.extend_from_slice(&fixture);
+        form.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        let content_type = format!("multipart/form-data; boundary={boundary}");
+
+        let (status, headers, body) = proxy(
+            &addr,
+            Method::POST,
+            "/invok/ns/echo",
+            &[(CONTENT_TYPE, &content_type)],
+            chunked(&form),
+            proxy_options(false),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-echo-content-type"], content_type.as_str());
+        assert!(body == form, "multipart body changed on the way");
+    }
+
+    #[tokio::test]
+    async fn test_make_request_compressed_bodies() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let addr = echo_function();
+        let fixture = binary_fixture(BINARY_FIXTURE_SIZE / 8);
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&fixture).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let gzip = [(CONTENT_ENCODING, "gzip")];
+
+        // With compression on, gzip bodies are decoded for the function
+        let (status, headers, body) = proxy(
+            &addr,
+            Method::POST,
+            "/invok/ns/echo",
+            &gzip,
+            Body::from(compressed.clone()),
+            proxy_options(false),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-echo-content-encoding"], "");
+        assert!(body == fixture, "gzip body not decoded");
+
+        // Unless the function takes its bodies raw
+        let (status, headers, body) = proxy(
+            &addr,
+            Method::PUT,
+            "/invok/ns/echo",
+            &gzip,
+            Body::from(compressed.clone()),
+            proxy_options(true),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-echo-content-encoding"], "gzip");
+        assert!(body == compressed, "raw body was decoded");
+    }
+
+    #[tokio::test]
+    async fn test_make_request_raw_query() {
+        let addr = echo_function();
+        let uri = "/invok/ns/echo?b=2&a=x%20y&flag";
+
+        let (_, headers, _) = proxy(
+            &addr,
+            Method::GET,
+            uri,
+            &[],
+            Body::empty(),
+            proxy_options(true),
+        )
+        .await;
+        assert_eq!(headers["x-echo-query"], "b=2&a=x%20y&flag");
+
+        // Otherwise the query is parsed and encoded again
+        let (_, headers, _) = proxy(
+            &addr,
+            Method::GET,
+            uri,
+            &[],
+            Body::empty(),
+            proxy_options(false),
+        )
+        .await;
+        let query = headers["x-echo-query"].to_str().unwrap();
+        let mut params: Vec<_> = query.split('&').collect();
+        params.sort();
+        assert_eq!(params, ["a=x%20y", "b=2", "flag="]);
+    }
+
+    #[tokio::test]
+    async fn test_make_request_body_limit() {
+        let addr = echo_function();
+        let fixture = binary_fixture(2 * MAX_REQUEST_BYTES);
+
+        // Bodies with a length over the limit are refused before reaching the function
+        let (status, _, _) = proxy(
+            &addr,
+            Method::POST,
+            "/invok/ns/echo",
+            &[],
+            Body::from(fixture.clone()),
+            proxy_options(false),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Without one, they are cut off as they stream
+        let (status, _, _) = proxy(
+            &addr,
+            Method::POST,
+            "/invok/ns/echo",
+            &[],
+            chunked(&fixture),
+            proxy_options(true),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,565 @@
+//! Harness of the end-to-end tests.
+//!
+//! Runs the controller the way a Kubernetes pod with a DinD sidecar does: a
+//! Docker-in-Docker daemon, and the controller image sharing its network namespace so
+//! it dials function containers on the daemon's `bridge` network. Postgres and Redis run
+//! in containers of their own. Tests only talk to the controller over HTTP.
+//!
+//! The controller image is not built here; build it from the repository root first:
+//!
+//! ```bash
+//! docker build -t invok-core:e2e .
+//! ```
+//!
+//! `INVOK_E2E_IMAGE` names another image, e.g. one built in CI.
+//!
+//! [`Harness::start_replicas`] runs more controllers the same way, each on a port of its
+//! own, sharing the pools through Redis.
+
+use reqwest::multipart;
+use serde_json::Value;
+use shared_utils::{compress_dir_with_excludes, to_camel_case_handler};
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+use templates::nodejs_template;
+use testcontainers::core::{IntoContainerPort, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::redis::Redis;
+
+/// Controller image used when `INVOK_E2E_IMAGE` isn't set
+const DEFAULT_IMAGE: &str = "invok-core:e2e";
+
+/// Docker-in-Docker image the functions run on
+const DIND_IMAGE: (&str, &str) = ("docker", "27-dind");
+
+/// Port the controller listens on, published by the DinD container whose network
+/// namespace it shares; further replicas listen on the ports after it
+const CONTROLLER_PORT: u16 = 3000;
+
+/// How long the controller may take to become ready
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Deploys build an image from scratch, pulling the runtime's base images; they run in
+/// the background and are polled for
+const DEPLOY_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// How long requests other than deploys may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often a condition that isn't met yet is checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub type HarnessResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The controller and its dependencies, removed when dropped
+pub struct Harness {
+    base_url: String,
+    client: reqwest::Client,
+    controller: ContainerAsync<GenericImage>,
+    /// Base URL and container of each controller after the first
+    replicas: Vec<(String, ContainerAsync<GenericImage>)>,
+    _docker: ContainerAsync<GenericImage>,
+    _postgres: ContainerAsync<Postgres>,
+    _redis: ContainerAsync<Redis>,
+}
+
+/// An account registered on the controller
+pub struct Session {
+    pub token: String,
+    pub namespace: String,
+}
+
+/// A function deployed by the tests, as its files
+pub struct SampleFunction {
+    pub name: String,
+    files: Vec<(&'static str, String)>,
+}
+
+impl SampleFunction {
+    /// A Go function answering `Hello World!`, logging the `id` query parameter
+    pub fn go(name: &str, settings: Value) -> Self {
+        let handler = to_camel_case_handler(name);
+        let source = format!(
+            r#"package main
+
+import (
+    "log"
+    "net/http"
+)
+
+func {handler}(w http.ResponseWriter, r *http.Request) {{
+    log.Printf("e2e invocation %s", r.URL.Query().Get("id"))
+    w.WriteHeader(http.StatusOK)
+    w.Write([]byte("Hello World!"))
+}}
+"#
+        );
+        Self {
+            name: name.to_string(),
+            files: vec![
+                ("config.json", config(name, "go", settings)),
+                ("function.go", source),
+            ],
+        }
+    }
+
+    /// A Go function answering with the request body it got, and with its method, raw
+    /// query, `Content-Type` and `Content-Encoding` in `X-Echo-*` headers
+    pub fn go_echo(name: &str, settings: Value) -> Self {
+        let handler = to_camel_case_handler(name);
+        let source = format!(
+            r#"package main
+
+import (
+    "io"
+    "net/http"
+)
+
+func {handler}(w http.ResponseWriter, r *http.Request) {{
+    body, err := io.ReadAll(r.Body)
+    if err != nil {{
+        w.WriteHeader(http.StatusBadRequest)
+        return
+    }}
+    w.Header().Set("X-Echo-Method", r.Method)
+    w.Header().Set("X-Echo-Query", r.URL.RawQuery)
+    w.Header().Set("X-Echo-Content-Type", r.Header.Get("Content-Type"))
+    w.Header().Set("X-Echo-Content-Encoding", r.Header.Get("Content-Encoding"))
+    w.Header().Set("Content-Type", "application/octet-stream")
+    w.WriteHeader(http.StatusOK)
+    w.Write(body)
+}}
+"#
+        );
+        Self {
+            name: name.to_string(),
+            files: vec![
+                ("config.json", config(name, "go", settings)),
+                ("function.go", source),
+            ],
+        }
+    }
+
+    /// A Go function answering with the host name of its container, i.e. the short
+    /// container ID
+    pub fn go_hostname(name: &str, settings: Value) -> Self {
+        let handler = to_camel_case_handler(name);
+        let source = format!(
+            r#"package main
+
+import (
+    "net/http"
+    "os"
+)
+
+func {handler}(w http.ResponseWriter, r *http.Request) {{
+    hostname, err := os.Hostname()
+    if err != nil {{
+        w.WriteHeader(http.StatusInternalServerError)
+        return
+    }}
+    w.WriteHeader(http.StatusOK)
+    w.Write([]byte(hostname))
+}}
+"#
+        );
+        Self {
+            name: name.to_string(),
+            files: vec![
+                ("config.json", config(name, "go", settings)),
+                ("function.go", source),
+            ],
+        }
+    }
+
+    /// The Node.js template function, logging the `name` query parameter
+    pub fn nodejs(name: &str, settings: Value) -> Self {
+        let answer = "reply.code(201);";
+        let logged = "request.log.info(`e2e invocation ${request.query.name}`);";
+        assert!(
+            nodejs_template::ROUTE_TEMPLATE.contains(answer),
+            "Node.js template changed"
+        );
+        let source = nodejs_template::ROUTE_TEMPLATE
+            .replace("{{ROUTE}}", name)
+            .replace(answer, &format!("{logged} {answer}"));
+        Self {
+            name: name.to_string(),
+            files: vec![
+                ("config.json", config(name, "nodejs", settings)),
+                ("function.ts", source),
+                (
+                    "package.json",
+                    nodejs_template::PACKAGE_JSON_TEMPLATE.to_string(),
+                ),
+                (
+                    "tsconfig.json",
+                    nodejs_template::TS_CONFIG_TEMPLATE.to_string(),
+                ),
+            ],
+        }
+    }
+
+    /// The function zipped like `invok deploy` does
+    fn archive(&self) -> HarnessResult<Vec<u8>> {
+        let dir = tempfile::tempdir()?;
+        for (file, content) in &self.files {
+            std::fs::write(dir.path().join(file), content)?;
+        }
+        let mut archive = Cursor::new(Vec::new());
+        compress_dir_with_excludes(dir.path(), &mut archive, &[])?;
+        Ok(archive.into_inner())
+    }
+}
+
+fn config(name: &str, runtime: &str, settings: Value) -> String {
+    let mut config = serde_json::json!({
+        "function_name": name,
+        "runtime": runtime,
+        "env": {},
+    });
+    if let (Some(config), Value::Object(settings)) = (config.as_object_mut(), settings) {
+        config.extend(settings);
+    }
+    config.to_string()
+}
+
+impl Harness {
+    /// Starts Postgres, Redis, the DinD daemon and the controller, and waits for the
+    /// controller to be ready
+    pub async fn start() -> HarnessResult<Self> {
+        Self::start_replicas(1).await
+    }
+
+    /// Starts `count` controllers on the same DinD daemon, Postgres and Redis, with
+    /// shared routing when there are several, and waits for each to be ready. The first
+    /// one started takes the scaling lead.
+    pub async fn start_replicas(count: u16) -> HarnessResult<Self> {
+        let postgres = Postgres::default().start().await?;
+        let redis = Redis::default().start().await?;
+        let postgres_ip = postgres.get_bridge_ip_address().await?;
+        let redis_ip = redis.get_bridge_ip_address().await?;
+
+        let mut docker =
+            GenericImage::new(DIND_IMAGE.0, DIND_IMAGE.1).with_exposed_port(CONTROLLER_PORT.tcp());
+        for replica in 1..count {
+            docker = docker.with_exposed_port((CONTROLLER_PORT + replica).tcp());
+        }
+        let docker = docker
+            .with_wait_for(WaitFor::message_on_stderr("API listen on"))
+            .with_privileged(true)
+            // Plain TCP on 2375, as the controller's DOCKER_HOST expects
+            .with_env_var("DOCKER_TLS_CERTDIR", "")
+            .start()
+            .await?;
+
+        let image = std::env::var("INVOK_E2E_IMAGE").unwrap_or_else(|_| DEFAULT_IMAGE.into());
+        let (repository, tag) = image.rsplit_once(':').unwrap_or((&image, "latest"));
+        let network = format!("container:{}", docker.id());
+        let controller = |port: u16| {
+            GenericImage::new(repository, tag)
+                .with_wait_for(WaitFor::Nothing)
+                .with_network(network.clone())
+                .with_env_var(
+                    "DATABASE_URL",
+                    format!("postgres://postgres:postgres@{postgres_ip}:5432/postgres"),
+                )
+                .with_env_var("REDIS_URL", format!("redis://{redis_ip}:6379"))
+                .with_env_var("AUTH_JWT_SECRET", "e2e-secret")
+                .with_env_var("DOCKER_HOST", "tcp://localhost:2375")
+                .with_env_var("SERVER_PORT", port.to_string())
+                .with_env_var("SHARED_ROUTING", (count > 1).to_string())
+                .with_env_var("RUST_LOG", "info")
+        };
+
+        let host = docker.get_host().await?;
+        let port = docker.get_host_port_ipv4(CONTROLLER_PORT).await?;
+        let mut harness = Self {
+            base_url: format!("http://{host}:{port}"),
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?,
+            controller: controller(CONTROLLER_PORT).start().await?,
+            replicas: Vec::new(),
+            _docker: docker,
+            _postgres: postgres,
+            _redis: redis,
+        };
+        harness.wait_until_ready(0).await?;
+
+        // One at a time, so only the first runs the migrations
+        for replica in 1..count {
+            let port = CONTROLLER_PORT + replica;
+            let container = controller(port).start().await?;
+            let port = harness._docker.get_host_port_ipv4(port).await?;
+            harness
+                .replicas
+                .push((format!("http://{host}:{port}"), container));
+            harness.wait_until_ready(replica.into()).await?;
+        }
+        Ok(harness)
+    }
+
+    async fn wait_until_ready(&self, replica: usize) -> HarnessResult<()> {
+        let started = Instant::now();
+        loop {
+            let ready = self
+                .client
+                .get(self.replica_url(replica, "/readyz"))
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_success());
+            if ready {
+                return Ok(());
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err(format!(
+                    "Controller not ready after {:?}:\n{}",
+                    STARTUP_TIMEOUT,
+                    self.controller_logs().await
+                )
+                .into());
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// URL of `path` on a controller, the first one being replica 0
+    pub fn replica_url(&self, replica: usize, path: &str) -> String {
+        match replica {
+            0 => self.url(path),
+            _ => format!("{}{}", self.replicas[replica - 1].0, path),
+        }
+    }
+
+    /// Everything the controllers logged so far, to make failures readable
+    pub async fn controller_logs(&self) -> String {
+        let controllers =
+            std::iter::once(&self.controller).chain(self.replicas.iter().map(|(_, c)| c));
+        let mut logs = String::new();
+        for (replica, controller) in controllers.enumerate() {
+            let stdout = controller.stdout_to_vec().await.unwrap_or_default();
+            let stderr = controller.stderr_to_vec().await.unwrap_or_default();
+            if !self.replicas.is_empty() {
+                logs.push_str(&format!("--- replica {replica} ---\n"));
+            }
+            logs.push_str(&String::from_utf8_lossy(&stdout));
+            logs.push_str(&String::from_utf8_lossy(&stderr));
+        }
+        logs
+    }
+
+    /// Registers a fresh account
+    pub async fn register(&self, email: &str) -> HarnessResult<Session> {
+        let response = self
+            .client
+            .post(self.url("/auth/register"))
+            .json(&serde_json::json!({
+                "email": email,
+                "password": "e2e-Passw0rd-long-enough",
+            }))
+            .send()
+            .await?;
+        let body: Value = expect_success(response).await?.json().await?;
+        Ok(Session {
+            token: body["token"].as_str().ok_or("No token")?.to_string(),
+            namespace: body["user"]["uuid"].as_str().ok_or("No uuid")?.to_string(),
+        })
+    }
+
+    /// Deploys a function through `POST /invok/deploy` and waits for the queued deploy
+    /// to finish, returning its output
+    pub async fn deploy(
+        &self,
+        session: &Session,
+        function: &SampleFunction,
+    ) -> HarnessResult<String> {
+        let archive = multipart::Part::bytes(function.archive()?)
+            .file_name(format!("{}.zip", function.name))
+            .mime_str("application/zip")?;
+        let response = self
+            .client
+            .post(self.url("/invok/deploy"))
+            .bearer_auth(&session.token)
+            .multipart(multipart::Form::new().part("file", archive))
+            .send()
+            .await?;
+        let queued = expect_success(response).await?.text().await?;
+        let id = queued
+            .lines()
+            .find_map(|line| line.strip_prefix("Deployment: "))
+            .ok_or_else(|| format!("No deployment in '{queued}'"))?
+            .trim()
+            .to_string();
+
+        let started = Instant::now();
+        while started.elapsed() < DEPLOY_TIMEOUT {
+            let response = self
+                .client
+                .get(self.url(&format!("/invok/deployments/{id}")))
+                .bearer_auth(&session.token)
+                .send()
+                .await?;
+            let deployment: Value = expect_success(response).await?.json().await?;
+            let message = deployment["message"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            match deployment["status"].as_str() {
+                Some("succeeded") => return Ok(message),
+                Some("failed") => return Err(format!("Deploy {id} failed: {message}").into()),
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        Err(format!(
+            "Deploy {id} didn't finish within {DEPLOY_TIMEOUT:?}:\n{}",
+            self.controller_logs().await
+        )
+        .into())
+    }
+
+    /// Invokes a function with a `GET`, like a browser would
+    pub async fn invoke(
+        &self,
+        session: &Session,
+        name: &str,
+        query: &[(&str, &str)],
+    ) -> HarnessResult<reqwest::Response> {
+        Ok(self
+            .client
+            .get(self.url(&format!("/invok/{}/{}", session.namespace, name)))
+            .query(query)
+            .send()
+            .await?)
+    }
+
+    /// Invokes a function with a `GET` through one of the controllers, with extra headers
+    pub async fn invoke_on(
+        &self,
+        replica: usize,
+        session: &Session,
+        name: &str,
+        headers: &[(&str, &str)],
+    ) -> HarnessResult<reqwest::Response> {
+        let path = format!("/invok/{}/{}", session.namespace, name);
+        let mut request = self.client.get(self.replica_url(replica, &path));
+        for (header, value) in headers {
+            request = request.header(*header, *value);
+        }
+        Ok(request.send().await?)
+    }
+
+    /// Starts a request to a function with any method, for the caller to add a body,
+    /// headers or a raw query to
+    pub fn request(
+        &self,
+        session: &Session,
+        method: reqwest::Method,
+        name: &str,
+    ) -> reqwest::RequestBuilder {
+        self.client.request(
+            method,
+            self.url(&format!("/invok/{}/{}", session.namespace, name)),
+        )
+    }
+
+    /// The function's description, including its container pool
+    pub async fn status(&self, session: &Session, name: &str) -> HarnessResult<Value> {
+        let response = self
+            .client
+            .get(self.url(&format!("/invok/status/{}/{}", session.namespace, name)))
+            .bearer_auth(&session.token)
+            .send()
+            .await?;
+        Ok(expect_success(response).await?.json().await?)
+    }
+
+    /// The controller's health report, including the function lookup counters
+    pub async fn health(&self) -> HarnessResult<Value> {
+        self.health_of(0).await
+    }
+
+    /// The health report of one of the controllers, including its replica role
+    pub async fn health_of(&self, replica: usize) -> HarnessResult<Value> {
+        let response = self
+            .client
+            .get(self.replica_url(replica, "/healthz"))
+            .send()
+            .await?;
+        Ok(expect_success(response).await?.json().await?)
+    }
+
+    /// Follows a function's logs until a line contains `needle`
+    pub async fn wait_for_log(
+        &self,
+        session: &Session,
+        name: &str,
+        needle: &str,
+        timeout: Duration,
+    ) -> HarnessResult<String> {
+        let mut response = expect_success(
+            self.client
+                .get(self.url(&format!("/invok/logs/{}/{}", session.namespace, name)))
+                .bearer_auth(&session.token)
+                .timeout(timeout)
+                .send()
+                .await?,
+        )
+        .await?;
+
+        let mut logs = String::new();
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+            logs.push_str(&String::from_utf8_lossy(&chunk));
+            if let Some(line) = logs.lines().find(|line| line.contains(needle)) {
+                return Ok(line.to_string());
+            }
+        }
+        Err(format!("'{needle}' not logged by '{name}' within {timeout:?}:\n{logs}").into())
+    }
+
+    /// Checks `condition` until it holds or `timeout` passes
+    pub async fn eventually<F, Fut>(
+        &self,
+        what: &str,
+        timeout: Duration,
+        mut condition: F,
+    ) -> HarnessResult<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = HarnessResult<bool>>,
+    {
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            if condition().await? {
+                return Ok(());
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Err(format!(
+            "Timed out after {timeout:?} waiting for {what}:\n{}",
+            self.controller_logs().await
+        )
+        .into())
+    }
+}
+
+/// Turns an unsuccessful answer into an error carrying its body
+async fn expect_success(response: reqwest::Response) -> HarnessResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().clone();
+    let body = response.text().await.unwrap_or_default();
+    Err(format!("{url} answered {status}: {body}").into())
+}
@@ -0,0 +1,351 @@
+//! Deploys, invokes and scales functions on a real controller, and on controller
+//! replicas sharing its pools.
+//!
+//! Needs a Docker daemon that allows privileged containers, and the controller image
+//! (see `common`). Ignored by default; run with:
+//!
+//! ```bash
+//! docker build -t invok-core:e2e .
+//! cargo test -p serverless_core --test end_to_end -- --ignored --nocapture
+//! ```
+
+mod common;
+
+use common::{Harness, HarnessResult, SampleFunction, Session};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use reqwest::{multipart, Method};
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::time::Duration;
+
+/// Containers are warmed and scaled in the background
+const SCALE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Function logs reach the stream shortly after they are written
+const LOG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Size of the binary bodies sent through the proxy, large enough to arrive in many chunks
+const BINARY_FIXTURE_SIZE: usize = 8 * 1024 * 1024;
+
+/// `max_request_size` of the raw body echo function
+const RAW_ECHO_MAX_REQUEST_SIZE: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs Docker and the controller image; run with --ignored"]
+async fn test_deploy_invoke_and_scale() -> HarnessResult<()> {
+    let harness = Harness::start().await?;
+    let session = harness.register("e2e@invok.test").await?;
+
+    go_function(&harness, &session).await?;
+    nodejs_function(&harness, &session).await?;
+    missing_function(&harness, &session).await?;
+    binary_bodies(&harness, &session).await?;
+    raw_bodies(&harness, &session).await?;
+
+    // Every invocation above went through the function lookup
+    let health = harness.health().await?;
+    let lookups = &health["function_lookups"];
+    let answered = ["hits", "negative_hits", "misses"]
+        .iter()
+        .map(|counter| lookups[counter].as_u64().unwrap_or(0))
+        .sum::<u64>();
+    assert!(answered >= 4, "lookups not counted: {lookups}");
+    Ok(())
+}
+
+/// A Go function is deployed, answers, logs, and is scaled to its minimum
+async fn go_function(harness: &Harness, session: &Session) -> HarnessResult<()> {
+    let function = SampleFunction::go("e2e-go", serde_json::json!({ "min_containers": 2 }));
+    let deployed = harness.deploy(session, &function).await?;
+    assert!(deployed.contains("deployed successfully"), "{deployed}");
+
+    let response = harness
+        .invoke(session, &function.name, &[("id", "go-1")])
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await?, "Hello World!");
+
+    let logs = harness.wait_for_log(session, &function.name, "e2e invocation", LOG_TIMEOUT);
+    let invocation = harness.invoke(session, &function.name, &[("id", "go-2")]);
+    let (logged, invoked) = tokio::join!(logs, invocation);
+    assert_eq!(invoked?.status(), 200);
+    assert!(logged?.contains("e2e invocation"));
+
+    harness
+        .eventually("two containers of e2e-go", SCALE_TIMEOUT, || async {
+            let status = harness.status(session, &function.name).await?;
+            Ok(status["pool"]["total_containers"].as_u64().unwrap_or(0) >= 2)
+        })
+        .await
+}
+
+/// A Node.js function is deployed, answers, logs, and its settings survive a redeploy
+async fn nodejs_function(harness: &Harness, session: &Session) -> HarnessResult<()> {
+    let function = SampleFunction::nodejs("e2e-node", serde_json::json!({}));
+    harness.deploy(session, &function).await?;
+
+    let response = harness
+        .invoke(session, &function.name, &[("name", "node-1")])
+        .await?;
+    assert_eq!(response.status(), 201);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["message"], "node-1 says Hello");
+
+    let logs = harness.wait_for_log(session, &function.name, "e2e invocation", LOG_TIMEOUT);
+    let invocation = harness.invoke(session, &function.name, &[("name", "node-2")]);
+    let (logged, invoked) = tokio::join!(logs, invocation);
+    assert_eq!(invoked?.status(), 201);
+    assert!(logged?.contains("node-2"));
+
+    // Redeploying with a minimum scales the running function up
+    let scaled = SampleFunction::nodejs("e2e-node", serde_json::json!({ "min_containers": 2 }));
+    harness.deploy(session, &scaled).await?;
+    harness
+        .eventually("two containers of e2e-node", SCALE_TIMEOUT, || async {
+            let status = harness.status(session, &scaled.name).await?;
+            Ok(status["settings"]["min_containers"] == 2
+                && status["pool"]["total_containers"].as_u64().unwrap_or(0) >= 2)
+        })
+        .await
+}
+
+/// Functions that were never deployed aren't found
+async fn missing_function(harness: &Harness, session: &Session) -> HarnessResult<()> {
+    let response = harness.invoke(session, "e2e-missing", &[]).await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
+
+/// Binary and multipart bodies reach the function byte for byte, whatever the method
+async fn binary_bodies(harness: &Harness, session: &Session) -> HarnessResult<()> {
+    let function = SampleFunction::go_echo("e2e-echo", serde_json::json!({ "compression": true }));
+    harness.deploy(session, &function).await?;
+    let fixture = binary_fixture(BINARY_FIXTURE_SIZE);
+
+    for method in [
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::OPTIONS,
+    ] {
+        let response = harness
+            .request(session, method.clone(), &function.name)
+            .header("content-type", "application/octet-stream")
+            .body(fixture.clone())
+            .send()
+            .await?;
+        assert_eq!(response.status(), 200, "{method}");
+        assert_eq!(response.headers()["x-echo-method"], method.as_str());
+        let echoed = response.bytes().await?;
+        assert!(
+            echoed == fixture,
+            "{method} body changed on the way: {} bytes came back",
+            echoed.len()
+        );
+    }
+
+    let response = harness
+        .request(session, Method::HEAD, &function.name)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-echo-method"], "HEAD");
+
+    // Multipart uploads keep their boundary and their binary parts
+    let form = multipart::Form::new().text("note", "fixture").part(
+        "file",
+        multipart::Part::bytes(fixture.clone()).file_name("fixture.bin"),
+    );
+    let boundary = form.boundary().to_string();
+    let response = harness
+        .request(session, Method::POST, &function.name)
+        .multipart(form)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let content_type = response.headers()["x-echo-content-type"]
+        .to_str()?
+        .to_string();
+    assert!(content_type.contains(&boundary), "{content_type}");
+    let echoed = response.bytes().await?;
+    let part_start = find(&echoed, b"filename=\"fixture.bin\"")
+        .and_then(|start| find(&echoed[start..], b"\r\n\r\n").map(|end| start + end + 4))
+        .ok_or("file part missing from the echoed form")?;
+    assert!(
+        echoed.get(part_start..part_start + fixture.len()) == Some(&fixture[..]),
+        "file part changed on the way"
+    );
+
+    // With compression on, gzip bodies are decoded for the function
+    let response = harness
+        .request(session, Method::POST, &function.name)
+        .header("content-encoding", "gzip")
+        .body(gzip(&fixture)?)
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    let encoding = response.headers().get("x-echo-content-encoding");
+    assert!(
+        encoding.is_none_or(|value| value.is_empty()),
+        "{encoding:?}"
+    );
+    assert!(response.bytes().await? == fixture, "gzip body not decoded");
+    Ok(())
+}
+
+/// Functions in raw body mode get the body and query string exactly as they were sent,
+/// and streamed bodies over their limit are cut off
+async fn raw_bodies(harness: &Harness, session: &Session) -> HarnessResult<()> {
+    let function = SampleFunction::go_echo(
+        "e2e-echo-raw",
+        serde_json::json!({
+            "raw_body": true,
+            "compression": true,
+            "max_request_size": RAW_ECHO_MAX_REQUEST_SIZE,
+        }),
+    );
+    harness.deploy(session, &function).await?;
+
+    let compressed = gzip(&binary_fixture(RAW_ECHO_MAX_REQUEST_SIZE / 2))?;
+    let response = harness
+        .request(session, Method::PUT, &function.name)
+        .header("content-encoding", "gzip")
+        .body(compressed.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-echo-content-encoding"], "gzip");
+    assert!(
+        response.bytes().await? == compressed,
+        "raw body was decoded"
+    );
+
+    let query = "b=2&a=1&a=x%20y&flag";
+    let url = format!(
+        "{}?{query}",
+        harness.url(&format!("/invok/{}/{}", session.namespace, function.name))
+    );
+    let response = reqwest::Client::new().post(url).send().await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-echo-query"], query);
+
+    // Without a Content-Length, the limit is enforced as the body streams
+    let chunks = binary_fixture(2 * RAW_ECHO_MAX_REQUEST_SIZE)
+        .chunks(64 * 1024)
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+        .collect::<Vec<_>>();
+    let response = harness
+        .request(session, Method::POST, &function.name)
+        .body(reqwest::Body::wrap_stream(futures_util::stream::iter(
+            chunks,
+        )))
+        .send()
+        .await?;
+    assert_eq!(response.status(), 413);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs Docker and the controller image; run with --ignored"]
+async fn test_replicas_route_to_the_same_containers() -> HarnessResult<()> {
+    let harness = Harness::start_replicas(2).await?;
+
+    // The first controller started took the lead
+    harness
+        .eventually("a scaling leader", SCALE_TIMEOUT, || async {
+            let leader = harness.health_of(0).await?;
+            Ok(leader["replica"]["leader"] == true)
+        })
+        .await?;
+    let follower = harness.health_of(1).await?;
+    assert_eq!(follower["replica"]["leader"], false, "{follower}");
+
+    let session = harness.register("e2e-replicas@invok.test").await?;
+    let function = SampleFunction::go_hostname(
+        "e2e-replicas",
+        serde_json::json!({
+            "min_containers": 2,
+            "sticky": { "header": "x-session-id" },
+        }),
+    );
+    harness.deploy(&session, &function).await?;
+    harness
+        .eventually("two containers of e2e-replicas", SCALE_TIMEOUT, || async {
+            let status = harness.status(&session, &function.name).await?;
+            Ok(status["pool"]["total_containers"].as_u64().unwrap_or(0) >= 2)
+        })
+        .await?;
+
+    // The follower spreads invocations over the leader's containers once it has
+    // published them
+    harness
+        .eventually(
+            "both containers via the follower",
+            SCALE_TIMEOUT,
+            || async {
+                let reached = hostnames(&harness, 1, &session, &function.name, 10).await?;
+                Ok(reached.len() == 2)
+            },
+        )
+        .await?;
+
+    // Both replicas send a session to the same container, and never anywhere else
+    let both = hostnames(&harness, 1, &session, &function.name, 10).await?;
+    for id in ["alice", "bob", "carol", "dave", "erin"] {
+        let headers = [("x-session-id", id)];
+        let mut answers = Vec::new();
+        for replica in [0, 1, 0, 1] {
+            let response = harness
+                .invoke_on(replica, &session, &function.name, &headers)
+                .await?;
+            assert_eq!(response.status(), 200, "replica {replica}, session {id}");
+            answers.push(response.text().await?);
+        }
+        assert!(
+            answers.iter().all(|answer| *answer == answers[0]),
+            "session {id} routed to {answers:?}"
+        );
+        assert!(both.contains(&answers[0]), "{} not in {both:?}", answers[0]);
+    }
+    Ok(())
+}
+
+/// Host names of the containers `count` invocations through a replica reached
+async fn hostnames(
+    harness: &Harness,
+    replica: usize,
+    session: &Session,
+    name: &str,
+    count: usize,
+) -> HarnessResult<BTreeSet<String>> {
+    let mut hostnames = BTreeSet::new();
+    for _ in 0..count {
+        let response = harness.invoke_on(replica, session, name, &[]).await?;
+        assert_eq!(response.status(), 200, "replica {replica}");
+        hostnames.insert(response.text().await?);
+    }
+    Ok(hostnames)
+}
+
+/// Pseudo-random bytes covering every byte value, the same on every run
+fn binary_fixture(size: usize) -> Vec<u8> {
+    let mut fixture = vec![0; size];
+    StdRng::seed_from_u64(7).fill_bytes(&mut fixture);
+    fixture
+}
+
+fn gzip(data: &[u8]) -> HarnessResult<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
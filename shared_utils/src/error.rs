@@ -0,0 +1,66 @@
+/// What kind of failure an error is, shared by the controller, the runtime and the CLI
+/// so they answer it the same way: the HTTP status the controller responds with and the
+/// exit code the CLI ends with both derive from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The request or input was wrong; sending it again fails again
+    User,
+    /// Something broke on our side; the details are logged, not shown
+    System,
+    /// A dependency is unavailable or overloaded; the same request may succeed later
+    Retryable,
+}
+
+impl ErrorClass {
+    /// HTTP status an error of this class is answered with
+    pub fn status_code(self) -> u16 {
+        match self {
+            ErrorClass::User => 400,
+            ErrorClass::System => 500,
+            ErrorClass::Retryable => 503,
+        }
+    }
+
+    /// Class of an error response of the controller, for clients
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            408 | 429 | 502 | 503 | 504 => ErrorClass::Retryable,
+            400..=499 => ErrorClass::User,
+            _ => ErrorClass::System,
+        }
+    }
+
+    /// Exit code the CLI ends with on an error of this class: 2 for usage errors,
+    /// 75 (`EX_TEMPFAIL`) when trying again may help, 1 otherwise
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorClass::User => 2,
+            ErrorClass::System => 1,
+            ErrorClass::Retryable => 75,
+        }
+    }
+
+    pub fn is_retryable(self) -> bool {
+        self == ErrorClass::Retryable
+    }
+}
+
+/// Errors that know their [`ErrorClass`]
+pub trait Classify {
+    fn class(&self) -> ErrorClass;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trips_through_class() {
+        for class in [ErrorClass::User, ErrorClass::System, ErrorClass::Retryable] {
+            assert_eq!(ErrorClass::from_status(class.status_code()), class);
+        }
+        assert_eq!(ErrorClass::from_status(404), ErrorClass::User);
+        assert_eq!(ErrorClass::from_status(429), ErrorClass::Retryable);
+        assert_eq!(ErrorClass::from_status(501), ErrorClass::System);
+    }
+}
@@ -1,10 +1,23 @@
+pub mod error;
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::{self, Cursor, Write};
+use std::io::{self, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tar::{Builder, Header};
 use zip::write::FileOptions;
 use zip::{ZipArchive, ZipWriter};
 
+/// Directory of a function's archive holding the shared packages it uses, each under
+/// its name
+pub const SHARED_PACKAGES_DIR: &str = ".invok-shared";
+
+/// Directory of a function's archive holding the data files it reads at runtime, e.g.
+/// models, lookup tables or templates
+pub const BUNDLED_DATA_DIR: &str = "data";
+
 pub fn to_camel_case_handler(input: &str) -> String {
     let mut result = String::new();
     let mut capitalize_next = false;
@@ -44,7 +57,38 @@ pub fn compress_dir_with_excludes(
     let mut zip = ZipWriter::new(dest_zip);
     let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
 
-    add_dir_to_zip(&mut zip, src_dir, src_dir, options, excludes)?;
+    add_dir_to_zip(&mut zip, src_dir, src_dir, "", options, excludes)?;
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Compresses a function's directory into a ZIP file like [`compress_dir_with_excludes`],
+/// with the shared packages it uses under [`SHARED_PACKAGES_DIR`].
+///
+/// # Arguments
+///
+/// * `src_dir` - The function's directory.
+/// * `shared` - The name and directory of each shared package.
+/// * `dest_zip` - The destination ZIP file.
+/// * `excludes` - File names to exclude from the function's files.
+/// * `shared_excludes` - File names to exclude from the shared packages' files.
+pub fn compress_function_with_shared(
+    src_dir: &Path,
+    shared: &[(String, PathBuf)],
+    dest_zip: &mut Cursor<Vec<u8>>,
+    excludes: &[&str],
+    shared_excludes: &[&str],
+) -> io::Result<()> {
+    let mut zip = ZipWriter::new(dest_zip);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    add_dir_to_zip(&mut zip, src_dir, src_dir, "", options, excludes)?;
+    for (name, dir) in shared {
+        let prefix = format!("{SHARED_PACKAGES_DIR}/{name}/");
+        zip.add_directory(prefix.as_str(), options)?;
+        add_dir_to_zip(&mut zip, dir, dir, &prefix, options, shared_excludes)?;
+    }
     zip.finish()?;
 
     Ok(())
@@ -54,19 +98,23 @@ fn add_dir_to_zip<W: Write + io::Seek>(
     zip: &mut ZipWriter<W>,
     src_dir: &Path,
     base_path: &Path,
+    prefix: &str,
     options: FileOptions,
     excludes: &[&str],
 ) -> io::Result<()> {
     for entry in fs::read_dir(src_dir)? {
         let entry = entry?;
         let path = entry.path();
-        let name = path.strip_prefix(base_path).unwrap().to_str().unwrap();
+        let name = format!(
+            "{prefix}{}",
+            path.strip_prefix(base_path).unwrap().to_str().unwrap()
+        );
 
         if path.is_dir() && !excludes.contains(&path.file_name().unwrap().to_str().unwrap()) {
-            zip.add_directory(name, options)?;
-            add_dir_to_zip(zip, &path, base_path, options, excludes)?;
+            zip.add_directory(name.as_str(), options)?;
+            add_dir_to_zip(zip, &path, base_path, prefix, options, excludes)?;
         } else if !excludes.contains(&entry.file_name().to_str().unwrap()) {
-            zip.start_file(name, options)?;
+            zip.start_file(name.as_str(), options)?;
             io::copy(&mut File::open(&path)?, zip)?;
         }
     }
@@ -104,6 +152,31 @@ pub fn add_dir_to_tar<W: Write>(
     Ok(())
 }
 
+/// Archives some of the files of a directory, e.g. the ones changed since the last sync,
+/// as a tar archive.
+///
+/// # Arguments
+///
+/// * `base_dir` - The directory the paths are relative to.
+/// * `paths` - The files to archive, relative to `base_dir` with `/` separators.
+///
+/// # Returns
+///
+/// The tar archive, with the files under their relative paths.
+pub fn tar_files(base_dir: &Path, paths: &[String]) -> io::Result<Vec<u8>> {
+    let mut tar = Builder::new(Vec::new());
+    for path in paths {
+        let mut file = File::open(base_dir.join(path))?;
+        let mut header = Header::new_gnu();
+        header.set_size(file.metadata()?.len());
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        tar.append_data(&mut header, path, &mut file)?;
+    }
+    tar.into_inner()
+}
+
 pub fn extract_zip_from_cursor(cursor: Cursor<Vec<u8>>, dest_dir: &Path) -> io::Result<()> {
     let mut archive = ZipArchive::new(cursor)?;
 
@@ -127,6 +200,68 @@ pub fn extract_zip_from_cursor(cursor: Cursor<Vec<u8>>, dest_dir: &Path) -> io::
     Ok(())
 }
 
+/// Seconds since the Unix epoch; 0 if the clock is set before it
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// Hex-encoded SHA-256 digest of some bytes
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Digests of every file in a ZIP archive, for comparing two archives without sending
+/// either.
+///
+/// # Arguments
+///
+/// * `archive` - The ZIP archive.
+///
+/// # Returns
+///
+/// The [`sha256_hex`] digest of each file, by its path in the archive.
+pub fn zip_digests(archive: &[u8]) -> io::Result<BTreeMap<String, String>> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    let mut digests = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        digests.insert(file.name().to_string(), sha256_hex(&content));
+    }
+    Ok(digests)
+}
+
+/// Builds a ZIP archive of files given by their path in the archive and content, e.g.
+/// the lone `config.json` of a function deployed from an image
+pub fn zip_files(files: &[(&str, &[u8])]) -> io::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (path, content) in files {
+        zip.start_file(*path, options)?;
+        zip.write_all(content)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Reads one file out of a ZIP archive, `None` if the archive doesn't have it
+pub fn read_zip_file(archive: &[u8], path: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
 pub fn find_file_in_path(file_name: &str, path: &PathBuf) -> Option<String> {
     let dir = fs::read_dir(path).ok()?;
     for entry in dir {
@@ -159,4 +294,75 @@ mod tests {
         let excludes = ["test.txt"];
         compress_dir_with_excludes(src_dir, &mut dest_zip, &excludes).unwrap();
     }
+
+    #[test]
+    fn test_compress_function_with_shared() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let shared = [("utils".to_string(), manifest_dir.to_path_buf())];
+        let mut dest_zip = Cursor::new(Vec::new());
+        compress_function_with_shared(
+            &manifest_dir.join("src"),
+            &shared,
+            &mut dest_zip,
+            &[],
+            &["src", "target"],
+        )
+        .unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(dest_zip.into_inner())).unwrap();
+        let names: Vec<String> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_string())
+            .collect();
+        assert!(names.contains(&"lib.rs".to_string()));
+        assert!(names.contains(&".invok-shared/utils/Cargo.toml".to_string()));
+        assert!(!names
+            .iter()
+            .any(|name| name.starts_with(".invok-shared/utils/src")));
+    }
+
+    #[test]
+    fn test_tar_files() {
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let archive = tar_files(base_dir, &["Cargo.toml".to_string()]).unwrap();
+
+        let mut archive = tar::Archive::new(Cursor::new(archive));
+        let paths: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert_eq!(paths, vec!["Cargo.toml".to_string()]);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_zip_digests() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut dest_zip = Cursor::new(Vec::new());
+        compress_dir_with_excludes(&manifest_dir.join("src"), &mut dest_zip, &[]).unwrap();
+        let archive = dest_zip.into_inner();
+
+        let digests = zip_digests(&archive).unwrap();
+        let lib = fs::read(manifest_dir.join("src/lib.rs")).unwrap();
+        assert_eq!(digests.get("lib.rs"), Some(&sha256_hex(&lib)));
+        assert_eq!(read_zip_file(&archive, "lib.rs").unwrap(), Some(lib));
+        assert_eq!(read_zip_file(&archive, "missing.rs").unwrap(), None);
+    }
+
+    #[test]
+    fn test_zip_files() {
+        let archive = zip_files(&[("config.json", b"{}")]).unwrap();
+        assert_eq!(
+            read_zip_file(&archive, "config.json").unwrap(),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(zip_digests(&archive).unwrap().len(), 1);
+    }
 }
@@ -4,6 +4,15 @@ import (
     "net/http"
 )
 
+// Optional lifecycle hooks, e.g. to open connections before the first invocation:
+// func init() {
+//     OnInit(func() error { return nil })
+//     OnShutdown(func() error { return nil })
+// }
+// Functions with "protocol": "h2c" in config.json can serve gRPC the same way, with
+// ServeGRPC(server) for a *grpc.Server, and those with "service": "tcp" or "udp" raw
+// connections, with ServeTCP(func(net.Conn)) or ServeUDP(func(net.PacketConn)).
+
 // Handler for the "/{{ROUTE}}" endpoint.
 func {{HANDLER}}(w http.ResponseWriter, r *http.Request) {
     // You can access query params via r.URL.Query().
@@ -11,6 +20,13 @@ func {{HANDLER}}(w http.ResponseWriter, r *http.Request) {
     // query := r.URL.Query()
     // name := query.Get("name")
 
+    // Every invocation carries its context:
+    // - r.Header.Get("X-Request-Id"): id of the invocation, to tag logs and traces with
+    // - r.Header.Get("X-Invok-Deadline"): when the caller stops waiting, in Unix milliseconds
+    // - r.Header.Get("X-Invok-Namespace") and r.Header.Get("X-Invok-Function")
+    // The environment holds INVOK_NAMESPACE, INVOK_FUNCTION and INVOK_FUNCTION_VERSION, and
+    // INVOK_DATA_DIR, where the files of the bundle's data/ directory are.
+
 	w.WriteHeader(http.StatusOK)
 	w.Write([]byte("Hello World!"))
 }
\ No newline at end of file
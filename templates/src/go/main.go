@@ -2,40 +2,163 @@ package main
 
 import (
     "context"
+    "io"
     "log"
     "net"
     "net/http"
     "os"
     "os/signal"
+    "strings"
     "syscall"
     "time"
     "fmt"
 
     "github.com/gorilla/mux"
+    "golang.org/x/net/http2"
+    "golang.org/x/net/http2/h2c"
 )
 
+// Lifecycle hooks, called by the controller once the container is ready and before it
+// is removed. Register them from an init() function in the handler's file:
+//
+//	func init() { OnInit(connect); OnShutdown(disconnect) }
+//
+// An init hook error fails the start and shows up in the function's boot logs.
+var (
+    initHook     = func() error { return nil }
+    shutdownHook = func() error { return nil }
+)
+
+// OnInit sets the hook run before the container receives invocations.
+func OnInit(hook func() error) { initHook = hook }
+
+// OnShutdown sets the hook run before the container is removed.
+func OnShutdown(hook func() error) { shutdownHook = hook }
+
+// grpcHandler serves the gRPC calls of functions with "protocol": "h2c", e.g. a
+// *grpc.Server. Register it from an init() function in the handler's file:
+//
+//	func init() { ServeGRPC(server) }
+var grpcHandler http.Handler
+
+// ServeGRPC sets the handler of gRPC calls, whatever their path.
+func ServeGRPC(handler http.Handler) { grpcHandler = handler }
+
+// connHandler and packetHandler serve functions with "service": "tcp" or "udp" in
+// config.json instead of HTTP. Register one from an init() function in the handler's file:
+//
+//	func init() { ServeTCP(handleConn) }
+var (
+    connHandler   func(net.Conn)
+    packetHandler func(net.PacketConn)
+)
+
+// ServeTCP serves each connection with handler, in its own goroutine.
+func ServeTCP(handler func(net.Conn)) { connHandler = handler }
+
+// ServeUDP hands the function's UDP socket to handler, which reads and answers
+// datagrams with ReadFrom and WriteTo until the socket is closed.
+func ServeUDP(handler func(net.PacketConn)) { packetHandler = handler }
+
+// serveRaw serves a TCP or UDP service until the container is stopped. The lifecycle
+// hooks run around it, as there is no HTTP server to call them on.
+func serveRaw(port string) {
+    if err := initHook(); err != nil {
+        log.Fatalf("Init hook failed: %v", err)
+    }
+
+    var closer io.Closer
+    if packetHandler != nil {
+        conn, err := net.ListenPacket("udp", ":"+port)
+        if err != nil {
+            log.Fatalf("Error starting listener: %v", err)
+        }
+        closer = conn
+        go packetHandler(conn)
+    } else {
+        listener, err := net.Listen("tcp", ":"+port)
+        if err != nil {
+            log.Fatalf("Error starting listener: %v", err)
+        }
+        closer = listener
+        go func() {
+            for {
+                conn, err := listener.Accept()
+                if err != nil {
+                    return
+                }
+                go connHandler(conn)
+            }
+        }()
+    }
+    // signal process fully started
+    fmt.Println("<<READY_TO_ACCEPT_CONN>>")
+    log.Printf("Service is running on port %s...\n", port)
+
+    stop := make(chan os.Signal, 1)
+    signal.Notify(stop, os.Interrupt, syscall.SIGTERM)
+    <-stop
+    log.Println("Shutting down the service...")
+    closer.Close()
+    if err := shutdownHook(); err != nil {
+        log.Printf("Shutdown hook failed: %v", err)
+    }
+}
+
+// lifecycleHandler answers the controller's call to a lifecycle hook.
+func lifecycleHandler(hook *func() error) http.HandlerFunc {
+    return func(w http.ResponseWriter, r *http.Request) {
+        if err := (*hook)(); err != nil {
+            log.Printf("Lifecycle hook failed: %v", err)
+            http.Error(w, err.Error(), http.StatusInternalServerError)
+            return
+        }
+        w.WriteHeader(http.StatusOK)
+    }
+}
+
 func main() {
     // 1. Use environment variable or a default for the server port.
     port := os.Getenv("PORT")
     if port == "" {
         port = "8080"
     }
+    if connHandler != nil || packetHandler != nil {
+        serveRaw(port)
+        return
+    }
 
     // 2. Create a new router.
     r := mux.NewRouter()
 
     // 3. Register endpoints.
+    // gRPC calls go to the registered handler, on the service's own paths.
+    if grpcHandler != nil {
+        r.MatcherFunc(func(req *http.Request, _ *mux.RouteMatch) bool {
+            return strings.HasPrefix(req.Header.Get("Content-Type"), "application/grpc")
+        }).Handler(grpcHandler)
+    }
     // Register the "/{{ROUTE}}" endpoint with the {{HANDLER}}.
 	r.HandleFunc("/{{ROUTE}}", {{HANDLER}})
+    r.HandleFunc("/__invok/init", lifecycleHandler(&initHook)).Methods(http.MethodPost)
+    r.HandleFunc("/__invok/shutdown", lifecycleHandler(&shutdownHook)).Methods(http.MethodPost)
 
-    // 4. Create an HTTP server with timeouts & the router.
+    // 4. Create an HTTP server with timeouts & the router, speaking cleartext HTTP/2
+    // (h2c) besides HTTP/1.
     srv := &http.Server{
         Addr:         ":" + port,
-        Handler:      r,
+        Handler:      h2c.NewHandler(r, &http2.Server{}),
         ReadTimeout:  5 * time.Second,  // protect against slowloris
         WriteTimeout: 10 * time.Second, // overall request timeout
         IdleTimeout:  15 * time.Second, // keep-alive time
     }
+    if grpcHandler != nil {
+        // Streaming calls outlive a request; only bound reading the headers.
+        srv.ReadTimeout, srv.WriteTimeout = 0, 0
+        srv.ReadHeaderTimeout = 5 * time.Second
+        // The proxy keeps its connection open between invocations.
+        srv.IdleTimeout = 90 * time.Second
+    }
 
     // 5. Create a net.Listener to have more control over incoming connections.
 	listener, err := net.Listen("tcp", ":"+port)
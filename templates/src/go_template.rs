@@ -1,4 +1,5 @@
 pub const MAIN_TEMPLATE: &str = include_str!("go/main.go");
 pub const ROUTES_TEMPLATE: &str = include_str!("go/handler.go");
 pub const DOCKERFILE_TEMPLATE: &str = include_str!("go/Dockerfile");
+pub const PROMOTE_DOCKERFILE_TEMPLATE: &str = include_str!("go/Dockerfile.promote");
 pub const FUNCTION_MODULE_TEMPLATE: &str = include_str!("go/go.mod");
@@ -0,0 +1 @@
+pub const DOCKERFILE_TEMPLATE: &str = include_str!("image/Dockerfile");
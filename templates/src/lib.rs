@@ -1,2 +1,4 @@
 pub mod go_template;
+pub mod image_template;
 pub mod nodejs_template;
+pub mod rust_template;
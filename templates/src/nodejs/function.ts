@@ -16,8 +16,18 @@ export default {
             done()
         }
     ] ,
+    // Every invocation carries its context:
+    // - request.headers['x-request-id']: id of the invocation, to tag logs and traces with
+    // - request.headers['x-invok-deadline']: when the caller stops waiting, in Unix milliseconds
+    // - request.headers['x-invok-namespace'] and request.headers['x-invok-function']
+    // process.env holds INVOK_NAMESPACE, INVOK_FUNCTION, INVOK_FUNCTION_VERSION and
+    // INVOK_DATA_DIR, where the files of the bundle's data/ directory are.
     function: async (request: FastifyRequest<{ Querystring: QueryParams }>, reply: FastifyReply) => {
         reply.code(201);
         return { message: `${request.query.name} says Hello` }
     },
-} as { name: string, hooks: InvokHooks[], function: InvokFunction };
\ No newline at end of file
+    // Optional lifecycle hooks, e.g. to open connections before the first invocation.
+    // A rejected init fails the container's start.
+    init: async () => {},
+    shutdown: async () => {},
+} as { name: string, hooks: InvokHooks[], function: InvokFunction, init?: () => Promise<void>, shutdown?: () => Promise<void> };
\ No newline at end of file
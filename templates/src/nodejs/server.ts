@@ -1,4 +1,4 @@
-import Fastify, { FastifyInstance } from 'fastify';
+import Fastify, { FastifyInstance, FastifyReply, FastifyRequest } from 'fastify';
 import cors from '@fastify/cors';
 import helmet from '@fastify/helmet';
 import { env } from 'node:process';
@@ -18,7 +18,9 @@ const server = Fastify({
     }
   } : {
     level: env['LOG_LEVEL'] || 'info'
-  }
+  },
+  // Tag request logs with the id the controller gives each invocation
+  requestIdHeader: 'x-request-id'
 });
 
 // Declare server startup function
@@ -89,6 +91,24 @@ const registerRoutes = (fastify: FastifyInstance) => {
   })
 
   fastify.all(`/${routes.name}`, routes.function);
+
+  // Lifecycle hooks, called by the controller once the container is ready and before it
+  // is removed; an init error fails the start and shows up in the function's boot logs
+  const hooks = routes as { init?: LifecycleHook, shutdown?: LifecycleHook };
+  fastify.post('/__invok/init', lifecycleHandler(hooks.init));
+  fastify.post('/__invok/shutdown', lifecycleHandler(hooks.shutdown));
+}
+
+type LifecycleHook = () => Promise<void>;
+
+const lifecycleHandler = (hook?: LifecycleHook) => async (_request: FastifyRequest, reply: FastifyReply) => {
+  try {
+    await hook?.();
+    reply.code(200).send();
+  } catch (err) {
+    server.log.error(err);
+    reply.code(500).send(err instanceof Error ? err.message : String(err));
+  }
 }
 
 // Listen for termination signals
@@ -3,4 +3,5 @@ pub const TS_CONFIG_TEMPLATE: &str = include_str!("nodejs/tsconfig.json");
 pub const SERVER_TEMPLATE: &str = include_str!("nodejs/server.ts");
 pub const ROUTE_TEMPLATE: &str = include_str!("nodejs/function.ts");
 pub const DOCKERFILE_TEMPLATE: &str = include_str!("nodejs/Dockerfile");
+pub const PROMOTE_DOCKERFILE_TEMPLATE: &str = include_str!("nodejs/Dockerfile.promote");
 pub const GIT_IGNORE_TEMPLATE: &str = include_str!("nodejs/.gitignore");
@@ -0,0 +1,30 @@
+use invok::{Request, Response};
+
+// Handler for the "/{{ROUTE}}" endpoint.
+// `#[invok::handler]` generates the `main` serving it; env variables from config.json
+// are read with `invok::env` and `invok::secret`.
+//
+// Every invocation carries its context:
+// - request.request_id(): id of the invocation, attached to what `invok::info!` logs
+// - request.deadline() and request.time_remaining(): when the caller stops waiting
+// - invok::namespace(), invok::function_name() and invok::function_version()
+// Files of the bundle's data/ directory are read with `invok::data_file`.
+//
+// `init` runs once the container is ready, before the first invocation (e.g. to open
+// connections); an error fails the start. `shutdown` runs before the container is removed.
+#[invok::handler(init = init, shutdown = shutdown)]
+async fn handler(request: Request) -> Response {
+    // You can access query params via request.query("name").
+    let name = request.query("name").unwrap_or("someone");
+    invok::info!("greeting {}", name);
+
+    Response::text(format!("{name} says Hello"))
+}
+
+async fn init() -> Result<(), String> {
+    Ok(())
+}
+
+async fn shutdown() -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub const ROUTE_TEMPLATE: &str = include_str!("rust/function.rs");
+pub const CARGO_TOML_TEMPLATE: &str = include_str!("rust/Cargo.toml");
+pub const DOCKERFILE_TEMPLATE: &str = include_str!("rust/Dockerfile");
+pub const PROMOTE_DOCKERFILE_TEMPLATE: &str = include_str!("rust/Dockerfile.promote");
+pub const GIT_IGNORE_TEMPLATE: &str = include_str!("rust/.gitignore");
@@ -0,0 +1,105 @@
+package main
+
+import (
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/json"
+	"io"
+	"log"
+	"net/http"
+	"os"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// How old a signed event may be, against replays
+const signatureTolerance = {{TOLERANCE_SECONDS}} * time.Second
+
+// Stripe events can be large, but not this large
+const maxPayloadBytes = 1 << 20
+
+type stripeEvent struct {
+	ID   string          `json:"id"`
+	Type string          `json:"type"`
+	Data json.RawMessage `json:"data"`
+}
+
+// Handler for the "/{{ROUTE}}" endpoint: receives Stripe's webhook events.
+func {{HANDLER}}(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodPost {
+		http.Error(w, "method not allowed", http.StatusMethodNotAllowed)
+		return
+	}
+
+	payload, err := io.ReadAll(io.LimitReader(r.Body, maxPayloadBytes))
+	if err != nil {
+		http.Error(w, "failed to read the payload", http.StatusBadRequest)
+		return
+	}
+	if !validSignature(payload, r.Header.Get("Stripe-Signature"), os.Getenv("{{SECRET_ENV}}")) {
+		http.Error(w, "invalid signature", http.StatusBadRequest)
+		return
+	}
+
+	var event stripeEvent
+	if err := json.Unmarshal(payload, &event); err != nil {
+		http.Error(w, "invalid event", http.StatusBadRequest)
+		return
+	}
+
+	switch event.Type {
+	case "checkout.session.completed":
+		// Fulfil the order here
+		log.Printf("Checkout completed: %s", event.ID)
+	case "invoice.payment_failed":
+		// Notify the customer here
+		log.Printf("Payment failed: %s", event.ID)
+	default:
+		log.Printf("Unhandled event %s: %s", event.Type, event.ID)
+	}
+
+	w.WriteHeader(http.StatusOK)
+}
+
+// validSignature checks a Stripe-Signature header ("t=<timestamp>,v1=<signature>,...")
+// against the payload, as Stripe documents it.
+func validSignature(payload []byte, header string, secret string) bool {
+	if secret == "" {
+		log.Printf("{{SECRET_ENV}} isn't set")
+		return false
+	}
+
+	var timestamp string
+	var signatures []string
+	for _, part := range strings.Split(header, ",") {
+		key, value, found := strings.Cut(part, "=")
+		if !found {
+			continue
+		}
+		switch key {
+		case "t":
+			timestamp = value
+		case "v1":
+			signatures = append(signatures, value)
+		}
+	}
+
+	seconds, err := strconv.ParseInt(timestamp, 10, 64)
+	if err != nil || time.Since(time.Unix(seconds, 0)) > signatureTolerance {
+		return false
+	}
+
+	mac := hmac.New(sha256.New, []byte(secret))
+	mac.Write([]byte(timestamp + "."))
+	mac.Write(payload)
+	expected := mac.Sum(nil)
+	for _, signature := range signatures {
+		decoded, err := hex.DecodeString(signature)
+		if err == nil && hmac.Equal(decoded, expected) {
+			return true
+		}
+	}
+	return false
+}
@@ -0,0 +1,3 @@
+module serverless-function
+
+go 1.23